@@ -0,0 +1,53 @@
+/*
+ * C interface to the Kanoniv identity specification validator.
+ *
+ * Every function taking a spec returns a newly allocated, NUL-terminated
+ * JSON envelope, never NULL:
+ *
+ *     {"ok": true, "result": ...}
+ *     {"ok": false, "error": "..."}
+ *
+ * Release it with kanoniv_free. Arguments are NUL-terminated UTF-8;
+ * optional ones may be NULL.
+ */
+
+#ifndef KANONIV_H
+#define KANONIV_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Every finding in the spec, with its code, path, line and column. */
+char *kanoniv_validate(const char *yaml);
+
+/* The spec as JSON. */
+char *kanoniv_parse(const char *yaml);
+
+/* The spec compiled for target: "ir" (default, JSON) or "sql", "pyspark" or
+ * "kafka" (a string of code). dialect is the SQL dialect (default "ansi"). */
+char *kanoniv_compile(const char *yaml, const char *target, const char *dialect);
+
+/* Changes from spec a to spec b, classified by impact. */
+char *kanoniv_diff(const char *a, const char *b);
+
+/* The execution plan: stages, match strategies, risk flags and summary. */
+char *kanoniv_plan(const char *yaml);
+
+/* Canonical hash of the spec, e.g. "sha256:<hex>". algorithm is "sha256"
+ * (default), "sha512" or "blake3"; key_file, or the environment variable
+ * named by key_env, keys the hash. */
+char *kanoniv_hash(const char *yaml, const char *algorithm, const char *key_env,
+                   const char *key_file);
+
+/* This library's version, e.g. "0.1.0". Static: do not free. */
+const char *kanoniv_version(void);
+
+/* Release a string returned by a kanoniv_* function. NULL is ignored. */
+void kanoniv_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KANONIV_H */
@@ -0,0 +1,191 @@
+//! C ABI over `kanoniv_core`, for JVM (Snowflake UDFs, Java services), Go
+//! and other native callers that embed the validator in-process.
+//!
+//! Every function takes NUL-terminated UTF-8 and returns a newly allocated,
+//! NUL-terminated JSON envelope, never null, which the caller releases with
+//! `kanoniv_free`:
+//!
+//! ```json
+//! {"ok": true, "result": ...}
+//! {"ok": false, "error": "Invalid YAML: ..."}
+//! ```
+//!
+//! `include/kanoniv.h` declares the functions. Strings passed in are only
+//! read during the call.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Every finding in the spec (errors, warnings and info) with its code,
+/// path, line and column. Broken YAML is reported as findings too.
+///
+/// # Safety
+///
+/// `yaml` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kanoniv_validate(yaml: *const c_char) -> *mut c_char {
+    respond(|| {
+        let yaml = text(yaml, "yaml")?;
+        Ok(serde_json::to_value(kanoniv_core::diagnose_yaml(yaml))?)
+    })
+}
+
+/// The spec as JSON.
+///
+/// # Safety
+///
+/// `yaml` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kanoniv_parse(yaml: *const c_char) -> *mut c_char {
+    respond(|| kanoniv_core::parse_yaml(text(yaml, "yaml")?))
+}
+
+/// The spec compiled for `target`: `ir` (the default) as JSON, or `sql`,
+/// `pyspark` or `kafka` as a string of code. `dialect` picks the SQL
+/// dialect (default `ansi`).
+///
+/// # Safety
+///
+/// Each argument must be null or point to a NUL-terminated string; `yaml`
+/// must not be null.
+#[no_mangle]
+pub unsafe extern "C" fn kanoniv_compile(
+    yaml: *const c_char,
+    target: *const c_char,
+    dialect: *const c_char,
+) -> *mut c_char {
+    respond(|| {
+        let spec = kanoniv_core::parse_yaml(text(yaml, "yaml")?)?;
+        let ir = kanoniv_core::compile_to_ir(&spec)?;
+        let target = optional_text(target, "target")?.unwrap_or("ir");
+        if target == "ir" {
+            return Ok(ir);
+        }
+        let typed = kanoniv_core::Ir::from_value(&ir)?;
+        let code = match target {
+            "sql" => {
+                let dialect = optional_text(dialect, "dialect")?.unwrap_or("ansi");
+                kanoniv_core::generate_sql(&typed, dialect.parse()?)?
+            }
+            "pyspark" => kanoniv_core::generate_pyspark(&typed)?,
+            "kafka" => kanoniv_core::generate_kafka(&typed)?,
+            other => bail!(
+                "Unknown compile target: '{}'. Expected one of: ir, sql, pyspark, kafka",
+                other
+            ),
+        };
+        Ok(Value::String(code))
+    })
+}
+
+/// Changes from spec `a` to spec `b`, classified by impact.
+///
+/// # Safety
+///
+/// `a` and `b` must be null or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn kanoniv_diff(a: *const c_char, b: *const c_char) -> *mut c_char {
+    respond(|| {
+        let result = kanoniv_core::compute_diff(text(a, "a")?, text(b, "b")?)?;
+        Ok(serde_json::to_value(result)?)
+    })
+}
+
+/// The execution plan: stages, match strategies, risk flags and summary.
+///
+/// # Safety
+///
+/// `yaml` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kanoniv_plan(yaml: *const c_char) -> *mut c_char {
+    respond(|| {
+        let result = kanoniv_core::generate_plan(text(yaml, "yaml")?)?;
+        Ok(serde_json::to_value(result)?)
+    })
+}
+
+/// Canonical hash of the spec, e.g. `sha256:<hex>`, as a JSON string.
+/// `algorithm` is `sha256` (the default), `sha512` or `blake3`. With
+/// `key_file`, or the name of an environment variable in `key_env`, the
+/// hash is keyed, as in `kanoniv hash`.
+///
+/// # Safety
+///
+/// Each argument must be null or point to a NUL-terminated string; `yaml`
+/// must not be null.
+#[no_mangle]
+pub unsafe extern "C" fn kanoniv_hash(
+    yaml: *const c_char,
+    algorithm: *const c_char,
+    key_env: *const c_char,
+    key_file: *const c_char,
+) -> *mut c_char {
+    respond(|| {
+        let spec = kanoniv_core::parse_yaml(text(yaml, "yaml")?)?;
+        let algorithm = match optional_text(algorithm, "algorithm")? {
+            Some(name) => name.parse()?,
+            None => kanoniv_core::HashAlgorithm::default(),
+        };
+        let key = kanoniv_core::KeySource {
+            key_env: optional_text(key_env, "key_env")?.map(str::to_string),
+            key_file: optional_text(key_file, "key_file")?.map(Into::into),
+        };
+        let hasher = kanoniv_core::Hasher::from_source(algorithm, &key)?;
+        Ok(Value::String(kanoniv_core::canonical_hash_with(
+            &spec, &hasher,
+        )))
+    })
+}
+
+/// This library's version, e.g. `0.1.0`, as a static string the caller
+/// must not free.
+#[no_mangle]
+pub extern "C" fn kanoniv_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Release a string returned by a `kanoniv_*` function. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a pointer returned by this library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn kanoniv_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Run `f` and wrap its outcome in the JSON envelope. Panics become
+/// errors rather than unwinding into the caller.
+fn respond(f: impl FnOnce() -> Result<Value>) -> *mut c_char {
+    let envelope = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(result)) => json!({ "ok": true, "result": result }),
+        Ok(Err(e)) => json!({ "ok": false, "error": format!("{:#}", e) }),
+        Err(_) => json!({ "ok": false, "error": "internal error in kanoniv" }),
+    };
+    // serde_json escapes NUL, so the text never contains one.
+    CString::new(envelope.to_string())
+        .unwrap_or_default()
+        .into_raw()
+}
+
+unsafe fn text<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    match optional_text(ptr, name)? {
+        Some(s) => Ok(s),
+        None => bail!("{} must not be null", name),
+    }
+}
+
+unsafe fn optional_text<'a>(ptr: *const c_char, name: &str) -> Result<Option<&'a str>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(Some)
+        .with_context(|| format!("{} is not valid UTF-8", name))
+}
@@ -0,0 +1,88 @@
+use serde_json::Value;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+const SPEC: &str = "\
+api_version: kanoniv/v2
+identity_version: retail_v1.0
+entity:
+  name: customer
+sources:
+  - name: crm
+    system: salesforce
+    table: contacts
+    id: contact_id
+    attributes:
+      email: email
+rules:
+  - name: email_exact
+    type: exact
+    field: email
+    weight: 1.0
+decision:
+  thresholds:
+    match: 0.9
+";
+
+/// Read and free an envelope returned by the library.
+fn envelope(ptr: *mut c_char) -> Value {
+    assert!(!ptr.is_null());
+    let text = unsafe { CStr::from_ptr(ptr) }.to_str().unwrap().to_string();
+    unsafe { kanoniv::kanoniv_free(ptr) };
+    serde_json::from_str(&text).unwrap()
+}
+
+#[test]
+fn test_ffi_returns_json_envelopes() {
+    let spec = CString::new(SPEC).unwrap();
+
+    let plan = envelope(unsafe { kanoniv::kanoniv_plan(spec.as_ptr()) });
+    assert_eq!(plan["ok"], true);
+    assert_eq!(plan["result"]["entity"], "customer");
+
+    let diagnostics = envelope(unsafe { kanoniv::kanoniv_validate(spec.as_ptr()) });
+    assert!(diagnostics["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|d| d["severity"] != "error"));
+
+    let sql = CString::new("sql").unwrap();
+    let compiled = envelope(unsafe {
+        kanoniv::kanoniv_compile(spec.as_ptr(), sql.as_ptr(), std::ptr::null())
+    });
+    assert!(compiled["result"].as_str().unwrap().contains("SELECT"));
+
+    let hash = envelope(unsafe {
+        kanoniv::kanoniv_hash(
+            spec.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null(),
+            std::ptr::null(),
+        )
+    });
+    assert!(hash["result"].as_str().unwrap().starts_with("sha256:"));
+
+    let version = unsafe { CStr::from_ptr(kanoniv::kanoniv_version()) };
+    assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn test_ffi_reports_errors_in_the_envelope() {
+    let broken = CString::new("entity: [").unwrap();
+    let parsed = envelope(unsafe { kanoniv::kanoniv_parse(broken.as_ptr()) });
+    assert_eq!(parsed["ok"], false);
+    assert!(parsed["error"].as_str().unwrap().contains("line 2"));
+
+    let missing = envelope(unsafe { kanoniv::kanoniv_plan(std::ptr::null()) });
+    assert_eq!(missing["error"], "yaml must not be null");
+
+    let invalid = CString::new(vec![0xff, 0xfe]).unwrap();
+    let utf8 = envelope(unsafe { kanoniv::kanoniv_validate(invalid.as_ptr()) });
+    assert!(utf8["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("yaml is not valid UTF-8"));
+
+    unsafe { kanoniv::kanoniv_free(std::ptr::null_mut()) };
+}
@@ -0,0 +1,276 @@
+//! Language features computed from a document's text.
+//!
+//! Everything here is a pure function of the current buffer so the server
+//! can recompute on every change. Positions coming from the client are LSP
+//! positions (0-based line, UTF-16 character); the core works in 1-based
+//! lines and byte columns.
+
+use kanoniv_core::attributes;
+use kanoniv_core::blocking::STRATEGIES;
+use kanoniv_core::clustering;
+use kanoniv_core::deletion;
+use kanoniv_core::encoding;
+use kanoniv_core::execution;
+use kanoniv_core::mappings;
+use kanoniv_core::sample;
+use kanoniv_core::similarity::BUILTIN_ALGORITHMS;
+use kanoniv_core::survivorship;
+use kanoniv_core::temporal;
+use kanoniv_core::{diagnose_yaml, parse_yaml_recovering, spec_json_schema, Severity, SourceMap};
+use serde_json::{json, Value};
+
+// ── Completion vocabularies ────────────────────────────────────────
+
+pub const MATCH_TYPES: &[&str] = &["exact", "fuzzy", "semantic"];
+pub const ALGORITHMS: &[&str] = BUILTIN_ALGORITHMS;
+pub const SURVIVORSHIP_STRATEGIES: &[&str] = survivorship::STRATEGIES;
+pub const TRANSFORMS: &[&str] = sample::TRANSFORMS;
+pub const TRANSFORM_PREFIXES: &[&str] = sample::TRANSFORM_PREFIXES;
+
+// ── Diagnostics ────────────────────────────────────────────────────
+
+pub fn diagnostics(text: &str) -> Vec<Value> {
+    diagnose_yaml(text)
+        .into_iter()
+        .map(|d| {
+            let range = match d.span {
+                Some(span) => line_range(text, span.line - 1, span.column - 1),
+                None => line_range(text, 0, 0),
+            };
+            json!({
+                "range": range,
+                "severity": match d.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                    Severity::Info => 3,
+                },
+                "code": d.code,
+                "source": "kanoniv",
+                "message": d.to_string(),
+            })
+        })
+        .collect()
+}
+
+// ── Hover ──────────────────────────────────────────────────────────
+
+pub fn hover(text: &str, line: usize, character: usize) -> Option<Value> {
+    let map = SourceMap::from_yaml(text);
+    let column = byte_column(line_text(text, line), character);
+    let path = map.path_at(line + 1, column + 1)?;
+    let root = spec_json_schema();
+    let schema = schema_at(&root, path)?;
+
+    let mut doc = format!("**{}**", path);
+    if let Some(description) = schema.get("description").and_then(|d| d.as_str()) {
+        doc.push_str(&format!("\n\n{}", description));
+    }
+    if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+        let fields: Vec<&str> = required.iter().filter_map(|f| f.as_str()).collect();
+        doc.push_str(&format!("\n\nRequired: `{}`", fields.join("`, `")));
+    }
+    if let Some(max) = schema.get("maxItems") {
+        doc.push_str(&format!("\n\nAt most {} entries.", max));
+    }
+    if let (Some(min), Some(max)) = (schema.get("minimum"), schema.get("maximum")) {
+        doc.push_str(&format!("\n\nBetween {} and {}.", min, max));
+    }
+    if let Some(examples) = schema.get("examples").and_then(|e| e.as_array()) {
+        let values: Vec<&str> = examples.iter().filter_map(|e| e.as_str()).collect();
+        doc.push_str(&format!("\n\nExamples: `{}`", values.join("`, `")));
+    }
+
+    Some(json!({ "contents": { "kind": "markdown", "value": doc } }))
+}
+
+/// Follow a YAML path (`rules[0].weight`) through the JSON Schema.
+fn schema_at<'a>(schema: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut node = schema;
+    for segment in path.split('.') {
+        let (key, indexes) = match segment.find('[') {
+            Some(i) => (&segment[..i], segment[i..].matches('[').count()),
+            None => (segment, 0),
+        };
+        if !key.is_empty() {
+            node = node.get("properties")?.get(key)?;
+        }
+        for _ in 0..indexes {
+            node = node.get("items")?;
+        }
+    }
+    Some(node)
+}
+
+// ── Go to definition ───────────────────────────────────────────────
+
+/// Resolve attribute references (rule, survivorship and blocking `field`s)
+/// to the source attribute, and `source_priority` entries to the source.
+pub fn definition(uri: &str, text: &str, line: usize, character: usize) -> Option<Value> {
+    let map = SourceMap::from_yaml(text);
+    let line_str = line_text(text, line);
+    let column = byte_column(line_str, character);
+    let path = map.path_at(line + 1, column + 1)?;
+    let word = word_at(line_str, column)?;
+
+    let target = if path.contains("source_priority") {
+        source_definition(text, &map, word)
+    } else if is_attribute_reference(path) {
+        map.iter()
+            .filter(|(p, _)| {
+                p.starts_with("sources") && p.ends_with(&format!(".attributes.{}", word))
+            })
+            .min_by_key(|(_, span)| (span.line, span.column))
+            .map(|(_, span)| span)
+    } else {
+        None
+    }?;
+
+    let target_line = line_text(text, target.line - 1);
+    let start = utf16_column(target_line, target.column - 1);
+    Some(json!({
+        "uri": uri,
+        "range": {
+            "start": { "line": target.line - 1, "character": start },
+            "end": { "line": target.line - 1, "character": utf16_column(target_line, target_line.len()) },
+        },
+    }))
+}
+
+fn is_attribute_reference(path: &str) -> bool {
+    (path.starts_with("rules[") || path.starts_with("survivorship.")) && path.ends_with(".field")
+        || path.starts_with("blocking.keys[")
+            && (path.ends_with(".field") || path.ends_with(".name") || path.ends_with(']'))
+}
+
+fn source_definition(text: &str, map: &SourceMap, name: &str) -> Option<kanoniv_core::Span> {
+    let spec = parse_yaml_recovering(text).value;
+    match spec.get("sources")? {
+        Value::Array(sources) => sources
+            .iter()
+            .position(|s| s.get("name").and_then(|n| n.as_str()) == Some(name))
+            .and_then(|i| map.get(&format!("sources[{}].name", i))),
+        // Mapping-style sources are keyed by name.
+        Value::Object(_) => map.get(&format!("sources.{}", name)),
+        _ => None,
+    }
+}
+
+// ── Completion ─────────────────────────────────────────────────────
+
+pub fn completion(text: &str, line: usize, character: usize) -> Vec<Value> {
+    let line_str = line_text(text, line);
+    let column = byte_column(line_str, character);
+    let before = line_str[..column].trim_start();
+    let before = before.strip_prefix("- ").unwrap_or(before);
+    let Some((key, _)) = before.split_once(':') else {
+        return Vec::new();
+    };
+
+    let map = SourceMap::from_yaml(text);
+    let path = map.path_at(line + 1, column + 1).unwrap_or("");
+    let in_section = |s: &str| path.starts_with(s);
+
+    let (values, detail): (Vec<String>, &str) = match key.trim() {
+        "type" if in_section("rules") => (owned(MATCH_TYPES), "match type"),
+        "type" if in_section("sources") => (owned(attributes::TYPES), "attribute type"),
+        "algorithm" => (owned(ALGORITHMS), "similarity algorithm"),
+        "strategy" if in_section("survivorship") => {
+            (owned(SURVIVORSHIP_STRATEGIES), "survivorship strategy")
+        }
+        "strategy" if in_section("blocking") => (owned(STRATEGIES), "blocking strategy"),
+        "strategy" if in_section("clustering") => {
+            (owned(clustering::STRATEGIES), "clustering strategy")
+        }
+        "mode" if in_section("execution") => (owned(execution::MODES), "execution mode"),
+        "scope" if in_section("deletion") => (owned(deletion::SCOPES), "deletion scope"),
+        "method" if in_section("encoding") => (owned(encoding::METHODS), "encoding method"),
+        "transform" | "transformation" => (
+            owned(TRANSFORMS)
+                .into_iter()
+                .chain(owned(TRANSFORM_PREFIXES))
+                .collect(),
+            "blocking transform",
+        ),
+        "field" | "sort_key" => (attribute_names(text), "source attribute"),
+        "effective_from" | "effective_to" | "delta_column" | "tombstone" => {
+            (attribute_names(text), "source attribute")
+        }
+        _ if in_section("temporal.attributes") => (owned(temporal::HISTORY_MODES), "history mode"),
+        _ => return Vec::new(),
+    };
+
+    values
+        .into_iter()
+        .map(|value| json!({ "label": value, "kind": 12, "detail": detail }))
+        .collect()
+}
+
+fn owned(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+/// Canonical attributes declared by any source, sorted.
+fn attribute_names(text: &str) -> Vec<String> {
+    let spec = mappings::resolve(&parse_yaml_recovering(text).value);
+    let sources: Vec<&Value> = match spec.get("sources") {
+        Some(Value::Array(sources)) => sources.iter().collect(),
+        Some(Value::Object(sources)) => sources.values().collect(),
+        _ => Vec::new(),
+    };
+    let mut names: Vec<String> = sources
+        .iter()
+        .filter_map(|s| s.get("attributes").and_then(|a| a.as_object()))
+        .flat_map(|attrs| attrs.keys().cloned())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// ── Positions ──────────────────────────────────────────────────────
+
+fn line_text(text: &str, line: usize) -> &str {
+    text.lines().nth(line).unwrap_or("")
+}
+
+/// Byte offset in `line` of a UTF-16 character offset.
+fn byte_column(line: &str, character: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= character {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// UTF-16 character offset of a byte offset in `line`.
+fn utf16_column(line: &str, byte: usize) -> usize {
+    line[..byte.min(line.len())].encode_utf16().count()
+}
+
+/// Range from a byte column to the end of the line's content.
+fn line_range(text: &str, line: usize, column: usize) -> Value {
+    let line_str = line_text(text, line);
+    let end = line_str.trim_end().len().max(column);
+    json!({
+        "start": { "line": line, "character": utf16_column(line_str, column) },
+        "end": { "line": line, "character": utf16_column(line_str, end) },
+    })
+}
+
+fn word_at(line: &str, column: usize) -> Option<&str> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let start = line[..column.min(line.len())]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !is_word(*c))
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(0);
+    let end = line[start..]
+        .find(|c: char| !is_word(c))
+        .map(|i| start + i)
+        .unwrap_or(line.len());
+    (start < end).then(|| &line[start..end])
+}
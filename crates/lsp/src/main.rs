@@ -0,0 +1,27 @@
+//! `kanoniv-lsp` — language server for Kanoniv spec YAML files.
+//!
+//! Speaks LSP over stdio and provides diagnostics as you type, hover
+//! documentation for spec fields, go-to-definition for attribute and source
+//! references, and completion for match types, algorithms, survivorship
+//! strategies, blocking transforms and attribute names.
+
+mod features;
+mod server;
+mod transport;
+
+use std::io::{self, BufReader};
+
+fn main() {
+    let stdin = io::stdin();
+    let mut reader = BufReader::new(stdin.lock());
+    let mut writer = io::stdout().lock();
+
+    let code = match server::Server::default().run(&mut reader, &mut writer) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("kanoniv-lsp: {:#}", e);
+            1
+        }
+    };
+    std::process::exit(code);
+}
@@ -0,0 +1,161 @@
+//! Request dispatch and open-document state.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use crate::features;
+use crate::transport::{read_message, write_message, Frame};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, String>,
+    shutdown_requested: bool,
+}
+
+impl Server {
+    /// Serve until `exit`; returns the process exit code.
+    pub fn run(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> Result<i32> {
+        while let Some(frame) = read_message(reader)? {
+            let message = match frame {
+                Frame::Message(message) => message,
+                // The id is unknown, so the reply's is null.
+                Frame::Malformed(reason) => {
+                    write_message(
+                        writer,
+                        &json!({
+                            "jsonrpc": "2.0",
+                            "id": null,
+                            "error": { "code": PARSE_ERROR, "message": reason },
+                        }),
+                    )?;
+                    continue;
+                }
+            };
+            if message.get("method").and_then(|m| m.as_str()) == Some("exit") {
+                return Ok(if self.shutdown_requested { 0 } else { 1 });
+            }
+            for reply in self.handle(&message) {
+                write_message(writer, &reply)?;
+            }
+        }
+        Ok(if self.shutdown_requested { 0 } else { 1 })
+    }
+
+    /// Handle one message, returning responses and notifications to send.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "initialize" => Ok(initialize_result()),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Ok(Value::Null)
+            }
+            "textDocument/didOpen" => {
+                let uri = str_at(&params, &["textDocument", "uri"]);
+                let text = str_at(&params, &["textDocument", "text"]);
+                self.documents.insert(uri.to_string(), text.to_string());
+                return vec![publish(uri, features::diagnostics(text))];
+            }
+            "textDocument/didChange" => {
+                // Full sync: the last change carries the whole document.
+                let uri = str_at(&params, &["textDocument", "uri"]);
+                let text = params
+                    .get("contentChanges")
+                    .and_then(|c| c.as_array())
+                    .and_then(|c| c.last())
+                    .and_then(|c| c.get("text"))
+                    .and_then(|t| t.as_str());
+                let Some(text) = text else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.to_string(), text.to_string());
+                return vec![publish(uri, features::diagnostics(text))];
+            }
+            "textDocument/didClose" => {
+                let uri = str_at(&params, &["textDocument", "uri"]);
+                self.documents.remove(uri);
+                return vec![publish(uri, Vec::new())];
+            }
+            "textDocument/hover" => self.with_position(&params, |_, text, line, character| {
+                features::hover(text, line, character)
+            }),
+            "textDocument/definition" => self
+                .with_position(&params, |uri, text, line, character| {
+                    features::definition(uri, text, line, character)
+                }),
+            "textDocument/completion" => self.with_position(&params, |_, text, line, character| {
+                Some(Value::Array(features::completion(text, line, character)))
+            }),
+            _ if id.is_none() => return Vec::new(),
+            _ => Err((METHOD_NOT_FOUND, format!("Unhandled method: {}", method))),
+        };
+
+        // Notifications never get a response.
+        let Some(id) = id else {
+            return Vec::new();
+        };
+        vec![match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        }]
+    }
+
+    fn with_position(
+        &self,
+        params: &Value,
+        f: impl FnOnce(&str, &str, usize, usize) -> Option<Value>,
+    ) -> std::result::Result<Value, (i64, String)> {
+        let uri = str_at(params, &["textDocument", "uri"]);
+        let Some(text) = self.documents.get(uri) else {
+            return Err((INVALID_REQUEST, format!("Document not open: {}", uri)));
+        };
+        let position = |key: &str| {
+            params
+                .get("position")
+                .and_then(|p| p.get(key))
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0) as usize
+        };
+        Ok(f(uri, text, position("line"), position("character")).unwrap_or(Value::Null))
+    }
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "completionProvider": { "triggerCharacters": [":", " "] },
+        },
+        "serverInfo": { "name": "kanoniv-lsp", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn str_at<'a>(value: &'a Value, path: &[&str]) -> &'a str {
+    path.iter()
+        .try_fold(value, |v, key| v.get(key))
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+}
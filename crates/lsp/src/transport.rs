@@ -0,0 +1,72 @@
+//! JSON-RPC framing over stdio (`Content-Length` headers).
+
+use anyhow::Result;
+use serde_json::Value;
+use std::io::{self, BufRead, Read, Write};
+
+/// The largest body read; a larger one is skipped as malformed rather
+/// than buffered.
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// One frame off the stream: a message, or why it could not be read.
+pub enum Frame {
+    Message(Value),
+    Malformed(String),
+}
+
+/// Read one frame; `Ok(None)` on a clean end of stream. Only I/O failures
+/// are errors: a frame with a bad header, a bad body or a body over
+/// `MAX_BODY_SIZE` is `Frame::Malformed`, and the next frame is read past
+/// whatever is left of it.
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Frame>> {
+    let mut length = None;
+    let mut bad_header = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        // A body without its length runs into the next frame's header.
+        if let Some(at) = header.find("Content-Length:") {
+            let header = &header[at..];
+            match header["Content-Length:".len()..].trim().parse::<usize>() {
+                Ok(value) => length = Some(value),
+                Err(_) => bad_header = Some(format!("Bad Content-Length header: {}", header)),
+            }
+        }
+    }
+
+    let length = match (length, bad_header) {
+        (_, Some(reason)) => return Ok(Some(Frame::Malformed(reason))),
+        (None, None) => {
+            return Ok(Some(Frame::Malformed(
+                "Message without Content-Length header".to_string(),
+            )))
+        }
+        (Some(length), None) => length,
+    };
+    if length > MAX_BODY_SIZE {
+        io::copy(&mut reader.take(length as u64), &mut io::sink())?;
+        return Ok(Some(Frame::Malformed(format!(
+            "Message of {} bytes is larger than the {} byte limit",
+            length, MAX_BODY_SIZE
+        ))));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(match serde_json::from_slice(&body) {
+        Ok(message) => Frame::Message(message),
+        Err(e) => Frame::Malformed(format!("Malformed JSON-RPC message: {}", e)),
+    }))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()?;
+    Ok(())
+}
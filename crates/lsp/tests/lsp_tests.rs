@@ -0,0 +1,209 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const SPEC: &str = "\
+api_version: kanoniv/v2
+identity_version: retail_v1.0
+entity:
+  name: customer
+sources:
+  - name: crm
+    system: salesforce
+    table: contacts
+    id: contact_id
+    attributes:
+      email: email
+rules:
+  - name: email_exact
+    type: exact
+    field: emial
+    weight: 1.0
+  - name: email_fuzzy
+    type: fuzzy
+    field: email
+    algorithm: 
+survivorship:
+  rules:
+    - field: email
+      strategy: source_priority
+      source_priority: [crm]
+decision:
+  thresholds:
+    match: 0.9
+privacy:
+  retention_days: 730
+  fields:
+    email:
+      masking: hash
+";
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_kanoniv-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Client {
+            child,
+            stdin,
+            stdout,
+            next_id: 1,
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        self.send_raw(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    }
+
+    fn send_raw(&mut self, frame: &str) {
+        self.stdin.write_all(frame.as_bytes()).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        let response = self.receive();
+        assert_eq!(response["id"], id);
+        response["result"].clone()
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+}
+
+fn at(line: u64, character: u64) -> Value {
+    json!({
+        "textDocument": { "uri": "file:///spec.yaml" },
+        "position": { "line": line, "character": character },
+    })
+}
+
+#[test]
+fn test_language_features() {
+    let mut client = Client::start();
+
+    let init = client.request("initialize", json!({ "capabilities": {} }));
+    assert_eq!(init["capabilities"]["hoverProvider"], true);
+    client.notify("initialized", json!({}));
+
+    client.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": {
+            "uri": "file:///spec.yaml", "languageId": "yaml", "version": 1, "text": SPEC,
+        }}),
+    );
+    let published = client.receive();
+    assert_eq!(published["method"], "textDocument/publishDiagnostics");
+    let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "KNV0101");
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 14, "character": 4 })
+    );
+
+    // Hover on `weight` shows the schema's bounds.
+    let hover = client.request("textDocument/hover", at(15, 6));
+    let doc = hover["contents"]["value"].as_str().unwrap();
+    assert!(doc.contains("rules[0].weight"), "{}", doc);
+    assert!(doc.contains("Between 0 and 1"), "{}", doc);
+
+    // `field: email` jumps to the attribute, `[crm]` to the source.
+    let definition = client.request("textDocument/definition", at(18, 12));
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 10, "character": 6 })
+    );
+    let definition = client.request("textDocument/definition", at(24, 24));
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 5, "character": 4 })
+    );
+
+    // Completion after `algorithm: ` offers algorithm names.
+    let items = client.request("textDocument/completion", at(19, 15));
+    let labels: Vec<&str> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["label"].as_str().unwrap())
+        .collect();
+    assert!(labels.contains(&"jaro_winkler"), "{:?}", labels);
+
+    // Fixing the typo clears the diagnostic.
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": "file:///spec.yaml", "version": 2 },
+            "contentChanges": [{ "text": SPEC.replace("emial", "email") }],
+        }),
+    );
+    let published = client.receive();
+    assert_eq!(published["params"]["diagnostics"], json!([]));
+
+    client.request("shutdown", Value::Null);
+    client.notify("exit", Value::Null);
+    assert!(client.child.wait().unwrap().success());
+}
+
+#[test]
+fn test_malformed_frames_get_a_parse_error() {
+    let mut client = Client::start();
+
+    // A body that is not JSON, then a header that is not a length.
+    client.send_raw("Content-Length: 9\r\n\r\n{\"id\": 1,");
+    let reply = client.receive();
+    assert_eq!(reply["id"], Value::Null);
+    assert_eq!(reply["error"]["code"], -32700);
+    client.send_raw("Content-Length: ten\r\n\r\n{}");
+    let reply = client.receive();
+    assert_eq!(reply["error"]["code"], -32700);
+    assert!(reply["error"]["message"].as_str().unwrap().contains("Bad Content-Length"));
+
+    // A body over the size limit is skipped, not buffered.
+    let body = " ".repeat(16 * 1024 * 1024 + 1);
+    client.send_raw(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+    let reply = client.receive();
+    assert_eq!(reply["error"]["code"], -32700);
+    assert!(reply["error"]["message"].as_str().unwrap().contains("byte limit"));
+
+    // The server keeps serving.
+    let init = client.request("initialize", json!({ "capabilities": {} }));
+    assert_eq!(init["capabilities"]["hoverProvider"], true);
+    client.request("shutdown", Value::Null);
+    client.notify("exit", Value::Null);
+    assert!(client.child.wait().unwrap().success());
+}
@@ -0,0 +1,47 @@
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+
+/// What the cache's build hash digests (see `src/cache.rs`).
+const BUILD_INPUTS: &[&str] = &["Cargo.toml", "src", "templates", "proto"];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The coordinator/worker protocol (see `src/distributed.rs`).
+    #[cfg(feature = "distributed")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+        tonic_build::configure().compile_protos(&["proto/distributed.proto"], &["proto"])?;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    for input in BUILD_INPUTS {
+        println!("cargo:rerun-if-changed={}", input);
+        hash_path(Path::new(input), &mut hasher)?;
+    }
+    let mut features: Vec<String> = std::env::vars()
+        .map(|(name, _)| name)
+        .filter(|name| name.starts_with("CARGO_FEATURE_"))
+        .collect();
+    features.sort();
+    features.hash(&mut hasher);
+    println!(
+        "cargo:rustc-env=KANONIV_BUILD_HASH={:016x}",
+        hasher.finish()
+    );
+    Ok(())
+}
+
+/// Hash the file at `path`, or every file under it, by path and content.
+fn hash_path(path: &Path, hasher: &mut DefaultHasher) -> std::io::Result<()> {
+    if path.is_file() {
+        path.to_string_lossy().replace('\\', "/").hash(hasher);
+        fs::read(path)?.hash(hasher);
+        return Ok(());
+    }
+    let mut entries: Vec<_> = fs::read_dir(path)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        hash_path(&entry.path(), hasher)?;
+    }
+    Ok(())
+}
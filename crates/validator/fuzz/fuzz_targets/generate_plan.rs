@@ -0,0 +1,11 @@
+//! Specs built from arbitrary shapes plan or fail with an error.
+
+#![no_main]
+
+use kanoniv_core::fuzzing::SpecShape;
+use kanoniv_core::generate_plan;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|shape: SpecShape| {
+    let _ = generate_plan(&shape.to_yaml());
+});
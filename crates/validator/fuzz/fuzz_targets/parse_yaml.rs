@@ -0,0 +1,11 @@
+//! Any text, spec or not, parses to a value or an error.
+
+#![no_main]
+
+use kanoniv_core::{parse_yaml, parse_yaml_recovering};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    let _ = parse_yaml(text);
+    let _ = parse_yaml_recovering(text);
+});
@@ -0,0 +1,13 @@
+//! Whatever YAML parses to, the schema and semantic checks report on it.
+
+#![no_main]
+
+use kanoniv_core::{parse_yaml, semantic_diagnostics, validate_schema};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    if let Ok(spec) = parse_yaml(text) {
+        let _ = validate_schema(&spec);
+        let _ = semantic_diagnostics(&spec);
+    }
+});
@@ -0,0 +1,350 @@
+//! Postal address standardization and geohashes for blocking.
+//!
+//! Addresses are tokenized the way libpostal does it, reduced: case folded,
+//! accents and punctuation removed, and the usual abbreviations expanded
+//! (`St` -> `street`, `Ave.` -> `avenue`, `N` -> `north`, `Apt` ->
+//! `apartment`), so `123 N. Main St, Apt 4` and `123 north main street
+//! apartment 4` agree. `parse` picks out the house number, street, unit and
+//! postcode; the first comma-separated part is taken as the street line.
+//!
+//! Blocking keys use these as transforms: `address` (the whole standardized
+//! address), `street`, `postcode`, and `geohash` / `geohash:<precision>`
+//! for a field holding `lat,lon` coordinates.
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Geohash precision when `geohash` is given without one (cells of about
+/// 1.2 km by 0.6 km).
+pub const DEFAULT_GEOHASH_PRECISION: usize = 6;
+
+const GEOHASH_ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Street suffixes, abbreviation -> expansion.
+const SUFFIXES: &[(&str, &str)] = &[
+    ("st", "street"),
+    ("str", "street"),
+    ("ave", "avenue"),
+    ("av", "avenue"),
+    ("rd", "road"),
+    ("blvd", "boulevard"),
+    ("dr", "drive"),
+    ("ln", "lane"),
+    ("ct", "court"),
+    ("pl", "place"),
+    ("sq", "square"),
+    ("ter", "terrace"),
+    ("cir", "circle"),
+    ("trl", "trail"),
+    ("pkwy", "parkway"),
+    ("hwy", "highway"),
+    ("expy", "expressway"),
+    ("fwy", "freeway"),
+    ("aly", "alley"),
+    ("cres", "crescent"),
+];
+
+/// Street suffixes written out, which end a street name.
+const SUFFIX_WORDS: &[&str] = &["way", "row", "close", "walk", "mews", "gardens"];
+
+const DIRECTIONALS: &[(&str, &str)] = &[
+    ("n", "north"),
+    ("s", "south"),
+    ("e", "east"),
+    ("w", "west"),
+    ("ne", "northeast"),
+    ("nw", "northwest"),
+    ("se", "southeast"),
+    ("sw", "southwest"),
+];
+
+/// Unit designators, abbreviation -> expansion.
+const UNITS: &[(&str, &str)] = &[
+    ("apt", "apartment"),
+    ("ste", "suite"),
+    ("fl", "floor"),
+    ("flr", "floor"),
+    ("rm", "room"),
+    ("bldg", "building"),
+    ("unit", "unit"),
+    ("#", "unit"),
+];
+
+const OTHER: &[(&str, &str)] = &[("mt", "mount"), ("ft", "fort"), ("pob", "po box")];
+
+/// An address split into the components blocking keys use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Address {
+    pub house_number: Option<String>,
+    /// Standardized street name, without the house number or unit.
+    pub street: Option<String>,
+    /// Standardized unit (`apartment 4b`).
+    pub unit: Option<String>,
+    /// Postcode, uppercase without spaces (`94105`, `SW1A1AA`).
+    pub postcode: Option<String>,
+}
+
+/// The standardized address: tokens expanded and joined by single spaces.
+pub fn standardize(value: &str) -> String {
+    value
+        .split(',')
+        .map(|part| expand(&tokenize(part)).join(" "))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Split an address into its components.
+pub fn parse(value: &str) -> Address {
+    let parts: Vec<Vec<String>> = value.split(',').map(tokenize).collect();
+    let all: Vec<String> = parts.iter().flatten().cloned().collect();
+    let found = postcode(&all);
+    let mut address = Address {
+        postcode: found.as_ref().map(|(code, _)| code.clone()),
+        ..Address::default()
+    };
+
+    let mut line = parts.first().cloned().unwrap_or_default();
+    // A postcode on the street line is not part of the street.
+    if let Some((_, at)) = found {
+        line.truncate(at);
+    }
+    if line
+        .first()
+        .is_some_and(|t| t.starts_with(|c: char| c.is_ascii_digit()))
+    {
+        address.house_number = Some(line.remove(0));
+    }
+    if let Some(at) = line.iter().position(|t| lookup(UNITS, t).is_some()) {
+        let unit = expand(&line.split_off(at)).join(" ");
+        address.unit = Some(unit);
+    }
+    let street = expand(&line).join(" ");
+    address.street = (!street.is_empty()).then_some(street);
+    address
+}
+
+/// Lowercase, accent-free words and numbers, split on whitespace and
+/// punctuation; `#` is kept as a unit designator.
+fn tokenize(value: &str) -> Vec<String> {
+    let folded: String = caseless::default_case_fold_str(value)
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect();
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    for c in folded.chars() {
+        if c.is_alphanumeric() {
+            token.push(c);
+        } else {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+            if c == '#' {
+                tokens.push("#".to_string());
+            }
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Expand abbreviations. `st` opening a street name and followed by a name
+/// is `saint` (`123 St Marks Pl`); single-letter directionals expand only
+/// where they are not the last word (`E Main St` is `east`, but `Avenue E`
+/// is a street name).
+fn expand(tokens: &[String]) -> Vec<String> {
+    let mut out = Vec::with_capacity(tokens.len());
+    for (i, token) in tokens.iter().enumerate() {
+        let opens = i == 0 || tokens[i - 1].starts_with(|c: char| c.is_ascii_digit());
+        if token == "#" && i > 0 && lookup(UNITS, &tokens[i - 1]).is_some() {
+            continue; // `Apt #4`
+        }
+        let next = tokens.get(i + 1);
+        let named = next.is_some_and(|n| !is_suffix(n) && lookup(UNITS, n).is_none());
+        let word = if token == "st" && opens && named {
+            Some("saint")
+        } else if let Some(suffix) = lookup(SUFFIXES, token) {
+            Some(suffix)
+        } else if let Some(direction) = lookup(DIRECTIONALS, token) {
+            (token.len() > 1 || next.is_some()).then_some(direction)
+        } else {
+            lookup(UNITS, token).or_else(|| lookup(OTHER, token))
+        };
+        out.push(word.unwrap_or(token).to_string());
+    }
+    out
+}
+
+fn lookup(table: &[(&str, &'static str)], token: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(abbr, _)| *abbr == token)
+        .map(|(_, full)| *full)
+}
+
+fn is_suffix(token: &str) -> bool {
+    lookup(SUFFIXES, token).is_some()
+        || SUFFIXES.iter().any(|(_, full)| *full == token)
+        || SUFFIX_WORDS.contains(&token)
+}
+
+/// The last postcode-shaped token (or token pair) and where it starts: a
+/// US ZIP (`94105`, `94105-1234`, reduced to five digits), a UK or Canadian
+/// postcode (`SW1A 1AA`, `K1A 0B1`), or another 4 to 6 digit code. A
+/// leading house number is never taken for a postcode.
+fn postcode(tokens: &[String]) -> Option<(String, usize)> {
+    let digits = |t: &str, n: std::ops::RangeInclusive<usize>| {
+        n.contains(&t.len()) && t.bytes().all(|b| b.is_ascii_digit())
+    };
+    let shaped = |t: &str, shape: &str| {
+        t.len() == shape.len()
+            && t.bytes().zip(shape.bytes()).all(|(c, s)| match s {
+                b'a' => c.is_ascii_alphabetic(),
+                b'9' => c.is_ascii_digit(),
+                _ => c.is_ascii_alphanumeric(),
+            })
+    };
+    for i in (1..tokens.len()).rev() {
+        let (prev, token) = (tokens[i - 1].as_str(), tokens[i].as_str());
+        let outward = ["a9", "a99", "aa9", "aa99", "a9a", "aa9a"];
+        if shaped(token, "9aa") && outward.iter().any(|s| shaped(prev, s)) {
+            return Some((format!("{}{}", prev, token).to_ascii_uppercase(), i - 1));
+        }
+        if shaped(token, "9a9") && shaped(prev, "a9a") {
+            return Some((format!("{}{}", prev, token).to_ascii_uppercase(), i - 1));
+        }
+        if digits(token, 4..=4) && digits(prev, 5..=5) && i > 1 {
+            return Some((prev.to_string(), i - 1));
+        }
+        if digits(token, 4..=6) {
+            return Some((token.to_string(), i));
+        }
+    }
+    None
+}
+
+// ── Geohash ────────────────────────────────────────────────────────
+
+/// `lat,lon` (or `lat lon`) in degrees; `None` if not two in-range numbers.
+pub fn parse_coordinates(value: &str) -> Option<(f64, f64)> {
+    let mut parts = value
+        .split([',', ' ', ';'])
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse::<f64>().ok());
+    let (lat, lon) = (parts.next()??, parts.next()??);
+    if parts.next().is_some() || !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon)
+    {
+        return None;
+    }
+    Some((lat, lon))
+}
+
+/// Geohash of a point, `precision` characters long (1 to 12).
+pub fn geohash(lat: f64, lon: f64, precision: usize) -> String {
+    let (mut lat_range, mut lon_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut hash = String::with_capacity(precision);
+    let (mut bits, mut bit, mut even) = (0usize, 0, true);
+    while hash.len() < precision.clamp(1, 12) {
+        let (range, value) = if even {
+            (&mut lon_range, lon)
+        } else {
+            (&mut lat_range, lat)
+        };
+        let mid = (range.0 + range.1) / 2.0;
+        bits <<= 1;
+        if value >= mid {
+            bits |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+        even = !even;
+        bit += 1;
+        if bit == 5 {
+            hash.push(GEOHASH_ALPHABET[bits] as char);
+            (bits, bit) = (0, 0);
+        }
+    }
+    hash
+}
+
+/// Precision of a `geohash` or `geohash:<n>` transform; `None` for other
+/// transforms or a precision outside 1 to 12.
+pub fn geohash_precision(transform: &str) -> Option<usize> {
+    match transform.strip_prefix("geohash")? {
+        "" => Some(DEFAULT_GEOHASH_PRECISION),
+        rest => rest
+            .strip_prefix(':')?
+            .parse()
+            .ok()
+            .filter(|p| (1..=12).contains(p)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_address_standardization_and_geohash_blocking() {
+        use crate::address::{self, Address};
+        use crate::sample::apply_transform;
+
+        assert_eq!(
+            address::standardize("123 N. Main St., Apt #4"),
+            "123 north main street apartment 4"
+        );
+        assert_eq!(
+            address::standardize("123 St Marks Pl"),
+            "123 saint marks place"
+        );
+        assert_eq!(
+            address::parse("123 N Main St Apt 4B, Springfield, IL 62704-1234"),
+            Address {
+                house_number: Some("123".to_string()),
+                street: Some("north main street".to_string()),
+                unit: Some("apartment 4b".to_string()),
+                postcode: Some("62704".to_string()),
+            }
+        );
+        assert_eq!(
+            address::parse("10 Downing St, London SW1A 2AA")
+                .postcode
+                .as_deref(),
+            Some("SW1A2AA")
+        );
+        assert_eq!(
+            apply_transform("1 Main Street, 94105", "street"),
+            apply_transform("1 MAIN ST.", "street")
+        );
+
+        // The geohash example from its original description.
+        assert_eq!(address::geohash(57.64911, 10.40744, 11), "u4pruydqqvj");
+        assert_eq!(apply_transform("57.64911, 10.40744", "geohash"), "u4pruy");
+        assert_eq!(apply_transform("57.64911,10.40744", "geohash:3"), "u4p");
+        assert_eq!(apply_transform("not a point", "geohash"), "");
+        assert_eq!(address::geohash_precision("geohash:13"), None);
+
+        let spec = |transform: &str| {
+            format!(
+                "{}blocking:\n  keys:\n    - field: email\n      transform: \"{}\"\n",
+                MINIMAL, transform
+            )
+        };
+        let plan = crate::generate_plan(&spec("geohash:3")).unwrap();
+        assert_eq!(plan.blocking_analysis.estimated_reduction, "low");
+        assert!(plan
+            .blocking_analysis
+            .warnings
+            .iter()
+            .any(|w| w.contains("156 km")));
+        let plan = crate::generate_plan(&spec("address")).unwrap();
+        assert_eq!(plan.blocking_analysis.estimated_reduction, "medium");
+        let plan = crate::generate_plan(&spec("postcode")).unwrap();
+        assert_eq!(plan.blocking_analysis.estimated_reduction, "low");
+        assert!(plan.blocking_analysis.warnings.is_empty());
+    }
+}
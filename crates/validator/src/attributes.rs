@@ -0,0 +1,254 @@
+//! Source attribute mappings and attribute types.
+//!
+//! A source maps each canonical attribute to the column holding it, either
+//! as the column name or as a mapping that also declares the attribute's
+//! type:
+//!
+//! ```yaml
+//! attributes:
+//!   email: email_address          # untyped
+//!   dob:
+//!     column: date_of_birth
+//!     type: date                  # string, email, phone, date, numeric
+//!                                 # or identifier
+//! ```
+//!
+//! Types are optional. Declared ones let validation reject rules that
+//! cannot work on the values (a string similarity over dates, an exact
+//! match on free text nobody normalized) and let the plan recommend the
+//! normalizers each type needs.
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::normalize::Normalizer;
+use crate::similarity::BUILTIN_ALGORITHMS;
+
+pub const TYPES: &[&str] = &["string", "email", "phone", "date", "numeric", "identifier"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AttributeType {
+    /// Free text: names, titles, addresses.
+    String,
+    Email,
+    Phone,
+    /// ISO 8601 dates.
+    Date,
+    Numeric,
+    /// Codes that identify a record on their own: tax ids, account numbers.
+    Identifier,
+}
+
+impl AttributeType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AttributeType::String => "string",
+            AttributeType::Email => "email",
+            AttributeType::Phone => "phone",
+            AttributeType::Date => "date",
+            AttributeType::Numeric => "numeric",
+            AttributeType::Identifier => "identifier",
+        }
+    }
+
+    /// Whether values are text a string similarity is meaningful on. The
+    /// built-in algorithms compare characters, so dates and numbers that
+    /// are close differ as much as ones that are far apart.
+    pub fn is_textual(&self) -> bool {
+        !matches!(self, AttributeType::Date | AttributeType::Numeric)
+    }
+
+    /// Whether `algorithm` can compare values of this type. Algorithms an
+    /// engine registers itself are assumed to know their inputs.
+    pub fn accepts_algorithm(&self, algorithm: &str) -> bool {
+        self.is_textual() || !BUILTIN_ALGORITHMS.contains(&algorithm)
+    }
+
+    /// Whether `normalizer` applies to values of this type.
+    pub fn accepts_normalizer(&self, normalizer: &Normalizer) -> bool {
+        match normalizer {
+            Normalizer::Trim
+            | Normalizer::Lower
+            | Normalizer::Upper
+            | Normalizer::RegexReplace(_)
+            | Normalizer::Nfkc
+            | Normalizer::CaseFold
+            | Normalizer::StripDiacritics => true,
+            Normalizer::Phone { .. } => *self == AttributeType::Phone,
+            Normalizer::RemoveHonorifics
+            | Normalizer::ExpandNicknames
+            | Normalizer::Soundex
+            | Normalizer::Metaphone
+            | Normalizer::Address => *self == AttributeType::String,
+        }
+    }
+}
+
+impl FromStr for AttributeType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "string" => Ok(AttributeType::String),
+            "email" => Ok(AttributeType::Email),
+            "phone" => Ok(AttributeType::Phone),
+            "date" => Ok(AttributeType::Date),
+            "numeric" => Ok(AttributeType::Numeric),
+            "identifier" => Ok(AttributeType::Identifier),
+            _ => bail!("Unknown attribute type '{}'. Use {}", s, TYPES.join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for AttributeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The column `attribute` is read from, given its `mapping` in a source's
+/// `attributes`: the column name, a mapping's `column`, or the attribute's
+/// own name.
+pub fn column<'a>(attribute: &'a str, mapping: &'a Value) -> &'a str {
+    match mapping {
+        Value::String(column) => column,
+        Value::Object(map) => map
+            .get("column")
+            .and_then(Value::as_str)
+            .unwrap_or(attribute),
+        _ => attribute,
+    }
+}
+
+/// The type a `mapping` declares, if it declares a valid one.
+pub fn declared_type(mapping: &Value) -> Option<AttributeType> {
+    mapping.get("type")?.as_str()?.parse().ok()
+}
+
+/// Each typed attribute's type, as the first source declaring one gives it.
+pub fn declared_types(spec: &Value) -> BTreeMap<String, AttributeType> {
+    let mut types = BTreeMap::new();
+    let sources = spec.get("sources").and_then(Value::as_array);
+    for attributes in sources
+        .into_iter()
+        .flatten()
+        .filter_map(|source| source.get("attributes")?.as_object())
+    {
+        for (attribute, mapping) in attributes {
+            if let Some(declared) = declared_type(mapping) {
+                types.entry(attribute.clone()).or_insert(declared);
+            }
+        }
+    }
+    types
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MINIMAL, TYPED};
+    use crate::{Ir, Severity};
+
+    #[test]
+    fn test_attribute_types_constrain_rules_and_normalizers() {
+        use crate::{AttributeType, Impact};
+
+        let codes = |yaml: &str| -> Vec<(String, Option<String>)> {
+            crate::diagnose_yaml(yaml)
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .map(|d| (d.code, d.path))
+                .collect()
+        };
+        let finding = |code: &str, path: &str| (code.to_string(), Some(path.to_string()));
+        assert!(codes(TYPED).is_empty());
+        assert_eq!(crate::format_spec(TYPED).unwrap(), TYPED);
+
+        // Typed attributes compile to their columns, with the types alongside.
+        let compiled = crate::compile_to_ir(&crate::parse_yaml(TYPED).unwrap()).unwrap();
+        let ir = Ir::from_value(&compiled).unwrap();
+        assert_eq!(ir.sources[0].attributes["email"], "email_address");
+        assert_eq!(ir.types()["dob"], AttributeType::Date);
+        assert_eq!(
+            crate::compile_to_ir(&ir.to_spec()).unwrap()["plan_hash"],
+            compiled["plan_hash"]
+        );
+        let untyped = crate::compile_to_ir(&crate::parse_yaml(MINIMAL).unwrap()).unwrap();
+        assert!(untyped["sources"][0].get("types").is_none());
+
+        // Mappings need a column, and a type the validator knows.
+        let malformed = TYPED.replace(
+            "        column: date_of_birth\n        type: date\n",
+            "        type: datetime\n",
+        );
+        assert_eq!(
+            codes(&malformed),
+            [
+                finding("KNV0008", "sources[0].attributes.dob.column"),
+                finding("KNV0008", "sources[0].attributes.dob.type"),
+            ]
+        );
+
+        // String similarities on dates, free text matched as written and
+        // normalizers meant for other types are rejected.
+        let incoherent = TYPED
+            .replace(
+                "    type: exact\n    field: dob\n",
+                "    type: fuzzy\n    field: dob\n    algorithm: jaro_winkler\n",
+            )
+            .replace("    last_name: [nfkc, casefold]\n", "")
+            .replace("phone: [phone]", "phone: [phone, soundex]");
+        assert_eq!(
+            codes(&incoherent),
+            [
+                finding("KNV0123", "rules[1].algorithm"),
+                finding("KNV0123", "rules[3]"),
+                finding("KNV0123", "normalization.fields.phone[1]"),
+            ]
+        );
+
+        // Each type's normalization needs show up as risk flags.
+        let flags = |yaml: &str| -> Vec<String> {
+            crate::generate_plan(yaml)
+                .unwrap()
+                .risk_flags
+                .into_iter()
+                .map(|f| f.code)
+                .collect()
+        };
+        let typed_flags = [
+            "PHONE_NOT_NORMALIZED",
+            "EMAIL_CASE_SENSITIVE",
+            "FUZZY_IDENTIFIER",
+        ];
+        assert!(flags(TYPED)
+            .iter()
+            .all(|f| !typed_flags.contains(&f.as_str())));
+        // A phone attribute is a phone whatever its name.
+        assert!(flags(TYPED).contains(&"PHONE_WITHOUT_BLOCKING".to_string()));
+        let unnormalized = TYPED
+        .replace("    email: [casefold]\n", "")
+        .replace("    phone: [phone]\n", "")
+        .replace(
+            "    type: exact\n    field: account_number\n",
+            "    type: fuzzy\n    field: account_number\n    algorithm: levenshtein\n    threshold: 0.95\n",
+        );
+        let raised = flags(&unnormalized);
+        assert!(
+            typed_flags.iter().all(|f| raised.contains(&f.to_string())),
+            "{:?}",
+            raised
+        );
+
+        // Declaring a type is safe; changing one changes what is checked.
+        let untyped = TYPED.replace("        type: date\n", "");
+        let declared = crate::compute_diff(&untyped, TYPED).unwrap().compatibility;
+        assert_eq!(declared.impact, Some(Impact::Safe));
+        let retyped = TYPED.replace("        type: date\n", "        type: string\n");
+        let changed = crate::compute_diff(TYPED, &retyped).unwrap().compatibility;
+        assert_eq!(changed.impact, Some(Impact::Risky));
+        assert!(changed.changes[0].path.ends_with("attributes.dob.type"));
+    }
+}
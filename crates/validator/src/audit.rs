@@ -0,0 +1,314 @@
+//! Audit trail: how each entity came to be.
+//!
+//! The pipeline's last stage writes an `audit_trail` next to the canonical
+//! and lineage tables (see `plan`). Its records are `AuditRecord`s, one
+//! event each, stamped with the plan hash and identity version of the spec
+//! that produced them:
+//!
+//! - `match_decision`: a compared pair's score and decision (`match`,
+//!   `review` or `reject`)
+//! - `merge`: the records an entity joins
+//! - `split`: records transitive closure would join that the clustering
+//!   strategy keeps in several entities
+//! - `survivorship_choice`: the value a golden field takes and the record
+//!   it survives from
+//! - `steward_override`: a stewardship assertion (see `stewardship`) the
+//!   run honored over scores or survivorship rules
+//!
+//! `cluster_decisions` emits the first three and the overrides of merges,
+//! `golden_records` the choices and the overrides of fields.
+//! Records serialize as JSON objects tagged by `event`, written one per
+//! line; `audit_json_schema` describes them for downstream consumers.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::ir::Ir;
+
+pub const AUDIT_SCHEMA_ID: &str = "https://oss.kanoniv.com/schema/audit.json";
+
+/// Event names, as the `event` tag reads.
+pub const EVENTS: &[&str] = &[
+    "match_decision",
+    "merge",
+    "split",
+    "survivorship_choice",
+    "steward_override",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub plan_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_version: Option<String>,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    MatchDecision(MatchDecision),
+    Merge(MergeEvent),
+    Split(SplitEvent),
+    SurvivorshipChoice(SurvivorshipChoice),
+    StewardOverride(StewardOverride),
+}
+
+/// A compared pair and the decision its score got.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchDecision {
+    pub left_key: String,
+    pub right_key: String,
+    pub score: f64,
+    pub decision: String,
+}
+
+/// Records joined into one entity, in key order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergeEvent {
+    pub entity_id: String,
+    pub record_keys: Vec<String>,
+    /// Clustering strategy that joined them.
+    pub strategy: String,
+}
+
+/// A chain of matches the clustering strategy cut into several entities.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitEvent {
+    /// Lowest record key of the records the matches chain together.
+    pub component_id: String,
+    pub entity_ids: Vec<String>,
+    pub strategy: String,
+}
+
+/// The value a golden field takes; `None` is null.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SurvivorshipChoice {
+    pub entity_id: String,
+    pub field: String,
+    pub value: Option<String>,
+    /// Record the value survives from.
+    pub record_key: Option<String>,
+    pub strategy: String,
+}
+
+/// A stewardship assertion a run honored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StewardOverride {
+    /// `always_merge`, `never_merge` or `locked`.
+    pub assertion: String,
+    pub record_keys: Vec<String>,
+    /// The entity the records joined, or whose field was locked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl AuditRecord {
+    /// `event`, stamped with `ir`'s plan hash and identity version.
+    pub fn new(ir: &Ir, event: AuditEvent) -> Self {
+        AuditRecord {
+            plan_hash: ir.plan_hash.clone(),
+            identity_version: ir.identity_version.clone(),
+            event,
+        }
+    }
+}
+
+/// `records` as JSON Lines, one record per line.
+pub fn to_json_lines(records: &[AuditRecord]) -> Result<String> {
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+    Ok(lines)
+}
+
+/// The JSON Schema (draft-07) of one audit record.
+pub fn audit_json_schema() -> Value {
+    let key = json!({ "type": "string", "minLength": 1 });
+    let keys = json!({ "type": "array", "items": key, "minItems": 1 });
+    let nullable = json!({ "type": ["string", "null"] });
+    let event = |name: &str, description: &str, properties: Value, required: &[&str]| {
+        let mut properties = properties;
+        properties["event"] = json!({ "const": name });
+        properties["plan_hash"] = json!({ "$ref": "#/definitions/plan_hash" });
+        properties["identity_version"] = json!({ "type": "string" });
+        let mut required: Vec<&str> = required.to_vec();
+        required.extend(["event", "plan_hash"]);
+        json!({
+            "description": description,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    };
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "$id": AUDIT_SCHEMA_ID,
+        "title": "Kanoniv audit record",
+        "description": "One event of the audit trail, written one JSON object per line.",
+        "definitions": {
+            "plan_hash": {
+                "description": "Plan hash of the spec that produced the event.",
+                "type": "string",
+                "pattern": "^sha256:[0-9a-f]{64}$",
+            },
+        },
+        "oneOf": [
+            event(
+                "match_decision",
+                "A compared pair and the decision its score got.",
+                json!({
+                    "left_key": key,
+                    "right_key": key,
+                    "score": { "type": "number" },
+                    "decision": { "enum": ["match", "review", "reject"] },
+                }),
+                &["left_key", "right_key", "score", "decision"],
+            ),
+            event(
+                "merge",
+                "Records joined into one entity, in key order.",
+                json!({
+                    "entity_id": key,
+                    "record_keys": keys,
+                    "strategy": { "enum": crate::clustering::STRATEGIES },
+                }),
+                &["entity_id", "record_keys", "strategy"],
+            ),
+            event(
+                "split",
+                "A chain of matches the clustering strategy cut into several entities.",
+                json!({
+                    "component_id": key,
+                    "entity_ids": keys,
+                    "strategy": { "enum": crate::clustering::STRATEGIES },
+                }),
+                &["component_id", "entity_ids", "strategy"],
+            ),
+            event(
+                "survivorship_choice",
+                "The value a golden field takes and the record it survives from.",
+                json!({
+                    "entity_id": key,
+                    "field": key,
+                    "value": nullable,
+                    "record_key": nullable,
+                    "strategy": { "enum": crate::survivorship::STRATEGIES },
+                }),
+                &["entity_id", "field", "value", "record_key", "strategy"],
+            ),
+            event(
+                "steward_override",
+                "A stewardship assertion the run honored over scores or survivorship rules.",
+                json!({
+                    "assertion": { "enum": crate::stewardship::KEYS },
+                    "record_keys": keys,
+                    "entity_id": key,
+                    "field": key,
+                    "value": { "type": "string" },
+                    "reason": { "type": "string" },
+                }),
+                &["assertion", "record_keys"],
+            ),
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{AS_OF, MINIMAL};
+
+    #[test]
+    fn test_engine_emits_audit_trail() {
+        use crate::audit::to_json_lines;
+        use crate::{
+            audit_json_schema, cluster_decisions, golden_records, AuditEvent, AuditRecord,
+            MergeEvent, Sample, SplitEvent, SurvivorshipChoice,
+        };
+
+        let spec = format!("{}clustering:\n  strategy: star\n", MINIMAL);
+        let decisions = Sample::from_csv(
+        "left_key,right_key,score,decision\na,b,0.95,match\nb,c,0.92,match\nc,d,0.94,match\na,c,0.4,reject\n",
+    )
+    .unwrap();
+        let clusters = cluster_decisions(&spec, &decisions).unwrap();
+        let events: Vec<&AuditEvent> = clusters.audit.iter().map(|r| &r.event).collect();
+        assert_eq!(events.len(), 6);
+        assert!(
+            matches!(events[3], AuditEvent::MatchDecision(d) if d.left_key == "a" && d.right_key == "c" && d.decision == "reject")
+        );
+        // b, with the most matches, centers a star of a, b and c; d is left
+        // alone although the chain c-d matched.
+        assert_eq!(
+            *events[4],
+            AuditEvent::Merge(MergeEvent {
+                entity_id: "a".to_string(),
+                record_keys: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                strategy: "star".to_string(),
+            })
+        );
+        assert_eq!(
+            *events[5],
+            AuditEvent::Split(SplitEvent {
+                component_id: "a".to_string(),
+                entity_ids: vec!["a".to_string(), "d".to_string()],
+                strategy: "star".to_string(),
+            })
+        );
+        assert!(clusters
+            .audit
+            .iter()
+            .all(|r| r.identity_version.as_deref() == Some("retail_v1.0")));
+
+        let members = Sample::from_csv(
+            "cluster_id,source_name,record_key,email\ne1,crm,a,ann@x.com\ne1,crm,b,\ne2,crm,c,\n",
+        )
+        .unwrap();
+        let golden = golden_records(MINIMAL, &members, AS_OF).unwrap();
+        assert_eq!(
+            golden.audit[0].event,
+            AuditEvent::SurvivorshipChoice(SurvivorshipChoice {
+                entity_id: "e1".to_string(),
+                field: "email".to_string(),
+                value: Some("ann@x.com".to_string()),
+                record_key: Some("a".to_string()),
+                strategy: "most_complete".to_string(),
+            })
+        );
+        assert!(
+            matches!(&golden.audit[1].event, AuditEvent::SurvivorshipChoice(c) if c.value.is_none())
+        );
+
+        // Records round-trip through their JSON Lines form, tagged by event.
+        let lines = to_json_lines(&clusters.audit).unwrap();
+        assert!(lines
+            .lines()
+            .nth(4)
+            .unwrap()
+            .contains(r#""event":"merge","entity_id":"a""#));
+        let read: Vec<AuditRecord> = lines
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(read, clusters.audit);
+        let schema = audit_json_schema();
+        let events: Vec<&str> = schema["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["properties"]["event"]["const"].as_str().unwrap())
+            .collect();
+        assert_eq!(events, crate::audit::EVENTS);
+    }
+}
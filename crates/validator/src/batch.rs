@@ -0,0 +1,235 @@
+//! Validating many spec files at once.
+//!
+//! `validate_many` validates each file as `kanoniv validate` would on its
+//! own, across a thread pool with the `parallel` feature (and one file
+//! after another without it). A worker reads, validates and drops one file
+//! before taking the next, so the specs held in memory at once are bounded
+//! by the number of workers, not of files; only the reports are kept, in
+//! the order of the paths given.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::cache::{Cache, CacheKey};
+use crate::compose;
+use crate::diagnostics::{codes, Diagnostic, Profile, Tiers};
+use crate::interpolate::Variables;
+use crate::policy::Policy;
+
+/// The findings for one spec file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileReport {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub findings: Tiers,
+}
+
+impl FileReport {
+    pub fn is_valid(&self) -> bool {
+        self.findings.is_valid()
+    }
+}
+
+/// How to validate a batch of files.
+#[derive(Debug, Default)]
+pub struct BatchOptions {
+    pub profile: Profile,
+    /// Worker threads; all cores when `None`. Without the `parallel`
+    /// feature files are validated on the calling thread regardless.
+    pub jobs: Option<usize>,
+    /// Where results are kept between runs, so unchanged files are not
+    /// validated again.
+    pub cache: Option<Cache>,
+    /// The entity read from workspaces; all of them when `None`.
+    pub entity: Option<String>,
+    /// What spec files are interpolated with.
+    pub variables: Variables,
+}
+
+/// Validate every file of `paths` under the default profile.
+pub fn validate_many(paths: &[PathBuf]) -> Vec<FileReport> {
+    validate_many_with(paths, &BatchOptions::default())
+}
+
+/// As `validate_many`, with `options`.
+pub fn validate_many_with(paths: &[PathBuf], options: &BatchOptions) -> Vec<FileReport> {
+    map(paths, options.jobs, |path| {
+        let findings = match read_spec(path, options.entity.as_deref(), &options.variables) {
+            Ok((content, policy)) => {
+                let validate = || crate::validate_tiers_with(&content, options.profile, &policy);
+                match &options.cache {
+                    Some(cache) => {
+                        let key = cache_key("tiers", &content, options.profile, &policy);
+                        cache.get_or_insert(&key.finish(), validate)
+                    }
+                    None => validate(),
+                }
+            }
+            Err(e) => unreadable(&e, options.profile),
+        };
+        FileReport {
+            path: path.clone(),
+            findings,
+        }
+    })
+}
+
+/// Validate the spec at `path` as `kanoniv validate` does: composed over
+/// any base it extends, under the policy governing it. A file that cannot
+/// be read or composed has a single `UNREADABLE_SPEC` error. References
+/// are interpolated from the environment alone.
+pub fn validate_file(path: &Path, profile: Profile) -> Tiers {
+    match read_spec(path, None, &Variables::default()) {
+        Ok((content, policy)) => crate::validate_tiers_with(&content, profile, &policy),
+        Err(e) => unreadable(&e, profile),
+    }
+}
+
+/// The composed spec at `path` (`entity` of a workspace, or all of them),
+/// and the policy governing it.
+pub(crate) fn read_spec(
+    path: &Path,
+    entity: Option<&str>,
+    variables: &Variables,
+) -> Result<(String, Policy)> {
+    let content = compose::read_workspace(path, entity, variables)?;
+    let policy = Policy::discover(path)?;
+    Ok((content, policy))
+}
+
+/// The key for results of `kind` computed from the composed spec
+/// `content` under `profile` and `policy`.
+pub(crate) fn cache_key(kind: &str, content: &str, profile: Profile, policy: &Policy) -> CacheKey {
+    CacheKey::new(kind)
+        .with(format!("{:?}", profile))
+        .with(serde_json::to_string(policy).unwrap_or_default())
+        .spec(content)
+}
+
+/// The findings for a spec that could not be read.
+pub(crate) fn unreadable(error: &anyhow::Error, profile: Profile) -> Tiers {
+    Tiers::split(
+        vec![Diagnostic::error(
+            codes::UNREADABLE_SPEC,
+            "",
+            format!("{:#}", error),
+        )],
+        profile,
+    )
+}
+
+/// `f` of each of `items`, in order, run on `jobs` worker threads (all
+/// cores when `None`).
+#[cfg(feature = "parallel")]
+pub(crate) fn map<T, R, F>(items: &[T], jobs: Option<usize>, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    use rayon::prelude::*;
+
+    match jobs {
+        Some(0 | 1) => items.iter().map(f).collect(),
+        Some(jobs) => match rayon::ThreadPoolBuilder::new().num_threads(jobs).build() {
+            Ok(pool) => pool.install(|| items.par_iter().map(f).collect()),
+            // No threads to be had: do the work here.
+            Err(_) => items.iter().map(f).collect(),
+        },
+        None => items.par_iter().map(f).collect(),
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn map<T, R, F>(items: &[T], _jobs: Option<usize>, f: F) -> Vec<R>
+where
+    F: Fn(&T) -> R,
+{
+    items.iter().map(f).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+    use crate::Profile;
+
+    #[test]
+    fn test_validate_many_reports_each_file_in_order_across_threads() {
+        use crate::{check_repo, check_repo_with, validate_many, validate_many_with, BatchOptions};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = Vec::new();
+        for i in 0..40 {
+            let path = dir.path().join(format!("spec_{:02}.yaml", i));
+            let text = MINIMAL.replace("name: customer", &format!("name: entity_{}", i));
+            // Every fifth spec is broken.
+            let text = match i % 5 {
+                0 => text.replace("field: email", "field: unknown_field"),
+                _ => text,
+            };
+            std::fs::write(&path, text).unwrap();
+            paths.push(path);
+        }
+        paths.push(dir.path().join("missing.yaml"));
+
+        let reports = validate_many(&paths);
+        assert_eq!(
+            reports.iter().map(|r| &r.path).collect::<Vec<_>>(),
+            paths.iter().collect::<Vec<_>>()
+        );
+        let invalid: Vec<usize> = reports
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| !r.is_valid())
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(invalid, [0, 5, 10, 15, 20, 25, 30, 35, 40]);
+        assert_eq!(reports[40].findings.errors[0].code, "KNV0903");
+        assert_eq!(
+            reports[3].findings,
+            crate::validate_tiers(
+                &std::fs::read_to_string(&paths[3]).unwrap(),
+                Profile::Default
+            )
+        );
+
+        // However many workers, the same reports in the same order.
+        let serial = validate_many_with(
+            &paths,
+            &BatchOptions {
+                profile: Profile::Strict,
+                jobs: Some(1),
+                ..Default::default()
+            },
+        );
+        let parallel = validate_many_with(
+            &paths,
+            &BatchOptions {
+                profile: Profile::Strict,
+                jobs: Some(4),
+                ..Default::default()
+            },
+        );
+        assert_eq!(serial, parallel);
+        assert!(
+            serial.iter().all(|r| !r.is_valid()),
+            "strict fails on the minimal spec's warnings"
+        );
+
+        let one = check_repo_with(
+            dir.path(),
+            &BatchOptions {
+                jobs: Some(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let all = check_repo(dir.path(), Profile::Default).unwrap();
+        assert_eq!(
+            serde_json::to_value(&one).unwrap(),
+            serde_json::to_value(&all).unwrap()
+        );
+        assert_eq!(all.files.len(), 40);
+    }
+}
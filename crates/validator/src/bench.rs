@@ -0,0 +1,255 @@
+//! Benchmark datasets for `kanoniv bench-suite`.
+//!
+//! A dataset is a spec, records by source and each record's true cluster.
+//! Two are built into the binary:
+//!
+//! - `restaurants`: two restaurant guides listing many of the same places,
+//!   with names, addresses and phone numbers written differently, in the
+//!   style of the Fodor's/Zagat benchmark;
+//! - `census`: census-style people in an enumeration and a survey with no
+//!   shared identifier, made up by `generate_data` from the bundled spec,
+//!   so their size and seed can be chosen.
+//!
+//! Other datasets are directories holding `spec.yaml`, the records (as
+//! `records.json`, or stacked with a `source_name` column in
+//! `records.csv`, `.parquet` or `.arrow`) and `truth.csv`, so a standard
+//! benchmark converted to that layout runs the same way. `run_dataset`
+//! scores a spec on a dataset with `evaluate`, and `regressions` compares
+//! the results with those of an earlier run.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::cancel::CancellationToken;
+use crate::commands::compile::compile_to_ir;
+use crate::embedding::Embedder;
+use crate::evaluation::{evaluate_with, records_by_source};
+use crate::ir::Ir;
+use crate::parser;
+use crate::sample::Sample;
+use crate::synthetic::{generate_data, GenerateOptions};
+
+/// Record files a dataset directory may hold, in the order they are looked
+/// for.
+const RECORD_FILES: &[&str] = &[
+    "records.json",
+    "records.csv",
+    "records.parquet",
+    "records.arrow",
+];
+
+/// Smallest fall in a metric counted as a regression.
+const EPSILON: f64 = 1e-9;
+
+/// A dataset built into the binary.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BuiltIn {
+    pub name: &'static str,
+    pub description: &'static str,
+    #[serde(skip)]
+    spec: &'static str,
+    /// Records and truth, or `None` for records made up from the spec.
+    #[serde(skip)]
+    data: Option<(&'static str, &'static str)>,
+}
+
+pub const DATASETS: &[BuiltIn] = &[
+    BuiltIn {
+        name: "restaurants",
+        description: "Two restaurant guides listing the same places differently (36 records)",
+        spec: include_str!("../benchmarks/restaurants/spec.yaml"),
+        data: Some((
+            include_str!("../benchmarks/restaurants/records.json"),
+            include_str!("../benchmarks/restaurants/truth.csv"),
+        )),
+    },
+    BuiltIn {
+        name: "census",
+        description: "Census-style people in two surveys with no shared identifier (generated)",
+        spec: include_str!("../benchmarks/census/spec.yaml"),
+        data: None,
+    },
+];
+
+/// A spec, the records to run it on and their true clusters.
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    pub name: String,
+    /// The dataset's own spec, as YAML; `None` for a directory without one.
+    pub spec: Option<String>,
+    /// Records by source, as `execute_plan` reads them.
+    pub records: Value,
+    /// `record_key` and `cluster_id` of each record.
+    pub truth: Sample,
+}
+
+/// What a spec scored on a dataset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub dataset: String,
+    pub records: usize,
+    pub true_entities: usize,
+    pub predicted_entities: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    /// Share of true pairs blocking compared.
+    pub candidate_recall: f64,
+    pub adjusted_rand_index: f64,
+    pub seconds: f64,
+}
+
+/// A metric that fell further below its baseline than allowed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Regression {
+    pub dataset: String,
+    pub metric: String,
+    pub baseline: f64,
+    pub actual: f64,
+}
+
+/// The built-in dataset called `name`, generating `census` with `options`.
+pub fn built_in(name: &str, options: &GenerateOptions) -> Result<Dataset> {
+    let Some(built_in) = DATASETS.iter().find(|d| d.name == name) else {
+        let names: Vec<&str> = DATASETS.iter().map(|d| d.name).collect();
+        bail!(
+            "Unknown dataset '{}'. Expected one of: {}, or a dataset directory",
+            name,
+            names.join(", ")
+        );
+    };
+    let (records, truth) = match built_in.data {
+        Some((records, truth)) => (serde_json::from_str(records)?, Sample::from_csv(truth)?),
+        None => {
+            let data = generate_data(&compile(built_in.spec)?, options)?;
+            (data.records_json(), Sample::from_csv(&data.labels_csv()?)?)
+        }
+    };
+    Ok(Dataset {
+        name: built_in.name.to_string(),
+        spec: Some(built_in.spec.to_string()),
+        records,
+        truth,
+    })
+}
+
+/// The dataset in directory `dir`, named after it.
+pub fn load_dataset(dir: &Path) -> Result<Dataset> {
+    if !dir.is_dir() {
+        bail!("Dataset directory not found: {}", dir.display());
+    }
+    let spec_path = dir.join("spec.yaml");
+    let spec = match spec_path.is_file() {
+        true => Some(
+            fs::read_to_string(&spec_path)
+                .with_context(|| format!("Failed to read file: {}", spec_path.display()))?,
+        ),
+        false => None,
+    };
+    let Some(path) = RECORD_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+    else {
+        bail!(
+            "No records in {}: expected one of {}",
+            dir.display(),
+            RECORD_FILES.join(", ")
+        );
+    };
+    let records = match path.extension().is_some_and(|ext| ext == "json") {
+        true => {
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read records: {}", path.display()))?;
+            serde_json::from_str(&text)
+                .with_context(|| format!("Invalid JSON in {}", path.display()))?
+        }
+        false => records_by_source(&Sample::load(&path)?)?,
+    };
+    let name = dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| dir.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| dir.display().to_string());
+    Ok(Dataset {
+        name,
+        spec,
+        records,
+        truth: Sample::load(&dir.join("truth.csv"))?,
+    })
+}
+
+/// Score `spec` (YAML), or the dataset's own spec if `None`, on `dataset`
+/// as of the date `as_of`.
+pub fn run_dataset(
+    dataset: &Dataset,
+    spec: Option<&str>,
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+    as_of: &str,
+) -> Result<BenchResult> {
+    let Some(spec) = spec.or(dataset.spec.as_deref()) else {
+        bail!(
+            "Dataset '{}' has no spec.yaml; give a spec to run on it",
+            dataset.name
+        );
+    };
+    let ir = compile(spec)
+        .with_context(|| format!("Failed to compile the spec for '{}'", dataset.name))?;
+    let started = Instant::now();
+    let report = evaluate_with(&ir, &dataset.records, &dataset.truth, embedder, token, as_of)
+        .with_context(|| format!("Failed to run dataset '{}'", dataset.name))?;
+    Ok(BenchResult {
+        dataset: dataset.name.clone(),
+        records: report.records,
+        true_entities: report.true_entities,
+        predicted_entities: report.predicted_entities,
+        precision: report.pairwise.precision,
+        recall: report.pairwise.recall,
+        f1: report.pairwise.f1,
+        candidate_recall: report.pairwise.candidate_recall,
+        adjusted_rand_index: report.clusters.adjusted_rand_index,
+        seconds: started.elapsed().as_secs_f64(),
+    })
+}
+
+/// Precision, recall and F1 of `results` more than `tolerance` below those
+/// of the same dataset in `baseline`. Datasets missing from either are
+/// not compared, and differences below `EPSILON` (a baseline read back
+/// from JSON can be off in the last bit) are not regressions.
+pub fn regressions(
+    results: &[BenchResult],
+    baseline: &[BenchResult],
+    tolerance: f64,
+) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    for result in results {
+        let Some(base) = baseline.iter().find(|b| b.dataset == result.dataset) else {
+            continue;
+        };
+        for (metric, base, actual) in [
+            ("precision", base.precision, result.precision),
+            ("recall", base.recall, result.recall),
+            ("f1", base.f1, result.f1),
+        ] {
+            if actual < base - tolerance - EPSILON {
+                regressions.push(Regression {
+                    dataset: result.dataset.clone(),
+                    metric: metric.to_string(),
+                    baseline: base,
+                    actual,
+                });
+            }
+        }
+    }
+    regressions
+}
+
+fn compile(spec: &str) -> Result<Ir> {
+    let spec = parser::parse_yaml(spec).with_context(|| "Failed to parse YAML")?;
+    Ir::from_value(&compile_to_ir(&spec)?)
+}
@@ -0,0 +1,369 @@
+//! Blocking strategies that do not need records to share a key value.
+//!
+//! `sorted_neighborhood` sorts records on a key and pairs each with the
+//! records in a sliding window after it, so near-equal keys (`Jonson`,
+//! `Johnson`) still meet; the number of pairs grows linearly with the
+//! records. `canopy` groups records into overlapping canopies with a cheap
+//! similarity: a record within `loose` of a canopy's center joins it, and
+//! one within `tight` neither starts nor joins another canopy.
+//!
+//! ```yaml
+//! blocking:
+//!   strategy: sorted_neighborhood
+//!   sorted_neighborhood:
+//!     sort_key: last_name
+//!     transform: soundex    # optional, as for blocking keys
+//!     window: 10
+//! ```
+//!
+//! ```yaml
+//! blocking:
+//!   strategy: canopy
+//!   canopy:
+//!     field: company_name
+//!     algorithm: jaccard    # any similarity algorithm; jaccard if omitted
+//!     loose: 0.4
+//!     tight: 0.8
+//! ```
+//!
+//! Blocking keys, if any, are used alongside: a pair is a candidate when
+//! the strategy or a key puts it together.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::similarity::AlgorithmRegistry;
+
+/// Blocking strategies the planner and engine know; other names block on
+/// the keys alone.
+pub const STRATEGIES: &[&str] = &["composite", "lsh", "sorted_neighborhood", "canopy"];
+
+/// Sorted-neighbourhood window sizes: at least a pair, and at most so
+/// many that blocking still removes most comparisons.
+pub const DEFAULT_WINDOW: usize = 10;
+pub const MIN_WINDOW: usize = 2;
+pub const MAX_WINDOW: usize = 1000;
+
+/// Canopies are built with this algorithm when none is named.
+pub const DEFAULT_CANOPY_ALGORITHM: &str = "jaccard";
+
+/// The `blocking.sorted_neighborhood` parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SortedNeighborhood {
+    pub sort_key: String,
+    /// Blocking transform applied to the sort key before sorting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+    /// Records per window: each record is paired with the `window - 1`
+    /// records sorted after it.
+    pub window: usize,
+}
+
+/// The `blocking.canopy` parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Canopy {
+    pub field: String,
+    pub algorithm: String,
+    /// Similarity to a center at which a record joins its canopy.
+    pub loose: f64,
+    /// Similarity to a center at which a record stops being a center
+    /// candidate; at least `loose`.
+    pub tight: f64,
+}
+
+/// The strategy's section of `blocking`, if the strategy is `strategy`.
+fn section<'a>(spec: &'a Value, strategy: &str) -> Option<&'a Value> {
+    let blocking = spec.get("blocking")?;
+    if blocking.get("strategy").and_then(Value::as_str) != Some(strategy) {
+        return None;
+    }
+    Some(blocking.get(strategy).unwrap_or(&Value::Null))
+}
+
+impl SortedNeighborhood {
+    /// Read `blocking.sorted_neighborhood` from a parsed spec; `None`
+    /// unless that is the blocking strategy.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(section) = section(spec, "sorted_neighborhood") else {
+            return Ok(None);
+        };
+        let Some(sort_key) = section.get("sort_key").and_then(Value::as_str) else {
+            bail!("blocking.sorted_neighborhood.sort_key is required");
+        };
+        let window = match section.get("window") {
+            None => DEFAULT_WINDOW,
+            Some(window) => match window.as_u64() {
+                Some(n) if (MIN_WINDOW as u64..=MAX_WINDOW as u64).contains(&n) => n as usize,
+                _ => bail!(
+                    "blocking.sorted_neighborhood.window must be a whole number from {} to {}, got {}",
+                    MIN_WINDOW,
+                    MAX_WINDOW,
+                    window
+                ),
+            },
+        };
+        Ok(Some(SortedNeighborhood {
+            sort_key: sort_key.to_string(),
+            transform: section
+                .get("transform")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            window,
+        }))
+    }
+
+    /// The windows over records with the given sort keys (`None` where
+    /// missing, which leaves the record out): sorted positions
+    /// `i..i + window`, as record indexes.
+    pub fn windows(&self, keys: &[Option<String>]) -> Vec<Vec<usize>> {
+        let mut sorted: Vec<(&str, usize)> = keys
+            .iter()
+            .enumerate()
+            .filter_map(|(record, key)| Some((key.as_deref()?, record)))
+            .collect();
+        sorted.sort();
+        let records: Vec<usize> = sorted.into_iter().map(|(_, record)| record).collect();
+        if records.len() <= self.window {
+            return if records.len() > 1 {
+                vec![records]
+            } else {
+                Vec::new()
+            };
+        }
+        records
+            .windows(self.window)
+            .map(<[usize]>::to_vec)
+            .collect()
+    }
+}
+
+impl Canopy {
+    /// Read `blocking.canopy` from a parsed spec; `None` unless that is the
+    /// blocking strategy.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(section) = section(spec, "canopy") else {
+            return Ok(None);
+        };
+        let Some(field) = section.get("field").and_then(Value::as_str) else {
+            bail!("blocking.canopy.field is required");
+        };
+        let threshold = |name: &str| match section.get(name).and_then(Value::as_f64) {
+            Some(t) if (0.0..=1.0).contains(&t) => Ok(t),
+            _ => bail!(
+                "blocking.canopy.{} must be a similarity between 0 and 1",
+                name
+            ),
+        };
+        let (loose, tight) = (threshold("loose")?, threshold("tight")?);
+        if loose > tight {
+            bail!(
+                "blocking.canopy.loose ({}) must not exceed blocking.canopy.tight ({})",
+                loose,
+                tight
+            );
+        }
+        Ok(Some(Canopy {
+            field: field.to_string(),
+            algorithm: section
+                .get("algorithm")
+                .and_then(Value::as_str)
+                .unwrap_or(DEFAULT_CANOPY_ALGORITHM)
+                .to_string(),
+            loose,
+            tight,
+        }))
+    }
+
+    /// Canopies over records with the given values (`None` where missing,
+    /// which leaves the record out), as record indexes. Centers are taken
+    /// in record order, and each canopy draws on the records no earlier
+    /// center was tightly similar to; values are compared lowercased.
+    pub fn canopies(
+        &self,
+        values: &[Option<String>],
+        algorithms: &AlgorithmRegistry,
+    ) -> Vec<Vec<usize>> {
+        let values: Vec<(usize, String)> = values
+            .iter()
+            .enumerate()
+            .filter_map(|(record, value)| Some((record, value.as_deref()?.to_lowercase())))
+            .collect();
+        let similarity = |a: &str, b: &str| {
+            algorithms
+                .score(&self.algorithm, a, b)
+                .or_else(|| algorithms.score(DEFAULT_CANOPY_ALGORITHM, a, b))
+                .unwrap_or_default()
+        };
+
+        let mut centers = vec![true; values.len()];
+        let mut canopies = Vec::new();
+        for center in 0..values.len() {
+            if !centers[center] {
+                continue;
+            }
+            let mut canopy = Vec::new();
+            for (other, (record, value)) in values.iter().enumerate() {
+                if !centers[other] {
+                    continue;
+                }
+                let score = if other == center {
+                    1.0
+                } else {
+                    similarity(&values[center].1, value)
+                };
+                if score >= self.loose {
+                    canopy.push(*record);
+                }
+                if score >= self.tight {
+                    centers[other] = false;
+                }
+            }
+            if canopy.len() > 1 {
+                canopies.push(canopy);
+            }
+        }
+        canopies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::compiled_ir;
+    use crate::{diagnose_yaml, Severity};
+
+    #[test]
+    fn test_sorted_neighborhood_and_canopy_blocking() {
+        use crate::{
+            generate_plan, generate_plan_with, generate_sql, AlgorithmRegistry, Canopy, Dialect,
+            PlanOptions, Sample,
+        };
+
+        let spec = r#"
+api_version: kanoniv/v2
+identity_version: person_v1
+entity:
+  name: person
+sources:
+  - name: crm
+    system: salesforce
+    table: contacts
+    id: contact_id
+    attributes:
+      last_name: surname
+      company_name: company
+rules:
+  - name: name_fuzzy
+    type: fuzzy
+    field: last_name
+    algorithm: jaro_winkler
+    threshold: 0.85
+    weight: 1.0
+blocking:
+  strategy: sorted_neighborhood
+  sorted_neighborhood:
+    sort_key: last_name
+    transform: soundex
+    window: 2
+decision:
+  thresholds:
+    match: 0.9
+"#;
+        assert!(crate::validate_yaml(spec).unwrap().is_empty());
+        let errors = |yaml: &str| -> Vec<(String, String)> {
+            diagnose_yaml(yaml)
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .map(|d| (d.code.to_string(), d.path.unwrap()))
+                .collect()
+        };
+        let pair = |code: &str, path: &str| (code.to_string(), path.to_string());
+        assert_eq!(
+            errors(&spec.replace("sort_key: last_name", "transform_only: true")),
+            [pair("KNV0001", "blocking.sorted_neighborhood.sort_key")]
+        );
+        assert_eq!(
+            errors(&spec.replace("window: 2", "window: 1")),
+            [pair("KNV0004", "blocking.sorted_neighborhood.window")]
+        );
+        assert_eq!(
+            errors(&spec.replace("sort_key: last_name", "sort_key: surname_typo")),
+            [pair("KNV0101", "blocking.sorted_neighborhood.sort_key")]
+        );
+        assert_eq!(
+            errors(&spec.replace("transform: soundex", "transform: sondex")),
+            [pair("KNV0135", "blocking.sorted_neighborhood.transform")]
+        );
+
+        let canopy_spec = spec.replace(
+        "strategy: sorted_neighborhood\n  sorted_neighborhood:\n    sort_key: last_name\n    transform: soundex\n    window: 2",
+        "strategy: canopy\n  canopy:\n    field: company_name\n    loose: 0.3\n    tight: 0.6",
+    );
+        assert!(crate::validate_yaml(&canopy_spec).unwrap().is_empty());
+        assert_eq!(
+            errors(&canopy_spec.replace("loose: 0.3", "loose: 0.7")),
+            [pair("KNV0104", "blocking.canopy")]
+        );
+        assert_eq!(
+            errors(&canopy_spec.replace("tight: 0.6", "tight: 1.5")),
+            [pair("KNV0004", "blocking.canopy.tight")]
+        );
+        assert_eq!(
+            errors(&canopy_spec.replace("loose: 0.3", "algorithm: jacard\n    loose: 0.3")),
+            [pair("KNV0111", "blocking.canopy.algorithm")]
+        );
+
+        let plan = generate_plan(spec).unwrap();
+        assert_eq!(plan.blocking_analysis.estimated_reduction, "high");
+        assert!(plan.execution_stages[1]
+            .description
+            .contains("sort on last_name (soundex), window 2"));
+        assert!(!plan.risk_flags.iter().any(|f| f.code == "NO_BLOCKING"));
+        let canopy_plan = generate_plan(&canopy_spec).unwrap();
+        assert_eq!(canopy_plan.blocking_analysis.estimated_reduction, "medium");
+        assert!(canopy_plan.execution_stages[1]
+            .description
+            .contains("company_name via jaccard, loose 0.3, tight 0.6"));
+
+        // Soundex sorts Jones, Johnson, Smith, Smyth; window 2 pairs neighbours.
+        let csv = "surname,company\nSmith,Acme Corp\nSmyth,Acme Corp Inc\nJones,Globex Corp\nJohnson,Initech\n,Acme Holdings\n";
+        let options = PlanOptions {
+            sample: Some(Sample::from_csv(csv).unwrap()),
+            ..PlanOptions::default()
+        };
+        let sampled = generate_plan_with(spec, &options).unwrap();
+        let sample = sampled.blocking_analysis.sample.as_ref().unwrap();
+        assert_eq!((sample.total_pairs, sample.candidate_pairs), (10, 3));
+        let windows = sample.strategy.as_ref().unwrap();
+        assert_eq!((windows.cardinality, windows.missing), (3, 1));
+
+        // "acme corp" takes in every record sharing a word with it; the rest
+        // start no canopy of more than one record.
+        let values: Vec<Option<String>> = [
+            "Acme Corp",
+            "Acme Corp Inc",
+            "Globex Corp",
+            "Initech",
+            "Acme Holdings",
+        ]
+        .iter()
+        .map(|v| Some(v.to_string()))
+        .collect();
+        let canopy = Canopy::from_spec(&crate::parse_yaml(&canopy_spec).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            canopy.canopies(&values, &AlgorithmRegistry::builtin()),
+            [vec![0, 1, 2, 4]]
+        );
+        let sampled = generate_plan_with(&canopy_spec, &options).unwrap();
+        let sample = sampled.blocking_analysis.sample.as_ref().unwrap();
+        assert_eq!(sample.candidate_pairs, 6);
+
+        let ir = compiled_ir(spec);
+        let sql = generate_sql(&ir, Dialect::Postgres).unwrap();
+        assert!(sql.contains("ROW_NUMBER() OVER (ORDER BY SOUNDEX(e.last_name), e.record_key)"));
+        assert!(sql.contains("b.position < a.position + 2"));
+        assert!(!sql.contains("full pairwise"));
+    }
+}
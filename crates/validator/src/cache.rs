@@ -0,0 +1,257 @@
+//! An on-disk cache of validation and plan results.
+//!
+//! CI validates the same specs run after run. Each result is stored under
+//! a key digesting everything it depends on: the kanoniv version and
+//! build, what was computed and how (the profile, the rule pack's digest,
+//! the policy, plan options), and the spec. An unchanged spec is then answered from disk
+//! without being parsed.
+//!
+//! The spec enters the key as its composed text, not as its canonical
+//! hash. The canonical hash leaves out comments (where waivers may be) and
+//! layout, and findings depend on both: layout through the lines they
+//! point at. A spec composed
+//! over a base changes with the base. A spec with a waiver that expires is
+//! keyed by the day as well.
+//!
+//! Entries are JSON files named by their key, in `$KANONIV_CACHE_DIR`,
+//! or else `kanoniv` in the user's cache directory, for CI to keep between
+//! runs. A missing or unreadable entry is a miss, and a failed write is
+//! ignored: the cache never fails a run. Nothing expires entries; clearing
+//! the directory is always safe.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::clock::today;
+
+pub const CACHE_DIR_ENV: &str = "KANONIV_CACHE_DIR";
+
+/// Bumped when what entries hold changes shape.
+const FORMAT: &str = "kanoniv-cache/1";
+
+/// `sha256:<hex>` of `bytes`.
+pub fn digest(bytes: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(bytes))
+}
+
+/// What a cached result depends on, digested into its key.
+pub struct CacheKey(Sha256);
+
+impl CacheKey {
+    /// A key for results of `kind` (e.g. `validate`, `plan`) computed by
+    /// this build of kanoniv.
+    pub fn new(kind: &str) -> Self {
+        CacheKey(Sha256::new())
+            .with(FORMAT)
+            .with(env!("CARGO_PKG_VERSION"))
+            // A digest of the sources this kanoniv was built from, so a
+            // rebuild under the same version is not answered from entries
+            // an older build wrote (see `build.rs`).
+            .with(env!("KANONIV_BUILD_HASH"))
+            .with(kind)
+    }
+
+    /// The key, depending on `part` too.
+    pub fn with(mut self, part: impl AsRef<[u8]>) -> Self {
+        let part = part.as_ref();
+        self.0.update((part.len() as u64).to_le_bytes());
+        self.0.update(part);
+        self
+    }
+
+    /// The key, depending on the composed text of a spec, and on the day
+    /// when a waiver in it can expire.
+    pub fn spec(self, text: &str) -> Self {
+        let key = self.with(text);
+        match text.contains("expires") {
+            true => key.with(today()),
+            false => key,
+        }
+    }
+
+    pub fn finish(self) -> String {
+        format!("{:x}", self.0.finalize())
+    }
+}
+
+/// Lookups since a cache was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// Results stored in a directory, by key.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl Cache {
+    pub fn new(dir: &Path) -> Self {
+        Cache {
+            dir: dir.to_path_buf(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// The cache in `$KANONIV_CACHE_DIR`, or else in `kanoniv` under
+    /// `$XDG_CACHE_HOME`, `~/.cache` or (on Windows) `%LOCALAPPDATA%`.
+    /// `None` when there is no such directory to use.
+    pub fn discover() -> Option<Self> {
+        let var = |name: &str| {
+            std::env::var_os(name)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        let dir = var(CACHE_DIR_ENV).or_else(|| {
+            let user = var("XDG_CACHE_HOME")
+                .or_else(|| var("HOME").map(|home| home.join(".cache")))
+                .or_else(|| var("LOCALAPPDATA"))?;
+            Some(user.join("kanoniv"))
+        })?;
+        Some(Self::new(&dir))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The result stored under `key`, if any.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let found = fs::read(self.entry(key))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        let counter = match found {
+            Some(_) => &self.hits,
+            None => &self.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    /// Store `value` under `key`. The entry is written beside its final
+    /// name and renamed into place, so a concurrent reader sees all of it
+    /// or none.
+    pub fn put<T: Serialize>(&self, key: &str, value: &T) {
+        let Ok(json) = serde_json::to_vec(value) else {
+            return;
+        };
+        let entry = self.entry(key);
+        static WRITES: AtomicUsize = AtomicUsize::new(0);
+        let partial = entry.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|()| fs::write(&partial, json))
+            .and_then(|()| fs::rename(&partial, &entry));
+        if written.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+    }
+
+    /// The result stored under `key`, or else `compute`'s, stored.
+    pub fn get_or_insert<T, F>(&self, key: &str, compute: F) -> T
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> T,
+    {
+        if let Some(cached) = self.get(key) {
+            return cached;
+        }
+        let value = compute();
+        self.put(key, &value);
+        value
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn entry(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_cache_answers_unchanged_specs_from_disk() {
+        use crate::{
+            check_repo_with, validate_many, validate_many_with, BatchOptions, Cache, CacheStats,
+            RulePack,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = ["customer.yaml", "household.yaml"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        std::fs::write(&paths[0], MINIMAL).unwrap();
+        std::fs::write(
+            &paths[1],
+            MINIMAL
+                .replace("name: customer", "name: household")
+                .replace("retail_v1.0", "household_v1"),
+        )
+        .unwrap();
+        let options = BatchOptions {
+            cache: Some(Cache::new(cache_dir.path())),
+            ..Default::default()
+        };
+        let stats = || options.cache.as_ref().unwrap().stats();
+
+        let first = validate_many_with(&paths, &options);
+        assert_eq!(stats(), CacheStats { hits: 0, misses: 2 });
+        assert_eq!(validate_many_with(&paths, &options), first);
+        assert_eq!(stats(), CacheStats { hits: 2, misses: 2 });
+
+        // A comment moves every finding a line down: the spec is validated again.
+        std::fs::write(
+            &paths[1],
+            format!(
+                "# households\n{}",
+                std::fs::read_to_string(&paths[1]).unwrap()
+            ),
+        )
+        .unwrap();
+        let edited = validate_many_with(&paths, &options);
+        assert_eq!(stats(), CacheStats { hits: 3, misses: 3 });
+        assert_eq!(edited, validate_many(&paths));
+        let line =
+            |reports: &[crate::FileReport]| reports[1].findings.warnings[0].span.unwrap().line;
+        assert_eq!(line(&edited), line(&first) + 1);
+
+        let check = || check_repo_with(dir.path(), &options).unwrap();
+        let (uncached, cached) = (check(), check());
+        assert_eq!(uncached.cache.map(|c| c.misses), Some(5));
+        assert_eq!(cached.cache.map(|c| c.hits), Some(5));
+        assert_eq!(
+            serde_json::to_value(&uncached.files).unwrap(),
+            serde_json::to_value(&cached.files).unwrap()
+        );
+
+        // A rule pack's digest is its version.
+        let pack = |code: &str| {
+            RulePack::from_yaml(&format!("checks:\n  - code: {}\n    severity: info\n    message: m\n    require: {{ exists: entity }}\n", code)).unwrap()
+        };
+        assert_ne!(pack("CORP_ONE").digest(), pack("CORP_TWO").digest());
+        assert_eq!(pack("CORP_ONE").digest(), pack("CORP_ONE").digest());
+        assert_eq!(RulePack::default().digest(), "");
+    }
+}
@@ -0,0 +1,516 @@
+//! Match rule calibration against human-labeled pairs.
+//!
+//! `calibrate` scores each match rule (see `MatchStrategySummary`) on pairs
+//! of records labeled match or non-match, and recommends a threshold and a
+//! weight per rule and the decision thresholds for the combined score.
+//! Values are normalized as the spec's `normalization` section says (see
+//! `normalize`); scores then follow the compiled SQL: values are
+//! lowercased, exact rules compare for equality, fuzzy rules use the rule's
+//! algorithm (see `similarity`; Jaro-Winkler if none), semantic rules the
+//! cosine similarity of the values' embeddings (see `embedding`) and the
+//! combined score is the weight-averaged rule score, with missing values
+//! scoring 0. A rule with a `condition` scores only the pairs it holds for,
+//! as if values were missing elsewhere.
+//!
+//! The labels CSV has a `label` column (`match`/`non_match`, `1`/`0`,
+//! `true`/`false` or `yes`/`no`) and, per rule field, a `left_<field>` and
+//! a `right_<field>` column.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::commands::compile::compile_to_ir;
+use crate::commands::plan::{extract_match_strategies, MatchStrategySummary};
+use crate::embedding::{self, Embedder, EmbeddingModel, HttpEmbedder};
+use crate::encoding::{self as encodings, Encoder};
+use crate::expression::{Expression, Value};
+use crate::ir::Ir;
+use crate::normalize::Normalization;
+use crate::parser;
+use crate::sample::Sample;
+use crate::similarity::{AlgorithmRegistry, DEFAULT_ALGORITHM};
+
+/// Candidate thresholds, 0.50 to 1.00 in steps of 0.05 for rules and 0.05
+/// to 1.00 for decisions.
+const RULE_STEPS: std::ops::RangeInclusive<u32> = 10..=20;
+const DECISION_STEPS: std::ops::RangeInclusive<u32> = 1..=20;
+
+/// The review threshold recommended is the highest below `match` that
+/// keeps this share of true matches.
+const REVIEW_RECALL: f64 = 0.95;
+
+#[derive(Debug, Serialize)]
+pub struct Calibration {
+    pub pairs: usize,
+    pub matches: usize,
+    pub rules: Vec<RuleCalibration>,
+    pub decision: DecisionCalibration,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuleCalibration {
+    pub rule_name: String,
+    pub match_type: String,
+    pub field: String,
+    /// Labeled pairs with a value on both sides; 0 if the labels lack the
+    /// field's columns.
+    pub pairs: usize,
+    /// At the rule's current threshold (or equality, for exact rules).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<CurvePoint>,
+    /// Precision and recall by threshold, for fuzzy rules.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub curve: Vec<CurvePoint>,
+    pub threshold: Option<f64>,
+    /// The threshold with the best F1 on the curve.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommended_threshold: Option<f64>,
+    pub weight: f64,
+    /// Agreement weight log2(m/u), scaled so the strongest rule gets 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommended_weight: Option<f64>,
+    /// Share of true matches on which the rule agrees.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub m_probability: Option<f64>,
+    /// Share of non-matches on which the rule agrees.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub u_probability: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CurvePoint {
+    pub threshold: f64,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+/// The combined score under the spec's current rules and weights.
+#[derive(Debug, Serialize)]
+pub struct DecisionCalibration {
+    pub match_threshold: Option<f64>,
+    pub review_threshold: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<CurvePoint>,
+    pub curve: Vec<CurvePoint>,
+    pub recommended_match: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommended_review: Option<f64>,
+}
+
+/// Score `yaml`'s match rules on the labeled pairs in `labels`, embedding
+/// values for semantic rules through the rules' endpoints.
+pub fn calibrate(yaml: &str, labels: &Sample) -> Result<Calibration> {
+    calibrate_with(yaml, labels, &HttpEmbedder::new()?)
+}
+
+/// `calibrate`, embedding values for semantic rules with `embedder`.
+pub fn calibrate_with(yaml: &str, labels: &Sample, embedder: &dyn Embedder) -> Result<Calibration> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let strategies = extract_match_strategies(&Ir::from_value(&compile_to_ir(&spec)?)?);
+    if strategies.is_empty() {
+        bail!("The spec has no match rules to calibrate");
+    }
+    let truth = read_labels(labels)?;
+    let matches = truth.iter().filter(|&&m| m).count();
+    if matches == 0 || matches == truth.len() {
+        bail!("The labels need both matches and non-matches");
+    }
+
+    // Each rule's similarity per pair; `None` where a value is missing.
+    let algorithms = AlgorithmRegistry::builtin();
+    let normalization = Normalization::from_spec(&spec)?;
+    let encoder = encodings::encoder(&spec)?;
+    let similarities: Vec<Vec<Option<f64>>> = strategies
+        .iter()
+        .map(|rule| {
+            similarities(
+                labels,
+                rule,
+                &algorithms,
+                embedder,
+                &normalization,
+                encoder.as_ref(),
+            )
+        })
+        .collect::<Result<_>>()?;
+
+    // Fuzzy rules' curves, and the cut each rule is judged to agree at:
+    // equality for exact rules, else the best threshold on the curve.
+    let curves: Vec<Vec<CurvePoint>> = strategies
+        .iter()
+        .zip(&similarities)
+        .map(|(rule, scores)| {
+            if rule.match_type == "exact" {
+                return Vec::new();
+            }
+            RULE_STEPS
+                .map(|step| point(&truth, scores, step as f64 / 20.0))
+                .collect()
+        })
+        .collect();
+    let agreement: Vec<Option<(f64, f64)>> = strategies
+        .iter()
+        .zip(&similarities)
+        .zip(&curves)
+        .map(|((rule, scores), curve)| {
+            let cut = best(curve)
+                .map(|p| p.threshold)
+                .or(rule.threshold)
+                .unwrap_or(1.0);
+            m_u(&truth, scores, cut)
+        })
+        .collect();
+    let strongest = agreement
+        .iter()
+        .flatten()
+        .map(|&(m, u)| (m / u).log2())
+        .fold(0.0, f64::max);
+
+    let rules = strategies
+        .iter()
+        .zip(&similarities)
+        .zip(curves)
+        .zip(&agreement)
+        .map(|(((rule, scores), curve), agreement)| {
+            let fuzzy = rule.match_type != "exact";
+            let pairs = scores.iter().flatten().count();
+            RuleCalibration {
+                rule_name: rule.rule_name.clone(),
+                match_type: rule.match_type.clone(),
+                field: rule.field.clone(),
+                pairs,
+                current: (pairs > 0 && (!fuzzy || rule.threshold.is_some()))
+                    .then(|| point(&truth, scores, rule.threshold.unwrap_or(1.0))),
+                recommended_threshold: best(&curve).map(|p| p.threshold),
+                curve,
+                threshold: rule.threshold,
+                weight: rule.weight,
+                recommended_weight: agreement.map(|(m, u)| {
+                    let weight = (m / u).log2().max(0.0);
+                    if strongest > 0.0 {
+                        (weight / strongest * 100.0).round() / 100.0
+                    } else {
+                        0.0
+                    }
+                }),
+                m_probability: agreement.map(|(m, _)| round(m)),
+                u_probability: agreement.map(|(_, u)| round(u)),
+            }
+        })
+        .collect();
+
+    Ok(Calibration {
+        pairs: truth.len(),
+        matches,
+        rules,
+        decision: decide(&spec, &strategies, &similarities, &truth),
+    })
+}
+
+fn read_labels(labels: &Sample) -> Result<Vec<bool>> {
+    let Some(column) = labels.find_column("label") else {
+        bail!("The labels have no 'label' column");
+    };
+    labels
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let label = row.get(column).map(|v| v.trim()).unwrap_or_default();
+            match label.to_ascii_lowercase().as_str() {
+                "match" | "1" | "true" | "yes" | "y" => Ok(true),
+                "non_match" | "non-match" | "no_match" | "0" | "false" | "no" | "n" => Ok(false),
+                _ => bail!("Row {}: unknown label '{}'", i + 2, label),
+            }
+        })
+        .collect()
+}
+
+fn similarities(
+    labels: &Sample,
+    rule: &MatchStrategySummary,
+    algorithms: &AlgorithmRegistry,
+    embedder: &dyn Embedder,
+    normalization: &Normalization,
+    encoder: Option<&Encoder>,
+) -> Result<Vec<Option<f64>>> {
+    let left = labels.find_column(&format!("left_{}", rule.field));
+    let right = labels.find_column(&format!("right_{}", rule.field));
+    let value = |row: &[String], column: Option<usize>| {
+        let value = normalization
+            .apply(&rule.field, row.get(column?)?)
+            .trim()
+            .to_lowercase();
+        (!value.is_empty()).then(|| encodings::encode(encoder, &rule.field, value))
+    };
+    let condition = rule_condition(rule)?;
+    let holds = |row: &[String]| {
+        condition.as_ref().is_none_or(|condition| {
+            condition_holds(condition, &|side, attribute| {
+                let column = labels.find_column(&format!("{}_{}", side, attribute))?;
+                let value = normalization.apply(attribute, row.get(column)?);
+                let value = value.trim();
+                (!value.is_empty()).then(|| value.to_string())
+            })
+        })
+    };
+    let pairs: Vec<Option<(String, String)>> = labels
+        .rows
+        .iter()
+        .map(|row| {
+            if !holds(row) {
+                return None;
+            }
+            Some((value(row, left)?, value(row, right)?))
+        })
+        .collect();
+
+    if rule.match_type == "semantic" {
+        let model = EmbeddingModel::new(
+            rule.model.clone().unwrap_or_default(),
+            rule.endpoint.clone().unwrap_or_default(),
+        );
+        let values = pairs
+            .iter()
+            .flatten()
+            .flat_map(|(a, b)| [a.as_str(), b.as_str()]);
+        let vectors = embedding::embed_all(embedder, &model, values)?;
+        return Ok(pairs
+            .iter()
+            .map(|pair| {
+                let (a, b) = pair.as_ref()?;
+                Some(embedding::cosine(&vectors[a], &vectors[b]))
+            })
+            .collect());
+    }
+
+    Ok(pairs
+        .into_iter()
+        .map(|pair| {
+            let (a, b) = pair?;
+            Some(score(rule, algorithms, &a, &b))
+        })
+        .collect())
+}
+
+/// A rule's parsed `condition`, if it has one.
+pub(crate) fn rule_condition(rule: &MatchStrategySummary) -> Result<Option<Expression>> {
+    rule.condition
+        .as_deref()
+        .map(|c| {
+            Expression::parse(c)
+                .with_context(|| format!("Invalid condition on rule '{}'", rule.rule_name))
+        })
+        .transpose()
+}
+
+/// Whether a rule's condition holds for a pair, with `value(side,
+/// attribute)` reading `left.<attribute>` and `right.<attribute>`. A
+/// condition that fails to evaluate does not hold.
+pub(crate) fn condition_holds(
+    condition: &Expression,
+    value: &dyn Fn(&str, &str) -> Option<String>,
+) -> bool {
+    let lookup = |name: &str| {
+        let (side, attribute) = name.split_once('.')?;
+        matches!(side, "left" | "right")
+            .then(|| Value::from_cell(value(side, attribute).as_deref()))
+    };
+    condition
+        .evaluate(&lookup)
+        .is_ok_and(|v| v == Value::Bool(true))
+}
+
+/// An exact or fuzzy rule's score for two normalized, lowercased values.
+pub(crate) fn score(
+    rule: &MatchStrategySummary,
+    algorithms: &AlgorithmRegistry,
+    a: &str,
+    b: &str,
+) -> f64 {
+    if rule.match_type == "exact" {
+        return if a == b { 1.0 } else { 0.0 };
+    }
+    // Backends score unknown algorithms with their default.
+    let algorithm = rule.algorithm.as_deref().unwrap_or(DEFAULT_ALGORITHM);
+    algorithms
+        .score(algorithm, a, b)
+        .or_else(|| algorithms.score(DEFAULT_ALGORITHM, a, b))
+        .unwrap_or_default()
+}
+
+/// m and u probabilities of agreeing at `threshold`, smoothed so neither
+/// is 0; `None` without pairs to count.
+fn m_u(truth: &[bool], scores: &[Option<f64>], threshold: f64) -> Option<(f64, f64)> {
+    let (mut agree, mut total) = ([0.0; 2], [0.0; 2]);
+    for (&is_match, score) in truth.iter().zip(scores) {
+        let Some(score) = score else { continue };
+        let i = is_match as usize;
+        total[i] += 1.0;
+        if *score >= threshold {
+            agree[i] += 1.0;
+        }
+    }
+    if total[0] + total[1] == 0.0 {
+        return None;
+    }
+    let smoothed = |i: usize| (agree[i] + 0.5) / (total[i] + 1.0);
+    Some((smoothed(1), smoothed(0)))
+}
+
+/// Precision and recall of predicting a match where the score reaches
+/// `threshold`; a missing score predicts no match.
+fn point(truth: &[bool], scores: &[Option<f64>], threshold: f64) -> CurvePoint {
+    let (mut tp, mut fp, mut fn_) = (0usize, 0usize, 0usize);
+    for (&is_match, score) in truth.iter().zip(scores) {
+        let predicted = score.is_some_and(|s| s >= threshold - 1e-9);
+        match (predicted, is_match) {
+            (true, true) => tp += 1,
+            (true, false) => fp += 1,
+            (false, true) => fn_ += 1,
+            (false, false) => {}
+        }
+    }
+    let ratio = |n: usize, d: usize| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+    let precision = ratio(tp, tp + fp);
+    let recall = ratio(tp, tp + fn_);
+    let f1 = if precision + recall == 0.0 {
+        0.0
+    } else {
+        2.0 * precision * recall / (precision + recall)
+    };
+    CurvePoint {
+        threshold: round(threshold),
+        precision: round(precision),
+        recall: round(recall),
+        f1: round(f1),
+    }
+}
+
+/// The point with the best F1, the highest threshold on ties.
+fn best(curve: &[CurvePoint]) -> Option<&CurvePoint> {
+    curve.iter().filter(|p| p.f1 > 0.0).max_by(|a, b| {
+        a.f1.total_cmp(&b.f1)
+            .then(a.threshold.total_cmp(&b.threshold))
+    })
+}
+
+fn decide(
+    spec: &serde_json::Value,
+    strategies: &[MatchStrategySummary],
+    similarities: &[Vec<Option<f64>>],
+    truth: &[bool],
+) -> DecisionCalibration {
+    let thresholds = spec.get("decision").and_then(|d| d.get("thresholds"));
+    let threshold = |name: &str| {
+        thresholds
+            .and_then(|t| t.get(name))
+            .and_then(|v| v.as_f64())
+    };
+    let (match_threshold, review_threshold) = (threshold("match"), threshold("review"));
+
+    let combined: Vec<Option<f64>> = combined_scores(strategies, similarities, truth.len())
+        .into_iter()
+        .map(Some)
+        .collect();
+
+    let curve: Vec<CurvePoint> = DECISION_STEPS
+        .map(|step| point(truth, &combined, step as f64 / 20.0))
+        .collect();
+    let recommended_match = best(&curve).map_or(1.0, |p| p.threshold);
+    let recommended_review = curve
+        .iter()
+        .rev()
+        .filter(|p| p.threshold < recommended_match)
+        .find(|p| p.recall >= REVIEW_RECALL)
+        .map(|p| p.threshold);
+    DecisionCalibration {
+        match_threshold,
+        review_threshold,
+        current: match_threshold.map(|t| point(truth, &combined, t)),
+        curve,
+        recommended_match,
+        recommended_review,
+    }
+}
+
+/// Each pair's combined score: the weight-averaged rule score, where a fuzzy
+/// or semantic rule with a threshold scores 1 at or above it and 0 below,
+/// and a missing score is 0.
+pub(crate) fn combined_scores(
+    strategies: &[MatchStrategySummary],
+    similarities: &[Vec<Option<f64>>],
+    pairs: usize,
+) -> Vec<f64> {
+    let total_weight: f64 = strategies.iter().map(|r| r.weight).sum();
+    (0..pairs)
+        .map(|pair| {
+            if total_weight <= 0.0 {
+                return 0.0;
+            }
+            let weighted: f64 = strategies
+                .iter()
+                .zip(similarities)
+                .map(|(rule, scores)| {
+                    let score = scores[pair].unwrap_or(0.0);
+                    let score = match (rule.match_type.as_str(), rule.threshold) {
+                        ("exact", _) | (_, None) => score,
+                        (_, Some(t)) => (score >= t) as u8 as f64,
+                    };
+                    rule.weight * score
+                })
+                .sum();
+            weighted / total_weight
+        })
+        .collect()
+}
+
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn test_calibrate_scores_rules_on_labeled_pairs() {
+        use crate::{calibrate, Sample};
+
+        let spec = include_str!("../conformance/multi_source/spec.yaml");
+        let labels = Sample::from_csv(
+            "label,left_email,right_email,left_last_name,right_last_name\n\
+         match,a@x.com,a@x.com,Smith,Smyth\n\
+         match,b@x.com,c@y.com,Martha,Marhta\n\
+         1,d@x.com,d@x.com,Dwayne,Duane\n\
+         non_match,e@x.com,f@x.com,Smith,Jones\n\
+         0,g@x.com,h@x.com,Dixon,Dicksonx\n\
+         no,i@x.com,j@x.com,Lee,Kim\n",
+        )
+        .unwrap();
+        let calibration = calibrate(spec, &labels).unwrap();
+        assert_eq!((calibration.pairs, calibration.matches), (6, 3));
+
+        let email = &calibration.rules[0];
+        let current = email.current.as_ref().unwrap();
+        assert_eq!((current.precision, current.recall), (1.0, 0.667));
+        assert_eq!(email.recommended_weight, Some(1.0));
+        assert_eq!(email.m_probability, Some(0.625));
+
+        // Jaro-Winkler gives the textbook 0.961 for Martha/Marhta, 0.840 for
+        // Dwayne/Duane and 0.813 for Dixon/Dicksonx: at 0.85 Dwayne is missed,
+        // and 0.80 catches it at the cost of Dixon.
+        let last_name = &calibration.rules[2];
+        assert_eq!(last_name.curve.len(), 11);
+        let current = last_name.current.as_ref().unwrap();
+        assert_eq!((current.precision, current.recall), (1.0, 0.667));
+        assert_eq!(last_name.recommended_threshold, Some(0.8));
+
+        // The phone rule has no columns to score.
+        assert_eq!(calibration.rules[1].pairs, 0);
+        assert!(calibration.rules[1].current.is_none());
+
+        let unlabeled = Sample::from_csv("left_email,right_email\na,b\n").unwrap();
+        assert!(calibrate(spec, &unlabeled).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Cooperative cancellation for long-running core operations.
+//!
+//! A `CancellationToken` is cheap to clone and shared between the caller and
+//! the operation. Operations call `check()` between units of work and stop with
+//! a `Cancelled` error once the token is cancelled or its deadline passes.
+
+use anyhow::{bail, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Iterations of a long loop (records, pairs) between checks.
+pub(crate) const CHECK_INTERVAL: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Cancelled {
+    #[error("operation was cancelled")]
+    Cancelled,
+    #[error("operation timed out after {0:?}")]
+    TimedOut(Duration),
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    deadline: Option<(Instant, Duration)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that also expires `timeout` from now. A timeout too long
+    /// for the clock never expires.
+    pub fn with_timeout(timeout: Duration) -> Self {
+        CancellationToken {
+            inner: Arc::new(Inner {
+                cancelled: AtomicBool::new(false),
+                deadline: Instant::now()
+                    .checked_add(timeout)
+                    .map(|deadline| (deadline, timeout)),
+            }),
+        }
+    }
+
+    /// A token expiring `seconds` from now, as a `--timeout` gives it.
+    pub fn with_timeout_secs(seconds: f64) -> Result<Self> {
+        if !seconds.is_finite() || seconds < 0.0 {
+            bail!(
+                "Invalid timeout {}: expected a finite number of seconds, 0 or more",
+                seconds
+            );
+        }
+        match Duration::try_from_secs_f64(seconds) {
+            Ok(timeout) => Ok(Self::with_timeout(timeout)),
+            Err(_) => bail!("Invalid timeout {}: too long", seconds),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.check().is_err()
+    }
+
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.inner.cancelled.load(Ordering::SeqCst) {
+            return Err(Cancelled::Cancelled);
+        }
+        if let Some((deadline, timeout)) = self.inner.deadline {
+            if Instant::now() >= deadline {
+                return Err(Cancelled::TimedOut(timeout));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check an optional token; operations without a token never cancel.
+pub fn check(token: Option<&CancellationToken>) -> Result<(), Cancelled> {
+    token.map_or(Ok(()), |t| t.check())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, AS_OF, EXECUTION, EXECUTION_RECORDS, MINIMAL};
+    use crate::{CancellationToken, Cancelled};
+
+    #[test]
+    fn test_long_runs_stop_once_cancelled() {
+        use crate::{evaluate_with, execute_plan_with, learn_weights_with, HttpEmbedder, Sample};
+
+        // A timeout must be a number of seconds the clock can hold.
+        for seconds in [f64::INFINITY, f64::NAN, -1.0, 1e30] {
+            assert!(
+                CancellationToken::with_timeout_secs(seconds).is_err(),
+                "{}",
+                seconds
+            );
+        }
+        assert!(CancellationToken::with_timeout_secs(0.0)
+            .unwrap()
+            .is_cancelled());
+        assert!(!CancellationToken::with_timeout_secs(3600.0)
+            .unwrap()
+            .is_cancelled());
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let cancelled =
+            |err: anyhow::Error| err.downcast_ref::<Cancelled>() == Some(&Cancelled::Cancelled);
+
+        let ir = compiled_ir(EXECUTION);
+        let records: serde_json::Value = serde_json::from_str(EXECUTION_RECORDS).unwrap();
+        let embedder = HttpEmbedder::new().unwrap();
+        assert!(execute_plan_with(&ir, &records, &embedder, None, AS_OF).is_ok());
+        assert!(cancelled(
+            execute_plan_with(&ir, &records, &embedder, Some(&token), AS_OF).unwrap_err()
+        ));
+
+        let truth = Sample::from_csv("record_key,cluster_id\ncrm:1,ann\nbilling:10,ann\n").unwrap();
+        assert!(cancelled(
+            evaluate_with(&ir, &records, &truth, &embedder, Some(&token), AS_OF).unwrap_err()
+        ));
+
+        let data = Sample::from_csv("email\na@x.com\na@x.com\nb@x.com\n").unwrap();
+        assert!(learn_weights_with(MINIMAL, &data, &embedder, None).is_ok());
+        assert!(cancelled(
+            learn_weights_with(MINIMAL, &data, &embedder, Some(&token)).unwrap_err()
+        ));
+    }
+}
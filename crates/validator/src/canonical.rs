@@ -0,0 +1,331 @@
+//! Canonical form of a spec, the input to every plan hash.
+//!
+//! Two specs that mean the same thing hash the same. Map keys are sorted
+//! and trimmed, numbers normalized (`1`, `1.0` and `1e0` are one value),
+//! identifiers trimmed, null entries dropped, rules put in name order, and
+//! documentation that does not affect resolution (`description` text of the
+//! spec, its entity, rules and sources, and the `signatures` section)
+//! removed. Other strings are kept as written: a replacement of `' '` is
+//! not one of `''`. Waivers and policy stay in, since they decide what a
+//! gate lets through, as do `owners`, source `owner`s and source `rows`,
+//! which route and estimate a plan's findings. Comments and YAML style
+//! never reach the parsed value.
+//!
+//! Compiled IR has its own, narrower canonical form (`ir_hash`): the
+//! compiler already fixes its layout, so only keys, numbers, nulls and rule
+//! order are normalized.
+
+use serde_json::{Map, Number, Value};
+use sha2::{Digest, Sha256};
+
+use crate::hashing::Hasher;
+
+/// Top-level sections that document a spec without changing what it does.
+const NON_SEMANTIC_SECTIONS: &[&str] = &["signatures"];
+
+/// Keys whose string values, or lists of them, name something in the spec,
+/// where surrounding whitespace is never meant.
+const IDENTIFIER_KEYS: &[&str] = &["name", "id", "field", "fields"];
+
+/// Sections whose `description` is documentation. Elsewhere `description`
+/// may be data: an attribute (in `encoding.fields`, say) or a column.
+const DOCUMENTED_SECTIONS: &[&str] = &["entity", "rules", "sources"];
+
+/// `sha256:<hex>` over the canonical JSON of `spec`.
+pub fn canonical_hash(spec: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(canonical_json(spec).as_bytes());
+    format!("sha256:{:x}", hasher.finalize())
+}
+
+/// `canonical_hash` with another algorithm or a key, e.g. `blake3:<hex>` or
+/// `hmac-sha256:<hex>`. The default `Hasher` gives `canonical_hash`.
+pub fn canonical_hash_with(spec: &Value, hasher: &Hasher) -> String {
+    hasher.digest(canonical_json(spec).as_bytes())
+}
+
+/// `sha256:<hex>` over compiled IR: keys sorted, numbers normalized, null
+/// entries dropped and rules put in name order, but entries otherwise kept
+/// as compiled. The IR's `plan_hash`.
+pub fn ir_hash(ir: &Value) -> String {
+    let mut canonical = normalize_ir(ir);
+    if let Some(Value::Array(rules)) = canonical.get("rules") {
+        let ordered = ordered_rules(rules)
+            .into_iter()
+            .map(|(_, rule)| rule.clone())
+            .collect();
+        canonical["rules"] = Value::Array(ordered);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(sorted_json(&canonical).as_bytes());
+    format!("sha256:{:x}", hasher.finalize())
+}
+
+/// Compact JSON of the canonical form, keys sorted at every level.
+pub fn canonical_json(spec: &Value) -> String {
+    sorted_json(&canonical_form(spec))
+}
+
+/// Compact JSON of `value` as it is, keys sorted at every level.
+pub(crate) fn sorted_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_json(value, &mut out);
+    out
+}
+
+/// The canonical form itself, for callers that compare specs.
+pub fn canonical_form(spec: &Value) -> Value {
+    let mut canonical = normalize(spec, None);
+    remove_description(&mut canonical);
+    if let Value::Object(root) = &mut canonical {
+        for section in NON_SEMANTIC_SECTIONS {
+            root.remove(*section);
+        }
+        for section in DOCUMENTED_SECTIONS {
+            match root.get_mut(*section) {
+                Some(Value::Array(items)) => items.iter_mut().for_each(remove_description),
+                Some(Value::Object(sources)) if *section == "sources" => {
+                    sources.values_mut().for_each(remove_description)
+                }
+                Some(documented) => remove_description(documented),
+                None => {}
+            }
+        }
+        if let Some(Value::Array(rules)) = root.get("rules") {
+            let ordered = ordered_rules(rules)
+                .into_iter()
+                .map(|(_, rule)| rule.clone())
+                .collect();
+            root.insert("rules".to_string(), Value::Array(ordered));
+        }
+    }
+    canonical
+}
+
+fn remove_description(section: &mut Value) {
+    if let Value::Object(section) = section {
+        if section.get("description").is_some_and(Value::is_string) {
+            section.remove("description");
+        }
+    }
+}
+
+/// Rules in canonical order (by name, stable), with their original indexes.
+/// Rule order carries no meaning: exact rules are always evaluated before
+/// fuzzy ones and scores are summed.
+pub(crate) fn ordered_rules(rules: &[Value]) -> Vec<(usize, &Value)> {
+    let mut ordered: Vec<(usize, &Value)> = rules.iter().enumerate().collect();
+    ordered.sort_by_key(|(_, rule)| rule.get("name").and_then(|n| n.as_str()).unwrap_or(""));
+    ordered
+}
+
+fn normalize(value: &Value, parent_key: Option<&str>) -> Value {
+    match value {
+        Value::String(s) if parent_key.is_some_and(|k| IDENTIFIER_KEYS.contains(&k)) => {
+            Value::String(s.trim().to_string())
+        }
+        Value::Number(n) => Value::Number(normalize_number(n)),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| normalize(v, parent_key)).collect())
+        }
+        Value::Object(map) => {
+            let mut out = Map::new();
+            for (key, value) in map {
+                if value.is_null() {
+                    continue;
+                }
+                let key = key.trim();
+                out.insert(key.to_string(), normalize(value, Some(key)));
+            }
+            Value::Object(out)
+        }
+        other => other.clone(),
+    }
+}
+
+fn normalize_ir(value: &Value) -> Value {
+    match value {
+        Value::Number(n) => Value::Number(normalize_number(n)),
+        Value::Array(items) => Value::Array(items.iter().map(normalize_ir).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key.clone(), normalize_ir(value)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Integral floats (including `-0.0`) become integers; other floats keep
+/// their shortest form.
+fn normalize_number(n: &Number) -> Number {
+    if n.is_i64() || n.is_u64() {
+        return n.clone();
+    }
+    match n.as_f64() {
+        Some(f) if f.fract() == 0.0 && f.abs() < 9_007_199_254_740_992.0 => Number::from(f as i64),
+        _ => n.clone(),
+    }
+}
+
+/// Equality that treats `1` and `1.0` as the same number, at any depth.
+pub(crate) fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(a, b)| same_value(a, b))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(k, a)| y.get(k).is_some_and(|b| same_value(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+fn write_json(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_json(&map[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_canonical_hash_ignores_style() {
+        let hash = |yaml: &str| crate::canonical_hash(&crate::parse_yaml(yaml).unwrap());
+        let base = hash(MINIMAL);
+
+        let restyled = MINIMAL
+            .replace("weight: 1.0", "weight: 1")
+            .replace("match: 0.9", "match: 9.0e-1")
+            .replace("name: customer", "name: \"  customer \"")
+            .replace("entity:\n", "entity:\n  description: People we sell to\n")
+            + "# reviewed 2026-01\n";
+        assert_eq!(hash(&restyled), base);
+        assert_eq!(
+            crate::compute_hash(&crate::parse_yaml(&restyled).unwrap()).unwrap(),
+            base
+        );
+        assert!(crate::compute_diff(MINIMAL, &restyled)
+            .unwrap()
+            .rules_modified
+            .is_empty());
+
+        // Real changes still change the hash.
+        assert_ne!(hash(&MINIMAL.replace("weight: 1.0", "weight: 0.9")), base);
+        // Owners route findings and row counts estimate costs in the plan.
+        assert_ne!(
+            hash(&(MINIMAL.to_string() + "owners:\n  rules: \"@matching-team\"\n")),
+            base
+        );
+        assert_ne!(
+            hash(&MINIMAL.replace(
+                "    id: contact_id\n",
+                "    id: contact_id\n    rows: 5000\n"
+            )),
+            base
+        );
+        let keyed = |key: &str| {
+            let spec = serde_json::json!({ "entity": { key: "  customer " } });
+            crate::canonical_form(&spec)["entity"]["name"].clone()
+        };
+        assert_eq!(keyed(" name "), "customer");
+        // Waivers and policy decide what a gate lets through.
+        assert_ne!(
+            hash(
+                &(MINIMAL.to_string()
+                    + "waivers:\n  - code: NO_BLOCKING\n    reason: tiny table\n")
+            ),
+            base
+        );
+        assert_ne!(
+            hash(&(MINIMAL.to_string() + "policy:\n  NO_BLOCKING: ignore\n")),
+            base
+        );
+        // Only identifiers are trimmed; a replacement of ' ' is not one of ''.
+        let replacing = |replacement: &str| {
+            hash(&format!(
+            "{}normalization:\n  fields:\n    email:\n      - regex_replace: {{ pattern: '\\s+', replacement: '{}' }}\n",
+            MINIMAL, replacement
+        ))
+        };
+        assert_ne!(replacing(" "), replacing(""));
+        // A column named `description` is data, not documentation.
+        assert_ne!(
+            hash(&MINIMAL.replace(
+                "      email: email\n",
+                "      email: email\n      description: notes\n"
+            )),
+            base
+        );
+    }
+
+    /// `description` is documentation only on the spec, its entity, rules and
+    /// sources; an attribute of that name is settings like any other.
+    #[test]
+    fn test_canonical_hash_keeps_attributes_named_description() {
+        use crate::{canonical_hash, compile_to_ir, parse_yaml};
+
+        let spec = MINIMAL.replace(
+            "      email: email\n",
+            "      email: email\n      description: notes\n",
+        );
+        let hashes = |yaml: &str| {
+            let spec = parse_yaml(yaml).unwrap();
+            (
+                canonical_hash(&spec),
+                compile_to_ir(&spec).unwrap()["plan_hash"].clone(),
+            )
+        };
+        let base = hashes(&spec);
+        for section in [
+            "encoding:\n  salt_env: KANONIV_SALT\n  fields:\n    description: hash\n",
+            "normalization:\n  fields:\n    description: [lower]\n",
+            "privacy:\n  fields:\n    description:\n      masking: redact\n",
+        ] {
+            let (hash, plan_hash) = hashes(&format!("{}{}", spec, section));
+            assert_ne!(hash, base.0, "{}", section);
+            assert_ne!(plan_hash, base.1, "{}", section);
+        }
+        let documented = spec
+            .replace("api_version:", "description: Customers\napi_version:")
+            .replace(
+                "  - name: crm\n",
+                "  - name: crm\n    description: The CRM\n",
+            )
+            .replace(
+                "    type: exact\n",
+                "    type: exact\n    description: Same address\n",
+            );
+        assert_eq!(hashes(&documented), base);
+    }
+}
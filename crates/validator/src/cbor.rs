@@ -0,0 +1,319 @@
+//! CBOR (RFC 8949) for JSON values, in its core deterministic encoding.
+//!
+//! Integers and lengths take their shortest form, floats the shortest of
+//! half, single and double precision that holds them exactly, and map keys
+//! are ordered by their encoded bytes. A value therefore always encodes to
+//! the same bytes, which can be digested like a canonical form, and
+//! decoding gives back the value it was encoded from: integers stay
+//! integers and floats stay floats. Only what JSON has is read: no byte
+//! strings, tags or indefinite lengths.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Number, Value};
+
+const UNSIGNED: u8 = 0;
+const NEGATIVE: u8 = 1;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+const SIMPLE: u8 = 7;
+
+const FALSE: u8 = 20;
+const TRUE: u8 = 21;
+const NULL: u8 = 22;
+const HALF: u8 = 25;
+const SINGLE: u8 = 26;
+const DOUBLE: u8 = 27;
+
+/// Deepest nesting read, so hostile input cannot exhaust the stack.
+const MAX_DEPTH: usize = 128;
+
+/// `value` in deterministic CBOR.
+pub fn to_vec(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode(value, &mut out);
+    out
+}
+
+/// The value a CBOR document holds, which must be all of `bytes`.
+pub fn from_slice(bytes: &[u8]) -> Result<Value> {
+    let mut decoder = Decoder { bytes, offset: 0 };
+    let value = decoder.value(0)?;
+    if decoder.offset != bytes.len() {
+        bail!(
+            "{} trailing byte(s) after the CBOR value",
+            bytes.len() - decoder.offset
+        );
+    }
+    Ok(value)
+}
+
+fn encode(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(SIMPLE << 5 | NULL),
+        Value::Bool(false) => out.push(SIMPLE << 5 | FALSE),
+        Value::Bool(true) => out.push(SIMPLE << 5 | TRUE),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => head(UNSIGNED, n, out),
+            (None, Some(n)) => head(NEGATIVE, (-1 - n) as u64, out),
+            (None, None) => float(n.as_f64().unwrap_or_default(), out),
+        },
+        Value::String(s) => {
+            head(TEXT, s.len() as u64, out);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(items) => {
+            head(ARRAY, items.len() as u64, out);
+            for item in items {
+                encode(item, out);
+            }
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(Vec<u8>, &Value)> = map
+                .iter()
+                .map(|(key, value)| (to_vec(&Value::String(key.clone())), value))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            head(MAP, entries.len() as u64, out);
+            for (key, value) in entries {
+                out.extend_from_slice(&key);
+                encode(value, out);
+            }
+        }
+    }
+}
+
+/// A major type and its argument, in the shortest form.
+fn head(major: u8, argument: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match argument {
+        0..=23 => out.push(major | argument as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, argument as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(argument as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&argument.to_be_bytes());
+        }
+    }
+}
+
+fn float(value: f64, out: &mut Vec<u8>) {
+    if let Some(half) = to_half(value) {
+        out.push(SIMPLE << 5 | HALF);
+        out.extend_from_slice(&half.to_be_bytes());
+    } else if (value as f32) as f64 == value {
+        out.push(SIMPLE << 5 | SINGLE);
+        out.extend_from_slice(&(value as f32).to_be_bytes());
+    } else {
+        out.push(SIMPLE << 5 | DOUBLE);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// `value` as a half-precision float, if one holds it exactly.
+fn to_half(value: f64) -> Option<u16> {
+    let single = value as f32;
+    if single as f64 != value || !value.is_finite() {
+        return None;
+    }
+    let bits = single.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127;
+    let mantissa = bits & 0x7f_ffff;
+    if value == 0.0 {
+        return Some(sign);
+    }
+    match exponent {
+        // Normal: 10 mantissa bits.
+        -14..=15 if mantissa & 0x1fff == 0 => {
+            Some(sign | ((exponent + 15) as u16) << 10 | (mantissa >> 13) as u16)
+        }
+        // Subnormal: multiples of 2^-24.
+        -24..=-15 => {
+            let shift = (-1 - exponent) as u32;
+            let significand = mantissa | 0x80_0000;
+            (significand & ((1 << shift) - 1) == 0).then(|| sign | (significand >> shift) as u16)
+        }
+        _ => None,
+    }
+}
+
+fn from_half(half: u16) -> f64 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f64;
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8]> {
+        let end = self
+            .offset
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("CBOR ends inside a value at byte {}", self.offset))?;
+        let taken = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(taken)
+    }
+
+    fn argument(&mut self, info: u8) -> Result<u64> {
+        let bytes = match info {
+            0..=23 => return Ok(info as u64),
+            24 => self.take(1)?,
+            25 => self.take(2)?,
+            26 => self.take(4)?,
+            27 => self.take(8)?,
+            _ => bail!("Unsupported CBOR length at byte {}", self.offset - 1),
+        };
+        Ok(bytes.iter().fold(0, |n, &b| n << 8 | b as u64))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            bail!("CBOR nested more than {} deep", MAX_DEPTH);
+        }
+        let start = self.offset;
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == SIMPLE {
+            return match info {
+                FALSE => Ok(Value::Bool(false)),
+                TRUE => Ok(Value::Bool(true)),
+                NULL => Ok(Value::Null),
+                HALF | SINGLE | DOUBLE => {
+                    let bits = self.argument(info)?;
+                    let value = match info {
+                        HALF => from_half(bits as u16),
+                        SINGLE => f32::from_bits(bits as u32) as f64,
+                        _ => f64::from_bits(bits),
+                    };
+                    Number::from_f64(value)
+                        .map(Value::Number)
+                        .ok_or_else(|| anyhow!("CBOR float at byte {} is not a JSON number", start))
+                }
+                _ => bail!("Unsupported CBOR simple value {} at byte {}", info, start),
+            };
+        }
+        let argument = self.argument(info)?;
+        match major {
+            UNSIGNED => Ok(Value::from(argument)),
+            NEGATIVE => match i64::try_from(argument) {
+                Ok(n) => Ok(Value::from(-1 - n)),
+                Err(_) => bail!("CBOR integer at byte {} is out of range", start),
+            },
+            TEXT => {
+                let bytes = self.take(argument as usize)?;
+                let text = std::str::from_utf8(bytes)
+                    .map_err(|_| anyhow!("CBOR text at byte {} is not UTF-8", start))?;
+                Ok(Value::String(text.to_string()))
+            }
+            ARRAY => {
+                let mut items = Vec::new();
+                for _ in 0..argument {
+                    items.push(self.value(depth + 1)?);
+                }
+                Ok(Value::Array(items))
+            }
+            MAP => {
+                let mut map = Map::new();
+                for _ in 0..argument {
+                    let Value::String(key) = self.value(depth + 1)? else {
+                        bail!("CBOR map at byte {} has a key that is not text", start);
+                    };
+                    let value = self.value(depth + 1)?;
+                    map.insert(key, value);
+                }
+                Ok(Value::Object(map))
+            }
+            other => bail!("Unsupported CBOR major type {} at byte {}", other, start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{EXECUTION, MAPPINGS, MINIMAL, RELATIONSHIPS, TYPED};
+
+    #[test]
+    fn test_cbor_ir_round_trips_with_a_stable_digest() {
+        use crate::{cbor, compile_to_ir, ir_digest, parse_yaml, IrEncoding};
+
+        for yaml in [MINIMAL, TYPED, MAPPINGS, RELATIONSHIPS, EXECUTION] {
+            let ir = compile_to_ir(&parse_yaml(yaml).unwrap()).unwrap();
+            let json = IrEncoding::Json.encode(&ir).unwrap();
+            let bytes = IrEncoding::Cbor.encode(&ir).unwrap();
+            assert!(
+                bytes.len() * 3 < json.len() * 2,
+                "{} vs {} bytes",
+                bytes.len(),
+                json.len()
+            );
+            // Integers stay integers and floats floats, so the plan hash holds.
+            let decoded = cbor::from_slice(&bytes).unwrap();
+            assert_eq!(decoded, ir);
+            assert!(crate::ir::plan_hash_matches(&decoded));
+            let base64 = IrEncoding::CborBase64.encode(&ir).unwrap();
+            assert_eq!(crate::ir::decode(&base64).unwrap(), ir);
+            assert_eq!(crate::ir::decode(&json).unwrap(), ir);
+            assert_eq!(ir_digest(&decoded), ir_digest(&ir));
+        }
+
+        // Deterministic: shortest heads and floats, keys ordered by encoding.
+        let value =
+            serde_json::json!({ "bb": 1.5, "a": [0, 24, -1, -500, 0.1, 1e300, true, null] });
+        assert_eq!(
+            cbor::to_vec(&value),
+            [
+                &[0xa2, 0x61, b'a', 0x88, 0x00, 0x18, 24, 0x20, 0x39, 0x01, 0xf3][..],
+                &[0xfb, 0x3f, 0xb9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a],
+                &[0xfb, 0x7e, 0x37, 0xe4, 0x3c, 0x88, 0x00, 0x75, 0x9c, 0xf5, 0xf6],
+                &[0x62, b'b', b'b', 0xf9, 0x3e, 0x00],
+            ]
+            .concat()
+        );
+        assert_eq!(cbor::from_slice(&cbor::to_vec(&value)).unwrap(), value);
+        assert_eq!(
+            ir_digest(&value),
+            ir_digest(
+                &serde_json::from_str(r#"{"a":[0,24,-1,-500,0.1,1e300,true,null],"bb":1.5}"#)
+                    .unwrap()
+            )
+        );
+
+        assert!(cbor::from_slice(&[0xa1, 0x61])
+            .unwrap_err()
+            .to_string()
+            .contains("ends inside a value"));
+        assert!(cbor::from_slice(&[0x40])
+            .unwrap_err()
+            .to_string()
+            .contains("major type 2"));
+        assert!(cbor::from_slice(&[0xf6, 0xf6])
+            .unwrap_err()
+            .to_string()
+            .contains("1 trailing byte(s)"));
+        assert!("msgpack"
+            .parse::<IrEncoding>()
+            .unwrap_err()
+            .to_string()
+            .contains("json, cbor, cbor-base64"));
+    }
+}
@@ -0,0 +1,66 @@
+//! UTC dates and timestamps without a date-time dependency.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Today's UTC date as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let (date, _) = split(now_secs());
+    date
+}
+
+/// The current UTC time as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn now() -> String {
+    let (date, secs) = split(now_secs());
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        date,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// The UTC date `days` days after 1970-01-01, as `YYYY-MM-DD`.
+pub fn date(days: i64) -> String {
+    let (date, _) = split(days * 86_400);
+    date
+}
+
+fn now_secs() -> i64 {
+    now_millis().div_euclid(1000)
+}
+
+/// Milliseconds since the epoch.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0) as i64
+}
+
+/// `SystemTime` panics in the browser; ask JavaScript instead.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now_millis() -> i64 {
+    js_sys::Date::now() as i64
+}
+
+/// Seconds since the epoch -> (`YYYY-MM-DD`, seconds into the day).
+fn split(secs: i64) -> (String, i64) {
+    let days = secs.div_euclid(86_400);
+    // Civil-from-days (H. Hinnant).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        secs.rem_euclid(86_400),
+    )
+}
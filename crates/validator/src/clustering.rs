@@ -0,0 +1,745 @@
+//! Grouping matched records into entities.
+//!
+//! By default every pair decided `match` links its records and entities are
+//! the connected components (`transitive_closure`), so one weak link in a
+//! chain `A-B-C` merges `A` and `C` although they were never matched. The
+//! `clustering` section selects a strategy that resists such chains:
+//!
+//! ```yaml
+//! clustering:
+//!   strategy: hierarchical    # transitive_closure (default), star,
+//!                             # correlation_clustering or hierarchical
+//!   threshold: 0.85           # hierarchical only: the linkage cut
+//! ```
+//!
+//! - `star`: the unassigned record with the most matches becomes a center
+//!   and takes its unassigned matches; no record is more than one match
+//!   from its center.
+//! - `correlation_clustering`: each unassigned record in turn takes its
+//!   unassigned matches, then records move to the cluster they agree with
+//!   most, counting a match as agreement and any other pair in the cluster
+//!   (scored below `match`, or never compared) as disagreement.
+//! - `hierarchical`: clusters are merged in order of average linkage, the
+//!   mean score over all their cross pairs (0 for pairs never compared),
+//!   while it is at least `threshold`.
+//!
+//! All strategies are deterministic: ties go to the lower record index, and
+//! a cluster is identified by its lowest record, as in the generated SQL.
+//!
+//! `cluster_decisions` clusters a CSV export of the pipeline's
+//! `match_decisions` (`left_key`, `right_key`, `score`, `decision`) under a
+//! spec's strategy, recording the decisions, merges and splits for the
+//! audit trail (see `audit`). Stewardship assertions (see `stewardship`)
+//! outrank both the decisions and the strategy.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::audit::{
+    AuditEvent, AuditRecord, MatchDecision, MergeEvent, SplitEvent, StewardOverride,
+};
+use crate::commands::compile::compile_to_ir;
+use crate::ir::Ir;
+use crate::parser;
+use crate::sample::Sample;
+use crate::stewardship::Stewardship;
+
+pub const STRATEGIES: &[&str] = &[
+    "transitive_closure",
+    "star",
+    "correlation_clustering",
+    "hierarchical",
+];
+
+/// Local-search passes of correlation clustering; each pass that moves a
+/// record strictly improves agreement, so this only bounds pathological
+/// inputs.
+const MAX_PASSES: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusteringStrategy {
+    #[default]
+    TransitiveClosure,
+    Star,
+    CorrelationClustering,
+    Hierarchical,
+}
+
+impl ClusteringStrategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClusteringStrategy::TransitiveClosure => "transitive_closure",
+            ClusteringStrategy::Star => "star",
+            ClusteringStrategy::CorrelationClustering => "correlation_clustering",
+            ClusteringStrategy::Hierarchical => "hierarchical",
+        }
+    }
+}
+
+impl FromStr for ClusteringStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "transitive_closure" => Ok(ClusteringStrategy::TransitiveClosure),
+            "star" => Ok(ClusteringStrategy::Star),
+            "correlation_clustering" => Ok(ClusteringStrategy::CorrelationClustering),
+            "hierarchical" => Ok(ClusteringStrategy::Hierarchical),
+            _ => bail!(
+                "Unknown clustering strategy '{}'. Use {}",
+                s,
+                STRATEGIES.join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ClusteringStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The `clustering` section.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Clustering {
+    pub strategy: ClusteringStrategy,
+    /// Average linkage at which `hierarchical` clusters stop merging.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+}
+
+/// A compared pair of records, by index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoredPair {
+    pub left: usize,
+    pub right: usize,
+    pub score: f64,
+    /// Whether the pair was decided `match`.
+    pub matched: bool,
+}
+
+impl Clustering {
+    /// Read `clustering` from a parsed spec; `None` if the spec has none.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(section) = spec.get("clustering").filter(|c| !c.is_null()) else {
+            return Ok(None);
+        };
+        let strategy = match section.get("strategy") {
+            None => ClusteringStrategy::default(),
+            Some(strategy) => match strategy.as_str() {
+                Some(name) => name.parse()?,
+                None => bail!("clustering.strategy must be a name, got {}", strategy),
+            },
+        };
+        let threshold = match section.get("threshold") {
+            None => None,
+            Some(value) => match value.as_f64() {
+                Some(t) if (0.0..=1.0).contains(&t) => Some(t),
+                _ => bail!(
+                    "clustering.threshold must be between 0 and 1, got {}",
+                    value
+                ),
+            },
+        };
+        match (strategy, threshold) {
+            (ClusteringStrategy::Hierarchical, None) => {
+                bail!("clustering.threshold is required by the hierarchical strategy")
+            }
+            (ClusteringStrategy::Hierarchical, _) | (_, None) => {}
+            (strategy, Some(_)) => bail!(
+                "clustering.threshold is only used by the hierarchical strategy, not '{}'",
+                strategy
+            ),
+        }
+        Ok(Some(Clustering {
+            strategy,
+            threshold,
+        }))
+    }
+
+    /// What the clustering stage does, for the plan.
+    pub fn description(&self) -> String {
+        match self.strategy {
+            ClusteringStrategy::TransitiveClosure => {
+                "Transitive closure via UnionFind to group matched entities".to_string()
+            }
+            ClusteringStrategy::Star => {
+                "Star clustering: the best-connected unassigned entity takes its direct matches"
+                    .to_string()
+            }
+            ClusteringStrategy::CorrelationClustering => {
+                "Correlation clustering: pivot on matched entities, then move each to the cluster it agrees with most"
+                    .to_string()
+            }
+            ClusteringStrategy::Hierarchical => format!(
+                "Average-linkage hierarchical clustering, cut at linkage {}",
+                self.threshold.unwrap_or(1.0)
+            ),
+        }
+    }
+
+    /// The cluster of each of `records` records, given the compared
+    /// `pairs`: the index of the cluster's lowest record.
+    pub fn cluster(&self, records: usize, pairs: &[ScoredPair]) -> Vec<usize> {
+        let pairs = distinct(records, pairs);
+        let labels = match self.strategy {
+            ClusteringStrategy::TransitiveClosure => transitive_closure(records, &pairs),
+            ClusteringStrategy::Star => star(records, &pairs),
+            ClusteringStrategy::CorrelationClustering => correlation(records, &pairs),
+            ClusteringStrategy::Hierarchical => {
+                average_linkage(records, &pairs, self.threshold.unwrap_or(1.0))
+            }
+        };
+        lowest_member(&labels)
+    }
+}
+
+/// Pairs in range with `left < right`, one per pair of records (the
+/// highest score, matched if any copy was).
+fn distinct(records: usize, pairs: &[ScoredPair]) -> Vec<ScoredPair> {
+    let mut by_records: BTreeMap<(usize, usize), ScoredPair> = BTreeMap::new();
+    for pair in pairs {
+        let (left, right) = (pair.left.min(pair.right), pair.left.max(pair.right));
+        if left == right || right >= records {
+            continue;
+        }
+        let entry = by_records.entry((left, right)).or_insert(ScoredPair {
+            left,
+            right,
+            score: pair.score,
+            matched: false,
+        });
+        entry.score = entry.score.max(pair.score);
+        entry.matched |= pair.matched;
+    }
+    by_records.into_values().collect()
+}
+
+/// Each record's matched records, ascending.
+fn matches(records: usize, pairs: &[ScoredPair]) -> Vec<Vec<usize>> {
+    let mut adjacent = vec![Vec::new(); records];
+    for pair in pairs.iter().filter(|p| p.matched) {
+        adjacent[pair.left].push(pair.right);
+        adjacent[pair.right].push(pair.left);
+    }
+    for neighbours in &mut adjacent {
+        neighbours.sort_unstable();
+    }
+    adjacent
+}
+
+/// Relabel clusters by their lowest member.
+fn lowest_member(labels: &[usize]) -> Vec<usize> {
+    let mut lowest: BTreeMap<usize, usize> = BTreeMap::new();
+    for (record, label) in labels.iter().enumerate() {
+        lowest.entry(*label).or_insert(record);
+    }
+    labels.iter().map(|label| lowest[label]).collect()
+}
+
+pub(crate) fn find(parent: &mut [usize], mut record: usize) -> usize {
+    while parent[record] != record {
+        parent[record] = parent[parent[record]];
+        record = parent[record];
+    }
+    record
+}
+
+fn transitive_closure(records: usize, pairs: &[ScoredPair]) -> Vec<usize> {
+    let mut parent: Vec<usize> = (0..records).collect();
+    for pair in pairs.iter().filter(|p| p.matched) {
+        let (a, b) = (find(&mut parent, pair.left), find(&mut parent, pair.right));
+        parent[a.max(b)] = a.min(b);
+    }
+    (0..records).map(|r| find(&mut parent, r)).collect()
+}
+
+fn star(records: usize, pairs: &[ScoredPair]) -> Vec<usize> {
+    let adjacent = matches(records, pairs);
+    let mut centers: Vec<usize> = (0..records).collect();
+    centers.sort_by_key(|&r| (Reverse(adjacent[r].len()), r));
+    let mut label = vec![None; records];
+    for center in centers {
+        if label[center].is_some() {
+            continue;
+        }
+        label[center] = Some(center);
+        for &record in &adjacent[center] {
+            label[record].get_or_insert(center);
+        }
+    }
+    label.into_iter().map(|l| l.unwrap_or_default()).collect()
+}
+
+fn correlation(records: usize, pairs: &[ScoredPair]) -> Vec<usize> {
+    let adjacent = matches(records, pairs);
+
+    // Pivot: each unassigned record takes its unassigned matches.
+    let mut label = vec![usize::MAX; records];
+    for pivot in 0..records {
+        if label[pivot] != usize::MAX {
+            continue;
+        }
+        label[pivot] = pivot;
+        for &record in &adjacent[pivot] {
+            if label[record] == usize::MAX {
+                label[record] = pivot;
+            }
+        }
+    }
+
+    // Local search: move records to the cluster they agree with most.
+    // Labels past `records` are singletons split off during the search.
+    let mut size = vec![0usize; records];
+    for &l in &label {
+        size[l] += 1;
+    }
+    for _ in 0..MAX_PASSES {
+        let mut moved = false;
+        for record in 0..records {
+            let current = label[record];
+            let mut links: BTreeMap<usize, i64> = BTreeMap::new();
+            for &other in &adjacent[record] {
+                *links.entry(label[other]).or_default() += 1;
+            }
+            // Matches minus other pairs with the rest of the cluster.
+            let agreement = |cluster: usize, links: i64| {
+                let others = size[cluster] - usize::from(cluster == current);
+                2 * links - others as i64
+            };
+            let mut best = (
+                agreement(current, links.get(&current).copied().unwrap_or(0)),
+                current,
+            );
+            for (&cluster, &n) in &links {
+                if agreement(cluster, n) > best.0 {
+                    best = (agreement(cluster, n), cluster);
+                }
+            }
+            if best.0 < 0 && size[current] > 1 {
+                best = (0, size.len());
+                size.push(0);
+            }
+            if best.1 != current {
+                size[current] -= 1;
+                size[best.1] += 1;
+                label[record] = best.1;
+                moved = true;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+    label
+}
+
+/// A possible merge, valid while neither cluster has changed since.
+#[derive(PartialEq)]
+struct Merge {
+    linkage: f64,
+    a: usize,
+    b: usize,
+    versions: (usize, usize),
+}
+
+impl Eq for Merge {}
+
+impl Ord for Merge {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.linkage
+            .total_cmp(&other.linkage)
+            .then_with(|| (other.a, other.b).cmp(&(self.a, self.b)))
+    }
+}
+
+impl PartialOrd for Merge {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn average_linkage(records: usize, pairs: &[ScoredPair], threshold: f64) -> Vec<usize> {
+    let mut parent: Vec<usize> = (0..records).collect();
+    let mut size = vec![1usize; records];
+    let mut version = vec![0usize; records];
+    // Summed scores between clusters, keyed by their lowest record.
+    let mut links: Vec<BTreeMap<usize, f64>> = vec![BTreeMap::new(); records];
+    for pair in pairs {
+        links[pair.left].insert(pair.right, pair.score);
+        links[pair.right].insert(pair.left, pair.score);
+    }
+
+    let merge = |a: usize, b: usize, sum: f64, size: &[usize], version: &[usize]| {
+        let (a, b) = (a.min(b), a.max(b));
+        Merge {
+            linkage: sum / (size[a] * size[b]) as f64,
+            a,
+            b,
+            versions: (version[a], version[b]),
+        }
+    };
+    let mut heap = BinaryHeap::new();
+    for pair in pairs {
+        heap.push(merge(pair.left, pair.right, pair.score, &size, &version));
+    }
+
+    while let Some(next) = heap.pop() {
+        if next.linkage < threshold {
+            break;
+        }
+        let (a, b) = (next.a, next.b);
+        if next.versions != (version[a], version[b]) || parent[a] != a || parent[b] != b {
+            continue;
+        }
+        parent[b] = a;
+        size[a] += size[b];
+        size[b] = 0;
+        version[a] += 1;
+        version[b] += 1;
+        for (other, sum) in std::mem::take(&mut links[b]) {
+            links[other].remove(&b);
+            if other != a {
+                *links[a].entry(other).or_default() += sum;
+                *links[other].entry(a).or_default() += sum;
+            }
+        }
+        for (&other, &sum) in &links[a] {
+            heap.push(merge(a, other, sum, &size, &version));
+        }
+    }
+    (0..records).map(|r| find(&mut parent, r)).collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntityClusters {
+    pub strategy: ClusteringStrategy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    /// Records that appear in a pair.
+    pub records: usize,
+    pub pairs: usize,
+    pub clusters: usize,
+    pub largest_cluster: usize,
+    /// Clusters transitive closure would form, for comparison.
+    pub transitive_clusters: usize,
+    /// Stewardship assertions honored.
+    pub overrides: usize,
+    pub assignments: Vec<ClusterAssignment>,
+    /// Match decisions, merges and splits, for the audit trail.
+    #[serde(skip)]
+    pub audit: Vec<AuditRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClusterAssignment {
+    pub record_key: String,
+    pub cluster_id: String,
+}
+
+/// Cluster the records of the `match_decisions` export `decisions` with
+/// `yaml`'s clustering strategy. Records are those named in a pair, ordered
+/// by key, so a cluster's id is its lowest record key.
+pub fn cluster_decisions(yaml: &str, decisions: &Sample) -> Result<EntityClusters> {
+    cluster_decisions_with(yaml, decisions, &Stewardship::default())
+}
+
+/// `cluster_decisions`, honoring `stewardship` (a sidecar file's
+/// assertions) after the spec's own. Records an `always_merge` entry names
+/// are clustered whether or not they appear in a pair.
+pub fn cluster_decisions_with(
+    yaml: &str,
+    decisions: &Sample,
+    stewardship: &Stewardship,
+) -> Result<EntityClusters> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    cluster_decisions_from_ir(&ir, decisions, &[], stewardship)
+}
+
+/// `cluster_decisions_with`, for compiled IR. `records` are keys clustered
+/// even if no decision names them, so they come back as singletons.
+pub(crate) fn cluster_decisions_from_ir(
+    ir: &Ir,
+    decisions: &Sample,
+    records: &[&str],
+    stewardship: &Stewardship,
+) -> Result<EntityClusters> {
+    let clustering = ir.clustering.unwrap_or_default();
+    let stewardship = ir.stewardship.clone().unwrap_or_default().with(stewardship);
+    stewardship.check()?;
+
+    let required = |name: &str| match decisions.find_column(name) {
+        Some(index) => Ok(index),
+        None => bail!("decisions table has no '{}' column", name),
+    };
+    let (left, right) = (required("left_key")?, required("right_key")?);
+    let (score, decision) = (required("score")?, required("decision")?);
+
+    let mut keys: Vec<&str> = decisions
+        .rows
+        .iter()
+        .flat_map(|row| [cell(row, left), cell(row, right)])
+        .chain(
+            stewardship
+                .always_merge
+                .iter()
+                .flat_map(|a| a.records.iter().map(String::as_str)),
+        )
+        .chain(records.iter().copied())
+        .filter(|key| !key.is_empty())
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    let index = |key: &str| keys.binary_search(&key).ok();
+
+    let mut pairs = Vec::new();
+    let mut audit = Vec::new();
+    for (i, row) in decisions.rows.iter().enumerate() {
+        let (Some(l), Some(r)) = (index(cell(row, left)), index(cell(row, right))) else {
+            continue;
+        };
+        let value = cell(row, score);
+        let Ok(score) = value.parse::<f64>() else {
+            bail!("decisions row {}: score '{}' is not a number", i + 1, value);
+        };
+        pairs.push(ScoredPair {
+            left: l,
+            right: r,
+            score,
+            matched: cell(row, decision) == "match",
+        });
+        audit.push(AuditRecord::new(
+            ir,
+            AuditEvent::MatchDecision(MatchDecision {
+                left_key: keys[l].to_string(),
+                right_key: keys[r].to_string(),
+                score,
+                decision: cell(row, decision).to_string(),
+            }),
+        ));
+    }
+
+    stewardship.constrain_pairs(&mut pairs, index);
+    let mut labels = clustering.cluster(keys.len(), &pairs);
+    if !stewardship.is_empty() {
+        labels = stewardship.enforce(&labels, &pairs, index);
+    }
+    let mut overrides = 0;
+    for (assertion, entries) in [
+        ("always_merge", &stewardship.always_merge),
+        ("never_merge", &stewardship.never_merge),
+    ] {
+        for entry in entries {
+            let records: Vec<usize> = entry.records.iter().filter_map(|r| index(r)).collect();
+            if records.len() < 2 {
+                continue;
+            }
+            let merged = assertion == "always_merge";
+            overrides += 1;
+            audit.push(AuditRecord::new(
+                ir,
+                AuditEvent::StewardOverride(StewardOverride {
+                    assertion: assertion.to_string(),
+                    record_keys: records.iter().map(|&r| keys[r].to_string()).collect(),
+                    entity_id: merged.then(|| keys[labels[records[0]]].to_string()),
+                    field: None,
+                    value: None,
+                    reason: entry.reason.clone(),
+                }),
+            ));
+        }
+    }
+    let transitive_labels = transitive_closure(keys.len(), &distinct(keys.len(), &pairs));
+    let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut components: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (record, (&label, &component)) in labels.iter().zip(&transitive_labels).enumerate() {
+        members.entry(label).or_default().push(record);
+        let entities = components.entry(component).or_default();
+        if !entities.contains(&label) {
+            entities.push(label);
+        }
+    }
+    let strategy = clustering.strategy.name().to_string();
+    for (label, records) in members.iter().filter(|(_, records)| records.len() > 1) {
+        audit.push(AuditRecord::new(
+            ir,
+            AuditEvent::Merge(MergeEvent {
+                entity_id: keys[*label].to_string(),
+                record_keys: records.iter().map(|&r| keys[r].to_string()).collect(),
+                strategy: strategy.clone(),
+            }),
+        ));
+    }
+    for (component, entities) in components.iter().filter(|(_, entities)| entities.len() > 1) {
+        let mut entity_ids: Vec<String> = entities.iter().map(|&e| keys[e].to_string()).collect();
+        entity_ids.sort();
+        audit.push(AuditRecord::new(
+            ir,
+            AuditEvent::Split(SplitEvent {
+                component_id: keys[*component].to_string(),
+                entity_ids,
+                strategy: strategy.clone(),
+            }),
+        ));
+    }
+    let count = |labels: &[usize]| {
+        let mut sizes: BTreeMap<usize, usize> = BTreeMap::new();
+        for label in labels {
+            *sizes.entry(*label).or_default() += 1;
+        }
+        sizes
+    };
+    let sizes = count(&labels);
+    let transitive = count(&transitive_labels);
+
+    Ok(EntityClusters {
+        strategy: clustering.strategy,
+        threshold: clustering.threshold,
+        records: keys.len(),
+        pairs: pairs.len(),
+        clusters: sizes.len(),
+        largest_cluster: sizes.values().copied().max().unwrap_or(0),
+        transitive_clusters: transitive.len(),
+        overrides,
+        assignments: keys
+            .iter()
+            .zip(&labels)
+            .map(|(key, label)| ClusterAssignment {
+                record_key: key.to_string(),
+                cluster_id: keys[*label].to_string(),
+            })
+            .collect(),
+        audit,
+    })
+}
+
+fn cell(row: &[String], column: usize) -> &str {
+    row.get(column).map(String::as_str).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+    use crate::{diagnose_yaml, Severity};
+
+    #[test]
+    fn test_clustering_strategies_avoid_chain_merging() {
+        use crate::{
+            cluster_decisions, compute_diff, generate_plan, Clustering, Sample, ScoredPair,
+        };
+
+        let spec = format!(
+            "{}clustering:\n  strategy: hierarchical\n  threshold: 0.8\n",
+            MINIMAL
+        );
+        assert!(crate::validate_yaml(&spec).unwrap().is_empty());
+        let errors = |yaml: &str| -> Vec<(String, String)> {
+            diagnose_yaml(yaml)
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .map(|d| (d.code.to_string(), d.path.unwrap()))
+                .collect()
+        };
+        let pair = |code: &str, path: &str| (code.to_string(), path.to_string());
+        assert_eq!(
+            errors(&spec.replace("  threshold: 0.8\n", "")),
+            [pair("KNV0001", "clustering.threshold")]
+        );
+        assert_eq!(
+            errors(&spec.replace("threshold: 0.8", "threshold: 80")),
+            [pair("KNV0004", "clustering.threshold")]
+        );
+        assert_eq!(
+            errors(&spec.replace("strategy: hierarchical", "strategy: star")),
+            [pair("KNV0115", "clustering.threshold")]
+        );
+        assert_eq!(
+            errors(&spec.replace("strategy: hierarchical", "strategy: louvain")),
+            [pair("KNV0115", "clustering.strategy")]
+        );
+
+        let plan = generate_plan(&spec).unwrap();
+        assert_eq!(
+            plan.execution_stages[5].description,
+            "Average-linkage hierarchical clustering, cut at linkage 0.8"
+        );
+        assert!(plan
+            .summary
+            .contains("Clustering:   hierarchical (cut at 0.8)"));
+        // Without the section, the plan is unchanged.
+        let default_plan = generate_plan(MINIMAL).unwrap();
+        assert!(default_plan.clustering.is_none());
+        assert!(default_plan.execution_stages[5]
+            .description
+            .starts_with("Transitive closure"));
+        // A higher cut only merges less; any other change regroups entities.
+        let diff = |to: &str| compute_diff(&spec, to).unwrap().compatibility.bump;
+        assert_eq!(
+            diff(&spec.replace("threshold: 0.8", "threshold: 0.9")),
+            "patch"
+        );
+        assert_eq!(
+            diff(&spec.replace("threshold: 0.8", "threshold: 0.7")),
+            "minor"
+        );
+
+        // A chain 0-1-2-3 whose ends were compared and rejected, and a pair 4-5.
+        let scored = |left, right, score: f64| ScoredPair {
+            left,
+            right,
+            score,
+            matched: score >= 0.9,
+        };
+        let pairs = [
+            scored(0, 1, 0.95),
+            scored(1, 2, 0.92),
+            scored(2, 3, 0.94),
+            scored(0, 2, 0.4),
+            scored(4, 5, 0.97),
+        ];
+        let cluster = |strategy: &str| {
+            let yaml = spec.replace("hierarchical", strategy);
+            let yaml = if strategy == "hierarchical" {
+                yaml
+            } else {
+                yaml.replace("  threshold: 0.8\n", "")
+            };
+            let clustering = Clustering::from_spec(&crate::parse_yaml(&yaml).unwrap())
+                .unwrap()
+                .unwrap();
+            clustering.cluster(7, &pairs)
+        };
+        assert_eq!(cluster("transitive_closure"), [0, 0, 0, 0, 4, 4, 6]);
+        assert_eq!(cluster("star"), [0, 0, 0, 3, 4, 4, 6]);
+        assert_eq!(cluster("correlation_clustering"), [0, 0, 2, 2, 4, 4, 6]);
+        assert_eq!(cluster("hierarchical"), [0, 0, 2, 2, 4, 4, 6]);
+
+        let decisions = Sample::from_csv(
+        "left_key,right_key,score,decision\na,b,0.95,match\nb,c,0.92,match\nc,d,0.94,match\na,c,0.4,reject\n",
+    )
+    .unwrap();
+        let clusters = cluster_decisions(&spec, &decisions).unwrap();
+        assert_eq!(
+            (
+                clusters.records,
+                clusters.clusters,
+                clusters.transitive_clusters
+            ),
+            (4, 2, 1)
+        );
+        let ids: Vec<&str> = clusters
+            .assignments
+            .iter()
+            .map(|a| a.cluster_id.as_str())
+            .collect();
+        assert_eq!(ids, ["a", "a", "c", "c"]);
+    }
+}
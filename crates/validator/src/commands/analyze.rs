@@ -0,0 +1,117 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::path::Path;
+
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::sample::Sample;
+use crate::sensitivity::{analyze_thresholds, Outcomes};
+
+/// Merge, review and reject volumes on the pairs in `data` across match
+/// thresholds, comparing the spec's thresholds with any given.
+#[allow(clippy::too_many_arguments)]
+pub fn thresholds(
+    file: &Path,
+    data: &Path,
+    match_threshold: Option<f64>,
+    review: Option<f64>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let mut analysis = analyze_thresholds(&content, &Sample::load(data)?)?;
+    if match_threshold.is_some() || review.is_some() {
+        analysis.propose(match_threshold, review)?;
+    }
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&analysis)?);
+        return Ok(());
+    }
+
+    let scored = match analysis.records {
+        Some(records) => format!(" from {} records", records),
+        None => String::new(),
+    };
+    let review = analysis
+        .proposed
+        .map_or(analysis.review_threshold, |p| p.review_threshold);
+    out.info(format!(
+        "{} {} pairs{}{}",
+        "Threshold sensitivity:".bold(),
+        analysis.pairs,
+        scored,
+        review
+            .map(|r| format!(", review at {}", r))
+            .unwrap_or_default()
+    ));
+    for warning in &analysis.warnings {
+        out.warn(format!("{} {}", out.warn_mark(), warning));
+    }
+    out.result(format!(
+        "  {:>5}  {:>7}  {:>7}  {:>7}",
+        "MATCH", "MERGE", "REVIEW", "REJECT"
+    ));
+    let marks = |t: f64| {
+        let is = |o: &Option<Outcomes>| o.is_some_and(|o| (o.match_threshold - t).abs() < 1e-9);
+        match (is(&analysis.current), is(&analysis.proposed)) {
+            (true, true) => "  (current, proposed)",
+            (true, false) => "  (current)",
+            (false, true) => "  (proposed)",
+            (false, false) => "",
+        }
+    };
+    for point in &analysis.curve {
+        let line = format!(
+            "  {:>5}  {:>7}  {:>7}  {:>7}{}",
+            format!("{:.2}", point.match_threshold),
+            point.merge,
+            point.review,
+            point.reject,
+            marks(point.match_threshold)
+        );
+        if marks(point.match_threshold).is_empty() {
+            out.result(line);
+        } else {
+            out.result(line.bold().to_string());
+        }
+    }
+
+    out.result("");
+    match &analysis.current {
+        Some(current) => out.result(format!("{} {}", "Current: ".bold(), describe(current))),
+        None => out.result(format!(
+            "{} {} the spec sets no match threshold",
+            "Current: ".bold(),
+            out.dash()
+        )),
+    }
+    if let Some(proposed) = &analysis.proposed {
+        out.result(format!("{} {}", "Proposed:".bold(), describe(proposed)));
+        if let Some(current) = &analysis.current {
+            let delta = |now: usize, then: usize| format!("{:+}", then as i64 - now as i64);
+            out.result(format!(
+                "           {} merge {}, review {}, reject {}",
+                out.arrow(),
+                delta(current.merge, proposed.merge),
+                delta(current.review, proposed.review),
+                delta(current.reject, proposed.reject)
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn describe(outcomes: &Outcomes) -> String {
+    let review = outcomes
+        .review_threshold
+        .map(|r| format!(", review {}", r))
+        .unwrap_or_default();
+    format!(
+        "match {}{}: {} merge, {} review, {} reject",
+        outcomes.match_threshold, review, outcomes.merge, outcomes.review, outcomes.reject
+    )
+}
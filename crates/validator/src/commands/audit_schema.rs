@@ -0,0 +1,23 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::audit::audit_json_schema;
+use crate::output::Output;
+
+pub fn export(format: &str, output: Option<&Path>, out: &Output) -> Result<()> {
+    let rendered = match format {
+        "json-schema" => serde_json::to_string_pretty(&audit_json_schema())?,
+        other => bail!("Unknown schema format '{}'. Expected: json-schema", other),
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(path, rendered + "\n")
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            out.info(format!("{} Wrote {}", out.ok_mark(), path.display()));
+        }
+        None => out.result(rendered),
+    }
+    Ok(())
+}
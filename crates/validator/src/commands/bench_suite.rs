@@ -0,0 +1,138 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::bench::{built_in, load_dataset, regressions, run_dataset, BenchResult, DATASETS};
+use crate::cancel::CancellationToken;
+use crate::clock;
+use crate::compose;
+use crate::embedding::HttpEmbedder;
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::synthetic::GenerateOptions;
+
+/// Run each dataset (a built-in name or a directory; all built-in ones if
+/// none are given) with `spec` or its own, and report precision, recall
+/// and F1, failing if any fell more than `tolerance` below `baseline`.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    datasets: &[String],
+    spec: Option<&Path>,
+    options: &GenerateOptions,
+    baseline: Option<&Path>,
+    tolerance: f64,
+    cancel: Option<&CancellationToken>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    if !(tolerance.is_finite() && tolerance >= 0.0) {
+        bail!(
+            "The tolerance must be a number of at least 0, not {}",
+            tolerance
+        );
+    }
+    let spec = spec
+        .map(|file| compose::read_spec(file, entity, variables))
+        .transpose()?;
+    let names: Vec<String> = match datasets.is_empty() {
+        true => DATASETS.iter().map(|d| d.name.to_string()).collect(),
+        false => datasets.to_vec(),
+    };
+    let baseline = baseline.map(read_baseline).transpose()?;
+
+    let embedder = HttpEmbedder::new()?;
+    let today = clock::today();
+    let mut results = Vec::new();
+    for name in &names {
+        let path = Path::new(name);
+        let dataset = match path.is_dir() {
+            true => load_dataset(path)?,
+            false => built_in(name, options)?,
+        };
+        out.detail(format!("Running {}", dataset.name));
+        results.push(run_dataset(&dataset, spec.as_deref(), &embedder, cancel, &today)?);
+    }
+    let regressed = baseline
+        .as_deref()
+        .map(|baseline| regressions(&results, baseline, tolerance))
+        .unwrap_or_default();
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&json!({
+            "passed": regressed.is_empty(),
+            "results": results,
+            "regressions": regressed,
+        }))?);
+    } else {
+        for result in &results {
+            let base = baseline
+                .as_deref()
+                .and_then(|b| b.iter().find(|b| b.dataset == result.dataset));
+            let delta = match base {
+                Some(base) => {
+                    // Rounded first (and -0 made 0), as JSON does not keep
+                    // the last bit of an f64: no change prints as +0.000.
+                    let change = ((result.f1 - base.f1) * 1000.0).round() / 1000.0 + 0.0;
+                    format!(" ({:+.3} vs baseline)", change)
+                }
+                None => String::new(),
+            };
+            out.result(format!(
+                "{} {} records: precision {:.3}, recall {:.3}, F1 {:.3}{}",
+                format!("{}:", result.dataset).bold(),
+                result.records,
+                result.precision,
+                result.recall,
+                result.f1,
+                delta
+            ));
+            out.detail(format!(
+                "  {} true entities {} {} predicted; blocking compared {:.1}% of true pairs; ARI {:.3}; {:.2}s",
+                result.true_entities,
+                out.arrow(),
+                result.predicted_entities,
+                result.candidate_recall * 100.0,
+                result.adjusted_rand_index,
+                result.seconds
+            ));
+        }
+        for regression in &regressed {
+            out.warn(format!(
+                "{} {}: {} fell from {:.3} to {:.3}",
+                out.warn_mark(),
+                regression.dataset,
+                regression.metric,
+                regression.baseline,
+                regression.actual
+            ));
+        }
+    }
+
+    if !regressed.is_empty() {
+        bail!(
+            "{} metric(s) fell more than {} below the baseline",
+            regressed.len(),
+            tolerance
+        );
+    }
+    Ok(())
+}
+
+/// The results of an earlier `bench-suite --format json` run.
+fn read_baseline(path: &Path) -> Result<Vec<BenchResult>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read baseline: {}", path.display()))?;
+    let value: Value = serde_json::from_str(&text)
+        .with_context(|| format!("Invalid JSON in {}", path.display()))?;
+    let results = value.get("results").cloned().unwrap_or(value);
+    serde_json::from_value(results).with_context(|| {
+        format!(
+            "{} is not bench-suite results (run it with --format json)",
+            path.display()
+        )
+    })
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::path::Path;
+
+use crate::calibration::{calibrate, CurvePoint};
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::sample::Sample;
+
+/// Score the spec's match rules on the labeled pairs in `labels`.
+pub fn run(
+    file: &Path,
+    labels: &Path,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let calibration = calibrate(&content, &Sample::load(labels)?)?;
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&calibration)?);
+        return Ok(());
+    }
+
+    out.info(format!(
+        "{} {} labeled pairs ({} matches)",
+        "Calibration:".bold(),
+        calibration.pairs,
+        calibration.matches
+    ));
+    out.result(format!(
+        "  {:<20}  {:<6}  {:>5}  {:>9}  {:>6}  {:<14}  WEIGHT",
+        "RULE", "TYPE", "PAIRS", "PRECISION", "RECALL", "THRESHOLD"
+    ));
+    let change = |current: Option<f64>, recommended: Option<f64>| match (current, recommended) {
+        (Some(c), Some(r)) if (c - r).abs() > 1e-9 => format!("{} {} {}", c, out.arrow(), r),
+        (None, Some(r)) => format!("- {} {}", out.arrow(), r),
+        (Some(c), _) => c.to_string(),
+        (None, None) => "-".to_string(),
+    };
+    for rule in &calibration.rules {
+        let (precision, recall) = match &rule.current {
+            Some(p) => (format!("{:.2}", p.precision), format!("{:.2}", p.recall)),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let pairs = if rule.pairs == 0 {
+            "(none)".dimmed().to_string()
+        } else {
+            rule.pairs.to_string()
+        };
+        out.result(format!(
+            "  {:<20}  {:<6}  {:>5}  {:>9}  {:>6}  {:<14}  {}",
+            rule.rule_name,
+            rule.match_type,
+            pairs,
+            precision,
+            recall,
+            change(rule.threshold, rule.recommended_threshold),
+            change(Some(rule.weight), rule.recommended_weight)
+        ));
+    }
+
+    let decision = &calibration.decision;
+    let stats = |p: &CurvePoint| format!("precision {:.2}, recall {:.2}", p.precision, p.recall);
+    let recommended = decision
+        .curve
+        .iter()
+        .find(|p| p.threshold == decision.recommended_match);
+    out.result("");
+    out.result(format!("{}:", "Decision thresholds".bold()));
+    out.result(format!(
+        "  match: {}{}",
+        change(decision.match_threshold, Some(decision.recommended_match)),
+        recommended
+            .map(|p| format!(" ({})", stats(p)))
+            .unwrap_or_default()
+    ));
+    if let Some(current) = &decision.current {
+        out.result(format!("    now: {}", stats(current)));
+    }
+    if decision.review_threshold.is_some() || decision.recommended_review.is_some() {
+        out.result(format!(
+            "  review: {}",
+            change(decision.review_threshold, decision.recommended_review)
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,589 @@
+use anyhow::{bail, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::batch::{self, BatchOptions};
+use crate::cache::{Cache, CacheStats};
+use crate::canonical::canonical_hash;
+use crate::commands::validate::format_diagnostic;
+use crate::compose;
+use crate::diagnostics::{codes, Diagnostic, Profile, Tiers};
+use crate::output::Output;
+use crate::parser::{self, SourceMap};
+use crate::workspace;
+
+/// How a spec file fared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Valid,
+    Invalid,
+    /// Extended by other specs, and checked as part of them.
+    Base,
+}
+
+/// One spec file of a repository check.
+#[derive(Debug, Serialize)]
+pub struct FileCheck {
+    /// Relative to the directory checked.
+    pub path: String,
+    pub status: FileStatus,
+    /// Names of the entities the file defines.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<String>,
+    /// The specs extending a base.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extended_by: Vec<String>,
+    /// Its own findings, then those across specs.
+    #[serde(flatten)]
+    pub findings: Tiers,
+}
+
+/// Every spec file under a directory, validated and checked against one
+/// another.
+#[derive(Debug, Serialize)]
+pub struct RepoCheck {
+    pub files: Vec<FileCheck>,
+    pub entities: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Files answered from the cache, and those validated, when cached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheStats>,
+}
+
+impl RepoCheck {
+    pub fn is_valid(&self) -> bool {
+        self.errors == 0
+    }
+}
+
+/// An entity spec, as far as the checks across specs need it.
+#[derive(Debug, Serialize, Deserialize)]
+struct Document {
+    /// Path prefix within the file: `entities[i].` in a workspace.
+    prefix: String,
+    name: String,
+    /// Its `identity_version`, and the hash of the spec.
+    version: Option<(String, String)>,
+    /// Each source's name and system, by index.
+    sources: Vec<(usize, String, String)>,
+}
+
+/// A spec file's findings and entities, as cached.
+#[derive(Debug, Serialize, Deserialize)]
+struct Checked {
+    findings: Tiers,
+    documents: Vec<Document>,
+}
+
+/// An entity of one of the files checked.
+struct Entity {
+    /// Index of its file.
+    file: usize,
+    /// What variants of one entity have in common: the base they extend,
+    /// or else the file itself.
+    family: String,
+    document: Document,
+}
+
+/// Validate every spec file under `root` (see `compose::find_specs`) and
+/// check them against one another: entity names are unique except among
+/// variants extending one base, a source name always means one system and
+/// a system is always spelled one way, and an `identity_version` is not
+/// reused for a different spec. Base files other specs extend are checked
+/// as part of those specs.
+pub fn check_repo(root: &Path, profile: Profile) -> Result<RepoCheck> {
+    check_repo_with(
+        root,
+        &BatchOptions {
+            profile,
+            ..Default::default()
+        },
+    )
+}
+
+/// As `check_repo`, validating files across `options.jobs` workers (see
+/// `batch`), and with `options.cache` only files that changed. Only what
+/// the checks across specs compare is kept of each.
+pub fn check_repo_with(root: &Path, options: &BatchOptions) -> Result<RepoCheck> {
+    let profile = options.profile;
+    let specs = compose::find_specs(root)?;
+    if specs.is_empty() {
+        bail!("No spec files under {}", root.display());
+    }
+    let relative = |path: &Path| {
+        path.strip_prefix(root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+
+    // Bases first: a spec extending a file found here makes it a base.
+    let canonical: Vec<PathBuf> = batch::map(&specs, options.jobs, |path| {
+        fs::canonicalize(path).unwrap_or_else(|_| path.clone())
+    });
+    let references = batch::map(&specs, options.jobs, |path| {
+        let text = compose::read_file(path, &options.variables).unwrap_or_default();
+        compose::extends_references(&text).into_iter().next()
+    });
+    let mut families: Vec<String> = Vec::new();
+    let mut extended_by: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for (path, reference) in specs.iter().zip(references) {
+        let family = match reference {
+            // Variants may extend different versions of one published spec.
+            Some(reference) => match reference.strip_prefix("registry://") {
+                Some(published) => published.split('@').next().unwrap_or_default().to_string(),
+                None => {
+                    let base = path.parent().unwrap_or(Path::new("")).join(&reference);
+                    let base = fs::canonicalize(&base).unwrap_or(base);
+                    match canonical.iter().position(|c| *c == base) {
+                        Some(b) => {
+                            extended_by.entry(b).or_default().push(relative(path));
+                            relative(&specs[b])
+                        }
+                        None => reference,
+                    }
+                }
+            },
+            None => relative(path),
+        };
+        families.push(family);
+    }
+
+    let indices: Vec<usize> = (0..specs.len()).collect();
+    let checked = batch::map(&indices, options.jobs, |&i| {
+        match extended_by.contains_key(&i) {
+            true => None,
+            false => Some(check_file(&specs[i], options)),
+        }
+    });
+    let mut files = Vec::new();
+    let mut entities: Vec<Entity> = Vec::new();
+    for (i, checked) in checked.into_iter().enumerate() {
+        let mut file = FileCheck {
+            path: relative(&specs[i]),
+            status: FileStatus::Valid,
+            entities: Vec::new(),
+            extended_by: extended_by.remove(&i).unwrap_or_default(),
+            findings: Tiers::default(),
+        };
+        match checked {
+            None => file.status = FileStatus::Base,
+            Some(checked) => {
+                file.findings = checked.findings;
+                file.entities = checked
+                    .documents
+                    .iter()
+                    .filter(|d| !d.name.is_empty())
+                    .map(|d| d.name.clone())
+                    .collect();
+                entities.extend(checked.documents.into_iter().map(|document| Entity {
+                    file: i,
+                    family: families[i].clone(),
+                    document,
+                }));
+            }
+        }
+        files.push(file);
+    }
+
+    let mut findings: Vec<Vec<Diagnostic>> = vec![Vec::new(); files.len()];
+    let mut names: HashMap<&str, &Entity> = HashMap::new();
+    let mut versions: HashMap<&str, (&Entity, &str)> = HashMap::new();
+    let mut source_systems: HashMap<&str, (&str, usize)> = HashMap::new();
+    let mut spellings: HashMap<String, (&str, usize)> = HashMap::new();
+    for entity in &entities {
+        let (file, document) = (entity.file, &entity.document);
+        let (prefix, name) = (&document.prefix, document.name.as_str());
+        match names.get(name) {
+            // A workspace's own duplicates are reported by validation.
+            Some(first) if first.file != file && first.family != entity.family => {
+                findings[file].push(Diagnostic::error(
+                    codes::DUPLICATE_ENTITY,
+                    format!("{}entity.name", prefix),
+                    format!(
+                        "Entity '{}' is also defined in {}",
+                        name, files[first.file].path
+                    ),
+                ));
+            }
+            Some(_) => {}
+            None if name.is_empty() => {}
+            None => {
+                names.insert(name, entity);
+            }
+        }
+
+        if let Some((version, hash)) = &document.version {
+            match versions.get(version.as_str()) {
+                Some((first, first_hash)) if first_hash != hash => {
+                    findings[file].push(Diagnostic::error(
+                        codes::IDENTITY_VERSION_REUSE,
+                        format!("{}identity_version", prefix),
+                        format!(
+                            "identity_version '{}' is also used in {}, for a different spec",
+                            version, files[first.file].path
+                        ),
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    versions.insert(version, (entity, hash));
+                }
+            }
+        }
+
+        for (k, source_name, system) in &document.sources {
+            let (source_name, system) = (source_name.as_str(), system.as_str());
+            let path = format!("{}sources[{}].system", prefix, k);
+            let key = system_key(system);
+            match spellings.get(&key) {
+                Some((spelled, first)) if *spelled != system => {
+                    findings[file].push(Diagnostic::warning(
+                        codes::INCONSISTENT_SOURCE_SYSTEM,
+                        &path,
+                        format!(
+                            "System '{}' is spelled '{}' in {}",
+                            system, spelled, files[*first].path
+                        ),
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    spellings.insert(key.clone(), (system, file));
+                }
+            }
+            match source_systems.get(source_name) {
+                Some((other, first)) if system_key(other) != key => {
+                    findings[file].push(Diagnostic::warning(
+                        codes::INCONSISTENT_SOURCE_SYSTEM,
+                        &path,
+                        format!(
+                            "Source '{}' is system '{}' here but '{}' in {}",
+                            source_name, system, other, files[*first].path
+                        ),
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    source_systems.insert(source_name, (system, file));
+                }
+            }
+        }
+    }
+
+    for ((file, mut found), path) in files.iter_mut().zip(findings).zip(&specs) {
+        if found.is_empty() {
+            continue;
+        }
+        // Specs are not kept for the few files with findings across specs.
+        if let Ok(text) =
+            compose::read_workspace(path, options.entity.as_deref(), &options.variables)
+        {
+            let map = SourceMap::from_yaml(&text);
+            for diagnostic in &mut found {
+                diagnostic.locate(&map);
+            }
+        }
+        file.findings.extend(found, profile);
+    }
+    for file in &mut files {
+        if file.status == FileStatus::Valid && !file.findings.is_valid() {
+            file.status = FileStatus::Invalid;
+        }
+    }
+    Ok(RepoCheck {
+        entities: files.iter().map(|f| f.entities.len()).sum(),
+        errors: files.iter().map(|f| f.findings.errors.len()).sum(),
+        warnings: files.iter().map(|f| f.findings.warnings.len()).sum(),
+        cache: options.cache.as_ref().map(Cache::stats),
+        files,
+    })
+}
+
+/// Validate the spec file at `path` and summarize the entities it defines.
+fn check_file(path: &Path, options: &BatchOptions) -> Checked {
+    let (content, policy) =
+        match batch::read_spec(path, options.entity.as_deref(), &options.variables) {
+            Ok(read) => read,
+            Err(e) => {
+                return Checked {
+                    findings: batch::unreadable(&e, options.profile),
+                    documents: Vec::new(),
+                }
+            }
+        };
+    let check = || Checked {
+        findings: crate::validate_tiers_with(&content, options.profile, &policy),
+        documents: documents(&content),
+    };
+    match &options.cache {
+        Some(cache) => {
+            let key = batch::cache_key("check", &content, options.profile, &policy);
+            cache.get_or_insert(&key.finish(), check)
+        }
+        None => check(),
+    }
+}
+
+/// The entity specs of a spec file: itself, or a workspace's.
+fn documents(content: &str) -> Vec<Document> {
+    let spec = parser::parse_yaml_recovering(content).value;
+    let documents = match workspace::is_workspace(&spec) {
+        true => spec["entities"].as_array().cloned().unwrap_or_default(),
+        false => vec![spec],
+    };
+    let many = documents.len() > 1;
+    documents
+        .iter()
+        .enumerate()
+        .map(|(j, document)| Document {
+            prefix: if many {
+                format!("entities[{}].", j)
+            } else {
+                String::new()
+            },
+            name: document
+                .pointer("/entity/name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            version: document
+                .get("identity_version")
+                .and_then(Value::as_str)
+                .map(|version| (version.to_string(), canonical_hash(document))),
+            sources: document
+                .get("sources")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .enumerate()
+                .filter_map(|(k, source)| {
+                    let name = source.get("name").and_then(Value::as_str)?;
+                    let system = source.get("system").and_then(Value::as_str)?;
+                    Some((k, name.to_string(), system.to_string()))
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// A system name with case and punctuation dropped, so that spellings of
+/// one system compare equal.
+fn system_key(system: &str) -> String {
+    system
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// `kanoniv check`: report each spec file under `root`, then the totals,
+/// failing if any file has errors.
+pub fn run(root: &Path, format: &str, options: &BatchOptions, out: &Output) -> Result<()> {
+    let check = check_repo_with(root, options)?;
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&check)?);
+    } else {
+        for file in &check.files {
+            let path = Path::new(&file.path);
+            let entities = match file.entities.is_empty() {
+                true => String::new(),
+                false => format!("  {}", file.entities.join(", ").dimmed()),
+            };
+            match file.status {
+                FileStatus::Base => out.info(format!(
+                    "{} {}  base of {}",
+                    out.dash(),
+                    file.path,
+                    file.extended_by.join(", ")
+                )),
+                FileStatus::Valid => {
+                    out.info(format!("{} {}{}", out.ok_mark(), file.path, entities))
+                }
+                FileStatus::Invalid => out.error(format!(
+                    "{} {}{}: {} error(s)",
+                    out.fail_mark(),
+                    file.path,
+                    entities,
+                    file.findings.errors.len()
+                )),
+            }
+            for diagnostic in &file.findings.errors {
+                out.error(format_diagnostic(path, diagnostic, out));
+            }
+            for diagnostic in &file.findings.warnings {
+                out.warn(format_diagnostic(path, diagnostic, out));
+            }
+            for diagnostic in &file.findings.info {
+                out.detail(format_diagnostic(path, diagnostic, out));
+            }
+        }
+        let invalid = check
+            .files
+            .iter()
+            .filter(|f| f.status == FileStatus::Invalid)
+            .count();
+        let summary = format!(
+            "Checked {} spec file(s), {} entities: {} invalid, {} error(s), {} warning(s)",
+            check.files.len(),
+            check.entities,
+            invalid,
+            check.errors,
+            check.warnings
+        );
+        match check.is_valid() {
+            true => out.info(format!("{} {}", out.ok_mark(), summary)),
+            false => out.error(format!("{} {}", out.fail_mark(), summary)),
+        }
+        if let (Some(stats), Some(cache)) = (check.cache, &options.cache) {
+            out.info(format!(
+                "  {} cache: {} unchanged, {} validated ({})",
+                out.dash(),
+                stats.hits,
+                stats.misses,
+                cache.dir().display()
+            ));
+        }
+    }
+    if !check.is_valid() {
+        bail!(
+            "{} error(s) across the specs under {}",
+            check.errors,
+            root.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+    use crate::Profile;
+
+    #[test]
+    fn test_check_repo_enforces_invariants_across_specs() {
+        use crate::{check_repo, FileStatus};
+
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, text: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        };
+        write("specs/customer.yaml", MINIMAL);
+        write(
+            "specs/household.yaml",
+            &MINIMAL
+                .replace("name: customer", "name: household")
+                .replace("retail_v1.0", "household_v1")
+                .replace("system: salesforce", "system: Salesforce"),
+        );
+        // Variants of one base share its entity; the base is checked through them.
+        write(
+            "specs/regions/base.yaml",
+            &MINIMAL
+                .replace("name: customer", "name: account")
+                .replace("retail_v1.0", "account_v1"),
+        );
+        write(
+            "specs/regions/emea.yaml",
+            "extends: base.yaml\nidentity_version: account_emea_v1\n",
+        );
+        write(
+            "specs/regions/apac.yaml",
+            "extends: base.yaml\nidentity_version: account_apac_v1\n",
+        );
+        // Other YAML is not a spec.
+        write(".github/workflows/ci.yml", "on: push\n");
+        write("rules/pack.yaml", "checks: []\n");
+
+        let check = check_repo(dir.path(), Profile::Default).unwrap();
+        let paths: Vec<(&str, FileStatus)> = check
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.status))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("specs/customer.yaml", FileStatus::Valid),
+                ("specs/household.yaml", FileStatus::Valid),
+                ("specs/regions/apac.yaml", FileStatus::Valid),
+                ("specs/regions/base.yaml", FileStatus::Base),
+                ("specs/regions/emea.yaml", FileStatus::Valid),
+            ]
+        );
+        assert!(check.is_valid());
+        assert_eq!(check.entities, 4);
+        assert_eq!(
+            check.files[3].extended_by,
+            ["specs/regions/apac.yaml", "specs/regions/emea.yaml"]
+        );
+        let household = &check.files[1].findings.warnings;
+        let spelling = household.iter().find(|d| d.code == "KNV0133").unwrap();
+        assert_eq!(
+            spelling.message,
+            "System 'Salesforce' is spelled 'salesforce' in specs/customer.yaml"
+        );
+        assert_eq!(spelling.path.as_deref(), Some("sources[0].system"));
+        assert_eq!(spelling.span.map(|s| s.line), Some(7));
+
+        // A second customer, reusing the first's identity_version for another spec.
+        write(
+            "specs/customer_copy.yaml",
+            &MINIMAL
+                .replace("match: 0.9", "match: 0.8")
+                .replace("system: salesforce", "system: hubspot"),
+        );
+        let check = check_repo(dir.path(), Profile::Default).unwrap();
+        assert!(!check.is_valid());
+        let copy = check
+            .files
+            .iter()
+            .find(|f| f.path == "specs/customer_copy.yaml")
+            .unwrap();
+        assert_eq!(copy.status, FileStatus::Invalid);
+        let messages: Vec<(&str, &str)> = copy
+            .findings
+            .errors
+            .iter()
+            .chain(
+                copy.findings
+                    .warnings
+                    .iter()
+                    .filter(|d| d.code == "KNV0133"),
+            )
+            .map(|d| (d.code.as_str(), d.message.as_str()))
+            .collect();
+        assert_eq!(
+        messages,
+        [
+            ("KNV0120", "Entity 'customer' is also defined in specs/customer.yaml"),
+            ("KNV0134", "identity_version 'retail_v1.0' is also used in specs/customer.yaml, for a different spec"),
+            ("KNV0133", "Source 'crm' is system 'hubspot' here but 'salesforce' in specs/customer.yaml"),
+        ]
+    );
+
+        write(
+            "specs/broken.yaml",
+            "extends: missing.yaml\nidentity_version: broken_v1\n",
+        );
+        let check = check_repo(dir.path(), Profile::Default).unwrap();
+        let broken = check
+            .files
+            .iter()
+            .find(|f| f.path == "specs/broken.yaml")
+            .unwrap();
+        assert_eq!(broken.findings.errors[0].code, "KNV0903");
+        assert!(check_repo(&dir.path().join("rules"), Profile::Default)
+            .unwrap_err()
+            .to_string()
+            .starts_with("No spec files under"));
+    }
+}
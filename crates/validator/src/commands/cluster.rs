@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+use crate::audit::to_json_lines;
+use crate::clustering::{cluster_decisions_with, EntityClusters};
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::openlineage::{self, Dataset};
+use crate::output::Output;
+use crate::review::{apply_verdicts, review_pairs, ReviewQueue};
+use crate::sample::Sample;
+use crate::stewardship::Stewardship;
+
+/// Cluster the pairs in `decisions` with the spec's clustering strategy and
+/// write the assignments to `output` (or stdout, with the summary on
+/// stderr). With `review_queue`, pairs in the review band are queued (with
+/// their records from `records`) and those already decided cluster as the
+/// stewards decided them. Stewardship assertions from `stewardship` (or the
+/// spec's sidecar file) outrank both.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: &Path,
+    decisions: &Path,
+    output: Option<&Path>,
+    audit: Option<&Path>,
+    review_queue: Option<&str>,
+    records: Option<&Path>,
+    stewardship: Option<&Path>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let stewardship = Stewardship::discover(file, stewardship)?;
+    let inputs = [Some(decisions), records].into_iter().flatten();
+    let outputs = [output, audit, review_queue.map(Path::new)]
+        .into_iter()
+        .flatten();
+    let warn = |msg: String| out.warn(format!("{} {}", out.warn_mark(), msg));
+    let (clusters, csv, review) = openlineage::observe(
+        &content,
+        inputs.map(Dataset::file).collect(),
+        outputs.map(Dataset::file).collect(),
+        warn,
+        || {
+            let mut decisions = Sample::load(decisions)?;
+            // Pairs queued, and verdicts applied.
+            let mut review = None;
+            if let Some(location) = review_queue {
+                let records = records.map(Sample::load).transpose()?;
+                let queue = ReviewQueue::open(location)?;
+                let queued =
+                    queue.enqueue(review_pairs(&content, &decisions, records.as_ref())?)?;
+                let (applied, changed) = apply_verdicts(&decisions, &queue.items()?)?;
+                decisions = applied;
+                review = Some((queued, changed));
+            }
+            let clusters = cluster_decisions_with(&content, &decisions, &stewardship)?;
+            let csv = assignments_csv(&clusters)?;
+            if let Some(path) = output {
+                fs::write(path, &csv)
+                    .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            }
+            if let Some(path) = audit {
+                fs::write(path, to_json_lines(&clusters.audit)?)
+                    .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            }
+            Ok((clusters, csv, review))
+        },
+    )?;
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&clusters)?);
+        return Ok(());
+    }
+
+    // The assignments may go to stdout; the summary must not mix with it.
+    let note = |msg: String| {
+        if output.is_some() {
+            out.info(msg)
+        } else {
+            out.warn(msg)
+        }
+    };
+    let cut = clusters
+        .threshold
+        .map_or(String::new(), |t| format!(", cut at {}", t));
+    note(format!(
+        "{} {} records, {} pairs {} {} clusters ({}{}), largest {}",
+        "Clusters:".bold(),
+        clusters.records,
+        clusters.pairs,
+        out.arrow(),
+        clusters.clusters,
+        clusters.strategy,
+        cut,
+        clusters.largest_cluster
+    ));
+    if clusters.transitive_clusters != clusters.clusters {
+        note(format!(
+            "  Transitive closure would form {} clusters",
+            clusters.transitive_clusters
+        ));
+    }
+    if clusters.overrides > 0 {
+        note(format!(
+            "  {} stewardship assertion(s) honored",
+            clusters.overrides
+        ));
+    }
+
+    if let (Some(location), Some((queued, applied))) = (review_queue, review) {
+        note(format!(
+            "{} Review queue {}: {} pair(s) queued, {} verdict(s) applied",
+            out.ok_mark(),
+            location,
+            queued,
+            applied
+        ));
+    }
+    if let Some(path) = audit {
+        note(format!(
+            "{} Wrote audit trail: {}",
+            out.ok_mark(),
+            path.display()
+        ));
+    }
+    match output {
+        Some(path) => out.info(format!("{} Wrote {}", out.ok_mark(), path.display())),
+        None => print!("{}", csv),
+    }
+    Ok(())
+}
+
+fn assignments_csv(clusters: &EntityClusters) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["record_key", "cluster_id"])?;
+    for assignment in &clusters.assignments {
+        writer.write_record([&assignment.record_key, &assignment.cluster_id])?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
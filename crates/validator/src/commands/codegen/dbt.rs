@@ -0,0 +1,159 @@
+use anyhow::Result;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::sql::{stage_queries, Dialect, Naming};
+use super::GeneratedFile;
+use crate::ir::Ir;
+
+/// Generate a dbt project where each execution stage is a model wired
+/// together with `ref()`, plus `sources.yml` and schema tests.
+pub fn generate_dbt_project(ir: &Ir, dialect: Dialect) -> Result<Vec<GeneratedFile>> {
+    let entity = ir.entity_name();
+    let naming = Naming::Dbt { entity };
+    let stages = stage_queries(ir, dialect, &naming)?;
+    let project = project_name(entity);
+
+    let mut files = vec![
+        GeneratedFile {
+            path: PathBuf::from("dbt_project.yml"),
+            contents: dbt_project_yml(ir, &project, entity)?,
+        },
+        GeneratedFile {
+            path: PathBuf::from("models/sources.yml"),
+            contents: sources_yml(ir)?,
+        },
+        GeneratedFile {
+            path: PathBuf::from("models/schema.yml"),
+            contents: schema_yml(&naming)?,
+        },
+    ];
+
+    for stage in &stages {
+        let materialized = if stage.stage == 1 { "view" } else { "table" };
+        let mut contents = format!(
+            "-- Stage {}: {}\n-- Depends on: {}\n{{{{ config(materialized='{}') }}}}\n\n",
+            stage.stage,
+            stage.title,
+            if stage.depends_on.is_empty() {
+                "sources".to_string()
+            } else {
+                stage.depends_on.join(", ")
+            },
+            materialized
+        );
+        contents.push_str(&stage.sql);
+        contents.push('\n');
+        files.push(GeneratedFile {
+            path: PathBuf::from("models")
+                .join(entity)
+                .join(format!("{}.sql", naming.relation_name(&stage.output))),
+            contents,
+        });
+    }
+
+    Ok(files)
+}
+
+fn project_name(entity: &str) -> String {
+    let cleaned: String = entity
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("kanoniv_{}", cleaned)
+}
+
+fn dbt_project_yml(ir: &Ir, project: &str, entity: &str) -> Result<String> {
+    let mut models = BTreeMap::new();
+    models.insert(
+        project.to_string(),
+        json!({ entity: { "+tags": ["kanoniv", "identity"] } }),
+    );
+    let doc = json!({
+        "name": project,
+        "version": "1.0.0",
+        "config-version": 2,
+        "profile": "kanoniv",
+        "model-paths": ["models"],
+        "vars": {
+            "kanoniv_identity_version": ir.identity_version,
+            "kanoniv_plan_hash": ir.plan_hash,
+        },
+        "models": models,
+    });
+    Ok(format!(
+        "# Generated by kanoniv; regenerate with `kanoniv compile --target dbt`\n{}",
+        serde_yaml::to_string(&doc)?
+    ))
+}
+
+fn sources_yml(ir: &Ir) -> Result<String> {
+    // dbt groups tables under a source name; use each spec source's system.
+    let mut systems: BTreeMap<String, Vec<serde_json::Value>> = BTreeMap::new();
+    for source in &ir.sources {
+        let system = source.system.clone().unwrap_or_else(|| source.name.clone());
+        let table = source.table.clone().unwrap_or_else(|| source.name.clone());
+        let columns: Vec<serde_json::Value> = source
+            .id
+            .iter()
+            .map(|id| json!({ "name": id, "tests": ["not_null"] }))
+            .collect();
+        systems.entry(system).or_default().push(json!({
+            "name": table,
+            "description": format!("Kanoniv source '{}'", source.name),
+            "columns": columns,
+        }));
+    }
+    let sources: Vec<serde_json::Value> = systems
+        .into_iter()
+        .map(|(name, tables)| json!({ "name": name, "tables": tables }))
+        .collect();
+    Ok(serde_yaml::to_string(
+        &json!({ "version": 2, "sources": sources }),
+    )?)
+}
+
+fn schema_yml(naming: &Naming) -> Result<String> {
+    let not_null_unique = json!(["not_null", "unique"]);
+    let models = vec![
+        json!({
+            "name": naming.relation_name("normalized_entities"),
+            "columns": [{ "name": "record_key", "tests": not_null_unique }],
+        }),
+        json!({
+            "name": naming.relation_name("candidate_pairs"),
+            "columns": [
+                { "name": "left_key", "tests": ["not_null"] },
+                { "name": "right_key", "tests": ["not_null"] },
+            ],
+        }),
+        json!({
+            "name": naming.relation_name("match_decisions"),
+            "columns": [{
+                "name": "decision",
+                "tests": [{ "accepted_values": { "values": ["match", "review", "reject"] } }],
+            }],
+        }),
+        json!({
+            "name": naming.relation_name("entity_clusters"),
+            "columns": [
+                { "name": "record_key", "tests": not_null_unique },
+                { "name": "cluster_id", "tests": ["not_null"] },
+            ],
+        }),
+        json!({
+            "name": naming.relation_name("canonical_entities"),
+            "columns": [{ "name": "entity_id", "tests": not_null_unique }],
+        }),
+    ];
+    Ok(serde_yaml::to_string(
+        &json!({ "version": 2, "models": models }),
+    )?)
+}
@@ -0,0 +1,544 @@
+use anyhow::{bail, Result};
+use std::fmt::Write;
+
+use super::pyspark::{encode_call, py_str, write_encoders, DICE, JARO_WINKLER};
+use super::sql::engine_only_notes;
+use crate::deletion::{DeletionScope, LIVE_VALUES};
+use crate::ir::{Ir, IrRule};
+
+const IMPORTS: &str = r#"import argparse
+import hashlib
+import hmac
+import json
+import os
+import sqlite3
+import sys
+import urllib.request
+import uuid
+from datetime import datetime, timezone
+
+from confluent_kafka import Consumer, Producer
+"#;
+
+const HELPERS: &str = r#"def _levenshtein_ratio(a, b):
+    previous = list(range(len(b) + 1))
+    for i, ca in enumerate(a, 1):
+        current = [i]
+        for j, cb in enumerate(b, 1):
+            current.append(min(previous[j] + 1, current[j - 1] + 1, previous[j - 1] + (ca != cb)))
+        previous = current
+    return 1.0 - previous[-1] / max(len(a), len(b), 1)
+
+
+_SOUNDEX = {c: str(d) for d, letters in enumerate(["", "bfpv", "cgjkqsxz", "dt", "l", "mn", "r"]) for c in letters}
+
+
+def _soundex(value):
+    letters = [c for c in value.lower() if c.isalpha()]
+    if not letters:
+        return None
+    code, last = letters[0].upper(), _SOUNDEX.get(letters[0])
+    for c in letters[1:]:
+        digit = _SOUNDEX.get(c)
+        if digit is not None and digit != last:
+            code += digit
+        if c not in "hw":
+            last = digit
+    return (code + "000")[:4]
+
+
+def _soundex_equal(a, b):
+    return 1.0 if _soundex(a) == _soundex(b) else 0.0
+
+
+def _exact(a, b):
+    return 1.0 if a is not None and b is not None and a.lower() == b.lower() else 0.0
+
+
+def _similarity(algorithm, a, b, threshold=None):
+    if a is None or b is None:
+        return 0.0
+    score = algorithm(a.lower(), b.lower())
+    if threshold is None:
+        return score
+    return 1.0 if score >= threshold else 0.0
+
+
+class StateStore:
+    """Records, blocking keys and cluster assignments seen so far, in SQLite."""
+
+    def __init__(self, path):
+        self.db = sqlite3.connect(path)
+        self.db.executescript(
+            "CREATE TABLE IF NOT EXISTS records (record_key TEXT PRIMARY KEY, source_name TEXT, attributes TEXT);"
+            "CREATE TABLE IF NOT EXISTS blocks (block TEXT, record_key TEXT, PRIMARY KEY (block, record_key));"
+            "CREATE TABLE IF NOT EXISTS clusters (record_key TEXT PRIMARY KEY, cluster_id TEXT);"
+            "CREATE INDEX IF NOT EXISTS clusters_by_id ON clusters (cluster_id);"
+        )
+
+    def upsert(self, record_key, source_name, record, blocks):
+        self.db.execute("INSERT OR REPLACE INTO records VALUES (?, ?, ?)", (record_key, source_name, json.dumps(record)))
+        self.db.execute("DELETE FROM blocks WHERE record_key = ?", (record_key,))
+        self.db.executemany("INSERT OR IGNORE INTO blocks VALUES (?, ?)", [(block, record_key) for block in blocks])
+        self.db.execute("INSERT OR IGNORE INTO clusters VALUES (?, ?)", (record_key, record_key))
+
+    def candidates(self, record_key, blocks):
+        if not BLOCKED:
+            rows = self.db.execute("SELECT record_key, attributes FROM records WHERE record_key != ?", (record_key,))
+        elif blocks:
+            marks = ", ".join("?" * len(blocks))
+            rows = self.db.execute(
+                f"SELECT DISTINCT r.record_key, r.attributes FROM blocks b JOIN records r USING (record_key) "
+                f"WHERE b.block IN ({marks}) AND r.record_key != ? ORDER BY r.record_key",
+                (*blocks, record_key),
+            )
+        else:
+            rows = []
+        return [(key, json.loads(attributes)) for key, attributes in rows]
+
+    def members(self, record_key):
+        """The record keys of a record's cluster."""
+        row = self.db.execute("SELECT cluster_id FROM clusters WHERE record_key = ?", (record_key,)).fetchone()
+        if row is None:
+            return [record_key]
+        return [key for (key,) in self.db.execute("SELECT record_key FROM clusters WHERE cluster_id = ? ORDER BY record_key", row)]
+
+    def forget(self, record_keys):
+        """Remove records; the rest of their clusters keep their cluster ids."""
+        for table in ("records", "blocks", "clusters"):
+            self.db.executemany(f"DELETE FROM {table} WHERE record_key = ?", [(key,) for key in record_keys])
+
+    def merge(self, left_key, right_key):
+        """Merge the clusters of two matched records; the lowest record key names the result."""
+        ids = [self.db.execute("SELECT cluster_id FROM clusters WHERE record_key = ?", (key,)).fetchone()[0] for key in (left_key, right_key)]
+        cluster_id = min(ids)
+        self.db.execute("UPDATE clusters SET cluster_id = ? WHERE cluster_id IN (?, ?)", (cluster_id, *ids))
+        return cluster_id
+
+    def commit(self):
+        self.db.commit()
+"#;
+
+/// Generate a standalone Kafka consumer that resolves records as they
+/// arrive: each source's topic is read, every record is matched against the
+/// records sharing its blocking keys in a SQLite state store, and merge
+/// decisions go to an output topic.
+pub fn generate_kafka(ir: &Ir) -> Result<String> {
+    if ir.sources.is_empty() {
+        bail!("IR has no sources; nothing to generate");
+    }
+
+    let entity = ir.entity_name();
+    let attributes = ir.attributes();
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "\"\"\"Generated by kanoniv from {} ({}).",
+        entity,
+        ir.identity_version.as_deref().unwrap_or("unknown")
+    )?;
+    writeln!(out)?;
+    writeln!(out, "Plan hash: {}", ir.plan_hash)?;
+    writeln!(out, "Requires the confluent-kafka package.")?;
+    writeln!(
+        out,
+        "Emits merge decisions only: golden records are built downstream, and clusters never split here; rebuild in batch to undo a merge."
+    )?;
+    // Survivorship does not run here, so its notes do not apply.
+    for note in engine_only_notes(ir)
+        .into_iter()
+        .filter(|n| !n.starts_with("Custom survivorship"))
+    {
+        writeln!(out, "{}.", note)?;
+    }
+    if let Some(deletion) = &ir.deletion {
+        writeln!(
+            out,
+            "Records with {} set are removed from the state store and emitted as delete decisions{}.",
+            deletion.tombstone,
+            match deletion.scope {
+                DeletionScope::Record => "; the rest of their clusters stay merged",
+                DeletionScope::Entity => ", with every member of their cluster",
+            }
+        )?;
+    }
+    if ir.blocking.sorted_neighborhood.is_some() {
+        writeln!(
+            out,
+            "Sorted-neighbourhood blocking needs a batch engine; records here are compared with {}.",
+            if ir.blocking.keys.is_empty() {
+                "every stored record"
+            } else {
+                "those sharing a blocking key value"
+            }
+        )?;
+    }
+    writeln!(out, "\"\"\"")?;
+    writeln!(
+        out,
+        "{}\n\n{}\n\n{}\n\n{}",
+        IMPORTS, JARO_WINKLER, DICE, HELPERS
+    )?;
+    write_encoders(&mut out, ir)?;
+
+    write_settings(&mut out, ir, &attributes)?;
+    super::openlineage::write_lineage(&mut out, ir)?;
+    write_normalize(&mut out, ir)?;
+    write_blocking_keys(&mut out, ir)?;
+    write_score(&mut out, ir)?;
+    write_process(&mut out, ir)?;
+    write_main(&mut out, entity)?;
+
+    Ok(out)
+}
+
+fn write_settings(out: &mut String, ir: &Ir, attributes: &[String]) -> Result<()> {
+    let attributes: Vec<String> = attributes.iter().map(|a| py_str(a)).collect();
+    writeln!(out, "ATTRIBUTES = [{}]", attributes.join(", "))?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "# Each source's topic carries JSON objects with the source's columns."
+    )?;
+    writeln!(out, "SOURCES = {{")?;
+    for source in &ir.sources {
+        let columns: Vec<String> = source
+            .attributes
+            .iter()
+            .map(|(attribute, column)| format!("{}: {}", py_str(attribute), py_str(column)))
+            .collect();
+        writeln!(
+            out,
+            "    {}: {{\"topic\": {}, \"id\": {}, \"columns\": {{{}}}}},",
+            py_str(&source.name),
+            py_str(source.table.as_deref().unwrap_or(&source.name)),
+            py_str(source.id.as_deref().unwrap_or("id")),
+            columns.join(", ")
+        )?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(
+        out,
+        "OUTPUT_TOPIC = {}",
+        py_str(&format!("{}.merge_decisions", ir.entity_name()))
+    )?;
+    let thresholds = ir.thresholds.as_ref();
+    writeln!(
+        out,
+        "MATCH_THRESHOLD = {:?}",
+        thresholds.and_then(|t| t.match_).unwrap_or(1.0)
+    )?;
+    writeln!(
+        out,
+        "REVIEW_THRESHOLD = {}",
+        thresholds
+            .and_then(|t| t.review)
+            .map_or("None".to_string(), |r| format!("{:?}", r))
+    )?;
+    writeln!(
+        out,
+        "BLOCKED = {}",
+        if ir.blocking.keys.is_empty() {
+            "False"
+        } else {
+            "True"
+        }
+    )?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn write_normalize(out: &mut String, ir: &Ir) -> Result<()> {
+    writeln!(out, "\n# Stage 1: Normalize sources")?;
+    writeln!(out, "def normalize(source_name, message):")?;
+    writeln!(out, "    source = SOURCES[source_name]")?;
+    writeln!(out, "    record = dict.fromkeys(ATTRIBUTES)")?;
+    writeln!(
+        out,
+        "    for attribute, column in source[\"columns\"].items():"
+    )?;
+    writeln!(out, "        value = message.get(column)")?;
+    writeln!(
+        out,
+        "        record[attribute] = str(value).strip() or None if value is not None else None"
+    )?;
+    if let Some(encoding) = &ir.encoding {
+        writeln!(
+            out,
+            "    # Encode with the shared salt; values already encoded pass through"
+        )?;
+        for field in encoding.fields.keys() {
+            let value = format!("(record[{}] or \"\").lower()", py_str(field));
+            if let Some(call) = encode_call(ir, field, &value) {
+                writeln!(out, "    record[{}] = {}", py_str(field), call)?;
+            }
+        }
+    }
+    writeln!(
+        out,
+        "    return f\"{{source_name}}:{{message[source['id']]}}\", record\n"
+    )?;
+    Ok(())
+}
+
+/// Python expression for a blocking key `value` under `transform`.
+fn block_expr(transform: Option<&str>) -> String {
+    match transform.unwrap_or("identity") {
+        "lower" | "lowercase" => "value.lower()".to_string(),
+        "upper" | "uppercase" => "value.upper()".to_string(),
+        "trim" => "value.strip()".to_string(),
+        "soundex" => "_soundex(value)".to_string(),
+        other => match other
+            .strip_prefix("first_")
+            .or_else(|| other.strip_prefix("prefix:"))
+            .and_then(|n| n.parse::<usize>().ok())
+        {
+            Some(n) => format!("value.lower()[:{}]", n),
+            None => "value".to_string(),
+        },
+    }
+}
+
+fn write_blocking_keys(out: &mut String, ir: &Ir) -> Result<()> {
+    writeln!(
+        out,
+        "\n# Stage 2: Generate blocking keys (the state store indexes records by them)"
+    )?;
+    writeln!(out, "def blocking_keys(record):")?;
+    writeln!(out, "    blocks = []")?;
+    for (i, key) in ir.blocking.keys.iter().enumerate() {
+        writeln!(out, "    value = record.get({})", py_str(&key.field))?;
+        writeln!(
+            out,
+            "    block = {} if value is not None else None",
+            block_expr(key.transform.as_deref())
+        )?;
+        writeln!(out, "    if block:")?;
+        writeln!(out, "        blocks.append(\"{}:\" + block)", i)?;
+    }
+    writeln!(out, "    return blocks\n")?;
+    Ok(())
+}
+
+fn rule_expr(rule: &IrRule) -> Option<String> {
+    if rule.match_type == "semantic" {
+        return None;
+    }
+    let field = rule.field.as_deref()?;
+    let (a, b) = (
+        format!("a.get({})", py_str(field)),
+        format!("b.get({})", py_str(field)),
+    );
+    if rule.match_type == "exact" {
+        return Some(format!("_exact({}, {})", a, b));
+    }
+    let algorithm = match rule.algorithm.as_deref() {
+        Some("levenshtein") => "_levenshtein_ratio",
+        Some("soundex") => "_soundex_equal",
+        Some("dice") => "_dice",
+        _ => "_jaro_winkler",
+    };
+    Some(match rule.threshold {
+        Some(t) => format!("_similarity({}, {}, {}, {:?})", algorithm, a, b, t),
+        None => format!("_similarity({}, {}, {})", algorithm, a, b),
+    })
+}
+
+fn write_score(out: &mut String, ir: &Ir) -> Result<()> {
+    writeln!(
+        out,
+        "\n# Stages 3-5: Exact and fuzzy matches, score & decide"
+    )?;
+    writeln!(out, "def score_pair(a, b):")?;
+    writeln!(out, "    scores = [")?;
+    for rule in &ir.rules {
+        if let Some(expr) = rule_expr(rule) {
+            writeln!(
+                out,
+                "        ({:?}, {}),  # {}",
+                rule.weight, expr, rule.name
+            )?;
+        }
+    }
+    writeln!(out, "    ]")?;
+    writeln!(out, "    total = sum(weight for weight, _ in scores)")?;
+    writeln!(
+        out,
+        "    score = sum(weight * s for weight, s in scores) / total if total > 0 else 0.0"
+    )?;
+    writeln!(out, "    if score >= MATCH_THRESHOLD:")?;
+    writeln!(out, "        return score, \"match\"")?;
+    writeln!(
+        out,
+        "    if REVIEW_THRESHOLD is not None and score >= REVIEW_THRESHOLD:"
+    )?;
+    writeln!(out, "        return score, \"review\"")?;
+    writeln!(out, "    return score, \"reject\"\n")?;
+    Ok(())
+}
+
+fn write_process(out: &mut String, ir: &Ir) -> Result<()> {
+    writeln!(
+        out,
+        "\n# Stage 6: Cluster entities (clusters merge as matches arrive)"
+    )?;
+    writeln!(out, "def process(store, producer, source_name, message):")?;
+    writeln!(
+        out,
+        "    record_key, record = normalize(source_name, message)"
+    )?;
+    if let Some(deletion) = &ir.deletion {
+        let values: Vec<String> = LIVE_VALUES.iter().map(|v| py_str(v)).collect();
+        writeln!(
+            out,
+            "    if (record.get({}) or \"\").lower() not in ({}):",
+            py_str(&deletion.tombstone),
+            values.join(", ")
+        )?;
+        match deletion.scope {
+            DeletionScope::Record => writeln!(out, "        deleted = [record_key]")?,
+            DeletionScope::Entity => {
+                writeln!(
+                    out,
+                    "        # Erase the entity: every member of the record's cluster"
+                )?;
+                writeln!(out, "        deleted = store.members(record_key)")?;
+            }
+        }
+        writeln!(out, "        store.forget(deleted)")?;
+        writeln!(out, "        for key in deleted:")?;
+        writeln!(
+            out,
+            "            producer.produce(OUTPUT_TOPIC, key=key, value=json.dumps({{\"record_key\": key, \"decision\": \"delete\"}}))"
+        )?;
+        writeln!(out, "        store.commit()")?;
+        writeln!(out, "        return")?;
+    }
+    writeln!(out, "    blocks = blocking_keys(record)")?;
+    writeln!(
+        out,
+        "    store.upsert(record_key, source_name, record, blocks)"
+    )?;
+    writeln!(
+        out,
+        "    for other_key, other in store.candidates(record_key, blocks):"
+    )?;
+    writeln!(out, "        score, decision = score_pair(record, other)")?;
+    writeln!(out, "        if decision == \"reject\":")?;
+    writeln!(out, "            continue")?;
+    writeln!(
+        out,
+        "        left_key, right_key = sorted((record_key, other_key))"
+    )?;
+    writeln!(out, "        event = {{\"left_key\": left_key, \"right_key\": right_key, \"score\": score, \"decision\": decision}}")?;
+    writeln!(out, "        if decision == \"match\":")?;
+    writeln!(
+        out,
+        "            event[\"cluster_id\"] = store.merge(left_key, right_key)"
+    )?;
+    writeln!(
+        out,
+        "        producer.produce(OUTPUT_TOPIC, key=left_key, value=json.dumps(event))"
+    )?;
+    writeln!(out, "    store.commit()\n")?;
+    Ok(())
+}
+
+fn write_main(out: &mut String, entity: &str) -> Result<()> {
+    writeln!(
+        out,
+        "\n# Stage 8: Emit outputs (merge decisions, committed after each record)"
+    )?;
+    writeln!(out, "def main() -> None:")?;
+    writeln!(
+        out,
+        "    parser = argparse.ArgumentParser(description={})",
+        py_str(&format!("Resolve {} records from Kafka.", entity))
+    )?;
+    writeln!(
+        out,
+        "    parser.add_argument(\"--bootstrap-servers\", default=\"localhost:9092\")"
+    )?;
+    writeln!(
+        out,
+        "    parser.add_argument(\"--group-id\", default={})",
+        py_str(&format!("kanoniv-{}", entity))
+    )?;
+    writeln!(
+        out,
+        "    parser.add_argument(\"--state\", default={}, help=\"SQLite state store\")",
+        py_str(&format!("{}_state.db", entity))
+    )?;
+    writeln!(out, "    parser.add_argument(\"--topic\", action=\"append\", default=[], metavar=\"SOURCE=TOPIC\", help=\"Read a source from another topic\")")?;
+    writeln!(out, "    args = parser.parse_args()")?;
+    writeln!(
+        out,
+        "    topics = {{name: source[\"topic\"] for name, source in SOURCES.items()}}"
+    )?;
+    writeln!(out, "    for override in args.topic:")?;
+    writeln!(out, "        name, _, topic = override.partition(\"=\")")?;
+    writeln!(out, "        if name not in topics or not topic:")?;
+    writeln!(
+        out,
+        "            parser.error(f\"--topic expects SOURCE=TOPIC with a source of {{', '.join(topics)}}\")"
+    )?;
+    writeln!(out, "        topics[name] = topic")?;
+    writeln!(
+        out,
+        "    sources = {{topic: name for name, topic in topics.items()}}"
+    )?;
+    writeln!(out)?;
+    writeln!(out, "    store = StateStore(args.state)")?;
+    writeln!(out, "    consumer = Consumer({{\"bootstrap.servers\": args.bootstrap_servers, \"group.id\": args.group_id, \"enable.auto.commit\": False, \"auto.offset.reset\": \"earliest\"}})")?;
+    writeln!(
+        out,
+        "    producer = Producer({{\"bootstrap.servers\": args.bootstrap_servers}})"
+    )?;
+    writeln!(out, "    consumer.subscribe(list(sources))")?;
+    writeln!(
+        out,
+        "    namespace = f\"kafka://{{args.bootstrap_servers}}\""
+    )?;
+    writeln!(
+        out,
+        "    inputs = [{{\"namespace\": namespace, \"name\": topic}} for topic in sources]"
+    )?;
+    writeln!(
+        out,
+        "    outputs = [{{\"namespace\": namespace, \"name\": OUTPUT_TOPIC}}]"
+    )?;
+    writeln!(out, "    run_id = str(uuid.uuid4())")?;
+    writeln!(out, "    emit_lineage(\"START\", run_id, inputs, outputs)")?;
+    writeln!(out, "    try:")?;
+    writeln!(out, "        while True:")?;
+    writeln!(out, "            message = consumer.poll(1.0)")?;
+    writeln!(out, "            if message is None:")?;
+    writeln!(out, "                continue")?;
+    writeln!(out, "            if message.error():")?;
+    writeln!(out, "                raise RuntimeError(message.error())")?;
+    writeln!(
+        out,
+        "            process(store, producer, sources[message.topic()], json.loads(message.value()))"
+    )?;
+    writeln!(out, "            producer.flush()")?;
+    writeln!(
+        out,
+        "            consumer.commit(message=message, asynchronous=False)"
+    )?;
+    writeln!(out, "    except KeyboardInterrupt:")?;
+    writeln!(
+        out,
+        "        emit_lineage(\"COMPLETE\", run_id, inputs, outputs)"
+    )?;
+    writeln!(out, "    except Exception as error:")?;
+    writeln!(
+        out,
+        "        emit_lineage(\"FAIL\", run_id, inputs, outputs, error)"
+    )?;
+    writeln!(out, "        raise")?;
+    writeln!(out, "    finally:")?;
+    writeln!(out, "        consumer.close()")?;
+    writeln!(out, "\n\nif __name__ == \"__main__\":\n    main()")?;
+    Ok(())
+}
@@ -0,0 +1,32 @@
+//! Code generation backends that lower compiled IR into executable pipelines.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub mod dbt;
+pub mod kafka;
+mod openlineage;
+pub mod pyspark;
+pub mod sql;
+
+/// A file produced by a multi-file codegen target, relative to its output root.
+#[derive(Debug, Clone)]
+pub struct GeneratedFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// Write generated files beneath `root`, creating directories as needed.
+pub fn write_files(root: &Path, files: &[GeneratedFile]) -> Result<()> {
+    for file in files {
+        let path = root.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        fs::write(&path, &file.contents)
+            .with_context(|| format!("Failed to write file: {}", path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! OpenLineage run events for the generated Python jobs (see
+//! `crate::openlineage`): the job name and `kanoniv` run facet are fixed at
+//! generation, the namespace and transport read from the environment when
+//! the job runs. Targets import `json`, `os`, `sys`, `urllib.request`, `uuid`
+//! and `datetime`.
+
+use anyhow::Result;
+use std::fmt::Write;
+
+use super::pyspark::py_str;
+use crate::commands::plan::{generate_plan_from_ir, PlanOptions};
+use crate::ir::Ir;
+use crate::openlineage::{
+    job_name, plan_facet, API_KEY_ENV, DEFAULT_NAMESPACE, ENDPOINT, ERROR_FACET_SCHEMA_URL,
+    NAMESPACE_ENV, PRODUCER, SCHEMA_URL, URL_ENV,
+};
+
+const EMIT_LINEAGE: &str = r#"def emit_lineage(event_type, run_id, inputs, outputs, error=None):
+    """Send an OpenLineage run event to OPENLINEAGE_URL, if set; undelivered events only warn."""
+    url = os.environ.get(LINEAGE_URL_ENV)
+    if not url:
+        return
+    facets = dict(LINEAGE_RUN_FACETS)
+    if error is not None:
+        facets["errorMessage"] = {"_producer": LINEAGE_PRODUCER, "_schemaURL": LINEAGE_ERROR_SCHEMA_URL, "message": str(error), "programmingLanguage": "python"}
+    event = json.dumps({
+        "eventType": event_type,
+        "eventTime": datetime.now(timezone.utc).isoformat(),
+        "run": {"runId": run_id, "facets": facets},
+        "job": {"namespace": LINEAGE_NAMESPACE, "name": LINEAGE_JOB},
+        "inputs": inputs,
+        "outputs": outputs,
+        "producer": LINEAGE_PRODUCER,
+        "schemaURL": LINEAGE_SCHEMA_URL,
+    })
+    try:
+        if url.startswith(("http://", "https://")):
+            request = urllib.request.Request(url.rstrip("/") + "/" + LINEAGE_ENDPOINT, data=event.encode(), headers={"Content-Type": "application/json"})
+            if os.environ.get(LINEAGE_API_KEY_ENV):
+                request.add_header("Authorization", "Bearer " + os.environ[LINEAGE_API_KEY_ENV])
+            urllib.request.urlopen(request, timeout=10).close()
+        else:
+            with open(url.removeprefix("file://"), "a") as events:
+                events.write(event + "\n")
+    except OSError as e:
+        print(f"OpenLineage: failed to emit {event_type}: {e}", file=sys.stderr)
+"#;
+
+/// The `LINEAGE_*` settings and `emit_lineage`.
+pub(super) fn write_lineage(out: &mut String, ir: &Ir) -> Result<()> {
+    let facet = plan_facet(&generate_plan_from_ir(ir, &PlanOptions::default())?);
+    writeln!(out, "\n# OpenLineage run events")?;
+    for (name, value) in [
+        ("LINEAGE_URL_ENV", URL_ENV),
+        ("LINEAGE_API_KEY_ENV", API_KEY_ENV),
+        ("LINEAGE_ENDPOINT", ENDPOINT),
+        ("LINEAGE_PRODUCER", PRODUCER),
+        ("LINEAGE_SCHEMA_URL", SCHEMA_URL),
+        ("LINEAGE_ERROR_SCHEMA_URL", ERROR_FACET_SCHEMA_URL),
+    ] {
+        writeln!(out, "{} = {}", name, py_str(value))?;
+    }
+    writeln!(
+        out,
+        "LINEAGE_NAMESPACE = os.environ.get({}) or {}",
+        py_str(NAMESPACE_ENV),
+        py_str(DEFAULT_NAMESPACE)
+    )?;
+    writeln!(out, "LINEAGE_JOB = {}", py_str(&job_name(ir)))?;
+    writeln!(
+        out,
+        "LINEAGE_RUN_FACETS = {{\"kanoniv\": json.loads({})}}",
+        py_str(&facet.to_string())
+    )?;
+    writeln!(out, "\n\n{}", EMIT_LINEAGE)?;
+    Ok(())
+}
@@ -0,0 +1,722 @@
+use anyhow::{bail, Result};
+use std::fmt::Write;
+
+use super::sql::engine_only_notes;
+use crate::deletion::{DeletionScope, LIVE_VALUES};
+use crate::encoding::FieldEncoding;
+use crate::ir::{Ir, IrRule, IrSurvivorship};
+use crate::openlineage::Dataset;
+use crate::survivorship;
+
+const IMPORTS: &str = r#"import hashlib
+import hmac
+import json
+import os
+import sys
+import urllib.request
+import uuid
+from datetime import datetime, timezone
+
+from pyspark.sql import SparkSession, DataFrame, Window
+from pyspark.sql import functions as F
+from pyspark.sql.types import DoubleType
+from graphframes import GraphFrame
+"#;
+
+/// Jaro-Winkler similarity in plain Python, shared with the Kafka target.
+pub(super) const JARO_WINKLER: &str = r#"def _jaro_winkler(a, b):
+    if a is None or b is None:
+        return None
+    if a == b:
+        return 1.0
+    la, lb = len(a), len(b)
+    if la == 0 or lb == 0:
+        return 0.0
+    window = max(max(la, lb) // 2 - 1, 0)
+    a_flags, b_flags = [False] * la, [False] * lb
+    matches = 0
+    for i, ca in enumerate(a):
+        for j in range(max(0, i - window), min(lb, i + window + 1)):
+            if not b_flags[j] and b[j] == ca:
+                a_flags[i] = b_flags[j] = True
+                matches += 1
+                break
+    if matches == 0:
+        return 0.0
+    transpositions, k = 0, 0
+    for i in range(la):
+        if a_flags[i]:
+            while not b_flags[k]:
+                k += 1
+            if a[i] != b[k]:
+                transpositions += 1
+            k += 1
+    m = float(matches)
+    jaro = (m / la + m / lb + (m - transpositions / 2.0) / m) / 3.0
+    prefix = 0
+    for ca, cb in zip(a[:4], b[:4]):
+        if ca != cb:
+            break
+        prefix += 1
+    return jaro + prefix * 0.1 * (1.0 - jaro)
+"#;
+
+/// Dice similarity in plain Python, shared with the Kafka target; keep in
+/// step with `similarity::dice`.
+pub(super) const DICE: &str = r#"def _ngrams(value, n):
+    padded = f" {value} " if n > 1 else value
+    if not padded:
+        return set()
+    n = min(n, len(padded))
+    return {padded[i:i + n] for i in range(len(padded) - n + 1)}
+
+
+def _dice(a, b):
+    if a is None or b is None:
+        return None
+    if a.startswith("bloom:") and b.startswith("bloom:") and len(a) == len(b):
+        x, y = bytes.fromhex(a[6:]), bytes.fromhex(b[6:])
+        total = sum(bin(byte).count("1") for byte in x + y)
+        common = sum(bin(p & q).count("1") for p, q in zip(x, y))
+        return 2.0 * common / total if total else 0.0
+    x, y = _ngrams(a.lower(), 2), _ngrams(b.lower(), 2)
+    total = len(x) + len(y)
+    return 2.0 * len(x & y) / total if total else 1.0
+"#;
+
+/// Salted encodings in plain Python, shared with the Kafka target; keep in
+/// step with `encoding::Encoder`. Needs `SALT`, `HASH_LABEL` and
+/// `HASH_DIGEST`.
+pub(super) const ENCODERS: &str = r#"def _encode_hash(value):
+    if not value:
+        return None
+    if value.startswith(HASH_LABEL + ":"):
+        return value
+    return HASH_LABEL + ":" + hmac.new(SALT, value.encode(), HASH_DIGEST).hexdigest()
+
+
+def _encode_bloom(value, size, hashes, ngram):
+    if not value:
+        return None
+    if value.startswith("bloom:"):
+        return value
+    bits = bytearray(size // 8)
+    for gram in _ngrams(value, ngram):
+        digest = hmac.new(SALT, gram.encode(), hashlib.sha256).digest()
+        h1, h2 = int.from_bytes(digest[:8], "little"), int.from_bytes(digest[8:16], "little")
+        for i in range(hashes):
+            bit = (h1 + i * h2) % 2**64 % size
+            bits[bit // 8] |= 1 << (bit % 8)
+    return "bloom:" + bits.hex()
+"#;
+
+const PRELUDE: &str = r#"jaro_winkler = F.udf(_jaro_winkler, DoubleType())
+
+
+def levenshtein_ratio(a, b):
+    longest = F.greatest(F.length(a), F.length(b), F.lit(1))
+    return F.lit(1.0) - F.levenshtein(a, b) / longest
+
+
+def soundex_equal(a, b):
+    return F.when(F.soundex(a) == F.soundex(b), F.lit(1.0)).otherwise(F.lit(0.0))
+
+
+dice = F.udf(_dice, DoubleType())
+"#;
+
+/// Generate a standalone PySpark job implementing the execution stages.
+pub fn generate_pyspark(ir: &Ir) -> Result<String> {
+    if ir.sources.is_empty() {
+        bail!("IR has no sources; nothing to generate");
+    }
+
+    let entity = ir.entity_name();
+    let attributes = ir.attributes();
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "\"\"\"Generated by kanoniv from {} ({}).",
+        entity,
+        ir.identity_version.as_deref().unwrap_or("unknown")
+    )?;
+    writeln!(out)?;
+    writeln!(out, "Plan hash: {}", ir.plan_hash)?;
+    writeln!(
+        out,
+        "Requires the graphframes package on the Spark classpath."
+    )?;
+    for note in engine_only_notes(ir) {
+        writeln!(out, "{}.", note)?;
+    }
+    writeln!(out, "\"\"\"")?;
+    writeln!(
+        out,
+        "{}\n\n{}\n\n{}\n\n{}",
+        IMPORTS, JARO_WINKLER, DICE, PRELUDE
+    )?;
+    write_encoders(&mut out, ir)?;
+    writeln!(out, "ATTRIBUTES = [{}]", py_list(&attributes))?;
+    writeln!(out)?;
+
+    write_load_sources(&mut out, ir, &attributes)?;
+    write_candidate_pairs(&mut out, ir)?;
+    write_scores(&mut out, ir)?;
+    write_clusters(&mut out, ir)?;
+    write_survivorship(&mut out, ir, &attributes)?;
+    super::openlineage::write_lineage(&mut out, ir)?;
+    write_main(&mut out, ir)?;
+
+    Ok(out)
+}
+
+pub(super) fn py_str(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn py_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|s| py_str(s))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn write_load_sources(out: &mut String, ir: &Ir, attributes: &[String]) -> Result<()> {
+    writeln!(out, "\n# Stage 1: Normalize sources")?;
+    writeln!(out, "def load_sources(spark: SparkSession) -> DataFrame:")?;
+    writeln!(out, "    frames = []")?;
+    for source in &ir.sources {
+        let table = source.table.as_deref().unwrap_or(&source.name);
+        let id = source.id.as_deref().unwrap_or("id");
+        writeln!(out, "    df = spark.table({})", py_str(table))?;
+        writeln!(out, "    frames.append(df.select(")?;
+        writeln!(
+            out,
+            "        F.lit({}).alias(\"source_name\"),",
+            py_str(&source.name)
+        )?;
+        writeln!(
+            out,
+            "        F.concat_ws(\":\", F.lit({}), F.col({}).cast(\"string\")).alias(\"record_key\"),",
+            py_str(&source.name),
+            py_str(id)
+        )?;
+        for attr in attributes {
+            match source.attributes.get(attr) {
+                Some(column) => writeln!(
+                    out,
+                    "        F.trim(F.col({}).cast(\"string\")).alias({}),",
+                    py_str(column),
+                    py_str(attr)
+                )?,
+                None => writeln!(
+                    out,
+                    "        F.lit(None).cast(\"string\").alias({}),",
+                    py_str(attr)
+                )?,
+            }
+        }
+        writeln!(out, "    ))")?;
+    }
+    writeln!(out, "    result = frames[0]")?;
+    writeln!(out, "    for frame in frames[1:]:")?;
+    writeln!(out, "        result = result.unionByName(frame)")?;
+    if let Some(deletion) = ir
+        .deletion
+        .as_ref()
+        .filter(|d| d.scope == DeletionScope::Record)
+    {
+        writeln!(
+            out,
+            "    # Drop records with {} set before matching",
+            deletion.tombstone
+        )?;
+        writeln!(
+            out,
+            "    result = result.where({})",
+            live(&deletion.tombstone)
+        )?;
+    }
+    if let Some(encoding) = &ir.encoding {
+        writeln!(
+            out,
+            "    # Encode with the shared salt; values already encoded pass through"
+        )?;
+        for field in encoding.fields.keys() {
+            if let Some(call) = encode_call(ir, field, "value") {
+                writeln!(
+                    out,
+                    "    result = result.withColumn({0}, F.udf(lambda value: {1})(F.lower(F.col({0}))))",
+                    py_str(field),
+                    call
+                )?;
+            }
+        }
+    }
+    writeln!(out, "    return result\n")?;
+    Ok(())
+}
+
+/// The salt and `ENCODERS`, if the IR encodes attributes.
+pub(super) fn write_encoders(out: &mut String, ir: &Ir) -> Result<()> {
+    let Some(encoding) = ir.encoding.as_ref().filter(|e| !e.fields.is_empty()) else {
+        return Ok(());
+    };
+    let algorithm = ir
+        .hashing
+        .as_ref()
+        .map_or("sha256", |h| h.algorithm.as_str());
+    if !matches!(algorithm, "sha256" | "sha512")
+        && encoding.fields.values().any(|f| *f == FieldEncoding::Hash)
+    {
+        bail!(
+            "Generated code encodes with hmac-sha256 or hmac-sha512; hashing algorithm {} needs an engine",
+            algorithm
+        );
+    }
+    writeln!(out, "def _salt():")?;
+    match (&encoding.salt_file, &encoding.salt_env) {
+        (Some(path), _) => {
+            writeln!(out, "    with open({}, \"rb\") as f:", py_str(path))?;
+            writeln!(out, "        return f.read().rstrip(b\"\\r\\n\")")?;
+        }
+        (None, Some(name)) => writeln!(out, "    return os.environ[{}].encode()", py_str(name))?,
+        (None, None) => bail!("encoding names no salt_env or salt_file"),
+    }
+    writeln!(out, "\n\nSALT = _salt()")?;
+    writeln!(out, "HASH_LABEL = \"hmac-{}\"", algorithm)?;
+    writeln!(out, "HASH_DIGEST = hashlib.{}", algorithm)?;
+    writeln!(out, "\n\n{}", ENCODERS)?;
+    Ok(())
+}
+
+/// Python call encoding `value` of `field`, if the IR encodes it.
+pub(super) fn encode_call(ir: &Ir, field: &str, value: &str) -> Option<String> {
+    Some(match ir.encoding.as_ref()?.field(field)? {
+        FieldEncoding::Hash => format!("_encode_hash({})", value),
+        FieldEncoding::Bloom(params) => format!(
+            "_encode_bloom({}, {}, {}, {})",
+            value, params.size, params.hashes, params.ngram
+        ),
+    })
+}
+
+/// Whether a record's `tombstone` leaves it live; keep in step with
+/// `Deletion::is_deleted`.
+fn live(tombstone: &str) -> String {
+    let values: Vec<String> = LIVE_VALUES.iter().map(|v| py_str(v)).collect();
+    format!(
+        "F.coalesce(F.lower(F.col({})), F.lit(\"\")).isin({})",
+        py_str(tombstone),
+        values.join(", ")
+    )
+}
+
+fn blocking_expr(field: &str, transform: Option<&str>) -> String {
+    let col = format!("F.col({})", py_str(field));
+    match transform.unwrap_or("identity") {
+        "lower" | "lowercase" => format!("F.lower({})", col),
+        "upper" | "uppercase" => format!("F.upper({})", col),
+        "trim" => format!("F.trim({})", col),
+        "soundex" => format!("F.soundex({})", col),
+        other => match other
+            .strip_prefix("first_")
+            .or_else(|| other.strip_prefix("prefix:"))
+            .and_then(|n| n.parse::<usize>().ok())
+        {
+            Some(n) => format!("F.substring(F.lower({}), 1, {})", col, n),
+            None => col,
+        },
+    }
+}
+
+fn write_candidate_pairs(out: &mut String, ir: &Ir) -> Result<()> {
+    writeln!(
+        out,
+        "\n# Stage 2: Generate blocking keys (blocking keys become repartition keys)"
+    )?;
+    writeln!(
+        out,
+        "def candidate_pairs(entities: DataFrame) -> DataFrame:"
+    )?;
+    if ir.blocking.keys.is_empty() && ir.blocking.sorted_neighborhood.is_none() {
+        writeln!(
+            out,
+            "    # No blocking keys: full pairwise comparison (O(n^2))"
+        )?;
+        writeln!(
+            out,
+            "    a, b = entities.alias(\"a\"), entities.alias(\"b\")"
+        )?;
+        writeln!(
+            out,
+            "    return a.join(b, F.col(\"a.record_key\") < F.col(\"b.record_key\")).select("
+        )?;
+        writeln!(out, "        F.col(\"a.record_key\").alias(\"left_key\"), F.col(\"b.record_key\").alias(\"right_key\"))\n")?;
+        return Ok(());
+    }
+    writeln!(out, "    pairs = None")?;
+    if let Some(window) = &ir.blocking.sorted_neighborhood {
+        writeln!(
+            out,
+            "    # Sorted neighbourhood on {}, window {}: each record pairs with the next {} in sort order",
+            window.sort_key,
+            window.window,
+            window.window - 1
+        )?;
+        writeln!(
+            out,
+            "    ranked = entities.where(F.col({0}).isNotNull()).withColumn(\"_position\", F.row_number().over(Window.orderBy({1}, F.col(\"record_key\"))))",
+            py_str(&window.sort_key),
+            blocking_expr(&window.sort_key, window.transform.as_deref())
+        )?;
+        writeln!(out, "    a, b = ranked.alias(\"a\"), ranked.alias(\"b\")")?;
+        writeln!(
+            out,
+            "    joined = a.join(b, (F.col(\"b._position\") > F.col(\"a._position\")) & (F.col(\"b._position\") < F.col(\"a._position\") + {}))",
+            window.window
+        )?;
+        writeln!(out, "    joined = joined.select(F.least(\"a.record_key\", \"b.record_key\").alias(\"left_key\"), F.greatest(\"a.record_key\", \"b.record_key\").alias(\"right_key\"))")?;
+        writeln!(out, "    pairs = joined")?;
+    }
+    for key in &ir.blocking.keys {
+        writeln!(
+            out,
+            "    keyed = entities.where(F.col({0}).isNotNull()).withColumn(\"_block\", {1}).repartition(\"_block\")",
+            py_str(&key.field),
+            blocking_expr(&key.field, key.transform.as_deref())
+        )?;
+        writeln!(out, "    a, b = keyed.alias(\"a\"), keyed.alias(\"b\")")?;
+        writeln!(out, "    joined = a.join(b, (F.col(\"a._block\") == F.col(\"b._block\")) & (F.col(\"a.record_key\") < F.col(\"b.record_key\")))")?;
+        writeln!(out, "    joined = joined.select(F.col(\"a.record_key\").alias(\"left_key\"), F.col(\"b.record_key\").alias(\"right_key\"))")?;
+        writeln!(
+            out,
+            "    pairs = joined if pairs is None else pairs.union(joined)"
+        )?;
+    }
+    writeln!(out, "    return pairs.distinct()\n")?;
+    Ok(())
+}
+
+fn rule_expr(rule: &IrRule) -> Option<String> {
+    if rule.match_type == "semantic" {
+        return None;
+    }
+    let field = rule.field.as_deref()?;
+    let a = format!("F.lower(F.col({}))", py_str(&format!("a.{}", field)));
+    let b = format!("F.lower(F.col({}))", py_str(&format!("b.{}", field)));
+    let expr = if rule.match_type == "exact" {
+        format!("F.when({} == {}, F.lit(1.0)).otherwise(F.lit(0.0))", a, b)
+    } else {
+        let sim = match rule.algorithm.as_deref() {
+            Some("levenshtein") => format!("levenshtein_ratio({}, {})", a, b),
+            Some("soundex") => format!("soundex_equal({}, {})", a, b),
+            Some("dice") => format!("dice({}, {})", a, b),
+            _ => format!("jaro_winkler({}, {})", a, b),
+        };
+        match rule.threshold {
+            Some(t) => format!("F.when({} >= {}, F.lit(1.0)).otherwise(F.lit(0.0))", sim, t),
+            None => sim,
+        }
+    };
+    Some(format!("F.coalesce({}, F.lit(0.0))", expr))
+}
+
+fn write_scores(out: &mut String, ir: &Ir) -> Result<()> {
+    let match_t = ir.thresholds.as_ref().and_then(|t| t.match_).unwrap_or(1.0);
+    let review_t = ir.thresholds.as_ref().and_then(|t| t.review);
+
+    writeln!(
+        out,
+        "\n# Stages 3-5: Exact and fuzzy matches (UDFs), score & decide"
+    )?;
+    writeln!(
+        out,
+        "def score_pairs(pairs: DataFrame, entities: DataFrame) -> DataFrame:"
+    )?;
+    writeln!(
+        out,
+        "    a, b = entities.alias(\"a\"), entities.alias(\"b\")"
+    )?;
+    writeln!(out, "    joined = pairs.join(a, F.col(\"left_key\") == F.col(\"a.record_key\")).join(b, F.col(\"right_key\") == F.col(\"b.record_key\"))")?;
+    writeln!(out, "    scored = joined.select(")?;
+    writeln!(out, "        \"left_key\",\n        \"right_key\",")?;
+
+    let mut weighted = Vec::new();
+    let mut total = 0.0;
+    for rule in &ir.rules {
+        if let Some(expr) = rule_expr(rule) {
+            let column = format!("{}_score", rule.name);
+            writeln!(out, "        {}.alias({}),", expr, py_str(&column))?;
+            weighted.push(format!("F.col({}) * {}", py_str(&column), rule.weight));
+            total += rule.weight;
+        }
+    }
+    writeln!(out, "    )")?;
+    let score = if weighted.is_empty() || total <= 0.0 {
+        "F.lit(0.0)".to_string()
+    } else {
+        format!("({}) / {}", weighted.join(" + "), total)
+    };
+    writeln!(out, "    scored = scored.withColumn(\"score\", {})", score)?;
+    let review = review_t
+        .map(|r| format!(".when(F.col(\"score\") >= {}, F.lit(\"review\"))", r))
+        .unwrap_or_default();
+    writeln!(
+        out,
+        "    return scored.withColumn(\"decision\", F.when(F.col(\"score\") >= {}, F.lit(\"match\")){}.otherwise(F.lit(\"reject\")))\n",
+        match_t, review
+    )?;
+    Ok(())
+}
+
+fn write_clusters(out: &mut String, ir: &Ir) -> Result<()> {
+    writeln!(
+        out,
+        "\n# Stage 6: Cluster entities (GraphFrames connected components)"
+    )?;
+    writeln!(
+        out,
+        "def cluster(entities: DataFrame, decisions: DataFrame) -> DataFrame:"
+    )?;
+    writeln!(
+        out,
+        "    vertices = entities.select(F.col(\"record_key\").alias(\"id\"))"
+    )?;
+    writeln!(
+        out,
+        "    edges = decisions.where(F.col(\"decision\") == \"match\").select("
+    )?;
+    writeln!(
+        out,
+        "        F.col(\"left_key\").alias(\"src\"), F.col(\"right_key\").alias(\"dst\"))"
+    )?;
+    writeln!(
+        out,
+        "    components = GraphFrame(vertices, edges).connectedComponents()"
+    )?;
+    writeln!(out, "    return components.select(F.col(\"id\").alias(\"record_key\"), F.col(\"component\").cast(\"string\").alias(\"cluster_id\"))\n")?;
+    if let Some(deletion) = ir
+        .deletion
+        .as_ref()
+        .filter(|d| d.scope == DeletionScope::Entity)
+    {
+        writeln!(
+            out,
+            "\n# Erase entities holding a record with {} set",
+            deletion.tombstone
+        )?;
+        writeln!(
+            out,
+            "def erase_entities(entities: DataFrame, clusters: DataFrame) -> DataFrame:"
+        )?;
+        writeln!(
+            out,
+            "    deleted = entities.where(~{}).join(clusters, \"record_key\").select(\"cluster_id\").distinct()",
+            live(&deletion.tombstone)
+        )?;
+        writeln!(
+            out,
+            "    return clusters.join(deleted, \"cluster_id\", \"left_anti\")\n"
+        )?;
+    }
+    Ok(())
+}
+
+fn survivorship_order(rule: &IrSurvivorship, field: &str, current: Option<&str>) -> String {
+    let mut nulls_last = format!("F.col({}).isNull()", py_str(field));
+    if let Some(current) = current {
+        write!(nulls_last, ", F.col({}).isNotNull()", py_str(current)).ok();
+    }
+    let order = match rule.strategy.as_str() {
+        "source_priority" => {
+            let priority = rule.source_priority.clone().unwrap_or_default();
+            if priority.is_empty() {
+                "F.col(\"record_key\")".to_string()
+            } else {
+                let mut expr = "F".to_string();
+                for (i, s) in priority.iter().enumerate() {
+                    write!(
+                        expr,
+                        ".when(F.col(\"source_name\") == {}, F.lit({}))",
+                        py_str(s),
+                        i
+                    )
+                    .ok();
+                }
+                format!(
+                    "{}.otherwise(F.lit({})), F.col(\"record_key\")",
+                    expr,
+                    priority.len()
+                )
+            }
+        }
+        "longest" | "most_complete" => format!(
+            "F.length(F.col({})).desc(), F.col(\"record_key\")",
+            py_str(field)
+        ),
+        "most_frequent" => format!(
+            "F.col({}).desc(), F.col(\"record_key\")",
+            py_str(&format!("{}__freq", field))
+        ),
+        "most_recent" => match &rule.timestamp {
+            Some(ts) => format!(
+                "F.col({0}).isNull(), F.col({0}).desc(), F.col(\"record_key\").desc()",
+                py_str(ts)
+            ),
+            None => "F.col(\"record_key\").desc()".to_string(),
+        },
+        _ => "F.col(\"record_key\")".to_string(),
+    };
+    format!("{}, {}", nulls_last, order)
+}
+
+fn write_survivorship(out: &mut String, ir: &Ir, attributes: &[String]) -> Result<()> {
+    writeln!(out, "\n# Stage 7: Apply survivorship (window functions)")?;
+    writeln!(
+        out,
+        "def golden_records(entities: DataFrame, clusters: DataFrame) -> DataFrame:"
+    )?;
+    writeln!(out, "    members = entities.join(clusters, \"record_key\")")?;
+    let mut cols = vec!["\"cluster_id\"".to_string()];
+    for attr in attributes {
+        let rule = survivorship::rule_for(ir, attr);
+        let current = survivorship::current_marker(ir, attr);
+        if rule.strategy == "most_frequent" {
+            writeln!(
+                out,
+                "    members = members.withColumn({}, F.count(F.lit(1)).over(Window.partitionBy(\"cluster_id\", {})))",
+                py_str(&format!("{}__freq", attr)),
+                py_str(attr)
+            )?;
+        }
+        // non_null_priority takes the first listed field any member has,
+        // from the lowest key; `first` already skips nulls.
+        let fields = match rule.fields.clone() {
+            Some(fields) if rule.strategy == "non_null_priority" => fields,
+            _ => vec![attr.clone()],
+        };
+        let order = match current.as_deref() {
+            Some(current) if rule.strategy == "non_null_priority" => format!(
+                "F.col({}).isNotNull(), F.col(\"record_key\")",
+                py_str(current)
+            ),
+            None if rule.strategy == "non_null_priority" => "F.col(\"record_key\")".to_string(),
+            _ => survivorship_order(&rule, attr, current.as_deref()),
+        };
+        writeln!(
+            out,
+            "    w_{0} = Window.partitionBy(\"cluster_id\").orderBy({1}).rowsBetween(Window.unboundedPreceding, Window.unboundedFollowing)",
+            attr,
+            order
+        )?;
+        let firsts: Vec<String> = fields
+            .iter()
+            .map(|f| {
+                format!(
+                    "F.first(F.col({}), ignorenulls=True).over(w_{})",
+                    py_str(f),
+                    attr
+                )
+            })
+            .collect();
+        let golden = match firsts.as_slice() {
+            [first] => first.clone(),
+            _ => format!("F.coalesce({})", firsts.join(", ")),
+        };
+        writeln!(
+            out,
+            "    members = members.withColumn({}, {})",
+            py_str(&format!("{}__golden", attr)),
+            golden
+        )?;
+        cols.push(format!(
+            "F.col({}).alias({})",
+            py_str(&format!("{}__golden", attr)),
+            py_str(attr)
+        ));
+    }
+    writeln!(
+        out,
+        "    return members.select({}).distinct()\n",
+        cols.join(", ")
+    )?;
+    Ok(())
+}
+
+fn write_main(out: &mut String, ir: &Ir) -> Result<()> {
+    let entity = ir.entity_name();
+    let tables = ["match_decisions", "entity_clusters", "canonical_entities"]
+        .map(|output| py_str(&format!("{}_{}", entity, output)));
+    writeln!(out, "\n# Stage 8: Emit outputs")?;
+    writeln!(out, "def main() -> None:")?;
+    writeln!(
+        out,
+        "    spark = SparkSession.builder.appName({}).getOrCreate()",
+        py_str(&format!("kanoniv-{}", entity))
+    )?;
+    writeln!(
+        out,
+        "    spark.sparkContext.setCheckpointDir(\"/tmp/kanoniv-checkpoints\")"
+    )?;
+    let inputs: Vec<String> = ir
+        .sources
+        .iter()
+        .map(Dataset::source)
+        .map(|d| {
+            format!(
+                "{{\"namespace\": {}, \"name\": {}}}",
+                py_str(&d.namespace),
+                py_str(&d.name)
+            )
+        })
+        .collect();
+    writeln!(out, "    inputs = [{}]", inputs.join(", "))?;
+    writeln!(
+        out,
+        "    outputs = [{{\"namespace\": LINEAGE_NAMESPACE, \"name\": table}} for table in ({})]",
+        tables.join(", ")
+    )?;
+    writeln!(out, "    run_id = str(uuid.uuid4())")?;
+    writeln!(out, "    emit_lineage(\"START\", run_id, inputs, outputs)")?;
+    writeln!(out, "    try:")?;
+    writeln!(out, "        entities = load_sources(spark).cache()")?;
+    writeln!(
+        out,
+        "        decisions = score_pairs(candidate_pairs(entities), entities)"
+    )?;
+    writeln!(out, "        clusters = cluster(entities, decisions)")?;
+    if ir
+        .deletion
+        .as_ref()
+        .is_some_and(|d| d.scope == DeletionScope::Entity)
+    {
+        writeln!(out, "        clusters = erase_entities(entities, clusters)")?;
+    }
+    writeln!(out, "        golden = golden_records(entities, clusters)")?;
+    writeln!(
+        out,
+        "        decisions.write.mode(\"overwrite\").saveAsTable({})",
+        tables[0]
+    )?;
+    writeln!(
+        out,
+        "        clusters.write.mode(\"overwrite\").saveAsTable({})",
+        tables[1]
+    )?;
+    writeln!(out, "        golden.withColumnRenamed(\"cluster_id\", \"entity_id\").write.mode(\"overwrite\").saveAsTable({})", tables[2])?;
+    writeln!(out, "    except Exception as error:")?;
+    writeln!(
+        out,
+        "        emit_lineage(\"FAIL\", run_id, inputs, outputs, error)"
+    )?;
+    writeln!(out, "        raise")?;
+    writeln!(
+        out,
+        "    emit_lineage(\"COMPLETE\", run_id, inputs, outputs)"
+    )?;
+    writeln!(out, "\n\nif __name__ == \"__main__\":\n    main()")?;
+    Ok(())
+}
@@ -0,0 +1,1033 @@
+use anyhow::{bail, Result};
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::address;
+use crate::clustering::ClusteringStrategy;
+use crate::deletion::{DeletionScope, LIVE_VALUES};
+use crate::encoding::FieldEncoding;
+use crate::ir::{Ir, IrRule, IrSource, IrSurvivorship};
+use crate::survivorship;
+
+// ── Dialects ───────────────────────────────────────────────────────
+
+/// Words reserved by ANSI SQL or one of the dialects, which name a column
+/// or table only quoted.
+const RESERVED_WORDS: &[&str] = &[
+    "all", "alter", "analyse", "analyze", "and", "any", "array", "as", "asc",
+    "asymmetric", "at", "authorization", "between", "binary", "both", "by", "case",
+    "cast", "check", "collate", "column", "connect", "constraint", "contains",
+    "create", "cross", "cube", "current", "current_date", "current_role",
+    "current_time", "current_timestamp", "current_user", "default", "deferrable",
+    "define", "delete", "desc", "distinct", "do", "drop", "else", "end", "enum",
+    "escape", "except", "exclude", "exists", "extract", "false", "fetch",
+    "following", "for", "foreign", "freeze", "from", "full", "grant", "group",
+    "grouping", "groups", "hash", "having", "if", "ignore", "ilike", "in",
+    "increment", "initially", "inner", "insert", "intersect", "interval", "into",
+    "is", "isnull", "join", "lateral", "leading", "left", "like", "limit",
+    "localtime", "localtimestamp", "lookup", "merge", "minus", "natural", "new",
+    "no", "not", "notnull", "null", "nulls", "of", "offset", "on", "only", "or",
+    "order", "outer", "over", "overlaps", "partition", "placing", "preceding",
+    "primary", "proto", "qualify", "range", "recursive", "references", "regexp",
+    "respect", "returning", "right", "rlike", "rollup", "row", "rows", "sample",
+    "select", "session_user", "set", "similar", "some", "start", "struct",
+    "symmetric", "table", "tablesample", "then", "to", "trailing", "treat", "true",
+    "try_cast", "unbounded", "union", "unique", "update", "user", "using",
+    "values", "variadic", "verbose", "when", "whenever", "where", "window",
+    "with", "within",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Ansi,
+    Postgres,
+    Snowflake,
+    BigQuery,
+}
+
+impl FromStr for Dialect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "ansi" => Ok(Dialect::Ansi),
+            "postgres" | "postgresql" => Ok(Dialect::Postgres),
+            "snowflake" => Ok(Dialect::Snowflake),
+            "bigquery" => Ok(Dialect::BigQuery),
+            other => bail!(
+                "Unknown SQL dialect: '{}'. Expected one of: ansi, postgres, snowflake, bigquery",
+                other
+            ),
+        }
+    }
+}
+
+impl Dialect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dialect::Ansi => "ansi",
+            Dialect::Postgres => "postgres",
+            Dialect::Snowflake => "snowflake",
+            Dialect::BigQuery => "bigquery",
+        }
+    }
+
+    pub(crate) fn requirements_note(&self) -> Option<&'static str> {
+        match self {
+            Dialect::Postgres => Some("Requires extensions: pg_trgm, fuzzystrmatch"),
+            Dialect::Ansi => {
+                Some("ANSI SQL has no string similarity; fuzzy rules degrade to equality")
+            }
+            _ => None,
+        }
+    }
+
+    fn string_type(&self) -> &'static str {
+        match self {
+            Dialect::BigQuery => "STRING",
+            Dialect::Postgres => "TEXT",
+            _ => "VARCHAR",
+        }
+    }
+
+    fn float_type(&self) -> &'static str {
+        match self {
+            Dialect::BigQuery => "FLOAT64",
+            Dialect::Snowflake => "FLOAT",
+            _ => "DOUBLE PRECISION",
+        }
+    }
+
+    /// `name` as an identifier: as it is where it is a plain one, quoted
+    /// (backticks for BigQuery, double quotes elsewhere) where it is not
+    /// or is a reserved word.
+    fn ident(&self, name: &str) -> String {
+        let plain = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !RESERVED_WORDS.iter().any(|w| w.eq_ignore_ascii_case(name));
+        match self {
+            _ if plain => name.to_string(),
+            Dialect::BigQuery => format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`")),
+            _ => format!("\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+
+    /// A possibly schema-qualified table name, each part an identifier.
+    fn table(&self, name: &str) -> String {
+        name.split('.')
+            .map(|part| self.ident(part))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// `value` as a string literal.
+    fn literal(&self, value: &str) -> String {
+        match self {
+            Dialect::BigQuery => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+            _ => format!("'{}'", value.replace('\'', "''")),
+        }
+    }
+
+    pub(crate) fn create_view(&self, name: &str) -> String {
+        match self {
+            Dialect::Ansi => format!("CREATE VIEW {} AS", name),
+            _ => format!("CREATE OR REPLACE VIEW {} AS", name),
+        }
+    }
+
+    /// Geohash of a `lat,lon` string expression, where the dialect has
+    /// geospatial functions (PostGIS for Postgres); `None` for ANSI SQL.
+    fn geohash(&self, expr: &str, precision: usize) -> Option<String> {
+        let part = |n: usize| match self {
+            Dialect::BigQuery => format!("SPLIT({}, ',')[SAFE_OFFSET({})]", expr, n - 1),
+            _ => format!("SPLIT_PART({}, ',', {})", expr, n),
+        };
+        let (lat, lon) = (
+            format!("CAST(TRIM({}) AS {})", part(1), self.float_type()),
+            format!("CAST(TRIM({}) AS {})", part(2), self.float_type()),
+        );
+        let point = match self {
+            Dialect::Ansi => return None,
+            Dialect::Postgres => "ST_GeoHash(ST_MakePoint",
+            Dialect::Snowflake => "ST_GEOHASH(ST_MAKEPOINT",
+            Dialect::BigQuery => "ST_GEOHASH(ST_GEOGPOINT",
+        };
+        Some(format!("{}({}, {}), {})", point, lon, lat, precision))
+    }
+
+    fn prefix(&self, expr: &str, n: usize) -> String {
+        match self {
+            Dialect::Ansi => format!("SUBSTRING({} FROM 1 FOR {})", expr, n),
+            _ => format!("SUBSTR({}, 1, {})", expr, n),
+        }
+    }
+
+    /// Similarity in [0, 1] between two already-lowercased string expressions.
+    fn similarity(&self, algorithm: Option<&str>, a: &str, b: &str) -> String {
+        let edit_ratio = |func: &str| {
+            format!(
+                "1.0 - CAST({}({}, {}) AS {}) / GREATEST(LENGTH({}), LENGTH({}), 1)",
+                func,
+                a,
+                b,
+                self.float_type(),
+                a,
+                b
+            )
+        };
+        let soundex = format!(
+            "CASE WHEN SOUNDEX({}) = SOUNDEX({}) THEN 1.0 ELSE 0.0 END",
+            a, b
+        );
+
+        match (self, algorithm.unwrap_or("default")) {
+            (Dialect::Ansi, _) => format!("CASE WHEN {} = {} THEN 1.0 ELSE 0.0 END", a, b),
+            (_, "soundex") => soundex,
+            (Dialect::Postgres, "levenshtein") => edit_ratio("levenshtein"),
+            (Dialect::Postgres, _) => format!("similarity({}, {})", a, b),
+            (Dialect::Snowflake, "levenshtein") => edit_ratio("EDITDISTANCE"),
+            (Dialect::Snowflake, _) => format!("JAROWINKLER_SIMILARITY({}, {}) / 100.0", a, b),
+            (Dialect::BigQuery, _) => edit_ratio("EDIT_DISTANCE"),
+        }
+    }
+}
+
+// ── Stage queries ──────────────────────────────────────────────────
+
+/// How generated queries refer to sources and to other stages' relations.
+pub(crate) enum Naming<'a> {
+    /// Plain views named `<entity>_<output>` reading source tables directly.
+    Views { entity: &'a str },
+    /// dbt models referenced through `ref()` and `source()`.
+    Dbt { entity: &'a str },
+}
+
+impl Naming<'_> {
+    pub(crate) fn relation_name(&self, output: &str) -> String {
+        match self {
+            Naming::Views { entity } | Naming::Dbt { entity } => format!("{}_{}", entity, output),
+        }
+    }
+
+    fn relation(&self, output: &str, dialect: Dialect) -> String {
+        match self {
+            Naming::Views { .. } => dialect.ident(&self.relation_name(output)),
+            Naming::Dbt { .. } => format!(
+                "{{{{ ref({}) }}}}",
+                jinja_string(&self.relation_name(output))
+            ),
+        }
+    }
+
+    fn source(&self, source: &IrSource, dialect: Dialect) -> String {
+        let table = source.table.as_deref().unwrap_or(&source.name);
+        match self {
+            Naming::Views { .. } => dialect.table(table),
+            Naming::Dbt { .. } => format!(
+                "{{{{ source({}, {}) }}}}",
+                jinja_string(source.system.as_deref().unwrap_or(&source.name)),
+                jinja_string(table)
+            ),
+        }
+    }
+}
+
+/// `value` as a Jinja string literal, for `ref()` and `source()` arguments.
+fn jinja_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// One executable query per plan execution stage, named after the stage output.
+#[derive(Debug, Clone)]
+pub struct StageQuery {
+    pub stage: usize,
+    pub title: String,
+    pub output: String,
+    pub depends_on: Vec<String>,
+    pub sql: String,
+}
+
+pub(crate) fn stage_queries(ir: &Ir, dialect: Dialect, naming: &Naming) -> Result<Vec<StageQuery>> {
+    if ir.sources.is_empty() {
+        bail!("IR has no sources; nothing to generate");
+    }
+    let attributes = ir.attributes();
+    // Semantic rules need an embedding model, which SQL cannot call, and
+    // Bloom filter rules need bit counts SQL has no portable way to take.
+    let (exact, fuzzy): (Vec<&IrRule>, Vec<&IrRule>) = ir
+        .rules
+        .iter()
+        .filter(|r| r.field.is_some() && r.match_type != "semantic" && !compares_bloom(ir, r))
+        .partition(|r| r.match_type == "exact");
+
+    Ok(vec![
+        stage(
+            1,
+            "Normalize sources",
+            "normalized_entities",
+            &[],
+            normalize_sql(ir, &attributes, dialect, naming),
+        ),
+        stage(
+            2,
+            "Generate blocking keys",
+            "candidate_pairs",
+            &["normalized_entities"],
+            candidate_pairs_sql(ir, dialect, naming),
+        ),
+        stage(
+            3,
+            "Exact matches",
+            "exact_match_scores",
+            &["candidate_pairs", "normalized_entities"],
+            scores_sql(&exact, dialect, naming),
+        ),
+        stage(
+            4,
+            "Fuzzy matches",
+            "fuzzy_match_scores",
+            &["candidate_pairs", "normalized_entities"],
+            scores_sql(&fuzzy, dialect, naming),
+        ),
+        stage(
+            5,
+            "Score & decide",
+            "match_decisions",
+            &["exact_match_scores", "fuzzy_match_scores"],
+            decisions_sql(ir, &exact, &fuzzy, dialect, naming),
+        ),
+        stage(
+            6,
+            "Cluster entities",
+            "entity_clusters",
+            &["match_decisions", "normalized_entities"],
+            clusters_sql(ir, dialect, naming),
+        ),
+        stage(
+            7,
+            "Apply survivorship",
+            "golden_records",
+            &["entity_clusters", "normalized_entities"],
+            golden_records_sql(ir, &attributes, dialect, naming),
+        ),
+        stage(
+            8,
+            "Emit outputs",
+            "canonical_entities",
+            &["golden_records"],
+            canonical_sql(&attributes, dialect, naming),
+        ),
+    ])
+}
+
+fn stage(n: usize, title: &str, output: &str, depends_on: &[&str], sql: String) -> StageQuery {
+    StageQuery {
+        stage: n,
+        title: title.to_string(),
+        output: output.to_string(),
+        depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+        sql,
+    }
+}
+
+// ── Generation ─────────────────────────────────────────────────────
+
+/// Lower compiled IR into a script of views, one per execution stage.
+pub fn generate_sql(ir: &Ir, dialect: Dialect) -> Result<String> {
+    let entity = ir.entity_name();
+    let naming = Naming::Views { entity };
+    let stages = stage_queries(ir, dialect, &naming)?;
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "-- Generated by kanoniv from {} ({})",
+        entity,
+        ir.identity_version.as_deref().unwrap_or("unknown")
+    )?;
+    writeln!(out, "-- Dialect: {}", dialect.name())?;
+    writeln!(out, "-- Plan hash: {}", ir.plan_hash)?;
+    if let Some(note) = dialect.requirements_note() {
+        writeln!(out, "-- {}", note)?;
+    }
+    for note in engine_only_notes(ir) {
+        writeln!(out, "-- {}", note)?;
+    }
+    for note in encoding_notes(ir) {
+        writeln!(out, "-- {}", note)?;
+    }
+
+    for stage in &stages {
+        writeln!(out)?;
+        writeln!(out, "-- Stage {}: {}", stage.stage, stage.title)?;
+        writeln!(
+            out,
+            "{}",
+            dialect.create_view(&naming.relation(&stage.output, dialect))
+        )?;
+        writeln!(out, "{};", stage.sql)?;
+    }
+
+    Ok(out)
+}
+
+/// Whether `rule` compares a Bloom filter encoded attribute.
+fn compares_bloom(ir: &Ir, rule: &IrRule) -> bool {
+    let encoding = rule
+        .field
+        .as_deref()
+        .and_then(|f| ir.encoding.as_ref()?.field(f));
+    matches!(encoding, Some(FieldEncoding::Bloom(_))) && rule.match_type != "exact"
+}
+
+/// What SQL leaves to whoever loads the sources: encoded attributes are
+/// compared as the tables hold them, and Bloom filter rules are not
+/// generated.
+fn encoding_notes(ir: &Ir) -> Vec<String> {
+    let Some(encoding) = ir.encoding.as_ref().filter(|e| !e.fields.is_empty()) else {
+        return Vec::new();
+    };
+    let fields: Vec<&str> = encoding.fields.keys().map(String::as_str).collect();
+    let mut notes = vec![format!(
+        "Encoded attributes are compared as the sources hold them; encode them with kanoniv tokenize --field: {}",
+        fields.join(", ")
+    )];
+    let bloom: Vec<&str> = ir
+        .rules
+        .iter()
+        .filter(|r| compares_bloom(ir, r))
+        .map(|r| r.name.as_str())
+        .collect();
+    if !bloom.is_empty() {
+        notes.push(format!(
+            "Bloom filter rules need an engine and are not generated: {}",
+            bloom.join(", ")
+        ));
+    }
+    notes
+}
+
+/// What generated code leaves to an engine: semantic rules, LSH and canopy
+/// blocking, which are generated as blocking on the key values, and
+/// clustering strategies other than transitive closure.
+pub(crate) fn engine_only_notes(ir: &Ir) -> Vec<String> {
+    let mut notes = Vec::new();
+    let names: Vec<&str> = ir
+        .rules
+        .iter()
+        .filter(|r| r.match_type == "semantic")
+        .map(|r| r.name.as_str())
+        .collect();
+    if !names.is_empty() {
+        notes.push(format!(
+            "Semantic rules need an embedding model and are not generated: {}",
+            names.join(", ")
+        ));
+    }
+    if ir.blocking.lsh.is_some() {
+        notes.push(
+            "LSH blocking needs an engine; candidate pairs here share a blocking key value"
+                .to_string(),
+        );
+    }
+    if ir.blocking.canopy.is_some() {
+        notes.push(
+            "Canopy blocking needs an engine; candidate pairs here share a blocking key value"
+                .to_string(),
+        );
+    }
+    let custom: Vec<&str> = ir
+        .survivorship
+        .iter()
+        .filter(|r| r.strategy == "custom")
+        .map(|r| r.field.as_str())
+        .collect();
+    if !custom.is_empty() {
+        notes.push(format!(
+            "Custom survivorship expressions need an engine; these fields keep the lowest record key's value: {}",
+            custom.join(", ")
+        ));
+    }
+    let mut conditions: Vec<String> = ir
+        .rules
+        .iter()
+        .filter(|r| r.condition.is_some())
+        .map(|r| format!("rule {}", r.name))
+        .collect();
+    conditions.extend(
+        ir.survivorship
+            .iter()
+            .filter(|r| r.condition.is_some())
+            .map(|r| format!("survivorship of {}", r.field)),
+    );
+    if !conditions.is_empty() {
+        notes.push(format!(
+            "Conditions need an engine and are not applied here: {}",
+            conditions.join(", ")
+        ));
+    }
+    if ir
+        .temporal
+        .as_ref()
+        .is_some_and(|t| t.match_window_days.is_some())
+    {
+        notes.push(
+            "Temporal match windows need an engine; candidate pairs here ignore validity intervals"
+                .to_string(),
+        );
+    }
+    if let Some(clustering) = ir
+        .clustering
+        .filter(|c| c.strategy != ClusteringStrategy::TransitiveClosure)
+    {
+        notes.push(format!(
+            "Clustering strategy {} needs an engine; clusters here are the transitive closure of matches",
+            clustering.strategy
+        ));
+    }
+    notes
+}
+
+fn normalize_sql(ir: &Ir, attributes: &[String], dialect: Dialect, naming: &Naming) -> String {
+    let string_type = dialect.string_type();
+
+    let selects: Vec<String> = ir
+        .sources
+        .iter()
+        .map(|source| {
+            let id = dialect.ident(source.id.as_deref().unwrap_or("id"));
+            let name = dialect.literal(&source.name);
+            let mut cols = vec![
+                format!("    {} AS source_name", name),
+                format!(
+                    "    CONCAT({}, ':', CAST({} AS {})) AS record_key",
+                    name, id, string_type
+                ),
+            ];
+            for attr in attributes {
+                let expr = match source.attributes.get(attr) {
+                    Some(column) => {
+                        format!("TRIM(CAST({} AS {}))", dialect.ident(column), string_type)
+                    }
+                    None => format!("CAST(NULL AS {})", string_type),
+                };
+                cols.push(format!("    {} AS {}", expr, dialect.ident(attr)));
+            }
+            // Record-scoped deletions drop tombstoned records before matching.
+            let tombstone = ir
+                .deletion
+                .as_ref()
+                .filter(|d| d.scope == DeletionScope::Record)
+                .and_then(|d| source.attributes.get(&d.tombstone));
+            let live = match tombstone {
+                Some(column) => format!(
+                    "\nWHERE {}",
+                    live_condition(&format!(
+                        "TRIM(CAST({} AS {}))",
+                        dialect.ident(column),
+                        string_type
+                    ))
+                ),
+                None => String::new(),
+            };
+            format!(
+                "SELECT\n{}\nFROM {}{}",
+                cols.join(",\n"),
+                naming.source(source, dialect),
+                live
+            )
+        })
+        .collect();
+
+    selects.join("\nUNION ALL\n")
+}
+
+fn blocking_expr(field: &str, transform: Option<&str>, dialect: Dialect, alias: &str) -> String {
+    let col = format!("{}.{}", alias, dialect.ident(field));
+    match transform.unwrap_or("identity") {
+        "lower" | "lowercase" => format!("LOWER({})", col),
+        "upper" | "uppercase" => format!("UPPER({})", col),
+        "trim" => format!("TRIM({})", col),
+        "soundex" if dialect != Dialect::Ansi => format!("SOUNDEX({})", col),
+        other => {
+            let prefix = other
+                .strip_prefix("first_")
+                .or_else(|| other.strip_prefix("prefix:"))
+                .and_then(|n| n.parse::<usize>().ok());
+            match (address::geohash_precision(other), prefix) {
+                (Some(precision), _) => dialect.geohash(&col, precision).unwrap_or(col),
+                (None, Some(n)) => dialect.prefix(&format!("LOWER({})", col), n),
+                (None, None) => col,
+            }
+        }
+    }
+}
+
+fn candidate_pairs_sql(ir: &Ir, dialect: Dialect, naming: &Naming) -> String {
+    let normalized = naming.relation("normalized_entities", dialect);
+
+    let mut selects: Vec<String> = Vec::new();
+    if let Some(window) = &ir.blocking.sorted_neighborhood {
+        // Each record pairs with the next `window - 1` in sort order.
+        let field = &window.sort_key;
+        let sort = blocking_expr(field, window.transform.as_deref(), dialect, "e");
+        let ranked = format!(
+            "(SELECT e.record_key, ROW_NUMBER() OVER (ORDER BY {}, e.record_key) AS position\n FROM {} e\n WHERE e.{} IS NOT NULL)",
+            sort,
+            normalized,
+            dialect.ident(field)
+        );
+        selects.push(format!(
+            "-- Sorted neighbourhood on {0}, window {1}\nSELECT\n  CASE WHEN a.record_key < b.record_key THEN a.record_key ELSE b.record_key END AS left_key,\n  CASE WHEN a.record_key < b.record_key THEN b.record_key ELSE a.record_key END AS right_key\nFROM {2} a\nJOIN {2} b\n  ON b.position > a.position\n AND b.position < a.position + {1}",
+            field, window.window, ranked
+        ));
+    }
+
+    if ir.blocking.keys.is_empty() && selects.is_empty() {
+        return format!(
+            "-- No blocking keys: full pairwise comparison (O(n^2))\nSELECT a.record_key AS left_key, b.record_key AS right_key\nFROM {0} a\nJOIN {0} b ON a.record_key < b.record_key",
+            normalized
+        );
+    }
+
+    selects.extend(ir.blocking.keys.iter().map(|key| {
+            let left = blocking_expr(&key.field, key.transform.as_deref(), dialect, "a");
+            let right = blocking_expr(&key.field, key.transform.as_deref(), dialect, "b");
+            format!(
+                "SELECT a.record_key AS left_key, b.record_key AS right_key\nFROM {0} a\nJOIN {0} b\n  ON {1} = {2}\n AND a.record_key < b.record_key\nWHERE a.{3} IS NOT NULL",
+                normalized, left, right, dialect.ident(&key.field)
+            )
+    }));
+
+    let union = match dialect {
+        Dialect::BigQuery => "\nUNION DISTINCT\n",
+        _ => "\nUNION\n",
+    };
+    selects.join(union)
+}
+
+fn rule_score_expr(rule: &IrRule, dialect: Dialect) -> Option<String> {
+    let field = rule.field.as_deref()?;
+    let a = format!("LOWER(a.{})", dialect.ident(field));
+    let b = format!("LOWER(b.{})", dialect.ident(field));
+
+    let expr = if rule.match_type == "exact" {
+        format!("CASE WHEN {} = {} THEN 1.0 ELSE 0.0 END", a, b)
+    } else {
+        let sim = dialect.similarity(rule.algorithm.as_deref(), &a, &b);
+        match rule.threshold {
+            Some(t) => format!("CASE WHEN {} >= {} THEN 1.0 ELSE 0.0 END", sim, t),
+            None => sim,
+        }
+    };
+    Some(format!("COALESCE({}, 0.0)", expr))
+}
+
+fn scores_sql(rules: &[&IrRule], dialect: Dialect, naming: &Naming) -> String {
+    let mut cols = vec!["    p.left_key".to_string(), "    p.right_key".to_string()];
+    for rule in rules {
+        if let Some(expr) = rule_score_expr(rule, dialect) {
+            cols.push(format!("    {} AS {}", expr, score_column(rule, dialect)));
+        }
+    }
+    let normalized = naming.relation("normalized_entities", dialect);
+    format!(
+        "SELECT\n{}\nFROM {} p\nJOIN {2} a ON a.record_key = p.left_key\nJOIN {2} b ON b.record_key = p.right_key",
+        cols.join(",\n"),
+        naming.relation("candidate_pairs", dialect),
+        normalized
+    )
+}
+
+/// The column holding `rule`'s score in the match score stages.
+fn score_column(rule: &IrRule, dialect: Dialect) -> String {
+    dialect.ident(&format!("{}_score", rule.name))
+}
+
+fn decisions_sql(
+    ir: &Ir,
+    exact: &[&IrRule],
+    fuzzy: &[&IrRule],
+    dialect: Dialect,
+    naming: &Naming,
+) -> String {
+    let match_t = ir.thresholds.as_ref().and_then(|t| t.match_).unwrap_or(1.0);
+    let review_t = ir.thresholds.as_ref().and_then(|t| t.review);
+
+    let weighted: Vec<String> = exact
+        .iter()
+        .map(|r| format!("{} * e.{}", r.weight, score_column(r, dialect)))
+        .chain(
+            fuzzy
+                .iter()
+                .map(|r| format!("{} * f.{}", r.weight, score_column(r, dialect))),
+        )
+        .collect();
+    let total_weight: f64 = exact.iter().chain(fuzzy.iter()).map(|r| r.weight).sum();
+    let score = if weighted.is_empty() || total_weight <= 0.0 {
+        "0.0".to_string()
+    } else {
+        format!("({}) / {}", weighted.join(" + "), total_weight)
+    };
+
+    let review = review_t
+        .map(|r| format!("\n        WHEN score >= {} THEN 'review'", r))
+        .unwrap_or_default();
+
+    format!(
+        "WITH scored AS (\n    SELECT e.left_key, e.right_key, {} AS score\n    FROM {} e\n    JOIN {} f ON f.left_key = e.left_key AND f.right_key = e.right_key\n)\nSELECT\n    left_key,\n    right_key,\n    score,\n    CASE\n        WHEN score >= {} THEN 'match'{}\n        ELSE 'reject'\n    END AS decision\nFROM scored",
+        score,
+        naming.relation("exact_match_scores", dialect),
+        naming.relation("fuzzy_match_scores", dialect),
+        match_t,
+        review
+    )
+}
+
+fn clusters_sql(ir: &Ir, dialect: Dialect, naming: &Naming) -> String {
+    // Recursive walks are depth-bounded so engines without cycle detection
+    // (Snowflake, BigQuery) terminate.
+    let decisions = naming.relation("match_decisions", dialect);
+    let normalized = naming.relation("normalized_entities", dialect);
+    let walk = format!(
+        "WITH RECURSIVE edges AS (\n    SELECT left_key AS src, right_key AS dst FROM {0} WHERE decision = 'match'\n    UNION ALL\n    SELECT right_key AS src, left_key AS dst FROM {0} WHERE decision = 'match'\n),\nreach (record_key, reachable, depth) AS (\n    SELECT record_key, record_key, 0 FROM {1}\n    UNION ALL\n    SELECT r.record_key, e.dst, r.depth + 1\n    FROM reach r\n    JOIN edges e ON e.src = r.reachable\n    WHERE r.depth < 16\n)",
+        decisions, normalized
+    );
+    match ir.deletion.as_ref().filter(|d| d.scope == DeletionScope::Entity) {
+        // Entity-scoped deletions erase every cluster holding a tombstoned record.
+        Some(deletion) => format!(
+            "{},\nclusters AS (\n    SELECT record_key, MIN(reachable) AS cluster_id\n    FROM reach\n    GROUP BY record_key\n)\nSELECT c.record_key, c.cluster_id\nFROM clusters c\nWHERE c.cluster_id NOT IN (\n    SELECT d.cluster_id\n    FROM clusters d\n    JOIN {} n ON n.record_key = d.record_key\n    WHERE NOT {}\n)",
+            walk,
+            normalized,
+            live_condition(&format!("n.{}", dialect.ident(&deletion.tombstone)))
+        ),
+        None => format!(
+            "{}\nSELECT record_key, MIN(reachable) AS cluster_id\nFROM reach\nGROUP BY record_key",
+            walk
+        ),
+    }
+}
+
+/// Whether a tombstone expression leaves its record live; keep in step
+/// with `Deletion::is_deleted`.
+fn live_condition(expr: &str) -> String {
+    let values: Vec<String> = LIVE_VALUES.iter().map(|v| format!("'{}'", v)).collect();
+    format!("COALESCE(LOWER({}), '') IN ({})", expr, values.join(", "))
+}
+
+/// Keep in step with `survivorship::survivor`, which applies the same order
+/// to exported clusters. Members with an empty `current` column come first.
+fn survivorship_order(
+    rule: &IrSurvivorship,
+    field: &str,
+    current: Option<&str>,
+    dialect: Dialect,
+) -> String {
+    let column = dialect.ident(field);
+    let mut nulls_last = format!("CASE WHEN m.{} IS NULL THEN 1 ELSE 0 END", column);
+    if let Some(current) = current {
+        nulls_last.push_str(&format!(
+            ", CASE WHEN m.{} IS NULL THEN 0 ELSE 1 END",
+            dialect.ident(current)
+        ));
+    }
+    let order = match rule.strategy.as_str() {
+        "source_priority" => {
+            let priority = rule.source_priority.clone().unwrap_or_default();
+            let whens: Vec<String> = priority
+                .iter()
+                .enumerate()
+                .map(|(i, s)| format!("WHEN {} THEN {}", dialect.literal(s), i))
+                .collect();
+            if whens.is_empty() {
+                "m.record_key".to_string()
+            } else {
+                format!(
+                    "CASE m.source_name {} ELSE {} END, m.record_key",
+                    whens.join(" "),
+                    priority.len()
+                )
+            }
+        }
+        "longest" | "most_complete" => format!("LENGTH(m.{}) DESC, m.record_key", column),
+        "most_frequent" => format!("m.{} DESC, m.record_key", frequency_column(field, dialect)),
+        // Without a timestamp attribute the newest record key is the most recent.
+        "most_recent" => match &rule.timestamp {
+            Some(ts) => format!(
+                "CASE WHEN m.{0} IS NULL THEN 1 ELSE 0 END, m.{0} DESC, m.record_key DESC",
+                dialect.ident(ts)
+            ),
+            None => "m.record_key DESC".to_string(),
+        },
+        // Custom expressions need an engine; see `engine_only_notes`.
+        _ => "m.record_key".to_string(),
+    };
+    format!("{}, {}", nulls_last, order)
+}
+
+/// The column counting how many of a cluster's members share each value of
+/// `field`, for `most_frequent` survivorship.
+fn frequency_column(field: &str, dialect: Dialect) -> String {
+    dialect.ident(&format!("{}__freq", field))
+}
+
+fn golden_records_sql(ir: &Ir, attributes: &[String], dialect: Dialect, naming: &Naming) -> String {
+    let mut freq_cols = Vec::new();
+    let mut cols = vec!["    m.cluster_id".to_string()];
+    for attr in attributes {
+        let rule = survivorship::rule_for(ir, attr);
+        let current = survivorship::current_marker(ir, attr);
+        if rule.strategy == "most_frequent" {
+            freq_cols.push(format!(
+                "        COUNT(*) OVER (PARTITION BY c.cluster_id, n.{}) AS {}",
+                dialect.ident(attr),
+                frequency_column(attr, dialect)
+            ));
+        }
+        let first_value = |field: &str, order: String| {
+            format!(
+                "FIRST_VALUE(m.{}) OVER (\n        PARTITION BY m.cluster_id\n        ORDER BY {}\n        ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING\n    )",
+                dialect.ident(field),
+                order
+            )
+        };
+        let value = match rule.fields.as_deref() {
+            // The first listed field any member has, from the lowest key.
+            Some(fields) if rule.strategy == "non_null_priority" => format!(
+                "COALESCE(\n        {}\n    )",
+                fields
+                    .iter()
+                    .map(|f| first_value(
+                        f,
+                        survivorship_order(&rule, f, current.as_deref(), dialect)
+                    )
+                    .replace('\n', "\n    "))
+                    .collect::<Vec<_>>()
+                    .join(",\n        ")
+            ),
+            _ => first_value(
+                attr,
+                survivorship_order(&rule, attr, current.as_deref(), dialect),
+            ),
+        };
+        cols.push(format!("    {} AS {}", value, dialect.ident(attr)));
+    }
+
+    let extra = if freq_cols.is_empty() {
+        String::new()
+    } else {
+        format!(",\n{}", freq_cols.join(",\n"))
+    };
+    format!(
+        "WITH members AS (\n    SELECT\n        c.cluster_id,\n        n.*{}\n    FROM {} n\n    JOIN {} c ON c.record_key = n.record_key\n)\nSELECT DISTINCT\n{}\nFROM members m",
+        extra,
+        naming.relation("normalized_entities", dialect),
+        naming.relation("entity_clusters", dialect),
+        cols.join(",\n")
+    )
+}
+
+fn canonical_sql(attributes: &[String], dialect: Dialect, naming: &Naming) -> String {
+    let mut cols = vec!["    cluster_id AS entity_id".to_string()];
+    cols.extend(
+        attributes
+            .iter()
+            .map(|a| format!("    {}", dialect.ident(a))),
+    );
+    format!(
+        "SELECT\n{}\nFROM {}",
+        cols.join(",\n"),
+        naming.relation("golden_records", dialect)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, quoted_execution, EXECUTION};
+    #[cfg(feature = "sqlite")]
+    use crate::test_support::{AS_OF, EXECUTION_RECORDS};
+
+    #[test]
+    fn test_generated_sql_quotes_names_and_escapes_literals() {
+        use crate::{generate_dbt_project, generate_sql, Dialect};
+
+        let ir = compiled_ir(&quoted_execution().0);
+        let sql = generate_sql(&ir, Dialect::Postgres).unwrap();
+        assert!(
+            sql.contains("CONCAT('crm''x', ':', CAST(contact_id AS TEXT)) AS record_key"),
+            "{}",
+            sql
+        );
+        assert!(sql.contains("TRIM(CAST(\"Email Address\" AS TEXT)) AS email"));
+        assert!(sql.contains("FROM \"crm contacts\"\n"));
+        assert!(sql
+            .contains("CASE m.source_name WHEN 'billing' THEN 0 WHEN 'crm''x' THEN 1 ELSE 2 END"));
+        // Plain names are left as they are.
+        assert!(sql.contains("CREATE OR REPLACE VIEW customer_candidate_pairs AS"));
+
+        let sql = generate_sql(&ir, Dialect::BigQuery).unwrap();
+        assert!(sql.contains("'crm\\'x' AS source_name"), "{}", sql);
+        assert!(sql.contains("TRIM(CAST(`Email Address` AS STRING)) AS email"));
+
+        let files = generate_dbt_project(&ir, Dialect::Postgres).unwrap();
+        let normalize = files
+            .iter()
+            .find(|f| f.path.ends_with("customer_normalized_entities.sql"))
+            .unwrap();
+        assert!(
+            normalize
+                .contents
+                .contains("FROM {{ source('sales\\'force', 'crm contacts') }}"),
+            "{}",
+            normalize.contents
+        );
+
+        // Reserved words are quoted, whatever their case.
+        let reserved = EXECUTION
+            .replace("family_name", "Order")
+            .replace("table: customers", "table: group");
+        let ir = compiled_ir(&reserved);
+        let sql = generate_sql(&ir, Dialect::Postgres).unwrap();
+        assert!(
+            sql.contains("TRIM(CAST(\"Order\" AS TEXT)) AS last_name"),
+            "{}",
+            sql
+        );
+        assert!(sql.contains("FROM \"group\"\n"));
+        assert!(generate_sql(&ir, Dialect::BigQuery)
+            .unwrap()
+            .contains("FROM `group`\n"));
+    }
+
+    /// Generated SQL run in SQLite must agree with the interpreter on the
+    /// fixture, and on the fixture with names that need quoting. Its fuzzy rule
+    /// compares names that are equal or far apart, since ANSI SQL degrades
+    /// fuzzy rules to equality.
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_generated_sql_agrees_with_the_interpreter() {
+        use crate::{execute_plan, generate_sql, Dialect};
+        use rusqlite::types::Value as SqlValue;
+        use std::collections::BTreeSet;
+
+        for (spec, records) in [
+            (EXECUTION.to_string(), EXECUTION_RECORDS.to_string()),
+            quoted_execution(),
+        ] {
+            let ir = compiled_ir(&spec);
+            let records: serde_json::Value = serde_json::from_str(&records).unwrap();
+            let expected = execute_plan(&ir, &records, AS_OF).unwrap();
+
+            let db = rusqlite::Connection::open_in_memory().unwrap();
+            for source in &ir.sources {
+                let rows = records[&source.name].as_array().unwrap();
+                let mut columns: Vec<&str> = Vec::new();
+                for row in rows {
+                    for column in row.as_object().unwrap().keys() {
+                        if !columns.contains(&column.as_str()) {
+                            columns.push(column);
+                        }
+                    }
+                }
+                let table = source.table.as_deref().unwrap();
+                let names: Vec<String> = columns.iter().map(|c| format!("\"{}\"", c)).collect();
+                db.execute_batch(&format!(
+                    "CREATE TABLE \"{}\" ({})",
+                    table,
+                    names.join(", ")
+                ))
+                .unwrap();
+                let insert = format!(
+                    "INSERT INTO \"{}\" ({}) VALUES ({})",
+                    table,
+                    names.join(", "),
+                    vec!["?"; columns.len()].join(", ")
+                );
+                for row in rows {
+                    let values = columns.iter().map(|c| match &row[*c] {
+                        serde_json::Value::Null => SqlValue::Null,
+                        serde_json::Value::String(s) => SqlValue::Text(s.clone()),
+                        other => SqlValue::Text(other.to_string()),
+                    });
+                    db.execute(&insert, rusqlite::params_from_iter(values))
+                        .unwrap();
+                }
+            }
+            db.execute_batch(&generate_sql(&ir, Dialect::Ansi).unwrap())
+                .unwrap();
+
+            let text = |row: &rusqlite::Row, i: usize| row.get::<_, Option<String>>(i).unwrap();
+            let mut decisions = db.prepare("SELECT left_key, right_key, score, decision FROM customer_match_decisions ORDER BY left_key, right_key").unwrap();
+            let decisions: Vec<(String, String, f64, String)> = decisions
+                .query_map([], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                })
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(decisions.len(), expected.decisions.len());
+            for ((left, right, score, decision), want) in decisions.iter().zip(&expected.decisions)
+            {
+                assert_eq!(
+                    (left, right, decision),
+                    (&want.left_key, &want.right_key, &want.decision)
+                );
+                assert!(
+                    (score - want.score).abs() < 1e-9,
+                    "{} {}: {} vs {}",
+                    left,
+                    right,
+                    score,
+                    want.score
+                );
+            }
+
+            let mut clusters = db
+                .prepare("SELECT record_key, cluster_id FROM customer_entity_clusters")
+                .unwrap();
+            let clusters: BTreeSet<(String, String)> = clusters
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            let want: BTreeSet<(String, String)> = expected
+                .clusters
+                .assignments
+                .iter()
+                .map(|a| (a.record_key.clone(), a.cluster_id.clone()))
+                .collect();
+            assert_eq!(clusters, want);
+
+            let fields: Vec<&str> = expected
+                .golden
+                .fields
+                .iter()
+                .map(|f| f.field.as_str())
+                .collect();
+            let mut entities = db
+                .prepare(&format!(
+                    "SELECT entity_id, {} FROM customer_canonical_entities",
+                    fields.join(", ")
+                ))
+                .unwrap();
+            let entities: BTreeSet<Vec<Option<String>>> = entities
+                .query_map([], |row| {
+                    Ok((0..=fields.len()).map(|i| text(row, i)).collect())
+                })
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            let want: BTreeSet<Vec<Option<String>>> = expected
+                .golden
+                .records
+                .iter()
+                .map(|r| {
+                    std::iter::once(Some(r.entity_id.clone()))
+                        .chain(r.values.iter().cloned())
+                        .collect()
+                })
+                .collect();
+            assert_eq!(entities, want);
+        }
+    }
+}
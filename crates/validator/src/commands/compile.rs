@@ -1,59 +1,330 @@
 use anyhow::{Context, Result};
-use sha2::{Digest, Sha256};
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 
+use crate::attributes;
+use crate::blocking::{Canopy, SortedNeighborhood};
+use crate::canonical::ir_hash;
+use crate::clustering::Clustering;
+use crate::commands::codegen;
+use crate::compose;
+use crate::deletion::Deletion;
+use crate::encoding::Encoding;
+use crate::execution::Execution;
+use crate::hashing::HashingConfig;
+use crate::interpolate::Variables;
+use crate::ir::{self, Ir, IrEncoding, IR_VERSION};
+use crate::lsh::LshConfig;
+use crate::mappings;
+use crate::normalize::Normalization;
+use crate::output::Output;
 use crate::parser;
+use crate::plugins::{Compiled, PluginRegistry};
+use crate::privacy::Privacy;
+use crate::relationships::Relationship;
+use crate::stewardship::Stewardship;
 
-pub fn run(file: &Path, output: Option<&Path>) -> Result<()> {
-    let content = fs::read_to_string(file)
-        .with_context(|| format!("Failed to read file: {}", file.display()))?;
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: &Path,
+    output: Option<&Path>,
+    target: &str,
+    dialect: &str,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    plugins: &PluginRegistry,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
 
     let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
 
     let ir = compile_to_ir(&spec)?;
+    out.detail(format!(
+        "Plan hash: {}",
+        ir.get("plan_hash").and_then(|h| h.as_str()).unwrap_or("")
+    ));
 
-    let output_json = serde_json::to_string_pretty(&ir)?;
+    let encoding: IrEncoding = format.parse()?;
+    if target != "ir" && encoding != IrEncoding::Json {
+        anyhow::bail!("--format {} applies only to --target ir", format);
+    }
+    let output_text = match target {
+        "ir" if encoding != IrEncoding::Json => {
+            let bytes = encoding.encode(&ir)?;
+            match output {
+                Some(path) => {
+                    fs::write(path, &bytes)
+                        .with_context(|| format!("Failed to write file: {}", path.display()))?;
+                    out.info(format!(
+                        "Compiled to: {} ({} bytes, digest {})",
+                        path.display(),
+                        bytes.len(),
+                        ir::ir_digest(&ir)
+                    ));
+                }
+                None if encoding.is_text() => out.result(String::from_utf8(bytes)?),
+                None => std::io::stdout().write_all(&bytes)?,
+            }
+            return Ok(());
+        }
+        "ir" => serde_json::to_string_pretty(&ir)?,
+        "sql" => {
+            let dialect: codegen::sql::Dialect = dialect.parse()?;
+            codegen::sql::generate_sql(&Ir::from_value(&ir)?, dialect)?
+        }
+        "pyspark" => codegen::pyspark::generate_pyspark(&Ir::from_value(&ir)?)?,
+        "kafka" => codegen::kafka::generate_kafka(&Ir::from_value(&ir)?)?,
+        "dbt" => {
+            let dir = output.with_context(|| "--target dbt requires an output directory (-o)")?;
+            let dialect: codegen::sql::Dialect = dialect.parse()?;
+            let files = codegen::dbt::generate_dbt_project(&Ir::from_value(&ir)?, dialect)?;
+            codegen::write_files(dir, &files)?;
+            out.info(format!(
+                "Generated dbt project: {} ({} files)",
+                dir.display(),
+                files.len()
+            ));
+            return Ok(());
+        }
+        other => match plugins.target(other) {
+            Some(plugin) => match plugin.compile(other, &Ir::from_value(&ir)?)? {
+                Compiled::Text(text) => text,
+                Compiled::Files(files) => {
+                    let dir = output.with_context(|| {
+                        format!("--target {} requires an output directory (-o)", other)
+                    })?;
+                    codegen::write_files(dir, &files)?;
+                    out.info(format!(
+                        "Generated {} output: {} ({} files)",
+                        other,
+                        dir.display(),
+                        files.len()
+                    ));
+                    return Ok(());
+                }
+            },
+            None => anyhow::bail!(
+                "Unknown compile target: '{}'. Expected one of: {}",
+                other,
+                ["ir", "sql", "dbt", "pyspark", "kafka"]
+                    .into_iter()
+                    .chain(plugins.targets())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        },
+    };
 
     if let Some(output_path) = output {
-        fs::write(output_path, &output_json)?;
-        println!("Compiled to: {}", output_path.display());
+        fs::write(output_path, &output_text)?;
+        out.info(format!("Compiled to: {}", output_path.display()));
     } else {
-        println!("{}", output_json);
+        out.result(output_text);
     }
 
     Ok(())
 }
 
 pub fn compile_to_ir(spec: &serde_json::Value) -> Result<serde_json::Value> {
-    let ir = serde_json::json!({
+    // The IR is in canonical attribute space; column aliases are resolved
+    // into each source's attributes.
+    let spec = &mappings::resolve(spec);
+    let mut ir = serde_json::json!({
         "api_version": spec.get("api_version"),
         "identity_version": spec.get("identity_version"),
         "entity": spec.get("entity").and_then(|e| e.get("name")),
         "sources": spec.get("sources").map(|s| {
             s.as_array().map(|arr| {
-                arr.iter().map(|source| {
-                    serde_json::json!({
-                        "name": source.get("name"),
-                        "system": source.get("system"),
-                        "table": source.get("table"),
-                    })
-                }).collect::<Vec<_>>()
+                arr.iter().map(compile_source).collect::<Vec<_>>()
             })
         }),
         "rule_count": spec.get("rules").and_then(|r| r.as_array()).map(|a| a.len()),
+        "rules": spec.get("rules").and_then(|r| r.as_array()).map(|rules| {
+            rules.iter().map(|rule| {
+                let mut compiled = serde_json::json!({
+                    "name": rule.get("name"),
+                    "type": rule.get("type"),
+                    "field": rule.get("field"),
+                    "algorithm": rule.get("algorithm"),
+                    "threshold": rule.get("threshold"),
+                    "weight": rule.get("weight").and_then(|w| w.as_f64()).unwrap_or(0.0),
+                });
+                // Only semantic rules name a model, and only some rules have a
+                // condition; other rules' IR is unchanged.
+                for key in ["model", "endpoint", "condition"] {
+                    if let Some(value) = rule.get(key).filter(|v| !v.is_null()) {
+                        compiled[key] = value.clone();
+                    }
+                }
+                compiled
+            }).collect::<Vec<_>>()
+        }),
         "blocking_strategy": spec.get("blocking").and_then(|b| b.get("strategy")),
+        "blocking": {
+            "strategy": spec.get("blocking").and_then(|b| b.get("strategy")),
+            "keys": compile_blocking_keys(spec),
+        },
+        "survivorship": spec.get("survivorship")
+            .and_then(|s| s.get("rules"))
+            .and_then(|r| r.as_array())
+            .map(|rules| {
+                rules.iter().map(|rule| {
+                    let mut compiled = serde_json::json!({
+                        "field": rule.get("field"),
+                        "strategy": rule.get("strategy"),
+                        "source_priority": rule.get("source_priority"),
+                    });
+                    // Only the strategies that read them carry these keys.
+                    for key in ["timestamp", "fields", "expression", "condition"] {
+                        if let Some(value) = rule.get(key).filter(|v| !v.is_null()) {
+                            compiled[key] = value.clone();
+                        }
+                    }
+                    compiled
+                }).collect::<Vec<_>>()
+            }),
         "thresholds": spec.get("decision").and_then(|d| d.get("thresholds")),
     });
 
+    if let Some(temporal) = spec.get("temporal").filter(|t| !t.is_null()) {
+        ir["temporal"] = temporal.clone();
+    }
+    if spec.get("normalization").is_some_and(|n| !n.is_null()) {
+        let normalization = Normalization::from_spec(spec)?;
+        let fields: serde_json::Map<String, serde_json::Value> = normalization
+            .fields
+            .iter()
+            .map(|(field, steps)| {
+                let steps = steps.iter().map(|s| s.to_value()).collect();
+                (field.clone(), serde_json::Value::Array(steps))
+            })
+            .collect();
+        ir["normalization"] = serde_json::json!({
+            "locale": normalization.locale.name(),
+            "fields": fields,
+        });
+        if let Some(region) = normalization.default_region {
+            ir["normalization"]["default_region"] = serde_json::json!(region.code());
+        }
+    }
+    if let Some(lsh) = LshConfig::from_spec(spec)? {
+        ir["blocking"]["lsh"] = serde_json::to_value(lsh)?;
+    }
+    if let Some(window) = SortedNeighborhood::from_spec(spec)? {
+        ir["blocking"]["sorted_neighborhood"] = serde_json::to_value(window)?;
+    }
+    if let Some(canopy) = Canopy::from_spec(spec)? {
+        ir["blocking"]["canopy"] = serde_json::to_value(canopy)?;
+    }
+    if let Some(clustering) = Clustering::from_spec(spec)? {
+        ir["clustering"] = serde_json::to_value(clustering)?;
+    }
+    if let Some(execution) = Execution::from_spec(spec)? {
+        ir["execution"] = serde_json::to_value(execution)?;
+    }
+    if let Some(stewardship) = Stewardship::from_spec(spec)?.filter(|s| !s.is_empty()) {
+        stewardship.check()?;
+        ir["stewardship"] = serde_json::to_value(stewardship)?;
+    }
+    if let Some(privacy) = Privacy::from_spec(spec)? {
+        ir["privacy"] = serde_json::to_value(privacy)?;
+    }
+    if let Some(encoding) = Encoding::from_spec(spec)? {
+        ir["encoding"] = serde_json::to_value(encoding)?;
+    }
+    if let Some(deletion) = Deletion::from_spec(spec)? {
+        ir["deletion"] = serde_json::to_value(deletion)?;
+    }
+    let relationships = Relationship::from_spec(spec)?;
+    if !relationships.is_empty() {
+        ir["relationships"] = serde_json::to_value(relationships)?;
+    }
+    if spec.get("hashing").is_some_and(|h| !h.is_null()) {
+        let hashing = HashingConfig::from_spec(spec)?;
+        ir["hashing"] = serde_json::json!({
+            "algorithm": hashing.algorithm.name(),
+            "keyed": !hashing.key.is_none(),
+            "key_env": hashing.key.key_env,
+            "key_file": hashing.key.key_file.map(|p| p.display().to_string()),
+        });
+    }
+
     // Compute plan hash over the IR (without the hash itself)
-    let canonical_json = serde_json::to_string(&ir)?;
-    let mut hasher = Sha256::new();
-    hasher.update(canonical_json.as_bytes());
-    let hash = format!("sha256:{:x}", hasher.finalize());
+    let hash = ir_hash(&ir);
 
     let mut ir_with_hash = ir;
     ir_with_hash["plan_hash"] = serde_json::Value::String(hash);
+    // The format version is not hashed: a spec keeps its plan hash across
+    // IR versions.
+    ir_with_hash["ir_version"] = serde_json::Value::String(IR_VERSION.to_string());
 
     Ok(ir_with_hash)
 }
+
+/// A source's IR: attributes map to their columns, the types of typed
+/// attributes go in `types` and `pii` tags in `pii`; sources without either
+/// leave them out.
+fn compile_source(source: &serde_json::Value) -> serde_json::Value {
+    let mut compiled = serde_json::json!({
+        "name": source.get("name"),
+        "system": source.get("system"),
+        "table": source.get("table"),
+        "id": source.get("id"),
+        "attributes": source.get("attributes"),
+    });
+    if let Some(attributes) = source.get("attributes").and_then(|a| a.as_object()) {
+        let columns: serde_json::Map<String, serde_json::Value> = attributes
+            .iter()
+            .map(|(attribute, mapping)| {
+                let column = attributes::column(attribute, mapping);
+                (attribute.clone(), serde_json::Value::from(column))
+            })
+            .collect();
+        let types: serde_json::Map<String, serde_json::Value> = attributes
+            .iter()
+            .filter_map(|(attribute, mapping)| {
+                let declared = attributes::declared_type(mapping)?;
+                Some((attribute.clone(), serde_json::Value::from(declared.name())))
+            })
+            .collect();
+        let pii: serde_json::Map<String, serde_json::Value> = attributes
+            .iter()
+            .filter_map(|(attribute, mapping)| {
+                let tag = mapping.get("pii")?.as_bool()?;
+                Some((attribute.clone(), serde_json::Value::from(tag)))
+            })
+            .collect();
+        compiled["attributes"] = columns.into();
+        if !types.is_empty() {
+            compiled["types"] = types.into();
+        }
+        if !pii.is_empty() {
+            compiled["pii"] = pii.into();
+        }
+    }
+    compiled
+}
+
+/// Blocking keys may be written as a bare field name or as a mapping with
+/// `field`/`name` and an optional `transform`/`transformation`.
+fn compile_blocking_keys(spec: &serde_json::Value) -> Vec<serde_json::Value> {
+    spec.get("blocking")
+        .and_then(|b| b.get("keys"))
+        .and_then(|k| k.as_array())
+        .map(|keys| {
+            keys.iter()
+                .filter_map(|key| {
+                    if let Some(field) = key.as_str() {
+                        return Some(serde_json::json!({ "field": field, "transform": null }));
+                    }
+                    let field = key.get("field").or_else(|| key.get("name"))?.as_str()?;
+                    let transform = key.get("transform").or_else(|| key.get("transformation"));
+                    Some(serde_json::json!({ "field": field, "transform": transform }))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
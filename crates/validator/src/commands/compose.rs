@@ -0,0 +1,95 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::compose::{self, Composed};
+use crate::interpolate::Variables;
+use crate::output::Output;
+
+/// Write the spec composed over its base to `output` (or stdout), or with
+/// `diff` show what it changes in the base.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: &Path,
+    registry: Option<&str>,
+    diff: bool,
+    output: Option<&Path>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let Some(composed) = compose::resolve_file(file, registry, entity, variables)? else {
+        bail!("{} does not extend a base spec", file.display());
+    };
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&composed)?);
+        return Ok(());
+    }
+    if diff {
+        print_overrides(file, &composed, out);
+        return Ok(());
+    }
+    match output {
+        Some(path) => {
+            fs::write(path, &composed.yaml)
+                .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            out.info(format!(
+                "{} Composed {} over {} to {}",
+                out.ok_mark(),
+                file.display(),
+                composed.extends,
+                path.display()
+            ));
+        }
+        None => print!("{}", composed.yaml),
+    }
+    Ok(())
+}
+
+fn print_overrides(file: &Path, composed: &Composed, out: &Output) {
+    let version = match &composed.version {
+        Some(version) => format!(" ({}, {})", version.identity_version, version.hash),
+        None => String::new(),
+    };
+    out.info(format!(
+        "{} {} extends {}{}\n",
+        "Composing:".bold(),
+        file.display(),
+        composed.extends,
+        version
+    ));
+    if composed.overrides.is_empty() {
+        out.info("No changes to the base.");
+        return;
+    }
+    for change in &composed.overrides {
+        let line = match change.change.as_str() {
+            "added" => format!("  {} {}", "+".green(), change.path),
+            "removed" => format!("  {} {}", "-".red(), change.path),
+            _ => match (&change.base, &change.child) {
+                (Some(base), Some(child)) if is_scalar(base) && is_scalar(child) => format!(
+                    "  {} {}: {} {} {}",
+                    "~".yellow(),
+                    change.path,
+                    base,
+                    out.arrow(),
+                    child
+                ),
+                _ => format!("  {} {}", "~".yellow(), change.path),
+            },
+        };
+        out.result(line);
+    }
+    out.detail(format!(
+        "{} change(s) to the base",
+        composed.overrides.len()
+    ));
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !value.is_object() && !value.is_array()
+}
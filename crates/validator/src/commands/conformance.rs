@@ -0,0 +1,300 @@
+//! Cross-platform determinism checks against a fixture corpus.
+//!
+//! Each case is a directory holding `spec.yaml` and `expected.json` with the
+//! spec hash, compiled IR and plan. A case with `records.json` (records per
+//! source, as `execute` reads them) also fixes the match and cluster
+//! decisions the spec makes on them. Outputs are compared byte-for-byte in
+//! their canonical JSON form, so a case that passes on one OS/architecture
+//! (or binding) and fails on another points at a real divergence.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commands::compile::compile_to_ir;
+use crate::commands::hash::compute_hash;
+use crate::commands::plan::generate_plan;
+use crate::embedding::HttpEmbedder;
+use crate::interpreter::execute_plan_with;
+use crate::ir::Ir;
+use crate::output::Output;
+use crate::parser;
+
+/// Surfaces compared for every case, in `expected.json` key order.
+pub const SURFACES: [&str; 3] = ["hash", "ir", "plan"];
+
+/// Surface of the cases with records: the pairs' scores and decisions and
+/// the clusters they form.
+pub const DECISIONS: &str = "decisions";
+
+/// The date decision cases are run as of, so retention never depends on
+/// the day the corpus is checked.
+pub const AS_OF: &str = "2026-01-01";
+
+// ── Types ──────────────────────────────────────────────────────────
+
+#[derive(Debug, Serialize)]
+pub struct CaseResult {
+    pub name: String,
+    /// SHA-256 over the canonical outputs of every surface; equal
+    /// fingerprints on two machines mean identical outputs.
+    pub fingerprint: String,
+    pub mismatches: Vec<Mismatch>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Mismatch {
+    pub surface: String,
+    /// JSON pointer to the first differing value.
+    pub pointer: String,
+    pub expected: Value,
+    pub actual: Value,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+// ── CLI entry point ────────────────────────────────────────────────
+
+pub fn run(corpus: &Path, update: bool, format: &str, out: &Output) -> Result<()> {
+    let cases = case_dirs(corpus)?;
+    out.detail(format!("{} case(s) in {}", cases.len(), corpus.display()));
+
+    if update {
+        for dir in &cases {
+            let actual = case_outputs(dir)?;
+            fs::write(
+                dir.join("expected.json"),
+                serde_json::to_string_pretty(&actual)? + "\n",
+            )?;
+            out.info(format!("{} updated {}", out.ok_mark(), case_name(dir)));
+        }
+        return Ok(());
+    }
+
+    let results = cases
+        .iter()
+        .map(|dir| check_case(dir))
+        .collect::<Result<Vec<_>>>()?;
+    let failed = results.iter().filter(|r| !r.passed()).count();
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&json!({
+            "platform": platform(),
+            "passed": failed == 0,
+            "cases": results,
+        }))?);
+    } else {
+        out.detail(format!("Platform: {}", platform()));
+        for result in &results {
+            if result.passed() {
+                out.info(format!(
+                    "{} {} {}",
+                    out.ok_mark(),
+                    result.name,
+                    &result.fingerprint[..19]
+                ));
+                continue;
+            }
+            out.error(format!("{} {}", out.fail_mark(), result.name));
+            for m in &result.mismatches {
+                out.error(format!(
+                    "  {} {} differs at {}: expected {}, got {}",
+                    out.arrow(),
+                    m.surface,
+                    if m.pointer.is_empty() {
+                        "/"
+                    } else {
+                        &m.pointer
+                    },
+                    m.expected,
+                    m.actual
+                ));
+            }
+        }
+        out.info(format!(
+            "{} of {} case(s) conform",
+            results.len() - failed,
+            results.len()
+        ));
+    }
+
+    if failed > 0 {
+        bail!("{} conformance case(s) failed", failed);
+    }
+    Ok(())
+}
+
+// ── Core logic ─────────────────────────────────────────────────────
+
+/// Hash, IR and plan for a spec, keyed by surface name.
+pub fn conformance_outputs(yaml: &str) -> Result<Value> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    Ok(json!({
+        "hash": compute_hash(&spec)?,
+        "ir": compile_to_ir(&spec)?,
+        "plan": serde_json::to_value(generate_plan(yaml)?)?,
+    }))
+}
+
+/// The decisions surface: what the spec decides for each candidate pair of
+/// `records` and the clusters it forms, as of `AS_OF`.
+pub fn decision_outputs(yaml: &str, records: &Value) -> Result<Value> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let result = execute_plan_with(&ir, records, &HttpEmbedder::new()?, None, AS_OF)?;
+    Ok(json!({
+        "decisions": result.decisions,
+        "clusters": result.clusters,
+    }))
+}
+
+/// Run one corpus case against its `expected.json`.
+pub fn check_case(dir: &Path) -> Result<CaseResult> {
+    let actual = case_outputs(dir)?;
+    let expected_path = dir.join("expected.json");
+    let expected: Value = serde_json::from_str(
+        &fs::read_to_string(&expected_path)
+            .with_context(|| format!("Failed to read {}", expected_path.display()))?,
+    )
+    .with_context(|| format!("Malformed {}", expected_path.display()))?;
+
+    let mut mismatches = Vec::new();
+    for surface in surfaces(&actual) {
+        let (Some(want), Some(got)) = (expected.get(surface), actual.get(surface)) else {
+            bail!("{}: missing '{}' output", expected_path.display(), surface);
+        };
+        if canonical(want)? != canonical(got)? {
+            let (pointer, want, got) = first_difference(want, got, String::new());
+            mismatches.push(Mismatch {
+                surface: surface.to_string(),
+                pointer,
+                expected: want.clone(),
+                actual: got.clone(),
+            });
+        }
+    }
+
+    Ok(CaseResult {
+        name: case_name(dir),
+        fingerprint: fingerprint(&actual)?,
+        mismatches,
+    })
+}
+
+/// Case directories (those containing `spec.yaml`), sorted by name.
+pub fn case_dirs(corpus: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(corpus)
+        .with_context(|| format!("Failed to read corpus: {}", corpus.display()))?
+    {
+        let path = entry?.path();
+        if path.join("spec.yaml").is_file() {
+            dirs.push(path);
+        }
+    }
+    if dirs.is_empty() {
+        bail!("No conformance cases found in {}", corpus.display());
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn case_outputs(dir: &Path) -> Result<Value> {
+    let spec_path = dir.join("spec.yaml");
+    let yaml = fs::read_to_string(&spec_path)
+        .with_context(|| format!("Failed to read {}", spec_path.display()))?;
+    let mut outputs =
+        conformance_outputs(&yaml).with_context(|| format!("Case '{}'", case_name(dir)))?;
+    let records_path = dir.join("records.json");
+    if records_path.is_file() {
+        let records: Value = serde_json::from_str(
+            &fs::read_to_string(&records_path)
+                .with_context(|| format!("Failed to read {}", records_path.display()))?,
+        )
+        .with_context(|| format!("Malformed {}", records_path.display()))?;
+        outputs[DECISIONS] = decision_outputs(&yaml, &records)
+            .with_context(|| format!("Case '{}'", case_name(dir)))?;
+    }
+    Ok(outputs)
+}
+
+/// The surfaces a case's outputs cover: `SURFACES`, and `DECISIONS` if
+/// the case has records.
+fn surfaces(outputs: &Value) -> Vec<&'static str> {
+    let mut surfaces = SURFACES.to_vec();
+    if outputs.get(DECISIONS).is_some() {
+        surfaces.push(DECISIONS);
+    }
+    surfaces
+}
+
+fn case_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| dir.display().to_string())
+}
+
+fn canonical(value: &Value) -> Result<String> {
+    Ok(serde_json::to_string(value)?)
+}
+
+fn fingerprint(outputs: &Value) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for surface in surfaces(outputs) {
+        hasher.update(surface.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(canonical(&outputs[surface])?.as_bytes());
+        hasher.update(b"\n");
+    }
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
+
+fn first_difference<'a>(
+    expected: &'a Value,
+    actual: &'a Value,
+    pointer: String,
+) -> (String, &'a Value, &'a Value) {
+    match (expected, actual) {
+        (Value::Object(a), Value::Object(b)) => {
+            for key in a.keys().chain(b.keys()) {
+                let (x, y) = (a.get(key), b.get(key));
+                if x != y {
+                    let pointer = format!("{}/{}", pointer, key);
+                    return match (x, y) {
+                        (Some(x), Some(y)) => first_difference(x, y, pointer),
+                        _ => (
+                            pointer,
+                            x.unwrap_or(&Value::Null),
+                            y.unwrap_or(&Value::Null),
+                        ),
+                    };
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                if x != y {
+                    return first_difference(x, y, format!("{}/{}", pointer, i));
+                }
+            }
+        }
+        _ => {}
+    }
+    (pointer, expected, actual)
+}
+
+fn platform() -> String {
+    format!(
+        "{}-{} (kanoniv {})",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        env!("CARGO_PKG_VERSION")
+    )
+}
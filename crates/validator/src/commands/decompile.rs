@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::commands::compile::compile_to_ir;
+use crate::format::format_annotated;
+use crate::ir::{self, Ir, IrVersion};
+use crate::output::Output;
+use crate::parser;
+
+/// IR keys compiled from others, which compiling the spec restores.
+const DERIVED: &[&str] = &["rule_count", "blocking_strategy"];
+
+/// A spec reconstructed from compiled IR.
+#[derive(Debug, Serialize)]
+pub struct Decompiled {
+    /// The spec, with what it could not carry over in comments.
+    pub yaml: String,
+    /// What the spec could not carry over.
+    pub notes: Vec<String>,
+    /// Whether compiling `yaml` gives the IR's plan hash.
+    pub round_trips: bool,
+}
+
+/// Reconstruct a spec from compiled IR. Compilation keeps only what plans
+/// and engines read, so comments, descriptions, owners, waivers and
+/// signatures are gone, and mappings, bases and templates come back
+/// resolved; anything else the spec cannot carry is noted in comments.
+pub fn decompile_ir(value: &Value) -> Result<Decompiled> {
+    let ir = Ir::from_value(value)?;
+    let spec = ir.to_spec();
+    let Value::Object(root) = &spec else {
+        unreachable!("to_spec builds a mapping");
+    };
+
+    // Notes go above the node they concern, or else in the header.
+    let mut notes = Vec::new();
+    let mut header = Vec::new();
+    let mut annotations: HashMap<String, Vec<String>> = HashMap::new();
+    if root.contains_key("entity") {
+        annotations.insert(
+            "entity".to_string(),
+            vec!["# Only the entity's name is compiled.".to_string()],
+        );
+    }
+    if !ir::plan_hash_matches(value) {
+        notes.push(
+            "plan_hash does not match the IR's contents; it was edited after compiling".to_string(),
+        );
+        header.extend(notes.last().cloned());
+    }
+    // Keys the IR types do not hold, from hand edits or other tools, are lost.
+    let read = ir::compatibility(value, &serde_json::to_value(&ir)?)?;
+    for change in read
+        .changes
+        .iter()
+        .filter(|c| c.description == "no longer used" && !DERIVED.contains(&c.path.as_str()))
+    {
+        let note = format!(
+            "{} is not read by this kanoniv and is left out",
+            change.path
+        );
+        let section = change.path.split(['.', '[']).next().unwrap_or_default();
+        match root.contains_key(section) {
+            true => annotations
+                .entry(section.to_string())
+                .or_default()
+                .push(format!("# {}", note)),
+            false => header.push(note.clone()),
+        }
+        notes.push(note);
+    }
+    let body = format_annotated(root, annotations);
+
+    let recompiled = compile_to_ir(&parser::parse_yaml(&body)?)?;
+    let hash = recompiled["plan_hash"].as_str().unwrap_or_default();
+    let round_trips = hash == ir.plan_hash;
+    if !round_trips {
+        notes.push(format!(
+            "Compiling this spec gives plan hash {}, not the IR's {}",
+            hash, ir.plan_hash
+        ));
+        header.extend(notes.last().cloned());
+    }
+
+    let mut yaml = format!(
+        "# Decompiled from IR {} (plan hash {}).\n",
+        IrVersion::of(value)?,
+        ir.plan_hash
+    );
+    yaml.push_str(
+        "# Comments, descriptions, owners, waivers and signatures are not compiled;\n\
+         # mappings, bases and templates appear resolved.\n",
+    );
+    for note in &header {
+        yaml.push_str(&format!("# {}\n", note));
+    }
+    yaml.push_str(&body);
+    Ok(Decompiled {
+        yaml,
+        notes,
+        round_trips,
+    })
+}
+
+/// Write the spec decompiled from the IR file `file` to `output` (or stdout).
+pub fn run(file: &Path, output: Option<&Path>, out: &Output) -> Result<()> {
+    let decompiled = decompile_ir(&ir::load_value(file)?)?;
+    for note in &decompiled.notes {
+        out.warn(format!("{} {}", out.warn_mark(), note));
+    }
+    match output {
+        Some(path) => {
+            fs::write(path, &decompiled.yaml)
+                .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            out.info(format!("{} Wrote {}", out.ok_mark(), path.display()));
+        }
+        None => print!("{}", decompiled.yaml),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{EXECUTION, MAPPINGS, MINIMAL, TYPED};
+
+    #[test]
+    fn test_decompiled_ir_compiles_to_the_same_plan() {
+        use crate::{compile_to_ir, decompile_ir, parse_yaml};
+
+        for yaml in [MINIMAL, TYPED, MAPPINGS, EXECUTION] {
+            let ir = compile_to_ir(&parse_yaml(yaml).unwrap()).unwrap();
+            let decompiled = decompile_ir(&ir).unwrap();
+            assert!(
+                decompiled.round_trips && decompiled.notes.is_empty(),
+                "{:?}",
+                decompiled.notes
+            );
+            let recompiled = compile_to_ir(&parse_yaml(&decompiled.yaml).unwrap()).unwrap();
+            assert_eq!(recompiled["plan_hash"], ir["plan_hash"]);
+            assert!(decompiled
+                .yaml
+                .starts_with("# Decompiled from IR 1.0 (plan hash sha256:"));
+            assert!(decompiled
+                .yaml
+                .contains("# Only the entity's name is compiled.\nentity:\n  name: customer\n"));
+        }
+        // Mappings come back resolved into each source's attributes.
+        let decompiled =
+            decompile_ir(&compile_to_ir(&parse_yaml(MAPPINGS).unwrap()).unwrap()).unwrap();
+        assert!(!decompiled.yaml.contains("mappings:"));
+        assert!(decompiled.yaml.contains("      email: email_addr\n"));
+
+        // Keys this version does not read are dropped, and said to be.
+        let mut edited = compile_to_ir(&parse_yaml(EXECUTION).unwrap()).unwrap();
+        edited["blocking"]["future_key"] = serde_json::json!(true);
+        edited["future_section"] = serde_json::json!({ "enabled": true });
+        let decompiled = decompile_ir(&edited).unwrap();
+        assert!(decompiled.round_trips);
+        assert_eq!(
+            decompiled.notes,
+            [
+                "plan_hash does not match the IR's contents; it was edited after compiling",
+                "blocking.future_key is not read by this kanoniv and is left out",
+                "future_section is not read by this kanoniv and is left out",
+            ]
+        );
+        assert!(decompiled.yaml.contains(
+            "# blocking.future_key is not read by this kanoniv and is left out\nblocking:\n"
+        ));
+        assert!(!decompiled.yaml.contains("future_section:"));
+    }
+}
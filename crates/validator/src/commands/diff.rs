@@ -1,15 +1,31 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Result};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use serde_json::Value;
+use std::collections::BTreeMap;
 use std::path::Path;
 
+use crate::attributes::{column, declared_type, AttributeType};
+use crate::canonical::canonical_form;
+use crate::commands::plan::{print_routing, write_routing};
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::mappings;
+use crate::output::Output;
+use crate::owners::{Owners, RoutedFinding, Routing};
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct DiffResult {
     pub rules_added: Vec<String>,
     pub rules_removed: Vec<String>,
     pub rules_modified: Vec<RuleChange>,
     pub thresholds_changed: bool,
+    /// Every change classified by impact, with a recommended version bump.
+    #[serde(default)]
+    pub compatibility: Compatibility,
+    /// Changes by responsible owner, when either spec declares owners.
+    #[serde(default, skip_serializing_if = "Routing::is_empty")]
+    pub routing: Routing,
     pub summary: String,
 }
 
@@ -21,23 +37,99 @@ pub struct RuleChange {
     pub new_value: String,
 }
 
-pub fn run(file1: &Path, file2: &Path) -> Result<()> {
+/// How a change affects resolved identities, least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Impact {
+    /// Only makes merging more conservative, or changes labels.
+    Safe,
+    /// Can merge records that were previously kept apart, or change golden
+    /// records; review before deploying.
+    Risky,
+    /// Invalidates existing identities (entity renamed, source re-keyed or
+    /// removed); downstream consumers must migrate.
+    Breaking,
+}
+
+impl Impact {
+    /// Semantic version component to bump.
+    pub fn bump(self) -> &'static str {
+        match self {
+            Impact::Safe => "patch",
+            Impact::Risky => "minor",
+            Impact::Breaking => "major",
+        }
+    }
+}
+
+impl std::fmt::Display for Impact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Impact::Safe => "safe",
+            Impact::Risky => "risky",
+            Impact::Breaking => "breaking",
+        })
+    }
+}
+
+impl std::str::FromStr for Impact {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "safe" => Ok(Impact::Safe),
+            "risky" => Ok(Impact::Risky),
+            "breaking" => Ok(Impact::Breaking),
+            other => bail!("Unknown impact '{}' (expected safe, risky or breaking)", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassifiedChange {
+    /// Spec path of the change (`rules.email_exact.threshold`).
+    pub path: String,
+    pub impact: Impact,
+    pub description: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Compatibility {
+    /// The most severe change; `None` when the specs are equivalent.
+    pub impact: Option<Impact>,
+    /// `major`, `minor`, `patch` or `none`.
+    pub bump: String,
+    /// The old `identity_version` with `bump` applied, when it ends in a
+    /// version number (`retail_v1.0` -> `retail_v2.0`).
+    pub suggested_version: Option<String>,
+    pub changes: Vec<ClassifiedChange>,
+}
+
+pub fn run(
+    file1: &Path,
+    file2: &Path,
+    routing: Option<&Path>,
+    fail_on: Option<Impact>,
+    entity: Option<&str>,
+    variables: &Variables,
+    out: &Output,
+) -> Result<()> {
     // Read files
-    let content1 = fs::read_to_string(file1)
-        .with_context(|| format!("Failed to read file: {}", file1.display()))?;
-    let content2 = fs::read_to_string(file2)
-        .with_context(|| format!("Failed to read file: {}", file2.display()))?;
+    let content1 = compose::read_spec(file1, entity, variables)?;
+    let content2 = compose::read_spec(file2, entity, variables)?;
 
     let diff = compute_diff(&content1, &content2)?;
+    if let Some(path) = routing {
+        write_routing(path, &diff.routing, out)?;
+    }
 
     // Print readable output (preserving CLI behavior)
-    println!(
-        "{} {} vs {}",
+    out.info(format!(
+        "{} {} vs {}\n",
         "Comparing:".bold(),
         file1.display(),
         file2.display()
-    );
-    println!();
+    ));
 
     if !diff.rules_added.is_empty() {
         println!("{}:", "Rules Added".green());
@@ -62,19 +154,56 @@ pub fn run(file1: &Path, file2: &Path) -> Result<()> {
 
     if diff.thresholds_changed {
         println!("{}:", "Thresholds".cyan());
-        println!("  {} Thresholds have changed.", "⚠".yellow());
+        println!("  {} Thresholds have changed.", out.warn_mark());
     }
 
-    if diff.rules_added.is_empty() && diff.rules_removed.is_empty() && diff.rules_modified.is_empty() && !diff.thresholds_changed {
-        println!("No significant changes detected.");
+    let compat = &diff.compatibility;
+    if diff.rules_added.is_empty() && diff.rules_removed.is_empty() && diff.rules_modified.is_empty() && !diff.thresholds_changed && compat.changes.is_empty() {
+        out.info("No significant changes detected.");
+    }
+
+    if let Some(impact) = compat.impact {
+        out.info(format!("{}:", "Compatibility".bold()));
+        for change in &compat.changes {
+            let label = match change.impact {
+                Impact::Breaking => change.impact.to_string().red().bold().to_string(),
+                Impact::Risky => change.impact.to_string().yellow().to_string(),
+                Impact::Safe => change.impact.to_string().green().to_string(),
+            };
+            out.info(out.wrap_block(&format!("  [{}] {}", label, change.description)));
+        }
+        let suggestion = match &compat.suggested_version {
+            Some(version) => format!(" {} {}", out.arrow(), version),
+            None => String::new(),
+        };
+        out.info(format!(
+            "  Overall: {}, recommended bump: {}{}",
+            impact, compat.bump, suggestion
+        ));
+    }
+
+    print_routing(&diff.routing, out);
+
+    out.detail(&diff.summary);
+
+    if let (Some(threshold), Some(impact)) = (fail_on, compat.impact) {
+        if impact >= threshold {
+            bail!("Diff contains {} changes (--fail-on {})", impact, threshold);
+        }
     }
 
     Ok(())
 }
 
 pub fn compute_diff(content1: &str, content2: &str) -> Result<DiffResult> {
-    let spec1: serde_json::Value = serde_yaml::from_str(content1)?;
-    let spec2: serde_json::Value = serde_yaml::from_str(content2)?;
+    // Compare canonical forms so style-only edits (`1` vs `1.0`, padded
+    // strings, reordered rules) are not reported as changes.
+    let raw1: serde_json::Value = serde_yaml::from_str(content1)?;
+    let raw2: serde_json::Value = serde_yaml::from_str(content2)?;
+    // Sources are compared in canonical attribute space, so listing
+    // columns through `mappings` instead of mapping them is no change.
+    let spec1 = mappings::resolve(&canonical_form(&raw1));
+    let spec2 = mappings::resolve(&canonical_form(&raw2));
 
     let mut diff = DiffResult::default();
 
@@ -125,6 +254,18 @@ pub fn compute_diff(content1: &str, content2: &str) -> Result<DiffResult> {
         diff.thresholds_changed = true;
     }
 
+    diff.compatibility = classify(&spec1, &spec2);
+
+    // Ownership is stripped from the canonical form; read it from the new
+    // version, or the old one if the new one declares none.
+    let owners = match Owners::from_spec(&raw2) {
+        owners if owners.is_empty() => Owners::from_spec(&raw1),
+        owners => owners,
+    };
+    if !owners.is_empty() {
+        diff.routing = route_changes(&diff, &spec1, &spec2, &owners);
+    }
+
     diff.summary = format!(
         "Diff: {} added, {} removed, {} modified. Thresholds changed: {}. Version: {} -> {}",
         diff.rules_added.len(),
@@ -136,3 +277,539 @@ pub fn compute_diff(content1: &str, content2: &str) -> Result<DiffResult> {
 
     Ok(diff)
 }
+
+/// Attribute each change to the owners of what it touches.
+fn route_changes(
+    diff: &DiffResult,
+    spec1: &serde_json::Value,
+    spec2: &serde_json::Value,
+    owners: &Owners,
+) -> Routing {
+    let rule_field = |spec: &serde_json::Value, name: &str| {
+        spec.get("rules")
+            .and_then(|r| r.as_array())
+            .and_then(|rules| rules.iter().find(|r| r.get("name").and_then(|n| n.as_str()) == Some(name)))
+            .and_then(|r| r.get("field"))
+            .and_then(|f| f.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let change = |code: &str, section: &str, message: String| RoutedFinding {
+        kind: "change".to_string(),
+        code: code.to_string(),
+        section: section.to_string(),
+        message,
+    };
+
+    let mut findings = Vec::new();
+    for name in &diff.rules_added {
+        findings.push((
+            owners.for_rule_field(&rule_field(spec2, name)),
+            change("RULE_ADDED", "rules", format!("Rule '{}' added", name)),
+        ));
+    }
+    for name in &diff.rules_removed {
+        findings.push((
+            owners.for_rule_field(&rule_field(spec1, name)),
+            change("RULE_REMOVED", "rules", format!("Rule '{}' removed", name)),
+        ));
+    }
+    for m in &diff.rules_modified {
+        findings.push((
+            owners.for_rule_field(&rule_field(spec2, &m.name)),
+            change(
+                "RULE_MODIFIED",
+                "rules",
+                format!("Rule '{}' {} changed from {} to {}", m.name, m.field, m.old_value, m.new_value),
+            ),
+        ));
+    }
+    if diff.thresholds_changed {
+        findings.push((
+            owners.for_section("decision"),
+            change("THRESHOLDS_CHANGED", "decision", "Decision thresholds changed".to_string()),
+        ));
+    }
+    owners.route(findings)
+}
+
+// ── Compatibility ──────────────────────────────────────────────────
+
+/// Classify every difference between two canonical specs.
+fn classify(old: &Value, new: &Value) -> Compatibility {
+    let mut changes = Changes::default();
+
+    if old.get("api_version") != new.get("api_version") {
+        changes.push(
+            "api_version",
+            Impact::Breaking,
+            format!(
+                "api_version changed from {} to {}",
+                show(old.get("api_version")),
+                show(new.get("api_version"))
+            ),
+        );
+    }
+    let entity = |spec: &Value| spec.get("entity").and_then(|e| e.as_object()).cloned().unwrap_or_default();
+    let (before, after) = (entity(old), entity(new));
+    let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys.into_iter().filter(|k| before.get(*k) != after.get(*k)) {
+        changes.push(
+            &format!("entity.{}", key),
+            Impact::Breaking,
+            format!(
+                "entity.{} changed from {} to {}",
+                key,
+                show(before.get(key)),
+                show(after.get(key))
+            ),
+        );
+    }
+    if old.get("identity_version") != new.get("identity_version") {
+        changes.push(
+            "identity_version",
+            Impact::Safe,
+            format!(
+                "identity_version changed from {} to {}",
+                show(old.get("identity_version")),
+                show(new.get("identity_version"))
+            ),
+        );
+    }
+
+    compare_sources(old, new, &mut changes);
+    compare_rules(old, new, &mut changes);
+    compare_blocking(old, new, &mut changes);
+    compare_thresholds(old, new, &mut changes);
+    compare_clustering(old, new, &mut changes);
+
+    let covered = [
+        "api_version",
+        "identity_version",
+        "entity",
+        "sources",
+        "rules",
+        "blocking",
+        "decision",
+        "clustering",
+    ];
+    let keys = |spec: &Value| -> Vec<String> {
+        spec.as_object()
+            .map(|m| m.keys().cloned().collect())
+            .unwrap_or_default()
+    };
+    let mut others: Vec<String> = keys(old).into_iter().chain(keys(new)).collect();
+    others.sort();
+    others.dedup();
+    for key in others.iter().filter(|k| !covered.contains(&k.as_str())) {
+        if old.get(key) != new.get(key) {
+            changes.push(key, Impact::Risky, format!("{} changed", key));
+        }
+    }
+    // Decision settings other than thresholds.
+    let without_thresholds = |spec: &Value| {
+        let mut decision = spec.get("decision").cloned().unwrap_or(Value::Null);
+        if let Value::Object(map) = &mut decision {
+            map.remove("thresholds");
+        }
+        decision
+    };
+    if without_thresholds(old) != without_thresholds(new) {
+        changes.push("decision", Impact::Risky, "decision settings changed".to_string());
+    }
+
+    let changes = changes.0;
+    let impact = changes.iter().map(|c| c.impact).max();
+    let bump = impact.map(Impact::bump).unwrap_or("none");
+    let suggested_version = impact.and_then(|_| {
+        old.get("identity_version")
+            .and_then(|v| v.as_str())
+            .and_then(|v| bump_version(v, bump))
+    });
+    Compatibility {
+        impact,
+        bump: bump.to_string(),
+        suggested_version,
+        changes,
+    }
+}
+
+#[derive(Default)]
+struct Changes(Vec<ClassifiedChange>);
+
+impl Changes {
+    fn push(&mut self, path: &str, impact: Impact, description: String) {
+        self.0.push(ClassifiedChange {
+            path: path.to_string(),
+            impact,
+            description,
+        });
+    }
+}
+
+fn compare_sources(old: &Value, new: &Value, changes: &mut Changes) {
+    let (old, new) = (by_name(old.get("sources")), by_name(new.get("sources")));
+    for (name, source) in &old {
+        let path = format!("sources.{}", name);
+        let Some(updated) = new.get(name) else {
+            changes.push(
+                &path,
+                Impact::Breaking,
+                format!("Source '{}' removed; its records lose their identities", name),
+            );
+            continue;
+        };
+        for key in ["id", "table", "system"] {
+            if source.get(key) != updated.get(key) {
+                changes.push(
+                    &format!("{}.{}", path, key),
+                    Impact::Breaking,
+                    format!(
+                        "Source '{}' {} changed from {} to {}",
+                        name,
+                        key,
+                        show(source.get(key)),
+                        show(updated.get(key))
+                    ),
+                );
+            }
+        }
+        let attributes = |s: &Value| s.get("attributes").cloned().unwrap_or(Value::Null);
+        let (before, after) = (attributes(source), attributes(updated));
+        for (attribute, mapping) in before.as_object().into_iter().flatten() {
+            let attr_path = format!("{}.attributes.{}", path, attribute);
+            let Some(updated) = after.get(attribute) else {
+                changes.push(
+                    &attr_path,
+                    Impact::Risky,
+                    format!("Source '{}' no longer maps attribute '{}'", name, attribute),
+                );
+                continue;
+            };
+            let (was, now) = (column(attribute, mapping), column(attribute, updated));
+            if was != now {
+                changes.push(
+                    &attr_path,
+                    Impact::Risky,
+                    format!(
+                        "Source '{}' attribute '{}' remapped from '{}' to '{}'",
+                        name, attribute, was, now
+                    ),
+                );
+            }
+            // Declaring a type only adds checks; changing one changes them.
+            let (was, now) = (declared_type(mapping), declared_type(updated));
+            if was != now {
+                let shown = |t: Option<AttributeType>| t.map_or("(none)", |t| t.name());
+                changes.push(
+                    &format!("{}.type", attr_path),
+                    if was.is_none() { Impact::Safe } else { Impact::Risky },
+                    format!(
+                        "Source '{}' attribute '{}' type changed from {} to {}",
+                        name,
+                        attribute,
+                        shown(was),
+                        shown(now)
+                    ),
+                );
+            }
+        }
+        for attribute in after.as_object().into_iter().flatten().map(|(k, _)| k) {
+            if before.get(attribute).is_none() {
+                changes.push(
+                    &format!("{}.attributes.{}", path, attribute),
+                    Impact::Safe,
+                    format!("Source '{}' maps new attribute '{}'", name, attribute),
+                );
+            }
+        }
+    }
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        changes.push(
+            &format!("sources.{}", name),
+            Impact::Risky,
+            format!("Source '{}' added; its records can merge into existing identities", name),
+        );
+    }
+}
+
+fn compare_rules(old: &Value, new: &Value, changes: &mut Changes) {
+    let (old, new) = (by_name(old.get("rules")), by_name(new.get("rules")));
+    for (name, rule) in &old {
+        let path = format!("rules.{}", name);
+        let Some(updated) = new.get(name) else {
+            changes.push(&path, Impact::Risky, format!("Rule '{}' removed", name));
+            continue;
+        };
+        let mut keys: Vec<&String> = rule
+            .as_object()
+            .into_iter()
+            .chain(updated.as_object())
+            .flat_map(|m| m.keys())
+            .collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            let (before, after) = (rule.get(key), updated.get(key));
+            if before == after {
+                continue;
+            }
+            let numbers = (before.and_then(Value::as_f64), after.and_then(Value::as_f64));
+            // A higher threshold or lower weight only makes matching stricter.
+            let impact = match (key.as_str(), numbers) {
+                ("threshold", (Some(b), Some(a))) if a > b => Impact::Safe,
+                ("weight", (Some(b), Some(a))) if a < b => Impact::Safe,
+                _ => Impact::Risky,
+            };
+            changes.push(
+                &format!("{}.{}", path, key),
+                impact,
+                format!(
+                    "Rule '{}' {} changed from {} to {}",
+                    name,
+                    key,
+                    show(before),
+                    show(after)
+                ),
+            );
+        }
+    }
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        changes.push(
+            &format!("rules.{}", name),
+            Impact::Risky,
+            format!("Rule '{}' added", name),
+        );
+    }
+}
+
+fn compare_blocking(old: &Value, new: &Value, changes: &mut Changes) {
+    let strategy = |spec: &Value| spec.get("blocking").and_then(|b| b.get("strategy")).cloned();
+    if strategy(old) != strategy(new) {
+        changes.push(
+            "blocking.strategy",
+            Impact::Risky,
+            format!(
+                "Blocking strategy changed from {} to {}",
+                show(strategy(old).as_ref()),
+                show(strategy(new).as_ref())
+            ),
+        );
+    }
+    let strategy_params: [(&str, &str, &[&str]); 3] = [
+        ("lsh", "LSH", &["method", "bands", "rows", "shingle_size"]),
+        ("sorted_neighborhood", "Sorted neighbourhood", &["sort_key", "transform", "window"]),
+        ("canopy", "Canopy", &["field", "algorithm", "loose", "tight"]),
+    ];
+    for (section, label, params) in strategy_params {
+        let param_of = |spec: &Value, param: &str| {
+            spec.get("blocking")
+                .and_then(|b| b.get(section))
+                .and_then(|s| s.get(param))
+                .cloned()
+        };
+        for param in params {
+            let (before, after) = (param_of(old, param), param_of(new, param));
+            if before != after {
+                changes.push(
+                    &format!("blocking.{}.{}", section, param),
+                    Impact::Risky,
+                    format!(
+                        "{} {} changed from {} to {}; candidate pairs change",
+                        label,
+                        param,
+                        show(before.as_ref()),
+                        show(after.as_ref())
+                    ),
+                );
+            }
+        }
+    }
+    let keys = |spec: &Value| -> Vec<Value> {
+        spec.get("blocking")
+            .and_then(|b| b.get("keys"))
+            .and_then(|k| k.as_array())
+            .cloned()
+            .unwrap_or_default()
+    };
+    let (before, after) = (keys(old), keys(new));
+    for key in before.iter().filter(|k| !after.contains(k)) {
+        changes.push(
+            "blocking.keys",
+            Impact::Risky,
+            format!("Blocking key {} removed; candidate pairs change", show(Some(key))),
+        );
+    }
+    for key in after.iter().filter(|k| !before.contains(k)) {
+        changes.push(
+            "blocking.keys",
+            Impact::Safe,
+            format!("Blocking key {} added", show(Some(key))),
+        );
+    }
+}
+
+fn compare_thresholds(old: &Value, new: &Value, changes: &mut Changes) {
+    let thresholds = |spec: &Value| {
+        spec.get("decision")
+            .and_then(|d| d.get("thresholds"))
+            .and_then(|t| t.as_object())
+            .cloned()
+            .unwrap_or_default()
+    };
+    let (before, after) = (thresholds(old), thresholds(new));
+    let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let (b, a) = (before.get(name), after.get(name));
+        if b == a {
+            continue;
+        }
+        // Raising any band boundary only demands more evidence.
+        let impact = match (b.and_then(Value::as_f64), a.and_then(Value::as_f64)) {
+            (Some(b), Some(a)) if a > b => Impact::Safe,
+            _ => Impact::Risky,
+        };
+        changes.push(
+            &format!("decision.thresholds.{}", name),
+            impact,
+            format!("Threshold '{}' changed from {} to {}", name, show(b), show(a)),
+        );
+    }
+}
+
+fn compare_clustering(old: &Value, new: &Value, changes: &mut Changes) {
+    let param = |spec: &Value, key: &str| spec.get("clustering").and_then(|c| c.get(key)).cloned();
+    let (before, after) = (param(old, "strategy"), param(new, "strategy"));
+    if before != after {
+        changes.push(
+            "clustering.strategy",
+            Impact::Risky,
+            format!(
+                "Clustering strategy changed from {} to {}; entities are regrouped",
+                show(before.as_ref()),
+                show(after.as_ref())
+            ),
+        );
+    }
+    let (before, after) = (param(old, "threshold"), param(new, "threshold"));
+    if before != after {
+        // A higher cut only merges less.
+        let impact = match (before.as_ref().and_then(Value::as_f64), after.as_ref().and_then(Value::as_f64)) {
+            (Some(b), Some(a)) if a > b => Impact::Safe,
+            _ => Impact::Risky,
+        };
+        changes.push(
+            "clustering.threshold",
+            impact,
+            format!(
+                "Clustering threshold changed from {} to {}",
+                show(before.as_ref()),
+                show(after.as_ref())
+            ),
+        );
+    }
+}
+
+/// Named entries of a list (or name-keyed mapping) section.
+fn by_name(section: Option<&Value>) -> BTreeMap<String, Value> {
+    match section {
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| {
+                let name = item.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+                (name.to_string(), item.clone())
+            })
+            .collect(),
+        Some(Value::Object(map)) => map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+fn show(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "(none)".to_string(),
+        Some(Value::String(s)) => format!("'{}'", s),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Apply `bump` to the version number ending `version`.
+fn bump_version(version: &str, bump: &str) -> Option<String> {
+    let mut start = version
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_digit() || *c == '.')
+        .last()
+        .map(|(i, _)| i)?;
+    while version[start..].starts_with('.') {
+        start += 1;
+    }
+    let (prefix, number) = version.split_at(start);
+    let mut parts: Vec<u64> = number
+        .split('.')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let index = match bump {
+        "major" => 0,
+        "minor" => 1,
+        "patch" => 2,
+        _ => return None,
+    };
+    parts.resize(parts.len().max(index + 1), 0);
+    parts[index] += 1;
+    for part in &mut parts[index + 1..] {
+        *part = 0;
+    }
+    let numbers: Vec<String> = parts.iter().map(u64::to_string).collect();
+    Some(format!("{}{}", prefix, numbers.join(".")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_diff_classifies_compatibility() {
+        use crate::Impact;
+
+        let compat = |new: &str| crate::compute_diff(MINIMAL, new).unwrap().compatibility;
+        let unchanged = compat(MINIMAL);
+        assert_eq!((unchanged.impact, unchanged.bump.as_str()), (None, "none"));
+        assert!(unchanged.changes.is_empty());
+
+        let stricter = compat(
+            &MINIMAL
+                .replace("match: 0.9", "match: 0.95")
+                .replace("weight: 1.0", "weight: 0.8"),
+        );
+        assert_eq!(stricter.impact, Some(Impact::Safe));
+        assert_eq!(stricter.bump, "patch");
+        assert_eq!(stricter.suggested_version.as_deref(), Some("retail_v1.0.1"));
+
+        let looser = compat(&MINIMAL.replace("match: 0.9", "match: 0.7"));
+        assert_eq!(looser.impact, Some(Impact::Risky));
+        assert_eq!(looser.suggested_version.as_deref(), Some("retail_v1.1"));
+        assert_eq!(looser.changes[0].path, "decision.thresholds.match");
+
+        let blocked = MINIMAL.to_string() + "blocking:\n  keys:\n    - field: email\n";
+        let unblocked = crate::compute_diff(&blocked, MINIMAL)
+            .unwrap()
+            .compatibility;
+        assert_eq!(unblocked.impact, Some(Impact::Risky));
+        assert_eq!(unblocked.changes[0].path, "blocking.keys");
+
+        let renamed = compat(
+            &MINIMAL
+                .replace("name: customer", "name: person")
+                .replace("id: contact_id", "id: sfid"),
+        );
+        assert_eq!(renamed.impact, Some(Impact::Breaking));
+        assert_eq!(renamed.suggested_version.as_deref(), Some("retail_v2.0"));
+        let paths: Vec<&str> = renamed.changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, ["entity.name", "sources.crm.id"]);
+    }
+}
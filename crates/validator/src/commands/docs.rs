@@ -0,0 +1,352 @@
+//! `kanoniv docs`: Markdown documentation of a spec, to commit next to it.
+//!
+//! The page is rendered from the compiled IR, so it describes what runs:
+//! canonical attributes, defaults filled in. It carries no timestamp, so it
+//! only changes when the spec does.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::commands::compile::compile_to_ir;
+use crate::commands::plan::{self, generate_plan_from_ir, PlanOptions};
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::ir::{Ir, IrSource};
+use crate::output::Output;
+use crate::owners::Owners;
+use crate::parser;
+
+pub fn run(
+    file: &Path,
+    output: Option<&Path>,
+    entity: Option<&str>,
+    variables: &Variables,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let docs = generate_docs(&content)?;
+    match output {
+        Some(path) => {
+            fs::write(path, &docs)
+                .with_context(|| format!("Failed to write docs: {}", path.display()))?;
+            out.info(format!("{} Wrote docs: {}", out.ok_mark(), path.display()));
+        }
+        None => out.result(docs.trim_end()),
+    }
+    Ok(())
+}
+
+/// Markdown documentation of the spec in `yaml`.
+pub fn generate_docs(yaml: &str) -> Result<String> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML for docs")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let owners = Owners::from_spec(&spec);
+    let entity = ir.entity.as_deref().unwrap_or("entity");
+
+    let mut md = String::new();
+    md.push_str("<!-- Generated by `kanoniv docs`; edit the spec and regenerate. -->\n\n");
+    md.push_str(&format!("# {}\n\n", entity));
+    md.push_str(&overview(&ir));
+    md.push_str(&sources(&ir, &owners));
+    md.push_str(&rules(&ir));
+    md.push_str(&blocking(&ir)?);
+    md.push_str(&survivorship(&ir));
+    md.push_str(&decision(&ir));
+    if !ir.relationships.is_empty() {
+        md.push_str("## Relationships\n\n");
+        let rows = ir.relationships.iter().map(|relationship| {
+            vec![
+                relationship.name.clone(),
+                relationship.to.clone(),
+                relationship.cardinality.name().to_string(),
+                relationship.description(entity),
+            ]
+        });
+        md.push_str(&table(
+            &["Relationship", "To", "Cardinality", "Resolved by"],
+            rows,
+        ));
+    }
+    Ok(md)
+}
+
+fn overview(ir: &Ir) -> String {
+    let mut facts = Vec::new();
+    if let Some(version) = &ir.identity_version {
+        facts.push(format!("Identity version: `{}`", version));
+    }
+    if let Some(api) = &ir.api_version {
+        facts.push(format!("API version: `{}`", api));
+    }
+    facts.push(format!("Plan hash: `{}`", ir.plan_hash));
+    let names: Vec<&str> = ir.sources.iter().map(|s| s.name.as_str()).collect();
+    facts.push(format!("Sources: {} ({})", names.len(), names.join(", ")));
+    facts.push(format!("Match rules: {}", ir.rules.len()));
+    facts.push(format!(
+        "Clustering: {}",
+        ir.clustering.unwrap_or_default().description()
+    ));
+    if let Some(execution) = ir.execution.as_ref().filter(|e| e.mode.is_incremental()) {
+        facts.push(format!(
+            "Execution: {}. {}",
+            execution.mode.name(),
+            execution.delta_description()
+        ));
+    }
+    if let Some(temporal) = &ir.temporal {
+        let history = temporal.history_attributes();
+        if !history.is_empty() {
+            facts.push(format!("History kept for: {}", history.join(", ")));
+        }
+    }
+    let mut md = String::from("## Overview\n\n");
+    for fact in facts {
+        md.push_str(&format!("- {}\n", fact));
+    }
+    md.push('\n');
+    md
+}
+
+fn sources(ir: &Ir, owners: &Owners) -> String {
+    let mut md = String::from("## Sources\n\n");
+    let normalization = ir.normalization.as_ref();
+    for source in &ir.sources {
+        md.push_str(&format!("### {}\n\n", source.name));
+        md.push_str(&format!("{}\n\n", source_facts(source, owners).join(" · ")));
+        let rows = source.attributes.iter().map(|(attribute, column)| {
+            let steps = normalization
+                .and_then(|n| n.fields.get(attribute))
+                .map(|steps| {
+                    steps
+                        .iter()
+                        .map(plan::describe_step)
+                        .collect::<Vec<_>>()
+                        .join(" → ")
+                })
+                .unwrap_or_default();
+            vec![
+                attribute.clone(),
+                format!("`{}`", column),
+                source.types.get(attribute).cloned().unwrap_or_default(),
+                steps,
+            ]
+        });
+        md.push_str(&table(
+            &["Attribute", "Column", "Type", "Normalization"],
+            rows,
+        ));
+    }
+    md
+}
+
+fn source_facts(source: &IrSource, owners: &Owners) -> Vec<String> {
+    let mut facts = Vec::new();
+    if let Some(system) = &source.system {
+        facts.push(format!("System: {}", system));
+    }
+    if let Some(table) = &source.table {
+        facts.push(format!("Table: `{}`", table));
+    }
+    if let Some(id) = &source.id {
+        facts.push(format!("Key: `{}`", id));
+    }
+    let stewards = owners.for_source(&source.name);
+    if !stewards.is_empty() {
+        facts.push(format!("Owner: {}", stewards.join(", ")));
+    }
+    facts
+}
+
+fn rules(ir: &Ir) -> String {
+    let mut md = String::from("## Match Rules\n\nEvaluated in order.\n\n");
+    let rows = ir.rules.iter().enumerate().map(|(i, rule)| {
+        let method = match (&rule.algorithm, &rule.model) {
+            (_, Some(model)) => model.clone(),
+            (Some(algorithm), None) => algorithm.clone(),
+            (None, None) => String::new(),
+        };
+        vec![
+            (i + 1).to_string(),
+            rule.name.clone(),
+            rule.match_type.clone(),
+            rule.field.clone().unwrap_or_default(),
+            method,
+            rule.threshold.map_or(String::new(), |t| t.to_string()),
+            rule.weight.to_string(),
+            rule.condition
+                .as_ref()
+                .map_or(String::new(), |c| format!("`{}`", c)),
+        ]
+    });
+    md.push_str(&table(
+        &[
+            "#",
+            "Rule",
+            "Type",
+            "Field",
+            "Algorithm",
+            "Threshold",
+            "Weight",
+            "Compares",
+        ],
+        rows,
+    ));
+    md
+}
+
+fn blocking(ir: &Ir) -> Result<String> {
+    let plan = generate_plan_from_ir(ir, &PlanOptions::default())?;
+    let blocking = &plan.blocking_analysis;
+    let mut md = format!("## Blocking\n\nStrategy: `{}`", blocking.strategy);
+    if let Some(params) = plan::strategy_params(blocking) {
+        md.push_str(&format!(" ({})", params));
+    }
+    md.push_str(&format!(
+        ". Estimated pair reduction: {}.\n\n",
+        blocking.estimated_reduction
+    ));
+    if !blocking.keys.is_empty() {
+        let rows = blocking
+            .keys
+            .iter()
+            .map(|key| vec![key.name.clone(), key.transformation.clone()]);
+        md.push_str(&table(&["Key", "Transform"], rows));
+    }
+    Ok(md)
+}
+
+/// Attributes by survivorship strategy, with each source's rank in the
+/// priority (or whether it provides the attribute at all).
+fn survivorship(ir: &Ir) -> String {
+    let mut md = String::from("## Survivorship\n\n");
+    if ir.survivorship.is_empty() {
+        md.push_str("No survivorship rules.\n\n");
+        return md;
+    }
+    let mut headings = vec!["Attribute", "Strategy"];
+    headings.extend(ir.sources.iter().map(|s| s.name.as_str()));
+    headings.push("Details");
+    let rows = ir.survivorship.iter().map(|rule| {
+        let mut row = vec![rule.field.clone(), rule.strategy.clone()];
+        for source in &ir.sources {
+            let rank = rule
+                .source_priority
+                .iter()
+                .flatten()
+                .position(|name| *name == source.name);
+            row.push(match rank {
+                Some(rank) => (rank + 1).to_string(),
+                None if source.attributes.contains_key(&rule.field) => "✓".to_string(),
+                None => String::new(),
+            });
+        }
+        let mut details = Vec::new();
+        if let Some(timestamp) = &rule.timestamp {
+            details.push(format!("latest by `{}`", timestamp));
+        }
+        if let Some(fields) = &rule.fields {
+            details.push(format!("first non-null of {}", fields.join(", ")));
+        }
+        if let Some(expression) = &rule.expression {
+            details.push(format!("highest `{}`", expression));
+        }
+        if let Some(condition) = &rule.condition {
+            details.push(format!("preferring `{}`", condition));
+        }
+        row.push(details.join("; "));
+        row
+    });
+    md.push_str(&table(&headings, rows));
+    md.push_str(
+        "Numbers are a source's rank in the priority; ✓ marks sources providing the attribute.\n\n",
+    );
+    md
+}
+
+fn decision(ir: &Ir) -> String {
+    let mut md = String::from("## Decision Thresholds\n\n");
+    let Some(thresholds) = &ir.thresholds else {
+        md.push_str("No thresholds.\n\n");
+        return md;
+    };
+    let rows = [
+        (
+            "Match",
+            thresholds.match_,
+            "Pairs scoring at least this are merged",
+        ),
+        (
+            "Review",
+            thresholds.review,
+            "Pairs scoring at least this, below match, are reviewed",
+        ),
+        (
+            "Reject",
+            thresholds.reject,
+            "Pairs scoring below this are kept apart",
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(decision, score, outcome)| {
+        score.map(|score| vec![decision.to_string(), score.to_string(), outcome.to_string()])
+    });
+    md.push_str(&table(&["Decision", "Score", "Outcome"], rows));
+    md
+}
+
+/// A Markdown table; `|` in cells is escaped.
+fn table(headings: &[&str], rows: impl Iterator<Item = Vec<String>>) -> String {
+    let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+    let mut md = format!(
+        "| {} |\n",
+        headings
+            .iter()
+            .map(|h| cell(h))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    );
+    md.push_str(&format!("|{}\n", "---|".repeat(headings.len())));
+    for row in rows {
+        md.push_str(&format!(
+            "| {} |\n",
+            row.iter().map(|c| cell(c)).collect::<Vec<_>>().join(" | ")
+        ));
+    }
+    md.push('\n');
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_docs_render_survivorship_matrix() {
+        use crate::generate_docs;
+
+        let spec = MINIMAL
+        .replace(
+            "      email: email\n",
+            "      email: email\n      name: full_name\n  - name: billing\n    table: customers\n    id: id\n    attributes:\n      email: email\n",
+        )
+        .replace("    weight: 1.0\n", "    weight: 1.0\n    condition: \"a.email != 'x|y'\"\n")
+        + "survivorship:\n  rules:\n    - field: email\n      strategy: source_priority\n      source_priority: [billing, crm]\n    - field: name\n      strategy: most_recent\n      timestamp: updated_at\n";
+        let docs = generate_docs(&spec).unwrap();
+        assert!(docs.starts_with("<!-- Generated by `kanoniv docs`"));
+        assert!(docs.contains("# customer\n"));
+        assert!(docs.contains("- Sources: 2 (crm, billing)\n"));
+        assert!(docs.contains("| Attribute | Strategy | crm | billing | Details |\n"));
+        assert!(docs.contains("| email | source_priority | 2 | 1 |  |\n"));
+        assert!(docs.contains("| name | most_recent | ✓ |  | latest by `updated_at` |\n"));
+        assert!(
+            docs.contains("`a.email != 'x\\|y'`"),
+            "pipes in cells are escaped"
+        );
+        assert!(docs.contains("| Match | 0.9 | Pairs scoring at least this are merged |\n"));
+        assert!(!docs.contains("## Relationships"));
+
+        // Regenerating an unchanged spec gives the same page.
+        assert_eq!(generate_docs(&spec).unwrap(), docs);
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+use crate::cancel::CancellationToken;
+use crate::clock;
+use crate::commands::compile::compile_to_ir;
+use crate::compose;
+use crate::embedding::HttpEmbedder;
+use crate::evaluation::{evaluate_with, records_by_source};
+use crate::interpolate::Variables;
+use crate::ir::Ir;
+use crate::output::Output;
+use crate::parser;
+use crate::sample::Sample;
+
+/// Run the spec over the records in `data` and score its clusters against
+/// the ground truth in `truth`.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: &Path,
+    truth: &Path,
+    data: &Path,
+    cancel: Option<&CancellationToken>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    // Records by source as JSON, or stacked with a source_name column.
+    let records = match data.extension().is_some_and(|ext| ext == "json") {
+        true => {
+            let text = fs::read_to_string(data)
+                .with_context(|| format!("Failed to read records: {}", data.display()))?;
+            serde_json::from_str(&text)
+                .with_context(|| format!("Invalid JSON in {}", data.display()))?
+        }
+        false => records_by_source(&Sample::load(data)?)?,
+    };
+    let report = evaluate_with(
+        &ir,
+        &records,
+        &Sample::load(truth)?,
+        &HttpEmbedder::new()?,
+        cancel,
+        &clock::today(),
+    )?;
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    for warning in &report.warnings {
+        out.warn(format!("{} {}", out.warn_mark(), warning));
+    }
+    let pairwise = &report.pairwise;
+    let clusters = &report.clusters;
+    out.result(format!(
+        "{} {} records: {} true entities {} {} predicted",
+        "Evaluated:".bold(),
+        report.records,
+        report.true_entities,
+        out.arrow(),
+        report.predicted_entities
+    ));
+    out.result(format!(
+        "  Pairwise: precision {:.3}, recall {:.3}, F1 {:.3} ({} TP, {} FP, {} FN)",
+        pairwise.precision,
+        pairwise.recall,
+        pairwise.f1,
+        pairwise.true_positives,
+        pairwise.false_positives,
+        pairwise.false_negatives
+    ));
+    out.result(format!(
+        "  Blocking compared {:.1}% of true pairs",
+        pairwise.candidate_recall * 100.0
+    ));
+    out.result(format!(
+        "  Clusters: ARI {:.3}, B-cubed precision {:.3}, recall {:.3}, F1 {:.3}; {} exact",
+        clusters.adjusted_rand_index,
+        clusters.b_cubed_precision,
+        clusters.b_cubed_recall,
+        clusters.b_cubed_f1,
+        clusters.exact_clusters
+    ));
+    if !report.rules.is_empty() {
+        out.result(format!("  {}", "Rules:".bold()));
+    }
+    let mean = |value: Option<f64>| value.map_or_else(|| "-".to_string(), |v| format!("{:.3}", v));
+    for rule in &report.rules {
+        let without = match rule.f1_without {
+            Some(f1) => format!("; F1 without it {:.3} ({:+.3})", f1, f1 - pairwise.f1),
+            None => String::new(),
+        };
+        out.result(format!(
+            "    {} ({}): mean similarity {} on matches, {} on non-matches{}",
+            rule.rule_name,
+            rule.field,
+            mean(rule.mean_on_matches),
+            mean(rule.mean_on_non_matches),
+            without
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+use crate::cancel::CancellationToken;
+use crate::clock;
+use crate::commands::compile::compile_to_ir;
+use crate::commands::golden_records::golden_csv;
+use crate::commands::maintain;
+use crate::compose;
+#[cfg(feature = "distributed")]
+use crate::distributed::{Coordinator, TlsIdentity};
+use crate::embedded::ExecutionBackend;
+use crate::embedding::HttpEmbedder;
+use crate::interpolate::Variables;
+use crate::interpreter::{sources_json, split_sources};
+use crate::ir::{self, Ir};
+use crate::output::Output;
+use crate::parser;
+use crate::sample::Sample;
+
+/// Without the `distributed` feature there is no coordinator to pass.
+#[cfg(not(feature = "distributed"))]
+pub type Coordinator = std::convert::Infallible;
+
+/// Listen on `address` for the workers of a distributed run, serving TLS
+/// with the PEM certificate chain `tls_cert` and key `tls_key`.
+#[cfg(feature = "distributed")]
+pub fn bind(
+    address: &str,
+    partitions: usize,
+    secret: &str,
+    tls_cert: &Path,
+    tls_key: &Path,
+) -> Result<Coordinator> {
+    Coordinator::bind(
+        address,
+        partitions,
+        secret,
+        &TlsIdentity::load(tls_cert, tls_key)?,
+    )
+}
+
+#[cfg(not(feature = "distributed"))]
+pub fn bind(
+    _address: &str,
+    _partitions: usize,
+    _secret: &str,
+    _tls_cert: &Path,
+    _tls_key: &Path,
+) -> Result<Coordinator> {
+    anyhow::bail!("kanoniv was built without the `distributed` feature")
+}
+
+/// Run a spec's plan (or a compiled IR file's) over the records in
+/// `records` on `backend` as of `as_of` (today if `None`), and write the
+/// canonical entities to `output` (or stdout, with the summary on stderr).
+/// With `coordinator`, workers that connect to it score the candidate
+/// pairs.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: Option<&Path>,
+    from_ir: Option<&Path>,
+    records: &Path,
+    output: Option<&Path>,
+    as_of: Option<&str>,
+    backend: ExecutionBackend,
+    coordinator: Option<&Coordinator>,
+    cancel: Option<&CancellationToken>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let ir = match (file, from_ir) {
+        (_, Some(path)) => {
+            let value = ir::load_value(path)?;
+            if !ir::plan_hash_matches(&value) {
+                out.warn(format!(
+                    "{} {}: plan_hash does not match the IR's contents; it was edited after compiling",
+                    out.warn_mark(),
+                    path.display()
+                ));
+            }
+            Ir::from_value(&value)?
+        }
+        (Some(file), None) => {
+            let content = compose::read_spec(file, entity, variables)?;
+            let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
+            Ir::from_value(&compile_to_ir(&spec)?)?
+        }
+        (None, None) => unreachable!("clap requires FILE or --from-ir"),
+    };
+    let as_of = as_of.map_or_else(clock::today, str::to_string);
+    let embedder = HttpEmbedder::new()?;
+    #[cfg(feature = "distributed")]
+    if let Some(coordinator) = coordinator {
+        out.warn(format!(
+            "Waiting for workers: kanoniv worker {}",
+            coordinator.local_addr()?
+        ));
+    }
+    // Records by source as JSON, or stacked with a source_name column.
+    let sources = match records.extension().is_some_and(|ext| ext == "json") {
+        true => {
+            let text = fs::read_to_string(records)
+                .with_context(|| format!("Failed to read records: {}", records.display()))?;
+            let records_json: serde_json::Value = serde_json::from_str(&text)
+                .with_context(|| format!("Invalid JSON in {}", records.display()))?;
+            sources_json(&records_json)?
+        }
+        false => split_sources(&Sample::load(records)?)?,
+    };
+    let (result, linked_by) = match coordinator {
+        #[cfg(feature = "distributed")]
+        Some(coordinator) => {
+            let (result, dispatched) =
+                coordinator.execute_sources(&ir, &sources, cancel, &as_of)?;
+            let linked_by = format!(
+                "  clustered in {} partition(s) by {} worker(s)",
+                dispatched.partitions, dispatched.workers
+            );
+            (result, Some(linked_by))
+        }
+        #[cfg(not(feature = "distributed"))]
+        Some(never) => match *never {},
+        None => (
+            backend.execute_sources(&ir, &sources, &embedder, cancel, &as_of)?,
+            None,
+        ),
+    };
+    let csv = golden_csv(&result.golden)?;
+    if let Some(path) = output {
+        fs::write(path, &csv)
+            .with_context(|| format!("Failed to write file: {}", path.display()))?;
+    }
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    // The entities may go to stdout; the summary must not mix with it.
+    let note = |msg: String| {
+        if output.is_some() {
+            out.info(msg)
+        } else {
+            out.warn(msg)
+        }
+    };
+    for warning in &result.warnings {
+        out.warn(format!("{} {}", out.warn_mark(), warning));
+    }
+    let count = |decision: &str| {
+        result
+            .decisions
+            .iter()
+            .filter(|d| d.decision == decision)
+            .count()
+    };
+    note(format!(
+        "{} {} records {} {} candidate pairs {} {} entities",
+        "Executed:".bold(),
+        result.records,
+        out.arrow(),
+        result.candidate_pairs,
+        out.arrow(),
+        result.golden.records.len()
+    ));
+    note(format!(
+        "  {} match, {} review, {} reject",
+        count("match"),
+        count("review"),
+        count("reject")
+    ));
+    if let Some(linked_by) = linked_by {
+        note(linked_by);
+    }
+    if result.deleted > 0 {
+        note(format!("  {} deleted record(s) dropped", result.deleted));
+    }
+    if let Some(summary) = result.golden.retention.as_ref().and_then(maintain::summary) {
+        note(summary);
+    }
+    match output {
+        Some(path) => out.info(format!("{} Wrote {}", out.ok_mark(), path.display())),
+        None => print!("{}", csv),
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use anyhow::{bail, Result};
+use colored::Colorize;
+
+use crate::diagnostics::codes;
+use crate::output::Output;
+
+pub fn run(code: Option<&str>, out: &Output) -> Result<()> {
+    let Some(code) = code else {
+        for info in codes::ALL {
+            out.result(format!("{}  {:<24} {}", info.code, info.name, info.title));
+        }
+        return Ok(());
+    };
+
+    let Some(info) = codes::lookup(code) else {
+        bail!(
+            "Unknown diagnostic code '{}'. Run `kanoniv explain` to list all codes.",
+            code
+        );
+    };
+
+    out.result(format!("{} ({})", info.code.bold(), info.name));
+    out.result(info.title);
+    out.result("");
+    out.result(info.explanation);
+    Ok(())
+}
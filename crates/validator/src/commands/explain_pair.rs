@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::compose;
+use crate::explanation::{explain_pair, PairExplanation};
+use crate::interpolate::Variables;
+use crate::output::Output;
+
+/// Run the spec's blocking keys and match rules on the records in `a` and
+/// `b` and break down the decision.
+pub fn run(
+    file: &Path,
+    a: &Path,
+    b: &Path,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let explanation = explain_pair(&content, &read_record(a)?, &read_record(b)?)?;
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&explanation)?);
+        return Ok(());
+    }
+    print_explanation(&explanation, out);
+    Ok(())
+}
+
+/// The blocking keys, rule table, score and decision of `explanation`.
+pub fn print_explanation(explanation: &PairExplanation, out: &Output) {
+    let shown = |value: &Option<String>| value.as_deref().unwrap_or(out.dash()).to_string();
+    out.result(format!("{}", "Blocking:".bold()));
+    if explanation.blocking.is_empty() {
+        out.result(format!("  {} no keys; every pair is compared", out.dash()));
+    }
+    for key in &explanation.blocking {
+        let mark = if key.agrees {
+            out.ok_mark()
+        } else {
+            out.warn_mark()
+        };
+        let transform = key
+            .transform
+            .as_ref()
+            .map(|t| format!(" ({})", t))
+            .unwrap_or_default();
+        out.result(format!(
+            "  {} {}{}: {} / {}",
+            mark,
+            key.field,
+            transform,
+            shown(&key.left),
+            shown(&key.right)
+        ));
+    }
+
+    out.result("");
+    out.result(format!(
+        "  {:<20}  {:<8}  {:>5}  {:>9}  {:>6}  {:>12}  VALUES",
+        "RULE", "TYPE", "SCORE", "THRESHOLD", "WEIGHT", "CONTRIBUTION"
+    ));
+    for rule in &explanation.rules {
+        let score = rule.score.map_or("-".to_string(), |s| format!("{:.3}", s));
+        let values = match &rule.note {
+            Some(note) => note.dimmed().to_string(),
+            None => format!("{} / {}", shown(&rule.left), shown(&rule.right)),
+        };
+        out.result(format!(
+            "  {:<20}  {:<8}  {:>5}  {:>9}  {:>6}  {:>12}  {}",
+            rule.rule_name,
+            rule.match_type,
+            score,
+            rule.threshold.map_or("-".to_string(), |t| t.to_string()),
+            rule.weight,
+            format!("{:.3}", rule.contribution),
+            values
+        ));
+    }
+
+    out.result("");
+    out.result(format!("{} {:.3}", "Score:".bold(), explanation.score));
+    out.result(format!(
+        "{} {} ({})",
+        "Decision:".bold(),
+        explanation.decision,
+        explanation.reason
+    ));
+}
+
+fn read_record(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Invalid JSON {}", path.display()))
+}
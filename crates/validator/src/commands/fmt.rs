@@ -0,0 +1,40 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::format::format_spec;
+use crate::output::Output;
+
+/// Rewrite each file in canonical form, or with `check`, only report the
+/// files that are not and fail.
+pub fn run(files: &[PathBuf], check: bool, out: &Output) -> Result<()> {
+    let mut unformatted = 0;
+    for file in files {
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read file: {}", file.display()))?;
+        let formatted = format_spec(&content)
+            .with_context(|| format!("Failed to format {}", file.display()))?;
+        if formatted == content {
+            out.detail(format!("{} {} is formatted", out.ok_mark(), file.display()));
+            continue;
+        }
+
+        unformatted += 1;
+        if check {
+            out.error(format!(
+                "{} {} is not formatted",
+                out.fail_mark(),
+                file.display()
+            ));
+        } else {
+            fs::write(file, &formatted)
+                .with_context(|| format!("Failed to write file: {}", file.display()))?;
+            out.info(format!("{} Formatted {}", out.ok_mark(), file.display()));
+        }
+    }
+
+    if check && unformatted > 0 {
+        bail!("{} file(s) need formatting; run `kanoniv fmt`", unformatted);
+    }
+    Ok(())
+}
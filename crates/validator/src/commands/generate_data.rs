@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+use crate::commands::compile::compile_to_ir;
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::ir::Ir;
+use crate::output::Output;
+use crate::parser;
+use crate::synthetic::{generate_data, sample_csv, GenerateOptions};
+
+/// Make up records for the spec's sources and write them to `output`: a
+/// CSV per source, all of them as `records.json` (for `kanoniv execute`)
+/// and each record's true cluster in `labels.csv`.
+pub fn run(
+    file: &Path,
+    options: &GenerateOptions,
+    output: &Path,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let data = generate_data(&ir, options)?;
+
+    fs::create_dir_all(output)
+        .with_context(|| format!("Failed to create directory: {}", output.display()))?;
+    let write = |name: &str, contents: String| {
+        let path = output.join(name);
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write file: {}", path.display()))?;
+        Ok::<_, anyhow::Error>(path)
+    };
+    let mut files = Vec::new();
+    for (name, sample) in &data.sources {
+        files.push(write(&format!("{}.csv", name), sample_csv(sample)?)?);
+    }
+    let records = write(
+        "records.json",
+        serde_json::to_string_pretty(&data.records_json())? + "\n",
+    )?;
+    let labels = write("labels.csv", data.labels_csv()?)?;
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&json!({
+            "records": data.labels.len(),
+            "entities": data.entities,
+            "duplicates": data.duplicates,
+            "seed": options.seed,
+            "sources": data
+                .sources
+                .iter()
+                .zip(&files)
+                .map(|((name, sample), path)| json!({ "source": name, "records": sample.len(), "file": path }))
+                .collect::<Vec<_>>(),
+            "records_json": records,
+            "labels": labels,
+        }))?);
+        return Ok(());
+    }
+
+    out.info(format!(
+        "{} {} records of {} entities ({} duplicates, seed {})",
+        "Generated:".bold(),
+        data.labels.len(),
+        data.entities,
+        data.duplicates,
+        options.seed
+    ));
+    for ((name, sample), path) in data.sources.iter().zip(&files) {
+        out.info(format!(
+            "  {}: {} records {} {}",
+            name,
+            sample.len(),
+            out.arrow(),
+            path.display()
+        ));
+    }
+    out.info(format!(
+        "{} Wrote {} and {}",
+        out.ok_mark(),
+        records.display(),
+        labels.display()
+    ));
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+use crate::audit::to_json_lines;
+use crate::clock;
+use crate::commands::maintain;
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::openlineage::{self, Dataset};
+use crate::output::Output;
+use crate::sample::Sample;
+use crate::stewardship::Stewardship;
+use crate::survivorship::{golden_records_with, GoldenRecords};
+
+/// Build golden records for the clusters in `members` under the spec's
+/// survivorship rules, with values expired as of `as_of` (today if `None`),
+/// and write them to `output` (or stdout, with the summary on stderr).
+/// Fields locked by stewardship assertions, from `stewardship` or the
+/// spec's sidecar file, keep their locked values.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: &Path,
+    members: &Path,
+    as_of: Option<&str>,
+    output: Option<&Path>,
+    audit: Option<&Path>,
+    lineage: Option<&Path>,
+    stewardship: Option<&Path>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let stewardship = Stewardship::discover(file, stewardship)?;
+    let as_of = as_of.map_or_else(clock::today, str::to_string);
+    let outputs = [output, audit, lineage]
+        .into_iter()
+        .flatten()
+        .map(Dataset::file);
+    let warn = |msg: String| out.warn(format!("{} {}", out.warn_mark(), msg));
+    let (golden, csv) = openlineage::observe(
+        &content,
+        vec![Dataset::file(members)],
+        outputs.collect(),
+        warn,
+        || {
+            let golden = golden_records_with(&content, &Sample::load(members)?, &stewardship, &as_of)?;
+            let csv = golden_csv(&golden)?;
+            if let Some(path) = output {
+                fs::write(path, &csv)
+                    .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            }
+            if let Some(path) = audit {
+                fs::write(path, to_json_lines(&golden.audit)?)
+                    .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            }
+            if let Some(path) = lineage {
+                if path.extension().is_some_and(|ext| ext == "parquet") {
+                    golden.lineage.write_parquet(path)?;
+                } else {
+                    fs::write(path, serde_json::to_string_pretty(&golden.lineage)?)
+                        .with_context(|| format!("Failed to write file: {}", path.display()))?;
+                }
+            }
+            Ok((golden, csv))
+        },
+    )?;
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&golden)?);
+        return Ok(());
+    }
+
+    // The records may go to stdout; the summary must not mix with it.
+    let note = |msg: String| {
+        if output.is_some() {
+            out.info(msg)
+        } else {
+            out.warn(msg)
+        }
+    };
+    note(format!(
+        "{} {} members {} {} golden records",
+        "Golden records:".bold(),
+        golden.members,
+        out.arrow(),
+        golden.records.len()
+    ));
+    for field in &golden.fields {
+        note(format!("  {} ({})", field.field, field.strategy));
+    }
+    if golden.locked > 0 {
+        note(format!(
+            "  {} field(s) locked by stewardship",
+            golden.locked
+        ));
+    }
+    if golden.deleted > 0 {
+        note(format!("  {} deleted member(s) dropped", golden.deleted));
+    }
+    if let Some(summary) = golden.retention.as_ref().and_then(maintain::summary) {
+        note(summary);
+    }
+
+    if let Some(path) = audit {
+        note(format!(
+            "{} Wrote audit trail: {}",
+            out.ok_mark(),
+            path.display()
+        ));
+    }
+    if let Some(path) = lineage {
+        note(format!(
+            "{} Wrote lineage: {}",
+            out.ok_mark(),
+            path.display()
+        ));
+    }
+    match output {
+        Some(path) => out.info(format!("{} Wrote {}", out.ok_mark(), path.display())),
+        None => print!("{}", csv),
+    }
+    Ok(())
+}
+
+pub(crate) fn golden_csv(golden: &GoldenRecords) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let mut header = vec!["entity_id"];
+    header.extend(golden.fields.iter().map(|f| f.field.as_str()));
+    writer.write_record(&header)?;
+    for record in &golden.records {
+        let mut row = vec![record.entity_id.as_str()];
+        row.extend(
+            record
+                .values
+                .iter()
+                .map(|v| v.as_deref().unwrap_or_default()),
+        );
+        writer.write_record(&row)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
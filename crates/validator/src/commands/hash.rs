@@ -1,26 +1,35 @@
 use anyhow::{Context, Result};
-use sha2::{Digest, Sha256};
-use std::fs;
 use std::path::Path;
 
-pub fn run(file: &Path) -> Result<()> {
+use crate::canonical::{canonical_hash, canonical_hash_with};
+use crate::compose;
+use crate::hashing::{HashAlgorithm, Hasher, KeySource};
+use crate::interpolate::Variables;
+use crate::output::Output;
+
+pub fn run(
+    file: &Path,
+    algorithm: &str,
+    key: &KeySource,
+    entity: Option<&str>,
+    variables: &Variables,
+    out: &Output,
+) -> Result<()> {
+    let hasher = Hasher::from_source(algorithm.parse::<HashAlgorithm>()?, key)?;
+
     // Read file
-    let content = fs::read_to_string(file)
-        .with_context(|| format!("Failed to read file: {}", file.display()))?;
+    let content = compose::read_spec(file, entity, variables)?;
 
     // Parse YAML to JSON for canonical representation
     let spec: serde_json::Value =
         serde_yaml::from_str(&content).with_context(|| "Failed to parse YAML")?;
 
-    // Convert to canonical JSON (sorted keys, no whitespace variation)
-    let canonical = serde_json::to_string(&spec)?;
-
-    // Compute SHA-256 hash
-    let mut hasher = Sha256::new();
-    hasher.update(canonical.as_bytes());
-    let hash = format!("sha256:{:x}", hasher.finalize());
-
-    println!("{}", hash);
+    out.result(canonical_hash_with(&spec, &hasher));
 
     Ok(())
 }
+
+/// SHA-256 over the spec's canonical form; see `canonical_hash`.
+pub fn compute_hash(spec: &serde_json::Value) -> Result<String> {
+    Ok(canonical_hash(spec))
+}
@@ -0,0 +1,124 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::output::Output;
+use crate::scaffold::Starter;
+use crate::templates;
+
+/// Write a starter spec to `output`, asking on stdin for the entity,
+/// sources and matching fields not given as flags. With `template`, the
+/// built-in blueprint is written instead, with the entity and source given
+/// (or its defaults) and nothing asked.
+pub fn run(
+    output: &Path,
+    template: Option<&str>,
+    entity: Option<&str>,
+    sources: &[String],
+    fields: &[String],
+    force: bool,
+    out: &Output,
+) -> Result<()> {
+    if output.exists() && !force {
+        bail!(
+            "{} already exists; pass --force to overwrite it",
+            output.display()
+        );
+    }
+
+    let yaml = match template {
+        Some(name) => from_template(name, entity, sources)?,
+        None => interactive(entity, sources, fields)?,
+    };
+    fs::write(output, yaml)
+        .with_context(|| format!("Failed to write file: {}", output.display()))?;
+
+    out.info(format!("{} Wrote {}", out.ok_mark(), output.display()));
+    out.info(format!(
+        "Point each source at its table and key column, then run `kanoniv plan {}`",
+        output.display()
+    ));
+    Ok(())
+}
+
+fn from_template(name: &str, entity: Option<&str>, sources: &[String]) -> Result<String> {
+    let template = templates::lookup(name)?;
+    let mut values = Vec::new();
+    if let Some(entity) = entity {
+        values.push(("entity", entity.to_string()));
+    }
+    match sources {
+        [] => {}
+        [source] => {
+            let (name, system) = Starter::parse_source(source);
+            values.push(("source", name));
+            values.push(("system", system));
+        }
+        _ => bail!("A template starts from one source; add the others to the spec afterwards"),
+    }
+    let values: Vec<(&str, &str)> = values.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    template.render(&values)
+}
+
+fn interactive(entity: Option<&str>, sources: &[String], fields: &[String]) -> Result<String> {
+    let mut input = io::stdin().lock();
+    let entity = match entity {
+        Some(entity) => entity.to_string(),
+        None => ask(&mut input, "Entity name", Some("customer"))?,
+    };
+    let sources = match sources {
+        [] => split(&ask(
+            &mut input,
+            "Sources, as name or name:system (comma-separated)",
+            None,
+        )?),
+        sources => sources.to_vec(),
+    };
+    let fields = match fields {
+        [] => split(&ask(
+            &mut input,
+            "Fields to match on (comma-separated)",
+            Some("email, phone, full_name"),
+        )?),
+        fields => fields.to_vec(),
+    };
+
+    let starter = Starter {
+        entity,
+        sources: sources.iter().map(|s| Starter::parse_source(s)).collect(),
+        fields,
+    };
+    starter.render()
+}
+
+/// Prompt on stderr (stdout may be redirected) and read one answer;
+/// an empty answer takes `default`.
+fn ask(input: &mut impl BufRead, question: &str, default: Option<&str>) -> Result<String> {
+    loop {
+        match default {
+            Some(default) => eprint!("{} [{}]: ", question, default),
+            None => eprint!("{}: ", question),
+        }
+        io::stderr().flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            bail!("No answer to '{}'; pass it as a flag instead", question);
+        }
+        match (line.trim(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => continue,
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+fn split(answer: &str) -> Vec<String> {
+    answer
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,72 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::ir::{self, ir_json_schema};
+use crate::output::Output;
+
+pub fn schema(format: &str, output: Option<&Path>, out: &Output) -> Result<()> {
+    let rendered = match format {
+        "json-schema" => serde_json::to_string_pretty(&ir_json_schema())?,
+        other => bail!("Unknown schema format '{}'. Expected: json-schema", other),
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(path, rendered + "\n")
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            out.info(format!("{} Wrote {}", out.ok_mark(), path.display()));
+        }
+        None => out.result(rendered),
+    }
+    Ok(())
+}
+
+/// Compare the formats of two compiled IR files, failing if an engine that
+/// read `old` cannot read `new`.
+pub fn check(old: &Path, new: &Path, format: &str, out: &Output) -> Result<()> {
+    let compatibility = ir::compatibility(&ir::load_value(old)?, &ir::load_value(new)?)?;
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&compatibility)?);
+    } else {
+        out.info(format!(
+            "IR {} {} {}",
+            compatibility.old_version,
+            out.arrow(),
+            compatibility.new_version
+        ));
+        for change in &compatibility.changes {
+            let line = format!("{}: {}", change.path, change.description);
+            match change.breaking {
+                true => out.error(format!("{} {}", out.fail_mark(), line)),
+                false => out.info(format!("  {}", line)),
+            }
+        }
+    }
+
+    let breaking = compatibility.changes.iter().filter(|c| c.breaking).count();
+    if breaking > 0 {
+        bail!(
+            "{} is not compatible with {}: {} breaking change(s)",
+            new.display(),
+            old.display(),
+            breaking
+        );
+    }
+    if format != "json" {
+        out.info(format!(
+            "{} Engines that read {} can read {}",
+            out.ok_mark(),
+            old.display(),
+            new.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Print the digest of a compiled IR file, the same for each encoding of it.
+pub fn digest(file: &Path, out: &Output) -> Result<()> {
+    out.result(ir::ir_digest(&ir::load_value(file)?));
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+use crate::cancel::CancellationToken;
+use crate::compose;
+use crate::embedding::HttpEmbedder;
+use crate::interpolate::Variables;
+use crate::learning::learn_weights_with;
+use crate::output::Output;
+use crate::sample::Sample;
+
+/// Learn the spec's rule weights from the records in `data` and write the
+/// spec with them to `output` (or stdout, with the summary on stderr).
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: &Path,
+    data: &Path,
+    output: Option<&Path>,
+    cancel: Option<&CancellationToken>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let learned = learn_weights_with(
+        &content,
+        &Sample::load(data)?,
+        &HttpEmbedder::new()?,
+        cancel,
+    )?;
+
+    if let Some(path) = output {
+        fs::write(path, &learned.yaml)
+            .with_context(|| format!("Failed to write file: {}", path.display()))?;
+    }
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&learned)?);
+        return Ok(());
+    }
+
+    // The spec may go to stdout; the summary must not mix with it.
+    let note = |msg: String| {
+        if output.is_some() {
+            out.info(msg)
+        } else {
+            out.warn(msg)
+        }
+    };
+    note(format!(
+        "{} {} records, {} pairs, match share {} ({} iterations{})",
+        "Learned weights:".bold(),
+        learned.records,
+        learned.pairs,
+        learned.match_proportion,
+        learned.iterations,
+        if learned.converged {
+            ""
+        } else {
+            ", not converged"
+        }
+    ));
+    note(format!(
+        "  {:<20}  {:>7}  {:>6}  {:>6}  WEIGHT",
+        "RULE", "PAIRS", "M", "U"
+    ));
+    let probability = |p: Option<f64>| p.map_or("-".to_string(), |p| format!("{:.3}", p));
+    for rule in &learned.rules {
+        let weight = match rule.recommended_weight {
+            Some(w) if (w - rule.weight).abs() > 1e-9 => {
+                format!("{} {} {}", rule.weight, out.arrow(), w)
+            }
+            _ => rule.weight.to_string(),
+        };
+        note(format!(
+            "  {:<20}  {:>7}  {:>6}  {:>6}  {}",
+            rule.rule_name,
+            rule.pairs,
+            probability(rule.m_probability),
+            probability(rule.u_probability),
+            weight
+        ));
+    }
+    for warning in &learned.warnings {
+        out.warn(format!("{} {}", out.warn_mark(), warning));
+    }
+
+    match output {
+        Some(path) => out.info(format!("{} Wrote {}", out.ok_mark(), path.display())),
+        None => print!("{}", learned.yaml),
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+
+use crate::clock;
+use crate::commands::compile::compile_to_ir;
+use crate::commands::golden_records::golden_csv;
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::ir::Ir;
+use crate::output::Output;
+use crate::parser;
+use crate::privacy::Expiry;
+use crate::retention::{undated_warning, RetentionAudit};
+use crate::sample::Sample;
+use crate::stewardship::Stewardship;
+use crate::survivorship::golden_records_as_of;
+use crate::synthetic::sample_csv;
+
+/// Expire the values of the clustered `members` past the spec's retention
+/// periods as of `as_of` (today if `None`) and report each. With `output`,
+/// write the maintained members, their golden records and lineage, and the
+/// retention audit there.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: &Path,
+    members: &Path,
+    as_of: Option<&str>,
+    output: Option<&Path>,
+    stewardship: Option<&Path>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let stewardship = Stewardship::discover(file, stewardship)?;
+    let as_of = as_of.map_or_else(clock::today, str::to_string);
+
+    let mut members = Sample::load(members)?;
+    let golden = golden_records_as_of(&ir, &mut members, &stewardship, &as_of)?;
+    let Some(audit) = &golden.retention else {
+        bail!("The spec sets no retention periods: give privacy.retention_days, or retention_days per attribute");
+    };
+
+    let mut written = Vec::new();
+    if let Some(dir) = output {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+        let write = |name: &str, contents: String| {
+            let path = dir.join(name);
+            fs::write(&path, contents)
+                .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            Ok::<_, anyhow::Error>(path)
+        };
+        written.push(write("members.csv", sample_csv(&members)?)?);
+        written.push(write("golden.csv", golden_csv(&golden)?)?);
+        written.push(write(
+            "lineage.json",
+            serde_json::to_string_pretty(&golden.lineage)? + "\n",
+        )?);
+        written.push(write(
+            "retention_audit.json",
+            serde_json::to_string_pretty(audit)? + "\n",
+        )?);
+    }
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(audit)?);
+        return Ok(());
+    }
+
+    out.info(format!(
+        "{} {} expired value(s) in {} records as of {} ({})",
+        "Retention:".bold(),
+        audit.expired.len(),
+        audit.records,
+        audit.as_of,
+        audit.on_expiry
+    ));
+    for expired in &audit.expired {
+        out.detail(format!(
+            "  {} {}: collected {}, expired {} ({} days)",
+            expired.record_key,
+            expired.field,
+            expired.collected_at,
+            expired.expired_on,
+            expired.retention_days
+        ));
+    }
+    if let Some(summary) = summary(audit) {
+        out.info(summary);
+    }
+    if let Some(warning) = undated_warning(audit) {
+        out.warn(format!("{} {}", out.warn_mark(), warning));
+    }
+    for path in &written {
+        out.info(format!("{} Wrote {}", out.ok_mark(), path.display()));
+    }
+    Ok(())
+}
+
+/// What expiry did, as a line under a run's summary; `None` if nothing
+/// expired.
+pub(crate) fn summary(audit: &RetentionAudit) -> Option<String> {
+    if audit.expired.is_empty() {
+        return None;
+    }
+    Some(match audit.on_expiry {
+        Expiry::Nullify => format!(
+            "  {} value(s) past retention nullified (as of {})",
+            audit.expired.len(),
+            audit.as_of
+        ),
+        Expiry::Tombstone => format!(
+            "  {} record(s) tombstoned for {} value(s) past retention (as of {})",
+            audit.tombstoned,
+            audit.expired.len(),
+            audit.as_of
+        ),
+    })
+}
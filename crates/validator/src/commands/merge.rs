@@ -0,0 +1,43 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::merge::merge_specs;
+use crate::output::Output;
+
+/// Merge `ours` and `theirs` against their common `base`, writing the
+/// result to `output` (or stdout). Fails if any conflict remains.
+pub fn run(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    output: Option<&Path>,
+    out: &Output,
+) -> Result<()> {
+    let read = |path: &Path| {
+        fs::read_to_string(path).with_context(|| format!("Failed to read file: {}", path.display()))
+    };
+    let merged = merge_specs(&read(base)?, &read(ours)?, &read(theirs)?)?;
+
+    match output {
+        Some(path) => {
+            fs::write(path, &merged.yaml)
+                .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            if merged.is_clean() {
+                out.info(format!("{} Merged into {}", out.ok_mark(), path.display()));
+            }
+        }
+        None => print!("{}", merged.yaml),
+    }
+
+    if merged.is_clean() {
+        return Ok(());
+    }
+    for conflict in &merged.conflicts {
+        out.error(format!("{} Conflict at {}", out.fail_mark(), conflict.path));
+    }
+    bail!(
+        "{} conflict(s); resolve the markers, then run `kanoniv validate`",
+        merged.conflicts.len()
+    )
+}
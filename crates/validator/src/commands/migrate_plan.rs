@@ -0,0 +1,322 @@
+//! Operational steps to move a deployment from one spec version to another.
+//!
+//! Each step is a stage of the new version's execution plan, limited to
+//! what the diff actually invalidates: a threshold change re-decides
+//! existing scores, a rule change re-scores only that rule, a survivorship
+//! change rebuilds only the affected golden record fields. A breaking change
+//! (entity renamed, source removed or re-keyed) or a change the planner does
+//! not model (e.g. `temporal`) needs a full rebuild.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::canonical::{canonical_form, canonical_hash};
+use crate::commands::diff::{compute_diff, Impact};
+use crate::commands::plan::{generate_plan, ExecutionStage};
+use crate::output::Output;
+use crate::parser;
+
+/// Scope entry meaning a stage recomputes everything.
+pub const ALL: &str = "all";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MigrationPlan {
+    pub from_version: String,
+    pub to_version: String,
+    pub from_hash: String,
+    pub to_hash: String,
+    /// Every stage reruns over all data.
+    pub full_rebuild: bool,
+    /// Stages to run, in execution order; empty when nothing is invalidated.
+    pub steps: Vec<MigrationStep>,
+    pub summary: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MigrationStep {
+    #[serde(flatten)]
+    pub stage: ExecutionStage,
+    /// What to recompute: `all`, or the affected sources, rules or fields.
+    pub scope: Vec<String>,
+    pub reason: String,
+}
+
+pub fn run(old: &Path, new: &Path, format: &str, out: &Output) -> Result<()> {
+    let read = |path: &Path| {
+        fs::read_to_string(path).with_context(|| format!("Failed to read file: {}", path.display()))
+    };
+    let plan = generate_migration_plan(&read(old)?, &read(new)?)?;
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&plan)?);
+        return Ok(());
+    }
+
+    out.info(format!(
+        "{} {} {} {}",
+        "Migration:".bold(),
+        plan.from_version,
+        out.arrow(),
+        plan.to_version
+    ));
+    for (i, step) in plan.steps.iter().enumerate() {
+        out.result(out.wrap_block(&format!(
+            "  {}. {} [{}] {} {}",
+            i + 1,
+            step.stage.name,
+            step.scope.join(", "),
+            out.dash(),
+            step.reason
+        )));
+    }
+    out.info(out.wrap_block(&plan.summary));
+    Ok(())
+}
+
+/// Steps to migrate from `old_yaml` to `new_yaml`.
+pub fn generate_migration_plan(old_yaml: &str, new_yaml: &str) -> Result<MigrationPlan> {
+    let old =
+        canonical_form(&parser::parse_yaml(old_yaml).with_context(|| "Failed to parse old spec")?);
+    let new =
+        canonical_form(&parser::parse_yaml(new_yaml).with_context(|| "Failed to parse new spec")?);
+    let diff = compute_diff(old_yaml, new_yaml)?;
+    let stages = generate_plan(new_yaml)?.execution_stages;
+
+    let mut work = Work::default();
+    for change in &diff.compatibility.changes {
+        let mut path = change.path.split('.');
+        let section = path.next().unwrap_or_default();
+        let name = path.next().unwrap_or_default().to_string();
+        match section {
+            "identity_version" => {}
+            _ if change.impact == Impact::Breaking => work.full = true,
+            "sources" => {
+                work.sources.insert(name);
+            }
+            "blocking" => work.reblock = true,
+            "rules" => {
+                let rule_type = rule_type(&new, &name).or_else(|| rule_type(&old, &name));
+                match rule_type.as_deref() {
+                    Some("exact") => work.exact.insert(name),
+                    Some("semantic") => work.semantic.insert(name),
+                    _ => work.fuzzy.insert(name),
+                };
+            }
+            "decision" => work.redecide = true,
+            "survivorship" => work.fields.extend(survivorship_changes(&old, &new)),
+            // Sections the planner does not model.
+            _ => work.full = true,
+        }
+    }
+
+    let steps = work.steps(&stages);
+    let summary = if work.full {
+        format!(
+            "Full rebuild: all {} stages rerun over all data.",
+            steps.len()
+        )
+    } else if steps.is_empty() {
+        "No recomputation needed; existing identities remain valid.".to_string()
+    } else {
+        format!(
+            "Incremental migration: {} of {} stages, starting at '{}'.",
+            steps.len(),
+            stages.len(),
+            steps[0].stage.name
+        )
+    };
+
+    let version = |spec: &Value| {
+        spec.get("identity_version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown")
+            .to_string()
+    };
+    Ok(MigrationPlan {
+        from_version: version(&old),
+        to_version: version(&new),
+        from_hash: canonical_hash(&old),
+        to_hash: canonical_hash(&new),
+        full_rebuild: work.full,
+        steps,
+        summary,
+    })
+}
+
+/// What a diff invalidates.
+#[derive(Default)]
+struct Work {
+    full: bool,
+    /// Sources to re-normalize.
+    sources: BTreeSet<String>,
+    reblock: bool,
+    exact: BTreeSet<String>,
+    fuzzy: BTreeSet<String>,
+    semantic: BTreeSet<String>,
+    redecide: bool,
+    /// Golden record fields to rebuild (`all` for every field).
+    fields: BTreeSet<String>,
+}
+
+impl Work {
+    /// Walk the plan's stages in order. A stage reruns fully when its input
+    /// was recomputed, or partially for changes aimed at it; scores of
+    /// unchanged rules stay valid as long as the candidate pairs do.
+    fn steps(&self, stages: &[ExecutionStage]) -> Vec<MigrationStep> {
+        let all = || vec![ALL.to_string()];
+        let reblocked = self.reblock || !self.sources.is_empty();
+        let mut steps = Vec::new();
+        let mut upstream = false;
+        for stage in stages {
+            let output = stage
+                .outputs
+                .first()
+                .map(String::as_str)
+                .unwrap_or_default();
+            let (scope, reason) = if self.full {
+                (all(), "Breaking or unmodelled change".to_string())
+            } else {
+                match output {
+                    "normalized_entities" if !self.sources.is_empty() => (
+                        self.sources.iter().cloned().collect(),
+                        "Source mappings changed".to_string(),
+                    ),
+                    "candidate_pairs" if self.reblock => (all(), "Blocking changed".to_string()),
+                    "exact_match_scores" | "fuzzy_match_scores" if reblocked => {
+                        (all(), "Candidate pairs changed".to_string())
+                    }
+                    "exact_match_scores" if !self.exact.is_empty() => (
+                        self.exact.iter().cloned().collect(),
+                        "Exact rules changed".to_string(),
+                    ),
+                    "fuzzy_match_scores" if !self.fuzzy.is_empty() => (
+                        self.fuzzy.iter().cloned().collect(),
+                        "Fuzzy rules changed".to_string(),
+                    ),
+                    "exact_match_scores" | "fuzzy_match_scores" => continue,
+                    // Semantic matches search all entities, not the blocked
+                    // pairs, so only new source records invalidate them.
+                    "semantic_match_scores" if !self.sources.is_empty() => {
+                        (all(), "Source records changed".to_string())
+                    }
+                    "semantic_match_scores" if !self.semantic.is_empty() => (
+                        self.semantic.iter().cloned().collect(),
+                        "Semantic rules changed".to_string(),
+                    ),
+                    "semantic_match_scores" => continue,
+                    "match_decisions" if !upstream && self.redecide => (
+                        all(),
+                        "Decision thresholds changed; existing scores are reused".to_string(),
+                    ),
+                    "golden_records" if !upstream && !self.fields.is_empty() => (
+                        self.fields.iter().cloned().collect(),
+                        "Survivorship changed".to_string(),
+                    ),
+                    _ if upstream => (all(), "Input recomputed upstream".to_string()),
+                    _ => continue,
+                }
+            };
+            upstream = true;
+            steps.push(MigrationStep {
+                stage: stage.clone(),
+                scope,
+                reason,
+            });
+        }
+        steps
+    }
+}
+
+fn rule_type(spec: &Value, name: &str) -> Option<String> {
+    spec.get("rules")?
+        .as_array()?
+        .iter()
+        .find(|r| r.get("name").and_then(|n| n.as_str()) == Some(name))
+        .and_then(|r| r.get("type"))
+        .and_then(|t| t.as_str())
+        .map(str::to_string)
+}
+
+/// Golden record fields whose survivorship rule changed, or `all` when the
+/// default strategy changed.
+fn survivorship_changes(old: &Value, new: &Value) -> BTreeSet<String> {
+    let section = |spec: &Value| spec.get("survivorship").cloned().unwrap_or(Value::Null);
+    let (old, new) = (section(old), section(new));
+    if old.get("default") != new.get("default") {
+        return BTreeSet::from([ALL.to_string()]);
+    }
+    let rules = |s: &Value| -> Vec<(String, Value)> {
+        s.get("rules")
+            .and_then(|r| r.as_array())
+            .into_iter()
+            .flatten()
+            .map(|r| {
+                let field = r.get("field").and_then(|f| f.as_str()).unwrap_or("unknown");
+                (field.to_string(), r.clone())
+            })
+            .collect()
+    };
+    let (before, after) = (rules(&old), rules(&new));
+    let mut fields: BTreeSet<String> = before
+        .iter()
+        .filter(|rule| !after.contains(rule))
+        .chain(after.iter().filter(|rule| !before.contains(rule)))
+        .map(|(field, _)| field.clone())
+        .collect();
+    if fields.is_empty() && old != new {
+        fields.insert(ALL.to_string());
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_migration_plan_scopes_steps_to_the_diff() {
+        let steps = |new: &str| {
+            let plan = crate::generate_migration_plan(MINIMAL, new).unwrap();
+            let steps: Vec<(String, Vec<String>)> = plan
+                .steps
+                .into_iter()
+                .map(|s| (s.stage.name, s.scope))
+                .collect();
+            (plan.full_rebuild, steps)
+        };
+        let names = |steps: &[(String, Vec<String>)]| -> Vec<String> {
+            steps.iter().map(|(name, _)| name.clone()).collect()
+        };
+
+        assert_eq!(steps(MINIMAL), (false, vec![]));
+
+        let (full, redecide) = steps(&MINIMAL.replace("match: 0.9", "match: 0.95"));
+        assert!(!full);
+        assert_eq!(
+            names(&redecide),
+            [
+                "Score & decide",
+                "Cluster entities",
+                "Apply survivorship",
+                "Emit outputs"
+            ]
+        );
+
+        let (_, rescore) = steps(&MINIMAL.replace("weight: 1.0", "weight: 0.8"));
+        assert_eq!(
+            rescore[0],
+            ("Exact matches".to_string(), vec!["email_exact".to_string()])
+        );
+        assert_eq!(names(&rescore[1..]), names(&redecide));
+
+        let (full, rebuild) = steps(&MINIMAL.replace("name: customer", "name: person"));
+        assert!(full);
+        assert_eq!(rebuild[0].0, "Normalize sources");
+        assert!(rebuild.iter().all(|(_, scope)| scope == &["all"]));
+    }
+}
@@ -1,5 +1,45 @@
+pub mod analyze;
+pub mod audit_schema;
+pub mod bench_suite;
+pub mod calibrate;
+pub mod check;
+pub mod cluster;
+pub mod codegen;
 pub mod compile;
+pub mod compose;
+pub mod conformance;
+pub mod decompile;
 pub mod diff;
+pub mod docs;
+pub mod evaluate;
+pub mod execute;
+pub mod explain;
+pub mod explain_pair;
+pub mod fmt;
+pub mod generate_data;
+pub mod golden_records;
 pub mod hash;
+pub mod init;
+pub mod ir;
+pub mod learn_weights;
+pub mod maintain;
+pub mod merge;
+pub mod migrate_plan;
+pub mod plugins;
 pub mod plan;
+pub mod profile;
+pub mod registry;
+pub mod report;
+pub mod review;
+pub mod risk_trend;
+pub mod schema;
+pub mod sign;
+pub mod snapshot;
+pub mod survivorship_impact;
+pub mod templates;
+pub mod test;
+pub mod tokenize;
 pub mod validate;
+pub mod verify;
+pub mod watch;
+pub mod worker;
@@ -1,11 +1,40 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::Path;
 
-use crate::parser;
+use crate::address;
+use crate::attributes::AttributeType;
+use crate::blocking::{Canopy, SortedNeighborhood};
+use crate::cache::{Cache, CacheKey};
+use crate::cancel::{self, CancellationToken};
+use crate::clustering::{Clustering, ClusteringStrategy};
+use crate::canonical::{canonical_hash, ir_hash};
+use crate::commands::compile::compile_to_ir;
+use crate::compose;
+use crate::custom_risks::CustomRisks;
+use crate::dag::Dag;
+use crate::deletion::{Deletion, DeletionScope};
+use crate::encoding::Encoding;
+use crate::diagnostics::{render_ci, Diagnostic, Profile, Tiers, CI_FORMATS};
+use crate::estimate::{self, CostEstimate, DEFAULT_PAIR_BUDGET};
+use crate::execution::{Execution, ExecutionMode};
+use crate::interpolate::Variables;
+use crate::ir::{self, Ir, IrNormalization};
+use crate::lsh::{self, LshConfig};
+use crate::output::Output;
+use crate::owners::{Owners, RoutedFinding, Routing};
+use crate::parser::{self, SourceMap};
+use crate::plugins::PluginRegistry;
+use crate::policy::{Level, Policy};
+use crate::privacy::{self, Masking, PiiAttribute};
+use crate::relationships::Relationship;
+use crate::sample::Sample;
+use crate::similarity::AlgorithmRegistry;
+use crate::temporal::Temporal;
+use crate::waivers::{Waived, Waivers};
 
 // ── Types ──────────────────────────────────────────────────────────
 
@@ -19,10 +48,41 @@ pub struct PlanResult {
     pub match_strategies: Vec<MatchStrategySummary>,
     pub survivorship_summary: Vec<SurvivorshipSummary>,
     pub blocking_analysis: BlockingAnalysis,
+    /// The spec's clustering strategy, when it sets one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clustering: Option<Clustering>,
+    /// The spec's execution mode, when it sets one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<Execution>,
+    /// Pair counts, runtime and memory at the sources' row counts, when
+    /// any are known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<CostEstimate>,
+    /// Attributes classified as PII, with their masking and retention.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pii: Vec<PiiAttribute>,
     pub risk_flags: Vec<RiskFlag>,
+    /// Severity-weighted sum of the risk flags, 0 (no flags) to 100.
+    pub risk_score: u32,
+    /// Risk flags suppressed by a waiver in the spec, with the reason.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub waived: Vec<Waived>,
+    /// Risk flags by responsible owner, when the spec declares owners.
+    #[serde(default, skip_serializing_if = "Routing::is_empty")]
+    pub routing: Routing,
+    /// The severity policy the flags were planned under, when there is one.
+    #[serde(default, skip_serializing_if = "Policy::is_empty")]
+    pub policy: Policy,
     pub summary: String,
 }
 
+impl PlanResult {
+    /// The execution stages as a graph, with each match rule a node.
+    pub fn dag(&self) -> Dag {
+        Dag::from_plan(self)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PlanSource {
     pub name: String,
@@ -30,7 +90,7 @@ pub struct PlanSource {
     pub field_count: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionStage {
     pub stage: usize,
     pub name: String,
@@ -48,6 +108,14 @@ pub struct MatchStrategySummary {
     pub threshold: Option<f64>,
     pub weight: f64,
     pub evaluation_order: usize,
+    /// Embedding model and endpoint of a semantic rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Pairs the rule compares, if not all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -61,14 +129,76 @@ pub struct SurvivorshipSummary {
 pub struct BlockingAnalysis {
     pub strategy: String,
     pub keys: Vec<BlockingKeySummary>,
+    /// `none`/`low`/`medium`/`high` from the key count, or the measured
+    /// reduction (`97.5%`) with a sample.
     pub estimated_reduction: String,
     pub warnings: Vec<String>,
+    /// Pair counts measured on a sample (`plan --sample`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample: Option<SampleBlocking>,
+    /// Collision probabilities of `lsh` blocking.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lsh: Option<LshAnalysis>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sorted_neighborhood: Option<SortedNeighborhood>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canopy: Option<Canopy>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LshAnalysis {
+    #[serde(flatten)]
+    pub config: LshConfig,
+    /// Similarity around which pairs go from rarely to usually colliding.
+    pub threshold: f64,
+    /// Chance that a pair shares a band, by similarity.
+    pub collision: Vec<CollisionPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollisionPoint {
+    pub similarity: f64,
+    pub probability: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BlockingKeySummary {
     pub name: String,
     pub transformation: String,
+    /// Measured on the sample, when it has a column for the key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample: Option<KeyStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyStats {
+    /// Distinct key values, i.e. blocks.
+    pub cardinality: usize,
+    /// Records without a value; this key never pairs them.
+    pub missing: usize,
+    pub largest_block: usize,
+    /// Share of this key's pairs that come from its largest block, 0 to 1.
+    pub skew: f64,
+    /// Record pairs sharing a value of this key; under LSH, summed over
+    /// the bands.
+    pub pairs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleBlocking {
+    pub records: usize,
+    /// All record pairs, n(n-1)/2.
+    pub total_pairs: u64,
+    /// Pairs sharing at least one key: the pairs that would be scored.
+    pub candidate_pairs: u64,
+    /// Fraction of all pairs blocking removes, 0 to 1. Carries over to the
+    /// full dataset as long as block sizes grow in proportion.
+    pub reduction: f64,
+    /// The windows (`sorted_neighborhood`) or canopies (`canopy`) of the
+    /// blocking strategy, as blocks; pairs are counted once however many
+    /// blocks share them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<KeyStats>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -79,21 +209,328 @@ pub struct RiskFlag {
     pub recommendation: String,
 }
 
+/// Score contribution per flag severity; the total is capped at 100.
+pub const RISK_WEIGHTS: &[(&str, u32)] = &[("critical", 25), ("high", 10), ("medium", 4), ("low", 1)];
+
+/// Spec section each built-in risk flag concerns, for owner routing.
+pub const FLAG_SECTIONS: &[(&str, &str)] = &[
+    ("NO_BLOCKING", "blocking"),
+    ("SINGLE_SIGNAL", "rules"),
+    ("LOW_THRESHOLD", "rules"),
+    ("HIGH_WEIGHT_FUZZY", "rules"),
+    ("NO_SURVIVORSHIP", "survivorship"),
+    ("PHONE_WITHOUT_BLOCKING", "blocking"),
+    ("NO_REVIEW_THRESHOLD", "decision"),
+    ("SINGLE_SOURCE", "sources"),
+    ("MISSING_TEMPORAL", "temporal"),
+    ("SKEWED_BLOCKING_KEY", "blocking"),
+    ("WEAK_BLOCKING", "blocking"),
+    ("INCREMENTAL_WITHOUT_STABLE_IDS", "execution"),
+    ("INCREMENTAL_WITHOUT_DELTA_COLUMN", "execution"),
+    ("INCREMENTAL_CLUSTER_DRIFT", "execution"),
+    ("PHONE_NOT_NORMALIZED", "normalization"),
+    ("EMAIL_CASE_SENSITIVE", "normalization"),
+    ("FUZZY_IDENTIFIER", "rules"),
+    ("ESTIMATED_PAIRS_EXCEEDS_BUDGET", "blocking"),
+    ("PII_IN_BLOCKING_KEY", "privacy"),
+];
+
+/// LSH blocking is warned about when pairs this similar collide less
+/// often than `LSH_MIN_RECALL`, or when the threshold is below
+/// `LSH_MIN_THRESHOLD` (nearly everything collides).
+const LSH_NEAR_DUPLICATE: f64 = 0.9;
+const LSH_MIN_RECALL: f64 = 0.9;
+const LSH_MIN_THRESHOLD: f64 = 0.3;
+
+/// Blocking transforms that keep a value recognizable; keys built with them
+/// hold the raw value.
+const PRESERVING_TRANSFORMS: &[&str] = &["lower", "lowercase", "upper", "uppercase", "trim"];
+
+/// Canopies with a loose threshold below this take in nearly every record.
+const CANOPY_MIN_LOOSE: f64 = 0.2;
+
+/// Sampled blocking is flagged as weak when it keeps more than this share
+/// of all pairs.
+const WEAK_BLOCKING_RATIO: f64 = 0.1;
+
+/// Beyond this many per-key pairs, candidate pairs are summed over keys
+/// (an upper bound) rather than de-duplicated.
+const PAIR_ENUMERATION_LIMIT: u64 = 20_000_000;
+
+/// Options for `generate_plan_with`.
+#[derive(Debug, Clone, Default)]
+pub struct PlanOptions {
+    /// Checked between planning phases; planning stops with `Cancelled`.
+    pub cancel: Option<CancellationToken>,
+    /// Organization-defined checks, flagged alongside the built-in ones.
+    pub custom_risks: CustomRisks,
+    /// Records to measure blocking on.
+    pub sample: Option<Sample>,
+    /// Levels for risk flags, usually a `.kanoniv.toml`'s; the spec's own
+    /// `policy` section is laid over it.
+    pub policy: Policy,
+    /// Plugins whose risk analyzers flag alongside the built-in ones.
+    pub plugins: PluginRegistry,
+    /// Row counts by source, over the spec's own `rows`.
+    pub rows: BTreeMap<String, u64>,
+    /// Candidate pairs beyond which the plan is flagged;
+    /// `DEFAULT_PAIR_BUDGET` if unset.
+    pub pair_budget: Option<u64>,
+}
+
 // ── CLI entry point ────────────────────────────────────────────────
 
-pub fn run(file: &Path) -> Result<()> {
-    let content = fs::read_to_string(file)
-        .with_context(|| format!("Failed to read file: {}", file.display()))?;
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: &Path,
+    options: &PlanOptions,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    fail_on: Option<&str>,
+    routing: Option<&Path>,
+    cache: Option<&Cache>,
+    out: &Output,
+) -> Result<()> {
+    fail_on.map(risk_weight).transpose()?;
+    let content = compose::read_spec(file, entity, variables)?;
+
+    // A sample's records and plugins' analyzers are not in the key, so
+    // with either the spec is always planned
+    let cache = cache.filter(|_| options.sample.is_none() && options.plugins.is_empty());
+    let key = CacheKey::new("plan")
+        .with(serde_json::to_string(&options.policy)?)
+        .with(options.custom_risks.digest())
+        .with(serde_json::to_string(&options.rows)?)
+        .with(format!("{:?}", options.pair_budget))
+        .spec(&content)
+        .finish();
+    // The deadline holds for a cached plan too.
+    cancel::check(options.cancel.as_ref())?;
+    let plan = match cache.and_then(|cache| cache.get::<PlanResult>(&key)) {
+        Some(plan) => {
+            out.detail("Unchanged since last planned: plan from the cache");
+            plan
+        }
+        None => {
+            let plan = generate_plan_with(&content, options)?;
+            if let Some(cache) = cache {
+                cache.put(&key, &plan);
+            }
+            plan
+        }
+    };
+    if let Some(path) = routing {
+        write_routing(path, &plan.routing, out)?;
+    }
+    let map = SourceMap::from_yaml(&content);
+    report(file, &plan, options, format, fail_on, Some(&map), out)
+}
+
+/// `kanoniv plan --from-ir`: plan from a compiled IR file.
+pub fn run_from_ir(
+    ir_path: &Path,
+    options: &PlanOptions,
+    format: &str,
+    fail_on: Option<&str>,
+    routing: Option<&Path>,
+    out: &Output,
+) -> Result<()> {
+    fail_on.map(risk_weight).transpose()?;
+    let value = ir::load_value(ir_path)?;
+    if !ir::plan_hash_matches(&value) {
+        out.warn(format!(
+            "{} {}: plan_hash does not match the IR's contents; it was edited after compiling",
+            out.warn_mark(),
+            ir_path.display()
+        ));
+    }
+    let plan = generate_plan_from_ir(&Ir::from_value(&value)?, options)?;
+    if let Some(path) = routing {
+        write_routing(path, &plan.routing, out)?;
+    }
+    report(ir_path, &plan, options, format, fail_on, None, out)
+}
 
-    let plan = generate_plan(&content)?;
+/// Print the plan, its execution DAG or its risk flags in a CI format, and
+/// fail if any flag is at least `fail_on` severe or the policy makes it an
+/// error.
+fn report(
+    file: &Path,
+    plan: &PlanResult,
+    options: &PlanOptions,
+    format: &str,
+    fail_on: Option<&str>,
+    map: Option<&SourceMap>,
+    out: &Output,
+) -> Result<()> {
+    let findings = flag_findings(plan, &options.custom_risks, fail_on, map);
+    let uri = file.to_string_lossy().replace('\\', "/");
+    match format {
+        "dot" => out.result(plan.dag().to_dot().trim_end()),
+        "mermaid" => out.result(plan.dag().to_mermaid().trim_end()),
+        "text" => print_plan(plan, out),
+        _ => match render_ci(format, "kanoniv plan", &uri, &findings) {
+            Some(report) if !report.is_empty() => out.result(report.trim_end()),
+            Some(_) => {}
+            None => bail!(
+                "Unknown plan format '{}'. Use text, {}, dot or mermaid",
+                format,
+                CI_FORMATS.join(", ")
+            ),
+        },
+    }
+    if findings.is_valid() {
+        return Ok(());
+    }
+    let failed = findings.errors.len();
+    match fail_on {
+        Some(threshold) if plan.policy.is_empty() => {
+            bail!("{} risk flag(s) at or above {} (--fail-on {})", failed, threshold, threshold)
+        }
+        Some(threshold) => {
+            bail!("{} risk flag(s) fail the severity policy (with --fail-on {})", failed, threshold)
+        }
+        None => bail!("{} risk flag(s) fail the severity policy", failed),
+    }
+}
 
+fn print_plan(plan: &PlanResult, out: &Output) {
     // Print human-readable summary
-    println!("{}", "Plan Summary:".bold());
-    println!("{}", plan.summary);
+    out.info("Plan Summary:".bold());
+    out.result(out.wrap_block(&plan.summary));
+
+    out.detail("");
+    out.detail(format!("{}:", "Execution Stages".bold()));
+    for stage in &plan.execution_stages {
+        out.detail(out.wrap_block(&format!(
+            "  {}. {}: {}",
+            stage.stage, stage.name, stage.description
+        )));
+    }
+
+    if let Some(lsh) = &plan.blocking_analysis.lsh {
+        out.info("");
+        out.info(format!(
+            "{} ({}):",
+            "LSH Blocking".bold(),
+            lsh_params(&lsh.config)
+        ));
+        out.info(format!("  Threshold: similarity ~{}", lsh.threshold));
+        for point in &lsh.collision {
+            out.info(format!(
+                "  Similarity {:.2} {} {:.1}% of pairs collide",
+                point.similarity,
+                out.arrow(),
+                point.probability * 100.0
+            ));
+        }
+    }
+
+    if let Some(sample) = &plan.blocking_analysis.sample {
+        out.info("");
+        out.info(format!(
+            "{} ({} records):",
+            "Blocking Sample".bold(),
+            sample.records
+        ));
+        for key in &plan.blocking_analysis.keys {
+            let Some(stats) = &key.sample else {
+                continue;
+            };
+            out.info(out.wrap_block(&format!(
+                "  {} ({}) {} {} blocks, largest {}, {} missing, {} pairs",
+                key.name,
+                key.transformation,
+                out.dash(),
+                stats.cardinality,
+                stats.largest_block,
+                stats.missing,
+                stats.pairs
+            )));
+        }
+        if let Some(stats) = &sample.strategy {
+            let blocks = if plan.blocking_analysis.canopy.is_some() {
+                "canopies"
+            } else {
+                "windows"
+            };
+            out.info(out.wrap_block(&format!(
+                "  {} {} {} {}, largest {}, {} missing, {} pairs",
+                plan.blocking_analysis.strategy,
+                out.dash(),
+                stats.cardinality,
+                blocks,
+                stats.largest_block,
+                stats.missing,
+                stats.pairs
+            )));
+        }
+        out.info(format!(
+            "  Candidate pairs: {} of {} ({:.1}% reduction)",
+            sample.candidate_pairs,
+            sample.total_pairs,
+            sample.reduction * 100.0
+        ));
+        for warning in &plan.blocking_analysis.warnings {
+            out.warn(format!("{} {}", out.warn_mark(), warning));
+        }
+    }
+
+    if let Some(estimate) = &plan.estimate {
+        out.info("");
+        out.info(format!(
+            "{} ({} records, {}):",
+            "Estimates".bold(),
+            estimate::count(estimate.records as f64),
+            estimate.basis
+        ));
+        out.info(format!(
+            "  Candidate pairs: ~{} of {}",
+            estimate::count(estimate.candidate_pairs as f64),
+            estimate::count(estimate.total_pairs as f64)
+        ));
+        for key in &estimate.keys {
+            out.info(format!(
+                "  {} ({}) {} ~{} pairs",
+                key.name,
+                key.transformation,
+                out.dash(),
+                estimate::count(key.pairs as f64)
+            ));
+        }
+        for rule in &estimate.rules {
+            out.info(format!(
+                "  {} {} ~{} comparisons, ~{}",
+                rule.rule_name,
+                out.dash(),
+                estimate::count(rule.comparisons as f64),
+                estimate::duration(rule.seconds)
+            ));
+        }
+        for stage in &estimate.stages {
+            out.info(format!(
+                "  {}. {} {} ~{}, {}",
+                stage.stage,
+                stage.name,
+                out.dash(),
+                estimate::duration(stage.seconds),
+                estimate::bytes(stage.memory_bytes)
+            ));
+        }
+        out.info(format!(
+            "  Total: ~{}, peak {}",
+            estimate::duration(estimate.seconds),
+            estimate::bytes(estimate.peak_memory_bytes)
+        ));
+        for warning in &estimate.warnings {
+            out.warn(format!("{} {}", out.warn_mark(), warning));
+        }
+    }
 
     if !plan.risk_flags.is_empty() {
-        println!();
-        println!("{}:", "Risk Flags".bold());
+        out.info("");
+        out.info(format!("{}:", "Risk Flags".bold()));
         for flag in &plan.risk_flags {
             let severity = match flag.severity.as_str() {
                 "critical" => flag.severity.red().bold().to_string(),
@@ -101,55 +538,188 @@ pub fn run(file: &Path) -> Result<()> {
                 "medium" => flag.severity.yellow().to_string(),
                 _ => flag.severity.cyan().to_string(),
             };
-            println!("  [{}] {} — {}", severity, flag.code, flag.message);
-            println!("         {}", flag.recommendation.dimmed());
+            out.info(out.wrap_block(&format!(
+                "  [{}] {} {} {}",
+                severity,
+                flag.code,
+                out.dash(),
+                flag.message
+            )));
+            out.info(
+                out.wrap_block(&format!("         {}", flag.recommendation))
+                    .dimmed(),
+            );
         }
     }
 
+    if !plan.waived.is_empty() {
+        out.info("");
+        out.info(format!("{}:", "Waived".bold()));
+        for waived in &plan.waived {
+            out.info(out.wrap_block(&format!(
+                "  {} {} {}",
+                waived.code,
+                out.dash(),
+                waived.message
+            )));
+            let expires = match &waived.waiver.expires {
+                Some(date) => format!(" (until {})", date),
+                None => String::new(),
+            };
+            out.info(
+                out.wrap_block(&format!(
+                    "         reason: {}{}",
+                    waived.waiver.reason, expires
+                ))
+                .dimmed(),
+            );
+        }
+    }
+
+    print_routing(&plan.routing, out);
+}
+
+/// List each owner's findings.
+pub(crate) fn print_routing(routing: &Routing, out: &Output) {
+    if routing.is_empty() {
+        return;
+    }
+    out.info("");
+    out.info(format!("{}:", "Routing".bold()));
+    for (owner, findings) in routing {
+        let codes: Vec<&str> = findings.iter().map(|f| f.code.as_str()).collect();
+        out.info(out.wrap_block(&format!("  {} {} {}", owner, out.dash(), codes.join(", "))));
+    }
+}
+
+/// Write the owner -> findings map for review tooling.
+pub(crate) fn write_routing(path: &Path, routing: &Routing, out: &Output) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(routing)? + "\n")
+        .with_context(|| format!("Failed to write routing file: {}", path.display()))?;
+    if routing.is_empty() {
+        out.warn(format!("{} The spec declares no owners; {} is empty", out.warn_mark(), path.display()));
+    }
     Ok(())
 }
 
 // ── Core logic ─────────────────────────────────────────────────────
 
 pub fn generate_plan(yaml_str: &str) -> Result<PlanResult> {
+    generate_plan_with(yaml_str, &PlanOptions::default())
+}
+
+pub fn generate_plan_with(yaml_str: &str, options: &PlanOptions) -> Result<PlanResult> {
+    cancel::check(options.cancel.as_ref())?;
+
     let spec = parser::parse_yaml(yaml_str)
         .with_context(|| "Failed to parse YAML for plan generation")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
 
-    // Extract identity info
-    let entity = spec
-        .get("entity")
-        .and_then(|e| e.get("name"))
-        .and_then(|n| n.as_str())
-        .unwrap_or("unknown")
-        .to_string();
+    plan_ir(
+        &ir,
+        &spec,
+        &Waivers::collect(yaml_str, &spec),
+        &Owners::from_spec(&spec),
+        compute_plan_hash(&spec)?,
+        options,
+    )
+}
 
-    let identity_version = spec
-        .get("identity_version")
-        .and_then(|v| v.as_str())
-        .unwrap_or("unknown")
-        .to_string();
+/// Plan from compiled IR alone, for systems that keep IR rather than YAML.
+/// The IR carries no waivers or owners, so nothing is waived or routed, and
+/// the plan hash is the IR's own `plan_hash`. Custom risks see the IR laid
+/// out as a spec (`Ir::to_spec`).
+pub fn generate_plan_from_ir(ir: &Ir, options: &PlanOptions) -> Result<PlanResult> {
+    cancel::check(options.cancel.as_ref())?;
+
+    let plan_hash = if ir.plan_hash.is_empty() {
+        ir_hash(&serde_json::to_value(ir)?)
+    } else {
+        ir.plan_hash.clone()
+    };
+    plan_ir(
+        ir,
+        &ir.to_spec(),
+        &Waivers::default(),
+        &Owners::default(),
+        plan_hash,
+        options,
+    )
+}
+
+/// The plan for `ir`. `spec` is what custom risks are evaluated on.
+fn plan_ir(
+    ir: &Ir,
+    spec: &serde_json::Value,
+    waivers: &Waivers,
+    owners: &Owners,
+    plan_hash: String,
+    options: &PlanOptions,
+) -> Result<PlanResult> {
+    let token = options.cancel.as_ref();
+
+    let entity = ir.entity.clone().unwrap_or_else(|| "unknown".to_string());
+    let identity_version = ir
+        .identity_version
+        .clone()
+        .unwrap_or_else(|| "unknown".to_string());
 
     // Extract sources
-    let sources = extract_sources(&spec);
+    let sources = extract_sources(ir);
 
     // Extract match strategies from rules
-    let match_strategies = extract_match_strategies(&spec);
+    let match_strategies = extract_match_strategies(ir);
 
     // Extract survivorship
-    let survivorship_summary = extract_survivorship(&spec);
+    let survivorship_summary = extract_survivorship(ir);
 
     // Analyse blocking
-    let blocking_analysis = analyse_blocking(&spec);
+    let blocking_analysis = analyse_blocking(ir, options.sample.as_ref(), token)?;
+    cancel::check(token)?;
 
     // Build execution stages
     let source_names: Vec<String> = sources.iter().map(|s| s.name.clone()).collect();
-    let execution_stages = build_execution_stages(&source_names, &match_strategies, &blocking_analysis);
+    let clustering = ir.clustering;
+    let execution_stages = build_execution_stages(
+        &source_names,
+        &match_strategies,
+        &blocking_analysis,
+        &clustering.unwrap_or_default(),
+        ir.temporal.as_ref(),
+        &ir.execution.clone().unwrap_or_default(),
+        &entity,
+        &ir.relationships,
+        ir.normalization.as_ref(),
+        ir.encoding.as_ref(),
+        ir.deletion.as_ref(),
+    );
 
-    // Static analysis risk flags
-    let risk_flags = analyse_risks(&spec, &match_strategies, &blocking_analysis, &survivorship_summary, &sources);
+    // Estimate costs at the sources' row counts
+    let rows = estimate::row_counts(ir, spec, &options.rows)?;
+    let estimate = CostEstimate::new(ir, &rows, &blocking_analysis, &match_strategies, &execution_stages);
 
-    // Compute plan hash
-    let plan_hash = compute_plan_hash(&spec)?;
+    // Attributes holding PII
+    let pii = privacy::classify_ir(ir);
+
+    // Static analysis risk flags
+    let mut risk_flags = analyse_risks(
+        ir,
+        &match_strategies,
+        &blocking_analysis,
+        &survivorship_summary,
+        &sources,
+        &pii,
+    );
+    let budget = options.pair_budget.unwrap_or(DEFAULT_PAIR_BUDGET);
+    risk_flags.extend(estimate.as_ref().and_then(|e| e.budget_flag(budget)));
+    risk_flags.extend(options.custom_risks.evaluate(spec));
+    risk_flags.extend(options.plugins.risks(spec)?);
+    let (mut risk_flags, waived) = apply_waivers(risk_flags, waivers);
+    let policy = options.policy.for_spec(spec);
+    risk_flags.retain(|flag| policy.level(&flag.code) != Some(Level::Ignore));
+    let risk_score = risk_score(&risk_flags);
+    let routing = route_flags(&risk_flags, owners, &options.custom_risks);
+    cancel::check(token)?;
 
     // Build human-readable summary
     let summary = build_summary(
@@ -158,9 +728,14 @@ pub fn generate_plan(yaml_str: &str) -> Result<PlanResult> {
         &sources,
         &match_strategies,
         &blocking_analysis,
-        &spec,
+        ir,
         &survivorship_summary,
+        execution_stages.len(),
+        estimate.as_ref(),
+        &pii,
         &risk_flags,
+        risk_score,
+        waived.len(),
         &plan_hash,
     );
 
@@ -173,193 +748,532 @@ pub fn generate_plan(yaml_str: &str) -> Result<PlanResult> {
         match_strategies,
         survivorship_summary,
         blocking_analysis,
+        clustering,
+        execution: ir.execution.clone(),
+        estimate,
+        pii,
         risk_flags,
+        risk_score,
+        waived,
+        routing,
+        policy,
         summary,
     })
 }
 
-fn extract_sources(spec: &serde_json::Value) -> Vec<PlanSource> {
-    spec.get("sources")
-        .and_then(|s| s.as_array())
-        .map(|arr| {
-            arr.iter()
-                .map(|source| {
-                    let name = source
-                        .get("name")
-                        .and_then(|n| n.as_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let system = source
-                        .get("system")
-                        .and_then(|s| s.as_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let field_count = source
-                        .get("attributes")
-                        .and_then(|a| a.as_object())
-                        .map(|o| o.len())
-                        .unwrap_or(0);
-                    PlanSource {
-                        name,
-                        system,
-                        field_count,
-                    }
-                })
-                .collect()
-        })
-        .unwrap_or_default()
+/// `name`, or `unknown` where the spec left it out.
+fn or_unknown(name: &str) -> String {
+    if name.is_empty() {
+        "unknown".to_string()
+    } else {
+        name.to_string()
+    }
 }
 
-fn extract_match_strategies(spec: &serde_json::Value) -> Vec<MatchStrategySummary> {
-    spec.get("rules")
-        .and_then(|r| r.as_array())
-        .map(|rules| {
-            rules
-                .iter()
-                .map(|rule| {
-                    let rule_name = rule
-                        .get("name")
-                        .and_then(|n| n.as_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let match_type = rule
-                        .get("type")
-                        .and_then(|t| t.as_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let field = rule
-                        .get("field")
-                        .and_then(|f| f.as_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let algorithm = rule.get("algorithm").and_then(|a| a.as_str()).map(String::from);
-                    let threshold = rule.get("threshold").and_then(|t| t.as_f64());
-                    let weight = rule.get("weight").and_then(|w| w.as_f64()).unwrap_or(0.0);
-
-                    // Exact rules evaluate before fuzzy
-                    let evaluation_order = match match_type.as_str() {
-                        "exact" => 3,
-                        "fuzzy" | "phonetic" => 4,
-                        "composite" => 4,
-                        _ => 4,
-                    };
-
-                    MatchStrategySummary {
-                        rule_name,
-                        match_type,
-                        field,
-                        algorithm,
-                        threshold,
-                        weight,
-                        evaluation_order,
-                    }
-                })
-                .collect()
+fn extract_sources(ir: &Ir) -> Vec<PlanSource> {
+    ir.sources
+        .iter()
+        .map(|source| PlanSource {
+            name: or_unknown(&source.name),
+            system: or_unknown(source.system.as_deref().unwrap_or_default()),
+            field_count: source.attributes.len(),
         })
-        .unwrap_or_default()
+        .collect()
 }
 
-fn extract_survivorship(spec: &serde_json::Value) -> Vec<SurvivorshipSummary> {
-    spec.get("survivorship")
-        .and_then(|s| s.get("rules"))
-        .and_then(|r| r.as_array())
-        .map(|rules| {
-            rules
-                .iter()
-                .map(|rule| {
-                    let field = rule
-                        .get("field")
-                        .and_then(|f| f.as_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let strategy = rule
-                        .get("strategy")
-                        .and_then(|s| s.as_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let source_priority = rule
-                        .get("source_priority")
-                        .and_then(|sp| sp.as_array())
-                        .map(|arr| {
-                            arr.iter()
-                                .filter_map(|v| v.as_str().map(String::from))
-                                .collect()
-                        });
-                    SurvivorshipSummary {
-                        field,
-                        strategy,
-                        source_priority,
-                    }
-                })
-                .collect()
+pub(crate) fn extract_match_strategies(ir: &Ir) -> Vec<MatchStrategySummary> {
+    ir.rules
+        .iter()
+        .map(|rule| {
+            let match_type = or_unknown(&rule.match_type);
+
+            // Exact rules evaluate before fuzzy, and semantic rules after
+            let evaluation_order = match match_type.as_str() {
+                "exact" => 3,
+                "fuzzy" | "phonetic" => 4,
+                "composite" => 4,
+                "semantic" => 5,
+                _ => 4,
+            };
+
+            MatchStrategySummary {
+                rule_name: or_unknown(&rule.name),
+                match_type,
+                field: or_unknown(rule.field.as_deref().unwrap_or_default()),
+                algorithm: rule.algorithm.clone(),
+                threshold: rule.threshold,
+                weight: rule.weight,
+                evaluation_order,
+                model: rule.model.clone(),
+                endpoint: rule.endpoint.clone(),
+                condition: rule.condition.clone(),
+            }
         })
-        .unwrap_or_default()
+        .collect()
 }
 
-fn analyse_blocking(spec: &serde_json::Value) -> BlockingAnalysis {
-    let blocking = spec.get("blocking");
-
-    let strategy = blocking
-        .and_then(|b| b.get("strategy"))
-        .and_then(|s| s.as_str())
-        .unwrap_or("none")
-        .to_string();
+fn extract_survivorship(ir: &Ir) -> Vec<SurvivorshipSummary> {
+    ir.survivorship
+        .iter()
+        .map(|rule| SurvivorshipSummary {
+            field: or_unknown(&rule.field),
+            strategy: or_unknown(&rule.strategy),
+            source_priority: rule.source_priority.clone(),
+        })
+        .collect()
+}
 
-    let keys: Vec<BlockingKeySummary> = blocking
-        .and_then(|b| b.get("keys"))
-        .and_then(|k| k.as_array())
-        .map(|arr| {
-            arr.iter()
-                .map(|key| {
-                    let name = key
-                        .get("field")
-                        .or_else(|| key.get("name"))
-                        .and_then(|n| n.as_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    let transformation = key
-                        .get("transform")
-                        .or_else(|| key.get("transformation"))
-                        .and_then(|t| t.as_str())
-                        .unwrap_or("identity")
-                        .to_string();
-                    BlockingKeySummary {
-                        name,
-                        transformation,
-                    }
-                })
-                .collect()
+fn analyse_blocking(
+    ir: &Ir,
+    sample: Option<&Sample>,
+    token: Option<&CancellationToken>,
+) -> Result<BlockingAnalysis> {
+    let strategy = ir
+        .blocking
+        .strategy
+        .clone()
+        .unwrap_or_else(|| "none".to_string());
+
+    let mut keys: Vec<BlockingKeySummary> = ir
+        .blocking
+        .keys
+        .iter()
+        .map(|key| BlockingKeySummary {
+            name: or_unknown(&key.field),
+            transformation: key
+                .transform
+                .clone()
+                .unwrap_or_else(|| "identity".to_string()),
+            sample: None,
         })
-        .unwrap_or_default();
+        .collect();
 
     let mut warnings = Vec::new();
-    let estimated_reduction;
+    let mut estimated_reduction;
+    let lsh = ir.blocking.lsh.map(|config| analyse_lsh(config, &mut warnings));
 
     if keys.is_empty() && strategy == "none" {
         warnings.push("No blocking keys defined — O(n\u{00B2}) pairwise comparisons".to_string());
         estimated_reduction = "none".to_string();
-    } else if keys.len() == 1 {
-        estimated_reduction = "low".to_string();
-    } else if keys.len() <= 3 {
-        estimated_reduction = "medium".to_string();
-    } else {
+    } else if let Some(lsh) = &lsh {
+        // LSH keeps pairs above its threshold, however many keys it hashes.
+        estimated_reduction = match lsh.threshold {
+            t if t >= 0.7 => "high",
+            t if t >= 0.4 => "medium",
+            _ => "low",
+        }
+        .to_string();
+    } else if ir.blocking.sorted_neighborhood.is_some() {
+        // Pairs grow with the records rather than their square.
         estimated_reduction = "high".to_string();
+    } else if let Some(canopy) = &ir.blocking.canopy {
+        estimated_reduction = match canopy.loose {
+            t if t >= 0.6 => "high",
+            t if t >= 0.3 => "medium",
+            _ => "low",
+        }
+        .to_string();
+        if canopy.loose < CANOPY_MIN_LOOSE {
+            warnings.push(format!(
+                "Canopy loose threshold {} puts most records in every canopy — raise it",
+                canopy.loose
+            ));
+        }
+    } else {
+        // By key count, a tier up if some key is near-unique and a tier
+        // down if every key only splits records coarsely.
+        let mut tier: usize = match keys.len() {
+            1 => 0,
+            0..=3 => 1,
+            _ => 2,
+        };
+        let grains: Vec<Granularity> = keys.iter().map(|k| granularity(&k.transformation)).collect();
+        if grains.contains(&Granularity::Fine) {
+            tier = (tier + 1).min(2);
+        } else if !grains.is_empty() && grains.iter().all(|g| *g == Granularity::Coarse) {
+            tier = tier.saturating_sub(1);
+        }
+        estimated_reduction = ["low", "medium", "high"][tier].to_string();
+    }
+    for key in &keys {
+        let transform = key.transformation.as_str();
+        match address::geohash_precision(transform) {
+            Some(precision) if precision <= 4 => warnings.push(format!(
+                "Blocking key '{}' uses {} — cells about {} across make large blocks",
+                key.name,
+                transform,
+                GEOHASH_CELL_WIDTHS[precision - 1]
+            )),
+            None if transform.starts_with("geohash") => warnings.push(format!(
+                "Blocking key '{}' uses {}, which is not a geohash precision (1 to 12) — the raw value is used",
+                key.name, transform
+            )),
+            _ => {}
+        }
     }
 
-    BlockingAnalysis {
+    let sample = match sample {
+        Some(sample) => {
+            let measured = measure_blocking(ir, &mut keys, sample, &mut warnings, token)?;
+            estimated_reduction = format!("{:.1}%", measured.reduction * 100.0);
+            Some(measured)
+        }
+        None => None,
+    };
+
+    Ok(BlockingAnalysis {
         strategy,
         keys,
         estimated_reduction,
         warnings,
+        sample,
+        lsh,
+        sorted_neighborhood: ir.blocking.sorted_neighborhood.clone(),
+        canopy: ir.blocking.canopy.clone(),
+    })
+}
+
+/// `sort on last_name (soundex), window 10` or `company_name via jaccard,
+/// loose 0.4, tight 0.8`; `None` for strategies without parameters.
+pub(crate) fn strategy_params(blocking: &BlockingAnalysis) -> Option<String> {
+    if let Some(window) = &blocking.sorted_neighborhood {
+        let transform = window.transform.as_deref().unwrap_or("identity");
+        return Some(format!(
+            "sort on {} ({}), window {}",
+            window.sort_key, transform, window.window
+        ));
+    }
+    if let Some(canopy) = &blocking.canopy {
+        return Some(format!(
+            "{} via {}, loose {}, tight {}",
+            canopy.field, canopy.algorithm, canopy.loose, canopy.tight
+        ));
+    }
+    blocking.lsh.as_ref().map(|lsh| lsh_params(&lsh.config))
+}
+
+/// `minhash, 20 bands x 5 rows, 3-character shingles`
+fn lsh_params(config: &LshConfig) -> String {
+    format!(
+        "{}, {} bands x {} rows, {}-character shingles",
+        config.method, config.bands, config.rows, config.shingle_size
+    )
+}
+
+fn analyse_lsh(config: LshConfig, warnings: &mut Vec<String>) -> LshAnalysis {
+    let round = |x: f64| (x * 1000.0).round() / 1000.0;
+    let threshold = round(config.threshold());
+    let near_duplicates = config.collision_probability(LSH_NEAR_DUPLICATE);
+    if near_duplicates < LSH_MIN_RECALL {
+        warnings.push(format!(
+            "LSH with {} bands of {} rows pairs only {:.0}% of records at similarity {} — add bands or remove rows",
+            config.bands,
+            config.rows,
+            near_duplicates * 100.0,
+            LSH_NEAR_DUPLICATE
+        ));
+    }
+    if threshold < LSH_MIN_THRESHOLD {
+        warnings.push(format!(
+            "LSH threshold is about {} — most pairs collide; add rows or remove bands",
+            threshold
+        ));
+    }
+    LshAnalysis {
+        config,
+        threshold,
+        collision: lsh::REPORTED_SIMILARITIES
+            .iter()
+            .map(|&similarity| CollisionPoint {
+                similarity,
+                probability: round(config.collision_probability(similarity)),
+            })
+            .collect(),
+    }
+}
+
+/// Approximate width of a geohash cell, by precision.
+const GEOHASH_CELL_WIDTHS: [&str; 12] = [
+    "5000 km", "1250 km", "156 km", "39 km", "4.9 km", "1.2 km", "153 m", "38 m", "4.8 m",
+    "1.2 m", "15 cm", "4 cm",
+];
+
+/// How finely a blocking transform splits records, next to blocking on
+/// the value as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Granularity {
+    /// Large blocks: a coarse geohash, or a street name shared across towns.
+    Coarse,
+    Normal,
+    /// Nearly one record per block: a standardized address or fine geohash.
+    Fine,
+}
+
+pub(crate) fn granularity(transform: &str) -> Granularity {
+    match (address::geohash_precision(transform), transform) {
+        (Some(precision), _) if precision <= 4 => Granularity::Coarse,
+        (Some(precision), _) if precision >= 8 => Granularity::Fine,
+        (Some(_), _) => Granularity::Normal,
+        (None, "street") => Granularity::Coarse,
+        (None, "address") => Granularity::Fine,
+        _ => Granularity::Normal,
+    }
+}
+
+/// One key's blocks over a sample.
+struct Partition {
+    /// Each record's blocks: none where the key is missing, several for a
+    /// strategy whose blocks overlap.
+    block_of: Vec<Vec<usize>>,
+    /// Each block's records.
+    blocks: Vec<Vec<usize>>,
+}
+
+impl Partition {
+    /// A partition of `records` records into possibly overlapping blocks.
+    fn overlapping(records: usize, blocks: Vec<Vec<usize>>) -> Self {
+        let mut block_of = vec![Vec::new(); records];
+        for (id, block) in blocks.iter().enumerate() {
+            for &record in block {
+                block_of[record].push(id);
+            }
+        }
+        Partition { block_of, blocks }
+    }
+}
+
+/// Pairs of records sharing a block of at least one of `partitions`, each
+/// counted once.
+fn distinct_pairs(
+    partitions: &[Partition],
+    records: usize,
+    token: Option<&CancellationToken>,
+) -> Result<u64> {
+    // Count each record's later partners once, however many blocks it
+    // shares with them.
+    let mut seen_by = vec![usize::MAX; records];
+    let mut count = 0u64;
+    for record in 0..records {
+        if record % cancel::CHECK_INTERVAL == 0 {
+            cancel::check(token)?;
+        }
+        for partition in partitions {
+            for &block in &partition.block_of[record] {
+                for &other in &partition.blocks[block] {
+                    if other > record && seen_by[other] != record {
+                        seen_by[other] = record;
+                        count += 1;
+                    }
+                }
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Records grouped by their block value.
+fn partition(values: impl IntoIterator<Item = Option<String>>) -> Partition {
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    let mut blocks: Vec<Vec<usize>> = Vec::new();
+    let block_of = values
+        .into_iter()
+        .enumerate()
+        .map(|(record, value)| {
+            let Some(value) = value else {
+                return Vec::new();
+            };
+            let id = *ids.entry(value).or_insert_with(|| {
+                blocks.push(Vec::new());
+                blocks.len() - 1
+            });
+            blocks[id].push(record);
+            vec![id]
+        })
+        .collect();
+    Partition { block_of, blocks }
+}
+
+/// Block `sample` on each key, filling in the keys' statistics, and count
+/// the pairs that share at least one key.
+fn measure_blocking(
+    ir: &Ir,
+    keys: &mut [BlockingKeySummary],
+    sample: &Sample,
+    warnings: &mut Vec<String>,
+    token: Option<&CancellationToken>,
+) -> Result<SampleBlocking> {
+    let pairs = |n: usize| (n as u64) * (n as u64).saturating_sub(1) / 2;
+    let records = sample.len();
+
+    let mut partitions: Vec<Partition> = Vec::new();
+    for key in keys.iter_mut() {
+        cancel::check(token)?;
+        let field = key.name.as_str();
+        let Some(column) = sample.ir_column(ir, field) else {
+            warnings.push(format!("Sample has no column for blocking key '{}'", field));
+            continue;
+        };
+        let values = sample.keys(column, &key.transformation);
+        let missing = values.iter().filter(|v| v.is_none()).count();
+        // Under LSH each band is a partition of its own.
+        let key_partitions: Vec<Partition> = match ir.blocking.lsh {
+            Some(config) => {
+                let bands: Vec<Vec<String>> = values
+                    .iter()
+                    .map(|v| v.as_deref().map(|v| config.band_keys(v)).unwrap_or_default())
+                    .collect();
+                (0..config.bands)
+                    .map(|band| partition(bands.iter().map(|keys| keys.get(band).cloned())))
+                    .collect()
+            }
+            None => vec![partition(values)],
+        };
+
+        let blocks = || key_partitions.iter().flat_map(|p| &p.blocks);
+        let key_pairs: u64 = blocks().map(|b| pairs(b.len())).sum();
+        let largest_block = blocks().map(Vec::len).max().unwrap_or(0);
+        key.sample = Some(KeyStats {
+            cardinality: blocks().count(),
+            missing,
+            largest_block,
+            skew: if key_pairs == 0 {
+                0.0
+            } else {
+                pairs(largest_block) as f64 / key_pairs as f64
+            },
+            pairs: key_pairs,
+        });
+        partitions.extend(key_partitions);
+    }
+
+    cancel::check(token)?;
+    let strategy = match measure_strategy(ir, sample, warnings) {
+        Some(blocks) => {
+            let largest_block = blocks.blocks.iter().map(Vec::len).max().unwrap_or(0);
+            let strategy_pairs = distinct_pairs(std::slice::from_ref(&blocks), records, token)?;
+            let stats = KeyStats {
+                cardinality: blocks.blocks.len(),
+                missing: blocks.block_of.iter().filter(|b| b.is_empty()).count(),
+                largest_block,
+                skew: if strategy_pairs == 0 {
+                    0.0
+                } else {
+                    (pairs(largest_block) as f64 / strategy_pairs as f64).min(1.0)
+                },
+                pairs: strategy_pairs,
+            };
+            partitions.push(blocks);
+            Some(stats)
+        }
+        None => None,
+    };
+
+    let summed: u64 = keys
+        .iter()
+        .filter_map(|k| k.sample.as_ref())
+        .chain(&strategy)
+        .map(|s| s.pairs)
+        .sum();
+    let candidate_pairs = if partitions.len() <= 1 || summed > PAIR_ENUMERATION_LIMIT {
+        if partitions.len() > 1 {
+            warnings.push(
+                "Candidate pairs summed over keys; pairs sharing several keys are counted more than once"
+                    .to_string(),
+            );
+        }
+        summed
+    } else {
+        distinct_pairs(&partitions, records, token)?
+    };
+
+    let total_pairs = pairs(records);
+    let (candidate_pairs, reduction) = if partitions.is_empty() {
+        // Nothing measured: assume every pair is compared.
+        (total_pairs, 0.0)
+    } else if total_pairs == 0 {
+        (candidate_pairs, 0.0)
+    } else {
+        (
+            candidate_pairs,
+            1.0 - candidate_pairs.min(total_pairs) as f64 / total_pairs as f64,
+        )
+    };
+    Ok(SampleBlocking {
+        records,
+        total_pairs,
+        candidate_pairs,
+        reduction,
+        strategy,
+    })
+}
+
+/// The sample's sorted-neighbourhood windows or canopies, if the blocking
+/// strategy has them and the sample has its column.
+fn measure_strategy(ir: &Ir, sample: &Sample, warnings: &mut Vec<String>) -> Option<Partition> {
+    let blocking = &ir.blocking;
+    let (strategy, field) = match &blocking.sorted_neighborhood {
+        Some(window) => ("sorted_neighborhood", &window.sort_key),
+        None => ("canopy", &blocking.canopy.as_ref()?.field),
+    };
+    let Some(column) = sample.ir_column(ir, field) else {
+        warnings.push(format!("Sample has no column for {} field '{}'", strategy, field));
+        return None;
+    };
+    let blocks = match &blocking.sorted_neighborhood {
+        Some(window) => {
+            let transform = window.transform.as_deref().unwrap_or("identity");
+            window.windows(&sample.keys(column, transform))
+        }
+        None => blocking.canopy.as_ref()?.canopies(
+            &sample.keys(column, "identity"),
+            &AlgorithmRegistry::builtin(),
+        ),
+    };
+    Some(Partition::overlapping(sample.len(), blocks))
+}
+
+/// A compiled normalization step as stage descriptions show it.
+pub(crate) fn describe_step(step: &serde_json::Value) -> String {
+    match step.get("regex_replace") {
+        Some(args) => format!(
+            "regex_replace('{}' => '{}')",
+            args["pattern"].as_str().unwrap_or_default(),
+            args["replacement"].as_str().unwrap_or_default()
+        ),
+        None => step.as_str().unwrap_or_default().to_string(),
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_execution_stages(
     source_names: &[String],
     match_strategies: &[MatchStrategySummary],
     blocking: &BlockingAnalysis,
+    clustering: &Clustering,
+    temporal: Option<&Temporal>,
+    execution: &Execution,
+    entity: &str,
+    relationships: &[Relationship],
+    normalization: Option<&IrNormalization>,
+    encoding: Option<&Encoding>,
+    deletion: Option<&Deletion>,
 ) -> Vec<ExecutionStage> {
     let source_list = source_names.join(", ");
+    let pipelines = match normalization.filter(|n| !n.fields.is_empty()) {
+        Some(normalization) => format!(
+            ". Pipelines: {}",
+            normalization
+                .fields
+                .iter()
+                .map(|(field, steps)| {
+                    let steps = steps.iter().map(describe_step).collect::<Vec<_>>();
+                    format!("{} ({})", field, steps.join(" → "))
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        None => String::new(),
+    };
 
     let exact_rules: Vec<&MatchStrategySummary> = match_strategies
         .iter()
@@ -367,7 +1281,11 @@ fn build_execution_stages(
         .collect();
     let fuzzy_rules: Vec<&MatchStrategySummary> = match_strategies
         .iter()
-        .filter(|m| m.match_type != "exact")
+        .filter(|m| m.match_type != "exact" && m.match_type != "semantic")
+        .collect();
+    let semantic_rules: Vec<&MatchStrategySummary> = match_strategies
+        .iter()
+        .filter(|m| m.match_type == "semantic")
         .collect();
 
     let exact_desc = if exact_rules.is_empty() {
@@ -393,7 +1311,9 @@ fn build_execution_stages(
             .join(", ")
     };
 
-    let blocking_desc = if blocking.keys.is_empty() {
+    let blocking_desc = if blocking.keys.is_empty() && strategy_params(blocking).is_some() {
+        "none".to_string()
+    } else if blocking.keys.is_empty() {
         "No blocking keys — full pairwise comparison".to_string()
     } else {
         blocking
@@ -404,18 +1324,26 @@ fn build_execution_stages(
             .join(", ")
     };
 
-    vec![
+    let mut stages = vec![
         ExecutionStage {
             stage: 1,
             name: "Normalize sources".to_string(),
-            description: format!("Ingest and normalize fields from: {}", source_list),
+            description: format!(
+                "Ingest and normalize fields from: {}{}",
+                source_list, pipelines
+            ),
             inputs: source_names.to_vec(),
             outputs: vec!["normalized_entities".to_string()],
         },
         ExecutionStage {
             stage: 2,
             name: "Generate blocking keys".to_string(),
-            description: format!("Blocking strategy: {}. Keys: {}", blocking.strategy, blocking_desc),
+            description: format!(
+                "Blocking strategy: {}{}. Keys: {}",
+                blocking.strategy,
+                strategy_params(blocking).map_or(String::new(), |params| format!(" ({})", params)),
+                blocking_desc
+            ),
             inputs: vec!["normalized_entities".to_string()],
             outputs: vec!["candidate_pairs".to_string()],
         },
@@ -446,7 +1374,7 @@ fn build_execution_stages(
         ExecutionStage {
             stage: 6,
             name: "Cluster entities".to_string(),
-            description: "Transitive closure via UnionFind to group matched entities".to_string(),
+            description: clustering.description(),
             inputs: vec!["match_decisions".to_string()],
             outputs: vec!["entity_clusters".to_string()],
         },
@@ -468,15 +1396,266 @@ fn build_execution_stages(
                 "audit_trail".to_string(),
             ],
         },
-    ]
+    ];
+
+    // Semantic rules find their own candidates: every record is embedded
+    // into a vector index and its nearest neighbours are searched for, so
+    // the stage reads all entities rather than the blocked pairs.
+    if !semantic_rules.is_empty() {
+        let semantic_desc = semantic_rules
+            .iter()
+            .map(|r| {
+                format!(
+                    "{} on {} via {} (cosine >= {}, w={})",
+                    r.rule_name,
+                    r.field,
+                    r.model.as_deref().unwrap_or("unknown model"),
+                    r.threshold.map_or("?".to_string(), |t| t.to_string()),
+                    r.weight
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        stages.insert(
+            4,
+            ExecutionStage {
+                stage: 5,
+                name: "Semantic matches".to_string(),
+                description: format!(
+                    "Build vector index and run approximate nearest neighbour search: {}",
+                    semantic_desc
+                ),
+                inputs: vec!["normalized_entities".to_string()],
+                outputs: vec!["semantic_match_scores".to_string()],
+            },
+        );
+        let decide = &mut stages[5];
+        decide.inputs.push("semantic_match_scores".to_string());
+    }
+
+    // Validity intervals are resolved once, then bound which pairs are
+    // compared and which members survive.
+    if let Some(temporal) = temporal {
+        let bounds = [&temporal.effective_from, &temporal.effective_to]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" to ");
+        let history = temporal.history_attributes();
+        stages.insert(
+            1,
+            ExecutionStage {
+                stage: 2,
+                name: "Resolve validity intervals".to_string(),
+                description: format!(
+                    "Validity intervals from {}{}",
+                    bounds,
+                    if history.is_empty() {
+                        String::new()
+                    } else {
+                        format!("; history kept for {}", history.join(", "))
+                    }
+                ),
+                inputs: vec!["normalized_entities".to_string()],
+                outputs: vec!["validity_intervals".to_string()],
+            },
+        );
+        if let Some(days) = temporal.match_window_days {
+            stages.insert(
+                3,
+                ExecutionStage {
+                    stage: 4,
+                    name: "Apply match window".to_string(),
+                    description: format!(
+                        "Drop candidate pairs whose validity intervals are more than {} days apart",
+                        days
+                    ),
+                    inputs: vec![
+                        "candidate_pairs".to_string(),
+                        "validity_intervals".to_string(),
+                    ],
+                    outputs: vec!["candidate_pairs".to_string()],
+                },
+            );
+        }
+        if let Some(survivorship) = stages.iter_mut().find(|s| s.name == "Apply survivorship") {
+            survivorship.inputs.push("validity_intervals".to_string());
+            if temporal.effective_to.is_some() {
+                survivorship
+                    .description
+                    .push_str("; current records survive before expired ones");
+            }
+        }
+    }
+
+    // Incremental runs resolve only what changed: the changed records are
+    // matched against the records they share a key with, and only the
+    // clusters they touch are repaired and re-survived.
+    if execution.mode.is_incremental() {
+        let name = match execution.mode {
+            ExecutionMode::Streaming => "Consume changes",
+            _ => "Detect deltas",
+        };
+        stages.insert(
+            1,
+            ExecutionStage {
+                stage: 2,
+                name: name.to_string(),
+                description: execution.delta_description(),
+                inputs: vec!["normalized_entities".to_string()],
+                outputs: vec!["changed_records".to_string()],
+            },
+        );
+        for stage in stages.iter_mut() {
+            match stage.name.as_str() {
+                "Generate blocking keys" => {
+                    stage.inputs.insert(0, "changed_records".to_string());
+                    stage
+                        .description
+                        .push_str("; changed records are paired with the records sharing their keys");
+                }
+                "Cluster entities" => {
+                    stage.name = "Repair clusters".to_string();
+                    stage.description = format!(
+                        "Re-cluster the clusters changed records touch; the rest keep their members. {}",
+                        stage.description
+                    );
+                    stage.inputs.push("entity_clusters".to_string());
+                    stage.outputs.push("touched_clusters".to_string());
+                }
+                "Apply survivorship" => {
+                    stage.name = "Re-apply survivorship".to_string();
+                    stage.description = stage
+                        .description
+                        .replacen("to build golden records", "to the touched clusters' golden records", 1);
+                    stage.inputs.push("touched_clusters".to_string());
+                }
+                "Emit outputs" => {
+                    stage.description = format!(
+                        "Upsert the touched clusters into the canonical table, lineage table, and audit trail{}",
+                        if execution.stable_ids {
+                            ", keeping entity ids across runs"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Relationships link golden records to the target entity's canonical
+    // table (or to other golden records of this one), one edges table each.
+    if !relationships.is_empty() {
+        let emit = stages
+            .iter()
+            .position(|s| s.name == "Emit outputs")
+            .unwrap_or(stages.len());
+        for (i, relationship) in relationships.iter().enumerate() {
+            let mut inputs = vec!["golden_records".to_string()];
+            if relationship.to != entity {
+                inputs.push(format!("{}.canonical_entities", relationship.to));
+            }
+            stages.insert(
+                emit + i,
+                ExecutionStage {
+                    stage: emit + i + 1,
+                    name: format!("Resolve relationship {}", relationship.name),
+                    description: relationship.description(entity),
+                    inputs,
+                    outputs: vec![relationship.edges_table()],
+                },
+            );
+        }
+        let emit = &mut stages[emit + relationships.len()];
+        emit.inputs
+            .extend(relationships.iter().map(Relationship::edges_table));
+        emit.outputs.push("relationship_edges".to_string());
+        emit.description.push_str(", with relationship edges");
+    }
+
+    // Encoded attributes are encoded once normalized, so blocking and
+    // matching only ever see the encodings.
+    if let Some(encoding) = encoding.filter(|e| !e.fields.is_empty()) {
+        let at = stages
+            .iter()
+            .position(|s| s.outputs.iter().any(|o| o == "normalized_entities"))
+            .map_or(1, |i| i + 1);
+        let fields: Vec<String> = encoding
+            .fields
+            .iter()
+            .map(|(field, method)| format!("{} ({})", field, method))
+            .collect();
+        stages.insert(
+            at,
+            ExecutionStage {
+                stage: at + 1,
+                name: "Encode attributes".to_string(),
+                description: format!(
+                    "Encode with the shared salt: {}; values already encoded pass through",
+                    fields.join(", ")
+                ),
+                inputs: vec!["normalized_entities".to_string()],
+                outputs: vec!["normalized_entities".to_string()],
+            },
+        );
+    }
+
+    // Deleted records leave before matching, or take their entities with
+    // them once clustered; the tombstones reach the outputs either way, so
+    // downstream tables drop what was deleted.
+    if let Some(deletion) = deletion {
+        let (after, name, inputs, output, emitted) = match deletion.scope {
+            DeletionScope::Record => (
+                "normalized_entities",
+                "Apply tombstones",
+                vec!["normalized_entities"],
+                "normalized_entities",
+                "golden records left without members are deleted",
+            ),
+            DeletionScope::Entity => (
+                "entity_clusters",
+                "Erase entities",
+                vec!["entity_clusters", "normalized_entities"],
+                "entity_clusters",
+                "erased entities are deleted",
+            ),
+        };
+        let at = stages
+            .iter()
+            .position(|s| s.outputs.iter().any(|o| o == after))
+            .map_or(1, |i| i + 1);
+        stages.insert(
+            at,
+            ExecutionStage {
+                stage: at + 1,
+                name: name.to_string(),
+                description: deletion.description(),
+                inputs: inputs.into_iter().map(str::to_string).collect(),
+                outputs: vec![output.to_string(), "tombstones".to_string()],
+            },
+        );
+        if let Some(emit) = stages.iter_mut().find(|s| s.name == "Emit outputs") {
+            emit.inputs.push("tombstones".to_string());
+            emit.description.push_str(&format!("; {}", emitted));
+        }
+    }
+
+    for (i, stage) in stages.iter_mut().enumerate() {
+        stage.stage = i + 1;
+    }
+    stages
 }
 
 fn analyse_risks(
-    spec: &serde_json::Value,
+    ir: &Ir,
     match_strategies: &[MatchStrategySummary],
     blocking: &BlockingAnalysis,
     survivorship: &[SurvivorshipSummary],
     sources: &[PlanSource],
+    pii: &[PiiAttribute],
 ) -> Vec<RiskFlag> {
     let mut flags = Vec::new();
 
@@ -543,13 +1722,9 @@ fn analyse_risks(
     }
 
     // PHONE_WITHOUT_BLOCKING — high
-    let has_phone_rule = match_strategies
-        .iter()
-        .any(|m| m.field.contains("phone"));
-    let has_phone_blocking = blocking
-        .keys
-        .iter()
-        .any(|k| k.name.contains("phone"));
+    let is_phone = phone_fields(ir);
+    let has_phone_rule = match_strategies.iter().any(|m| is_phone(&m.field));
+    let has_phone_blocking = blocking.keys.iter().any(|k| is_phone(&k.name));
     if has_phone_rule && !has_phone_blocking {
         flags.push(RiskFlag {
             severity: "high".to_string(),
@@ -559,12 +1734,118 @@ fn analyse_risks(
         });
     }
 
+    // Typed attributes — the normalization and rules their type calls for
+    let types = ir.types();
+    let steps = |field: &str| {
+        ir.normalization
+            .as_ref()
+            .and_then(|n| n.fields.get(field))
+            .cloned()
+            .unwrap_or_default()
+    };
+    let used: BTreeSet<&str> = match_strategies
+        .iter()
+        .map(|m| m.field.as_str())
+        .chain(ir.blocking.keys.iter().map(|k| k.field.as_str()))
+        .collect();
+
+    // PHONE_NOT_NORMALIZED — medium
+    for field in used
+        .iter()
+        .filter(|f| types.get(**f) == Some(&AttributeType::Phone))
+    {
+        if !steps(field).iter().any(|s| s == "phone") {
+            flags.push(RiskFlag {
+                severity: "medium".to_string(),
+                code: "PHONE_NOT_NORMALIZED".to_string(),
+                message: format!(
+                    "Phone attribute '{}' is matched as written — the same number formatted differently never agrees",
+                    field
+                ),
+                recommendation: format!("Add the phone normalizer to normalization.fields.{}", field),
+            });
+        }
+    }
+
+    // EMAIL_CASE_SENSITIVE — medium
+    for field in types
+        .iter()
+        .filter(|(_, ty)| **ty == AttributeType::Email)
+        .map(|(field, _)| field.as_str())
+    {
+        let exact = match_strategies
+            .iter()
+            .any(|m| m.field == field && m.match_type == "exact");
+        let blocked = ir
+            .blocking
+            .keys
+            .iter()
+            .any(|k| k.field == field && k.transform.as_deref() != Some("lowercase"));
+        if (exact || blocked)
+            && !steps(field)
+                .iter()
+                .any(|s| s == "casefold" || s == "lower")
+        {
+            flags.push(RiskFlag {
+                severity: "medium".to_string(),
+                code: "EMAIL_CASE_SENSITIVE".to_string(),
+                message: format!(
+                    "Email attribute '{}' is compared case-sensitively — 'Ann@x.com' and 'ann@x.com' differ",
+                    field
+                ),
+                recommendation: format!("Add casefold to normalization.fields.{}", field),
+            });
+        }
+    }
+
+    // FUZZY_IDENTIFIER — medium
+    for strategy in match_strategies {
+        if strategy.match_type == "fuzzy"
+            && types.get(&strategy.field) == Some(&AttributeType::Identifier)
+        {
+            flags.push(RiskFlag {
+                severity: "medium".to_string(),
+                code: "FUZZY_IDENTIFIER".to_string(),
+                message: format!(
+                    "Fuzzy rule '{}' compares identifier '{}' — near-identical identifiers belong to different records",
+                    strategy.rule_name, strategy.field
+                ),
+                recommendation: "Match identifiers exactly".to_string(),
+            });
+        }
+    }
+
+    // PII_IN_BLOCKING_KEY — medium
+    for key in &ir.blocking.keys {
+        let Some(attribute) = pii.iter().find(|a| a.attribute == key.field) else {
+            continue;
+        };
+        let raw = key
+            .transform
+            .as_deref()
+            .is_none_or(|t| PRESERVING_TRANSFORMS.contains(&t));
+        let encoded = ir
+            .encoding
+            .as_ref()
+            .is_some_and(|e| e.field(&key.field).is_some());
+        if raw && attribute.masking != Some(Masking::Hash) && !encoded {
+            flags.push(RiskFlag {
+                severity: "medium".to_string(),
+                code: "PII_IN_BLOCKING_KEY".to_string(),
+                message: format!(
+                    "Blocking key '{}' holds raw PII ({}) — block tables and logs expose the values",
+                    key.field, attribute.kind
+                ),
+                recommendation: format!(
+                    "Set privacy.fields.{}.masking: hash to block on digests, or block on a coarser transform",
+                    key.field
+                ),
+            });
+        }
+    }
+
     // NO_REVIEW_THRESHOLD — medium
-    let has_review = spec
-        .get("decision")
-        .and_then(|d| d.get("thresholds"))
-        .and_then(|t| t.get("review"))
-        .is_some();
+    let has_review = ir.thresholds.as_ref().is_some_and(|t| t.review.is_some());
     if !has_review {
         flags.push(RiskFlag {
             severity: "medium".to_string(),
@@ -585,7 +1866,7 @@ fn analyse_risks(
     }
 
     // MISSING_TEMPORAL — low
-    let has_temporal = spec.get("temporal").is_some();
+    let has_temporal = ir.temporal.is_some();
     if !has_temporal {
         flags.push(RiskFlag {
             severity: "low".to_string(),
@@ -595,14 +1876,222 @@ fn analyse_risks(
         });
     }
 
+    // Incremental runs — the ids, deltas and clusters they repair
+    if let Some(execution) = ir.execution.as_ref().filter(|e| e.mode.is_incremental()) {
+        if !execution.stable_ids {
+            flags.push(RiskFlag {
+                severity: "high".to_string(),
+                code: "INCREMENTAL_WITHOUT_STABLE_IDS".to_string(),
+                message: format!(
+                    "{} runs renumber entities whose clusters merge or split: ids come from each cluster's lowest record key",
+                    execution.mode
+                ),
+                recommendation: "Set execution.stable_ids and keep entity ids in a registry across runs".to_string(),
+            });
+        }
+        if execution.mode == ExecutionMode::Incremental && execution.delta_column.is_none() {
+            flags.push(RiskFlag {
+                severity: "medium".to_string(),
+                code: "INCREMENTAL_WITHOUT_DELTA_COLUMN".to_string(),
+                message: "Incremental runs compare every source with the last snapshot to find changes".to_string(),
+                recommendation: "Set execution.delta_column to an attribute updated on every change".to_string(),
+            });
+        }
+        let strategy = ir.clustering.unwrap_or_default().strategy;
+        if strategy != ClusteringStrategy::TransitiveClosure {
+            flags.push(RiskFlag {
+                severity: "medium".to_string(),
+                code: "INCREMENTAL_CLUSTER_DRIFT".to_string(),
+                message: format!(
+                    "{} clustering of the touched clusters alone can differ from a full rebuild",
+                    strategy
+                ),
+                recommendation: "Schedule periodic full rebuilds, or use transitive_closure clustering".to_string(),
+            });
+        }
+    }
+
+    // SKEWED_BLOCKING_KEY — medium (sample only)
+    for key in &blocking.keys {
+        let Some(stats) = &key.sample else {
+            continue;
+        };
+        if stats.skew > 0.5 && stats.largest_block > 2 {
+            flags.push(RiskFlag {
+                severity: "medium".to_string(),
+                code: "SKEWED_BLOCKING_KEY".to_string(),
+                message: format!(
+                    "Blocking key '{}': one block of {} records produces {:.0}% of its pairs",
+                    key.name,
+                    stats.largest_block,
+                    stats.skew * 100.0
+                ),
+                recommendation: "Exclude placeholder values or combine the key with another field".to_string(),
+            });
+        }
+    }
+
+    // WEAK_BLOCKING — high (sample only)
+    if let Some(sample) = &blocking.sample {
+        let kept = 1.0 - sample.reduction;
+        let blocked = !blocking.keys.is_empty() || sample.strategy.is_some();
+        if sample.total_pairs > 0 && blocked && kept > WEAK_BLOCKING_RATIO {
+            flags.push(RiskFlag {
+                severity: "high".to_string(),
+                code: "WEAK_BLOCKING".to_string(),
+                message: format!(
+                    "Blocking keeps {:.1}% of all pairs in the sample ({} of {})",
+                    kept * 100.0,
+                    sample.candidate_pairs,
+                    sample.total_pairs
+                ),
+                recommendation: "Use more selective blocking keys or transforms".to_string(),
+            });
+        }
+    }
+
     flags
 }
 
+/// Whether an attribute holds phone numbers: those typed `phone` or
+/// normalized with the `phone` normalizer or, if the spec declares none,
+/// those named like one.
+fn phone_fields(ir: &Ir) -> impl Fn(&str) -> bool {
+    let declared: Vec<String> = ir
+        .normalization
+        .iter()
+        .flat_map(|n| &n.fields)
+        .filter(|(_, steps)| steps.iter().any(|s| s == "phone"))
+        .map(|(field, _)| field.clone())
+        .chain(
+            ir.types()
+                .into_iter()
+                .filter(|(_, ty)| *ty == AttributeType::Phone)
+                .map(|(field, _)| field),
+        )
+        .collect();
+    move |field| {
+        if declared.is_empty() {
+            field.contains("phone")
+        } else {
+            declared.iter().any(|d| d == field)
+        }
+    }
+}
+
+/// Overall risk of a spec from its (unwaived) flags; see `RISK_WEIGHTS`.
+pub fn risk_score(flags: &[RiskFlag]) -> u32 {
+    let total: u32 = flags
+        .iter()
+        .map(|flag| {
+            RISK_WEIGHTS
+                .iter()
+                .find(|(severity, _)| *severity == flag.severity)
+                .map(|(_, weight)| *weight)
+                .unwrap_or(0)
+        })
+        .sum();
+    total.min(100)
+}
+
+/// Set aside the risk flags a waiver covers.
+fn apply_waivers(flags: Vec<RiskFlag>, waivers: &Waivers) -> (Vec<RiskFlag>, Vec<Waived>) {
+    let mut kept = Vec::new();
+    let mut waived = Vec::new();
+    for flag in flags {
+        match waivers.find(&flag.code) {
+            Some(waiver) => waived.push(Waived {
+                code: flag.code,
+                message: flag.message,
+                waiver: waiver.clone(),
+            }),
+            None => kept.push(flag),
+        }
+    }
+    (kept, waived)
+}
+
+/// The weight of a flag severity in the risk score; see `RISK_WEIGHTS`.
+pub fn risk_weight(severity: &str) -> Result<u32> {
+    RISK_WEIGHTS
+        .iter()
+        .find(|(s, _)| *s == severity)
+        .map(|(_, weight)| *weight)
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown severity '{}' (expected critical, high, medium or low)",
+                severity
+            )
+        })
+}
+
+/// The spec section a flag concerns, or empty if none is known.
+fn flag_section(flag: &RiskFlag, custom_risks: &CustomRisks) -> String {
+    FLAG_SECTIONS
+        .iter()
+        .find(|(code, _)| *code == flag.code)
+        .map(|(_, section)| section.to_string())
+        .or_else(|| custom_risks.section_of(&flag.code))
+        .unwrap_or_default()
+}
+
+/// Risk flags as findings at the section each concerns, for CI formats:
+/// flags at the level the plan's policy gives them, otherwise errors when
+/// at least `fail_on` severe and warnings when not.
+fn flag_findings(
+    plan: &PlanResult,
+    custom_risks: &CustomRisks,
+    fail_on: Option<&str>,
+    map: Option<&SourceMap>,
+) -> Tiers {
+    let threshold = fail_on.and_then(|severity| risk_weight(severity).ok());
+    let findings = plan
+        .risk_flags
+        .iter()
+        .map(|flag| {
+            let failing = threshold
+                .is_some_and(|threshold| risk_weight(&flag.severity).unwrap_or(0) >= threshold);
+            let finding = match plan.policy.level(&flag.code) {
+                Some(Level::Error) => Diagnostic::error,
+                Some(Level::Info) => Diagnostic::info,
+                Some(_) => Diagnostic::warning,
+                None if failing => Diagnostic::error,
+                None => Diagnostic::warning,
+            };
+            let mut finding = finding(
+                &flag.code,
+                flag_section(flag, custom_risks),
+                format!("[{}] {}.", flag.severity, flag.message.trim_end_matches('.')),
+            )
+            .with_suggestion(format!("{}.", flag.recommendation.trim_end_matches('.')));
+            if let Some(map) = map {
+                finding.locate(map);
+            }
+            finding
+        })
+        .collect();
+    Tiers::split_waived(findings, plan.waived.clone(), Profile::Default)
+}
+
+/// Attribute each flag to the owners of the section it concerns.
+fn route_flags(flags: &[RiskFlag], owners: &Owners, custom_risks: &CustomRisks) -> Routing {
+    if owners.is_empty() {
+        return Routing::new();
+    }
+    owners.route(flags.iter().map(|flag| {
+        let section = flag_section(flag, custom_risks);
+        let finding = RoutedFinding {
+            kind: "risk".to_string(),
+            code: flag.code.clone(),
+            section: section.clone(),
+            message: flag.message.clone(),
+        };
+        (owners.for_section(&section), finding)
+    }))
+}
+
 fn compute_plan_hash(spec: &serde_json::Value) -> Result<String> {
-    let canonical = serde_json::to_string(spec)?;
-    let mut hasher = Sha256::new();
-    hasher.update(canonical.as_bytes());
-    Ok(format!("sha256:{:x}", hasher.finalize()))
+    Ok(canonical_hash(spec))
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -612,9 +2101,14 @@ fn build_summary(
     sources: &[PlanSource],
     match_strategies: &[MatchStrategySummary],
     blocking: &BlockingAnalysis,
-    spec: &serde_json::Value,
+    ir: &Ir,
     survivorship: &[SurvivorshipSummary],
+    stage_count: usize,
+    estimate: Option<&CostEstimate>,
+    pii: &[PiiAttribute],
     risk_flags: &[RiskFlag],
+    risk_score: u32,
+    waived_count: usize,
     plan_hash: &str,
 ) -> String {
     let source_names: Vec<&str> = sources.iter().map(|s| s.name.as_str()).collect();
@@ -623,7 +2117,7 @@ fn build_summary(
     let signals: Vec<String> = match_strategies
         .iter()
         .map(|m| {
-            if let Some(ref algo) = m.algorithm {
+            if let Some(algo) = m.algorithm.as_ref().or(m.model.as_ref()) {
                 format!("{} ({}/{}, w={})", m.field, m.match_type, algo, m.weight)
             } else {
                 format!("{} ({}, w={})", m.field, m.match_type, m.weight)
@@ -633,33 +2127,130 @@ fn build_summary(
     let signals_str = signals.join(", ");
 
     let blocking_keys: Vec<&str> = blocking.keys.iter().map(|k| k.name.as_str()).collect();
-    let blocking_str = if blocking_keys.is_empty() {
+    let mut blocking_str = if blocking_keys.is_empty() {
         "none".to_string()
     } else {
         blocking_keys.join(", ")
     };
+    if let Some(lsh) = &blocking.lsh {
+        blocking_str.push_str(&format!(
+            " (LSH {}x{} {}, threshold ~{})",
+            lsh.config.bands, lsh.config.rows, lsh.config.method, lsh.threshold
+        ));
+    }
+    if let Some(params) = strategy_params(blocking).filter(|_| blocking.lsh.is_none()) {
+        blocking_str.push_str(&format!(" ({} {})", blocking.strategy, params));
+    }
+    if let Some(sample) = &blocking.sample {
+        blocking_str.push_str(&format!(
+            " (sample: {} of {} pairs kept, {:.1}% reduction)",
+            sample.candidate_pairs,
+            sample.total_pairs,
+            sample.reduction * 100.0
+        ));
+    }
 
-    let match_threshold = spec
-        .get("decision")
-        .and_then(|d| d.get("thresholds"))
-        .and_then(|t| t.get("match"))
-        .and_then(|m| m.as_f64());
-    let review_threshold = spec
-        .get("decision")
-        .and_then(|d| d.get("thresholds"))
-        .and_then(|t| t.get("review"))
-        .and_then(|r| r.as_f64());
+    let match_threshold = ir.thresholds.as_ref().and_then(|t| t.match_);
+    let review_threshold = ir.thresholds.as_ref().and_then(|t| t.review);
 
     let thresholds_str = match (match_threshold, review_threshold) {
         (Some(m), Some(r)) => format!("merge >= {}, review >= {}", m, r),
         (Some(m), None) => format!("merge >= {}", m),
         _ => "not configured".to_string(),
     };
+    let clustering_str = match &ir.clustering {
+        Some(Clustering { strategy, threshold: Some(t) }) => {
+            format!("\n  Clustering:   {} (cut at {})", strategy, t)
+        }
+        Some(clustering) => format!("\n  Clustering:   {}", clustering.strategy),
+        None => String::new(),
+    };
+    let relationships_str = if ir.relationships.is_empty() {
+        String::new()
+    } else {
+        let links: Vec<String> = ir
+            .relationships
+            .iter()
+            .map(|r| format!("{} -> {} ({})", r.name, r.to, r.cardinality))
+            .collect();
+        format!("\n  Links:        {}", links.join(", "))
+    };
+    let execution_str = match &ir.execution {
+        Some(execution) => {
+            let mut details = Vec::new();
+            if let Some(column) = &execution.delta_column {
+                details.push(format!("deltas by {}", column));
+            }
+            if execution.stable_ids {
+                details.push("stable ids".to_string());
+            }
+            if details.is_empty() {
+                format!("\n  Execution:    {}", execution.mode)
+            } else {
+                format!("\n  Execution:    {} ({})", execution.mode, details.join(", "))
+            }
+        }
+        None => String::new(),
+    };
+
+    let estimate_str = match estimate {
+        Some(estimate) => format!(
+            "\n  Estimate:     ~{} candidate pairs of {}, ~{}, peak {}",
+            estimate::count(estimate.candidate_pairs as f64),
+            estimate::count(estimate.total_pairs as f64),
+            estimate::duration(estimate.seconds),
+            estimate::bytes(estimate.peak_memory_bytes)
+        ),
+        None => String::new(),
+    };
+
+    let pii_str = if pii.is_empty() {
+        String::new()
+    } else {
+        let attributes: Vec<String> = pii
+            .iter()
+            .map(|a| match a.masking {
+                Some(masking) => format!("{} ({}, {})", a.attribute, a.kind, masking),
+                None => format!("{} ({})", a.attribute, a.kind),
+            })
+            .collect();
+        format!("\n  PII:          {}", attributes.join(", "))
+    };
+
+    let encoding_str = match ir.encoding.as_ref().filter(|e| !e.fields.is_empty()) {
+        Some(encoding) => {
+            let fields: Vec<String> = encoding
+                .fields
+                .iter()
+                .map(|(field, method)| format!("{} ({})", field, method))
+                .collect();
+            format!("\n  Encoding:     {}", fields.join(", "))
+        }
+        None => String::new(),
+    };
+
+    let deletion_str = match &ir.deletion {
+        Some(deletion) => format!(
+            "\n  Deletion:     {} ({} scope{})",
+            deletion.tombstone,
+            deletion.scope,
+            deletion
+                .purge_after_days
+                .map_or(String::new(), |days| format!(", purged after {} days", days))
+        ),
+        None => String::new(),
+    };
 
     let critical_count = risk_flags.iter().filter(|f| f.severity == "critical").count();
     let high_count = risk_flags.iter().filter(|f| f.severity == "high").count();
     let medium_count = risk_flags.iter().filter(|f| f.severity == "medium").count();
 
+    let waived_str = if waived_count > 0 {
+        format!(", {} waived", waived_count)
+    } else {
+        String::new()
+    };
+
     let short_hash = if plan_hash.len() > 15 {
         &plan_hash[..15]
     } else {
@@ -667,7 +2258,7 @@ fn build_summary(
     };
 
     format!(
-        "  Identity:     {} ({})\n  Sources:      {} ({})\n  Signals:      {}\n  Blocking:     {}\n  Thresholds:   {}\n  Stages:       8 execution stages\n  Survivorship: {} fields configured\n  Risk flags:   {} critical, {} high, {} medium\n  Plan hash:    {}...",
+        "  Identity:     {} ({})\n  Sources:      {} ({})\n  Signals:      {}\n  Blocking:     {}\n  Thresholds:   {}{}{}{}\n  Stages:       {} execution stages{}\n  Survivorship: {} fields configured{}{}{}\n  Risk flags:   {} critical, {} high, {} medium{}\n  Risk score:   {}/100\n  Plan hash:    {}...",
         entity,
         identity_version,
         sources.len(),
@@ -675,10 +2266,132 @@ fn build_summary(
         signals_str,
         blocking_str,
         thresholds_str,
+        clustering_str,
+        execution_str,
+        relationships_str,
+        stage_count,
+        estimate_str,
         survivorship.len(),
+        pii_str,
+        encoding_str,
+        deletion_str,
         critical_count,
         high_count,
         medium_count,
+        waived_str,
+        risk_score,
         short_hash,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+    use crate::{Ir, PlanResult};
+
+    #[test]
+    fn test_risk_score_weights_flags_by_severity() {
+        let flag = |severity: &str| crate::RiskFlag {
+            severity: severity.to_string(),
+            code: "TEST".to_string(),
+            message: String::new(),
+            recommendation: String::new(),
+        };
+        assert_eq!(crate::risk_score(&[]), 0);
+        assert_eq!(
+            crate::risk_score(&[flag("critical"), flag("high"), flag("medium"), flag("low")]),
+            40
+        );
+        let critical: Vec<_> = (0..5).map(|_| flag("critical")).collect();
+        assert_eq!(crate::risk_score(&critical), 100);
+
+        // Waived flags do not count.
+        let plan = crate::generate_plan(MINIMAL).unwrap();
+        let waived = crate::generate_plan(
+            &(MINIMAL.to_string() + "waivers:\n  - code: NO_BLOCKING\n    reason: tiny table\n"),
+        )
+        .unwrap();
+        assert_eq!(plan.risk_score, crate::risk_score(&plan.risk_flags));
+        assert_eq!(waived.risk_score, plan.risk_score - 25);
+        assert!(plan
+            .summary
+            .contains(&format!("Risk score:   {}/100", plan.risk_score)));
+    }
+
+    #[test]
+    fn test_plan_measures_blocking_on_a_sample() {
+        use crate::{generate_plan_with, PlanOptions, Sample};
+
+        let spec = include_str!("../../conformance/multi_source/spec.yaml");
+        // `family_name` is read through crm's `last_name` mapping; the soundex
+        // transform puts Smith and Smyth in one block.
+        let mut csv = String::from("email,family_name\n");
+        for i in 0..20 {
+            let name = if i < 12 {
+                ["Smith", "Smyth"][i % 2]
+            } else {
+                "Jones"
+            };
+            csv.push_str(&format!("user{}@example.com,{}\n", i, name));
+        }
+        let options = PlanOptions {
+            sample: Some(Sample::from_csv(&csv).unwrap()),
+            ..PlanOptions::default()
+        };
+        let plan = generate_plan_with(spec, &options).unwrap();
+        let blocking = &plan.blocking_analysis;
+
+        let last_name = blocking.keys[1].sample.as_ref().unwrap();
+        assert_eq!((last_name.cardinality, last_name.largest_block), (2, 12));
+        assert_eq!(last_name.pairs, 66 + 28);
+        assert!((last_name.skew - 66.0 / 94.0).abs() < 1e-9);
+        assert_eq!(blocking.keys[0].sample.as_ref().unwrap().pairs, 0);
+        assert!(blocking.keys[2].sample.is_none());
+
+        let sample = blocking.sample.as_ref().unwrap();
+        assert_eq!((sample.total_pairs, sample.candidate_pairs), (190, 94));
+        assert_eq!(blocking.estimated_reduction, "50.5%");
+        let codes: Vec<&str> = plan.risk_flags.iter().map(|f| f.code.as_str()).collect();
+        assert!(codes.contains(&"SKEWED_BLOCKING_KEY") && codes.contains(&"WEAK_BLOCKING"));
+
+        let unsampled = crate::generate_plan(spec).unwrap();
+        assert_eq!(unsampled.blocking_analysis.estimated_reduction, "medium");
+        assert!(unsampled.blocking_analysis.sample.is_none());
+    }
+
+    #[test]
+    fn test_plan_and_validate_from_compiled_ir() {
+        let yaml = include_str!("../../conformance/multi_source/spec.yaml");
+        let compiled = crate::compile_to_ir(&crate::parse_yaml(yaml).unwrap()).unwrap();
+        assert!(crate::ir::plan_hash_matches(&compiled));
+        let ir = Ir::from_value(&compiled).unwrap();
+
+        // The IR laid out as a spec compiles back to the same IR.
+        let spec = ir.to_spec();
+        assert_eq!(
+            crate::compile_to_ir(&spec).unwrap()["plan_hash"],
+            compiled["plan_hash"]
+        );
+        assert!(crate::schema_diagnostics(&spec).is_empty());
+
+        let from_yaml = crate::generate_plan(yaml).unwrap();
+        let from_ir = crate::generate_plan_from_ir(&ir, &crate::PlanOptions::default()).unwrap();
+        assert_eq!(from_ir.plan_hash, ir.plan_hash);
+        assert_eq!(
+            serde_json::to_value(&from_ir.execution_stages).unwrap(),
+            serde_json::to_value(&from_yaml.execution_stages).unwrap()
+        );
+        let codes = |plan: &PlanResult| {
+            plan.risk_flags
+                .iter()
+                .map(|f| f.code.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(codes(&from_ir), codes(&from_yaml));
+        assert_eq!(from_ir.risk_score, from_yaml.risk_score);
+
+        let mut edited = compiled.clone();
+        edited["rules"][0]["weight"] = serde_json::json!(0.5);
+        assert!(!crate::ir::plan_hash_matches(&edited));
+    }
+}
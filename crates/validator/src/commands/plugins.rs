@@ -0,0 +1,33 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::output::Output;
+use crate::plugins::PluginRegistry;
+
+/// `kanoniv --list-plugins`: the plugins found and what each provides.
+pub fn list(plugins: &PluginRegistry, out: &Output) -> Result<()> {
+    if plugins.is_empty() {
+        out.info("No plugins found (kanoniv-plugin-* executables in KANONIV_PLUGIN_PATH or PATH)");
+        return Ok(());
+    }
+    for plugin in plugins.plugins() {
+        let info = plugin.info();
+        let mut provides = info.capabilities.clone();
+        if !info.targets.is_empty() {
+            provides.push(format!("targets: {}", info.targets.join(", ")));
+        }
+        out.result(format!(
+            "{} {}  {}",
+            info.name.bold(),
+            info.version,
+            provides.join("; ")
+        ));
+        if !info.description.is_empty() {
+            out.result(format!("  {}", info.description));
+        }
+        if let Some(path) = plugin.path() {
+            out.detail(format!("  {}", path.display()));
+        }
+    }
+    Ok(())
+}
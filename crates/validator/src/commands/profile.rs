@@ -0,0 +1,86 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::path::Path;
+
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::profile::profile_source;
+use crate::sample::Sample;
+
+/// Profile the records in `data` as the spec's `source`.
+pub fn run(
+    spec: &Path,
+    data: &Path,
+    source: Option<&str>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(spec, entity, variables)?;
+    let profile = profile_source(&content, &Sample::load(data)?, source)?;
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&profile)?);
+        return Ok(());
+    }
+
+    out.info(format!(
+        "{} {} ({} records)",
+        "Profile:".bold(),
+        profile.source,
+        profile.records
+    ));
+    out.result(format!(
+        "  {:<20}  {:<20}  {:>6}  {:>9}  {:<12}  RULES",
+        "ATTRIBUTE", "COLUMN", "NULLS", "DISTINCT", "FORMAT"
+    ));
+    for attribute in &profile.attributes {
+        let (nulls, distinct) = if attribute.present {
+            (
+                format!("{:.1}%", attribute.null_rate * 100.0),
+                attribute.distinct.to_string(),
+            )
+        } else {
+            ("-".to_string(), "(missing)".to_string())
+        };
+        let format = match (&attribute.format, attribute.conformance) {
+            (Some(format), Some(conformance)) => format!("{} {:.0}%", format, conformance * 100.0),
+            (Some(format), None) => format.clone(),
+            _ => String::new(),
+        };
+        out.result(format!(
+            "  {:<20}  {:<20}  {:>6}  {:>9}  {:<12}  {}",
+            attribute.attribute,
+            attribute.column,
+            nulls,
+            distinct,
+            format,
+            attribute.rules.join(", ")
+        ));
+    }
+
+    if !profile.findings.is_empty() && !out.is_quiet() {
+        println!();
+        println!("{}:", "Findings".bold());
+        for finding in &profile.findings {
+            println!(
+                "{}",
+                out.wrap_block(&format!(
+                    "  [{}] {} {} {}",
+                    finding.severity,
+                    finding.code,
+                    out.dash(),
+                    finding.message
+                ))
+            );
+            println!(
+                "{}",
+                out.wrap_block(&format!("         {}", finding.recommendation))
+                    .dimmed()
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::registry::Registry;
+
+/// Publish a spec file, optionally tagging the version.
+pub fn publish(
+    file: &Path,
+    registry: &str,
+    tags: &[String],
+    variables: &Variables,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_file(file, variables)?;
+    let registry = Registry::open(registry)?;
+    let published = registry.publish(&content, tags)?;
+
+    let verb = if published.created {
+        "Published"
+    } else {
+        "Already published"
+    };
+    out.info(format!(
+        "{} {} {} {} to {}",
+        out.ok_mark(),
+        verb,
+        published.entity,
+        published.version.identity_version,
+        registry.location()
+    ));
+    if !tags.is_empty() {
+        out.info(format!("  {} tagged {}", out.arrow(), tags.join(", ")));
+    }
+    out.result(&published.version.hash);
+    Ok(())
+}
+
+/// Write a published version to `output`, or stdout.
+pub fn pull(
+    entity: &str,
+    version: Option<&str>,
+    registry: &str,
+    output: Option<&Path>,
+    out: &Output,
+) -> Result<()> {
+    let registry = Registry::open(registry)?;
+    let (version, yaml) = registry.pull(entity, version)?;
+    match output {
+        Some(path) => {
+            fs::write(path, &yaml)
+                .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            out.info(format!(
+                "{} Pulled {} {} ({}) to {}",
+                out.ok_mark(),
+                entity,
+                version.hash,
+                version.identity_version,
+                path.display()
+            ));
+        }
+        None => print!("{}", yaml),
+    }
+    Ok(())
+}
+
+/// List the published versions of an entity, newest first.
+pub fn versions(entity: &str, registry: &str, format: &str, out: &Output) -> Result<()> {
+    let registry = Registry::open(registry)?;
+    let mut versions = registry.versions(entity)?;
+    versions.reverse();
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&versions)?);
+        return Ok(());
+    }
+    if versions.is_empty() {
+        out.info(format!(
+            "No versions of '{}' in {}",
+            entity,
+            registry.location()
+        ));
+        return Ok(());
+    }
+    out.result(format!(
+        "{:<14}  {:<20}  {:<20}  TAGS",
+        "HASH", "IDENTITY_VERSION", "PUBLISHED"
+    ));
+    for version in &versions {
+        let hex = version.hash.trim_start_matches("sha256:");
+        out.result(format!(
+            "{:<14}  {:<20}  {:<20}  {}",
+            &hex[..hex.len().min(12)],
+            version.identity_version,
+            version.published_at,
+            version.tags.join(", ")
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,488 @@
+//! `kanoniv report`: a self-contained HTML page of a spec's validation,
+//! plan and changes, for reviewers who do not use the CLI.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::clock;
+use crate::commands::diff::{compute_diff, DiffResult, Impact};
+use crate::commands::plan::{generate_plan_with, PlanOptions, PlanResult};
+use crate::commands::risk_trend;
+use crate::compose;
+use crate::diagnostics::{Diagnostic, Profile, Tiers};
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::waivers::Waived;
+
+/// Everything a report shows.
+#[derive(Debug)]
+pub struct Report {
+    /// The spec's path, for the heading.
+    pub spec: String,
+    pub validation: Tiers,
+    /// The plan, or why the spec could not be planned.
+    pub plan: Result<PlanResult, String>,
+    /// The version compared with (a path or a commit) and what changed
+    /// since; `None` without one.
+    pub changes: Option<(String, DiffResult)>,
+}
+
+pub fn run(
+    file: &Path,
+    output: &Path,
+    previous: Option<&Path>,
+    options: &PlanOptions,
+    entity: Option<&str>,
+    variables: &Variables,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let previous = match previous {
+        Some(path) => Some((
+            path.display().to_string(),
+            compose::read_spec(path, entity, variables)?,
+        )),
+        None => risk_trend::previous_version(file, &fs::read_to_string(file)?)
+            .map(|(commit, old)| (format!("commit {}", &commit[..commit.len().min(8)]), old)),
+    };
+    let report = Report::new(&file.display().to_string(), &content, previous, options)?;
+    fs::write(output, report.to_html())
+        .with_context(|| format!("Failed to write report: {}", output.display()))?;
+    out.info(format!(
+        "{} Wrote report: {}",
+        out.ok_mark(),
+        output.display()
+    ));
+    Ok(())
+}
+
+impl Report {
+    /// Validate and plan the spec `content` (named `spec`), and diff it
+    /// against `previous`, a label and the earlier version's text.
+    pub fn new(
+        spec: &str,
+        content: &str,
+        previous: Option<(String, String)>,
+        options: &PlanOptions,
+    ) -> Result<Self> {
+        let changes = match previous {
+            Some((label, old)) => Some((
+                label.clone(),
+                compute_diff(&old, content)
+                    .with_context(|| format!("Failed to compare with {}", label))?,
+            )),
+            None => None,
+        };
+        Ok(Report {
+            spec: spec.to_string(),
+            validation: crate::validate_tiers_with(content, Profile::Default, &options.policy),
+            plan: generate_plan_with(content, options).map_err(|e| format!("{:#}", e)),
+            changes,
+        })
+    }
+
+    pub fn to_html(&self) -> String {
+        let title = match &self.plan {
+            Ok(plan) => format!("{} ({})", plan.entity, plan.identity_version),
+            Err(_) => self.spec.clone(),
+        };
+        let mut html = String::new();
+        html.push_str(&format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Kanoniv report: {}</title>\n<style>{}</style>\n</head>\n<body>\n",
+            escape(&title),
+            STYLE
+        ));
+        html.push_str(&format!("<h1>{}</h1>\n", escape(&title)));
+        let mut meta = vec![escape(&self.spec)];
+        if let Ok(plan) = &self.plan {
+            meta.push(format!(
+                "plan hash <code>{}</code>",
+                escape(&plan.plan_hash)
+            ));
+        }
+        meta.push(format!("generated {}", clock::now()));
+        html.push_str(&format!(
+            "<p class=\"meta\">{}</p>\n",
+            meta.join(" &middot; ")
+        ));
+
+        self.overview(&mut html);
+        self.validation_section(&mut html);
+        match &self.plan {
+            Ok(plan) => plan_sections(plan, &mut html),
+            Err(error) => html.push_str(&format!(
+                "<h2>Plan</h2>\n<p class=\"error\">The spec could not be planned: {}</p>\n",
+                escape(error)
+            )),
+        }
+        self.changes_section(&mut html);
+
+        html.push_str(&format!("<script>{}</script>\n</body>\n</html>\n", SCRIPT));
+        html
+    }
+
+    fn overview(&self, html: &mut String) {
+        let errors = self.validation.errors.len();
+        let validation = match errors {
+            0 => ("ok", "Valid".to_string()),
+            n => ("bad", format!("{} error(s)", n)),
+        };
+        let (score, flags) = match &self.plan {
+            Ok(plan) => (
+                (
+                    risk_class(plan.risk_score),
+                    format!("{} / 100", plan.risk_score),
+                ),
+                ("", plan.risk_flags.len().to_string()),
+            ),
+            Err(_) => (("bad", "-".to_string()), ("", "-".to_string())),
+        };
+        let changes = match &self.changes {
+            Some((_, diff)) => match diff.compatibility.impact {
+                Some(impact) => (
+                    impact_class(impact),
+                    format!("{} ({})", impact, diff.compatibility.bump),
+                ),
+                None => ("ok", "None".to_string()),
+            },
+            None => ("", "No previous version".to_string()),
+        };
+        html.push_str("<div class=\"cards\">\n");
+        for (label, (class, value)) in [
+            ("Validation", validation),
+            ("Risk score", score),
+            ("Risk flags", flags),
+            ("Changes", changes),
+        ] {
+            html.push_str(&format!(
+                "<div class=\"{}\"><div class=\"label\">{}</div><div class=\"value\">{}</div></div>\n",
+                format!("card {}", class).trim_end(),
+                label,
+                escape(&value)
+            ));
+        }
+        html.push_str("</div>\n");
+    }
+
+    fn validation_section(&self, html: &mut String) {
+        html.push_str("<h2>Validation</h2>\n");
+        let tiers = [
+            ("error", &self.validation.errors),
+            ("warning", &self.validation.warnings),
+            ("info", &self.validation.info),
+        ];
+        let findings: Vec<(&str, &Diagnostic)> = tiers
+            .into_iter()
+            .flat_map(|(severity, tier)| tier.iter().map(move |d| (severity, d)))
+            .collect();
+        if findings.is_empty() {
+            html.push_str("<p>No findings.</p>\n");
+        } else {
+            let rows = findings.into_iter().map(|(severity, d)| {
+                let mut message = escape(&d.message);
+                if let Some(suggestion) = &d.suggestion {
+                    message.push_str(&format!(
+                        "<br><span class=\"hint\">{}</span>",
+                        escape(suggestion)
+                    ));
+                }
+                vec![
+                    severity_badge(severity),
+                    format!("<code>{}</code>", escape(&d.code)),
+                    d.span.map_or(String::new(), |span| span.line.to_string()),
+                    escape(d.path.as_deref().unwrap_or("")),
+                    message,
+                ]
+            });
+            table(html, &["Severity", "Code", "Line", "Path", "Message"], rows);
+        }
+        if !self.validation.waived.is_empty() {
+            html.push_str("<h3>Waived</h3>\n");
+            waived_table(html, &self.validation.waived);
+        }
+    }
+
+    fn changes_section(&self, html: &mut String) {
+        html.push_str("<h2>Changes</h2>\n");
+        let Some((label, diff)) = &self.changes else {
+            html.push_str(
+                "<p>No previous version to compare with: commit the spec to git, or pass \
+                 <code>--previous</code>.</p>\n",
+            );
+            return;
+        };
+        html.push_str(&format!("<p>Compared with {}.</p>\n", escape(label)));
+        let compatibility = &diff.compatibility;
+        let Some(impact) = compatibility.impact else {
+            html.push_str("<p>No changes.</p>\n");
+            return;
+        };
+        let suggested = match &compatibility.suggested_version {
+            Some(version) => format!(", suggested version <code>{}</code>", escape(version)),
+            None => String::new(),
+        };
+        html.push_str(&format!(
+            "<p>Overall: <span class=\"badge {}\">{}</span>, recommended bump: {}{}</p>\n",
+            impact_class(impact),
+            impact,
+            compatibility.bump,
+            suggested
+        ));
+        let rows = compatibility.changes.iter().map(|change| {
+            vec![
+                format!(
+                    "<span class=\"badge {}\">{}</span>",
+                    impact_class(change.impact),
+                    change.impact
+                ),
+                format!("<code>{}</code>", escape(&change.path)),
+                escape(&change.description),
+            ]
+        });
+        table(html, &["Impact", "Path", "Change"], rows);
+    }
+}
+
+fn plan_sections(plan: &PlanResult, html: &mut String) {
+    html.push_str(&format!(
+        "<h2>Plan</h2>\n<pre>{}</pre>\n",
+        escape(&plan.summary)
+    ));
+
+    html.push_str("<h2>Execution Stages</h2>\n");
+    let rows = plan.execution_stages.iter().map(|stage| {
+        vec![
+            stage.stage.to_string(),
+            escape(&stage.name),
+            escape(&stage.description),
+            escape(&stage.inputs.join(", ")),
+            escape(&stage.outputs.join(", ")),
+        ]
+    });
+    table(
+        html,
+        &["#", "Stage", "Description", "Inputs", "Outputs"],
+        rows,
+    );
+
+    html.push_str("<h2>Risk Flags</h2>\n");
+    if plan.risk_flags.is_empty() {
+        html.push_str("<p>No risk flags.</p>\n");
+    } else {
+        let rows = plan.risk_flags.iter().map(|flag| {
+            vec![
+                severity_badge(&flag.severity),
+                format!("<code>{}</code>", escape(&flag.code)),
+                escape(&flag.message),
+                escape(&flag.recommendation),
+            ]
+        });
+        table(
+            html,
+            &["Severity", "Code", "Message", "Recommendation"],
+            rows,
+        );
+    }
+    if !plan.waived.is_empty() {
+        html.push_str("<h3>Waived</h3>\n");
+        waived_table(html, &plan.waived);
+    }
+
+    let blocking = &plan.blocking_analysis;
+    html.push_str("<h2>Blocking Analysis</h2>\n");
+    html.push_str(&format!(
+        "<p>Strategy: <strong>{}</strong> &middot; estimated reduction: <strong>{}</strong></p>\n",
+        escape(&blocking.strategy),
+        escape(&blocking.estimated_reduction)
+    ));
+    if !blocking.keys.is_empty() {
+        let rows = blocking.keys.iter().map(|key| {
+            let mut row = vec![escape(&key.name), escape(&key.transformation)];
+            match &key.sample {
+                Some(stats) => row.extend([
+                    stats.cardinality.to_string(),
+                    stats.largest_block.to_string(),
+                    stats.missing.to_string(),
+                    stats.pairs.to_string(),
+                ]),
+                None => row.extend(["-", "-", "-", "-"].map(String::from)),
+            }
+            row
+        });
+        table(
+            html,
+            &[
+                "Key",
+                "Transformation",
+                "Blocks",
+                "Largest",
+                "Missing",
+                "Pairs",
+            ],
+            rows,
+        );
+    }
+    if let Some(sample) = &blocking.sample {
+        html.push_str(&format!(
+            "<p>Measured on {} sample records: {} of {} candidate pairs ({:.1}% reduction).</p>\n",
+            sample.records,
+            sample.candidate_pairs,
+            sample.total_pairs,
+            sample.reduction * 100.0
+        ));
+    }
+    if let Some(lsh) = &blocking.lsh {
+        html.push_str(&format!(
+            "<p>LSH pairs start to collide at similarity ~{}.</p>\n",
+            lsh.threshold
+        ));
+    }
+    if !blocking.warnings.is_empty() {
+        html.push_str("<ul class=\"warnings\">\n");
+        for warning in &blocking.warnings {
+            html.push_str(&format!("<li>{}</li>\n", escape(warning)));
+        }
+        html.push_str("</ul>\n");
+    }
+}
+
+/// A table whose columns sort when their heading is clicked. Cells are
+/// HTML.
+fn table(html: &mut String, headings: &[&str], rows: impl Iterator<Item = Vec<String>>) {
+    html.push_str("<table class=\"sortable\">\n<thead><tr>");
+    for heading in headings {
+        html.push_str(&format!("<th>{}</th>", heading));
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for cell in row {
+            html.push_str(&format!("<td>{}</td>", cell));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+}
+
+fn waived_table(html: &mut String, waived: &[Waived]) {
+    let rows = waived.iter().map(|w| {
+        vec![
+            format!("<code>{}</code>", escape(&w.code)),
+            escape(&w.message),
+            escape(&w.waiver.reason),
+            escape(w.waiver.expires.as_deref().unwrap_or("")),
+        ]
+    });
+    table(html, &["Code", "Message", "Reason", "Expires"], rows);
+}
+
+fn severity_badge(severity: &str) -> String {
+    format!("<span class=\"badge {0}\">{0}</span>", escape(severity))
+}
+
+fn risk_class(score: u32) -> &'static str {
+    match score {
+        0..=20 => "ok",
+        21..=50 => "warn",
+        _ => "bad",
+    }
+}
+
+fn impact_class(impact: Impact) -> &'static str {
+    match impact {
+        Impact::Safe => "ok",
+        Impact::Risky => "warn",
+        Impact::Breaking => "bad",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str = "
+body { font: 14px/1.5 -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; color: #1f2328; max-width: 1100px; margin: 2em auto; padding: 0 1em; }
+h1 { margin-bottom: 0; }
+h2 { border-bottom: 1px solid #d0d7de; padding-bottom: .3em; margin-top: 2em; }
+.meta, .hint { color: #656d76; }
+pre { background: #f6f8fa; padding: 1em; overflow-x: auto; }
+code { font-size: 90%; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border: 1px solid #d0d7de; padding: .4em .6em; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; cursor: pointer; user-select: none; }
+th[data-order=asc]::after { content: ' \\25B2'; }
+th[data-order=desc]::after { content: ' \\25BC'; }
+.cards { display: flex; gap: 1em; margin: 1.5em 0; }
+.card { flex: 1; border: 1px solid #d0d7de; border-radius: 6px; padding: .8em 1em; }
+.card .label { color: #656d76; font-size: 90%; }
+.card .value { font-size: 150%; font-weight: 600; }
+.card.ok { border-top: 4px solid #1a7f37; }
+.card.warn { border-top: 4px solid #9a6700; }
+.card.bad { border-top: 4px solid #cf222e; }
+.badge { border-radius: 1em; padding: 0 .6em; font-size: 90%; white-space: nowrap; background: #eaeef2; }
+.badge.critical, .badge.error, .badge.bad { background: #ffebe9; color: #cf222e; font-weight: 600; }
+.badge.high, .badge.warning, .badge.warn { background: #fff8c5; color: #9a6700; }
+.badge.ok { background: #dafbe1; color: #1a7f37; }
+.error { color: #cf222e; }
+";
+
+/// Click a heading to sort by its column; severities sort by rank.
+const SCRIPT: &str = "
+var rank = { critical: 0, error: 0, high: 1, warning: 1, medium: 2, info: 2, low: 3 };
+document.querySelectorAll('table.sortable th').forEach(function (th, column) {
+  th.addEventListener('click', function () {
+    var body = th.closest('table').tBodies[0];
+    var ascending = th.dataset.order !== 'asc';
+    th.closest('tr').querySelectorAll('th').forEach(function (other) { delete other.dataset.order; });
+    th.dataset.order = ascending ? 'asc' : 'desc';
+    var key = function (row) {
+      var text = row.cells[column].textContent.trim();
+      return text in rank ? rank[text] : text;
+    };
+    Array.from(body.rows).sort(function (a, b) {
+      var x = key(a), y = key(b);
+      var order = isNaN(x) || isNaN(y) || x === '' || y === '' ? String(x).localeCompare(String(y)) : x - y;
+      return ascending ? order : -order;
+    }).forEach(function (row) { body.appendChild(row); });
+  });
+});
+";
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_report_combines_validation_plan_and_changes() {
+        use crate::{Impact, PlanOptions, Report};
+
+        let loose = MINIMAL.replace("match: 0.9", "match: 0.7");
+        let previous = Some(("<v1>".to_string(), MINIMAL.to_string()));
+        let report =
+            Report::new("specs/a&b.yaml", &loose, previous, &PlanOptions::default()).unwrap();
+        assert!(report.validation.is_valid());
+        assert_eq!(report.plan.as_ref().unwrap().risk_score, 45);
+        let (label, diff) = report.changes.as_ref().unwrap();
+        assert_eq!(
+            (label.as_str(), diff.compatibility.impact),
+            ("<v1>", Some(Impact::Risky))
+        );
+
+        let html = report.to_html();
+        assert!(html.contains("<p class=\"meta\">specs/a&amp;b.yaml &middot; plan hash <code>"));
+        assert!(html.contains("<p>Compared with &lt;v1&gt;.</p>"));
+
+        // A spec that cannot be planned still reports its findings.
+        let broken =
+            Report::new("broken.yaml", "entity: [", None, &PlanOptions::default()).unwrap();
+        assert!(!broken.validation.is_valid());
+        let html = broken.to_html();
+        assert!(html.contains("<p class=\"error\">The spec could not be planned: "));
+        assert!(html.contains("<title>Kanoniv report: broken.yaml</title>"));
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::commands::explain_pair::print_explanation;
+use crate::compose;
+use crate::explanation::explain_pair;
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::review::{status_counts, training_labels, LogEntry, ReviewQueue, ReviewStatus};
+
+/// List the queued pairs, optionally only those with `status`.
+pub fn list(queue: &str, status: Option<&str>, format: &str, out: &Output) -> Result<()> {
+    let queue = ReviewQueue::open(queue)?;
+    let status = status.map(str::parse::<ReviewStatus>).transpose()?;
+    let all = queue.items()?;
+    let items: Vec<_> = all
+        .iter()
+        .filter(|item| status.is_none_or(|s| item.status == s))
+        .collect();
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&items)?);
+        return Ok(());
+    }
+    if items.is_empty() {
+        out.info(format!("No pairs in {}", queue.location()));
+        return Ok(());
+    }
+    out.result(format!(
+        "{:<12}  {:<9}  {:>5}  {:<20}  {:<20}  REVIEWER",
+        "ID", "STATUS", "SCORE", "LEFT", "RIGHT"
+    ));
+    for item in &items {
+        let reviewer = item.verdict.as_ref().and_then(|v| v.reviewer.as_deref());
+        out.result(format!(
+            "{:<12}  {:<9}  {:>5.3}  {:<20}  {:<20}  {}",
+            item.pair.id,
+            item.status,
+            item.pair.score,
+            item.pair.left_key,
+            item.pair.right_key,
+            reviewer.unwrap_or(out.dash())
+        ));
+    }
+    let counts = status_counts(&all);
+    out.info(format!(
+        "\n{} pending, {} approved, {} rejected",
+        counts["pending"], counts["approved"], counts["rejected"]
+    ));
+    Ok(())
+}
+
+/// Show a queued pair, its records and verdict, and with a spec the
+/// breakdown `explain-pair` gives of it.
+pub fn inspect(
+    queue: &str,
+    id: &str,
+    spec: Option<&Path>,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let item = ReviewQueue::open(queue)?.item(id)?;
+    let explanation = match (spec, &item.pair.left, &item.pair.right) {
+        (Some(file), Some(left), Some(right)) => Some(explain_pair(
+            &compose::read_spec(file, entity, variables)?,
+            left,
+            right,
+        )?),
+        (Some(_), _, _) => bail!(
+            "Pair {} was queued without its records; queue it with `kanoniv cluster --records`",
+            item.pair.id
+        ),
+        (None, _, _) => None,
+    };
+
+    if format == "json" {
+        let mut value = serde_json::to_value(&item)?;
+        if let Some(explanation) = &explanation {
+            value["explanation"] = serde_json::to_value(explanation)?;
+        }
+        out.result(serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+
+    out.result(format!(
+        "{} {} ({} / {})",
+        "Pair:".bold(),
+        item.pair.id,
+        item.pair.left_key,
+        item.pair.right_key
+    ));
+    out.result(format!(
+        "  score {:.3}, queued {} under plan {}",
+        item.pair.score, item.pair.queued_at, item.pair.plan_hash
+    ));
+    let verdict = match &item.verdict {
+        Some(verdict) => {
+            let mut text = format!("{} {}", item.status, verdict.decided_at);
+            if let Some(reviewer) = &verdict.reviewer {
+                text.push_str(&format!(" by {}", reviewer));
+            }
+            if let Some(note) = &verdict.note {
+                text.push_str(&format!(": {}", note));
+            }
+            text
+        }
+        None => item.status.to_string(),
+    };
+    out.result(format!("{} {}", "Status:".bold(), verdict));
+    for (side, record) in [("Left:", &item.pair.left), ("Right:", &item.pair.right)] {
+        let record = record
+            .as_ref()
+            .map_or(out.dash().to_string(), Value::to_string);
+        out.result(format!("{} {}", side.bold(), record));
+    }
+    if let Some(explanation) = &explanation {
+        out.result("");
+        print_explanation(explanation, out);
+    }
+    Ok(())
+}
+
+/// Approve (merge) or reject (unmerge) a queued pair.
+pub fn decide(
+    queue: &str,
+    id: &str,
+    status: ReviewStatus,
+    reviewer: Option<&str>,
+    note: Option<&str>,
+    out: &Output,
+) -> Result<()> {
+    let queue = ReviewQueue::open(queue)?;
+    let item = queue.decide(id, status, reviewer, note)?;
+    let verb = match status {
+        ReviewStatus::Approved => "Approved",
+        _ => "Rejected",
+    };
+    out.info(format!(
+        "{} {} {} ({} / {})",
+        out.ok_mark(),
+        verb,
+        item.pair.id,
+        item.pair.left_key,
+        item.pair.right_key
+    ));
+    Ok(())
+}
+
+/// Print the decision log, oldest entry first.
+pub fn log(queue: &str, format: &str, out: &Output) -> Result<()> {
+    let queue = ReviewQueue::open(queue)?;
+    let entries = queue.log()?;
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        out.info(format!("No entries in {}", queue.location()));
+        return Ok(());
+    }
+    for entry in &entries {
+        let detail = match entry {
+            LogEntry::Queued(pair) => format!(
+                "{} / {} at {:.3}",
+                pair.left_key, pair.right_key, pair.score
+            ),
+            LogEntry::Merge(verdict) | LogEntry::Unmerge(verdict) => {
+                [verdict.reviewer.as_deref(), verdict.note.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(": ")
+            }
+        };
+        let line = format!(
+            "{}  {:<8}  {}  {}",
+            entry.time(),
+            entry.name(),
+            entry.pair_id(),
+            detail
+        );
+        out.result(line.trim_end());
+    }
+    Ok(())
+}
+
+/// Write the decided pairs as labeled pairs for `kanoniv calibrate`, to
+/// `output` or stdout.
+pub fn labels(
+    queue: &str,
+    file: &Path,
+    output: Option<&Path>,
+    entity: Option<&str>,
+    variables: &Variables,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(file, entity, variables)?;
+    let queue = ReviewQueue::open(queue)?;
+    let (labels, missing) = training_labels(&content, &queue.items()?)?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&labels.columns)?;
+    for row in &labels.rows {
+        writer.write_record(row)?;
+    }
+    let csv = String::from_utf8(writer.into_inner()?)?;
+    if missing > 0 {
+        out.warn(format!(
+            "{} {} decided pair(s) have no records and were left out",
+            out.warn_mark(),
+            missing
+        ));
+    }
+    match output {
+        Some(path) => {
+            fs::write(path, &csv)
+                .with_context(|| format!("Failed to write file: {}", path.display()))?;
+            out.info(format!(
+                "{} Wrote {} labeled pair(s) to {}",
+                out.ok_mark(),
+                labels.rows.len(),
+                path.display()
+            ));
+        }
+        None => print!("{}", csv),
+    }
+    Ok(())
+}
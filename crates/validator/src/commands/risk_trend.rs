@@ -0,0 +1,146 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+use crate::commands::plan::generate_plan;
+use crate::output::Output;
+
+/// Risk score of one committed version of a spec.
+#[derive(Debug, Serialize)]
+pub struct TrendPoint {
+    pub commit: String,
+    pub date: String,
+    pub subject: String,
+    /// `None` when this version could not be planned (e.g. broken YAML).
+    pub risk_score: Option<u32>,
+    pub risk_flags: Option<usize>,
+}
+
+/// Score every committed version of `file`, oldest first.
+pub fn run(file: &Path, limit: Option<usize>, format: &str, out: &Output) -> Result<()> {
+    let points = risk_trend(file, limit)?;
+    if points.is_empty() {
+        bail!("{} has no git history", file.display());
+    }
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&points)?);
+        return Ok(());
+    }
+
+    out.result(format!(
+        "{:<10}  {:<8}  {:>5}  {:>5}  {:<20}  SUBJECT",
+        "DATE", "COMMIT", "SCORE", "FLAGS", "TREND"
+    ));
+    for point in &points {
+        let (score, flags, bar) = match (point.risk_score, point.risk_flags) {
+            (Some(score), Some(flags)) => (score.to_string(), flags.to_string(), bar(score, out)),
+            _ => (
+                "-".to_string(),
+                "-".to_string(),
+                "(unparseable)".to_string(),
+            ),
+        };
+        out.result(format!(
+            "{:<10}  {:<8}  {:>5}  {:>5}  {:<20}  {}",
+            point.date,
+            &point.commit[..point.commit.len().min(8)],
+            score,
+            flags,
+            bar,
+            point.subject
+        ));
+    }
+
+    let scored: Vec<u32> = points.iter().filter_map(|p| p.risk_score).collect();
+    if let (Some(first), Some(last)) = (scored.first(), scored.last()) {
+        let verdict = match last.cmp(first) {
+            std::cmp::Ordering::Less => "safer",
+            std::cmp::Ordering::Greater => "riskier",
+            std::cmp::Ordering::Equal => "unchanged",
+        };
+        out.info(format!("\nRisk score {} -> {} ({})", first, last, verdict));
+    }
+    Ok(())
+}
+
+/// Risk scores across the git history of `file`, oldest first; with
+/// `limit`, only the most recent versions.
+pub fn risk_trend(file: &Path, limit: Option<usize>) -> Result<Vec<TrendPoint>> {
+    let (dir, path) = git_path(file)?;
+    let mut args = vec!["log".to_string(), "--format=%H%x09%cs%x09%s".to_string()];
+    if let Some(limit) = limit {
+        args.push(format!("-n{}", limit));
+    }
+    args.extend(["--".to_string(), path.clone()]);
+    let log = git(dir, &args)?;
+
+    let mut points = Vec::new();
+    for line in log.lines().rev() {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(commit), Some(date)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let subject = fields.next().unwrap_or("").to_string();
+        let plan = git(dir, &["show".to_string(), format!("{}:{}", commit, path)])
+            .ok()
+            .and_then(|content| generate_plan(&content).ok());
+        points.push(TrendPoint {
+            commit: commit.to_string(),
+            date: date.to_string(),
+            subject,
+            risk_score: plan.as_ref().map(|p| p.risk_score),
+            risk_flags: plan.as_ref().map(|p| p.risk_flags.len()),
+        });
+    }
+    Ok(points)
+}
+
+/// The most recent committed version of `file` whose contents differ from
+/// `current`, with its commit; `None` outside git or when there is none.
+pub fn previous_version(file: &Path, current: &str) -> Option<(String, String)> {
+    let (dir, path) = git_path(file).ok()?;
+    let args = ["log", "--format=%H", "--", &path].map(String::from);
+    let log = git(dir, &args).ok()?;
+    log.lines().find_map(|commit| {
+        let content = git(dir, &["show".to_string(), format!("{}:{}", commit, path)]).ok()?;
+        (content.trim() != current.trim()).then(|| (commit.to_string(), content))
+    })
+}
+
+/// The directory to run git in for `file`, and the file's path from it.
+fn git_path(file: &Path) -> Result<(&Path, String)> {
+    let dir = match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = file
+        .file_name()
+        .and_then(|n| n.to_str())
+        .with_context(|| format!("Not a file path: {}", file.display()))?;
+    Ok((dir, format!("./{}", name)))
+}
+
+fn git(dir: &Path, args: &[String]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .with_context(|| "Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.first().map(String::as_str).unwrap_or(""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// One block per 5 points, so 100 fills the 20-column trend column.
+fn bar(score: u32, out: &Output) -> String {
+    let unit = if out.plain { "#" } else { "█" };
+    unit.repeat(score.div_ceil(5) as usize)
+}
@@ -0,0 +1,187 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::compose;
+use crate::hashing::KeySource;
+use crate::output::Output;
+use crate::signing::{self, SigningKey, PUBLIC_KEY_EXTENSION};
+
+/// Sign the spec at `file` with the key `key` names, or a new key written
+/// to `generate_key` (and its public key beside it; existing key files are
+/// only replaced with `force`). The signature goes to
+/// the spec's detached signature file, or with `embed`, into its
+/// `signatures` section; either way it replaces an earlier one by the same
+/// key.
+pub fn run(
+    file: Option<&Path>,
+    key: &KeySource,
+    generate_key: Option<&Path>,
+    force: bool,
+    embed: bool,
+    entity: Option<&str>,
+    out: &Output,
+) -> Result<()> {
+    let signing_key = match generate_key {
+        Some(path) => {
+            let signing_key = SigningKey::generate()?;
+            write_key(path, &signing_key, force)?;
+            out.info(format!(
+                "{} Wrote signing key {} and public key {} (key id {})",
+                out.ok_mark(),
+                path.display(),
+                public_key_path(path)?.display(),
+                signing_key.public_key().key_id()
+            ));
+            signing_key
+        }
+        None => SigningKey::from_source(key)?,
+    };
+    let Some(file) = file else {
+        return Ok(());
+    };
+
+    let content = compose::read_template(file, entity)?;
+    let spec: serde_json::Value =
+        serde_yaml::from_str(&content).with_context(|| "Failed to parse YAML")?;
+    let signature = signing_key.sign(&spec);
+    let hash = signature.hash.clone();
+    let written = if embed {
+        let signatures = signing::replace(signing::embedded(&spec)?, signature);
+        let text = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read file: {}", file.display()))?;
+        fs::write(file, signing::embed(&text, &signatures))
+            .with_context(|| format!("Failed to write file: {}", file.display()))?;
+        file.to_path_buf()
+    } else {
+        let path = signing::detached_path(file);
+        let earlier = match path.exists() {
+            true => signing::load_detached(&path)?,
+            false => Vec::new(),
+        };
+        let signatures = signing::replace(earlier, signature);
+        fs::write(&path, signing::render_detached(&signatures)?)
+            .with_context(|| format!("Failed to write file: {}", path.display()))?;
+        path
+    };
+    out.info(format!(
+        "{} Signed {} ({}) with key {}",
+        out.ok_mark(),
+        file.display(),
+        hash,
+        signing_key.public_key().key_id()
+    ));
+    out.result(written.display());
+    Ok(())
+}
+
+/// The public key's path: the secret key's file name with `.pub` appended.
+fn public_key_path(path: &Path) -> Result<PathBuf> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == PUBLIC_KEY_EXTENSION)
+    {
+        bail!(
+            "{} looks like a public key file; name the signing key something else",
+            path.display()
+        );
+    }
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(PUBLIC_KEY_EXTENSION);
+    Ok(PathBuf::from(name))
+}
+
+fn write_key(path: &Path, key: &SigningKey, force: bool) -> Result<()> {
+    let public = public_key_path(path)?;
+    for path in [path, public.as_path()] {
+        if path.exists() && !force {
+            bail!(
+                "{} already exists; pass --force to overwrite it",
+                path.display()
+            );
+        }
+    }
+    write_new(path, &format!("{}\n", key.to_hex()), true)?;
+    write_new(&public, &format!("{}\n", key.public_key().to_hex()), false)
+}
+
+/// Write `content` to a file created for it, readable only by its owner when
+/// `secret`, so a signing key is never on disk with wider permissions.
+fn write_new(path: &Path, content: &str, secret: bool) -> Result<()> {
+    if path.exists() {
+        fs::remove_file(path)
+            .with_context(|| format!("Failed to replace key file: {}", path.display()))?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(if secret { 0o600 } else { 0o644 });
+    }
+    #[cfg(not(unix))]
+    let _ = secret;
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .with_context(|| format!("Failed to write key file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_key_path_appends_to_the_file_name() {
+        assert_eq!(
+            public_key_path(Path::new("keys/release.key")).unwrap(),
+            PathBuf::from("keys/release.key.pub")
+        );
+        assert_eq!(
+            public_key_path(Path::new("release")).unwrap(),
+            PathBuf::from("release.pub")
+        );
+        let err = public_key_path(Path::new("keys/release.pub")).unwrap_err();
+        assert!(
+            err.to_string().contains("looks like a public key file"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_write_key_refuses_to_overwrite_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("release.key");
+        let key = SigningKey::generate().unwrap();
+        write_key(&path, &key, false).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let other = SigningKey::generate().unwrap();
+        let err = write_key(&path, &other, false).unwrap_err();
+        assert!(
+            err.to_string().contains("already exists; pass --force"),
+            "{err}"
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n", key.to_hex())
+        );
+
+        write_key(&path, &other, true).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}\n", other.to_hex())
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("release.key.pub")).unwrap(),
+            format!("{}\n", other.public_key().to_hex())
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! Golden snapshots of the compiled output of every spec in a repository.
+//!
+//! Each spec file under a directory (see `compose::find_specs`) has a
+//! snapshot in `snapshots/`, at its own path with a `.json` extension: its
+//! hash, IR and plan (see `conformance_outputs`), or the error it fails
+//! with. A workspace of several entities has one per entity,
+//! `<file>.<entity>.json`. Checking regenerates every snapshot and fails if
+//! any differs from the committed one, is missing or has no spec left, so a
+//! change to plan logic that alters compiled behavior cannot go unnoticed;
+//! `--update` accepts the changes.
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commands::conformance::{conformance_outputs, Mismatch};
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::parser;
+use crate::workspace::Workspace;
+
+/// Directory the snapshots are kept in, under the directory snapshotted.
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Differences listed per snapshot in text output; JSON has them all.
+const SHOWN_DIFFERENCES: usize = 10;
+
+/// How a snapshot compares with the spec's current output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotStatus {
+    Unchanged,
+    Changed,
+    /// The spec has no snapshot yet.
+    New,
+    /// The snapshot's spec or entity is gone.
+    Obsolete,
+}
+
+/// One snapshot of a check.
+#[derive(Debug, Serialize)]
+pub struct SnapshotResult {
+    /// Relative to the snapshots directory.
+    pub snapshot: String,
+    /// The spec file, relative to the directory snapshotted; `None` for an
+    /// obsolete snapshot.
+    pub spec: Option<String>,
+    /// The entity, in a workspace of several.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+    pub status: SnapshotStatus,
+    /// Where a changed snapshot first differs, by output.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl SnapshotResult {
+    pub fn passed(&self) -> bool {
+        self.status == SnapshotStatus::Unchanged
+    }
+}
+
+// ── CLI entry point ────────────────────────────────────────────────
+
+pub fn run(
+    path: &Path,
+    dir: Option<&Path>,
+    update: bool,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let dir = dir.map_or_else(|| snapshots_dir(path), Path::to_path_buf);
+    let results = snapshot(path, &dir, update, variables)?;
+    let failed = results.iter().filter(|r| !r.passed()).count();
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&json!({
+            "passed": update || failed == 0,
+            "updated": update,
+            "snapshots": results,
+        }))?);
+    } else {
+        out.detail(format!(
+            "{} snapshot(s) in {}",
+            results.len(),
+            dir.display()
+        ));
+        for result in &results {
+            let file = dir.join(&result.snapshot);
+            let subject = match (&result.spec, &result.entity) {
+                (Some(spec), Some(entity)) => format!("{} ({})", spec, entity),
+                (Some(spec), None) => spec.clone(),
+                (None, _) => file.display().to_string(),
+            };
+            match (result.status, update) {
+                (SnapshotStatus::Unchanged, _) => out.info(format!(
+                    "{} {} {} {}",
+                    out.ok_mark(),
+                    subject,
+                    out.arrow(),
+                    file.display()
+                )),
+                (SnapshotStatus::Changed, true) => {
+                    out.info(format!("{} updated {}", out.ok_mark(), file.display()))
+                }
+                (SnapshotStatus::New, true) => {
+                    out.info(format!("{} wrote {}", out.ok_mark(), file.display()))
+                }
+                (SnapshotStatus::Obsolete, true) => {
+                    out.info(format!("{} removed {}", out.ok_mark(), file.display()))
+                }
+                (SnapshotStatus::Changed, false) => {
+                    out.error(format!("{} {} changed", out.fail_mark(), subject))
+                }
+                (SnapshotStatus::New, false) => {
+                    out.error(format!("{} {} has no snapshot", out.fail_mark(), subject))
+                }
+                (SnapshotStatus::Obsolete, false) => {
+                    out.error(format!("{} {} has no spec", out.fail_mark(), subject))
+                }
+            }
+            if update {
+                continue;
+            }
+            for m in result.mismatches.iter().take(SHOWN_DIFFERENCES) {
+                out.error(format!(
+                    "  {} {} differs at {}: expected {}, got {}",
+                    out.arrow(),
+                    m.surface,
+                    if m.pointer.is_empty() {
+                        "/"
+                    } else {
+                        &m.pointer
+                    },
+                    m.expected,
+                    m.actual
+                ));
+            }
+            let hidden = result.mismatches.len().saturating_sub(SHOWN_DIFFERENCES);
+            if hidden > 0 {
+                out.error(format!(
+                    "  {} and {} more difference(s)",
+                    out.arrow(),
+                    hidden
+                ));
+            }
+        }
+        match update {
+            true => out.info(format!(
+                "{} of {} snapshot(s) updated",
+                failed,
+                results.len()
+            )),
+            false => out.info(format!(
+                "{} of {} snapshot(s) unchanged",
+                results.len() - failed,
+                results.len()
+            )),
+        }
+    }
+
+    if failed > 0 && !update {
+        bail!(
+            "{} snapshot(s) out of date; run `kanoniv snapshot --update` to accept the changes",
+            failed
+        );
+    }
+    Ok(())
+}
+
+// ── Core logic ─────────────────────────────────────────────────────
+
+/// Compare the snapshots in `dir` with the current output of every spec
+/// under `root`, or of the spec file `root`. With `update`, write those
+/// that changed or are new and remove obsolete ones; the results still say
+/// what was found. Specs are interpolated with `variables`.
+pub fn snapshot(
+    root: &Path,
+    dir: &Path,
+    update: bool,
+    variables: &Variables,
+) -> Result<Vec<SnapshotResult>> {
+    let single = root.is_file();
+    let (base, specs) = match single {
+        true => (
+            root.parent().unwrap_or(Path::new("")),
+            vec![root.to_path_buf()],
+        ),
+        false => (root, compose::find_specs(root)?),
+    };
+    if specs.is_empty() {
+        bail!("No spec files under {}", root.display());
+    }
+
+    let mut current: BTreeMap<String, (String, Option<String>, Value)> = BTreeMap::new();
+    for spec in &specs {
+        let relative = spec
+            .strip_prefix(base)
+            .unwrap_or(spec)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let stem = relative
+            .rsplit_once('.')
+            .map_or(relative.as_str(), |(stem, _)| stem)
+            .to_string();
+        let entities = spec_entities(spec, variables);
+        if entities.is_empty() {
+            current.insert(
+                format!("{}.json", stem),
+                (relative, None, spec_outputs(spec, None, base, variables)),
+            );
+            continue;
+        }
+        for entity in entities {
+            let outputs = spec_outputs(spec, Some(&entity), base, variables);
+            current.insert(
+                format!("{}.{}.json", stem, entity),
+                (relative.clone(), Some(entity), outputs),
+            );
+        }
+    }
+    let committed = snapshot_files(dir)?;
+
+    let mut results = Vec::new();
+    for (snapshot, (spec, entity, actual)) in &current {
+        let path = dir.join(snapshot);
+        let (status, mismatches) = match committed.contains(snapshot) {
+            true => {
+                let expected = read_snapshot(&path)?;
+                let mismatches = differences(&expected, actual);
+                match mismatches.is_empty() {
+                    true => (SnapshotStatus::Unchanged, mismatches),
+                    false => (SnapshotStatus::Changed, mismatches),
+                }
+            }
+            false => (SnapshotStatus::New, Vec::new()),
+        };
+        if update && status != SnapshotStatus::Unchanged {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+            }
+            fs::write(&path, serde_json::to_string_pretty(actual)? + "\n")
+                .with_context(|| format!("Failed to write file: {}", path.display()))?;
+        }
+        results.push(SnapshotResult {
+            snapshot: snapshot.clone(),
+            spec: Some(spec.clone()),
+            entity: entity.clone(),
+            status,
+            mismatches,
+        });
+    }
+    // Snapshots of the other specs beside a single file are not its own.
+    let obsolete = committed
+        .iter()
+        .filter(|s| !single && !current.contains_key(*s));
+    for snapshot in obsolete {
+        if update {
+            let path = dir.join(snapshot);
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove file: {}", path.display()))?;
+        }
+        results.push(SnapshotResult {
+            snapshot: snapshot.clone(),
+            spec: None,
+            entity: None,
+            status: SnapshotStatus::Obsolete,
+            mismatches: Vec::new(),
+        });
+    }
+    Ok(results)
+}
+
+/// The snapshots directory for `root`: `snapshots/` in it, or beside it
+/// if it is a file.
+pub fn snapshots_dir(root: &Path) -> PathBuf {
+    let parent = match root.is_file() {
+        true => root.parent().unwrap_or(Path::new("")),
+        false => root,
+    };
+    match parent == Path::new(".") {
+        true => PathBuf::from(SNAPSHOTS_DIR),
+        false => parent.join(SNAPSHOTS_DIR),
+    }
+}
+
+/// The entities of a workspace of several; empty for any other file.
+fn spec_entities(path: &Path, variables: &Variables) -> Vec<String> {
+    let Ok(spec) = compose::read_file(path, variables).and_then(|text| parser::parse_yaml(&text))
+    else {
+        return Vec::new();
+    };
+    match Workspace::from_value(&spec) {
+        Some(workspace) if workspace.entities.len() > 1 => {
+            workspace.names().into_iter().map(str::to_string).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// A spec's hash, IR and plan, or the error it fails with. Paths in the
+/// error are made relative to `base`, so the snapshot does not depend on
+/// where the command ran.
+fn spec_outputs(path: &Path, entity: Option<&str>, base: &Path, variables: &Variables) -> Value {
+    match compose::read_spec(path, entity, variables).and_then(|yaml| conformance_outputs(&yaml)) {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            let error = format!("{:#}", e);
+            if base.as_os_str().is_empty() {
+                return json!({ "error": error });
+            }
+            let prefix = format!("{}{}", base.display(), std::path::MAIN_SEPARATOR);
+            let pattern = Regex::new(&format!(r#"(^|[\s'"(]){}"#, regex::escape(&prefix)))
+                .expect("an escaped path is a valid pattern");
+            json!({ "error": pattern.replace_all(&error, "$1") })
+        }
+    }
+}
+
+/// Snapshot files in `dir`, relative to it, `/`-separated.
+fn snapshot_files(dir: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = fs::read_dir(&current)
+            .with_context(|| format!("Failed to read directory: {}", current.display()))?;
+        for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "json") {
+                let relative = path.strip_prefix(dir).unwrap_or(&path);
+                files.push(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn read_snapshot(path: &Path) -> Result<Value> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("Malformed {}", path.display()))
+}
+
+/// Every place the outputs (`hash`, `ir`, `plan` or `error`) differ.
+fn differences(expected: &Value, actual: &Value) -> Vec<Mismatch> {
+    let (null, empty) = (Value::Null, serde_json::Map::new());
+    let want = expected.as_object().unwrap_or(&empty);
+    let got = actual.as_object().unwrap_or(&empty);
+    let mut surfaces: Vec<&String> = want.keys().chain(got.keys()).collect();
+    surfaces.sort();
+    surfaces.dedup();
+    let mut mismatches = Vec::new();
+    for surface in surfaces {
+        let (x, y) = (
+            want.get(surface).unwrap_or(&null),
+            got.get(surface).unwrap_or(&null),
+        );
+        leaf_differences(surface, x, y, String::new(), &mut mismatches);
+    }
+    mismatches
+}
+
+/// The values that differ under `pointer`; arrays of different lengths
+/// differ as a whole.
+fn leaf_differences(
+    surface: &str,
+    expected: &Value,
+    actual: &Value,
+    pointer: String,
+    found: &mut Vec<Mismatch>,
+) {
+    if expected == actual {
+        return;
+    }
+    let null = Value::Null;
+    match (expected, actual) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut keys: Vec<&String> = a.keys().collect();
+            keys.extend(b.keys().filter(|key| !a.contains_key(*key)));
+            for key in keys {
+                let (x, y) = (a.get(key).unwrap_or(&null), b.get(key).unwrap_or(&null));
+                leaf_differences(surface, x, y, format!("{}/{}", pointer, key), found);
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                leaf_differences(surface, x, y, format!("{}/{}", pointer, i), found);
+            }
+        }
+        _ => found.push(Mismatch {
+            surface: surface.to_string(),
+            pointer,
+            expected: expected.clone(),
+            actual: actual.clone(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::EXECUTION;
+
+    #[test]
+    fn test_snapshots_record_outputs_and_errors() {
+        use crate::{snapshot, snapshots_dir, SnapshotStatus};
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("customer.yaml"), EXECUTION).unwrap();
+        // A spec that does not compile is snapshotted with its error.
+        std::fs::write(
+            dir.path().join("broken.yaml"),
+            "extends: missing.yaml\nidentity_version: broken_v1\n",
+        )
+        .unwrap();
+        let snapshots = snapshots_dir(dir.path());
+        assert_eq!(snapshots, dir.path().join("snapshots"));
+
+        let statuses = |update| {
+            snapshot(dir.path(), &snapshots, update, &Default::default())
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.snapshot, r.status))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            statuses(true),
+            [
+                ("broken.json".to_string(), SnapshotStatus::New),
+                ("customer.json".to_string(), SnapshotStatus::New)
+            ]
+        );
+        assert!(statuses(false)
+            .iter()
+            .all(|(_, status)| *status == SnapshotStatus::Unchanged));
+
+        let broken: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(snapshots.join("broken.json")).unwrap())
+                .unwrap();
+        assert!(broken["error"].is_string());
+        let customer: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(snapshots.join("customer.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(customer["plan"]["plan_hash"], customer["hash"]);
+        assert_eq!(customer["ir"]["thresholds"]["match"], 0.7);
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use colored::Colorize;
+use std::path::Path;
+
+use crate::clock;
+use crate::compose;
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::sample::Sample;
+use crate::survivorship::survivorship_impact;
+
+/// Report the golden fields `spec` would change for the clusters in
+/// `members`, against the `canonical` table.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    spec: &Path,
+    members: &Path,
+    canonical: &Path,
+    limit: usize,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let content = compose::read_spec(spec, entity, variables)?;
+    let impact = survivorship_impact(
+        &content,
+        &Sample::load(members)?,
+        &Sample::load(canonical)?,
+        &clock::today(),
+    )?;
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&impact)?);
+        return Ok(());
+    }
+
+    if !impact.unmatched.is_empty() {
+        out.warn(format!(
+            "{} cluster(s) have no canonical row and were skipped (e.g. {})",
+            impact.unmatched.len(),
+            impact.unmatched[0]
+        ));
+    }
+    out.info(format!(
+        "{} {} of {} entities would change ({} field value(s))",
+        "Survivorship impact:".bold(),
+        impact.changed_entities,
+        impact.entities,
+        impact.changes.len()
+    ));
+    for field in &impact.fields {
+        out.result(format!(
+            "  {} ({}) {} {} changed",
+            field.field,
+            field.strategy,
+            out.dash(),
+            field.changed
+        ));
+    }
+
+    if impact.changes.is_empty() {
+        return Ok(());
+    }
+    let show = |v: &Option<String>| match v {
+        Some(v) => format!("{:?}", v),
+        None => "null".to_string(),
+    };
+    out.result("");
+    out.result(format!("{}:", "Changes".bold()));
+    for change in impact.changes.iter().take(limit) {
+        let from = change
+            .record_key
+            .as_ref()
+            .map(|k| format!("  ({})", k))
+            .unwrap_or_default();
+        out.result(format!(
+            "  {}  {}: {} {} {}{}",
+            change.entity_id,
+            change.field,
+            show(&change.current),
+            out.arrow(),
+            show(&change.proposed),
+            from.dimmed()
+        ));
+    }
+    if impact.changes.len() > limit {
+        out.result(format!(
+            "  ... and {} more (use -f json for all)",
+            impact.changes.len() - limit
+        ));
+    }
+    Ok(())
+}
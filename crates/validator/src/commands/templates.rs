@@ -0,0 +1,32 @@
+use anyhow::Result;
+use serde_json::json;
+
+use crate::output::Output;
+use crate::templates::TEMPLATES;
+
+pub fn list(format: &str, out: &Output) -> Result<()> {
+    if format == "json" {
+        let listed: Vec<_> = TEMPLATES
+            .iter()
+            .map(|template| {
+                let params: serde_json::Map<_, _> = template
+                    .params()
+                    .into_iter()
+                    .map(|(name, default)| (name.to_string(), json!(default)))
+                    .collect();
+                json!({
+                    "name": template.name,
+                    "description": template.description,
+                    "params": params,
+                })
+            })
+            .collect();
+        out.result(serde_json::to_string_pretty(&listed)?);
+        return Ok(());
+    }
+
+    for template in TEMPLATES {
+        out.result(format!("{:<14} {}", template.name, template.description));
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use anyhow::{bail, Result};
+use serde_json::json;
+use std::path::Path;
+
+use crate::clock;
+use crate::embedding::HttpEmbedder;
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::spec_tests::{self, TestResult};
+
+/// Run the tests of the spec at `path`, or of every spec under it, and
+/// fail if any does not pass.
+pub fn run(
+    path: &Path,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let suites = spec_tests::discover(path)?;
+    if suites.is_empty() {
+        bail!(
+            "No spec tests found in {} (tests are YAML files in a `{}` directory beside the spec)",
+            path.display(),
+            spec_tests::TESTS_DIR
+        );
+    }
+    let embedder = HttpEmbedder::new()?;
+    let today = clock::today();
+    let results = suites
+        .iter()
+        .map(|suite| suite.run(&embedder, entity, variables, &today))
+        .collect::<Result<Vec<_>>>()?;
+    let total: usize = results.iter().map(Vec::len).sum();
+    let failed = results.iter().flatten().filter(|r| !r.passed()).count();
+    // Specs under a directory are shown relative to it, as `check` does.
+    let shown = |spec: &Path| match path.is_dir() {
+        true => spec
+            .strip_prefix(path)
+            .unwrap_or(spec)
+            .to_string_lossy()
+            .replace('\\', "/"),
+        false => spec.display().to_string(),
+    };
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&json!({
+            "passed": failed == 0,
+            "specs": suites
+                .iter()
+                .zip(&results)
+                .map(|(suite, tests)| json!({ "spec": shown(&suite.spec), "tests": tests }))
+                .collect::<Vec<_>>(),
+        }))?);
+    } else {
+        for (suite, tests) in suites.iter().zip(&results) {
+            out.info(shown(&suite.spec));
+            for result in tests {
+                report(result, out);
+            }
+        }
+        out.info(format!("{} of {} test(s) passed", total - failed, total));
+    }
+
+    if failed > 0 {
+        bail!("{} spec test(s) failed", failed);
+    }
+    Ok(())
+}
+
+fn report(result: &TestResult, out: &Output) {
+    let name = match &result.description {
+        Some(description) => format!("{} {} {}", result.name, out.dash(), description),
+        None => result.name.clone(),
+    };
+    if result.passed() {
+        out.info(format!("  {} {}", out.ok_mark(), name));
+        return;
+    }
+    out.error(format!("  {} {}", out.fail_mark(), name));
+    if let Some(error) = &result.error {
+        out.error(format!("    {} {}", out.arrow(), error));
+    }
+    for failure in &result.failures {
+        out.error(format!(
+            "    {} {}: expected {}, got {}",
+            out.arrow(),
+            failure.subject,
+            failure.expected,
+            failure.actual
+        ));
+    }
+}
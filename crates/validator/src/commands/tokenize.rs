@@ -0,0 +1,89 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use crate::encoding::Encoding;
+use crate::hashing::{HashingConfig, KeySource};
+use crate::normalize::Normalization;
+use crate::output::Output;
+use crate::parser;
+
+/// Tokenize record identifiers as the spec's `hashing` section says, with
+/// `algorithm` and `key` overriding it. Identifiers come from `ids`, or one
+/// per line on stdin. With `field`, values of that attribute are normalized
+/// and encoded as the spec's `encoding` section says instead, with `key`
+/// naming the salt.
+pub fn run(
+    spec: Option<&Path>,
+    ids: &[String],
+    field: Option<&str>,
+    algorithm: Option<&str>,
+    key: &KeySource,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let spec = match spec {
+        Some(path) => {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read file: {}", path.display()))?;
+            Some(parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?)
+        }
+        None => None,
+    };
+    let config = match &spec {
+        Some(spec) => HashingConfig::from_spec(spec)?,
+        None => HashingConfig::default(),
+    }
+    .overridden(algorithm, key)?;
+
+    let token: Box<dyn Fn(&str) -> String> = match field {
+        Some(field) => {
+            let Some(spec) = &spec else {
+                bail!("--field needs --spec, whose encoding section says how to encode");
+            };
+            let mut encoding = Encoding::from_spec(spec)?.unwrap_or_default();
+            if encoding.field(field).is_none() {
+                bail!("The spec does not encode '{}'", field);
+            }
+            if !key.is_none() {
+                encoding.salt_env = key.key_env.clone();
+                encoding.salt_file = key.key_file.as_ref().map(|p| p.display().to_string());
+            }
+            let encoder = encoding.encoder(config.algorithm, Normalization::from_spec(spec)?)?;
+            let field = field.to_string();
+            Box::new(move |value| encoder.encode_raw(&field, value).unwrap_or_default())
+        }
+        None => {
+            let hasher = config.hasher()?;
+            if !hasher.is_keyed() {
+                out.detail("No key configured; tokens are plain digests.");
+            }
+            Box::new(move |id| hasher.token(id))
+        }
+    };
+
+    let ids = if ids.is_empty() {
+        io::stdin()
+            .lock()
+            .lines()
+            .filter(|line| !line.as_ref().is_ok_and(|l| l.is_empty()))
+            .collect::<io::Result<Vec<_>>>()
+            .with_context(|| "Failed to read identifiers from stdin")?
+    } else {
+        ids.to_vec()
+    };
+
+    if format == "json" {
+        let tokens: Vec<serde_json::Value> = ids
+            .iter()
+            .map(|id| serde_json::json!({ "id": id, "token": token(id) }))
+            .collect();
+        out.result(serde_json::to_string_pretty(&tokens)?);
+        return Ok(());
+    }
+    for id in &ids {
+        out.result(token(id));
+    }
+    Ok(())
+}
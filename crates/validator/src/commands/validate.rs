@@ -1,60 +1,347 @@
-use anyhow::{Context, Result};
-use colored::Colorize;
-use std::fs;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::path::Path;
 
-use crate::parser;
+use crate::batch;
+use crate::cache::Cache;
+use crate::commands::plan::{generate_plan_from_ir, generate_plan_with, PlanOptions, PlanResult};
+use crate::compose;
+use crate::diagnostics::{
+    codes, locate_all, render_ci, Diagnostic, Profile, Severity, Tiers, CI_FORMATS,
+};
+use crate::interpolate::Variables;
+use crate::ir::{self, Ir};
+use crate::opa::OpaPolicies;
+use crate::output::Output;
+use crate::parser::{self, SourceMap};
+use crate::plugins::PluginRegistry;
+use crate::policy::Policy;
+use crate::rule_packs::RulePack;
 use crate::validator;
+use crate::waivers::Waivers;
 
-pub fn run(file: &Path, format: &str) -> Result<()> {
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    file: &Path,
+    entity: Option<&str>,
+    variables: &Variables,
+    format: &str,
+    profile: Profile,
+    rules: &RulePack,
+    plugins: &PluginRegistry,
+    policies: Option<&OpaPolicies>,
+    cache: Option<&Cache>,
+    out: &Output,
+) -> Result<()> {
     // Read file
-    let content = fs::read_to_string(file)
-        .with_context(|| format!("Failed to read file: {}", file.display()))?;
-
-    // Parse YAML
-    let spec = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
-
-    // Validate schema
-    let schema_errors = validator::validate_schema(&spec)?;
-    if !schema_errors.is_empty() {
-        if format == "json" {
-            println!("{}", serde_json::to_string_pretty(&schema_errors)?);
-        } else {
-            eprintln!("{} Schema validation failed:", "✗".red().bold());
-            for error in &schema_errors {
-                eprintln!("  {} {}", "→".red(), error);
+    let content = compose::read_workspace(file, entity, variables)?;
+    out.detail(format!("Read {} ({} bytes)", file.display(), content.len()));
+    let policy = Policy::discover(file)?;
+
+    // What plugins and Rego policies find depends on more than the key
+    // shows, so with either the spec is always validated
+    let cache = cache.filter(|_| plugins.is_empty() && policies.is_none());
+    let key = batch::cache_key("validate", &content, profile, &policy)
+        .with(rules.digest())
+        .finish();
+    let validated = match cache.and_then(|cache| cache.get::<Validated>(&key)) {
+        Some(validated) => {
+            out.detail("Unchanged since last validated: findings from the cache");
+            validated
+        }
+        None => {
+            let validated = validate(file, &content, profile, policy, rules, plugins, policies)?;
+            if let Some(cache) = cache {
+                cache.put(&key, &validated);
             }
+            validated
+        }
+    };
+    let tiers = &validated.tiers;
+
+    match validated.stage {
+        Stage::Yaml => {
+            report(file, format, "YAML", tiers, out)?;
+            return Err(anyhow::anyhow!("{} error(s)", tiers.errors.len()));
         }
-        return Err(anyhow::anyhow!("{} schema error(s)", schema_errors.len()));
+        Stage::Schema => {
+            report(file, format, "Schema", tiers, out)?;
+            return Err(anyhow::anyhow!("{} schema error(s)", tiers.errors.len()));
+        }
+        Stage::Semantic => {}
+    }
+    if format == "text" {
+        out.info(format!("{} Schema valid", out.ok_mark()));
+    }
+    if !tiers.is_valid() {
+        report(file, format, "Semantic", tiers, out)?;
+        return Err(anyhow::anyhow!(
+            "{} semantic error(s)",
+            tiers.errors.len()
+        ));
     }
 
     if format == "text" {
-        println!("{} Schema valid", "✓".green().bold());
-    }
-
-    // Validate semantics
-    let semantic_errors = validator::validate_semantics(&spec)?;
-    if !semantic_errors.is_empty() {
-        if format == "json" {
-            println!("{}", serde_json::to_string_pretty(&semantic_errors)?);
-        } else {
-            eprintln!("{} Semantic validation failed:", "✗".red().bold());
-            for error in &semantic_errors {
-                eprintln!("  {} {}", "→".red(), error);
-            }
+        out.info(format!("{} Semantic checks passed", out.ok_mark()));
+        print_advice(file, tiers, out);
+        out.info(format!("{} {} is valid", out.ok_mark(), file.display()));
+    } else {
+        report(file, format, "Semantic", tiers, out)?;
+    }
+
+    Ok(())
+}
+
+/// The stage validation stopped at: the first with errors, or the last.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Stage {
+    Yaml,
+    Schema,
+    Semantic,
+}
+
+/// What validating a spec found, and where it stopped.
+#[derive(Debug, Serialize, Deserialize)]
+struct Validated {
+    stage: Stage,
+    tiers: Tiers,
+}
+
+/// Validate the composed spec `content` read from `file`.
+fn validate(
+    file: &Path,
+    content: &str,
+    profile: Profile,
+    policy: Policy,
+    rules: &RulePack,
+    plugins: &PluginRegistry,
+    policies: Option<&OpaPolicies>,
+) -> Result<Validated> {
+    // Parse YAML; on a syntax error, recover per section and report
+    // everything found in one pass.
+    let (spec, source_map) = match parser::parse_yaml_with_locations(content) {
+        Ok(parsed) => parsed,
+        Err(_) => {
+            return Ok(Validated {
+                stage: Stage::Yaml,
+                tiers: crate::validate_tiers_with(content, profile, &policy),
+            })
         }
+    };
+
+    // Validate schema; the policy file and the spec's own section set the
+    // level of each finding
+    let policy = policy.for_spec(&spec);
+    let schema = Tiers::split(
+        located(
+            policy.apply(validator::schema_diagnostics(&spec)),
+            &source_map,
+        ),
+        profile,
+    );
+    if !schema.is_valid() {
+        return Ok(Validated {
+            stage: Stage::Schema,
+            tiers: schema,
+        });
+    }
+
+    // Validate semantics, the rule pack's checks, plugins' validators and
+    // Rego policies; waivers in the spec set aside matching advice
+    let mut semantic = validator::semantic_diagnostics(&spec);
+    semantic.extend(rules.diagnostics(&spec));
+    semantic.extend(plugins.validate(&spec)?);
+    if let Some(policies) = policies {
+        let plan = || {
+            let options = PlanOptions {
+                policy: Policy::discover(file)?,
+                plugins: plugins.clone(),
+                ..Default::default()
+            };
+            generate_plan_with(content, &options)
+        };
+        semantic.extend(policy_findings(policies, &spec, &semantic, plan)?);
+    }
+    let (semantic, waived) = Waivers::collect(content, &spec).apply(semantic);
+    Ok(Validated {
+        stage: Stage::Semantic,
+        tiers: Tiers::split_waived(
+            located(policy.apply(semantic), &source_map),
+            waived,
+            profile,
+        ),
+    })
+}
+
+/// `kanoniv validate --from-ir`: validate the spec a compiled IR file
+/// carries (see `Ir::to_spec`), and that the IR is as compiled. Findings
+/// have paths but no line numbers, and the IR carries no waivers or policy
+/// section; a `.kanoniv.toml` governing the IR file still applies.
+pub fn run_from_ir(
+    ir_path: &Path,
+    format: &str,
+    profile: Profile,
+    rules: &RulePack,
+    plugins: &PluginRegistry,
+    policies: Option<&OpaPolicies>,
+    out: &Output,
+) -> Result<()> {
+    let policy = Policy::discover(ir_path)?;
+    let value = ir::load_value(ir_path)?;
+    let compiled = Ir::from_value(&value)?;
+    let spec = compiled.to_spec();
+    out.detail(format!("Read IR {}", ir_path.display()));
+
+    let mut diagnostics = validator::schema_diagnostics(&spec);
+    if !ir::plan_hash_matches(&value) {
+        diagnostics.push(Diagnostic::error(
+            codes::IR_HASH_MISMATCH,
+            "plan_hash",
+            "plan_hash does not match the IR's contents; it was edited after compiling",
+        ));
+    }
+    let schema = Tiers::split(policy.apply(diagnostics), profile);
+    if !schema.is_valid() {
+        report(ir_path, format, "Schema", &schema, out)?;
+        return Err(anyhow::anyhow!("{} schema error(s)", schema.errors.len()));
+    }
+    if format == "text" {
+        out.info(format!("{} Schema valid", out.ok_mark()));
+    }
+
+    let mut semantic = validator::semantic_diagnostics(&spec);
+    semantic.extend(rules.diagnostics(&spec));
+    semantic.extend(plugins.validate(&spec)?);
+    if let Some(policies) = policies {
+        let plan = || {
+            let options = PlanOptions {
+                policy: policy.clone(),
+                plugins: plugins.clone(),
+                ..Default::default()
+            };
+            generate_plan_from_ir(&compiled, &options)
+        };
+        semantic.extend(policy_findings(policies, &spec, &semantic, plan)?);
+    }
+    let semantic = Tiers::split(policy.apply(semantic), profile);
+    if !semantic.is_valid() {
+        report(ir_path, format, "Semantic", &semantic, out)?;
         return Err(anyhow::anyhow!(
             "{} semantic error(s)",
-            semantic_errors.len()
+            semantic.errors.len()
         ));
     }
 
     if format == "text" {
-        println!("{} Semantic checks passed", "✓".green().bold());
-        println!("{} {} is valid", "✓".green().bold(), file.display());
+        out.info(format!("{} Semantic checks passed", out.ok_mark()));
+        print_advice(ir_path, &semantic, out);
+        out.info(format!("{} {} is valid", out.ok_mark(), ir_path.display()));
     } else {
-        println!(r#"{{"valid": true, "errors": []}}"#);
+        report(ir_path, format, "Semantic", &semantic, out)?;
     }
 
     Ok(())
 }
+
+/// The findings of Rego `policies` on `spec`, given its plan unless the
+/// spec's other checks found errors.
+fn policy_findings(
+    policies: &OpaPolicies,
+    spec: &Value,
+    semantic: &[Diagnostic],
+    plan: impl FnOnce() -> Result<PlanResult>,
+) -> Result<Vec<Diagnostic>> {
+    let plan = match semantic.iter().any(|d| d.severity == Severity::Error) {
+        true => None,
+        false => plan().ok(),
+    };
+    policies.evaluate(spec, plan.as_ref())
+}
+
+fn located(mut diagnostics: Vec<Diagnostic>, map: &SourceMap) -> Vec<Diagnostic> {
+    locate_all(&mut diagnostics, map);
+    diagnostics
+}
+
+fn report(file: &Path, format: &str, stage: &str, tiers: &Tiers, out: &Output) -> Result<()> {
+    let uri = file.to_string_lossy().replace('\\', "/");
+    if let Some(report) = render_ci(format, "kanoniv validate", &uri, tiers) {
+        if !report.is_empty() {
+            out.result(report.trim_end());
+        }
+        return Ok(());
+    }
+    if format == "json" {
+        let mut report = json!({
+            "valid": tiers.is_valid(),
+            "errors": tiers.errors,
+            "warnings": tiers.warnings,
+            "info": tiers.info,
+        });
+        if !tiers.waived.is_empty() {
+            report["waived"] = json!(tiers.waived);
+        }
+        out.result(serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    if format != "text" {
+        bail!(
+            "Unknown output format '{}'. Use text, json, {}",
+            format,
+            CI_FORMATS.join(", ")
+        );
+    }
+    out.error(format!("{} {} validation failed:", out.fail_mark(), stage));
+    for diagnostic in &tiers.errors {
+        out.error(format_diagnostic(file, diagnostic, out));
+    }
+    print_advice(file, tiers, out);
+    Ok(())
+}
+
+/// Warnings (hidden by `--quiet`), waived findings, and info (shown with
+/// `--verbose`).
+fn print_advice(file: &Path, tiers: &Tiers, out: &Output) {
+    for diagnostic in &tiers.warnings {
+        out.warn(format_diagnostic(file, diagnostic, out));
+    }
+    for waived in &tiers.waived {
+        let expires = match &waived.waiver.expires {
+            Some(date) => format!(", until {}", date),
+            None => String::new(),
+        };
+        out.info(format!(
+            "  {} waived [{}] {} (reason: {}{})",
+            out.dash(),
+            waived.code,
+            waived.message,
+            waived.waiver.reason,
+            expires
+        ));
+    }
+    for diagnostic in &tiers.info {
+        out.detail(format_diagnostic(file, diagnostic, out));
+    }
+}
+
+pub(crate) fn format_diagnostic(file: &Path, diagnostic: &Diagnostic, out: &Output) -> String {
+    let mark = match diagnostic.severity {
+        Severity::Error => out.arrow(),
+        Severity::Warning => out.warn_mark(),
+        Severity::Info => out.dash().to_string(),
+    };
+    match diagnostic.span {
+        Some(span) => format!(
+            "  {} [{}] {}:{}:{}: {}",
+            mark,
+            diagnostic.code,
+            file.display(),
+            span.line,
+            span.column,
+            diagnostic
+        ),
+        None => format!("  {} [{}] {}", mark, diagnostic.code, diagnostic),
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{bail, Context, Result};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+use crate::compose;
+use crate::output::Output;
+use crate::signing::{self, PublicKey, SignatureStatus};
+
+/// Check the spec at `file` against the trusted `keys`: its embedded
+/// signatures and those in `signatures`, or else in its detached signature
+/// file if there is one. Fails unless a trusted key signed the spec as it
+/// is.
+pub fn run(
+    file: &Path,
+    keys: &[PathBuf],
+    signatures: Option<&Path>,
+    entity: Option<&str>,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let keys = keys
+        .iter()
+        .map(|path| PublicKey::load(path))
+        .collect::<Result<Vec<_>>>()?;
+    let content = compose::read_template(file, entity)?;
+    let spec: serde_json::Value =
+        serde_yaml::from_str(&content).with_context(|| "Failed to parse YAML")?;
+
+    let mut found = signing::embedded(&spec)?;
+    let detached = signing::detached_path(file);
+    match signatures {
+        Some(path) => found.extend(signing::load_detached(path)?),
+        None if detached.exists() => found.extend(signing::load_detached(&detached)?),
+        None => {}
+    }
+    let hash = signing::signed_hash(&spec);
+    let checks = signing::verify(&spec, &found, &keys);
+    let verified = checks.iter().any(|c| c.status == SignatureStatus::Valid);
+
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&json!({
+            "file": file.display().to_string(),
+            "hash": hash,
+            "verified": verified,
+            "signatures": checks,
+        }))?);
+    } else {
+        for check in &checks {
+            let signature = &check.signature;
+            let signed_at = match &signature.signed_at {
+                Some(at) => format!(", signed {}", at),
+                None => String::new(),
+            };
+            let line = format!(
+                "key {} ({}{}): {}",
+                signature.key_id,
+                signature.algorithm,
+                signed_at,
+                check.status.name()
+            );
+            match check.status {
+                SignatureStatus::Valid => out.info(format!("{} {}", out.ok_mark(), line)),
+                SignatureStatus::Stale => out.warn(format!(
+                    "{} {}; it signed {}, the spec is now {}",
+                    out.warn_mark(),
+                    line,
+                    signature.hash,
+                    hash
+                )),
+                _ => out.warn(format!("{} {}", out.warn_mark(), line)),
+            }
+        }
+    }
+
+    if !verified {
+        match found.is_empty() {
+            true => bail!("{} is not signed", file.display()),
+            false => bail!(
+                "{} has no valid signature from a trusted key",
+                file.display()
+            ),
+        }
+    }
+    if format != "json" {
+        out.info(format!(
+            "{} {} is signed by a trusted key",
+            out.ok_mark(),
+            file.display()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,264 @@
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::batch;
+use crate::clock;
+use crate::commands::plan::{generate_plan_with, PlanOptions};
+use crate::commands::validate::format_diagnostic;
+use crate::diagnostics::{Diagnostic, Profile, Severity};
+use crate::interpolate::Variables;
+use crate::output::Output;
+use crate::watch::{DiagnosticDelta, SpecWatcher};
+
+/// The `name` option's `seconds`, at least `min` of them.
+fn duration(name: &str, seconds: f64, min: f64) -> Result<Duration> {
+    if !seconds.is_finite() {
+        bail!(
+            "Invalid {} {}: expected a finite number of seconds",
+            name,
+            seconds
+        );
+    }
+    match Duration::try_from_secs_f64(seconds.max(min)) {
+        Ok(duration) => Ok(duration),
+        Err(_) => bail!("Invalid {} {}: too long", name, seconds),
+    }
+}
+
+/// One check of a spec.
+#[derive(Debug, Default)]
+struct Check {
+    /// Why the spec could not be checked: unreadable, or a valid spec
+    /// that does not plan.
+    failure: Option<String>,
+    /// Validation findings, as `kanoniv validate` reports them.
+    findings: Vec<Diagnostic>,
+    /// The plan hash, with `--plan`, once the spec is valid.
+    plan_hash: Option<String>,
+    /// The plan's risk flags, as warnings. An invalid spec is not planned
+    /// and keeps the flags of its last plan, so fixing it reports only the
+    /// flags that moved.
+    risks: Vec<Diagnostic>,
+}
+
+impl Check {
+    fn count(&self, severity: Severity) -> usize {
+        let risks = match self.plan_hash {
+            Some(_) => &self.risks[..],
+            None => &[],
+        };
+        self.findings
+            .iter()
+            .chain(risks)
+            .filter(|d| d.severity == severity)
+            .count()
+    }
+
+    /// How the findings, and the risk flags of a plan, moved since `previous`.
+    fn delta(&self, previous: &Check) -> DiagnosticDelta {
+        let mut delta = DiagnosticDelta::between(&previous.findings, &self.findings);
+        if self.plan_hash.is_some() {
+            let risks = DiagnosticDelta::between(&previous.risks, &self.risks);
+            delta.new.extend(risks.new);
+            delta.resolved.extend(risks.resolved);
+            delta.unchanged += risks.unchanged;
+        }
+        delta
+    }
+}
+
+/// Validate (and with `plan`, plan) every spec under `root`, then again
+/// each time files change, reporting only what changed: the findings an
+/// edit introduced or resolved, and a spec's new plan hash. Runs until
+/// interrupted.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    root: &Path,
+    profile: Profile,
+    plan: bool,
+    interval: f64,
+    debounce: f64,
+    entity: Option<&str>,
+    variables: &Variables,
+    out: &Output,
+) -> Result<()> {
+    let interval = duration("interval", interval, 0.05)?;
+    let debounce = duration("debounce", debounce, 0.0)?;
+    let mut watcher = SpecWatcher::new(root)?;
+    let mut checks: BTreeMap<PathBuf, Check> = BTreeMap::new();
+    let specs = watcher.specs();
+    let checked = batch::map(&specs, None, |spec| {
+        check(spec, profile, plan, entity, variables)
+    });
+    for (spec, check) in specs.into_iter().zip(checked) {
+        report(&spec, None, &check, out);
+        checks.insert(spec, check);
+    }
+    out.info(format!(
+        "{} Watching {} spec(s) in {} (Ctrl-C to stop)",
+        out.arrow(),
+        checks.len(),
+        root.display()
+    ));
+
+    loop {
+        let changed = watcher.wait(interval, debounce)?;
+        let time = clock::now();
+        out.info(format!(
+            "\n{} {} file(s) changed",
+            format!("[{}]", &time[11..19]).dimmed(),
+            changed.len()
+        ));
+        // Specs extend and include one another, so every spec is checked
+        // again; only those whose results moved are reported.
+        let specs = watcher.specs();
+        for path in changed.iter().filter(|path| !specs.contains(path)) {
+            if checks.remove(path).is_some() {
+                out.info(format!("  {} {} removed", out.dash(), path.display()));
+            }
+        }
+        let rechecked = batch::map(&specs, None, |spec| {
+            check(spec, profile, plan, entity, variables)
+        });
+        for (spec, mut check) in specs.into_iter().zip(rechecked) {
+            let previous = checks.remove(&spec);
+            if let (None, Some(previous)) = (&check.plan_hash, &previous) {
+                check.risks = previous.risks.clone();
+            }
+            let touched = changed.contains(&spec);
+            let moved = previous.as_ref().is_none_or(|previous| {
+                previous.failure != check.failure
+                    || previous.plan_hash != check.plan_hash
+                    || !check.delta(previous).is_empty()
+            });
+            if touched || moved {
+                report(&spec, previous.as_ref(), &check, out);
+            }
+            checks.insert(spec, check);
+        }
+    }
+}
+
+/// Validate the spec at `path` as `kanoniv validate` does, under the
+/// policy governing it, and with `plan`, plan it once valid.
+fn check(
+    path: &Path,
+    profile: Profile,
+    plan: bool,
+    entity: Option<&str>,
+    variables: &Variables,
+) -> Check {
+    let (content, policy) = match batch::read_spec(path, entity, variables) {
+        Ok(read) => read,
+        Err(e) => {
+            return Check {
+                failure: Some(format!("{:#}", e)),
+                ..Check::default()
+            }
+        }
+    };
+    let tiers = crate::validate_tiers_with(&content, profile, &policy);
+    let valid = tiers.is_valid();
+    let mut check = Check {
+        findings: [tiers.errors, tiers.warnings, tiers.info].concat(),
+        ..Check::default()
+    };
+    if plan && valid {
+        let options = PlanOptions {
+            policy,
+            ..Default::default()
+        };
+        match generate_plan_with(&content, &options) {
+            Ok(result) => {
+                check.risks.extend(result.risk_flags.iter().map(|flag| {
+                    Diagnostic::warning(
+                        &flag.code,
+                        "",
+                        format!("{} risk: {}", flag.severity, flag.message),
+                    )
+                }));
+                check.plan_hash = Some(result.plan_hash);
+            }
+            Err(e) => check.failure = Some(format!("{:#}", e)),
+        }
+    }
+    check
+}
+
+/// Report `check` of `path`: its status, then every finding, or with a
+/// `previous` check, the findings new since it and those resolved.
+fn report(path: &Path, previous: Option<&Check>, check: &Check, out: &Output) {
+    if let Some(failure) = &check.failure {
+        out.error(format!(
+            "{} {}: {}",
+            out.fail_mark(),
+            path.display(),
+            failure
+        ));
+        return;
+    }
+    let (errors, warnings) = (check.count(Severity::Error), check.count(Severity::Warning));
+    let mut status = match errors {
+        0 => format!("{} {} is valid", out.ok_mark(), path.display()),
+        _ => format!(
+            "{} {} has {} error(s)",
+            out.fail_mark(),
+            path.display(),
+            errors
+        ),
+    };
+    if warnings > 0 {
+        status.push_str(&format!(", {} warning(s)", warnings));
+    }
+    if let Some(hash) = &check.plan_hash {
+        let before = previous.and_then(|p| p.plan_hash.as_deref());
+        match before {
+            Some(before) if before != hash => status.push_str(&format!(
+                " (plan {} {} {})",
+                short(before),
+                out.arrow(),
+                short(hash)
+            )),
+            _ => status.push_str(&format!(" (plan {})", short(hash))),
+        }
+    }
+    match errors {
+        0 => out.info(status),
+        _ => out.error(status),
+    }
+
+    let (new, resolved) = match previous {
+        Some(previous) => {
+            let delta = check.delta(previous);
+            (delta.new, delta.resolved)
+        }
+        None => ([&check.findings[..], &check.risks].concat(), Vec::new()),
+    };
+    for diagnostic in &new {
+        let line = format_diagnostic(path, diagnostic, out);
+        match diagnostic.severity {
+            Severity::Error => out.error(line),
+            Severity::Warning => out.warn(line),
+            Severity::Info => out.detail(line),
+        }
+    }
+    for diagnostic in &resolved {
+        out.info(format!(
+            "  {} resolved [{}] {}",
+            out.ok_mark(),
+            diagnostic.code,
+            diagnostic
+        ));
+    }
+    if previous.is_some() && new.is_empty() && resolved.is_empty() {
+        out.detail(format!("  {} no new findings", out.dash()));
+    }
+}
+
+/// A plan hash shortened for display, `sha256:` and 12 hex digits.
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(19)]
+}
@@ -0,0 +1,81 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::cancel::CancellationToken;
+use crate::output::Output;
+
+/// Link partitions for the coordinator at `coordinator` (a `kanoniv
+/// execute --distributed` run sharing `secret`, whose certificate the PEM
+/// file `tls_ca` vouches for) until it has none left, trying to reach it
+/// for `wait` seconds.
+pub fn run(
+    coordinator: &str,
+    secret: &str,
+    tls_ca: &Path,
+    wait: f64,
+    cancel: Option<&CancellationToken>,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    let Ok(wait) = Duration::try_from_secs_f64(wait) else {
+        bail!(
+            "Invalid wait {}: expected a finite number of seconds, 0 or more",
+            wait
+        );
+    };
+    let ca =
+        fs::read(tls_ca).with_context(|| format!("Failed to read file: {}", tls_ca.display()))?;
+    link(coordinator, secret, &ca, wait, cancel, format, out)
+}
+
+#[cfg(feature = "distributed")]
+fn link(
+    coordinator: &str,
+    secret: &str,
+    ca: &[u8],
+    wait: Duration,
+    cancel: Option<&CancellationToken>,
+    format: &str,
+    out: &Output,
+) -> Result<()> {
+    use colored::Colorize;
+
+    use crate::distributed::work;
+    use crate::embedding::HttpEmbedder;
+
+    let done = work(coordinator, secret, ca, wait, &HttpEmbedder::new()?, cancel)?;
+    if format == "json" {
+        out.result(serde_json::to_string_pretty(&done)?);
+        return Ok(());
+    }
+    match &done.plan_hash {
+        Some(plan_hash) => out.info(format!(
+            "{} {} records, {} candidate pairs in {} partition(s) for {}",
+            "Clustered:".bold(),
+            done.records,
+            done.pairs,
+            done.partitions,
+            plan_hash
+        )),
+        None => out.info(format!(
+            "{} the run was over before this worker joined",
+            "Clustered nothing:".bold()
+        )),
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "distributed"))]
+fn link(
+    _coordinator: &str,
+    _secret: &str,
+    _ca: &[u8],
+    _wait: Duration,
+    _cancel: Option<&CancellationToken>,
+    _format: &str,
+    _out: &Output,
+) -> Result<()> {
+    bail!("kanoniv was built without the `distributed` feature")
+}
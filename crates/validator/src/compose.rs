@@ -0,0 +1,663 @@
+//! Spec composition: a spec that extends a base spec.
+//!
+//! A spec naming a base with `extends` holds only what it changes:
+//!
+//! ```yaml
+//! extends: registry://org/person@1.4.0   # or a file: ../person.yaml
+//! identity_version: person_emea_v1
+//! rules:
+//!   - name: phone_exact       # added, or replaces the base rule of that name
+//!     type: exact
+//!     field: phone
+//! decision:
+//!   thresholds:
+//!     match: 0.85             # every other base threshold is kept
+//! ```
+//!
+//! `registry://<path>/<entity>@<version>` names a version published to the
+//! registry at `$KANONIV_REGISTRY` (see `registry`), with `<path>` a
+//! directory under it; the version is anything `Registry::resolve` accepts.
+//! Any other reference is a spec file, relative to the extending spec's
+//! directory: per-region variants of one base kept side by side. A
+//! published spec can only extend other published specs.
+//!
+//! The child is laid over the base: mappings merge key by key, a `null`
+//! removes a base key, entries of named lists (`sources`, `rules`,
+//! `blocking.keys`, ...) replace the base entry with the same name or are
+//! added after the base's, and any other value replaces the base's. Bases
+//! may themselves extend a base. Commands that read spec files compose them
+//! first, so validation and planning see the composed spec.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::format::format_merged;
+use crate::interpolate::{self, Variables};
+use crate::merge::{keyed, KEYED_LISTS};
+use crate::parser;
+use crate::registry::{check_name, Registry, SpecVersion};
+use crate::workspace::{self, Workspace};
+
+/// Environment variable holding the registry `registry://` references
+/// resolve against.
+pub const REGISTRY_ENV: &str = "KANONIV_REGISTRY";
+
+const SCHEME: &str = "registry://";
+
+/// Bases extending bases, at most this deep.
+const MAX_DEPTH: usize = 8;
+
+/// Top-level keys of spec and workspace documents, which other YAML files
+/// (CI workflows, rule packs, records) do not have.
+const SPEC_KEYS: &[&str] = &[
+    "api_version",
+    "identity_version",
+    "entity",
+    "extends",
+    "shared",
+];
+
+/// A `registry://` reference to a published spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseRef {
+    /// Directories under the registry root (`org`), possibly none.
+    pub path: Vec<String>,
+    pub entity: String,
+    /// Version selector; the latest version if absent.
+    pub version: Option<String>,
+}
+
+impl BaseRef {
+    /// Location of the registry holding the entity, under `root`.
+    pub fn location(&self, root: &str) -> String {
+        let mut location = root.trim_end_matches('/').to_string();
+        for dir in &self.path {
+            location.push('/');
+            location.push_str(dir);
+        }
+        location
+    }
+}
+
+impl FromStr for BaseRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(rest) = s.strip_prefix(SCHEME) else {
+            bail!(
+                "Invalid base '{}': expected {}<path>/<entity>@<version>",
+                s,
+                SCHEME
+            );
+        };
+        let (name, version) = match rest.split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (rest, None),
+        };
+        if version.is_some_and(str::is_empty) {
+            bail!("Invalid base '{}': the version after '@' is empty", s);
+        }
+        let mut path: Vec<String> = name.split('/').map(str::to_string).collect();
+        let entity = path.pop().unwrap_or_default();
+        for segment in path.iter().chain([&entity]) {
+            check_name("registry path segment", segment)
+                .with_context(|| format!("Invalid base '{}'", s))?;
+        }
+        Ok(BaseRef {
+            path,
+            entity,
+            version: version.map(str::to_string),
+        })
+    }
+}
+
+impl fmt::Display for BaseRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(SCHEME)?;
+        for dir in &self.path {
+            write!(f, "{}/", dir)?;
+        }
+        f.write_str(&self.entity)?;
+        if let Some(version) = &self.version {
+            write!(f, "@{}", version)?;
+        }
+        Ok(())
+    }
+}
+
+/// How the child changes one node of the base.
+#[derive(Debug, Clone, Serialize)]
+pub struct Override {
+    /// Dotted path, with list entries by name (`rules.email_exact`).
+    pub path: String,
+    /// `added`, `overridden` or `removed`.
+    pub change: String,
+    pub base: Option<Value>,
+    pub child: Option<Value>,
+}
+
+/// A spec composed over its base.
+#[derive(Debug, Clone, Serialize)]
+pub struct Composed {
+    /// The `extends` reference.
+    pub extends: String,
+    /// The published version it resolved to; `None` for a base file.
+    pub version: Option<SpecVersion>,
+    /// The composed spec, in canonical form.
+    pub yaml: String,
+    pub overrides: Vec<Override>,
+}
+
+/// Compose `yaml` over the base it extends, with references resolved
+/// against the registry at `registry` (by default `$KANONIV_REGISTRY`)
+/// and base files against the current directory, interpolated with the
+/// environment alone. `None` if the spec extends nothing.
+pub fn resolve(yaml: &str, registry: Option<&str>) -> Result<Option<Composed>> {
+    let child = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let variables = Variables::default();
+    resolve_spec(
+        &child,
+        registry,
+        Some(Path::new("")),
+        Some(&variables),
+        &mut Vec::new(),
+    )
+}
+
+/// `resolve` for the spec file at `path` (see `read_spec`), with base
+/// files relative to it.
+pub fn resolve_file(
+    path: &Path,
+    registry: Option<&str>,
+    entity: Option<&str>,
+    variables: &Variables,
+) -> Result<Option<Composed>> {
+    let content = workspace::select_yaml(&read_file(path, variables)?, entity)?;
+    let child = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
+    resolve_spec(
+        &child,
+        registry,
+        Some(directory(path)),
+        Some(variables),
+        &mut Vec::new(),
+    )
+}
+
+/// The spec `yaml` describes: composed over its base if it extends one,
+/// else `yaml` unchanged.
+pub fn compose_yaml(yaml: &str, registry: Option<&str>) -> Result<String> {
+    compose_in(yaml, registry, Path::new(""), Some(&Variables::default()))
+}
+
+/// Read a spec file, interpolated with `variables` (see `interpolate`),
+/// narrowed to `entity` if it is a workspace (see `workspace`; the only
+/// entity if `None`), and composed over its base if it extends one.
+pub fn read_spec(path: &Path, entity: Option<&str>, variables: &Variables) -> Result<String> {
+    let content = workspace::select_yaml(&read_file(path, variables)?, entity)?;
+    compose_in(&content, None, directory(path), Some(variables))
+}
+
+/// `read_spec` without interpolation: the spec and any base files it
+/// extends as written, `${VAR}` and `{{ params.name }}` references kept.
+/// What signatures cover, so one signature holds in every environment.
+pub fn read_template(path: &Path, entity: Option<&str>) -> Result<String> {
+    let content = workspace::select_yaml(&read_text(path, None)?, entity)?;
+    compose_in(&content, None, directory(path), None)
+}
+
+/// `read_spec`, except that a workspace of several entities is returned
+/// whole when no entity is selected, to be validated as one.
+pub fn read_workspace(path: &Path, entity: Option<&str>, variables: &Variables) -> Result<String> {
+    let content = read_file(path, variables)?;
+    if entity.is_none() {
+        let parsed = parser::parse_yaml(&content).ok();
+        if parsed
+            .and_then(|spec| Workspace::from_value(&spec))
+            .is_some_and(|w| w.entities.len() > 1)
+        {
+            return Ok(content);
+        }
+    }
+    let content = workspace::select_yaml(&content, entity)?;
+    compose_in(&content, None, directory(path), Some(variables))
+}
+
+/// A spec file's text, interpolated with `variables`. A directory reads as
+/// its `.yaml` and `.yml` files, by name, one document each.
+pub fn read_file(path: &Path, variables: &Variables) -> Result<String> {
+    read_text(path, Some(variables))
+}
+
+/// `read_file`, interpolated only with `Some` variables.
+fn read_text(path: &Path, variables: Option<&Variables>) -> Result<String> {
+    if path.is_dir() {
+        let mut files: Vec<_> = fs::read_dir(path)
+            .with_context(|| format!("Failed to read directory: {}", path.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|ext| ext == "yaml" || ext == "yml")
+            })
+            .collect();
+        files.sort();
+        if files.is_empty() {
+            bail!("No .yaml or .yml files in {}", path.display());
+        }
+        let mut documents = Vec::new();
+        for file in files {
+            let text = read_text(&file, variables)?;
+            documents.push(format!("---\n{}", text.trim_end()));
+        }
+        return Ok(documents.join("\n") + "\n");
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+    match variables {
+        Some(variables) => interpolate::interpolate(&content, variables),
+        None => Ok(content),
+    }
+}
+
+/// The spec files under `root`, in path order: `.yaml` and `.yml` files
+/// outside hidden directories with a top-level key only specs have. Files
+/// that do not read are left out.
+pub fn find_specs(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut specs = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+        for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if path.is_dir() {
+                if !hidden {
+                    pending.push(path);
+                }
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml")
+                && fs::read_to_string(&path).is_ok_and(|text| {
+                    top_level_entries(&text).any(|(key, _)| SPEC_KEYS.contains(&key))
+                })
+            {
+                specs.push(path);
+            }
+        }
+    }
+    specs.sort();
+    Ok(specs)
+}
+
+/// The bases the documents of a spec file's text extend, as written:
+/// `registry://` references and paths relative to the file.
+pub fn extends_references(text: &str) -> Vec<String> {
+    top_level_entries(text)
+        .filter(|(key, _)| *key == "extends")
+        .map(|(_, value)| {
+            let value = value.split(" #").next().unwrap_or_default().trim();
+            value.trim_matches(['"', '\'']).to_string()
+        })
+        .filter(|reference| !reference.is_empty())
+        .collect()
+}
+
+/// The `key: value` lines at the top level of each document of `text`,
+/// read without parsing so that broken documents still show theirs.
+fn top_level_entries(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.lines().filter_map(|line| {
+        if line.starts_with([' ', '\t', '#', '-']) {
+            return None;
+        }
+        let (key, value) = line.split_once(':')?;
+        let key = key.trim_matches(['"', '\'']);
+        let plain = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        plain.then_some((key, value.trim()))
+    })
+}
+
+/// Whether `yaml` extends a spec file rather than a published spec.
+pub fn extends_file(yaml: &str) -> bool {
+    parser::parse_yaml(yaml).is_ok_and(|spec| {
+        spec.get("extends")
+            .and_then(Value::as_str)
+            .is_some_and(|reference| !reference.starts_with(SCHEME))
+    })
+}
+
+fn compose_in(
+    yaml: &str,
+    registry: Option<&str>,
+    dir: &Path,
+    variables: Option<&Variables>,
+) -> Result<String> {
+    match parser::parse_yaml(yaml) {
+        Ok(spec) if spec.get("extends").is_some() => {
+            let composed = resolve_spec(&spec, registry, Some(dir), variables, &mut Vec::new())?;
+            Ok(composed.map(|c| c.yaml).unwrap_or_else(|| yaml.to_string()))
+        }
+        // Broken YAML is left for the caller to report.
+        _ => Ok(yaml.to_string()),
+    }
+}
+
+/// Where base files named by the spec at `path` are looked up.
+fn directory(path: &Path) -> &Path {
+    if path.is_dir() {
+        return path;
+    }
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// Lay `child` over `base`, listing what it changes.
+pub fn compose(base: &Value, child: &Value) -> (Value, Vec<Override>) {
+    let mut overrides = Vec::new();
+    let composed = overlay(base, child, "", "", &mut overrides);
+    (composed, overrides)
+}
+
+/// `dir` is where base files are looked up; `None` for a published spec,
+/// which cannot extend files. Base files are interpolated with `variables`,
+/// if any.
+fn resolve_spec(
+    child: &Value,
+    registry: Option<&str>,
+    dir: Option<&Path>,
+    variables: Option<&Variables>,
+    seen: &mut Vec<String>,
+) -> Result<Option<Composed>> {
+    let Some(extends) = child.get("extends") else {
+        return Ok(None);
+    };
+    let Some(reference) = extends.as_str() else {
+        bail!(
+            "`extends` must be a file path or a {}<path>/<entity>@<version> string",
+            SCHEME
+        );
+    };
+    if seen.len() >= MAX_DEPTH {
+        bail!("Bases extend more than {} levels deep", MAX_DEPTH);
+    }
+    let (version, base_yaml, base_path) = if reference.starts_with(SCHEME) {
+        let (version, base_yaml) = pull_base(reference, registry, seen)?;
+        (Some(version), base_yaml, None)
+    } else {
+        let Some(dir) = dir else {
+            bail!(
+                "Published specs can only extend {} bases, not the file '{}'",
+                SCHEME,
+                reference
+            );
+        };
+        let path = dir.join(reference);
+        let base_yaml = read_text(&path, variables)?;
+        let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let key = key.display().to_string();
+        if seen.contains(&key) {
+            bail!("{} extends itself", reference);
+        }
+        seen.push(key);
+        (None, base_yaml, Some(path))
+    };
+
+    let mut base = parser::parse_yaml(&base_yaml)
+        .with_context(|| format!("Failed to parse base spec {}", reference))?;
+    let base_dir = base_path.as_deref().map(directory);
+    if let Some(composed) = resolve_spec(&base, registry, base_dir, variables, seen)? {
+        base = parser::parse_yaml(&composed.yaml)?;
+    }
+    let (composed, overrides) = compose(&base, child);
+    let Value::Object(root) = &composed else {
+        bail!("{} is not a YAML mapping", reference);
+    };
+    Ok(Some(Composed {
+        extends: reference.to_string(),
+        version,
+        yaml: format_merged(root, HashMap::new()),
+        overrides,
+    }))
+}
+
+/// Fetch a `registry://` base, recording it in `seen`.
+fn pull_base(
+    reference: &str,
+    registry: Option<&str>,
+    seen: &mut Vec<String>,
+) -> Result<(SpecVersion, String)> {
+    let base_ref: BaseRef = reference.parse()?;
+
+    let root = match registry {
+        Some(root) => root.to_string(),
+        None => std::env::var(REGISTRY_ENV).with_context(|| {
+            format!(
+                "The spec extends {} but no registry is configured; set {}",
+                reference, REGISTRY_ENV
+            )
+        })?,
+    };
+    let location = base_ref.location(&root);
+    let (version, base_yaml) =
+        Registry::open(&location)?.pull(&base_ref.entity, base_ref.version.as_deref())?;
+    let key = format!("{}/{}@{}", location, base_ref.entity, version.hash);
+    if seen.contains(&key) {
+        bail!("{} extends itself", reference);
+    }
+    seen.push(key);
+    Ok((version, base_yaml))
+}
+
+/// `child` over `base` at `path` (display) / `shape` (list indexes removed).
+fn overlay(
+    base: &Value,
+    child: &Value,
+    path: &str,
+    shape: &str,
+    overrides: &mut Vec<Override>,
+) -> Value {
+    let join = |parent: &str, key: &str| {
+        if parent.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", parent, key)
+        }
+    };
+    match (base, child) {
+        (Value::Object(b), Value::Object(c)) => {
+            let mut merged: Map<String, Value> = b.clone();
+            for (key, value) in c {
+                if path.is_empty() && key == "extends" {
+                    continue;
+                }
+                let (at, at_shape) = (join(path, key), join(shape, key));
+                match (b.get(key), value) {
+                    (Some(old), Value::Null) => {
+                        overrides.push(change(&at, "removed", Some(old), None));
+                        merged.remove(key);
+                    }
+                    (None, Value::Null) => {}
+                    (Some(old), value) => {
+                        let value = overlay(old, value, &at, &at_shape, overrides);
+                        merged.insert(key.clone(), value);
+                    }
+                    (None, value) => {
+                        overrides.push(change(&at, "added", None, Some(value)));
+                        merged.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            Value::Object(merged)
+        }
+        (Value::Array(b), Value::Array(c)) => match overlay_entries(b, c, path, shape, overrides) {
+            Some(entries) => Value::Array(entries),
+            None => replace(base, child, path, overrides),
+        },
+        _ => replace(base, child, path, overrides),
+    }
+}
+
+/// Named list entries: the child's replace the base's of the same name and
+/// new ones follow; `None` if the list is not a named one.
+fn overlay_entries(
+    base: &[Value],
+    child: &[Value],
+    path: &str,
+    shape: &str,
+    overrides: &mut Vec<Override>,
+) -> Option<Vec<Value>> {
+    let fields = KEYED_LISTS
+        .iter()
+        .find(|(list, _)| *list == shape)
+        .map(|(_, fields)| *fields)?;
+    let (b, c) = (keyed(base, fields)?, keyed(child, fields)?);
+
+    let mut entries: Vec<Value> = Vec::new();
+    for (name, old) in &b {
+        let at = format!("{}.{}", path, name);
+        match c.iter().find(|(n, _)| n == name) {
+            Some((_, new)) => entries.push(replace(old, new, &at, overrides)),
+            None => entries.push((*old).clone()),
+        }
+    }
+    for (name, new) in c.iter().filter(|(n, _)| !b.iter().any(|(m, _)| m == n)) {
+        let at = format!("{}.{}", path, name);
+        overrides.push(change(&at, "added", None, Some(new)));
+        entries.push((*new).clone());
+    }
+    Some(entries)
+}
+
+fn replace(base: &Value, child: &Value, path: &str, overrides: &mut Vec<Override>) -> Value {
+    if base != child {
+        overrides.push(change(path, "overridden", Some(base), Some(child)));
+    }
+    child.clone()
+}
+
+fn change(path: &str, change: &str, base: Option<&Value>, child: Option<&Value>) -> Override {
+    Override {
+        path: path.to_string(),
+        change: change.to_string(),
+        base: base.cloned(),
+        child: child.cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_compose_lays_child_over_base() {
+        use crate::compose::compose;
+        use crate::BaseRef;
+
+        let base =
+            crate::parse_yaml(include_str!("../conformance/multi_source/spec.yaml")).unwrap();
+        let child = crate::parse_yaml(
+            "extends: registry://org/customer@1.4.0\nidentity_version: emea_v1\n\
+         rules:\n  - name: email_exact\n    type: exact\n    field: email\n    weight: 0.5\n\
+         \x20 - name: zip_exact\n    type: exact\n    field: zip\n\
+         decision:\n  thresholds:\n    review: null\n",
+        )
+        .unwrap();
+        assert!(crate::schema_diagnostics(&child).is_empty());
+
+        let (composed, overrides) = compose(&base, &child);
+        assert!(composed.get("extends").is_none());
+        assert_eq!(composed["entity"], base["entity"]);
+        assert_eq!(composed["identity_version"], "emea_v1");
+        let rules = composed["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), base["rules"].as_array().unwrap().len() + 1);
+        let email = rules.iter().find(|r| r["name"] == "email_exact").unwrap();
+        assert_eq!(email["weight"], 0.5);
+        assert_eq!(rules.last().unwrap()["name"], "zip_exact");
+        assert!(composed["decision"]["thresholds"].get("review").is_none());
+        assert_eq!(
+            composed["decision"]["thresholds"]["match"],
+            base["decision"]["thresholds"]["match"]
+        );
+
+        let changes: Vec<(&str, &str)> = overrides
+            .iter()
+            .map(|o| (o.path.as_str(), o.change.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("decision.thresholds.review", "removed"),
+                ("identity_version", "overridden"),
+                ("rules.email_exact", "overridden"),
+                ("rules.zip_exact", "added"),
+            ]
+        );
+
+        let reference: BaseRef = "registry://org/customer@1.4.0".parse().unwrap();
+        assert_eq!(
+            (reference.path.clone(), reference.entity.as_str()),
+            (vec!["org".to_string()], "customer")
+        );
+        assert_eq!(reference.location("/srv/registry/"), "/srv/registry/org");
+        assert_eq!(reference.to_string(), "registry://org/customer@1.4.0");
+        assert!("registry://../customer".parse::<BaseRef>().is_err());
+        assert!("https://example.com/customer.yaml"
+            .parse::<BaseRef>()
+            .is_err());
+        // Specs that extend nothing pass through untouched.
+        assert_eq!(crate::compose_yaml(MINIMAL, None).unwrap(), MINIMAL);
+    }
+
+    #[test]
+    fn test_compose_resolves_base_files_relative_to_the_spec() {
+        use crate::interpolate::Variables;
+
+        let dir = tempfile::tempdir().unwrap();
+        // Base files are interpolated with the variables the spec is read with.
+        std::fs::write(
+            dir.path().join("base.yaml"),
+            MINIMAL.replace("table: contacts", "table: \"{{ params.table }}\""),
+        )
+        .unwrap();
+        let child = dir.path().join("child.yaml");
+        std::fs::write(&child, "extends: base.yaml\nidentity_version: child_v1\n").unwrap();
+        let variables = Variables {
+            params: [("table".to_string(), "contacts".to_string())].into(),
+            ..Default::default()
+        };
+
+        let composed = crate::compose::resolve_file(&child, None, None, &variables)
+            .unwrap()
+            .unwrap();
+        assert_eq!(composed.extends, "base.yaml");
+        assert!(composed.version.is_none());
+        assert_eq!(composed.overrides.len(), 1);
+        assert!(composed.yaml.contains("table: contacts"));
+        assert_eq!(
+            crate::compose::read_spec(&child, None, &variables).unwrap(),
+            composed.yaml
+        );
+        let err = crate::compose::read_spec(&child, None, &Variables::default()).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("undefined parameter params.table"),
+            "{:#}",
+            err
+        );
+        let spec = crate::parse_yaml(&composed.yaml).unwrap();
+        assert_eq!(spec["identity_version"], "child_v1");
+        assert!(crate::validate_yaml(&composed.yaml).unwrap().is_empty());
+
+        assert!(crate::compose::extends_file("extends: base.yaml\n"));
+        assert!(!crate::compose::extends_file(
+            "extends: registry://org/customer\n"
+        ));
+        let err =
+            crate::compose::resolve_file(&dir.path().join("missing.yaml"), None, None, &variables);
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,409 @@
+//! Organization-defined plan risk checks.
+//!
+//! A rules file declares extra risk flags for `kanoniv plan`, each raised
+//! when its condition holds for the spec:
+//!
+//! ```yaml
+//! risks:
+//!   - code: NO_COUNTRY_BLOCKING
+//!     severity: high
+//!     message: Specs must block on country
+//!     recommendation: Add a blocking key on the country attribute
+//!     when:
+//!       not:
+//!         equals: { path: "blocking.keys[*].field", value: country }
+//! ```
+//!
+//! Paths are dotted, like diagnostic paths; `[*]` (or a `*` segment) selects
+//! every list entry or mapping value. A condition over a path holds when any
+//! selected value satisfies it:
+//!
+//! - `exists: PATH` / `missing: PATH`
+//! - `equals: { path, value }`
+//! - `less_than: { path, value }` / `greater_than: { path, value }` (numbers)
+//! - `all: [...]`, `any: [...]`, `not: CONDITION`
+//!
+//! An optional `section` (`rules`, `blocking`, ...) routes the flag to that
+//! section's owners. Rule packs (see `rule_packs`) reuse these conditions,
+//! evaluated at each node they select: there a path is relative to the node,
+//! and one starting with `$` (`$.survivorship.rules`) is from the spec root.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::cache;
+use crate::canonical::same_value;
+use crate::commands::plan::{RiskFlag, RISK_WEIGHTS};
+
+/// The checks declared in a custom risk rules file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomRisks {
+    #[serde(default)]
+    pub risks: Vec<CustomRisk>,
+    /// Digest of the YAML the rules were read from.
+    #[serde(skip)]
+    digest: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomRisk {
+    pub code: String,
+    /// `critical`, `high`, `medium` or `low`.
+    pub severity: String,
+    pub message: String,
+    #[serde(default)]
+    pub recommendation: String,
+    /// Spec section the flag concerns, for routing to its owners.
+    #[serde(default)]
+    pub section: Option<String>,
+    /// The flag is raised when this holds.
+    pub when: Condition,
+}
+
+/// A condition is a single-key mapping (`not: {...}`, `exists: path`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "Value")]
+pub enum Condition {
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    Exists(String),
+    Missing(String),
+    Equals { path: String, value: Value },
+    LessThan { path: String, value: f64 },
+    GreaterThan { path: String, value: f64 },
+}
+
+impl CustomRisks {
+    /// Read a rules file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read custom risks: {}", path.display()))?;
+        Self::from_yaml(&content)
+            .map_err(|e| anyhow!("Invalid custom risks file {}: {:#}", path.display(), e))
+    }
+
+    /// Parse rules from YAML text.
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let mut rules: CustomRisks = serde_yaml::from_str(yaml)?;
+        rules.digest = cache::digest(yaml.as_bytes());
+        for (i, risk) in rules.risks.iter().enumerate() {
+            if risk.code.trim().is_empty() {
+                bail!("risks[{}]: code must not be empty", i);
+            }
+            if !RISK_WEIGHTS.iter().any(|(s, _)| *s == risk.severity) {
+                bail!(
+                    "risks[{}] ({}): unknown severity '{}' (expected critical, high, medium or low)",
+                    i,
+                    risk.code,
+                    risk.severity
+                );
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Flags for every rule whose condition holds for `spec`.
+    pub fn evaluate(&self, spec: &Value) -> Vec<RiskFlag> {
+        self.risks
+            .iter()
+            .filter(|risk| risk.when.holds(spec))
+            .map(|risk| RiskFlag {
+                severity: risk.severity.clone(),
+                code: risk.code.trim().to_uppercase(),
+                message: risk.message.clone(),
+                recommendation: risk.recommendation.clone(),
+            })
+            .collect()
+    }
+
+    /// A digest of the rules' YAML: their version, for caching plans they
+    /// went into. Empty for no rules.
+    pub fn digest(&self) -> &str {
+        &self.digest
+    }
+
+    /// The section declared by the rule raising `code`.
+    pub fn section_of(&self, code: &str) -> Option<String> {
+        self.risks
+            .iter()
+            .find(|risk| risk.code.trim().eq_ignore_ascii_case(code))
+            .and_then(|risk| risk.section.clone())
+    }
+}
+
+impl TryFrom<Value> for Condition {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, String> {
+        let Some((op, arg)) = value
+            .as_object()
+            .filter(|map| map.len() == 1)
+            .and_then(|map| map.iter().next())
+        else {
+            return Err(format!(
+                "a condition must be a single-key mapping, got {}",
+                value
+            ));
+        };
+        let conditions = |arg: &Value| -> Result<Vec<Condition>, String> {
+            match arg {
+                Value::Array(items) => items.iter().cloned().map(Condition::try_from).collect(),
+                _ => Err(format!("'{}' takes a list of conditions", op)),
+            }
+        };
+        let path = |arg: &Value| -> Result<String, String> {
+            arg.as_str()
+                .map(str::to_string)
+                .ok_or_else(|| format!("'{}' takes a path", op))
+        };
+        let operand = |arg: &Value| -> Result<(String, Value), String> {
+            match (arg.get("path").and_then(|p| p.as_str()), arg.get("value")) {
+                (Some(path), Some(value)) if arg.as_object().is_some_and(|m| m.len() == 2) => {
+                    Ok((path.to_string(), value.clone()))
+                }
+                _ => Err(format!("'{}' takes {{ path, value }}", op)),
+            }
+        };
+        let number = |value: Value| -> Result<f64, String> {
+            value
+                .as_f64()
+                .ok_or_else(|| format!("'{}' compares against a number", op))
+        };
+
+        Ok(match op.as_str() {
+            "all" => Condition::All(conditions(arg)?),
+            "any" => Condition::Any(conditions(arg)?),
+            "not" => Condition::Not(Box::new(Condition::try_from(arg.clone())?)),
+            "exists" => Condition::Exists(path(arg)?),
+            "missing" => Condition::Missing(path(arg)?),
+            "equals" => {
+                let (path, value) = operand(arg)?;
+                Condition::Equals { path, value }
+            }
+            "less_than" => {
+                let (path, value) = operand(arg)?;
+                Condition::LessThan {
+                    path,
+                    value: number(value)?,
+                }
+            }
+            "greater_than" => {
+                let (path, value) = operand(arg)?;
+                Condition::GreaterThan {
+                    path,
+                    value: number(value)?,
+                }
+            }
+            other => {
+                return Err(format!(
+                    "unknown condition '{}' (expected all, any, not, exists, missing, equals, less_than or greater_than)",
+                    other
+                ))
+            }
+        })
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, spec: &Value) -> bool {
+        self.holds_at(spec, spec)
+    }
+
+    /// Whether the condition holds at `node` of `spec`: paths are relative
+    /// to `node`, or to `spec` when they start with `$`.
+    pub fn holds_at(&self, spec: &Value, node: &Value) -> bool {
+        let select = |path: &str| -> Vec<&Value> {
+            match path.strip_prefix('$') {
+                Some(path) => select(spec, path),
+                None => select(node, path),
+            }
+            .into_iter()
+            .map(|selected| selected.value)
+            .collect()
+        };
+        match self {
+            Condition::All(conditions) => conditions.iter().all(|c| c.holds_at(spec, node)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.holds_at(spec, node)),
+            Condition::Not(condition) => !condition.holds_at(spec, node),
+            Condition::Exists(path) => select(path).iter().any(|v| !v.is_null()),
+            Condition::Missing(path) => select(path).iter().all(|v| v.is_null()),
+            Condition::Equals { path, value } => select(path).iter().any(|v| same_value(v, value)),
+            Condition::LessThan { path, value } => select(path)
+                .iter()
+                .any(|v| v.as_f64().is_some_and(|n| n < *value)),
+            Condition::GreaterThan { path, value } => select(path)
+                .iter()
+                .any(|v| v.as_f64().is_some_and(|n| n > *value)),
+        }
+    }
+}
+
+/// A value picked out by a path, with where it was found.
+pub(crate) struct Selected<'a> {
+    /// Its dotted path from where the selection started (`rules[0].field`).
+    pub path: String,
+    /// The mapping key or list index it sits at.
+    pub key: String,
+    pub value: &'a Value,
+}
+
+/// Every value at `path`, expanding wildcards.
+pub(crate) fn select<'a>(spec: &'a Value, path: &str) -> Vec<Selected<'a>> {
+    let mut current = vec![Selected {
+        path: String::new(),
+        key: String::new(),
+        value: spec,
+    }];
+    for step in steps(path) {
+        current = current
+            .into_iter()
+            .flat_map(|selected| -> Vec<Selected> {
+                let at_key = |key: &str, value| Selected {
+                    path: match selected.path.as_str() {
+                        "" => key.to_string(),
+                        parent => format!("{}.{}", parent, key),
+                    },
+                    key: key.to_string(),
+                    value,
+                };
+                let at_index = |i: usize, value| Selected {
+                    path: format!("{}[{}]", selected.path, i),
+                    key: i.to_string(),
+                    value,
+                };
+                match (&step, selected.value) {
+                    (Step::Key(key), Value::Object(map)) => {
+                        map.get(key).map(|v| at_key(key, v)).into_iter().collect()
+                    }
+                    (Step::Index(i), Value::Array(items)) => {
+                        items.get(*i).map(|v| at_index(*i, v)).into_iter().collect()
+                    }
+                    (Step::All, Value::Array(items)) => items
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| at_index(i, v))
+                        .collect(),
+                    (Step::All, Value::Object(map)) => {
+                        map.iter().map(|(key, v)| at_key(key, v)).collect()
+                    }
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+    current
+}
+
+enum Step {
+    Key(String),
+    Index(usize),
+    All,
+}
+
+/// `blocking.keys[*].field` -> `blocking`, `keys`, all, `field`
+fn steps(path: &str) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let (key, indexes) = segment
+            .split_once('[')
+            .map_or((segment, ""), |(k, rest)| (k, rest));
+        match key {
+            "" => {}
+            "*" => steps.push(Step::All),
+            key => steps.push(Step::Key(key.to_string())),
+        }
+        for index in indexes.split('[').map(|i| i.trim_end_matches(']')) {
+            match index {
+                "" => {}
+                "*" => steps.push(Step::All),
+                i => steps.push(
+                    i.parse()
+                        .map(Step::Index)
+                        .unwrap_or(Step::Key(i.to_string())),
+                ),
+            }
+        }
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_custom_risks_flag_spec_policies() {
+        let rules = crate::CustomRisks::from_yaml(
+            r#"
+risks:
+  - code: no_country_blocking
+    severity: high
+    message: Specs must block on country
+    recommendation: Add a blocking key on country
+    when:
+      not:
+        equals: { path: "blocking.keys[*].field", value: country }
+  - code: LOOSE_MATCH
+    severity: medium
+    message: Match threshold below policy
+    when:
+      any:
+        - less_than: { path: decision.thresholds.match, value: 0.95 }
+        - missing: decision.thresholds
+  - code: CRM_ONLY
+    severity: low
+    message: Never raised for this spec
+    when:
+      all:
+        - exists: "sources[*].system"
+        - equals: { path: "sources[*].system", value: hubspot }
+"#,
+        )
+        .unwrap();
+        let options = crate::PlanOptions {
+            custom_risks: rules,
+            ..Default::default()
+        };
+
+        let plan = crate::generate_plan_with(MINIMAL, &options).unwrap();
+        let custom: Vec<(&str, &str)> = plan
+            .risk_flags
+            .iter()
+            .filter(|f| {
+                ["NO_COUNTRY_BLOCKING", "LOOSE_MATCH", "CRM_ONLY"].contains(&f.code.as_str())
+            })
+            .map(|f| (f.code.as_str(), f.severity.as_str()))
+            .collect();
+        assert_eq!(
+            custom,
+            [("NO_COUNTRY_BLOCKING", "high"), ("LOOSE_MATCH", "medium")]
+        );
+        let builtin = crate::generate_plan(MINIMAL).unwrap();
+        assert_eq!(plan.risk_score, builtin.risk_score + 14);
+
+        // Custom flags are satisfied, waived and scored like built-in ones.
+        let blocked = MINIMAL.to_string()
+        + "blocking:\n  keys:\n    - field: country\nwaivers:\n  - code: LOOSE_MATCH\n    reason: pilot\n";
+        let plan = crate::generate_plan_with(&blocked, &options).unwrap();
+        assert!(!plan
+            .risk_flags
+            .iter()
+            .any(|f| f.code == "NO_COUNTRY_BLOCKING" || f.code == "LOOSE_MATCH"));
+        assert!(plan.waived.iter().any(|w| w.code == "LOOSE_MATCH"));
+
+        let bad = crate::CustomRisks::from_yaml(
+        "risks:\n  - code: X\n    severity: severe\n    message: m\n    when:\n      exists: rules\n",
+    );
+        assert!(bad
+            .unwrap_err()
+            .to_string()
+            .contains("unknown severity 'severe'"));
+        assert!(crate::CustomRisks::from_yaml("risks:\n  - code: X\n    severity: low\n    message: m\n    when:\n      matches: rules\n").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! The execution DAG of a plan, for diagrams.
+//!
+//! Stages are joined by the datasets they pass: an edge runs from the last
+//! earlier stage writing a dataset to each stage reading it. Datasets no
+//! stage writes (the sources, other entities' tables, the previous run's
+//! clusters) are input nodes; datasets no later stage reads are output
+//! nodes. Each match rule is a node of its own between its match stage and
+//! the stages reading the scores, so the fan-in to scoring shows.
+//!
+//! `kanoniv plan --format dot` and `--format mermaid` render it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::commands::plan::{ExecutionStage, MatchStrategySummary, PlanResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Input,
+    Stage,
+    Rule,
+    Output,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DagNode {
+    /// `input_0`, `stage_3`, `rule_1`, `output_2`: safe as a DOT or Mermaid id.
+    pub id: String,
+    /// Lines of text to draw in the node.
+    pub label: Vec<String>,
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DagEdge {
+    pub from: String,
+    pub to: String,
+    /// The dataset passed between two stages; `None` into a rule, from an
+    /// input or to an output node, which name their dataset themselves.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dataset: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Dag {
+    pub nodes: Vec<DagNode>,
+    pub edges: Vec<DagEdge>,
+}
+
+impl Dag {
+    pub fn from_plan(plan: &PlanResult) -> Self {
+        Self::build(&plan.execution_stages, &plan.match_strategies)
+    }
+
+    fn build(stages: &[ExecutionStage], rules: &[MatchStrategySummary]) -> Self {
+        let mut dag = Dag::default();
+        let stage_id = |i: usize| format!("stage_{}", stages[i].stage);
+        let mut inputs: Vec<&str> = Vec::new();
+        let mut outputs: Vec<&str> = Vec::new();
+
+        for (i, stage) in stages.iter().enumerate() {
+            dag.node(
+                stage_id(i),
+                vec![format!("{}. {}", stage.stage, stage.name)],
+                NodeKind::Stage,
+            );
+        }
+
+        for (i, stage) in stages.iter().enumerate() {
+            for dataset in &stage.inputs {
+                let writer = stages[..i]
+                    .iter()
+                    .rposition(|s| s.outputs.contains(dataset));
+                let Some(writer) = writer else {
+                    let id = format!("input_{}", index_of(&mut inputs, dataset));
+                    if !dag.nodes.iter().any(|n| n.id == id) {
+                        dag.node(id.clone(), vec![dataset.clone()], NodeKind::Input);
+                    }
+                    dag.edge(id, stage_id(i), None);
+                    continue;
+                };
+                let scoring: Vec<usize> = match match_type(dataset) {
+                    Some(kind) => (0..rules.len())
+                        .filter(|&r| scores(&rules[r].match_type) == kind)
+                        .collect(),
+                    None => Vec::new(),
+                };
+                if scoring.is_empty() {
+                    dag.edge(stage_id(writer), stage_id(i), Some(dataset));
+                }
+                for r in scoring {
+                    let id = format!("rule_{}", r);
+                    if !dag.nodes.iter().any(|n| n.id == id) {
+                        dag.node(id.clone(), rule_label(&rules[r]), NodeKind::Rule);
+                        dag.edge(stage_id(writer), id.clone(), None);
+                    }
+                    dag.edge(id, stage_id(i), Some(dataset));
+                }
+            }
+        }
+
+        for (i, stage) in stages.iter().enumerate() {
+            for dataset in &stage.outputs {
+                let read_later = stages[i + 1..].iter().any(|s| s.inputs.contains(dataset));
+                if !read_later {
+                    let id = format!("output_{}", index_of(&mut outputs, dataset));
+                    if !dag.nodes.iter().any(|n| n.id == id) {
+                        dag.node(id.clone(), vec![dataset.clone()], NodeKind::Output);
+                    }
+                    dag.edge(stage_id(i), id, None);
+                }
+            }
+        }
+        dag
+    }
+
+    fn node(&mut self, id: String, label: Vec<String>, kind: NodeKind) {
+        self.nodes.push(DagNode { id, label, kind });
+    }
+
+    fn edge(&mut self, from: String, to: String, dataset: Option<&String>) {
+        self.edges.push(DagEdge {
+            from,
+            to,
+            dataset: dataset.cloned(),
+        });
+    }
+
+    /// Graphviz source, laid out left to right.
+    pub fn to_dot(&self) -> String {
+        let quote = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph plan {\n  rankdir=LR;\n  node [shape=box];\n");
+        for node in &self.nodes {
+            let shape = match node.kind {
+                NodeKind::Input | NodeKind::Output => ", shape=cylinder",
+                NodeKind::Rule => ", shape=ellipse",
+                NodeKind::Stage => "",
+            };
+            let label: Vec<String> = node.label.iter().map(|line| quote(line)).collect();
+            dot.push_str(&format!(
+                "  {} [label=\"{}\"{}];\n",
+                node.id,
+                label.join("\\n"),
+                shape
+            ));
+        }
+        for edge in &self.edges {
+            match &edge.dataset {
+                Some(dataset) => dot.push_str(&format!(
+                    "  {} -> {} [label=\"{}\"];\n",
+                    edge.from,
+                    edge.to,
+                    quote(dataset)
+                )),
+                None => dot.push_str(&format!("  {} -> {};\n", edge.from, edge.to)),
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// A Mermaid flowchart, laid out left to right.
+    pub fn to_mermaid(&self) -> String {
+        let quote = |text: &str| text.replace('"', "#quot;");
+        let mut mermaid = String::from("flowchart LR\n");
+        for node in &self.nodes {
+            let label: Vec<String> = node.label.iter().map(|line| quote(line)).collect();
+            let (open, close) = match node.kind {
+                NodeKind::Input | NodeKind::Output => ("[(", ")]"),
+                NodeKind::Rule => ("([", "])"),
+                NodeKind::Stage => ("[", "]"),
+            };
+            mermaid.push_str(&format!(
+                "  {}{}\"{}\"{}\n",
+                node.id,
+                open,
+                label.join("<br/>"),
+                close
+            ));
+        }
+        for edge in &self.edges {
+            match &edge.dataset {
+                Some(dataset) => mermaid.push_str(&format!(
+                    "  {} -->|\"{}\"| {}\n",
+                    edge.from,
+                    quote(dataset),
+                    edge.to
+                )),
+                None => mermaid.push_str(&format!("  {} --> {}\n", edge.from, edge.to)),
+            }
+        }
+        mermaid
+    }
+}
+
+/// Position of `dataset` in `seen`, adding it if new.
+fn index_of<'a>(seen: &mut Vec<&'a str>, dataset: &'a str) -> usize {
+    match seen.iter().position(|d| *d == dataset) {
+        Some(i) => i,
+        None => {
+            seen.push(dataset);
+            seen.len() - 1
+        }
+    }
+}
+
+/// The kind of rules whose scores `dataset` holds, if it holds scores.
+fn match_type(dataset: &str) -> Option<&'static str> {
+    match dataset {
+        "exact_match_scores" => Some("exact"),
+        "fuzzy_match_scores" => Some("fuzzy"),
+        "semantic_match_scores" => Some("semantic"),
+        _ => None,
+    }
+}
+
+/// The kind of scores a rule of `match_type` contributes to.
+fn scores(match_type: &str) -> &'static str {
+    match match_type {
+        "exact" => "exact",
+        "semantic" => "semantic",
+        _ => "fuzzy",
+    }
+}
+
+fn rule_label(rule: &MatchStrategySummary) -> Vec<String> {
+    let method = match (&rule.algorithm, &rule.model) {
+        (_, Some(model)) if rule.match_type == "semantic" => model.clone(),
+        (Some(algorithm), _) => algorithm.clone(),
+        _ => rule.match_type.clone(),
+    };
+    vec![
+        rule.rule_name.clone(),
+        format!("{} ({}, w={})", rule.field, method, rule.weight),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_plan_dag_fans_rules_into_scoring() {
+        use crate::{generate_plan, NodeKind};
+
+        let spec = MINIMAL.replace(
+        "    weight: 1.0\n",
+        "    weight: 1.0\n  - name: email_fuzzy\n    type: fuzzy\n    field: email\n    algorithm: jaro_winkler\n    threshold: 0.9\n    weight: 0.5\n",
+    ) + "execution:\n  mode: incremental\n  delta_column: email\n";
+        let plan = generate_plan(&spec).unwrap();
+        let dag = plan.dag();
+        let node = |id: &str| dag.nodes.iter().find(|n| n.id == id).unwrap();
+        let stage = |name: &str| {
+            let stage = plan
+                .execution_stages
+                .iter()
+                .find(|s| s.name == name)
+                .unwrap();
+            format!("stage_{}", stage.stage)
+        };
+
+        // Both rules feed scoring, each with its own scores.
+        assert_eq!(
+            node("rule_1").label,
+            ["email_fuzzy", "email (jaro_winkler, w=0.5)"]
+        );
+        let into_scoring: Vec<(&str, Option<&str>)> = dag
+            .edges
+            .iter()
+            .filter(|e| e.to == stage("Score & decide"))
+            .map(|e| (e.from.as_str(), e.dataset.as_deref()))
+            .collect();
+        assert_eq!(
+            into_scoring,
+            [
+                ("rule_0", Some("exact_match_scores")),
+                ("rule_1", Some("fuzzy_match_scores"))
+            ]
+        );
+        assert!(dag
+            .edges
+            .iter()
+            .any(|e| e.from == stage("Fuzzy matches") && e.to == "rule_1"));
+
+        // The previous run's clusters come in from outside; inputs are only
+        // read and outputs only written.
+        let inputs: Vec<&str> = dag
+            .nodes
+            .iter()
+            .filter(|n| n.kind == NodeKind::Input)
+            .map(|n| n.label[0].as_str())
+            .collect();
+        assert_eq!(inputs, ["crm", "entity_clusters"]);
+        for edge in &dag.edges {
+            assert_ne!(node(&edge.to).kind, NodeKind::Input);
+            assert_ne!(node(&edge.from).kind, NodeKind::Output);
+        }
+
+        let mermaid = dag.to_mermaid();
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("  rule_1([\"email_fuzzy<br/>email (jaro_winkler, w=0.5)\"])\n"));
+        assert!(mermaid.contains(&format!(
+            "  rule_1 -->|\"fuzzy_match_scores\"| {}\n",
+            stage("Score & decide")
+        )));
+        let dot = dag.to_dot();
+        assert!(dot.contains(
+            "  rule_1 [label=\"email_fuzzy\\nemail (jaro_winkler, w=0.5)\", shape=ellipse];\n"
+        ));
+        assert_eq!(
+            serde_json::to_value(&dag).unwrap()["nodes"][0]["kind"],
+            "stage"
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! Deletion: how a source record's deletion reaches clusters and golden
+//! records.
+//!
+//! Sources mark deleted records with a tombstone attribute instead of
+//! dropping the rows, so every run sees the deletion:
+//!
+//! ```yaml
+//! deletion:
+//!   tombstone: deleted_at     # attribute set on deleted records
+//!   scope: record             # record (default) or entity
+//!   purge_after_days: 30      # how long tombstones are kept downstream
+//! ```
+//!
+//! A record is deleted when its tombstone holds a value other than empty,
+//! `false` or `0`: a deletion time, or `true`. With `record` scope the
+//! record is dropped before matching, so its cluster is resolved from the
+//! remaining members and links that ran only through it break; a golden
+//! record left without members is deleted. With `entity` scope (the right
+//! to be forgotten) every entity holding a deleted record is erased, with
+//! its golden record and all its members.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+pub const SCOPES: &[&str] = &["record", "entity"];
+
+/// Tombstone values that leave a record live, compared lowercased.
+pub const LIVE_VALUES: &[&str] = &["", "false", "0"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletionScope {
+    /// Drop the deleted record; the rest of its cluster is re-resolved.
+    #[default]
+    Record,
+    /// Erase every entity holding a deleted record.
+    Entity,
+}
+
+impl DeletionScope {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeletionScope::Record => "record",
+            DeletionScope::Entity => "entity",
+        }
+    }
+}
+
+impl FromStr for DeletionScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "record" => Ok(DeletionScope::Record),
+            "entity" => Ok(DeletionScope::Entity),
+            _ => bail!("Unknown deletion scope '{}'. Use {}", s, SCOPES.join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for DeletionScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The `deletion` section.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deletion {
+    /// Attribute set on deleted records.
+    pub tombstone: String,
+    #[serde(default)]
+    pub scope: DeletionScope,
+    /// Days tombstones are kept so downstream tables see the deletion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purge_after_days: Option<u64>,
+}
+
+impl Deletion {
+    /// Read `deletion` from a parsed spec; `None` if the spec has none.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(section) = spec.get("deletion").filter(|d| !d.is_null()) else {
+            return Ok(None);
+        };
+        if !section.is_object() {
+            bail!("deletion must be a mapping, got {}", section);
+        }
+        let tombstone = match section.get("tombstone") {
+            Some(Value::String(attribute)) if !attribute.is_empty() => attribute.clone(),
+            Some(other) => bail!(
+                "deletion.tombstone must be an attribute name, got {}",
+                other
+            ),
+            None => bail!("deletion.tombstone is required"),
+        };
+        let scope = match section.get("scope") {
+            None | Some(Value::Null) => DeletionScope::default(),
+            Some(Value::String(name)) => name.parse()?,
+            Some(other) => bail!("deletion.scope must be a name, got {}", other),
+        };
+        let purge_after_days = match section.get("purge_after_days") {
+            None => None,
+            Some(days) => match days.as_u64() {
+                Some(days) if days > 0 => Some(days),
+                _ => bail!(
+                    "deletion.purge_after_days must be a positive number of days, got {}",
+                    days
+                ),
+            },
+        };
+        Ok(Some(Deletion {
+            tombstone,
+            scope,
+            purge_after_days,
+        }))
+    }
+
+    /// Whether a record whose tombstone holds `value` is deleted.
+    pub fn is_deleted(value: Option<&str>) -> bool {
+        value.is_some_and(|v| !LIVE_VALUES.contains(&v.trim().to_lowercase().as_str()))
+    }
+
+    /// What a run does with deleted records, for the plan.
+    pub fn description(&self) -> String {
+        let what = match self.scope {
+            DeletionScope::Record => format!(
+                "Drop records with {} set before matching; their clusters are re-resolved from the remaining members",
+                self.tombstone
+            ),
+            DeletionScope::Entity => format!(
+                "Erase every entity holding a record with {} set, with its golden record and members",
+                self.tombstone
+            ),
+        };
+        match self.purge_after_days {
+            Some(days) => format!("{}; tombstones are purged after {} days", what, days),
+            None => what,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, AS_OF, MINIMAL, PRIVACY};
+    use crate::{diagnose_yaml, Ir};
+
+    #[test]
+    fn test_deletion_propagates_tombstones() {
+        use crate::deletion::{Deletion, DeletionScope};
+        use crate::{
+            generate_kafka, generate_pyspark, generate_sql, golden_records, Dialect, Sample,
+        };
+
+        let tombstoned = MINIMAL.replace(
+            "      email: email\n",
+            "      email: email\n      deleted_at: deleted_at\n",
+        );
+        let spec = format!(
+            "{}{}deletion:\n  tombstone: deleted_at\n  purge_after_days: 30\n",
+            tombstoned, PRIVACY
+        );
+        assert!(
+            diagnose_yaml(&spec).is_empty(),
+            "{:?}",
+            diagnose_yaml(&spec)
+        );
+        assert!(Deletion::is_deleted(Some("2026-03-01")));
+        assert!(Deletion::is_deleted(Some("TRUE")));
+        assert!(!Deletion::is_deleted(Some("false")));
+        assert!(!Deletion::is_deleted(Some(" 0 ")));
+        assert!(!Deletion::is_deleted(None));
+
+        let ir = compiled_ir(&spec);
+        let deletion = ir.deletion.clone().unwrap();
+        assert_eq!(
+            (
+                deletion.tombstone.as_str(),
+                deletion.scope,
+                deletion.purge_after_days
+            ),
+            ("deleted_at", DeletionScope::Record, Some(30))
+        );
+        let recompiled = Ir::from_value(&crate::compile_to_ir(&ir.to_spec()).unwrap()).unwrap();
+        assert_eq!(recompiled.deletion, ir.deletion);
+
+        // Malformed sections, unknown tombstones and unmapped sources.
+        let findings = |yaml: &str| -> Vec<(String, String)> {
+            diagnose_yaml(yaml)
+                .into_iter()
+                .map(|d| (d.code.to_string(), d.path.unwrap_or_default()))
+                .collect()
+        };
+        let finding = |code: &str, path: &str| (code.to_string(), path.to_string());
+        assert_eq!(
+            findings(&spec.replace("purge_after_days: 30", "purge_after_days: -1")),
+            [finding("KNV0128", "deletion")]
+        );
+        assert_eq!(
+            findings(&spec.replace("tombstone: deleted_at", "tombstone: removed_at")),
+            [finding("KNV0101", "deletion.tombstone")]
+        );
+        let billing = spec.replace(
+        "rules:\n",
+        "  - name: billing\n    system: stripe\n    table: accounts\n    id: account_id\n    attributes:\n      email: email\nrules:\n",
+    );
+        assert!(findings(&billing).contains(&finding("KNV0129", "sources[1].attributes")));
+
+        // Record scope drops tombstoned records before matching.
+        let plan = crate::generate_plan(&spec).unwrap();
+        let stage = &plan.execution_stages[1];
+        assert_eq!(
+            (stage.name.as_str(), stage.outputs.as_slice()),
+            (
+                "Apply tombstones",
+                ["normalized_entities".to_string(), "tombstones".to_string()].as_slice()
+            )
+        );
+        assert!(stage.description.contains("purged after 30 days"));
+        assert!(plan
+            .execution_stages
+            .last()
+            .unwrap()
+            .inputs
+            .contains(&"tombstones".to_string()));
+        assert!(plan
+            .summary
+            .contains("Deletion:     deleted_at (record scope, purged after 30 days)"));
+        let sql = generate_sql(&ir, Dialect::Postgres).unwrap();
+        assert!(sql.contains("FROM contacts\nWHERE COALESCE(LOWER(TRIM(CAST(deleted_at AS TEXT))), '') IN ('', 'false', '0')"));
+        assert!(generate_pyspark(&ir)
+            .unwrap()
+            .contains("result = result.where("));
+        assert!(generate_kafka(&ir)
+            .unwrap()
+            .contains("        deleted = [record_key]\n"));
+
+        // Entity scope erases the clusters holding one after clustering.
+        let erasing = spec.replace("  purge_after_days: 30\n", "  scope: entity\n");
+        let plan = crate::generate_plan(&erasing).unwrap();
+        let names: Vec<&str> = plan
+            .execution_stages
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(
+            names[5..8],
+            ["Cluster entities", "Erase entities", "Apply survivorship"]
+        );
+        let erasing_ir = compiled_ir(&erasing);
+        let sql = generate_sql(&erasing_ir, Dialect::Postgres).unwrap();
+        assert!(sql.contains("WHERE c.cluster_id NOT IN ("));
+        assert!(!sql.contains("FROM contacts\nWHERE"));
+        assert!(generate_pyspark(&erasing_ir)
+            .unwrap()
+            .contains("clusters = erase_entities(entities, clusters)"));
+        assert!(generate_kafka(&erasing_ir)
+            .unwrap()
+            .contains("deleted = store.members(record_key)"));
+
+        // Golden records leave deleted members, or their entities, out.
+        let members = Sample::from_csv(
+        "cluster_id,source_name,record_key,email,deleted_at\ne1,crm,a,ann@x.com,2026-03-01\ne1,crm,b,ann@y.com,\ne2,crm,c,cy@x.com,false\ne3,crm,d,dee@x.com,true\n",
+    )
+    .unwrap();
+        let golden = golden_records(&spec, &members, AS_OF).unwrap();
+        let entities: Vec<&str> = golden
+            .records
+            .iter()
+            .map(|r| r.entity_id.as_str())
+            .collect();
+        assert_eq!(
+            (entities.as_slice(), golden.deleted),
+            (["e1", "e2"].as_slice(), 2)
+        );
+        assert_eq!(golden.records[0].values[1], Some("ann@y.com".to_string()));
+        let golden = golden_records(&erasing, &members, AS_OF).unwrap();
+        let entities: Vec<&str> = golden
+            .records
+            .iter()
+            .map(|r| r.entity_id.as_str())
+            .collect();
+        assert_eq!(
+            (entities.as_slice(), golden.deleted),
+            (["e2"].as_slice(), 3)
+        );
+    }
+}
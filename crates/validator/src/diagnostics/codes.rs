@@ -0,0 +1,769 @@
+//! Stable diagnostic codes.
+//!
+//! Codes never change meaning once released, so CI filters and suppressions
+//! can key on them instead of message text. `KNV00xx` are schema checks,
+//! `KNV01xx` semantic checks (errors and advice) and `KNV09xx` input errors.
+
+pub const MISSING_FIELD: &str = "KNV0001";
+pub const INVALID_API_VERSION: &str = "KNV0002";
+pub const TOO_MANY_RULES: &str = "KNV0003";
+pub const OUT_OF_RANGE: &str = "KNV0004";
+pub const TOO_MANY_SOURCES: &str = "KNV0005";
+pub const TOO_MANY_BLOCKING_KEYS: &str = "KNV0006";
+pub const INVALID_NORMALIZATION: &str = "KNV0007";
+pub const INVALID_ATTRIBUTE: &str = "KNV0008";
+pub const INVALID_MAPPING: &str = "KNV0009";
+pub const INVALID_POLICY: &str = "KNV0010";
+pub const UNKNOWN_FIELD: &str = "KNV0101";
+pub const DUPLICATE_RULE: &str = "KNV0102";
+pub const DUPLICATE_SOURCE: &str = "KNV0103";
+pub const THRESHOLD_ORDER: &str = "KNV0104";
+pub const UNUSED_ATTRIBUTE: &str = "KNV0105";
+pub const ZERO_WEIGHT_RULE: &str = "KNV0106";
+pub const FUZZY_WITHOUT_ALGORITHM: &str = "KNV0107";
+pub const MISSING_THRESHOLDS: &str = "KNV0108";
+pub const INVALID_WAIVER: &str = "KNV0109";
+pub const EXPIRED_WAIVER: &str = "KNV0110";
+pub const UNKNOWN_ALGORITHM: &str = "KNV0111";
+pub const INVALID_HASHING: &str = "KNV0112";
+pub const INVALID_SEMANTIC_RULE: &str = "KNV0113";
+pub const INVALID_LSH: &str = "KNV0114";
+pub const INVALID_CLUSTERING: &str = "KNV0115";
+pub const INVALID_SURVIVORSHIP: &str = "KNV0116";
+pub const INVALID_EXPRESSION: &str = "KNV0117";
+pub const INVALID_TEMPORAL: &str = "KNV0118";
+pub const INVALID_EXECUTION: &str = "KNV0119";
+pub const DUPLICATE_ENTITY: &str = "KNV0120";
+pub const INVALID_RELATIONSHIP: &str = "KNV0121";
+pub const PARTIAL_COVERAGE: &str = "KNV0122";
+pub const TYPE_MISMATCH: &str = "KNV0123";
+pub const INVALID_STEWARDSHIP: &str = "KNV0124";
+pub const INVALID_PRIVACY: &str = "KNV0125";
+pub const PII_WITHOUT_MASKING: &str = "KNV0126";
+pub const PII_WITHOUT_RETENTION: &str = "KNV0127";
+pub const INVALID_DELETION: &str = "KNV0128";
+pub const UNMAPPED_TOMBSTONE: &str = "KNV0129";
+pub const INVALID_ENCODING: &str = "KNV0130";
+pub const ENCODED_RULE_MISMATCH: &str = "KNV0131";
+pub const POLICY_DENIAL: &str = "KNV0132";
+pub const INCONSISTENT_SOURCE_SYSTEM: &str = "KNV0133";
+pub const IDENTITY_VERSION_REUSE: &str = "KNV0134";
+pub const UNKNOWN_TRANSFORM: &str = "KNV0135";
+pub const YAML_SYNTAX: &str = "KNV0901";
+pub const IR_HASH_MISMATCH: &str = "KNV0902";
+pub const UNREADABLE_SPEC: &str = "KNV0903";
+
+#[derive(Debug, Clone, Copy)]
+pub struct CodeInfo {
+    pub code: &'static str,
+    /// Short kebab-case name, accepted by `kanoniv explain` as an alias.
+    pub name: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+pub const ALL: &[CodeInfo] = &[
+    CodeInfo {
+        code: MISSING_FIELD,
+        name: "missing-field",
+        title: "A required field is missing",
+        explanation: "\
+Every spec needs `api_version`, `identity_version` and `entity.name`; every
+source needs `name`, `system`, `table`, `id` and `attributes`; every rule
+needs `name` and `type`.
+
+    sources:
+      - name: crm
+        system: salesforce
+        table: contacts
+        id: contact_id        # required
+        attributes:
+          email: email",
+    },
+    CodeInfo {
+        code: INVALID_API_VERSION,
+        name: "invalid-api-version",
+        title: "api_version is not of the form kanoniv/v<N>",
+        explanation: "\
+`api_version` selects the spec format and must look like `kanoniv/v2`.",
+    },
+    CodeInfo {
+        code: TOO_MANY_RULES,
+        name: "too-many-rules",
+        title: "More than 50 match rules",
+        explanation: "\
+A spec may declare at most 50 rules. Large rule sets are usually better
+expressed as fewer weighted rules over normalized fields.",
+    },
+    CodeInfo {
+        code: OUT_OF_RANGE,
+        name: "out-of-range",
+        title: "A rule weight or threshold is outside 0..1",
+        explanation: "\
+Rule `weight` and `threshold` are fractions and must be between 0 and 1
+inclusive.
+
+    rules:
+      - name: name_fuzzy
+        type: fuzzy
+        threshold: 0.85       # not 85
+        weight: 0.5",
+    },
+    CodeInfo {
+        code: TOO_MANY_SOURCES,
+        name: "too-many-sources",
+        title: "More than 10 sources",
+        explanation: "\
+A spec may declare at most 10 sources. Union similar tables upstream and
+declare them as one source.",
+    },
+    CodeInfo {
+        code: TOO_MANY_BLOCKING_KEYS,
+        name: "too-many-blocking-keys",
+        title: "More than 5 blocking keys",
+        explanation: "\
+At most 5 blocking keys are allowed; each extra key adds a full candidate
+generation pass.",
+    },
+    CodeInfo {
+        code: INVALID_NORMALIZATION,
+        name: "invalid-normalization",
+        title: "The normalization section is malformed",
+        explanation: "\
+`normalization.fields` maps attributes to lists of normalizers, applied in
+order: trim, lower, upper, nfkc, casefold, strip_diacritics,
+remove_honorifics, expand_nicknames, soundex, metaphone, phone or address,
+or a `regex_replace` step whose `pattern` must be a valid regular
+expression. `locale` (en, es, fr, de) picks the nickname and honorific
+tables; `default_region`, an ISO 3166-1 alpha-2 code, is the region of
+phone numbers written without a country code.
+
+    normalization:
+      locale: en
+      default_region: US
+      fields:
+        first_name: [nfkc, casefold, remove_honorifics, expand_nicknames]
+        phone: [phone]
+        address: [address]
+        company:
+          - trim
+          - regex_replace: { pattern: '\\s+(inc|llc)$', replacement: '' }",
+    },
+    CodeInfo {
+        code: INVALID_ATTRIBUTE,
+        name: "invalid-attribute",
+        title: "A source attribute mapping is malformed",
+        explanation: "\
+A source maps each attribute to the column holding it, either as the column
+name or as a mapping with `column` and an optional `type`: string, email,
+phone, date, numeric or identifier.
+
+    sources:
+      - name: crm
+        attributes:
+          email: email_address
+          dob:
+            column: date_of_birth
+            type: date",
+    },
+    CodeInfo {
+        code: INVALID_MAPPING,
+        name: "invalid-mapping",
+        title: "A column mapping is malformed or ambiguous",
+        explanation: "\
+`mappings` maps canonical attributes to the source columns holding them,
+matched case-insensitively. A column may stand for one attribute only, and
+a source may hold each attribute in one column.
+
+    mappings:
+      email: [email_addr, EMAIL]
+    sources:
+      - name: crm
+        attributes: [email_addr, first_name]",
+    },
+    CodeInfo {
+        code: INVALID_POLICY,
+        name: "invalid-policy",
+        title: "The policy section is malformed",
+        explanation: "\
+`policy` maps diagnostic codes (or their names) and risk flags to the level
+they are reported at: error, warning, info or ignore. Errors are never
+demoted; advice promoted to `error` fails validation, and risk flags at
+`error` fail `kanoniv plan`. A `[policy]` table in `.kanoniv.toml` sets the
+same levels for every spec below it.
+
+    policy:
+      LOW_THRESHOLD: error
+      SINGLE_SOURCE: ignore
+      unused-attribute: info",
+    },
+    CodeInfo {
+        code: UNKNOWN_FIELD,
+        name: "unknown-field",
+        title: "A rule references a field no source provides",
+        explanation: "\
+Rule `field` values must be canonical attribute names declared under some
+source's `attributes` map (the keys, not the source column names).
+
+    sources:
+      - name: crm
+        attributes:
+          email: email_address   # canonical `email`
+    rules:
+      - name: email_exact
+        field: email             # not `email_address`",
+    },
+    CodeInfo {
+        code: DUPLICATE_RULE,
+        name: "duplicate-rule",
+        title: "Two rules share a name",
+        explanation: "\
+Rule names identify rules in plans, diffs and scores, so they must be
+unique within a spec.",
+    },
+    CodeInfo {
+        code: DUPLICATE_SOURCE,
+        name: "duplicate-source",
+        title: "Two sources share a name",
+        explanation: "\
+Source names are referenced by survivorship `source_priority` and must be
+unique within a spec.",
+    },
+    CodeInfo {
+        code: THRESHOLD_ORDER,
+        name: "threshold-order",
+        title: "Decision thresholds are out of order",
+        explanation: "\
+Decision thresholds must satisfy `match >= review >= reject`; otherwise
+some scores would fall into no band.
+
+    decision:
+      thresholds:
+        match: 0.9
+        review: 0.7
+        reject: 0.3
+
+Canopy blocking thresholds are ordered the same way: `loose` must not be
+above `tight`.",
+    },
+    CodeInfo {
+        code: UNUSED_ATTRIBUTE,
+        name: "unused-attribute",
+        title: "A source attribute is never used (info)",
+        explanation: "\
+The attribute is mapped by a source but no rule, blocking key or
+survivorship rule refers to it. It is still carried into golden records;
+remove the mapping if it is not needed downstream.",
+    },
+    CodeInfo {
+        code: ZERO_WEIGHT_RULE,
+        name: "zero-weight-rule",
+        title: "A rule has weight 0 (warning)",
+        explanation: "\
+A rule with `weight: 0` is evaluated but never changes a score. Remove the
+rule or give it a weight.",
+    },
+    CodeInfo {
+        code: FUZZY_WITHOUT_ALGORITHM,
+        name: "fuzzy-without-algorithm",
+        title: "A fuzzy rule does not name an algorithm (warning)",
+        explanation: "\
+Without `algorithm`, each backend uses its own default similarity, so the
+same spec can score differently on Postgres, Snowflake and BigQuery. Pin
+one explicitly:
+
+    rules:
+      - name: name_fuzzy
+        type: fuzzy
+        field: name
+        algorithm: jaro_winkler",
+    },
+    CodeInfo {
+        code: MISSING_THRESHOLDS,
+        name: "missing-thresholds",
+        title: "Rules are defined without decision thresholds (warning)",
+        explanation: "\
+Without `decision.thresholds`, only pairs with a perfect score match and
+everything else is rejected, with no review band. Set explicit bands:
+
+    decision:
+      thresholds:
+        match: 0.9
+        review: 0.7",
+    },
+    CodeInfo {
+        code: INVALID_WAIVER,
+        name: "invalid-waiver",
+        title: "A waiver is missing its code or reason, or has a bad expiry",
+        explanation: "\
+Every waiver must name the finding it suppresses and say why; `expires`,
+if given, is a `YYYY-MM-DD` date. Either form works:
+
+    # kanoniv-ignore: LOW_THRESHOLD reason=\"approved by risk, RISK-123\" expires=2026-12-31
+
+    waivers:
+      - code: LOW_THRESHOLD
+        reason: approved by risk, RISK-123
+        expires: 2026-12-31",
+    },
+    CodeInfo {
+        code: EXPIRED_WAIVER,
+        name: "expired-waiver",
+        title: "A waiver has expired (warning)",
+        explanation: "\
+The waiver's `expires` date has passed, so the finding it covered is
+reported again. Fix the finding, or renew the waiver with a new date and
+reason.",
+    },
+    CodeInfo {
+        code: UNKNOWN_ALGORITHM,
+        name: "unknown-algorithm",
+        title: "A rule names a similarity algorithm that does not exist",
+        explanation: "\
+A rule's `algorithm` must be one the engine implements: jaro_winkler, jaro,
+levenshtein, damerau_levenshtein, trigram, jaccard, cosine, soundex,
+metaphone or double_metaphone.
+
+    rules:
+      - name: name_fuzzy
+        type: fuzzy
+        field: name
+        algorithm: jaro_winkler   # not jarowinkler",
+    },
+    CodeInfo {
+        code: INVALID_HASHING,
+        name: "invalid-hashing",
+        title: "The hashing section is invalid",
+        explanation: "\
+`hashing.algorithm` must be sha256, sha512 or blake3, and at most one of
+`key_env` and `key_file` may name the key. The key itself never belongs in
+the spec.
+
+    hashing:
+      algorithm: blake3
+      key_env: KANONIV_HASH_KEY",
+    },
+    CodeInfo {
+        code: INVALID_SEMANTIC_RULE,
+        name: "invalid-semantic-rule",
+        title: "A semantic rule's embedding configuration is invalid",
+        explanation: "\
+A `semantic` rule compares embeddings, so it needs the embedding `model`,
+the http(s) `endpoint` serving it and the cosine similarity `threshold` at
+which values agree. It takes no `algorithm`.
+
+    rules:
+      - name: company_semantic
+        type: semantic
+        field: company_name
+        model: text-embedding-3-small
+        endpoint: https://api.openai.com/v1/embeddings
+        threshold: 0.88",
+    },
+    CodeInfo {
+        code: INVALID_LSH,
+        name: "invalid-lsh",
+        title: "LSH blocking is misconfigured",
+        explanation: "\
+`blocking.lsh` configures the `lsh` blocking strategy and is only read with
+it. `method` is minhash or simhash; `bands`, `rows` and `shingle_size` are
+whole numbers of at least 1, shingles at most 10 characters, and a
+signature (bands times rows) at most 1024 MinHash or 64 SimHash hashes.
+LSH hashes the values of the blocking keys, so it needs at least one.
+
+    blocking:
+      strategy: lsh
+      lsh:
+        method: minhash
+        bands: 20
+        rows: 5
+        shingle_size: 3
+      keys:
+        - field: company_name",
+    },
+    CodeInfo {
+        code: INVALID_CLUSTERING,
+        name: "invalid-clustering",
+        title: "The clustering section is invalid",
+        explanation: "\
+`clustering.strategy` must be transitive_closure, star,
+correlation_clustering or hierarchical. `threshold`, the average linkage
+at which clusters stop merging, is read by the hierarchical strategy only.
+
+    clustering:
+      strategy: hierarchical
+      threshold: 0.85",
+    },
+    CodeInfo {
+        code: INVALID_SURVIVORSHIP,
+        name: "invalid-survivorship",
+        title: "A survivorship rule is invalid",
+        explanation: "\
+`strategy` must be source_priority, most_recent, most_complete, longest,
+most_frequent, non_null_priority or custom. most_recent may name a
+`timestamp` attribute to order by, non_null_priority needs `fields`, the
+attributes to take the value from in order, and custom needs an
+`expression` scoring each member; the highest score survives. Expressions
+read `value`, `source`, `record_key` and the attributes, and must parse and
+type-check as conditions do (see invalid-expression).
+
+    survivorship:
+      rules:
+        - field: phone
+          strategy: non_null_priority
+          fields: [mobile_phone, phone]
+        - field: email
+          strategy: custom
+          expression: \"source == 'crm' ? 2 : size(value)\"",
+    },
+    CodeInfo {
+        code: INVALID_EXPRESSION,
+        name: "invalid-expression",
+        title: "A condition does not parse or type-check",
+        explanation: "\
+Match rule and survivorship conditions are checked before they run: they
+must parse, read only known names and apply operators and functions to
+the right types (`size(1)` or `value * 2` do not). Every name is a string
+that may be null; use `number()` for arithmetic. Match rule conditions
+read `left.<attribute>` and `right.<attribute>` and survivorship
+conditions `value`, `source`, `record_key` and the attributes. A condition
+must be a bool.
+
+    rules:
+      - name: name_fuzzy
+        type: fuzzy
+        field: name
+        condition: \"left.country == right.country\"",
+    },
+    CodeInfo {
+        code: INVALID_TEMPORAL,
+        name: "invalid-temporal",
+        title: "The temporal section is invalid",
+        explanation: "\
+`temporal` says when each record's values hold. `effective_from` and
+`effective_to` name two different attributes holding ISO 8601 dates (an
+empty `effective_to` marks a current record), `match_window_days` is a
+whole number of days, and each entry under `attributes` is `current`
+(current records survive first) or `history` (every record counts alike).
+
+    temporal:
+      effective_from: valid_from
+      effective_to: valid_to
+      match_window_days: 365
+      attributes:
+        address: history",
+    },
+    CodeInfo {
+        code: INVALID_EXECUTION,
+        name: "invalid-execution",
+        title: "The execution section is invalid",
+        explanation: "\
+`execution.mode` must be full, incremental or streaming. `delta_column`
+names the attribute marking records changed since the last run and is only
+read by incremental runs; `stable_ids` is true or false.
+
+    execution:
+      mode: incremental
+      delta_column: updated_at
+      stable_ids: true",
+    },
+    CodeInfo {
+        code: DUPLICATE_ENTITY,
+        name: "duplicate-entity",
+        title: "Two specs in a workspace share an entity name",
+        explanation: "\
+A file or directory holding several entity specs selects one by its
+`entity.name` (`--entity customer`), so the names must be unique across the
+workspace, and `kanoniv check` holds a repository's specs to the same.
+Give each spec its own entity, or move variants of one entity to files
+that extend a common base.",
+    },
+    CodeInfo {
+        code: INVALID_RELATIONSHIP,
+        name: "invalid-relationship",
+        title: "A relationship is malformed or links to an unknown entity",
+        explanation: "\
+Each relationship needs a unique `name`, the entity it links `to` and at
+least one rule. Rules are `exact` or `fuzzy` and compare an attribute of
+this entity (`field`) with one of the target (`target_field`, the same
+attribute if omitted); `cardinality` is many_to_one, one_to_one or
+many_to_many. In a workspace, `to` must name one of its entities and
+`target_field` one of that entity's attributes.
+
+    relationships:
+      - name: member_of
+        to: household
+        threshold: 0.8
+        rules:
+          - name: address_exact
+            type: exact
+            field: address",
+    },
+    CodeInfo {
+        code: PARTIAL_COVERAGE,
+        name: "partial-coverage",
+        title: "A matched attribute is missing from some sources (info)",
+        explanation: "\
+A rule or blocking key uses an attribute that only some sources map, so
+records from the others never score on the rule or share the key. That is
+expected for an identifier only one system holds; otherwise map the
+attribute in every source that has it.
+
+    sources:
+      - name: crm
+        attributes:
+          phone: phone
+      - name: web
+        attributes:
+          phone: mobile_number   # now compared across both",
+    },
+    CodeInfo {
+        code: TYPE_MISMATCH,
+        name: "type-mismatch",
+        title: "A rule or normalizer does not fit an attribute's type",
+        explanation: "\
+Typed attributes only take rules and normalizers that work on their values:
+string similarities compare characters, so they are meaningless on dates and
+numbers; phonetic, nickname and address normalizers only apply to strings,
+and `phone` only to phones. An exact rule on a free-text `string` attribute
+needs normalization, or records differing in case or spacing never match.
+Sources must agree on each attribute's type.
+
+    sources:
+      - name: crm
+        attributes:
+          last_name: { column: surname, type: string }
+    normalization:
+      fields:
+        last_name: [nfkc, casefold]   # makes the exact rule meaningful
+    rules:
+      - name: last_name_exact
+        type: exact
+        field: last_name",
+    },
+    CodeInfo {
+        code: INVALID_STEWARDSHIP,
+        name: "invalid-stewardship",
+        title: "Stewardship assertions are malformed or contradict each other",
+        explanation: "\
+`always_merge` and `never_merge` entries list at least two record keys under
+`records`; a `locked` entry names a `record`, an attribute as `field` and the
+`value` it is fixed to (null to keep it empty). Records joined by
+`always_merge` entries, directly or through a chain, cannot also be kept
+apart, and a record's field cannot be locked to two values.
+
+    stewardship:
+      always_merge:
+        - records: [crm-1042, billing-88]
+      never_merge:
+        - records: [crm-2210, billing-301]
+          reason: Twins sharing an address",
+    },
+    CodeInfo {
+        code: INVALID_PRIVACY,
+        name: "invalid-privacy",
+        title: "The privacy section is malformed",
+        explanation: "\
+`privacy.retention_days` and each attribute's `retention_days` are a positive
+number of days; `masking` is one of hash, redact, partial or none, and
+`privacy.fields` names attributes the sources declare.
+
+    privacy:
+      retention_days: 730
+      fields:
+        email:
+          masking: hash",
+    },
+    CodeInfo {
+        code: PII_WITHOUT_MASKING,
+        name: "pii-without-masking",
+        title: "An attribute holding PII has no masking (warning)",
+        explanation: "\
+The attribute's name or type marks it as personal data (an email, phone,
+person's name, date of birth or national id), or it is tagged `pii: true`,
+but nothing says how it is masked outside the engine. Set its masking, or
+`none` to keep it as is on purpose; tag it `pii: false` if it is not PII.
+
+    privacy:
+      fields:
+        email:
+          masking: hash   # hash, redact, partial or none",
+    },
+    CodeInfo {
+        code: PII_WITHOUT_RETENTION,
+        name: "pii-without-retention",
+        title: "Attributes holding PII have no retention period (warning)",
+        explanation: "\
+Records holding personal data should not be kept indefinitely. Set how many
+days they are kept, for every attribute or for one:
+
+    privacy:
+      retention_days: 730
+      fields:
+        phone:
+          retention_days: 365",
+    },
+    CodeInfo {
+        code: INVALID_DELETION,
+        name: "invalid-deletion",
+        title: "The deletion section is malformed",
+        explanation: "\
+`deletion.tombstone` names the attribute set on deleted records, which the
+sources must declare; `scope` is record or entity, and `purge_after_days` a
+positive number of days.
+
+    deletion:
+      tombstone: deleted_at
+      scope: entity
+      purge_after_days: 30",
+    },
+    CodeInfo {
+        code: UNMAPPED_TOMBSTONE,
+        name: "unmapped-tombstone",
+        title: "A source has no tombstone column (warning)",
+        explanation: "\
+The source does not map the deletion section's tombstone attribute, so
+records deleted there are never seen as deleted: they keep matching and
+their values keep surviving into golden records. Map the source's
+soft-delete column to the tombstone attribute.
+
+    sources:
+      - name: billing
+        attributes:
+          deleted_at: removed_on",
+    },
+    CodeInfo {
+        code: INVALID_ENCODING,
+        name: "invalid-encoding",
+        title: "The encoding section is malformed",
+        explanation: "\
+`encoding.fields` maps attributes the sources declare to `hash` or `bloom`.
+Encoded attributes need a salt shared with the partner, named by
+`salt_env` or `salt_file`; the salt itself never goes in the spec. A Bloom
+filter's `size` is a multiple of 8 from 64 to 8192 bits, `hashes` from 1 to
+64 and at most size / 8, and `ngram` from 1 to 4.
+
+    encoding:
+      salt_env: PARTNER_SALT
+      fields:
+        ssn: hash
+        full_name: { method: bloom, size: 1024, hashes: 20, ngram: 2 }",
+    },
+    CodeInfo {
+        code: ENCODED_RULE_MISMATCH,
+        name: "encoded-rule-mismatch",
+        title: "A rule or blocking key cannot compare an encoded attribute",
+        explanation: "\
+Digests of `hash`-encoded attributes agree exactly when the values do and
+share nothing otherwise, so only exact rules and untransformed blocking
+keys can compare them. Fuzzy rules compare `bloom`-encoded attributes with
+`algorithm: dice`; no encoded attribute has meaningful embeddings.
+
+    rules:
+      - name: ssn_exact
+        type: exact
+        field: ssn
+      - name: name_bloom
+        type: fuzzy
+        field: full_name
+        algorithm: dice
+        threshold: 0.8",
+    },
+    CodeInfo {
+        code: POLICY_DENIAL,
+        name: "policy-denial",
+        title: "A Rego policy denies or warns about the spec",
+        explanation: "\
+A policy passed to `kanoniv validate --policy` raised this finding from a
+`deny` rule (an error) or a `warn` rule (a warning) in package `kanoniv` or
+beneath it. The message is the policy's own; the policy's owners decide
+what it requires. Findings that name their own `code` are reported under
+it instead.
+
+    package kanoniv
+
+    deny contains msg if {
+        not input.spec.blocking
+        msg := \"specs must declare blocking keys\"
+    }",
+    },
+    CodeInfo {
+        code: INCONSISTENT_SOURCE_SYSTEM,
+        name: "inconsistent-source-system",
+        title: "Specs in a repository name a source's system differently",
+        explanation: "\
+`kanoniv check` compares the sources of a repository's specs. A source
+name given different `system`s in two specs (`crm` as `salesforce` here
+and `hubspot` there), or one system spelled two ways (`Salesforce` and
+`sales_force`), splits lineage and owner routing across what is one
+system. Use one name and one spelling for each system everywhere.",
+    },
+    CodeInfo {
+        code: IDENTITY_VERSION_REUSE,
+        name: "identity-version-reuse",
+        title: "Two different specs share an identity_version",
+        explanation: "\
+`identity_version` names one version of one identity spec: audit trails,
+lineage and published registry versions refer to it. `kanoniv check`
+fails when two specs in a repository declare the same `identity_version`
+but differ. Give each spec, and each variant of a spec, its own version.
+
+    identity_version: customer_emea_v1",
+    },
+    CodeInfo {
+        code: UNKNOWN_TRANSFORM,
+        name: "unknown-transform",
+        title: "A blocking transform is not recognised",
+        explanation: "\
+Blocking keys and sorted-neighbourhood sort keys take an optional
+`transform`. Use identity, lowercase, uppercase, trim, soundex, address,
+street, postcode, geohash, geohash:<precision>, first_<n> or prefix:<n>.
+An unknown transform would block on the raw value.
+
+    blocking:
+      keys:
+        - field: last_name
+          transform: soundex",
+    },
+    CodeInfo {
+        code: YAML_SYNTAX,
+        name: "yaml-syntax",
+        title: "The file is not valid YAML",
+        explanation: "\
+The spec could not be parsed as YAML. The reported line and column point
+at where the parser gave up; the actual mistake (an unclosed bracket or
+quote, or inconsistent indentation) is often just before it.",
+    },
+    CodeInfo {
+        code: IR_HASH_MISMATCH,
+        name: "ir-hash-mismatch",
+        title: "Compiled IR does not match its plan hash",
+        explanation: "\
+`validate --from-ir` recomputes the IR's plan hash. A mismatch means the IR
+was edited after `kanoniv compile`, or truncated on the way. Recompile it
+from the spec instead of editing it by hand.",
+    },
+    CodeInfo {
+        code: UNREADABLE_SPEC,
+        name: "unreadable-spec",
+        title: "A spec file cannot be read or composed",
+        explanation: "\
+`kanoniv check` (or `validate_many`) could not read a spec file, or
+compose it over the base it `extends`: the base file or registry
+version is missing, or the file is not UTF-8. The message gives the cause.",
+    },
+];
+
+/// `code` as findings carry it: a diagnostic code for a code or its name
+/// (`zero-weight-rule` -> `KNV0106`); anything else, a risk flag, uppercased.
+pub fn canonical(code: &str) -> String {
+    lookup(code)
+        .map(|info| info.code.to_string())
+        .unwrap_or_else(|| code.to_uppercase())
+}
+
+/// Look up a code (`KNV0101`, case-insensitive) or its name (`unknown-field`).
+pub fn lookup(code: &str) -> Option<&'static CodeInfo> {
+    ALL.iter()
+        .find(|info| info.code.eq_ignore_ascii_case(code) || info.name == code)
+}
@@ -0,0 +1,48 @@
+//! GitHub Actions workflow commands for findings.
+//!
+//! Each finding becomes an `::error`, `::warning` or `::notice` line, which
+//! a workflow step's output turns into an annotation on the spec's line in
+//! the pull request. Waived findings are left out.
+
+use super::{Diagnostic, Severity, Tiers};
+
+/// One workflow command per finding in the file at `uri`, errors first.
+pub fn annotations(uri: &str, tiers: &Tiers) -> String {
+    tiers
+        .errors
+        .iter()
+        .chain(&tiers.warnings)
+        .chain(&tiers.info)
+        .map(|diagnostic| annotation(uri, diagnostic) + "\n")
+        .collect()
+}
+
+fn annotation(uri: &str, diagnostic: &Diagnostic) -> String {
+    let command = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "notice",
+    };
+    let mut properties = vec![format!("file={}", escape_property(uri))];
+    if let Some(span) = diagnostic.span {
+        properties.push(format!("line={}", span.line));
+        properties.push(format!("col={}", span.column));
+    }
+    properties.push(format!("title={}", escape_property(&diagnostic.code)));
+    format!(
+        "::{} {}::{}",
+        command,
+        properties.join(","),
+        escape_data(&diagnostic.to_string())
+    )
+}
+
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
@@ -0,0 +1,88 @@
+//! JUnit XML rendering of findings.
+//!
+//! CI systems that read test reports (GitLab, Jenkins, Azure Pipelines)
+//! show each error as a failed test case named after its code and path;
+//! warnings are passing cases carrying their message, and a report with
+//! neither holds one passing case so the suite is never empty. Info and
+//! waived findings are left out.
+
+use super::{Diagnostic, Severity, Tiers};
+
+/// A `<testsuites>` document with one suite, `suite`, for the file at `uri`.
+pub fn to_junit(suite: &str, uri: &str, tiers: &Tiers) -> String {
+    let mut cases: Vec<String> = tiers
+        .errors
+        .iter()
+        .chain(&tiers.warnings)
+        .map(|diagnostic| case(uri, diagnostic))
+        .collect();
+    if cases.is_empty() {
+        cases.push(format!(
+            "    <testcase classname=\"{}\" name=\"{}\"/>\n",
+            escape(uri),
+            escape(suite)
+        ));
+    }
+    let (tests, failures) = (cases.len(), tiers.errors.len());
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"kanoniv\" tests=\"{}\" failures=\"{}\">\n",
+        tests, failures
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+        escape(suite),
+        tests,
+        failures
+    ));
+    xml.push_str(&cases.concat());
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn case(uri: &str, diagnostic: &Diagnostic) -> String {
+    let name = match &diagnostic.path {
+        Some(path) => format!("{} {}", diagnostic.code, path),
+        None => diagnostic.code.clone(),
+    };
+    let location = match diagnostic.span {
+        Some(span) => format!("{}:{}:{}", uri, span.line, span.column),
+        None => uri.to_string(),
+    };
+    let body = match diagnostic.severity {
+        Severity::Error => format!(
+            "      <failure type=\"{}\" message=\"{}\">{}: {}</failure>\n",
+            escape(&diagnostic.code),
+            escape(&diagnostic.message),
+            escape(&location),
+            escape(&diagnostic.to_string())
+        ),
+        _ => format!(
+            "      <system-out>{}: {}</system-out>\n",
+            escape(&location),
+            escape(&diagnostic.to_string())
+        ),
+    };
+    format!(
+        "    <testcase classname=\"{}\" name=\"{}\">\n{}    </testcase>\n",
+        escape(uri),
+        escape(&name),
+        body
+    )
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            // Attribute values would otherwise lose their line breaks.
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,317 @@
+//! Structured validation findings.
+//!
+//! Validators report `Diagnostic`s carrying a code, severity, YAML path and,
+//! when the source text is available, a line/column span. The string-based
+//! API (`validate_schema`, `validate_semantics`, `validate_yaml`) renders
+//! these through `Display`, so its messages are unchanged. Findings come in
+//! three tiers — errors, warnings and info — and a `Profile` can promote or
+//! demote advice before the tiers are split.
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::parser::SourceMap;
+use crate::waivers::Waived;
+
+pub mod codes;
+pub mod github;
+pub mod junit;
+pub mod sarif;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// 1-based position in the spec source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Stable code from `codes`, e.g. `KNV0101`.
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    /// Dotted YAML path of the offending node, e.g. `rules[0].field`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &str, path: impl Into<String>, message: impl Into<String>) -> Self {
+        let path = path.into();
+        Diagnostic {
+            code: code.to_string(),
+            severity: Severity::Error,
+            message: message.into(),
+            path: (!path.is_empty()).then_some(path),
+            span: None,
+            suggestion: None,
+        }
+    }
+
+    pub fn warning(code: &str, path: impl Into<String>, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(code, path, message)
+        }
+    }
+
+    pub fn info(code: &str, path: impl Into<String>, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Info,
+            ..Diagnostic::error(code, path, message)
+        }
+    }
+
+    pub fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Fill in the span from the source map, falling back to the nearest
+    /// ancestor for nodes that are missing from the source.
+    pub fn locate(&mut self, map: &SourceMap) {
+        if self.span.is_none() {
+            if let Some(path) = &self.path {
+                self.span = map.nearest(path);
+            }
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats CI systems read findings in; see `render_ci`.
+pub const CI_FORMATS: &[&str] = &["sarif", "junit", "github"];
+
+/// `tiers`, found by `command` in the file at `uri`, in one of
+/// `CI_FORMATS`; `None` for any other format.
+pub fn render_ci(format: &str, command: &str, uri: &str, tiers: &Tiers) -> Option<String> {
+    match format {
+        "sarif" => serde_json::to_string_pretty(&sarif::to_sarif(uri, tiers)).ok(),
+        "junit" => Some(junit::to_junit(command, uri, tiers)),
+        "github" => Some(github::annotations(uri, tiers)),
+        _ => None,
+    }
+}
+
+/// Attach spans to every diagnostic that has a path.
+pub fn locate_all(diagnostics: &mut [Diagnostic], map: &SourceMap) {
+    for diagnostic in diagnostics {
+        diagnostic.locate(map);
+    }
+}
+
+/// How strictly advice is treated. Errors are never demoted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Profile {
+    /// Severities as reported by the checks.
+    #[default]
+    Default,
+    /// Warnings fail validation.
+    Strict,
+    /// Warnings are reported as info.
+    Lenient,
+}
+
+impl Profile {
+    pub fn apply(&self, diagnostic: &mut Diagnostic) {
+        match (self, diagnostic.severity) {
+            (Profile::Strict, Severity::Warning) => diagnostic.severity = Severity::Error,
+            (Profile::Lenient, Severity::Warning) => diagnostic.severity = Severity::Info,
+            _ => {}
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "default" => Ok(Profile::Default),
+            "strict" => Ok(Profile::Strict),
+            "lenient" => Ok(Profile::Lenient),
+            other => bail!(
+                "Unknown profile '{}'. Expected: default, strict, lenient",
+                other
+            ),
+        }
+    }
+}
+
+/// Findings split by severity.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tiers {
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+    pub info: Vec<Diagnostic>,
+    /// Findings suppressed by a waiver in the spec, kept for the record.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub waived: Vec<Waived>,
+}
+
+impl Tiers {
+    /// Apply `profile` and split, keeping each tier in report order.
+    pub fn split(diagnostics: Vec<Diagnostic>, profile: Profile) -> Self {
+        Self::split_waived(diagnostics, Vec::new(), profile)
+    }
+
+    /// As `split`, recording what waivers set aside.
+    pub fn split_waived(diagnostics: Vec<Diagnostic>, waived: Vec<Waived>, profile: Profile) -> Self {
+        let mut tiers = Tiers {
+            waived,
+            ..Tiers::default()
+        };
+        for mut diagnostic in diagnostics {
+            profile.apply(&mut diagnostic);
+            match diagnostic.severity {
+                Severity::Error => tiers.errors.push(diagnostic),
+                Severity::Warning => tiers.warnings.push(diagnostic),
+                Severity::Info => tiers.info.push(diagnostic),
+            }
+        }
+        tiers
+    }
+
+    /// Add findings after those of their tier, with `profile` applied.
+    pub fn extend(&mut self, diagnostics: Vec<Diagnostic>, profile: Profile) {
+        let added = Self::split(diagnostics, profile);
+        self.errors.extend(added.errors);
+        self.warnings.extend(added.warnings);
+        self.info.extend(added.info);
+    }
+
+    /// Valid when there are no errors; warnings and info never fail.
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MINIMAL, PRIVACY};
+    use crate::{diagnose_yaml, Profile, Severity};
+
+    #[test]
+    fn test_diagnostics_carry_code_path_and_span() {
+        let yaml = MINIMAL.replace("field: email", "field: emails");
+        let diagnostics = diagnose_yaml(&yaml);
+        assert_eq!(diagnostics.len(), 1);
+        let d = &diagnostics[0];
+        assert_eq!(d.code, "KNV0101");
+        assert_eq!(d.severity, Severity::Error);
+        assert_eq!(d.path.as_deref(), Some("rules[0].field"));
+        assert_eq!(d.suggestion.as_deref(), Some("Did you mean 'email'?"));
+        assert!(d.span.is_some());
+        // The string API renders the same finding unchanged.
+        assert_eq!(crate::validate_yaml(&yaml).unwrap(), vec![d.to_string()]);
+
+        let syntax = diagnose_yaml("entity: [unclosed");
+        assert_eq!(syntax[0].code, "KNV0901");
+        assert!(syntax[0].span.is_some());
+    }
+
+    #[test]
+    fn test_validation_tiers_and_profiles() {
+        let yaml = MINIMAL.replace("weight: 1.0", "weight: 0.0").replace(
+            "      email: email\n",
+            "      email: email\n      phone: phone\n",
+        ) + PRIVACY
+            + "    phone:\n      masking: hash\n";
+
+        let tiers = crate::validate_tiers(&yaml, Profile::Default);
+        assert!(tiers.is_valid());
+        let codes = |ds: &[crate::Diagnostic]| -> Vec<String> {
+            ds.iter().map(|d| d.code.clone()).collect()
+        };
+        assert_eq!(codes(&tiers.warnings), vec!["KNV0106"]);
+        assert_eq!(codes(&tiers.info), vec!["KNV0105"]);
+        assert_eq!(tiers.warnings[0].path.as_deref(), Some("rules[0].weight"));
+        // Advice never reaches the legacy string API.
+        assert!(crate::validate_yaml(&yaml).unwrap().is_empty());
+
+        let strict = crate::validate_tiers(&yaml, Profile::Strict);
+        assert!(!strict.is_valid());
+        assert_eq!(codes(&strict.errors), vec!["KNV0106"]);
+        assert_eq!(strict.errors[0].severity, Severity::Error);
+
+        let lenient = crate::validate_tiers(&yaml, Profile::Lenient);
+        assert!(lenient.warnings.is_empty());
+        assert_eq!(codes(&lenient.info), vec!["KNV0106", "KNV0105"]);
+
+        assert!("pedantic".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn test_ci_formats_escape_findings() {
+        use crate::diagnostics::render_ci;
+        use crate::{Diagnostic, Span, Tiers};
+
+        let findings = vec![
+            Diagnostic::error(
+                "KNV0101",
+                "rules[0].field",
+                "Field <email> & \"name\"\nat 100%",
+            )
+            .with_span(Span { line: 3, column: 2 }),
+            Diagnostic::warning("KNV0106", "rules[1].weight", "Rule 'b' has weight 0."),
+            Diagnostic::info("KNV0105", "", "Attribute 'fax' is unused."),
+        ];
+        let tiers = Tiers::split(findings, Profile::Default);
+
+        let github = render_ci("github", "kanoniv validate", "specs/a,b.yaml", &tiers).unwrap();
+        assert_eq!(
+        github.lines().collect::<Vec<_>>(),
+        [
+            "::error file=specs/a%2Cb.yaml,line=3,col=2,title=KNV0101::Field <email> & \"name\"%0Aat 100%25",
+            "::warning file=specs/a%2Cb.yaml,title=KNV0106::Rule 'b' has weight 0.",
+            "::notice file=specs/a%2Cb.yaml,title=KNV0105::Attribute 'fax' is unused.",
+        ]
+    );
+
+        // Info is left out of JUnit reports; warnings pass.
+        let junit = render_ci("junit", "kanoniv validate", "specs/a,b.yaml", &tiers).unwrap();
+        assert!(junit.contains("<testsuite name=\"kanoniv validate\" tests=\"2\" failures=\"1\">"));
+        assert!(
+            junit.contains("message=\"Field &lt;email&gt; &amp; &quot;name&quot;&#10;at 100%\"")
+        );
+        assert!(junit.contains("<system-out>specs/a,b.yaml: Rule 'b' has weight 0.</system-out>"));
+        assert!(!junit.contains("KNV0105"));
+
+        let clean = render_ci("junit", "kanoniv validate", "spec.yaml", &Tiers::default()).unwrap();
+        assert!(clean.contains("<testcase classname=\"spec.yaml\" name=\"kanoniv validate\"/>"));
+        assert!(render_ci("json", "kanoniv validate", "spec.yaml", &tiers).is_none());
+    }
+}
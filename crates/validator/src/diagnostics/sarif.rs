@@ -0,0 +1,161 @@
+//! SARIF 2.1.0 rendering of validation findings.
+//!
+//! `kanoniv validate --format sarif` writes one run whose rules are the
+//! diagnostic codes reported, so code scanning tools (GitHub's among them)
+//! show findings as annotations on the spec's lines. Errors are `error`
+//! results, warnings `warning` and info `note`; findings a waiver set aside
+//! are kept as results with an in-source suppression carrying its reason.
+
+use serde_json::{json, Value};
+
+use super::{codes, Diagnostic, Severity, Tiers};
+
+pub const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+pub const VERSION: &str = "2.1.0";
+
+/// The SARIF log for findings in the file at `uri`.
+pub fn to_sarif(uri: &str, tiers: &Tiers) -> Value {
+    let mut rules: Vec<String> = Vec::new();
+    let mut rule_index = |code: &str| match rules.iter().position(|r| *r == code) {
+        Some(i) => i,
+        None => {
+            rules.push(code.to_string());
+            rules.len() - 1
+        }
+    };
+
+    let mut results = Vec::new();
+    for diagnostic in tiers
+        .errors
+        .iter()
+        .chain(&tiers.warnings)
+        .chain(&tiers.info)
+    {
+        let index = rule_index(&diagnostic.code);
+        results.push(result(uri, diagnostic, index));
+    }
+    for waived in &tiers.waived {
+        let index = rule_index(&waived.code);
+        results.push(json!({
+            "ruleId": waived.code,
+            "ruleIndex": index,
+            "level": "warning",
+            "message": { "text": waived.message },
+            "locations": [{ "physicalLocation": { "artifactLocation": { "uri": uri } } }],
+            "suppressions": [{ "kind": "inSource", "justification": waived.waiver.reason }],
+        }));
+    }
+
+    json!({
+        "$schema": SCHEMA,
+        "version": VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "kanoniv",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.iter().map(|code| rule(code)).collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+/// A reporting descriptor for `code`, described from its `codes` entry.
+fn rule(code: &str) -> Value {
+    match codes::lookup(code) {
+        Some(info) => json!({
+            "id": info.code,
+            "name": info.name,
+            "shortDescription": { "text": info.title },
+            "fullDescription": { "text": info.explanation },
+            "help": { "text": format!("Run `kanoniv explain {}` for details.", info.code) },
+        }),
+        None => json!({ "id": code }),
+    }
+}
+
+fn result(uri: &str, diagnostic: &Diagnostic, rule_index: usize) -> Value {
+    let level = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "note",
+    };
+    let mut location = json!({
+        "physicalLocation": { "artifactLocation": { "uri": uri } },
+    });
+    if let Some(span) = diagnostic.span {
+        location["physicalLocation"]["region"] = json!({
+            "startLine": span.line,
+            "startColumn": span.column,
+        });
+    }
+    if let Some(path) = &diagnostic.path {
+        location["logicalLocations"] = json!([{ "fullyQualifiedName": path }]);
+    }
+    json!({
+        "ruleId": diagnostic.code,
+        "ruleIndex": rule_index,
+        "level": level,
+        "message": { "text": diagnostic.to_string() },
+        "locations": [location],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MINIMAL, PRIVACY};
+    use crate::Profile;
+
+    #[test]
+    fn test_sarif_log_carries_codes_levels_and_waivers() {
+        use crate::diagnostics::sarif;
+
+        let yaml = MINIMAL.replace(
+            "    weight: 1.0\n",
+            "    weight: 0.0  # kanoniv-ignore: zero-weight-rule reason=\"kept for reports\"\n",
+        )
+            + "waivers:\n  - code: SINGLE_SOURCE\n    reason: one CRM\n    expires: 2000-01-01\n"
+            + PRIVACY;
+        let tiers = crate::validate_tiers(&yaml, Profile::Lenient);
+        let log = sarif::to_sarif("specs/customer.yaml", &tiers);
+        assert_eq!(
+            (log["version"].as_str(), log["$schema"].as_str()),
+            (Some(sarif::VERSION), Some(sarif::SCHEMA))
+        );
+
+        let run = &log["runs"][0];
+        let rules: Vec<_> = run["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(rules, ["KNV0110", "KNV0106"]);
+        assert_eq!(
+            run["tool"]["driver"]["rules"][1]["shortDescription"]["text"],
+            "A rule has weight 0 (warning)"
+        );
+
+        // The lenient profile reports the expired waiver as a note.
+        let expired = &run["results"][0];
+        assert_eq!(
+            (expired["ruleId"].as_str(), expired["level"].as_str()),
+            (Some("KNV0110"), Some("note"))
+        );
+        let location = &expired["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "specs/customer.yaml");
+        assert!(location["region"]["startLine"].as_u64().is_some());
+
+        let waived = &run["results"][1];
+        assert_eq!(
+            (waived["ruleId"].as_str(), waived["ruleIndex"].as_u64()),
+            (Some("KNV0106"), Some(1))
+        );
+        assert_eq!(
+            waived["suppressions"][0]["justification"],
+            "kept for reports"
+        );
+    }
+}
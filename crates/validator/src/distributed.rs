@@ -0,0 +1,1245 @@
+//! Distributed execution: a coordinator has workers link the records.
+//!
+//! Pairing, scoring and clustering are the costly stages of a run (every
+//! rule over every candidate pair, with embedding calls for semantic
+//! rules), so a coordinator (`kanoniv execute --distributed ADDR`) loads
+//! the records and splits them into partitions on the blocking, and any
+//! number of workers (`kanoniv worker ADDR`) connect and claim a partition
+//! at a time. Records sharing a blocking key value, a sorted-neighbourhood
+//! window or a canopy go to the same partition (see `interpreter::blocks`),
+//! so a worker blocks, scores, decides and clusters its partition's records
+//! on its own and finds every pair and cluster a local run finds among
+//! them. The coordinator merges the partitions' decisions back into record
+//! order and their clusters into one set, whichever worker returns them,
+//! then builds golden records as a local run does: the result is the same
+//! for any number of workers and partitions. The merged clusters carry no
+//! audit trail; `kanoniv cluster --audit` writes one from the decisions.
+//!
+//! The protocol is gRPC over TLS (`proto/distributed.proto`): each worker
+//! calls `Work` once and the two sides exchange messages on its stream:
+//!
+//! ```text
+//! coordinator: challenge { nonce: "9f2c..." }
+//! worker:      hello { nonce: "41d8...", proof: "b07e..." }
+//! coordinator: welcome { proof: "5a11..." }
+//! coordinator: plan { ir: "{...}" }
+//! worker:      claim {}
+//! coordinator: partition { id: 0, columns, rows, stewardship: true }
+//! worker:      clustered { id: 0, decisions, rules, clusters, warnings }
+//! worker:      claim {}
+//! coordinator: done {}
+//! ```
+//!
+//! Workers check the coordinator's certificate against the one they are
+//! given, so records are never sent in the clear. Coordinator and workers
+//! also share a secret, and each proves it knows it with an HMAC-SHA256 of
+//! the other's random nonce before any records are sent; a stream that
+//! fails to, or takes longer than `HANDSHAKE` to answer the challenge, is
+//! closed. Neither side accepts a message larger than `MAX_MESSAGE`. A
+//! worker holds one partition at a time and may only return that one.
+//!
+//! A partition a worker claimed but never returned, because it went away,
+//! is handed to the next worker that claims one. Workers that connect
+//! before a run wait for it, and a worker joining a run that is over is
+//! sent `done` at once. A worker that cannot link a partition sends
+//! `failed { id, error }` and the run stops with its error.
+
+use anyhow::{anyhow, bail, Context, Result};
+use hmac::{Hmac, Mac};
+use prost::Message as _;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io::ErrorKind;
+use std::iter;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::audit::MatchDecision;
+use crate::cancel::{self, CancellationToken};
+use crate::clustering::{self, ClusterAssignment, EntityClusters};
+use crate::commands::plan::extract_match_strategies;
+use crate::embedding::Embedder;
+use crate::encoding::{self, Encoder};
+use crate::interpreter::{
+    blocks, execute_sources_linked, link_records, sources_json, ExecutionResult, Linked, KEY,
+};
+use crate::ir::Ir;
+use crate::sample::Sample;
+use crate::signing::{decode_hex, encode_hex};
+
+mod proto {
+    tonic::include_proto!("kanoniv.distributed");
+}
+
+use proto::coordinator_client::CoordinatorClient;
+use proto::coordinator_message::Message as ToWorker;
+use proto::coordinator_server::{Coordinator as Service, CoordinatorServer};
+use proto::worker_message::Message as FromWorker;
+use proto::{CoordinatorMessage, WorkerMessage};
+
+/// How often a waiting coordinator checks for workers and cancellation.
+const POLL: Duration = Duration::from_millis(20);
+
+/// The largest message either side accepts. A partition's records must fit
+/// in one; more partitions make each smaller.
+const MAX_MESSAGE: usize = 64 * 1024 * 1024;
+
+/// How long a worker has to answer the coordinator's challenge (and to
+/// connect at all).
+const HANDSHAKE: Duration = Duration::from_secs(10);
+
+/// How often idle connections are pinged, and how long an answer may take,
+/// so a worker that vanished without closing its connection gives its
+/// partition back.
+const KEEPALIVE: Duration = Duration::from_secs(30);
+
+/// How long a coordinator going away waits for its workers to be sent
+/// `done`.
+const GRACE: Duration = Duration::from_secs(5);
+
+type Reply = std::result::Result<CoordinatorMessage, Status>;
+
+/// Partitions waiting, claimed and clustered, shared by the streams.
+#[derive(Default)]
+struct Dispatch {
+    pending: VecDeque<usize>,
+    clustered: Vec<Option<proto::Clustered>>,
+    failed: Option<String>,
+    workers: usize,
+    finished: bool,
+}
+
+/// What a worker's claim gets.
+enum Next {
+    Partition(usize),
+    Done,
+    /// Claimed partitions may yet come back.
+    Wait,
+}
+
+impl Dispatch {
+    fn complete(&self) -> bool {
+        self.clustered.iter().all(Option::is_some)
+    }
+
+    fn next(&mut self) -> Next {
+        if self.finished || self.failed.is_some() || self.complete() {
+            return Next::Done;
+        }
+        match self.pending.pop_front() {
+            Some(id) => Next::Partition(id),
+            None => Next::Wait,
+        }
+    }
+}
+
+/// A run's plan and partitions, while workers link them.
+struct Run {
+    plan: CoordinatorMessage,
+    /// The plan's match rules, each of which a partition's pairs come back
+    /// scored on.
+    rules: usize,
+    partitions: Vec<CoordinatorMessage>,
+    dispatch: Mutex<Dispatch>,
+}
+
+enum Phase {
+    /// No run has started; workers wait for one.
+    Waiting,
+    Running(Arc<Run>),
+    /// The last run is over; workers are sent `done`.
+    Over,
+}
+
+/// The coordinator's side of every worker's stream.
+#[derive(Clone)]
+struct Sessions {
+    secret: Arc<String>,
+    phase: Arc<Mutex<Phase>>,
+}
+
+/// The run a worker joined and the partition it holds.
+#[derive(Default)]
+struct Session {
+    run: Option<Arc<Run>>,
+    claimed: Option<usize>,
+}
+
+/// How a distributed run was shared out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Dispatched {
+    pub partitions: usize,
+    /// Workers that connected while the records were linked.
+    pub workers: usize,
+}
+
+/// What a worker linked before the coordinator was done with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorkDone {
+    /// The plan linked for; `None` if the run was over before the worker
+    /// joined it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan_hash: Option<String>,
+    pub partitions: usize,
+    pub records: usize,
+    /// Candidate pairs found in the partitions' records.
+    pub pairs: usize,
+}
+
+/// The PEM-encoded certificate chain and private key a coordinator serves
+/// TLS with.
+#[derive(Clone)]
+pub struct TlsIdentity {
+    pub cert: Vec<u8>,
+    pub key: Vec<u8>,
+}
+
+impl TlsIdentity {
+    pub fn load(cert: &Path, key: &Path) -> Result<Self> {
+        let read = |path: &Path| {
+            fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))
+        };
+        Ok(TlsIdentity {
+            cert: read(cert)?,
+            key: read(key)?,
+        })
+    }
+}
+
+/// A coordinator listening for workers.
+pub struct Coordinator {
+    address: SocketAddr,
+    partitions: usize,
+    sessions: Sessions,
+    runtime: Runtime,
+    server: JoinHandle<std::result::Result<(), tonic::transport::Error>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl Coordinator {
+    /// Listen on `address` (`HOST:PORT`; port 0 picks one) for workers
+    /// that know `secret`, serving TLS as `identity`, and split each run's
+    /// records into up to `partitions` partitions.
+    pub fn bind(
+        address: &str,
+        partitions: usize,
+        secret: &str,
+        identity: &TlsIdentity,
+    ) -> Result<Self> {
+        if partitions == 0 {
+            bail!("A distributed run needs at least 1 partition");
+        }
+        if secret.is_empty() {
+            bail!("A distributed run needs a shared secret for its workers");
+        }
+        let sessions = Sessions {
+            secret: Arc::new(secret.to_string()),
+            phase: Arc::new(Mutex::new(Phase::Waiting)),
+        };
+        let tls =
+            ServerTlsConfig::new().identity(Identity::from_pem(&identity.cert, &identity.key));
+        let router = Server::builder()
+            .tls_config(tls)
+            .context("Invalid TLS certificate or key")?
+            .http2_keepalive_interval(Some(KEEPALIVE))
+            .http2_keepalive_timeout(Some(KEEPALIVE))
+            .add_service(
+                CoordinatorServer::new(sessions.clone())
+                    .max_decoding_message_size(MAX_MESSAGE)
+                    .max_encoding_message_size(MAX_MESSAGE),
+            );
+        let listener = std::net::TcpListener::bind(address)
+            .with_context(|| format!("Failed to listen on {}", address))?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let incoming = {
+            let _entered = runtime.enter();
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            TcpIncoming::from_listener(listener, true, Some(KEEPALIVE)).map_err(|e| anyhow!(e))?
+        };
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let server = runtime.spawn(router.serve_with_incoming_shutdown(incoming, async {
+            let _ = signal.await;
+        }));
+        Ok(Coordinator {
+            address,
+            partitions,
+            sessions,
+            runtime,
+            server,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.address)
+    }
+
+    /// `execute_plan`, with the records linked by workers; waits for them
+    /// until every partition is clustered or `token` is cancelled.
+    pub fn execute_plan(
+        &self,
+        ir: &Ir,
+        records: &Value,
+        token: Option<&CancellationToken>,
+        as_of: &str,
+    ) -> Result<(ExecutionResult, Dispatched)> {
+        self.execute_sources(ir, &sources_json(records)?, token, as_of)
+    }
+
+    /// `execute_sources`, with the records linked by workers.
+    pub fn execute_sources(
+        &self,
+        ir: &Ir,
+        sources: &[(String, Sample)],
+        token: Option<&CancellationToken>,
+        as_of: &str,
+    ) -> Result<(ExecutionResult, Dispatched)> {
+        let mut dispatched = Dispatched {
+            partitions: 0,
+            workers: 0,
+        };
+        let result =
+            execute_sources_linked(ir, sources, token, as_of, |data, encoder, warnings| {
+                let (linked, run) = self.link(ir, data, encoder, token, warnings)?;
+                dispatched = run;
+                Ok(linked)
+            })?;
+        Ok((result, dispatched))
+    }
+
+    fn link(
+        &self,
+        ir: &Ir,
+        data: &Sample,
+        encoder: Option<&Encoder>,
+        token: Option<&CancellationToken>,
+        warnings: &mut Vec<String>,
+    ) -> Result<(Linked, Dispatched)> {
+        let partitions = partition(ir, data, encoder, self.partitions)?;
+        let run = Arc::new(Run {
+            plan: to_worker(ToWorker::Plan(proto::Plan {
+                ir: serde_json::to_string(ir)?,
+            })),
+            rules: extract_match_strategies(ir).len(),
+            dispatch: Mutex::new(Dispatch {
+                pending: (0..partitions.len()).collect(),
+                clustered: vec![None; partitions.len()],
+                ..Default::default()
+            }),
+            partitions,
+        });
+        *self.sessions.phase.lock().unwrap() = Phase::Running(run.clone());
+
+        let outcome = loop {
+            {
+                let dispatch = run.dispatch.lock().unwrap();
+                if let Some(error) = &dispatch.failed {
+                    break Err(anyhow!("A worker failed: {}", error));
+                }
+                if dispatch.complete() {
+                    break Ok(());
+                }
+            }
+            if let Err(cancelled) = cancel::check(token) {
+                break Err(cancelled.into());
+            }
+            if self.server.is_finished() {
+                break Err(anyhow!("The coordinator stopped listening for workers"));
+            }
+            thread::sleep(POLL);
+        };
+
+        // Workers still linking are sent `done` when they next claim.
+        run.dispatch.lock().unwrap().finished = true;
+        *self.sessions.phase.lock().unwrap() = Phase::Over;
+        outcome?;
+        let dispatch = run.dispatch.lock().unwrap();
+        let clustered = dispatch
+            .clustered
+            .iter()
+            .map(|partition| partition.as_ref().expect("every partition is clustered"));
+        Ok((
+            merge(ir, clustered, warnings),
+            Dispatched {
+                partitions: run.partitions.len(),
+                workers: dispatch.workers,
+            },
+        ))
+    }
+}
+
+impl Drop for Coordinator {
+    fn drop(&mut self) {
+        // Workers waiting for a run are sent `done` rather than cut off;
+        // the server stops once their streams have closed.
+        *self.sessions.phase.lock().unwrap() = Phase::Over;
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let server = &mut self.server;
+        let _ = self
+            .runtime
+            .block_on(async { tokio::time::timeout(GRACE, server).await });
+    }
+}
+
+/// Split `data`, loaded records, into up to `partitions` partitions of
+/// about the same number of records. Records a block, window or canopy
+/// joins are unioned, and so are those the plan's steward assertions name,
+/// the lowest as root; each group goes whole to the partition being filled,
+/// in order of its root, so a partition is only larger than its share
+/// where a group is.
+fn partition(
+    ir: &Ir,
+    data: &Sample,
+    encoder: Option<&Encoder>,
+    partitions: usize,
+) -> Result<Vec<CoordinatorMessage>> {
+    let stewards: Vec<usize> = ir
+        .stewardship
+        .iter()
+        .flat_map(|s| s.always_merge.iter().chain(&s.never_merge))
+        .flat_map(|assertion| &assertion.records)
+        .filter_map(|key| {
+            data.rows
+                .binary_search_by(|row| row[KEY].as_str().cmp(key))
+                .ok()
+        })
+        .collect();
+    let mut parent: Vec<usize> = (0..data.len()).collect();
+    let groups = blocks(ir, data, encoder, &mut Vec::new());
+    for group in groups.iter().chain(iter::once(&stewards)) {
+        for joined in group.windows(2) {
+            let (a, b) = (
+                clustering::find(&mut parent, joined[0]),
+                clustering::find(&mut parent, joined[1]),
+            );
+            parent[a.max(b)] = a.min(b);
+        }
+    }
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for record in 0..data.len() {
+        groups
+            .entry(clustering::find(&mut parent, record))
+            .or_default()
+            .push(record);
+    }
+
+    let size = data.len().div_ceil(partitions).max(1);
+    let mut shares: Vec<Vec<usize>> = Vec::new();
+    for group in groups.into_values() {
+        match shares.last_mut() {
+            Some(share) if share.len() < size => share.extend(group),
+            _ => shares.push(group),
+        }
+    }
+    if shares.is_empty() {
+        shares.push(Vec::new());
+    }
+    // The assertions only apply where the records they name are.
+    let stewarded = stewards
+        .first()
+        .and_then(|steward| shares.iter().position(|share| share.contains(steward)))
+        .unwrap_or(0);
+
+    shares
+        .into_iter()
+        .enumerate()
+        .map(|(id, mut share)| {
+            share.sort_unstable();
+            let message = to_worker(ToWorker::Partition(proto::Partition {
+                id: id as u64,
+                columns: data.columns.clone(),
+                rows: share
+                    .iter()
+                    .map(|&r| proto::Row {
+                        values: data.rows[r].clone(),
+                    })
+                    .collect(),
+                stewardship: ir.stewardship.is_some() && id == stewarded,
+            }));
+            if message.encoded_len() > MAX_MESSAGE {
+                bail!(
+                    "Partition {} is {} bytes, more than a message may be ({}); use more partitions",
+                    id,
+                    message.encoded_len(),
+                    MAX_MESSAGE
+                );
+            }
+            Ok(message)
+        })
+        .collect()
+}
+
+/// The partitions' decisions and clusters as one run's: decisions, with
+/// their similarities, in record order, as a local run lists them, and the
+/// clusters' counts summed. Partitions share no records, so their clusters
+/// share none either. Every partition has the same columns, so the first's
+/// warnings are every one's.
+fn merge<'a>(
+    ir: &Ir,
+    partitions: impl Iterator<Item = &'a proto::Clustered>,
+    warnings: &mut Vec<String>,
+) -> Linked {
+    let clustering = ir.clustering.unwrap_or_default();
+    let mut clusters = EntityClusters {
+        strategy: clustering.strategy,
+        threshold: clustering.threshold,
+        records: 0,
+        pairs: 0,
+        clusters: 0,
+        largest_cluster: 0,
+        transitive_clusters: 0,
+        overrides: 0,
+        assignments: Vec::new(),
+        audit: Vec::new(),
+    };
+    let mut decisions: Vec<(MatchDecision, Vec<Option<f64>>)> = Vec::new();
+    for (id, partition) in partitions.enumerate() {
+        if id == 0 {
+            warnings.extend(partition.warnings.iter().cloned());
+        }
+        for (pair, decision) in partition.decisions.iter().enumerate() {
+            let similarities = partition
+                .rules
+                .iter()
+                .map(|rule| rule.scores[pair].similarity)
+                .collect();
+            let decision = MatchDecision {
+                left_key: decision.left_key.clone(),
+                right_key: decision.right_key.clone(),
+                score: decision.score,
+                decision: decision.decision.clone(),
+            };
+            decisions.push((decision, similarities));
+        }
+        let partial = partition.clusters.clone().unwrap_or_default();
+        clusters.records += partial.records as usize;
+        clusters.pairs += partial.pairs as usize;
+        clusters.clusters += partial.clusters as usize;
+        clusters.largest_cluster = clusters
+            .largest_cluster
+            .max(partial.largest_cluster as usize);
+        clusters.transitive_clusters += partial.transitive_clusters as usize;
+        clusters.overrides += partial.overrides as usize;
+        clusters
+            .assignments
+            .extend(partial.assignments.into_iter().map(|a| ClusterAssignment {
+                record_key: a.record_key,
+                cluster_id: a.cluster_id,
+            }));
+    }
+    decisions
+        .sort_by(|(a, _), (b, _)| (&a.left_key, &a.right_key).cmp(&(&b.left_key, &b.right_key)));
+    clusters
+        .assignments
+        .sort_by(|a, b| a.record_key.cmp(&b.record_key));
+    let similarities = (0..extract_match_strategies(ir).len())
+        .map(|rule| decisions.iter().map(|(_, scores)| scores[rule]).collect())
+        .collect();
+    Linked {
+        candidate_pairs: decisions.len(),
+        decisions: decisions
+            .into_iter()
+            .map(|(decision, _)| decision)
+            .collect(),
+        similarities,
+        clusters,
+    }
+}
+
+#[tonic::async_trait]
+impl Service for Sessions {
+    type WorkStream = ReceiverStream<Reply>;
+
+    async fn work(
+        &self,
+        request: Request<Streaming<WorkerMessage>>,
+    ) -> std::result::Result<Response<Self::WorkStream>, Status> {
+        let (outbound, replies) = mpsc::channel(4);
+        tokio::spawn(serve(self.clone(), request.into_inner(), outbound));
+        Ok(Response::new(ReceiverStream::new(replies)))
+    }
+}
+
+/// Talk to one worker until the run is over, it goes away or it breaks the
+/// protocol; a partition it claimed and did not return goes back in the
+/// queue.
+async fn serve(
+    sessions: Sessions,
+    inbound: Streaming<WorkerMessage>,
+    outbound: mpsc::Sender<Reply>,
+) {
+    let mut session = Session::default();
+    if let Err(status) = converse(&sessions, inbound, &outbound, &mut session).await {
+        let _ = outbound.send(Err(status)).await;
+    }
+    if let (Some(run), Some(id)) = (session.run, session.claimed) {
+        let mut dispatch = run.dispatch.lock().unwrap();
+        if dispatch.clustered[id].is_none() {
+            dispatch.pending.push_front(id);
+        }
+    }
+}
+
+async fn converse(
+    sessions: &Sessions,
+    mut inbound: Streaming<WorkerMessage>,
+    outbound: &mpsc::Sender<Reply>,
+    session: &mut Session,
+) -> std::result::Result<(), Status> {
+    let challenge = nonce().map_err(|e| Status::internal(e.to_string()))?;
+    send(
+        outbound,
+        ToWorker::Challenge(proto::Challenge {
+            nonce: challenge.clone(),
+        }),
+    )
+    .await?;
+    let hello = tokio::time::timeout(HANDSHAKE, inbound.message())
+        .await
+        .map_err(|_| {
+            Status::deadline_exceeded("A worker did not answer the challenge in time")
+        })??;
+    let Some(FromWorker::Hello(hello)) = hello.and_then(|m| m.message) else {
+        return Err(Status::invalid_argument(
+            "A worker did not answer the challenge",
+        ));
+    };
+    if !proves(&sessions.secret, WORKER, &challenge, &hello.proof) {
+        return Err(Status::unauthenticated(
+            "A worker does not know the shared secret",
+        ));
+    }
+    let proof = prove(&sessions.secret, COORDINATOR, &hello.nonce);
+    send(outbound, ToWorker::Welcome(proto::Welcome { proof })).await?;
+
+    let run = loop {
+        let run = match &*sessions.phase.lock().unwrap() {
+            Phase::Waiting => None,
+            Phase::Running(run) => Some(Some(run.clone())),
+            Phase::Over => Some(None),
+        };
+        match run {
+            Some(Some(run)) => break run,
+            Some(None) => return send(outbound, ToWorker::Done(proto::Done {})).await,
+            None if outbound.is_closed() => return Ok(()),
+            None => tokio::time::sleep(POLL).await,
+        }
+    };
+    run.dispatch.lock().unwrap().workers += 1;
+    session.run = Some(run.clone());
+    outbound
+        .send(Ok(run.plan.clone()))
+        .await
+        .map_err(|_| gone())?;
+
+    while let Some(message) = inbound.message().await? {
+        match message.message {
+            Some(FromWorker::Claim(_)) => {
+                if let Some(id) = session.claimed {
+                    return Err(Status::failed_precondition(format!(
+                        "A worker claimed another partition while holding partition {}",
+                        id
+                    )));
+                }
+                let id = loop {
+                    let next = run.dispatch.lock().unwrap().next();
+                    match next {
+                        Next::Partition(id) => break id,
+                        Next::Done => return send(outbound, ToWorker::Done(proto::Done {})).await,
+                        Next::Wait if outbound.is_closed() => return Ok(()),
+                        Next::Wait => tokio::time::sleep(POLL).await,
+                    }
+                };
+                session.claimed = Some(id);
+                outbound
+                    .send(Ok(run.partitions[id].clone()))
+                    .await
+                    .map_err(|_| gone())?;
+            }
+            Some(FromWorker::Clustered(clustered)) => {
+                let Some(id) = claimed(session, clustered.id) else {
+                    return Err(unclaimed("returned", clustered.id));
+                };
+                let complete = clustered.clusters.is_some()
+                    && clustered.rules.len() == run.rules
+                    && clustered
+                        .rules
+                        .iter()
+                        .all(|rule| rule.scores.len() == clustered.decisions.len());
+                let mut dispatch = run.dispatch.lock().unwrap();
+                match complete {
+                    true => dispatch.clustered[id] = Some(clustered),
+                    false => {
+                        dispatch.failed = Some(format!(
+                            "partition {} came back with the wrong number of scores",
+                            id
+                        ))
+                    }
+                }
+                session.claimed = None;
+            }
+            Some(FromWorker::Failed(failed)) => {
+                let Some(id) = claimed(session, failed.id) else {
+                    return Err(unclaimed("failed", failed.id));
+                };
+                run.dispatch.lock().unwrap().failed =
+                    Some(format!("partition {}: {}", id, failed.error));
+                session.claimed = None;
+                return Ok(());
+            }
+            other => {
+                return Err(Status::invalid_argument(format!(
+                    "Unexpected message from a worker: {:?}",
+                    other
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Partition `id`, if it is the one the worker holds.
+fn claimed(session: &Session, id: u64) -> Option<usize> {
+    session.claimed.filter(|&claimed| claimed as u64 == id)
+}
+
+fn unclaimed(did: &str, id: u64) -> Status {
+    Status::failed_precondition(format!(
+        "A worker {} partition {}, which it did not claim",
+        did, id
+    ))
+}
+
+fn to_worker(message: ToWorker) -> CoordinatorMessage {
+    CoordinatorMessage {
+        message: Some(message),
+    }
+}
+
+async fn send(
+    outbound: &mpsc::Sender<Reply>,
+    message: ToWorker,
+) -> std::result::Result<(), Status> {
+    outbound
+        .send(Ok(to_worker(message)))
+        .await
+        .map_err(|_| gone())
+}
+
+fn gone() -> Status {
+    Status::cancelled("The worker went away")
+}
+
+/// Link partitions for the coordinator at `address`, which must know
+/// `secret` and present a certificate for its host that the PEM
+/// certificates `ca` vouch for, until it has none left, trying to reach it
+/// for up to `wait`. Stops between partitions (and rules) once `token` is
+/// cancelled.
+pub fn work(
+    address: &str,
+    secret: &str,
+    ca: &[u8],
+    wait: Duration,
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+) -> Result<WorkDone> {
+    // Linking blocks (embedding calls go through a blocking client), so it
+    // runs on this thread while the runtime's keeps the connection alive.
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()?;
+    let host = match address.rsplit_once(':') {
+        Some((host, _)) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => address,
+    };
+    let tls = ClientTlsConfig::new()
+        .ca_certificate(Certificate::from_pem(ca))
+        .domain_name(host);
+    let endpoint = Endpoint::from_shared(format!("https://{}", address))
+        .with_context(|| format!("Invalid coordinator address: {}", address))?
+        .tls_config(tls)
+        .context("Invalid TLS certificate")?
+        .connect_timeout(HANDSHAKE)
+        .http2_keep_alive_interval(KEEPALIVE)
+        .keep_alive_timeout(KEEPALIVE);
+    let deadline = Instant::now() + wait;
+    let channel = loop {
+        match runtime.block_on(endpoint.connect()) {
+            Ok(channel) => break channel,
+            // It may not be listening yet.
+            Err(err) if refused(&err) && Instant::now() < deadline => {
+                cancel::check(token)?;
+                thread::sleep(POLL);
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to reach a coordinator at {}", address))
+            }
+        }
+    };
+    let mut client = CoordinatorClient::new(channel)
+        .max_decoding_message_size(MAX_MESSAGE)
+        .max_encoding_message_size(MAX_MESSAGE);
+    let (outbound, requests) = mpsc::channel(4);
+    let mut inbound = runtime
+        .block_on(client.work(ReceiverStream::new(requests)))
+        .map_err(|status| {
+            anyhow!(
+                "{} is not a kanoniv coordinator: {}",
+                address,
+                status.message()
+            )
+        })?
+        .into_inner();
+    let mut receive = || match runtime.block_on(inbound.message()) {
+        Ok(message) => Ok(message.and_then(|message| message.message)),
+        Err(status) => Err(status.message().to_string()),
+    };
+    let send = |message: FromWorker| {
+        outbound
+            .blocking_send(WorkerMessage {
+                message: Some(message),
+            })
+            .map_err(|_| anyhow!("The coordinator at {} went away", address))
+    };
+
+    let mut done = WorkDone {
+        plan_hash: None,
+        partitions: 0,
+        records: 0,
+        pairs: 0,
+    };
+    let challenge = match receive() {
+        Ok(Some(ToWorker::Challenge(challenge))) => challenge.nonce,
+        _ => bail!("{} is not a kanoniv coordinator", address),
+    };
+    let nonce = nonce()?;
+    send(FromWorker::Hello(proto::Hello {
+        nonce: nonce.clone(),
+        proof: prove(secret, WORKER, &challenge),
+    }))?;
+    match receive() {
+        Ok(Some(ToWorker::Welcome(welcome)))
+            if proves(secret, COORDINATOR, &nonce, &welcome.proof) => {}
+        Ok(Some(ToWorker::Welcome(_))) => bail!(
+            "The coordinator at {} does not know the shared secret",
+            address
+        ),
+        _ => bail!(
+            "The coordinator at {} turned this worker away; check the shared secret",
+            address
+        ),
+    }
+    let ir = match receive() {
+        Ok(Some(ToWorker::Plan(plan))) => Ir::from_value(&serde_json::from_str(&plan.ir)?)?,
+        Ok(Some(ToWorker::Done(_)) | None) => return Ok(done),
+        _ => bail!("{} is not a kanoniv coordinator", address),
+    };
+    done.plan_hash = Some(ir.plan_hash.clone());
+    let encoder = encoding::encoder(&ir.to_spec())?;
+    // Steward assertions only apply to the partition holding their records.
+    let unstewarded = Ir {
+        stewardship: None,
+        ..ir.clone()
+    };
+    loop {
+        cancel::check(token)?;
+        send(FromWorker::Claim(proto::Claim {}))?;
+        let partition = match receive() {
+            Ok(Some(ToWorker::Partition(partition))) => partition,
+            // The coordinator going away ends the work as well.
+            Ok(Some(ToWorker::Done(_)) | None) => return Ok(done),
+            Ok(Some(other)) => bail!("Unexpected message from the coordinator: {:?}", other),
+            Err(error) => bail!(
+                "The coordinator at {} ended the session: {}",
+                address,
+                error
+            ),
+        };
+        let id = partition.id;
+        let plan = match partition.stewardship {
+            true => &ir,
+            false => &unstewarded,
+        };
+        let data = Sample {
+            columns: partition.columns,
+            rows: partition.rows.into_iter().map(|row| row.values).collect(),
+        };
+        let mut warnings = Vec::new();
+        let linked = match link_records(
+            plan,
+            &data,
+            encoder.as_ref(),
+            embedder,
+            token,
+            &mut warnings,
+        ) {
+            Ok(linked) => linked,
+            Err(err) => {
+                let error = format!("{:#}", err);
+                send(FromWorker::Failed(proto::Failed { id, error }))?;
+                // The coordinator ends the session once it has the error.
+                let _ = receive();
+                return Err(err);
+            }
+        };
+        done.partitions += 1;
+        done.records += data.len();
+        done.pairs += linked.candidate_pairs;
+        send(FromWorker::Clustered(clustered(id, linked, warnings)))?;
+    }
+}
+
+/// Whether connecting failed because nothing listens there (yet).
+fn refused(err: &tonic::transport::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            return err.kind() == ErrorKind::ConnectionRefused;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// A partition's decisions and clusters as sent.
+fn clustered(id: u64, linked: Linked, warnings: Vec<String>) -> proto::Clustered {
+    let clusters = linked.clusters;
+    proto::Clustered {
+        id,
+        decisions: linked
+            .decisions
+            .into_iter()
+            .map(|d| proto::Decision {
+                left_key: d.left_key,
+                right_key: d.right_key,
+                score: d.score,
+                decision: d.decision,
+            })
+            .collect(),
+        rules: linked
+            .similarities
+            .into_iter()
+            .map(|scores| proto::RuleScores {
+                scores: scores
+                    .into_iter()
+                    .map(|similarity| proto::Score { similarity })
+                    .collect(),
+            })
+            .collect(),
+        clusters: Some(proto::Clusters {
+            records: clusters.records as u64,
+            pairs: clusters.pairs as u64,
+            clusters: clusters.clusters as u64,
+            largest_cluster: clusters.largest_cluster as u64,
+            transitive_clusters: clusters.transitive_clusters as u64,
+            overrides: clusters.overrides as u64,
+            assignments: clusters
+                .assignments
+                .into_iter()
+                .map(|a| proto::Assignment {
+                    record_key: a.record_key,
+                    cluster_id: a.cluster_id,
+                })
+                .collect(),
+        }),
+        warnings,
+    }
+}
+
+/// What each side's proof is labelled with, so a worker's proof cannot be
+/// played back as the coordinator's.
+const WORKER: &str = "kanoniv worker";
+const COORDINATOR: &str = "kanoniv coordinator";
+
+/// A fresh challenge: 32 random bytes in hex.
+fn nonce() -> Result<String> {
+    let mut nonce = [0u8; 32];
+    getrandom::fill(&mut nonce).map_err(|e| anyhow!("Failed to generate a nonce: {}", e))?;
+    Ok(encode_hex(&nonce))
+}
+
+fn mac(secret: &str, role: &str, nonce: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes any key length");
+    mac.update(role.as_bytes());
+    mac.update(b"\n");
+    mac.update(nonce.as_bytes());
+    mac
+}
+
+/// Proof that `role` knows `secret`, answering `nonce`.
+fn prove(secret: &str, role: &str, nonce: &str) -> String {
+    encode_hex(&mac(secret, role, nonce).finalize().into_bytes())
+}
+
+/// Whether `proof` is `role`'s answer to `nonce` under `secret`, compared
+/// in constant time.
+fn proves(secret: &str, role: &str, nonce: &str, proof: &str) -> bool {
+    decode_hex(proof).is_some_and(|proof| mac(secret, role, nonce).verify_slice(&proof).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::Duration;
+    use tokio::runtime::Runtime;
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::ReceiverStream;
+    use tonic::transport::{Certificate, ClientTlsConfig, Endpoint};
+    use tonic::{Code, Streaming};
+
+    use super::proto::coordinator_client::CoordinatorClient;
+    use super::proto::coordinator_message::Message as ToWorker;
+    use super::proto::worker_message::Message as FromWorker;
+    use super::proto::{self, CoordinatorMessage, WorkerMessage};
+    use super::{prove, TlsIdentity, WORKER};
+    use crate::test_support::{compiled_ir, AS_OF, EXECUTION, EXECUTION_RECORDS};
+    use crate::{CancellationToken, Cancelled};
+
+    const SECRET: &str = "s3cret";
+
+    /// A self-signed certificate for 127.0.0.1, as the coordinator's
+    /// identity and as what workers trust.
+    fn tls() -> (TlsIdentity, Vec<u8>) {
+        let certified = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+        let cert = certified.cert.pem().into_bytes();
+        let identity = TlsIdentity {
+            cert: cert.clone(),
+            key: certified.key_pair.serialize_pem().into_bytes(),
+        };
+        (identity, cert)
+    }
+
+    /// A hand-driven worker's stream, past the handshake and the plan.
+    struct Stream {
+        runtime: Runtime,
+        outbound: mpsc::Sender<WorkerMessage>,
+        inbound: Streaming<CoordinatorMessage>,
+    }
+
+    impl Stream {
+        fn connect(address: &str, ca: &[u8]) -> Stream {
+            let runtime = Runtime::new().unwrap();
+            let tls = ClientTlsConfig::new()
+                .ca_certificate(Certificate::from_pem(ca))
+                .domain_name("127.0.0.1");
+            let endpoint = Endpoint::from_shared(format!("https://{}", address))
+                .unwrap()
+                .tls_config(tls)
+                .unwrap();
+            let channel = runtime.block_on(endpoint.connect()).unwrap();
+            let (outbound, requests) = mpsc::channel(4);
+            let inbound = runtime
+                .block_on(CoordinatorClient::new(channel).work(ReceiverStream::new(requests)))
+                .unwrap()
+                .into_inner();
+            let mut stream = Stream {
+                runtime,
+                outbound,
+                inbound,
+            };
+            let Ok(Some(ToWorker::Challenge(challenge))) = stream.receive() else {
+                panic!("no challenge");
+            };
+            stream.send(FromWorker::Hello(proto::Hello {
+                nonce: "00".to_string(),
+                proof: prove(SECRET, WORKER, &challenge.nonce),
+            }));
+            assert!(matches!(stream.receive(), Ok(Some(ToWorker::Welcome(_)))));
+            assert!(matches!(stream.receive(), Ok(Some(ToWorker::Plan(_)))));
+            stream
+        }
+
+        fn send(&self, message: FromWorker) {
+            self.outbound
+                .blocking_send(WorkerMessage {
+                    message: Some(message),
+                })
+                .unwrap();
+        }
+
+        fn receive(&mut self) -> Result<Option<ToWorker>, Box<tonic::Status>> {
+            Ok(self
+                .runtime
+                .block_on(self.inbound.message())?
+                .and_then(|message| message.message))
+        }
+    }
+
+    #[test]
+    fn test_distributed_runs_agree_with_local_runs() {
+        use crate::{execute_plan, work, Coordinator, HttpEmbedder};
+
+        let (identity, ca) = tls();
+        let ir = compiled_ir(EXECUTION);
+        let records: serde_json::Value = serde_json::from_str(EXECUTION_RECORDS).unwrap();
+        let local = serde_json::to_value(execute_plan(&ir, &records, AS_OF).unwrap()).unwrap();
+        let worker = |address: String, secret: &'static str, ca: Vec<u8>| {
+            thread::spawn(move || {
+                work(
+                    &address,
+                    secret,
+                    &ca,
+                    Duration::from_secs(10),
+                    &HttpEmbedder::new().unwrap(),
+                    None,
+                )
+            })
+        };
+
+        for partitions in [1, 2, 16] {
+            let coordinator =
+                Coordinator::bind("127.0.0.1:0", partitions, SECRET, &identity).unwrap();
+            let address = coordinator.local_addr().unwrap().to_string();
+            let workers = [
+                worker(address.clone(), SECRET, ca.clone()),
+                worker(address, SECRET, ca.clone()),
+            ];
+            let (result, dispatched) = coordinator
+                .execute_plan(&ir, &records, None, AS_OF)
+                .unwrap();
+            assert_eq!(
+                serde_json::to_value(&result).unwrap(),
+                local,
+                "{} partition(s)",
+                partitions
+            );
+            drop(coordinator);
+            let done: Vec<_> = workers
+                .map(|w| w.join().unwrap().unwrap())
+                .into_iter()
+                .collect();
+            assert_eq!(
+                done.iter().map(|d| d.partitions).sum::<usize>(),
+                dispatched.partitions
+            );
+            assert_eq!(
+                done.iter().map(|d| d.pairs).sum::<usize>(),
+                result.candidate_pairs
+            );
+        }
+
+        // A partition whose worker goes away is linked by the next, and so
+        // is one whose worker claims a second while holding it.
+        let coordinator = Coordinator::bind("127.0.0.1:0", 2, SECRET, &identity).unwrap();
+        let address = coordinator.local_addr().unwrap().to_string();
+        let late = {
+            let (address, ca) = (address.clone(), ca.clone());
+            thread::spawn(move || {
+                let mut deserter = Stream::connect(&address, &ca);
+                deserter.send(FromWorker::Claim(proto::Claim {}));
+                assert!(matches!(
+                    deserter.receive(),
+                    Ok(Some(ToWorker::Partition(_)))
+                ));
+                drop(deserter);
+                let mut greedy = Stream::connect(&address, &ca);
+                greedy.send(FromWorker::Claim(proto::Claim {}));
+                assert!(matches!(greedy.receive(), Ok(Some(ToWorker::Partition(_)))));
+                greedy.send(FromWorker::Claim(proto::Claim {}));
+                let refused = greedy.receive().unwrap_err();
+                assert_eq!(refused.code(), Code::FailedPrecondition);
+                assert!(refused.message().contains("while holding partition"));
+                work(
+                    &address,
+                    SECRET,
+                    &ca,
+                    Duration::from_secs(10),
+                    &HttpEmbedder::new().unwrap(),
+                    None,
+                )
+                .unwrap()
+            })
+        };
+        let (result, dispatched) = coordinator
+            .execute_plan(&ir, &records, None, AS_OF)
+            .unwrap();
+        assert_eq!(serde_json::to_value(&result).unwrap(), local);
+        assert_eq!(dispatched.workers, 3);
+        drop(coordinator);
+        assert_eq!(late.join().unwrap().partitions, dispatched.partitions);
+
+        // Workers without the secret or trusting another certificate are
+        // turned away, and so is a worker returning clusters for a partition
+        // it did not claim; a connection that says nothing holds up no one.
+        let coordinator = Coordinator::bind("127.0.0.1:0", 2, SECRET, &identity).unwrap();
+        let address = coordinator.local_addr().unwrap().to_string();
+        let silent = TcpStream::connect(&address).unwrap();
+        let intruders = {
+            let (address, ca) = (address.clone(), ca.clone());
+            thread::spawn(move || {
+                let err = worker(address.clone(), "guess", ca.clone())
+                    .join()
+                    .unwrap()
+                    .unwrap_err();
+                assert!(err.to_string().contains("shared secret"), "{}", err);
+                let (_, stranger) = tls();
+                let err = worker(address.clone(), SECRET, stranger)
+                    .join()
+                    .unwrap()
+                    .unwrap_err();
+                assert!(
+                    format!("{:#}", err).contains("Failed to reach a coordinator"),
+                    "{:#}",
+                    err
+                );
+                let mut stream = Stream::connect(&address, &ca);
+                stream.send(FromWorker::Clustered(proto::Clustered {
+                    id: 0,
+                    ..Default::default()
+                }));
+                let refused = stream.receive().unwrap_err();
+                assert!(refused.message().contains("did not claim"), "{}", refused);
+                worker(address, SECRET, ca).join().unwrap().unwrap()
+            })
+        };
+        let (result, dispatched) = coordinator
+            .execute_plan(&ir, &records, None, AS_OF)
+            .unwrap();
+        assert_eq!(serde_json::to_value(&result).unwrap(), local);
+        assert_eq!(dispatched.workers, 2);
+        drop(coordinator);
+        drop(silent);
+        assert_eq!(intruders.join().unwrap().partitions, dispatched.partitions);
+
+        // Without workers, a coordinator waits until cancelled.
+        let coordinator = Coordinator::bind("127.0.0.1:0", 2, SECRET, &identity).unwrap();
+        let expired = CancellationToken::with_timeout(Duration::from_millis(50));
+        let err = coordinator
+            .execute_plan(&ir, &records, Some(&expired), AS_OF)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Cancelled>(),
+            Some(Cancelled::TimedOut(_))
+        ));
+        assert!(Coordinator::bind("127.0.0.1:0", 0, SECRET, &identity).is_err());
+        assert!(Coordinator::bind("127.0.0.1:0", 2, "", &identity).is_err());
+        let unkeyed = TlsIdentity {
+            key: identity.cert.clone(),
+            ..identity
+        };
+        assert!(Coordinator::bind("127.0.0.1:0", 2, SECRET, &unkeyed).is_err());
+    }
+}
@@ -0,0 +1,561 @@
+use ::duckdb::core::{DataChunkHandle, FlatVector, Inserter, LogicalTypeHandle, LogicalTypeId};
+use ::duckdb::ffi::{duckdb_string_t, duckdb_string_t_data, duckdb_string_t_length};
+use ::duckdb::types::Value as SqlValue;
+use ::duckdb::vscalar::{ScalarFunctionSignature, VScalar};
+use ::duckdb::vtab::arrow::WritableVector;
+use ::duckdb::{appender_params_from_iter, params, Connection};
+use anyhow::{anyhow, Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use crate::calibration;
+use crate::cancel::{self, CancellationToken};
+use crate::commands::plan::{extract_match_strategies, MatchStrategySummary};
+use crate::embedding::Embedder;
+use crate::encoding::{self, Encoder};
+use crate::interpreter::{candidate_pairs, execute_sources_staged, ExecutionResult, Similarities};
+use crate::ir::Ir;
+use crate::learning;
+use crate::normalize::Normalization;
+use crate::sample::Sample;
+use crate::similarity::AlgorithmRegistry;
+
+/// Run `ir` over the records of each source in an in-memory DuckDB
+/// database as of the date `as_of`, embedding values for semantic rules
+/// with `embedder` and stopping between stages once `token` is cancelled.
+pub fn execute_sources(
+    ir: &Ir,
+    sources: &[(String, Sample)],
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+    as_of: &str,
+) -> Result<ExecutionResult> {
+    let engine = DuckdbEngine::open(ir)?;
+    execute_sources_staged(
+        ir,
+        sources,
+        token,
+        as_of,
+        |data, _encoder, warnings| {
+            engine.load(data)?;
+            candidate_pairs(ir, data, warnings, |warnings| {
+                engine.keyed_pairs(data, warnings)
+            })
+        },
+        |data, pairs| engine.score_pairs(data, pairs, embedder, token),
+    )
+}
+
+/// What the registered functions compute with for one engine's plan.
+struct Functions {
+    /// Each blocking key's field and transform.
+    keys: Vec<(String, String)>,
+    rules: Vec<MatchStrategySummary>,
+    normalization: Normalization,
+    encoder: Option<Encoder>,
+    algorithms: AlgorithmRegistry,
+}
+
+/// The functions of every open engine, by engine. DuckDB's scalar
+/// functions keep no state a plan could be given in, so every call names
+/// its engine as its first argument.
+static ENGINES: LazyLock<Mutex<HashMap<u64, Arc<Functions>>>> = LazyLock::new(Default::default);
+static NEXT_ENGINE: AtomicU64 = AtomicU64::new(0);
+
+/// A plan's stages over a database of loaded records: a `records` table of
+/// the columns blocking keys and rules read (`c<column>`, by the loaded
+/// records' column), and a `comparable` table of each rule's values as it
+/// compares them (`r<rule>`, in the IR's rule order).
+struct DuckdbEngine<'a> {
+    ir: &'a Ir,
+    id: u64,
+    strategies: Vec<MatchStrategySummary>,
+    normalization: Normalization,
+    connection: Connection,
+}
+
+impl<'a> DuckdbEngine<'a> {
+    /// Open an in-memory database with the plan's functions registered.
+    fn open(ir: &'a Ir) -> Result<Self> {
+        let spec = ir.to_spec();
+        let normalization = Normalization::from_spec(&spec)?;
+        let connection =
+            Connection::open_in_memory().context("Failed to open an in-memory DuckDB database")?;
+        connection.register_scalar_function::<BlockKey>("kanoniv_block_key")?;
+        connection.register_scalar_function::<Normalize>("kanoniv_normalize")?;
+        connection.register_scalar_function::<Similarity>("kanoniv_similarity")?;
+
+        let functions = Functions {
+            keys: ir
+                .blocking
+                .keys
+                .iter()
+                .map(|key| {
+                    let transform = key.transform.as_deref().unwrap_or("identity");
+                    (key.field.clone(), transform.to_string())
+                })
+                .collect(),
+            rules: extract_match_strategies(ir),
+            normalization: normalization.clone(),
+            encoder: encoding::encoder(&spec)?,
+            algorithms: AlgorithmRegistry::builtin(),
+        };
+        let id = NEXT_ENGINE.fetch_add(1, Ordering::Relaxed);
+        ENGINES.lock().unwrap().insert(id, Arc::new(functions));
+        Ok(DuckdbEngine {
+            ir,
+            id,
+            strategies: extract_match_strategies(ir),
+            normalization,
+            connection,
+        })
+    }
+
+    /// Load the columns of `data` that blocking keys and rules read, and
+    /// each rule's comparable values.
+    fn load(&self, data: &Sample) -> Result<()> {
+        let columns: BTreeSet<usize> = self
+            .ir
+            .blocking
+            .keys
+            .iter()
+            .map(|key| key.field.as_str())
+            .chain(self.strategies.iter().map(|rule| rule.field.as_str()))
+            .filter_map(|field| data.ir_column(self.ir, field))
+            .collect();
+        let definitions: String = columns
+            .iter()
+            .map(|c| format!(", c{} VARCHAR", c))
+            .collect();
+        self.connection.execute_batch(&format!(
+            "CREATE TABLE records (idx BIGINT PRIMARY KEY{});",
+            definitions
+        ))?;
+
+        let mut appender = self.connection.appender("records")?;
+        for (idx, row) in data.rows.iter().enumerate() {
+            let values = columns.iter().map(|&c| match row.get(c) {
+                Some(value) => SqlValue::Text(value.clone()),
+                None => SqlValue::Null,
+            });
+            appender.append_row(appender_params_from_iter(
+                std::iter::once(SqlValue::BigInt(idx as i64)).chain(values),
+            ))?;
+        }
+        appender.flush()?;
+        drop(appender);
+
+        let comparable: Vec<String> = self
+            .strategies
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| match data.ir_column(self.ir, &rule.field) {
+                Some(column) => format!(
+                    ", kanoniv_normalize({}::UBIGINT, {}, c{}) AS r{}",
+                    self.id, i, column, i
+                ),
+                None => format!(", NULL::VARCHAR AS r{}", i),
+            })
+            .collect();
+        self.connection.execute_batch(&format!(
+            "CREATE TABLE comparable AS SELECT idx{} FROM records;",
+            comparable.concat()
+        ))?;
+        Ok(())
+    }
+
+    /// Pairs of records sharing a blocking key value, joined in the
+    /// database, within the temporal match window; all pairs without keys
+    /// the records have columns for. As `learning::candidate_pairs` gives
+    /// them, but without its cap.
+    fn keyed_pairs(
+        &self,
+        data: &Sample,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<(usize, usize)>> {
+        self.connection.execute_batch(
+            "CREATE TABLE block_keys (key INTEGER NOT NULL, idx BIGINT NOT NULL, value VARCHAR NOT NULL);",
+        )?;
+        let mut read = 0;
+        for (i, key) in self.ir.blocking.keys.iter().enumerate() {
+            let Some(column) = data.ir_column(self.ir, &key.field) else {
+                warnings.push(format!(
+                    "The data has no column for blocking key '{}'",
+                    key.field
+                ));
+                continue;
+            };
+            read += 1;
+            self.connection.execute_batch(&format!(
+                "INSERT INTO block_keys (key, idx, value)
+                 SELECT {1}, idx, value
+                 FROM (SELECT idx, kanoniv_block_key({0}::UBIGINT, {1}, c{2}) AS value FROM records)
+                 WHERE value IS NOT NULL",
+                self.id, i, column
+            ))?;
+        }
+        let query = match read {
+            0 => "SELECT a.idx, b.idx FROM records a JOIN records b ON b.idx > a.idx",
+            _ => {
+                "SELECT DISTINCT a.idx, b.idx FROM block_keys a
+                 JOIN block_keys b ON b.key = a.key AND b.value = a.value AND b.idx > a.idx"
+            }
+        };
+
+        let in_window = learning::match_window(self.ir, data, warnings);
+        let mut statement = self.connection.prepare(query)?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)? as usize,
+                row.get::<_, i64>(1)? as usize,
+            ))
+        })?;
+        let mut pairs = Vec::new();
+        for row in rows {
+            let (a, b) = row?;
+            if in_window(a, b) {
+                pairs.push((a, b));
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Each rule's similarity for each of `pairs`, as
+    /// `interpreter::score_pairs` gives them: exact and fuzzy rules are
+    /// scored in the database, semantic rules through `embedder`.
+    fn score_pairs(
+        &self,
+        data: &Sample,
+        pairs: &[(usize, usize)],
+        embedder: &dyn Embedder,
+        token: Option<&CancellationToken>,
+    ) -> Result<Similarities> {
+        self.connection.execute_batch(
+            "CREATE TABLE pairs (seq BIGINT PRIMARY KEY, a BIGINT NOT NULL, b BIGINT NOT NULL);",
+        )?;
+        let mut appender = self.connection.appender("pairs")?;
+        for (seq, &(a, b)) in pairs.iter().enumerate() {
+            appender.append_row(params![seq as i64, a as i64, b as i64])?;
+        }
+        appender.flush()?;
+        drop(appender);
+        cancel::check(token)?;
+
+        // Rules the database scores, by index, with their expressions.
+        let scored_here: Vec<(usize, String)> = self
+            .strategies
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.match_type != "semantic")
+            .filter(|(_, rule)| data.ir_column(self.ir, &rule.field).is_some())
+            .map(|(i, rule)| {
+                let expression = match rule.match_type.as_str() {
+                    "exact" => format!(
+                        "CASE WHEN l.r{0} IS NULL OR r.r{0} IS NULL THEN NULL \
+                         WHEN l.r{0} = r.r{0} THEN 1.0 ELSE 0.0 END",
+                        i
+                    ),
+                    _ => format!(
+                        "kanoniv_similarity({1}::UBIGINT, {0}, l.r{0}, r.r{0})",
+                        i, self.id
+                    ),
+                };
+                (i, expression)
+            })
+            .collect();
+        let mut columns: Vec<Vec<Option<f64>>> =
+            vec![Vec::with_capacity(pairs.len()); scored_here.len()];
+        if !scored_here.is_empty() {
+            let expressions: Vec<String> = scored_here
+                .iter()
+                .map(|(_, e)| format!("CAST({} AS DOUBLE)", e))
+                .collect();
+            let mut statement = self.connection.prepare(&format!(
+                "SELECT {} FROM pairs p
+                 JOIN comparable l ON l.idx = p.a
+                 JOIN comparable r ON r.idx = p.b
+                 ORDER BY p.seq",
+                expressions.join(", ")
+            ))?;
+            let mut rows = statement.query([])?;
+            while let Some(row) = rows.next()? {
+                for (i, column) in columns.iter_mut().enumerate() {
+                    column.push(row.get(i)?);
+                }
+            }
+        }
+        let mut from_database = scored_here.iter().map(|(i, _)| *i).zip(columns);
+
+        let spec = self.ir.to_spec();
+        let encoder: Option<Encoder> = encoding::encoder(&spec)?;
+        let mut scored = Vec::with_capacity(self.strategies.len());
+        for (i, rule) in self.strategies.iter().enumerate() {
+            cancel::check(token)?;
+            if data.ir_column(self.ir, &rule.field).is_none() {
+                scored.push(None);
+                continue;
+            }
+            if rule.match_type == "semantic" {
+                let mut semantic = learning::pair_scores(
+                    self.ir,
+                    data,
+                    std::slice::from_ref(rule),
+                    pairs,
+                    &self.normalization,
+                    encoder.as_ref(),
+                    embedder,
+                    token,
+                )?;
+                scored.push(semantic.pop().flatten());
+                continue;
+            }
+            let Some((_, mut scores)) = from_database.next().filter(|(rule, _)| *rule == i) else {
+                unreachable!("the database scores every exact and fuzzy rule with a column");
+            };
+            learning::apply_condition(
+                self.ir,
+                data,
+                rule,
+                pairs,
+                &self.normalization,
+                &mut scores,
+            )?;
+            scored.push(Some(scores));
+        }
+        Ok(scored)
+    }
+}
+
+impl Drop for DuckdbEngine<'_> {
+    fn drop(&mut self) {
+        ENGINES.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// `kanoniv_block_key(engine, key, value)`: `value`'s key for the
+/// engine's blocking key `key`, as `learning::block_key` derives it.
+struct BlockKey;
+
+impl VScalar for BlockKey {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let rows = input.len();
+        let Some(functions) = engine(input)? else {
+            return Ok(());
+        };
+        let (keys, values) = (input.flat_vector(1), input.flat_vector(2));
+        let keys = keys.as_slice_with_len::<i32>(rows);
+        let mut output = output.flat_vector();
+        for (row, &key) in keys.iter().enumerate() {
+            let (field, transform) = &functions.keys[key as usize];
+            let key = text(&values, row).and_then(|value| {
+                learning::block_key(&value, field, transform, functions.encoder.as_ref())
+            });
+            insert(&mut output, row, key);
+        }
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        text_function([LogicalTypeId::Varchar])
+    }
+}
+
+/// `kanoniv_normalize(engine, rule, value)`: `value` as the engine's
+/// rule `rule` compares it, as `learning::comparable` derives it.
+struct Normalize;
+
+impl VScalar for Normalize {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let rows = input.len();
+        let Some(functions) = engine(input)? else {
+            return Ok(());
+        };
+        let (rules, values) = (input.flat_vector(1), input.flat_vector(2));
+        let rules = rules.as_slice_with_len::<i32>(rows);
+        let mut output = output.flat_vector();
+        for (row, &rule) in rules.iter().enumerate() {
+            let field = &functions.rules[rule as usize].field;
+            let value = text(&values, row).and_then(|value| {
+                learning::comparable(
+                    &value,
+                    field,
+                    &functions.normalization,
+                    functions.encoder.as_ref(),
+                )
+            });
+            insert(&mut output, row, value);
+        }
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        text_function([LogicalTypeId::Varchar])
+    }
+}
+
+/// `kanoniv_similarity(engine, rule, left, right)`: the engine's rule
+/// `rule`'s score for two comparable values, as `calibration::score`
+/// gives it.
+struct Similarity;
+
+impl VScalar for Similarity {
+    type State = ();
+
+    unsafe fn invoke(
+        _: &Self::State,
+        input: &mut DataChunkHandle,
+        output: &mut dyn WritableVector,
+    ) -> Result<(), Box<dyn Error>> {
+        let rows = input.len();
+        let Some(functions) = engine(input)? else {
+            return Ok(());
+        };
+        let (rules, left, right) = (
+            input.flat_vector(1),
+            input.flat_vector(2),
+            input.flat_vector(3),
+        );
+        let rules = rules.as_slice_with_len::<i32>(rows);
+        let mut output = output.flat_vector();
+        for (row, &rule) in rules.iter().enumerate() {
+            let rule = &functions.rules[rule as usize];
+            match text(&left, row).zip(text(&right, row)) {
+                Some((a, b)) => {
+                    output.as_mut_slice_with_len::<f64>(rows)[row] =
+                        calibration::score(rule, &functions.algorithms, &a, &b)
+                }
+                None => output.set_null(row),
+            }
+        }
+        Ok(())
+    }
+
+    fn signatures() -> Vec<ScalarFunctionSignature> {
+        vec![ScalarFunctionSignature::exact(
+            arguments([LogicalTypeId::Varchar, LogicalTypeId::Varchar]),
+            LogicalTypeId::Double.into(),
+        )]
+    }
+}
+
+/// The functions of the engine a call names, if the chunk has rows.
+fn engine(input: &DataChunkHandle) -> Result<Option<Arc<Functions>>, Box<dyn Error>> {
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let engines = input.flat_vector(0);
+    let id = engines.as_slice_with_len::<u64>(1)[0];
+    let functions = ENGINES.lock().unwrap().get(&id).cloned();
+    functions
+        .map(Some)
+        .ok_or_else(|| anyhow!("No kanoniv engine {} is open", id).into())
+}
+
+/// The engine and index arguments every function starts with, then
+/// `rest`.
+fn arguments(rest: impl IntoIterator<Item = LogicalTypeId>) -> Vec<LogicalTypeHandle> {
+    [LogicalTypeId::UBigint, LogicalTypeId::Integer]
+        .into_iter()
+        .chain(rest)
+        .map(LogicalTypeHandle::from)
+        .collect()
+}
+
+fn text_function(rest: impl IntoIterator<Item = LogicalTypeId>) -> Vec<ScalarFunctionSignature> {
+    vec![ScalarFunctionSignature::exact(
+        arguments(rest),
+        LogicalTypeId::Varchar.into(),
+    )]
+}
+
+/// The text at `row` of a VARCHAR argument; `None` for NULL.
+fn text(vector: &FlatVector, row: usize) -> Option<String> {
+    if vector.row_is_null(row as u64) {
+        return None;
+    }
+    let mut value: duckdb_string_t = vector.as_slice_with_len::<duckdb_string_t>(row + 1)[row];
+    // SAFETY: the pointer and length are of `value`, a string DuckDB
+    // wrote, which short strings are inlined in.
+    let bytes = unsafe {
+        let length = duckdb_string_t_length(value) as usize;
+        let data = duckdb_string_t_data(&mut value);
+        std::slice::from_raw_parts(data.cast::<u8>(), length)
+    };
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn insert(output: &mut FlatVector, row: usize, value: Option<String>) {
+    match value {
+        Some(value) => output.insert(row, value.as_str()),
+        None => output.set_null(row),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, quoted_execution, AS_OF, EXECUTION, EXECUTION_RECORDS};
+
+    /// The embedded DuckDB backend joins and scores in its database but must
+    /// give what the interpreter does: on the fixture, on names that need
+    /// quoting, without blocking keys (every pair) and with a rule condition.
+    #[test]
+    fn test_duckdb_backend_agrees_with_the_interpreter() {
+        use crate::{execute_sources_with, ExecutionBackend, HttpEmbedder, Sample};
+
+        let unblocked = EXECUTION.replace(
+            "  keys:\n    - field: email\n      transform: lowercase\n    - phone\n",
+            "",
+        );
+        let conditional = EXECUTION.replace(
+            "    algorithm: jaro_winkler\n",
+            "    algorithm: jaro_winkler\n    condition: \"left.email != right.email\"\n",
+        );
+        assert_ne!(unblocked, EXECUTION);
+        assert_ne!(conditional, EXECUTION);
+        let embedder = HttpEmbedder::new().unwrap();
+        for (spec, records) in [
+            (EXECUTION.to_string(), EXECUTION_RECORDS.to_string()),
+            quoted_execution(),
+            (unblocked, EXECUTION_RECORDS.to_string()),
+            (conditional, EXECUTION_RECORDS.to_string()),
+        ] {
+            let ir = compiled_ir(&spec);
+            let records: serde_json::Value = serde_json::from_str(&records).unwrap();
+            let sources: Vec<(String, Sample)> = records
+                .as_object()
+                .unwrap()
+                .iter()
+                .map(|(name, rows)| {
+                    (
+                        name.clone(),
+                        Sample::from_json_records(rows.as_array().unwrap()).unwrap(),
+                    )
+                })
+                .collect();
+            let memory = execute_sources_with(&ir, &sources, &embedder, None, AS_OF).unwrap();
+            let duckdb = ExecutionBackend::Duckdb
+                .execute_sources(&ir, &sources, &embedder, None, AS_OF)
+                .unwrap();
+            assert!(memory.candidate_pairs > 0);
+            assert_eq!(
+                serde_json::to_value(&duckdb).unwrap(),
+                serde_json::to_value(&memory).unwrap()
+            );
+            assert_eq!(duckdb.similarities, memory.similarities);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Execution backends: where a run's stages are computed.
+//!
+//! `memory` is the reference interpreter (see `interpreter`), which pairs
+//! and scores records in Rust and stops at `MAX_PAIRS` candidate pairs.
+//! `duckdb` (with the `duckdb` feature) loads the records into an embedded,
+//! in-memory DuckDB database and pushes the heavy stages into it:
+//!
+//! - normalization, as a `kanoniv_normalize(engine, rule, value)` function
+//!   applied once per record to the columns rules compare;
+//! - blocking, as a self-join on a table of each record's key values
+//!   (`kanoniv_block_key(engine, key, value)` derives them), with no pair
+//!   cap;
+//! - exact rules, as equality in SQL, and fuzzy rules through a registered
+//!   `kanoniv_similarity(engine, rule, left, right)` function.
+//!
+//! DuckDB's functions take no state, so each call names the engine whose
+//! plan it computes with.
+//!
+//! Only the columns blocking keys and rules read are loaded, so wide
+//! inputs, such as Parquet files with many columns, cost little more than
+//! narrow ones. Sorted-neighbourhood windows and canopies, semantic rules
+//! and rule conditions are computed as the interpreter does, and deciding,
+//! clustering and survivorship are the interpreter's own, so both backends
+//! give the same result for the same records.
+
+#[cfg(feature = "duckdb")]
+pub mod duckdb;
+
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cancel::CancellationToken;
+use crate::embedding::Embedder;
+use crate::interpreter::{execute_sources_with, ExecutionResult};
+use crate::ir::Ir;
+use crate::sample::Sample;
+
+pub const BACKENDS: &[&str] = &["memory", "duckdb"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutionBackend {
+    #[default]
+    Memory,
+    Duckdb,
+}
+
+impl ExecutionBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExecutionBackend::Memory => "memory",
+            ExecutionBackend::Duckdb => "duckdb",
+        }
+    }
+
+    /// Run `ir` over the records of each source, given as
+    /// `(source, sample)`, as of the date `as_of`, on this backend.
+    pub fn execute_sources(
+        &self,
+        ir: &Ir,
+        sources: &[(String, Sample)],
+        embedder: &dyn Embedder,
+        token: Option<&CancellationToken>,
+        as_of: &str,
+    ) -> Result<ExecutionResult> {
+        match self {
+            ExecutionBackend::Memory => execute_sources_with(ir, sources, embedder, token, as_of),
+            ExecutionBackend::Duckdb => execute_duckdb(ir, sources, embedder, token, as_of),
+        }
+    }
+}
+
+impl FromStr for ExecutionBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "memory" => Ok(ExecutionBackend::Memory),
+            "duckdb" => Ok(ExecutionBackend::Duckdb),
+            _ => bail!(
+                "Unknown execution backend '{}'. Use {}",
+                s,
+                BACKENDS.join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(feature = "duckdb")]
+fn execute_duckdb(
+    ir: &Ir,
+    sources: &[(String, Sample)],
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+    as_of: &str,
+) -> Result<ExecutionResult> {
+    duckdb::execute_sources(ir, sources, embedder, token, as_of)
+}
+
+#[cfg(not(feature = "duckdb"))]
+fn execute_duckdb(
+    _ir: &Ir,
+    _sources: &[(String, Sample)],
+    _embedder: &dyn Embedder,
+    _token: Option<&CancellationToken>,
+    _as_of: &str,
+) -> Result<ExecutionResult> {
+    bail!("kanoniv was built without the `duckdb` feature")
+}
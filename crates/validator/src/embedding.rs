@@ -0,0 +1,294 @@
+//! Embedding models for semantic match rules.
+//!
+//! A `semantic` rule compares values by the cosine similarity of their
+//! embeddings rather than by characters, which suits values string metrics
+//! handle poorly (`IBM` and `International Business Machines`):
+//!
+//! ```yaml
+//! rules:
+//!   - name: company_semantic
+//!     type: semantic
+//!     field: company_name
+//!     model: text-embedding-3-small
+//!     endpoint: https://api.openai.com/v1/embeddings
+//!     threshold: 0.88
+//! ```
+//!
+//! Engines embed values through an `Embedder`; `HttpEmbedder` calls the
+//! rule's endpoint, and tests or engines with a local model supply their
+//! own. Scores are cosine similarities, with opposed vectors scoring 0 so
+//! rule scores stay between 0 and 1. Builds without the `remote` feature
+//! have an `HttpEmbedder` that refuses every call.
+
+#[cfg(feature = "remote")]
+use anyhow::Context;
+use anyhow::{bail, Result};
+#[cfg(feature = "remote")]
+use reqwest::blocking::Client;
+#[cfg(feature = "remote")]
+use reqwest::header::CONTENT_TYPE;
+#[cfg(feature = "remote")]
+use serde::Deserialize;
+#[cfg(feature = "remote")]
+use serde_json::json;
+use std::collections::HashMap;
+
+/// Environment variable holding a bearer token for embedding endpoints.
+pub const TOKEN_ENV: &str = "KANONIV_EMBEDDING_TOKEN";
+
+/// Values sent to an embedder per call.
+pub const BATCH_SIZE: usize = 128;
+
+/// The model a semantic rule embeds values with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmbeddingModel {
+    pub model: String,
+    pub endpoint: String,
+}
+
+impl EmbeddingModel {
+    pub fn new(model: impl Into<String>, endpoint: impl Into<String>) -> Self {
+        EmbeddingModel {
+            model: model.into(),
+            endpoint: endpoint.into(),
+        }
+    }
+}
+
+/// Turns text into vectors.
+pub trait Embedder {
+    /// One vector per text, in order.
+    fn embed(&self, model: &EmbeddingModel, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+}
+
+/// An embedder calling the model's endpoint with OpenAI-style requests:
+/// `POST {"model": ..., "input": [...]}`, answered with
+/// `{"data": [{"embedding": [...]}, ...]}`.
+#[cfg(feature = "remote")]
+pub struct HttpEmbedder {
+    client: Client,
+    token: Option<String>,
+}
+
+#[cfg(feature = "remote")]
+impl HttpEmbedder {
+    pub fn new() -> Result<Self> {
+        Ok(HttpEmbedder {
+            client: Client::builder()
+                .build()
+                .with_context(|| "Failed to create HTTP client")?,
+            token: std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty()),
+        })
+    }
+}
+
+#[cfg(feature = "remote")]
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[cfg(feature = "remote")]
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+#[cfg(feature = "remote")]
+impl Embedder for HttpEmbedder {
+    fn embed(&self, model: &EmbeddingModel, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut request = self
+            .client
+            .post(&model.endpoint)
+            .header(CONTENT_TYPE, "application/json")
+            .body(json!({ "model": model.model, "input": texts }).to_string());
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .with_context(|| format!("Failed to call {}", model.endpoint))?;
+        if !response.status().is_success() {
+            bail!(
+                "Embedding request to {} failed: HTTP {}",
+                model.endpoint,
+                response.status()
+            );
+        }
+        let body: EmbeddingResponse = serde_json::from_slice(&response.bytes()?)
+            .with_context(|| format!("Unexpected embedding response from {}", model.endpoint))?;
+        Ok(body.data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+/// Without the `remote` feature there is no HTTP client to call endpoints.
+#[cfg(not(feature = "remote"))]
+pub struct HttpEmbedder;
+
+#[cfg(not(feature = "remote"))]
+impl HttpEmbedder {
+    pub fn new() -> Result<Self> {
+        Ok(HttpEmbedder)
+    }
+}
+
+#[cfg(not(feature = "remote"))]
+impl Embedder for HttpEmbedder {
+    fn embed(&self, model: &EmbeddingModel, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        bail!(
+            "Cannot call {}: kanoniv was built without the `remote` feature",
+            model.endpoint
+        )
+    }
+}
+
+/// Embed each distinct value once, `BATCH_SIZE` at a time.
+pub fn embed_all<'a>(
+    embedder: &dyn Embedder,
+    model: &EmbeddingModel,
+    values: impl IntoIterator<Item = &'a str>,
+) -> Result<HashMap<String, Vec<f32>>> {
+    let mut distinct: Vec<String> = values.into_iter().map(str::to_string).collect();
+    distinct.sort();
+    distinct.dedup();
+
+    let mut vectors = HashMap::with_capacity(distinct.len());
+    for batch in distinct.chunks(BATCH_SIZE) {
+        let embedded = embedder.embed(model, batch)?;
+        if embedded.len() != batch.len() {
+            bail!(
+                "Model '{}' returned {} embeddings for {} values",
+                model.model,
+                embedded.len(),
+                batch.len()
+            );
+        }
+        vectors.extend(batch.iter().cloned().zip(embedded));
+    }
+    Ok(vectors)
+}
+
+/// Cosine similarity of two embeddings, from 0 (unrelated or opposed) to
+/// 1; 0 for vectors of different lengths or with no direction.
+pub fn cosine(a: &[f32], b: &[f32]) -> f64 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f64, 0.0f64, 0.0f64);
+    for (&x, &y) in a.iter().zip(b) {
+        let (x, y) = (x as f64, y as f64);
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    (dot / (norm_a.sqrt() * norm_b.sqrt())).clamp(0.0, 1.0)
+}
+
+/// Whether `endpoint` is an http(s) URL with a host.
+pub fn is_endpoint(endpoint: &str) -> bool {
+    let rest = endpoint
+        .strip_prefix("https://")
+        .or_else(|| endpoint.strip_prefix("http://"));
+    rest.and_then(|r| r.split(['/', '?']).next())
+        .is_some_and(|host| !host.is_empty() && !host.contains(char::is_whitespace))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnose_yaml;
+
+    #[test]
+    fn test_semantic_rules_validate_plan_and_calibrate() {
+        use crate::{calibrate_with, generate_plan, Embedder, EmbeddingModel, Sample};
+
+        let spec = r#"
+api_version: kanoniv/v2
+identity_version: company_v1
+entity:
+  name: company
+sources:
+  - name: crm
+    system: salesforce
+    table: accounts
+    id: account_id
+    attributes:
+      company_name: name
+rules:
+  - name: company_semantic
+    type: semantic
+    field: company_name
+    model: text-embedding-3-small
+    endpoint: https://embeddings.example.com/v1/embeddings
+    threshold: 0.9
+    weight: 1.0
+decision:
+  thresholds:
+    match: 0.9
+"#;
+        assert!(crate::validate_yaml(spec).unwrap().is_empty());
+
+        let broken = spec
+            .replace("    model: text-embedding-3-small\n", "")
+            .replace(
+                "https://embeddings.example.com/v1/embeddings",
+                "embeddings.example.com",
+            );
+        let codes: Vec<_> = diagnose_yaml(&broken)
+            .into_iter()
+            .filter(|d| d.code == "KNV0113")
+            .map(|d| d.path.unwrap())
+            .collect();
+        assert_eq!(codes, ["rules[0].model", "rules[0].endpoint"]);
+
+        // The model and endpoint reach the IR, and the plan gives semantic rules
+        // their own stage reading all entities.
+        let ir = crate::compile_to_ir(&crate::parse_yaml(spec).unwrap()).unwrap();
+        assert_eq!(ir["rules"][0]["model"], "text-embedding-3-small");
+        let plan = generate_plan(spec).unwrap();
+        let stage = &plan.execution_stages[4];
+        assert_eq!((stage.stage, stage.name.as_str()), (5, "Semantic matches"));
+        assert_eq!(stage.inputs, ["normalized_entities"]);
+        assert!(stage.description.contains("nearest neighbour"));
+        assert!(plan.execution_stages[5]
+            .inputs
+            .contains(&"semantic_match_scores".to_string()));
+        assert_eq!(plan.execution_stages.len(), 9);
+        assert_eq!(plan.match_strategies[0].evaluation_order, 5);
+
+        // Names that share no characters but mean the same company embed close.
+        struct Lookup;
+        impl Embedder for Lookup {
+            fn embed(
+                &self,
+                model: &EmbeddingModel,
+                texts: &[String],
+            ) -> anyhow::Result<Vec<Vec<f32>>> {
+                assert_eq!(model.model, "text-embedding-3-small");
+                Ok(texts
+                    .iter()
+                    .map(|t| match t.as_str() {
+                        "ibm" | "international business machines" => vec![1.0, 0.1, 0.0],
+                        "hp" | "hewlett-packard" => vec![0.0, 1.0, 0.1],
+                        _ => vec![0.1, 0.0, 1.0],
+                    })
+                    .collect())
+            }
+        }
+        let labels = Sample::from_csv(
+            "label,left_company_name,right_company_name\n\
+         match,IBM,International Business Machines\n\
+         match,HP,Hewlett-Packard\n\
+         non_match,IBM,HP\n\
+         non_match,Acme,HP\n",
+        )
+        .unwrap();
+        let calibration = calibrate_with(spec, &labels, &Lookup).unwrap();
+        let rule = &calibration.rules[0];
+        assert_eq!(rule.pairs, 4);
+        let current = rule.current.as_ref().unwrap();
+        assert_eq!((current.precision, current.recall), (1.0, 1.0));
+    }
+}
@@ -0,0 +1,626 @@
+//! Encoding: matching on identifiers neither party reveals.
+//!
+//! An `encoding` section names attributes that are compared as salted
+//! encodings rather than raw values, so two organisations can match their
+//! records (privacy-preserving record linkage) while exchanging only the
+//! encodings:
+//!
+//! ```yaml
+//! encoding:
+//!   salt_env: PARTNER_SALT    # secret shared with the partner; or salt_file
+//!   fields:
+//!     ssn: hash
+//!     full_name:
+//!       method: bloom
+//!       size: 1024            # bits, a multiple of 8
+//!       hashes: 20            # bits set per n-gram
+//!       ngram: 2
+//! ```
+//!
+//! `hash` replaces a value with its keyed digest (HMAC with the `hashing`
+//! algorithm, labelled as `hashing` labels digests, e.g. `hmac-sha256:`).
+//! Digests agree exactly when the values do, so only exact rules compare
+//! them. `bloom` encodes a value as a Bloom filter: for each n-gram of the
+//! value padded with a space at either end, `hashes` bits chosen by double
+//! hashing the n-gram's HMAC-SHA256, written `bloom:<hex>`. Fuzzy rules
+//! compare filters with `algorithm: dice`, the Dice coefficient of their
+//! bits, which follows the Dice coefficient of the values' n-grams.
+//!
+//! Values are encoded after normalization, trimmed and lowercased. Engines
+//! encode values as they read them and keep values that already carry
+//! their encoding's label, so a source may hold either; a partner encodes
+//! its data with `kanoniv tokenize --field`. Unsalted digests of short
+//! identifiers are reversed by hashing every candidate value, so encoded
+//! attributes need a salt, and like a `hashing` key it never appears in a
+//! spec or the IR.
+
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::hashing::{HashAlgorithm, HashKey, Hasher, HashingConfig, KeySource};
+use crate::normalize::Normalization;
+
+pub const METHODS: &[&str] = &["hash", "bloom"];
+
+/// Label of Bloom filter encodings.
+pub const BLOOM_PREFIX: &str = "bloom:";
+
+/// Filter size limits, in bits: smaller filters fill up on a single name,
+/// larger ones only cost space.
+pub const MIN_BLOOM_SIZE: usize = 64;
+pub const MAX_BLOOM_SIZE: usize = 8192;
+
+/// Most bits one n-gram sets.
+pub const MAX_BLOOM_HASHES: usize = 64;
+
+/// Longest n-gram; beyond this, one typo leaves few n-grams in common.
+pub const MAX_NGRAM: usize = 4;
+
+/// Bloom filter parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BloomParams {
+    pub size: usize,
+    pub hashes: usize,
+    pub ngram: usize,
+}
+
+impl Default for BloomParams {
+    fn default() -> Self {
+        BloomParams {
+            size: 1024,
+            hashes: 20,
+            ngram: 2,
+        }
+    }
+}
+
+/// How one attribute is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum FieldEncoding {
+    Hash,
+    Bloom(BloomParams),
+}
+
+impl FieldEncoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldEncoding::Hash => "hash",
+            FieldEncoding::Bloom(_) => "bloom",
+        }
+    }
+}
+
+impl fmt::Display for FieldEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldEncoding::Hash => f.write_str("hash"),
+            FieldEncoding::Bloom(params) => write!(
+                f,
+                "bloom {} bits, {} hashes, {}-grams",
+                params.size, params.hashes, params.ngram
+            ),
+        }
+    }
+}
+
+/// The `encoding` section.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Encoding {
+    /// Environment variable holding the salt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt_env: Option<String>,
+    /// File holding the salt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt_file: Option<String>,
+    /// Attribute -> encoding.
+    #[serde(default)]
+    pub fields: BTreeMap<String, FieldEncoding>,
+}
+
+impl Encoding {
+    /// Read `encoding` from a parsed spec; `None` if the spec has none.
+    /// Errors name the first problem; `check` lists them all.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(section) = spec.get("encoding").filter(|e| !e.is_null()) else {
+            return Ok(None);
+        };
+        if let Some((key, message)) = check(section).into_iter().next() {
+            match key.is_empty() {
+                true => bail!("Invalid encoding: {}", message),
+                false => bail!("Invalid encoding.{}: {}", key, message),
+            }
+        }
+        let text = |key: &str| section.get(key).and_then(Value::as_str).map(str::to_string);
+        let mut fields = BTreeMap::new();
+        for (field, value) in section
+            .get("fields")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+        {
+            fields.insert(field.clone(), field_encoding(value)?);
+        }
+        Ok(Some(Encoding {
+            salt_env: text("salt_env"),
+            salt_file: text("salt_file"),
+            fields,
+        }))
+    }
+
+    /// Where the salt is read from.
+    pub fn salt(&self) -> KeySource {
+        KeySource {
+            key_env: self.salt_env.clone(),
+            key_file: self.salt_file.as_ref().map(PathBuf::from),
+        }
+    }
+
+    pub fn field(&self, attribute: &str) -> Option<FieldEncoding> {
+        self.fields.get(attribute).copied()
+    }
+
+    /// An `Encoder` keyed with the salt, which must load; digests use
+    /// `algorithm`, and raw values are normalized with `normalization`.
+    pub fn encoder(
+        &self,
+        algorithm: HashAlgorithm,
+        normalization: Normalization,
+    ) -> Result<Encoder> {
+        let salt = self
+            .salt()
+            .load()
+            .with_context(|| "Failed to load the encoding salt")?
+            .context("encoding names no salt_env or salt_file")?;
+        Ok(self.salted(algorithm, normalization, salt))
+    }
+
+    /// An `Encoder` keyed with `salt` instead of the one the section names.
+    pub fn salted(
+        &self,
+        algorithm: HashAlgorithm,
+        normalization: Normalization,
+        salt: HashKey,
+    ) -> Encoder {
+        Encoder {
+            hasher: Hasher::keyed(algorithm, salt.clone()),
+            salt,
+            fields: self.fields.clone(),
+            normalization,
+        }
+    }
+}
+
+/// The encoder of a parsed spec, with its salt loaded, digests using the
+/// `hashing` algorithm and the spec's normalization; `None` if the spec
+/// encodes no attribute.
+pub fn encoder(spec: &Value) -> Result<Option<Encoder>> {
+    encoder_with(spec, None)
+}
+
+/// `encoder`, keyed with `salt` if one is given rather than loading the
+/// salt the spec names.
+pub fn encoder_with(spec: &Value, salt: Option<&HashKey>) -> Result<Option<Encoder>> {
+    match Encoding::from_spec(spec)?.filter(|e| !e.fields.is_empty()) {
+        Some(encoding) => {
+            let algorithm = HashingConfig::from_spec(spec)?.algorithm;
+            let normalization = Normalization::from_spec(spec)?;
+            match salt {
+                Some(salt) => Ok(Some(encoding.salted(
+                    algorithm,
+                    normalization,
+                    salt.clone(),
+                ))),
+                None => encoding.encoder(algorithm, normalization).map(Some),
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+fn field_encoding(value: &Value) -> Result<FieldEncoding> {
+    let method = match value {
+        Value::String(method) => method.as_str(),
+        _ => value
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or_default(),
+    };
+    match method {
+        "hash" => Ok(FieldEncoding::Hash),
+        "bloom" => {
+            let defaults = BloomParams::default();
+            let number = |key: &str| value.get(key).and_then(Value::as_u64).map(|n| n as usize);
+            Ok(FieldEncoding::Bloom(BloomParams {
+                size: number("size").unwrap_or(defaults.size),
+                hashes: number("hashes").unwrap_or(defaults.hashes),
+                ngram: number("ngram").unwrap_or(defaults.ngram),
+            }))
+        }
+        _ => bail!(
+            "Unknown encoding method '{}'. Use {}",
+            method,
+            METHODS.join(" or ")
+        ),
+    }
+}
+
+/// Problems with an `encoding` section, as (key under `encoding`, message);
+/// an empty key means the section itself.
+pub fn check(section: &Value) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    if !section.is_object() {
+        problems.push((
+            String::new(),
+            format!("expected a mapping, got {}", section),
+        ));
+        return problems;
+    }
+    if section.get("salt").is_some() {
+        problems.push((
+            "salt".to_string(),
+            "the salt must not be written into the spec; name salt_env or salt_file".to_string(),
+        ));
+    }
+    for key in ["salt_env", "salt_file"] {
+        if let Some(value) = section.get(key).filter(|v| !v.is_string()) {
+            problems.push((key.to_string(), format!("expected a name, got {}", value)));
+        }
+    }
+    if section.get("salt_env").is_some() && section.get("salt_file").is_some() {
+        problems.push((
+            "salt_file".to_string(),
+            "set salt_env or salt_file, not both".to_string(),
+        ));
+    }
+    let fields = match section.get("fields") {
+        None => None,
+        Some(Value::Object(fields)) => Some(fields),
+        Some(other) => {
+            problems.push((
+                "fields".to_string(),
+                format!(
+                    "expected a mapping of attributes to encodings, got {}",
+                    other
+                ),
+            ));
+            None
+        }
+    };
+    if fields.is_some_and(|f| !f.is_empty())
+        && section.get("salt_env").is_none()
+        && section.get("salt_file").is_none()
+    {
+        problems.push((
+            "salt_env".to_string(),
+            "encoded attributes need a salt; name salt_env or salt_file".to_string(),
+        ));
+    }
+    for (field, value) in fields.into_iter().flatten() {
+        let path = format!("fields.{}", field);
+        let method = match value {
+            Value::String(method) => Some(method.as_str()),
+            Value::Object(_) => value.get("method").and_then(Value::as_str),
+            _ => None,
+        };
+        match method {
+            Some("hash") => {}
+            Some("bloom") => problems.extend(
+                check_bloom(value)
+                    .into_iter()
+                    .map(|(key, message)| (format!("{}.{}", path, key), message)),
+            ),
+            _ => problems.push((
+                path,
+                format!("expected method {}, got {}", METHODS.join(" or "), value),
+            )),
+        }
+    }
+    problems
+}
+
+fn check_bloom(value: &Value) -> Vec<(&'static str, String)> {
+    let mut problems = Vec::new();
+    let defaults = BloomParams::default();
+    let mut number =
+        |key: &'static str, default: usize, min: usize, max: usize| match value.get(key) {
+            None => Some(default),
+            Some(n) => match n.as_u64().map(|n| n as usize) {
+                Some(n) if (min..=max).contains(&n) => Some(n),
+                _ => {
+                    problems.push((
+                        key,
+                        format!("expected a whole number from {} to {}, got {}", min, max, n),
+                    ));
+                    None
+                }
+            },
+        };
+    let size = number("size", defaults.size, MIN_BLOOM_SIZE, MAX_BLOOM_SIZE);
+    let hashes = number("hashes", defaults.hashes, 1, MAX_BLOOM_HASHES);
+    number("ngram", defaults.ngram, 1, MAX_NGRAM);
+    if let Some(size) = size.filter(|s| s % 8 != 0) {
+        problems.push((
+            "size",
+            format!("expected a multiple of 8 bits, got {}", size),
+        ));
+    }
+    // A filter of m bits holding n n-grams is least ambiguous at about
+    // (m / n) ln 2 hashes; at m / 8 hashes even a short name sets most bits.
+    if let (Some(size), Some(hashes)) = (size, hashes) {
+        if hashes * 8 > size {
+            problems.push((
+                "hashes",
+                format!(
+                    "{} hashes per n-gram fill a {}-bit filter on a few n-grams; use at most {}",
+                    hashes,
+                    size,
+                    size / 8
+                ),
+            ));
+        }
+    }
+    problems
+}
+
+/// Encodes attribute values with a loaded salt.
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    hasher: Hasher,
+    salt: HashKey,
+    fields: BTreeMap<String, FieldEncoding>,
+    normalization: Normalization,
+}
+
+impl Encoder {
+    pub fn encodes(&self, attribute: &str) -> bool {
+        self.fields.contains_key(attribute)
+    }
+
+    /// The encoding of a raw `value` of `attribute`, normalized, trimmed
+    /// and lowercased first; `None` if that leaves it blank.
+    pub fn encode_raw(&self, attribute: &str, value: &str) -> Option<String> {
+        let value = self
+            .normalization
+            .apply(attribute, value)
+            .trim()
+            .to_lowercase();
+        (!value.is_empty()).then(|| self.encode(attribute, &value))
+    }
+
+    /// The encoding of `value` of `attribute`: unchanged if the attribute
+    /// is not encoded or the value already carries its encoding's label.
+    pub fn encode(&self, attribute: &str, value: &str) -> String {
+        match self.fields.get(attribute) {
+            None => value.to_string(),
+            Some(FieldEncoding::Hash) if is_digest(value, &self.hasher) => value.to_string(),
+            Some(FieldEncoding::Bloom(_)) if value.starts_with(BLOOM_PREFIX) => value.to_string(),
+            Some(FieldEncoding::Hash) => self.hasher.digest(value.as_bytes()),
+            Some(FieldEncoding::Bloom(params)) => self.bloom(value, params),
+        }
+    }
+
+    fn bloom(&self, value: &str, params: &BloomParams) -> String {
+        let mut bits = vec![0u8; params.size / 8];
+        for gram in ngrams(value, params.ngram) {
+            let mut mac = Hmac::<Sha256>::new_from_slice(self.salt.bytes())
+                .expect("HMAC takes any key length");
+            mac.update(gram.as_bytes());
+            let digest = mac.finalize().into_bytes();
+            let word = |i: usize| u64::from_le_bytes(digest[i..i + 8].try_into().unwrap());
+            let (h1, h2) = (word(0), word(8));
+            for i in 0..params.hashes as u64 {
+                let bit = (h1.wrapping_add(i.wrapping_mul(h2)) % params.size as u64) as usize;
+                bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        let hex: String = bits.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}{}", BLOOM_PREFIX, hex)
+    }
+}
+
+/// `value` of `attribute` as `encoder` encodes it; unchanged without one.
+pub fn encode(encoder: Option<&Encoder>, attribute: &str, value: String) -> String {
+    match encoder {
+        Some(encoder) => encoder.encode(attribute, &value),
+        None => value,
+    }
+}
+
+fn is_digest(value: &str, hasher: &Hasher) -> bool {
+    value
+        .strip_prefix(hasher.label())
+        .is_some_and(|rest| rest.starts_with(':'))
+}
+
+/// The distinct `n`-character n-grams of `value` padded with a space at
+/// either end (unpadded unigrams); a value shorter than `n` is one n-gram.
+pub fn ngrams(value: &str, n: usize) -> Vec<String> {
+    let n = n.max(1);
+    let padded = if n > 1 {
+        format!(" {} ", value)
+    } else {
+        value.to_string()
+    };
+    let chars: Vec<char> = padded.chars().collect();
+    if chars.is_empty() {
+        return Vec::new();
+    }
+    let mut grams: Vec<String> = chars
+        .windows(n.min(chars.len()))
+        .map(|window| window.iter().collect())
+        .collect();
+    grams.sort_unstable();
+    grams.dedup();
+    grams
+}
+
+/// Dice coefficient of the bits of two Bloom filter encodings; `None`
+/// unless both are encodings of one size.
+pub fn bloom_dice(a: &str, b: &str) -> Option<f64> {
+    let bits = |value: &str| -> Option<Vec<u8>> {
+        let hex = value.strip_prefix(BLOOM_PREFIX)?;
+        if hex.len() % 2 != 0 {
+            return None;
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect()
+    };
+    let (a, b) = (bits(a)?, bits(b)?);
+    if a.len() != b.len() {
+        return None;
+    }
+    let ones = |bytes: &[u8]| bytes.iter().map(|b| b.count_ones()).sum::<u32>();
+    let common: u32 = a.iter().zip(&b).map(|(x, y)| (x & y).count_ones()).sum();
+    let total = ones(&a) + ones(&b);
+    Some(match total {
+        0 => 0.0,
+        _ => 2.0 * common as f64 / total as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, MINIMAL};
+    use crate::{diagnose_yaml, Ir};
+
+    #[test]
+    fn test_encoding_privacy_preserving_matching() {
+        use crate::embedding::HttpEmbedder;
+        use crate::encoding::{self, bloom_dice, ngrams};
+        use crate::similarity::dice;
+        use crate::{
+            explain_pair_with, generate_kafka, generate_pyspark, generate_sql, Dialect,
+            HashAlgorithm, HashKey, Hasher,
+        };
+        use serde_json::json;
+
+        // The salt is passed in, never read from the environment the spec
+        // names.
+        let salt = HashKey::new("s3cret").unwrap();
+        let spec = MINIMAL
+        .replace("      email: email\n", "      email: email\n      ssn: ssn\n      full_name: name\n")
+        .replace(
+            "    weight: 1.0\n",
+            "    weight: 1.0\n  - name: ssn_exact\n    type: exact\n    field: ssn\n    weight: 1.0\n  - name: name_bloom\n    type: fuzzy\n    field: full_name\n    algorithm: dice\n    threshold: 0.8\n    weight: 0.5\nblocking:\n  keys:\n    - ssn\n",
+        )
+        + "privacy:\n  retention_days: 730\n  fields:\n    email:\n      masking: hash\n    ssn:\n      masking: hash\n    full_name:\n      masking: redact\n"
+        + "encoding:\n  salt_env: KANONIV_TEST_ENCODING_SALT\n  fields:\n    ssn: hash\n    full_name:\n      method: bloom\n      size: 512\n      hashes: 16\n";
+        assert!(
+            diagnose_yaml(&spec).is_empty(),
+            "{:?}",
+            diagnose_yaml(&spec)
+        );
+        let ir = compiled_ir(&spec);
+        let recompiled = Ir::from_value(&crate::compile_to_ir(&ir.to_spec()).unwrap()).unwrap();
+        assert_eq!(recompiled.encoding, ir.encoding);
+
+        // Malformed sections and what cannot compare encodings.
+        let findings = |yaml: &str| -> Vec<(String, String)> {
+            diagnose_yaml(yaml)
+                .into_iter()
+                .map(|d| (d.code.to_string(), d.path.unwrap_or_default()))
+                .collect()
+        };
+        let finding = |code: &str, path: &str| (code.to_string(), path.to_string());
+        assert_eq!(
+            findings(&spec.replace("      hashes: 16\n", "      hashes: 16\n      ngram: 9\n")),
+            [finding("KNV0130", "encoding.fields.full_name.ngram")]
+        );
+        assert_eq!(
+            findings(&spec.replace("size: 512", "size: 500")),
+            [finding("KNV0130", "encoding.fields.full_name.size")]
+        );
+        assert_eq!(
+            findings(&spec.replace(
+                "  salt_env: KANONIV_TEST_ENCODING_SALT\n",
+                "  salt: s3cret\n"
+            ))[0],
+            finding("KNV0130", "encoding.salt")
+        );
+        assert_eq!(
+            findings(&spec.replace("    ssn: hash\n", "    ssn: hash\n    phone: hash\n")),
+            [finding("KNV0101", "encoding.fields.phone")]
+        );
+        assert_eq!(
+            findings(&spec.replace("algorithm: dice", "algorithm: jaro_winkler")),
+            [finding("KNV0131", "rules[2].algorithm")]
+        );
+        assert_eq!(
+            findings(&spec.replace("type: exact\n    field: ssn", "type: fuzzy\n    field: ssn")),
+            [finding("KNV0131", "rules[1].type")]
+        );
+        assert_eq!(
+            findings(&spec.replace(
+                "    - ssn\n",
+                "    - field: ssn\n      transform: first_1\n"
+            )),
+            [finding("KNV0131", "blocking.keys[0].transform")]
+        );
+
+        // Both parties encode under the shared salt; encodings pass through.
+        let encoder = encoding::encoder_with(&crate::parse_yaml(&spec).unwrap(), Some(&salt))
+            .unwrap()
+            .unwrap();
+        let token = encoder.encode_raw("ssn", " 123-45-6789 ").unwrap();
+        let expected = Hasher::keyed(HashAlgorithm::Sha256, salt.clone()).digest(b"123-45-6789");
+        assert_eq!(token, expected);
+        assert_eq!(encoder.encode("ssn", &token), token);
+        assert!(!encoder.encodes("email"));
+        let jon = encoder.encode_raw("full_name", "Jon Smith").unwrap();
+        assert_eq!(jon.len(), "bloom:".len() + 512 / 4);
+        assert_eq!(encoder.encode("full_name", &jon), jon);
+        assert_eq!(encoder.encode_raw("full_name", "  "), None);
+        let john = encoder.encode_raw("full_name", "john smith").unwrap();
+        let maria = encoder.encode_raw("full_name", "Maria Garcia").unwrap();
+        assert!(bloom_dice(&jon, &john).unwrap() > 0.8);
+        assert!(bloom_dice(&jon, &maria).unwrap() < 0.5);
+        assert_eq!(dice(&jon, &john), bloom_dice(&jon, &john).unwrap());
+        assert_eq!(ngrams("ann", 2), [" a", "an", "n ", "nn"]);
+        assert_eq!(dice("night", "nacht"), 0.5);
+
+        // One party's raw record against the other's encoded one.
+        let ours = json!({"email": "jon@x.com", "ssn": "123-45-6789", "name": "Jon Smith"});
+        let partner = json!({"email": "jon@x.com", "ssn": token, "name": john});
+        let embedder = HttpEmbedder::new().unwrap();
+        let explanation =
+            explain_pair_with(&spec, &ours, &partner, &embedder, Some(&salt)).unwrap();
+        assert!(explanation.compared);
+        assert_eq!(
+            explanation.blocking[0].left.as_deref(),
+            Some(expected.as_str())
+        );
+        assert!(
+            explanation
+                .rules
+                .iter()
+                .find(|r| r.rule_name == "name_bloom")
+                .unwrap()
+                .score
+                .unwrap()
+                > 0.8
+        );
+        assert_eq!(explanation.decision, "match");
+
+        let plan = crate::generate_plan(&spec).unwrap();
+        assert_eq!(plan.execution_stages[1].name, "Encode attributes");
+        assert!(plan
+            .summary
+            .contains("Encoding:     full_name (bloom 512 bits, 16 hashes, 2-grams), ssn (hash)"));
+        let sql = generate_sql(&ir, Dialect::Postgres).unwrap();
+        assert!(sql.contains("encode them with kanoniv tokenize --field: full_name, ssn"));
+        assert!(sql.contains("Bloom filter rules need an engine and are not generated: name_bloom"));
+        assert!(generate_pyspark(&ir)
+            .unwrap()
+            .contains("_encode_bloom(value, 512, 16, 2)"));
+        assert!(generate_kafka(&ir).unwrap().contains("_encode_hash("));
+    }
+}
@@ -0,0 +1,491 @@
+//! Cost and runtime estimates of a plan, from source row counts.
+//!
+//! Row counts come from each source's `rows`, or `kanoniv plan --rows`.
+//! With a sample (`plan --sample`) the measured pair counts are scaled up,
+//! assuming block sizes grow in proportion to the data. Without one, each
+//! blocking key is taken to split the records into equal blocks, more of
+//! them the finer its transform; sorted-neighbourhood blocking pairs each
+//! record with its window.
+//!
+//! Runtimes and memory assume a single worker at the throughputs below.
+//! They are orders of magnitude, for comparing specs and catching the ones
+//! that will not finish, not predictions.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::commands::plan::{
+    granularity, BlockingAnalysis, ExecutionStage, Granularity, MatchStrategySummary, RiskFlag,
+};
+use crate::ir::Ir;
+
+/// Candidate pairs beyond this are flagged `ESTIMATED_PAIRS_EXCEEDS_BUDGET`,
+/// unless the plan sets its own budget.
+pub const DEFAULT_PAIR_BUDGET: u64 = 1_000_000_000;
+
+/// Equal blocks a key is taken to split records into, by granularity.
+const COARSE_BLOCKS: f64 = 100.0;
+const NORMAL_BLOCKS: f64 = 10_000.0;
+const FINE_BLOCKS: f64 = 1_000_000.0;
+
+/// Similarity of two unrelated records, for the pairs LSH keeps without a
+/// sample.
+const LSH_BACKGROUND_SIMILARITY: f64 = 0.2;
+
+/// Nanoseconds to read, normalize or write one record.
+const RECORD_NS: f64 = 2_000.0;
+/// Nanoseconds to compute and sort one blocking key of one record.
+const KEY_NS: f64 = 500.0;
+/// Nanoseconds to emit, score or cluster one candidate pair.
+const PAIR_NS: f64 = 50.0;
+/// Nanoseconds to embed one record and search its neighbours.
+const EMBED_NS: f64 = 2_000_000.0;
+/// Neighbours a semantic rule compares each record with.
+const SEMANTIC_NEIGHBOURS: u64 = 10;
+
+/// Bytes a record takes in memory, plus each of its attributes.
+const RECORD_BYTES: f64 = 128.0;
+const ATTRIBUTE_BYTES: f64 = 32.0;
+/// Bytes per blocking key value.
+const KEY_BYTES: f64 = 32.0;
+/// Bytes per candidate pair: two record ids and a score.
+const PAIR_BYTES: f64 = 24.0;
+/// Bytes per embedding (768 32-bit floats).
+const EMBEDDING_BYTES: f64 = 3_072.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostEstimate {
+    /// Records across the sources with a row count.
+    pub records: u64,
+    /// All record pairs, n(n-1)/2.
+    pub total_pairs: u64,
+    /// Pairs sharing a block: the pairs the rules compare.
+    pub candidate_pairs: u64,
+    /// `sample` when scaled up from `plan --sample`, else `heuristic`.
+    pub basis: String,
+    pub keys: Vec<KeyEstimate>,
+    pub rules: Vec<RuleEstimate>,
+    pub stages: Vec<StageEstimate>,
+    /// All stages, one after the other.
+    pub seconds: f64,
+    /// The most memory any one stage holds.
+    pub peak_memory_bytes: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyEstimate {
+    pub name: String,
+    pub transformation: String,
+    /// Record pairs sharing a value of this key.
+    pub pairs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleEstimate {
+    pub rule_name: String,
+    /// Pairs the rule scores: the candidate pairs, or each record's
+    /// nearest neighbours for a semantic rule.
+    pub comparisons: u64,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageEstimate {
+    pub stage: usize,
+    pub name: String,
+    pub seconds: f64,
+    pub memory_bytes: u64,
+}
+
+/// Row counts by source: each source's `rows` in `spec`, overridden by
+/// `overrides`. An override for a source `ir` lacks is an error.
+pub fn row_counts(
+    ir: &Ir,
+    spec: &Value,
+    overrides: &BTreeMap<String, u64>,
+) -> Result<BTreeMap<String, u64>> {
+    let declared: Vec<(String, &Value)> = match spec.get("sources") {
+        Some(Value::Array(sources)) => sources
+            .iter()
+            .filter_map(|s| Some((s.get("name")?.as_str()?.to_string(), s.get("rows")?)))
+            .collect(),
+        Some(Value::Object(sources)) => sources
+            .iter()
+            .filter_map(|(name, s)| Some((name.clone(), s.get("rows")?)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut rows: BTreeMap<String, u64> = declared
+        .into_iter()
+        .filter_map(|(name, rows)| Some((name, rows.as_f64().filter(|r| *r >= 0.0)? as u64)))
+        .collect();
+    for (name, count) in overrides {
+        if !ir.sources.iter().any(|s| s.name == *name) {
+            bail!("No source named '{}' to set rows for", name);
+        }
+        rows.insert(name.clone(), *count);
+    }
+    Ok(rows)
+}
+
+/// `--rows` flags, `SOURCE=ROWS` with ROWS like `2500000`, `2_500_000` or
+/// `2.5e6`.
+pub fn parse_rows(flags: &[String]) -> Result<BTreeMap<String, u64>> {
+    let mut rows = BTreeMap::new();
+    for flag in flags {
+        let Some((source, count)) = flag.split_once('=') else {
+            bail!("Invalid row count '{}': expected SOURCE=ROWS", flag);
+        };
+        let count = match count.trim().replace('_', "").parse::<f64>() {
+            Ok(count) if count >= 0.0 && count.is_finite() => count,
+            _ => bail!(
+                "Invalid row count '{}' for source '{}'",
+                count,
+                source.trim()
+            ),
+        };
+        rows.insert(source.trim().to_string(), count as u64);
+    }
+    Ok(rows)
+}
+
+impl CostEstimate {
+    /// The estimate for a plan of `ir`, or `None` without any row count.
+    pub fn new(
+        ir: &Ir,
+        rows: &BTreeMap<String, u64>,
+        blocking: &BlockingAnalysis,
+        rules: &[MatchStrategySummary],
+        stages: &[ExecutionStage],
+    ) -> Option<Self> {
+        if rows.is_empty() {
+            return None;
+        }
+        let mut warnings: Vec<String> = ir
+            .sources
+            .iter()
+            .filter(|s| !rows.contains_key(&s.name))
+            .map(|s| format!("No row count for source '{}'; it is left out", s.name))
+            .collect();
+        let records: u64 = ir.sources.iter().filter_map(|s| rows.get(&s.name)).sum();
+        let n = records as f64;
+        let total = n * (n - 1.0).max(0.0) / 2.0;
+
+        // Pairs per key and overall, scaled from the sample when measured.
+        let scale = blocking
+            .sample
+            .as_ref()
+            .filter(|s| s.total_pairs > 0)
+            .map(|s| total / s.total_pairs as f64);
+        let keys: Vec<KeyEstimate> = blocking
+            .keys
+            .iter()
+            .map(|key| {
+                let pairs = match (scale, &key.sample) {
+                    (Some(scale), Some(stats)) => stats.pairs as f64 * scale,
+                    _ => match &blocking.lsh {
+                        Some(lsh) => {
+                            total * lsh.config.collision_probability(LSH_BACKGROUND_SIMILARITY)
+                        }
+                        None => equal_blocks(n, granularity(&key.transformation)),
+                    },
+                };
+                KeyEstimate {
+                    name: key.name.clone(),
+                    transformation: key.transformation.clone(),
+                    pairs: pairs.min(total) as u64,
+                }
+            })
+            .collect();
+        let candidates = match (scale, &blocking.sample) {
+            (Some(scale), Some(sample)) => sample.candidate_pairs as f64 * scale,
+            _ if blocking.keys.is_empty() && blocking.strategy == "none" => total,
+            _ => {
+                let strategy = match (&blocking.sorted_neighborhood, &blocking.canopy) {
+                    (Some(window), _) => {
+                        let w = window.window as f64;
+                        n * (w - 1.0) - w * (w - 1.0) / 2.0
+                    }
+                    // Canopies split records about as a plain key does.
+                    (None, Some(_)) => equal_blocks(n, Granularity::Normal),
+                    (None, None) => 0.0,
+                };
+                keys.iter().map(|k| k.pairs as f64).sum::<f64>() + strategy.max(0.0)
+            }
+        }
+        .min(total);
+        if blocking.sample.is_none() && !blocking.keys.is_empty() {
+            warnings.push(
+                "Pairs per key assume evenly sized blocks; measure them with --sample".to_string(),
+            );
+        }
+
+        let rule_estimates: Vec<RuleEstimate> = rules
+            .iter()
+            .map(|rule| {
+                let (comparisons, seconds) = match rule.match_type.as_str() {
+                    "semantic" => {
+                        let comparisons = records.saturating_mul(SEMANTIC_NEIGHBOURS);
+                        (comparisons, n * EMBED_NS / 1e9)
+                    }
+                    _ => (candidates as u64, candidates * comparison_ns(rule) / 1e9),
+                };
+                RuleEstimate {
+                    rule_name: rule.rule_name.clone(),
+                    comparisons,
+                    seconds,
+                }
+            })
+            .collect();
+
+        let attributes = ir
+            .sources
+            .iter()
+            .map(|s| s.attributes.len())
+            .max()
+            .unwrap_or(0) as f64;
+        let record_bytes = n * (RECORD_BYTES + ATTRIBUTE_BYTES * attributes);
+        let pair_bytes = candidates * PAIR_BYTES;
+        // A match stage takes its rules' time and holds their scores; one
+        // without rules costs nothing.
+        let match_stage = |kind: &dyn Fn(&str) -> bool, bytes: f64| -> (f64, f64) {
+            let seconds: Vec<f64> = rules
+                .iter()
+                .zip(&rule_estimates)
+                .filter(|(rule, _)| kind(&rule.match_type))
+                .map(|(_, estimate)| estimate.seconds)
+                .collect();
+            if seconds.is_empty() {
+                (0.0, 0.0)
+            } else {
+                (seconds.iter().sum::<f64>() * 1e9, bytes)
+            }
+        };
+        let semantic = rules.iter().filter(|r| r.match_type == "semantic").count() as f64;
+        let key_count = blocking.keys.len().max(1) as f64;
+
+        let stage_estimates: Vec<StageEstimate> = stages
+            .iter()
+            .map(|stage| {
+                let (ns, bytes) = match stage.name.as_str() {
+                    "Generate blocking keys" => (
+                        n * key_count * KEY_NS + candidates * PAIR_NS,
+                        n * key_count * KEY_BYTES + pair_bytes,
+                    ),
+                    "Apply match window" => (candidates * PAIR_NS, pair_bytes),
+                    "Exact matches" => match_stage(&|t| t == "exact", pair_bytes),
+                    "Fuzzy matches" => {
+                        match_stage(&|t| t != "exact" && t != "semantic", pair_bytes)
+                    }
+                    "Semantic matches" => {
+                        match_stage(&|t| t == "semantic", n * EMBEDDING_BYTES * semantic)
+                    }
+                    "Score & decide" => {
+                        (candidates * PAIR_NS * rules.len().max(1) as f64, pair_bytes)
+                    }
+                    "Cluster entities" | "Repair clusters" => {
+                        (candidates * PAIR_NS + n * PAIR_NS, n * 16.0)
+                    }
+                    _ => (n * RECORD_NS, record_bytes),
+                };
+                StageEstimate {
+                    stage: stage.stage,
+                    name: stage.name.clone(),
+                    seconds: ns / 1e9,
+                    memory_bytes: bytes as u64,
+                }
+            })
+            .collect();
+
+        Some(CostEstimate {
+            records,
+            total_pairs: total as u64,
+            candidate_pairs: candidates as u64,
+            basis: if scale.is_some() {
+                "sample"
+            } else {
+                "heuristic"
+            }
+            .to_string(),
+            keys,
+            rules: rule_estimates,
+            seconds: stage_estimates.iter().map(|s| s.seconds).sum(),
+            peak_memory_bytes: stage_estimates
+                .iter()
+                .map(|s| s.memory_bytes)
+                .max()
+                .unwrap_or(0),
+            stages: stage_estimates,
+            warnings,
+        })
+    }
+
+    /// `ESTIMATED_PAIRS_EXCEEDS_BUDGET` if more candidate pairs than
+    /// `budget` are expected.
+    pub fn budget_flag(&self, budget: u64) -> Option<RiskFlag> {
+        if self.candidate_pairs <= budget {
+            return None;
+        }
+        Some(RiskFlag {
+            severity: "high".to_string(),
+            code: "ESTIMATED_PAIRS_EXCEEDS_BUDGET".to_string(),
+            message: format!(
+                "About {} candidate pairs over {} records, above the budget of {}",
+                count(self.candidate_pairs as f64),
+                count(self.records as f64),
+                count(budget as f64)
+            ),
+            recommendation: "Add or tighten blocking keys, or raise the budget with --pair-budget"
+                .to_string(),
+        })
+    }
+}
+
+/// Pairs among `n` records split evenly into as many blocks as keys of
+/// this granularity make (no more blocks than records).
+fn equal_blocks(n: f64, grain: Granularity) -> f64 {
+    let blocks = match grain {
+        Granularity::Coarse => COARSE_BLOCKS,
+        Granularity::Normal => NORMAL_BLOCKS,
+        Granularity::Fine => FINE_BLOCKS,
+    };
+    (n * (n / blocks - 1.0) / 2.0).max(0.0)
+}
+
+/// Nanoseconds one comparison of `rule` takes.
+fn comparison_ns(rule: &MatchStrategySummary) -> f64 {
+    if rule.match_type == "exact" {
+        return 50.0;
+    }
+    match rule.algorithm.as_deref() {
+        Some("soundex" | "metaphone" | "double_metaphone") => 300.0,
+        Some("levenshtein" | "damerau_levenshtein") => 2_000.0,
+        Some("trigram" | "jaccard" | "cosine") => 1_500.0,
+        _ => 800.0,
+    }
+}
+
+/// `950`, `12.5K`, `3.2M`, `48.0B`, `1.2T`.
+pub fn count(n: f64) -> String {
+    match n {
+        n if n >= 1e12 => format!("{:.1}T", n / 1e12),
+        n if n >= 1e9 => format!("{:.1}B", n / 1e9),
+        n if n >= 1e6 => format!("{:.1}M", n / 1e6),
+        n if n >= 1e4 => format!("{:.1}K", n / 1e3),
+        n => format!("{}", n.round()),
+    }
+}
+
+/// `0.4 s`, `12 min`, `3.5 h`, `4.2 days`.
+pub fn duration(seconds: f64) -> String {
+    match seconds {
+        s if s >= 172_800.0 => format!("{:.1} days", s / 86_400.0),
+        s if s >= 7_200.0 => format!("{:.1} h", s / 3_600.0),
+        s if s >= 120.0 => format!("{:.0} min", s / 60.0),
+        s if s >= 10.0 => format!("{:.0} s", s),
+        s => format!("{:.1} s", s),
+    }
+}
+
+/// `512 B`, `3.4 MB`, `1.2 GB`.
+pub fn bytes(n: u64) -> String {
+    let n = n as f64;
+    match n {
+        n if n >= 1e12 => format!("{:.1} TB", n / 1e12),
+        n if n >= 1e9 => format!("{:.1} GB", n / 1e9),
+        n if n >= 1e6 => format!("{:.1} MB", n / 1e6),
+        n if n >= 1e3 => format!("{:.1} KB", n / 1e3),
+        n => format!("{} B", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_plan_estimates_costs_from_row_counts() {
+        use crate::{generate_plan_with, PlanOptions, Sample};
+
+        let spec = include_str!("../conformance/multi_source/spec.yaml").replace(
+            "    id: contact_id\n",
+            "    id: contact_id\n    rows: 20000\n",
+        );
+        assert!(crate::generate_plan(MINIMAL).unwrap().estimate.is_none());
+
+        // 30,000 records; each key splits them into 10,000 even blocks.
+        let options = PlanOptions {
+            rows: [("billing".to_string(), 10_000)].into_iter().collect(),
+            ..PlanOptions::default()
+        };
+        let plan = generate_plan_with(&spec, &options).unwrap();
+        let estimate = plan.estimate.as_ref().unwrap();
+        assert_eq!(
+            (estimate.records, estimate.total_pairs),
+            (30_000, 449_985_000)
+        );
+        assert_eq!(estimate.basis, "heuristic");
+        let keys: Vec<u64> = estimate.keys.iter().map(|k| k.pairs).collect();
+        assert_eq!(keys, [30_000, 30_000, 30_000]);
+        assert_eq!(estimate.candidate_pairs, 90_000);
+        assert!(estimate.rules.iter().all(|r| r.comparisons == 90_000));
+        assert_eq!(estimate.stages.len(), plan.execution_stages.len());
+        assert!(estimate.warnings[0].contains("No row count for source 'support'"));
+        assert!(plan
+            .summary
+            .contains("Estimate:     ~90.0K candidate pairs of 450.0M"));
+        assert!(!plan
+            .risk_flags
+            .iter()
+            .any(|f| f.code == "ESTIMATED_PAIRS_EXCEEDS_BUDGET"));
+
+        // Over the budget, the plan is flagged.
+        let tight = PlanOptions {
+            pair_budget: Some(50_000),
+            ..options.clone()
+        };
+        let plan = generate_plan_with(&spec, &tight).unwrap();
+        let flag = plan
+            .risk_flags
+            .iter()
+            .find(|f| f.code == "ESTIMATED_PAIRS_EXCEEDS_BUDGET")
+            .unwrap();
+        assert_eq!(flag.severity, "high");
+        assert!(flag
+            .message
+            .contains("90.0K candidate pairs over 30.0K records"));
+
+        // A sample's pair counts are scaled up to the row counts.
+        let mut csv = String::from("email,family_name\n");
+        for i in 0..20 {
+            let name = if i < 12 {
+                ["Smith", "Smyth"][i % 2]
+            } else {
+                "Jones"
+            };
+            csv.push_str(&format!("user{}@example.com,{}\n", i, name));
+        }
+        let sampled = PlanOptions {
+            sample: Some(Sample::from_csv(&csv).unwrap()),
+            ..options.clone()
+        };
+        let estimate = generate_plan_with(&spec, &sampled)
+            .unwrap()
+            .estimate
+            .unwrap();
+        assert_eq!(estimate.basis, "sample");
+        assert!(estimate.candidate_pairs.abs_diff(449_985_000 * 94 / 190) <= 1);
+        assert_eq!(estimate.keys[0].pairs, 0);
+
+        let unknown = PlanOptions {
+            rows: [("erp".to_string(), 5)].into_iter().collect(),
+            ..PlanOptions::default()
+        };
+        let err = generate_plan_with(&spec, &unknown).unwrap_err();
+        assert_eq!(err.to_string(), "No source named 'erp' to set rows for");
+    }
+}
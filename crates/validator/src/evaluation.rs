@@ -0,0 +1,424 @@
+//! How well a spec resolves records whose true entities are known.
+//!
+//! `evaluate` runs a spec's plan over records with the reference
+//! interpreter (see `interpreter`) and compares the clusters it makes with
+//! ground-truth labels, a `record_key` and a `cluster_id` (or `entity_id`)
+//! per record, as `kanoniv generate-data` writes them:
+//!
+//! - pairwise precision, recall and F1 over pairs of records put in one
+//!   cluster, and the share of true pairs blocking compares at all;
+//! - cluster-level agreement: the adjusted Rand index, and B-cubed
+//!   precision, recall and F1 (averaged over records);
+//! - per rule, the mean similarity it gives compared pairs of one true
+//!   entity and of different ones, and the pairwise F1 without it.
+//!
+//! Only records both labeled and run are evaluated; the rest are counted
+//! in warnings. Review decisions are not merges, as in the clusters.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::cancel::CancellationToken;
+use crate::embedding::{Embedder, HttpEmbedder};
+use crate::interpreter::{self, execute_plan_with, ExecutionResult};
+use crate::ir::Ir;
+use crate::sample::Sample;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvaluationReport {
+    pub plan_hash: String,
+    /// Records both labeled and run.
+    pub records: usize,
+    pub true_entities: usize,
+    pub predicted_entities: usize,
+    pub pairwise: PairwiseMetrics,
+    pub clusters: ClusterMetrics,
+    pub rules: Vec<RuleEvaluation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Pairs of records put in one cluster, against pairs of one entity.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PairwiseMetrics {
+    pub true_positives: u64,
+    pub false_positives: u64,
+    pub false_negatives: u64,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    /// Share of the pairs of one entity that blocking compared.
+    pub candidate_recall: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClusterMetrics {
+    pub adjusted_rand_index: f64,
+    pub b_cubed_precision: f64,
+    pub b_cubed_recall: f64,
+    pub b_cubed_f1: f64,
+    /// Predicted clusters holding exactly one entity's records.
+    pub exact_clusters: usize,
+}
+
+/// What one match rule does for the result.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuleEvaluation {
+    pub rule_name: String,
+    pub field: String,
+    /// Mean similarity over compared pairs of one entity with a value on
+    /// both sides; `None` if there are none.
+    pub mean_on_matches: Option<f64>,
+    /// The same over compared pairs of different entities.
+    pub mean_on_non_matches: Option<f64>,
+    /// Pairwise F1 with the rule left out of the spec; `None` if it is the
+    /// only rule.
+    pub f1_without: Option<f64>,
+}
+
+/// Evaluate `ir` on `records` (by source, as `execute_plan` reads them)
+/// against the labels in `truth`, as of the date `as_of`, embedding values
+/// for semantic rules through the rules' endpoints.
+pub fn evaluate(ir: &Ir, records: &Value, truth: &Sample, as_of: &str) -> Result<EvaluationReport> {
+    evaluate_with(ir, records, truth, &HttpEmbedder::new()?, None, as_of)
+}
+
+/// `evaluate`, embedding values for semantic rules with `embedder` and
+/// stopping once `token` is cancelled.
+pub fn evaluate_with(
+    ir: &Ir,
+    records: &Value,
+    truth: &Sample,
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+    as_of: &str,
+) -> Result<EvaluationReport> {
+    let truth = truth_clusters(truth)?;
+    let result = execute_plan_with(ir, records, embedder, token, as_of)?;
+    let mut warnings = result.warnings.clone();
+
+    let predicted = predicted_clusters(&result);
+    let unlabeled = predicted
+        .keys()
+        .filter(|key| !truth.contains_key(**key))
+        .count();
+    if unlabeled > 0 {
+        warnings.push(format!(
+            "{} record(s) have no label and are not evaluated",
+            unlabeled
+        ));
+    }
+    let missing = truth
+        .keys()
+        .filter(|key| !predicted.contains_key(key.as_str()))
+        .count();
+    if missing > 0 {
+        warnings.push(format!(
+            "{} labeled record(s) were not run (absent or deleted) and are not evaluated",
+            missing
+        ));
+    }
+    let table = Contingency::new(&truth, &predicted);
+    if table.records == 0 {
+        bail!(
+            "No labeled record was run: record keys are `source:id`, as the labels must give them"
+        );
+    }
+    let pairwise = table.pairwise(&result, &truth);
+
+    let rules = ir
+        .rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| {
+            let mut sums = [(0.0, 0usize); 2];
+            for (decision, score) in result.decisions.iter().zip(&result.similarities[i]) {
+                let (Some(left), Some(right), Some(score)) = (
+                    truth.get(&decision.left_key),
+                    truth.get(&decision.right_key),
+                    score,
+                ) else {
+                    continue;
+                };
+                let sum = &mut sums[usize::from(left != right)];
+                sum.0 += score;
+                sum.1 += 1;
+            }
+            let mean = |(total, n): (f64, usize)| (n > 0).then(|| total / n as f64);
+            let f1_without = match ir.rules.len() {
+                1 => None,
+                _ => {
+                    let mut ablated = ir.clone();
+                    ablated.rules.remove(i);
+                    let result = execute_plan_with(&ablated, records, embedder, token, as_of)?;
+                    let predicted = predicted_clusters(&result);
+                    Some(
+                        Contingency::new(&truth, &predicted)
+                            .pairwise(&result, &truth)
+                            .f1,
+                    )
+                }
+            };
+            Ok(RuleEvaluation {
+                rule_name: rule.name.clone(),
+                field: rule.field.clone().unwrap_or_default(),
+                mean_on_matches: mean(sums[0]),
+                mean_on_non_matches: mean(sums[1]),
+                f1_without,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(EvaluationReport {
+        plan_hash: result.plan_hash.clone(),
+        records: table.records,
+        true_entities: table.truth_sizes.len(),
+        predicted_entities: table.predicted_sizes.len(),
+        pairwise,
+        clusters: table.clusters(),
+        rules,
+        warnings,
+    })
+}
+
+/// Each labeled record's true cluster, from a `record_key` column and a
+/// `cluster_id` or `entity_id` column.
+pub fn truth_clusters(labels: &Sample) -> Result<BTreeMap<String, String>> {
+    let Some(key) = labels.find_column("record_key") else {
+        bail!("The labels have no 'record_key' column");
+    };
+    let Some(cluster) = labels
+        .find_column("cluster_id")
+        .or_else(|| labels.find_column("entity_id"))
+    else {
+        bail!("The labels have no 'cluster_id' or 'entity_id' column");
+    };
+    let mut truth = BTreeMap::new();
+    for (i, row) in labels.rows.iter().enumerate() {
+        let value = |c: usize| row.get(c).map(|v| v.trim()).unwrap_or_default();
+        if value(key).is_empty() || value(cluster).is_empty() {
+            bail!("Label {} has no record key or cluster", i + 1);
+        }
+        if truth
+            .insert(value(key).to_string(), value(cluster).to_string())
+            .is_some()
+        {
+            bail!("Record '{}' is labeled twice", value(key));
+        }
+    }
+    Ok(truth)
+}
+
+/// Records stacked in one sample with a `source_name` column, each in its
+/// source's columns, split by source as `execute_plan` reads them.
+pub fn records_by_source(stacked: &Sample) -> Result<Value> {
+    let sources = interpreter::split_sources(stacked)?;
+    Ok(interpreter::records_json(&sources))
+}
+
+fn predicted_clusters(result: &ExecutionResult) -> BTreeMap<&str, &str> {
+    result
+        .clusters
+        .assignments
+        .iter()
+        .map(|a| (a.record_key.as_str(), a.cluster_id.as_str()))
+        .collect()
+}
+
+/// Records counted by true and predicted cluster, over the records both
+/// labeled and run.
+struct Contingency<'a> {
+    records: usize,
+    cells: HashMap<(&'a str, &'a str), u64>,
+    truth_sizes: HashMap<&'a str, u64>,
+    predicted_sizes: HashMap<&'a str, u64>,
+}
+
+impl<'a> Contingency<'a> {
+    fn new(truth: &'a BTreeMap<String, String>, predicted: &BTreeMap<&'a str, &'a str>) -> Self {
+        let mut table = Contingency {
+            records: 0,
+            cells: HashMap::new(),
+            truth_sizes: HashMap::new(),
+            predicted_sizes: HashMap::new(),
+        };
+        for (key, cluster) in predicted {
+            let Some(entity) = truth.get(*key) else {
+                continue;
+            };
+            table.records += 1;
+            *table.cells.entry((entity.as_str(), cluster)).or_default() += 1;
+            *table.truth_sizes.entry(entity.as_str()).or_default() += 1;
+            *table.predicted_sizes.entry(cluster).or_default() += 1;
+        }
+        table
+    }
+
+    fn pairwise(
+        &self,
+        result: &ExecutionResult,
+        truth: &BTreeMap<String, String>,
+    ) -> PairwiseMetrics {
+        let true_pairs: u64 = self.truth_sizes.values().map(|&n| pairs(n)).sum();
+        let predicted_pairs: u64 = self.predicted_sizes.values().map(|&n| pairs(n)).sum();
+        let true_positives: u64 = self.cells.values().map(|&n| pairs(n)).sum();
+        let compared = result
+            .decisions
+            .iter()
+            .filter(|d| {
+                matches!(
+                    (truth.get(&d.left_key), truth.get(&d.right_key)),
+                    (Some(left), Some(right)) if left == right
+                )
+            })
+            .count() as u64;
+        let precision = ratio(true_positives, predicted_pairs);
+        let recall = ratio(true_positives, true_pairs);
+        PairwiseMetrics {
+            true_positives,
+            false_positives: predicted_pairs - true_positives,
+            false_negatives: true_pairs - true_positives,
+            precision,
+            recall,
+            f1: harmonic_mean(precision, recall),
+            candidate_recall: ratio(compared, true_pairs),
+        }
+    }
+
+    fn clusters(&self) -> ClusterMetrics {
+        let n = self.records as f64;
+        let index: f64 = self.cells.values().map(|&c| pairs(c) as f64).sum();
+        let truth: f64 = self.truth_sizes.values().map(|&c| pairs(c) as f64).sum();
+        let predicted: f64 = self
+            .predicted_sizes
+            .values()
+            .map(|&c| pairs(c) as f64)
+            .sum();
+        let expected = truth * predicted / pairs(self.records as u64).max(1) as f64;
+        let maximum = (truth + predicted) / 2.0;
+        // Both sides a single cluster, or all singletons: they agree.
+        let adjusted_rand_index = match maximum - expected {
+            spread if spread.abs() < f64::EPSILON => 1.0,
+            spread => (index - expected) / spread,
+        };
+
+        let (mut precision, mut recall) = (0.0, 0.0);
+        for (&(entity, cluster), &count) in &self.cells {
+            let count = count as f64;
+            precision += count * count / self.predicted_sizes[cluster] as f64;
+            recall += count * count / self.truth_sizes[entity] as f64;
+        }
+        let (precision, recall) = (precision / n, recall / n);
+        let exact_clusters = self
+            .cells
+            .iter()
+            .filter(|(&(entity, cluster), &count)| {
+                self.truth_sizes[entity] == count && self.predicted_sizes[cluster] == count
+            })
+            .count();
+        ClusterMetrics {
+            adjusted_rand_index,
+            b_cubed_precision: precision,
+            b_cubed_recall: recall,
+            b_cubed_f1: harmonic_mean(precision, recall),
+            exact_clusters,
+        }
+    }
+}
+
+fn pairs(n: u64) -> u64 {
+    n * n.saturating_sub(1) / 2
+}
+
+/// `part / whole`; 1 when there is nothing to get right.
+fn ratio(part: u64, whole: u64) -> f64 {
+    match whole {
+        0 => 1.0,
+        _ => part as f64 / whole as f64,
+    }
+}
+
+fn harmonic_mean(a: f64, b: f64) -> f64 {
+    match a + b {
+        sum if sum > 0.0 => 2.0 * a * b / sum,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, AS_OF, EXECUTION, EXECUTION_RECORDS};
+
+    #[test]
+    fn test_evaluation_scores_clusters_against_ground_truth() {
+        use crate::{evaluate, Sample};
+
+        let ir = compiled_ir(EXECUTION);
+        let records: serde_json::Value = serde_json::from_str(EXECUTION_RECORDS).unwrap();
+        // crm:4 and crm:5 belong with Ann and Cy, and billing:12 was never run.
+        let truth = Sample::from_csv(
+            "record_key,cluster_id\n\
+         billing:10,ann\ncrm:1,ann\ncrm:4,ann\nbilling:11,bob\ncrm:2,bob\n\
+         crm:3,cy\ncrm:5,cy\nbilling:13,dee\nbilling:12,eve\n",
+        )
+        .unwrap();
+        let report = evaluate(&ir, &records, &truth, AS_OF).unwrap();
+        assert_eq!(
+            (
+                report.records,
+                report.true_entities,
+                report.predicted_entities
+            ),
+            (8, 4, 6)
+        );
+        assert_eq!(report.warnings.len(), 1);
+
+        // Five true pairs, three of them found; every true pair was a candidate.
+        let pairwise = &report.pairwise;
+        assert_eq!(
+            (
+                pairwise.true_positives,
+                pairwise.false_positives,
+                pairwise.false_negatives
+            ),
+            (3, 0, 2)
+        );
+        assert_eq!(
+            (
+                pairwise.precision,
+                pairwise.recall,
+                pairwise.candidate_recall
+            ),
+            (1.0, 0.6, 1.0)
+        );
+        assert!((pairwise.f1 - 0.75).abs() < 1e-9);
+
+        let clusters = &report.clusters;
+        assert!((clusters.adjusted_rand_index - 69.0 / 97.0).abs() < 1e-9);
+        assert_eq!(
+            (clusters.b_cubed_precision, clusters.b_cubed_recall),
+            (1.0, 0.75)
+        );
+        assert_eq!(clusters.exact_clusters, 2);
+
+        // Dropping any rule changes F1; the fuzzy name rule carries the most.
+        let names: Vec<_> = report.rules.iter().map(|r| r.rule_name.as_str()).collect();
+        assert_eq!(names, ["email_exact", "phone_exact", "last_name_fuzzy"]);
+        assert!(report.rules.iter().all(|r| r.mean_on_non_matches.is_none()));
+        let without = |name: &str| {
+            report
+                .rules
+                .iter()
+                .find(|r| r.rule_name == name)
+                .unwrap()
+                .f1_without
+                .unwrap()
+        };
+        assert!(without("last_name_fuzzy") < without("email_exact"));
+
+        let unlabeled = Sample::from_csv("record_key,cluster_id\nnobody:1,x\n").unwrap();
+        assert!(evaluate(&ir, &records, &unlabeled, AS_OF).is_err());
+    }
+}
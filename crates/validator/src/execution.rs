@@ -0,0 +1,245 @@
+//! How a pipeline runs: full rebuilds, incremental deltas or streaming.
+//!
+//! ```yaml
+//! execution:
+//!   mode: incremental         # full (default), incremental or streaming
+//!   delta_column: updated_at  # attribute marking records changed since
+//!                             # the last run
+//!   stable_ids: true          # entity ids survive cluster repairs
+//! ```
+//!
+//! A `full` run resolves every record from scratch. An `incremental` run
+//! detects the records added, changed or deleted since the last run, matches
+//! them against the records they share a blocking key with, repairs only the
+//! clusters they touch and re-applies survivorship to those. A `streaming`
+//! run does the same for each record as it arrives.
+//!
+//! Entity ids are derived from each cluster's lowest record key, so a repair
+//! that merges or splits a cluster renumbers entities; `stable_ids` says the
+//! engine keeps ids in a registry across runs instead.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+pub const MODES: &[&str] = &["full", "incremental", "streaming"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionMode {
+    #[default]
+    Full,
+    Incremental,
+    Streaming,
+}
+
+impl ExecutionMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExecutionMode::Full => "full",
+            ExecutionMode::Incremental => "incremental",
+            ExecutionMode::Streaming => "streaming",
+        }
+    }
+
+    /// Whether runs resolve only what changed rather than every record.
+    pub fn is_incremental(&self) -> bool {
+        *self != ExecutionMode::Full
+    }
+}
+
+impl FromStr for ExecutionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "full" => Ok(ExecutionMode::Full),
+            "incremental" => Ok(ExecutionMode::Incremental),
+            "streaming" => Ok(ExecutionMode::Streaming),
+            _ => bail!("Unknown execution mode '{}'. Use {}", s, MODES.join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The `execution` section.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Execution {
+    pub mode: ExecutionMode,
+    /// Attribute marking records changed since the last run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_column: Option<String>,
+    /// Whether entity ids are kept across runs rather than derived from
+    /// each cluster's lowest record key.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stable_ids: bool,
+}
+
+impl Execution {
+    /// Read `execution` from a parsed spec; `None` if the spec has none.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(section) = spec.get("execution").filter(|e| !e.is_null()) else {
+            return Ok(None);
+        };
+        let mode = match section.get("mode") {
+            None => ExecutionMode::default(),
+            Some(mode) => match mode.as_str() {
+                Some(name) => name.parse()?,
+                None => bail!("execution.mode must be a name, got {}", mode),
+            },
+        };
+        let delta_column = match section.get("delta_column") {
+            None => None,
+            Some(Value::String(column)) => Some(column.clone()),
+            Some(other) => bail!(
+                "execution.delta_column must name an attribute, got {}",
+                other
+            ),
+        };
+        let stable_ids = match section.get("stable_ids") {
+            None => false,
+            Some(Value::Bool(stable)) => *stable,
+            Some(other) => bail!("execution.stable_ids must be true or false, got {}", other),
+        };
+        Ok(Some(Execution {
+            mode,
+            delta_column,
+            stable_ids,
+        }))
+    }
+
+    /// How changed records are found, for plan descriptions.
+    pub fn delta_description(&self) -> String {
+        match (&self.mode, &self.delta_column) {
+            (ExecutionMode::Streaming, _) => {
+                "Consume record changes from each source as they arrive".to_string()
+            }
+            (_, Some(column)) => format!(
+                "Select records added, changed or deleted since the last run by {}",
+                column
+            ),
+            (_, None) => {
+                "Compare every source with the last run's snapshot to find added, changed and deleted records"
+                    .to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::compiled_ir;
+
+    #[test]
+    fn test_plan_stages_and_flags_follow_execution_mode() {
+        use crate::{generate_plan, generate_plan_from_ir, parse_yaml, PlanOptions};
+
+        let spec = "api_version: kanoniv/v2\nidentity_version: retail_v1.0\nentity:\n  name: customer\n\
+        sources:\n  - name: crm\n    system: salesforce\n    table: contacts\n    id: contact_id\n    attributes:\n      \
+        email: email\n      updated_at: updated_at\n\
+        rules:\n  - name: email_exact\n    type: exact\n    field: email\n    weight: 1.0\n\
+        blocking:\n  keys:\n    - field: email\n\
+        clustering:\n  strategy: star\n\
+        execution:\n  mode: incremental\n  delta_column: updated_at\n";
+        let names = |plan: &crate::PlanResult| -> Vec<String> {
+            plan.execution_stages
+                .iter()
+                .map(|s| s.name.clone())
+                .collect()
+        };
+        let codes = |plan: &crate::PlanResult| -> Vec<String> {
+            plan.risk_flags.iter().map(|f| f.code.clone()).collect()
+        };
+
+        let full = generate_plan(&spec.replace(
+            "execution:\n  mode: incremental\n  delta_column: updated_at\n",
+            "",
+        ))
+        .unwrap();
+        assert_eq!(full.execution_stages.len(), 8);
+        assert!(
+            full.summary.contains("8 execution stages") && !full.summary.contains("Execution:")
+        );
+
+        let plan = generate_plan(spec).unwrap();
+        assert_eq!(
+            names(&plan),
+            [
+                "Normalize sources",
+                "Detect deltas",
+                "Generate blocking keys",
+                "Exact matches",
+                "Fuzzy matches",
+                "Score & decide",
+                "Repair clusters",
+                "Re-apply survivorship",
+                "Emit outputs"
+            ]
+        );
+        assert!(plan.execution_stages[1]
+            .description
+            .contains("by updated_at"));
+        assert_eq!(
+            plan.execution_stages[2].inputs,
+            ["changed_records", "normalized_entities"]
+        );
+        assert!(plan.execution_stages[7]
+            .inputs
+            .contains(&"touched_clusters".to_string()));
+        assert!(plan
+            .summary
+            .contains("Execution:    incremental (deltas by updated_at)"));
+        assert!(plan.summary.contains("9 execution stages"));
+        let flags = codes(&plan);
+        assert!(flags.contains(&"INCREMENTAL_WITHOUT_STABLE_IDS".to_string()));
+        assert!(flags.contains(&"INCREMENTAL_CLUSTER_DRIFT".to_string()));
+        assert!(!flags.contains(&"INCREMENTAL_WITHOUT_DELTA_COLUMN".to_string()));
+
+        // The mode survives compilation.
+        let ir = compiled_ir(spec);
+        assert_eq!(
+            names(&generate_plan_from_ir(&ir, &PlanOptions::default()).unwrap()),
+            names(&plan)
+        );
+
+        let streaming = generate_plan(
+            &spec
+                .replace(
+                    "mode: incremental\n  delta_column: updated_at",
+                    "mode: streaming\n  stable_ids: true",
+                )
+                .replace("strategy: star", "strategy: transitive_closure"),
+        )
+        .unwrap();
+        assert_eq!(streaming.execution_stages[1].name, "Consume changes");
+        assert!(codes(&streaming)
+            .iter()
+            .all(|c| !c.starts_with("INCREMENTAL_")));
+
+        let invalid = spec.replace("mode: incremental", "mode: nightly\n  stable_ids: \"yes\"");
+        let found: Vec<(String, String)> =
+            crate::semantic_diagnostics(&parse_yaml(&invalid).unwrap())
+                .into_iter()
+                .map(|d| (d.code, d.path.unwrap_or_default()))
+                .collect();
+        let expected = [
+            ("KNV0119", "execution.mode"),
+            ("KNV0119", "execution.stable_ids"),
+        ];
+        assert_eq!(found, expected.map(|(c, p)| (c.to_string(), p.to_string())));
+        let streaming_delta = spec.replace("mode: incremental", "mode: streaming");
+        let found: Vec<String> =
+            crate::semantic_diagnostics(&parse_yaml(&streaming_delta).unwrap())
+                .into_iter()
+                .filter_map(|d| d.path)
+                .collect();
+        assert_eq!(found, ["execution.delta_column"]);
+    }
+}
@@ -0,0 +1,317 @@
+//! Why two records did or did not merge.
+//!
+//! `explain_pair` runs a spec's blocking keys and match rules on one pair
+//! of records and breaks the decision down: whether the records share a
+//! blocking key (without one they are never compared), each rule's score
+//! and its share of the combined score, and the decision the combined
+//! score gets. Rules score as in `calibration`, as the compiled SQL scores
+//! them; the decision follows `decision.thresholds`, with a missing
+//! `match` threshold taken as 1.
+//!
+//! Records are JSON objects keyed by canonical attribute or by the columns
+//! of any source.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::calibration;
+use crate::commands::compile::compile_to_ir;
+use crate::commands::plan::extract_match_strategies;
+use crate::embedding::{Embedder, HttpEmbedder};
+use crate::encoding;
+use crate::hashing::HashKey;
+use crate::ir::Ir;
+use crate::learning;
+use crate::normalize::Normalization;
+use crate::parser;
+use crate::sample::Sample;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairExplanation {
+    pub blocking: Vec<KeyAgreement>,
+    /// Whether blocking puts the records in one block; it does without
+    /// keys the records have values for.
+    pub compared: bool,
+    pub rules: Vec<RuleContribution>,
+    /// The weight-averaged rule score.
+    pub score: f64,
+    pub match_threshold: f64,
+    pub review_threshold: Option<f64>,
+    /// `match`, `review` or `reject`, as the compiled SQL decides the pair;
+    /// a pair blocking keeps apart is rejected.
+    pub decision: String,
+    pub reason: String,
+}
+
+/// One blocking key's values for the two records.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyAgreement {
+    pub field: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub agrees: bool,
+}
+
+/// One match rule's score for the pair and its share of the combined score.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleContribution {
+    pub rule_name: String,
+    pub match_type: String,
+    pub field: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    /// Normalized, lowercased values compared, encoded if the spec encodes
+    /// them.
+    pub left: Option<String>,
+    pub right: Option<String>,
+    /// Similarity of the values; `None` where the rule does not compare them.
+    pub score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    pub weight: f64,
+    /// Weight times the score (1 or 0 past a fuzzy or semantic threshold),
+    /// over the total weight.
+    pub contribution: f64,
+    /// Why the rule scores 0, if it does not compare the values.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Explain how `yaml` decides the records `a` and `b`, embedding values
+/// for semantic rules through the rules' endpoints.
+pub fn explain_pair(yaml: &str, a: &Value, b: &Value) -> Result<PairExplanation> {
+    explain_pair_with(yaml, a, b, &HttpEmbedder::new()?, None)
+}
+
+/// `explain_pair`, embedding values for semantic rules with `embedder` and
+/// encoding attributes with `salt` if one is given, instead of the salt
+/// the spec names.
+pub fn explain_pair_with(
+    yaml: &str,
+    a: &Value,
+    b: &Value,
+    embedder: &dyn Embedder,
+    salt: Option<&HashKey>,
+) -> Result<PairExplanation> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let data = Sample::from_json_records(&[canonical(&ir, a)?, canonical(&ir, b)?])?;
+    let normalization = Normalization::from_spec(&spec)?;
+    let encoder = encoding::encoder_with(&spec, salt)?;
+
+    let blocking: Vec<KeyAgreement> = ir
+        .blocking
+        .keys
+        .iter()
+        .map(|key| {
+            let transform = key.transform.as_deref().unwrap_or("identity");
+            let values = match data.ir_column(&ir, &key.field) {
+                Some(column) => {
+                    learning::block_keys(&data, column, &key.field, transform, encoder.as_ref())
+                }
+                None => vec![None, None],
+            };
+            KeyAgreement {
+                field: key.field.clone(),
+                transform: key.transform.clone(),
+                agrees: values[0].is_some() && values[0] == values[1],
+                left: values[0].clone(),
+                right: values[1].clone(),
+            }
+        })
+        .collect();
+    let keyed = blocking
+        .iter()
+        .any(|k| data.ir_column(&ir, &k.field).is_some());
+    let compared = !keyed || blocking.iter().any(|k| k.agrees);
+
+    let strategies = extract_match_strategies(&ir);
+    let scored = learning::pair_scores(
+        &ir,
+        &data,
+        &strategies,
+        &[(0, 1)],
+        &normalization,
+        encoder.as_ref(),
+        embedder,
+        None,
+    )?;
+    let similarities: Vec<Vec<Option<f64>>> = scored
+        .into_iter()
+        .map(|s| s.unwrap_or_else(|| vec![None]))
+        .collect();
+    let total_weight: f64 = strategies.iter().map(|r| r.weight).sum();
+    let rules = strategies
+        .iter()
+        .zip(&similarities)
+        .map(|(rule, scores)| {
+            let value = |record: usize| {
+                let column = data.ir_column(&ir, &rule.field)?;
+                let value = normalization
+                    .apply(&rule.field, &data.rows[record][column])
+                    .trim()
+                    .to_lowercase();
+                (!value.is_empty()).then(|| encoding::encode(encoder.as_ref(), &rule.field, value))
+            };
+            let (left, right) = (value(0), value(1));
+            let score = scores[0];
+            let note = match score {
+                Some(_) => None,
+                None if data.ir_column(&ir, &rule.field).is_none() => {
+                    Some(format!("neither record has '{}'", rule.field))
+                }
+                None if left.is_none() || right.is_none() => Some("a value is missing".to_string()),
+                None => Some("the rule's condition does not hold".to_string()),
+            };
+            // The rule's own combined score is its score past the threshold.
+            let effective =
+                calibration::combined_scores(std::slice::from_ref(rule), &[vec![score]], 1)[0];
+            let contribution = if total_weight > 0.0 {
+                effective * rule.weight / total_weight
+            } else {
+                0.0
+            };
+            RuleContribution {
+                rule_name: rule.rule_name.clone(),
+                match_type: rule.match_type.clone(),
+                field: rule.field.clone(),
+                algorithm: rule.algorithm.clone(),
+                left,
+                right,
+                score: score.map(round),
+                threshold: rule.threshold,
+                weight: rule.weight,
+                contribution: round(contribution),
+                note,
+            }
+        })
+        .collect();
+    let score = calibration::combined_scores(&strategies, &similarities, 1)[0];
+
+    let thresholds = ir.thresholds.as_ref();
+    let match_threshold = thresholds.and_then(|t| t.match_).unwrap_or(1.0);
+    let review_threshold = thresholds.and_then(|t| t.review);
+    let (decision, reason) = if !compared {
+        (
+            "reject",
+            "the records share no blocking key, so they are never compared".to_string(),
+        )
+    } else if score >= match_threshold {
+        (
+            "match",
+            format!(
+                "score {} is at least match {}",
+                round(score),
+                match_threshold
+            ),
+        )
+    } else if let Some(review) = review_threshold.filter(|&r| score >= r) {
+        (
+            "review",
+            format!(
+                "score {} is at least review {}, below match {}",
+                round(score),
+                review,
+                match_threshold
+            ),
+        )
+    } else {
+        let below = match review_threshold {
+            Some(review) => format!("review {}", review),
+            None => format!("match {}", match_threshold),
+        };
+        (
+            "reject",
+            format!("score {} is below {}", round(score), below),
+        )
+    };
+
+    Ok(PairExplanation {
+        blocking,
+        compared,
+        rules,
+        score: round(score),
+        match_threshold,
+        review_threshold,
+        decision: decision.to_string(),
+        reason,
+    })
+}
+
+/// `record` with each attribute it has under a source's column name added
+/// under the attribute's name; the records of a pair may come from
+/// different sources.
+pub(crate) fn canonical(ir: &Ir, record: &Value) -> Result<Value> {
+    let Value::Object(fields) = record else {
+        bail!("A record must be a JSON object");
+    };
+    let mut canonical = fields.clone();
+    for source in &ir.sources {
+        for (attribute, column) in &source.attributes {
+            if canonical.contains_key(attribute) {
+                continue;
+            }
+            if let Some(value) = fields.get(column) {
+                canonical.insert(attribute.clone(), value.clone());
+            }
+        }
+    }
+    Ok(Value::Object(canonical))
+}
+
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn test_explain_pair_breaks_down_the_decision() {
+        use crate::explain_pair;
+        use serde_json::json;
+
+        let spec = include_str!("../conformance/multi_source/spec.yaml");
+        // A crm contact and a billing customer, each in its source's columns.
+        let crm = json!({"email_address": "Jane.Doe@Example.com", "given_name": "Jane", "family_name": "Doe", "mobile": "555-0100"});
+        let billing = json!({"email": "jane.doe@example.com", "name_last": "Doe"});
+        let explanation = explain_pair(spec, &crm, &billing).unwrap();
+        assert!(explanation.compared);
+        let email = &explanation.blocking[0];
+        assert!(email.agrees);
+        assert_eq!(email.right.as_deref(), Some("jane.doe@example.com"));
+
+        let rule = |name: &str| {
+            explanation
+                .rules
+                .iter()
+                .find(|r| r.rule_name == name)
+                .unwrap()
+        };
+        assert_eq!(rule("email_exact").score, Some(1.0));
+        assert_eq!(rule("last_name_fuzzy").score, Some(1.0));
+        assert_eq!(
+            rule("phone_exact").note.as_deref(),
+            Some("a value is missing")
+        );
+        assert_eq!(rule("first_name_fuzzy").contribution, 0.0);
+        // (1.0 + 0.3) / 2.15 of the weight agrees.
+        assert_eq!(explanation.score, 0.605);
+        assert_eq!(explanation.decision, "review");
+        assert_eq!(
+            explanation.reason,
+            "score 0.605 is at least review 0.6, below match 0.9"
+        );
+
+        // Without a shared blocking key the records are never compared.
+        let stranger = json!({"email": "someone@else.com", "name_last": "Smith"});
+        let explanation = explain_pair(spec, &crm, &stranger).unwrap();
+        assert!(!explanation.compared);
+        assert_eq!(explanation.decision, "reject");
+
+        assert!(explain_pair(spec, &crm, &json!(["not", "a", "record"])).is_err());
+    }
+}
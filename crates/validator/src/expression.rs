@@ -0,0 +1,858 @@
+//! A small expression language for conditions and custom survivorship.
+//!
+//! Expressions are CEL-like and evaluate to a null, boolean, number or
+//! string:
+//!
+//! ```text
+//! source == "crm" ? 2 : size(value)
+//! has(updated_at) && number(confidence) >= 0.8
+//! left.country == right.country
+//! ```
+//!
+//! - literals: `1`, `0.5`, `"text"` or `'text'`, `true`, `false`, `null`
+//! - names, possibly qualified (`left.email`), bound by the caller: for
+//!   survivorship `value`, `source`, `record_key` and the attributes, for
+//!   match rule conditions `left.<attribute>` and `right.<attribute>`
+//! - `!` and unary `-`; `*`, `/`, `%`, `+` (numbers, or joins strings),
+//!   `-`; `==`, `!=`, `<`, `<=`, `>`, `>=`; `&&`, `||`; `cond ? a : b`
+//! - functions: `size`, `lower`, `upper`, `trim`, `contains`,
+//!   `starts_with`, `ends_with`, `number` and `has`
+//!
+//! Null is contagious: an operator or function given a null returns null,
+//! except `==`, `!=` and `has`, and `&&`, `||` and `?:`, which read null as
+//! false. Dividing by zero and `number` of a non-number also give null.
+//!
+//! `Expression::check` infers an expression's type before it runs, so
+//! `validate` rejects `size(1)` or `value * 2` rather than the engine.
+//! Evaluation is sandboxed: an expression reads only the names it is given,
+//! has no loops or side effects, and is limited in length and nesting.
+
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Longest expression source, in characters.
+pub const MAX_LENGTH: usize = 1000;
+
+/// Deepest nesting of operators, calls and parentheses.
+pub const MAX_DEPTH: usize = 32;
+
+/// Functions and the number of arguments each takes.
+pub const FUNCTIONS: &[(&str, usize)] = &[
+    ("size", 1),
+    ("lower", 1),
+    ("upper", 1),
+    ("trim", 1),
+    ("contains", 2),
+    ("starts_with", 2),
+    ("ends_with", 2),
+    ("number", 1),
+    ("has", 1),
+];
+
+/// The type of a value. A name of any type may be null, so null is
+/// accepted wherever a type is expected; `Null` itself is the type of the
+/// `null` literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Null,
+    Bool,
+    Number,
+    String,
+}
+
+impl Type {
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Null => "null",
+            Type::Bool => "bool",
+            Type::Number => "number",
+            Type::String => "string",
+        }
+    }
+
+    /// The type of a value that is either of `self` or `other`, if one is
+    /// null or both agree.
+    pub fn unify_with(self, other: Type) -> Option<Type> {
+        match (self, other) {
+            (Type::Null, t) | (t, Type::Null) => Some(t),
+            (a, b) => (a == b).then_some(a),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl Value {
+    pub fn type_of(&self) -> Type {
+        match self {
+            Value::Null => Type::Null,
+            Value::Bool(_) => Type::Bool,
+            Value::Number(_) => Type::Number,
+            Value::String(_) => Type::String,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_of().name()
+    }
+
+    /// A text cell as a value: empty is null.
+    pub fn from_cell(cell: Option<&str>) -> Self {
+        match cell {
+            Some(text) if !text.is_empty() => Value::String(text.to_string()),
+            _ => Value::Null,
+        }
+    }
+
+    /// Sort order: values of one type by value, across types null, bool,
+    /// number, string.
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        let rank = |v: &Value| match v {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+        };
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+
+    /// Truth of a condition: null is false.
+    fn truth(&self) -> Result<bool> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            Value::Null => Ok(false),
+            other => bail!("expected a bool, got {}", other.type_name()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => f.write_str(s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(Value),
+    Name(String),
+    Not(Box<Node>),
+    Negate(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    Call(&'static str, Vec<Node>),
+}
+
+/// A parsed expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self> {
+        if source.chars().count() > MAX_LENGTH {
+            bail!("Expression is longer than {} characters", MAX_LENGTH);
+        }
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            depth: 0,
+        };
+        let root = parser.conditional()?;
+        if let Some(token) = parser.peek() {
+            bail!(
+                "Unexpected {} at column {}",
+                token.kind.describe(),
+                token.column
+            );
+        }
+        Ok(Expression {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The names the expression reads, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        fn walk<'a>(node: &'a Node, names: &mut Vec<&'a str>) {
+            match node {
+                Node::Literal(_) => {}
+                Node::Name(name) => names.push(name),
+                Node::Not(a) | Node::Negate(a) => walk(a, names),
+                Node::Binary(_, a, b) => {
+                    walk(a, names);
+                    walk(b, names);
+                }
+                Node::Conditional(c, a, b) => {
+                    walk(c, names);
+                    walk(a, names);
+                    walk(b, names);
+                }
+                Node::Call(_, args) => args.iter().for_each(|a| walk(a, names)),
+            }
+        }
+        let mut names = Vec::new();
+        walk(&self.root, &mut names);
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// The type the expression evaluates to, with names typed by `types`.
+    /// A name `types` does not know, or an operator or function given the
+    /// wrong type, is an error.
+    pub fn check(&self, types: &dyn Fn(&str) -> Option<Type>) -> Result<Type> {
+        check(&self.root, types)
+    }
+
+    /// Evaluate with the names bound by `lookup`; a name it does not know
+    /// is an error.
+    pub fn evaluate(&self, lookup: &dyn Fn(&str) -> Option<Value>) -> Result<Value> {
+        eval(&self.root, lookup)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Expression::parse(s)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+// ── Tokens ─────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    String(String),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+impl TokenKind {
+    fn describe(&self) -> String {
+        match self {
+            TokenKind::Number(n) => format!("number {}", n),
+            TokenKind::String(s) => format!("string {:?}", s),
+            TokenKind::Ident(name) => format!("'{}'", name),
+            TokenKind::Symbol(s) => format!("'{}'", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// 1-based character column.
+    column: usize,
+}
+
+const SYMBOLS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "*", "/", "%", "?", ":", "(", ")",
+    ",",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let Ok(n) = text.parse() else {
+                bail!("Invalid number '{}' at column {}", text, column);
+            };
+            tokens.push(Token {
+                kind: TokenKind::Number(n),
+                column,
+            });
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => bail!("Unterminated string at column {}", column),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        text.push(match chars.get(i + 1) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some(&e @ ('\\' | '"' | '\'')) => e,
+                            _ => bail!("Invalid escape at column {}", i + 1),
+                        });
+                        i += 2;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push(Token {
+                kind: TokenKind::String(text),
+                column,
+            });
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            // A dot joins the parts of a qualified name.
+            let part = |c: Option<&char>| c.is_some_and(|c| c.is_alphabetic() || *c == '_');
+            while i < chars.len()
+                && (chars[i].is_alphanumeric()
+                    || chars[i] == '_'
+                    || (chars[i] == '.' && part(chars.get(i + 1))))
+            {
+                i += 1;
+            }
+            tokens.push(Token {
+                kind: TokenKind::Ident(chars[start..i].iter().collect()),
+                column,
+            });
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) else {
+                bail!("Unexpected '{}' at column {}", c, column);
+            };
+            i += symbol.len();
+            tokens.push(Token {
+                kind: TokenKind::Symbol(symbol),
+                column,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+// ── Parser ─────────────────────────────────────────────────────────
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(Token {
+                kind: TokenKind::Symbol(s),
+                ..
+            }) if *s == symbol => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => bail!(
+                "Expected '{}' at column {}, found {}",
+                symbol,
+                token.column,
+                token.kind.describe()
+            ),
+            None => bail!("Expected '{}' at the end of the expression", symbol),
+        }
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            bail!("Expression is nested more than {} levels deep", MAX_DEPTH);
+        }
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn conditional(&mut self) -> Result<Node> {
+        self.nested(|p| {
+            let condition = p.binary(0)?;
+            if !p.eat("?") {
+                return Ok(condition);
+            }
+            let then = p.conditional()?;
+            p.expect(":")?;
+            let otherwise = p.conditional()?;
+            Ok(Node::Conditional(
+                Box::new(condition),
+                Box::new(then),
+                Box::new(otherwise),
+            ))
+        })
+    }
+
+    /// Binary operators by precedence, loosest first; comparisons do not
+    /// chain.
+    fn binary(&mut self, level: usize) -> Result<Node> {
+        const LEVELS: &[&[(&str, BinaryOp)]] = &[
+            &[("||", BinaryOp::Or)],
+            &[("&&", BinaryOp::And)],
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+        ];
+        let Some(operators) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(&(_, op)) = operators.iter().find(|(s, _)| self.eat(s)) {
+            let right = self.binary(level + 1)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+            if level == 2 {
+                break;
+            }
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        if self.eat("!") {
+            return self.nested(|p| Ok(Node::Not(Box::new(p.unary()?))));
+        }
+        if self.eat("-") {
+            return self.nested(|p| Ok(Node::Negate(Box::new(p.unary()?))));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        let Some(token) = self.peek().cloned() else {
+            bail!("Unexpected end of the expression");
+        };
+        self.position += 1;
+        match token.kind {
+            TokenKind::Number(n) => Ok(Node::Literal(Value::Number(n))),
+            TokenKind::String(s) => Ok(Node::Literal(Value::String(s))),
+            TokenKind::Symbol("(") => {
+                let inner = self.conditional()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            TokenKind::Ident(name) => match name.as_str() {
+                "true" => Ok(Node::Literal(Value::Bool(true))),
+                "false" => Ok(Node::Literal(Value::Bool(false))),
+                "null" => Ok(Node::Literal(Value::Null)),
+                _ if self.eat("(") => self.call(&name, token.column),
+                _ => Ok(Node::Name(name)),
+            },
+            kind => bail!("Unexpected {} at column {}", kind.describe(), token.column),
+        }
+    }
+
+    fn call(&mut self, name: &str, column: usize) -> Result<Node> {
+        let Some(&(function, arity)) = FUNCTIONS.iter().find(|(f, _)| *f == name) else {
+            bail!("Unknown function '{}' at column {}", name, column);
+        };
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.conditional()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        if args.len() != arity {
+            bail!(
+                "{}() takes {} argument(s), got {} at column {}",
+                function,
+                arity,
+                args.len(),
+                column
+            );
+        }
+        Ok(Node::Call(function, args))
+    }
+}
+
+// ── Type checking ──────────────────────────────────────────────────
+
+/// Fail unless `actual` is `expected` (or null).
+fn expect(actual: Type, expected: Type, operator: &str) -> Result<()> {
+    if actual.unify_with(expected) != Some(expected) {
+        bail!("{} expects a {}, got a {}", operator, expected, actual);
+    }
+    Ok(())
+}
+
+fn check(node: &Node, types: &dyn Fn(&str) -> Option<Type>) -> Result<Type> {
+    Ok(match node {
+        Node::Literal(value) => value.type_of(),
+        Node::Name(name) => match types(name) {
+            Some(t) => t,
+            None => bail!("Unknown name '{}'", name),
+        },
+        Node::Not(a) => {
+            expect(check(a, types)?, Type::Bool, "'!'")?;
+            Type::Bool
+        }
+        Node::Negate(a) => {
+            expect(check(a, types)?, Type::Number, "'-'")?;
+            Type::Number
+        }
+        Node::Conditional(c, a, b) => {
+            expect(check(c, types)?, Type::Bool, "'?:'")?;
+            let (a, b) = (check(a, types)?, check(b, types)?);
+            match a.unify_with(b) {
+                Some(t) => t,
+                None => bail!("the branches of '?:' are a {} and a {}", a, b),
+            }
+        }
+        Node::Binary(op, a, b) => {
+            let (a, b) = (check(a, types)?, check(b, types)?);
+            let symbol = match op {
+                BinaryOp::Or => "'||'",
+                BinaryOp::And => "'&&'",
+                BinaryOp::Eq => "'=='",
+                BinaryOp::Ne => "'!='",
+                BinaryOp::Lt => "'<'",
+                BinaryOp::Le => "'<='",
+                BinaryOp::Gt => "'>'",
+                BinaryOp::Ge => "'>='",
+                BinaryOp::Add => "'+'",
+                BinaryOp::Sub => "'-'",
+                BinaryOp::Mul => "'*'",
+                BinaryOp::Div => "'/'",
+                BinaryOp::Rem => "'%'",
+            };
+            match op {
+                BinaryOp::Or | BinaryOp::And => {
+                    expect(a, Type::Bool, symbol)?;
+                    expect(b, Type::Bool, symbol)?;
+                    Type::Bool
+                }
+                BinaryOp::Eq
+                | BinaryOp::Ne
+                | BinaryOp::Lt
+                | BinaryOp::Le
+                | BinaryOp::Gt
+                | BinaryOp::Ge => {
+                    if a.unify_with(b).is_none() {
+                        bail!("{} cannot compare a {} with a {}", symbol, a, b);
+                    }
+                    Type::Bool
+                }
+                BinaryOp::Add => match a.unify_with(b) {
+                    Some(t @ (Type::Null | Type::Number | Type::String)) => t,
+                    _ => bail!("'+' cannot add a {} and a {}", a, b),
+                },
+                _ => {
+                    expect(a, Type::Number, symbol)?;
+                    expect(b, Type::Number, symbol)?;
+                    Type::Number
+                }
+            }
+        }
+        Node::Call(function, args) => {
+            let args = args
+                .iter()
+                .map(|a| check(a, types))
+                .collect::<Result<Vec<_>>>()?;
+            let what = format!("{}()", function);
+            match *function {
+                "has" => Type::Bool,
+                "number" => match args[0] {
+                    Type::Null | Type::Number | Type::String => Type::Number,
+                    other => bail!("number() expects a string, got a {}", other),
+                },
+                _ => {
+                    for &arg in &args {
+                        expect(arg, Type::String, &what)?;
+                    }
+                    match *function {
+                        "size" => Type::Number,
+                        "lower" | "upper" | "trim" => Type::String,
+                        _ => Type::Bool,
+                    }
+                }
+            }
+        }
+    })
+}
+
+// ── Evaluation ─────────────────────────────────────────────────────
+
+fn eval(node: &Node, lookup: &dyn Fn(&str) -> Option<Value>) -> Result<Value> {
+    Ok(match node {
+        Node::Literal(value) => value.clone(),
+        Node::Name(name) => match lookup(name) {
+            Some(value) => value,
+            None => bail!("Unknown name '{}'", name),
+        },
+        Node::Not(a) => match eval(a, lookup)? {
+            Value::Null => Value::Null,
+            value => Value::Bool(!value.truth()?),
+        },
+        Node::Negate(a) => match eval(a, lookup)? {
+            Value::Null => Value::Null,
+            Value::Number(n) => Value::Number(-n),
+            other => bail!("cannot negate a {}", other.type_name()),
+        },
+        Node::Conditional(c, a, b) => {
+            if eval(c, lookup)?.truth()? {
+                eval(a, lookup)?
+            } else {
+                eval(b, lookup)?
+            }
+        }
+        Node::Binary(BinaryOp::And, a, b) => {
+            Value::Bool(eval(a, lookup)?.truth()? && eval(b, lookup)?.truth()?)
+        }
+        Node::Binary(BinaryOp::Or, a, b) => {
+            Value::Bool(eval(a, lookup)?.truth()? || eval(b, lookup)?.truth()?)
+        }
+        Node::Binary(op, a, b) => binary(*op, eval(a, lookup)?, eval(b, lookup)?)?,
+        Node::Call(function, args) => {
+            let args = args
+                .iter()
+                .map(|a| eval(a, lookup))
+                .collect::<Result<Vec<_>>>()?;
+            call(function, args)?
+        }
+    })
+}
+
+fn binary(op: BinaryOp, a: Value, b: Value) -> Result<Value> {
+    match op {
+        BinaryOp::Eq => return Ok(Value::Bool(a == b)),
+        BinaryOp::Ne => return Ok(Value::Bool(a != b)),
+        _ if a == Value::Null || b == Value::Null => return Ok(Value::Null),
+        _ => {}
+    }
+    Ok(match (op, &a, &b) {
+        (BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge, _, _) => {
+            if a.type_name() != b.type_name() {
+                bail!(
+                    "cannot compare a {} with a {}",
+                    a.type_name(),
+                    b.type_name()
+                );
+            }
+            let order = a.total_cmp(&b);
+            Value::Bool(match op {
+                BinaryOp::Lt => order.is_lt(),
+                BinaryOp::Le => order.is_le(),
+                BinaryOp::Gt => order.is_gt(),
+                _ => order.is_ge(),
+            })
+        }
+        (BinaryOp::Add, Value::String(x), Value::String(y)) => Value::String(format!("{}{}", x, y)),
+        (_, Value::Number(x), Value::Number(y)) => match op {
+            BinaryOp::Add => Value::Number(x + y),
+            BinaryOp::Sub => Value::Number(x - y),
+            BinaryOp::Mul => Value::Number(x * y),
+            _ if *y == 0.0 => Value::Null,
+            BinaryOp::Div => Value::Number(x / y),
+            _ => Value::Number(x % y),
+        },
+        _ => bail!(
+            "cannot apply arithmetic to a {} and a {}",
+            a.type_name(),
+            b.type_name()
+        ),
+    })
+}
+
+fn call(function: &str, args: Vec<Value>) -> Result<Value> {
+    if function == "has" {
+        return Ok(Value::Bool(args[0] != Value::Null));
+    }
+    if args.contains(&Value::Null) {
+        return Ok(Value::Null);
+    }
+    let text = |i: usize| match &args[i] {
+        Value::String(s) => Ok(s.as_str()),
+        other => bail!("{}() expects a string, got {}", function, other.type_name()),
+    };
+    Ok(match function {
+        "size" => Value::Number(text(0)?.chars().count() as f64),
+        "lower" => Value::String(text(0)?.to_lowercase()),
+        "upper" => Value::String(text(0)?.to_uppercase()),
+        "trim" => Value::String(text(0)?.trim().to_string()),
+        "contains" => Value::Bool(text(0)?.contains(text(1)?)),
+        "starts_with" => Value::Bool(text(0)?.starts_with(text(1)?)),
+        "ends_with" => Value::Bool(text(0)?.ends_with(text(1)?)),
+        "number" => match &args[0] {
+            Value::Number(n) => Value::Number(*n),
+            Value::String(s) => s.trim().parse().map_or(Value::Null, Value::Number),
+            other => bail!("number() expects a string, got {}", other.type_name()),
+        },
+        _ => bail!("Unknown function '{}'", function),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::AS_OF;
+
+    #[test]
+    fn test_expression_conditions_type_check_and_apply() {
+        use crate::expression::{Expression, Type};
+        use crate::{calibrate, golden_records, Sample};
+
+        let strings = |_: &str| Some(Type::String);
+        let check = |source: &str| Expression::parse(source).unwrap().check(&strings);
+        assert_eq!(check("left.country == right.country").unwrap(), Type::Bool);
+        assert_eq!(
+            check("has(value) ? number(value) * 2 : null").unwrap(),
+            Type::Number
+        );
+        assert_eq!(check("lower(value) + '!'").unwrap(), Type::String);
+        for invalid in [
+            "size(1)",
+            "value * 2",
+            "value > 1",
+            "!value",
+            "value ? 1 : 2",
+            "true ? 1 : 'a'",
+        ] {
+            assert!(check(invalid).is_err(), "{}", invalid);
+        }
+
+        // A condition limits a match rule to the pairs it holds for.
+        let spec = include_str!("../conformance/multi_source/spec.yaml").replacen(
+            "    type: exact\n",
+            "    type: exact\n    condition: \"left.last_name == right.last_name\"\n",
+            1,
+        );
+        let labels = Sample::from_csv(
+            "label,left_email,right_email,left_last_name,right_last_name\n\
+         match,a@x.com,a@x.com,Smith,Smith\n\
+         match,b@x.com,b@x.com,Lee,Li\n\
+         non_match,c@x.com,d@x.com,Kim,Kim\n",
+        )
+        .unwrap();
+        let calibration = calibrate(&spec, &labels).unwrap();
+        assert_eq!(
+            (
+                calibration.rules[0].rule_name.as_str(),
+                calibration.rules[0].pairs
+            ),
+            ("email_exact", 2)
+        );
+
+        // A survivorship condition breaks ties ahead of the strategy.
+        let golden_spec = format!(
+        "{}survivorship:\n  rules:\n    - field: email\n      strategy: most_complete\n      condition: \"ends_with(value, '.com')\"\n",
+        include_str!("../tests/fixtures/valid/minimal.yaml")
+    );
+        let members = Sample::from_csv(
+        "cluster_id,source_name,record_key,email\ne1,crm,a,ann@example.org\ne1,crm,b,ann@x.com\n",
+    )
+    .unwrap();
+        let golden = golden_records(&golden_spec, &members, AS_OF).unwrap();
+        assert_eq!(golden.records[0].values, [Some("ann@x.com".to_string())]);
+
+        let invalid = spec
+            .replace(
+                "condition: \"left.last_name == right.last_name\"",
+                "condition: \"size(left.last_name)\"",
+            )
+            .replacen(
+                "strategy: most_complete",
+                "strategy: most_complete\n      condition: \"value * 2 > 1\"",
+                1,
+            );
+        let diagnostics = crate::semantic_diagnostics(&crate::parse_yaml(&invalid).unwrap());
+        let found: Vec<(&str, &str)> = diagnostics
+            .iter()
+            .filter(|d| d.is_error())
+            .map(|d| (d.code.as_str(), d.path.as_deref().unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("KNV0117", "rules[0].condition"),
+                ("KNV0117", "survivorship.rules[2].condition")
+            ]
+        );
+        assert!(diagnostics[0]
+            .message
+            .contains("must be a bool, not a number"));
+    }
+}
@@ -0,0 +1,689 @@
+//! Canonical formatting for spec YAML.
+//!
+//! `format_spec` is the human-facing counterpart of the canonical form that
+//! `canonical_hash` hashes: the same document with rules sorted by name, keys in
+//! a fixed order (known spec fields in schema order, everything else
+//! alphabetical), two-space indentation, flow style for lists of scalars and
+//! quotes only where a plain scalar would be misread. Formatting never
+//! changes the plan hash, and formatting formatted output is a no-op.
+//!
+//! Comments are kept with the key or list entry they precede or trail.
+//! Comments inside flow collections (`[a, b]`) and block scalars are dropped.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::canonical::ordered_rules;
+use crate::parser::{self, strip_comment, SourceMap};
+
+/// Preferred key order per mapping, by path with list indexes removed.
+const KEY_ORDER: &[(&str, &[&str])] = &[
+    (
+        "",
+        &[
+            "extends",
+            "api_version",
+            "identity_version",
+            "entity",
+            "mappings",
+            "sources",
+            "rules",
+            "blocking",
+            "survivorship",
+            "decision",
+            "clustering",
+            "temporal",
+            "execution",
+            "relationships",
+            "stewardship",
+            "privacy",
+            "encoding",
+            "deletion",
+            "owners",
+            "waivers",
+            "policy",
+            "signatures",
+        ],
+    ),
+    ("entity", &["name"]),
+    (
+        "sources[]",
+        &[
+            "name",
+            "system",
+            "table",
+            "id",
+            "owner",
+            "rows",
+            "attributes",
+        ],
+    ),
+    (
+        "rules[]",
+        &[
+            "name",
+            "type",
+            "field",
+            "algorithm",
+            "threshold",
+            "weight",
+            "condition",
+        ],
+    ),
+    ("blocking", &["strategy", "keys"]),
+    (
+        "blocking.keys[]",
+        &["name", "field", "transform", "transformation"],
+    ),
+    ("survivorship", &["default", "rules"]),
+    (
+        "survivorship.rules[]",
+        &[
+            "field",
+            "strategy",
+            "source_priority",
+            "timestamp",
+            "fields",
+            "expression",
+            "condition",
+        ],
+    ),
+    ("decision", &["thresholds"]),
+    ("decision.thresholds", &["match", "review", "reject"]),
+    ("clustering", &["strategy", "threshold"]),
+    ("execution", &["mode", "delta_column", "stable_ids"]),
+    (
+        "relationships[]",
+        &["name", "to", "cardinality", "threshold", "rules"],
+    ),
+    (
+        "relationships[].rules[]",
+        &[
+            "name",
+            "type",
+            "field",
+            "target_field",
+            "algorithm",
+            "threshold",
+            "weight",
+        ],
+    ),
+    ("stewardship", &["always_merge", "never_merge", "locked"]),
+    ("stewardship.always_merge[]", &["records", "reason"]),
+    ("stewardship.never_merge[]", &["records", "reason"]),
+    (
+        "stewardship.locked[]",
+        &["record", "field", "value", "reason"],
+    ),
+    (
+        "privacy",
+        &["retention_days", "collected_at", "on_expiry", "fields"],
+    ),
+    ("encoding", &["salt_env", "salt_file", "fields"]),
+    ("deletion", &["tombstone", "scope", "purge_after_days"]),
+    ("owners", &["default"]),
+    ("waivers[]", &["code", "reason", "expires"]),
+    (
+        "signatures[]",
+        &["key_id", "algorithm", "hash", "signature", "signed_at"],
+    ),
+];
+
+/// Rewrite a spec in canonical form. Each document of a workspace is
+/// formatted on its own. `{{ params.name }}` expressions are kept as
+/// written, even where they stand unquoted for a whole value.
+pub fn format_spec(yaml: &str) -> Result<String> {
+    let (masked, templates) = mask_templates(yaml)?;
+    let mut formatted = format_documents(&masked)?;
+    for (i, template) in templates.iter().enumerate() {
+        formatted = formatted.replace(&template_placeholder(i), template);
+    }
+    Ok(formatted)
+}
+
+fn format_documents(yaml: &str) -> Result<String> {
+    let documents = parser::split_documents(yaml);
+    if documents.len() == 1 {
+        return format_document(yaml);
+    }
+    let mut out = String::new();
+    for (_, text) in documents {
+        let (marker, body) = if text.starts_with("---") {
+            text.split_at(text.find('\n').map_or(text.len(), |i| i + 1))
+        } else {
+            ("", text)
+        };
+        out.push_str(marker);
+        match parser::parse_yaml(body) {
+            // Comments only.
+            Ok(Value::Null) => out.push_str(body),
+            _ => out.push_str(&format_document(body)?),
+        }
+    }
+    Ok(out)
+}
+
+const TEMPLATE_PLACEHOLDER: &str = "__kanoniv_template_";
+
+fn template_placeholder(i: usize) -> String {
+    format!("{}{}__", TEMPLATE_PLACEHOLDER, i)
+}
+
+/// `yaml` with each `{{ ... }}` that starts a scalar replaced by a plain
+/// placeholder, and the expressions replaced. Unquoted, such an expression
+/// would read as a flow mapping: it only makes YAML once interpolated.
+fn mask_templates(yaml: &str) -> Result<(String, Vec<String>)> {
+    if yaml.contains(TEMPLATE_PLACEHOLDER) {
+        bail!(
+            "The spec contains the reserved text '{}'",
+            TEMPLATE_PLACEHOLDER
+        );
+    }
+    let mut masked = String::with_capacity(yaml.len());
+    let mut templates = Vec::new();
+    let mut rest = yaml;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}").map(|end| end + 2) else {
+            break;
+        };
+        let before = rest[..start].trim_end_matches(' ');
+        let starts_scalar = before.is_empty()
+            || before.ends_with(['\n', ':', '-', '[', ','])
+            || before.ends_with(": ");
+        masked.push_str(&rest[..start]);
+        if starts_scalar {
+            masked.push_str(&template_placeholder(templates.len()));
+            templates.push(rest[start..start + len].to_string());
+        } else {
+            masked.push_str(&rest[start..start + len]);
+        }
+        rest = &rest[start + len..];
+    }
+    masked.push_str(rest);
+    Ok((masked, templates))
+}
+
+fn format_document(yaml: &str) -> Result<String> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let Value::Object(root) = &spec else {
+        bail!("A spec must be a YAML mapping");
+    };
+
+    Ok(format_patched(yaml, root))
+}
+
+/// Write `root`, a copy of the spec `yaml` with some values changed, in
+/// canonical form with the comments of `yaml`.
+pub(crate) fn format_patched(yaml: &str, root: &Map<String, Value>) -> String {
+    let mut emitter = Emitter {
+        out: String::new(),
+        comments: Comments::collect(yaml),
+        conflicts: HashMap::new(),
+    };
+    emitter.mapping(root, "", 0);
+    for comment in &emitter.comments.dangling {
+        emitter.out.push_str(comment);
+        emitter.out.push('\n');
+    }
+    emitter.out
+}
+
+/// Write `root` in canonical form with `notes`, full comment lines, above
+/// the nodes at their paths.
+pub(crate) fn format_annotated(
+    root: &Map<String, Value>,
+    notes: HashMap<String, Vec<String>>,
+) -> String {
+    let mut emitter = Emitter {
+        out: String::new(),
+        comments: Comments {
+            leading: notes,
+            ..Comments::default()
+        },
+        conflicts: HashMap::new(),
+    };
+    emitter.mapping(root, "", 0);
+    emitter.out
+}
+
+/// A node the two sides of a merge disagree on.
+pub(crate) struct Alternatives {
+    /// Path shown on the opening marker.
+    pub label: String,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+/// Write a merged spec in canonical form, with the node at each path in
+/// `conflicts` replaced by both alternatives between conflict markers.
+pub(crate) fn format_merged(
+    root: &Map<String, Value>,
+    conflicts: HashMap<String, Alternatives>,
+) -> String {
+    let mut emitter = Emitter {
+        out: String::new(),
+        comments: Comments::default(),
+        conflicts,
+    };
+    emitter.mapping(root, "", 0);
+    emitter.out
+}
+
+// ── Comments ───────────────────────────────────────────────────────
+
+#[derive(Default)]
+struct Comments {
+    /// Full-line comments above a node, by path.
+    leading: HashMap<String, Vec<String>>,
+    /// Comment at the end of a node's first line, by path.
+    trailing: HashMap<String, String>,
+    /// Comments after the last node.
+    dangling: Vec<String>,
+}
+
+impl Comments {
+    fn collect(yaml: &str) -> Self {
+        // The outermost node starting on each line owns its comments.
+        let mut owners: HashMap<usize, (usize, &str)> = HashMap::new();
+        let map = SourceMap::from_yaml(yaml);
+        for (path, span) in map.iter() {
+            let owner = owners.entry(span.line).or_insert((span.column, path));
+            if span.column < owner.0 {
+                *owner = (span.column, path);
+            }
+        }
+
+        let mut comments = Comments::default();
+        let mut pending = Vec::new();
+        let mut block_scalar: Option<usize> = None;
+        for (i, raw) in yaml.lines().enumerate() {
+            let indent = raw.len() - raw.trim_start().len();
+            let trimmed = raw.trim_start();
+            if let Some(parent) = block_scalar {
+                if trimmed.is_empty() || indent > parent {
+                    continue;
+                }
+                block_scalar = None;
+            }
+            let code = strip_comment(trimmed);
+            let comment = trimmed[code.len()..].trim();
+            match owners.get(&(i + 1)) {
+                Some((_, path)) => {
+                    if !pending.is_empty() {
+                        comments
+                            .leading
+                            .insert(path.to_string(), std::mem::take(&mut pending));
+                    }
+                    if comment.starts_with('#') {
+                        comments
+                            .trailing
+                            .insert(path.to_string(), comment.to_string());
+                    }
+                    let value = code.rsplit_once(": ").map(|(_, v)| v).unwrap_or("");
+                    if value.starts_with(['|', '>']) || code.ends_with(['|', '>']) {
+                        block_scalar = Some(indent);
+                    }
+                }
+                None if code.is_empty() && comment.starts_with('#') => {
+                    pending.push(comment.to_string())
+                }
+                None => {}
+            }
+        }
+        comments.dangling = pending;
+        comments
+    }
+}
+
+// ── Emitter ────────────────────────────────────────────────────────
+
+struct Emitter {
+    out: String,
+    comments: Comments,
+    /// Merge conflicts, by the path of the node they replace.
+    conflicts: HashMap<String, Alternatives>,
+}
+
+impl Emitter {
+    fn mapping(&mut self, map: &Map<String, Value>, path: &str, indent: usize) {
+        for key in ordered_keys(map, path) {
+            let child = if path.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            self.leading(&child, indent);
+            let head = format!("{}{}:", " ".repeat(indent), scalar_str(key));
+            if !self.conflict(&child, |e, value| {
+                e.node(head.clone(), value, &child, indent)
+            }) {
+                self.node(head, &map[key], &child, indent);
+            }
+        }
+    }
+
+    fn sequence(&mut self, items: &[(usize, &Value)], path: &str, indent: usize) {
+        for (i, item) in items {
+            let child = format!("{}[{}]", path, i);
+            self.leading(&child, indent);
+            if !self.conflict(&child, |e, value| e.item(value, &child, indent)) {
+                self.item(item, &child, indent);
+            }
+        }
+    }
+
+    fn item(&mut self, item: &Value, child: &str, indent: usize) {
+        let prefix = format!("{}- ", " ".repeat(indent));
+        match item {
+            Value::Object(map) if !map.is_empty() => {
+                // The first key shares the dash's line.
+                let start = self.out.len();
+                self.mapping(map, child, indent + 2);
+                let first = start
+                    + self.out[start..]
+                        .split_inclusive('\n')
+                        .take_while(|line| line.trim_start().starts_with('#'))
+                        .map(str::len)
+                        .sum::<usize>();
+                if self.out[first..].starts_with("<<<<<<<") {
+                    // Unless it is conflicted.
+                    self.out
+                        .insert_str(first, &format!("{}-\n", " ".repeat(indent)));
+                    return;
+                }
+                self.out.replace_range(first..first + indent + 2, &prefix);
+                if let Some(comment) = self.comments.trailing.get(child) {
+                    let end = first + self.out[first..].find('\n').unwrap_or(0);
+                    self.out.insert_str(end, &format!("  {}", comment));
+                }
+            }
+            _ => {
+                let head = format!("{}-", " ".repeat(indent));
+                self.node(head, item, child, indent);
+            }
+        }
+    }
+
+    /// Emit both alternatives if `path` is conflicted; an absent side
+    /// leaves its half of the markers empty.
+    fn conflict(&mut self, path: &str, emit: impl Fn(&mut Self, &Value)) -> bool {
+        let Some(alternatives) = self.conflicts.remove(path) else {
+            return false;
+        };
+        self.line(format!("<<<<<<< ours ({})", alternatives.label));
+        if let Some(value) = &alternatives.ours {
+            emit(self, value);
+        }
+        self.line("=======".to_string());
+        if let Some(value) = &alternatives.theirs {
+            emit(self, value);
+        }
+        self.line(">>>>>>> theirs".to_string());
+        true
+    }
+
+    /// Emit `head` (`key:` or `-`) followed by `value`.
+    fn node(&mut self, head: String, value: &Value, path: &str, indent: usize) {
+        let trailing = match self.comments.trailing.get(path) {
+            Some(comment) => format!("  {}", comment),
+            None => String::new(),
+        };
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                self.line(format!("{}{}", head, trailing));
+                self.mapping(map, path, indent + 2);
+            }
+            Value::Array(items) if !items.is_empty() => {
+                let ordered: Vec<(usize, &Value)> = if path == "rules" {
+                    ordered_rules(items)
+                } else {
+                    items.iter().enumerate().collect()
+                };
+                let flow = items.iter().all(is_scalar)
+                    && (0..items.len()).all(|i| !self.has_comments(&format!("{}[{}]", path, i)));
+                if flow {
+                    let values: Vec<String> = items.iter().map(scalar_str_in_flow).collect();
+                    self.line(format!("{} [{}]{}", head, values.join(", "), trailing));
+                } else {
+                    self.line(format!("{}{}", head, trailing));
+                    self.sequence(&ordered, path, indent + 2);
+                }
+            }
+            Value::String(s) if is_block_literal(s) => {
+                let chomp = if s.ends_with('\n') { "" } else { "-" };
+                self.line(format!("{} |{}{}", head, chomp, trailing));
+                for text in s.trim_end_matches('\n').lines() {
+                    if text.is_empty() {
+                        self.line(String::new());
+                    } else {
+                        self.line(format!("{}{}", " ".repeat(indent + 2), text));
+                    }
+                }
+            }
+            _ => self.line(format!("{} {}{}", head, scalar_value(value), trailing)),
+        }
+    }
+
+    fn leading(&mut self, path: &str, indent: usize) {
+        if let Some(lines) = self.comments.leading.get(path) {
+            let lines: Vec<String> = lines
+                .iter()
+                .map(|c| format!("{}{}", " ".repeat(indent), c))
+                .collect();
+            for line in lines {
+                self.line(line);
+            }
+        }
+    }
+
+    fn has_comments(&self, path: &str) -> bool {
+        self.comments.leading.contains_key(path) || self.comments.trailing.contains_key(path)
+    }
+
+    fn line(&mut self, text: String) {
+        self.out.push_str(&text);
+        self.out.push('\n');
+    }
+}
+
+fn ordered_keys<'a>(map: &'a Map<String, Value>, path: &str) -> Vec<&'a str> {
+    // A workspace's `shared:` definitions are ordered as a spec's.
+    let path = match path.strip_prefix("shared") {
+        Some("") => "",
+        Some(rest) => rest.strip_prefix('.').unwrap_or(path),
+        None => path,
+    };
+    let pattern = strip_indexes(path);
+    let preferred = KEY_ORDER
+        .iter()
+        .find(|(p, _)| *p == pattern)
+        .map(|(_, keys)| *keys)
+        .unwrap_or(&[]);
+    let mut keys: Vec<&str> = map.keys().map(String::as_str).collect();
+    keys.sort_by_key(|key| {
+        (
+            preferred
+                .iter()
+                .position(|p| p == key)
+                .unwrap_or(preferred.len()),
+            *key,
+        )
+    });
+    keys
+}
+
+/// `rules[3].field` -> `rules[].field`
+fn strip_indexes(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut in_index = false;
+    for c in path.chars() {
+        match c {
+            '[' => {
+                in_index = true;
+                out.push_str("[]");
+            }
+            ']' => in_index = false,
+            _ if in_index => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// ── Scalars ────────────────────────────────────────────────────────
+
+fn is_scalar(value: &Value) -> bool {
+    !matches!(value, Value::Object(m) if !m.is_empty())
+        && !matches!(value, Value::Array(a) if !a.is_empty())
+}
+
+fn scalar_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => scalar_str(s),
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(_) => "[]".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn scalar_str_in_flow(value: &Value) -> String {
+    match value {
+        Value::String(s) if s.contains([',', '[', ']', '{', '}']) => quoted(s),
+        other => scalar_value(other),
+    }
+}
+
+/// A string, plain when a YAML reader would read it back unchanged.
+pub(crate) fn scalar_str(s: &str) -> String {
+    if is_plain_safe(s) {
+        s.to_string()
+    } else {
+        quoted(s)
+    }
+}
+
+fn quoted(s: &str) -> String {
+    // JSON string syntax is valid double-quoted YAML.
+    Value::String(s.to_string()).to_string()
+}
+
+fn is_plain_safe(s: &str) -> bool {
+    const YAML_11_BOOLEANS: &[&str] = &["y", "n", "yes", "no", "on", "off"];
+    let Some(first) = s.chars().next() else {
+        return false;
+    };
+    if s.trim() != s
+        || "-?:,[]{}#&*!|>'\"%@`".contains(first)
+        || s.contains(": ")
+        || s.contains(" #")
+        || s.ends_with(':')
+        || s.contains(['\n', '\t', '\r'])
+        || YAML_11_BOOLEANS.contains(&s.to_ascii_lowercase().as_str())
+    {
+        return false;
+    }
+    // Number- and date-like strings (`02134`, `2026-12-31`) that some YAML
+    // 1.1 readers would convert.
+    if (first.is_ascii_digit() || first == '.')
+        && s.chars()
+            .all(|c| c.is_ascii_hexdigit() || "_.:+-xXoO".contains(c))
+    {
+        return false;
+    }
+    // Rejects anything that reads back as a number, boolean or null.
+    matches!(serde_yaml::from_str::<Value>(s), Ok(Value::String(ref read)) if read == s)
+}
+
+fn is_block_literal(s: &str) -> bool {
+    s.contains('\n')
+        && !s.starts_with([' ', '\n'])
+        && !s.ends_with("\n\n")
+        && s.lines().all(|line| line.trim_end() == line)
+        && !s.contains(['\t', '\r'])
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn test_format_spec_is_canonical_and_hash_preserving() {
+        let messy = "\
+rules:
+    -   weight: 0.5
+        type: fuzzy
+        name: name_fuzzy    # tuned in Q3
+        field: name
+        algorithm: jaro_winkler
+        threshold: .85
+    - {name: email_exact, type: exact, field: email, weight: 1.0}
+entity: {name: customer}
+# Identity version bumps on every rule change.
+identity_version: 'retail_v1.0'
+api_version: \"kanoniv/v2\"
+sources:
+- name: crm
+  system: salesforce
+  table: contacts
+  id: contact_id
+  attributes: {name: full_name, email: email, zip: \"02134\"}
+decision: {thresholds: {review: 0.7, match: 0.9}}
+";
+        let formatted = crate::format_spec(messy).unwrap();
+        assert_eq!(
+            formatted,
+            "\
+api_version: kanoniv/v2
+# Identity version bumps on every rule change.
+identity_version: retail_v1.0
+entity:
+  name: customer
+sources:
+  - name: crm
+    system: salesforce
+    table: contacts
+    id: contact_id
+    attributes:
+      email: email
+      name: full_name
+      zip: \"02134\"
+rules:
+  - name: email_exact
+    type: exact
+    field: email
+    weight: 1.0
+  - name: name_fuzzy  # tuned in Q3
+    type: fuzzy
+    field: name
+    algorithm: jaro_winkler
+    threshold: 0.85
+    weight: 0.5
+decision:
+  thresholds:
+    match: 0.9
+    review: 0.7
+"
+        );
+
+        let hash = |yaml: &str| crate::compute_hash(&crate::parse_yaml(yaml).unwrap()).unwrap();
+        assert_eq!(hash(&formatted), hash(messy));
+        assert_eq!(crate::format_spec(&formatted).unwrap(), formatted);
+
+        // Parameters stay as written, unquoted ones included.
+        let templated = messy
+            .replace("threshold: .85", "threshold: {{ params.fuzzy }}")
+            .replace("table: contacts", "table: \"{{ params.schema }}.contacts\"");
+        let formatted = crate::format_spec(&templated).unwrap();
+        assert!(
+            formatted.contains("    threshold: {{ params.fuzzy }}\n"),
+            "{}",
+            formatted
+        );
+        assert!(
+            formatted.contains("    table: \"{{ params.schema }}.contacts\"\n"),
+            "{}",
+            formatted
+        );
+        assert_eq!(crate::format_spec(&formatted).unwrap(), formatted);
+    }
+}
@@ -0,0 +1,496 @@
+//! Arbitrary specs for fuzzing and property tests.
+//!
+//! A `SpecShape` is the outline of a spec: its sources, rules, blocking
+//! keys, survivorship rules, thresholds and a few other sections, each slot
+//! holding a `Scalar`. `Scalar::Pick` chooses one of the values that belong
+//! in the slot (a known field, rule type, strategy, ...), so a shape of
+//! picks mostly makes a valid spec; the other variants put text, numbers
+//! (NaN and infinities included), booleans, nulls and lists where they do
+//! not belong. `SpecShape::to_yaml` writes the spec.
+//!
+//! With the `arbitrary` feature shapes implement `arbitrary::Arbitrary`,
+//! for the cargo-fuzz targets in `fuzz/`. With the `proptest` feature,
+//! `strategies` generates them for property tests. Both shrink part by
+//! part: a failing spec reduces to the fewest sources, rules and keys, and
+//! its slots to the first value that belongs there.
+
+use serde_yaml::{Mapping, Value};
+
+use crate::{blocking, clustering, deletion, execution, similarity, survivorship};
+
+const API_VERSIONS: &[&str] = &["kanoniv/v2", "kanoniv/v1"];
+const IDENTITY_VERSIONS: &[&str] = &["v1", "retail_v1.0"];
+const ENTITIES: &[&str] = &["customer", "person", "company"];
+const SOURCES: &[&str] = &["crm", "billing", "erp", "web"];
+const SYSTEMS: &[&str] = &["salesforce", "stripe", "postgres"];
+const TABLES: &[&str] = &["contacts", "customers", "accounts"];
+const ID_COLUMNS: &[&str] = &["id", "contact_id", "customer_id"];
+const FIELDS: &[&str] = &[
+    "email",
+    "phone",
+    "first_name",
+    "last_name",
+    "dob",
+    "address",
+    "ssn",
+    "company",
+    "deleted",
+];
+const RULE_NAMES: &[&str] = &["email_exact", "phone_exact", "name_fuzzy", "dob_exact"];
+const RULE_TYPES: &[&str] = &["exact", "fuzzy", "semantic"];
+const TRANSFORMS: &[&str] = &[
+    "lowercase",
+    "trim",
+    "soundex",
+    "first_3",
+    "postcode",
+    "geohash:5",
+];
+
+/// What a slot of a section holds.
+#[derive(Clone, Copy)]
+enum Slot {
+    OneOf(&'static [&'static str]),
+    Number,
+    Count,
+    Flag,
+}
+
+/// Other top-level sections, and their keys.
+const SECTIONS: &[(&str, &[(&str, Slot)])] = &[
+    (
+        "clustering",
+        &[
+            ("strategy", Slot::OneOf(clustering::STRATEGIES)),
+            ("threshold", Slot::Number),
+        ],
+    ),
+    (
+        "execution",
+        &[
+            ("mode", Slot::OneOf(execution::MODES)),
+            ("delta_column", Slot::OneOf(FIELDS)),
+            ("stable_ids", Slot::Flag),
+        ],
+    ),
+    (
+        "deletion",
+        &[
+            ("tombstone", Slot::OneOf(FIELDS)),
+            ("scope", Slot::OneOf(deletion::SCOPES)),
+        ],
+    ),
+    (
+        "temporal",
+        &[
+            ("effective_from", Slot::OneOf(FIELDS)),
+            ("effective_to", Slot::OneOf(FIELDS)),
+            ("match_window_days", Slot::Count),
+        ],
+    ),
+    ("privacy", &[("retention_days", Slot::Count)]),
+];
+
+/// The value of one slot of a spec.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Scalar {
+    /// The `n`th (wrapping) of the values that belong in the slot; a number
+    /// slot takes `n / 255`.
+    Pick(u8),
+    Text(String),
+    Float(f64),
+    Int(i64),
+    Bool(bool),
+    Null,
+    List(Vec<String>),
+}
+
+impl Scalar {
+    /// The value for a slot holding one of `pool`.
+    fn text(&self, pool: &[&str]) -> Value {
+        match self {
+            Scalar::Pick(n) => Value::from(pool[*n as usize % pool.len()]),
+            _ => self.raw(),
+        }
+    }
+
+    /// The value for a slot holding a number.
+    fn number(&self) -> Value {
+        match self {
+            Scalar::Pick(n) => Value::from(f64::from(*n) / 255.0),
+            _ => self.raw(),
+        }
+    }
+
+    fn slot(&self, slot: Slot) -> Value {
+        match (slot, self) {
+            (Slot::OneOf(pool), _) => self.text(pool),
+            (Slot::Number, _) => self.number(),
+            (Slot::Count, Scalar::Pick(n)) => Value::from(*n),
+            (Slot::Flag, Scalar::Pick(n)) => Value::from(n % 2 == 0),
+            _ => self.raw(),
+        }
+    }
+
+    fn raw(&self) -> Value {
+        match self {
+            Scalar::Pick(n) => Value::from(*n),
+            Scalar::Text(text) => Value::from(text.as_str()),
+            Scalar::Float(number) => Value::from(*number),
+            Scalar::Int(number) => Value::from(*number),
+            Scalar::Bool(flag) => Value::from(*flag),
+            Scalar::Null => Value::Null,
+            Scalar::List(items) => Value::Sequence(
+                items
+                    .iter()
+                    .map(|item| Value::from(item.as_str()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SourceShape {
+    pub name: Scalar,
+    pub system: Scalar,
+    pub table: Scalar,
+    pub id: Scalar,
+    /// Attribute (a pick of the known fields) -> its column.
+    pub attributes: Vec<(u8, Scalar)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct RuleShape {
+    pub name: Scalar,
+    pub kind: Scalar,
+    pub field: Scalar,
+    pub algorithm: Option<Scalar>,
+    pub threshold: Option<Scalar>,
+    pub weight: Option<Scalar>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct BlockingShape {
+    pub strategy: Scalar,
+    /// Each key's field and, if any, its transform.
+    pub keys: Vec<(Scalar, Option<Scalar>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SurvivorshipShape {
+    pub field: Scalar,
+    pub strategy: Scalar,
+    pub source_priority: Vec<Scalar>,
+}
+
+/// One of `SECTIONS` and its entries, keys picked from the section's.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SectionShape {
+    pub section: u8,
+    pub entries: Vec<(u8, Scalar)>,
+}
+
+/// The outline of a spec; see the module docs.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SpecShape {
+    pub api_version: Scalar,
+    pub identity_version: Scalar,
+    pub entity: Scalar,
+    pub sources: Vec<SourceShape>,
+    pub rules: Vec<RuleShape>,
+    pub blocking: Option<BlockingShape>,
+    pub survivorship: Vec<SurvivorshipShape>,
+    /// Match and review thresholds.
+    pub decision: Option<(Scalar, Scalar)>,
+    pub sections: Vec<SectionShape>,
+}
+
+impl SpecShape {
+    /// The spec as YAML.
+    pub fn to_yaml(&self) -> String {
+        serde_yaml::to_string(&self.to_value()).expect("a YAML mapping serializes")
+    }
+
+    fn to_value(&self) -> Value {
+        let mut spec = Mapping::new();
+        spec.insert("api_version".into(), self.api_version.text(API_VERSIONS));
+        spec.insert(
+            "identity_version".into(),
+            self.identity_version.text(IDENTITY_VERSIONS),
+        );
+        spec.insert(
+            "entity".into(),
+            mapping([("name", self.entity.text(ENTITIES))]),
+        );
+        spec.insert(
+            "sources".into(),
+            self.sources.iter().map(SourceShape::to_value).collect(),
+        );
+        spec.insert(
+            "rules".into(),
+            self.rules.iter().map(RuleShape::to_value).collect(),
+        );
+        if let Some(blocking) = &self.blocking {
+            let keys = blocking
+                .keys
+                .iter()
+                .map(|(field, transform)| match transform {
+                    Some(transform) => mapping([
+                        ("field", field.text(FIELDS)),
+                        ("transform", transform.text(TRANSFORMS)),
+                    ]),
+                    None => field.text(FIELDS),
+                });
+            spec.insert(
+                "blocking".into(),
+                mapping([
+                    ("strategy", blocking.strategy.text(blocking::STRATEGIES)),
+                    ("keys", keys.collect()),
+                ]),
+            );
+        }
+        if !self.survivorship.is_empty() {
+            let rules = self.survivorship.iter().map(|rule| {
+                let mut value = Mapping::new();
+                value.insert("field".into(), rule.field.text(FIELDS));
+                value.insert(
+                    "strategy".into(),
+                    rule.strategy.text(survivorship::STRATEGIES),
+                );
+                if !rule.source_priority.is_empty() {
+                    let sources = rule
+                        .source_priority
+                        .iter()
+                        .map(|source| source.text(SOURCES));
+                    value.insert("source_priority".into(), sources.collect());
+                }
+                Value::Mapping(value)
+            });
+            spec.insert("survivorship".into(), mapping([("rules", rules.collect())]));
+        }
+        if let Some((matched, review)) = &self.decision {
+            let thresholds = mapping([("match", matched.number()), ("review", review.number())]);
+            spec.insert("decision".into(), mapping([("thresholds", thresholds)]));
+        }
+        for shape in &self.sections {
+            let (name, keys) = SECTIONS[shape.section as usize % SECTIONS.len()];
+            let mut section = Mapping::new();
+            for (key, value) in &shape.entries {
+                let (key, slot) = keys[*key as usize % keys.len()];
+                section.insert(key.into(), value.slot(slot));
+            }
+            spec.insert(name.into(), Value::Mapping(section));
+        }
+        Value::Mapping(spec)
+    }
+}
+
+impl SourceShape {
+    fn to_value(&self) -> Value {
+        let attributes = self.attributes.iter().map(|(field, column)| {
+            (
+                Value::from(FIELDS[*field as usize % FIELDS.len()]),
+                column.text(FIELDS),
+            )
+        });
+        mapping([
+            ("name", self.name.text(SOURCES)),
+            ("system", self.system.text(SYSTEMS)),
+            ("table", self.table.text(TABLES)),
+            ("id", self.id.text(ID_COLUMNS)),
+            ("attributes", Value::Mapping(attributes.collect())),
+        ])
+    }
+}
+
+impl RuleShape {
+    fn to_value(&self) -> Value {
+        let mut rule = Mapping::new();
+        rule.insert("name".into(), self.name.text(RULE_NAMES));
+        rule.insert("type".into(), self.kind.text(RULE_TYPES));
+        rule.insert("field".into(), self.field.text(FIELDS));
+        if let Some(algorithm) = &self.algorithm {
+            rule.insert(
+                "algorithm".into(),
+                algorithm.text(similarity::BUILTIN_ALGORITHMS),
+            );
+        }
+        if let Some(threshold) = &self.threshold {
+            rule.insert("threshold".into(), threshold.number());
+        }
+        if let Some(weight) = &self.weight {
+            rule.insert("weight".into(), weight.number());
+        }
+        Value::Mapping(rule)
+    }
+}
+
+fn mapping<const N: usize>(entries: [(&str, Value); N]) -> Value {
+    Value::Mapping(
+        entries
+            .into_iter()
+            .map(|(key, value)| (Value::from(key), value))
+            .collect(),
+    )
+}
+
+/// Proptest strategies for specs.
+#[cfg(feature = "proptest")]
+pub mod strategies {
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::spec::Spec;
+
+    /// Any scalar, picks most often.
+    pub fn scalar() -> impl Strategy<Value = Scalar> {
+        prop_oneof![
+            8 => any::<u8>().prop_map(Scalar::Pick),
+            1 => any::<String>().prop_map(Scalar::Text),
+            1 => any::<f64>().prop_map(Scalar::Float),
+            1 => any::<i64>().prop_map(Scalar::Int),
+            1 => any::<bool>().prop_map(Scalar::Bool),
+            1 => Just(Scalar::Null),
+            1 => vec(any::<String>(), 0..3).prop_map(Scalar::List),
+        ]
+    }
+
+    /// Spec shapes with any scalar in any slot.
+    pub fn spec_shape() -> impl Strategy<Value = SpecShape> {
+        shape(scalar().boxed())
+    }
+
+    /// Spec shapes whose slots all hold values that belong there.
+    pub fn well_formed_spec_shape() -> impl Strategy<Value = SpecShape> {
+        shape(any::<u8>().prop_map(Scalar::Pick).boxed())
+    }
+
+    /// The YAML of `spec_shape`s.
+    pub fn spec_yaml() -> impl Strategy<Value = String> {
+        spec_shape().prop_map(|shape| shape.to_yaml())
+    }
+
+    /// Parsed `well_formed_spec_shape`s.
+    pub fn spec() -> impl Strategy<Value = Spec> {
+        well_formed_spec_shape()
+            .prop_map(|shape| Spec::parse(&shape.to_yaml()).expect("generated YAML parses"))
+    }
+
+    fn shape(scalar: BoxedStrategy<Scalar>) -> impl Strategy<Value = SpecShape> {
+        let s = || scalar.clone();
+        let source = (s(), s(), s(), s(), vec((any::<u8>(), s()), 0..5)).prop_map(
+            |(name, system, table, id, attributes)| SourceShape {
+                name,
+                system,
+                table,
+                id,
+                attributes,
+            },
+        );
+        let rule = (
+            s(),
+            s(),
+            s(),
+            proptest::option::of(s()),
+            proptest::option::of(s()),
+            proptest::option::of(s()),
+        )
+            .prop_map(
+                |(name, kind, field, algorithm, threshold, weight)| RuleShape {
+                    name,
+                    kind,
+                    field,
+                    algorithm,
+                    threshold,
+                    weight,
+                },
+            );
+        let blocking = (s(), vec((s(), proptest::option::of(s())), 0..4))
+            .prop_map(|(strategy, keys)| BlockingShape { strategy, keys });
+        let survivorship =
+            (s(), s(), vec(s(), 0..3)).prop_map(|(field, strategy, source_priority)| {
+                SurvivorshipShape {
+                    field,
+                    strategy,
+                    source_priority,
+                }
+            });
+        let section = (any::<u8>(), vec((any::<u8>(), s()), 0..3))
+            .prop_map(|(section, entries)| SectionShape { section, entries });
+        (
+            (s(), s(), s()),
+            vec(source, 0..4),
+            vec(rule, 0..5),
+            proptest::option::of(blocking),
+            vec(survivorship, 0..3),
+            proptest::option::of((s(), s())),
+            vec(section, 0..3),
+        )
+            .prop_map(
+                |(
+                    (api_version, identity_version, entity),
+                    sources,
+                    rules,
+                    blocking,
+                    survivorship,
+                    decision,
+                    sections,
+                )| {
+                    SpecShape {
+                        api_version,
+                        identity_version,
+                        entity,
+                        sources,
+                        rules,
+                        blocking,
+                        survivorship,
+                        decision,
+                        sections,
+                    }
+                },
+            )
+    }
+}
+
+/// Specs of any shape, and any prefix of their YAML, are rejected with
+/// errors rather than panics by the parser, the validators and the planner.
+#[cfg(all(test, feature = "proptest"))]
+mod tests {
+    use crate::fuzzing::strategies::{spec, spec_yaml};
+    use crate::{generate_plan, parse_yaml, semantic_diagnostics, validate_schema};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_any_spec_is_checked_without_panicking(yaml in spec_yaml()) {
+            if let Ok(spec) = parse_yaml(&yaml) {
+                let _ = validate_schema(&spec);
+                let _ = semantic_diagnostics(&spec);
+            }
+            let _ = generate_plan(&yaml);
+        }
+
+        #[test]
+        fn test_truncated_yaml_is_parsed_without_panicking(yaml in spec_yaml(), cut in any::<prop::sample::Index>()) {
+            let end = (0..=cut.index(yaml.len() + 1)).rev().find(|&end| yaml.is_char_boundary(end)).unwrap_or(0);
+            let _ = parse_yaml(&yaml[..end]);
+        }
+
+        #[test]
+        fn test_well_formed_specs_plan_deterministically(spec in spec()) {
+            let _ = spec.diagnostics();
+            if let Ok(plan) = generate_plan(spec.source()) {
+                prop_assert_eq!(generate_plan(spec.source()).unwrap().plan_hash, plan.plan_hash);
+            }
+        }
+    }
+}
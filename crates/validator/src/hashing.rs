@@ -0,0 +1,313 @@
+//! Hash algorithms and keyed hashing for specs and record identifiers.
+//!
+//! Spec hashes default to SHA-256 (`canonical_hash`), which is also what
+//! plan hashes and registry versions use; a `Hasher` computes the same digest
+//! with SHA-512 or BLAKE3 instead. With a key, digests are keyed: HMAC for
+//! the SHA-2 algorithms and BLAKE3's keyed mode (under a key derived from the
+//! secret) for BLAKE3. Keyed digests are labelled `hmac-sha256:`,
+//! `hmac-sha512:` or `blake3-keyed:` so they are never mistaken for plain
+//! content hashes.
+//!
+//! Record identifiers are tokenized with the same digests, so a spec's
+//! `hashing` section (`algorithm`, and `key_env` or `key_file` naming where
+//! the key lives) tells every engine how to tokenize them. The key itself
+//! never appears in a spec or the IR.
+
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Context string for deriving BLAKE3 keys; changing it changes every token.
+const BLAKE3_KEY_CONTEXT: &str = "kanoniv 2024 keyed identifier hashing";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub const ALL: &'static [HashAlgorithm] = &[
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha512,
+        HashAlgorithm::Blake3,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Label of keyed digests.
+    pub fn keyed_name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "hmac-sha256",
+            HashAlgorithm::Sha512 => "hmac-sha512",
+            HashAlgorithm::Blake3 => "blake3-keyed",
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            _ => bail!(
+                "Unknown hash algorithm: '{}'. Expected one of: sha256, sha512, blake3",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Secret key for keyed hashing.
+#[derive(Clone)]
+pub struct HashKey(Vec<u8>);
+
+impl HashKey {
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Result<Self> {
+        let bytes = bytes.into();
+        if bytes.is_empty() {
+            bail!("hash key is empty");
+        }
+        Ok(HashKey(bytes))
+    }
+
+    /// The value of environment variable `name`.
+    pub fn from_env(name: &str) -> Result<Self> {
+        let value = std::env::var(name)
+            .with_context(|| format!("hash key variable {} is not set", name))?;
+        Self::new(value).with_context(|| format!("hash key variable {} is empty", name))
+    }
+
+    /// The contents of `path`, without a trailing newline.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let mut bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read key file: {}", path.display()))?;
+        while matches!(bytes.last(), Some(b'\n' | b'\r')) {
+            bytes.pop();
+        }
+        Self::new(bytes).with_context(|| format!("key file {} is empty", path.display()))
+    }
+
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HashKey(..)")
+    }
+}
+
+/// Where a key comes from: `key_file` if given, else `key_env`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeySource {
+    pub key_env: Option<String>,
+    pub key_file: Option<PathBuf>,
+}
+
+impl KeySource {
+    pub fn is_none(&self) -> bool {
+        self.key_env.is_none() && self.key_file.is_none()
+    }
+
+    /// Load the key, or `None` if no source is named.
+    pub fn load(&self) -> Result<Option<HashKey>> {
+        match (&self.key_file, &self.key_env) {
+            (Some(path), _) => HashKey::from_file(path).map(Some),
+            (None, Some(name)) => HashKey::from_env(name).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// A spec's `hashing` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HashingConfig {
+    pub algorithm: HashAlgorithm,
+    pub key: KeySource,
+}
+
+impl HashingConfig {
+    /// Read `hashing` from a parsed spec; defaults if the section is absent.
+    pub fn from_spec(spec: &Value) -> Result<Self> {
+        let Some(section) = spec.get("hashing").filter(|h| !h.is_null()) else {
+            return Ok(Self::default());
+        };
+        let text = |key: &str| section.get(key).and_then(Value::as_str).map(str::to_string);
+        let algorithm = match text("algorithm") {
+            Some(name) => name.parse()?,
+            None => HashAlgorithm::default(),
+        };
+        Ok(HashingConfig {
+            algorithm,
+            key: KeySource {
+                key_env: text("key_env"),
+                key_file: text("key_file").map(PathBuf::from),
+            },
+        })
+    }
+
+    /// This config with `algorithm` and `key`, where given, in its place.
+    pub fn overridden(mut self, algorithm: Option<&str>, key: &KeySource) -> Result<Self> {
+        if let Some(algorithm) = algorithm {
+            self.algorithm = algorithm.parse()?;
+        }
+        if !key.is_none() {
+            self.key = key.clone();
+        }
+        Ok(self)
+    }
+
+    /// A `Hasher` with the configured algorithm, keyed if a key source is named.
+    pub fn hasher(&self) -> Result<Hasher> {
+        Hasher::from_source(self.algorithm, &self.key)
+    }
+}
+
+/// Computes labelled digests with one algorithm and an optional key.
+#[derive(Debug, Clone, Default)]
+pub struct Hasher {
+    pub algorithm: HashAlgorithm,
+    key: Option<HashKey>,
+}
+
+impl Hasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        Hasher {
+            algorithm,
+            key: None,
+        }
+    }
+
+    pub fn keyed(algorithm: HashAlgorithm, key: HashKey) -> Self {
+        Hasher {
+            algorithm,
+            key: Some(key),
+        }
+    }
+
+    /// Keyed if `key` names a key source, which must then load.
+    pub fn from_source(algorithm: HashAlgorithm, key: &KeySource) -> Result<Self> {
+        Ok(match key.load()? {
+            Some(key) => Hasher::keyed(algorithm, key),
+            None => Hasher::new(algorithm),
+        })
+    }
+
+    pub fn is_keyed(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Label prefixed to digests, e.g. `sha256` or `hmac-sha256`.
+    pub fn label(&self) -> &'static str {
+        if self.is_keyed() {
+            self.algorithm.keyed_name()
+        } else {
+            self.algorithm.name()
+        }
+    }
+
+    /// `<label>:<hex>` over `data`.
+    pub fn digest(&self, data: &[u8]) -> String {
+        let hex = match (&self.key, self.algorithm) {
+            (None, HashAlgorithm::Sha256) => format!("{:x}", Sha256::digest(data)),
+            (None, HashAlgorithm::Sha512) => format!("{:x}", Sha512::digest(data)),
+            (None, HashAlgorithm::Blake3) => blake3::hash(data).to_hex().to_string(),
+            (Some(HashKey(key)), HashAlgorithm::Sha256) => {
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key length");
+                mac.update(data);
+                format!("{:x}", mac.finalize().into_bytes())
+            }
+            (Some(HashKey(key)), HashAlgorithm::Sha512) => {
+                let mut mac =
+                    Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes any key length");
+                mac.update(data);
+                format!("{:x}", mac.finalize().into_bytes())
+            }
+            (Some(HashKey(key)), HashAlgorithm::Blake3) => {
+                let derived = blake3::derive_key(BLAKE3_KEY_CONTEXT, key);
+                blake3::keyed_hash(&derived, data).to_hex().to_string()
+            }
+        };
+        format!("{}:{}", self.label(), hex)
+    }
+
+    /// Token for a record identifier: its digest, exactly as given.
+    pub fn token(&self, id: &str) -> String {
+        self.digest(id.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, MINIMAL};
+
+    #[test]
+    fn test_hashing_section_and_keyed_hashes() {
+        use crate::{canonical_hash, canonical_hash_with, HashAlgorithm, HashKey, Hasher, Ir};
+
+        let spec = crate::parse_yaml(MINIMAL).unwrap();
+        assert_eq!(
+            canonical_hash_with(&spec, &Hasher::default()),
+            canonical_hash(&spec)
+        );
+        assert!(
+            canonical_hash_with(&spec, &Hasher::new(HashAlgorithm::Blake3)).starts_with("blake3:")
+        );
+
+        let keyed = |key: &str| Hasher::keyed(HashAlgorithm::Sha256, HashKey::new(key).unwrap());
+        assert_eq!(
+            keyed("secret").token("abc"),
+            "hmac-sha256:9946dad4e00e913fc8be8e5d3f7e110a4a9e832f83fb09c345285d78638d8a0e"
+        );
+        assert_ne!(keyed("other").token("abc"), keyed("secret").token("abc"));
+        let blake3 = Hasher::keyed(HashAlgorithm::Blake3, HashKey::new("secret").unwrap());
+        assert!(blake3.token("abc").starts_with("blake3-keyed:"));
+
+        // The IR says how to tokenize, never with what key; specs without the
+        // section keep their plan hash.
+        let with_hashing = format!(
+            "{}hashing:\n  algorithm: BLAKE3\n  key_env: ID_KEY\n",
+            MINIMAL
+        );
+        let ir = compiled_ir(&with_hashing);
+        let hashing = ir.hashing.unwrap();
+        assert_eq!(
+            (hashing.algorithm.as_str(), hashing.keyed),
+            ("blake3", true)
+        );
+        assert_eq!(hashing.key_env.as_deref(), Some("ID_KEY"));
+        assert!(Ir::from_value(&crate::compile_to_ir(&spec).unwrap())
+            .unwrap()
+            .hashing
+            .is_none());
+
+        let invalid = format!("{}hashing:\n  algorithm: md5\n", MINIMAL);
+        let diagnostics = crate::semantic_diagnostics(&crate::parse_yaml(&invalid).unwrap());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "KNV0112");
+    }
+}
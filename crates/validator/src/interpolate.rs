@@ -0,0 +1,242 @@
+//! `${VAR}` and `{{ params.name }}` interpolation in spec files.
+//!
+//! Spec files are interpolated as they are read, before parsing, so a
+//! value can stand for any YAML scalar: `match: ${MATCH_THRESHOLD}` reads
+//! as a number. `${VAR}` comes from the environment, else the env file
+//! (`--env-file`); `{{ params.name }}` from `--param name=value`. Every
+//! reference must resolve: anything undefined is an error naming it and its
+//! line. Comments are left as they are, and `$${` writes a literal `${`.
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Values references resolve to, besides the environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variables {
+    /// `{{ params.name }}` values.
+    pub params: BTreeMap<String, String>,
+    /// `${VAR}` values for variables not in the environment.
+    pub env_file: BTreeMap<String, String>,
+}
+
+impl Variables {
+    /// From `name=value` parameters and an optional env file.
+    pub fn from_flags(params: &[String], env_file: Option<&Path>) -> Result<Self> {
+        let mut variables = Variables::default();
+        for param in params {
+            let Some((name, value)) = param.split_once('=') else {
+                bail!("Invalid parameter '{}': expected NAME=VALUE", param);
+            };
+            let name = name.trim();
+            if !is_name(name) {
+                bail!(
+                    "Invalid parameter name '{}' (use letters, digits and '_')",
+                    name
+                );
+            }
+            variables.params.insert(name.to_string(), value.to_string());
+        }
+        if let Some(path) = env_file {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read env file: {}", path.display()))?;
+            variables.env_file = parse_env_file(&text)
+                .with_context(|| format!("Invalid env file {}", path.display()))?;
+        }
+        Ok(variables)
+    }
+
+    fn env(&self, name: &str) -> Option<String> {
+        std::env::var(name)
+            .ok()
+            .or_else(|| self.env_file.get(name).cloned())
+    }
+}
+
+/// `KEY=VALUE` lines, with `#` comments, blank lines, an optional `export`
+/// and quotes around the value allowed.
+pub fn parse_env_file(text: &str) -> Result<BTreeMap<String, String>> {
+    let mut values = BTreeMap::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((name, value)) = line.split_once('=') else {
+            bail!("line {}: expected KEY=VALUE", n + 1);
+        };
+        let name = name.trim();
+        if !is_name(name) {
+            bail!("line {}: invalid variable name '{}'", n + 1, name);
+        }
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+            .unwrap_or(value);
+        values.insert(name.to_string(), value.to_string());
+    }
+    Ok(values)
+}
+
+/// `text` with every reference replaced by its value.
+pub fn interpolate(text: &str, variables: &Variables) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut errors = Vec::new();
+    for (n, line) in text.split_inclusive('\n').enumerate() {
+        let (body, comment) = line.split_at(comment_start(line));
+        let mut rest = body;
+        while let Some(start) = rest.find(['$', '{']) {
+            out.push_str(&rest[..start]);
+            let tail = &rest[start..];
+            let (replacement, used) = if tail.starts_with("$${") {
+                (Ok("${".to_string()), 3)
+            } else if tail.starts_with("${") {
+                env_reference(tail, variables)
+            } else if tail.starts_with("{{") {
+                param_reference(tail, variables)
+            } else {
+                (Ok(tail[..1].to_string()), 1)
+            };
+            match replacement {
+                Ok(value) => out.push_str(&value),
+                Err(e) => errors.push(format!("line {}: {}", n + 1, e)),
+            }
+            rest = &tail[used..];
+        }
+        out.push_str(rest);
+        out.push_str(comment);
+    }
+    if !errors.is_empty() {
+        bail!("Failed to interpolate the spec:\n  {}", errors.join("\n  "));
+    }
+    Ok(out)
+}
+
+/// The value of the `${VAR}` starting `tail`, and the bytes it spans.
+fn env_reference(tail: &str, variables: &Variables) -> (Result<String, String>, usize) {
+    let Some(end) = tail.find('}') else {
+        return (Err("unclosed ${".to_string()), 2);
+    };
+    let name = &tail[2..end];
+    let value = if !is_name(name) {
+        Err(format!("invalid variable ${{{}}}", name))
+    } else {
+        variables.env(name).ok_or_else(|| {
+            format!(
+                "undefined variable ${{{}}}; set it in the environment or an --env-file",
+                name
+            )
+        })
+    };
+    (value, end + 1)
+}
+
+/// The value of the `{{ params.name }}` starting `tail`, and the bytes it
+/// spans. Other `{{` are YAML's own braces, unless they hold a name: a
+/// reference missing `params.`.
+fn param_reference(tail: &str, variables: &Variables) -> (Result<String, String>, usize) {
+    let Some(end) = tail.find("}}") else {
+        return (Ok("{{".to_string()), 2);
+    };
+    let inner = tail[2..end].trim();
+    let value = match inner.strip_prefix("params.") {
+        Some(name) if is_name(name) => variables.params.get(name).cloned().ok_or_else(|| {
+            format!(
+                "undefined parameter params.{}; pass --param {}=...",
+                name, name
+            )
+        }),
+        _ if is_path(inner) => Err(format!(
+            "unknown reference {{{{ {} }}}}; expected {{{{ params.<name> }}}}",
+            inner
+        )),
+        _ => return (Ok("{{".to_string()), 2),
+    };
+    (value, end + 2)
+}
+
+/// Where a YAML comment starts in `line`: a `#` at its start or after
+/// whitespace.
+fn comment_start(line: &str) -> usize {
+    let bytes = line.as_bytes();
+    (0..bytes.len())
+        .find(|&i| bytes[i] == b'#' && (i == 0 || bytes[i - 1] == b' ' || bytes[i - 1] == b'\t'))
+        .unwrap_or(line.len())
+}
+
+fn is_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_path(inner: &str) -> bool {
+    !inner.is_empty() && inner.split('.').all(is_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_interpolate_params_and_env_values() {
+        use crate::interpolate::{interpolate, parse_env_file, Variables};
+
+        let env =
+            parse_env_file("# dev\nexport KNV_TEST_MATCH=0.85\nKNV_TEST_SCHEMA='crm dev'\n\n")
+                .unwrap();
+        assert_eq!(env["KNV_TEST_MATCH"], "0.85");
+        assert_eq!(env["KNV_TEST_SCHEMA"], "crm dev");
+        assert!(parse_env_file("KNV_TEST_MATCH\n").is_err());
+
+        let variables = Variables {
+            params: [("schema".to_string(), "crm_prod".to_string())].into(),
+            env_file: env,
+        };
+        let yaml = MINIMAL
+            .replace(
+                "table: contacts",
+                "table: \"{{ params.schema }}.contacts\"  # not ${KNV_TEST_UNSET}",
+            )
+            .replace("match: 0.9", "match: ${KNV_TEST_MATCH}");
+        let interpolated = interpolate(&yaml, &variables).unwrap();
+        assert!(interpolated.contains("table: \"crm_prod.contacts\"  # not ${KNV_TEST_UNSET}"));
+        let spec = crate::parse_yaml(&interpolated).unwrap();
+        assert_eq!(spec["decision"]["thresholds"]["match"], 0.85);
+        assert_eq!(
+            interpolate("a: $${KEEP} {a: 1}\n", &variables).unwrap(),
+            "a: ${KEEP} {a: 1}\n"
+        );
+
+        let err = interpolate(&yaml, &Variables::default())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("line 8: undefined parameter params.schema"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("line 19: undefined variable ${KNV_TEST_MATCH}"),
+            "{}",
+            err
+        );
+        let err = interpolate("a: {{ schema }}\nb: ${1X}\n", &variables)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("line 1: unknown reference {{ schema }}"),
+            "{}",
+            err
+        );
+        assert!(err.contains("line 2: invalid variable ${1X}"), "{}", err);
+
+        let flags = Variables::from_flags(&["schema=crm=1".to_string()], None).unwrap();
+        assert_eq!(flags.params["schema"], "crm=1");
+        assert!(Variables::from_flags(&["schema".to_string()], None).is_err());
+    }
+}
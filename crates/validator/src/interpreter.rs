@@ -0,0 +1,682 @@
+//! A reference interpreter for compiled IR.
+//!
+//! `execute_plan` runs a plan's stages in memory over JSON records, with
+//! the semantics the engine gives them: records are keyed `source:id`,
+//! paired on the blocking keys (and the sorted-neighbourhood windows or
+//! canopies of those strategies), scored as in `calibration`, decided on
+//! the thresholds, clustered and merged into golden records by the
+//! survivorship rules. It is small and slow on purpose: it is the oracle
+//! generated code is tested against, so SQL and Spark output for a fixture
+//! must agree with it.
+//!
+//! Records are given per source, each in its source's own columns:
+//!
+//! ```json
+//! { "crm": [{ "id": "1", "email": "a@x.com" }], "web": [ ... ] }
+//! ```
+//!
+//! or, with `execute_sources`, as a sample per source, such as
+//! `Sample::from_parquet` reads or `split_sources` takes out of one file
+//! with a `source_name` column. Either way values are compared as text.
+//!
+//! Values are trimmed, as generated code reads them, values past their
+//! retention period as of the run's date are expired (see `retention`),
+//! and the spec's
+//! normalization pipelines apply to scoring. Records are ordered by key,
+//! so a cluster's id is its lowest record key.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::audit::MatchDecision;
+use crate::calibration;
+use crate::cancel::{self, CancellationToken};
+use crate::clustering::{self, EntityClusters};
+use crate::commands::plan::extract_match_strategies;
+use crate::deletion::{Deletion, DeletionScope};
+use crate::embedding::{Embedder, HttpEmbedder};
+use crate::encoding;
+use crate::ir::Ir;
+use crate::learning::{self, MAX_PAIRS};
+use crate::normalize::Normalization;
+use crate::retention;
+use crate::sample::Sample;
+use crate::similarity::AlgorithmRegistry;
+use crate::stewardship::Stewardship;
+use crate::survivorship::{self, GoldenRecords};
+
+/// Each rule's similarities for a list of pairs, as `score_pairs` gives
+/// them.
+pub(crate) type Similarities = Vec<Option<Vec<Option<f64>>>>;
+
+/// What running a plan over a set of records produced.
+#[derive(Debug, Serialize)]
+pub struct ExecutionResult {
+    pub plan_hash: String,
+    /// Records read, deleted ones included.
+    pub records: usize,
+    /// Records dropped as deleted, with the members of erased entities.
+    pub deleted: usize,
+    pub candidate_pairs: usize,
+    /// Every candidate pair's score and decision, ordered by key.
+    pub decisions: Vec<MatchDecision>,
+    /// Each rule's similarity for every pair of `decisions`, in the IR's
+    /// rule order; `None` where a value is missing.
+    #[serde(skip)]
+    pub similarities: Vec<Vec<Option<f64>>>,
+    pub clusters: EntityClusters,
+    pub golden: GoldenRecords,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Run `ir` over `records` as of the date `as_of` (`YYYY-MM-DD`), embedding
+/// values for semantic rules through the rules' endpoints.
+pub fn execute_plan(ir: &Ir, records: &Value, as_of: &str) -> Result<ExecutionResult> {
+    execute_plan_with(ir, records, &HttpEmbedder::new()?, None, as_of)
+}
+
+/// `execute_plan`, embedding values for semantic rules with `embedder`
+/// and stopping between stages once `token` is cancelled.
+pub fn execute_plan_with(
+    ir: &Ir,
+    records: &Value,
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+    as_of: &str,
+) -> Result<ExecutionResult> {
+    execute_sources_with(ir, &sources_json(records)?, embedder, token, as_of)
+}
+
+/// Run `ir` over the records of each source, given as `(source, sample)`,
+/// as of the date `as_of`.
+pub fn execute_sources(
+    ir: &Ir,
+    sources: &[(String, Sample)],
+    as_of: &str,
+) -> Result<ExecutionResult> {
+    execute_sources_with(ir, sources, &HttpEmbedder::new()?, None, as_of)
+}
+
+/// `execute_sources`, embedding values for semantic rules with `embedder`
+/// and stopping between stages once `token` is cancelled.
+pub fn execute_sources_with(
+    ir: &Ir,
+    sources: &[(String, Sample)],
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+    as_of: &str,
+) -> Result<ExecutionResult> {
+    execute_sources_linked(ir, sources, token, as_of, |data, encoder, warnings| {
+        link_records(ir, data, encoder, embedder, token, warnings)
+    })
+}
+
+/// `execute_sources_with`, with the candidate pairs of the loaded records
+/// from `pair`, as `candidate_pairs` gives them, and their similarities
+/// from `score`, as `score_pairs` gives them: an embedded backend joins
+/// them in its database (see `embedded`).
+#[cfg(feature = "duckdb")]
+pub(crate) fn execute_sources_staged(
+    ir: &Ir,
+    sources: &[(String, Sample)],
+    token: Option<&CancellationToken>,
+    as_of: &str,
+    pair: impl FnOnce(
+        &Sample,
+        Option<&encoding::Encoder>,
+        &mut Vec<String>,
+    ) -> Result<Vec<(usize, usize)>>,
+    score: impl FnOnce(&Sample, &[(usize, usize)]) -> Result<Similarities>,
+) -> Result<ExecutionResult> {
+    execute_sources_linked(ir, sources, token, as_of, |data, encoder, warnings| {
+        let pairs = pair(data, encoder, warnings)?;
+        cancel::check(token)?;
+        let scored = score(data, &pairs)?;
+        link(ir, data, &pairs, scored, token, warnings)
+    })
+}
+
+/// `execute_sources_with`, with the loaded records paired, scored, decided
+/// and clustered by `link`, as `link_records` does it: a coordinator has
+/// its workers do so (see `distributed`).
+pub(crate) fn execute_sources_linked(
+    ir: &Ir,
+    sources: &[(String, Sample)],
+    token: Option<&CancellationToken>,
+    as_of: &str,
+    link: impl FnOnce(&Sample, Option<&encoding::Encoder>, &mut Vec<String>) -> Result<Linked>,
+) -> Result<ExecutionResult> {
+    let spec = ir.to_spec();
+    let mut warnings = Vec::new();
+
+    let (mut data, read) = load_records(ir, &spec, sources)?;
+    let retention = retention::expire(ir, &mut data, as_of)?;
+    warnings.extend(retention.as_ref().and_then(retention::undated_warning));
+    let mut dropped = read - data.len();
+    let encoder = encoding::encoder(&spec)?;
+    let Linked {
+        candidate_pairs,
+        decisions,
+        similarities,
+        mut clusters,
+    } = link(&data, encoder.as_ref(), &mut warnings)?;
+
+    let cluster_of: HashMap<&str, &str> = clusters
+        .assignments
+        .iter()
+        .map(|a| (a.record_key.as_str(), a.cluster_id.as_str()))
+        .collect();
+    let mut members = Sample {
+        columns: ["cluster_id"]
+            .into_iter()
+            .map(String::from)
+            .chain(data.columns.iter().cloned())
+            .collect(),
+        rows: Vec::with_capacity(data.len()),
+    };
+    for row in &data.rows {
+        let cluster = cluster_of
+            .get(row[KEY].as_str())
+            .copied()
+            .unwrap_or(&row[KEY]);
+        let mut member = vec![cluster.to_string()];
+        member.extend(row.iter().cloned());
+        members.rows.push(member);
+    }
+    cancel::check(token)?;
+    let mut golden = survivorship::golden_records_from_ir(ir, &members, &Stewardship::default())?;
+    golden.retention = retention;
+
+    // Erased entities leave the clusters as well as the golden records.
+    if ir
+        .deletion
+        .as_ref()
+        .is_some_and(|d| d.scope == DeletionScope::Entity)
+    {
+        let kept: HashSet<&str> = golden
+            .records
+            .iter()
+            .map(|r| r.entity_id.as_str())
+            .collect();
+        clusters
+            .assignments
+            .retain(|a| kept.contains(a.cluster_id.as_str()));
+        let mut sizes: BTreeMap<&str, usize> = BTreeMap::new();
+        for assignment in &clusters.assignments {
+            *sizes.entry(assignment.cluster_id.as_str()).or_default() += 1;
+        }
+        clusters.records = clusters.assignments.len();
+        clusters.clusters = sizes.len();
+        clusters.largest_cluster = sizes.values().copied().max().unwrap_or(0);
+    }
+    dropped += golden.deleted;
+
+    Ok(ExecutionResult {
+        plan_hash: ir.plan_hash.clone(),
+        records: read,
+        deleted: dropped,
+        candidate_pairs,
+        decisions,
+        similarities,
+        clusters,
+        golden,
+        warnings,
+    })
+}
+
+/// The candidate pairs of a set of loaded records, scored, decided and
+/// clustered: a run up to its golden records.
+pub(crate) struct Linked {
+    pub candidate_pairs: usize,
+    pub decisions: Vec<MatchDecision>,
+    pub similarities: Vec<Vec<Option<f64>>>,
+    pub clusters: EntityClusters,
+}
+
+/// Pair `data`, loaded records, on the plan's blocking, then score, decide
+/// and cluster the pairs. Only the records' own values count, so any set
+/// of records holding every record a block or window joins to them links
+/// them alike (see `blocks`).
+pub(crate) fn link_records(
+    ir: &Ir,
+    data: &Sample,
+    encoder: Option<&encoding::Encoder>,
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+    warnings: &mut Vec<String>,
+) -> Result<Linked> {
+    let pairs = candidate_pairs(ir, data, warnings, |warnings| {
+        let (keyed, capped) = learning::candidate_pairs(ir, data, encoder, warnings);
+        if capped {
+            bail!(
+                "More than {} candidate pairs; the interpreter is for fixtures, not full runs",
+                MAX_PAIRS
+            );
+        }
+        Ok(keyed)
+    })?;
+    cancel::check(token)?;
+    let scored = score_pairs(ir, data, &pairs, embedder, token)?;
+    link(ir, data, &pairs, scored, token, warnings)
+}
+
+/// Decide `pairs` of `data` on their similarities, `scored`, and cluster
+/// the records.
+fn link(
+    ir: &Ir,
+    data: &Sample,
+    pairs: &[(usize, usize)],
+    scored: Similarities,
+    token: Option<&CancellationToken>,
+    warnings: &mut Vec<String>,
+) -> Result<Linked> {
+    let strategies = extract_match_strategies(ir);
+    let similarities: Vec<Vec<Option<f64>>> = strategies
+        .iter()
+        .zip(scored)
+        .map(|(rule, scores)| {
+            scores.unwrap_or_else(|| {
+                warnings.push(format!(
+                    "The records have no column for '{}'; rule '{}' scores 0",
+                    rule.field, rule.rule_name
+                ));
+                vec![None; pairs.len()]
+            })
+        })
+        .collect();
+    let scores = calibration::combined_scores(&strategies, &similarities, pairs.len());
+
+    let thresholds = ir.thresholds.as_ref();
+    let match_threshold = thresholds.and_then(|t| t.match_).unwrap_or(1.0);
+    let review_threshold = thresholds.and_then(|t| t.review);
+    let key = |record: usize| data.rows[record][KEY].as_str();
+    let decisions: Vec<MatchDecision> = pairs
+        .iter()
+        .zip(scores)
+        .map(|(&(a, b), score)| {
+            let decision = if score >= match_threshold {
+                "match"
+            } else if review_threshold.is_some_and(|r| score >= r) {
+                "review"
+            } else {
+                "reject"
+            };
+            MatchDecision {
+                left_key: key(a).to_string(),
+                right_key: key(b).to_string(),
+                score,
+                decision: decision.to_string(),
+            }
+        })
+        .collect();
+
+    let table = Sample {
+        columns: ["left_key", "right_key", "score", "decision"]
+            .map(String::from)
+            .to_vec(),
+        rows: decisions
+            .iter()
+            .map(|d| {
+                vec![
+                    d.left_key.clone(),
+                    d.right_key.clone(),
+                    d.score.to_string(),
+                    d.decision.clone(),
+                ]
+            })
+            .collect(),
+    };
+    cancel::check(token)?;
+    let keys: Vec<&str> = (0..data.len()).map(key).collect();
+    let clusters =
+        clustering::cluster_decisions_from_ir(ir, &table, &keys, &Stewardship::default())?;
+    Ok(Linked {
+        candidate_pairs: pairs.len(),
+        decisions,
+        similarities,
+        clusters,
+    })
+}
+
+/// Each rule's similarity for each of `pairs` of `data`, loaded records,
+/// in the IR's rule order; `None` for a rule whose field `data` has no
+/// column for. Only the pairs' own values count, so any subset of the
+/// records holding them scores a pair alike.
+pub(crate) fn score_pairs(
+    ir: &Ir,
+    data: &Sample,
+    pairs: &[(usize, usize)],
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+) -> Result<Similarities> {
+    let spec = ir.to_spec();
+    learning::pair_scores(
+        ir,
+        data,
+        &extract_match_strategies(ir),
+        pairs,
+        &Normalization::from_spec(&spec)?,
+        encoding::encoder(&spec)?.as_ref(),
+        embedder,
+        token,
+    )
+}
+
+/// Records of several sources, each in its own columns, in the form
+/// `execute_plan` reads them; empty values are null.
+pub fn records_json(sources: &[(String, Sample)]) -> Value {
+    let sources = sources
+        .iter()
+        .map(|(name, sample)| {
+            let rows = sample
+                .rows
+                .iter()
+                .map(|row| {
+                    let record: serde_json::Map<String, Value> = sample
+                        .columns
+                        .iter()
+                        .zip(row)
+                        .map(|(column, value)| {
+                            let value = match value.is_empty() {
+                                true => Value::Null,
+                                false => Value::String(value.clone()),
+                            };
+                            (column.clone(), value)
+                        })
+                        .collect();
+                    Value::Object(record)
+                })
+                .collect();
+            (name.clone(), Value::Array(rows))
+        })
+        .collect();
+    Value::Object(sources)
+}
+
+/// Take the records of one sample with a `source_name` column apart by
+/// source, in order of first appearance, without that column.
+pub fn split_sources(stacked: &Sample) -> Result<Vec<(String, Sample)>> {
+    let Some(source) = stacked.find_column("source_name") else {
+        bail!("The records have no 'source_name' column saying which source each is from");
+    };
+    let columns: Vec<String> = stacked
+        .columns
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != source)
+        .map(|(_, column)| column.clone())
+        .collect();
+    let mut parts: Vec<(String, Sample)> = Vec::new();
+    for row in &stacked.rows {
+        let name = row.get(source).map(|v| v.trim()).unwrap_or_default();
+        let values = row
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != source)
+            .map(|(_, value)| value.clone())
+            .collect();
+        match parts.iter_mut().find(|(n, _)| n == name) {
+            Some((_, part)) => part.rows.push(values),
+            None => parts.push((
+                name.to_string(),
+                Sample {
+                    columns: columns.clone(),
+                    rows: vec![values],
+                },
+            )),
+        }
+    }
+    Ok(parts)
+}
+
+/// Column of the stacked records holding the record key, after the
+/// source name and before the attributes.
+pub(crate) const KEY: usize = 1;
+
+/// JSON records by source as a sample per source.
+pub(crate) fn sources_json(records: &Value) -> Result<Vec<(String, Sample)>> {
+    let Value::Object(sources) = records else {
+        bail!("Records must be a JSON object of source name to records");
+    };
+    sources
+        .iter()
+        .map(|(name, rows)| {
+            let Value::Array(rows) = rows else {
+                bail!("Records of source '{}' must be an array", name);
+            };
+            Ok((name.clone(), Sample::from_json_records(rows)?))
+        })
+        .collect()
+}
+
+/// Stack the records of each source into one sample, keyed and ordered by
+/// record key, with records deleted under a record-scoped `deletion` left
+/// out. Also returns the number of records read.
+fn load_records(ir: &Ir, spec: &Value, parts: &[(String, Sample)]) -> Result<(Sample, usize)> {
+    let mut keys: Vec<(String, String)> = Vec::new();
+    for (name, part) in parts {
+        let Some(source) = ir.sources.iter().find(|s| s.name == *name) else {
+            bail!("Records given for unknown source '{}'", name);
+        };
+        let id = source.id.as_deref().unwrap_or("id");
+        let column = part.find_column(id);
+        for (i, row) in part.rows.iter().enumerate() {
+            let value = column
+                .and_then(|c| row.get(c))
+                .map(|v| v.trim())
+                .unwrap_or_default();
+            if value.is_empty() {
+                bail!("Record {} of source '{}' has no '{}'", i + 1, name, id);
+            }
+            keys.push((name.clone(), format!("{}:{}", name, value)));
+        }
+    }
+    let stacked = Sample::from_sources(spec, parts)?;
+    let read = stacked.len();
+
+    let tombstone = ir
+        .deletion
+        .as_ref()
+        .filter(|d| d.scope == DeletionScope::Record)
+        .and_then(|d| stacked.ir_column(ir, &d.tombstone));
+    let mut rows: Vec<Vec<String>> = keys
+        .into_iter()
+        .zip(stacked.rows)
+        .filter(|(_, row)| {
+            tombstone.is_none_or(|c| !Deletion::is_deleted(row.get(c).map(String::as_str)))
+        })
+        .map(|((source, key), row)| {
+            let mut record = vec![source, key];
+            record.extend(row.iter().map(|v| v.trim().to_string()));
+            record
+        })
+        .collect();
+    rows.sort_by(|a, b| a[KEY].cmp(&b[KEY]));
+    if let Some(pair) = rows.windows(2).find(|pair| pair[0][KEY] == pair[1][KEY]) {
+        bail!("Duplicate record key '{}'", pair[0][KEY]);
+    }
+
+    let mut columns = vec!["source_name".to_string(), "record_key".to_string()];
+    columns.extend(stacked.columns);
+    Ok((Sample { columns, rows }, read))
+}
+
+/// Pairs on the blocking keys, as `keyed` gives them (see
+/// `learning::candidate_pairs`), and within the sorted-neighbourhood
+/// windows or canopies if that is the strategy, as record indexes `(a, b)`
+/// with `a < b`, in order.
+pub(crate) fn candidate_pairs(
+    ir: &Ir,
+    data: &Sample,
+    warnings: &mut Vec<String>,
+    keyed: impl FnOnce(&mut Vec<String>) -> Result<Vec<(usize, usize)>>,
+) -> Result<Vec<(usize, usize)>> {
+    let blocking = &ir.blocking;
+    let windowed = blocking.sorted_neighborhood.is_some() || blocking.canopy.is_some();
+    let mut pairs: HashSet<(usize, usize)> = HashSet::new();
+    // Without keys, a windowed strategy alone decides what is compared.
+    if !blocking.keys.is_empty() || !windowed {
+        pairs.extend(keyed(warnings)?);
+    }
+
+    for block in &windows(ir, data, warnings) {
+        for (i, &a) in block.iter().enumerate() {
+            for &b in &block[i + 1..] {
+                pairs.insert((a.min(b), a.max(b)));
+            }
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = pairs.into_iter().collect();
+    pairs.sort_unstable();
+    Ok(pairs)
+}
+
+/// The sorted-neighbourhood windows or canopies of `data`, loaded
+/// records, if that is the strategy, as record indexes.
+fn windows(ir: &Ir, data: &Sample, warnings: &mut Vec<String>) -> Vec<Vec<usize>> {
+    let blocking = &ir.blocking;
+    match (&blocking.sorted_neighborhood, &blocking.canopy) {
+        (Some(window), _) => match data.ir_column(ir, &window.sort_key) {
+            Some(column) => {
+                let transform = window.transform.as_deref().unwrap_or("identity");
+                window.windows(&data.keys(column, transform))
+            }
+            None => {
+                warnings.push(format!(
+                    "The records have no column for sort key '{}'",
+                    window.sort_key
+                ));
+                Vec::new()
+            }
+        },
+        (None, Some(canopy)) => match data.ir_column(ir, &canopy.field) {
+            Some(column) => canopy.canopies(
+                &data.keys(column, "identity"),
+                &AlgorithmRegistry::builtin(),
+            ),
+            None => {
+                warnings.push(format!(
+                    "The records have no column for canopy field '{}'",
+                    canopy.field
+                ));
+                Vec::new()
+            }
+        },
+        (None, None) => Vec::new(),
+    }
+}
+
+/// Every group of records of `data`, loaded records, that
+/// `candidate_pairs` pairs within: the blocks of each blocking key and
+/// the windows or canopies. A set of records holding every group that
+/// holds one of its records pairs as all of them do.
+pub(crate) fn blocks(
+    ir: &Ir,
+    data: &Sample,
+    encoder: Option<&encoding::Encoder>,
+    warnings: &mut Vec<String>,
+) -> Vec<Vec<usize>> {
+    let blocking = &ir.blocking;
+    let windowed = blocking.sorted_neighborhood.is_some() || blocking.canopy.is_some();
+    let mut blocks = Vec::new();
+    if !blocking.keys.is_empty() || !windowed {
+        blocks.extend(learning::key_blocks(ir, data, encoder, warnings));
+    }
+    blocks.extend(windows(ir, data, warnings));
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, AS_OF, EXECUTION, EXECUTION_RECORDS};
+
+    #[test]
+    fn test_interpreter_executes_ir_over_records() {
+        use crate::execute_plan;
+
+        let ir = compiled_ir(EXECUTION);
+        let records: serde_json::Value = serde_json::from_str(EXECUTION_RECORDS).unwrap();
+        let result = execute_plan(&ir, &records, AS_OF).unwrap();
+        assert_eq!(result.plan_hash, ir.plan_hash);
+        // billing:12 is tombstoned and never matched.
+        assert_eq!((result.records, result.deleted), (9, 1));
+        let decisions: Vec<(&str, &str, &str)> = result
+            .decisions
+            .iter()
+            .map(|d| {
+                (
+                    d.left_key.as_str(),
+                    d.right_key.as_str(),
+                    d.decision.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            decisions,
+            [
+                ("billing:10", "crm:1", "match"),
+                ("billing:10", "crm:4", "match"),
+                ("billing:11", "crm:2", "review"),
+                ("crm:1", "crm:4", "match"),
+                ("crm:3", "crm:5", "reject"),
+            ]
+        );
+        // Every live record is clustered, singletons included, under its cluster's lowest key.
+        let cluster = |key: &str| {
+            result
+                .clusters
+                .assignments
+                .iter()
+                .find(|a| a.record_key == key)
+                .map(|a| a.cluster_id.as_str())
+        };
+        assert_eq!(result.clusters.records, 8);
+        assert_eq!(cluster("crm:4"), Some("billing:10"));
+        assert_eq!(cluster("billing:13"), Some("billing:13"));
+        assert_eq!(cluster("billing:12"), None);
+
+        let golden = &result.golden;
+        let field = |name: &str| golden.fields.iter().position(|f| f.field == name).unwrap();
+        let ann = golden
+            .records
+            .iter()
+            .find(|r| r.entity_id == "billing:10")
+            .unwrap();
+        assert_eq!(
+            ann.values[field("email")].as_deref(),
+            Some("ann@example.com")
+        );
+        assert_eq!(ann.values[field("phone")].as_deref(), Some("555-0101"));
+        assert_eq!(golden.records.len(), 6);
+
+        // Entity-scoped deletion erases the cluster holding the tombstone instead.
+        let erasing =
+            EXECUTION.replace("tombstone: deleted", "tombstone: deleted\n  scope: entity");
+        let ir = compiled_ir(&erasing);
+        let mut tombstoned = records.clone();
+        tombstoned["billing"][0]["deleted"] = serde_json::json!("true");
+        tombstoned["billing"][2]["deleted"] = serde_json::json!("false");
+        let result = execute_plan(&ir, &tombstoned, AS_OF).unwrap();
+        assert_eq!(result.deleted, 3);
+        assert!(result
+            .golden
+            .records
+            .iter()
+            .all(|r| r.entity_id != "billing:10"));
+        assert!(result
+            .clusters
+            .assignments
+            .iter()
+            .all(|a| a.cluster_id != "billing:10"));
+
+        let unknown = serde_json::json!({ "erp": [] });
+        assert!(execute_plan(&ir, &unknown, AS_OF)
+            .unwrap_err()
+            .to_string()
+            .contains("unknown source 'erp'"));
+    }
+}
@@ -0,0 +1,905 @@
+//! Typed view of the intermediate representation produced by `compile_to_ir`.
+//!
+//! The IR is emitted as JSON so it can be stored and exchanged, or as
+//! deterministic CBOR where size matters (`IrEncoding`); codegen backends
+//! deserialize it into these types rather than walking raw values.
+//!
+//! Compiled IR declares its format in `ir_version`, `MAJOR.MINOR`. A minor
+//! version only adds optional keys, so an IR that does not use them reads
+//! the same as under the minor before; a major version removes or retypes
+//! keys. Keys are written in sorted order, so a spec always compiles to the
+//! same bytes. `ir_json_schema` publishes the format, and `compatibility`
+//! tells an engine whether it can read a new IR the way it read an old one.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::attributes::AttributeType;
+use crate::blocking::{Canopy, SortedNeighborhood};
+use crate::canonical::ir_hash;
+use crate::cbor;
+use crate::clustering::Clustering;
+use crate::deletion::Deletion;
+use crate::encoding::Encoding;
+use crate::execution::Execution;
+use crate::lsh::LshConfig;
+use crate::privacy::Privacy;
+use crate::relationships::Relationship;
+use crate::stewardship::Stewardship;
+use crate::temporal::Temporal;
+
+/// Version of the IR format `compile_to_ir` writes.
+pub const IR_VERSION: IrVersion = IrVersion { major: 1, minor: 0 };
+
+pub const IR_SCHEMA_ID: &str = "https://oss.kanoniv.com/schema/ir.json";
+
+/// Maps keyed by attribute name; their keys are data, not format.
+const ATTRIBUTE_MAPS: &[&str] = &[
+    "sources[].attributes",
+    "sources[].types",
+    "sources[].pii",
+    "normalization.fields",
+    "temporal.attributes",
+    "privacy.fields",
+    "encoding.fields",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ir {
+    /// Format version, `MAJOR.MINOR`; absent in IR compiled before
+    /// versioning, which is version 1.0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ir_version: Option<String>,
+    pub api_version: Option<String>,
+    pub identity_version: Option<String>,
+    pub entity: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub sources: Vec<IrSource>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub rules: Vec<IrRule>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub blocking: IrBlocking,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub survivorship: Vec<IrSurvivorship>,
+    pub thresholds: Option<IrThresholds>,
+    /// Validity intervals and slowly-changing attribute handling.
+    pub temporal: Option<Temporal>,
+    /// Per-attribute normalizers the engine applies before blocking.
+    pub normalization: Option<IrNormalization>,
+    /// How record identifiers are tokenized; absent means plain SHA-256.
+    pub hashing: Option<IrHashing>,
+    /// How matched records are grouped; absent means transitive closure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clustering: Option<Clustering>,
+    /// How runs resolve records; absent means full rebuilds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<Execution>,
+    /// Links to other entities, resolved after golden records.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relationships: Vec<Relationship>,
+    /// Steward assertions that outrank scores and survivorship rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stewardship: Option<Stewardship>,
+    /// Retention and masking of the attributes holding PII.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<Privacy>,
+    /// Attributes matched as salted hashes or Bloom filters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<Encoding>,
+    /// How deleted source records reach clusters and golden records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletion: Option<Deletion>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub plan_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrSource {
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub name: String,
+    pub system: Option<String>,
+    pub table: Option<String>,
+    pub id: Option<String>,
+    /// Canonical attribute name → source column.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub attributes: BTreeMap<String, String>,
+    /// Canonical attribute name → declared type, for typed attributes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub types: BTreeMap<String, String>,
+    /// Canonical attribute name → `pii` tag, for tagged attributes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pii: BTreeMap<String, bool>,
+}
+
+impl IrSource {
+    /// The source as a spec declares it: typed and tagged attributes map to
+    /// their column, type and tag.
+    fn to_spec(&self) -> Value {
+        let attributes: serde_json::Map<String, Value> = self
+            .attributes
+            .iter()
+            .map(|(attribute, column)| {
+                let (ty, pii) = (self.types.get(attribute), self.pii.get(attribute));
+                let mapping = match (ty, pii) {
+                    (None, None) => json!(column),
+                    _ => json!({ "column": column, "type": ty, "pii": pii }),
+                };
+                (attribute.clone(), mapping)
+            })
+            .collect();
+        json!({
+            "name": self.name,
+            "system": self.system,
+            "table": self.table,
+            "id": self.id,
+            "attributes": attributes,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrRule {
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub name: String,
+    #[serde(rename = "type", default, deserialize_with = "null_as_default")]
+    pub match_type: String,
+    pub field: Option<String>,
+    pub algorithm: Option<String>,
+    pub threshold: Option<f64>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub weight: f64,
+    /// Embedding model of a `semantic` rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Endpoint serving the embedding model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Pairs the rule compares; it scores no others.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IrBlocking {
+    pub strategy: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub keys: Vec<IrBlockingKey>,
+    /// Signature parameters of `lsh` blocking.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lsh: Option<LshConfig>,
+    /// Sort key and window of `sorted_neighborhood` blocking.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sorted_neighborhood: Option<SortedNeighborhood>,
+    /// Field and thresholds of `canopy` blocking.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canopy: Option<Canopy>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrBlockingKey {
+    pub field: String,
+    pub transform: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrSurvivorship {
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub field: String,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub strategy: String,
+    pub source_priority: Option<Vec<String>>,
+    /// Attribute `most_recent` orders by; the record key if absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// Attributes `non_null_priority` takes the value from, first non-null wins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+    /// Score of a `custom` rule's candidates; the highest survives.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<String>,
+    /// Members this holds for survive before the others.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrThresholds {
+    #[serde(rename = "match")]
+    pub match_: Option<f64>,
+    pub review: Option<f64>,
+    pub reject: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrNormalization {
+    pub locale: String,
+    /// Region of phone numbers written without a country code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_region: Option<String>,
+    /// Attribute → normalizer steps, applied in order: normalizer names,
+    /// or `regex_replace` with its pattern and replacement.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub fields: BTreeMap<String, Vec<Value>>,
+}
+
+/// Identifier hashing: the algorithm, and where the key is found if tokens
+/// are keyed. The key itself is never compiled in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrHashing {
+    pub algorithm: String,
+    #[serde(default)]
+    pub keyed: bool,
+    pub key_env: Option<String>,
+    pub key_file: Option<String>,
+}
+
+impl Ir {
+    /// Deserialize a compiled IR value (as returned by `compile_to_ir`),
+    /// which must be in a version of the format this crate reads.
+    pub fn from_value(value: &serde_json::Value) -> Result<Self> {
+        let version = IrVersion::of(value)?;
+        if !IR_VERSION.reads(version) {
+            bail!(
+                "Cannot read IR version {}; this kanoniv reads IR {}.x up to {}",
+                version,
+                IR_VERSION.major,
+                IR_VERSION
+            );
+        }
+        serde_json::from_value(value.clone()).with_context(|| "Malformed IR")
+    }
+
+    /// Read a compiled IR file (the output of `kanoniv compile`).
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_value(&load_value(path)?)
+    }
+
+    /// The sections the IR carries, laid out as in a spec, so spec-level
+    /// checks (validation, custom risks) can run on IR alone. What
+    /// compilation drops (`waivers`, `owners`, descriptions, unknown keys)
+    /// is absent; for a spec without such keys, compiling the result gives
+    /// the same plan hash.
+    pub fn to_spec(&self) -> Value {
+        let mut spec = json!({
+            "api_version": self.api_version,
+            "identity_version": self.identity_version,
+            "entity": self.entity.as_ref().map(|name| json!({ "name": name })),
+            "temporal": self.temporal,
+            "normalization": self.normalization,
+            "hashing": self.hashing.as_ref().map(|h| json!({
+                "algorithm": h.algorithm,
+                "key_env": h.key_env,
+                "key_file": h.key_file,
+            })),
+            "clustering": self.clustering,
+            "execution": self.execution,
+            "stewardship": self.stewardship,
+            "privacy": self.privacy,
+            "encoding": self.encoding,
+            "deletion": self.deletion,
+        });
+        if !self.sources.is_empty() {
+            spec["sources"] = self.sources.iter().map(IrSource::to_spec).collect();
+        }
+        if !self.rules.is_empty() {
+            spec["rules"] = json!(self.rules);
+        }
+        if self.blocking.strategy.is_some() || !self.blocking.keys.is_empty() {
+            spec["blocking"] = json!(self.blocking);
+        }
+        if !self.survivorship.is_empty() {
+            spec["survivorship"] = json!({ "rules": self.survivorship });
+        }
+        if let Some(thresholds) = &self.thresholds {
+            spec["decision"] = json!({ "thresholds": thresholds });
+        }
+        if !self.relationships.is_empty() {
+            spec["relationships"] = json!(self.relationships);
+        }
+        strip_nulls(&mut spec);
+        spec
+    }
+
+    pub fn entity_name(&self) -> &str {
+        self.entity.as_deref().unwrap_or("entity")
+    }
+
+    /// All canonical attributes declared by any source, in sorted order.
+    pub fn attributes(&self) -> Vec<String> {
+        let mut attrs: Vec<String> = self
+            .sources
+            .iter()
+            .flat_map(|s| s.attributes.keys().cloned())
+            .collect();
+        attrs.sort();
+        attrs.dedup();
+        attrs
+    }
+
+    /// Each typed attribute's type, as the first source typing it gives it.
+    pub fn types(&self) -> BTreeMap<String, AttributeType> {
+        let mut types = BTreeMap::new();
+        for source in &self.sources {
+            for (attribute, ty) in &source.types {
+                if let Ok(ty) = ty.parse() {
+                    types.entry(attribute.clone()).or_insert(ty);
+                }
+            }
+        }
+        types
+    }
+}
+
+/// Read a compiled IR file, in any `IrEncoding`.
+pub fn load_value(path: &Path) -> Result<Value> {
+    let content =
+        std::fs::read(path).with_context(|| format!("Failed to read file: {}", path.display()))?;
+    decode(&content).with_context(|| format!("Invalid IR {}", path.display()))
+}
+
+/// Compiled IR in any `IrEncoding`, told apart by its first byte: JSON
+/// opens an object, CBOR a map, and anything else is taken for base64.
+pub fn decode(bytes: &[u8]) -> Result<Value> {
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace());
+    match start.map(|i| bytes[i]) {
+        Some(b'{') => serde_json::from_slice(bytes).context("Invalid IR JSON"),
+        Some(b) if b >> 5 == 5 => cbor::from_slice(bytes).context("Invalid IR CBOR"),
+        _ => {
+            let text = std::str::from_utf8(bytes).unwrap_or_default().trim();
+            let bytes = BASE64
+                .decode(text)
+                .context("IR is neither JSON, CBOR nor base64 CBOR")?;
+            cbor::from_slice(&bytes).context("Invalid IR CBOR")
+        }
+    }
+}
+
+/// `sha256:<hex>` over the IR's CBOR. The encoding is deterministic, so
+/// the digest names the IR's exact contents, `plan_hash` and `ir_version`
+/// included, whichever encoding it is stored in.
+pub fn ir_digest(ir: &Value) -> String {
+    format!("sha256:{:x}", Sha256::digest(cbor::to_vec(ir)))
+}
+
+/// How compiled IR is written. `json` is for people and diffs; `cbor` is
+/// the same value in deterministic CBOR, a fraction of the size, and
+/// `cbor-base64` that as text, for embedding in job configs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IrEncoding {
+    #[default]
+    Json,
+    Cbor,
+    CborBase64,
+}
+
+impl IrEncoding {
+    pub fn encode(self, ir: &Value) -> Result<Vec<u8>> {
+        Ok(match self {
+            IrEncoding::Json => serde_json::to_string_pretty(ir)?.into_bytes(),
+            IrEncoding::Cbor => cbor::to_vec(ir),
+            IrEncoding::CborBase64 => BASE64.encode(cbor::to_vec(ir)).into_bytes(),
+        })
+    }
+
+    /// Whether the encoding is text, so it can be printed.
+    pub fn is_text(self) -> bool {
+        self != IrEncoding::Cbor
+    }
+}
+
+impl FromStr for IrEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(IrEncoding::Json),
+            "cbor" => Ok(IrEncoding::Cbor),
+            "cbor-base64" => Ok(IrEncoding::CborBase64),
+            other => bail!(
+                "Unknown IR format '{}'. Expected one of: json, cbor, cbor-base64",
+                other
+            ),
+        }
+    }
+}
+
+/// Whether a compiled IR value's `plan_hash` is the hash of its contents,
+/// i.e. the IR is as compiled. The hash leaves out `ir_version`, so a spec
+/// keeps its plan hash when the format moves on.
+pub fn plan_hash_matches(ir: &Value) -> bool {
+    let Some(hash) = ir.get("plan_hash").and_then(Value::as_str) else {
+        return false;
+    };
+    let mut contents = ir.clone();
+    if let Value::Object(map) = &mut contents {
+        map.remove("plan_hash");
+        map.remove("ir_version");
+    }
+    ir_hash(&contents) == hash
+}
+
+/// A version of the IR format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IrVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl IrVersion {
+    /// The version a compiled IR value declares.
+    pub fn of(ir: &Value) -> Result<Self> {
+        match ir.get("ir_version") {
+            None | Some(Value::Null) => Ok(IrVersion { major: 1, minor: 0 }),
+            Some(Value::String(version)) => version.parse(),
+            Some(other) => bail!("ir_version must be a string, got {}", other),
+        }
+    }
+
+    /// Whether an engine reading this version reads IR of `other` fully:
+    /// the same major version, and no minor version after this one.
+    pub fn reads(self, other: IrVersion) -> bool {
+        self.major == other.major && self.minor >= other.minor
+    }
+}
+
+impl FromStr for IrVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parsed = s
+            .split_once('.')
+            .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)));
+        match parsed {
+            Some((major, minor)) => Ok(IrVersion { major, minor }),
+            None => bail!("Invalid ir_version '{}'; expected MAJOR.MINOR", s),
+        }
+    }
+}
+
+impl fmt::Display for IrVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// How the format of one compiled IR differs from another's.
+#[derive(Debug, Clone, Serialize)]
+pub struct IrCompatibility {
+    pub old_version: String,
+    pub new_version: String,
+    /// No change is breaking: an engine that read the old IR reads the new
+    /// one, ignoring any keys it does not know.
+    pub compatible: bool,
+    pub changes: Vec<IrChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IrChange {
+    /// Key path, with `[]` for list items and `*` for attribute names.
+    pub path: String,
+    pub breaking: bool,
+    pub description: String,
+}
+
+/// Compare the formats of two compiled IR values: their versions, and the
+/// keys and value types they use. Values themselves are not compared; a
+/// key only one of them uses is reported but not breaking, since absent
+/// keys mean their defaults.
+pub fn compatibility(old: &Value, new: &Value) -> Result<IrCompatibility> {
+    let (old_version, new_version) = (IrVersion::of(old)?, IrVersion::of(new)?);
+    let mut changes = Vec::new();
+    if old_version.major != new_version.major {
+        changes.push(IrChange {
+            path: "ir_version".to_string(),
+            breaking: true,
+            description: format!(
+                "major version changed from {} to {}",
+                old_version, new_version
+            ),
+        });
+    } else if new_version.minor > old_version.minor {
+        changes.push(IrChange {
+            path: "ir_version".to_string(),
+            breaking: false,
+            description: format!(
+                "minor version changed from {} to {}; keys it adds need engine support",
+                old_version, new_version
+            ),
+        });
+    }
+
+    let (mut old_shape, mut new_shape) = (BTreeMap::new(), BTreeMap::new());
+    shape(old, "", &mut old_shape);
+    shape(new, "", &mut new_shape);
+    for (path, types) in &old_shape {
+        if let Some(new_types) = new_shape.get(path).filter(|t| *t != types) {
+            changes.push(IrChange {
+                path: path.clone(),
+                breaking: true,
+                description: format!("was {}, is {}", join_types(types), join_types(new_types)),
+            });
+        }
+    }
+    // Of keys only one IR uses, the outermost stands for those inside it.
+    let only_in = |shape: &BTreeMap<String, BTreeSet<&'static str>>,
+                   other: &BTreeMap<String, BTreeSet<&'static str>>| {
+        let mut paths: Vec<(String, String)> = Vec::new();
+        for (path, types) in shape {
+            let inside = paths.last().is_some_and(|(outer, _)| {
+                path.strip_prefix(outer.as_str())
+                    .is_some_and(|rest| rest.starts_with(['.', '[']))
+            });
+            if !other.contains_key(path) && !inside {
+                paths.push((path.clone(), join_types(types)));
+            }
+        }
+        paths
+    };
+    for (path, _) in only_in(&old_shape, &new_shape) {
+        changes.push(IrChange {
+            path,
+            breaking: false,
+            description: "no longer used".to_string(),
+        });
+    }
+    for (path, types) in only_in(&new_shape, &old_shape) {
+        changes.push(IrChange {
+            path,
+            breaking: false,
+            description: format!("new {}; engines that do not read it ignore it", types),
+        });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(IrCompatibility {
+        old_version: old_version.to_string(),
+        new_version: new_version.to_string(),
+        compatible: changes.iter().all(|c| !c.breaking),
+        changes,
+    })
+}
+
+/// Each key path in `value` and the JSON types found there; `null` means
+/// absent and has no type.
+fn shape(value: &Value, path: &str, shapes: &mut BTreeMap<String, BTreeSet<&'static str>>) {
+    let kind = match value {
+        Value::Null => return,
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "list",
+        Value::Object(_) => "object",
+    };
+    if !path.is_empty() {
+        shapes.entry(path.to_string()).or_default().insert(kind);
+    }
+    let child = |key: &str| match path {
+        "" => key.to_string(),
+        _ => format!("{}.{}", path, key),
+    };
+    match value {
+        Value::Array(items) => {
+            let items_path = format!("{}[]", path);
+            for item in items {
+                shape(item, &items_path, shapes);
+            }
+        }
+        Value::Object(map) if ATTRIBUTE_MAPS.contains(&path) => {
+            for item in map.values() {
+                shape(item, &child("*"), shapes);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                shape(item, &child(key), shapes);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn join_types(types: &BTreeSet<&str>) -> String {
+    types.iter().copied().collect::<Vec<_>>().join(" or ")
+}
+
+/// JSON Schema of compiled IR in the current version, for engines that
+/// consume it. The sections carried over from the spec are objects laid
+/// out as in the spec schema.
+pub fn ir_json_schema() -> Value {
+    let text = json!({ "type": ["string", "null"] });
+    let number = json!({ "type": ["number", "null"] });
+    let names = json!({ "type": ["array", "null"], "items": { "type": "string" } });
+    let section =
+        |description: &str| json!({ "description": description, "type": ["object", "null"] });
+    let source = json!({
+        "type": "object",
+        "properties": {
+            "name": text,
+            "system": text,
+            "table": text,
+            "id": text,
+            "attributes": {
+                "description": "Canonical attribute name to source column.",
+                "type": ["object", "null"],
+                "additionalProperties": { "type": "string" },
+            },
+            "types": {
+                "description": "Canonical attribute name to declared type, for typed attributes.",
+                "type": "object",
+                "additionalProperties": { "enum": crate::attributes::TYPES },
+            },
+            "pii": {
+                "description": "Canonical attribute name to `pii` tag, for tagged attributes.",
+                "type": "object",
+                "additionalProperties": { "type": "boolean" },
+            },
+        },
+        "additionalProperties": false,
+    });
+    let rule = json!({
+        "type": "object",
+        "properties": {
+            "name": text,
+            "type": text,
+            "field": text,
+            "algorithm": text,
+            "threshold": number,
+            "weight": { "type": "number" },
+            "model": { "type": "string" },
+            "endpoint": { "type": "string" },
+            "condition": { "type": "string" },
+        },
+        "required": ["weight"],
+        "additionalProperties": false,
+    });
+    let blocking = json!({
+        "type": "object",
+        "properties": {
+            "strategy": text,
+            "keys": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": { "field": { "type": "string" }, "transform": text },
+                    "required": ["field"],
+                    "additionalProperties": false,
+                },
+            },
+            "lsh": section("Signature parameters of `lsh` blocking."),
+            "sorted_neighborhood": section("Sort key and window of `sorted_neighborhood` blocking."),
+            "canopy": section("Field and thresholds of `canopy` blocking."),
+        },
+        "additionalProperties": false,
+    });
+    let survivorship = json!({
+        "type": "object",
+        "properties": {
+            "field": text,
+            "strategy": text,
+            "source_priority": names,
+            "timestamp": { "type": "string" },
+            "fields": names,
+            "expression": { "type": "string" },
+            "condition": { "type": "string" },
+        },
+        "additionalProperties": false,
+    });
+    let thresholds = json!({
+        "type": ["object", "null"],
+        "properties": { "match": number, "review": number, "reject": number },
+    });
+    let normalization = json!({
+        "description": "Per-attribute normalizers the engine applies before blocking.",
+        "type": "object",
+        "properties": {
+            "locale": { "type": "string" },
+            "default_region": { "type": "string" },
+            "fields": {
+                "type": "object",
+                "additionalProperties": { "type": "array", "items": { "type": ["string", "object"] } },
+            },
+        },
+        "required": ["locale", "fields"],
+        "additionalProperties": false,
+    });
+    let hashing = json!({
+        "description": "Identifier hashing; the key itself is never compiled in.",
+        "type": "object",
+        "properties": {
+            "algorithm": { "type": "string" },
+            "keyed": { "type": "boolean" },
+            "key_env": text,
+            "key_file": text,
+        },
+        "required": ["algorithm", "keyed"],
+        "additionalProperties": false,
+    });
+    let properties = json!({
+        "ir_version": {
+            "description": "Version of the IR format, MAJOR.MINOR.",
+            "type": "string",
+            "pattern": "^[0-9]+\\.[0-9]+$",
+        },
+        "plan_hash": {
+            "description": "Canonical hash of the IR without plan_hash and ir_version.",
+            "type": "string",
+            "pattern": "^sha256:[0-9a-f]{64}$",
+        },
+        "api_version": text,
+        "identity_version": text,
+        "entity": text,
+        "sources": { "type": ["array", "null"], "items": source },
+        "rule_count": { "type": ["integer", "null"], "minimum": 0 },
+        "rules": { "type": ["array", "null"], "items": rule },
+        "blocking_strategy": text,
+        "blocking": blocking,
+        "survivorship": { "type": ["array", "null"], "items": survivorship },
+        "thresholds": thresholds,
+        "temporal": section("Validity intervals and slowly-changing attribute handling."),
+        "normalization": normalization,
+        "hashing": hashing,
+        "clustering": section("How matched records are grouped."),
+        "execution": section("How runs resolve records."),
+        "relationships": { "type": "array", "items": { "type": "object" } },
+        "stewardship": section("Steward assertions that outrank scores and survivorship rules."),
+        "privacy": section("Retention and masking of the attributes holding PII."),
+        "encoding": section("Attributes matched as salted hashes or Bloom filters."),
+        "deletion": section("How deleted source records reach clusters and golden records."),
+    });
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "$id": IR_SCHEMA_ID,
+        "title": "Kanoniv IR",
+        "description": format!("A compiled identity spec, IR version {}.", IR_VERSION),
+        "type": "object",
+        "properties": properties,
+        "required": ["ir_version", "plan_hash", "blocking"],
+        "additionalProperties": false,
+    })
+}
+
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+/// Compiled IR writes `null` for absent sections; treat those like missing keys.
+fn null_as_default<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MAPPINGS, MINIMAL, PRIVACY, RELATIONSHIPS, TYPED};
+    use crate::Ir;
+
+    #[test]
+    fn test_ir_is_versioned_and_matches_its_schema() {
+        use crate::ir::{self, IR_SCHEMA_ID};
+        use crate::{compile_to_ir, ir_json_schema, parse_yaml, IrVersion, IR_VERSION};
+
+        let compiled = compile_to_ir(&parse_yaml(MINIMAL).unwrap()).unwrap();
+        assert_eq!(compiled["ir_version"], IR_VERSION.to_string());
+        // The version is not part of the plan hash, which the conformance
+        // corpus pins.
+        assert!(ir::plan_hash_matches(&compiled));
+
+        let schema_value = ir_json_schema();
+        assert_eq!(schema_value["$id"], IR_SCHEMA_ID);
+        let schema = jsonschema::JSONSchema::compile(&schema_value).unwrap();
+        let mut specs = vec![
+            MINIMAL.to_string(),
+            RELATIONSHIPS.to_string(),
+            TYPED.to_string(),
+            MAPPINGS.to_string(),
+            format!("{}{}", MINIMAL, PRIVACY),
+            format!(
+                "{}hashing:\n  algorithm: BLAKE3\n  key_env: ID_KEY\n",
+                MINIMAL
+            ),
+            format!(
+                "{}normalization:\n  locale: es\n  fields:\n    email: [casefold]\n",
+                MINIMAL
+            ),
+            include_str!("../conformance/multi_source/spec.yaml").to_string(),
+        ];
+        specs.extend(
+            crate::templates::TEMPLATES
+                .iter()
+                .map(|t| t.render(&[]).unwrap()),
+        );
+        for yaml in &specs {
+            let ir = compile_to_ir(&parse_yaml(yaml).unwrap()).unwrap();
+            let errors: Vec<String> = match schema.validate(&ir) {
+                Ok(()) => Vec::new(),
+                Err(errors) => errors
+                    .map(|e| format!("{} at {}", e, e.instance_path))
+                    .collect(),
+            };
+            assert!(errors.is_empty(), "{:?} for:\n{}", errors, yaml);
+        }
+
+        // IR compiled before versioning is 1.0; a newer major version is refused.
+        let mut unversioned = compiled.clone();
+        unversioned.as_object_mut().unwrap().remove("ir_version");
+        assert_eq!(
+            IrVersion::of(&unversioned).unwrap(),
+            IrVersion { major: 1, minor: 0 }
+        );
+        assert!(Ir::from_value(&unversioned).is_ok());
+        let mut newer = compiled.clone();
+        newer["ir_version"] = serde_json::json!(format!("{}.0", IR_VERSION.major + 1));
+        assert!(Ir::from_value(&newer)
+            .unwrap_err()
+            .to_string()
+            .contains("Cannot read IR version"));
+        assert!(!schema.is_valid(&serde_json::json!({ "ir_version": "1", "plan_hash": compiled["plan_hash"], "blocking": {} })));
+    }
+
+    #[test]
+    fn test_ir_compatibility_compares_formats() {
+        use crate::ir::compatibility;
+        use crate::{compile_to_ir, parse_yaml};
+
+        let old = compile_to_ir(&parse_yaml(MINIMAL).unwrap()).unwrap();
+        let same = compatibility(&old, &old).unwrap();
+        assert!(same.compatible && same.changes.is_empty());
+
+        // Other values, attribute names and sources are not format changes.
+        let renamed =
+            compile_to_ir(&parse_yaml(&MINIMAL.replace("email", "email_address")).unwrap())
+                .unwrap();
+        assert!(compatibility(&old, &renamed).unwrap().changes.is_empty());
+
+        // A key only the new IR uses is reported once, at its outermost path.
+        let typed = compile_to_ir(&parse_yaml(TYPED).unwrap()).unwrap();
+        let added = compatibility(&old, &typed).unwrap();
+        assert!(added.compatible);
+        assert!(added
+            .changes
+            .iter()
+            .any(|c| c.path == "sources[].types" && !c.breaking));
+        assert!(!added.changes.iter().any(|c| c.path == "sources[].types.*"));
+        let removed = compatibility(&typed, &old).unwrap();
+        assert!(removed
+            .changes
+            .iter()
+            .any(|c| c.path == "sources[].types" && c.description == "no longer used"));
+
+        let mut retyped = old.clone();
+        retyped["rules"][0]["weight"] = serde_json::json!("1.0");
+        retyped["ir_version"] = serde_json::json!("2.0");
+        let breaking = compatibility(&old, &retyped).unwrap();
+        assert!(!breaking.compatible);
+        let paths: Vec<(&str, &str)> = breaking
+            .changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.description.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("ir_version", "major version changed from 1.0 to 2.0"),
+                ("rules[].weight", "was number, is string"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,735 @@
+//! Match weights learned from unlabeled records (Fellegi-Sunter with EM).
+//!
+//! `learn_weights` pairs the records of a sample, records for each pair
+//! whether each match rule agrees, and fits the Fellegi-Sunter model to
+//! those comparison vectors with expectation maximization: the share of
+//! pairs that match, and per rule the m probability (the rule agrees on a
+//! match) and u probability (it agrees on a non-match). A rule's weight is
+//! its agreement weight log2(m/u), scaled so the strongest rule gets 1, as
+//! `calibrate` recommends from labeled pairs.
+//!
+//! Records are paired on the spec's blocking keys, or all with each other
+//! without keys, at most `MAX_PAIRS` pairs. Values are normalized and
+//! scored as in `calibration`: exact rules agree on equal values, fuzzy and
+//! semantic rules where the score reaches the rule's threshold
+//! (`DEFAULT_AGREEMENT` without one). A missing value, or a pair the rule's
+//! `condition` does not hold for, says nothing, so the rule is left out of
+//! that pair's likelihood. With a temporal `match_window_days`, records
+//! whose validity intervals lie further apart are not paired.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::calibration;
+use crate::cancel::{self, CancellationToken};
+use crate::commands::compile::compile_to_ir;
+use crate::commands::plan::{extract_match_strategies, MatchStrategySummary};
+use crate::embedding::{self, Embedder, EmbeddingModel, HttpEmbedder};
+use crate::encoding::{self as encodings, Encoder};
+use crate::format::format_patched;
+use crate::ir::Ir;
+use crate::normalize::Normalization;
+use crate::parser;
+use crate::sample::{apply_transform, Sample};
+use crate::similarity::AlgorithmRegistry;
+use crate::temporal::Interval;
+
+/// Record pairs compared, at most.
+pub const MAX_PAIRS: usize = 1_000_000;
+
+/// Score at which a fuzzy or semantic rule without a threshold agrees.
+pub const DEFAULT_AGREEMENT: f64 = 0.9;
+
+/// EM stops after this many iterations, or once no probability moves by
+/// more than `CONVERGENCE`.
+pub const MAX_ITERATIONS: usize = 200;
+pub const CONVERGENCE: f64 = 1e-6;
+
+/// Starting point of EM: matches are rare and rules agree on most of them.
+const INITIAL_MATCH_PROPORTION: f64 = 0.1;
+const INITIAL_M: f64 = 0.9;
+
+/// Probabilities are kept this far from 0 and 1, so every weight is finite.
+const EPSILON: f64 = 1e-4;
+
+#[derive(Debug, Serialize)]
+pub struct LearnedWeights {
+    pub records: usize,
+    /// Record pairs compared.
+    pub pairs: usize,
+    pub iterations: usize,
+    pub converged: bool,
+    /// Estimated share of compared pairs that match.
+    pub match_proportion: f64,
+    pub rules: Vec<LearnedRule>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// The spec with the learned weights.
+    pub yaml: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LearnedRule {
+    pub rule_name: String,
+    pub match_type: String,
+    pub field: String,
+    /// Compared pairs with a value on both sides.
+    pub pairs: usize,
+    pub weight: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub m_probability: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub u_probability: Option<f64>,
+    /// log2(m/u); negative when agreement points away from a match.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_weight: Option<f64>,
+    /// The weight written to the spec; `None` leaves the rule's weight.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommended_weight: Option<f64>,
+}
+
+/// Learn `yaml`'s rule weights from the records in `data`, embedding values
+/// for semantic rules through the rules' endpoints.
+pub fn learn_weights(yaml: &str, data: &Sample) -> Result<LearnedWeights> {
+    learn_weights_with(yaml, data, &HttpEmbedder::new()?, None)
+}
+
+/// `learn_weights`, embedding values for semantic rules with `embedder`
+/// and stopping once `token` is cancelled.
+pub fn learn_weights_with(
+    yaml: &str,
+    data: &Sample,
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+) -> Result<LearnedWeights> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let strategies = extract_match_strategies(&ir);
+    if strategies.is_empty() {
+        bail!("The spec has no match rules to weigh");
+    }
+    if data.len() < 2 {
+        bail!("The data needs at least two records");
+    }
+
+    let mut warnings = Vec::new();
+    let encoder = encodings::encoder(&spec)?;
+    let (pairs, capped) = candidate_pairs(&ir, data, encoder.as_ref(), &mut warnings);
+    if pairs.is_empty() {
+        bail!("No two records share a blocking key; nothing to compare");
+    }
+    if capped {
+        warnings.push(format!(
+            "Compared the first {} candidate pairs only",
+            MAX_PAIRS
+        ));
+    }
+    cancel::check(token)?;
+
+    // Each pair's comparison vector: per rule, agreement or `None` where a
+    // value is missing.
+    let normalization = Normalization::from_spec(&spec)?;
+    let scored = pair_scores(
+        &ir,
+        data,
+        &strategies,
+        &pairs,
+        &normalization,
+        encoder.as_ref(),
+        embedder,
+        token,
+    )?;
+    let mut agreement: Vec<Vec<Option<bool>>> =
+        vec![Vec::with_capacity(strategies.len()); pairs.len()];
+    for (rule, scores) in strategies.iter().zip(scored) {
+        let Some(scores) = scores else {
+            warnings.push(format!(
+                "The data has no column for '{}'; rule '{}' keeps its weight",
+                rule.field, rule.rule_name
+            ));
+            agreement.iter_mut().for_each(|vector| vector.push(None));
+            continue;
+        };
+        let cut = match rule.match_type.as_str() {
+            "exact" => 1.0,
+            _ => rule.threshold.unwrap_or(DEFAULT_AGREEMENT),
+        };
+        for (vector, score) in agreement.iter_mut().zip(scores) {
+            vector.push(score.map(|s| s >= cut - 1e-9));
+        }
+    }
+
+    // Identical vectors share their E step.
+    let mut patterns: HashMap<Vec<Option<bool>>, f64> = HashMap::new();
+    for vector in agreement {
+        *patterns.entry(vector).or_default() += 1.0;
+    }
+    let patterns: Vec<(Vec<Option<bool>>, f64)> = patterns.into_iter().collect();
+    let model = fit(&patterns, strategies.len());
+
+    let compared: Vec<usize> = (0..strategies.len())
+        .map(|k| {
+            patterns
+                .iter()
+                .filter(|(vector, _)| vector[k].is_some())
+                .map(|(_, count)| *count as usize)
+                .sum()
+        })
+        .collect();
+    let match_weights: Vec<Option<f64>> = (0..strategies.len())
+        .map(|k| (compared[k] > 0).then(|| (model.m[k] / model.u[k]).log2()))
+        .collect();
+    let strongest = match_weights
+        .iter()
+        .flatten()
+        .fold(0.0, |a: f64, &b| a.max(b));
+
+    let rules: Vec<LearnedRule> = strategies
+        .iter()
+        .enumerate()
+        .map(|(k, rule)| {
+            let known = compared[k] > 0;
+            LearnedRule {
+                rule_name: rule.rule_name.clone(),
+                match_type: rule.match_type.clone(),
+                field: rule.field.clone(),
+                pairs: compared[k],
+                weight: rule.weight,
+                m_probability: known.then(|| round(model.m[k])),
+                u_probability: known.then(|| round(model.u[k])),
+                match_weight: match_weights[k].map(round),
+                recommended_weight: match_weights[k].map(|weight| {
+                    if strongest > 0.0 {
+                        (weight.max(0.0) / strongest * 100.0).round() / 100.0
+                    } else {
+                        0.0
+                    }
+                }),
+            }
+        })
+        .collect();
+    if !model.converged {
+        warnings.push(format!(
+            "EM did not converge in {} iterations",
+            MAX_ITERATIONS
+        ));
+    }
+
+    Ok(LearnedWeights {
+        records: data.len(),
+        pairs: pairs.len(),
+        iterations: model.iterations,
+        converged: model.converged,
+        match_proportion: round(model.match_proportion),
+        yaml: patched(yaml, &spec, &rules)?,
+        rules,
+        warnings,
+    })
+}
+
+/// Each rule's score for each pair; `None` where a value is missing or the
+/// rule's condition does not hold, and for a rule whose field the data has
+/// no column for. Values of encoded attributes are compared encoded.
+/// Stops between rules once `token` is cancelled.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn pair_scores(
+    ir: &Ir,
+    data: &Sample,
+    strategies: &[MatchStrategySummary],
+    pairs: &[(usize, usize)],
+    normalization: &Normalization,
+    encoder: Option<&Encoder>,
+    embedder: &dyn Embedder,
+    token: Option<&CancellationToken>,
+) -> Result<Vec<Option<Vec<Option<f64>>>>> {
+    let algorithms = AlgorithmRegistry::builtin();
+    let mut scored = Vec::with_capacity(strategies.len());
+    for rule in strategies {
+        cancel::check(token)?;
+        let Some(column) = data.ir_column(ir, &rule.field) else {
+            scored.push(None);
+            continue;
+        };
+        let values = values(data, column, &rule.field, normalization, encoder);
+        let mut scores = scores(rule, &values, pairs, &algorithms, embedder)?;
+        apply_condition(ir, data, rule, pairs, normalization, &mut scores)?;
+        scored.push(Some(scores));
+    }
+    Ok(scored)
+}
+
+/// Clear the scores of the pairs the rule's `condition` does not hold
+/// for: a rule compares only the pairs its condition holds for.
+pub(crate) fn apply_condition(
+    ir: &Ir,
+    data: &Sample,
+    rule: &MatchStrategySummary,
+    pairs: &[(usize, usize)],
+    normalization: &Normalization,
+    scores: &mut [Option<f64>],
+) -> Result<()> {
+    let Some(condition) = calibration::rule_condition(rule)? else {
+        return Ok(());
+    };
+    for (score, &(a, b)) in scores.iter_mut().zip(pairs) {
+        let value = |side: &str, attribute: &str| {
+            let row = &data.rows[if side == "left" { a } else { b }];
+            let column = data.ir_column(ir, attribute)?;
+            let value = normalization.apply(attribute, row.get(column)?);
+            let value = value.trim();
+            (!value.is_empty()).then(|| value.to_string())
+        };
+        if !calibration::condition_holds(&condition, &value) {
+            *score = None;
+        }
+    }
+    Ok(())
+}
+
+/// Pairs of records sharing a blocking key value (all pairs without keys
+/// the data has columns for) whose validity intervals fall within the
+/// temporal match window, in record order; and whether `MAX_PAIRS` cut
+/// them short. Keys on encoded attributes are their encodings.
+pub(crate) fn candidate_pairs(
+    ir: &Ir,
+    data: &Sample,
+    encoder: Option<&Encoder>,
+    warnings: &mut Vec<String>,
+) -> (Vec<(usize, usize)>, bool) {
+    let blocks = key_blocks(ir, data, encoder, warnings);
+    let in_window = match_window(ir, data, warnings);
+
+    let mut seen: HashSet<(usize, usize)> = HashSet::new();
+    let mut pairs = Vec::new();
+    for block in &blocks {
+        for (i, &a) in block.iter().enumerate() {
+            for &b in &block[i + 1..] {
+                if seen.insert((a, b)) && in_window(a, b) {
+                    if pairs.len() == MAX_PAIRS {
+                        return (pairs, true);
+                    }
+                    pairs.push((a, b));
+                }
+            }
+        }
+    }
+    (pairs, false)
+}
+
+/// The records of `data` sharing a value of each blocking key, key by key,
+/// as record indexes in order; all of them in one block if the data has no
+/// column for any key.
+pub(crate) fn key_blocks(
+    ir: &Ir,
+    data: &Sample,
+    encoder: Option<&Encoder>,
+    warnings: &mut Vec<String>,
+) -> Vec<Vec<usize>> {
+    let mut blocks: Vec<Vec<usize>> = Vec::new();
+    let mut read = 0;
+    for key in &ir.blocking.keys {
+        let Some(column) = data.ir_column(ir, &key.field) else {
+            warnings.push(format!(
+                "The data has no column for blocking key '{}'",
+                key.field
+            ));
+            continue;
+        };
+        read += 1;
+        let transform = key.transform.as_deref().unwrap_or("identity");
+        let mut by_value: HashMap<String, Vec<usize>> = HashMap::new();
+        for (record, value) in block_keys(data, column, &key.field, transform, encoder)
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(value) = value {
+                by_value.entry(value).or_default().push(record);
+            }
+        }
+        let mut key_blocks: Vec<Vec<usize>> =
+            by_value.into_values().filter(|b| b.len() > 1).collect();
+        key_blocks.sort();
+        blocks.extend(key_blocks);
+    }
+    if read == 0 {
+        blocks.push((0..data.len()).collect());
+    }
+    blocks
+}
+
+/// Whether two records, by index, have validity intervals within the
+/// temporal match window; all do without a `match_window_days`.
+pub(crate) fn match_window<'a>(
+    ir: &'a Ir,
+    data: &Sample,
+    warnings: &mut Vec<String>,
+) -> impl Fn(usize, usize) -> bool + 'a {
+    let window = ir
+        .temporal
+        .as_ref()
+        .filter(|t| t.match_window_days.is_some());
+    let intervals: Vec<Interval> = match window {
+        Some(temporal) => {
+            let column =
+                |attribute: &Option<String>| attribute.as_ref().and_then(|a| data.ir_column(ir, a));
+            let (from, to) = (
+                column(&temporal.effective_from),
+                column(&temporal.effective_to),
+            );
+            if from.is_none() && to.is_none() {
+                warnings.push(
+                    "The data has no validity interval columns; the match window is not applied"
+                        .to_string(),
+                );
+            }
+            data.rows
+                .iter()
+                .map(|row| {
+                    let cell = |column: Option<usize>| column.and_then(|c| row.get(c));
+                    Interval::parse(cell(from).map(String::as_str), cell(to).map(String::as_str))
+                })
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    move |a: usize, b: usize| {
+        window.is_none_or(|temporal| temporal.within_window(intervals[a], intervals[b]))
+    }
+}
+
+/// Each record's blocking key value for `field` under `transform`; the
+/// encoding of its value if the spec encodes `field`.
+pub(crate) fn block_keys(
+    data: &Sample,
+    column: usize,
+    field: &str,
+    transform: &str,
+    encoder: Option<&Encoder>,
+) -> Vec<Option<String>> {
+    data.rows
+        .iter()
+        .map(|row| {
+            let value = row.get(column).map(String::as_str).unwrap_or_default();
+            block_key(value, field, transform, encoder)
+        })
+        .collect()
+}
+
+/// A value's blocking key for `field` under `transform`, or its encoding
+/// if the spec encodes `field`; `None` where that leaves nothing.
+pub(crate) fn block_key(
+    value: &str,
+    field: &str,
+    transform: &str,
+    encoder: Option<&Encoder>,
+) -> Option<String> {
+    match encoder.filter(|e| e.encodes(field)) {
+        Some(encoder) => encoder.encode_raw(field, value),
+        None => {
+            let key = apply_transform(value.trim(), transform);
+            (!key.is_empty()).then_some(key)
+        }
+    }
+}
+
+/// Each record's normalized, lowercased value of `field`, encoded if the
+/// spec encodes it.
+fn values(
+    data: &Sample,
+    column: usize,
+    field: &str,
+    normalization: &Normalization,
+    encoder: Option<&Encoder>,
+) -> Vec<Option<String>> {
+    data.rows
+        .iter()
+        .map(|row| comparable(row.get(column)?, field, normalization, encoder))
+        .collect()
+}
+
+/// A value of `field` as rules compare it: normalized, trimmed and
+/// lowercased, and encoded if the spec encodes `field`; `None` if blank.
+pub(crate) fn comparable(
+    value: &str,
+    field: &str,
+    normalization: &Normalization,
+    encoder: Option<&Encoder>,
+) -> Option<String> {
+    let value = normalization.apply(field, value).trim().to_lowercase();
+    (!value.is_empty()).then(|| encodings::encode(encoder, field, value))
+}
+
+/// The rule's score for each pair; `None` where a value is missing.
+fn scores(
+    rule: &MatchStrategySummary,
+    values: &[Option<String>],
+    pairs: &[(usize, usize)],
+    algorithms: &AlgorithmRegistry,
+    embedder: &dyn Embedder,
+) -> Result<Vec<Option<f64>>> {
+    let pair_values =
+        |&(a, b): &(usize, usize)| Some((values[a].as_deref()?, values[b].as_deref()?));
+    if rule.match_type == "semantic" {
+        let model = EmbeddingModel::new(
+            rule.model.clone().unwrap_or_default(),
+            rule.endpoint.clone().unwrap_or_default(),
+        );
+        let vectors = embedding::embed_all(
+            embedder,
+            &model,
+            values.iter().flatten().map(String::as_str),
+        )?;
+        return Ok(pairs
+            .iter()
+            .map(|pair| {
+                let (a, b) = pair_values(pair)?;
+                Some(embedding::cosine(&vectors[a], &vectors[b]))
+            })
+            .collect());
+    }
+    Ok(pairs
+        .iter()
+        .map(|pair| {
+            let (a, b) = pair_values(pair)?;
+            Some(calibration::score(rule, algorithms, a, b))
+        })
+        .collect())
+}
+
+struct Model {
+    match_proportion: f64,
+    m: Vec<f64>,
+    u: Vec<f64>,
+    iterations: usize,
+    converged: bool,
+}
+
+/// Fit the Fellegi-Sunter model to comparison vectors with their counts.
+fn fit(patterns: &[(Vec<Option<bool>>, f64)], rules: usize) -> Model {
+    let total: f64 = patterns.iter().map(|(_, count)| count).sum();
+    // u starts at each rule's overall agreement rate: nearly all pairs are
+    // non-matches.
+    let u: Vec<f64> = (0..rules)
+        .map(|k| {
+            let (mut agree, mut known) = (0.0, 0.0);
+            for (vector, count) in patterns {
+                if let Some(agrees) = vector[k] {
+                    known += count;
+                    if agrees {
+                        agree += count;
+                    }
+                }
+            }
+            let rate = if known > 0.0 { agree / known } else { 0.5 };
+            rate.clamp(EPSILON, INITIAL_M - 0.1)
+        })
+        .collect();
+    let mut model = Model {
+        match_proportion: INITIAL_MATCH_PROPORTION,
+        m: vec![INITIAL_M; rules],
+        u,
+        iterations: 0,
+        converged: false,
+    };
+
+    while model.iterations < MAX_ITERATIONS && !model.converged {
+        model.iterations += 1;
+        // E step: the probability that each pattern is a match.
+        let posteriors: Vec<f64> = patterns
+            .iter()
+            .map(|(vector, _)| {
+                let (mut matched, mut unmatched) =
+                    (model.match_proportion, 1.0 - model.match_proportion);
+                for (k, agrees) in vector.iter().enumerate() {
+                    match agrees {
+                        Some(true) => {
+                            matched *= model.m[k];
+                            unmatched *= model.u[k];
+                        }
+                        Some(false) => {
+                            matched *= 1.0 - model.m[k];
+                            unmatched *= 1.0 - model.u[k];
+                        }
+                        None => {}
+                    }
+                }
+                if matched + unmatched > 0.0 {
+                    matched / (matched + unmatched)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        // M step: re-estimate the parameters from the expected counts.
+        let expected: f64 = patterns
+            .iter()
+            .zip(&posteriors)
+            .map(|((_, count), p)| count * p)
+            .sum();
+        let mut change = (expected / total - model.match_proportion).abs();
+        model.match_proportion = (expected / total).clamp(EPSILON, 1.0 - EPSILON);
+        for k in 0..rules {
+            let (mut m_agree, mut m_known, mut u_agree, mut u_known) = (0.0, 0.0, 0.0, 0.0);
+            for ((vector, count), p) in patterns.iter().zip(&posteriors) {
+                let Some(agrees) = vector[k] else { continue };
+                m_known += count * p;
+                u_known += count * (1.0 - p);
+                if agrees {
+                    m_agree += count * p;
+                    u_agree += count * (1.0 - p);
+                }
+            }
+            let estimate = |agree: f64, known: f64, previous: f64| {
+                if known > 0.0 {
+                    (agree / known).clamp(EPSILON, 1.0 - EPSILON)
+                } else {
+                    previous
+                }
+            };
+            let (m, u) = (
+                estimate(m_agree, m_known, model.m[k]),
+                estimate(u_agree, u_known, model.u[k]),
+            );
+            change = change
+                .max((m - model.m[k]).abs())
+                .max((u - model.u[k]).abs());
+            (model.m[k], model.u[k]) = (m, u);
+        }
+        model.converged = change < CONVERGENCE;
+    }
+    model
+}
+
+/// `yaml` with each rule's recommended weight.
+fn patched(yaml: &str, spec: &Value, rules: &[LearnedRule]) -> Result<String> {
+    let mut spec = spec.clone();
+    if let Some(spec_rules) = spec.get_mut("rules").and_then(Value::as_array_mut) {
+        for rule in spec_rules {
+            let name = rule.get("name").and_then(Value::as_str).unwrap_or_default();
+            let learned = rules
+                .iter()
+                .find(|r| r.rule_name == name)
+                .and_then(|r| r.recommended_weight);
+            if let Some(weight) = learned {
+                rule["weight"] = json!(weight);
+            }
+        }
+    }
+    let Value::Object(root) = &spec else {
+        bail!("A spec must be a YAML mapping");
+    };
+    Ok(format_patched(yaml, root))
+}
+
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "parquet")]
+    use crate::test_support::write_parquet;
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_learn_weights_from_unlabeled_records() {
+        use crate::{learn_weights, Sample};
+
+        let spec = r#"
+api_version: kanoniv/v2
+identity_version: person_v1
+entity:
+  name: person
+sources:
+  - name: crm
+    system: salesforce
+    table: contacts
+    id: contact_id
+    attributes:
+      email: email_address
+      first_name: first
+      last_name: last
+      city: city
+rules:
+  - name: email_exact  # the strongest signal
+    type: exact
+    field: email
+    weight: 0.5
+  - name: first_fuzzy
+    type: fuzzy
+    field: first_name
+    algorithm: jaro_winkler
+    threshold: 0.85
+    weight: 0.5
+  - name: city_exact
+    type: exact
+    field: city
+    weight: 0.5
+blocking:
+  keys:
+    - field: last_name
+decision:
+  thresholds:
+    match: 0.8
+"#;
+        // 60 people in five last-name blocks; every other one appears twice,
+        // sometimes with a typo in the first name or without an email.
+        let firsts = ["john", "jane", "mike", "anna", "paul", "lisa", "mark"];
+        let lasts = ["smith", "jones", "brown", "lee", "wong"];
+        let cities = ["paris", "london", "berlin", "rome"];
+        let mut rows = vec![["first", "last", "email_address", "city"].map(String::from)];
+        for i in 0..60 {
+            let (first, last) = (firsts[i % 7], lasts[i % 5]);
+            let email = format!("{}.{}{}@example.com", first, last, i);
+            let city = cities[(i / 5) % 4];
+            rows.push([first, last, &email, city].map(String::from));
+            if i % 2 == 0 {
+                let first = if i % 6 == 0 {
+                    format!("{}n", first)
+                } else {
+                    first.to_string()
+                };
+                let email = if i % 8 == 0 { String::new() } else { email };
+                rows.push([&first, last, &email, city].map(String::from));
+            }
+        }
+        let csv: String = rows.iter().map(|r| r.join(",") + "\n").collect();
+        let learned = learn_weights(spec, &Sample::from_csv(&csv).unwrap()).unwrap();
+
+        assert_eq!(learned.records, 90);
+        assert!(learned.converged);
+        // 30 of the pairs within blocks are true matches.
+        assert!((learned.match_proportion * learned.pairs as f64 - 30.0).abs() < 5.0);
+        let rule = |name: &str| learned.rules.iter().find(|r| r.rule_name == name).unwrap();
+        let email = rule("email_exact");
+        assert!(email.m_probability.unwrap() > 0.9);
+        assert!(email.u_probability.unwrap() < 0.01);
+        assert_eq!(email.recommended_weight, Some(1.0));
+        let city = rule("city_exact");
+        assert!(city.recommended_weight.unwrap() < 1.0);
+        assert!(city.u_probability.unwrap() > email.u_probability.unwrap());
+
+        // The patched spec keeps its comments and takes the learned weights.
+        assert!(learned.yaml.contains("# the strongest signal"));
+        let patched = crate::parse_yaml(&learned.yaml).unwrap();
+        let weights: Vec<f64> = patched["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["weight"].as_f64().unwrap())
+            .collect();
+        assert!(weights.contains(&1.0) && weights.contains(&city.recommended_weight.unwrap()));
+
+        // Parquet samples read the same as CSV.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("people.parquet");
+        write_parquet(&path, &rows);
+        let parquet = Sample::load(&path).unwrap();
+        let from_csv = Sample::from_csv(&csv).unwrap();
+        assert_eq!(parquet.columns, from_csv.columns);
+        assert_eq!(parquet.rows, from_csv.rows);
+    }
+}
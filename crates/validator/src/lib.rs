@@ -3,19 +3,162 @@
 //! This module re-exports the core validation, compilation, hashing,
 //! and diffing functions for use by other Rust crates (including PyO3 bindings).
 
+pub mod address;
+pub mod attributes;
+pub mod audit;
+pub mod batch;
+pub mod bench;
+pub mod blocking;
+pub mod cache;
+pub mod calibration;
+pub mod cancel;
+pub mod canonical;
+pub mod cbor;
+pub(crate) mod clock;
+pub mod clustering;
+pub mod custom_risks;
+pub mod dag;
+pub mod deletion;
+pub mod diagnostics;
+#[cfg(feature = "distributed")]
+pub mod distributed;
+pub mod embedded;
+pub mod embedding;
+pub mod encoding;
+pub mod estimate;
+pub mod evaluation;
+pub mod execution;
+pub mod explanation;
+pub mod expression;
+pub mod format;
+pub mod fuzzing;
+pub mod hashing;
+pub mod interpolate;
+pub mod interpreter;
+pub mod learning;
+pub mod lineage;
+pub mod lsh;
+pub mod mappings;
+pub mod openlineage;
 pub mod validator;
 pub mod parser;
+pub mod registry;
+pub mod review;
+pub mod sample;
+pub mod scaffold;
+pub mod sensitivity;
+pub mod signing;
 pub mod commands;
-
-#[cfg(feature = "python")]
-pub mod python;
+pub mod compose;
+pub mod ir;
+pub mod merge;
+pub mod normalize;
+pub mod opa;
+pub mod output;
+pub mod owners;
+pub mod phone;
+pub mod plugins;
+pub mod policy;
+pub mod privacy;
+pub mod profile;
+pub mod relationships;
+pub mod retention;
+pub mod rule_packs;
+pub mod schema;
+pub mod similarity;
+pub mod spec;
+pub mod spec_tests;
+pub mod stewardship;
+pub mod survivorship;
+pub mod synthetic;
+pub mod task;
+pub mod temporal;
+pub mod templates;
+#[cfg(test)]
+mod test_support;
+pub mod waivers;
+pub mod watch;
+pub mod workspace;
 
 // Re-export the primary public functions
-pub use validator::{validate_schema, validate_semantics};
-pub use parser::parse_yaml;
-pub use commands::diff::{compute_diff, DiffResult, RuleChange};
+pub use validator::{schema_diagnostics, semantic_diagnostics, semantic_diagnostics_with, validate_schema, validate_semantics, validate_semantics_with_rules};
+pub use parser::{parse_yaml, parse_yaml_recovering, parse_yaml_with_locations, Recovered, SourceMap};
+pub use diagnostics::{Diagnostic, Profile, Severity, Span, Tiers};
+pub use commands::docs::generate_docs;
+pub use commands::diff::{compute_diff, ClassifiedChange, Compatibility, DiffResult, Impact, RuleChange};
+pub use batch::{validate_file, validate_many, validate_many_with, BatchOptions, FileReport};
+pub use bench::{built_in, load_dataset, regressions, run_dataset, BenchResult, BuiltIn, Dataset, Regression};
+pub use commands::check::{check_repo, check_repo_with, FileCheck, FileStatus, RepoCheck};
+pub use commands::snapshot::{snapshot, snapshots_dir, SnapshotResult, SnapshotStatus};
 pub use commands::compile::compile_to_ir;
-pub use commands::plan::{generate_plan, PlanResult};
+pub use commands::decompile::{decompile_ir, Decompiled};
+pub use compose::{compose_yaml, BaseRef, Composed, Override};
+pub use commands::codegen::dbt::generate_dbt_project;
+pub use commands::codegen::kafka::generate_kafka;
+pub use commands::codegen::pyspark::generate_pyspark;
+pub use commands::codegen::sql::{generate_sql, Dialect};
+pub use commands::codegen::GeneratedFile;
+pub use ir::{ir_digest, ir_json_schema, Ir, IrCompatibility, IrEncoding, IrVersion, IR_VERSION};
+pub use cache::{Cache, CacheKey, CacheStats};
+pub use canonical::{canonical_form, canonical_hash, canonical_hash_with, canonical_json, ir_hash};
+pub use hashing::{HashAlgorithm, HashKey, Hasher, HashingConfig, KeySource};
+pub use interpreter::{execute_plan, execute_plan_with, execute_sources, execute_sources_with, records_json, split_sources, ExecutionResult};
+pub use learning::{learn_weights, learn_weights_with, LearnedRule, LearnedWeights};
+pub use lineage::{Candidate, FieldLineage, Lineage};
+pub use openlineage::{LineageRun, RunEvent};
+pub use opa::OpaPolicies;
+pub use lsh::{LshConfig, LshMethod};
+pub use mappings::Mappings;
+pub use blocking::{Canopy, SortedNeighborhood};
+pub use clustering::{cluster_decisions, cluster_decisions_with, ClusterAssignment, Clustering, ClusteringStrategy, EntityClusters, ScoredPair};
+pub use commands::hash::compute_hash;
+pub use commands::migrate_plan::{generate_migration_plan, MigrationPlan, MigrationStep};
+pub use arrow_array::RecordBatch;
+pub use calibration::{calibrate, calibrate_with, Calibration, CurvePoint, DecisionCalibration, RuleCalibration};
+pub use cancel::{CancellationToken, Cancelled};
+pub use commands::plan::{generate_plan, generate_plan_from_ir, generate_plan_with, risk_score, BlockingAnalysis, KeyStats, MatchStrategySummary, PlanOptions, PlanResult, RiskFlag, SampleBlocking};
+pub use commands::report::Report;
+pub use custom_risks::{Condition, CustomRisk, CustomRisks};
+pub use dag::{Dag, DagEdge, DagNode, NodeKind};
+pub use embedding::{Embedder, EmbeddingModel, HttpEmbedder};
+#[cfg(feature = "distributed")]
+pub use distributed::{work, Coordinator, Dispatched, TlsIdentity, WorkDone};
+pub use embedded::ExecutionBackend;
+pub use estimate::{CostEstimate, KeyEstimate, RuleEstimate, StageEstimate, DEFAULT_PAIR_BUDGET};
+pub use evaluation::{evaluate, evaluate_with, records_by_source, truth_clusters, ClusterMetrics, EvaluationReport, PairwiseMetrics, RuleEvaluation};
+pub use explanation::{explain_pair, explain_pair_with, KeyAgreement, PairExplanation, RuleContribution};
+pub use format::format_spec;
+pub use merge::{merge_specs, MergeConflict, Merged};
+pub use owners::{Owners, RoutedFinding, Routing};
+pub use plugins::{Compiled, Plugin, PluginInfo, PluginRegistry, ProcessPlugin};
+pub use policy::{Level, Policy};
+pub use profile::{profile_source, AttributeProfile, SourceProfile};
+pub use relationships::{Cardinality, Relationship, RelationshipRule};
+pub use registry::{Published, Registry, SpecVersion};
+pub use retention::{expire, retention_windows, ExpiredValue, RetentionAudit};
+pub use review::{apply_verdicts, review_pairs, training_labels, ReviewItem, ReviewPair, ReviewQueue, ReviewStatus};
+pub use rule_packs::{Check, RulePack};
+pub use sample::Sample;
+pub use scaffold::Starter;
+pub use signing::{signed_hash, PublicKey, SignatureCheck, SignatureStatus, SigningKey, SpecSignature};
+pub use sensitivity::{analyze_thresholds, analyze_thresholds_with, Outcomes, ThresholdAnalysis};
+pub use attributes::AttributeType;
+pub use audit::{audit_json_schema, AuditEvent, AuditRecord, MatchDecision, MergeEvent, SplitEvent, StewardOverride, SurvivorshipChoice};
+pub use schema::spec_json_schema;
+pub use similarity::AlgorithmRegistry;
+pub use spec::Spec;
+pub use spec_tests::{Decision, GoldenExpectation, PairExpectation, SpecTest, Suite, TestFailure, TestResult};
+pub use survivorship::{
+    golden_records, golden_records_as_of, golden_records_with, survivorship_impact, FieldImpact, FieldStrategy, GoldenChange, GoldenRecord,
+    GoldenRecords, SurvivorshipImpact,
+};
+pub use synthetic::{generate_data, GenerateOptions, SyntheticData, TruthLabel};
+pub use task::BlockingTask;
+pub use stewardship::{Assertion, Lock, Stewardship};
+pub use temporal::{Interval, Temporal};
+pub use templates::Template;
+pub use waivers::{Waived, Waiver, Waivers};
+pub use workspace::Workspace;
 
 /// Convenience: validate a YAML string and return all errors.
 pub fn validate_yaml(yaml: &str) -> anyhow::Result<Vec<String>> {
@@ -24,3 +167,47 @@ pub fn validate_yaml(yaml: &str) -> anyhow::Result<Vec<String>> {
     errors.extend(validate_semantics(&spec)?);
     Ok(errors)
 }
+
+/// Validate a YAML string and return located diagnostics. YAML syntax errors
+/// are reported as diagnostics rather than an `Err`; when the document is
+/// broken, intact top-level sections are still checked. Findings waived in
+/// the spec are left out.
+pub fn diagnose_yaml(yaml: &str) -> Vec<Diagnostic> {
+    diagnose(yaml).0
+}
+
+/// Validate a YAML string and split the findings into errors, warnings and
+/// info, after `profile` has promoted or demoted advice.
+pub fn validate_tiers(yaml: &str, profile: Profile) -> Tiers {
+    validate_tiers_with(yaml, profile, &Policy::default())
+}
+
+/// As `validate_tiers`, with findings first set to the levels `policy` (and
+/// the spec's own `policy` section) gives them.
+pub fn validate_tiers_with(yaml: &str, profile: Profile, policy: &Policy) -> Tiers {
+    let (diagnostics, waived) = diagnose_with(yaml, policy);
+    Tiers::split_waived(diagnostics, waived, profile)
+}
+
+fn diagnose(yaml: &str) -> (Vec<Diagnostic>, Vec<Waived>) {
+    diagnose_with(yaml, &Policy::default())
+}
+
+fn diagnose_with(yaml: &str, policy: &Policy) -> (Vec<Diagnostic>, Vec<Waived>) {
+    let recovered = parser::parse_yaml_recovering(yaml);
+    let mut findings = schema_diagnostics(&recovered.value);
+    findings.extend(semantic_diagnostics(&recovered.value));
+    // A section that failed to parse is not "missing"; its syntax error
+    // already covers it.
+    findings.retain(|d| {
+        let root = d.path.as_deref().map(|p| p.split(['.', '[']).next().unwrap_or(p));
+        !root.is_some_and(|root| recovered.failed_sections.iter().any(|s| s == root))
+    });
+    let (findings, waived) = Waivers::collect(yaml, &recovered.value).apply(findings);
+    let findings = policy.for_spec(&recovered.value).apply(findings);
+
+    let mut diagnostics = recovered.diagnostics;
+    diagnostics.extend(findings);
+    diagnostics::locate_all(&mut diagnostics, &SourceMap::from_yaml(yaml));
+    (diagnostics, waived)
+}
@@ -0,0 +1,283 @@
+//! Identity lineage: where each golden field comes from.
+//!
+//! The pipeline's last stage writes `identity_lineage` next to the
+//! canonical table (see `plan`). For every golden record field it names
+//! the member record the value survives from, the candidates that lost,
+//! best first, and the survivorship rule that ranked them (see
+//! `survivorship`). `golden_records` builds it along with the golden
+//! records.
+//!
+//! As JSON, lineage nests the candidates under each field; as a table
+//! (`to_record_batch`, or Parquet with `write_parquet`) it has one row per
+//! candidate, the survivor at rank 1.
+
+use anyhow::Result;
+use arrow_array::{ArrayRef, BooleanArray, RecordBatch, StringArray, UInt32Array};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::ir::{Ir, IrSurvivorship};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Lineage {
+    pub plan_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_version: Option<String>,
+    pub fields: Vec<FieldLineage>,
+}
+
+/// One golden field and the members that competed for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldLineage {
+    pub entity_id: String,
+    pub field: String,
+    /// `None` is null.
+    pub value: Option<String>,
+    /// Source and key of the record the value survives from.
+    pub source: Option<String>,
+    pub record_key: Option<String>,
+    pub strategy: String,
+    /// The survivorship rule that decided, as `describe_rule` states it.
+    pub rule: String,
+    /// Members whose value lost, best first.
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub source: String,
+    pub record_key: String,
+    pub value: Option<String>,
+}
+
+impl Lineage {
+    pub(crate) fn new(ir: &Ir) -> Self {
+        Lineage {
+            plan_hash: ir.plan_hash.clone(),
+            identity_version: ir.identity_version.clone(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// The lineage of `entity_id`'s fields, or of `field` across entities,
+    /// or of one field of one entity; all of it without either.
+    pub fn query(&self, entity_id: Option<&str>, field: Option<&str>) -> Vec<&FieldLineage> {
+        self.fields
+            .iter()
+            .filter(|f| entity_id.is_none_or(|e| f.entity_id == e))
+            .filter(|f| field.is_none_or(|name| f.field == name))
+            .collect()
+    }
+
+    /// The `identity_lineage` table: a row per candidate, ranked from 1
+    /// (the survivor) within each entity's field.
+    pub fn to_record_batch(&self) -> Result<RecordBatch> {
+        // Field, rank, source, record key, value.
+        type Row<'a> = (&'a FieldLineage, u32, &'a str, &'a str, Option<&'a str>);
+        let rows: Vec<Row> = self
+            .fields
+            .iter()
+            .flat_map(|f| {
+                let winner = f.source.as_deref().zip(f.record_key.as_deref());
+                let winner = winner.map(|(source, key)| (f, 1, source, key, f.value.as_deref()));
+                let losers = f.candidates.iter().zip(2..).map(move |(c, rank)| {
+                    (
+                        f,
+                        rank,
+                        c.source.as_str(),
+                        c.record_key.as_str(),
+                        c.value.as_deref(),
+                    )
+                });
+                winner.into_iter().chain(losers)
+            })
+            .collect();
+        let text = |values: Vec<Option<&str>>| Arc::new(StringArray::from(values)) as ArrayRef;
+        Ok(RecordBatch::try_from_iter([
+            (
+                "entity_id",
+                text(rows.iter().map(|r| Some(r.0.entity_id.as_str())).collect()),
+            ),
+            (
+                "field",
+                text(rows.iter().map(|r| Some(r.0.field.as_str())).collect()),
+            ),
+            (
+                "rank",
+                Arc::new(rows.iter().map(|r| r.1).collect::<UInt32Array>()) as ArrayRef,
+            ),
+            (
+                "survived",
+                Arc::new(
+                    rows.iter()
+                        .map(|r| Some(r.1 == 1))
+                        .collect::<BooleanArray>(),
+                ) as ArrayRef,
+            ),
+            (
+                "source_name",
+                text(rows.iter().map(|r| Some(r.2)).collect()),
+            ),
+            ("record_key", text(rows.iter().map(|r| Some(r.3)).collect())),
+            ("value", text(rows.iter().map(|r| r.4).collect())),
+            (
+                "strategy",
+                text(rows.iter().map(|r| Some(r.0.strategy.as_str())).collect()),
+            ),
+            (
+                "rule",
+                text(rows.iter().map(|r| Some(r.0.rule.as_str())).collect()),
+            ),
+            (
+                "plan_hash",
+                text(rows.iter().map(|_| Some(self.plan_hash.as_str())).collect()),
+            ),
+        ])?)
+    }
+
+    /// Write `to_record_batch` as a Parquet file.
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet(&self, path: &Path) -> Result<()> {
+        use anyhow::Context;
+        use parquet::arrow::ArrowWriter;
+
+        let batch = self.to_record_batch()?;
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to write file: {}", path.display()))?;
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    pub fn write_parquet(&self, _path: &Path) -> Result<()> {
+        anyhow::bail!("kanoniv was built without the `parquet` feature")
+    }
+}
+
+/// A survivorship rule in a line, e.g. `source_priority: crm > billing` or
+/// `most_recent by updated_at`; `default` marks an attribute without a
+/// rule of its own.
+pub fn describe_rule(rule: &IrSurvivorship, default: bool) -> String {
+    let mut text = match rule.strategy.as_str() {
+        "source_priority" => format!(
+            "source_priority: {}",
+            rule.source_priority
+                .as_deref()
+                .unwrap_or_default()
+                .join(" > ")
+        ),
+        "most_recent" => match &rule.timestamp {
+            Some(timestamp) => format!("most_recent by {}", timestamp),
+            None => "most_recent by record key".to_string(),
+        },
+        "non_null_priority" => format!(
+            "non_null_priority: {}",
+            rule.fields.as_deref().unwrap_or_default().join(", ")
+        ),
+        "custom" => format!("custom: {}", rule.expression.as_deref().unwrap_or_default()),
+        strategy => strategy.to_string(),
+    };
+    if let Some(condition) = &rule.condition {
+        text.push_str(&format!(", preferring {}", condition));
+    }
+    if default {
+        text.push_str(" (default)");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{AS_OF, MINIMAL};
+
+    #[test]
+    fn test_golden_records_trace_field_lineage() {
+        use crate::lineage::describe_rule;
+        use crate::{golden_records, Candidate, Sample};
+
+        let spec = MINIMAL.replace("      email: email\n", "      email: email\n      phone: phone\n")
+        + "survivorship:\n  rules:\n    - field: email\n      strategy: source_priority\n      source_priority: [billing, crm]\n";
+        let members = Sample::from_csv(
+            "cluster_id,source_name,record_key,email,phone\n\
+         e1,crm,a,ann@x.com,555-1\n\
+         e1,billing,b,ann@y.com,\n\
+         e1,support,c,,555-12\n\
+         e2,crm,d,,\n",
+        )
+        .unwrap();
+        let golden = golden_records(&spec, &members, AS_OF).unwrap();
+        let lineage = &golden.lineage;
+        assert_eq!(lineage.identity_version.as_deref(), Some("retail_v1.0"));
+        assert_eq!(lineage.fields.len(), 4);
+
+        let email = lineage.query(Some("e1"), Some("email"));
+        assert_eq!(email.len(), 1);
+        assert_eq!(email[0].value.as_deref(), Some("ann@y.com"));
+        assert_eq!(
+            (email[0].source.as_deref(), email[0].record_key.as_deref()),
+            (Some("billing"), Some("b"))
+        );
+        assert_eq!(email[0].rule, "source_priority: billing > crm");
+        assert_eq!(
+            email[0].candidates,
+            [
+                Candidate {
+                    source: "crm".to_string(),
+                    record_key: "a".to_string(),
+                    value: Some("ann@x.com".to_string())
+                },
+                Candidate {
+                    source: "support".to_string(),
+                    record_key: "c".to_string(),
+                    value: None
+                },
+            ]
+        );
+        let phone = lineage.query(Some("e1"), Some("phone"));
+        assert_eq!(phone[0].value.as_deref(), Some("555-12"));
+        assert_eq!(phone[0].rule, "most_complete (default)");
+        assert_eq!(lineage.query(None, Some("email")).len(), 2);
+        assert_eq!(lineage.query(Some("e2"), None).len(), 2);
+
+        let rule: crate::ir::IrSurvivorship =
+        serde_json::from_value(serde_json::json!({ "field": "email", "strategy": "most_recent", "timestamp": "updated_at" })).unwrap();
+        assert_eq!(describe_rule(&rule, false), "most_recent by updated_at");
+
+        // As a table, a row per candidate, the survivor ranked first.
+        let batch = lineage.to_record_batch().unwrap();
+        let table = Sample::from_record_batches(&[batch]).unwrap();
+        let column = |name: &str| table.find_column(name).unwrap();
+        let rows: Vec<(&str, &str, &str, &str)> = table
+            .rows
+            .iter()
+            .filter(|row| row[column("field")] == "email")
+            .map(|row| {
+                (
+                    row[column("entity_id")].as_str(),
+                    row[column("rank")].as_str(),
+                    row[column("survived")].as_str(),
+                    row[column("record_key")].as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("e1", "1", "true", "b"),
+                ("e1", "2", "false", "a"),
+                ("e1", "3", "false", "c"),
+                ("e2", "1", "true", "d")
+            ]
+        );
+
+        if cfg!(feature = "parquet") {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("lineage.parquet");
+            lineage.write_parquet(&path).unwrap();
+            assert_eq!(Sample::load(&path).unwrap().len(), table.len());
+        }
+    }
+}
@@ -0,0 +1,420 @@
+//! Locality-sensitive hashing for blocking.
+//!
+//! With `strategy: lsh`, each blocking key's values are cut into character
+//! shingles and hashed into a signature, and the signature into `bands`
+//! bands of `rows` hashes each. Two records are candidates when any band
+//! of any key agrees, so pairs collide with a probability that rises
+//! steeply with their similarity:
+//!
+//! ```yaml
+//! blocking:
+//!   strategy: lsh
+//!   lsh:
+//!     method: minhash     # or simhash
+//!     bands: 20
+//!     rows: 5
+//!     shingle_size: 3
+//!   keys:
+//!     - field: company_name
+//! ```
+//!
+//! `minhash` signatures estimate the Jaccard similarity of the shingle
+//! sets, `simhash` (one 64-bit fingerprint) their cosine similarity.
+//! Hashes are FNV-1a and SplitMix64, so every engine computes the same
+//! band keys.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+pub const METHODS: &[&str] = &["minhash", "simhash"];
+
+/// Signature length limits: one SimHash fingerprint has 64 bits; longer
+/// MinHash signatures cost more than they separate.
+pub const MAX_MINHASH_SIGNATURE: usize = 1024;
+pub const MAX_SIMHASH_SIGNATURE: usize = 64;
+
+/// Longest shingle; beyond this, small typos leave no shingle in common.
+pub const MAX_SHINGLE_SIZE: usize = 10;
+
+/// Similarities the plan reports collision probabilities at.
+pub const REPORTED_SIMILARITIES: &[f64] = &[0.3, 0.5, 0.7, 0.8, 0.9, 0.95];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LshMethod {
+    #[default]
+    MinHash,
+    SimHash,
+}
+
+impl LshMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LshMethod::MinHash => "minhash",
+            LshMethod::SimHash => "simhash",
+        }
+    }
+
+    fn max_signature(&self) -> usize {
+        match self {
+            LshMethod::MinHash => MAX_MINHASH_SIGNATURE,
+            LshMethod::SimHash => MAX_SIMHASH_SIGNATURE,
+        }
+    }
+}
+
+impl FromStr for LshMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "minhash" => Ok(LshMethod::MinHash),
+            "simhash" => Ok(LshMethod::SimHash),
+            _ => bail!("Unknown LSH method '{}'. Use minhash or simhash", s),
+        }
+    }
+}
+
+impl fmt::Display for LshMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The `blocking.lsh` parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LshConfig {
+    pub method: LshMethod,
+    pub bands: usize,
+    pub rows: usize,
+    pub shingle_size: usize,
+}
+
+impl Default for LshConfig {
+    fn default() -> Self {
+        LshConfig {
+            method: LshMethod::MinHash,
+            bands: 20,
+            rows: 5,
+            shingle_size: 3,
+        }
+    }
+}
+
+impl LshConfig {
+    /// Read `blocking.lsh` from a parsed spec; `None` unless the blocking
+    /// strategy is `lsh`. Missing parameters take their defaults.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(blocking) = spec.get("blocking") else {
+            return Ok(None);
+        };
+        if blocking.get("strategy").and_then(Value::as_str) != Some("lsh") {
+            return Ok(None);
+        }
+        let section = blocking.get("lsh").filter(|s| !s.is_null());
+        if let Some((key, message)) = section.and_then(|s| check(s).into_iter().next()) {
+            bail!("Invalid blocking.lsh.{}: {}", key, message);
+        }
+        let mut config = LshConfig::default();
+        let Some(section) = section else {
+            return Ok(Some(config));
+        };
+        if let Some(method) = section.get("method").and_then(Value::as_str) {
+            config.method = method.parse()?;
+        }
+        let number = |key: &str| section.get(key).and_then(Value::as_u64).map(|n| n as usize);
+        config.bands = number("bands").unwrap_or(config.bands);
+        config.rows = number("rows").unwrap_or(config.rows);
+        config.shingle_size = number("shingle_size").unwrap_or(config.shingle_size);
+        Ok(Some(config))
+    }
+
+    /// Hashes per signature.
+    pub fn signature_len(&self) -> usize {
+        self.bands * self.rows
+    }
+
+    /// Probability that a pair with `similarity` (Jaccard for MinHash,
+    /// cosine for SimHash) shares at least one band: 1 - (1 - p^rows)^bands,
+    /// with p the chance that one signature hash agrees.
+    pub fn collision_probability(&self, similarity: f64) -> f64 {
+        let p = self.hash_agreement(similarity.clamp(0.0, 1.0));
+        1.0 - (1.0 - p.powi(self.rows as i32)).powi(self.bands as i32)
+    }
+
+    /// The similarity around which pairs go from rarely to usually
+    /// colliding, (1/bands)^(1/rows) as a hash agreement.
+    pub fn threshold(&self) -> f64 {
+        let p = (1.0 / self.bands as f64).powf(1.0 / self.rows as f64);
+        match self.method {
+            LshMethod::MinHash => p,
+            LshMethod::SimHash => (std::f64::consts::PI * (1.0 - p)).cos().max(0.0),
+        }
+    }
+
+    fn hash_agreement(&self, similarity: f64) -> f64 {
+        match self.method {
+            LshMethod::MinHash => similarity,
+            // Random hyperplanes split two vectors with probability θ/π.
+            LshMethod::SimHash => 1.0 - similarity.acos() / std::f64::consts::PI,
+        }
+    }
+
+    /// The band keys of `value`, `<band>:<hash>`; none for a blank value.
+    pub fn band_keys(&self, value: &str) -> Vec<String> {
+        let shingles = shingles(value, self.shingle_size);
+        if shingles.is_empty() {
+            return Vec::new();
+        }
+        let signature = match self.method {
+            LshMethod::MinHash => minhash(&shingles, self.signature_len()),
+            LshMethod::SimHash => {
+                let fingerprint = simhash(&shingles);
+                (0..self.signature_len())
+                    .map(|bit| (fingerprint >> bit) & 1)
+                    .collect()
+            }
+        };
+        signature
+            .chunks(self.rows)
+            .enumerate()
+            .map(|(band, rows)| {
+                let hash = rows
+                    .iter()
+                    .fold(FNV_OFFSET, |h, &row| fnv1a(h, &row.to_le_bytes()));
+                format!("{}:{:016x}", band, hash)
+            })
+            .collect()
+    }
+}
+
+/// Problems with a `blocking.lsh` section, as (parameter, message).
+pub fn check(section: &Value) -> Vec<(&'static str, String)> {
+    let mut problems = Vec::new();
+    let method = match section.get("method") {
+        None => Some(LshMethod::default()),
+        Some(value) => match value.as_str().map(str::parse::<LshMethod>) {
+            Some(Ok(method)) => Some(method),
+            _ => {
+                problems.push((
+                    "method",
+                    format!("expected minhash or simhash, got {}", value),
+                ));
+                None
+            }
+        },
+    };
+    let defaults = LshConfig::default();
+    let mut number = |key: &'static str, default: usize, max: usize| match section.get(key) {
+        None => Some(default),
+        Some(value) => match value.as_u64().map(|n| n as usize) {
+            Some(n) if (1..=max).contains(&n) => Some(n),
+            _ => {
+                problems.push((
+                    key,
+                    format!("expected a whole number from 1 to {}, got {}", max, value),
+                ));
+                None
+            }
+        },
+    };
+    let bands = number("bands", defaults.bands, MAX_MINHASH_SIGNATURE);
+    let rows = number("rows", defaults.rows, MAX_MINHASH_SIGNATURE);
+    number("shingle_size", defaults.shingle_size, MAX_SHINGLE_SIZE);
+    if let (Some(method), Some(bands), Some(rows)) = (method, bands, rows) {
+        if bands * rows > method.max_signature() {
+            problems.push((
+                "bands",
+                format!(
+                    "{} bands of {} rows need {} {} hashes; at most {} fit",
+                    bands,
+                    rows,
+                    bands * rows,
+                    method,
+                    method.max_signature()
+                ),
+            ));
+        }
+    }
+    problems
+}
+
+// ── Signatures ─────────────────────────────────────────────────────
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Hashes of the distinct `size`-character shingles of `value`, lowercased
+/// with whitespace collapsed; a value shorter than `size` is one shingle.
+pub fn shingles(value: &str, size: usize) -> Vec<u64> {
+    let chars: Vec<char> = value
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect();
+    if chars.is_empty() {
+        return Vec::new();
+    }
+    let size = size.max(1);
+    let mut hashes: Vec<u64> = chars
+        .windows(size.min(chars.len()))
+        .map(|window| {
+            let shingle: String = window.iter().collect();
+            fnv1a(FNV_OFFSET, shingle.as_bytes())
+        })
+        .collect();
+    hashes.sort_unstable();
+    hashes.dedup();
+    hashes
+}
+
+/// The minimum of each of `len` independent hashes over the shingles.
+fn minhash(shingles: &[u64], len: usize) -> Vec<u64> {
+    (0..len as u64)
+        .map(|seed| {
+            let salt = splitmix64(seed);
+            shingles
+                .iter()
+                .map(|&s| splitmix64(s ^ salt))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+/// 64-bit SimHash: bit i is set when most shingle hashes set it.
+fn simhash(shingles: &[u64]) -> u64 {
+    let mut weights = [0i64; 64];
+    for &shingle in shingles {
+        let hash = splitmix64(shingle);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if (hash >> bit) & 1 == 1 { 1 } else { -1 };
+        }
+    }
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, &w)| w > 0)
+        .fold(0u64, |fingerprint, (bit, _)| fingerprint | (1 << bit))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnose_yaml;
+
+    #[test]
+    fn test_lsh_blocking_strategy() {
+        use crate::{generate_plan, generate_plan_with, LshConfig, LshMethod, PlanOptions, Sample};
+
+        let spec = r#"
+api_version: kanoniv/v2
+identity_version: company_v1
+entity:
+  name: company
+sources:
+  - name: crm
+    system: salesforce
+    table: accounts
+    id: account_id
+    attributes:
+      company_name: name
+rules:
+  - name: company_fuzzy
+    type: fuzzy
+    field: company_name
+    algorithm: trigram
+    threshold: 0.8
+    weight: 1.0
+blocking:
+  strategy: lsh
+  lsh:
+    method: minhash
+    bands: 20
+    rows: 5
+    shingle_size: 3
+  keys:
+    - field: company_name
+decision:
+  thresholds:
+    match: 0.9
+"#;
+        assert!(crate::validate_yaml(spec).unwrap().is_empty());
+        let lsh_errors = |yaml: &str| -> Vec<String> {
+            diagnose_yaml(yaml)
+                .into_iter()
+                .filter(|d| d.code == "KNV0114")
+                .map(|d| d.path.unwrap())
+                .collect()
+        };
+        let broken = spec
+            .replace("method: minhash", "method: simhash")
+            .replace("rows: 5", "rows: 0");
+        assert_eq!(lsh_errors(&broken), ["blocking.lsh.rows"]);
+        // 20 x 5 bits do not fit one 64-bit SimHash fingerprint.
+        let too_long = spec.replace("method: minhash", "method: simhash");
+        assert_eq!(lsh_errors(&too_long), ["blocking.lsh.bands"]);
+        assert_eq!(
+            lsh_errors(&spec.replace("strategy: lsh", "strategy: composite")),
+            ["blocking.lsh"]
+        );
+
+        // (1/20)^(1/5): pairs start colliding around similarity 0.55.
+        let config = LshConfig::default();
+        assert_eq!(config.method, LshMethod::MinHash);
+        assert!((config.threshold() - 0.549).abs() < 1e-3);
+        assert!(config.collision_probability(0.9) > 0.99);
+        assert!(config.collision_probability(0.3) < 0.05);
+        let keys = config.band_keys("Acme Corporation");
+        assert_eq!(keys.len(), 20);
+        assert_eq!(keys, config.band_keys("  acme   CORPORATION "));
+        assert!(config.band_keys("").is_empty());
+
+        let plan = generate_plan(spec).unwrap();
+        let lsh = plan.blocking_analysis.lsh.as_ref().unwrap();
+        assert_eq!(lsh.threshold, 0.549);
+        assert_eq!(plan.blocking_analysis.estimated_reduction, "medium");
+        assert!(plan.execution_stages[1]
+            .description
+            .contains("20 bands x 5 rows"));
+
+        // Near-duplicate names share bands; different companies do not.
+        let mut csv = String::from("name\n");
+        for name in [
+            "Acme Corporation",
+            "Acme Corporation Inc",
+            "Globex",
+            "Initech",
+            "Umbrella",
+        ] {
+            csv.push_str(name);
+            csv.push('\n');
+        }
+        let options = PlanOptions {
+            sample: Some(Sample::from_csv(&csv).unwrap()),
+            ..PlanOptions::default()
+        };
+        let sampled = generate_plan_with(spec, &options).unwrap();
+        let sample = sampled.blocking_analysis.sample.as_ref().unwrap();
+        assert_eq!((sample.total_pairs, sample.candidate_pairs), (10, 1));
+    }
+}
@@ -2,9 +2,11 @@ use clap::{Parser, Subcommand};
 use colored::Colorize;
 use std::path::PathBuf;
 
-mod commands;
-mod parser;
-mod validator;
+use kanoniv_core::commands;
+use kanoniv_core::estimate;
+use kanoniv_core::interpolate::Variables;
+use kanoniv_core::output::Output;
+use kanoniv_core::{BatchOptions, Cache, CancellationToken, CustomRisks, ExecutionBackend, GenerateOptions, KeySource, OpaPolicies, PluginRegistry, Policy, ReviewStatus, RulePack, Sample};
 
 #[derive(Parser)]
 #[command(name = "kanoniv")]
@@ -14,19 +16,137 @@ mod validator;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Only print results and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print additional detail
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Plain output: no colors or Unicode symbols
+    #[arg(long, global = true)]
+    plain: bool,
+
+    /// Disable colored output (also honors NO_COLOR)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Set a spec parameter, for `{{ params.NAME }}`; repeatable
+    #[arg(long = "param", value_name = "NAME=VALUE", global = true)]
+    params: Vec<String>,
+
+    /// Read `${VAR}` values missing from the environment from a dotenv file
+    #[arg(long, value_name = "FILE", global = true)]
+    env_file: Option<PathBuf>,
+
+    /// Entity to work on in a file or directory of several specs (for
+    /// `init`, the new spec's entity, e.g. customer)
+    #[arg(long = "entity", value_name = "ENTITY", global = true)]
+    select_entity: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Write a starter specification, asking for what the flags leave out
+    Init {
+        /// Output file path
+        #[arg(short, long, default_value = "kanoniv.yml")]
+        output: PathBuf,
+
+        /// Start from a built-in template (see `kanoniv templates list`)
+        #[arg(long, value_name = "NAME", conflicts_with = "fields")]
+        template: Option<String>,
+
+        /// Source as NAME or NAME:SYSTEM; repeatable
+        #[arg(long = "source", value_name = "SOURCE")]
+        sources: Vec<String>,
+
+        /// Attribute to match records on; repeatable
+        #[arg(long = "field", value_name = "FIELD")]
+        fields: Vec<String>,
+
+        /// Overwrite the output file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+
     /// Validate a Kanoniv identity specification
     Validate {
         /// Path to the YAML file
-        #[arg(value_name = "FILE")]
-        file: PathBuf,
+        #[arg(value_name = "FILE", required_unless_present = "from_ir")]
+        file: Option<PathBuf>,
+
+        /// Validate a compiled IR file (`kanoniv compile` output) instead
+        #[arg(long, value_name = "IR", conflicts_with = "file")]
+        from_ir: Option<PathBuf>,
+
+        /// Output format (text, json, sarif, junit, github)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json", "sarif", "junit", "github"])]
+        format: String,
+
+        /// Finding severity profile (default, strict: warnings fail, lenient: warnings become info)
+        #[arg(long, default_value = "default")]
+        profile: String,
+
+        /// Organization-defined checks to run (a rule pack YAML file or a directory of them)
+        #[arg(long, value_name = "RULES")]
+        rules: Option<PathBuf>,
+
+        /// Rego policies to evaluate with OPA (a .rego file or a directory of them)
+        #[arg(long, value_name = "POLICIES")]
+        policy: Option<PathBuf>,
+
+        /// Validate every spec, neither reading nor writing the results cache
+        #[arg(long)]
+        no_cache: bool,
+    },
+
+    /// Validate specs again each time they change, reporting new and resolved findings
+    Watch {
+        /// Directory of specs (or one spec file) to watch
+        #[arg(value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+
+        /// Also plan each valid spec, reporting plan hash and risk flag changes
+        #[arg(long)]
+        plan: bool,
+
+        /// Finding severity profile (default, strict: warnings fail, lenient: warnings become info)
+        #[arg(long, default_value = "default")]
+        profile: String,
+
+        /// Seconds between checks for changed files
+        #[arg(long, value_name = "SECS", default_value = "0.5")]
+        interval: f64,
+
+        /// Seconds files must be unchanged before specs are checked again
+        #[arg(long, value_name = "SECS", default_value = "0.3")]
+        debounce: f64,
+    },
+
+    /// Validate every spec under a directory and check them against one another
+    Check {
+        /// Directory to search for spec files
+        #[arg(value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
 
         /// Output format (text, json)
-        #[arg(short, long, default_value = "text")]
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
         format: String,
+
+        /// Finding severity profile (default, strict: warnings fail, lenient: warnings become info)
+        #[arg(long, default_value = "default")]
+        profile: String,
+
+        /// Specs to validate at once (default: one per core)
+        #[arg(short, long, value_name = "N")]
+        jobs: Option<usize>,
+
+        /// Validate every spec, neither reading nor writing the results cache
+        #[arg(long)]
+        no_cache: bool,
     },
 
     /// Compile a specification to intermediate representation
@@ -35,7 +155,30 @@ enum Commands {
         #[arg(value_name = "FILE")]
         file: PathBuf,
 
-        /// Output file path
+        /// Output file path (directory for `--target dbt`)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Compile target (ir, sql, dbt, pyspark, kafka)
+        #[arg(short, long, default_value = "ir")]
+        target: String,
+
+        /// SQL dialect for `sql` and `dbt` targets (ansi, postgres, snowflake, bigquery)
+        #[arg(long, default_value = "ansi")]
+        dialect: String,
+
+        /// Encoding of `ir` output (json, cbor, cbor-base64)
+        #[arg(long, default_value = "json", value_parser = ["json", "cbor", "cbor-base64"])]
+        format: String,
+    },
+
+    /// Reconstruct a specification from compiled IR
+    Decompile {
+        /// Compiled IR file (`kanoniv compile` output)
+        #[arg(value_name = "IR")]
+        file: PathBuf,
+
+        /// Write the spec here (stdout if omitted)
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
@@ -45,6 +188,106 @@ enum Commands {
         /// Path to the YAML file
         #[arg(value_name = "FILE")]
         file: PathBuf,
+
+        /// Hash algorithm (sha256, sha512, blake3)
+        #[arg(long, default_value = "sha256")]
+        algorithm: String,
+
+        /// Environment variable holding a key; the hash becomes keyed (HMAC)
+        #[arg(long, value_name = "VAR")]
+        key_env: Option<String>,
+
+        /// File holding a key; the hash becomes keyed (HMAC)
+        #[arg(long, value_name = "PATH", conflicts_with = "key_env")]
+        key_file: Option<PathBuf>,
+    },
+
+    /// Sign a specification with an Ed25519 key
+    Sign {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE", required_unless_present = "generate_key")]
+        file: Option<PathBuf>,
+
+        /// Environment variable holding the signing key (hex)
+        #[arg(long, value_name = "VAR")]
+        key_env: Option<String>,
+
+        /// File holding the signing key (hex)
+        #[arg(long, value_name = "PATH", conflicts_with = "key_env")]
+        key_file: Option<PathBuf>,
+
+        /// Write a new signing key here, and its public key beside it (.pub), then sign with it
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["key_env", "key_file"])]
+        generate_key: Option<PathBuf>,
+
+        /// Overwrite the key files --generate-key writes if they exist
+        #[arg(long, requires = "generate_key")]
+        force: bool,
+
+        /// Add the signature to the spec's `signatures` section instead of FILE.sig
+        #[arg(long)]
+        embed: bool,
+    },
+
+    /// Verify that a trusted key signed a specification as it is
+    Verify {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Trusted public key file (hex); repeatable
+        #[arg(long = "key", value_name = "PATH", required = true)]
+        keys: Vec<PathBuf>,
+
+        /// Detached signature file (default: FILE.sig, if present)
+        #[arg(long, value_name = "PATH")]
+        signatures: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Tokenize record identifiers with the spec's identifier hashing, or encode attribute values
+    Tokenize {
+        /// Identifiers (values with --field) to tokenize, read one per line from stdin if omitted
+        #[arg(value_name = "ID")]
+        ids: Vec<String>,
+
+        /// Spec whose `hashing` section sets the algorithm and key
+        #[arg(long, value_name = "FILE")]
+        spec: Option<PathBuf>,
+
+        /// Encode values of this attribute as the spec's `encoding` section says
+        #[arg(long, value_name = "ATTRIBUTE", requires = "spec")]
+        field: Option<String>,
+
+        /// Hash algorithm (sha256, sha512, blake3); overrides the spec
+        #[arg(long)]
+        algorithm: Option<String>,
+
+        /// Environment variable holding the key (the salt with --field); overrides the spec
+        #[arg(long, value_name = "VAR")]
+        key_env: Option<String>,
+
+        /// File holding the key (the salt with --field); overrides the spec
+        #[arg(long, value_name = "PATH", conflicts_with = "key_env")]
+        key_file: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Rewrite specifications in canonical form
+    Fmt {
+        /// Paths to the YAML files
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+
+        /// Report files that are not formatted instead of rewriting them
+        #[arg(long)]
+        check: bool,
     },
 
     /// Compare two specification versions
@@ -56,30 +299,1312 @@ enum Commands {
         /// Second version
         #[arg(value_name = "FILE2")]
         file2: PathBuf,
+
+        /// Write changes grouped by owner to this JSON file
+        #[arg(long, value_name = "FILE")]
+        routing: Option<PathBuf>,
+
+        /// Exit non-zero if any change is at least this severe (safe, risky, breaking)
+        #[arg(long, value_name = "IMPACT")]
+        fail_on: Option<String>,
+    },
+    /// Three-way merge of two edits to a specification
+    Merge {
+        /// Common ancestor
+        #[arg(value_name = "BASE")]
+        base: PathBuf,
+
+        /// Our version
+        #[arg(value_name = "OURS")]
+        ours: PathBuf,
+
+        /// Their version
+        #[arg(value_name = "THEIRS")]
+        theirs: PathBuf,
+
+        /// Write the merged spec to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
     },
     /// Generate an execution plan for a specification
     Plan {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE", required_unless_present = "from_ir")]
+        file: Option<PathBuf>,
+
+        /// Plan from a compiled IR file (`kanoniv compile` output) instead
+        #[arg(long, value_name = "IR", conflicts_with = "file")]
+        from_ir: Option<PathBuf>,
+
+        /// Abort planning after this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<f64>,
+
+        /// Extra risk checks to evaluate (YAML rules file)
+        #[arg(long, value_name = "RULES")]
+        custom_risks: Option<PathBuf>,
+
+        /// Write risk flags grouped by owner to this JSON file
+        #[arg(long, value_name = "FILE")]
+        routing: Option<PathBuf>,
+
+        /// Measure blocking on sample records (CSV with a header row, Parquet or Arrow IPC)
+        #[arg(long, value_name = "FILE")]
+        sample: Option<PathBuf>,
+
+        /// Rows in a source, for cost estimates, as SOURCE=ROWS (e.g. crm=2.5e6); repeatable
+        #[arg(long = "rows", value_name = "SOURCE=ROWS")]
+        rows: Vec<String>,
+
+        /// Flag the plan if more candidate pairs than this are estimated (default 1e9)
+        #[arg(long, value_name = "PAIRS")]
+        pair_budget: Option<f64>,
+
+        /// Output format (text, sarif, junit, github, or dot or mermaid for the execution DAG)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "sarif", "junit", "github", "dot", "mermaid"])]
+        format: String,
+
+        /// Exit non-zero if any risk flag is at least this severe (critical, high, medium, low)
+        #[arg(long, value_name = "SEVERITY")]
+        fail_on: Option<String>,
+
+        /// Plan every spec, neither reading nor writing the results cache
+        #[arg(long)]
+        no_cache: bool,
+    },
+
+    /// Profile source data against the attributes a spec maps
+    Profile {
+        /// Spec declaring the source
+        #[arg(long, value_name = "FILE")]
+        spec: PathBuf,
+
+        /// Source records (CSV with a header row, Parquet or Arrow IPC)
+        #[arg(long, value_name = "FILE")]
+        data: PathBuf,
+
+        /// Source the data belongs to (default: inferred from the columns)
+        #[arg(long, value_name = "NAME")]
+        source: Option<String>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Tune match rules and decision thresholds on labeled pairs
+    Calibrate {
         /// Path to the YAML file
         #[arg(value_name = "FILE")]
         file: PathBuf,
+
+        /// Labeled pairs (CSV: label, left_<field>, right_<field>)
+        #[arg(long, value_name = "CSV")]
+        labels: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// What-if analysis of a spec on sample data
+    Analyze {
+        #[command(subcommand)]
+        action: AnalyzeAction,
+    },
+
+    /// Learn match rule weights from unlabeled records (Fellegi-Sunter EM)
+    LearnWeights {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Records to learn from (CSV with a header row, Parquet or Arrow IPC)
+        #[arg(long, value_name = "FILE")]
+        data: PathBuf,
+
+        /// Write the spec with the learned weights here (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Abort after this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<f64>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Group scored pairs into entities with the spec's clustering strategy
+    Cluster {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Scored pairs (CSV, Parquet or Arrow IPC: left_key, right_key, score, decision)
+        #[arg(long, value_name = "FILE")]
+        decisions: PathBuf,
+
+        /// Write the cluster assignments here as CSV (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Write the audit trail here as JSON Lines
+        #[arg(long, value_name = "FILE")]
+        audit: Option<PathBuf>,
+
+        /// Queue pairs in the review band here and cluster with the verdicts reached (JSON Lines, or SQLite by a .db extension)
+        #[arg(long, value_name = "QUEUE")]
+        review_queue: Option<String>,
+
+        /// Records to queue with the pairs (CSV, Parquet or Arrow IPC with a record_key column)
+        #[arg(long, value_name = "FILE", requires = "review_queue")]
+        records: Option<PathBuf>,
+
+        /// Stewardship assertions to honor (default: <FILE>.stewardship.yaml, if present)
+        #[arg(long, value_name = "FILE")]
+        stewardship: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Work through the pairs `cluster --review-queue` sends to review
+    Review {
+        #[command(subcommand)]
+        action: ReviewAction,
+    },
+
+    /// Build golden records from cluster members with the spec's survivorship rules
+    GoldenRecords {
+        /// Spec with the survivorship rules
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Cluster members (CSV, Parquet or Arrow IPC: cluster_id, source_name, record_key, attributes)
+        #[arg(long, value_name = "FILE")]
+        members: PathBuf,
+
+        /// Expire retained values as of this date, YYYY-MM-DD (default: today)
+        #[arg(long, value_name = "DATE")]
+        as_of: Option<String>,
+
+        /// Write the golden records here as CSV (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Write the audit trail here as JSON Lines
+        #[arg(long, value_name = "FILE")]
+        audit: Option<PathBuf>,
+
+        /// Write each golden field's lineage here (JSON, or Parquet by a .parquet extension)
+        #[arg(long, value_name = "FILE")]
+        lineage: Option<PathBuf>,
+
+        /// Stewardship assertions to honor (default: <FILE>.stewardship.yaml, if present)
+        #[arg(long, value_name = "FILE")]
+        stewardship: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Expire cluster members' values past the spec's retention periods
+    Maintain {
+        /// Spec with the privacy section
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Cluster members (CSV, Parquet or Arrow IPC: cluster_id, source_name, record_key, attributes)
+        #[arg(long, value_name = "FILE")]
+        members: PathBuf,
+
+        /// Expire as of this date, YYYY-MM-DD (default: today)
+        #[arg(long, value_name = "DATE")]
+        as_of: Option<String>,
+
+        /// Write members.csv, golden.csv, lineage.json and retention_audit.json into this directory
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+
+        /// Stewardship assertions to honor (default: <FILE>.stewardship.yaml, if present)
+        #[arg(long, value_name = "FILE")]
+        stewardship: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Run a plan over JSON, CSV, Parquet or Arrow IPC records
+    #[command(visible_alias = "run")]
+    Execute {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE", required_unless_present = "from_ir")]
+        file: Option<PathBuf>,
+
+        /// Run a compiled IR file (`kanoniv compile` output) instead
+        #[arg(long, value_name = "IR", conflicts_with = "file")]
+        from_ir: Option<PathBuf>,
+
+        /// Records by source (JSON: {"<source>": [{<column>: <value>, ...}, ...]}), or stacked with a source_name column (CSV, Parquet or Arrow IPC)
+        #[arg(long, value_name = "FILE")]
+        records: PathBuf,
+
+        /// Write the canonical entities here as CSV (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Expire retained values as of this date, YYYY-MM-DD (default: today)
+        #[arg(long, value_name = "DATE")]
+        as_of: Option<String>,
+
+        /// Where to run the stages: memory (the reference interpreter) or duckdb (an embedded database, for large or wide inputs)
+        #[arg(long, default_value = "memory", conflicts_with = "distributed")]
+        backend: String,
+
+        /// Coordinate a distributed run: listen here (HOST:PORT) for workers to block, score and cluster the records
+        #[arg(long, value_name = "ADDR", requires_all = ["tls_cert", "tls_key"])]
+        distributed: Option<String>,
+
+        /// PEM certificate chain a distributed run's coordinator serves TLS with
+        #[arg(long, value_name = "FILE", requires = "distributed")]
+        tls_cert: Option<PathBuf>,
+
+        /// PEM private key for --tls-cert
+        #[arg(long, value_name = "FILE", requires = "distributed")]
+        tls_key: Option<PathBuf>,
+
+        /// Partitions of the records, split on the blocking, that workers claim one at a time
+        #[arg(long, value_name = "N", default_value_t = 16, requires = "distributed")]
+        partitions: usize,
+
+        /// Secret shared with the workers of a distributed run
+        #[arg(long, env = "KANONIV_WORKER_SECRET", hide_env_values = true)]
+        secret: Option<String>,
+
+        /// Abort after this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<f64>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Block, score and cluster records for a distributed run (`execute --distributed`)
+    Worker {
+        /// The coordinator's address (HOST:PORT)
+        #[arg(value_name = "ADDR")]
+        coordinator: String,
+
+        /// Keep trying to reach the coordinator for this many seconds
+        #[arg(long, value_name = "SECONDS", default_value_t = 30.0)]
+        wait: f64,
+
+        /// Secret shared with the coordinator
+        #[arg(long, env = "KANONIV_WORKER_SECRET", hide_env_values = true)]
+        secret: String,
+
+        /// PEM certificate to check the coordinator's against (its own, or the CA that signed it)
+        #[arg(long, value_name = "FILE")]
+        tls_ca: PathBuf,
+
+        /// Abort after this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<f64>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Run a spec's tests (YAML files in the `tests` directory beside it) with the reference interpreter
+    Test {
+        /// Spec file, or a directory to test every spec under
+        #[arg(value_name = "PATH", default_value = ".")]
+        path: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Make up records for a spec's sources, with duplicates and their true clusters
+    GenerateData {
+        /// Path to the YAML file
+        #[arg(long, value_name = "FILE")]
+        spec: PathBuf,
+
+        /// Records to make, duplicates included
+        #[arg(long, default_value = "1000")]
+        rows: usize,
+
+        /// Share of the records that duplicate another, perturbed (0 to below 1)
+        #[arg(long, default_value = "0.2")]
+        duplicate_rate: f64,
+
+        /// Seed; the same seed makes the same records
+        #[arg(long, default_value = "0")]
+        seed: u64,
+
+        /// Directory to write the CSV per source, records.json and labels.csv to
+        #[arg(short, long, default_value = "synthetic")]
+        output: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Score a spec's clusters against ground truth (precision, recall, ARI, B-cubed, per rule)
+    Evaluate {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// True cluster of each record (CSV: record_key, cluster_id), as generate-data writes
+        #[arg(long, value_name = "CSV")]
+        truth: PathBuf,
+
+        /// Records by source (JSON, as for execute), or stacked with a source_name column (CSV, Parquet or Arrow IPC)
+        #[arg(long, value_name = "FILE")]
+        data: PathBuf,
+
+        /// Abort after this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<f64>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Run a spec over benchmark datasets and report precision, recall and F1
+    BenchSuite {
+        /// Datasets: built-in names (restaurants, census) or directories holding spec.yaml, records and truth.csv (default: every built-in one)
+        #[arg(value_name = "DATASET")]
+        datasets: Vec<String>,
+
+        /// Spec to run on every dataset instead of the dataset's own
+        #[arg(long, value_name = "FILE")]
+        spec: Option<PathBuf>,
+
+        /// Records to generate for the census dataset, duplicates included
+        #[arg(long, default_value = "1000")]
+        rows: usize,
+
+        /// Share of the generated records that duplicate another (0 to below 1)
+        #[arg(long, default_value = "0.2")]
+        duplicate_rate: f64,
+
+        /// Seed for the generated records
+        #[arg(long, default_value = "0")]
+        seed: u64,
+
+        /// Results of an earlier run (bench-suite --format json) to compare against
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+
+        /// How far precision, recall or F1 may fall below the baseline before the run fails
+        #[arg(long, default_value = "0.01")]
+        tolerance: f64,
+
+        /// Abort after this many seconds
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<f64>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Check every spec's hash, IR and plan against its snapshot in snapshots/
+    Snapshot {
+        /// Spec file, or a directory to snapshot every spec under
+        #[arg(value_name = "PATH", default_value = ".")]
+        path: PathBuf,
+
+        /// Snapshots directory (default: snapshots/ in PATH, or beside a spec file)
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+
+        /// Write new and changed snapshots and remove obsolete ones
+        #[arg(long)]
+        update: bool,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Golden fields a spec's survivorship rules would change, without re-matching
+    SurvivorshipImpact {
+        /// Spec with the new survivorship rules
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Current cluster members (CSV: cluster_id, source_name, record_key, attributes)
+        #[arg(long, value_name = "CSV")]
+        members: PathBuf,
+
+        /// Current canonical table (CSV: entity_id, attributes)
+        #[arg(long, value_name = "CSV")]
+        canonical: PathBuf,
+
+        /// Changes to list in text output
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Steps to migrate a deployment from one spec version to another
+    MigratePlan {
+        /// Deployed version
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+
+        /// Version to deploy
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Render Markdown documentation of a spec
+    Docs {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Write the documentation to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Write a self-contained HTML report of a spec's validation, plan and changes
+    Report {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Write the report to this file
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Show changes since this version of the spec (default: its last committed change)
+        #[arg(long, value_name = "FILE")]
+        previous: Option<PathBuf>,
+
+        /// Extra risk checks to evaluate (YAML rules file)
+        #[arg(long, value_name = "RULES")]
+        custom_risks: Option<PathBuf>,
+
+        /// Measure blocking on sample records (CSV with a header row, Parquet or Arrow IPC)
+        #[arg(long, value_name = "FILE")]
+        sample: Option<PathBuf>,
+    },
+
+    /// Tabulate a spec's risk score across its git history
+    RiskTrend {
+        /// Path to the YAML file (must be tracked by git)
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Only the most recent N versions
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Publish a spec version to a registry
+    Publish {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Registry location (directory, s3://bucket/prefix or http(s):// URL)
+        #[arg(long, env = "KANONIV_REGISTRY")]
+        registry: String,
+
+        /// Point a tag (e.g. production) at this version; repeatable
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+
+    /// Fetch a spec version from a registry
+    Pull {
+        /// Entity name
+        #[arg(value_name = "ENTITY")]
+        entity: String,
+
+        /// Tag, hash, hash prefix or identity_version (default: latest)
+        #[arg(value_name = "VERSION")]
+        version: Option<String>,
+
+        /// Registry location (directory, s3://bucket/prefix or http(s):// URL)
+        #[arg(long, env = "KANONIV_REGISTRY")]
+        registry: String,
+
+        /// Output file path (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Flatten a spec that extends a base spec into one spec
+    #[command(visible_alias = "resolve")]
+    Compose {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Registry that `registry://` references resolve against
+        #[arg(long, env = "KANONIV_REGISTRY")]
+        registry: Option<String>,
+
+        /// Show what the spec changes in its base instead
+        #[arg(long)]
+        diff: bool,
+
+        /// Output file path (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// List the versions of an entity's spec in a registry
+    Versions {
+        /// Entity name
+        #[arg(value_name = "ENTITY")]
+        entity: String,
+
+        /// Registry location (directory, s3://bucket/prefix or http(s):// URL)
+        #[arg(long, env = "KANONIV_REGISTRY")]
+        registry: String,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Work with the spec format's schema
+    Schema {
+        #[command(subcommand)]
+        action: SchemaAction,
+    },
+
+    /// Work with the audit trail's record format
+    AuditSchema {
+        #[command(subcommand)]
+        action: AuditSchemaAction,
+    },
+
+    /// Work with the compiled IR's format
+    Ir {
+        #[command(subcommand)]
+        action: IrAction,
+    },
+
+    /// Work with the built-in spec templates
+    Templates {
+        #[command(subcommand)]
+        action: TemplatesAction,
+    },
+
+    /// Break down how a spec decides one pair of records
+    ExplainPair {
+        /// Path to the YAML file
+        #[arg(long = "spec", value_name = "FILE")]
+        file: PathBuf,
+
+        /// First record (a JSON object keyed by attribute or source column)
+        #[arg(long, value_name = "JSON")]
+        a: PathBuf,
+
+        /// Second record
+        #[arg(long, value_name = "JSON")]
+        b: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Explain a diagnostic code (lists all codes when none is given)
+    Explain {
+        /// Code such as KNV0101, or its name (unknown-field)
+        #[arg(value_name = "CODE")]
+        code: Option<String>,
+    },
+
+    /// Check hash, IR and plan output against a conformance corpus
+    Conformance {
+        /// Corpus directory (one sub-directory per case)
+        #[arg(value_name = "CORPUS")]
+        corpus: PathBuf,
+
+        /// Rewrite each case's expected.json from the current outputs
+        #[arg(long)]
+        update: bool,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// List the plugins found (kanoniv-plugin-* executables in KANONIV_PLUGIN_PATH or PATH)
+    #[command(long_flag = "list-plugins")]
+    Plugins,
+}
+
+#[derive(Subcommand)]
+enum AnalyzeAction {
+    /// Merge, review and reject volumes across decision thresholds
+    Thresholds {
+        /// Path to the YAML file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Scored pairs (CSV with a score column) or records to pair and score
+        #[arg(long, value_name = "FILE")]
+        data: PathBuf,
+
+        /// Match threshold to compare with the spec's
+        #[arg(long = "match", value_name = "SCORE")]
+        match_threshold: Option<f64>,
+
+        /// Review threshold to compare with the spec's
+        #[arg(long, value_name = "SCORE")]
+        review: Option<f64>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReviewAction {
+    /// List the queued pairs
+    List {
+        /// Review queue (JSON Lines, or SQLite by a .db extension)
+        #[arg(long, value_name = "QUEUE", env = "KANONIV_REVIEW_QUEUE")]
+        queue: String,
+
+        /// Only pairs with this status (pending, approved, rejected)
+        #[arg(long)]
+        status: Option<String>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Show a pair, its records and verdict, and how the spec decides it
+    Inspect {
+        /// Pair id (a unique prefix will do)
+        #[arg(value_name = "ID")]
+        id: String,
+
+        /// Review queue (JSON Lines, or SQLite by a .db extension)
+        #[arg(long, value_name = "QUEUE", env = "KANONIV_REVIEW_QUEUE")]
+        queue: String,
+
+        /// Break the pair down with this spec, as explain-pair does
+        #[arg(long = "spec", value_name = "FILE")]
+        file: Option<PathBuf>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Approve a pair: its records merge
+    Approve {
+        /// Pair id (a unique prefix will do)
+        #[arg(value_name = "ID")]
+        id: String,
+
+        /// Review queue (JSON Lines, or SQLite by a .db extension)
+        #[arg(long, value_name = "QUEUE", env = "KANONIV_REVIEW_QUEUE")]
+        queue: String,
+
+        /// Who decided
+        #[arg(long)]
+        reviewer: Option<String>,
+
+        /// Why
+        #[arg(long)]
+        note: Option<String>,
+    },
+
+    /// Reject a pair: its records stay apart
+    Reject {
+        /// Pair id (a unique prefix will do)
+        #[arg(value_name = "ID")]
+        id: String,
+
+        /// Review queue (JSON Lines, or SQLite by a .db extension)
+        #[arg(long, value_name = "QUEUE", env = "KANONIV_REVIEW_QUEUE")]
+        queue: String,
+
+        /// Who decided
+        #[arg(long)]
+        reviewer: Option<String>,
+
+        /// Why
+        #[arg(long)]
+        note: Option<String>,
+    },
+
+    /// Print the merge/unmerge decision log
+    Log {
+        /// Review queue (JSON Lines, or SQLite by a .db extension)
+        #[arg(long, value_name = "QUEUE", env = "KANONIV_REVIEW_QUEUE")]
+        queue: String,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+
+    /// Write the decided pairs as labeled pairs for calibrate
+    Labels {
+        /// Review queue (JSON Lines, or SQLite by a .db extension)
+        #[arg(long, value_name = "QUEUE", env = "KANONIV_REVIEW_QUEUE")]
+        queue: String,
+
+        /// Path to the YAML file
+        #[arg(long = "spec", value_name = "FILE")]
+        file: PathBuf,
+
+        /// Write the labels here as CSV (stdout if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SchemaAction {
+    /// Print the spec schema for editors and other toolchains
+    Export {
+        /// Schema format (json-schema)
+        #[arg(short, long, default_value = "json-schema", value_parser = ["json-schema"])]
+        format: String,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditSchemaAction {
+    /// Print the schema of audit records for downstream consumers
+    Export {
+        /// Schema format (json-schema)
+        #[arg(short, long, default_value = "json-schema", value_parser = ["json-schema"])]
+        format: String,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IrAction {
+    /// Print the IR schema for engines that consume it
+    Schema {
+        /// Schema format (json-schema)
+        #[arg(short, long, default_value = "json-schema", value_parser = ["json-schema"])]
+        format: String,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check that engines reading one compiled IR can read another
+    Check {
+        /// IR the engine reads today
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+
+        /// IR to check against it
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
+    },
+    /// Print the digest of a compiled IR file, in any encoding
+    Digest {
+        /// Compiled IR file (JSON, CBOR or base64 CBOR)
+        #[arg(value_name = "IR")]
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplatesAction {
+    /// List the templates `kanoniv init --template` accepts
+    List {
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text", value_parser = ["text", "json"])]
+        format: String,
     },
 }
 
 fn main() {
     let cli = Cli::parse();
+    let out = Output::from_flags(cli.quiet, cli.verbose, cli.plain, cli.no_color);
+    out.apply();
 
-    let result = match cli.command {
-        Commands::Validate { file, format } => commands::validate::run(&file, &format),
-        Commands::Compile { file, output } => commands::compile::run(&file, output.as_deref()),
-        Commands::Hash { file } => commands::hash::run(&file),
-        Commands::Diff { file1, file2 } => commands::diff::run(&file1, &file2),
-        Commands::Plan { file } => commands::plan::run(&file),
-    };
+    let entity = cli.select_entity.as_deref();
+    let variables = Variables::from_flags(&cli.params, cli.env_file.as_deref());
+    let result = variables.and_then(|variables| match cli.command {
+        Commands::Init {
+            output,
+            template,
+            sources,
+            fields,
+            force,
+        } => commands::init::run(
+            &output,
+            template.as_deref(),
+            entity,
+            &sources,
+            &fields,
+            force,
+            &out,
+        ),
+        Commands::Validate {
+            file,
+            from_ir,
+            format,
+            profile,
+            rules,
+            policy,
+            no_cache,
+        } => profile.parse().and_then(|profile| {
+            let rules = rules.as_deref().map(RulePack::load).transpose()?.unwrap_or_default();
+            let plugins = PluginRegistry::discover()?;
+            let policies = policy.as_deref().map(OpaPolicies::load).transpose()?;
+            let cache = Cache::discover().filter(|_| !no_cache);
+            match (file, from_ir) {
+                (_, Some(ir)) => commands::validate::run_from_ir(&ir, &format, profile, &rules, &plugins, policies.as_ref(), &out),
+                (Some(file), None) => commands::validate::run(&file, entity, &variables, &format, profile, &rules, &plugins, policies.as_ref(), cache.as_ref(), &out),
+                (None, None) => unreachable!("clap requires FILE or --from-ir"),
+            }
+        }),
+        Commands::Watch {
+            dir,
+            plan,
+            profile,
+            interval,
+            debounce,
+        } => profile.parse().and_then(|profile| commands::watch::run(&dir, profile, plan, interval, debounce, entity, &variables, &out)),
+        Commands::Check {
+            dir,
+            format,
+            profile,
+            jobs,
+            no_cache,
+        } => profile.parse().and_then(|profile| {
+            let options = BatchOptions {
+                profile,
+                jobs,
+                cache: Cache::discover().filter(|_| !no_cache),
+                entity: entity.map(str::to_string),
+                variables,
+            };
+            commands::check::run(&dir, &format, &options, &out)
+        }),
+        Commands::Compile {
+            file,
+            output,
+            target,
+            dialect,
+            format,
+        } => PluginRegistry::discover().and_then(|plugins| commands::compile::run(&file, output.as_deref(), &target, &dialect, entity, &variables, &format, &plugins, &out)),
+        Commands::Decompile { file, output } => commands::decompile::run(&file, output.as_deref(), &out),
+        Commands::Hash {
+            file,
+            algorithm,
+            key_env,
+            key_file,
+        } => commands::hash::run(&file, &algorithm, &KeySource { key_env, key_file }, entity, &variables, &out),
+        Commands::Sign {
+            file,
+            key_env,
+            key_file,
+            generate_key,
+            force,
+            embed,
+        } => commands::sign::run(file.as_deref(), &KeySource { key_env, key_file }, generate_key.as_deref(), force, embed, entity, &out),
+        Commands::Verify {
+            file,
+            keys,
+            signatures,
+            format,
+        } => commands::verify::run(&file, &keys, signatures.as_deref(), entity, &format, &out),
+        Commands::Tokenize {
+            ids,
+            spec,
+            field,
+            algorithm,
+            key_env,
+            key_file,
+            format,
+        } => commands::tokenize::run(
+            spec.as_deref(),
+            &ids,
+            field.as_deref(),
+            algorithm.as_deref(),
+            &KeySource { key_env, key_file },
+            &format,
+            &out,
+        ),
+        Commands::Fmt { files, check } => commands::fmt::run(&files, check, &out),
+        Commands::Diff {
+            file1,
+            file2,
+            routing,
+            fail_on,
+        } => fail_on
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .and_then(|fail_on| commands::diff::run(&file1, &file2, routing.as_deref(), fail_on, entity, &variables, &out)),
+        Commands::Merge {
+            base,
+            ours,
+            theirs,
+            output,
+        } => commands::merge::run(&base, &ours, &theirs, output.as_deref(), &out),
+        Commands::Plan {
+            file,
+            from_ir,
+            timeout,
+            custom_risks,
+            routing,
+            sample,
+            rows,
+            pair_budget,
+            format,
+            fail_on,
+            no_cache,
+        } => custom_risks
+            .as_deref()
+            .map(CustomRisks::load)
+            .transpose()
+            .and_then(|custom_risks| {
+                let options = commands::plan::PlanOptions {
+                    cancel: timeout.map(CancellationToken::with_timeout_secs).transpose()?,
+                    custom_risks: custom_risks.unwrap_or_default(),
+                    sample: sample.as_deref().map(Sample::load).transpose()?,
+                    policy: from_ir.as_deref().or(file.as_deref()).map(Policy::discover).transpose()?.unwrap_or_default(),
+                    plugins: PluginRegistry::discover()?,
+                    rows: estimate::parse_rows(&rows)?,
+                    pair_budget: pair_budget.map(|pairs| pairs.max(0.0) as u64),
+                };
+                match (file, from_ir) {
+                    (_, Some(ir)) => commands::plan::run_from_ir(&ir, &options, &format, fail_on.as_deref(), routing.as_deref(), &out),
+                    (Some(file), None) => {
+                        let cache = Cache::discover().filter(|_| !no_cache);
+                        commands::plan::run(&file, &options, entity, &variables, &format, fail_on.as_deref(), routing.as_deref(), cache.as_ref(), &out)
+                    }
+                    (None, None) => unreachable!("clap requires FILE or --from-ir"),
+                }
+            }),
+        Commands::Profile {
+            spec,
+            data,
+            source,
+            format,
+        } => commands::profile::run(&spec, &data, source.as_deref(), entity, &variables, &format, &out),
+        Commands::Calibrate {
+            file,
+            labels,
+            format,
+        } => commands::calibrate::run(&file, &labels, entity, &variables, &format, &out),
+        Commands::Analyze {
+            action:
+                AnalyzeAction::Thresholds {
+                    file,
+                    data,
+                    match_threshold,
+                    review,
+                    format,
+                },
+        } => commands::analyze::thresholds(&file, &data, match_threshold, review, entity, &variables, &format, &out),
+        Commands::LearnWeights {
+            file,
+            data,
+            output,
+            timeout,
+            format,
+        } => {
+            let cancel = timeout.map(CancellationToken::with_timeout_secs).transpose()?;
+            commands::learn_weights::run(&file, &data, output.as_deref(), cancel.as_ref(), entity, &variables, &format, &out)
+        }
+        Commands::Cluster {
+            file,
+            decisions,
+            output,
+            audit,
+            review_queue,
+            records,
+            stewardship,
+            format,
+        } => commands::cluster::run(&file, &decisions, output.as_deref(), audit.as_deref(), review_queue.as_deref(), records.as_deref(), stewardship.as_deref(), entity, &variables, &format, &out),
+        Commands::Review { action } => match action {
+            ReviewAction::List { queue, status, format } => commands::review::list(&queue, status.as_deref(), &format, &out),
+            ReviewAction::Inspect { id, queue, file, format } => commands::review::inspect(&queue, &id, file.as_deref(), entity, &variables, &format, &out),
+            ReviewAction::Approve { id, queue, reviewer, note } => commands::review::decide(&queue, &id, ReviewStatus::Approved, reviewer.as_deref(), note.as_deref(), &out),
+            ReviewAction::Reject { id, queue, reviewer, note } => commands::review::decide(&queue, &id, ReviewStatus::Rejected, reviewer.as_deref(), note.as_deref(), &out),
+            ReviewAction::Log { queue, format } => commands::review::log(&queue, &format, &out),
+            ReviewAction::Labels { queue, file, output } => commands::review::labels(&queue, &file, output.as_deref(), entity, &variables, &out),
+        },
+        Commands::GoldenRecords {
+            file,
+            members,
+            as_of,
+            output,
+            audit,
+            lineage,
+            stewardship,
+            format,
+        } => commands::golden_records::run(&file, &members, as_of.as_deref(), output.as_deref(), audit.as_deref(), lineage.as_deref(), stewardship.as_deref(), entity, &variables, &format, &out),
+        Commands::Maintain {
+            file,
+            members,
+            as_of,
+            output,
+            stewardship,
+            format,
+        } => commands::maintain::run(&file, &members, as_of.as_deref(), output.as_deref(), stewardship.as_deref(), entity, &variables, &format, &out),
+        Commands::Execute {
+            file,
+            from_ir,
+            records,
+            output,
+            as_of,
+            backend,
+            distributed,
+            tls_cert,
+            tls_key,
+            partitions,
+            secret,
+            timeout,
+            format,
+        } => {
+            let cancel = timeout.map(CancellationToken::with_timeout_secs).transpose()?;
+            let backend: ExecutionBackend = backend.parse()?;
+            let coordinator = match (distributed, tls_cert, tls_key) {
+                (Some(address), Some(cert), Some(key)) => Some(commands::execute::bind(&address, partitions, secret.as_deref().unwrap_or_default(), &cert, &key)?),
+                _ => None,
+            };
+            commands::execute::run(file.as_deref(), from_ir.as_deref(), &records, output.as_deref(), as_of.as_deref(), backend, coordinator.as_ref(), cancel.as_ref(), entity, &variables, &format, &out)
+        }
+        Commands::Worker {
+            coordinator,
+            wait,
+            secret,
+            tls_ca,
+            timeout,
+            format,
+        } => {
+            let cancel = timeout.map(CancellationToken::with_timeout_secs).transpose()?;
+            commands::worker::run(&coordinator, &secret, &tls_ca, wait, cancel.as_ref(), &format, &out)
+        }
+        Commands::Test { path, format } => commands::test::run(&path, entity, &variables, &format, &out),
+        Commands::GenerateData {
+            spec,
+            rows,
+            duplicate_rate,
+            seed,
+            output,
+            format,
+        } => commands::generate_data::run(
+            &spec,
+            &GenerateOptions {
+                rows,
+                duplicate_rate,
+                seed,
+            },
+            &output,
+            entity,
+            &variables,
+            &format,
+            &out,
+        ),
+        Commands::Evaluate {
+            file,
+            truth,
+            data,
+            timeout,
+            format,
+        } => {
+            let cancel = timeout.map(CancellationToken::with_timeout_secs).transpose()?;
+            commands::evaluate::run(&file, &truth, &data, cancel.as_ref(), entity, &variables, &format, &out)
+        }
+        Commands::BenchSuite {
+            datasets,
+            spec,
+            rows,
+            duplicate_rate,
+            seed,
+            baseline,
+            tolerance,
+            timeout,
+            format,
+        } => {
+            let cancel = timeout.map(CancellationToken::with_timeout_secs).transpose()?;
+            commands::bench_suite::run(
+                &datasets,
+                spec.as_deref(),
+                &GenerateOptions {
+                    rows,
+                    duplicate_rate,
+                    seed,
+                },
+                baseline.as_deref(),
+                tolerance,
+                cancel.as_ref(),
+                entity,
+                &variables,
+                &format,
+                &out,
+            )
+        }
+        Commands::Snapshot {
+            path,
+            dir,
+            update,
+            format,
+        } => commands::snapshot::run(&path, dir.as_deref(), update, &variables, &format, &out),
+        Commands::SurvivorshipImpact {
+            file,
+            members,
+            canonical,
+            limit,
+            format,
+        } => commands::survivorship_impact::run(&file, &members, &canonical, limit, entity, &variables, &format, &out),
+        Commands::MigratePlan { old, new, format } => {
+            commands::migrate_plan::run(&old, &new, &format, &out)
+        }
+        Commands::Docs { file, output } => commands::docs::run(&file, output.as_deref(), entity, &variables, &out),
+        Commands::Report {
+            file,
+            output,
+            previous,
+            custom_risks,
+            sample,
+        } => custom_risks
+            .as_deref()
+            .map(CustomRisks::load)
+            .transpose()
+            .and_then(|custom_risks| {
+                let options = commands::plan::PlanOptions {
+                    custom_risks: custom_risks.unwrap_or_default(),
+                    sample: sample.as_deref().map(Sample::load).transpose()?,
+                    policy: Policy::discover(&file)?,
+                    plugins: PluginRegistry::discover()?,
+                    ..Default::default()
+                };
+                commands::report::run(&file, &output, previous.as_deref(), &options, entity, &variables, &out)
+            }),
+        Commands::RiskTrend {
+            file,
+            limit,
+            format,
+        } => commands::risk_trend::run(&file, limit, &format, &out),
+        Commands::Publish {
+            file,
+            registry,
+            tags,
+        } => commands::registry::publish(&file, &registry, &tags, &variables, &out),
+        Commands::Pull {
+            entity,
+            version,
+            registry,
+            output,
+        } => commands::registry::pull(
+            &entity,
+            version.as_deref(),
+            &registry,
+            output.as_deref(),
+            &out,
+        ),
+        Commands::Compose {
+            file,
+            registry,
+            diff,
+            output,
+            format,
+        } => commands::compose::run(
+            &file,
+            registry.as_deref(),
+            diff,
+            output.as_deref(),
+            entity,
+            &variables,
+            &format,
+            &out,
+        ),
+        Commands::Versions {
+            entity,
+            registry,
+            format,
+        } => commands::registry::versions(&entity, &registry, &format, &out),
+        Commands::Schema {
+            action: SchemaAction::Export { format, output },
+        } => commands::schema::export(&format, output.as_deref(), &out),
+        Commands::AuditSchema {
+            action: AuditSchemaAction::Export { format, output },
+        } => commands::audit_schema::export(&format, output.as_deref(), &out),
+        Commands::Ir {
+            action: IrAction::Schema { format, output },
+        } => commands::ir::schema(&format, output.as_deref(), &out),
+        Commands::Ir {
+            action: IrAction::Check { old, new, format },
+        } => commands::ir::check(&old, &new, &format, &out),
+        Commands::Ir {
+            action: IrAction::Digest { file },
+        } => commands::ir::digest(&file, &out),
+        Commands::Templates {
+            action: TemplatesAction::List { format },
+        } => commands::templates::list(&format, &out),
+        Commands::ExplainPair { file, a, b, format } => commands::explain_pair::run(&file, &a, &b, entity, &variables, &format, &out),
+        Commands::Explain { code } => commands::explain::run(code.as_deref(), &out),
+        Commands::Conformance {
+            corpus,
+            update,
+            format,
+        } => commands::conformance::run(&corpus, update, &format, &out),
+        Commands::Plugins => PluginRegistry::discover().and_then(|plugins| commands::plugins::list(&plugins, &out)),
+    });
 
     match result {
         Ok(_) => std::process::exit(0),
         Err(e) => {
-            eprintln!("{} {}", "error:".red().bold(), e);
+            out.error(format!("{} {}", "error:".red().bold(), e));
             std::process::exit(1);
         }
     }
@@ -0,0 +1,200 @@
+//! Source column aliases for canonical attributes.
+//!
+//! Sources rarely agree on column names. The `mappings` section names, for
+//! each canonical attribute, the columns sources hold it in; a source can
+//! then list its columns instead of mapping each one by hand:
+//!
+//! ```yaml
+//! mappings:
+//!   email: [email_addr, EMAIL]    # canonical attribute: source columns
+//!   phone: [mobile, phone_number]
+//! sources:
+//!   - name: crm
+//!     attributes: [email_addr, mobile, first_name]
+//!   - name: web
+//!     attributes:
+//!       EMAIL: EMAIL              # keyed by an alias: read as `email`
+//! ```
+//!
+//! Columns match aliases, and the attributes' own names, case-insensitively;
+//! a column no mapping names is an attribute of its own name. `resolve` rewrites a spec into canonical
+//! attribute space, with every source's `attributes` mapping canonical
+//! names to columns, and is what validation, compilation and the plan see:
+//! rules, keys and survivorship name canonical attributes, never columns.
+
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// The `mappings` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mappings {
+    /// Lowercased column alias → canonical attribute.
+    aliases: BTreeMap<String, String>,
+}
+
+impl Mappings {
+    /// Read `mappings` from a parsed spec; `None` if the spec has none.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(section) = spec.get("mappings").filter(|m| !m.is_null()) else {
+            return Ok(None);
+        };
+        let Some(entries) = section.as_object() else {
+            bail!(
+                "mappings must map attributes to lists of columns, got {}",
+                section
+            );
+        };
+        let mut aliases = BTreeMap::new();
+        for (canonical, columns) in entries {
+            let columns = columns.as_array().and_then(|columns| {
+                columns
+                    .iter()
+                    .map(Value::as_str)
+                    .collect::<Option<Vec<_>>>()
+            });
+            let Some(columns) = columns else {
+                bail!("mappings.{} must be a list of columns", canonical);
+            };
+            // An attribute's own name is one of its aliases. A column
+            // claimed twice stands for the first attribute claiming it;
+            // validation reports the second.
+            for column in std::iter::once(canonical.as_str()).chain(columns) {
+                aliases
+                    .entry(column.to_lowercase())
+                    .or_insert_with(|| canonical.clone());
+            }
+        }
+        Ok(Some(Mappings { aliases }))
+    }
+
+    /// The canonical attribute `column` holds: the one whose aliases include
+    /// it, or `column` itself.
+    pub fn canonical<'a>(&'a self, column: &'a str) -> &'a str {
+        self.alias_of(column).unwrap_or(column)
+    }
+
+    /// The canonical attribute `name` is an alias of, if it is one.
+    pub fn alias_of(&self, name: &str) -> Option<&str> {
+        self.aliases
+            .get(&name.to_lowercase())
+            .map(String::as_str)
+            .filter(|canonical| *canonical != name)
+    }
+}
+
+/// `spec` in canonical attribute space: each source's `attributes` maps
+/// canonical names to columns, whether the source listed columns or keyed
+/// them by alias, and `mappings` is gone. Specs without either come back
+/// unchanged; malformed mappings resolve nothing (validation reports them).
+pub fn resolve(spec: &Value) -> Value {
+    let mappings = Mappings::from_spec(spec).ok().flatten();
+    let listed = spec
+        .get("sources")
+        .and_then(Value::as_array)
+        .is_some_and(|sources| {
+            sources
+                .iter()
+                .any(|s| s.get("attributes").is_some_and(Value::is_array))
+        });
+    if mappings.is_none() && !listed {
+        return spec.clone();
+    }
+    let mappings = mappings.unwrap_or_default();
+
+    let mut resolved = spec.clone();
+    if let Value::Object(root) = &mut resolved {
+        root.remove("mappings");
+    }
+    let sources = resolved.get_mut("sources").and_then(Value::as_array_mut);
+    for source in sources.into_iter().flatten() {
+        let Some(attributes) = source.get_mut("attributes") else {
+            continue;
+        };
+        let mut canonical = Map::new();
+        match attributes {
+            Value::Array(columns) => {
+                for column in columns.iter().filter_map(Value::as_str) {
+                    canonical
+                        .entry(mappings.canonical(column))
+                        .or_insert_with(|| Value::from(column));
+                }
+            }
+            Value::Object(mapped) => {
+                for (name, mapping) in mapped.iter() {
+                    canonical
+                        .entry(mappings.canonical(name))
+                        .or_insert_with(|| mapping.clone());
+                }
+            }
+            _ => continue,
+        }
+        *attributes = Value::Object(canonical);
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MAPPINGS;
+    use crate::{Ir, Severity};
+
+    #[test]
+    fn test_mappings_resolve_source_columns_to_canonical_attributes() {
+        let codes = |yaml: &str| -> Vec<(String, Option<String>)> {
+            crate::diagnose_yaml(yaml)
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .map(|d| (d.code, d.path))
+                .collect()
+        };
+        let finding = |code: &str, path: &str| (code.to_string(), Some(path.to_string()));
+        assert!(codes(MAPPINGS).is_empty());
+        assert_eq!(crate::format_spec(MAPPINGS).unwrap(), MAPPINGS);
+
+        // The IR is in canonical attribute space, whichever way sources name
+        // their columns: mapping them by hand compiles to the same plan.
+        let compiled = crate::compile_to_ir(&crate::parse_yaml(MAPPINGS).unwrap()).unwrap();
+        let ir = Ir::from_value(&compiled).unwrap();
+        assert_eq!(ir.attributes(), ["email", "first_name", "phone"]);
+        assert_eq!(ir.sources[0].attributes["phone"], "mobile");
+        assert_eq!(ir.sources[1].attributes["email"], "EMAIL");
+        let explicit = MAPPINGS
+        .replace("mappings:\n  email: [email_addr, EMAIL]\n  phone: [mobile, phone_number]\n", "")
+        .replace(
+            "    attributes: [email_addr, mobile, first_name]\n",
+            "    attributes:\n      email: email_addr\n      first_name: first_name\n      phone: mobile\n",
+        )
+        .replace("      EMAIL: EMAIL\n      phone_number: phone_number\n", "      email: EMAIL\n      phone: phone_number\n");
+        let by_hand = crate::compile_to_ir(&crate::parse_yaml(&explicit).unwrap()).unwrap();
+        assert_eq!(by_hand["plan_hash"], compiled["plan_hash"]);
+        let diff = crate::compute_diff(&explicit, MAPPINGS).unwrap();
+        assert_eq!(diff.compatibility.impact, None);
+
+        // Rules name attributes, not the columns that hold them.
+        let by_column = MAPPINGS.replace("    field: phone\n", "    field: mobile\n");
+        assert_eq!(codes(&by_column), [finding("KNV0101", "rules[1].field")]);
+        let spec = crate::parse_yaml(&by_column).unwrap();
+        let suggestion = crate::semantic_diagnostics(&spec)[0].suggestion.clone();
+        assert_eq!(
+            suggestion.as_deref(),
+            Some("'mobile' is a source column; refer to its attribute 'phone'.")
+        );
+
+        // A column stands for one attribute, and a source holds each attribute
+        // in one column.
+        let ambiguous = MAPPINGS.replace(
+            "phone: [mobile, phone_number]",
+            "phone: [email, phone_number]",
+        );
+        assert_eq!(codes(&ambiguous), [finding("KNV0009", "mappings.phone[0]")]);
+        let twice = MAPPINGS.replace(
+            "[email_addr, mobile, first_name]",
+            "[email_addr, mobile, EMAIL]",
+        );
+        assert_eq!(
+            codes(&twice),
+            [finding("KNV0009", "sources[0].attributes[2]")]
+        );
+    }
+}
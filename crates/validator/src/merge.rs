@@ -0,0 +1,328 @@
+//! Three-way merge of concurrently edited specs.
+//!
+//! `merge_specs` merges the parsed documents rather than their text: each
+//! node takes the side that changed it from the base, and only nodes both
+//! sides changed differently conflict. Lists of named entries (`rules`,
+//! `sources`, `blocking.keys`, `survivorship.rules`, `waivers`) merge entry
+//! by entry, so two teams adding different rules never conflict. Other
+//! lists merge as a whole.
+//!
+//! The result is written in canonical form (see `format_spec`), with each
+//! conflict between markers at the node it concerns:
+//!
+//! ```yaml
+//! rules:
+//!   - name: email_exact
+//! <<<<<<< ours (rules.email_exact.weight)
+//!     weight: 0.9
+//! =======
+//!     weight: 0.7
+//! >>>>>>> theirs
+//! ```
+//!
+//! Comments are not carried over.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::canonical::same_value;
+use crate::format::{format_merged, Alternatives};
+use crate::parser;
+
+/// Lists merged entry by entry, with the fields identifying an entry. A
+/// string entry (a bare blocking key) identifies itself.
+pub(crate) const KEYED_LISTS: &[(&str, &[&str])] = &[
+    ("sources", &["name"]),
+    ("rules", &["name"]),
+    ("blocking.keys", &["field", "name"]),
+    ("survivorship.rules", &["field"]),
+    ("waivers", &["code"]),
+];
+
+#[derive(Debug, Clone)]
+pub struct Merged {
+    /// The merged spec, with conflict markers if there are conflicts.
+    pub yaml: String,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl Merged {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// A node both sides changed differently. `None` means absent (or removed).
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeConflict {
+    /// Dotted path, with list entries by name (`rules.email_exact.weight`).
+    pub path: String,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+/// Merge the changes `ours` and `theirs` each made to `base`.
+pub fn merge_specs(base: &str, ours: &str, theirs: &str) -> Result<Merged> {
+    let parse = |yaml: &str, side: &str| -> Result<Value> {
+        let spec = parser::parse_yaml(yaml).with_context(|| format!("Failed to parse {}", side))?;
+        if !spec.is_object() {
+            bail!("{} must be a YAML mapping", side);
+        }
+        Ok(spec)
+    };
+    let (base, ours, theirs) = (
+        parse(base, "base")?,
+        parse(ours, "ours")?,
+        parse(theirs, "theirs")?,
+    );
+
+    let mut merger = Merger::default();
+    let merged = merger.merge(
+        Some(&base),
+        Some(&ours),
+        Some(&theirs),
+        &Location::default(),
+    );
+    let Some(Value::Object(root)) = merged else {
+        unreachable!("merging three mappings yields a mapping");
+    };
+    let yaml = format_merged(&root, merger.alternatives);
+    Ok(Merged {
+        yaml,
+        conflicts: merger.conflicts,
+    })
+}
+
+#[derive(Default)]
+struct Merger {
+    conflicts: Vec<MergeConflict>,
+    /// By position in the merged document, for the formatter.
+    alternatives: HashMap<String, Alternatives>,
+}
+
+/// Where a node sits: its position in the merged document (`rules[2]`),
+/// its path with list indexes removed (`rules[]`) and its display path
+/// (`rules.email_exact`).
+#[derive(Default)]
+struct Location {
+    position: String,
+    shape: String,
+    display: String,
+}
+
+impl Location {
+    fn key(&self, key: &str) -> Location {
+        let join = |parent: &str| {
+            if parent.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", parent, key)
+            }
+        };
+        Location {
+            position: join(&self.position),
+            shape: join(&self.shape),
+            display: join(&self.display),
+        }
+    }
+
+    fn entry(&self, index: usize, name: &str) -> Location {
+        Location {
+            position: format!("{}[{}]", self.position, index),
+            shape: format!("{}[]", self.shape),
+            display: format!("{}.{}", self.display, name),
+        }
+    }
+}
+
+impl Merger {
+    /// The merged node, or `None` if it ends up removed.
+    fn merge(
+        &mut self,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+        at: &Location,
+    ) -> Option<Value> {
+        let same = |a: Option<&Value>, b: Option<&Value>| match (a, b) {
+            (Some(a), Some(b)) => same_value(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        if same(ours, theirs) || same(theirs, base) {
+            return ours.cloned();
+        }
+        if same(ours, base) {
+            return theirs.cloned();
+        }
+
+        match (base, ours, theirs) {
+            (None | Some(Value::Object(_)), Some(Value::Object(o)), Some(Value::Object(t))) => {
+                let b = base.and_then(Value::as_object);
+                Some(Value::Object(self.merge_maps(b, o, t, at)))
+            }
+            (None | Some(Value::Array(_)), Some(Value::Array(o)), Some(Value::Array(t))) => {
+                let b = base.and_then(Value::as_array);
+                match self.merge_lists(b, o, t, at) {
+                    Some(merged) => Some(Value::Array(merged)),
+                    None => self.conflict(base, ours, theirs, at),
+                }
+            }
+            _ => self.conflict(base, ours, theirs, at),
+        }
+    }
+
+    fn merge_maps(
+        &mut self,
+        base: Option<&Map<String, Value>>,
+        ours: &Map<String, Value>,
+        theirs: &Map<String, Value>,
+        at: &Location,
+    ) -> Map<String, Value> {
+        let keys = ours
+            .keys()
+            .chain(theirs.keys().filter(|k| !ours.contains_key(*k)))
+            .chain(
+                base.into_iter()
+                    .flat_map(|b| b.keys())
+                    .filter(|k| !ours.contains_key(*k) && !theirs.contains_key(*k)),
+            );
+        let mut merged = Map::new();
+        for key in keys {
+            let value = self.merge(
+                base.and_then(|b| b.get(key)),
+                ours.get(key),
+                theirs.get(key),
+                &at.key(key),
+            );
+            if let Some(value) = value {
+                merged.insert(key.clone(), value);
+            }
+        }
+        merged
+    }
+
+    /// Entry-by-entry merge, in our order with their new entries after;
+    /// `None` if the list is not keyed.
+    fn merge_lists(
+        &mut self,
+        base: Option<&Vec<Value>>,
+        ours: &[Value],
+        theirs: &[Value],
+        at: &Location,
+    ) -> Option<Vec<Value>> {
+        let fields = KEYED_LISTS
+            .iter()
+            .find(|(path, _)| *path == at.shape)
+            .map(|(_, fields)| *fields)?;
+        let base = base.map(Vec::as_slice).unwrap_or_default();
+        let (b, o, t) = (
+            keyed(base, fields)?,
+            keyed(ours, fields)?,
+            keyed(theirs, fields)?,
+        );
+
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in o.iter().chain(&t).chain(&b) {
+            if !names.contains(name) {
+                names.push(name);
+            }
+        }
+        let mut merged = Vec::new();
+        for name in names {
+            let entry = self.merge(
+                find(&b, name),
+                find(&o, name),
+                find(&t, name),
+                &at.entry(merged.len(), name),
+            );
+            merged.extend(entry);
+        }
+        Some(merged)
+    }
+
+    /// Record a conflict; the merged document keeps a stand-in the
+    /// formatter replaces with both sides.
+    fn conflict(
+        &mut self,
+        base: Option<&Value>,
+        ours: Option<&Value>,
+        theirs: Option<&Value>,
+        at: &Location,
+    ) -> Option<Value> {
+        self.conflicts.push(MergeConflict {
+            path: at.display.clone(),
+            base: base.cloned(),
+            ours: ours.cloned(),
+            theirs: theirs.cloned(),
+        });
+        self.alternatives.insert(
+            at.position.clone(),
+            Alternatives {
+                label: at.display.clone(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            },
+        );
+        ours.or(theirs).cloned()
+    }
+}
+
+/// Entries by name, or `None` if some entry has no name or a name repeats.
+pub(crate) fn keyed<'a>(entries: &'a [Value], fields: &[&str]) -> Option<Vec<(&'a str, &'a Value)>> {
+    let mut seen = HashSet::new();
+    entries
+        .iter()
+        .map(|entry| {
+            let name = match entry {
+                Value::String(name) => Some(name.as_str()),
+                _ => fields
+                    .iter()
+                    .find_map(|field| entry.get(*field).and_then(Value::as_str)),
+            }?;
+            seen.insert(name).then_some((name, entry))
+        })
+        .collect()
+}
+
+fn find<'a>(entries: &[(&str, &'a Value)], name: &str) -> Option<&'a Value> {
+    entries.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_merge_specs_three_way() {
+        let ours = MINIMAL.replace("match: 0.9", "match: 0.95");
+        let theirs = MINIMAL.replace(
+            "decision:",
+            "  - name: phone_exact\n    type: exact\n    field: phone\n    weight: 0.5\ndecision:",
+        );
+        let clean = crate::merge_specs(MINIMAL, &ours, &theirs).unwrap();
+        assert!(clean.is_clean());
+        assert!(clean.yaml.contains("match: 0.95"));
+        assert!(clean.yaml.contains("name: phone_exact"));
+        assert_eq!(clean.yaml, crate::format_spec(&clean.yaml).unwrap());
+
+        let ours = MINIMAL.replace("weight: 1.0", "weight: 0.9");
+        let theirs = MINIMAL.replace("weight: 1.0", "weight: 0.7");
+        let conflicted = crate::merge_specs(MINIMAL, &ours, &theirs).unwrap();
+        let paths: Vec<&str> = conflicted
+            .conflicts
+            .iter()
+            .map(|c| c.path.as_str())
+            .collect();
+        assert_eq!(paths, ["rules.email_exact.weight"]);
+        assert!(conflicted.yaml.contains(
+        "<<<<<<< ours (rules.email_exact.weight)\n    weight: 0.9\n=======\n    weight: 0.7\n>>>>>>> theirs\n"
+    ));
+
+        let removed = MINIMAL.replace("    weight: 1.0\n", "");
+        let conflicted = crate::merge_specs(MINIMAL, &removed, &theirs).unwrap();
+        assert_eq!(conflicted.conflicts[0].ours, None);
+    }
+}
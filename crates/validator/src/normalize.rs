@@ -0,0 +1,718 @@
+//! Attribute normalization applied before matching.
+//!
+//! A spec's `normalization` section lists, per attribute, the normalizers
+//! applied to its values in order, with a `locale` choosing the nickname and
+//! honorific tables:
+//!
+//! ```yaml
+//! normalization:
+//!   locale: en
+//!   fields:
+//!     first_name: [nfkc, casefold, strip_diacritics, remove_honorifics, expand_nicknames]
+//!     last_name: [nfkc, casefold, strip_diacritics, metaphone]
+//!     company:
+//!       - trim
+//!       - lower
+//!       - regex_replace: { pattern: '\b(inc|llc|ltd)\.?$', replacement: '' }
+//!       - strip_diacritics
+//! ```
+//!
+//! Steps are normalizer names, except `regex_replace`, which takes the
+//! pattern to rewrite and its replacement (`$1` and `${name}` refer to
+//! groups; an omitted replacement deletes the matches).
+//!
+//! The `phone` normalizer makes an attribute a phone number, rewritten to
+//! E.164 with numbers in national format read in the section's
+//! `default_region` (see `phone`).
+//!
+//! `validate_schema` checks normalizer, locale and region names, and the section is
+//! compiled into the IR for the engine, which normalizes records before
+//! blocking and matching. Word-level normalizers split on whitespace and
+//! join the words back with single spaces.
+
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::fmt;
+use std::str::FromStr;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::address;
+use crate::phone::{self, Region};
+use crate::similarity::{metaphone, soundex};
+
+/// Normalizer names, as the `normalization` section gives them.
+pub const NORMALIZERS: &[&str] = &[
+    "trim",
+    "lower",
+    "upper",
+    "regex_replace",
+    "nfkc",
+    "casefold",
+    "strip_diacritics",
+    "remove_honorifics",
+    "expand_nicknames",
+    "soundex",
+    "metaphone",
+    "phone",
+    "address",
+];
+
+/// Locales with nickname and honorific tables.
+pub const LOCALES: &[&str] = &["en", "es", "fr", "de"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Normalizer {
+    /// Remove leading and trailing whitespace.
+    Trim,
+    Lower,
+    Upper,
+    /// Rewrite every match of a pattern.
+    RegexReplace(RegexReplace),
+    /// Unicode compatibility composition: full-width letters, ligatures and
+    /// other compatibility forms become their plain equivalents.
+    Nfkc,
+    /// Unicode default case folding (`Straße` and `STRASSE` agree).
+    CaseFold,
+    /// Remove accents and other combining marks (`José` -> `Jose`).
+    StripDiacritics,
+    /// Drop leading titles such as `Dr.` or `Mrs`.
+    RemoveHonorifics,
+    /// Replace nicknames with the formal name (`Bill` -> `William`).
+    ExpandNicknames,
+    /// Replace each word with its Soundex code.
+    Soundex,
+    /// Replace each word with its Metaphone code.
+    Metaphone,
+    /// Parse a phone number and rewrite it to E.164, reading numbers
+    /// without a country code in `default_region`; invalid numbers become
+    /// empty.
+    Phone {
+        default_region: Option<Region>,
+    },
+    /// Standardize a postal address: tokens case folded, punctuation
+    /// dropped and abbreviations expanded (`N Main St` -> `north main
+    /// street`).
+    Address,
+}
+
+impl Normalizer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Normalizer::Trim => "trim",
+            Normalizer::Lower => "lower",
+            Normalizer::Upper => "upper",
+            Normalizer::RegexReplace(_) => "regex_replace",
+            Normalizer::Nfkc => "nfkc",
+            Normalizer::CaseFold => "casefold",
+            Normalizer::StripDiacritics => "strip_diacritics",
+            Normalizer::RemoveHonorifics => "remove_honorifics",
+            Normalizer::ExpandNicknames => "expand_nicknames",
+            Normalizer::Soundex => "soundex",
+            Normalizer::Metaphone => "metaphone",
+            Normalizer::Phone { .. } => "phone",
+            Normalizer::Address => "address",
+        }
+    }
+
+    pub fn apply(&self, value: &str, locale: Locale) -> String {
+        match self {
+            Normalizer::Trim => value.trim().to_string(),
+            Normalizer::Lower => value.to_lowercase(),
+            Normalizer::Upper => value.to_uppercase(),
+            Normalizer::RegexReplace(rewrite) => rewrite.apply(value),
+            Normalizer::Nfkc => value.nfkc().collect(),
+            Normalizer::CaseFold => caseless::default_case_fold_str(value),
+            Normalizer::StripDiacritics => strip_diacritics(value),
+            Normalizer::RemoveHonorifics => remove_honorifics(value, locale),
+            Normalizer::ExpandNicknames => words(value)
+                .map(|word| match nickname(word, locale) {
+                    Some(formal) => match_case(formal, word),
+                    None => word.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            Normalizer::Soundex => words(value).map(soundex).collect::<Vec<_>>().join(" "),
+            Normalizer::Metaphone => words(value).map(metaphone).collect::<Vec<_>>().join(" "),
+            Normalizer::Phone { default_region } => phone::to_e164(value, *default_region),
+            Normalizer::Address => address::standardize(value),
+        }
+    }
+
+    /// Read a step as the `normalization` section gives it: a normalizer's
+    /// name, or `regex_replace` with its arguments.
+    pub fn from_value(step: &Value) -> Result<Self> {
+        match step {
+            Value::String(name) => name.parse(),
+            Value::Object(map) if map.len() == 1 && map.contains_key("regex_replace") => {
+                let args = &map["regex_replace"];
+                let Some(pattern) = args.get("pattern").and_then(Value::as_str) else {
+                    bail!("regex_replace needs a `pattern` string");
+                };
+                let replacement = match args.get("replacement") {
+                    None | Some(Value::Null) => "",
+                    Some(Value::String(replacement)) => replacement,
+                    Some(other) => bail!(
+                        "regex_replace `replacement` must be a string, got {}",
+                        other
+                    ),
+                };
+                Ok(Normalizer::RegexReplace(RegexReplace::new(
+                    pattern,
+                    replacement,
+                )?))
+            }
+            other => bail!(
+                "Unknown normalizer: {}. Expected one of: {}",
+                other,
+                NORMALIZERS.join(", ")
+            ),
+        }
+    }
+
+    /// The step as the IR carries it: its name, or `regex_replace` with its
+    /// arguments.
+    pub fn to_value(&self) -> Value {
+        match self {
+            Normalizer::RegexReplace(rewrite) => json!({
+                "regex_replace": {
+                    "pattern": rewrite.pattern(),
+                    "replacement": rewrite.replacement,
+                }
+            }),
+            other => Value::from(other.name()),
+        }
+    }
+}
+
+impl FromStr for Normalizer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "trim" => Ok(Normalizer::Trim),
+            "lower" => Ok(Normalizer::Lower),
+            "upper" => Ok(Normalizer::Upper),
+            "regex_replace" => bail!(
+                "regex_replace takes a pattern: {{regex_replace: {{pattern: ..., replacement: ...}}}}"
+            ),
+            "nfkc" => Ok(Normalizer::Nfkc),
+            "casefold" => Ok(Normalizer::CaseFold),
+            "strip_diacritics" => Ok(Normalizer::StripDiacritics),
+            "remove_honorifics" => Ok(Normalizer::RemoveHonorifics),
+            "expand_nicknames" => Ok(Normalizer::ExpandNicknames),
+            "soundex" => Ok(Normalizer::Soundex),
+            "metaphone" => Ok(Normalizer::Metaphone),
+            "phone" => Ok(Normalizer::Phone {
+                default_region: None,
+            }),
+            "address" => Ok(Normalizer::Address),
+            other => bail!(
+                "Unknown normalizer: '{}'. Expected one of: {}",
+                other,
+                NORMALIZERS.join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Normalizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A `regex_replace` step. Steps compare by pattern and replacement.
+#[derive(Debug, Clone)]
+pub struct RegexReplace {
+    regex: Regex,
+    pub replacement: String,
+}
+
+impl RegexReplace {
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).map_err(|e| {
+            // The error renders the pattern with a caret over several
+            // lines; its last line says what is wrong.
+            let message = e.to_string();
+            let reason = message.lines().last().unwrap_or_default();
+            anyhow!(
+                "Invalid regex_replace pattern '{}': {}",
+                pattern,
+                reason.trim_start_matches("error: ")
+            )
+        })?;
+        Ok(RegexReplace {
+            regex,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        self.regex
+            .replace_all(value, self.replacement.as_str())
+            .into_owned()
+    }
+}
+
+impl PartialEq for RegexReplace {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern() == other.pattern() && self.replacement == other.replacement
+    }
+}
+
+impl Eq for RegexReplace {}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+    Fr,
+    De,
+}
+
+impl Locale {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+            Locale::Fr => "fr",
+            Locale::De => "de",
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    /// A language code; a region (`en-GB`, `es_MX`) is accepted and ignored.
+    fn from_str(s: &str) -> Result<Self> {
+        let language = s.split(['-', '_']).next().unwrap_or_default();
+        match language.to_ascii_lowercase().as_str() {
+            "en" => Ok(Locale::En),
+            "es" => Ok(Locale::Es),
+            "fr" => Ok(Locale::Fr),
+            "de" => Ok(Locale::De),
+            _ => bail!(
+                "Unknown locale: '{}'. Expected one of: {}",
+                s,
+                LOCALES.join(", ")
+            ),
+        }
+    }
+}
+
+/// Normalizers for one attribute, applied in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    pub locale: Locale,
+    pub steps: Vec<Normalizer>,
+}
+
+impl Pipeline {
+    pub fn apply(&self, value: &str) -> String {
+        self.steps.iter().fold(value.to_string(), |value, step| {
+            step.apply(&value, self.locale)
+        })
+    }
+}
+
+/// A spec's `normalization` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Normalization {
+    pub locale: Locale,
+    /// Region of phone numbers written without a country code.
+    pub default_region: Option<Region>,
+    /// Attribute -> normalizers.
+    pub fields: Vec<(String, Vec<Normalizer>)>,
+}
+
+impl Normalization {
+    /// Read `normalization` from a parsed spec; empty if the section is
+    /// absent. Errors name the first invalid entry; `validate_schema`
+    /// reports them all.
+    pub fn from_spec(spec: &Value) -> Result<Self> {
+        let Some(section) = spec.get("normalization").filter(|n| !n.is_null()) else {
+            return Ok(Self::default());
+        };
+        let locale = match section.get("locale").and_then(Value::as_str) {
+            Some(locale) => locale.parse()?,
+            None => Locale::default(),
+        };
+        let default_region = match section.get("default_region").and_then(Value::as_str) {
+            Some(region) => Some(region.parse()?),
+            None => None,
+        };
+        let mut fields = Vec::new();
+        if let Some(map) = section.get("fields").and_then(Value::as_object) {
+            for (field, steps) in map {
+                let steps = steps
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|step| {
+                        Ok(match Normalizer::from_value(step)? {
+                            Normalizer::Phone { .. } => Normalizer::Phone { default_region },
+                            normalizer => normalizer,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                fields.push((field.clone(), steps));
+            }
+        }
+        Ok(Normalization {
+            locale,
+            default_region,
+            fields,
+        })
+    }
+
+    /// Attributes normalized as phone numbers.
+    pub fn phone_fields(&self) -> impl Iterator<Item = &str> {
+        self.fields
+            .iter()
+            .filter(|(_, steps)| steps.iter().any(|s| matches!(s, Normalizer::Phone { .. })))
+            .map(|(field, _)| field.as_str())
+    }
+
+    /// The pipeline for `field`, if the section names it.
+    pub fn pipeline(&self, field: &str) -> Option<Pipeline> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, steps)| Pipeline {
+                locale: self.locale,
+                steps: steps.clone(),
+            })
+    }
+
+    /// Normalize `value` of `field`; fields without a pipeline pass through.
+    pub fn apply(&self, field: &str, value: &str) -> String {
+        match self.pipeline(field) {
+            Some(pipeline) => pipeline.apply(value),
+            None => value.to_string(),
+        }
+    }
+}
+
+fn words(value: &str) -> impl Iterator<Item = &str> {
+    value.split_whitespace()
+}
+
+/// Decompose and drop combining marks. Letters that are not composed with a
+/// mark (`ø`, `ł`, `ß`) have no decomposition and are mapped directly.
+fn strip_diacritics(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.nfd().filter(|c| !is_combining_mark(*c)) {
+        match c {
+            'ø' => out.push('o'),
+            'Ø' => out.push('O'),
+            'ł' => out.push('l'),
+            'Ł' => out.push('L'),
+            'đ' => out.push('d'),
+            'Đ' => out.push('D'),
+            'ß' => out.push_str("ss"),
+            'æ' => out.push_str("ae"),
+            'Æ' => out.push_str("AE"),
+            'œ' => out.push_str("oe"),
+            'Œ' => out.push_str("OE"),
+            c => out.push(c),
+        }
+    }
+    out.nfc().collect()
+}
+
+/// Drop leading honorifics (`Dr. Jane Smith` -> `Jane Smith`), keeping at
+/// least one word so a bare `Dean` or `Don` survives.
+fn remove_honorifics(value: &str, locale: Locale) -> String {
+    let words: Vec<&str> = words(value).collect();
+    let titles = honorifics(locale);
+    let leading = words
+        .iter()
+        .take_while(|word| {
+            let bare = word.trim_end_matches('.').to_lowercase();
+            titles.contains(&bare.as_str())
+        })
+        .count()
+        .min(words.len().saturating_sub(1));
+    words[leading..].join(" ")
+}
+
+fn honorifics(locale: Locale) -> &'static [&'static str] {
+    match locale {
+        Locale::En => &[
+            "mr", "mrs", "ms", "miss", "mx", "dr", "prof", "sir", "dame", "lady", "lord", "rev",
+            "fr",
+        ],
+        Locale::Es => &[
+            "sr", "sra", "srta", "don", "doña", "dona", "dr", "dra", "lic", "ing",
+        ],
+        Locale::Fr => &["m", "mme", "mlle", "me", "dr", "pr"],
+        Locale::De => &["herr", "frau", "hr", "fr", "dr", "prof"],
+    }
+}
+
+/// The formal name for a nickname, in lowercase. Formal names keep their
+/// accents; `strip_diacritics` after `expand_nicknames` removes them.
+fn nickname(word: &str, locale: Locale) -> Option<&'static str> {
+    let word = word.to_lowercase();
+    nicknames(locale)
+        .iter()
+        .find(|(_, nicks)| nicks.contains(&word.as_str()))
+        .map(|(formal, _)| *formal)
+}
+
+fn nicknames(locale: Locale) -> &'static [(&'static str, &'static [&'static str])] {
+    match locale {
+        Locale::En => &[
+            ("william", &["bill", "billy", "will", "willy"]),
+            ("robert", &["bob", "bobby", "rob", "robbie"]),
+            ("richard", &["dick", "rick", "ricky", "rich"]),
+            ("james", &["jim", "jimmy", "jamie"]),
+            ("john", &["jack", "johnny"]),
+            ("michael", &["mike", "mikey", "mick"]),
+            ("thomas", &["tom", "tommy"]),
+            ("joseph", &["joe", "joey"]),
+            ("charles", &["charlie", "chuck"]),
+            ("edward", &["ed", "eddie", "ned"]),
+            ("christopher", &["chris"]),
+            ("daniel", &["dan", "danny"]),
+            ("david", &["dave", "davy"]),
+            ("anthony", &["tony"]),
+            ("alexander", &["alex"]),
+            ("benjamin", &["ben", "benny"]),
+            ("matthew", &["matt"]),
+            ("nicholas", &["nick", "nicky"]),
+            ("samuel", &["sam", "sammy"]),
+            ("steven", &["steve"]),
+            ("elizabeth", &["liz", "lizzie", "beth", "betty", "eliza"]),
+            ("margaret", &["maggie", "peggy", "meg"]),
+            ("katherine", &["kate", "katie", "kathy"]),
+            ("jennifer", &["jen", "jenny"]),
+            ("susan", &["sue", "susie"]),
+            ("patricia", &["patty", "trish"]),
+            ("deborah", &["deb", "debbie"]),
+            ("rebecca", &["becky"]),
+        ],
+        Locale::Es => &[
+            ("josé", &["pepe", "chepe"]),
+            ("francisco", &["paco", "pancho", "curro"]),
+            ("ignacio", &["nacho"]),
+            ("guillermo", &["memo"]),
+            ("jesús", &["chucho", "chuy"]),
+            ("enrique", &["quique"]),
+            ("manuel", &["manolo"]),
+            ("guadalupe", &["lupe"]),
+            ("concepción", &["concha", "conchita"]),
+            ("dolores", &["lola"]),
+            ("rosario", &["charo"]),
+        ],
+        Locale::Fr => &[
+            ("jean", &["jeannot"]),
+            ("nicolas", &["nico"]),
+            ("dominique", &["dom"]),
+            ("catherine", &["cathy"]),
+            ("isabelle", &["isa"]),
+        ],
+        Locale::De => &[
+            ("johannes", &["hans", "hannes"]),
+            ("josef", &["sepp", "seppl"]),
+            ("friedrich", &["fritz"]),
+            ("wilhelm", &["willi"]),
+            ("katharina", &["kathi", "katja"]),
+            ("elisabeth", &["lisa", "liesel"]),
+        ],
+    }
+}
+
+/// `formal` in the case style of `like`: lowercase, uppercase or capitalized.
+fn match_case(formal: &str, like: &str) -> String {
+    if like.chars().all(|c| !c.is_uppercase()) {
+        formal.to_string()
+    } else if like.chars().count() > 1 && like.chars().all(|c| !c.is_lowercase()) {
+        formal.to_uppercase()
+    } else {
+        let mut chars = formal.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MINIMAL, PRIVACY};
+    use crate::Ir;
+
+    #[test]
+    fn test_normalizers_and_normalization_section() {
+        use crate::normalize::{Locale, Normalization, Normalizer, Pipeline};
+
+        let pipeline = |locale: Locale, steps: &[&str]| Pipeline {
+            locale,
+            steps: steps
+                .iter()
+                .map(|s| s.parse::<Normalizer>().unwrap())
+                .collect(),
+        };
+        let names = pipeline(
+            Locale::En,
+            &[
+                "nfkc",
+                "casefold",
+                "strip_diacritics",
+                "remove_honorifics",
+                "expand_nicknames",
+            ],
+        );
+        assert_eq!(names.apply("Dr.  ＢＩＬＬ  Renée"), "william renee");
+        assert_eq!(names.apply("Mr"), "mr");
+        assert_eq!(
+            pipeline(Locale::Es, &["remove_honorifics", "expand_nicknames"]).apply("Sra. Lupe"),
+            "Guadalupe"
+        );
+        assert_eq!(
+            pipeline(Locale::De, &["casefold"]).apply("STRASSE"),
+            pipeline(Locale::De, &["casefold"]).apply("Straße")
+        );
+        assert_eq!(
+            pipeline(Locale::En, &["strip_diacritics", "metaphone"]).apply("Smíth Thompson"),
+            "SM0 0MPSN"
+        );
+        assert!("en-GB".parse::<Locale>().is_ok());
+
+        let spec = format!(
+            "{}{}normalization:\n  locale: es\n  fields:\n    email: [casefold]\n",
+            MINIMAL, PRIVACY
+        );
+        let spec = crate::parse_yaml(&spec).unwrap();
+        assert!(crate::schema_diagnostics(&spec).is_empty());
+        assert!(crate::semantic_diagnostics(&spec).is_empty());
+        let normalization = Normalization::from_spec(&spec).unwrap();
+        assert_eq!(normalization.apply("email", "A@X.COM"), "a@x.com");
+        assert_eq!(normalization.apply("phone", "A"), "A");
+        let ir = Ir::from_value(&crate::compile_to_ir(&spec).unwrap()).unwrap();
+        let compiled = ir.normalization.unwrap();
+        assert_eq!(
+            (compiled.locale.as_str(), compiled.fields["email"].clone()),
+            ("es", vec![serde_json::json!("casefold")])
+        );
+
+        let invalid = format!(
+        "{}normalization:\n  locale: xx\n  fields:\n    email: [casefold, lowercase]\n    name: [nfkc]\n",
+        MINIMAL
+    );
+        let invalid = crate::parse_yaml(&invalid).unwrap();
+        let schema: Vec<_> = crate::schema_diagnostics(&invalid)
+            .into_iter()
+            .map(|d| (d.code, d.path.unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            schema,
+            [
+                ("KNV0007".to_string(), "normalization.locale".to_string()),
+                (
+                    "KNV0007".to_string(),
+                    "normalization.fields.email[1]".to_string()
+                ),
+            ]
+        );
+        let semantic = crate::semantic_diagnostics(&invalid);
+        assert_eq!(
+            (semantic[0].code.as_str(), semantic[0].path.as_deref()),
+            ("KNV0101", Some("normalization.fields.name"))
+        );
+    }
+
+    #[test]
+    fn test_transformation_pipelines_with_regex_replace() {
+        use crate::normalize::Normalization;
+
+        let spec = format!(
+        "{}normalization:\n  fields:\n    email:\n      - trim\n      - lower\n      - regex_replace: {{ pattern: '\\+[^@]*@', replacement: '@' }}\n      - strip_diacritics\n",
+        MINIMAL
+    );
+        let parsed = crate::parse_yaml(&spec).unwrap();
+        assert!(crate::schema_diagnostics(&parsed).is_empty());
+        let normalization = Normalization::from_spec(&parsed).unwrap();
+        assert_eq!(
+            normalization.apply("email", "  Zoë+News@X.com "),
+            "zoe@x.com"
+        );
+
+        // Named steps compile to their names, regex_replace to its arguments.
+        let ir = Ir::from_value(&crate::compile_to_ir(&parsed).unwrap()).unwrap();
+        assert_eq!(
+            ir.normalization.unwrap().fields["email"],
+            [
+                serde_json::json!("trim"),
+                serde_json::json!("lower"),
+                serde_json::json!({"regex_replace": {"pattern": "\\+[^@]*@", "replacement": "@"}}),
+                serde_json::json!("strip_diacritics"),
+            ]
+        );
+        let plan = crate::generate_plan(&spec).unwrap();
+        assert_eq!(
+        plan.execution_stages[0].description,
+        "Ingest and normalize fields from: crm. Pipelines: email (trim → lower → regex_replace('\\+[^@]*@' => '@') → strip_diacritics)"
+    );
+        // Lowering the email is as good as case folding it.
+        assert!(!plan
+            .risk_flags
+            .iter()
+            .any(|f| f.code == "EMAIL_CASE_SENSITIVE"));
+
+        // Files are interpolated first, so a named group is written `$${name}`;
+        // `${name}` is a variable, even where it is a replacement.
+        use crate::interpolate::{interpolate, Variables};
+        let named = format!(
+        "{}normalization:\n  fields:\n    email:\n      - regex_replace: {{ pattern: '^(?P<user>[^+]+)\\+[^@]*', replacement: '$${{user}}' }}\n",
+        MINIMAL
+    );
+        let variables = Variables {
+            env_file: [("user".to_string(), "attacker".to_string())].into(),
+            ..Default::default()
+        };
+        let parsed = crate::parse_yaml(&interpolate(&named, &variables).unwrap()).unwrap();
+        let normalization = Normalization::from_spec(&parsed).unwrap();
+        assert_eq!(normalization.apply("email", "zoe+news@x.com"), "zoe@x.com");
+        let err = interpolate(&named.replace("$${", "${"), &Variables::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("undefined variable ${user}"), "{}", err);
+
+        let invalid = format!(
+        "{}normalization:\n  fields:\n    email:\n      - rtrim\n      - regex_replace: {{ pattern: '(inc' }}\n      - regex_replace: {{ replacement: x }}\n      - regex_replace\n",
+        MINIMAL
+    );
+        let invalid = crate::parse_yaml(&invalid).unwrap();
+        let schema = crate::schema_diagnostics(&invalid);
+        let paths: Vec<_> = schema
+            .iter()
+            .map(|d| (d.code.as_str(), d.path.as_deref().unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("KNV0007", "normalization.fields.email[0]"),
+                ("KNV0007", "normalization.fields.email[1]"),
+                ("KNV0007", "normalization.fields.email[2]"),
+                ("KNV0007", "normalization.fields.email[3]"),
+            ]
+        );
+        assert_eq!(
+            schema[1].message,
+            "Field 'email': Invalid regex_replace pattern '(inc': unclosed group."
+        );
+        assert!(schema[2]
+            .message
+            .contains("regex_replace needs a `pattern` string"));
+    }
+}
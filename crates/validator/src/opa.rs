@@ -0,0 +1,278 @@
+//! Rego policies, evaluated with Open Policy Agent.
+//!
+//! `kanoniv validate --policy policies/` runs `opa eval` on a `.rego` file or
+//! a directory of them (with any data files OPA loads alongside), with the
+//! input
+//!
+//! ```json
+//! { "spec": { ... }, "plan": { ... } }
+//! ```
+//!
+//! `spec` is the parsed spec and `plan` the `kanoniv plan` result, `null`
+//! when the spec has errors a plan cannot be made with. Policies live in
+//! package `kanoniv` or a package beneath it, and raise findings from `deny`
+//! (errors) and `warn` (warnings) rules:
+//!
+//! ```rego
+//! package kanoniv.pii
+//!
+//! deny contains msg if {
+//!     some rule in input.spec.rules
+//!     rule.field == "ssn"
+//!     rule.type == "fuzzy"
+//!     msg := sprintf("rule %s compares ssn fuzzily", [rule.name])
+//! }
+//!
+//! warn contains {"code": "CORP_RISK", "msg": "high risk", "path": "decision"} if {
+//!     input.plan.risk_score > 50
+//! }
+//! ```
+//!
+//! A finding is a message, or an object with `msg` and optionally `code`,
+//! `path` and `suggestion`. Without a code it is `KNV0132`; a policy cannot
+//! reuse another built-in code. Findings are located, waived and set by a
+//! severity policy like built-in ones.
+//!
+//! The `opa` executable is `KANONIV_OPA` if set, else `opa` on `PATH`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::commands::plan::PlanResult;
+use crate::diagnostics::{codes, Diagnostic};
+
+/// Environment variable naming the `opa` executable.
+pub const OPA_VAR: &str = "KANONIV_OPA";
+
+/// The document whose `deny` and `warn` rules are read.
+pub const QUERY: &str = "data.kanoniv";
+
+/// Rego policies to evaluate with an `opa` executable.
+#[derive(Debug, Clone)]
+pub struct OpaPolicies {
+    path: PathBuf,
+    opa: PathBuf,
+}
+
+impl OpaPolicies {
+    /// The policies at `path`, a `.rego` file or a directory OPA loads
+    /// recursively, evaluated with the `opa` from the environment.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            bail!("Policy path not found: {}", path.display());
+        }
+        let opa = std::env::var_os(OPA_VAR)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("opa"));
+        Ok(OpaPolicies {
+            path: path.to_path_buf(),
+            opa,
+        })
+    }
+
+    /// These policies, evaluated with the `opa` executable at `opa`.
+    pub fn with_executable(mut self, opa: impl Into<PathBuf>) -> Self {
+        self.opa = opa.into();
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The findings of the policies on `spec` and its `plan`.
+    pub fn evaluate(&self, spec: &Value, plan: Option<&PlanResult>) -> Result<Vec<Diagnostic>> {
+        let input = json!({ "spec": spec, "plan": plan });
+        let mut child = Command::new(&self.opa)
+            .args(["eval", "--format", "json", "--stdin-input", "--data"])
+            .arg(&self.path)
+            .arg(QUERY)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| {
+                format!(
+                    "Failed to run {} (install OPA, or name it with {})",
+                    self.opa.display(),
+                    OPA_VAR
+                )
+            })?;
+        let written = child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(serde_json::to_string(&input)?.as_bytes());
+        let output = child.wait_with_output()?;
+        let answer: Option<Value> = serde_json::from_slice(&output.stdout).ok();
+        if !output.status.success() {
+            // OPA reports compile and evaluation errors as JSON on stdout.
+            let errors: Vec<String> = answer
+                .as_ref()
+                .and_then(|a| a.get("errors"))
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(describe_error)
+                .collect();
+            let message = match errors.is_empty() {
+                true => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                false => errors.join("; "),
+            };
+            bail!(
+                "OPA failed to evaluate {} ({}): {}",
+                self.path.display(),
+                output.status,
+                message
+            );
+        }
+        if written.is_err() && output.stdout.is_empty() {
+            bail!("{} did not read the policy input", self.opa.display());
+        }
+        let answer = answer.ok_or_else(|| anyhow!("OPA answered with invalid JSON"))?;
+        // An undefined document (no `kanoniv` package) has no result.
+        let document = answer
+            .pointer("/result/0/expressions/0/value")
+            .cloned()
+            .unwrap_or(Value::Null);
+        findings(&document)
+            .map_err(|e| anyhow!("Invalid finding from {}: {:#}", self.path.display(), e))
+    }
+}
+
+/// The findings in a `data.kanoniv` document: its `deny` and `warn` sets and
+/// those of the packages beneath it, in package order.
+pub fn findings(document: &Value) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    collect(document, &mut diagnostics)?;
+    Ok(diagnostics)
+}
+
+fn collect(document: &Value, diagnostics: &mut Vec<Diagnostic>) -> Result<()> {
+    let Some(map) = document.as_object() else {
+        return Ok(());
+    };
+    for (rule, severity) in [("deny", "error"), ("warn", "warning")] {
+        for finding in map
+            .get(rule)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            diagnostics.push(finding_diagnostic(finding, severity)?);
+        }
+    }
+    for (name, package) in map {
+        if name != "deny" && name != "warn" {
+            collect(package, diagnostics)?;
+        }
+    }
+    Ok(())
+}
+
+fn finding_diagnostic(finding: &Value, severity: &str) -> Result<Diagnostic> {
+    let text = |key: &str| finding.get(key).and_then(Value::as_str);
+    let message = match finding {
+        Value::String(message) => message.as_str(),
+        _ => text("msg")
+            .or_else(|| text("message"))
+            .ok_or_else(|| anyhow!("expected a message or an object with msg, got {}", finding))?,
+    };
+    let code = text("code")
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .unwrap_or(codes::POLICY_DENIAL);
+    if code != codes::POLICY_DENIAL && codes::lookup(code).is_some() {
+        bail!("code {} is a built-in diagnostic code", code);
+    }
+    let path = text("path").unwrap_or("");
+    let diagnostic = match severity {
+        "error" => Diagnostic::error(code, path, message),
+        _ => Diagnostic::warning(code, path, message),
+    };
+    Ok(match text("suggestion") {
+        Some(suggestion) => diagnostic.with_suggestion(suggestion),
+        None => diagnostic,
+    })
+}
+
+fn describe_error(error: &Value) -> String {
+    let message = error
+        .get("message")
+        .and_then(Value::as_str)
+        .unwrap_or("error");
+    let location = error.get("location").and_then(|l| {
+        Some(format!(
+            "{}:{}",
+            l.get("file")?.as_str()?,
+            l.get("row")?.as_u64()?
+        ))
+    });
+    match location {
+        Some(location) => format!("{}: {}", location, message),
+        None => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Severity;
+
+    #[test]
+    fn test_rego_policy_findings() {
+        use crate::opa::{findings, OpaPolicies};
+        use serde_json::json;
+
+        let document = json!({
+            "deny": ["specs must declare blocking keys"],
+            "allow": true,
+            "pii": {
+                "warn": [{"code": "CORP_PII", "msg": "email is not masked", "path": "sources[0].attributes.email", "suggestion": "Mask it."}],
+            },
+        });
+        let diagnostics = findings(&document).unwrap();
+        let summary: Vec<(&str, Severity, &str, &str)> = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.code.as_str(),
+                    d.severity,
+                    d.path.as_deref().unwrap_or(""),
+                    d.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "KNV0132",
+                    Severity::Error,
+                    "",
+                    "specs must declare blocking keys"
+                ),
+                (
+                    "CORP_PII",
+                    Severity::Warning,
+                    "sources[0].attributes.email",
+                    "email is not masked"
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[1].suggestion.as_deref(), Some("Mask it."));
+        // An undefined document (no kanoniv package) raises nothing.
+        assert!(findings(&serde_json::Value::Null).unwrap().is_empty());
+        assert!(findings(&json!({"deny": [{"code": "KNV0101", "msg": "shadowing"}]})).is_err());
+        assert!(findings(&json!({"deny": [42]})).is_err());
+
+        assert!(OpaPolicies::load(std::path::Path::new("no/such/policies")).is_err());
+        let missing = OpaPolicies::load(std::path::Path::new("tests/fixtures"))
+            .unwrap()
+            .with_executable("no-such-opa");
+        assert!(missing.evaluate(&json!({}), None).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! OpenLineage events for identity resolution runs.
+//!
+//! A run reports itself to a lineage catalog (Marquez, DataHub, ...) as
+//! OpenLineage `RunEvent`s: `START` as it begins, then `COMPLETE` or `FAIL`.
+//! The job is the spec's entity and identity version
+//! (`customer.retail_v1.0`), the datasets are what the run reads and writes,
+//! and a `kanoniv` run facet carries the plan hash and a summary of the
+//! plan's risk flags.
+//!
+//! `kanoniv cluster` and `kanoniv golden-records` emit events when
+//! `OPENLINEAGE_URL` is set, as do the PySpark and Kafka jobs `kanoniv
+//! compile` generates. An `http(s)` URL takes each event as a `POST` to
+//! `/api/v1/lineage`, with `OPENLINEAGE_API_KEY` as a bearer token; any other
+//! value is a file the events are appended to, one JSON object per line.
+//! `OPENLINEAGE_NAMESPACE` names the jobs' namespace (`kanoniv` if unset).
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::hash_map::RandomState;
+use std::fs::OpenOptions;
+use std::hash::BuildHasher;
+use std::io::Write;
+use std::path::Path;
+
+use crate::clock;
+use crate::commands::compile::compile_to_ir;
+use crate::commands::plan::{generate_plan, PlanResult};
+use crate::ir::{Ir, IrSource};
+use crate::parser;
+
+/// Environment variable naming where events go.
+pub const URL_ENV: &str = "OPENLINEAGE_URL";
+
+/// Environment variable naming the jobs' namespace.
+pub const NAMESPACE_ENV: &str = "OPENLINEAGE_NAMESPACE";
+
+/// Environment variable holding a bearer token for an HTTP backend.
+pub const API_KEY_ENV: &str = "OPENLINEAGE_API_KEY";
+
+pub const DEFAULT_NAMESPACE: &str = "kanoniv";
+
+/// Path an HTTP backend takes events on, relative to `OPENLINEAGE_URL`.
+pub const ENDPOINT: &str = "api/v1/lineage";
+
+pub const PRODUCER: &str = concat!(
+    "https://github.com/kanoniv/kanoniv/tree/v",
+    env!("CARGO_PKG_VERSION")
+);
+
+pub const SCHEMA_URL: &str = "https://openlineage.io/spec/2-0-2/OpenLineage.json#/$defs/RunEvent";
+
+pub const FACET_SCHEMA_URL: &str = "https://oss.kanoniv.com/schema/openlineage-facet.json";
+
+pub const ERROR_FACET_SCHEMA_URL: &str =
+    "https://openlineage.io/spec/facets/1-0-1/ErrorMessageRunFacet.json#/$defs/ErrorMessageRunFacet";
+
+/// Severities the risk summary counts, most severe first.
+const SEVERITIES: &[&str] = &["critical", "high", "medium", "low"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum EventType {
+    Start,
+    Complete,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunEvent {
+    pub event_type: EventType,
+    pub event_time: String,
+    pub run: Run,
+    pub job: Job,
+    pub inputs: Vec<Dataset>,
+    pub outputs: Vec<Dataset>,
+    pub producer: String,
+    #[serde(rename = "schemaURL")]
+    pub schema_url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Run {
+    pub run_id: String,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub facets: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub namespace: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dataset {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl Dataset {
+    /// A file, named by its absolute path.
+    pub fn file(path: &Path) -> Self {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        Dataset {
+            namespace: "file".to_string(),
+            name: path.display().to_string(),
+        }
+    }
+
+    /// A source's table, in the namespace of its system.
+    pub fn source(source: &IrSource) -> Self {
+        Dataset {
+            namespace: source.system.clone().unwrap_or_else(|| source.name.clone()),
+            name: source.table.clone().unwrap_or_else(|| source.name.clone()),
+        }
+    }
+}
+
+/// One run of a spec's job; its events share the run id, job, datasets and
+/// run facets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineageRun {
+    pub run_id: String,
+    pub job: Job,
+    pub inputs: Vec<Dataset>,
+    pub outputs: Vec<Dataset>,
+    pub facets: Map<String, Value>,
+}
+
+impl LineageRun {
+    /// A new run of `ir`'s job in `namespace`, with `plan`'s facet and no
+    /// datasets yet.
+    pub fn new(ir: &Ir, plan: &PlanResult, namespace: &str) -> Self {
+        LineageRun {
+            run_id: run_id(),
+            job: Job {
+                namespace: namespace.to_string(),
+                name: job_name(ir),
+            },
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            facets: Map::from_iter([("kanoniv".to_string(), plan_facet(plan))]),
+        }
+    }
+
+    pub fn event(&self, event_type: EventType) -> RunEvent {
+        RunEvent {
+            event_type,
+            event_time: clock::now(),
+            run: Run {
+                run_id: self.run_id.clone(),
+                facets: self.facets.clone(),
+            },
+            job: self.job.clone(),
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+            producer: PRODUCER.to_string(),
+            schema_url: SCHEMA_URL.to_string(),
+        }
+    }
+
+    /// The `FAIL` event, carrying `message` in an `errorMessage` facet.
+    pub fn fail(&self, message: &str) -> RunEvent {
+        let mut event = self.event(EventType::Fail);
+        event.run.facets.insert(
+            "errorMessage".to_string(),
+            json!({
+                "_producer": PRODUCER,
+                "_schemaURL": ERROR_FACET_SCHEMA_URL,
+                "message": message,
+                "programmingLanguage": "rust",
+            }),
+        );
+        event
+    }
+}
+
+/// The job a spec's runs belong to: `<entity>.<identity_version>`, or the
+/// entity alone for an unversioned spec.
+pub fn job_name(ir: &Ir) -> String {
+    match &ir.identity_version {
+        Some(version) => format!("{}.{}", ir.entity_name(), version),
+        None => ir.entity_name().to_string(),
+    }
+}
+
+/// The `kanoniv` run facet: the plan hash and identity version, and the
+/// plan's risk score and unwaived flags by severity.
+pub fn plan_facet(plan: &PlanResult) -> Value {
+    let risks: Map<String, Value> = SEVERITIES
+        .iter()
+        .map(|&severity| {
+            let count = plan
+                .risk_flags
+                .iter()
+                .filter(|flag| flag.severity == severity)
+                .count();
+            (severity.to_string(), json!(count))
+        })
+        .collect();
+    json!({
+        "_producer": PRODUCER,
+        "_schemaURL": FACET_SCHEMA_URL,
+        "planHash": plan.plan_hash,
+        "identityVersion": plan.identity_version,
+        "riskScore": plan.risk_score,
+        "risks": risks,
+        "riskCodes": plan.risk_flags.iter().map(|flag| &flag.code).collect::<Vec<_>>(),
+    })
+}
+
+/// A UUIDv7: the time in milliseconds, then random bits.
+pub fn run_id() -> String {
+    let millis = clock::now_millis() as u128 & 0xffff_ffff_ffff;
+    // A fresh `RandomState` is randomly keyed.
+    let state = RandomState::new();
+    let (a, b) = (state.hash_one(0u8) as u128, state.hash_one(1u8) as u128);
+    let bits =
+        millis << 80 | 0x7 << 76 | (a & 0xfff) << 64 | 0b10 << 62 | b & 0x3fff_ffff_ffff_ffff;
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Where events go: an HTTP backend or a JSON Lines file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transport {
+    pub target: String,
+    pub api_key: Option<String>,
+}
+
+impl Transport {
+    /// The transport `OPENLINEAGE_URL` names; `None` when it is unset.
+    pub fn from_env() -> Option<Self> {
+        let target = std::env::var(URL_ENV).ok().filter(|t| !t.is_empty())?;
+        Some(Transport {
+            target,
+            api_key: std::env::var(API_KEY_ENV).ok().filter(|k| !k.is_empty()),
+        })
+    }
+
+    pub fn emit(&self, event: &RunEvent) -> Result<()> {
+        let body = serde_json::to_string(event)?;
+        if self.target.starts_with("http://") || self.target.starts_with("https://") {
+            return self.post(body);
+        }
+        let path = self.target.strip_prefix("file://").unwrap_or(&self.target);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to write file: {}", path))?;
+        writeln!(file, "{}", body).with_context(|| format!("Failed to write file: {}", path))
+    }
+
+    #[cfg(feature = "remote")]
+    fn post(&self, body: String) -> Result<()> {
+        let url = format!("{}/{}", self.target.trim_end_matches('/'), ENDPOINT);
+        let mut request = reqwest::blocking::Client::new()
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        request
+            .send()
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to call {}", url))?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "remote"))]
+    fn post(&self, _body: String) -> Result<()> {
+        anyhow::bail!(
+            "kanoniv was built without the `remote` feature; cannot send events over HTTP"
+        )
+    }
+}
+
+/// Run `f` as a run of `yaml`'s job, reported to the transport
+/// `OPENLINEAGE_URL` names: `START` before it, then `COMPLETE` or `FAIL` by
+/// its result. Without `OPENLINEAGE_URL`, or for a spec that does not plan,
+/// `f` just runs. Events that cannot be delivered go to `warn`; they never
+/// fail the run.
+pub fn observe<T>(
+    yaml: &str,
+    inputs: Vec<Dataset>,
+    outputs: Vec<Dataset>,
+    warn: impl Fn(String),
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let Some(transport) = Transport::from_env() else {
+        return f();
+    };
+    let Ok(run) = lineage_run(yaml, inputs, outputs) else {
+        return f();
+    };
+    let emit = |event: RunEvent| {
+        if let Err(e) = transport.emit(&event) {
+            warn(format!("OpenLineage: {:#}", e));
+        }
+    };
+    emit(run.event(EventType::Start));
+    let result = f();
+    match &result {
+        Ok(_) => emit(run.event(EventType::Complete)),
+        Err(e) => emit(run.fail(&format!("{:#}", e))),
+    }
+    result
+}
+
+fn lineage_run(yaml: &str, inputs: Vec<Dataset>, outputs: Vec<Dataset>) -> Result<LineageRun> {
+    let spec = parser::parse_yaml(yaml)?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let namespace = std::env::var(NAMESPACE_ENV)
+        .ok()
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| DEFAULT_NAMESPACE.to_string());
+    let mut run = LineageRun::new(&ir, &generate_plan(yaml)?, &namespace);
+    run.inputs = inputs;
+    run.outputs = outputs;
+    Ok(run)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, MINIMAL};
+
+    #[test]
+    fn test_openlineage_run_events() {
+        use crate::generate_plan;
+        use crate::openlineage::{run_id, Dataset, EventType, LineageRun, Transport, SCHEMA_URL};
+
+        let ir = compiled_ir(MINIMAL);
+        let plan = generate_plan(MINIMAL).unwrap();
+        let mut run = LineageRun::new(&ir, &plan, "identity");
+        run.inputs = ir.sources.iter().map(Dataset::source).collect();
+        assert_eq!(run.job.name, "customer.retail_v1.0");
+        assert_eq!(
+            (
+                run.inputs[0].namespace.as_str(),
+                run.inputs[0].name.as_str()
+            ),
+            ("salesforce", "contacts")
+        );
+
+        let start = serde_json::to_value(run.event(EventType::Start)).unwrap();
+        assert_eq!(start["eventType"], "START");
+        assert_eq!(start["schemaURL"], SCHEMA_URL);
+        assert_eq!(start["run"]["runId"].as_str(), Some(run.run_id.as_str()));
+        let facet = &start["run"]["facets"]["kanoniv"];
+        assert_eq!(facet["planHash"].as_str(), Some(plan.plan_hash.as_str()));
+        assert_eq!(facet["riskScore"], plan.risk_score);
+        let counted: u64 = facet["risks"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|n| n.as_u64().unwrap())
+            .sum();
+        assert_eq!(counted as usize, plan.risk_flags.len());
+        let fail = run.fail("boom");
+        assert_eq!(fail.event_type, EventType::Fail);
+        assert_eq!(fail.run.facets["errorMessage"]["message"], "boom");
+
+        // Run ids are UUIDv7s and differ between runs.
+        let id = run_id();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "7");
+        assert!("89ab".contains(&id[19..20]));
+        assert_ne!(id, run_id());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let transport = Transport {
+            target: format!("file://{}", path.display()),
+            api_key: None,
+        };
+        transport.emit(&run.event(EventType::Start)).unwrap();
+        transport.emit(&run.event(EventType::Complete)).unwrap();
+        let events: Vec<crate::RunEvent> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].event_type, EventType::Complete);
+    }
+}
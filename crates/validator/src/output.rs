@@ -0,0 +1,225 @@
+//! Terminal output controller shared by all CLI commands.
+//!
+//! Honors `NO_COLOR`, `--no-color`, `--plain` (no color, ASCII symbols and
+//! text), `--quiet` and `--verbose`, and wraps summary blocks to the terminal
+//! width reported by `COLUMNS` so narrow CI logs stay readable.
+
+use colored::Colorize;
+use std::fmt::Display;
+
+/// Typographic characters in messages and their `--plain` spellings.
+const PLAIN_TEXT: &[(char, &str)] = &[
+    ('—', "-"),
+    ('→', "->"),
+    ('²', "^2"),
+    ('·', "-"),
+    ('…', "..."),
+    ('≥', ">="),
+    ('≤', "<="),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub verbosity: Verbosity,
+    pub color: bool,
+    pub plain: bool,
+    pub width: Option<usize>,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output {
+            verbosity: Verbosity::Normal,
+            color: true,
+            plain: false,
+            width: None,
+        }
+    }
+}
+
+impl Output {
+    /// Build the controller from CLI flags and the process environment.
+    pub fn from_flags(quiet: bool, verbose: bool, plain: bool, no_color: bool) -> Self {
+        let verbosity = if quiet {
+            Verbosity::Quiet
+        } else if verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        };
+        let env_no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let width = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|c| c.trim().parse::<usize>().ok())
+            .filter(|w| *w >= 20);
+
+        Output {
+            verbosity,
+            color: !(plain || no_color || env_no_color),
+            plain,
+            width,
+        }
+    }
+
+    /// Apply the color decision to the `colored` crate. Called once by the
+    /// binary. Only disabling is forced; otherwise `colored` decides from
+    /// `CLICOLOR`, `CLICOLOR_FORCE` and whether stdout is a terminal.
+    pub fn apply(&self) {
+        if !self.color {
+            colored::control::set_override(false);
+        }
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.verbosity == Verbosity::Quiet
+    }
+
+    pub fn is_verbose(&self) -> bool {
+        self.verbosity == Verbosity::Verbose
+    }
+
+    /// Informational line on stdout; suppressed by `--quiet`.
+    pub fn info(&self, msg: impl Display) {
+        if !self.is_quiet() {
+            println!("{}", self.text(&msg.to_string()));
+        }
+    }
+
+    /// Extra detail on stdout; only shown with `--verbose`.
+    pub fn detail(&self, msg: impl Display) {
+        if self.is_verbose() {
+            println!("{}", self.text(&msg.to_string()));
+        }
+    }
+
+    /// Primary command result on stdout; always printed.
+    pub fn result(&self, msg: impl Display) {
+        println!("{}", msg);
+    }
+
+    /// Warnings on stderr; suppressed by `--quiet`.
+    pub fn warn(&self, msg: impl Display) {
+        if !self.is_quiet() {
+            eprintln!("{}", self.text(&msg.to_string()));
+        }
+    }
+
+    /// Diagnostics on stderr; always printed.
+    pub fn error(&self, msg: impl Display) {
+        eprintln!("{}", msg);
+    }
+
+    pub fn ok_mark(&self) -> String {
+        if self.plain {
+            "[ok]".to_string()
+        } else {
+            "✓".green().bold().to_string()
+        }
+    }
+
+    pub fn fail_mark(&self) -> String {
+        if self.plain {
+            "[fail]".to_string()
+        } else {
+            "✗".red().bold().to_string()
+        }
+    }
+
+    pub fn warn_mark(&self) -> String {
+        if self.plain {
+            "!".to_string()
+        } else {
+            "⚠".yellow().to_string()
+        }
+    }
+
+    pub fn arrow(&self) -> String {
+        if self.plain {
+            "->".to_string()
+        } else {
+            "→".red().to_string()
+        }
+    }
+
+    pub fn dash(&self) -> &'static str {
+        if self.plain {
+            "-"
+        } else {
+            "—"
+        }
+    }
+
+    /// A message as written, or under `--plain` with its typographic
+    /// characters spelled in ASCII.
+    pub fn text(&self, msg: &str) -> String {
+        if !self.plain {
+            return msg.to_string();
+        }
+        let mut text = String::with_capacity(msg.len());
+        for c in msg.chars() {
+            match PLAIN_TEXT.iter().find(|(typographic, _)| *typographic == c) {
+                Some((_, ascii)) => text.push_str(ascii),
+                None => text.push(c),
+            }
+        }
+        text
+    }
+
+    /// Wrap a `Label:   value` block to the configured width, continuing long
+    /// values on lines indented to the value column.
+    pub fn wrap_block(&self, block: &str) -> String {
+        let block = self.text(block);
+        let Some(width) = self.width else {
+            return block;
+        };
+        block
+            .lines()
+            .map(|line| wrap_line(line, width))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn wrap_line(line: &str, width: usize) -> String {
+    if line.chars().count() <= width {
+        return line.to_string();
+    }
+
+    let leading = line.len() - line.trim_start().len();
+    let hang = line
+        .find(':')
+        .map(|i| i + 1 + line[i + 1..].len() - line[i + 1..].trim_start().len())
+        .filter(|h| *h < width / 2)
+        .unwrap_or(leading);
+    let indent = " ".repeat(hang);
+
+    let mut lines = Vec::new();
+    let mut current = line[..hang].to_string();
+    let mut current_len = current.chars().count();
+    let mut first_word = true;
+    for word in line[hang..].split_whitespace() {
+        let word_len = word.chars().count();
+        if !first_word && current_len + 1 + word_len > width {
+            lines.push(current);
+            current = indent.clone();
+            current_len = hang;
+            first_word = true;
+        }
+        if !first_word {
+            current.push(' ');
+            current_len += 1;
+        }
+        current.push_str(word);
+        current_len += word_len;
+        first_word = false;
+    }
+    lines.push(current);
+    lines.join("\n")
+}
@@ -0,0 +1,226 @@
+//! Ownership of spec sections, for routing findings to reviewers.
+//!
+//! A spec names who is responsible for each part of it:
+//!
+//! ```yaml
+//! owners:
+//!   default: "@identity-team"
+//!   rules: ["@matching-team", "@risk"]
+//!   decision: "@risk"
+//! sources:
+//!   - name: crm
+//!     owner: "@crm-stewards"
+//!     ...
+//! ```
+//!
+//! Section owners fall back to `default`. A source's owner is responsible
+//! for the source and shares ownership of every rule matching on one of its
+//! attributes. Ownership decides where findings go, so it is part of the
+//! plan hash.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::mappings;
+
+/// Routing key for findings nobody owns.
+pub const UNOWNED: &str = "unowned";
+
+/// Owner -> the findings they should review.
+pub type Routing = BTreeMap<String, Vec<RoutedFinding>>;
+
+/// A risk flag or change, attributed to a section of the spec.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutedFinding {
+    /// `risk` or `change`.
+    pub kind: String,
+    pub code: String,
+    /// Top-level section the finding concerns (`rules`, `decision`, ...).
+    pub section: String,
+    pub message: String,
+}
+
+/// The owners a spec declares.
+#[derive(Debug, Clone, Default)]
+pub struct Owners {
+    default: Vec<String>,
+    sections: BTreeMap<String, Vec<String>>,
+    /// Source name -> owners.
+    sources: BTreeMap<String, Vec<String>>,
+    /// Canonical attribute -> names of the sources mapping it.
+    attributes: BTreeMap<String, Vec<String>>,
+}
+
+impl Owners {
+    pub fn from_spec(spec: &Value) -> Self {
+        let spec = &mappings::resolve(spec);
+        let mut owners = Owners::default();
+        if let Some(declared) = spec.get("owners").and_then(|o| o.as_object()) {
+            for (section, value) in declared {
+                let names = owner_list(value);
+                if section == "default" {
+                    owners.default = names;
+                } else if !names.is_empty() {
+                    owners.sections.insert(section.clone(), names);
+                }
+            }
+        }
+
+        let sources: Vec<(String, &Value)> = match spec.get("sources") {
+            Some(Value::Array(sources)) => sources
+                .iter()
+                .map(|s| {
+                    let name = s.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+                    (name.to_string(), s)
+                })
+                .collect(),
+            Some(Value::Object(sources)) => sources.iter().map(|(k, s)| (k.clone(), s)).collect(),
+            _ => Vec::new(),
+        };
+        for (name, source) in sources {
+            let names = source.get("owner").map(owner_list).unwrap_or_default();
+            if !names.is_empty() {
+                owners.sources.insert(name.clone(), names);
+            }
+            if let Some(attributes) = source.get("attributes").and_then(|a| a.as_object()) {
+                for attribute in attributes.keys() {
+                    owners
+                        .attributes
+                        .entry(attribute.clone())
+                        .or_default()
+                        .push(name.clone());
+                }
+            }
+        }
+        owners
+    }
+
+    /// Whether the spec declares any owner at all.
+    pub fn is_empty(&self) -> bool {
+        self.default.is_empty() && self.sections.is_empty() && self.sources.is_empty()
+    }
+
+    /// Owners of a top-level section; `sources` includes every source owner.
+    pub fn for_section(&self, section: &str) -> Vec<String> {
+        let mut owners = self.sections.get(section).unwrap_or(&self.default).clone();
+        if section == "sources" {
+            owners.extend(self.sources.values().flatten().cloned());
+        }
+        dedup(owners)
+    }
+
+    /// Stewards of the source called `name`.
+    pub fn for_source(&self, name: &str) -> Vec<String> {
+        self.sources.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Owners of a rule matching on `field`: the `rules` owners and the
+    /// owners of the sources that map the field.
+    pub fn for_rule_field(&self, field: &str) -> Vec<String> {
+        let mut owners = self.for_section("rules");
+        for source in self.attributes.get(field).into_iter().flatten() {
+            owners.extend(self.sources.get(source).into_iter().flatten().cloned());
+        }
+        dedup(owners)
+    }
+
+    /// Group findings by owner; findings without an owner go to `UNOWNED`.
+    pub fn route(
+        &self,
+        findings: impl IntoIterator<Item = (Vec<String>, RoutedFinding)>,
+    ) -> Routing {
+        let mut routing = Routing::new();
+        for (owners, finding) in findings {
+            if owners.is_empty() {
+                routing
+                    .entry(UNOWNED.to_string())
+                    .or_default()
+                    .push(finding);
+                continue;
+            }
+            for owner in owners {
+                routing.entry(owner).or_default().push(finding.clone());
+            }
+        }
+        routing
+    }
+}
+
+/// An owner is a string or a list of strings.
+fn owner_list(value: &Value) -> Vec<String> {
+    let names = match value {
+        Value::String(name) => vec![name.trim().to_string()],
+        Value::Array(names) => names
+            .iter()
+            .filter_map(|n| n.as_str())
+            .map(|n| n.trim().to_string())
+            .collect(),
+        _ => Vec::new(),
+    };
+    names.into_iter().filter(|n| !n.is_empty()).collect()
+}
+
+fn dedup(mut owners: Vec<String>) -> Vec<String> {
+    let mut seen = Vec::new();
+    owners.retain(|o| {
+        let new = !seen.contains(o);
+        if new {
+            seen.push(o.clone());
+        }
+        new
+    });
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_plan_routes_risk_flags_to_owners() {
+        let owned = MINIMAL.replace(
+            "    id: contact_id\n",
+            "    id: contact_id\n    owner: \"@crm\"\n",
+        ) + "owners:\n  default: \"@identity\"\n  decision: [\"@risk\"]\n";
+        let plan = crate::generate_plan(&owned).unwrap();
+        let codes = |owner: &str| -> Vec<String> {
+            plan.routing[owner].iter().map(|f| f.code.clone()).collect()
+        };
+        assert_eq!(codes("@risk"), ["NO_REVIEW_THRESHOLD"]);
+        assert_eq!(codes("@crm"), ["SINGLE_SOURCE"]);
+        assert!(codes("@identity").contains(&"NO_BLOCKING".to_string()));
+        assert!(!codes("@identity").contains(&"NO_REVIEW_THRESHOLD".to_string()));
+        // Every unwaived flag is routed; ownership, which decides the routing,
+        // changes the hash.
+        let routed: usize = plan.routing.values().map(Vec::len).sum();
+        assert_eq!(routed, plan.risk_flags.len() + 1);
+        assert_ne!(
+            plan.plan_hash,
+            crate::generate_plan(MINIMAL).unwrap().plan_hash
+        );
+        assert!(crate::generate_plan(MINIMAL).unwrap().routing.is_empty());
+
+        // A custom risk's section picks its owners; sections without owners
+        // fall back to nobody.
+        let rules = crate::CustomRisks::from_yaml(
+        "risks:\n  - code: NEEDS_TEMPORAL\n    severity: low\n    message: m\n    section: decision\n    when:\n      missing: temporal\n",
+    )
+    .unwrap();
+        let options = crate::PlanOptions {
+            custom_risks: rules,
+            ..Default::default()
+        };
+        let plan = crate::generate_plan_with(
+            &(MINIMAL.to_string() + "owners:\n  decision: \"@risk\"\n"),
+            &options,
+        )
+        .unwrap();
+        assert!(plan.routing["@risk"]
+            .iter()
+            .any(|f| f.code == "NEEDS_TEMPORAL"));
+        assert!(plan.routing[crate::owners::UNOWNED]
+            .iter()
+            .any(|f| f.code == "NO_BLOCKING"));
+    }
+}
@@ -1,7 +1,486 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde_json::Value;
+use std::collections::HashMap;
 
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::workspace::Workspace;
+
+/// Parse a spec. Content with several `---`-separated documents is a
+/// workspace (see `workspace`), returned as `Workspace::to_value`.
 pub fn parse_yaml(content: &str) -> Result<Value> {
-    let value: Value = serde_yaml::from_str(content)?;
-    Ok(value)
+    let documents = split_documents(content);
+    if documents.len() <= 1 {
+        let value: Value = serde_yaml::from_str(content)?;
+        return Ok(value);
+    }
+    let mut values = Vec::new();
+    for (start, text) in documents {
+        let value: Value = serde_yaml::from_str(text)
+            .map_err(|e| anyhow!(shift_lines(&e.to_string(), start)))?;
+        if !value.is_null() {
+            values.push(value);
+        }
+    }
+    Ok(documents_value(values))
+}
+
+/// Split `content` at `---` document markers into `(first line, text)` per
+/// document, the marker included. Lines before the first marker are a
+/// document of their own.
+pub fn split_documents(content: &str) -> Vec<(usize, &str)> {
+    let mut documents = Vec::new();
+    let (mut start, mut start_line, mut offset) = (0, 0, 0);
+    for (idx, line) in content.split_inclusive('\n').enumerate() {
+        let marker = line
+            .strip_prefix("---")
+            .is_some_and(|rest| rest.trim().is_empty() || rest.starts_with([' ', '\t']));
+        if marker && offset > 0 {
+            documents.push((start_line, &content[start..offset]));
+            (start, start_line) = (offset, idx);
+        }
+        offset += line.len();
+    }
+    documents.push((start_line, &content[start..]));
+    documents
+}
+
+/// One spec, or a workspace when there are several documents.
+fn documents_value(mut values: Vec<Value>) -> Value {
+    if values.len() == 1 {
+        values.pop().expect("one document")
+    } else {
+        Workspace::from_documents(values).to_value()
+    }
+}
+
+/// Parse YAML and keep the location of every block-style node so findings
+/// can point back at the source.
+pub fn parse_yaml_with_locations(content: &str) -> Result<(Value, SourceMap)> {
+    let value = parse_yaml(content)?;
+    Ok((value, SourceMap::from_yaml(content)))
+}
+
+/// Turn a YAML syntax error into a located diagnostic.
+pub fn syntax_diagnostic(err: &serde_yaml::Error) -> Diagnostic {
+    section_syntax_diagnostic(err, 0)
+}
+
+/// Result of `parse_yaml_recovering`.
+#[derive(Debug, Clone)]
+pub struct Recovered {
+    /// Every top-level section that parsed.
+    pub value: Value,
+    /// One syntax diagnostic per broken section.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Top-level keys whose section failed to parse.
+    pub failed_sections: Vec<String>,
+}
+
+/// Parse YAML, and if the document is broken, retry each top-level section
+/// on its own so one mistake does not hide errors in the rest of the spec.
+///
+/// Sections are parsed in isolation, so an alias to an anchor defined in
+/// another section is reported as an error in recovery mode.
+pub fn parse_yaml_recovering(content: &str) -> Recovered {
+    let documents = split_documents(content);
+    if documents.len() > 1 {
+        return recover_documents(&documents);
+    }
+    let err = match serde_yaml::from_str::<Value>(content) {
+        Ok(value) => {
+            return Recovered {
+                value,
+                diagnostics: Vec::new(),
+                failed_sections: Vec::new(),
+            }
+        }
+        Err(err) => err,
+    };
+
+    let mut map = serde_json::Map::new();
+    let mut diagnostics = Vec::new();
+    let mut failed_sections = Vec::new();
+    for (name, start, text) in top_level_sections(content) {
+        match serde_yaml::from_str::<Value>(&text) {
+            Ok(Value::Object(entries)) => map.extend(entries),
+            Ok(Value::Null) => {}
+            Ok(_) => {
+                diagnostics.push(
+                    Diagnostic::error(
+                        codes::YAML_SYNTAX,
+                        "",
+                        "Invalid YAML: expected a `key: value` mapping at the top level",
+                    )
+                    .with_span(Span {
+                        line: start + 1,
+                        column: 1,
+                    }),
+                );
+                failed_sections.push(name);
+            }
+            Err(e) => {
+                diagnostics.push(section_syntax_diagnostic(&e, start));
+                failed_sections.push(name);
+            }
+        }
+    }
+
+    // Every section parses alone: the problem spans sections (duplicate
+    // top-level key, cross-section alias), so report the original error.
+    if diagnostics.is_empty() {
+        diagnostics.push(syntax_diagnostic(&err));
+    }
+
+    Recovered {
+        value: Value::Object(map),
+        diagnostics,
+        failed_sections,
+    }
+}
+
+/// `parse_yaml_recovering` for a workspace: documents that parse are kept,
+/// one syntax diagnostic per document that does not.
+fn recover_documents(documents: &[(usize, &str)]) -> Recovered {
+    let mut values = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (start, text) in documents {
+        match serde_yaml::from_str::<Value>(text) {
+            Ok(Value::Null) => {}
+            Ok(value) => values.push(value),
+            Err(e) => diagnostics.push(section_syntax_diagnostic(&e, *start)),
+        }
+    }
+    Recovered {
+        value: documents_value(values),
+        diagnostics,
+        failed_sections: Vec::new(),
+    }
+}
+
+/// Split a document at unindented keys into `(key, first line, text)`.
+/// Lines before the first key form a section with an empty name.
+fn top_level_sections(content: &str) -> Vec<(String, usize, String)> {
+    let mut sections: Vec<(String, usize, String)> = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let starts_section = line
+            .chars()
+            .next()
+            .is_some_and(|c| !c.is_whitespace() && c != '#' && c != '-')
+            || line.starts_with("---")
+            || line.starts_with("...");
+        if starts_section || sections.is_empty() {
+            let name = split_key(strip_comment(line))
+                .map(|(key, _)| key)
+                .unwrap_or_default();
+            sections.push((name, idx, String::new()));
+        }
+        let text = &mut sections.last_mut().expect("section pushed above").2;
+        text.push_str(line);
+        text.push('\n');
+    }
+    sections
+}
+
+fn section_syntax_diagnostic(err: &serde_yaml::Error, line_offset: usize) -> Diagnostic {
+    let message = format!(
+        "Invalid YAML: {}",
+        shift_lines(&err.to_string(), line_offset)
+    );
+    let diagnostic = Diagnostic::error(codes::YAML_SYNTAX, "", message);
+    match err.location() {
+        Some(loc) => diagnostic.with_span(Span {
+            line: loc.line() + line_offset,
+            column: loc.column(),
+        }),
+        None => diagnostic,
+    }
+}
+
+/// Rewrite `line N` references in a serde_yaml message by `offset`.
+fn shift_lines(message: &str, offset: usize) -> String {
+    if offset == 0 {
+        return message.to_string();
+    }
+    let mut result = String::new();
+    let mut rest = message;
+    while let Some(pos) = rest.find("line ") {
+        let (head, tail) = rest.split_at(pos + 5);
+        result.push_str(head);
+        let digits = tail.len() - tail.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        match tail[..digits].parse::<usize>() {
+            Ok(line) => result.push_str(&(line + offset).to_string()),
+            Err(_) => result.push_str(&tail[..digits]),
+        }
+        rest = &tail[digits..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Map from YAML path (`rules[0].field`) to where that node starts.
+///
+/// serde_yaml does not expose marks on deserialized values, so this scans the
+/// block structure line by line. Flow collections (`{a: 1}`, `[x, y]`) are
+/// located at their parent key.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    spans: HashMap<String, Span>,
+}
+
+#[derive(Debug)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug)]
+struct Frame {
+    column: usize,
+    segment: Segment,
+    /// Mapping key whose value continues on the following lines.
+    open: bool,
+}
+
+impl Frame {
+    /// Whether a node starting at `column` ends this frame. A sequence item at
+    /// the same column either continues this sequence or, YAML allowing
+    /// `key:\n- item`, is the value of an open key.
+    fn closed_by(&self, column: usize, is_item: bool) -> bool {
+        if self.column != column {
+            return self.column > column;
+        }
+        match self.segment {
+            Segment::Index(_) => !is_item,
+            Segment::Key(_) => !(is_item && self.open),
+        }
+    }
+}
+
+impl SourceMap {
+    pub fn from_yaml(content: &str) -> Self {
+        let mut spans = HashMap::new();
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut block_scalar: Option<usize> = None;
+
+        for (line_idx, raw) in content.lines().enumerate() {
+            let indent = raw.len() - raw.trim_start_matches(' ').len();
+            let text = strip_comment(&raw[indent..]);
+            if let Some(parent) = block_scalar {
+                if text.is_empty() || indent > parent {
+                    continue;
+                }
+                block_scalar = None;
+            }
+            if text.is_empty() || text == "---" || text == "..." {
+                continue;
+            }
+
+            let mut column = indent;
+            let mut rest = text;
+            loop {
+                let is_item = rest == "-" || rest.starts_with("- ");
+                while stack.last().is_some_and(|f| f.closed_by(column, is_item)) {
+                    stack.pop();
+                }
+
+                if is_item {
+                    let index = match stack.last() {
+                        Some(Frame {
+                            column: c,
+                            segment: Segment::Index(i),
+                            ..
+                        }) if *c == column => {
+                            let next = i + 1;
+                            stack.pop();
+                            next
+                        }
+                        _ => 0,
+                    };
+                    stack.push(Frame {
+                        column,
+                        segment: Segment::Index(index),
+                        open: false,
+                    });
+                    spans.insert(
+                        path_of(&stack),
+                        Span {
+                            line: line_idx + 1,
+                            column: column + 1,
+                        },
+                    );
+                    let after = rest[1..].trim_start_matches(' ');
+                    column += rest.len() - after.len();
+                    rest = after;
+                    if rest.is_empty() {
+                        break;
+                    }
+                    continue;
+                }
+
+                if let Some((key, value)) = split_key(rest) {
+                    stack.push(Frame {
+                        column,
+                        segment: Segment::Key(key),
+                        open: value.is_empty(),
+                    });
+                    spans.insert(
+                        path_of(&stack),
+                        Span {
+                            line: line_idx + 1,
+                            column: column + 1,
+                        },
+                    );
+                    if value.starts_with('|') || value.starts_with('>') {
+                        block_scalar = Some(indent);
+                    }
+                }
+                break;
+            }
+        }
+
+        SourceMap { spans }
+    }
+
+    pub fn get(&self, path: &str) -> Option<Span> {
+        self.spans.get(path).copied()
+    }
+
+    /// Span of `path`, or of its closest ancestor present in the source.
+    pub fn nearest(&self, path: &str) -> Option<Span> {
+        let mut current = path;
+        loop {
+            if let Some(span) = self.get(current) {
+                return Some(span);
+            }
+            let cut = current.rfind(['.', '['])?;
+            current = &current[..cut];
+        }
+    }
+
+    /// All located paths, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Span)> {
+        self.spans.iter().map(|(path, span)| (path.as_str(), *span))
+    }
+
+    /// The innermost node starting on `line` at or before `column`.
+    pub fn path_at(&self, line: usize, column: usize) -> Option<&str> {
+        self.iter()
+            .filter(|(_, span)| span.line == line && span.column <= column)
+            .max_by_key(|(_, span)| span.column)
+            .map(|(path, _)| path)
+    }
+}
+
+fn path_of(stack: &[Frame]) -> String {
+    let mut path = String::new();
+    for frame in stack {
+        match &frame.segment {
+            Segment::Key(key) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+            Segment::Index(i) => path.push_str(&format!("[{}]", i)),
+        }
+    }
+    path
+}
+
+/// Split `key: value` on the first unquoted `: ` (or trailing `:`).
+fn split_key(text: &str) -> Option<(String, &str)> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') if i == 0 => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '{') | (None, '[') if i == 0 => return None,
+            (None, ':') => {
+                let after = &text[i + 1..];
+                if after.is_empty() || after.starts_with(' ') {
+                    let key = text[..i].trim().trim_matches(['"', '\'']).to_string();
+                    return Some((key, after.trim()));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+pub(crate) fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') if prev == ' ' => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if prev == ' ' || prev == '\t' => return text[..i].trim_end(),
+            _ => {}
+        }
+        prev = c;
+    }
+    text.trim_end()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+    use crate::{diagnose_yaml, parse_yaml_recovering, SourceMap};
+
+    #[test]
+    fn test_source_map_locates_nested_nodes() {
+        let yaml = "\
+api_version: kanoniv/v2
+entity:
+  name: customer
+sources:
+- name: crm   # same-indent sequence
+  attributes:
+    email: email
+rules:
+  - name: email_exact
+    type: exact
+  - name: \"fuzzy\"
+    field: emial
+blocking:
+  keys:
+    - field: email
+      transform: lowercase
+";
+        let map = SourceMap::from_yaml(yaml);
+        let at = |path: &str| map.get(path).map(|s| (s.line, s.column));
+        assert_eq!(at("entity.name"), Some((3, 3)));
+        assert_eq!(at("sources[0].name"), Some((5, 3)));
+        assert_eq!(at("sources[0].attributes.email"), Some((7, 5)));
+        assert_eq!(at("rules[1]"), Some((11, 3)));
+        assert_eq!(at("rules[1].field"), Some((12, 5)));
+        assert_eq!(at("blocking.keys[0].transform"), Some((16, 7)));
+        assert_eq!(map.nearest("rules[1].threshold").map(|s| s.line), Some(11));
+    }
+
+    #[test]
+    fn test_recovering_parse_reports_every_broken_section() {
+        let yaml = MINIMAL
+            .replace("table: contacts", "table: \"contacts")
+            .replace("weight: 1.0", "weight: 4.0")
+            + "blocking:\n  keys: [email, {field: phone\n";
+
+        let recovered = parse_yaml_recovering(&yaml);
+        assert_eq!(recovered.failed_sections, vec!["sources", "blocking"]);
+        assert!(recovered.value.get("rules").is_some());
+
+        let codes: Vec<(String, Option<usize>)> = diagnose_yaml(&yaml)
+            .into_iter()
+            .map(|d| (d.code, d.span.map(|s| s.line)))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                ("KNV0901".to_string(), Some(12)),
+                ("KNV0901".to_string(), Some(22)),
+                ("KNV0004".to_string(), Some(16)),
+            ]
+        );
+    }
 }
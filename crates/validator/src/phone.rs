@@ -0,0 +1,173 @@
+//! Phone number parsing and E.164 normalization.
+//!
+//! An attribute is a phone number when its `normalization` pipeline includes
+//! the `phone` normalizer, which rewrites values to E.164 (`+14155550123`).
+//! Numbers written with a country code (`+44 20 7946 0958`, or an
+//! international prefix such as `00` or `011`) carry their own country;
+//! numbers in national format (`(415) 555-0123`) are read in the section's
+//! `default_region`:
+//!
+//! ```yaml
+//! normalization:
+//!   default_region: US
+//!   fields:
+//!     phone: [phone]
+//! ```
+//!
+//! Values that are not valid numbers normalize to the empty string, so they
+//! read as missing rather than matching each other.
+
+use anyhow::{bail, Result};
+use phonenumber::country::Id;
+use phonenumber::Mode;
+use std::fmt;
+use std::str::FromStr;
+
+/// A region whose national numbering plan is known, by its ISO 3166-1
+/// alpha-2 code (`US`, `GB`, `DE`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region(Id);
+
+impl Region {
+    pub fn code(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_uppercase().parse::<Id>() {
+            Ok(id) => Ok(Region(id)),
+            Err(_) => bail!(
+                "Unknown region: '{}'. Expected an ISO 3166-1 alpha-2 code such as US, GB or DE",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// A valid phone number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phone {
+    /// The number in E.164 format.
+    pub e164: String,
+    /// Country calling code (`1`, `44`).
+    pub country_code: u16,
+    /// The region the number belongs to, if the calling code and number
+    /// identify one (`+1 416` is `CA`).
+    pub region: Option<Region>,
+}
+
+/// Parse `value` as a phone number, reading national-format numbers in
+/// `default_region`. `None` if it is not a valid number, including national
+/// numbers when no default region is given.
+pub fn parse(value: &str, default_region: Option<Region>) -> Option<Phone> {
+    let number = phonenumber::parse(default_region.map(|r| r.0), value).ok()?;
+    if !phonenumber::is_valid(&number) {
+        return None;
+    }
+    Some(Phone {
+        e164: number.format().mode(Mode::E164).to_string(),
+        country_code: number.code().value(),
+        region: number.country().id().map(Region),
+    })
+}
+
+/// `value` in E.164 format, or the empty string if it is not a valid number.
+pub fn to_e164(value: &str, default_region: Option<Region>) -> String {
+    parse(value, default_region)
+        .map(|phone| phone.e164)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Ir;
+
+    #[test]
+    fn test_phone_normalization_and_default_region() {
+        use crate::normalize::Normalization;
+        use crate::phone::{self, Region};
+        use crate::{profile_source, Sample};
+
+        let us: Region = "us".parse().unwrap();
+        assert_eq!(phone::to_e164("(201) 555-0123", Some(us)), "+12015550123");
+        assert_eq!(phone::to_e164("+1 201 555 0123", None), "+12015550123");
+        // A country code overrides the default region, and the region is inferred.
+        let london = phone::parse("+44 20 7946 0958", Some(us)).unwrap();
+        assert_eq!(
+            (london.e164.as_str(), london.country_code),
+            ("+442079460958", 44)
+        );
+        assert_eq!(london.region.map(|r| r.to_string()).as_deref(), Some("GB"));
+        assert_eq!(
+            phone::to_e164("020 7946 0958", Some("GB".parse().unwrap())),
+            "+442079460958"
+        );
+        // National numbers need a default region; invalid numbers become empty.
+        assert_eq!(phone::to_e164("(201) 555-0123", None), "");
+        assert_eq!(phone::to_e164("12", Some(us)), "");
+        assert!("XX".parse::<Region>().is_err());
+
+        let spec = "api_version: kanoniv/v2\nidentity_version: v1\nentity:\n  name: customer\n\
+        sources:\n  - name: crm\n    system: s\n    table: t\n    id: id\n    attributes:\n      mobile: cell\n      email: email\n\
+        rules:\n  - name: mobile_exact\n    type: exact\n    field: mobile\n    weight: 1.0\n\
+        blocking:\n  keys:\n    - [email]\n\
+        decision:\n  thresholds:\n    match: 0.9\n\
+        normalization:\n  default_region: US\n  fields:\n    mobile: [phone]\n";
+        let parsed = crate::parse_yaml(spec).unwrap();
+        assert!(crate::schema_diagnostics(&parsed).is_empty());
+        let normalization = Normalization::from_spec(&parsed).unwrap();
+        assert_eq!(
+            normalization.apply("mobile", "201.555.0123"),
+            "+12015550123"
+        );
+        assert_eq!(normalization.phone_fields().collect::<Vec<_>>(), ["mobile"]);
+        let ir = Ir::from_value(&crate::compile_to_ir(&parsed).unwrap()).unwrap();
+        assert_eq!(
+            ir.normalization.unwrap().default_region.as_deref(),
+            Some("US")
+        );
+
+        // `mobile` is a phone by its normalization, not its name.
+        let plan = crate::generate_plan(spec).unwrap();
+        assert!(plan
+            .risk_flags
+            .iter()
+            .any(|f| f.code == "PHONE_WITHOUT_BLOCKING"));
+        let sample =
+            Sample::from_csv("id,cell,email\n1,201-555-0123,a@x.com\n2,555,b@x.com\n").unwrap();
+        let profile = profile_source(spec, &sample, None).unwrap();
+        let mobile = profile
+            .attributes
+            .iter()
+            .find(|a| a.attribute == "mobile")
+            .unwrap();
+        assert_eq!(
+            (mobile.format.as_deref(), mobile.conformance),
+            (Some("phone"), Some(0.5))
+        );
+
+        let invalid =
+            crate::parse_yaml(&spec.replace("default_region: US", "default_region: USA")).unwrap();
+        let schema: Vec<_> = crate::schema_diagnostics(&invalid)
+            .into_iter()
+            .map(|d| (d.code, d.path.unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            schema,
+            [(
+                "KNV0007".to_string(),
+                "normalization.default_region".to_string()
+            )]
+        );
+    }
+}
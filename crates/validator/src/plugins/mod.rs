@@ -0,0 +1,319 @@
+//! Plugins: validators, risk analyzers and compile targets from outside
+//! the crate.
+//!
+//! A plugin implements `Plugin` and describes what it provides with a
+//! `PluginInfo`. Engines embedding the crate register plugins on a
+//! `PluginRegistry` and pass it to validation, planning (`PlanOptions`) and
+//! compilation. The CLI discovers out-of-process plugins: executables named
+//! `kanoniv-plugin-<name>` in `KANONIV_PLUGIN_PATH` or `PATH`, which speak a
+//! JSON protocol over stdin and stdout (see `process`).
+//!
+//! Plugin findings and flags take part like built-in ones: they are located,
+//! waived, set by a policy and scored. A built-in compile target always wins
+//! over a plugin's of the same name.
+
+pub mod process;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::commands::codegen::GeneratedFile;
+use crate::commands::plan::RiskFlag;
+use crate::diagnostics::Diagnostic;
+use crate::ir::Ir;
+
+pub use process::ProcessPlugin;
+
+/// What a plugin can provide.
+pub const CAPABILITIES: &[&str] = &["validate", "risks", "compile"];
+
+/// Prefix of plugin executables; the rest of the file name is the plugin's.
+pub const EXECUTABLE_PREFIX: &str = "kanoniv-plugin-";
+
+/// Directories searched for plugin executables before `PATH`.
+pub const PLUGIN_PATH_VAR: &str = "KANONIV_PLUGIN_PATH";
+
+/// A plugin's description of itself.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PluginInfo {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// Which of `CAPABILITIES` it provides.
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Compile targets it provides, with the `compile` capability.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+}
+
+impl PluginInfo {
+    pub fn provides(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+/// What a compile target produced: one text (printed or written to `-o`)
+/// or a set of files (written beneath the `-o` directory).
+#[derive(Debug, Clone)]
+pub enum Compiled {
+    Text(String),
+    Files(Vec<GeneratedFile>),
+}
+
+pub trait Plugin: Send + Sync {
+    fn info(&self) -> &PluginInfo;
+
+    /// Where the plugin was loaded from; `None` for one registered in-process.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Extra findings for `spec`, with the `validate` capability.
+    fn validate(&self, _spec: &Value) -> Result<Vec<Diagnostic>> {
+        Ok(Vec::new())
+    }
+
+    /// Extra plan risk flags for `spec`, with the `risks` capability.
+    fn risks(&self, _spec: &Value) -> Result<Vec<RiskFlag>> {
+        Ok(Vec::new())
+    }
+
+    /// Lower `ir` for `target`, one of the plugin's targets.
+    fn compile(&self, target: &str, _ir: &Ir) -> Result<Compiled> {
+        bail!(
+            "Plugin '{}' does not compile to '{}'",
+            self.info().name,
+            target
+        )
+    }
+}
+
+/// The plugins validation, planning and compilation consult.
+#[derive(Clone, Default)]
+pub struct PluginRegistry {
+    plugins: Vec<Arc<dyn Plugin>>,
+}
+
+impl PluginRegistry {
+    /// A registry with no plugins.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every plugin executable in `KANONIV_PLUGIN_PATH` and then `PATH`. The
+    /// first of a name wins; a plugin that fails to describe itself is an
+    /// error.
+    pub fn discover() -> Result<Self> {
+        let mut registry = Self::new();
+        for path in find_executables() {
+            registry.register(ProcessPlugin::load(&path)?);
+        }
+        Ok(registry)
+    }
+
+    /// Add `plugin`.
+    pub fn register(&mut self, plugin: impl Plugin + 'static) -> &mut Self {
+        self.plugins.push(Arc::new(plugin));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    pub fn plugins(&self) -> impl Iterator<Item = &dyn Plugin> {
+        self.plugins.iter().map(|plugin| plugin.as_ref())
+    }
+
+    /// Findings of every plugin validating specs.
+    pub fn validate(&self, spec: &Value) -> Result<Vec<Diagnostic>> {
+        let mut findings = Vec::new();
+        for plugin in self.providing("validate") {
+            findings.extend(plugin.validate(spec)?);
+        }
+        Ok(findings)
+    }
+
+    /// Risk flags of every plugin analysing risks.
+    pub fn risks(&self, spec: &Value) -> Result<Vec<RiskFlag>> {
+        let mut flags = Vec::new();
+        for plugin in self.providing("risks") {
+            flags.extend(plugin.risks(spec)?);
+        }
+        Ok(flags)
+    }
+
+    /// Compile targets plugins provide, in registration order.
+    pub fn targets(&self) -> Vec<&str> {
+        self.providing("compile")
+            .flat_map(|plugin| plugin.info().targets.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// The plugin providing compile target `target`, if any.
+    pub fn target(&self, target: &str) -> Option<&dyn Plugin> {
+        self.providing("compile")
+            .find(|plugin| plugin.info().targets.iter().any(|t| t == target))
+    }
+
+    fn providing<'a>(&'a self, capability: &'a str) -> impl Iterator<Item = &'a dyn Plugin> {
+        self.plugins()
+            .filter(move |plugin| plugin.info().provides(capability))
+    }
+}
+
+impl fmt::Debug for PluginRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.plugins().map(|p| p.info().name.as_str()).collect();
+        f.debug_struct("PluginRegistry")
+            .field("plugins", &names)
+            .finish()
+    }
+}
+
+/// Plugin executables on the search path, one per name.
+fn find_executables() -> Vec<PathBuf> {
+    let dirs = [PLUGIN_PATH_VAR, "PATH"]
+        .into_iter()
+        .filter_map(env::var_os)
+        .flat_map(|paths| env::split_paths(&paths).collect::<Vec<_>>());
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = dir.read_dir() else {
+            continue;
+        };
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+        paths.sort();
+        for path in paths {
+            let Some(name) = plugin_name(&path) else {
+                continue;
+            };
+            if is_executable(&path) && seen.insert(name) {
+                found.push(path);
+            }
+        }
+    }
+    found
+}
+
+/// `databricks` for `kanoniv-plugin-databricks` (or `.exe`).
+fn plugin_name(path: &Path) -> Option<String> {
+    let stem = match path.extension().is_some_and(|ext| ext == "exe") {
+        true => path.file_stem()?,
+        false => path.file_name()?,
+    };
+    let name = stem.to_str()?.strip_prefix(EXECUTABLE_PREFIX)?;
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+    use crate::{Ir, Severity};
+
+    #[test]
+    fn test_plugin_registry_extends_validation_plans_and_targets() {
+        use crate::{Compiled, Diagnostic, Plugin, PluginInfo, PluginRegistry, RiskFlag};
+        use serde_json::Value;
+
+        struct Databricks(PluginInfo);
+
+        impl Plugin for Databricks {
+            fn info(&self) -> &PluginInfo {
+                &self.0
+            }
+
+            fn validate(&self, spec: &Value) -> anyhow::Result<Vec<Diagnostic>> {
+                Ok(match spec.get("owners") {
+                    Some(_) => Vec::new(),
+                    None => vec![Diagnostic::warning("DBX_OWNERS", "", "Jobs need owners.")],
+                })
+            }
+
+            fn risks(&self, _spec: &Value) -> anyhow::Result<Vec<RiskFlag>> {
+                Ok(vec![RiskFlag {
+                    severity: "medium".into(),
+                    code: "DBX_NO_CLUSTER".into(),
+                    message: "No cluster policy".into(),
+                    recommendation: "Set a cluster policy".into(),
+                }])
+            }
+
+            fn compile(&self, target: &str, ir: &Ir) -> anyhow::Result<Compiled> {
+                Ok(Compiled::Text(format!(
+                    "-- {} job for {}",
+                    target,
+                    ir.entity.as_deref().unwrap_or("")
+                )))
+            }
+        }
+
+        let info = PluginInfo {
+            name: "databricks".into(),
+            capabilities: vec!["validate".into(), "risks".into(), "compile".into()],
+            targets: vec!["databricks".into()],
+            ..Default::default()
+        };
+        let mut plugins = PluginRegistry::new();
+        plugins.register(Databricks(info.clone()));
+        // A plugin only takes part in what its capabilities name.
+        plugins.register(Databricks(PluginInfo {
+            name: "quiet".into(),
+            capabilities: vec![],
+            ..info
+        }));
+
+        let spec = crate::parse_yaml(MINIMAL).unwrap();
+        let findings = plugins.validate(&spec).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            (findings[0].code.as_str(), findings[0].severity),
+            ("DBX_OWNERS", Severity::Warning)
+        );
+
+        let baseline = crate::generate_plan(MINIMAL).unwrap();
+        let options = crate::PlanOptions {
+            plugins: plugins.clone(),
+            ..Default::default()
+        };
+        let plan = crate::generate_plan_with(MINIMAL, &options).unwrap();
+        assert_eq!(plan.risk_flags.len(), baseline.risk_flags.len() + 1);
+        assert!(plan.risk_flags.iter().any(|f| f.code == "DBX_NO_CLUSTER"));
+
+        assert_eq!(plugins.targets(), ["databricks"]);
+        assert!(plugins.target("sql").is_none());
+        let ir = Ir::from_value(&crate::compile_to_ir(&spec).unwrap()).unwrap();
+        match plugins
+            .target("databricks")
+            .unwrap()
+            .compile("databricks", &ir)
+            .unwrap()
+        {
+            Compiled::Text(text) => assert_eq!(text, "-- databricks job for customer"),
+            Compiled::Files(_) => panic!("expected text"),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Out-of-process plugins.
+//!
+//! A plugin executable is run once per request: it reads one JSON object
+//! from stdin and writes one to stdout, exiting non-zero (with a message on
+//! stderr) if it cannot answer. Every request carries `protocol` (currently
+//! 1) and a `method`:
+//!
+//! | method     | request fields   | response                                          |
+//! |------------|------------------|---------------------------------------------------|
+//! | `describe` |                  | `{ name, version, description, capabilities, targets }` |
+//! | `validate` | `spec`           | `{ diagnostics: [{ severity, code, path, message, suggestion }] }` |
+//! | `risks`    | `spec`           | `{ risk_flags: [{ severity, code, message, recommendation }] }` |
+//! | `compile`  | `target`, `ir`   | `{ output: "..." }` or `{ files: [{ path, contents }] }` |
+//!
+//! `spec` is the parsed spec, `ir` the compiled IR. Finding severities are
+//! `error`, `warning` or `info`; risk flag severities `critical`, `high`,
+//! `medium` or `low`. `describe`'s `name` defaults to the executable's.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::{plugin_name, Compiled, Plugin, PluginInfo};
+use crate::commands::codegen::GeneratedFile;
+use crate::commands::plan::{RiskFlag, RISK_WEIGHTS};
+use crate::diagnostics::Diagnostic;
+use crate::ir::Ir;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A plugin executable, described by running it.
+#[derive(Debug, Clone)]
+pub struct ProcessPlugin {
+    path: PathBuf,
+    info: PluginInfo,
+}
+
+#[derive(Deserialize)]
+struct Finding {
+    severity: String,
+    code: String,
+    #[serde(default)]
+    path: String,
+    message: String,
+    #[serde(default)]
+    suggestion: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Validated {
+    #[serde(default)]
+    diagnostics: Vec<Finding>,
+}
+
+#[derive(Deserialize)]
+struct Risks {
+    #[serde(default)]
+    risk_flags: Vec<RiskFlag>,
+}
+
+#[derive(Deserialize)]
+struct Output {
+    #[serde(default)]
+    output: Option<String>,
+    #[serde(default)]
+    files: Option<Vec<File>>,
+}
+
+#[derive(Deserialize)]
+struct File {
+    path: PathBuf,
+    contents: String,
+}
+
+impl ProcessPlugin {
+    /// Run the executable at `path` to ask what it provides.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut plugin = ProcessPlugin {
+            path: path.to_path_buf(),
+            info: PluginInfo::default(),
+        };
+        let mut info: PluginInfo = plugin.call(json!({ "method": "describe" })).map_err(|e| {
+            anyhow!(
+                "Plugin {} failed to describe itself: {:#}",
+                path.display(),
+                e
+            )
+        })?;
+        if info.name.is_empty() {
+            info.name = plugin_name(path).unwrap_or_default();
+        }
+        plugin.info = info;
+        Ok(plugin)
+    }
+
+    /// Send `request` and parse the response.
+    fn call<T: DeserializeOwned>(&self, mut request: Value) -> Result<T> {
+        request["protocol"] = json!(PROTOCOL_VERSION);
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run plugin {}", self.path.display()))?;
+        let written = child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(serde_json::to_string(&request)?.as_bytes());
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!(
+                "Plugin {} failed ({}): {}",
+                self.path.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        // A plugin may answer without reading its whole request.
+        if written.is_err() && output.stdout.is_empty() {
+            bail!("Plugin {} did not read its request", self.path.display());
+        }
+        serde_json::from_slice(&output.stdout).map_err(|e| {
+            anyhow!(
+                "Plugin {} answered with invalid JSON: {}",
+                self.path.display(),
+                e
+            )
+        })
+    }
+}
+
+impl Plugin for ProcessPlugin {
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    fn validate(&self, spec: &Value) -> Result<Vec<Diagnostic>> {
+        let validated: Validated = self.call(json!({ "method": "validate", "spec": spec }))?;
+        validated
+            .diagnostics
+            .into_iter()
+            .map(|finding| {
+                let diagnostic = match finding.severity.as_str() {
+                    "error" => Diagnostic::error,
+                    "warning" => Diagnostic::warning,
+                    "info" => Diagnostic::info,
+                    other => bail!(
+                        "Plugin '{}' reported {} with unknown severity '{}' (expected error, warning or info)",
+                        self.info.name,
+                        finding.code,
+                        other
+                    ),
+                };
+                let diagnostic = diagnostic(&finding.code, finding.path, finding.message);
+                Ok(match finding.suggestion {
+                    Some(suggestion) => diagnostic.with_suggestion(suggestion),
+                    None => diagnostic,
+                })
+            })
+            .collect()
+    }
+
+    fn risks(&self, spec: &Value) -> Result<Vec<RiskFlag>> {
+        let risks: Risks = self.call(json!({ "method": "risks", "spec": spec }))?;
+        for flag in &risks.risk_flags {
+            if !RISK_WEIGHTS.iter().any(|(s, _)| *s == flag.severity) {
+                bail!(
+                    "Plugin '{}' raised {} with unknown severity '{}' (expected critical, high, medium or low)",
+                    self.info.name,
+                    flag.code,
+                    flag.severity
+                );
+            }
+        }
+        Ok(risks.risk_flags)
+    }
+
+    fn compile(&self, target: &str, ir: &Ir) -> Result<Compiled> {
+        let output: Output =
+            self.call(json!({ "method": "compile", "target": target, "ir": ir }))?;
+        match (output.files, output.output) {
+            (Some(files), _) => Ok(Compiled::Files(
+                files
+                    .into_iter()
+                    .map(|file| GeneratedFile {
+                        path: file.path,
+                        contents: file.contents,
+                    })
+                    .collect(),
+            )),
+            (None, Some(text)) => Ok(Compiled::Text(text)),
+            (None, None) => bail!(
+                "Plugin '{}' compiled '{}' to neither output nor files",
+                self.info.name,
+                target
+            ),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! Severity policy: promote, demote or ignore findings by code.
+//!
+//! A team sets the level of any diagnostic code or plan risk flag in a
+//! `.kanoniv.toml` next to its specs (or in a parent directory)
+//!
+//! ```toml
+//! [policy]
+//! LOW_THRESHOLD = "error"       # the plan fails when it is raised
+//! SINGLE_SOURCE = "ignore"      # never reported or scored
+//! zero-weight-rule = "error"    # codes by name, as for waivers
+//! ```
+//!
+//! or in a spec's own `policy` section, whose entries win over the file's:
+//!
+//! ```yaml
+//! policy:
+//!   UNUSED_ATTRIBUTE: ignore
+//! ```
+//!
+//! Levels are `error`, `warning`, `info` and `ignore`. Findings that are
+//! errors stay errors: a policy can promote advice but never lets an invalid
+//! spec through. Risk flags have no level of their own; under a policy they
+//! take the one it gives them, and an `error` flag fails `kanoniv plan`.
+//! Unlike a waiver, a policy needs no reason and never expires; it records
+//! how a team weighs findings, not an exception to them. Waivers apply
+//! first, so a waived finding stays waived whatever level its code has.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::diagnostics::{codes, Diagnostic, Severity};
+
+pub const LEVELS: &[&str] = &["error", "warning", "info", "ignore"];
+
+/// File a policy is read from, looked up from a spec's directory upwards.
+pub const POLICY_FILE: &str = ".kanoniv.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warning,
+    Info,
+    /// Drop the finding.
+    Ignore,
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Info => "info",
+            Level::Ignore => "ignore",
+        }
+    }
+
+    /// The severity findings at this level are reported with; `None` for
+    /// `ignore`.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            Level::Error => Some(Severity::Error),
+            Level::Warning => Some(Severity::Warning),
+            Level::Info => Some(Severity::Info),
+            Level::Ignore => None,
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "error" => Ok(Level::Error),
+            "warning" => Ok(Level::Warning),
+            "info" => Ok(Level::Info),
+            "ignore" => Ok(Level::Ignore),
+            _ => bail!("Unknown policy level '{}'. Use {}", s, LEVELS.join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Finding code -> the level a team gives it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Policy {
+    /// Codes normalized as for waivers (`zero-weight-rule` -> `KNV0106`).
+    levels: BTreeMap<String, Level>,
+}
+
+impl Policy {
+    /// Read the `policy` section of a parsed spec; `None` if it has none.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(section) = spec.get("policy").filter(|p| !p.is_null()) else {
+            return Ok(None);
+        };
+        Self::from_levels(section).map(Some)
+    }
+
+    /// Read the `[policy]` table of a `.kanoniv.toml`; empty if it has none.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let file: toml::Table = toml::from_str(text)?;
+        match file.get("policy") {
+            Some(table) => Self::from_levels(&serde_json::to_value(table)?),
+            None => Ok(Policy::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read policy file: {}", path.display()))?;
+        Self::from_toml(&text)
+            .map_err(|e| anyhow!("Invalid policy file {}: {:#}", path.display(), e))
+    }
+
+    /// The `.kanoniv.toml` governing the spec at `spec_path`: the nearest one
+    /// in its directory or a parent directory.
+    pub fn find(spec_path: &Path) -> Option<PathBuf> {
+        let dir = spec_path.parent().filter(|d| !d.as_os_str().is_empty());
+        let start = std::path::absolute(dir.unwrap_or(Path::new("."))).ok()?;
+        start
+            .ancestors()
+            .map(|dir| dir.join(POLICY_FILE))
+            .find(|file| file.is_file())
+    }
+
+    /// Load the policy governing the spec at `spec_path`; empty if no
+    /// `.kanoniv.toml` governs it.
+    pub fn discover(spec_path: &Path) -> Result<Self> {
+        match Self::find(spec_path) {
+            Some(file) => Self::load(&file),
+            None => Ok(Policy::default()),
+        }
+    }
+
+    fn from_levels(section: &Value) -> Result<Self> {
+        let Some(entries) = section.as_object() else {
+            bail!("policy must map finding codes to levels, got {}", section);
+        };
+        let mut levels = BTreeMap::new();
+        for (code, level) in entries {
+            let Some(level) = level.as_str() else {
+                bail!("policy.{} must be one of {}", code, LEVELS.join(", "));
+            };
+            levels.insert(codes::canonical(code), level.parse()?);
+        }
+        Ok(Policy { levels })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    /// This policy with `overrides`' entries laid over it.
+    pub fn with(&self, overrides: &Policy) -> Policy {
+        let mut levels = self.levels.clone();
+        levels.extend(overrides.levels.clone());
+        Policy { levels }
+    }
+
+    /// This policy with the `policy` section of `spec` laid over it. A
+    /// malformed section is ignored; validation reports it.
+    pub fn for_spec(&self, spec: &Value) -> Policy {
+        match Policy::from_spec(spec) {
+            Ok(Some(section)) => self.with(&section),
+            _ => self.clone(),
+        }
+    }
+
+    /// The level the policy gives findings with `code`, if any.
+    pub fn level(&self, code: &str) -> Option<Level> {
+        self.levels.get(&codes::canonical(code)).copied()
+    }
+
+    /// `diagnostics` at the levels the policy gives them, ignored ones
+    /// dropped. Errors are never demoted.
+    pub fn apply(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        diagnostics
+            .into_iter()
+            .filter_map(|mut diagnostic| {
+                if diagnostic.is_error() {
+                    return Some(diagnostic);
+                }
+                if let Some(level) = self.level(&diagnostic.code) {
+                    diagnostic.severity = level.severity()?;
+                }
+                Some(diagnostic)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+    use crate::{diagnose_yaml, Profile, Spec};
+
+    #[test]
+    fn test_policy_sets_levels_and_short_waivers_carry_a_reason() {
+        let yaml = MINIMAL.replace("    weight: 1.0\n", "    weight: 0.0\n")
+            + "policy:\n  zero-weight-rule: error\n  NO_BLOCKING: ignore\n";
+
+        // The spec's policy promotes the zero-weight warning to an error.
+        let tiers = crate::validate_tiers(&yaml, Profile::Default);
+        assert_eq!(tiers.errors.len(), 1, "{:?}", tiers.errors);
+        assert_eq!(tiers.errors[0].code, "KNV0106");
+        assert_eq!(
+            Spec::parse(&yaml)
+                .unwrap()
+                .tiers(Profile::Default)
+                .errors
+                .len(),
+            1
+        );
+
+        // A file policy is laid under it; the spec's entries win, and nothing
+        // demotes an error.
+        let file = crate::Policy::from_toml(
+            "[policy]\nKNV0106 = \"info\"\nSINGLE_SOURCE = \"error\"\nKNV0001 = \"ignore\"\n",
+        )
+        .unwrap();
+        assert_eq!(file.level("single_source"), Some(crate::Level::Error));
+        let tiers = crate::validate_tiers_with(MINIMAL, Profile::Default, &file);
+        assert!(tiers.is_valid());
+        let broken = MINIMAL.replace("api_version: kanoniv/v2\n", "");
+        assert!(!crate::validate_tiers_with(&broken, Profile::Default, &file).is_valid());
+
+        // Ignored flags are neither reported nor scored; the policy is recorded.
+        let plan = crate::generate_plan(&yaml).unwrap();
+        assert!(plan.risk_flags.iter().all(|f| f.code != "NO_BLOCKING"));
+        assert!(plan.risk_score < crate::generate_plan(MINIMAL).unwrap().risk_score);
+        assert_eq!(plan.policy.level("NO_BLOCKING"), Some(crate::Level::Ignore));
+
+        // A malformed level is a schema error.
+        let diagnostics = diagnose_yaml(&(MINIMAL.to_string() + "policy:\n  NO_BLOCKING: loud\n"));
+        assert_eq!(diagnostics[0].code, "KNV0010");
+        assert_eq!(diagnostics[0].path.as_deref(), Some("policy.NO_BLOCKING"));
+        assert!(crate::Policy::from_toml("[policy]\nNO_BLOCKING = \"loud\"\n").is_err());
+
+        // The short annotation takes the rest of the comment as its reason, and
+        // a waiver outranks the policy.
+        let waived = yaml.replace(
+            "    weight: 0.0\n",
+            "    weight: 0.0  # kanoniv:ignore zero-weight-rule kept for reports\n",
+        );
+        let spec = crate::parse_yaml(&waived).unwrap();
+        let waivers = crate::Waivers::collect_on(&waived, &spec, "2026-01-01");
+        assert_eq!(waivers.active[0].code, "KNV0106");
+        assert_eq!(waivers.active[0].reason, "kept for reports");
+        let tiers = crate::validate_tiers(&waived, Profile::Default);
+        assert!(tiers.is_valid(), "{:?}", tiers.errors);
+        assert_eq!(tiers.waived[0].waiver.reason, "kept for reports");
+        let unexplained = yaml.replace(
+            "    weight: 0.0\n",
+            "    weight: 0.0  # kanoniv:ignore zero-weight-rule\n",
+        );
+        assert!(diagnose_yaml(&unexplained)
+            .iter()
+            .any(|d| d.code == "KNV0109"));
+    }
+}
@@ -0,0 +1,493 @@
+//! Privacy: which attributes hold personal data, and how it is protected.
+//!
+//! Attributes are classified as PII from their type and name: `email` and
+//! `phone` types, and names such as `email`, `mobile`, `first_name`,
+//! `surname`, `dob`, `date_of_birth` or `ssn`. A mapping's `pii` tag
+//! overrides the guess either way:
+//!
+//! ```yaml
+//! sources:
+//!   - name: crm
+//!     attributes:
+//!       loyalty_id: { column: loyalty_no, pii: true }
+//!       account_name: { column: account, pii: false }
+//!
+//! privacy:
+//!   retention_days: 730       # how long records holding PII are kept
+//!   collected_at: updated_at  # attribute: when each record's values were collected
+//!   on_expiry: nullify        # nullify (default) or tombstone
+//!   fields:
+//!     email:
+//!       masking: hash         # hash, redact, partial or none
+//!       retention_days: 365   # this attribute's own period
+//! ```
+//!
+//! Masking says how an attribute appears outside the engine: `hash` digests
+//! it with the spec's `hashing`, `redact` blanks it, `partial` keeps enough
+//! to recognize it (`j***@example.com`), and `none` records that it is kept
+//! as is on purpose. Blocking keys on a `hash`-masked attribute are built
+//! from the digests, which agree exactly when the values do.
+//!
+//! Retention periods are enforced by the engine and `kanoniv maintain`
+//! (see `retention`), counted from `collected_at`, or the temporal
+//! `effective_from` without one: a value past its period is nulled, or
+//! with `on_expiry: tombstone` its whole record is dropped.
+//!
+//! Validation warns about PII attributes without masking or a retention
+//! period, and the plan flags PII used raw as a blocking key.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::attributes::{self, AttributeType};
+use crate::ir::Ir;
+
+pub const MASKINGS: &[&str] = &["hash", "redact", "partial", "none"];
+
+pub const EXPIRIES: &[&str] = &["nullify", "tombstone"];
+
+/// Name parts that mark an attribute as a person's name, date of birth or
+/// national identifier; matched against the attribute name split on `_`.
+const NAME_PARTS: &[&str] = &[
+    "firstname",
+    "lastname",
+    "fullname",
+    "surname",
+    "forename",
+    "maiden",
+];
+const NAME_QUALIFIERS: &[&str] = &[
+    "first", "last", "full", "given", "middle", "family", "maiden",
+];
+const BIRTH_PARTS: &[&str] = &["dob", "birthdate", "birthday", "birth"];
+const NATIONAL_ID_PARTS: &[&str] = &["ssn", "nino", "passport", "sin"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Name,
+    Email,
+    Phone,
+    DateOfBirth,
+    /// Social security, national insurance and passport numbers.
+    NationalId,
+    /// Tagged `pii: true` without a recognizable name or type.
+    Other,
+}
+
+impl PiiKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PiiKind::Name => "name",
+            PiiKind::Email => "email",
+            PiiKind::Phone => "phone",
+            PiiKind::DateOfBirth => "date_of_birth",
+            PiiKind::NationalId => "national_id",
+            PiiKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for PiiKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Masking {
+    Hash,
+    Redact,
+    Partial,
+    None,
+}
+
+impl Masking {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Masking::Hash => "hash",
+            Masking::Redact => "redact",
+            Masking::Partial => "partial",
+            Masking::None => "none",
+        }
+    }
+}
+
+impl FromStr for Masking {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hash" => Ok(Masking::Hash),
+            "redact" => Ok(Masking::Redact),
+            "partial" => Ok(Masking::Partial),
+            "none" => Ok(Masking::None),
+            _ => bail!("Unknown masking '{}'. Use {}", s, MASKINGS.join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for Masking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What becomes of a value past its retention period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Expiry {
+    /// Null the value; another member's may survive in its place.
+    #[default]
+    Nullify,
+    /// Drop the record holding it, as a deleted record is dropped.
+    Tombstone,
+}
+
+impl Expiry {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Expiry::Nullify => "nullify",
+            Expiry::Tombstone => "tombstone",
+        }
+    }
+}
+
+impl FromStr for Expiry {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nullify" => Ok(Expiry::Nullify),
+            "tombstone" => Ok(Expiry::Tombstone),
+            _ => bail!("Unknown expiry '{}'. Use {}", s, EXPIRIES.join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for Expiry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The `privacy` section.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Privacy {
+    /// Days records holding PII are kept, unless an attribute sets its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_days: Option<u64>,
+    /// Attribute holding when each record's values were collected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collected_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_expiry: Option<Expiry>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, FieldPrivacy>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldPrivacy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub masking: Option<Masking>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_days: Option<u64>,
+}
+
+impl Privacy {
+    /// Read `privacy` from a parsed spec; `None` if the spec has none.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        let Some(section) = spec.get("privacy").filter(|p| !p.is_null()) else {
+            return Ok(None);
+        };
+        if !section.is_object() {
+            bail!("privacy must be a mapping, got {}", section);
+        }
+        let collected_at = match section.get("collected_at") {
+            None => None,
+            Some(Value::String(attribute)) if !attribute.is_empty() => Some(attribute.clone()),
+            Some(other) => bail!(
+                "privacy.collected_at must be an attribute name, got {}",
+                other
+            ),
+        };
+        let on_expiry = match section.get("on_expiry") {
+            None => None,
+            Some(Value::String(name)) => Some(name.parse()?),
+            Some(other) => bail!("privacy.on_expiry must be a name, got {}", other),
+        };
+        let mut privacy = Privacy {
+            retention_days: retention(section, "privacy")?,
+            collected_at,
+            on_expiry,
+            fields: BTreeMap::new(),
+        };
+        match section.get("fields") {
+            None | Some(Value::Null) => {}
+            Some(Value::Object(fields)) => {
+                for (field, settings) in fields {
+                    let path = format!("privacy.fields.{}", field);
+                    if !settings.is_object() {
+                        bail!("{} must be a mapping, got {}", path, settings);
+                    }
+                    let masking = match settings.get("masking") {
+                        None => None,
+                        Some(Value::String(name)) => Some(name.parse()?),
+                        Some(other) => bail!("{}.masking must be a name, got {}", path, other),
+                    };
+                    let settings = FieldPrivacy {
+                        masking,
+                        retention_days: retention(settings, &path)?,
+                    };
+                    privacy.fields.insert(field.clone(), settings);
+                }
+            }
+            Some(other) => bail!("privacy.fields must be a mapping, got {}", other),
+        }
+        Ok(Some(privacy))
+    }
+
+    pub fn masking(&self, attribute: &str) -> Option<Masking> {
+        self.fields.get(attribute)?.masking
+    }
+
+    /// Days `attribute` is kept: its own period, or the section's.
+    pub fn retention_days(&self, attribute: &str) -> Option<u64> {
+        self.fields
+            .get(attribute)
+            .and_then(|f| f.retention_days)
+            .or(self.retention_days)
+    }
+}
+
+/// An attribute classified as PII.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PiiAttribute {
+    pub attribute: String,
+    pub kind: PiiKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub masking: Option<Masking>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_days: Option<u64>,
+}
+
+/// The kind of PII an attribute named `attribute` and typed `ty` looks like.
+pub fn detect(attribute: &str, ty: Option<AttributeType>) -> Option<PiiKind> {
+    match ty {
+        Some(AttributeType::Email) => return Some(PiiKind::Email),
+        Some(AttributeType::Phone) => return Some(PiiKind::Phone),
+        _ => {}
+    }
+    let name = attribute.to_lowercase();
+    let parts: Vec<&str> = name.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+    let has = |candidates: &[&str]| parts.iter().any(|p| candidates.contains(p));
+    if has(&["email"]) {
+        Some(PiiKind::Email)
+    } else if has(&["phone", "mobile", "telephone", "tel", "cell"]) {
+        Some(PiiKind::Phone)
+    } else if has(NATIONAL_ID_PARTS)
+        || name.contains("social_security")
+        || name.contains("national_id")
+    {
+        Some(PiiKind::NationalId)
+    } else if has(BIRTH_PARTS) {
+        Some(PiiKind::DateOfBirth)
+    } else if has(NAME_PARTS) || (has(&["name"]) && has(NAME_QUALIFIERS)) {
+        Some(PiiKind::Name)
+    } else {
+        None
+    }
+}
+
+/// The PII among a spec's canonical attributes, in sorted order. A `pii`
+/// tag in any source decides over detection; a `privacy` section that does
+/// not parse adds no protection.
+pub fn classify(spec: &Value) -> Vec<PiiAttribute> {
+    let types = attributes::declared_types(spec);
+    let mut tags: BTreeMap<String, Option<bool>> = BTreeMap::new();
+    let sources = spec.get("sources").and_then(Value::as_array);
+    for attributes in sources
+        .into_iter()
+        .flatten()
+        .filter_map(|source| source.get("attributes")?.as_object())
+    {
+        for (attribute, mapping) in attributes {
+            let tag = mapping.get("pii").and_then(Value::as_bool);
+            let entry = tags.entry(attribute.clone()).or_default();
+            *entry = entry.or(tag);
+        }
+    }
+    let privacy = Privacy::from_spec(spec).ok().flatten().unwrap_or_default();
+    collect(
+        tags.iter()
+            .map(|(attribute, tag)| (attribute.as_str(), types.get(attribute).copied(), *tag)),
+        &privacy,
+    )
+}
+
+/// `classify` for a compiled IR.
+pub fn classify_ir(ir: &Ir) -> Vec<PiiAttribute> {
+    let types = ir.types();
+    let mut tags: BTreeMap<&str, Option<bool>> = BTreeMap::new();
+    for source in &ir.sources {
+        for attribute in source.attributes.keys() {
+            let entry = tags.entry(attribute).or_default();
+            *entry = entry.or(source.pii.get(attribute).copied());
+        }
+    }
+    collect(
+        tags.iter()
+            .map(|(attribute, tag)| (*attribute, types.get(*attribute).copied(), *tag)),
+        &ir.privacy.clone().unwrap_or_default(),
+    )
+}
+
+fn collect<'a>(
+    attributes: impl Iterator<Item = (&'a str, Option<AttributeType>, Option<bool>)>,
+    privacy: &Privacy,
+) -> Vec<PiiAttribute> {
+    attributes
+        .filter_map(|(attribute, ty, tag)| {
+            let kind = match tag {
+                Some(false) => return None,
+                Some(true) => detect(attribute, ty).unwrap_or(PiiKind::Other),
+                None => detect(attribute, ty)?,
+            };
+            Some(PiiAttribute {
+                attribute: attribute.to_string(),
+                kind,
+                masking: privacy.masking(attribute),
+                retention_days: privacy.retention_days(attribute),
+            })
+        })
+        .collect()
+}
+
+fn retention(section: &Value, path: &str) -> Result<Option<u64>> {
+    match section.get("retention_days") {
+        None => Ok(None),
+        Some(days) => match days.as_u64() {
+            Some(days) if days > 0 => Ok(Some(days)),
+            _ => bail!(
+                "{}.retention_days must be a positive number of days, got {}",
+                path,
+                days
+            ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MINIMAL, PRIVACY};
+    use crate::{diagnose_yaml, Ir};
+
+    #[test]
+    fn test_pii_classification_and_privacy_checks() {
+        use crate::attributes::AttributeType;
+        use crate::privacy::{self, Masking, PiiKind};
+
+        assert_eq!(privacy::detect("contact_email", None), Some(PiiKind::Email));
+        assert_eq!(
+            privacy::detect("work", Some(AttributeType::Phone)),
+            Some(PiiKind::Phone)
+        );
+        assert_eq!(privacy::detect("FirstName", None), Some(PiiKind::Name));
+        assert_eq!(privacy::detect("first_name", None), Some(PiiKind::Name));
+        assert_eq!(
+            privacy::detect("date_of_birth", None),
+            Some(PiiKind::DateOfBirth)
+        );
+        assert_eq!(privacy::detect("ssn", None), Some(PiiKind::NationalId));
+        assert_eq!(privacy::detect("company_name", None), None);
+        assert!("tokenize".parse::<Masking>().is_err());
+
+        // Tags decide over the name, either way.
+        let tagged = MINIMAL.replace(
+        "      email: email\n",
+        "      email: { column: email, pii: false }\n      loyalty_id: { column: loyalty_no, pii: true }\n",
+    );
+        let spec = crate::parse_yaml(&tagged).unwrap();
+        let found: Vec<_> = privacy::classify(&spec)
+            .into_iter()
+            .map(|a| (a.attribute, a.kind))
+            .collect();
+        assert_eq!(found, [("loyalty_id".to_string(), PiiKind::Other)]);
+        let ir = Ir::from_value(&crate::compile_to_ir(&spec).unwrap()).unwrap();
+        assert_eq!(privacy::classify_ir(&ir), privacy::classify(&spec));
+        let recompiled = Ir::from_value(&crate::compile_to_ir(&ir.to_spec()).unwrap()).unwrap();
+        assert_eq!(recompiled.sources[0].pii, ir.sources[0].pii);
+
+        // Unprotected PII is a warning; a privacy section settles it.
+        let findings = |yaml: &str| -> Vec<(String, String)> {
+            diagnose_yaml(yaml)
+                .into_iter()
+                .map(|d| (d.code.to_string(), d.path.unwrap_or_default()))
+                .collect()
+        };
+        let finding = |code: &str, path: &str| (code.to_string(), path.to_string());
+        assert_eq!(
+            findings(MINIMAL),
+            [
+                finding("KNV0126", "sources[0].attributes.email"),
+                finding("KNV0127", "sources[0].attributes.email"),
+            ]
+        );
+        assert!(findings(&format!("{}{}", MINIMAL, PRIVACY)).is_empty());
+        let spec = crate::parse_yaml(&format!("{}{}", MINIMAL, PRIVACY)).unwrap();
+        let ir = Ir::from_value(&crate::compile_to_ir(&spec).unwrap()).unwrap();
+        assert_eq!(
+            ir.privacy.as_ref().unwrap().masking("email"),
+            Some(Masking::Hash)
+        );
+        assert_eq!(
+            ir.privacy.as_ref().unwrap().retention_days("email"),
+            Some(730)
+        );
+        assert_eq!(
+            findings(&format!(
+                "{}privacy:\n  fields:\n    emial:\n      masking: hash\n",
+                MINIMAL
+            ))[0],
+            finding("KNV0101", "privacy.fields.emial")
+        );
+        assert_eq!(
+            findings(&format!("{}privacy:\n  retention_days: 0\n", MINIMAL)),
+            [finding("KNV0125", "privacy")]
+        );
+
+        // Blocking on raw PII is flagged, unless it is hashed or coarsened.
+        let blocked = format!("{}blocking:\n  keys:\n    - email\n", MINIMAL);
+        let plan = crate::generate_plan(&blocked).unwrap();
+        assert!(plan
+            .risk_flags
+            .iter()
+            .any(|f| f.code == "PII_IN_BLOCKING_KEY"));
+        assert_eq!(plan.pii[0].attribute, "email");
+        assert!(plan.summary.contains("PII:          email (email)"));
+        let hashed = format!(
+            "{}{}{}",
+            MINIMAL, "blocking:\n  keys:\n    - email\n", PRIVACY
+        );
+        let plan = crate::generate_plan(&hashed).unwrap();
+        assert!(plan
+            .risk_flags
+            .iter()
+            .all(|f| f.code != "PII_IN_BLOCKING_KEY"));
+        assert!(plan.summary.contains("PII:          email (email, hash)"));
+        let coarse = format!(
+            "{}blocking:\n  keys:\n    - field: email\n      transform: soundex\n",
+            MINIMAL
+        );
+        let plan = crate::generate_plan(&coarse).unwrap();
+        assert!(plan
+            .risk_flags
+            .iter()
+            .all(|f| f.code != "PII_IN_BLOCKING_KEY"));
+    }
+}
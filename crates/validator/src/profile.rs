@@ -0,0 +1,422 @@
+//! Data profiling of a source against the spec that maps it.
+//!
+//! `profile_source` reads the attributes a source declares in `sources`
+//! from sample records (see `Sample`) and reports, per attribute, how often
+//! it is missing, how many distinct values it has and, for emails, phone
+//! numbers and dates, how many values look right. An attribute's declared
+//! type decides its format over its name; attributes the spec types or
+//! normalizes as `phone` count as conforming only when they parse as valid
+//! numbers. Attributes that match
+//! rules depend on are flagged when too sparse or malformed to match on.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+use crate::attributes::{self, AttributeType};
+use crate::commands::plan::RiskFlag;
+use crate::mappings;
+use crate::normalize::Normalization;
+use crate::parser;
+use crate::phone;
+use crate::sample::Sample;
+
+/// Cell values read as missing, besides empty ones.
+const NULL_TOKENS: &[&str] = &["null", "none", "n/a", "nan"];
+
+/// A rule field missing from more than this share of records is flagged
+/// high; from more than `SPARSE_MEDIUM`, medium.
+const SPARSE_HIGH: f64 = 0.5;
+const SPARSE_MEDIUM: f64 = 0.2;
+
+/// An attribute whose values conform to its format less often than this is
+/// flagged.
+const MIN_CONFORMANCE: f64 = 0.9;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceProfile {
+    pub source: String,
+    pub records: usize,
+    pub attributes: Vec<AttributeProfile>,
+    pub findings: Vec<RiskFlag>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttributeProfile {
+    /// Canonical attribute name.
+    pub attribute: String,
+    /// Column the source maps it to.
+    pub column: String,
+    /// Whether the data has the column; the counts are zero when not.
+    pub present: bool,
+    pub nulls: usize,
+    pub null_rate: f64,
+    pub distinct: usize,
+    /// `email`, `phone` or `date`, from the attribute's type,
+    /// normalization or name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Share of non-null values matching `format`, 0 to 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conformance: Option<f64>,
+    /// Match rules on this attribute.
+    pub rules: Vec<String>,
+}
+
+/// Profile the attributes `source` declares (by default the spec's only
+/// source, or the one whose columns the sample has most of).
+pub fn profile_source(yaml: &str, sample: &Sample, source: Option<&str>) -> Result<SourceProfile> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML for profiling")?;
+    let spec = mappings::resolve(&spec);
+    let (name, attributes) = choose_source(&spec, sample, source)?;
+    let normalization = Normalization::from_spec(&spec)?;
+    let types = attributes::declared_types(&spec);
+
+    let mut profiles = Vec::new();
+    let mut findings = Vec::new();
+    for (attribute, column) in attributes {
+        let rules = rules_on(&spec, &attribute);
+        let declared = types.get(&attribute).copied();
+        let profile = profile_attribute(sample, attribute, column, rules, declared, &normalization);
+        findings.extend(check(&profile));
+        profiles.push(profile);
+    }
+
+    Ok(SourceProfile {
+        source: name,
+        records: sample.len(),
+        attributes: profiles,
+        findings,
+    })
+}
+
+/// The source's name and its attribute -> column mapping.
+fn choose_source(
+    spec: &Value,
+    sample: &Sample,
+    wanted: Option<&str>,
+) -> Result<(String, Vec<(String, String)>)> {
+    let sources: Vec<(String, Vec<(String, String)>)> = match spec.get("sources") {
+        Some(Value::Array(sources)) => sources
+            .iter()
+            .map(|s| {
+                let name = s.get("name").and_then(Value::as_str).unwrap_or_default();
+                (name.to_string(), mapping(s))
+            })
+            .collect(),
+        Some(Value::Object(sources)) => sources
+            .iter()
+            .map(|(name, s)| (name.clone(), mapping(s)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    if sources.is_empty() {
+        bail!("The spec declares no sources");
+    }
+    let names = || {
+        sources
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    if let Some(wanted) = wanted {
+        return match sources.iter().find(|(name, _)| name == wanted) {
+            Some(source) => Ok(source.clone()),
+            None => bail!("Unknown source '{}' (expected one of: {})", wanted, names()),
+        };
+    }
+    if sources.len() == 1 {
+        return Ok(sources[0].clone());
+    }
+    let found = |attributes: &[(String, String)]| {
+        attributes
+            .iter()
+            .filter(|(_, column)| sample.find_column(column).is_some())
+            .count()
+    };
+    let best = sources.iter().map(|(_, a)| found(a)).max().unwrap_or(0);
+    let mut matching = sources.iter().filter(|(_, a)| found(a) == best);
+    match (matching.next(), matching.next()) {
+        (Some(source), None) if best > 0 => Ok(source.clone()),
+        _ => bail!(
+            "Cannot tell which source the data is from; pass --source ({})",
+            names()
+        ),
+    }
+}
+
+fn mapping(source: &Value) -> Vec<(String, String)> {
+    source
+        .get("attributes")
+        .and_then(Value::as_object)
+        .map(|attributes| {
+            attributes
+                .iter()
+                .map(|(attribute, mapping)| {
+                    (
+                        attribute.clone(),
+                        attributes::column(attribute, mapping).to_string(),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn rules_on(spec: &Value, attribute: &str) -> Vec<String> {
+    spec.get("rules")
+        .and_then(Value::as_array)
+        .map(|rules| {
+            rules
+                .iter()
+                .filter(|r| r.get("field").and_then(Value::as_str) == Some(attribute))
+                .filter_map(|r| r.get("name").and_then(Value::as_str))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn profile_attribute(
+    sample: &Sample,
+    attribute: String,
+    column: String,
+    rules: Vec<String>,
+    declared: Option<AttributeType>,
+    normalization: &Normalization,
+) -> AttributeProfile {
+    let declared_phone = declared == Some(AttributeType::Phone)
+        || normalization.phone_fields().any(|f| f == attribute);
+    let format = match declared {
+        _ if declared_phone => Some("phone"),
+        Some(AttributeType::Email) => Some("email"),
+        Some(AttributeType::Date) => Some("date"),
+        Some(_) => None,
+        None => format_of(&attribute),
+    };
+    let Some(index) = sample.find_column(&column) else {
+        return AttributeProfile {
+            attribute,
+            column,
+            present: false,
+            nulls: 0,
+            null_rate: 0.0,
+            distinct: 0,
+            format: format.map(str::to_string),
+            conformance: None,
+            rules,
+        };
+    };
+
+    let values: Vec<&str> = sample
+        .rows
+        .iter()
+        .map(|row| row.get(index).map(|v| v.trim()).unwrap_or_default())
+        .filter(|v| !is_null(v))
+        .collect();
+    let nulls = sample.len() - values.len();
+    let distinct = values.iter().collect::<HashSet<_>>().len();
+    let conformance = format.filter(|_| !values.is_empty()).map(|format| {
+        let conforming = values
+            .iter()
+            .filter(|v| {
+                if declared_phone {
+                    phone::parse(v, normalization.default_region).is_some()
+                } else {
+                    conforms(v, format)
+                }
+            })
+            .count();
+        conforming as f64 / values.len() as f64
+    });
+
+    AttributeProfile {
+        attribute,
+        column,
+        present: true,
+        nulls,
+        null_rate: if sample.is_empty() {
+            0.0
+        } else {
+            nulls as f64 / sample.len() as f64
+        },
+        distinct,
+        format: format.map(str::to_string),
+        conformance,
+        rules,
+    }
+}
+
+fn check(profile: &AttributeProfile) -> Vec<RiskFlag> {
+    let mut flags = Vec::new();
+    let used = !profile.rules.is_empty();
+
+    if !profile.present {
+        flags.push(RiskFlag {
+            severity: if used { "high" } else { "medium" }.to_string(),
+            code: "MISSING_COLUMN".to_string(),
+            message: format!(
+                "Attribute '{}' maps to column '{}', which the data does not have",
+                profile.attribute, profile.column
+            ),
+            recommendation: "Fix the attribute mapping in `sources`".to_string(),
+        });
+        return flags;
+    }
+
+    if used && profile.null_rate > SPARSE_MEDIUM {
+        flags.push(RiskFlag {
+            severity: if profile.null_rate > SPARSE_HIGH {
+                "high"
+            } else {
+                "medium"
+            }
+            .to_string(),
+            code: "SPARSE_MATCH_FIELD".to_string(),
+            message: format!(
+                "'{}' is missing in {:.0}% of records but is matched on by {}",
+                profile.attribute,
+                profile.null_rate * 100.0,
+                profile.rules.join(", ")
+            ),
+            recommendation: "Lower the weight of these rules or match on a better populated field"
+                .to_string(),
+        });
+    }
+
+    if let (Some(format), Some(conformance)) = (&profile.format, profile.conformance) {
+        if conformance < MIN_CONFORMANCE {
+            flags.push(RiskFlag {
+                severity: "medium".to_string(),
+                code: "FORMAT_MISMATCH".to_string(),
+                message: format!(
+                    "{:.0}% of '{}' values do not match the {} format",
+                    (1.0 - conformance) * 100.0,
+                    profile.attribute,
+                    format
+                ),
+                recommendation: "Normalize the column upstream or check the mapping".to_string(),
+            });
+        }
+    }
+    flags
+}
+
+fn is_null(value: &str) -> bool {
+    value.is_empty() || NULL_TOKENS.iter().any(|t| value.eq_ignore_ascii_case(t))
+}
+
+/// The format an attribute is expected to have, by its name.
+fn format_of(attribute: &str) -> Option<&'static str> {
+    let name = attribute.to_ascii_lowercase();
+    if name.contains("email") {
+        Some("email")
+    } else if ["phone", "mobile", "fax"].iter().any(|p| name.contains(p)) {
+        Some("phone")
+    } else if ["date", "dob", "birth"].iter().any(|p| name.contains(p))
+        || name.ends_with("_at")
+        || name.ends_with("_on")
+    {
+        Some("date")
+    } else {
+        None
+    }
+}
+
+fn conforms(value: &str, format: &str) -> bool {
+    match format {
+        "email" => is_email(value),
+        "phone" => is_phone(value),
+        "date" => is_date(value),
+        _ => true,
+    }
+}
+
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !value.contains(char::is_whitespace)
+}
+
+/// 7 to 15 digits, with only the usual separators.
+fn is_phone(value: &str) -> bool {
+    let digits = value.chars().filter(char::is_ascii_digit).count();
+    (7..=15).contains(&digits)
+        && value
+            .chars()
+            .all(|c| c.is_ascii_digit() || " +-().".contains(c))
+}
+
+/// `YYYY-MM-DD` (optionally with a time), `YYYY/MM/DD`, `MM/DD/YYYY` or
+/// `DD.MM.YYYY`.
+fn is_date(value: &str) -> bool {
+    let date = value.split(['T', ' ']).next().unwrap_or_default();
+    let parts: Vec<&str> = date.split(['-', '/', '.']).collect();
+    let [a, b, c] = parts.as_slice() else {
+        return false;
+    };
+    let number = |s: &str, len: std::ops::RangeInclusive<usize>| {
+        len.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_digit())
+    };
+    let (year, month, day) = if number(a, 4..=4) {
+        (*a, *b, *c)
+    } else if date.contains('.') {
+        (*c, *b, *a)
+    } else {
+        (*c, *a, *b)
+    };
+    number(year, 4..=4)
+        && number(month, 1..=2)
+        && number(day, 1..=2)
+        && (1..=12).contains(&month.parse::<u32>().unwrap_or(0))
+        && (1..=31).contains(&day.parse::<u32>().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn test_profile_source_reports_attributes() {
+        use crate::{profile_source, Sample};
+
+        let spec = include_str!("../conformance/multi_source/spec.yaml");
+        // billing's columns, so billing is the source profiled.
+        let sample = Sample::from_csv(
+            "customer_id,email,name_last\n1,a@x.com,Smith\n2,b@x.com,NULL\n3,,\n4,b@x.com,Jones\n",
+        )
+        .unwrap();
+        let profile = profile_source(spec, &sample, None).unwrap();
+        assert_eq!((profile.source.as_str(), profile.records), ("billing", 4));
+
+        let email = &profile.attributes[0];
+        assert_eq!(
+            (email.attribute.as_str(), email.column.as_str()),
+            ("email", "email")
+        );
+        assert_eq!((email.nulls, email.distinct), (1, 2));
+        assert_eq!(email.conformance, Some(1.0));
+        assert_eq!(email.rules, vec!["email_exact"]);
+
+        let last_name = &profile.attributes[1];
+        assert_eq!(last_name.null_rate, 0.5);
+        assert_eq!(profile.findings.len(), 2);
+        assert!(profile
+            .findings
+            .iter()
+            .all(|f| f.code == "SPARSE_MATCH_FIELD" && f.severity == "medium"));
+
+        // With the crm source named, none of its columns are in the data.
+        let crm = profile_source(spec, &sample, Some("crm")).unwrap();
+        assert!(crm.attributes.iter().all(|a| !a.present));
+        assert!(profile_source(spec, &sample, Some("pos")).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{bail, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::StatusCode;
+
+use super::Backend;
+
+/// Environment variable holding a bearer token for HTTP registries.
+pub const TOKEN_ENV: &str = "KANONIV_REGISTRY_TOKEN";
+
+/// A registry behind an HTTP server that serves `GET <base>/<key>` and
+/// accepts `PUT <base>/<key>`, e.g. a WebDAV share or object-store gateway.
+pub struct HttpBackend {
+    base: String,
+    client: Client,
+    token: Option<String>,
+}
+
+impl HttpBackend {
+    pub fn new(base: &str) -> Result<Self> {
+        Ok(HttpBackend {
+            base: base.trim_end_matches('/').to_string(),
+            client: Client::builder()
+                .build()
+                .with_context(|| "Failed to create HTTP client")?,
+            token: std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty()),
+        })
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.base, key)
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+impl Backend for HttpBackend {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let url = self.url(key);
+        let response = self
+            .authorized(self.client.get(&url))
+            .send()
+            .with_context(|| format!("Failed to fetch {}", url))?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.bytes()?.to_vec())),
+            status => bail!("Failed to fetch {}: HTTP {}", url, status),
+        }
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let url = self.url(key);
+        let response = self
+            .authorized(self.client.put(&url).body(data.to_vec()))
+            .send()
+            .with_context(|| format!("Failed to upload {}", url))?;
+        if !response.status().is_success() {
+            bail!("Failed to upload {}: HTTP {}", url, response.status());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+//! Spec registry: immutable spec versions keyed by canonical hash.
+//!
+//! A registry is a location holding, per entity,
+//!
+//! ```text
+//! <entity>/index.json     published versions, oldest first
+//! <entity>/<hex>.yaml     spec text as first published, by canonical hash
+//! ```
+//!
+//! Publishing the same spec twice (even restyled) finds the existing
+//! version; spec content is never overwritten. Tags (`production`,
+//! `staging`) name one version each and move on every publish that sets
+//! them, which is how a registry records what is deployed where.
+//!
+//! Locations: a directory path (or `file://`), `s3://bucket/prefix`, or an
+//! `http(s)://` base URL answering GET and PUT.
+
+#[cfg(feature = "remote")]
+pub mod http;
+pub mod local;
+pub mod s3;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::canonical::canonical_hash;
+use crate::clock;
+use crate::compose;
+use crate::parser;
+
+/// Where registry objects are stored.
+pub trait Backend: Send + Sync {
+    /// The object at `key`, or `None` if there is none.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+    /// Create or replace the object at `key`.
+    fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+}
+
+/// One published version of an entity's spec.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpecVersion {
+    /// `sha256:<hex>`, as computed by `canonical_hash`.
+    pub hash: String,
+    pub identity_version: String,
+    /// UTC, `YYYY-MM-DDTHH:MM:SSZ`.
+    pub published_at: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Outcome of `Registry::publish`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Published {
+    pub entity: String,
+    pub version: SpecVersion,
+    /// `false` when the spec was already in the registry.
+    pub created: bool,
+}
+
+pub struct Registry {
+    location: String,
+    backend: Box<dyn Backend>,
+}
+
+impl Registry {
+    /// Open the registry at `location`, choosing the backend by scheme.
+    pub fn open(location: &str) -> Result<Self> {
+        let backend: Box<dyn Backend> = if let Some(rest) = location.strip_prefix("s3://") {
+            Box::new(s3::S3Backend::new(rest)?)
+        } else if location.starts_with("http://") || location.starts_with("https://") {
+            http_backend(location)?
+        } else {
+            let path = location.strip_prefix("file://").unwrap_or(location);
+            Box::new(local::LocalBackend::new(path))
+        };
+        Ok(Self::with_backend(location, backend))
+    }
+
+    /// A registry over any backend.
+    pub fn with_backend(location: &str, backend: Box<dyn Backend>) -> Self {
+        Registry {
+            location: location.to_string(),
+            backend,
+        }
+    }
+
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// Store a valid spec (if it is new) and point `tags` at it. A spec
+    /// extending a base is stored as written and validated composed, so
+    /// its base must be published too.
+    pub fn publish(&self, yaml: &str, tags: &[String]) -> Result<Published> {
+        if compose::extends_file(yaml) {
+            bail!(
+                "The spec extends a local file, which the registry cannot resolve; \
+                 publish the base and extend it by registry://, or publish the \
+                 flattened spec (`kanoniv resolve`)"
+            );
+        }
+        let composed = compose::compose_yaml(yaml, None)?;
+        let errors = crate::validate_yaml(&composed)?;
+        if !errors.is_empty() {
+            bail!(
+                "Refusing to publish an invalid spec ({} error(s)): {}",
+                errors.len(),
+                errors.join("; ")
+            );
+        }
+        let spec = parser::parse_yaml(&composed)?;
+        let entity = spec
+            .get("entity")
+            .and_then(|e| e.get("name"))
+            .and_then(|n| n.as_str())
+            .unwrap_or_default()
+            .to_string();
+        check_name("entity name", &entity)?;
+        for tag in tags {
+            check_name("tag", tag)?;
+            if tag == "latest" {
+                bail!("'latest' always means the newest version and cannot be used as a tag");
+            }
+        }
+
+        let hash = canonical_hash(&parser::parse_yaml(yaml)?);
+        let mut versions = self.versions(&entity)?;
+        let created = !versions.iter().any(|v| v.hash == hash);
+        if created {
+            self.backend
+                .put(&spec_key(&entity, &hash), yaml.as_bytes())
+                .with_context(|| format!("Failed to store {} {}", entity, hash))?;
+            versions.push(SpecVersion {
+                hash: hash.clone(),
+                identity_version: spec
+                    .get("identity_version")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("unknown")
+                    .to_string(),
+                published_at: clock::now(),
+                tags: Vec::new(),
+            });
+        }
+        for version in &mut versions {
+            version.tags.retain(|t| !tags.contains(t));
+            if version.hash == hash {
+                version.tags.extend(tags.iter().cloned());
+                version.tags.sort();
+                version.tags.dedup();
+            }
+        }
+        if created || !tags.is_empty() {
+            self.backend
+                .put(&index_key(&entity), &serde_json::to_vec_pretty(&versions)?)
+                .with_context(|| format!("Failed to update the {} index", entity))?;
+        }
+
+        let version = versions
+            .into_iter()
+            .find(|v| v.hash == hash)
+            .expect("published version is in the index");
+        Ok(Published {
+            entity,
+            version,
+            created,
+        })
+    }
+
+    /// Published versions of `entity`, oldest first.
+    pub fn versions(&self, entity: &str) -> Result<Vec<SpecVersion>> {
+        check_name("entity name", entity)?;
+        match self.backend.get(&index_key(entity))? {
+            Some(data) => serde_json::from_slice(&data)
+                .with_context(|| format!("Corrupt registry index for {}", entity)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The version `selector` names: `latest` (the default), a tag, a
+    /// hash (`sha256:<hex>` or a unique hex prefix of 7+ characters) or an
+    /// `identity_version` (its newest publish).
+    pub fn resolve(&self, entity: &str, selector: Option<&str>) -> Result<SpecVersion> {
+        let versions = self.versions(entity)?;
+        if versions.is_empty() {
+            bail!("No versions of '{}' in {}", entity, self.location);
+        }
+        let selector = selector.unwrap_or("latest");
+        let hex = selector.strip_prefix("sha256:").unwrap_or(selector);
+        let by_prefix: Vec<&SpecVersion> =
+            if hex.len() >= 7 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                versions
+                    .iter()
+                    .filter(|v| v.hash.trim_start_matches("sha256:").starts_with(hex))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+        let found = if selector == "latest" {
+            versions.last()
+        } else if let Some(v) = versions
+            .iter()
+            .find(|v| v.tags.iter().any(|t| t == selector))
+        {
+            Some(v)
+        } else if let Some(v) = versions
+            .iter()
+            .rev()
+            .find(|v| v.identity_version == selector)
+        {
+            Some(v)
+        } else if by_prefix.len() > 1 {
+            bail!(
+                "'{}' matches {} versions of '{}'; use more of the hash",
+                selector,
+                by_prefix.len(),
+                entity
+            );
+        } else {
+            by_prefix.first().copied()
+        };
+        found.cloned().with_context(|| {
+            format!(
+                "No version '{}' of '{}' in {}",
+                selector, entity, self.location
+            )
+        })
+    }
+
+    /// The spec text of a version, checked against its hash.
+    pub fn pull(&self, entity: &str, selector: Option<&str>) -> Result<(SpecVersion, String)> {
+        let version = self.resolve(entity, selector)?;
+        let data = self
+            .backend
+            .get(&spec_key(entity, &version.hash))?
+            .with_context(|| {
+                format!(
+                    "{} {} is in the index but its spec is missing",
+                    entity, version.hash
+                )
+            })?;
+        let yaml = String::from_utf8(data).with_context(|| "Stored spec is not UTF-8")?;
+        let actual = canonical_hash(&parser::parse_yaml(&yaml)?);
+        if actual != version.hash {
+            bail!(
+                "Stored spec for {} {} hashes to {}; the registry is corrupt",
+                entity,
+                version.hash,
+                actual
+            );
+        }
+        Ok((version, yaml))
+    }
+}
+
+#[cfg(feature = "remote")]
+fn http_backend(location: &str) -> Result<Box<dyn Backend>> {
+    Ok(Box::new(http::HttpBackend::new(location)?))
+}
+
+#[cfg(not(feature = "remote"))]
+fn http_backend(location: &str) -> Result<Box<dyn Backend>> {
+    bail!(
+        "Cannot open {}: kanoniv was built without the `remote` feature",
+        location
+    )
+}
+
+fn index_key(entity: &str) -> String {
+    format!("{}/index.json", entity)
+}
+
+fn spec_key(entity: &str, hash: &str) -> String {
+    format!("{}/{}.yaml", entity, hash.trim_start_matches("sha256:"))
+}
+
+/// Names become object keys, so keep them to a safe alphabet.
+pub(crate) fn check_name(what: &str, name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c));
+    if !valid {
+        bail!(
+            "Invalid {} '{}' (use letters, digits, '_', '-' and '.')",
+            what,
+            name
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_registry_resolves_versions_over_any_backend() {
+        use crate::registry::Backend;
+        use std::collections::HashMap;
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Memory(Mutex<HashMap<String, Vec<u8>>>);
+        impl Backend for Memory {
+            fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+                Ok(self.0.lock().unwrap().get(key).cloned())
+            }
+            fn put(&self, key: &str, data: &[u8]) -> anyhow::Result<()> {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .insert(key.to_string(), data.to_vec());
+                Ok(())
+            }
+        }
+
+        let registry = crate::Registry::with_backend("memory", Box::new(Memory::default()));
+        let v1 = registry
+            .publish(MINIMAL, &["production".to_string()])
+            .unwrap();
+        assert!(v1.created);
+        assert_eq!(
+            v1.version.hash,
+            crate::canonical_hash(&crate::parse_yaml(MINIMAL).unwrap())
+        );
+        let v2_yaml = MINIMAL.replace("retail_v1.0", "retail_v1.1");
+        let v2 = registry.publish(&v2_yaml, &[]).unwrap();
+        assert!(!registry.publish(&v2_yaml, &[]).unwrap().created);
+
+        let resolve = |selector: Option<&str>| registry.resolve("customer", selector).unwrap().hash;
+        assert_eq!(resolve(None), v2.version.hash);
+        assert_eq!(resolve(Some("production")), v1.version.hash);
+        assert_eq!(resolve(Some("retail_v1.0")), v1.version.hash);
+        assert_eq!(
+            resolve(Some(&v1.version.hash["sha256:".len()..][..10])),
+            v1.version.hash
+        );
+        assert_eq!(
+            registry.pull("customer", Some("latest")).unwrap().1,
+            v2_yaml
+        );
+
+        assert!(registry.publish(MINIMAL, &["latest".to_string()]).is_err());
+        assert!(registry.versions("../etc").is_err());
+        assert!(registry.resolve("supplier", None).is_err());
+    }
+}
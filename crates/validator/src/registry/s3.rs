@@ -0,0 +1,72 @@
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use super::Backend;
+
+/// A registry in an S3 bucket, through the `aws` CLI so that every AWS
+/// credential source (profiles, SSO, instance roles) works unchanged.
+pub struct S3Backend {
+    /// `s3://bucket/prefix` without a trailing slash.
+    base: String,
+}
+
+impl S3Backend {
+    /// `location` is `bucket` or `bucket/prefix` (the part after `s3://`).
+    pub fn new(location: &str) -> Result<Self> {
+        let location = location.trim_end_matches('/');
+        if location.is_empty() {
+            bail!("S3 registry location needs a bucket: s3://bucket/prefix");
+        }
+        Ok(S3Backend {
+            base: format!("s3://{}", location),
+        })
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.base, key)
+    }
+}
+
+impl Backend for S3Backend {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let url = self.url(key);
+        let output = Command::new("aws")
+            .args(["s3", "cp", "--quiet", &url, "-"])
+            .output()
+            .with_context(|| "Failed to run the aws CLI (needed for s3:// registries)")?;
+        if output.status.success() {
+            return Ok(Some(output.stdout));
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("404") || stderr.contains("NoSuchKey") || stderr.contains("Not Found") {
+            return Ok(None);
+        }
+        bail!("Failed to read {}: {}", url, stderr.trim())
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let url = self.url(key);
+        let mut child = Command::new("aws")
+            .args(["s3", "cp", "--quiet", "-", &url])
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| "Failed to run the aws CLI (needed for s3:// registries)")?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(data)
+            .with_context(|| format!("Failed to upload {}", url))?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!(
+                "Failed to write {}: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+//! Links between resolved entities: people to households, contacts to
+//! accounts, accounts to their parent accounts.
+//!
+//! ```yaml
+//! relationships:
+//!   - name: member_of
+//!     to: household              # an entity of the workspace, or this one
+//!     cardinality: many_to_one   # many_to_one (default), one_to_one or
+//!                                # many_to_many
+//!     threshold: 0.8             # score at which an edge is kept
+//!     rules:
+//!       - name: address_exact
+//!         type: exact            # exact or fuzzy
+//!         field: address         # attribute of this entity
+//!         target_field: address  # attribute of the target; `field` if omitted
+//!         weight: 1.0
+//! ```
+//!
+//! Relationships are resolved after both entities have golden records: each
+//! golden record is compared with the target's canonical entities on the
+//! relationship's own rules, and pairs scoring at least `threshold` become
+//! edges in the `<name>_edges` table. Under `many_to_one` each record keeps
+//! only its best-scoring edge, under `one_to_one` each target does too, and
+//! `many_to_many` keeps every edge.
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+pub const CARDINALITIES: &[&str] = &["many_to_one", "one_to_one", "many_to_many"];
+
+/// Match types a relationship rule may use.
+pub const RULE_TYPES: &[&str] = &["exact", "fuzzy"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cardinality {
+    #[default]
+    ManyToOne,
+    OneToOne,
+    ManyToMany,
+}
+
+impl Cardinality {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cardinality::ManyToOne => "many_to_one",
+            Cardinality::OneToOne => "one_to_one",
+            Cardinality::ManyToMany => "many_to_many",
+        }
+    }
+}
+
+impl FromStr for Cardinality {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "many_to_one" => Ok(Cardinality::ManyToOne),
+            "one_to_one" => Ok(Cardinality::OneToOne),
+            "many_to_many" => Ok(Cardinality::ManyToMany),
+            _ => bail!(
+                "Unknown relationship cardinality '{}'. Use {}",
+                s,
+                CARDINALITIES.join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Cardinality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One entry of the `relationships` section.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relationship {
+    pub name: String,
+    /// Entity the links point to.
+    pub to: String,
+    #[serde(default)]
+    pub cardinality: Cardinality,
+    /// Score at which a pair becomes an edge; only perfect scores link if
+    /// absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    pub rules: Vec<RelationshipRule>,
+}
+
+/// A rule comparing an attribute of this entity with one of the target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationshipRule {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub match_type: String,
+    pub field: String,
+    pub target_field: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    pub weight: f64,
+}
+
+impl Relationship {
+    /// Read `relationships` from a parsed spec; empty if the spec has none.
+    pub fn from_spec(spec: &Value) -> Result<Vec<Self>> {
+        let Some(section) = spec.get("relationships").filter(|r| !r.is_null()) else {
+            return Ok(Vec::new());
+        };
+        let Some(entries) = section.as_array() else {
+            bail!("relationships must be a list, got {}", section);
+        };
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let mut entry = entry.clone();
+                // A rule compares the same attribute on both sides unless
+                // it names the target's.
+                if let Some(rules) = entry.get_mut("rules").and_then(Value::as_array_mut) {
+                    for rule in rules.iter_mut().filter_map(Value::as_object_mut) {
+                        if let Some(field) = rule.get("field").cloned() {
+                            rule.entry("target_field").or_insert(field);
+                        }
+                        rule.entry("weight").or_insert(Value::from(1.0));
+                    }
+                }
+                serde_json::from_value(entry)
+                    .map_err(|e| anyhow!("Invalid relationships[{}]: {}", i, e))
+            })
+            .collect()
+    }
+
+    /// The table the relationship's edges are written to.
+    pub fn edges_table(&self) -> String {
+        format!("{}_edges", self.name)
+    }
+
+    /// What resolving the relationship does, for plan descriptions.
+    pub fn description(&self, entity: &str) -> String {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| {
+                let compared = if rule.field == rule.target_field {
+                    rule.field.clone()
+                } else {
+                    format!("{} ~ {}", rule.field, rule.target_field)
+                };
+                match &rule.algorithm {
+                    Some(algorithm) => format!(
+                        "{} on {} via {} (w={})",
+                        rule.name, compared, algorithm, rule.weight
+                    ),
+                    None => format!("{} on {} (w={})", rule.name, compared, rule.weight),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let threshold = match self.threshold {
+            Some(threshold) => format!(" scoring >= {}", threshold),
+            None => String::new(),
+        };
+        format!(
+            "Link {} to {} ({}){}: {}",
+            entity, self.to, self.cardinality, threshold, rules
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::RELATIONSHIPS;
+    use crate::Ir;
+
+    #[test]
+    fn test_relationships_link_entities() {
+        use crate::workspace::select_yaml;
+        use crate::Cardinality;
+
+        assert!(crate::validate_yaml(RELATIONSHIPS).unwrap().is_empty());
+        assert_eq!(crate::format_spec(RELATIONSHIPS).unwrap(), RELATIONSHIPS);
+
+        let person = select_yaml(RELATIONSHIPS, Some("person")).unwrap();
+        let compiled = crate::compile_to_ir(&crate::parse_yaml(&person).unwrap()).unwrap();
+        let ir = Ir::from_value(&compiled).unwrap();
+        let [member_of] = ir.relationships.as_slice() else {
+            panic!("expected one relationship, got {:?}", ir.relationships);
+        };
+        assert_eq!(member_of.to, "household");
+        assert_eq!(member_of.cardinality, Cardinality::ManyToOne);
+        // A rule compares the same attribute on both sides unless told otherwise.
+        assert_eq!(member_of.rules[0].target_field, "address");
+        assert_eq!(member_of.rules[1].target_field, "family_name");
+        assert_eq!(
+            crate::compile_to_ir(&ir.to_spec()).unwrap()["plan_hash"],
+            compiled["plan_hash"]
+        );
+        // Specs without relationships compile as before.
+        let household = select_yaml(RELATIONSHIPS, Some("household")).unwrap();
+        let compiled = crate::compile_to_ir(&crate::parse_yaml(&household).unwrap()).unwrap();
+        assert!(compiled.get("relationships").is_none());
+
+        // Each relationship is resolved after survivorship into its edges table.
+        let plan = crate::generate_plan(&person).unwrap();
+        let stages: Vec<&str> = plan
+            .execution_stages
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(
+            &stages[stages.len() - 3..],
+            [
+                "Apply survivorship",
+                "Resolve relationship member_of",
+                "Emit outputs"
+            ]
+        );
+        let link = &plan.execution_stages[stages.len() - 2];
+        assert_eq!(
+            link.inputs,
+            ["golden_records", "household.canonical_entities"]
+        );
+        assert_eq!(link.outputs, ["member_of_edges"]);
+        let emit = plan.execution_stages.last().unwrap();
+        assert!(emit.inputs.contains(&"member_of_edges".to_string()));
+        assert!(emit.outputs.contains(&"relationship_edges".to_string()));
+        assert!(plan
+            .summary
+            .contains("member_of -> household (many_to_one)"));
+
+        // Links must name an entity of the workspace, and one of its attributes.
+        let codes = |yaml: &str| -> Vec<(String, Option<String>)> {
+            let spec = crate::parse_yaml(yaml).unwrap();
+            crate::semantic_diagnostics(&spec)
+                .into_iter()
+                .filter(|d| d.is_error())
+                .map(|d| (d.code, d.path))
+                .collect()
+        };
+        assert_eq!(
+            codes(&RELATIONSHIPS.replace("to: household", "to: family")),
+            [(
+                "KNV0121".to_string(),
+                Some("entities[0].relationships[0].to".to_string())
+            )]
+        );
+        assert_eq!(
+            codes(&RELATIONSHIPS.replace("target_field: family_name", "target_field: surname")),
+            [(
+                "KNV0101".to_string(),
+                Some("entities[0].relationships[0].rules[1].target_field".to_string())
+            )]
+        );
+        // On its own, a spec's links to other entities cannot be checked.
+        assert!(codes(&person.replace("to: household", "to: family")).is_empty());
+
+        // A hierarchy links an entity to itself, on its own attributes.
+        let hierarchy = person
+            .replace("to: household", "to: person")
+            .replace("target_field: family_name", "target_field: surname")
+            .replace("cardinality: many_to_one", "cardinality: one_to_many")
+            .replace("type: fuzzy", "type: semantic");
+        assert_eq!(
+            codes(&hierarchy),
+            [
+                (
+                    "KNV0121".to_string(),
+                    Some("relationships[0].cardinality".to_string())
+                ),
+                (
+                    "KNV0101".to_string(),
+                    Some("relationships[0].rules[1].target_field".to_string())
+                ),
+                (
+                    "KNV0121".to_string(),
+                    Some("relationships[0].rules[1].type".to_string())
+                ),
+            ]
+        );
+        let missing = crate::validate_yaml(&person.replace("    to: household\n", "")).unwrap();
+        assert_eq!(missing, ["relationships[0]: missing required field 'to'"]);
+    }
+}
@@ -0,0 +1,240 @@
+//! Retention: expiring values kept past their attribute's retention period.
+//!
+//! An attribute with a retention period (its own `retention_days` under
+//! `privacy.fields`, or the section's if it holds PII) expires on each
+//! record once that many days have passed since the record's values were
+//! collected: the date in its `privacy.collected_at` attribute, or the
+//! temporal `effective_from` without one. Only the date part counts.
+//!
+//! With `on_expiry: nullify` (the default) an expired value is nulled, so
+//! another member's value may survive in its place; with `tombstone` the
+//! record holding it is dropped, as a deleted record is. Either way the
+//! value reaches neither golden records nor lineage. Records without a
+//! date are kept as they are, and counted.
+//!
+//! `execute_plan` and `golden_records` expire values as of the date they
+//! are given (the CLI's `--as-of`, today by default) before they use them;
+//! `kanoniv maintain` does it to a stored members table and reports each
+//! expired value.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::clock;
+use crate::ir::Ir;
+use crate::privacy::{self, Expiry};
+use crate::sample::Sample;
+use crate::temporal::day_number;
+
+/// A value that outlived its retention period.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExpiredValue {
+    pub record_key: String,
+    pub field: String,
+    pub collected_at: String,
+    /// First day the value is no longer kept.
+    pub expired_on: String,
+    pub retention_days: u64,
+}
+
+/// What expiring a set of records did.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RetentionAudit {
+    pub as_of: String,
+    pub on_expiry: Expiry,
+    /// The attribute retention is counted from; `None` if the spec names
+    /// none, when every record is undated.
+    pub collected_at: Option<String>,
+    /// Records checked.
+    pub records: usize,
+    pub expired: Vec<ExpiredValue>,
+    /// Records dropped for holding an expired value.
+    pub tombstoned: usize,
+    /// Records with no date to count retention from, kept as they are.
+    pub undated: usize,
+}
+
+/// Days each attribute of `ir` is kept, for those with a retention period.
+pub fn retention_windows(ir: &Ir) -> BTreeMap<String, u64> {
+    let Some(privacy) = &ir.privacy else {
+        return BTreeMap::new();
+    };
+    let mut windows: BTreeMap<String, u64> = privacy::classify_ir(ir)
+        .into_iter()
+        .filter_map(|pii| Some((pii.attribute, pii.retention_days?)))
+        .collect();
+    for (attribute, field) in &privacy.fields {
+        if let Some(days) = field.retention_days {
+            windows.insert(attribute.clone(), days);
+        }
+    }
+    windows
+}
+
+/// Expire the values of `records` (a `record_key` column and a column per
+/// attribute; empty cells are nulls) as of `as_of`, a `YYYY-MM-DD` date.
+/// `None` if no attribute has a retention period.
+pub fn expire(ir: &Ir, records: &mut Sample, as_of: &str) -> Result<Option<RetentionAudit>> {
+    let Some(today) = day_number(as_of) else {
+        bail!("'{}' is not a date (YYYY-MM-DD)", as_of);
+    };
+    let windows = retention_windows(ir);
+    if windows.is_empty() {
+        return Ok(None);
+    }
+    let privacy = ir.privacy.clone().unwrap_or_default();
+    let collected_at = privacy
+        .collected_at
+        .clone()
+        .or_else(|| ir.temporal.as_ref().and_then(|t| t.effective_from.clone()));
+    let on_expiry = privacy.on_expiry.unwrap_or_default();
+    let mut audit = RetentionAudit {
+        as_of: clock::date(today),
+        on_expiry,
+        collected_at: collected_at.clone(),
+        records: records.len(),
+        expired: Vec::new(),
+        tombstoned: 0,
+        undated: 0,
+    };
+
+    let key = records.find_column("record_key");
+    let date = collected_at.as_deref().and_then(|c| records.find_column(c));
+    let columns: Vec<(&str, u64, usize)> = windows
+        .iter()
+        .filter_map(|(attribute, days)| {
+            Some((attribute.as_str(), *days, records.find_column(attribute)?))
+        })
+        .collect();
+    let mut kept = Vec::with_capacity(records.rows.len());
+    for mut row in std::mem::take(&mut records.rows) {
+        let collected = date
+            .and_then(|c| row.get(c))
+            .map(|v| v.trim().to_string())
+            .unwrap_or_default();
+        let Some(day) = day_number(&collected) else {
+            audit.undated += 1;
+            kept.push(row);
+            continue;
+        };
+        let mut expired = false;
+        for &(attribute, days, column) in &columns {
+            // Periods too long to reach a date never end.
+            let Some(expired_on) = i64::try_from(days).ok().and_then(|d| day.checked_add(d)) else {
+                continue;
+            };
+            if today < expired_on || row.get(column).is_none_or(|v| v.trim().is_empty()) {
+                continue;
+            }
+            expired = true;
+            audit.expired.push(ExpiredValue {
+                record_key: key.and_then(|k| row.get(k)).cloned().unwrap_or_default(),
+                field: attribute.to_string(),
+                collected_at: collected.clone(),
+                expired_on: clock::date(expired_on),
+                retention_days: days,
+            });
+            if on_expiry == Expiry::Nullify {
+                row[column].clear();
+            }
+        }
+        match expired && on_expiry == Expiry::Tombstone {
+            true => audit.tombstoned += 1,
+            false => kept.push(row),
+        }
+    }
+    records.rows = kept;
+    Ok(Some(audit))
+}
+
+/// A warning about the records of `audit` whose values were kept for want
+/// of a date, if any were.
+pub(crate) fn undated_warning(audit: &RetentionAudit) -> Option<String> {
+    if audit.undated == 0 {
+        return None;
+    }
+    Some(match &audit.collected_at {
+        Some(collected_at) => format!(
+            "{} record(s) have no '{}' date to count retention from; their values are kept",
+            audit.undated, collected_at
+        ),
+        None => "Retention periods are not enforced: give privacy.collected_at, the attribute to count them from".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, AS_OF, MINIMAL};
+
+    #[test]
+    fn test_expire_drops_values_past_their_retention_period() {
+        use crate::{expire, golden_records, retention_windows, Sample};
+
+        let spec = |on_expiry: &str| {
+            format!(
+                "{}privacy:\n  collected_at: updated_at\n  on_expiry: {}\n  fields:\n    email:\n      masking: none\n      retention_days: 365\n",
+                MINIMAL.replace("      email: email\n", "      email: email\n      updated_at: updated_at\n"),
+                on_expiry
+            )
+        };
+        let members = || {
+            Sample::from_csv("cluster_id,source_name,record_key,email,updated_at\ne1,crm,a,ann@x.com,2000-01-01\ne1,crm,b,ann@y.org,2025-06-01\ne2,crm,c,cy@z.com,\n").unwrap()
+        };
+        assert_eq!(
+            retention_windows(&compiled_ir(&spec("nullify"))).get("email"),
+            Some(&365)
+        );
+
+        // The value is kept through its last day, and nulled from the next.
+        let mut records = members();
+        let audit = expire(&compiled_ir(&spec("nullify")), &mut records, "2026-05-31")
+            .unwrap()
+            .unwrap();
+        assert_eq!(audit.expired.len(), 1);
+        assert_eq!(audit.expired[0].record_key, "a");
+        assert_eq!(audit.expired[0].expired_on, "2000-12-31");
+        assert_eq!(audit.undated, 1);
+        assert_eq!(records.rows[0][3], "");
+        assert_eq!(records.rows[1][3], "ann@y.org");
+        let mut records = members();
+        let audit = expire(&compiled_ir(&spec("nullify")), &mut records, "2026-06-01")
+            .unwrap()
+            .unwrap();
+        assert_eq!(audit.expired.len(), 2);
+        assert_eq!(records.rows[1][3], "");
+
+        // A tombstone drops the record; the undated one stays.
+        let mut records = members();
+        let audit = expire(&compiled_ir(&spec("tombstone")), &mut records, "2026-01-01")
+            .unwrap()
+            .unwrap();
+        assert_eq!(audit.tombstoned, 1);
+        assert_eq!(
+            records
+                .rows
+                .iter()
+                .map(|row| row[2].as_str())
+                .collect::<Vec<_>>(),
+            ["b", "c"]
+        );
+
+        // Golden records never see an expired value.
+        let golden = golden_records(&spec("nullify"), &members(), AS_OF).unwrap();
+        assert_eq!(golden.retention.as_ref().map(|r| r.expired.len()), Some(2));
+        assert!(golden.records.iter().all(|record| !record
+            .values
+            .iter()
+            .any(|v| v.as_deref() == Some("ann@x.com"))));
+
+        assert!(
+            expire(&compiled_ir(&spec("nullify")), &mut members(), "June 1st")
+                .unwrap_err()
+                .to_string()
+                .contains("not a date")
+        );
+        assert!(expire(&compiled_ir(MINIMAL), &mut members(), "2026-01-01")
+            .unwrap()
+            .is_none());
+    }
+}
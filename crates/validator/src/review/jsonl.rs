@@ -0,0 +1,56 @@
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+use super::{Backend, LogEntry};
+
+/// A queue in a JSON Lines file, one log entry per line.
+pub struct JsonlBackend {
+    path: PathBuf,
+}
+
+impl JsonlBackend {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JsonlBackend { path: path.into() }
+    }
+}
+
+impl Backend for JsonlBackend {
+    fn entries(&self) -> Result<Vec<LogEntry>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.path.display()))
+            }
+        };
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).with_context(|| {
+                    format!("{}:{}: not a review log entry", self.path.display(), i + 1)
+                })
+            })
+            .collect()
+    }
+
+    fn append(&self, entries: &[LogEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut lines = String::new();
+        for entry in entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to write file: {}", self.path.display()))?;
+        file.write_all(lines.as_bytes())
+            .with_context(|| format!("Failed to write file: {}", self.path.display()))
+    }
+}
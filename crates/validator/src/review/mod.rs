@@ -0,0 +1,561 @@
+//! Review: pairs scored into the review band, queued for data stewards.
+//!
+//! `kanoniv cluster --review-queue` routes each `review` decision to a
+//! queue, with the pair's records when it is given them, and clusters with
+//! the verdicts stewards have reached: an approved pair merges as a match,
+//! a rejected one stays apart. `kanoniv review` lists, inspects and decides
+//! pairs, and exports the decided ones as labeled pairs for `calibrate`.
+//!
+//! A queue is a decision log, oldest entry first: `queued` adds a pair,
+//! `merge` approves it and `unmerge` rejects it, with the reviewer, a note
+//! and the time. A pair stands as its latest verdict has it, so the log
+//! keeps every change of mind.
+//!
+//! Locations: a JSON Lines file, one entry per line, or a SQLite database
+//! for a path ending in `.db`, `.sqlite` or `.sqlite3` (with the `sqlite`
+//! feature), which suits a team of stewards.
+
+pub mod jsonl;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::clock;
+use crate::commands::compile::compile_to_ir;
+use crate::explanation::canonical;
+use crate::ir::Ir;
+use crate::parser;
+use crate::sample::Sample;
+
+pub const STATUSES: &[&str] = &["pending", "approved", "rejected"];
+
+/// Extensions of SQLite queue locations.
+pub const SQLITE_EXTENSIONS: &[&str] = &["db", "sqlite", "sqlite3"];
+
+/// Where a queue's log is kept.
+pub trait Backend {
+    /// Every entry, oldest first.
+    fn entries(&self) -> Result<Vec<LogEntry>>;
+    /// Add `entries` after the last.
+    fn append(&self, entries: &[LogEntry]) -> Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LogEntry {
+    Queued(ReviewPair),
+    Merge(Verdict),
+    Unmerge(Verdict),
+}
+
+impl LogEntry {
+    pub fn pair_id(&self) -> &str {
+        match self {
+            LogEntry::Queued(pair) => &pair.id,
+            LogEntry::Merge(verdict) | LogEntry::Unmerge(verdict) => &verdict.pair_id,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogEntry::Queued(_) => "queued",
+            LogEntry::Merge(_) => "merge",
+            LogEntry::Unmerge(_) => "unmerge",
+        }
+    }
+
+    /// When the entry was made, `YYYY-MM-DDTHH:MM:SSZ`.
+    pub fn time(&self) -> &str {
+        match self {
+            LogEntry::Queued(pair) => &pair.queued_at,
+            LogEntry::Merge(verdict) | LogEntry::Unmerge(verdict) => &verdict.decided_at,
+        }
+    }
+}
+
+/// A pair scored into the review band.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewPair {
+    /// `pair_id(left_key, right_key)`.
+    pub id: String,
+    pub left_key: String,
+    pub right_key: String,
+    pub score: f64,
+    pub plan_hash: String,
+    /// The records compared, as JSON objects, when the engine had them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right: Option<Value>,
+    pub queued_at: String,
+}
+
+/// A steward's verdict on a pair.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Verdict {
+    pub pair_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub decided_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl ReviewStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReviewStatus::Pending => "pending",
+            ReviewStatus::Approved => "approved",
+            ReviewStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl fmt::Display for ReviewStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for ReviewStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pending" => Ok(ReviewStatus::Pending),
+            "approved" => Ok(ReviewStatus::Approved),
+            "rejected" => Ok(ReviewStatus::Rejected),
+            other => bail!(
+                "Unknown review status: '{}'. Expected one of: {}",
+                other,
+                STATUSES.join(", ")
+            ),
+        }
+    }
+}
+
+/// A queued pair as its latest verdict leaves it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReviewItem {
+    #[serde(flatten)]
+    pub pair: ReviewPair,
+    pub status: ReviewStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<Verdict>,
+}
+
+/// A pair's id: the first 12 hex digits of the SHA-256 of its keys, the
+/// same on every run and short enough to type.
+pub fn pair_id(left_key: &str, right_key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(left_key.as_bytes());
+    hasher.update([0]);
+    hasher.update(right_key.as_bytes());
+    format!("{:x}", hasher.finalize())[..12].to_string()
+}
+
+pub struct ReviewQueue {
+    location: String,
+    backend: Box<dyn Backend>,
+}
+
+impl ReviewQueue {
+    /// Open the queue at `location`, creating it if need be; the backend is
+    /// chosen by extension.
+    pub fn open(location: &str) -> Result<Self> {
+        let path = Path::new(location);
+        let sqlite = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| SQLITE_EXTENSIONS.contains(&e));
+        let backend: Box<dyn Backend> = if sqlite {
+            sqlite_backend(path)?
+        } else {
+            Box::new(jsonl::JsonlBackend::new(path))
+        };
+        Ok(Self::with_backend(location, backend))
+    }
+
+    /// A queue over any backend.
+    pub fn with_backend(location: &str, backend: Box<dyn Backend>) -> Self {
+        ReviewQueue {
+            location: location.to_string(),
+            backend,
+        }
+    }
+
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// The decision log, oldest entry first.
+    pub fn log(&self) -> Result<Vec<LogEntry>> {
+        self.backend.entries()
+    }
+
+    /// Every queued pair, in queue order.
+    pub fn items(&self) -> Result<Vec<ReviewItem>> {
+        let mut items: Vec<ReviewItem> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for entry in self.log()? {
+            let (status, verdict) = match entry {
+                LogEntry::Queued(pair) => {
+                    if !index.contains_key(&pair.id) {
+                        index.insert(pair.id.clone(), items.len());
+                        items.push(ReviewItem {
+                            pair,
+                            status: ReviewStatus::Pending,
+                            verdict: None,
+                        });
+                    }
+                    continue;
+                }
+                LogEntry::Merge(verdict) => (ReviewStatus::Approved, verdict),
+                LogEntry::Unmerge(verdict) => (ReviewStatus::Rejected, verdict),
+            };
+            if let Some(&i) = index.get(&verdict.pair_id) {
+                items[i].status = status;
+                items[i].verdict = Some(verdict);
+            }
+        }
+        Ok(items)
+    }
+
+    /// The pair `id` names; a unique prefix will do.
+    pub fn item(&self, id: &str) -> Result<ReviewItem> {
+        let mut found: Vec<ReviewItem> = self
+            .items()?
+            .into_iter()
+            .filter(|item| item.pair.id.starts_with(id))
+            .collect();
+        match found.len() {
+            1 => Ok(found.remove(0)),
+            0 => bail!("No pair '{}' in the review queue {}", id, self.location),
+            n => bail!("'{}' names {} pairs; give more of the id", id, n),
+        }
+    }
+
+    /// Queue the pairs not queued before; returns how many were new.
+    pub fn enqueue(&self, pairs: Vec<ReviewPair>) -> Result<usize> {
+        let mut queued: Vec<String> = self
+            .log()?
+            .iter()
+            .filter(|entry| matches!(entry, LogEntry::Queued(_)))
+            .map(|entry| entry.pair_id().to_string())
+            .collect();
+        let mut entries = Vec::new();
+        for pair in pairs {
+            if !queued.contains(&pair.id) {
+                queued.push(pair.id.clone());
+                entries.push(LogEntry::Queued(pair));
+            }
+        }
+        self.backend.append(&entries)?;
+        Ok(entries.len())
+    }
+
+    /// Approve (merge) or reject (unmerge) the pair `id` names; a pair
+    /// decided before takes the new verdict.
+    pub fn decide(
+        &self,
+        id: &str,
+        status: ReviewStatus,
+        reviewer: Option<&str>,
+        note: Option<&str>,
+    ) -> Result<ReviewItem> {
+        let mut item = self.item(id)?;
+        let verdict = Verdict {
+            pair_id: item.pair.id.clone(),
+            reviewer: reviewer.map(str::to_string),
+            note: note.map(str::to_string),
+            decided_at: clock::now(),
+        };
+        let entry = match status {
+            ReviewStatus::Approved => LogEntry::Merge(verdict.clone()),
+            ReviewStatus::Rejected => LogEntry::Unmerge(verdict.clone()),
+            ReviewStatus::Pending => bail!("A pair can be approved or rejected, not made pending"),
+        };
+        self.backend.append(&[entry])?;
+        item.status = status;
+        item.verdict = Some(verdict);
+        Ok(item)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_backend(path: &Path) -> Result<Box<dyn Backend>> {
+    Ok(Box::new(sqlite::SqliteBackend::open(path)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn sqlite_backend(_path: &Path) -> Result<Box<dyn Backend>> {
+    bail!("kanoniv was built without the `sqlite` feature")
+}
+
+/// The pairs `decisions` sends to review, with their records from
+/// `records` (matched by `record_key`) if given.
+pub fn review_pairs(
+    yaml: &str,
+    decisions: &Sample,
+    records: Option<&Sample>,
+) -> Result<Vec<ReviewPair>> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let [left, right, score, decision] = decision_columns(decisions)?;
+    let by_key: HashMap<&str, &Vec<String>> = match records {
+        Some(records) => {
+            let Some(key) = records.find_column("record_key") else {
+                bail!("records table has no 'record_key' column");
+            };
+            records
+                .rows
+                .iter()
+                .map(|row| (row.get(key).map_or("", String::as_str), row))
+                .collect()
+        }
+        None => HashMap::new(),
+    };
+    let record = |key: &str| -> Option<Value> {
+        let row = by_key.get(key)?;
+        let columns = &records?.columns;
+        let fields: serde_json::Map<String, Value> = columns
+            .iter()
+            .zip(row.iter())
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(column, value)| (column.clone(), Value::String(value.clone())))
+            .collect();
+        Some(Value::Object(fields))
+    };
+
+    let queued_at = clock::now();
+    let mut pairs = Vec::new();
+    for (i, row) in decisions.rows.iter().enumerate() {
+        let cell = |column: usize| row.get(column).map_or("", String::as_str);
+        if cell(decision) != "review" {
+            continue;
+        }
+        let Ok(score) = cell(score).parse::<f64>() else {
+            bail!(
+                "decisions row {}: score '{}' is not a number",
+                i + 1,
+                cell(score)
+            );
+        };
+        let (left_key, right_key) = (cell(left), cell(right));
+        pairs.push(ReviewPair {
+            id: pair_id(left_key, right_key),
+            left_key: left_key.to_string(),
+            right_key: right_key.to_string(),
+            score,
+            plan_hash: ir.plan_hash.clone(),
+            left: record(left_key),
+            right: record(right_key),
+            queued_at: queued_at.clone(),
+        });
+    }
+    Ok(pairs)
+}
+
+/// `decisions` with the stewards' verdicts in: an approved pair's decision
+/// becomes `match`, a rejected one's `reject`. Also returns how many
+/// decisions changed.
+pub fn apply_verdicts(decisions: &Sample, items: &[ReviewItem]) -> Result<(Sample, usize)> {
+    let [left, right, _, decision] = decision_columns(decisions)?;
+    let verdicts: HashMap<&str, ReviewStatus> = items
+        .iter()
+        .map(|item| (item.pair.id.as_str(), item.status))
+        .collect();
+    let mut applied = decisions.clone();
+    let mut changed = 0;
+    for row in &mut applied.rows {
+        if row.get(decision).map(String::as_str) != Some("review") {
+            continue;
+        }
+        let cell = |column: usize| row.get(column).map_or("", String::as_str);
+        let verdict = match verdicts.get(pair_id(cell(left), cell(right)).as_str()) {
+            Some(ReviewStatus::Approved) => "match",
+            Some(ReviewStatus::Rejected) => "reject",
+            _ => continue,
+        };
+        row[decision] = verdict.to_string();
+        changed += 1;
+    }
+    Ok((applied, changed))
+}
+
+/// The decided pairs with both records as labeled pairs for `calibrate`: a
+/// `label` column (`match` for approved pairs, `non_match` for rejected
+/// ones) and a `left_<attribute>` and `right_<attribute>` column per
+/// attribute. Also returns how many decided pairs lack records.
+pub fn training_labels(yaml: &str, items: &[ReviewItem]) -> Result<(Sample, usize)> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let attributes = ir.attributes();
+
+    let mut columns = vec!["label".to_string()];
+    for side in ["left", "right"] {
+        columns.extend(attributes.iter().map(|a| format!("{}_{}", side, a)));
+    }
+    let mut rows = Vec::new();
+    let mut missing = 0;
+    for item in items {
+        let label = match item.status {
+            ReviewStatus::Approved => "match",
+            ReviewStatus::Rejected => "non_match",
+            ReviewStatus::Pending => continue,
+        };
+        let (Some(left), Some(right)) = (&item.pair.left, &item.pair.right) else {
+            missing += 1;
+            continue;
+        };
+        let mut row = vec![label.to_string()];
+        for record in [left, right] {
+            let record = canonical(&ir, record)?;
+            row.extend(attributes.iter().map(|a| match record.get(a) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => s.clone(),
+                Some(value) => value.to_string(),
+            }));
+        }
+        rows.push(row);
+    }
+    Ok((Sample { columns, rows }, missing))
+}
+
+/// Counts of queued pairs by status.
+pub fn status_counts(items: &[ReviewItem]) -> BTreeMap<&'static str, usize> {
+    let mut counts: BTreeMap<&'static str, usize> = STATUSES.iter().map(|&s| (s, 0)).collect();
+    for item in items {
+        *counts.entry(item.status.name()).or_default() += 1;
+    }
+    counts
+}
+
+fn decision_columns(decisions: &Sample) -> Result<[usize; 4]> {
+    let required = |name: &str| match decisions.find_column(name) {
+        Some(index) => Ok(index),
+        None => bail!("decisions table has no '{}' column", name),
+    };
+    Ok([
+        required("left_key")?,
+        required("right_key")?,
+        required("score")?,
+        required("decision")?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_review_queue_decision_log() {
+        use crate::review::{pair_id, LogEntry};
+        use crate::{
+            apply_verdicts, review_pairs, training_labels, ReviewQueue, ReviewStatus, Sample,
+        };
+
+        let rows = |rows: &[&[&str]]| -> Vec<Vec<String>> {
+            rows.iter()
+                .map(|r| r.iter().map(|c| c.to_string()).collect())
+                .collect()
+        };
+        let decisions = Sample {
+            columns: ["left_key", "right_key", "score", "decision"]
+                .map(String::from)
+                .to_vec(),
+            rows: rows(&[
+                &["a", "b", "0.85", "review"],
+                &["c", "d", "0.8", "review"],
+                &["e", "f", "0.95", "match"],
+            ]),
+        };
+        let records = Sample {
+            columns: ["record_key", "email"].map(String::from).to_vec(),
+            rows: rows(&[&["a", "a@x.com"], &["b", "A@x.com"], &["c", "c@x.com"]]),
+        };
+        let pairs = review_pairs(MINIMAL, &decisions, Some(&records)).unwrap();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].id, pair_id("a", "b"));
+        assert_eq!(pairs[0].left.as_ref().unwrap()["email"], "a@x.com");
+        assert!(pairs[1].right.is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut names = vec!["queue.jsonl"];
+        // SQLite queues need the sqlite feature.
+        if cfg!(feature = "sqlite") {
+            names.push("queue.db");
+        }
+        for name in names {
+            let queue = ReviewQueue::open(dir.path().join(name).to_str().unwrap()).unwrap();
+            assert_eq!(queue.enqueue(pairs.clone()).unwrap(), 2);
+            // Pairs already queued are not queued again.
+            assert_eq!(queue.enqueue(pairs.clone()).unwrap(), 0);
+            queue
+                .decide(&pairs[0].id[..6], ReviewStatus::Rejected, None, None)
+                .unwrap();
+            queue
+                .decide(
+                    &pairs[0].id,
+                    ReviewStatus::Approved,
+                    Some("ana"),
+                    Some("same inbox"),
+                )
+                .unwrap();
+            queue
+                .decide(&pairs[1].id, ReviewStatus::Rejected, None, None)
+                .unwrap();
+            assert!(queue
+                .decide("zzz", ReviewStatus::Approved, None, None)
+                .is_err());
+
+            let log = queue.log().unwrap();
+            assert_eq!(
+                log.iter().map(LogEntry::name).collect::<Vec<_>>(),
+                ["queued", "queued", "unmerge", "merge", "unmerge"]
+            );
+            let items = queue.items().unwrap();
+            assert_eq!(
+                (items[0].status, items[1].status),
+                (ReviewStatus::Approved, ReviewStatus::Rejected)
+            );
+            assert_eq!(
+                items[0].verdict.as_ref().unwrap().reviewer.as_deref(),
+                Some("ana")
+            );
+
+            let (applied, changed) = apply_verdicts(&decisions, &items).unwrap();
+            assert_eq!(changed, 2);
+            assert_eq!(
+                applied
+                    .rows
+                    .iter()
+                    .map(|r| r[3].as_str())
+                    .collect::<Vec<_>>(),
+                ["match", "reject", "match"]
+            );
+
+            // The pair queued without both records cannot be labeled.
+            let (labels, missing) = training_labels(MINIMAL, &items).unwrap();
+            assert_eq!(labels.columns, ["label", "left_email", "right_email"]);
+            assert_eq!(labels.rows, [["match", "a@x.com", "A@x.com"]]);
+            assert_eq!(missing, 1);
+        }
+    }
+}
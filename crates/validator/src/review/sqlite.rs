@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+use super::{Backend, LogEntry};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS review_log (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    event TEXT NOT NULL,
+    pair_id TEXT NOT NULL,
+    recorded_at TEXT NOT NULL,
+    entry TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS review_log_pair_id ON review_log (pair_id);
+";
+
+/// A queue in a SQLite database: a `review_log` table, a row per entry
+/// with the entry as JSON, which several stewards can share.
+pub struct SqliteBackend {
+    connection: Connection,
+}
+
+impl SqliteBackend {
+    /// Open the database at `path`, creating it and the table if need be.
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection =
+            Connection::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .with_context(|| format!("Failed to create the review log in {}", path.display()))?;
+        Ok(SqliteBackend { connection })
+    }
+}
+
+impl Backend for SqliteBackend {
+    fn entries(&self) -> Result<Vec<LogEntry>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT seq, entry FROM review_log ORDER BY seq")?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (seq, entry) = row?;
+            entries.push(
+                serde_json::from_str(&entry)
+                    .with_context(|| format!("review_log row {}: not a review log entry", seq))?,
+            );
+        }
+        Ok(entries)
+    }
+
+    fn append(&self, entries: &[LogEntry]) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO review_log (event, pair_id, recorded_at, entry) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for entry in entries {
+                statement.execute(params![
+                    entry.name(),
+                    entry.pair_id(),
+                    entry.time(),
+                    serde_json::to_string(entry)?
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+//! Organization-defined lint checks.
+//!
+//! A rule pack declares house standards for `kanoniv validate --rules`,
+//! each checked at every node its `select` path picks out of the spec:
+//!
+//! ```yaml
+//! checks:
+//!   - code: CORP_PII_SURVIVORSHIP
+//!     severity: error
+//!     select: "sources[*].attributes.*"
+//!     when:
+//!       any:
+//!         - equals: { path: type, value: email }
+//!         - equals: { path: type, value: phone }
+//!     require:
+//!       equals: { path: "$.survivorship.rules[*].field", value: "{key}" }
+//!     message: PII attribute '{key}' has no survivorship rule.
+//!     suggestion: Add a survivorship rule for '{key}'.
+//! ```
+//!
+//! `when` and `require` are the conditions of custom risk rules (see
+//! `custom_risks`), with paths relative to the selected node and `$` paths
+//! from the spec root. A finding is raised at each node where `when` holds
+//! (or is absent) and `require` does not. `{key}` (the node's mapping key
+//! or list index) and `{path}` are filled in in the message, the suggestion
+//! and the conditions' paths and string values. Without `select`, a check
+//! runs once on the whole spec.
+//!
+//! Severity is `error`, `warning` or `info`. Findings carry the check's code
+//! and path, so they are located, waived and set by a policy like built-in
+//! ones. A pack is a YAML file, or a directory of them.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::cache;
+use crate::custom_risks::{self, Condition};
+use crate::diagnostics::{codes, Diagnostic};
+use crate::validator;
+use crate::workspace::Workspace;
+
+pub const SEVERITIES: &[&str] = &["error", "warning", "info"];
+
+/// The checks declared in one or more rule pack files.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RulePack {
+    #[serde(default)]
+    pub checks: Vec<Check>,
+    /// Digest of the YAML the checks were read from.
+    #[serde(skip)]
+    digest: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Check {
+    pub code: String,
+    /// `error`, `warning` or `info`.
+    pub severity: String,
+    pub message: String,
+    #[serde(default)]
+    pub suggestion: Option<String>,
+    /// Path (with `[*]` wildcards) to the nodes checked; the spec if absent.
+    #[serde(default)]
+    pub select: Option<String>,
+    /// Only nodes where this holds are checked.
+    #[serde(default)]
+    pub when: Option<Condition>,
+    /// A finding is raised at each checked node where this does not hold.
+    pub require: Condition,
+}
+
+impl RulePack {
+    /// Read a rule pack file, or every `.yaml`/`.yml` file in a directory
+    /// (in name order).
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_dir() {
+            return Self::load_file(path);
+        }
+        let mut files: Vec<_> = fs::read_dir(path)
+            .with_context(|| format!("Failed to read rule packs: {}", path.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|file| {
+                file.extension()
+                    .is_some_and(|ext| ext == "yaml" || ext == "yml")
+            })
+            .collect();
+        files.sort();
+        let mut pack = RulePack::default();
+        let mut digests = Vec::new();
+        for file in files {
+            let file = Self::load_file(&file)?;
+            pack.checks.extend(file.checks);
+            digests.push(file.digest);
+        }
+        pack.digest = cache::digest(digests.join("\n").as_bytes());
+        Ok(pack)
+    }
+
+    fn load_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rule pack: {}", path.display()))?;
+        Self::from_yaml(&content)
+            .map_err(|e| anyhow!("Invalid rule pack {}: {:#}", path.display(), e))
+    }
+
+    /// Parse checks from YAML text.
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let mut pack: RulePack = serde_yaml::from_str(yaml)?;
+        pack.digest = cache::digest(yaml.as_bytes());
+        for (i, check) in pack.checks.iter().enumerate() {
+            if check.code.trim().is_empty() {
+                bail!("checks[{}]: code must not be empty", i);
+            }
+            if codes::lookup(check.code.trim()).is_some() {
+                bail!(
+                    "checks[{}]: code {} is a built-in diagnostic code",
+                    i,
+                    check.code
+                );
+            }
+            if !SEVERITIES.contains(&check.severity.as_str()) {
+                bail!(
+                    "checks[{}] ({}): unknown severity '{}' (expected {})",
+                    i,
+                    check.code,
+                    check.severity,
+                    SEVERITIES.join(", ")
+                );
+            }
+        }
+        Ok(pack)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+
+    /// A digest of the pack's YAML, which changes with any edit to it:
+    /// the pack's version, for caching results it went into. Empty for
+    /// no pack.
+    pub fn digest(&self) -> &str {
+        &self.digest
+    }
+
+    /// Findings for every check that fails on `spec`; in a workspace, on
+    /// each entity spec.
+    pub fn diagnostics(&self, spec: &Value) -> Vec<Diagnostic> {
+        if let Some(workspace) = Workspace::from_value(spec) {
+            return validator::per_entity(&workspace, |entity| self.diagnostics(entity));
+        }
+        self.checks
+            .iter()
+            .flat_map(|check| check.diagnostics(spec))
+            .collect()
+    }
+}
+
+impl Check {
+    fn diagnostics(&self, spec: &Value) -> Vec<Diagnostic> {
+        let code = self.code.trim().to_uppercase();
+        let selected = match &self.select {
+            Some(path) => custom_risks::select(spec, path.strip_prefix('$').unwrap_or(path)),
+            None => custom_risks::select(spec, ""),
+        };
+        let mut findings = Vec::new();
+        for node in selected {
+            let fill = |text: &str| {
+                text.replace("{key}", &node.key)
+                    .replace("{path}", &node.path)
+            };
+            let holds = |condition: &Condition| bind(condition, &fill).holds_at(spec, node.value);
+            if self.when.as_ref().is_some_and(|when| !holds(when)) || holds(&self.require) {
+                continue;
+            }
+            let finding = match self.severity.as_str() {
+                "error" => Diagnostic::error,
+                "warning" => Diagnostic::warning,
+                _ => Diagnostic::info,
+            };
+            let mut finding = finding(&code, node.path.clone(), fill(&self.message));
+            if let Some(suggestion) = &self.suggestion {
+                finding = finding.with_suggestion(fill(suggestion));
+            }
+            findings.push(finding);
+        }
+        findings
+    }
+}
+
+/// `condition` with `fill` applied to its paths and string values.
+fn bind(condition: &Condition, fill: &dyn Fn(&str) -> String) -> Condition {
+    let value = |value: &Value| match value {
+        Value::String(s) => Value::String(fill(s)),
+        other => other.clone(),
+    };
+    match condition {
+        Condition::All(conditions) => {
+            Condition::All(conditions.iter().map(|c| bind(c, fill)).collect())
+        }
+        Condition::Any(conditions) => {
+            Condition::Any(conditions.iter().map(|c| bind(c, fill)).collect())
+        }
+        Condition::Not(condition) => Condition::Not(Box::new(bind(condition, fill))),
+        Condition::Exists(path) => Condition::Exists(fill(path)),
+        Condition::Missing(path) => Condition::Missing(fill(path)),
+        Condition::Equals { path, value: v } => Condition::Equals {
+            path: fill(path),
+            value: value(v),
+        },
+        Condition::LessThan { path, value } => Condition::LessThan {
+            path: fill(path),
+            value: *value,
+        },
+        Condition::GreaterThan { path, value } => Condition::GreaterThan {
+            path: fill(path),
+            value: *value,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MINIMAL, TYPED};
+    use crate::Severity;
+
+    #[test]
+    fn test_rule_pack_checks_selected_nodes() {
+        let pack = crate::RulePack::from_yaml(
+            r#"
+checks:
+  - code: corp_pii_normalized
+    severity: warning
+    select: "sources[*].attributes.*"
+    when:
+      any:
+        - equals: { path: type, value: email }
+        - equals: { path: type, value: phone }
+    require:
+      exists: "$.normalization.fields.{key}"
+    message: PII attribute '{key}' is not normalized.
+    suggestion: Add a normalization pipeline for {path}.
+  - code: CORP_RULE_WEIGHT
+    severity: error
+    select: "rules[*]"
+    require:
+      greater_than: { path: weight, value: 0.4 }
+    message: Rule {key} weighs too little.
+"#,
+        )
+        .unwrap();
+
+        // Every typed PII attribute is normalized; removing one pipeline raises
+        // a warning at that attribute.
+        let typed = crate::parse_yaml(TYPED).unwrap();
+        assert!(pack.diagnostics(&typed).is_empty());
+        let yaml = TYPED.replace("    phone: [phone]\n", "");
+        let findings = pack.diagnostics(&crate::parse_yaml(&yaml).unwrap());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].code, "CORP_PII_NORMALIZED");
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(
+            findings[0].path.as_deref(),
+            Some("sources[0].attributes.phone")
+        );
+        assert_eq!(
+            findings[0].message,
+            "PII attribute 'phone' is not normalized."
+        );
+        assert_eq!(
+            findings[0].suggestion.as_deref(),
+            Some("Add a normalization pipeline for sources[0].attributes.phone.")
+        );
+
+        let light = crate::parse_yaml(&MINIMAL.replace("weight: 1.0", "weight: 0.2")).unwrap();
+        let errors = crate::validate_semantics_with_rules(&light, &pack).unwrap();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("Rule 0 weighs too little."));
+
+        let bad = |yaml: &str| crate::RulePack::from_yaml(yaml).unwrap_err().to_string();
+        assert!(bad("checks:\n  - code: X\n    severity: high\n    message: m\n    require: { exists: rules }\n")
+        .contains("unknown severity 'high'"));
+        assert!(bad("checks:\n  - code: zero-weight-rule\n    severity: info\n    message: m\n    require: { exists: rules }\n")
+        .contains("built-in diagnostic code"));
+    }
+}
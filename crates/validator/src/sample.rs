@@ -0,0 +1,516 @@
+//! Sample records for data-aware planning and profiling.
+//!
+//! `kanoniv plan --sample data.csv` measures blocking on real records
+//! instead of estimating it from the spec, and `kanoniv profile` checks a
+//! source's attributes (see `profile`). Samples are CSV files with a header
+//! row, Parquet files (`.parquet`) or Arrow IPC files (`.arrow`, `.feather`,
+//! `.ipc`), whose values are read as text; embedders can hand over Arrow
+//! record batches directly (`Sample::from_record_batches`), or an Arrow C
+//! stream such as a pandas or polars DataFrame exports. A
+//! canonical attribute is read from the column of the same name, or else
+//! from the column a source maps it to (`attributes: { email: email_address }`).
+//! Empty cells and nulls count as missing.
+
+use anyhow::{bail, Context, Result};
+use arrow_array::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+use arrow_array::RecordBatch;
+use arrow_cast::display::{ArrayFormatter, FormatOptions};
+use arrow_schema::{ArrowError, Field, SchemaRef};
+#[cfg(feature = "parquet")]
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::address;
+use crate::attributes;
+use crate::ir::Ir;
+use crate::mappings;
+use crate::similarity::soundex;
+
+#[derive(Debug, Clone, Default)]
+pub struct Sample {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Sample {
+    /// Read a CSV file, or a Parquet or Arrow IPC file by its extension.
+    pub fn load(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("parquet") => {
+                return Self::from_parquet(path)
+                    .with_context(|| format!("Invalid Parquet file {}", path.display()))
+            }
+            Some("arrow" | "feather" | "ipc") => {
+                return Self::from_arrow_ipc(path)
+                    .with_context(|| format!("Invalid Arrow file {}", path.display()))
+            }
+            _ => {}
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        Self::from_csv(&content).with_context(|| format!("Invalid CSV {}", path.display()))
+    }
+
+    /// Parse CSV text with a header row.
+    pub fn from_csv(text: &str) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(text.as_bytes());
+        let columns: Vec<String> = reader
+            .headers()?
+            .iter()
+            .map(|c| c.trim().to_string())
+            .collect();
+        if columns.iter().all(String::is_empty) {
+            bail!("the sample has no header row");
+        }
+        let rows = reader
+            .records()
+            .map(|record| record.map(|r| r.iter().map(str::to_string).collect()))
+            .collect::<Result<_, _>>()?;
+        Ok(Sample { columns, rows })
+    }
+
+    /// Records given as JSON objects, with a column per key. Nested values
+    /// are read in their JSON text form.
+    pub fn from_json_records(records: &[Value]) -> Result<Self> {
+        let mut columns: Vec<String> = Vec::new();
+        for (i, record) in records.iter().enumerate() {
+            let Value::Object(fields) = record else {
+                bail!("record {} is not a JSON object", i + 1);
+            };
+            for key in fields.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+        let rows = records
+            .iter()
+            .map(|record| {
+                columns
+                    .iter()
+                    .map(|column| match record.get(column) {
+                        None | Some(Value::Null) => String::new(),
+                        Some(Value::String(s)) => s.clone(),
+                        Some(value) => value.to_string(),
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(Sample { columns, rows })
+    }
+
+    /// Read a Parquet file's top-level columns, a row group at a time;
+    /// nested values are read in their JSON-like text form.
+    #[cfg(feature = "parquet")]
+    pub fn from_parquet(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        Self::from_batch_reader(ParquetRecordBatchReaderBuilder::try_new(file)?.build()?)
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    pub fn from_parquet(_path: &Path) -> Result<Self> {
+        bail!("kanoniv was built without the `parquet` feature")
+    }
+
+    /// Read an Arrow IPC (Feather v2) file, a record batch at a time.
+    pub fn from_arrow_ipc(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        Self::from_batch_reader(arrow_ipc::reader::FileReader::try_new(file, None)?)
+    }
+
+    /// Read Arrow record batches sharing one schema (nullability aside),
+    /// column by column.
+    /// Values are read as text, as `arrow-cast` displays them (dates and
+    /// timestamps in ISO 8601); nulls count as missing.
+    pub fn from_record_batches(batches: &[RecordBatch]) -> Result<Self> {
+        Self::from_batch_reader(batches.iter().cloned().map(Ok))
+    }
+
+    /// `from_record_batches`, reading each batch as the reader yields it so
+    /// only one is held at a time.
+    fn from_batch_reader(
+        batches: impl IntoIterator<Item = Result<RecordBatch, ArrowError>>,
+    ) -> Result<Self> {
+        let mut sample = Sample::default();
+        let mut schema: Option<SchemaRef> = None;
+        let options = FormatOptions::default().with_null("");
+        let same = |a: &Field, b: &Field| a.name() == b.name() && a.data_type() == b.data_type();
+        for batch in batches {
+            let batch = batch?;
+            let schema = schema.get_or_insert_with(|| batch.schema());
+            if sample.columns.is_empty() {
+                sample.columns = schema.fields().iter().map(|f| f.name().clone()).collect();
+            }
+            if batch.num_columns() != sample.columns.len()
+                || !batch
+                    .schema()
+                    .fields()
+                    .iter()
+                    .zip(schema.fields())
+                    .all(|(a, b)| same(a, b))
+            {
+                bail!("record batches have different schemas");
+            }
+            let formatters = batch
+                .columns()
+                .iter()
+                .map(|array| ArrayFormatter::try_new(array.as_ref(), &options))
+                .collect::<Result<Vec<_>, _>>()?;
+            sample.rows.reserve(batch.num_rows());
+            for row in 0..batch.num_rows() {
+                sample.rows.push(
+                    formatters
+                        .iter()
+                        .map(|formatter| formatter.value(row).to_string())
+                        .collect(),
+                );
+            }
+        }
+        Ok(sample)
+    }
+
+    /// Read an Arrow C stream (the Arrow C stream interface), taking
+    /// ownership of it.
+    ///
+    /// # Safety
+    ///
+    /// `stream` must point to a valid `ArrowArrayStream`; it is left
+    /// released.
+    pub unsafe fn from_arrow_c_stream(stream: *mut FFI_ArrowArrayStream) -> Result<Self> {
+        Self::from_batch_reader(ArrowArrayStreamReader::from_raw(stream)?)
+    }
+
+    /// Stack records of several sources, each in its own columns, into one
+    /// sample with a column per canonical attribute (in the order sources
+    /// declare them). An attribute is read from the column its source maps
+    /// it to, or else from the column of the same name.
+    pub fn from_sources(spec: &Value, parts: &[(String, Sample)]) -> Result<Self> {
+        let spec = &mappings::resolve(spec);
+        let sources: Vec<(&str, &Value)> = match spec.get("sources") {
+            Some(Value::Array(sources)) => sources
+                .iter()
+                .filter_map(|s| Some((s.get("name")?.as_str()?, s)))
+                .collect(),
+            Some(Value::Object(sources)) => {
+                sources.iter().map(|(name, s)| (name.as_str(), s)).collect()
+            }
+            _ => Vec::new(),
+        };
+        let mut columns: Vec<String> = Vec::new();
+        for (_, source) in &sources {
+            if let Some(Value::Object(attributes)) = source.get("attributes") {
+                for attribute in attributes.keys() {
+                    if !columns.contains(attribute) {
+                        columns.push(attribute.clone());
+                    }
+                }
+            }
+        }
+        let mut rows = Vec::new();
+        for (name, part) in parts {
+            let Some((_, source)) = sources.iter().find(|(n, _)| n == name) else {
+                bail!("unknown source '{}'", name);
+            };
+            let indices: Vec<Option<usize>> = columns
+                .iter()
+                .map(|attribute| {
+                    source
+                        .get("attributes")
+                        .and_then(|a| a.get(attribute))
+                        .and_then(|mapping| {
+                            part.find_column(attributes::column(attribute, mapping))
+                        })
+                        .or_else(|| part.find_column(attribute))
+                })
+                .collect();
+            rows.extend(part.rows.iter().map(|row| {
+                indices
+                    .iter()
+                    .map(|i| i.and_then(|i| row.get(i)).cloned().unwrap_or_default())
+                    .collect()
+            }));
+        }
+        Ok(Sample { columns, rows })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The column called `name`, ignoring case.
+    pub fn find_column(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(name))
+    }
+
+    /// The column holding canonical attribute `field`.
+    pub fn column(&self, spec: &Value, field: &str) -> Option<usize> {
+        let find = |name: &str| self.find_column(name);
+        find(field).or_else(|| {
+            let spec = &mappings::resolve(spec);
+            let sources: Vec<&Value> = match spec.get("sources") {
+                Some(Value::Array(sources)) => sources.iter().collect(),
+                Some(Value::Object(sources)) => sources.values().collect(),
+                _ => Vec::new(),
+            };
+            sources.into_iter().find_map(|source| {
+                source
+                    .get("attributes")
+                    .and_then(|a| a.get(field))
+                    .and_then(|mapping| find(attributes::column(field, mapping)))
+            })
+        })
+    }
+
+    /// `column`, with the attribute mappings of compiled IR.
+    pub fn ir_column(&self, ir: &Ir, field: &str) -> Option<usize> {
+        self.find_column(field).or_else(|| {
+            ir.sources
+                .iter()
+                .find_map(|source| source.attributes.get(field))
+                .and_then(|column| self.find_column(column))
+        })
+    }
+
+    /// Each row's blocking key value for `column` under `transform`;
+    /// `None` where the value is missing.
+    pub fn keys(&self, column: usize, transform: &str) -> Vec<Option<String>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let value = row.get(column).map(|v| v.trim()).unwrap_or_default();
+                let key = apply_transform(value, transform);
+                (!key.is_empty()).then_some(key)
+            })
+            .collect()
+    }
+}
+
+/// Blocking transforms by name; `TRANSFORM_PREFIXES` take a parameter
+/// after the prefix.
+pub const TRANSFORMS: &[&str] = &[
+    "identity",
+    "lowercase",
+    "lower",
+    "uppercase",
+    "upper",
+    "trim",
+    "soundex",
+    "address",
+    "street",
+    "postcode",
+    "geohash",
+];
+
+/// Blocking transforms with a parameter: `first_<n>` and `prefix:<n>` key
+/// on the first n characters, `geohash:<p>` on a cell of precision 1 to 12.
+pub const TRANSFORM_PREFIXES: &[&str] = &["first_", "prefix:", "geohash:"];
+
+/// Whether `transform` names a blocking transform `apply_transform` knows.
+pub fn is_transform(transform: &str) -> bool {
+    TRANSFORMS.contains(&transform)
+        || address::geohash_precision(transform).is_some()
+        || transform
+            .strip_prefix("first_")
+            .or_else(|| transform.strip_prefix("prefix:"))
+            .is_some_and(|n| n.parse::<usize>().is_ok())
+}
+
+/// A blocking transform, as the compiled SQL applies it; unknown
+/// transforms leave the value as is. Address transforms key on the
+/// standardized address or one of its components (see `address`), and
+/// `geohash` on the cell of a `lat,lon` value; both are empty where the
+/// component or coordinates are missing.
+pub fn apply_transform(value: &str, transform: &str) -> String {
+    match transform {
+        "lower" | "lowercase" => value.to_lowercase(),
+        "upper" | "uppercase" => value.to_uppercase(),
+        "trim" => value.trim().to_string(),
+        "soundex" => soundex(value),
+        "address" => address::standardize(value),
+        "street" => address::parse(value).street.unwrap_or_default(),
+        "postcode" => address::parse(value).postcode.unwrap_or_default(),
+        other if other.starts_with("geohash") => match (
+            address::geohash_precision(other),
+            address::parse_coordinates(value),
+        ) {
+            (Some(precision), Some((lat, lon))) => address::geohash(lat, lon, precision),
+            (Some(_), None) => String::new(),
+            (None, _) => value.to_string(),
+        },
+        other => match other
+            .strip_prefix("first_")
+            .or_else(|| other.strip_prefix("prefix:"))
+            .and_then(|n| n.parse::<usize>().ok())
+        {
+            Some(n) => value.to_lowercase().chars().take(n).collect(),
+            None => value.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::diagnose_yaml;
+    use crate::test_support::{MINIMAL, PRIVACY};
+
+    #[test]
+    fn test_unknown_transforms_are_rejected() {
+        use crate::sample::{apply_transform, is_transform};
+
+        let keyed = |transform: &str| {
+            format!(
+                "{}blocking:\n  keys:\n    - field: email\n      transform: {}\n",
+                MINIMAL, transform
+            ) + PRIVACY
+        };
+        for transform in [
+            "soundex",
+            "postcode",
+            "geohash:7",
+            "first_3",
+            "prefix:2",
+            "identity",
+        ] {
+            assert!(diagnose_yaml(&keyed(transform)).is_empty(), "{}", transform);
+        }
+        for transform in ["bogus", "geohash:13", "first_n", "prefix:x"] {
+            let diagnostics = diagnose_yaml(&keyed(transform));
+            assert_eq!(diagnostics.len(), 1, "{}: {:?}", transform, diagnostics);
+            assert_eq!(diagnostics[0].code, "KNV0135");
+            assert_eq!(
+                diagnostics[0].path.as_deref(),
+                Some("blocking.keys[0].transform")
+            );
+        }
+        assert!(diagnose_yaml(&keyed("bogus"))[0]
+            .suggestion
+            .as_deref()
+            .unwrap()
+            .ends_with("or first_<n>, prefix:<n>, geohash:<n>."));
+
+        // Every transform the validator accepts is one the interpreter applies.
+        assert!(is_transform("lower") && !is_transform("Soundex"));
+        assert_eq!(apply_transform("Smith", "soundex"), "S530");
+        assert_eq!(apply_transform("Smith", "prefix:2"), "sm");
+    }
+
+    #[test]
+    fn test_sample_reads_arrow_record_batches() {
+        use crate::{RecordBatch, Sample};
+        use arrow_array::{ArrayRef, Date32Array, Int64Array, StringArray};
+
+        let batch = |ids: Vec<i64>, emails: Vec<Option<&str>>, since: Vec<i32>| {
+            RecordBatch::try_from_iter([
+                ("id", Arc::new(Int64Array::from(ids)) as ArrayRef),
+                ("email", Arc::new(StringArray::from(emails)) as ArrayRef),
+                ("valid_from", Arc::new(Date32Array::from(since)) as ArrayRef),
+            ])
+            .unwrap()
+        };
+        let batches = [
+            batch(vec![1, 2], vec![Some("a@x.com"), None], vec![0, 19_000]),
+            batch(vec![3], vec![Some("b@x.com")], vec![19_001]),
+        ];
+        let sample = Sample::from_record_batches(&batches).unwrap();
+        assert_eq!(sample.columns, ["id", "email", "valid_from"]);
+        assert_eq!(
+            sample.rows,
+            [
+                ["1", "a@x.com", "1970-01-01"],
+                ["2", "", "2022-01-08"],
+                ["3", "b@x.com", "2022-01-09"],
+            ]
+        );
+
+        // Arrow IPC files load the same batches.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("people.arrow");
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer =
+            arrow_ipc::writer::FileWriter::try_new(file, &batches[0].schema()).unwrap();
+        for batch in &batches {
+            writer.write(batch).unwrap();
+        }
+        writer.finish().unwrap();
+        let loaded = Sample::load(&path).unwrap();
+        assert_eq!(loaded.rows, sample.rows);
+
+        let other =
+            RecordBatch::try_from_iter([("id", Arc::new(Int64Array::from(vec![4])) as ArrayRef)])
+                .unwrap();
+        assert!(Sample::from_record_batches(&[batches[0].clone(), other]).is_err());
+    }
+
+    #[test]
+    fn test_sample_reads_arrow_c_streams_by_source() {
+        use crate::{RecordBatch, Sample};
+        use arrow_array::ffi_stream::FFI_ArrowArrayStream;
+        use arrow_array::{ArrayRef, RecordBatchIterator, StringArray};
+
+        // What a DataFrame's __arrow_c_stream__ hands over.
+        let frame = |columns: Vec<(&str, Vec<&str>)>| {
+            let batch = RecordBatch::try_from_iter(
+                columns
+                    .into_iter()
+                    .map(|(name, values)| (name, Arc::new(StringArray::from(values)) as ArrayRef)),
+            )
+            .unwrap();
+            let schema = batch.schema();
+            let mut stream =
+                FFI_ArrowArrayStream::new(Box::new(RecordBatchIterator::new([Ok(batch)], schema)));
+            unsafe { Sample::from_arrow_c_stream(&mut stream) }.unwrap()
+        };
+        let crm = frame(vec![
+            ("email", vec!["a@x.com", "b@x.com"]),
+            ("first_name", vec!["Ann", "Bo"]),
+        ]);
+        let billing = frame(vec![
+            ("email_address", vec!["a@x.com"]),
+            ("given", vec!["Ann"]),
+        ]);
+        assert_eq!(crm.rows, [["a@x.com", "Ann"], ["b@x.com", "Bo"]]);
+
+        let spec = crate::parse_yaml(
+            r#"
+sources:
+  - name: crm
+    attributes: { email: email, first_name: first_name }
+  - name: billing
+    attributes: { email: email_address, first_name: given, phone: phone }
+"#,
+        )
+        .unwrap();
+        let stacked = Sample::from_sources(
+            &spec,
+            &[("crm".into(), crm.clone()), ("billing".into(), billing)],
+        )
+        .unwrap();
+        assert_eq!(stacked.columns, ["email", "first_name", "phone"]);
+        assert_eq!(
+            stacked.rows,
+            [
+                ["a@x.com", "Ann", ""],
+                ["b@x.com", "Bo", ""],
+                ["a@x.com", "Ann", ""]
+            ]
+        );
+        let err = Sample::from_sources(&spec, &[("erp".into(), crm)]).unwrap_err();
+        assert!(err.to_string().contains("unknown source 'erp'"));
+    }
+}
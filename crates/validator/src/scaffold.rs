@@ -0,0 +1,186 @@
+//! Starter specs for `kanoniv init`.
+//!
+//! A starter maps every matching field in every source, blocks on the
+//! identifier-like fields, compares identifiers exactly and everything else
+//! with Jaro-Winkler, keeps each field from the first source listed that
+//! has it, and hashes the fields holding PII. The text is commented for editing, in canonical form
+//! (`kanoniv fmt`), and validated before it is returned.
+
+use anyhow::{bail, Result};
+use std::fmt::Write;
+
+use crate::format::format_spec;
+use crate::privacy;
+use crate::validate_yaml;
+
+/// Fields compared exactly and blocked on: values that identify a record
+/// on their own.
+const IDENTIFIER_HINTS: &[&str] = &["email", "phone", "ssn", "tax", "id", "number", "code"];
+
+/// What `kanoniv init` asks for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Starter {
+    pub entity: String,
+    /// `(name, system)` per source.
+    pub sources: Vec<(String, String)>,
+    /// Canonical attributes records are matched on.
+    pub fields: Vec<String>,
+}
+
+impl Starter {
+    /// Parse a source as `name` or `name:system`.
+    pub fn parse_source(source: &str) -> (String, String) {
+        match source.split_once(':') {
+            Some((name, system)) => (name.trim().to_string(), system.trim().to_string()),
+            None => (source.trim().to_string(), source.trim().to_string()),
+        }
+    }
+
+    /// The starter spec's YAML, validated.
+    pub fn render(&self) -> Result<String> {
+        check_name("entity name", &self.entity)?;
+        if self.sources.is_empty() {
+            bail!("A spec needs at least one source");
+        }
+        for (name, system) in &self.sources {
+            check_name("source name", name)?;
+            check_name("source system", system)?;
+        }
+        if self.fields.is_empty() {
+            bail!("A spec needs at least one matching field");
+        }
+        for field in &self.fields {
+            check_name("field name", field)?;
+        }
+
+        let yaml = format_spec(&self.text()?)?;
+        let errors = validate_yaml(&yaml)?;
+        if !errors.is_empty() {
+            bail!("The starter spec is invalid:\n  {}", errors.join("\n  "));
+        }
+        Ok(yaml)
+    }
+
+    fn text(&self) -> Result<String> {
+        let identifiers: Vec<&String> = self.fields.iter().filter(|f| is_identifier(f)).collect();
+        let mut y = String::new();
+        writeln!(y, "api_version: kanoniv/v2")?;
+        writeln!(y, "identity_version: {}_v1.0", self.entity)?;
+        writeln!(y, "entity:\n  name: {}", self.entity)?;
+
+        writeln!(
+            y,
+            "# Point each source at its table and primary key column, and map"
+        )?;
+        writeln!(
+            y,
+            "# each attribute (left) to the column holding it (right)."
+        )?;
+        writeln!(y, "sources:")?;
+        for (name, system) in &self.sources {
+            writeln!(y, "  - name: {}", name)?;
+            writeln!(y, "    system: {}", system)?;
+            writeln!(y, "    table: {}", name)?;
+            writeln!(y, "    id: id")?;
+            writeln!(y, "    attributes:")?;
+            for field in &self.fields {
+                writeln!(y, "      {}: {}", field, field)?;
+            }
+        }
+
+        writeln!(y, "# Only records sharing a blocking key are compared.")?;
+        writeln!(y, "blocking:\n  keys:")?;
+        let blocked = if identifiers.is_empty() {
+            vec![&self.fields[0]]
+        } else {
+            identifiers
+        };
+        for field in blocked {
+            writeln!(y, "    - field: {}", field)?;
+            if field.contains("email") {
+                writeln!(y, "      transform: lowercase")?;
+            }
+        }
+
+        writeln!(y, "rules:")?;
+        for field in &self.fields {
+            if is_identifier(field) {
+                writeln!(y, "  - name: {}_exact", field)?;
+                writeln!(y, "    type: exact")?;
+                writeln!(y, "    field: {}", field)?;
+                writeln!(y, "    weight: 1.0")?;
+            } else {
+                writeln!(y, "  - name: {}_fuzzy", field)?;
+                writeln!(y, "    type: fuzzy")?;
+                writeln!(y, "    field: {}", field)?;
+                writeln!(y, "    algorithm: jaro_winkler")?;
+                writeln!(y, "    threshold: 0.9")?;
+                writeln!(y, "    weight: 0.5")?;
+            }
+        }
+
+        writeln!(
+            y,
+            "# Pairs scoring at least `match` merge; `review` and up are"
+        )?;
+        writeln!(y, "# queued for review. Tune with `kanoniv calibrate`.")?;
+        writeln!(
+            y,
+            "decision:\n  thresholds:\n    match: 0.9\n    review: 0.7"
+        )?;
+
+        writeln!(
+            y,
+            "# Golden records keep each field from the first source listed."
+        )?;
+        writeln!(y, "survivorship:\n  rules:")?;
+        let priority: Vec<&str> = self.sources.iter().map(|(name, _)| name.as_str()).collect();
+        for field in &self.fields {
+            writeln!(y, "    - field: {}", field)?;
+            writeln!(y, "      strategy: source_priority")?;
+            writeln!(y, "      source_priority: [{}]", priority.join(", "))?;
+        }
+
+        let pii: Vec<&String> = self
+            .fields
+            .iter()
+            .filter(|f| privacy::detect(f, None).is_some())
+            .collect();
+        if !pii.is_empty() {
+            writeln!(
+                y,
+                "# Personal data: how long it is kept, and hashed wherever it"
+            )?;
+            writeln!(y, "# leaves the engine (blocking keys included).")?;
+            writeln!(y, "privacy:\n  retention_days: 730\n  fields:")?;
+            for field in pii {
+                writeln!(y, "    {}:\n      masking: hash", field)?;
+            }
+        }
+        Ok(y)
+    }
+}
+
+fn is_identifier(field: &str) -> bool {
+    let field = field.to_ascii_lowercase();
+    field
+        .split('_')
+        .any(|word| IDENTIFIER_HINTS.contains(&word))
+}
+
+/// Names are written into the YAML unquoted, so keep them to identifiers.
+pub(crate) fn check_name(what: &str, name: &str) -> Result<()> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!(
+            "Invalid {} '{}' (use letters, digits and '_', not starting with a digit)",
+            what,
+            name
+        );
+    }
+    Ok(())
+}
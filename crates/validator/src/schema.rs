@@ -0,0 +1,573 @@
+//! JSON Schema for the spec YAML.
+//!
+//! Built from the same required-field lists and limits `validate_schema`
+//! enforces, so editors and other toolchains accept exactly what the
+//! validator accepts at the schema stage; properties the validator does not
+//! constrain carry descriptions only. Semantic checks (field references,
+//! duplicate names, threshold ordering) are not expressible here and still
+//! need `kanoniv validate`.
+
+use serde_json::{json, Value};
+
+use crate::attributes;
+use crate::blocking::{MAX_WINDOW, MIN_WINDOW, STRATEGIES};
+use crate::clustering;
+use crate::deletion;
+use crate::encoding;
+use crate::execution;
+use crate::lsh::METHODS;
+use crate::normalize::{LOCALES, NORMALIZERS};
+use crate::policy;
+use crate::privacy;
+use crate::relationships;
+use crate::signing;
+use crate::similarity::BUILTIN_ALGORITHMS;
+use crate::survivorship;
+use crate::temporal;
+use crate::validator::{
+    API_VERSION_PREFIX, MAX_BLOCKING_KEYS, MAX_RULES, MAX_SOURCES, REQUIRED_CANOPY,
+    REQUIRED_ENTITY, REQUIRED_HIERARCHICAL, REQUIRED_RELATIONSHIP, REQUIRED_RELATIONSHIP_RULE,
+    REQUIRED_RULE, REQUIRED_SORTED_NEIGHBORHOOD, REQUIRED_SOURCE, REQUIRED_TEMPORAL,
+    REQUIRED_TOP_LEVEL, UNIT_INTERVAL_CANOPY_FIELDS, UNIT_INTERVAL_RULE_FIELDS,
+};
+
+pub const SCHEMA_ID: &str = "https://oss.kanoniv.com/schema/spec.json";
+
+/// The authoritative JSON Schema (draft-07) for spec files.
+pub fn spec_json_schema() -> Value {
+    let mut rule_properties = json!({
+        "name": { "description": "Unique rule name." },
+        "type": {
+            "description": "Match type.",
+            "examples": ["exact", "fuzzy", "semantic"],
+        },
+        "field": { "description": "Canonical attribute compared by this rule." },
+        "algorithm": {
+            "description": "Similarity algorithm for fuzzy rules.",
+            "examples": BUILTIN_ALGORITHMS,
+        },
+        "model": { "description": "Embedding model for semantic rules." },
+        "endpoint": { "description": "URL serving the embedding model of a semantic rule." },
+        "condition": { "description": "Expression over left.<attribute> and right.<attribute>; the rule compares only the pairs it is true for." },
+    });
+    for field in UNIT_INTERVAL_RULE_FIELDS {
+        rule_properties[field] = json!({ "minimum": 0, "maximum": 1 });
+    }
+    let mut canopy_properties = json!({
+        "field": { "description": "Canonical attribute compared to canopy centers." },
+        "algorithm": {
+            "description": "Similarity algorithm; jaccard if omitted.",
+            "examples": BUILTIN_ALGORITHMS,
+        },
+    });
+    for field in UNIT_INTERVAL_CANOPY_FIELDS {
+        canopy_properties[field] = json!({ "minimum": 0, "maximum": 1 });
+    }
+    let mut relationship_rule_properties = json!({
+        "name": { "description": "Rule name." },
+        "type": { "description": "Match type.", "examples": relationships::RULE_TYPES },
+        "field": { "description": "Attribute of this entity compared by this rule." },
+        "target_field": { "description": "Attribute of the target entity it is compared with; field if omitted." },
+        "algorithm": {
+            "description": "Similarity algorithm for fuzzy rules.",
+            "examples": BUILTIN_ALGORITHMS,
+        },
+    });
+    for field in UNIT_INTERVAL_RULE_FIELDS {
+        relationship_rule_properties[field] = json!({ "minimum": 0, "maximum": 1 });
+    }
+    relationship_rule_properties["weight"]["description"] =
+        json!("Weight of the rule; 1 if omitted.");
+    canopy_properties["loose"]["description"] =
+        json!("Similarity to a center at which a record joins its canopy.");
+    canopy_properties["tight"]["description"] = json!("Similarity to a center at which a record can no longer start or join another canopy; at least loose.");
+
+    let retention_days = json!({
+        "description": "Days records holding PII are kept.",
+        "type": "integer",
+        "minimum": 1,
+    });
+
+    let field_encoding = json!({
+        "oneOf": [
+            { "enum": encoding::METHODS },
+            {
+                "type": "object",
+                "required": ["method"],
+                "additionalProperties": false,
+                "properties": {
+                    "method": { "enum": encoding::METHODS },
+                    "size": {
+                        "description": "Filter size in bits, a multiple of 8.",
+                        "type": "integer",
+                        "minimum": encoding::MIN_BLOOM_SIZE,
+                        "maximum": encoding::MAX_BLOOM_SIZE,
+                        "multipleOf": 8,
+                    },
+                    "hashes": {
+                        "description": "Bits set per n-gram.",
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": encoding::MAX_BLOOM_HASHES,
+                    },
+                    "ngram": {
+                        "description": "Characters per n-gram.",
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": encoding::MAX_NGRAM,
+                    },
+                },
+            },
+        ],
+    });
+
+    let signature = json!({
+        "type": "object",
+        "required": ["key_id", "algorithm", "hash", "signature"],
+        "properties": {
+            "key_id": { "type": "string", "description": "First 16 hex digits of the SHA-256 of the signer's public key." },
+            "algorithm": { "enum": [signing::ALGORITHM] },
+            "hash": { "type": "string", "description": "The canonical hash signed." },
+            "signature": { "type": "string", "description": "The signature, in hex." },
+            "signed_at": { "type": "string" },
+        },
+        "additionalProperties": false,
+    });
+    let assertion = json!({
+        "type": "object",
+        "required": ["records"],
+        "additionalProperties": false,
+        "properties": {
+            "records": { "description": "Record keys.", "type": "array", "minItems": 2 },
+            "reason": { "type": "string" },
+        },
+    });
+
+    let source_attributes = json!({
+        "description": "Canonical attribute name -> source column, or a column and the attribute's type; or a list of columns, resolved through `mappings`.",
+        "type": ["object", "array"],
+        "items": { "type": "string" },
+        "additionalProperties": {
+            "anyOf": [
+                { "type": "string" },
+                {
+                    "type": "object",
+                    "required": ["column"],
+                    "properties": {
+                        "column": { "type": "string", "description": "Source column." },
+                        "type": {
+                            "description": "What the values are; decides which rules and normalizers apply.",
+                            "enum": attributes::TYPES,
+                        },
+                        "pii": {
+                            "description": "Whether the attribute holds personal data, over what its name and type suggest.",
+                            "type": "boolean",
+                        },
+                    },
+                },
+            ],
+        },
+    });
+
+    let named_normalizers: Vec<&str> = NORMALIZERS
+        .iter()
+        .copied()
+        .filter(|n| *n != "regex_replace")
+        .collect();
+    let normalization_step = json!({
+        "anyOf": [
+            { "enum": named_normalizers },
+            {
+                "type": "object",
+                "required": ["regex_replace"],
+                "properties": {
+                    "regex_replace": {
+                        "type": "object",
+                        "required": ["pattern"],
+                        "properties": {
+                            "pattern": { "type": "string", "description": "Regular expression whose matches are rewritten." },
+                            "replacement": { "type": "string", "description": "Replacement; $1 and ${name} refer to groups. Matches are deleted if omitted." },
+                        },
+                    },
+                },
+            },
+        ],
+    });
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "$id": SCHEMA_ID,
+        "title": "Kanoniv identity specification",
+        "type": "object",
+        // A spec extending a base inherits the required fields.
+        "if": { "not": { "required": ["extends"] } },
+        "then": { "required": REQUIRED_TOP_LEVEL },
+        "properties": {
+            "extends": {
+                "description": "Base spec this spec overrides and adds to: a file path relative to this spec, or a published version, registry://<path>/<entity>@<version>.",
+                "type": "string",
+            },
+            "api_version": {
+                "description": "Spec format version, e.g. kanoniv/v2.",
+                "pattern": format!("^{}", regex_escape(API_VERSION_PREFIX)),
+            },
+            "identity_version": {
+                "description": "Version label of this identity definition.",
+            },
+            "entity": {
+                "type": "object",
+                "required": REQUIRED_ENTITY,
+                "properties": {
+                    "name": { "description": "Entity name." },
+                },
+            },
+            "mappings": {
+                "description": "Canonical attribute -> the source columns holding it, matched case-insensitively.",
+                "type": "object",
+                "additionalProperties": { "type": "array", "items": { "type": "string" } },
+            },
+            "sources": {
+                "description": "Source systems and their attribute mappings.",
+                "maxItems": MAX_SOURCES,
+                "items": {
+                    "type": "object",
+                    "required": REQUIRED_SOURCE,
+                    "properties": {
+                        "name": { "description": "Unique source name." },
+                        "system": { "description": "Source system, e.g. salesforce." },
+                        "table": { "description": "Table or file holding the records." },
+                        "id": { "description": "Primary key column." },
+                        "owner": {
+                            "description": "Steward(s) of this source; reviews changes to rules on its attributes.",
+                        },
+                        "rows": {
+                            "description": "Approximate row count, for the plan's cost estimates.",
+                            "type": "number",
+                            "minimum": 0,
+                        },
+                        "attributes": source_attributes,
+                    },
+                },
+            },
+            "rules": {
+                "description": "Match rules, evaluated in order.",
+                "maxItems": MAX_RULES,
+                "items": {
+                    "type": "object",
+                    "required": REQUIRED_RULE,
+                    "properties": rule_properties,
+                },
+            },
+            "blocking": {
+                // Strategy parameters are checked only for their strategy.
+                "allOf": [
+                    {
+                        "if": { "required": ["strategy"], "properties": { "strategy": { "const": "sorted_neighborhood" } } },
+                        "then": {
+                            "required": ["sorted_neighborhood"],
+                            "properties": {
+                                "sorted_neighborhood": {
+                                    "required": REQUIRED_SORTED_NEIGHBORHOOD,
+                                    "properties": {
+                                        "window": { "type": "integer", "minimum": MIN_WINDOW, "maximum": MAX_WINDOW },
+                                    },
+                                },
+                            },
+                        },
+                    },
+                    {
+                        "if": { "required": ["strategy"], "properties": { "strategy": { "const": "canopy" } } },
+                        "then": {
+                            "required": ["canopy"],
+                            "properties": {
+                                "canopy": { "required": REQUIRED_CANOPY, "properties": canopy_properties },
+                            },
+                        },
+                    },
+                ],
+                "properties": {
+                    "strategy": { "description": "Blocking strategy.", "examples": STRATEGIES },
+                    "sorted_neighborhood": {
+                        "description": "Sort key and window of the sorted_neighborhood strategy.",
+                        "properties": {
+                            "sort_key": { "description": "Canonical attribute records are sorted on." },
+                            "transform": { "description": "Blocking transform applied to the sort key before sorting." },
+                            "window": { "description": "Records per sliding window." },
+                        },
+                    },
+                    "canopy": {
+                        "description": "Field and loose/tight thresholds of the canopy strategy.",
+                    },
+                    "lsh": {
+                        "description": "Locality-sensitive hashing parameters for the lsh strategy.",
+                        "properties": {
+                            "method": { "description": "Signature: minhash (Jaccard) or simhash (cosine).", "examples": METHODS },
+                            "bands": { "description": "Signature bands; pairs agreeing on any band are candidates." },
+                            "rows": { "description": "Hashes per band." },
+                            "shingle_size": { "description": "Characters per shingle." },
+                        },
+                    },
+                    "keys": {
+                        "description": "Blocking keys: a field, or {field, transform} with transform lower, upper, trim, soundex, first_<n>, address, street, postcode or geohash[:<precision>].",
+                        "maxItems": MAX_BLOCKING_KEYS,
+                    },
+                },
+            },
+            "survivorship": {
+                "description": "How golden record fields are chosen.",
+                "properties": {
+                    "rules": {
+                        "items": {
+                            "properties": {
+                                "field": { "description": "Attribute the rule chooses." },
+                                "strategy": { "description": "Survivorship strategy; most_complete if the attribute has no rule.", "examples": survivorship::STRATEGIES },
+                                "source_priority": { "description": "source_priority: sources, most trusted first." },
+                                "timestamp": { "description": "most_recent: attribute to order by; temporal.effective_from, else the record key, if omitted." },
+                                "fields": { "description": "non_null_priority: attributes to take the value from, first non-null wins." },
+                                "expression": { "description": "custom: expression scoring each member, e.g. \"source == 'crm' ? 2 : size(value)\"; the highest survives." },
+                                "condition": { "description": "Expression over the same names; members it is true for survive before the others." },
+                            },
+                        },
+                    },
+                },
+            },
+            "clustering": {
+                "description": "How matched records are grouped into entities.",
+                // The cut threshold is required only by hierarchical clustering.
+                "if": { "required": ["strategy"], "properties": { "strategy": { "const": "hierarchical" } } },
+                "then": { "required": REQUIRED_HIERARCHICAL },
+                "properties": {
+                    "strategy": { "description": "Clustering strategy; transitive_closure if omitted.", "examples": clustering::STRATEGIES },
+                    "threshold": {
+                        "description": "Average linkage at which hierarchical clusters stop merging.",
+                        "minimum": 0,
+                        "maximum": 1,
+                    },
+                },
+            },
+            "execution": {
+                "description": "How runs resolve records: full rebuilds, incremental deltas or streaming.",
+                "properties": {
+                    "mode": { "description": "Execution mode; full if omitted.", "examples": execution::MODES },
+                    "delta_column": { "description": "incremental: attribute marking records changed since the last run." },
+                    "stable_ids": { "description": "Whether entity ids are kept across runs rather than derived from each cluster's lowest record key." },
+                },
+            },
+            "temporal": {
+                "description": "When each record's values hold, and how slowly-changing attributes resolve.",
+                "required": REQUIRED_TEMPORAL,
+                "properties": {
+                    "effective_from": { "description": "Attribute holding the ISO 8601 date each record became valid." },
+                    "effective_to": { "description": "Attribute holding the date each record stopped being valid; empty while current." },
+                    "match_window_days": {
+                        "description": "Compare records only if their validity intervals come within this many days.",
+                        "minimum": 0,
+                    },
+                    "attributes": {
+                        "description": "History mode by attribute; current (the default) or history.",
+                        "additionalProperties": { "examples": temporal::HISTORY_MODES },
+                    },
+                },
+            },
+            "stewardship": {
+                "description": "Steward assertions that outrank scores, the clustering strategy and survivorship rules.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "always_merge": { "description": "Records that are always one entity.", "type": "array", "items": assertion },
+                    "never_merge": { "description": "Records that are never the same entity.", "type": "array", "items": assertion },
+                    "locked": {
+                        "description": "Golden fields fixed for the entity holding a record.",
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "required": ["record", "field", "value"],
+                            "additionalProperties": false,
+                            "properties": {
+                                "record": { "description": "Record key of a member of the entity." },
+                                "field": { "description": "Canonical attribute locked." },
+                                "value": { "description": "The value the golden field takes; null keeps it empty." },
+                                "reason": { "type": "string" },
+                            },
+                        },
+                    },
+                },
+            },
+            "privacy": {
+                "description": "Retention and masking of the attributes holding PII.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "retention_days": retention_days,
+                    "collected_at": {
+                        "description": "Attribute holding when each record's values were collected, which retention counts from.",
+                        "type": "string",
+                    },
+                    "on_expiry": {
+                        "description": "nullify nulls a value past its retention period; tombstone drops its record.",
+                        "enum": privacy::EXPIRIES,
+                    },
+                    "fields": {
+                        "description": "Canonical attribute -> its masking and retention.",
+                        "type": "object",
+                        "additionalProperties": {
+                            "type": "object",
+                            "additionalProperties": false,
+                            "properties": {
+                                "masking": {
+                                    "description": "How the attribute appears outside the engine.",
+                                    "enum": privacy::MASKINGS,
+                                },
+                                "retention_days": retention_days,
+                            },
+                        },
+                    },
+                },
+            },
+            "encoding": {
+                "description": "Attributes matched as salted hashes or Bloom filters, so partners exchange encodings instead of values.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "salt_env": { "description": "Environment variable holding the salt shared with the partner.", "type": "string" },
+                    "salt_file": { "description": "File holding the salt shared with the partner.", "type": "string" },
+                    "fields": {
+                        "description": "Canonical attribute -> hash, bloom, or a method with its Bloom filter parameters.",
+                        "type": "object",
+                        "additionalProperties": field_encoding,
+                    },
+                },
+            },
+            "deletion": {
+                "description": "How deleted source records reach clusters and golden records.",
+                "type": "object",
+                "required": ["tombstone"],
+                "additionalProperties": false,
+                "properties": {
+                    "tombstone": {
+                        "description": "Attribute set on deleted records: a deletion time, or true.",
+                        "type": "string",
+                    },
+                    "scope": {
+                        "description": "record drops the deleted record; entity erases every entity holding one.",
+                        "enum": deletion::SCOPES,
+                    },
+                    "purge_after_days": {
+                        "description": "Days tombstones are kept so downstream tables see the deletion.",
+                        "type": "integer",
+                        "minimum": 1,
+                    },
+                },
+            },
+            "relationships": {
+                "description": "Links from this entity's golden records to another entity's (or its own), each written to a <name>_edges table.",
+                "items": {
+                    "type": "object",
+                    "required": REQUIRED_RELATIONSHIP,
+                    "properties": {
+                        "name": { "description": "Unique relationship name." },
+                        "to": { "description": "Entity linked to." },
+                        "cardinality": { "description": "Edges kept per record and target; many_to_one if omitted.", "examples": relationships::CARDINALITIES },
+                        "threshold": {
+                            "description": "Score at which a pair becomes an edge.",
+                            "minimum": 0,
+                            "maximum": 1,
+                        },
+                        "rules": {
+                            "items": {
+                                "type": "object",
+                                "required": REQUIRED_RELATIONSHIP_RULE,
+                                "properties": relationship_rule_properties,
+                            },
+                        },
+                    },
+                },
+            },
+            "decision": {
+                "properties": {
+                    "thresholds": {
+                        "description": "Score bands; must satisfy match >= review >= reject.",
+                    },
+                },
+            },
+            "normalization": {
+                "type": "object",
+                "description": "Normalizers applied to attribute values before matching.",
+                "properties": {
+                    "locale": { "description": "Locale of the nickname and honorific tables.", "examples": LOCALES },
+                    "default_region": { "description": "ISO 3166-1 alpha-2 region of phone numbers written without a country code.", "examples": ["US", "GB", "DE"] },
+                    "fields": {
+                        "type": "object",
+                        "description": "Canonical attribute -> normalizers, applied in order.",
+                        "additionalProperties": {
+                            "type": "array",
+                            "items": normalization_step,
+                        },
+                    },
+                },
+            },
+            "hashing": {
+                "description": "How record identifiers are tokenized.",
+                "properties": {
+                    "algorithm": { "description": "Hash algorithm.", "examples": ["sha256", "sha512", "blake3"] },
+                    "key_env": { "description": "Environment variable holding the key for keyed (HMAC) tokens." },
+                    "key_file": { "description": "File holding the key for keyed (HMAC) tokens." },
+                },
+            },
+            "owners": {
+                "description": "Reviewer(s) per top-level section (rules, blocking, decision, ...), with a `default` for the rest.",
+            },
+            "waivers": {
+                "description": "Accepted findings: each names a risk flag or diagnostic code, a reason and an optional expiry (YYYY-MM-DD).",
+            },
+            "policy": {
+                "description": "Level per diagnostic code or risk flag; errors are never demoted.",
+                "type": "object",
+                "additionalProperties": { "enum": policy::LEVELS },
+            },
+            "signatures": {
+                "description": "Signatures of the spec's canonical hash, written by `kanoniv sign --embed`; left out of the hash.",
+                "type": "array",
+                "items": signature,
+            },
+        },
+    })
+}
+
+fn regex_escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| {
+            let escape = "\\^$.|?*+()[]{}".contains(c);
+            escape.then_some('\\').into_iter().chain(std::iter::once(c))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spec_json_schema;
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_json_schema_agrees_with_schema_validation() {
+        let schema = jsonschema::JSONSchema::compile(&spec_json_schema()).unwrap();
+        let cases = [
+            MINIMAL.to_string(),
+            include_str!("../tests/fixtures/invalid/missing_entity.yaml").to_string(),
+            MINIMAL.replace("api_version: kanoniv/v2", "api_version: v2"),
+            MINIMAL.replace("weight: 1.0", "weight: 1.5"),
+            MINIMAL.replace("    id: contact_id\n", ""),
+            MINIMAL.replace("  name: customer\n", "  label: customer\n"),
+        ];
+        for yaml in &cases {
+            let spec = crate::parse_yaml(yaml).unwrap();
+            let schema_valid = crate::validate_schema(&spec).unwrap().is_empty();
+            assert_eq!(
+                schema.is_valid(&spec),
+                schema_valid,
+                "disagreement on:\n{}",
+                yaml
+            );
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! What-if analysis of the decision thresholds on sample pairs.
+//!
+//! `analyze_thresholds` scores sample pairs and counts how many each
+//! setting of `decision.thresholds` would merge (score at or above
+//! `match`), send to review (at or above `review`, below `match`) and
+//! reject (the rest), so a threshold change can be judged by the volumes
+//! it moves before it is made.
+//!
+//! The data is either scored pairs, with a `score` column (as the engine
+//! writes them for `kanoniv cluster`), or records: these are paired on the
+//! spec's blocking keys and scored as in `calibration`, with the combined
+//! score the weight-averaged rule score.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::calibration;
+use crate::commands::compile::compile_to_ir;
+use crate::commands::plan::extract_match_strategies;
+use crate::embedding::{Embedder, HttpEmbedder};
+use crate::encoding;
+use crate::ir::Ir;
+use crate::learning::{self, MAX_PAIRS};
+use crate::normalize::Normalization;
+use crate::parser;
+use crate::sample::Sample;
+
+/// Match thresholds swept, 0.05 to 1.00 in steps of 0.05, besides the
+/// spec's own and any proposed.
+const STEPS: std::ops::RangeInclusive<u32> = 1..=20;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThresholdAnalysis {
+    pub pairs: usize,
+    /// Records paired and scored; `None` when the data held scored pairs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub records: Option<usize>,
+    pub match_threshold: Option<f64>,
+    pub review_threshold: Option<f64>,
+    /// At the spec's thresholds, if it sets `match`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<Outcomes>,
+    /// At the thresholds passed to `propose`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proposed: Option<Outcomes>,
+    /// By match threshold, with the review threshold held.
+    pub curve: Vec<Outcomes>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Each pair's combined score.
+    #[serde(skip)]
+    pub scores: Vec<f64>,
+}
+
+/// Pairs per decision at one setting of the thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Outcomes {
+    pub match_threshold: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review_threshold: Option<f64>,
+    pub merge: usize,
+    pub review: usize,
+    pub reject: usize,
+}
+
+/// Sweep `yaml`'s decision thresholds over the pairs in `data`, embedding
+/// values for semantic rules through the rules' endpoints.
+pub fn analyze_thresholds(yaml: &str, data: &Sample) -> Result<ThresholdAnalysis> {
+    analyze_thresholds_with(yaml, data, &HttpEmbedder::new()?)
+}
+
+/// `analyze_thresholds`, embedding values for semantic rules with
+/// `embedder`.
+pub fn analyze_thresholds_with(
+    yaml: &str,
+    data: &Sample,
+    embedder: &dyn Embedder,
+) -> Result<ThresholdAnalysis> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let mut warnings = Vec::new();
+
+    let (scores, records) = match data.find_column("score") {
+        Some(column) => (read_scores(data, column)?, None),
+        None => {
+            let strategies = extract_match_strategies(&ir);
+            if strategies.is_empty() {
+                bail!("The spec has no match rules to score pairs with");
+            }
+            let encoder = encoding::encoder(&spec)?;
+            let (pairs, capped) =
+                learning::candidate_pairs(&ir, data, encoder.as_ref(), &mut warnings);
+            if capped {
+                warnings.push(format!(
+                    "Scored the first {} candidate pairs only",
+                    MAX_PAIRS
+                ));
+            }
+            let normalization = Normalization::from_spec(&spec)?;
+            let scored = learning::pair_scores(
+                &ir,
+                data,
+                &strategies,
+                &pairs,
+                &normalization,
+                encoder.as_ref(),
+                embedder,
+                None,
+            )?;
+            let similarities: Vec<Vec<Option<f64>>> = strategies
+                .iter()
+                .zip(scored)
+                .map(|(rule, scores)| {
+                    scores.unwrap_or_else(|| {
+                        warnings.push(format!(
+                            "The data has no column for '{}'; rule '{}' scores 0",
+                            rule.field, rule.rule_name
+                        ));
+                        vec![None; pairs.len()]
+                    })
+                })
+                .collect();
+            let scores = calibration::combined_scores(&strategies, &similarities, pairs.len());
+            (scores, Some(data.len()))
+        }
+    };
+    if scores.is_empty() {
+        bail!("No pairs to analyze");
+    }
+
+    let thresholds = ir.thresholds.as_ref();
+    let match_threshold = thresholds.and_then(|t| t.match_);
+    let review_threshold = thresholds.and_then(|t| t.review);
+    let mut analysis = ThresholdAnalysis {
+        pairs: scores.len(),
+        records,
+        match_threshold,
+        review_threshold,
+        current: None,
+        proposed: None,
+        curve: Vec::new(),
+        warnings,
+        scores,
+    };
+    analysis.current = match_threshold.map(|t| analysis.outcomes(t, review_threshold));
+    analysis.curve = analysis.sweep();
+    Ok(analysis)
+}
+
+impl ThresholdAnalysis {
+    /// Pairs merged, reviewed and rejected at these thresholds.
+    pub fn outcomes(&self, match_threshold: f64, review_threshold: Option<f64>) -> Outcomes {
+        let at_least = |t: f64| self.scores.iter().filter(|&&s| s >= t - 1e-9).count();
+        let merge = at_least(match_threshold);
+        let review = match review_threshold {
+            Some(review) if review < match_threshold => at_least(review) - merge,
+            _ => 0,
+        };
+        Outcomes {
+            match_threshold,
+            review_threshold,
+            merge,
+            review,
+            reject: self.pairs - merge - review,
+        }
+    }
+
+    /// Set `proposed` to the outcomes at `match_threshold` (the spec's if
+    /// `None`) and `review_threshold` (likewise), and add it to the curve.
+    pub fn propose(
+        &mut self,
+        match_threshold: Option<f64>,
+        review_threshold: Option<f64>,
+    ) -> Result<()> {
+        let Some(match_threshold) = match_threshold.or(self.match_threshold) else {
+            bail!("The spec sets no match threshold; propose one");
+        };
+        let review_threshold = review_threshold.or(self.review_threshold);
+        self.proposed = Some(self.outcomes(match_threshold, review_threshold));
+        self.curve = self.sweep();
+        Ok(())
+    }
+
+    /// Outcomes by match threshold, on the steps and at the current and
+    /// proposed thresholds, with the review threshold held.
+    fn sweep(&self) -> Vec<Outcomes> {
+        let mut thresholds: Vec<f64> = STEPS.map(|step| step as f64 / 20.0).collect();
+        thresholds.extend(self.current.iter().map(|o| o.match_threshold));
+        thresholds.extend(self.proposed.iter().map(|o| o.match_threshold));
+        thresholds.sort_by(f64::total_cmp);
+        thresholds.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+        let review = self
+            .proposed
+            .map_or(self.review_threshold, |p| p.review_threshold);
+        thresholds
+            .into_iter()
+            .map(|t| self.outcomes(t, review))
+            .collect()
+    }
+}
+
+fn read_scores(data: &Sample, column: usize) -> Result<Vec<f64>> {
+    data.rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let cell = row.get(column).map(|v| v.trim()).unwrap_or_default();
+            match cell.parse::<f64>() {
+                Ok(score) if score.is_finite() => Ok(score),
+                _ => bail!("Row {}: score '{}' is not a number", i + 2, cell),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+
+    #[test]
+    fn test_analyze_thresholds_counts_outcomes_per_threshold() {
+        use crate::{analyze_thresholds, Sample};
+
+        let spec = MINIMAL.replace("    match: 0.9\n", "    match: 0.9\n    review: 0.6\n");
+        let scored = Sample::from_csv(
+            "left_key,right_key,score\na,b,0.95\nb,c,0.88\nc,d,0.86\nd,e,0.7\ne,f,0.5\nf,g,0.91\n",
+        )
+        .unwrap();
+        let mut analysis = analyze_thresholds(&spec, &scored).unwrap();
+        assert_eq!((analysis.pairs, analysis.records), (6, None));
+        let current = analysis.current.unwrap();
+        assert_eq!((current.merge, current.review, current.reject), (2, 3, 1));
+        assert_eq!(analysis.curve.len(), 20);
+        assert!(analysis.curve.windows(2).all(|w| w[0].merge >= w[1].merge));
+
+        // Lowering match to 0.85 merges the two pairs it took from review.
+        analysis.propose(Some(0.85), None).unwrap();
+        let proposed = analysis.proposed.unwrap();
+        assert_eq!(
+            (proposed.merge, proposed.review, proposed.reject),
+            (4, 1, 1)
+        );
+        assert_eq!(proposed.review_threshold, Some(0.6));
+        analysis.propose(Some(0.87), None).unwrap();
+        assert_eq!(analysis.curve.len(), 21);
+
+        // Records are paired on blocking keys (none here) and scored by the rules.
+        let records = Sample::from_csv("email\na@x.com\na@x.com\nb@x.com\nc@x.com\n").unwrap();
+        let analysis = analyze_thresholds(MINIMAL, &records).unwrap();
+        assert_eq!((analysis.pairs, analysis.records), (6, Some(4)));
+        let current = analysis.current.unwrap();
+        assert_eq!((current.merge, current.review, current.reject), (1, 0, 5));
+
+        let bad = Sample::from_csv("left_key,right_key,score\na,b,high\n").unwrap();
+        let err = analyze_thresholds(MINIMAL, &bad).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Row 2: score 'high' is not a number"));
+    }
+}
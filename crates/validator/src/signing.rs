@@ -0,0 +1,402 @@
+//! Spec signatures.
+//!
+//! `kanoniv sign` signs a spec's `signed_hash` with an Ed25519 key, and
+//! `kanoniv verify` checks a spec against the public keys a deployment
+//! trusts. The hash is the canonical hash `kanoniv hash` prints, which
+//! leaves out `signatures`, of the spec as written and composed over its
+//! base: `${VAR}` and `{{ params.name }}` references are signed as
+//! references, so one signature holds in every environment.
+//!
+//! Signatures are kept in a detached `<spec>.sig` file or in the spec's own
+//! `signatures` section, the one part of the spec the hash leaves out:
+//!
+//! ```yaml
+//! signatures:
+//!   - key_id: "5c1e0f4f8a2b9d37"
+//!     algorithm: ed25519
+//!     hash: sha256:9f2c...
+//!     signature: 3a7b...
+//!     signed_at: 2026-03-01T12:00:00Z
+//! ```
+//!
+//! Keys are 32 bytes written as hex: a signing key's seed, or a public key.
+//! A key id is the first 16 hex digits of the SHA-256 of the public key.
+
+use anyhow::{anyhow, bail, Context, Result};
+use ed25519_dalek::{Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::canonical::canonical_hash;
+use crate::clock;
+use crate::format::scalar_str;
+use crate::hashing::KeySource;
+
+pub const ALGORITHM: &str = "ed25519";
+
+/// Extension appended to a spec's file name for its detached signatures.
+pub const DETACHED_EXTENSION: &str = "sig";
+
+/// Extension appended to a signing key's file name for its public key.
+pub const PUBLIC_KEY_EXTENSION: &str = "pub";
+
+/// An Ed25519 key that signs specs.
+#[derive(Clone)]
+pub struct SigningKey(ed25519_dalek::SigningKey);
+
+impl SigningKey {
+    /// A new key from the operating system's random source.
+    pub fn generate() -> Result<Self> {
+        let mut seed = [0u8; 32];
+        getrandom::fill(&mut seed).map_err(|e| anyhow!("Failed to generate a key: {}", e))?;
+        Ok(SigningKey(ed25519_dalek::SigningKey::from_bytes(&seed)))
+    }
+
+    /// The key in hex, as written to key files.
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let seed = decode_key(hex).context("signing key is not 32 bytes of hex")?;
+        Ok(SigningKey(ed25519_dalek::SigningKey::from_bytes(&seed)))
+    }
+
+    /// The key `source` names, which must load.
+    pub fn from_source(source: &KeySource) -> Result<Self> {
+        let Some(key) = source.load()? else {
+            bail!("No signing key; name it with --key-env or --key-file");
+        };
+        let hex = String::from_utf8_lossy(key.bytes()).trim().to_string();
+        Self::from_hex(&hex)
+    }
+
+    pub fn to_hex(&self) -> String {
+        encode_hex(self.0.as_bytes())
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(self.0.verifying_key())
+    }
+
+    /// A signature over `spec`'s `signed_hash`, stamped with the time.
+    pub fn sign(&self, spec: &Value) -> SpecSignature {
+        let hash = signed_hash(spec);
+        SpecSignature {
+            key_id: self.public_key().key_id(),
+            algorithm: ALGORITHM.to_string(),
+            signature: encode_hex(&self.0.sign(hash.as_bytes()).to_bytes()),
+            hash,
+            signed_at: Some(clock::now()),
+        }
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SigningKey({})", self.public_key().key_id())
+    }
+}
+
+/// An Ed25519 public key that verifies spec signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKey(ed25519_dalek::VerifyingKey);
+
+impl PublicKey {
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let bytes = decode_key(hex).context("public key is not 32 bytes of hex")?;
+        ed25519_dalek::VerifyingKey::from_bytes(&bytes)
+            .map(PublicKey)
+            .map_err(|_| anyhow!("public key is not a valid Ed25519 key"))
+    }
+
+    /// The public key in the file at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read public key: {}", path.display()))?;
+        Self::from_hex(text.trim())
+            .with_context(|| format!("Invalid public key {}", path.display()))
+    }
+
+    pub fn to_hex(&self) -> String {
+        encode_hex(self.0.as_bytes())
+    }
+
+    /// The first 16 hex digits of the key's SHA-256.
+    pub fn key_id(&self) -> String {
+        let digest = Sha256::digest(self.0.as_bytes());
+        encode_hex(&digest[..8])
+    }
+}
+
+/// One signature of a spec's `signed_hash`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpecSignature {
+    pub key_id: String,
+    pub algorithm: String,
+    /// The `signed_hash` signed, e.g. `sha256:<hex>`.
+    pub hash: String,
+    /// The Ed25519 signature of `hash`, in hex.
+    pub signature: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureStatus {
+    /// A trusted key signed the spec as it is.
+    Valid,
+    /// A trusted key signed another version of the spec.
+    Stale,
+    /// The signature does not verify under the key it names.
+    Invalid,
+    /// No trusted key has the signature's key id.
+    Untrusted,
+}
+
+impl SignatureStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SignatureStatus::Valid => "valid",
+            SignatureStatus::Stale => "stale",
+            SignatureStatus::Invalid => "invalid",
+            SignatureStatus::Untrusted => "untrusted",
+        }
+    }
+}
+
+/// A signature and what checking it found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignatureCheck {
+    #[serde(flatten)]
+    pub signature: SpecSignature,
+    pub status: SignatureStatus,
+}
+
+/// The hash signatures cover: `spec`'s `canonical_hash`, which leaves its
+/// `signatures` section out. `spec` is the composed spec before
+/// interpolation (see `compose::read_template`).
+pub fn signed_hash(spec: &Value) -> String {
+    canonical_hash(spec)
+}
+
+/// The signatures in `spec`'s `signatures` section.
+pub fn embedded(spec: &Value) -> Result<Vec<SpecSignature>> {
+    match spec.get("signatures") {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(signatures) => serde_json::from_value(signatures.clone())
+            .map_err(|e| anyhow!("Invalid signatures section: {}", e)),
+    }
+}
+
+/// Where the detached signatures of the spec at `file` are kept.
+pub fn detached_path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(DETACHED_EXTENSION);
+    file.with_file_name(name)
+}
+
+/// The signatures in a detached signature file.
+pub fn load_detached(path: &Path) -> Result<Vec<SpecSignature>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read signatures: {}", path.display()))?;
+    let value: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid signature file: {}", path.display()))?;
+    embedded(&value).with_context(|| format!("Invalid signature file: {}", path.display()))
+}
+
+/// A detached signature file holding `signatures`.
+pub fn render_detached(signatures: &[SpecSignature]) -> Result<String> {
+    #[derive(Serialize)]
+    struct Detached<'a> {
+        signatures: &'a [SpecSignature],
+    }
+    Ok(serde_json::to_string_pretty(&Detached { signatures })? + "\n")
+}
+
+/// `signatures` with `signature` in place of any earlier one by its key.
+pub fn replace(signatures: Vec<SpecSignature>, signature: SpecSignature) -> Vec<SpecSignature> {
+    let mut signatures: Vec<SpecSignature> = signatures
+        .into_iter()
+        .filter(|s| s.key_id != signature.key_id)
+        .collect();
+    signatures.push(signature);
+    signatures
+}
+
+/// Spec text with its `signatures` section replaced by `signatures`, at
+/// the end; the rest of the text, comments included, is kept as is.
+pub fn embed(content: &str, signatures: &[SpecSignature]) -> String {
+    let mut kept = Vec::new();
+    let mut in_section = false;
+    for line in content.lines() {
+        // A key at the margin starts a section; a list item there continues one.
+        let top_level = line.starts_with(|c: char| !c.is_whitespace() && c != '-');
+        if top_level {
+            in_section = line.starts_with("signatures:");
+        }
+        if !in_section {
+            kept.push(line);
+        }
+    }
+    while kept.last().is_some_and(|line| line.trim().is_empty()) {
+        kept.pop();
+    }
+    let mut block = String::from("signatures:\n");
+    for signature in signatures {
+        let fields = [
+            ("key_id", Some(&signature.key_id)),
+            ("algorithm", Some(&signature.algorithm)),
+            ("hash", Some(&signature.hash)),
+            ("signature", Some(&signature.signature)),
+            ("signed_at", signature.signed_at.as_ref()),
+        ];
+        let mut marker = "  - ";
+        for (key, value) in fields {
+            let Some(value) = value else { continue };
+            block.push_str(&format!("{}{}: {}\n", marker, key, scalar_str(value)));
+            marker = "    ";
+        }
+    }
+    format!("{}\n{}", kept.join("\n"), block)
+}
+
+/// Check each of `signatures` against `spec` as it is and the trusted `keys`.
+pub fn verify(
+    spec: &Value,
+    signatures: &[SpecSignature],
+    keys: &[PublicKey],
+) -> Vec<SignatureCheck> {
+    let hash = signed_hash(spec);
+    signatures
+        .iter()
+        .map(|signature| SignatureCheck {
+            signature: signature.clone(),
+            status: status(signature, &hash, keys),
+        })
+        .collect()
+}
+
+fn status(signature: &SpecSignature, hash: &str, keys: &[PublicKey]) -> SignatureStatus {
+    let Some(key) = keys.iter().find(|k| k.key_id() == signature.key_id) else {
+        return SignatureStatus::Untrusted;
+    };
+    let verified = signature.algorithm == ALGORITHM
+        && decode_hex(&signature.signature)
+            .and_then(|bytes| ed25519_dalek::Signature::from_slice(&bytes).ok())
+            .is_some_and(|sig| key.0.verify(signature.hash.as_bytes(), &sig).is_ok());
+    match (verified, signature.hash == hash) {
+        (false, _) => SignatureStatus::Invalid,
+        (true, false) => SignatureStatus::Stale,
+        (true, true) => SignatureStatus::Valid,
+    }
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn decode_key(hex: &str) -> Result<[u8; 32]> {
+    decode_hex(hex.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("expected 64 hex digits"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+    use crate::{diagnose_yaml, Severity};
+
+    #[test]
+    fn test_spec_signatures_cover_everything_but_the_signatures() {
+        use crate::signing::{self, PublicKey, SignatureStatus, SigningKey};
+
+        let key = SigningKey::generate().unwrap();
+        let trusted = [key.public_key()];
+        assert_eq!(
+            PublicKey::from_hex(&key.public_key().to_hex()).unwrap(),
+            key.public_key()
+        );
+        assert_eq!(
+            SigningKey::from_hex(&key.to_hex()).unwrap().public_key(),
+            key.public_key()
+        );
+        assert_eq!(key.public_key().key_id().len(), 16);
+        assert!(PublicKey::from_hex("abc").is_err());
+
+        let spec = crate::parse_yaml(MINIMAL).unwrap();
+        let signature = key.sign(&spec);
+        assert_eq!(signature.hash, signing::signed_hash(&spec));
+        assert_eq!(signature.hash, crate::canonical_hash(&spec));
+        let status = |yaml: &str, keys: &[PublicKey]| {
+            let spec = crate::parse_yaml(yaml).unwrap();
+            signing::verify(&spec, std::slice::from_ref(&signature), keys)[0].status
+        };
+        assert_eq!(status(MINIMAL, &trusted), SignatureStatus::Valid);
+        // Comments and the signatures themselves are not part of the hash.
+        let embedded = signing::embed(
+            &format!("# signed\n{}", MINIMAL),
+            std::slice::from_ref(&signature),
+        );
+        assert_eq!(status(&embedded, &trusted), SignatureStatus::Valid);
+        assert_eq!(crate::format_spec(&embedded).unwrap(), embedded);
+        assert_eq!(
+            signing::embedded(&crate::parse_yaml(&embedded).unwrap()).unwrap(),
+            std::slice::from_ref(&signature)
+        );
+        assert!(diagnose_yaml(&embedded)
+            .iter()
+            .all(|d| d.severity != Severity::Error));
+        assert_eq!(
+            status(&MINIMAL.replace("match: 0.9", "match: 0.8"), &trusted),
+            SignatureStatus::Stale
+        );
+        // Waivers and whitespace inside values are signed too.
+        let waived = format!(
+            "{}waivers:\n  - code: NO_BLOCKING\n    reason: small table\n",
+            MINIMAL
+        );
+        assert_eq!(status(&waived, &trusted), SignatureStatus::Stale);
+        assert_eq!(
+            status(
+                &MINIMAL.replace("table: contacts", "table: 'contacts '"),
+                &trusted
+            ),
+            SignatureStatus::Stale
+        );
+        assert_eq!(
+            status(MINIMAL, &[SigningKey::generate().unwrap().public_key()]),
+            SignatureStatus::Untrusted
+        );
+        let mut forged = signature.clone();
+        forged.hash = signing::signed_hash(
+            &crate::parse_yaml(&MINIMAL.replace("match: 0.9", "match: 0.5")).unwrap(),
+        );
+        let spec = crate::parse_yaml(&MINIMAL.replace("match: 0.9", "match: 0.5")).unwrap();
+        assert_eq!(
+            signing::verify(&spec, &[forged], &trusted)[0].status,
+            SignatureStatus::Invalid
+        );
+
+        // Re-signing replaces the key's earlier signature in place.
+        let resigned = signing::embed(
+            &embedded,
+            &signing::replace(vec![signature.clone()], key.sign(&spec)),
+        );
+        assert_eq!(resigned.matches("signatures:").count(), 1);
+        assert_eq!(resigned.matches("key_id:").count(), 1);
+        assert!(resigned.starts_with("# signed\n"));
+    }
+}
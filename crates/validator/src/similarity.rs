@@ -0,0 +1,1146 @@
+//! String similarity algorithms for fuzzy match rules.
+//!
+//! Each algorithm scores a pair of values from 0 (nothing in common) to 1
+//! (the same). A rule's `algorithm` names one of them; `AlgorithmRegistry`
+//! maps names to implementations, so `validate_semantics` can reject names
+//! nothing implements and an engine can `register` its own algorithms
+//! alongside the built-in ones.
+//!
+//! Edit-distance algorithms compare characters as given (callers lowercase
+//! first, as the compiled SQL does); token and phonetic algorithms ignore
+//! case. Phonetic algorithms score 1 when the codes agree and 0 otherwise.
+//! `dice` also compares Bloom filter encodings (see `encoding`) by their
+//! bits.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::encoding::{self, bloom_dice};
+
+/// Names of the built-in algorithms, as a rule's `algorithm` gives them.
+pub const BUILTIN_ALGORITHMS: &[&str] = &[
+    "jaro_winkler",
+    "jaro",
+    "levenshtein",
+    "damerau_levenshtein",
+    "trigram",
+    "jaccard",
+    "cosine",
+    "dice",
+    "soundex",
+    "metaphone",
+    "double_metaphone",
+];
+
+/// The algorithm fuzzy rules without an `algorithm` are scored with.
+pub const DEFAULT_ALGORITHM: &str = "jaro_winkler";
+
+type Algorithm = Arc<dyn Fn(&str, &str) -> f64 + Send + Sync>;
+
+/// Similarity algorithms by name.
+#[derive(Clone)]
+pub struct AlgorithmRegistry {
+    algorithms: BTreeMap<String, Algorithm>,
+}
+
+impl AlgorithmRegistry {
+    /// A registry with no algorithms.
+    pub fn empty() -> Self {
+        AlgorithmRegistry {
+            algorithms: BTreeMap::new(),
+        }
+    }
+
+    /// The built-in algorithms (see `BUILTIN_ALGORITHMS`).
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register("jaro_winkler", jaro_winkler)
+            .register("jaro", jaro)
+            .register("levenshtein", levenshtein)
+            .register("damerau_levenshtein", damerau_levenshtein)
+            .register("trigram", trigram)
+            .register("jaccard", jaccard)
+            .register("cosine", cosine)
+            .register("dice", dice)
+            .register("soundex", |a, b| same_code(soundex(a), soundex(b)))
+            .register("metaphone", |a, b| same_code(metaphone(a), metaphone(b)))
+            .register("double_metaphone", double_metaphone_similarity);
+        registry
+    }
+
+    /// Add `algorithm` under `name`, replacing any algorithm of that name.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        algorithm: impl Fn(&str, &str) -> f64 + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.algorithms.insert(name.into(), Arc::new(algorithm));
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.algorithms.contains_key(name)
+    }
+
+    /// Score `a` against `b` with the algorithm called `name`.
+    pub fn score(&self, name: &str, a: &str, b: &str) -> Option<f64> {
+        self.algorithms.get(name).map(|algorithm| algorithm(a, b))
+    }
+
+    /// Registered names, in sorted order.
+    pub fn names(&self) -> Vec<&str> {
+        self.algorithms.keys().map(String::as_str).collect()
+    }
+
+    /// The registered name closest to a misspelled one, if any is close.
+    pub fn closest(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase().replace(['-', ' '], "_");
+        self.algorithms
+            .keys()
+            .map(|known| (edit_distance(&name, known), known))
+            .filter(|(distance, _)| *distance <= 3)
+            .min()
+            .map(|(_, known)| known.as_str())
+    }
+}
+
+impl Default for AlgorithmRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl fmt::Debug for AlgorithmRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlgorithmRegistry")
+            .field("algorithms", &self.names())
+            .finish()
+    }
+}
+
+// ── Edit distance ──────────────────────────────────────────────────
+
+/// Jaro similarity.
+pub fn jaro(a: &str, b: &str) -> f64 {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut taken = vec![false; b.len()];
+    let mut matched = Vec::new();
+    for (i, ca) in a.iter().enumerate() {
+        let start = i.saturating_sub(window);
+        let end = (i + window + 1).min(b.len());
+        if let Some(j) = (start..end).find(|&j| !taken[j] && b[j] == *ca) {
+            taken[j] = true;
+            matched.push(*ca);
+        }
+    }
+    if matched.is_empty() {
+        return 0.0;
+    }
+    let in_b = b.iter().zip(&taken).filter(|(_, t)| **t).map(|(c, _)| c);
+    let transpositions = matched.iter().zip(in_b).filter(|(x, y)| x != y).count() / 2;
+    let m = matched.len() as f64;
+    (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64) / m) / 3.0
+}
+
+/// Jaro-Winkler: Jaro, boosted by up to 4 leading characters in common
+/// once it exceeds 0.7.
+pub fn jaro_winkler(a: &str, b: &str) -> f64 {
+    let similarity = jaro(a, b);
+    if similarity <= 0.7 {
+        return similarity;
+    }
+    let prefix = a
+        .chars()
+        .zip(b.chars())
+        .take(4)
+        .take_while(|(x, y)| x == y)
+        .count();
+    similarity + prefix as f64 * 0.1 * (1.0 - similarity)
+}
+
+/// One minus the Levenshtein distance over the longer length.
+pub fn levenshtein(a: &str, b: &str) -> f64 {
+    ratio(edit_distance(a, b), a, b)
+}
+
+/// One minus the Damerau-Levenshtein distance (which also counts swapping
+/// two characters as one edit) over the longer length.
+pub fn damerau_levenshtein(a: &str, b: &str) -> f64 {
+    ratio(damerau_distance(a, b), a, b)
+}
+
+fn ratio(distance: usize, a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count()).max(1);
+    1.0 - distance as f64 / longest as f64
+}
+
+/// Insertions, deletions and substitutions turning `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != *cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Edit distance counting adjacent transpositions, without the "optimal
+/// string alignment" restriction (`ca` to `abc` is 2).
+pub fn damerau_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let far = a.len() + b.len();
+    let mut d = vec![vec![0; b.len() + 2]; a.len() + 2];
+    d[0][0] = far;
+    for i in 0..=a.len() {
+        d[i + 1][0] = far;
+        d[i + 1][1] = i;
+    }
+    for j in 0..=b.len() {
+        d[0][j + 1] = far;
+        d[1][j + 1] = j;
+    }
+    // Last row each character was seen on.
+    let mut last_row: HashMap<char, usize> = HashMap::new();
+    for i in 1..=a.len() {
+        let mut last_column = 0;
+        for j in 1..=b.len() {
+            let k = last_row.get(&b[j - 1]).copied().unwrap_or(0);
+            let l = last_column;
+            let cost = if a[i - 1] == b[j - 1] {
+                last_column = j;
+                0
+            } else {
+                1
+            };
+            d[i + 1][j + 1] = (d[i][j] + cost)
+                .min(d[i + 1][j] + 1)
+                .min(d[i][j + 1] + 1)
+                .min(d[k][l] + (i - k - 1) + 1 + (j - l - 1));
+        }
+        last_row.insert(a[i - 1], i);
+    }
+    d[a.len() + 1][b.len() + 1]
+}
+
+// ── Tokens ─────────────────────────────────────────────────────────
+
+/// Jaccard similarity of the character trigrams of each word, padded as
+/// Postgres' `pg_trgm` pads them.
+pub fn trigram(a: &str, b: &str) -> f64 {
+    let trigrams = |value: &str| -> HashSet<Vec<char>> {
+        words(value)
+            .flat_map(|word| {
+                let padded: Vec<char> = format!("  {} ", word).chars().collect();
+                padded.windows(3).map(<[char]>::to_vec).collect::<Vec<_>>()
+            })
+            .collect()
+    };
+    set_similarity(&trigrams(a), &trigrams(b))
+}
+
+/// Jaccard similarity of the sets of words.
+pub fn jaccard(a: &str, b: &str) -> f64 {
+    let set = |value: &str| words(value).collect::<HashSet<_>>();
+    set_similarity(&set(a), &set(b))
+}
+
+/// Cosine similarity of word counts.
+pub fn cosine(a: &str, b: &str) -> f64 {
+    let counts = |value: &str| {
+        let mut counts: HashMap<String, f64> = HashMap::new();
+        for word in words(value) {
+            *counts.entry(word).or_default() += 1.0;
+        }
+        counts
+    };
+    let (a, b) = (counts(a), counts(b));
+    if a.is_empty() || b.is_empty() {
+        return (a.is_empty() && b.is_empty()) as u8 as f64;
+    }
+    let dot: f64 = a.iter().map(|(w, n)| n * b.get(w).unwrap_or(&0.0)).sum();
+    let norm = |counts: &HashMap<String, f64>| counts.values().map(|n| n * n).sum::<f64>().sqrt();
+    dot / (norm(&a) * norm(&b))
+}
+
+/// Dice coefficient of the character bigrams of the values, padded with a
+/// space at either end; for two Bloom filter encodings, of their bits.
+pub fn dice(a: &str, b: &str) -> f64 {
+    if let Some(score) = bloom_dice(a, b) {
+        return score;
+    }
+    let bigrams = |value: &str| -> HashSet<String> {
+        encoding::ngrams(&value.to_lowercase(), 2)
+            .into_iter()
+            .collect()
+    };
+    let (a, b) = (bigrams(a), bigrams(b));
+    let total = a.len() + b.len();
+    if total == 0 {
+        return 1.0;
+    }
+    2.0 * a.intersection(&b).count() as f64 / total as f64
+}
+
+/// Lowercased runs of letters and digits.
+fn words(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn set_similarity<T: Eq + std::hash::Hash>(a: &HashSet<T>, b: &HashSet<T>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+// ── Phonetic ───────────────────────────────────────────────────────
+
+fn same_code(a: String, b: String) -> f64 {
+    (a == b) as u8 as f64
+}
+
+/// American Soundex (`Robert` -> `R163`); empty for values without letters.
+pub fn soundex(value: &str) -> String {
+    let code = |c: char| match c {
+        'B' | 'F' | 'P' | 'V' => '1',
+        'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => '2',
+        'D' | 'T' => '3',
+        'L' => '4',
+        'M' | 'N' => '5',
+        'R' => '6',
+        // H and W do not separate letters with the same code.
+        'H' | 'W' => '-',
+        _ => '0',
+    };
+    let mut letters = value
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase());
+    let Some(first) = letters.next() else {
+        return String::new();
+    };
+    let mut out = first.to_string();
+    let mut last = code(first);
+    for c in letters {
+        let digit = code(c);
+        if digit == '-' {
+            continue;
+        }
+        if digit != '0' && digit != last {
+            out.push(digit);
+            if out.len() == 4 {
+                break;
+            }
+        }
+        last = digit;
+    }
+    format!("{:0<4}", out)
+}
+
+/// Philips' original Metaphone, untruncated (`Thompson` -> `TMSN`).
+pub fn metaphone(value: &str) -> String {
+    let mut w: Vec<char> = value
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    match w.as_slice() {
+        ['K' | 'G' | 'P', 'N', ..] | ['A', 'E', ..] | ['W', 'R', ..] => {
+            w.remove(0);
+        }
+        ['W', 'H', ..] => {
+            w.remove(1);
+        }
+        ['X', ..] => w[0] = 'S',
+        _ => {}
+    }
+
+    let at = |i: usize| w.get(i).copied().unwrap_or('\0');
+    let before = |i: usize| if i == 0 { '\0' } else { at(i - 1) };
+    let is_vowel = |c: char| "AEIOU".contains(c);
+    let front_vowel = |c: char| "EIY".contains(c);
+    let from = |i: usize, s: &str| {
+        let s: Vec<char> = s.chars().collect();
+        w.get(i..i + s.len()) == Some(s.as_slice())
+    };
+    let last = w.len().saturating_sub(1);
+
+    let mut code = String::new();
+    let mut i = 0;
+    while i < w.len() {
+        let c = w[i];
+        if c != 'C' && before(i) == c {
+            i += 1;
+            continue;
+        }
+        match c {
+            'A' | 'E' | 'I' | 'O' | 'U' => {
+                if i == 0 {
+                    code.push(c);
+                }
+            }
+            'B' => {
+                if !(before(i) == 'M' && i == last) {
+                    code.push('B');
+                }
+            }
+            'C' => {
+                if before(i) == 'S' && front_vowel(at(i + 1)) {
+                    // SCI, SCE, SCY: silent
+                } else if from(i, "CIA") {
+                    code.push('X');
+                } else if front_vowel(at(i + 1)) {
+                    code.push('S');
+                } else if before(i) == 'S' && at(i + 1) == 'H' {
+                    code.push('K');
+                    i += 1;
+                } else if at(i + 1) == 'H' {
+                    // Initial CH before a consonant (Christ) is hard.
+                    let hard = i == 0 && at(2) != '\0' && !is_vowel(at(2));
+                    code.push(if hard { 'K' } else { 'X' });
+                    i += 1;
+                } else {
+                    code.push('K');
+                }
+            }
+            'D' => {
+                if at(i + 1) == 'G' && front_vowel(at(i + 2)) {
+                    code.push('J');
+                    i += 2;
+                } else {
+                    code.push('T');
+                }
+            }
+            'G' => {
+                let silent = (at(i + 1) == 'H' && !is_vowel(at(i + 2))) || (i > 0 && from(i, "GN"));
+                if !silent {
+                    let hard = before(i) == 'G';
+                    code.push(if front_vowel(at(i + 1)) && !hard {
+                        'J'
+                    } else {
+                        'K'
+                    });
+                }
+            }
+            'H' => {
+                if i != last && !"CSPTG".contains(before(i)) && is_vowel(at(i + 1)) {
+                    code.push('H');
+                }
+            }
+            'K' => {
+                if before(i) != 'C' {
+                    code.push('K');
+                }
+            }
+            'P' => code.push(if at(i + 1) == 'H' { 'F' } else { 'P' }),
+            'Q' => code.push('K'),
+            'S' => {
+                if from(i, "SH") || from(i, "SIO") || from(i, "SIA") {
+                    code.push('X');
+                } else {
+                    code.push('S');
+                }
+            }
+            'T' => {
+                if from(i, "TIA") || from(i, "TIO") {
+                    code.push('X');
+                } else if from(i, "TH") {
+                    code.push('0');
+                } else if !from(i, "TCH") {
+                    code.push('T');
+                }
+            }
+            'V' => code.push('F'),
+            'W' | 'Y' => {
+                if is_vowel(at(i + 1)) {
+                    code.push(c);
+                }
+            }
+            'X' => code.push_str("KS"),
+            'Z' => code.push('S'),
+            // F, J, L, M, N, R
+            _ => code.push(c),
+        }
+        i += 1;
+    }
+    code
+}
+
+/// 1 if the primary Double Metaphone codes agree, 0.5 if only an
+/// alternate code agrees, else 0.
+fn double_metaphone_similarity(a: &str, b: &str) -> f64 {
+    let ((a1, a2), (b1, b2)) = (double_metaphone(a), double_metaphone(b));
+    if a1 == b1 {
+        1.0
+    } else if a1 == b2 || a2 == b1 || a2 == b2 {
+        0.5
+    } else {
+        0.0
+    }
+}
+
+/// Philips' Double Metaphone: the primary and alternate codes, up to 4
+/// characters each (`Schmidt` -> `XMT`, `SMT`).
+pub fn double_metaphone(value: &str) -> (String, String) {
+    DoubleMetaphone::new(value).encode()
+}
+
+const CODE_LENGTH: usize = 4;
+
+struct DoubleMetaphone {
+    w: Vec<char>,
+    slavo_germanic: bool,
+    primary: String,
+    alternate: String,
+}
+
+impl DoubleMetaphone {
+    fn new(value: &str) -> Self {
+        let w: Vec<char> = value.trim().to_uppercase().chars().collect();
+        let text: String = w.iter().collect();
+        let slavo_germanic = text.contains('W')
+            || text.contains('K')
+            || text.contains("CZ")
+            || text.contains("WITZ");
+        DoubleMetaphone {
+            w,
+            slavo_germanic,
+            primary: String::new(),
+            alternate: String::new(),
+        }
+    }
+
+    fn at(&self, i: isize) -> char {
+        if i < 0 {
+            return '\0';
+        }
+        self.w.get(i as usize).copied().unwrap_or('\0')
+    }
+
+    /// Whether the `len` characters from `start` are one of `options`.
+    fn is(&self, start: isize, len: usize, options: &[&str]) -> bool {
+        if start < 0 || start as usize + len > self.w.len() {
+            return false;
+        }
+        let found: String = self.w[start as usize..start as usize + len]
+            .iter()
+            .collect();
+        options.contains(&found.as_str())
+    }
+
+    fn vowel(&self, i: isize) -> bool {
+        "AEIOUY".contains(self.at(i))
+    }
+
+    fn last(&self) -> isize {
+        self.w.len() as isize - 1
+    }
+
+    fn add(&mut self, primary: &str, alternate: &str) {
+        self.add_primary(primary);
+        self.add_alternate(alternate);
+    }
+
+    fn both(&mut self, code: &str) {
+        self.add(code, code);
+    }
+
+    fn add_primary(&mut self, code: &str) {
+        let room = CODE_LENGTH.saturating_sub(self.primary.len());
+        self.primary.extend(code.chars().take(room));
+    }
+
+    fn add_alternate(&mut self, code: &str) {
+        let room = CODE_LENGTH.saturating_sub(self.alternate.len());
+        self.alternate.extend(code.chars().take(room));
+    }
+
+    fn complete(&self) -> bool {
+        self.primary.len() >= CODE_LENGTH && self.alternate.len() >= CODE_LENGTH
+    }
+
+    /// One more than `i`, or two when the next character is `c`.
+    fn skip(&self, i: isize, c: char) -> isize {
+        if self.at(i + 1) == c {
+            i + 2
+        } else {
+            i + 1
+        }
+    }
+
+    fn encode(mut self) -> (String, String) {
+        let mut i: isize = if self.is(0, 2, &["GN", "KN", "PN", "WR", "PS"]) {
+            1
+        } else {
+            0
+        };
+        while !self.complete() && i <= self.last() {
+            i = match self.at(i) {
+                'A' | 'E' | 'I' | 'O' | 'U' | 'Y' => {
+                    if i == 0 {
+                        self.both("A");
+                    }
+                    i + 1
+                }
+                'B' => {
+                    self.both("P");
+                    self.skip(i, 'B')
+                }
+                'Ç' => {
+                    self.both("S");
+                    i + 1
+                }
+                'C' => self.c(i),
+                'D' => self.d(i),
+                'F' => {
+                    self.both("F");
+                    self.skip(i, 'F')
+                }
+                'G' => self.g(i),
+                'H' => self.h(i),
+                'J' => self.j(i),
+                'K' => {
+                    self.both("K");
+                    self.skip(i, 'K')
+                }
+                'L' => self.l(i),
+                'M' => {
+                    self.both("M");
+                    let umb = self.is(i - 1, 3, &["UMB"])
+                        && (i + 1 == self.last() || self.is(i + 2, 2, &["ER"]));
+                    if self.at(i + 1) == 'M' || umb {
+                        i + 2
+                    } else {
+                        i + 1
+                    }
+                }
+                'N' => {
+                    self.both("N");
+                    self.skip(i, 'N')
+                }
+                'Ñ' => {
+                    self.both("N");
+                    i + 1
+                }
+                'P' => {
+                    if self.at(i + 1) == 'H' {
+                        self.both("F");
+                        i + 2
+                    } else {
+                        self.both("P");
+                        if self.is(i + 1, 1, &["P", "B"]) {
+                            i + 2
+                        } else {
+                            i + 1
+                        }
+                    }
+                }
+                'Q' => {
+                    self.both("K");
+                    self.skip(i, 'Q')
+                }
+                'R' => self.r(i),
+                'S' => self.s(i),
+                'T' => self.t(i),
+                'V' => {
+                    self.both("F");
+                    self.skip(i, 'V')
+                }
+                'W' => self.w(i),
+                'X' => self.x(i),
+                'Z' => self.z(i),
+                _ => i + 1,
+            };
+        }
+        (self.primary, self.alternate)
+    }
+
+    fn c(&mut self, i: isize) -> isize {
+        if self.c_is_k(i) {
+            self.both("K");
+            i + 2
+        } else if i == 0 && self.is(i, 6, &["CAESAR"]) {
+            self.both("S");
+            i + 2
+        } else if self.is(i, 2, &["CH"]) {
+            self.ch(i)
+        } else if self.is(i, 2, &["CZ"]) && !self.is(i - 2, 4, &["WICZ"]) {
+            self.add("S", "X");
+            i + 2
+        } else if self.is(i + 1, 3, &["CIA"]) {
+            self.both("X");
+            i + 3
+        } else if self.is(i, 2, &["CC"]) && !(i == 1 && self.at(0) == 'M') {
+            self.cc(i)
+        } else if self.is(i, 2, &["CK", "CG", "CQ"]) {
+            self.both("K");
+            i + 2
+        } else if self.is(i, 2, &["CI", "CE", "CY"]) {
+            if self.is(i, 3, &["CIO", "CIE", "CIA"]) {
+                self.add("S", "X");
+            } else {
+                self.both("S");
+            }
+            i + 2
+        } else {
+            self.both("K");
+            if self.is(i + 1, 2, &[" C", " Q", " G"]) {
+                i + 3
+            } else if self.is(i + 1, 1, &["C", "K", "Q"]) && !self.is(i + 1, 2, &["CE", "CI"]) {
+                i + 2
+            } else {
+                i + 1
+            }
+        }
+    }
+
+    /// Germanic `ACH` (`Bacher`), and `CHIA`.
+    fn c_is_k(&self, i: isize) -> bool {
+        if self.is(i, 4, &["CHIA"]) {
+            return true;
+        }
+        if i <= 1 || self.vowel(i - 2) || !self.is(i - 1, 3, &["ACH"]) {
+            return false;
+        }
+        let next = self.at(i + 2);
+        (next != 'I' && next != 'E') || self.is(i - 2, 6, &["BACHER", "MACHER"])
+    }
+
+    fn ch(&mut self, i: isize) -> isize {
+        if i > 0 && self.is(i, 4, &["CHAE"]) {
+            self.add("K", "X");
+        } else if self.greek_ch(i) || self.ch_is_k(i) {
+            self.both("K");
+        } else if i > 0 {
+            if self.is(0, 2, &["MC"]) {
+                self.both("K");
+            } else {
+                self.add("X", "K");
+            }
+        } else {
+            self.both("X");
+        }
+        i + 2
+    }
+
+    /// Greek roots at the start (`Chorus`, `Character`), but not `Chore`.
+    fn greek_ch(&self, i: isize) -> bool {
+        i == 0
+            && (self.is(i + 1, 5, &["HARAC", "HARIS"])
+                || self.is(i + 1, 3, &["HOR", "HYM", "HIA", "HEM"]))
+            && !self.is(0, 5, &["CHORE"])
+    }
+
+    fn ch_is_k(&self, i: isize) -> bool {
+        self.is(0, 4, &["VAN ", "VON "])
+            || self.is(0, 3, &["SCH"])
+            || self.is(i - 2, 6, &["ORCHES", "ARCHIT", "ORCHID"])
+            || self.is(i + 2, 1, &["T", "S"])
+            || ((self.is(i - 1, 1, &["A", "O", "U", "E"]) || i == 0)
+                && (self.is(
+                    i + 2,
+                    1,
+                    &["L", "R", "N", "M", "B", "H", "F", "V", "W", " "],
+                ) || i + 1 == self.last()))
+    }
+
+    fn cc(&mut self, i: isize) -> isize {
+        if self.is(i + 2, 1, &["I", "E", "H"]) && !self.is(i + 2, 2, &["HU"]) {
+            // Accident, accede, succeed; bacchus is K.
+            if (i == 1 && self.at(i - 1) == 'A') || self.is(i - 1, 5, &["UCCEE", "UCCES"]) {
+                self.both("KS");
+            } else {
+                self.both("X");
+            }
+            i + 3
+        } else {
+            self.both("K");
+            i + 2
+        }
+    }
+
+    fn d(&mut self, i: isize) -> isize {
+        if self.is(i, 2, &["DG"]) {
+            if self.is(i + 2, 1, &["I", "E", "Y"]) {
+                self.both("J");
+                i + 3
+            } else {
+                self.both("TK");
+                i + 2
+            }
+        } else if self.is(i, 2, &["DT", "DD"]) {
+            self.both("T");
+            i + 2
+        } else {
+            self.both("T");
+            i + 1
+        }
+    }
+
+    fn g(&mut self, i: isize) -> isize {
+        let next = self.at(i + 1);
+        if next == 'H' {
+            return self.gh(i);
+        }
+        if next == 'N' {
+            if i == 1 && self.vowel(0) && !self.slavo_germanic {
+                self.add("KN", "N");
+            } else if !self.is(i + 2, 2, &["EY"]) && !self.slavo_germanic {
+                self.add("N", "KN");
+            } else {
+                self.both("KN");
+            }
+            return i + 2;
+        }
+        if self.is(i + 1, 2, &["LI"]) && !self.slavo_germanic {
+            self.add("KL", "L");
+            return i + 2;
+        }
+        const SOFT: &[&str] = &[
+            "ES", "EP", "EB", "EL", "EY", "IB", "IL", "IN", "IE", "EI", "ER",
+        ];
+        if i == 0 && (next == 'Y' || self.is(i + 1, 2, SOFT)) {
+            self.add("K", "J");
+            return i + 2;
+        }
+        if (self.is(i + 1, 2, &["ER"]) || next == 'Y')
+            && !self.is(0, 6, &["DANGER", "RANGER", "MANGER"])
+            && !self.is(i - 1, 1, &["E", "I"])
+            && !self.is(i - 1, 3, &["RGY", "OGY"])
+        {
+            self.add("K", "J");
+            return i + 2;
+        }
+        if self.is(i + 1, 1, &["E", "I", "Y"]) || self.is(i - 1, 4, &["AGGI", "OGGI"]) {
+            if self.is(0, 4, &["VAN ", "VON "])
+                || self.is(0, 3, &["SCH"])
+                || self.is(i + 1, 2, &["ET"])
+            {
+                self.both("K");
+            } else if self.is(i + 1, 3, &["IER"]) {
+                self.both("J");
+            } else {
+                self.add("J", "K");
+            }
+            return i + 2;
+        }
+        self.both("K");
+        self.skip(i, 'G')
+    }
+
+    fn gh(&mut self, i: isize) -> isize {
+        if i > 0 && !self.vowel(i - 1) {
+            self.both("K");
+        } else if i == 0 {
+            self.both(if self.at(i + 2) == 'I' { "J" } else { "K" });
+        } else if (i > 1 && self.is(i - 2, 1, &["B", "H", "D"]))
+            || (i > 2 && self.is(i - 3, 1, &["B", "H", "D"]))
+            || (i > 3 && self.is(i - 4, 1, &["B", "H"]))
+        {
+            // Hugh, bough, broughton: silent.
+        } else if i > 2 && self.at(i - 1) == 'U' && self.is(i - 3, 1, &["C", "G", "L", "R", "T"]) {
+            // Laugh, cough, tough.
+            self.both("F");
+        } else if i > 0 && self.at(i - 1) != 'I' {
+            self.both("K");
+        }
+        i + 2
+    }
+
+    fn h(&mut self, i: isize) -> isize {
+        if (i == 0 || self.vowel(i - 1)) && self.vowel(i + 1) {
+            self.both("H");
+            i + 2
+        } else {
+            i + 1
+        }
+    }
+
+    fn j(&mut self, i: isize) -> isize {
+        if self.is(i, 4, &["JOSE"]) || self.is(0, 4, &["SAN "]) {
+            if (i == 0 && self.at(i + 4) == ' ') || self.w.len() == 4 || self.is(0, 4, &["SAN "]) {
+                self.both("H");
+            } else {
+                self.add("J", "H");
+            }
+            return i + 1;
+        }
+        if i == 0 {
+            self.add("J", "A");
+        } else if self.vowel(i - 1)
+            && !self.slavo_germanic
+            && (self.at(i + 1) == 'A' || self.at(i + 1) == 'O')
+        {
+            self.add("J", "H");
+        } else if i == self.last() {
+            self.add("J", "");
+        } else if !self.is(i + 1, 1, &["L", "T", "K", "S", "N", "M", "B", "Z"])
+            && !self.is(i - 1, 1, &["S", "K", "L"])
+        {
+            self.both("J");
+        }
+        self.skip(i, 'J')
+    }
+
+    fn l(&mut self, i: isize) -> isize {
+        if self.at(i + 1) != 'L' {
+            self.both("L");
+            return i + 1;
+        }
+        // Spanish `-llo`, `-lla`, `-alle`: the second code drops the L.
+        let spanish = (i == self.last() - 2 && self.is(i - 1, 4, &["ILLO", "ILLA", "ALLE"]))
+            || ((self.is(self.last() - 1, 2, &["AS", "OS"])
+                || self.is(self.last(), 1, &["A", "O"]))
+                && self.is(i - 1, 4, &["ALLE"]));
+        if spanish {
+            self.add_primary("L");
+        } else {
+            self.both("L");
+        }
+        i + 2
+    }
+
+    fn r(&mut self, i: isize) -> isize {
+        // French final `-ier` (Rogier) keeps the R only in the alternate.
+        if i == self.last()
+            && !self.slavo_germanic
+            && self.is(i - 2, 2, &["IE"])
+            && !self.is(i - 4, 2, &["ME", "MA"])
+        {
+            self.add_alternate("R");
+        } else {
+            self.both("R");
+        }
+        self.skip(i, 'R')
+    }
+
+    fn s(&mut self, i: isize) -> isize {
+        if self.is(i - 1, 3, &["ISL", "YSL"]) {
+            // Island, carlisle: silent.
+            i + 1
+        } else if i == 0 && self.is(i, 5, &["SUGAR"]) {
+            self.add("X", "S");
+            i + 1
+        } else if self.is(i, 2, &["SH"]) {
+            if self.is(i + 1, 4, &["HEIM", "HOEK", "HOLM", "HOLZ"]) {
+                self.both("S");
+            } else {
+                self.both("X");
+            }
+            i + 2
+        } else if self.is(i, 3, &["SIO", "SIA"]) || self.is(i, 4, &["SIAN"]) {
+            if self.slavo_germanic {
+                self.both("S");
+            } else {
+                self.add("S", "X");
+            }
+            i + 3
+        } else if (i == 0 && self.is(i + 1, 1, &["M", "N", "L", "W"])) || self.is(i + 1, 1, &["Z"])
+        {
+            self.add("S", "X");
+            self.skip(i, 'Z')
+        } else if self.is(i, 2, &["SC"]) {
+            self.sc(i)
+        } else {
+            if i == self.last() && self.is(i - 2, 2, &["AI", "OI"]) {
+                // French final `-ais`, `-ois`.
+                self.add_alternate("S");
+            } else {
+                self.both("S");
+            }
+            if self.is(i + 1, 1, &["S", "Z"]) {
+                i + 2
+            } else {
+                i + 1
+            }
+        }
+    }
+
+    fn sc(&mut self, i: isize) -> isize {
+        if self.at(i + 2) == 'H' {
+            if self.is(i + 3, 2, &["OO", "ER", "EN", "UY", "ED", "EM"]) {
+                if self.is(i + 3, 2, &["ER", "EN"]) {
+                    self.add("X", "SK");
+                } else {
+                    self.both("SK");
+                }
+            } else if i == 0 && !self.vowel(3) && self.at(3) != 'W' {
+                self.add("X", "S");
+            } else {
+                self.both("X");
+            }
+        } else if self.is(i + 2, 1, &["I", "E", "Y"]) {
+            self.both("S");
+        } else {
+            self.both("SK");
+        }
+        i + 3
+    }
+
+    fn t(&mut self, i: isize) -> isize {
+        if self.is(i, 4, &["TION"]) || self.is(i, 3, &["TIA", "TCH"]) {
+            self.both("X");
+            i + 3
+        } else if self.is(i, 2, &["TH"]) || self.is(i, 3, &["TTH"]) {
+            if self.is(i + 2, 2, &["OM", "AM"])
+                || self.is(0, 4, &["VAN ", "VON "])
+                || self.is(0, 3, &["SCH"])
+            {
+                self.both("T");
+            } else {
+                self.add("0", "T");
+            }
+            i + 2
+        } else {
+            self.both("T");
+            if self.is(i + 1, 1, &["T", "D"]) {
+                i + 2
+            } else {
+                i + 1
+            }
+        }
+    }
+
+    fn w(&mut self, i: isize) -> isize {
+        if self.is(i, 2, &["WR"]) {
+            self.both("R");
+            return i + 2;
+        }
+        if i == 0 && (self.vowel(i + 1) || self.is(i, 2, &["WH"])) {
+            if self.vowel(i + 1) {
+                self.add("A", "F");
+            } else {
+                self.both("A");
+            }
+        } else if (i == self.last() && self.vowel(i - 1))
+            || self.is(i - 1, 5, &["EWSKI", "EWSKY", "OWSKI", "OWSKY"])
+            || self.is(0, 3, &["SCH"])
+        {
+            self.add_alternate("F");
+        } else if self.is(i, 4, &["WICZ", "WITZ"]) {
+            self.add("TS", "FX");
+            return i + 4;
+        }
+        i + 1
+    }
+
+    fn x(&mut self, i: isize) -> isize {
+        if i == 0 {
+            self.both("S");
+            return i + 1;
+        }
+        // French final `-eaux`, `-oux` is silent.
+        let french = i == self.last()
+            && (self.is(i - 3, 3, &["IAU", "EAU"]) || self.is(i - 2, 2, &["AU", "OU"]));
+        if !french {
+            self.both("KS");
+        }
+        if self.is(i + 1, 1, &["C", "X"]) {
+            i + 2
+        } else {
+            i + 1
+        }
+    }
+
+    fn z(&mut self, i: isize) -> isize {
+        if self.at(i + 1) == 'H' {
+            self.both("J");
+            return i + 2;
+        }
+        if self.is(i + 1, 2, &["ZO", "ZI", "ZA"])
+            || (self.slavo_germanic && i > 0 && self.at(i - 1) != 'T')
+        {
+            self.add("S", "TS");
+        } else {
+            self.both("S");
+        }
+        self.skip(i, 'Z')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnose_yaml;
+    use crate::test_support::{MINIMAL, PRIVACY};
+
+    #[test]
+    fn test_similarity_algorithms() {
+        use crate::similarity::*;
+
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-3;
+        assert!(close(jaro_winkler("martha", "marhta"), 0.961));
+        assert!(close(jaro_winkler("dwayne", "duane"), 0.840));
+        assert!(close(levenshtein("kitten", "sitting"), 1.0 - 3.0 / 7.0));
+        assert_eq!(damerau_distance("abcd", "acbd"), 1);
+        assert_eq!(damerau_distance("ca", "abc"), 2);
+        // pg_trgm's similarity('word', 'two words').
+        assert!(close(trigram("word", "two words"), 4.0 / 11.0));
+        assert!(close(jaccard("Acme Corp", "acme corporation"), 1.0 / 3.0));
+        assert!(close(cosine("a b b", "b a"), 3.0 / 10f64.sqrt()));
+
+        assert_eq!(soundex("Robert"), "R163");
+        assert_eq!(metaphone("Knight"), "NT");
+        assert_eq!(metaphone("Thumb"), "0M");
+        assert_eq!(double_metaphone("Schmidt"), ("XMT".into(), "SMT".into()));
+        assert_eq!(
+            double_metaphone("Jankelowicz"),
+            ("JNKL".into(), "ANKL".into())
+        );
+        assert_eq!(double_metaphone("Xavier"), ("SF".into(), "SFR".into()));
+
+        let mut registry = AlgorithmRegistry::builtin();
+        assert_eq!(registry.names().len(), BUILTIN_ALGORITHMS.len());
+        assert_eq!(registry.score("soundex", "Smith", "Smyth"), Some(1.0));
+        assert_eq!(
+            registry.score("double_metaphone", "Smith", "Schmidt"),
+            Some(0.5)
+        );
+        assert_eq!(registry.score("nope", "a", "b"), None);
+        assert_eq!(registry.closest("Jaro-Winkler"), Some("jaro_winkler"));
+        registry.register("first_letter", |a, b| {
+            (a.get(..1) == b.get(..1)) as u8 as f64
+        });
+        assert_eq!(registry.score("first_letter", "ann", "amy"), Some(1.0));
+    }
+
+    #[test]
+    fn test_unknown_algorithms_are_rejected() {
+        use crate::{
+            parse_yaml, semantic_diagnostics, semantic_diagnostics_with, AlgorithmRegistry,
+        };
+
+        let yaml =
+            MINIMAL.replace("type: exact", "type: fuzzy\n    algorithm: jarowinkler") + PRIVACY;
+        let diagnostics = diagnose_yaml(&yaml);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "KNV0111");
+        assert_eq!(diagnostics[0].path.as_deref(), Some("rules[0].algorithm"));
+        assert_eq!(
+            diagnostics[0].suggestion.as_deref(),
+            Some("Did you mean 'jaro_winkler'?")
+        );
+
+        // An engine that registers the algorithm can accept it.
+        let spec = parse_yaml(&yaml).unwrap();
+        let mut algorithms = AlgorithmRegistry::builtin();
+        algorithms.register("jarowinkler", crate::similarity::jaro_winkler);
+        assert!(semantic_diagnostics_with(&spec, &algorithms).is_empty());
+        assert!(!semantic_diagnostics(&spec).is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! Shareable handle to a parsed specification.
+//!
+//! `Spec` is immutable once parsed and cheap to clone (the parsed tree and the
+//! original source sit behind an `Arc`), so a web service can load a spec once
+//! and hand clones to concurrent request handlers.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::commands::compile::compile_to_ir;
+use crate::commands::hash::compute_hash;
+use crate::cancel::CancellationToken;
+use crate::commands::plan::{generate_plan, generate_plan_with, PlanOptions, PlanResult};
+use crate::diagnostics::{locate_all, Diagnostic, Profile, Tiers};
+use crate::parser::{parse_yaml_with_locations, SourceMap};
+use crate::policy::Policy;
+use crate::task::BlockingTask;
+use crate::validator::{schema_diagnostics, semantic_diagnostics};
+use crate::waivers::{Waived, Waivers};
+
+#[derive(Debug)]
+struct SpecInner {
+    source: String,
+    value: Value,
+    source_map: SourceMap,
+}
+
+#[derive(Debug, Clone)]
+pub struct Spec {
+    inner: Arc<SpecInner>,
+}
+
+impl Spec {
+    pub fn parse(yaml: &str) -> Result<Self> {
+        let (value, source_map) = parse_yaml_with_locations(yaml)?;
+        Ok(Spec {
+            inner: Arc::new(SpecInner {
+                source: yaml.to_string(),
+                value,
+                source_map,
+            }),
+        })
+    }
+
+    /// The YAML text the spec was parsed from.
+    pub fn source(&self) -> &str {
+        &self.inner.source
+    }
+
+    /// The parsed spec tree.
+    pub fn value(&self) -> &Value {
+        &self.inner.value
+    }
+
+    /// Where each node of the spec appears in the source.
+    pub fn source_map(&self) -> &SourceMap {
+        &self.inner.source_map
+    }
+
+    /// Schema and semantic findings of every severity, located in the source.
+    /// Findings waived in the spec are left out.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnose().0
+    }
+
+    /// Findings split into errors, warnings and info under `profile`.
+    pub fn tiers(&self, profile: Profile) -> Tiers {
+        let (diagnostics, waived) = self.diagnose();
+        Tiers::split_waived(diagnostics, waived, profile)
+    }
+
+    /// Waivers declared in the spec.
+    pub fn waivers(&self) -> Waivers {
+        Waivers::collect(self.source(), self.value())
+    }
+
+    /// The spec's `policy` section; empty if it has none or it is malformed.
+    pub fn policy(&self) -> Policy {
+        Policy::default().for_spec(self.value())
+    }
+
+    fn diagnose(&self) -> (Vec<Diagnostic>, Vec<Waived>) {
+        let mut diagnostics = schema_diagnostics(self.value());
+        diagnostics.extend(semantic_diagnostics(self.value()));
+        let (diagnostics, waived) = self.waivers().apply(diagnostics);
+        let mut diagnostics = self.policy().apply(diagnostics);
+        locate_all(&mut diagnostics, self.source_map());
+        (diagnostics, waived)
+    }
+
+    pub fn validate(&self) -> Result<Vec<String>> {
+        Ok(self
+            .diagnostics()
+            .iter()
+            .filter(|d| d.is_error())
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    pub fn compile(&self) -> Result<Value> {
+        compile_to_ir(self.value())
+    }
+
+    pub fn plan(&self) -> Result<PlanResult> {
+        generate_plan(self.source())
+    }
+
+    /// Plan with a cancellation token (which may carry a timeout).
+    pub fn plan_with_cancel(&self, token: &CancellationToken) -> Result<PlanResult> {
+        let options = PlanOptions {
+            cancel: Some(token.clone()),
+            ..PlanOptions::default()
+        };
+        generate_plan_with(self.source(), &options)
+    }
+
+    pub fn hash(&self) -> Result<String> {
+        compute_hash(self.value())
+    }
+
+    /// Validate on a background thread; resolves on any executor.
+    pub fn validate_async(&self) -> BlockingTask<Result<Vec<String>>> {
+        let spec = self.clone();
+        BlockingTask::spawn(move || spec.validate())
+    }
+
+    /// Generate the plan on a background thread; resolves on any executor.
+    pub fn plan_async(&self) -> BlockingTask<Result<PlanResult>> {
+        let spec = self.clone();
+        BlockingTask::spawn(move || spec.plan())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::test_support::MINIMAL;
+    use crate::{CancellationToken, Cancelled, DiffResult, Ir, PlanResult, Spec};
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(value) => return value,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_public_types_are_send_sync() {
+        assert_send_sync::<Spec>();
+        assert_send_sync::<PlanResult>();
+        assert_send_sync::<DiffResult>();
+        assert_send_sync::<Ir>();
+    }
+
+    #[test]
+    fn test_spec_shared_across_threads() {
+        let spec = Spec::parse(MINIMAL).unwrap();
+        let expected = spec.hash().unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let spec = spec.clone();
+                thread::spawn(move || (spec.hash().unwrap(), spec.plan().unwrap().plan_hash))
+            })
+            .collect();
+
+        for handle in handles {
+            let (hash, plan_hash) = handle.join().unwrap();
+            assert_eq!(hash, expected);
+            assert_eq!(plan_hash, expected);
+        }
+    }
+
+    #[test]
+    fn test_spec_async_variants() {
+        let spec = Spec::parse(MINIMAL).unwrap();
+        let errors = block_on(spec.validate_async()).unwrap();
+        assert!(errors.is_empty());
+        let plan = block_on(spec.plan_async()).unwrap();
+        assert_eq!(plan.entity, "customer");
+    }
+
+    #[test]
+    fn test_plan_cancellation_and_timeout() {
+        let spec = Spec::parse(MINIMAL).unwrap();
+
+        let token = CancellationToken::new();
+        assert!(spec.plan_with_cancel(&token).is_ok());
+
+        token.cancel();
+        let err = spec.plan_with_cancel(&token).unwrap_err();
+        assert_eq!(err.downcast_ref::<Cancelled>(), Some(&Cancelled::Cancelled));
+
+        let expired = CancellationToken::with_timeout(Duration::ZERO);
+        let err = spec.plan_with_cancel(&expired).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Cancelled>(),
+            Some(Cancelled::TimedOut(_))
+        ));
+    }
+}
@@ -0,0 +1,425 @@
+//! Regression tests for specs, run by `kanoniv test`.
+//!
+//! A spec's directory may hold a `tests` directory of YAML files, each a
+//! handful of records and what the spec should make of them:
+//!
+//! ```yaml
+//! description: Ann's CRM and billing records are one customer
+//! records:
+//!   crm:
+//!     - { contact_id: 1, email_address: ann@example.com, mobile: 555-0101 }
+//!   billing:
+//!     - { customer_id: "10", email: ann@example.com }
+//! pairs:
+//!   - records: [crm:1, billing:10]
+//!     expect: merge
+//! golden:
+//!   - entity: crm:1
+//!     winners:
+//!       email: billing:10
+//!       phone: crm:1
+//! ```
+//!
+//! Records are given per source in the source's own columns, as for
+//! `kanoniv execute`, and named `source:id`. A pair expects `merge`,
+//! `review` or `reject`; a pair blocking never compares is rejected. A
+//! golden expectation names its entity by any record in it, and gives the
+//! record each field's value should survive from (`winners`) or the value
+//! itself (`values`); `null` expects no value. Tests run the spec's plan
+//! with the reference interpreter (see `interpreter`).
+//!
+//! When the directory holds several specs, each test names the one it is
+//! for with `spec`, relative to the directory.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commands::compile::compile_to_ir;
+use crate::compose;
+use crate::embedding::Embedder;
+use crate::interpolate::Variables;
+use crate::interpreter::{execute_plan_with, ExecutionResult};
+use crate::ir::Ir;
+use crate::parser;
+
+/// Directory beside a spec holding its tests.
+pub const TESTS_DIR: &str = "tests";
+
+/// One test file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpecTest {
+    /// The file's name, without its extension.
+    #[serde(skip)]
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// The spec tested, relative to the directory holding `tests`.
+    #[serde(default)]
+    pub spec: Option<String>,
+    /// Records by source, in each source's own columns.
+    pub records: Value,
+    #[serde(default)]
+    pub pairs: Vec<PairExpectation>,
+    #[serde(default)]
+    pub golden: Vec<GoldenExpectation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairExpectation {
+    /// The two records' `source:id` keys.
+    pub records: [String; 2],
+    pub expect: Decision,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    #[serde(alias = "match")]
+    Merge,
+    Review,
+    Reject,
+}
+
+impl Decision {
+    /// The decision the interpreter made, named as `decision` is.
+    fn from_interpreter(decision: &str) -> Self {
+        match decision {
+            "match" => Decision::Merge,
+            "review" => Decision::Review,
+            _ => Decision::Reject,
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Decision::Merge => "merge",
+            Decision::Review => "review",
+            Decision::Reject => "reject",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GoldenExpectation {
+    /// Any record of the entity.
+    pub entity: String,
+    /// Golden field to the record its value survives from.
+    #[serde(default)]
+    pub winners: BTreeMap<String, Option<String>>,
+    /// Golden field to its value.
+    #[serde(default)]
+    pub values: BTreeMap<String, Option<String>>,
+}
+
+/// A spec and the tests for it, in name order.
+#[derive(Debug)]
+pub struct Suite {
+    pub spec: PathBuf,
+    pub tests: Vec<SpecTest>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TestResult {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub failures: Vec<TestFailure>,
+    /// Why the test could not run, if it could not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty() && self.error.is_none()
+    }
+}
+
+/// An expectation the spec did not meet.
+#[derive(Debug, Serialize)]
+pub struct TestFailure {
+    /// The pair (`crm:1 ~ billing:10`) or golden field (`crm:1 email`).
+    pub subject: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl SpecTest {
+    /// Read a test file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read test: {}", path.display()))?;
+        let mut test = Self::from_yaml(&content)
+            .map_err(|e| anyhow!("Invalid test {}: {:#}", path.display(), e))?;
+        test.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(test)
+    }
+
+    /// Parse a test from YAML text.
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let test: SpecTest = serde_yaml::from_str(yaml)?;
+        if test.pairs.is_empty() && test.golden.is_empty() {
+            bail!("expects nothing: give `pairs` or `golden`");
+        }
+        Ok(test)
+    }
+
+    /// Run the test against `ir` as of the date `as_of`, embedding values
+    /// for semantic rules with `embedder`.
+    pub fn run(&self, ir: &Ir, embedder: &dyn Embedder, as_of: &str) -> TestResult {
+        let (failures, error) = match execute_plan_with(ir, &self.records, embedder, None, as_of) {
+            Ok(result) => (self.check(&result), None),
+            Err(e) => (Vec::new(), Some(format!("{:#}", e))),
+        };
+        TestResult {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            failures,
+            error,
+        }
+    }
+
+    fn check(&self, result: &ExecutionResult) -> Vec<TestFailure> {
+        let mut failures = Vec::new();
+        for pair in &self.pairs {
+            let [left, right] = &pair.records;
+            let decided = result.decisions.iter().find(|d| {
+                (d.left_key == *left && d.right_key == *right)
+                    || (d.left_key == *right && d.right_key == *left)
+            });
+            let (decision, actual) = match decided {
+                Some(d) => {
+                    let decision = Decision::from_interpreter(&d.decision);
+                    (decision, format!("{} (score {:.3})", decision, d.score))
+                }
+                None => (Decision::Reject, "reject (never compared)".to_string()),
+            };
+            if decision != pair.expect {
+                failures.push(TestFailure {
+                    subject: format!("{} ~ {}", left, right),
+                    expected: pair.expect.to_string(),
+                    actual,
+                });
+            }
+        }
+
+        let golden = &result.golden;
+        for expected in &self.golden {
+            let entity = result
+                .clusters
+                .assignments
+                .iter()
+                .find(|a| a.record_key == expected.entity)
+                .and_then(|a| golden.records.iter().find(|r| r.entity_id == a.cluster_id));
+            let Some(entity) = entity else {
+                failures.push(TestFailure {
+                    subject: expected.entity.clone(),
+                    expected: "a golden record".to_string(),
+                    actual: "no entity holds the record".to_string(),
+                });
+                continue;
+            };
+            let fields = [
+                ("from", &expected.winners, &entity.record_keys),
+                ("value", &expected.values, &entity.values),
+            ];
+            for (what, wanted, actual) in fields {
+                for (field, want) in wanted {
+                    let subject = format!("{} {}", expected.entity, field);
+                    let Some(i) = golden.fields.iter().position(|f| f.field == *field) else {
+                        failures.push(TestFailure {
+                            subject,
+                            expected: format!("{} {}", what, shown(want.as_deref())),
+                            actual: "no such golden field".to_string(),
+                        });
+                        continue;
+                    };
+                    if actual[i] != *want {
+                        failures.push(TestFailure {
+                            subject,
+                            expected: format!("{} {}", what, shown(want.as_deref())),
+                            actual: format!("{} {}", what, shown(actual[i].as_deref())),
+                        });
+                    }
+                }
+            }
+        }
+        failures
+    }
+}
+
+impl Suite {
+    /// Compile the spec (`entity` of a workspace), interpolated with
+    /// `variables`, and run each test against its plan as of `as_of`.
+    pub fn run(
+        &self,
+        embedder: &dyn Embedder,
+        entity: Option<&str>,
+        variables: &Variables,
+        as_of: &str,
+    ) -> Result<Vec<TestResult>> {
+        let ir = compile(&self.spec, entity, variables)
+            .with_context(|| format!("Failed to compile {}", self.spec.display()))?;
+        Ok(self
+            .tests
+            .iter()
+            .map(|test| test.run(&ir, embedder, as_of))
+            .collect())
+    }
+}
+
+/// The tests of the spec at `root`, or of every spec under the directory
+/// `root`, by spec in path order. Specs without tests are left out.
+pub fn discover(root: &Path) -> Result<Vec<Suite>> {
+    let specs = match root.is_file() {
+        true => vec![root.to_path_buf()],
+        false => compose::find_specs(root)?,
+    };
+    let mut dirs: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for spec in specs {
+        let dir = spec.parent().map(Path::to_path_buf).unwrap_or_default();
+        dirs.entry(dir).or_default().push(spec);
+    }
+
+    let mut suites: BTreeMap<PathBuf, Vec<SpecTest>> = BTreeMap::new();
+    for (dir, selected) in dirs {
+        let tests_dir = dir.join(TESTS_DIR);
+        if !tests_dir.is_dir() {
+            continue;
+        }
+        // The names of every spec beside the selected ones, for tests
+        // naming none.
+        let listed = match dir.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => dir.as_path(),
+        };
+        let siblings: Vec<String> = compose::find_specs(listed)?
+            .iter()
+            .filter(|spec| spec.parent() == Some(listed))
+            .filter_map(|spec| spec.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+        for path in test_files(&tests_dir)? {
+            let test = SpecTest::load(&path)?;
+            let name = match (&test.spec, siblings.as_slice()) {
+                (Some(name), _) if siblings.contains(name) => name,
+                (Some(name), _) => bail!(
+                    "{}: '{}' is not a spec in {}",
+                    path.display(),
+                    name,
+                    listed.display()
+                ),
+                (None, [name]) => name,
+                (None, _) => bail!(
+                    "{}: {} holds {} specs; name the one tested with `spec`",
+                    path.display(),
+                    listed.display(),
+                    siblings.len()
+                ),
+            };
+            let spec = dir.join(name);
+            if selected.contains(&spec) {
+                suites.entry(spec).or_default().push(test);
+            }
+        }
+    }
+    Ok(suites
+        .into_iter()
+        .map(|(spec, tests)| Suite { spec, tests })
+        .collect())
+}
+
+/// The `.yaml` and `.yml` files of `dir`, in name order.
+fn test_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read tests: {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|file| {
+            file.is_file()
+                && file
+                    .extension()
+                    .is_some_and(|ext| ext == "yaml" || ext == "yml")
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn compile(spec: &Path, entity: Option<&str>, variables: &Variables) -> Result<Ir> {
+    let content = compose::read_spec(spec, entity, variables)?;
+    let parsed = parser::parse_yaml(&content).with_context(|| "Failed to parse YAML")?;
+    Ir::from_value(&compile_to_ir(&parsed)?)
+}
+
+fn shown(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("'{}'", value),
+        None => "null".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, AS_OF, EXECUTION, EXECUTION_RECORDS};
+
+    #[test]
+    fn test_spec_test_checks_decisions_and_golden_winners() {
+        use crate::{HttpEmbedder, SpecTest};
+
+        let ir = compiled_ir(EXECUTION);
+        let test = SpecTest::from_yaml(&format!(
+        "records: {}\npairs:\n  - records: [crm:4, billing:10]\n    expect: match\n  - records: [crm:2, billing:11]\n    expect: merge\n  - records: [crm:1, crm:2]\n    expect: reject\ngolden:\n  - entity: crm:1\n    winners: {{ email: billing:10, phone: crm:4 }}\n    values: {{ first_name: Bob }}\n  - entity: billing:12\n    values: {{ email: ~ }}\n",
+        EXECUTION_RECORDS.replace('\n', " ")
+    ))
+    .unwrap();
+        let result = test.run(&ir, &HttpEmbedder::new().unwrap(), AS_OF);
+        assert!(result.error.is_none());
+        let failures: Vec<(&str, &str, &str)> = result
+            .failures
+            .iter()
+            .map(|f| (f.subject.as_str(), f.expected.as_str(), f.actual.as_str()))
+            .collect();
+        // A pair never compared is rejected; a tombstoned record is in no entity.
+        assert_eq!(
+            failures,
+            [
+                ("crm:2 ~ billing:11", "merge", "review (score 0.588)"),
+                ("crm:1 first_name", "value 'Bob'", "value 'Ann'"),
+                (
+                    "billing:12",
+                    "a golden record",
+                    "no entity holds the record"
+                ),
+            ]
+        );
+
+        let unknown = SpecTest::from_yaml(
+            "records: { erp: [] }\npairs: [{ records: [erp:1, erp:2], expect: merge }]",
+        )
+        .unwrap();
+        let result = unknown.run(&ir, &HttpEmbedder::new().unwrap(), AS_OF);
+        assert!(!result.passed());
+        assert!(result.error.unwrap().contains("unknown source 'erp'"));
+        assert!(SpecTest::from_yaml("records: {}")
+            .unwrap_err()
+            .to_string()
+            .contains("expects nothing"));
+        assert!(SpecTest::from_yaml(
+            "records: {}\npairs: [{ records: [a:1, b:1], expect: maybe }]"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,662 @@
+//! Stewardship: assertions data stewards make that outrank the matcher.
+//!
+//! The `stewardship` section records what people know and scores cannot:
+//!
+//! ```yaml
+//! stewardship:
+//!   always_merge:
+//!     - records: [crm-1042, billing-88]
+//!       reason: Confirmed by phone, ticket 5521
+//!   never_merge:
+//!     - records: [crm-2210, billing-301]
+//!       reason: Twins sharing an address
+//!   locked:
+//!     - record: crm-1042
+//!       field: email
+//!       value: jane.doe@example.com
+//!       reason: Customer asked for this address
+//! ```
+//!
+//! Records are named by record key. The records of an `always_merge` entry
+//! end up in one entity and those of a `never_merge` entry in different
+//! ones, whatever their scores and the clustering strategy; a `locked`
+//! field of the entity holding `record` takes `value` (null to force it
+//! empty) whatever the survivorship rules say. Assertions may not
+//! contradict each other: records merged by `always_merge` entries, however
+//! chained, cannot be kept apart, and a record's field cannot be locked to
+//! two values.
+//!
+//! The same entries can live in a sidecar file, `<spec>.stewardship.yaml`
+//! next to the spec (or any file given with `--stewardship`), so stewards
+//! can maintain them without touching the spec; the file's entries add to
+//! the section's. `cluster_decisions_with` and `golden_records_with` honor
+//! them, recording each one applied in the audit trail.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::clustering::{find, ScoredPair};
+use crate::parser;
+
+/// Extension of a spec's sidecar file, after the spec's file stem.
+pub const SIDECAR_EXTENSION: &str = "stewardship.yaml";
+
+/// Keys of the section.
+pub const KEYS: &[&str] = &["always_merge", "never_merge", "locked"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stewardship {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub always_merge: Vec<Assertion>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub never_merge: Vec<Assertion>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locked: Vec<Lock>,
+}
+
+/// Records asserted to be, or not to be, the same entity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Assertion {
+    pub records: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// A golden field fixed for the entity holding `record`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lock {
+    pub record: String,
+    pub field: String,
+    /// `None` is null.
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Assertions that contradict each other, by the path of the later one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: String,
+    pub message: String,
+}
+
+impl Stewardship {
+    /// Read `stewardship` from a parsed spec; `None` if the spec has none.
+    pub fn from_spec(spec: &Value) -> Result<Option<Self>> {
+        match spec.get("stewardship").filter(|s| !s.is_null()) {
+            Some(section) => Self::from_section(section, "stewardship").map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Read a sidecar file: the section's entries at the top level.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read stewardship file: {}", path.display()))?;
+        let value = parser::parse_yaml(&text)
+            .with_context(|| format!("Invalid stewardship file {}", path.display()))?;
+        if value.is_null() {
+            return Ok(Stewardship::default());
+        }
+        Self::from_section(&value, "")
+            .map_err(|e| anyhow!("Invalid stewardship file {}: {:#}", path.display(), e))
+    }
+
+    /// The sidecar file of the spec at `spec_path`, if there is one.
+    pub fn find(spec_path: &Path) -> Option<PathBuf> {
+        let stem = spec_path.file_stem()?.to_str()?;
+        let file = spec_path.with_file_name(format!("{}.{}", stem, SIDECAR_EXTENSION));
+        file.is_file().then_some(file)
+    }
+
+    /// The entries of `file`, or else of the spec at `spec_path`'s sidecar
+    /// file; empty if there is neither.
+    pub fn discover(spec_path: &Path, file: Option<&Path>) -> Result<Self> {
+        match file
+            .map(Path::to_path_buf)
+            .or_else(|| Self::find(spec_path))
+        {
+            Some(file) => Self::load(&file),
+            None => Ok(Stewardship::default()),
+        }
+    }
+
+    fn from_section(section: &Value, prefix: &str) -> Result<Self> {
+        let path = |key: &str| match prefix {
+            "" => key.to_string(),
+            prefix => format!("{}.{}", prefix, key),
+        };
+        let Some(entries) = section.as_object() else {
+            let what = if prefix.is_empty() {
+                "A stewardship file"
+            } else {
+                prefix
+            };
+            bail!("{} must be a mapping, got {}", what, section);
+        };
+        if let Some(key) = entries.keys().find(|k| !KEYS.contains(&k.as_str())) {
+            bail!("Unknown stewardship key '{}'. Use {}", key, KEYS.join(", "));
+        }
+        let list = |key: &str| -> Result<&[Value]> {
+            match section.get(key) {
+                None | Some(Value::Null) => Ok(&[]),
+                Some(Value::Array(items)) => Ok(items),
+                Some(other) => bail!("{} must be a list, got {}", path(key), other),
+            }
+        };
+        let assertions = |key: &str| -> Result<Vec<Assertion>> {
+            list(key)?
+                .iter()
+                .enumerate()
+                .map(|(i, item)| assertion(item, &format!("{}[{}]", path(key), i)))
+                .collect()
+        };
+        Ok(Stewardship {
+            always_merge: assertions("always_merge")?,
+            never_merge: assertions("never_merge")?,
+            locked: list("locked")?
+                .iter()
+                .enumerate()
+                .map(|(i, item)| lock(item, &format!("{}[{}]", path("locked"), i)))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.always_merge.is_empty() && self.never_merge.is_empty() && self.locked.is_empty()
+    }
+
+    /// These assertions followed by `other`'s.
+    pub fn with(&self, other: &Stewardship) -> Stewardship {
+        let mut merged = self.clone();
+        merged
+            .always_merge
+            .extend(other.always_merge.iter().cloned());
+        merged.never_merge.extend(other.never_merge.iter().cloned());
+        merged.locked.extend(other.locked.iter().cloned());
+        merged
+    }
+
+    /// Assertions that contradict earlier ones: a `never_merge` entry two of
+    /// whose records `always_merge` entries join, and a field locked to a
+    /// value after another for the same record.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let merged = self.merged_groups();
+        let mut conflicts = Vec::new();
+        for (i, assertion) in self.never_merge.iter().enumerate() {
+            let together = assertion.records.iter().enumerate().find_map(|(j, a)| {
+                let b = assertion.records[j + 1..].iter().find(|b| {
+                    a != *b
+                        && merged.contains_key(a.as_str())
+                        && merged.get(a.as_str()) == merged.get(b.as_str())
+                })?;
+                Some((a, b))
+            });
+            if let Some((a, b)) = together {
+                conflicts.push(Conflict {
+                    path: format!("stewardship.never_merge[{}].records", i),
+                    message: format!(
+                        "'{}' and '{}' are kept apart here but merged by always_merge.",
+                        a, b
+                    ),
+                });
+            }
+        }
+        let mut values: BTreeMap<(&str, &str), &Option<String>> = BTreeMap::new();
+        for (i, lock) in self.locked.iter().enumerate() {
+            match values.get(&(lock.record.as_str(), lock.field.as_str())) {
+                Some(value) if **value != lock.value => conflicts.push(Conflict {
+                    path: format!("stewardship.locked[{}].value", i),
+                    message: format!(
+                        "'{}' of record '{}' is already locked to {}.",
+                        lock.field,
+                        lock.record,
+                        shown(value)
+                    ),
+                }),
+                _ => {
+                    values.insert((&lock.record, &lock.field), &lock.value);
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Fail on the first conflict.
+    pub fn check(&self) -> Result<()> {
+        match self.conflicts().first() {
+            Some(conflict) => bail!("Conflicting stewardship assertions: {}", conflict.message),
+            None => Ok(()),
+        }
+    }
+
+    /// Record -> the lowest record `always_merge` entries join it with, for
+    /// every record they name.
+    fn merged_groups(&self) -> BTreeMap<&str, &str> {
+        let mut keys: Vec<&str> = self
+            .always_merge
+            .iter()
+            .flat_map(|a| a.records.iter().map(String::as_str))
+            .collect();
+        keys.sort_unstable();
+        keys.dedup();
+        let index = |key: &str| keys.binary_search(&key).unwrap_or_default();
+        let mut parent: Vec<usize> = (0..keys.len()).collect();
+        for assertion in &self.always_merge {
+            for pair in assertion.records.windows(2) {
+                union(&mut parent, index(&pair[0]), index(&pair[1]));
+            }
+        }
+        (0..keys.len())
+            .map(|i| (keys[i], keys[find(&mut parent, i)]))
+            .collect()
+    }
+
+    /// `pairs` with the assertions laid over them: pairs kept apart
+    /// unmatched and scored 0, records merged paired and matched at 1.
+    /// `index` finds a record by key.
+    pub(crate) fn constrain_pairs(
+        &self,
+        pairs: &mut Vec<ScoredPair>,
+        index: impl Fn(&str) -> Option<usize>,
+    ) {
+        let apart = self.pairs(&self.never_merge, &index);
+        for pair in pairs.iter_mut() {
+            let key = (pair.left.min(pair.right), pair.left.max(pair.right));
+            if apart.contains(&key) {
+                pair.matched = false;
+                pair.score = 0.0;
+            }
+        }
+        for assertion in &self.always_merge {
+            let records: Vec<usize> = assertion.records.iter().filter_map(|r| index(r)).collect();
+            for pair in records.windows(2) {
+                pairs.push(ScoredPair {
+                    left: pair[0],
+                    right: pair[1],
+                    score: 1.0,
+                    matched: true,
+                });
+            }
+        }
+    }
+
+    /// `labels` (each record's cluster, by its lowest record) with the
+    /// assertions enforced: clusters holding records to merge are joined,
+    /// then clusters holding records to keep apart split, records joined
+    /// by `always_merge` moving together to the part they score highest
+    /// with.
+    pub(crate) fn enforce(
+        &self,
+        labels: &[usize],
+        pairs: &[ScoredPair],
+        index: impl Fn(&str) -> Option<usize>,
+    ) -> Vec<usize> {
+        let records = labels.len();
+        // Units: records always merged together.
+        let mut unit: Vec<usize> = (0..records).collect();
+        for assertion in &self.always_merge {
+            let members: Vec<usize> = assertion.records.iter().filter_map(|r| index(r)).collect();
+            for pair in members.windows(2) {
+                union(&mut unit, pair[0], pair[1]);
+            }
+        }
+        let mut group: Vec<usize> = labels.to_vec();
+        for record in 0..records {
+            let (a, b) = (
+                find(&mut group, record),
+                find(&mut group, find(&mut unit, record)),
+            );
+            union(&mut group, a, b);
+        }
+
+        let apart = self.pairs(&self.never_merge, &index);
+        let mut scores: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+        for pair in pairs {
+            let key = (pair.left.min(pair.right), pair.left.max(pair.right));
+            let score = scores.entry(key).or_default();
+            *score = score.max(pair.score);
+        }
+        let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for record in 0..records {
+            let root = find(&mut group, record);
+            members.entry(root).or_default().push(record);
+        }
+
+        let mut label: Vec<usize> = (0..records).collect();
+        for records in members.values() {
+            let split = records.iter().enumerate().any(|(i, &a)| {
+                records[i + 1..]
+                    .iter()
+                    .any(|&b| apart.contains(&(a.min(b), a.max(b))))
+            });
+            if !split {
+                for &record in records {
+                    label[record] = records[0];
+                }
+                continue;
+            }
+            let mut units: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+            for &record in records {
+                units
+                    .entry(find(&mut unit, record))
+                    .or_default()
+                    .push(record);
+            }
+            let mut units: Vec<Vec<usize>> = units.into_values().collect();
+            units.sort_by_key(|u| u[0]);
+            let mut parts: Vec<Vec<usize>> = Vec::new();
+            for unit in units {
+                let conflicts = |part: &[usize]| {
+                    unit.iter()
+                        .any(|&a| part.iter().any(|&b| apart.contains(&(a.min(b), a.max(b)))))
+                };
+                let affinity = |part: &[usize]| -> f64 {
+                    unit.iter()
+                        .flat_map(|&a| part.iter().map(move |&b| (a.min(b), a.max(b))))
+                        .filter_map(|key| scores.get(&key))
+                        .sum()
+                };
+                let best = parts
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, part)| !conflicts(part))
+                    .map(|(i, part)| (i, affinity(part)))
+                    .filter(|(_, affinity)| *affinity > 0.0)
+                    .fold(None, |best: Option<(usize, f64)>, (i, a)| match best {
+                        Some((_, b)) if b >= a => best,
+                        _ => Some((i, a)),
+                    });
+                match best {
+                    Some((i, _)) => parts[i].extend(unit),
+                    None => parts.push(unit),
+                }
+            }
+            for part in &parts {
+                let lowest = part.iter().copied().min().unwrap_or_default();
+                for &record in part {
+                    label[record] = lowest;
+                }
+            }
+        }
+        label
+    }
+
+    /// The locks on `field` of an entity with `records`; fails if they
+    /// lock it to different values.
+    pub(crate) fn lock<'a>(&'a self, records: &[&str], field: &str) -> Result<Option<&'a Lock>> {
+        let mut locks = self
+            .locked
+            .iter()
+            .filter(|lock| lock.field == field && records.contains(&lock.record.as_str()));
+        let Some(first) = locks.next() else {
+            return Ok(None);
+        };
+        if let Some(other) = locks.find(|lock| lock.value != first.value) {
+            bail!(
+                "'{}' is locked to {} by record '{}' and to {} by record '{}', which are one entity",
+                field,
+                shown(&first.value),
+                first.record,
+                shown(&other.value),
+                other.record
+            );
+        }
+        Ok(Some(first))
+    }
+
+    /// Every pair of records an entry names, by index, lower first.
+    fn pairs(
+        &self,
+        assertions: &[Assertion],
+        index: &impl Fn(&str) -> Option<usize>,
+    ) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for assertion in assertions {
+            let records: Vec<usize> = assertion.records.iter().filter_map(|r| index(r)).collect();
+            for (i, &a) in records.iter().enumerate() {
+                for &b in &records[i + 1..] {
+                    if a != b {
+                        pairs.push((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+}
+
+fn assertion(item: &Value, path: &str) -> Result<Assertion> {
+    let Some(records) = item.get("records").and_then(|r| r.as_array()) else {
+        bail!("{}.records must list record keys", path);
+    };
+    let records: Vec<String> = records
+        .iter()
+        .map(|r| scalar(r).ok_or_else(|| anyhow!("{}.records: {} is not a record key", path, r)))
+        .collect::<Result<_>>()?;
+    if records.len() < 2 {
+        bail!("{}.records must name at least two records", path);
+    }
+    Ok(Assertion {
+        records,
+        reason: reason(item, path)?,
+    })
+}
+
+fn lock(item: &Value, path: &str) -> Result<Lock> {
+    let text = |key: &str| match item.get(key).and_then(scalar) {
+        Some(text) => Ok(text),
+        None => bail!("{}.{} is required", path, key),
+    };
+    let value = match item.get("value") {
+        None => bail!("{}.value is required (null to lock the field empty)", path),
+        Some(Value::Null) => None,
+        Some(value) => match scalar(value) {
+            Some(value) => Some(value),
+            None => bail!("{}.value must be a single value, got {}", path, value),
+        },
+    };
+    Ok(Lock {
+        record: text("record")?,
+        field: text("field")?,
+        value,
+        reason: reason(item, path)?,
+    })
+}
+
+fn reason(item: &Value, path: &str) -> Result<Option<String>> {
+    match item.get("reason") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(reason)) => Ok(Some(reason.clone())),
+        Some(other) => bail!("{}.reason must be text, got {}", path, other),
+    }
+}
+
+/// A string, number or boolean as text.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn shown(value: &Option<String>) -> String {
+    match value {
+        Some(value) => format!("'{}'", value),
+        None => "null".to_string(),
+    }
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    parent[a.max(b)] = a.min(b);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{AS_OF, MINIMAL};
+    use crate::{diagnose_yaml, Severity};
+
+    #[test]
+    fn test_stewardship_outranks_scores_and_survivorship() {
+        use crate::audit::AuditEvent;
+        use crate::{
+            cluster_decisions, cluster_decisions_with, golden_records, golden_records_with, Sample,
+            Stewardship,
+        };
+
+        let rows = |rows: &[&[&str]]| -> Vec<Vec<String>> {
+            rows.iter()
+                .map(|r| r.iter().map(|c| c.to_string()).collect())
+                .collect()
+        };
+        let stewardship = "stewardship:\n  always_merge:\n    - records: [d, e]\n      reason: Same customer\n  never_merge:\n    - records: [a, c]\n      reason: Twins\n  locked:\n    - record: b\n      field: email\n      value: ann@z.com\n";
+        let spec = format!("{}{}", MINIMAL, stewardship);
+        assert!(crate::validate_yaml(&spec).unwrap().is_empty());
+
+        // A chain a-b-c whose ends are kept apart, and a pair d-e scored apart.
+        let decisions = Sample {
+            columns: ["left_key", "right_key", "score", "decision"]
+                .map(String::from)
+                .to_vec(),
+            rows: rows(&[
+                &["a", "b", "0.95", "match"],
+                &["b", "c", "0.92", "match"],
+                &["d", "e", "0.2", "no_match"],
+            ]),
+        };
+        let assigned = |clusters: &crate::EntityClusters| -> Vec<(String, String)> {
+            clusters
+                .assignments
+                .iter()
+                .map(|a| (a.record_key.clone(), a.cluster_id.clone()))
+                .collect()
+        };
+        let pair = |k: &str, c: &str| (k.to_string(), c.to_string());
+        let plain = cluster_decisions(MINIMAL, &decisions).unwrap();
+        assert_eq!(
+            assigned(&plain),
+            [
+                pair("a", "a"),
+                pair("b", "a"),
+                pair("c", "a"),
+                pair("d", "d"),
+                pair("e", "e")
+            ]
+        );
+        let clusters = cluster_decisions(&spec, &decisions).unwrap();
+        assert_eq!(
+            assigned(&clusters),
+            [
+                pair("a", "a"),
+                pair("b", "a"),
+                pair("c", "c"),
+                pair("d", "d"),
+                pair("e", "d")
+            ]
+        );
+        assert_eq!(clusters.overrides, 2);
+        let overrides: Vec<_> = clusters
+            .audit
+            .iter()
+            .filter_map(|r| match &r.event {
+                AuditEvent::StewardOverride(o) => Some((o.assertion.as_str(), o.reason.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            overrides,
+            [
+                ("always_merge", Some("Same customer")),
+                ("never_merge", Some("Twins"))
+            ]
+        );
+
+        // The same assertions from a sidecar file.
+        let dir = tempfile::tempdir().unwrap();
+        let spec_path = dir.path().join("spec.yaml");
+        std::fs::write(&spec_path, MINIMAL).unwrap();
+        assert!(Stewardship::find(&spec_path).is_none());
+        let sidecar = dir.path().join("spec.stewardship.yaml");
+        std::fs::write(
+            &sidecar,
+            stewardship
+                .replace("stewardship:\n", "")
+                .replace("\n  ", "\n")
+                .trim_start(),
+        )
+        .unwrap();
+        assert_eq!(Stewardship::find(&spec_path), Some(sidecar));
+        let discovered = Stewardship::discover(&spec_path, None).unwrap();
+        assert_eq!(discovered.always_merge[0].records, ["d", "e"]);
+        assert_eq!(
+            assigned(&cluster_decisions_with(MINIMAL, &decisions, &discovered).unwrap()),
+            assigned(&clusters)
+        );
+
+        // A locked field outranks the survivorship rule.
+        let members = Sample::from_csv("cluster_id,source_name,record_key,email\ne1,crm,a,ann@x.com\ne1,crm,b,\ne2,crm,c,cy@x.com\n").unwrap();
+        assert_eq!(
+            golden_records(MINIMAL, &members, AS_OF).unwrap().records[0].values,
+            [Some("ann@x.com".to_string())]
+        );
+        let golden = golden_records_with(MINIMAL, &members, &discovered, AS_OF).unwrap();
+        assert_eq!(golden.locked, 1);
+        assert_eq!(golden.records[0].values, [Some("ann@z.com".to_string())]);
+        assert_eq!(golden.records[1].values, [Some("cy@x.com".to_string())]);
+        assert_eq!(golden.lineage.fields[0].strategy, "locked");
+        assert_eq!(golden.lineage.fields[0].record_key, None);
+        assert_eq!(
+            golden_records(&spec, &members, AS_OF).unwrap().records[0].values,
+            golden.records[0].values
+        );
+
+        // Assertions may not contradict each other.
+        let errors = |yaml: &str| -> Vec<(String, String)> {
+            diagnose_yaml(yaml)
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .map(|d| (d.code.to_string(), d.path.unwrap()))
+                .collect()
+        };
+        let diagnostic = |code: &str, path: &str| (code.to_string(), path.to_string());
+        let conflicting = spec.replace("records: [d, e]", "records: [a, b, c]");
+        assert_eq!(
+            errors(&conflicting),
+            [diagnostic("KNV0124", "stewardship.never_merge[0].records")]
+        );
+        assert!(cluster_decisions(&conflicting, &decisions)
+            .unwrap_err()
+            .to_string()
+            .contains("merged by always_merge"));
+        let relocked = format!(
+            "{}    - record: b\n      field: email\n      value: ann@w.com\n",
+            spec
+        );
+        assert_eq!(
+            errors(&relocked),
+            [diagnostic("KNV0124", "stewardship.locked[1].value")]
+        );
+        assert_eq!(
+            errors(&spec.replace("field: email\n      value", "field: phone\n      value")),
+            [diagnostic("KNV0101", "stewardship.locked[0].field")]
+        );
+        assert_eq!(
+            errors(&spec.replace("records: [d, e]", "records: [d]")),
+            [diagnostic("KNV0124", "stewardship")]
+        );
+        // Locks that disagree within one entity fail the run.
+        let split = format!(
+            "{}    - record: a\n      field: email\n      value: ann@w.com\n",
+            spec
+        );
+        assert!(golden_records(&split, &members, AS_OF).is_err());
+    }
+}
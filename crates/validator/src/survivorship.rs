@@ -0,0 +1,838 @@
+//! Survivorship: building golden records from clustered members.
+//!
+//! `golden_records` applies a spec's survivorship rules to each cluster,
+//! field by field, recording each field's survivor for the audit trail
+//! (see `audit`) and the lineage (see `lineage`), and `survivorship_impact` compares the result with the
+//! current canonical table to show which golden fields a spec change would
+//! rewrite, without re-matching. Rules are applied as the compiled SQL
+//! applies them (see `codegen::sql`): null values last, then the strategy's
+//! order, ties broken by record key; attributes without a rule use
+//! `most_complete`. The strategies:
+//!
+//! - `source_priority`: the first source in `source_priority`
+//! - `most_recent`: the latest `timestamp` attribute, or the newest record key
+//! - `most_complete` / `longest`: the longest value
+//! - `most_frequent`: the value most members share
+//! - `non_null_priority`: the first of `fields` any member has a value for
+//! - `custom`: the highest `expression` (see `expression`), which reads
+//!   `value`, `source`, `record_key` and the attributes
+//!
+//! A rule's `condition`, an expression over the same names, breaks ties
+//! ahead of the strategy: members it holds for survive before the others.
+//! With a `temporal` section, current members survive before expired ones
+//! (see `temporal`). A field a steward has locked (see `stewardship`) takes
+//! the locked value whatever the rule; its members all lose. With a
+//! `deletion` section, deleted members are dropped first, or with `entity`
+//! scope their whole clusters (see `deletion`). Values past their
+//! retention period are expired before anything else (see `retention`).
+//!
+//! Both tables are CSV exports of the pipeline's outputs. `members` is
+//! `normalized_entities` joined with `entity_clusters`: a `cluster_id` (or
+//! `entity_id`), `source_name` and `record_key` column plus one column per
+//! attribute. `canonical` is `canonical_entities`: `entity_id` plus the
+//! attributes. Empty cells are nulls.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::audit::{AuditEvent, AuditRecord, StewardOverride, SurvivorshipChoice};
+use crate::commands::compile::compile_to_ir;
+use crate::deletion::{Deletion, DeletionScope};
+use crate::expression::{Expression, Value};
+use crate::ir::{Ir, IrSurvivorship};
+use crate::lineage::{describe_rule, Candidate, FieldLineage, Lineage};
+use crate::parser;
+use crate::retention::{self, RetentionAudit};
+use crate::sample::Sample;
+use crate::stewardship::{Lock, Stewardship};
+
+pub const STRATEGIES: &[&str] = &[
+    "source_priority",
+    "most_recent",
+    "most_complete",
+    "longest",
+    "most_frequent",
+    "non_null_priority",
+    "custom",
+];
+
+/// Strategy a locked field reports in the lineage.
+pub const LOCKED_STRATEGY: &str = "locked";
+
+/// Strategy of attributes without a survivorship rule.
+pub const DEFAULT_STRATEGY: &str = "most_complete";
+
+/// Names a `custom` expression reads besides the attributes.
+pub const EXPRESSION_NAMES: &[&str] = &["value", "source", "record_key"];
+
+/// The rule `ir` applies to `attribute`: its survivorship rule or the
+/// default, with `most_recent` ordering by the temporal `effective_from`
+/// when the rule names no timestamp.
+pub(crate) fn rule_for(ir: &Ir, attribute: &str) -> IrSurvivorship {
+    let mut rule = ir
+        .survivorship
+        .iter()
+        .find(|r| r.field == attribute)
+        .cloned()
+        .unwrap_or_else(|| default_rule(attribute));
+    if rule.strategy == "most_recent" && rule.timestamp.is_none() {
+        rule.timestamp = ir.temporal.as_ref().and_then(|t| t.effective_from.clone());
+    }
+    rule
+}
+
+/// The attribute that is empty on current members, if they survive before
+/// expired ones for `attribute`: the temporal `effective_to`, unless the
+/// attribute keeps its history.
+pub(crate) fn current_marker(ir: &Ir, attribute: &str) -> Option<String> {
+    let temporal = ir.temporal.as_ref()?;
+    if temporal.keeps_history(attribute) {
+        return None;
+    }
+    temporal.effective_to.clone()
+}
+
+/// The rule of an attribute without one.
+pub(crate) fn default_rule(field: &str) -> IrSurvivorship {
+    IrSurvivorship {
+        field: field.to_string(),
+        strategy: DEFAULT_STRATEGY.to_string(),
+        source_priority: None,
+        timestamp: None,
+        fields: None,
+        expression: None,
+        condition: None,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GoldenRecords {
+    /// Member rows read.
+    pub members: usize,
+    pub fields: Vec<FieldStrategy>,
+    pub records: Vec<GoldenRecord>,
+    /// Each golden field's survivor, for the audit trail.
+    #[serde(skip)]
+    pub audit: Vec<AuditRecord>,
+    /// Each golden field's survivor and the candidates that lost.
+    #[serde(skip)]
+    pub lineage: Lineage,
+    /// Golden fields set by a stewardship lock.
+    pub locked: usize,
+    /// Members dropped as deleted, with the members of erased entities.
+    pub deleted: usize,
+    /// Values expired under the privacy retention periods.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionAudit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldStrategy {
+    pub field: String,
+    pub strategy: String,
+}
+
+/// One cluster's golden record; `values` and `record_keys` follow
+/// `GoldenRecords::fields`. `None` is null.
+#[derive(Debug, Serialize)]
+pub struct GoldenRecord {
+    pub entity_id: String,
+    pub values: Vec<Option<String>>,
+    /// Record each value survives from.
+    pub record_keys: Vec<Option<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SurvivorshipImpact {
+    /// Clusters compared against a canonical row.
+    pub entities: usize,
+    pub changed_entities: usize,
+    pub fields: Vec<FieldImpact>,
+    pub changes: Vec<GoldenChange>,
+    /// Clusters with no canonical row, which were skipped.
+    pub unmatched: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldImpact {
+    pub field: String,
+    pub strategy: String,
+    pub changed: usize,
+}
+
+/// A golden field whose value would change. `None` is null.
+#[derive(Debug, Serialize)]
+pub struct GoldenChange {
+    pub entity_id: String,
+    pub field: String,
+    pub current: Option<String>,
+    pub proposed: Option<String>,
+    /// Record the proposed value survives from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_key: Option<String>,
+}
+
+/// Build a golden record for each cluster in `members` under `yaml`'s
+/// survivorship rules, with values expired as of `as_of` (`YYYY-MM-DD`).
+pub fn golden_records(yaml: &str, members: &Sample, as_of: &str) -> Result<GoldenRecords> {
+    golden_records_with(yaml, members, &Stewardship::default(), as_of)
+}
+
+/// `golden_records`, honoring the locks of `stewardship` (a sidecar file's
+/// assertions) after the spec's own.
+pub fn golden_records_with(
+    yaml: &str,
+    members: &Sample,
+    stewardship: &Stewardship,
+    as_of: &str,
+) -> Result<GoldenRecords> {
+    let spec = parser::parse_yaml(yaml).with_context(|| "Failed to parse YAML")?;
+    let ir = Ir::from_value(&compile_to_ir(&spec)?)?;
+    let mut members = members.clone();
+    golden_records_as_of(&ir, &mut members, stewardship, as_of)
+}
+
+/// `golden_records_with` for compiled IR, with the values of `members`
+/// expired as of `as_of` (`YYYY-MM-DD`) first; expired values are taken
+/// out of `members` as well.
+pub fn golden_records_as_of(
+    ir: &Ir,
+    members: &mut Sample,
+    stewardship: &Stewardship,
+    as_of: &str,
+) -> Result<GoldenRecords> {
+    let read = members.len();
+    let retention = retention::expire(ir, members, as_of)?;
+    let mut golden = golden_records_from_ir(ir, members, stewardship)?;
+    golden.members = read;
+    golden.deleted += read - members.len();
+    golden.retention = retention;
+    Ok(golden)
+}
+
+/// `golden_records_with`, for compiled IR.
+pub(crate) fn golden_records_from_ir(
+    ir: &Ir,
+    members: &Sample,
+    stewardship: &Stewardship,
+) -> Result<GoldenRecords> {
+    let stewardship = ir.stewardship.clone().unwrap_or_default().with(stewardship);
+    stewardship.check()?;
+    let attributes = ir.attributes();
+
+    let cluster = required(members, &["cluster_id", "entity_id"], "members")?;
+    let source = required(members, &["source_name"], "members")?;
+    let key = required(members, &["record_key"], "members")?;
+    let columns: HashMap<&str, usize> = attributes
+        .iter()
+        .map(|attribute| {
+            Ok((
+                attribute.as_str(),
+                required(members, &[attribute], "members")?,
+            ))
+        })
+        .collect::<Result<_>>()?;
+
+    let rules = attributes
+        .iter()
+        .map(|attribute| {
+            FieldRule::new(
+                rule_for(ir, attribute),
+                current_marker(ir, attribute).as_deref(),
+                &columns,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut clusters: BTreeMap<&str, Vec<Member>> = BTreeMap::new();
+    for row in &members.rows {
+        clusters
+            .entry(cell(row, cluster).unwrap_or_default())
+            .or_default()
+            .push(Member {
+                row,
+                source: cell(row, source).unwrap_or_default(),
+                key: cell(row, key).unwrap_or_default(),
+            });
+    }
+    let mut deleted = 0;
+    if let Some(deletion) = &ir.deletion {
+        let tombstone = columns[deletion.tombstone.as_str()];
+        let is_deleted = |m: &Member| Deletion::is_deleted(cell(m.row, tombstone));
+        clusters.retain(|_, members| {
+            let before = members.len();
+            match deletion.scope {
+                DeletionScope::Record => members.retain(|m| !is_deleted(m)),
+                DeletionScope::Entity if members.iter().any(is_deleted) => members.clear(),
+                DeletionScope::Entity => {}
+            }
+            deleted += before - members.len();
+            !members.is_empty()
+        });
+    }
+
+    let descriptions: Vec<String> = attributes
+        .iter()
+        .zip(&rules)
+        .map(|(attribute, rule)| {
+            let default = !ir.survivorship.iter().any(|r| r.field == *attribute);
+            describe_rule(&rule.rule, default)
+        })
+        .collect();
+    // Each cluster's locked fields, by attribute.
+    let locks: Vec<Vec<Option<&Lock>>> = clusters
+        .values()
+        .map(|members| {
+            let keys: Vec<&str> = members.iter().map(|m| m.key).collect();
+            attributes
+                .iter()
+                .map(|attribute| stewardship.lock(&keys, attribute))
+                .collect()
+        })
+        .collect::<Result<_>>()?;
+    let mut lineage = Lineage::new(ir);
+    let records: Vec<GoldenRecord> = clusters
+        .iter()
+        .zip(&locks)
+        .map(|((id, members), locks)| {
+            let ranked: Vec<Vec<(&Member, usize)>> = attributes
+                .iter()
+                .zip(&rules)
+                .map(|(attribute, rule)| {
+                    ranked(members, columns[attribute.as_str()], rule, &columns)
+                })
+                .collect();
+            let winners: Vec<Option<(&Member, usize)>> = ranked
+                .iter()
+                .zip(locks)
+                .map(|(r, lock)| r.first().copied().filter(|_| lock.is_none()))
+                .collect();
+            for (i, ranked) in ranked.iter().enumerate() {
+                let value =
+                    |&(m, column): &(&Member, usize)| cell(m.row, column).map(str::to_string);
+                let candidate = |&(m, column): &(&Member, usize)| Candidate {
+                    source: m.source.to_string(),
+                    record_key: m.key.to_string(),
+                    value: value(&(m, column)),
+                };
+                if let Some(lock) = locks[i] {
+                    lineage.fields.push(FieldLineage {
+                        entity_id: id.to_string(),
+                        field: attributes[i].clone(),
+                        value: lock.value.clone(),
+                        source: None,
+                        record_key: None,
+                        strategy: LOCKED_STRATEGY.to_string(),
+                        rule: describe_lock(lock),
+                        candidates: ranked.iter().map(candidate).collect(),
+                    });
+                    continue;
+                }
+                let winner = ranked.first();
+                lineage.fields.push(FieldLineage {
+                    entity_id: id.to_string(),
+                    field: attributes[i].clone(),
+                    value: winner.and_then(value),
+                    source: winner.map(|(m, _)| m.source.to_string()),
+                    record_key: winner.map(|(m, _)| m.key.to_string()),
+                    strategy: rules[i].rule.strategy.clone(),
+                    rule: descriptions[i].clone(),
+                    candidates: ranked.iter().skip(1).map(candidate).collect(),
+                });
+            }
+            GoldenRecord {
+                entity_id: id.to_string(),
+                values: winners
+                    .iter()
+                    .zip(locks)
+                    .map(|(w, lock)| match lock {
+                        Some(lock) => lock.value.clone(),
+                        None => w
+                            .and_then(|(m, column)| cell(m.row, column))
+                            .map(str::to_string),
+                    })
+                    .collect(),
+                record_keys: winners
+                    .iter()
+                    .map(|w| w.map(|(m, _)| m.key.to_string()))
+                    .collect(),
+            }
+        })
+        .collect();
+    let fields: Vec<FieldStrategy> = attributes
+        .iter()
+        .zip(&rules)
+        .map(|(field, rule)| FieldStrategy {
+            field: field.clone(),
+            strategy: rule.rule.strategy.clone(),
+        })
+        .collect();
+    let audit = records
+        .iter()
+        .zip(&locks)
+        .flat_map(|(record, locks)| {
+            fields
+                .iter()
+                .zip(record.values.iter().zip(&record.record_keys))
+                .zip(locks)
+                .map(|((field, (value, key)), lock)| {
+                    let event = match lock {
+                        Some(lock) => AuditEvent::StewardOverride(StewardOverride {
+                            assertion: "locked".to_string(),
+                            record_keys: vec![lock.record.clone()],
+                            entity_id: Some(record.entity_id.clone()),
+                            field: Some(field.field.clone()),
+                            value: value.clone(),
+                            reason: lock.reason.clone(),
+                        }),
+                        None => AuditEvent::SurvivorshipChoice(SurvivorshipChoice {
+                            entity_id: record.entity_id.clone(),
+                            field: field.field.clone(),
+                            value: value.clone(),
+                            record_key: key.clone(),
+                            strategy: field.strategy.clone(),
+                        }),
+                    };
+                    AuditRecord::new(ir, event)
+                })
+        })
+        .collect();
+    Ok(GoldenRecords {
+        members: members.rows.len(),
+        fields,
+        records,
+        audit,
+        lineage,
+        locked: locks.iter().flatten().flatten().count(),
+        deleted,
+        retention: None,
+    })
+}
+
+/// A lock in a line, for the lineage: `locked via record crm-1042: reason`.
+fn describe_lock(lock: &Lock) -> String {
+    match &lock.reason {
+        Some(reason) => format!("locked via record {}: {}", lock.record, reason),
+        None => format!("locked via record {}", lock.record),
+    }
+}
+
+/// Recompute golden fields for the clusters in `members` under `yaml`'s
+/// survivorship rules as of `as_of` and diff them against `canonical`.
+pub fn survivorship_impact(
+    yaml: &str,
+    members: &Sample,
+    canonical: &Sample,
+    as_of: &str,
+) -> Result<SurvivorshipImpact> {
+    let golden = golden_records(yaml, members, as_of)?;
+    let entity = required(canonical, &["entity_id"], "canonical")?;
+    let columns: Vec<Option<usize>> = golden
+        .fields
+        .iter()
+        .map(|f| canonical.find_column(&f.field))
+        .collect();
+    let current: HashMap<&str, &Vec<String>> = canonical
+        .rows
+        .iter()
+        .filter_map(|row| Some((cell(row, entity)?, row)))
+        .collect();
+
+    let mut impact = SurvivorshipImpact {
+        entities: 0,
+        changed_entities: 0,
+        fields: golden
+            .fields
+            .iter()
+            .map(|f| FieldImpact {
+                field: f.field.clone(),
+                strategy: f.strategy.clone(),
+                changed: 0,
+            })
+            .collect(),
+        changes: Vec::new(),
+        unmatched: Vec::new(),
+    };
+    for record in &golden.records {
+        let Some(row) = current.get(record.entity_id.as_str()) else {
+            impact.unmatched.push(record.entity_id.clone());
+            continue;
+        };
+        impact.entities += 1;
+        let before = impact.changes.len();
+        for (i, column) in columns.iter().enumerate() {
+            let proposed = record.values[i].as_deref();
+            let current = column.and_then(|c| cell(row, c));
+            if proposed != current {
+                impact.fields[i].changed += 1;
+                impact.changes.push(GoldenChange {
+                    entity_id: record.entity_id.clone(),
+                    field: golden.fields[i].field.clone(),
+                    current: current.map(str::to_string),
+                    proposed: proposed.map(str::to_string),
+                    record_key: proposed.and(record.record_keys[i].clone()),
+                });
+            }
+        }
+        if impact.changes.len() > before {
+            impact.changed_entities += 1;
+        }
+    }
+    Ok(impact)
+}
+
+fn required(table: &Sample, names: &[&str], what: &str) -> Result<usize> {
+    match names.iter().find_map(|n| table.find_column(n)) {
+        Some(index) => Ok(index),
+        None => bail!("{} table has no '{}' column", what, names.join("' or '")),
+    }
+}
+
+struct Member<'a> {
+    row: &'a [String],
+    source: &'a str,
+    key: &'a str,
+}
+
+/// A rule with its attribute references resolved to member columns.
+struct FieldRule {
+    rule: IrSurvivorship,
+    timestamp: Option<usize>,
+    /// Column that is empty on current members.
+    current: Option<usize>,
+    fields: Vec<usize>,
+    expression: Option<Expression>,
+    condition: Option<Expression>,
+}
+
+impl FieldRule {
+    fn new(
+        rule: IrSurvivorship,
+        current: Option<&str>,
+        columns: &HashMap<&str, usize>,
+    ) -> Result<Self> {
+        let column = |name: &str| match columns.get(name) {
+            Some(&column) => Ok(column),
+            None => bail!(
+                "Survivorship rule for '{}' references unknown attribute '{}'",
+                rule.field,
+                name
+            ),
+        };
+        let timestamp = rule.timestamp.as_deref().map(column).transpose()?;
+        let current = current.map(column).transpose()?;
+        let fields = rule
+            .fields
+            .iter()
+            .flatten()
+            .map(|f| column(f))
+            .collect::<Result<_>>()?;
+        let parse = |source: Option<&str>, what: &str| {
+            source
+                .map(|e| {
+                    Expression::parse(e).with_context(|| {
+                        format!("Invalid survivorship {} for '{}'", what, rule.field)
+                    })
+                })
+                .transpose()
+        };
+        let expression = parse(rule.expression.as_deref(), "expression")?;
+        let condition = parse(rule.condition.as_deref(), "condition")?;
+        Ok(FieldRule {
+            rule,
+            timestamp,
+            current,
+            fields,
+            expression,
+            condition,
+        })
+    }
+}
+
+/// The trimmed cell, or `None` if it is empty.
+fn cell(row: &[String], column: usize) -> Option<&str> {
+    row.get(column).map(|v| v.trim()).filter(|v| !v.is_empty())
+}
+
+/// The members in the order their values survive for the field in
+/// `column`, each with the column its value is read from; the first
+/// survives. Members meeting the rule's condition come before the others.
+/// `non_null_priority` lists each member that has one of `fields` once per
+/// such attribute, in the order of `fields`.
+fn ranked<'a, 'b>(
+    members: &'b [Member<'a>],
+    column: usize,
+    rule: &FieldRule,
+    columns: &HashMap<&str, usize>,
+) -> Vec<(&'b Member<'a>, usize)> {
+    let value = |m: &Member<'a>| -> Option<&'a str> { cell(m.row, column) };
+    // An expression that fails on a member scores it null.
+    let evaluate = |expression: &Expression, m: &Member<'a>| {
+        let lookup = |name: &str| match name {
+            "value" => Some(Value::from_cell(value(m))),
+            "source" => Some(Value::from_cell(Some(m.source))),
+            "record_key" => Some(Value::from_cell(Some(m.key))),
+            _ => columns.get(name).map(|&c| Value::from_cell(cell(m.row, c))),
+        };
+        expression.evaluate(&lookup).unwrap_or(Value::Null)
+    };
+    // Current members first, then those meeting the condition.
+    let preference: Vec<(bool, bool)> = members
+        .iter()
+        .map(|m| {
+            (
+                rule.current.is_some_and(|c| cell(m.row, c).is_some()),
+                rule.condition
+                    .as_ref()
+                    .is_some_and(|c| evaluate(c, m) != Value::Bool(true)),
+            )
+        })
+        .collect();
+
+    if rule.rule.strategy == "non_null_priority" {
+        // The first listed attribute any member has, from the lowest key.
+        return rule
+            .fields
+            .iter()
+            .flat_map(|&field| {
+                let mut having: Vec<(usize, &'b Member<'a>)> = members
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, m)| cell(m.row, field).is_some())
+                    .collect();
+                having.sort_by(|&(i, a), &(j, b)| {
+                    preference[i].cmp(&preference[j]).then(a.key.cmp(b.key))
+                });
+                having.into_iter().map(move |(_, m)| (m, field))
+            })
+            .collect();
+    }
+
+    let frequency = |v: Option<&str>| members.iter().filter(|m| value(m) == v).count();
+    let priority = rule.rule.source_priority.as_deref().unwrap_or_default();
+    let rank = |m: &Member<'a>| {
+        priority
+            .iter()
+            .position(|s| s == m.source)
+            .unwrap_or(priority.len())
+    };
+    let length = |m: &Member<'a>| value(m).map_or(0, |v| v.chars().count());
+    let timestamp = |m: &Member<'a>| rule.timestamp.and_then(|t| cell(m.row, t));
+    let scores: Vec<Value> = match &rule.expression {
+        Some(expression) => members.iter().map(|m| evaluate(expression, m)).collect(),
+        None => Vec::new(),
+    };
+    let score = |i: usize| scores.get(i).filter(|s| **s != Value::Null);
+
+    let mut ranked: Vec<(usize, &'b Member<'a>)> = members.iter().enumerate().collect();
+    ranked.sort_by(|&(i, a), &(j, b)| {
+        let order = match rule.rule.strategy.as_str() {
+            "source_priority" => rank(a).cmp(&rank(b)).then(a.key.cmp(b.key)),
+            "longest" | "most_complete" => length(b).cmp(&length(a)).then(a.key.cmp(b.key)),
+            "most_frequent" => frequency(value(b))
+                .cmp(&frequency(value(a)))
+                .then(a.key.cmp(b.key)),
+            "most_recent" => match (timestamp(a), timestamp(b)) {
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (ta, tb) => tb.cmp(&ta).then(b.key.cmp(a.key)),
+            },
+            "custom" => match (score(i), score(j)) {
+                (Some(x), Some(y)) => y.total_cmp(x),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+            .then(a.key.cmp(b.key)),
+            _ => a.key.cmp(b.key),
+        };
+        match (value(a), value(b)) {
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            _ => preference[i].cmp(&preference[j]).then(order),
+        }
+    });
+    ranked.into_iter().map(|(_, m)| (m, column)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::AS_OF;
+
+    #[test]
+    fn test_survivorship_impact_applies_compiled_order() {
+        use crate::{survivorship_impact, Sample};
+
+        let spec = include_str!("../conformance/multi_source/spec.yaml");
+        let members = Sample::from_csv(
+            "cluster_id,source_name,record_key,email,first_name,last_name,phone\n\
+         e1,support,support:1,a@x.com,,Li,555-0001\n\
+         e1,crm,crm:7,,Ann,Smith-Li,555-0009\n\
+         e1,billing,billing:2,b@x.com,,,\n\
+         e2,crm,crm:3,c@x.com,,,\n",
+        )
+        .unwrap();
+        // e1's email skips crm's null for billing, the phone comes from the
+        // greatest record key and the last name is the longest. There is no
+        // first_name column, so it is null now.
+        let canonical = Sample::from_csv(
+            "entity_id,email,last_name,phone\ne1,a@x.com,Li,555-0009\ne9,x@x.com,,\n",
+        )
+        .unwrap();
+        let impact = survivorship_impact(spec, &members, &canonical, AS_OF).unwrap();
+
+        assert_eq!((impact.entities, impact.changed_entities), (1, 1));
+        assert_eq!(impact.unmatched, vec!["e2"]);
+        let changes: Vec<(&str, Option<&str>, Option<&str>)> = impact
+            .changes
+            .iter()
+            .map(|c| {
+                (
+                    c.field.as_str(),
+                    c.current.as_deref(),
+                    c.proposed.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("email", Some("a@x.com"), Some("b@x.com")),
+                ("first_name", None, Some("Ann")),
+                ("last_name", Some("Li"), Some("Smith-Li")),
+                ("phone", Some("555-0009"), Some("555-0001")),
+            ]
+        );
+        assert_eq!(impact.changes[0].record_key.as_deref(), Some("billing:2"));
+
+        let missing =
+            Sample::from_csv("cluster_id,source_name,record_key\ne1,crm,crm:1\n").unwrap();
+        assert!(survivorship_impact(spec, &missing, &canonical, AS_OF).is_err());
+    }
+
+    #[test]
+    fn test_golden_records_apply_every_survivorship_strategy() {
+        use crate::expression::{Expression, Value};
+        use crate::{golden_records, Sample};
+
+        let spec = "api_version: kanoniv/v2\nidentity_version: retail_v1.0\nentity:\n  name: customer\n\
+        sources:\n  - name: crm\n    system: salesforce\n    table: contacts\n    id: contact_id\n    attributes:\n      \
+        email: email\n      phone: phone\n      mobile: mobile\n      updated_at: updated_at\n      first_name: first_name\n\
+        rules:\n  - name: email_exact\n    type: exact\n    field: email\n    weight: 1.0\n\
+        survivorship:\n  rules:\n\
+        \x20   - field: email\n      strategy: most_recent\n      timestamp: updated_at\n\
+        \x20   - field: phone\n      strategy: non_null_priority\n      fields: [mobile, phone]\n\
+        \x20   - field: first_name\n      strategy: custom\n      expression: \"source == 'crm' ? 100 : size(value)\"\n\
+        \x20   - field: mobile\n      strategy: most_frequent\n";
+        assert!(crate::validate_semantics(&crate::parse_yaml(spec).unwrap())
+            .unwrap()
+            .is_empty());
+        let members = Sample::from_csv(
+            "cluster_id,source_name,record_key,email,phone,mobile,updated_at,first_name\n\
+         e1,crm,crm:1,old@x.com,555-1,,2024-01-05,Al\n\
+         e1,billing,billing:1,new@x.com,555-2,,2025-03-01,Alexander\n\
+         e1,support,support:1,none@x.com,,,,Alex\n\
+         e2,crm,crm:2,c@x.com,555-3,777-1,,\n\
+         e2,billing,billing:2,d@x.com,,777-2,2023-01-01,Chris\n",
+        )
+        .unwrap();
+        let golden = golden_records(spec, &members, AS_OF).unwrap();
+
+        let fields: Vec<(&str, &str)> = golden
+            .fields
+            .iter()
+            .map(|f| (f.field.as_str(), f.strategy.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("email", "most_recent"),
+                ("first_name", "custom"),
+                ("mobile", "most_frequent"),
+                ("phone", "non_null_priority"),
+                ("updated_at", "most_complete"),
+            ]
+        );
+        let values = |i: usize| -> Vec<Option<&str>> {
+            golden.records[i]
+                .values
+                .iter()
+                .map(|v| v.as_deref())
+                .collect()
+        };
+        // e1 has no mobile, so its phone is the lowest key's phone; crm's first
+        // name scores highest. e2's crm record has no timestamp or first name.
+        assert_eq!(
+            values(0),
+            [
+                Some("new@x.com"),
+                Some("Al"),
+                None,
+                Some("555-2"),
+                Some("2025-03-01")
+            ]
+        );
+        assert_eq!(
+            values(1),
+            [
+                Some("d@x.com"),
+                Some("Chris"),
+                Some("777-2"),
+                Some("777-2"),
+                Some("2023-01-01")
+            ]
+        );
+        assert_eq!(
+            golden.records[0].record_keys[3].as_deref(),
+            Some("billing:1")
+        );
+
+        let expression =
+            Expression::parse("has(x) && number(x) * 2 >= 3 ? lower(y) + '!' : -1").unwrap();
+        assert_eq!(expression.names(), ["x", "y"]);
+        let lookup = |x: &'static str| {
+            move |name: &str| match name {
+                "x" => Some(Value::from_cell(Some(x))),
+                "y" => Some(Value::String("OK".to_string())),
+                _ => None,
+            }
+        };
+        assert_eq!(
+            expression.evaluate(&lookup("1.5")).unwrap(),
+            Value::String("ok!".to_string())
+        );
+        assert_eq!(
+            expression.evaluate(&lookup("1")).unwrap(),
+            Value::Number(-1.0)
+        );
+        assert_eq!(
+            expression.evaluate(&lookup("")).unwrap(),
+            Value::Number(-1.0)
+        );
+        assert!(Expression::parse("size(a, b)").is_err());
+        assert!(Expression::parse("exec('rm')").is_err());
+        assert!(Expression::parse(&format!("{}1{}", "(".repeat(40), ")".repeat(40))).is_err());
+
+        let invalid = spec
+            .replace("strategy: most_frequent", "strategy: most_frequnt")
+            .replace("fields: [mobile, phone]", "fields: [mobile, fax]")
+            .replace("size(value)", "size(nickname)");
+        let diagnostics = crate::semantic_diagnostics(&crate::parse_yaml(&invalid).unwrap());
+        let found: Vec<(&str, &str)> = diagnostics
+            .iter()
+            .filter(|d| d.is_error())
+            .map(|d| (d.code.as_str(), d.path.as_deref().unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("KNV0101", "survivorship.rules[1].fields[1]"),
+                ("KNV0116", "survivorship.rules[2].expression"),
+                ("KNV0116", "survivorship.rules[3].strategy"),
+            ]
+        );
+        assert!(golden_records(&invalid, &members, AS_OF).is_err());
+    }
+}
@@ -0,0 +1,639 @@
+//! Synthetic records for evaluating a spec, for `kanoniv generate-data`.
+//!
+//! `generate_data` makes up people (or whatever the entity is) and writes
+//! each as a record of some source, in the source's own columns, and then
+//! writes a share of them again as duplicates: in another source or the
+//! same, with perturbations a real duplicate has. Values follow each
+//! attribute's declared type, or else what its name says it holds (see
+//! `privacy::detect`): names, emails, phones, dates, addresses, companies,
+//! identifiers and numbers, with free text for the rest. Values of one
+//! entity agree with each other, so its email is made of its name.
+//!
+//! A duplicate's attributes are each perturbed with `PERTURB_RATE`:
+//!
+//! - `typo`: a character dropped, doubled, transposed or replaced;
+//! - `format`: the same value written differently (`(555) 010-1234`,
+//!   `03/14/1985`, `LEE`, `A.`);
+//! - `missing`: the value left out.
+//!
+//! and its first and last names are swapped with `SWAP_RATE`. The records
+//! an entity was written as are its ground-truth cluster, named as the
+//! interpreter names clusters: by the lowest record key (`source:id`).
+//! Records are shuffled before they are numbered, so ids say nothing of
+//! which are duplicates. The same seed gives the same data on any platform.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::attributes::AttributeType;
+use crate::interpreter;
+use crate::ir::Ir;
+use crate::privacy::{self, PiiKind};
+use crate::sample::Sample;
+
+/// Chance that each attribute of a duplicate is perturbed.
+pub const PERTURB_RATE: f64 = 0.3;
+/// Chance that a duplicate's first and last names are swapped.
+pub const SWAP_RATE: f64 = 0.05;
+
+const FIRST_NAMES: &[&str] = &[
+    "Ann", "Bob", "Carla", "David", "Elena", "Farid", "Grace", "Hiro", "Ines", "James", "Keiko",
+    "Liam", "Maria", "Noah", "Olga", "Pedro", "Quinn", "Rosa", "Samir", "Tara", "Umar", "Vera",
+    "Wei", "Xavier", "Yusuf", "Zoe", "Amara", "Bruno", "Chloe", "Dmitri", "Emma", "Felix",
+];
+const LAST_NAMES: &[&str] = &[
+    "Lee", "Garcia", "Nguyen", "Smith", "Okafor", "Kowalski", "Tanaka", "Silva", "Muller", "Rossi",
+    "Haddad", "Johnson", "Novak", "Patel", "Kim", "Dubois", "Larsen", "Moreno", "Singh", "Brown",
+    "Ivanova", "Cohen", "Mensah", "Fischer", "Walsh", "Ortiz", "Yilmaz", "Chen", "Adeyemi",
+    "Bauer", "Hughes", "Sato",
+];
+const STREETS: &[&str] = &[
+    "Oak", "Maple", "Cedar", "Elm", "Pine", "Birch", "Willow", "Lake", "Hill", "Park", "Mill",
+    "River", "Church", "High", "Station", "Victoria",
+];
+const STREET_SUFFIXES: &[(&str, &str)] = &[
+    ("St", "Street"),
+    ("Ave", "Avenue"),
+    ("Rd", "Road"),
+    ("Ln", "Lane"),
+    ("Dr", "Drive"),
+];
+const CITIES: &[&str] = &[
+    "Springfield",
+    "Riverton",
+    "Lakeside",
+    "Fairview",
+    "Greenville",
+    "Oakland",
+    "Franklin",
+    "Clinton",
+    "Madison",
+    "Georgetown",
+    "Salem",
+    "Bristol",
+    "Ashland",
+    "Dover",
+];
+const COMPANY_WORDS: &[&str] = &[
+    "Acme",
+    "Globex",
+    "Initech",
+    "Umbrella",
+    "Stark",
+    "Wayne",
+    "Hooli",
+    "Vandelay",
+    "Soylent",
+    "Tyrell",
+    "Cyberdyne",
+    "Wonka",
+    "Aperture",
+    "Gringotts",
+];
+const COMPANY_SUFFIXES: &[&str] = &["Inc", "LLC", "Ltd", "Group", "Corp"];
+const DOMAINS: &[&str] = &["example.com", "example.org", "mail.test", "inbox.test"];
+const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+
+/// How many records to make, and how.
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    /// Records in all, duplicates included.
+    pub rows: usize,
+    /// Share of the records that duplicate another.
+    pub duplicate_rate: f64,
+    pub seed: u64,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            rows: 1000,
+            duplicate_rate: 0.2,
+            seed: 0,
+        }
+    }
+}
+
+/// Records made up for a spec's sources, with the truth about them.
+#[derive(Debug)]
+pub struct SyntheticData {
+    /// Each source's records in its own columns, id column first, in the
+    /// order the spec declares sources.
+    pub sources: Vec<(String, Sample)>,
+    /// Every record's true cluster, ordered by record key.
+    pub labels: Vec<TruthLabel>,
+    pub entities: usize,
+    pub duplicates: usize,
+}
+
+/// The entity a record was made for.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TruthLabel {
+    pub record_key: String,
+    /// Lowest key of the entity's records.
+    pub cluster_id: String,
+    /// What was done to the record, as `typo:last_name`; empty for the
+    /// entity's first record.
+    pub perturbations: Vec<String>,
+}
+
+impl SyntheticData {
+    /// Records by source as `kanoniv execute` reads them; missing values
+    /// are null.
+    pub fn records_json(&self) -> Value {
+        interpreter::records_json(&self.sources)
+    }
+
+    /// The labels as CSV: `record_key`, `cluster_id` and `perturbations`
+    /// (separated by `;`).
+    pub fn labels_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(["record_key", "cluster_id", "perturbations"])?;
+        for label in &self.labels {
+            writer.write_record([
+                label.record_key.as_str(),
+                label.cluster_id.as_str(),
+                label.perturbations.join(";").as_str(),
+            ])?;
+        }
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+/// A source's records as CSV with a header row.
+pub fn sample_csv(sample: &Sample) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&sample.columns)?;
+    for row in &sample.rows {
+        writer.write_record(row)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Make `options.rows` records for the sources of `ir`.
+pub fn generate_data(ir: &Ir, options: &GenerateOptions) -> Result<SyntheticData> {
+    if ir.sources.is_empty() {
+        bail!("The spec declares no sources to generate records for");
+    }
+    if options.rows == 0 {
+        bail!("Cannot generate 0 records");
+    }
+    if !(0.0..1.0).contains(&options.duplicate_rate) {
+        bail!(
+            "The duplicate rate must be at least 0 and below 1, not {}",
+            options.duplicate_rate
+        );
+    }
+    let mut rng = Rng::new(options.seed);
+    let types = ir.types();
+    let tombstone = ir.deletion.as_ref().map(|d| d.tombstone.as_str());
+    let kinds: BTreeMap<String, Kind> = ir
+        .attributes()
+        .into_iter()
+        .map(|attribute| {
+            let kind = match Some(attribute.as_str()) == tombstone {
+                true => Kind::Tombstone,
+                false => Kind::of(&attribute, types.get(&attribute).copied()),
+            };
+            (attribute, kind)
+        })
+        .collect();
+
+    let duplicates = (options.rows as f64 * options.duplicate_rate).round() as usize;
+    let entities = (options.rows - duplicates).max(1);
+    let duplicates = options.rows - entities;
+    let truths: Vec<BTreeMap<&str, String>> = (0..entities)
+        .map(|number| {
+            let person = Person::new(number, &mut rng);
+            kinds
+                .iter()
+                .map(|(attribute, kind)| (attribute.as_str(), kind.value(&person, &mut rng)))
+                .collect()
+        })
+        .collect();
+
+    // Each entity once, then the duplicates, shuffled into one order.
+    let mut records: Vec<(usize, bool)> = (0..entities).map(|e| (e, false)).collect();
+    records.extend((0..duplicates).map(|_| (rng.below(entities), true)));
+    for i in (1..records.len()).rev() {
+        records.swap(i, rng.below(i + 1));
+    }
+
+    let mut samples: Vec<Sample> = ir
+        .sources
+        .iter()
+        .map(|source| {
+            let mut columns = vec![source.id.clone().unwrap_or_else(|| "id".to_string())];
+            columns.extend(source.attributes.values().cloned());
+            Sample {
+                columns,
+                rows: Vec::new(),
+            }
+        })
+        .collect();
+    let mut written: Vec<(String, usize, Vec<String>)> = Vec::with_capacity(records.len());
+    for (entity, duplicate) in records {
+        let s = rng.below(ir.sources.len());
+        let source = &ir.sources[s];
+        let id = (samples[s].rows.len() + 1).to_string();
+        let mut values: Vec<(&str, Kind, String)> = source
+            .attributes
+            .keys()
+            .map(|attribute| {
+                let truth = truths[entity][attribute.as_str()].clone();
+                (attribute.as_str(), kinds[attribute], truth)
+            })
+            .collect();
+        let mut perturbations = Vec::new();
+        if duplicate {
+            perturb(&mut values, &mut perturbations, &mut rng);
+        }
+        let mut row = vec![id.clone()];
+        row.extend(values.into_iter().map(|(_, _, value)| value));
+        samples[s].rows.push(row);
+        written.push((format!("{}:{}", source.name, id), entity, perturbations));
+    }
+
+    let mut cluster_ids: BTreeMap<usize, &str> = BTreeMap::new();
+    for (key, entity, _) in &written {
+        let lowest = cluster_ids.entry(*entity).or_insert(key);
+        if key.as_str() < *lowest {
+            *lowest = key.as_str();
+        }
+    }
+    let mut labels: Vec<TruthLabel> = written
+        .iter()
+        .map(|(key, entity, perturbations)| TruthLabel {
+            record_key: key.clone(),
+            cluster_id: cluster_ids[entity].to_string(),
+            perturbations: perturbations.clone(),
+        })
+        .collect();
+    labels.sort_by(|a, b| a.record_key.cmp(&b.record_key));
+
+    Ok(SyntheticData {
+        sources: ir
+            .sources
+            .iter()
+            .map(|source| source.name.clone())
+            .zip(samples)
+            .collect(),
+        labels,
+        entities,
+        duplicates,
+    })
+}
+
+/// Perturb a duplicate's values, noting each perturbation as
+/// `<kind>:<attribute>`.
+fn perturb(values: &mut [(&str, Kind, String)], notes: &mut Vec<String>, rng: &mut Rng) {
+    for (attribute, kind, value) in values.iter_mut() {
+        if *kind == Kind::Tombstone || value.is_empty() || !rng.chance(PERTURB_RATE) {
+            continue;
+        }
+        let (note, perturbed) = match rng.below(5) {
+            0 | 1 => ("typo", typo(value, rng)),
+            2 | 3 => ("format", kind.variant(value, rng)),
+            _ => ("missing", String::new()),
+        };
+        if perturbed != *value {
+            *value = perturbed;
+            notes.push(format!("{}:{}", note, attribute));
+        }
+    }
+    let first = values
+        .iter()
+        .position(|(_, kind, _)| *kind == Kind::FirstName);
+    let last = values
+        .iter()
+        .position(|(_, kind, _)| *kind == Kind::LastName);
+    if let (Some(first), Some(last)) = (first, last) {
+        if rng.chance(SWAP_RATE) {
+            let name = values[first].2.clone();
+            values[first].2 = std::mem::replace(&mut values[last].2, name);
+            notes.push(format!("swap:{},{}", values[first].0, values[last].0));
+        }
+    }
+}
+
+/// `value` with one of its characters dropped, doubled, swapped with the
+/// next or replaced.
+fn typo(value: &str, rng: &mut Rng) -> String {
+    let mut chars: Vec<char> = value.chars().collect();
+    let i = rng.below(chars.len());
+    match rng.below(4) {
+        0 if chars.len() > 1 => {
+            chars.remove(i);
+        }
+        1 => chars.insert(i, chars[i]),
+        2 if i + 1 < chars.len() => chars.swap(i, i + 1),
+        _ => {
+            chars[i] = match chars[i] {
+                c if c.is_ascii_digit() => char::from(b'0' + rng.below(10) as u8),
+                c if c.is_ascii_uppercase() => char::from(rng.pick(LETTERS).to_ascii_uppercase()),
+                _ => char::from(rng.pick(LETTERS)),
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+/// What an attribute holds, for making up its values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    FirstName,
+    LastName,
+    FullName,
+    Email,
+    Phone,
+    Date,
+    Address,
+    City,
+    Postcode,
+    Company,
+    Identifier,
+    Numeric,
+    /// The deletion tombstone: never set, as records are live.
+    Tombstone,
+    Text,
+}
+
+impl Kind {
+    fn of(attribute: &str, ty: Option<AttributeType>) -> Self {
+        let name = attribute.to_lowercase();
+        let parts: Vec<&str> = name.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+        let has = |candidates: &[&str]| parts.iter().any(|p| candidates.contains(p));
+        match (ty, privacy::detect(attribute, ty)) {
+            (_, Some(PiiKind::Email)) => Kind::Email,
+            (_, Some(PiiKind::Phone)) => Kind::Phone,
+            (Some(AttributeType::Date), _) | (_, Some(PiiKind::DateOfBirth)) => Kind::Date,
+            (Some(AttributeType::Numeric), _) => Kind::Numeric,
+            (Some(AttributeType::Identifier), _) | (_, Some(PiiKind::NationalId)) => {
+                Kind::Identifier
+            }
+            _ if has(&["first", "given", "forename", "firstname"]) => Kind::FirstName,
+            _ if has(&["last", "family", "surname", "lastname"]) => Kind::LastName,
+            (_, Some(PiiKind::Name)) => Kind::FullName,
+            // Before `name`, for `company_name`.
+            _ if has(&["company", "employer", "organization", "org", "business"]) => Kind::Company,
+            _ if has(&["name"]) => Kind::FullName,
+            _ if has(&["address", "street", "addr"]) => Kind::Address,
+            _ if has(&["city", "town"]) => Kind::City,
+            _ if has(&["zip", "postcode", "postal", "zipcode"]) => Kind::Postcode,
+            _ if has(&["date", "dob", "born"]) => Kind::Date,
+            (None, _) if has(&["id", "number", "no", "account", "code"]) => Kind::Identifier,
+            _ => Kind::Text,
+        }
+    }
+
+    /// The value of an entity's attribute of this kind.
+    fn value(self, person: &Person, rng: &mut Rng) -> String {
+        match self {
+            Kind::FirstName => person.first.to_string(),
+            Kind::LastName => person.last.to_string(),
+            Kind::FullName => format!("{} {}", person.first, person.last),
+            Kind::Email => format!(
+                "{}.{}{}@{}",
+                person.first.to_lowercase(),
+                person.last.to_lowercase(),
+                person.number,
+                person.domain
+            ),
+            Kind::Phone => person.phone.clone(),
+            Kind::Date => {
+                let (year, month, day) = person.born;
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            }
+            Kind::Address => person.street.clone(),
+            Kind::City => person.city.to_string(),
+            Kind::Postcode => person.postcode.clone(),
+            Kind::Company => format!("{} {}", rng.pick(COMPANY_WORDS), rng.pick(COMPANY_SUFFIXES)),
+            Kind::Identifier => format!(
+                "{:03}-{:02}-{:04}",
+                rng.below(1000),
+                rng.below(100),
+                rng.below(10000)
+            ),
+            Kind::Numeric => rng.below(100_000).to_string(),
+            Kind::Tombstone => "false".to_string(),
+            Kind::Text => (0..8).map(|_| char::from(rng.pick(LETTERS))).collect(),
+        }
+    }
+
+    /// `value` written another way a source might write it.
+    fn variant(self, value: &str, rng: &mut Rng) -> String {
+        let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+        match self {
+            Kind::Phone if digits.len() == 10 => {
+                let (area, rest) = digits.split_at(3);
+                let (exchange, line) = rest.split_at(3);
+                match rng.below(4) {
+                    0 => format!("({}) {}-{}", area, exchange, line),
+                    1 => format!("{}.{}.{}", area, exchange, line),
+                    2 => format!("+1 {} {} {}", area, exchange, line),
+                    _ => digits,
+                }
+            }
+            Kind::Date if digits.len() == 8 => {
+                let (year, rest) = digits.split_at(4);
+                let (month, day) = rest.split_at(2);
+                match rng.below(2) {
+                    0 => format!("{}/{}/{}", month, day, year),
+                    _ => digits,
+                }
+            }
+            Kind::FirstName => match value.chars().next() {
+                Some(initial) if rng.chance(0.5) => format!("{}.", initial),
+                _ => value.to_uppercase(),
+            },
+            Kind::FullName => match value.split_once(' ') {
+                Some((first, last)) if rng.chance(0.5) => format!("{}, {}", last, first),
+                _ => value.to_uppercase(),
+            },
+            Kind::Address => {
+                let expanded = STREET_SUFFIXES.iter().find_map(|(short, long)| {
+                    let stem = value.strip_suffix(short)?;
+                    Some(format!("{}{}", stem, long))
+                });
+                expanded.unwrap_or_else(|| value.to_uppercase())
+            }
+            Kind::Postcode => format!("{}-{:04}", value, rng.below(10000)),
+            Kind::Company => match value.rsplit_once(' ') {
+                Some((name, _)) => name.to_string(),
+                None => value.to_uppercase(),
+            },
+            Kind::Identifier => match value.contains('-') {
+                true => value.replace('-', ""),
+                false => value.to_uppercase(),
+            },
+            Kind::Numeric => format!("{}.00", value),
+            Kind::Tombstone => value.to_string(),
+            _ => value.to_uppercase(),
+        }
+    }
+}
+
+/// The values an entity's attributes are made from.
+struct Person {
+    first: &'static str,
+    last: &'static str,
+    /// Sets the entity's email apart from others of the same name.
+    number: usize,
+    domain: &'static str,
+    phone: String,
+    born: (u32, u32, u32),
+    street: String,
+    city: &'static str,
+    postcode: String,
+}
+
+impl Person {
+    fn new(number: usize, rng: &mut Rng) -> Self {
+        let (suffix, _) = rng.pick(STREET_SUFFIXES);
+        Person {
+            first: rng.pick(FIRST_NAMES),
+            last: rng.pick(LAST_NAMES),
+            number,
+            domain: rng.pick(DOMAINS),
+            phone: format!(
+                "{}-{:03}-{:04}",
+                200 + rng.below(800),
+                rng.below(1000),
+                rng.below(10000)
+            ),
+            born: (
+                1940 + rng.below(66) as u32,
+                1 + rng.below(12) as u32,
+                1 + rng.below(28) as u32,
+            ),
+            street: format!("{} {} {}", 1 + rng.below(9999), rng.pick(STREETS), suffix),
+            city: rng.pick(CITIES),
+            postcode: format!("{:05}", rng.below(100_000)),
+        }
+    }
+}
+
+/// A seeded generator (SplitMix64): small, and the same on every platform.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Whether an event of probability `p` happens.
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{compiled_ir, AS_OF, EXECUTION};
+
+    #[test]
+    fn test_generated_data_follows_the_spec_and_labels_its_duplicates() {
+        use crate::{execute_plan, generate_data, GenerateOptions};
+
+        let ir = compiled_ir(EXECUTION);
+        let options = GenerateOptions {
+            rows: 200,
+            duplicate_rate: 0.25,
+            seed: 7,
+        };
+        let data = generate_data(&ir, &options).unwrap();
+        assert_eq!(
+            (data.entities, data.duplicates, data.labels.len()),
+            (150, 50, 200)
+        );
+        // The same seed makes the same records.
+        assert_eq!(generate_data(&ir, &options).unwrap().labels, data.labels);
+        assert_ne!(
+            generate_data(
+                &ir,
+                &GenerateOptions {
+                    seed: 8,
+                    ..options.clone()
+                }
+            )
+            .unwrap()
+            .labels,
+            data.labels
+        );
+
+        let (crm, billing) = (&data.sources[0].1, &data.sources[1].1);
+        assert_eq!(
+            crm.columns,
+            [
+                "contact_id",
+                "email_address",
+                "given_name",
+                "family_name",
+                "mobile"
+            ]
+        );
+        assert_eq!(
+            billing.columns,
+            ["customer_id", "deleted", "email", "name_last"]
+        );
+        assert_eq!(crm.len() + billing.len(), 200);
+        // Untyped attributes hold what their names say; records are live.
+        let first = &crm.rows[0];
+        assert!(first[1].contains('@') && first[1].starts_with(&first[2].to_lowercase()));
+        assert_eq!(
+            first[4].split('-').map(str::len).collect::<Vec<_>>(),
+            [3, 3, 4]
+        );
+        assert!(billing.rows.iter().all(|row| row[1] == "false"));
+
+        // Each entity's first record is clean, and a cluster is named by its lowest key.
+        let labels = &data.labels;
+        assert_eq!(
+            labels
+                .iter()
+                .filter(|l| l.record_key == l.cluster_id)
+                .count(),
+            150
+        );
+        assert!(labels.iter().all(|l| l.cluster_id <= l.record_key));
+        assert!(labels
+            .iter()
+            .any(|l| l.perturbations.iter().any(|p| p.starts_with("typo:"))));
+        assert!(data
+            .labels_csv()
+            .unwrap()
+            .starts_with("record_key,cluster_id,perturbations\n"));
+
+        // The records run through the interpreter as they are.
+        let result = execute_plan(&ir, &data.records_json(), AS_OF).unwrap();
+        assert_eq!((result.records, result.deleted), (200, 0));
+
+        assert!(generate_data(
+            &ir,
+            &GenerateOptions {
+                duplicate_rate: 1.0,
+                ..options
+            }
+        )
+        .is_err());
+    }
+}
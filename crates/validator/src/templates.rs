@@ -0,0 +1,186 @@
+//! Built-in blueprints for common entities, for `kanoniv init --template`.
+//!
+//! Each template is a complete, commented spec embedded in the binary, with
+//! `{{param|default}}` placeholders for the names a project picks: the
+//! `entity`, and the `source` table and its `system`. Rendering substitutes
+//! them, formats the result canonically and validates it, so a template
+//! cannot produce a spec that `kanoniv validate` rejects.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::format::format_spec;
+use crate::scaffold::check_name;
+use crate::validate_yaml;
+
+/// A built-in spec with placeholders.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Template {
+    pub name: &'static str,
+    pub description: &'static str,
+    #[serde(skip)]
+    pub text: &'static str,
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "person",
+        description: "People matched on email and phone, confirmed by name and date of birth",
+        text: include_str!("../templates/person.yaml"),
+    },
+    Template {
+        name: "organization",
+        description: "Companies matched on tax ID and domain, confirmed by name and address",
+        text: include_str!("../templates/organization.yaml"),
+    },
+    Template {
+        name: "household",
+        description: "People sharing an address and family name, grouped into households",
+        text: include_str!("../templates/household.yaml"),
+    },
+    Template {
+        name: "product",
+        description: "Catalog items matched on GTIN and part number, confirmed by brand and title",
+        text: include_str!("../templates/product.yaml"),
+    },
+    Template {
+        name: "location",
+        description: "Places matched on standardized address and coordinates",
+        text: include_str!("../templates/location.yaml"),
+    },
+];
+
+/// The template called `name`.
+pub fn lookup(name: &str) -> Result<&'static Template> {
+    TEMPLATES.iter().find(|t| t.name == name).with_context(|| {
+        let names: Vec<&str> = TEMPLATES.iter().map(|t| t.name).collect();
+        format!(
+            "Unknown template '{}'. Expected one of: {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+impl Template {
+    /// Each placeholder's name and default, in order of first use.
+    pub fn params(&self) -> Vec<(&'static str, &'static str)> {
+        let mut params: Vec<(&str, &str)> = Vec::new();
+        for (name, default) in placeholders(self.text) {
+            if !params.iter().any(|(seen, _)| *seen == name) {
+                params.push((name, default));
+            }
+        }
+        params
+    }
+
+    /// The spec with `values` substituted for their placeholders and the
+    /// defaults for the rest, formatted and validated.
+    pub fn render(&self, values: &[(&str, &str)]) -> Result<String> {
+        let params = self.params();
+        for (name, value) in values {
+            if !params.iter().any(|(param, _)| param == name) {
+                let known: Vec<&str> = params.iter().map(|(param, _)| *param).collect();
+                bail!(
+                    "Template '{}' has no parameter '{}'. Expected one of: {}",
+                    self.name,
+                    name,
+                    known.join(", ")
+                );
+            }
+            check_name(name, value)?;
+        }
+
+        let mut text = String::with_capacity(self.text.len());
+        let mut rest = self.text;
+        while let Some(start) = rest.find("{{") {
+            let end = start + rest[start..].find("}}").expect("placeholders are closed");
+            let (name, default) = split_placeholder(&rest[start + 2..end]);
+            let value = values
+                .iter()
+                .find(|(given, _)| *given == name)
+                .map_or(default, |(_, value)| *value);
+            text.push_str(&rest[..start]);
+            text.push_str(value);
+            rest = &rest[end + 2..];
+        }
+        text.push_str(rest);
+
+        let yaml = format_spec(&text)?;
+        let errors = validate_yaml(&yaml)?;
+        if !errors.is_empty() {
+            bail!(
+                "Template '{}' renders an invalid spec:\n  {}",
+                self.name,
+                errors.join("\n  ")
+            );
+        }
+        Ok(yaml)
+    }
+}
+
+fn placeholders(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.split("{{")
+        .skip(1)
+        .filter_map(|chunk| chunk.split_once("}}"))
+        .map(|(inner, _)| split_placeholder(inner))
+}
+
+fn split_placeholder(inner: &str) -> (&str, &str) {
+    inner.split_once('|').unwrap_or((inner, ""))
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[test]
+    fn test_templates_render_valid_specs() {
+        assert_eq!(
+            crate::templates::TEMPLATES
+                .iter()
+                .map(|t| t.name)
+                .collect::<Vec<_>>(),
+            ["person", "organization", "household", "product", "location"]
+        );
+        for template in crate::templates::TEMPLATES {
+            let yaml = template.render(&[]).unwrap();
+            assert!(
+                crate::validate_yaml(&yaml).unwrap().is_empty(),
+                "{}",
+                template.name
+            );
+            assert_eq!(crate::format_spec(&yaml).unwrap(), yaml);
+            assert_eq!(
+                template
+                    .params()
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>(),
+                ["entity", "source", "system"],
+                "{}",
+                template.name
+            );
+
+            let yaml = template
+                .render(&[
+                    ("entity", "customer"),
+                    ("source", "shop"),
+                    ("system", "shopify"),
+                ])
+                .unwrap();
+            let spec = crate::parse_yaml(&yaml).unwrap();
+            assert_eq!(spec["entity"]["name"], "customer");
+            assert_eq!(spec["sources"][0]["name"], "shop");
+            assert_eq!(spec["sources"][0]["system"], "shopify");
+            assert!(!yaml.contains("{{"));
+        }
+
+        let person = crate::templates::lookup("person").unwrap();
+        let err = person.render(&[("region", "eu")]).unwrap_err();
+        assert!(err.to_string().contains("no parameter 'region'"));
+        let err = person.render(&[("entity", "my person")]).unwrap_err();
+        assert!(err.to_string().contains("Invalid entity 'my person'"));
+        let err = crate::templates::lookup("vehicle").unwrap_err();
+        assert!(err.to_string().contains("Expected one of: person"));
+    }
+}
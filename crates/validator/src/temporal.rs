@@ -0,0 +1,242 @@
+//! Temporal identity resolution: records with validity intervals.
+//!
+//! The `temporal` section says when each record's values hold, and how
+//! slowly-changing attributes are resolved:
+//!
+//! ```yaml
+//! temporal:
+//!   effective_from: valid_from    # attribute: when the record became valid
+//!   effective_to: valid_to        # attribute: when it stopped; empty while current
+//!   match_window_days: 365        # compare records only if their intervals
+//!                                 # come within this many days
+//!   attributes:
+//!     address: history            # current (default) or history
+//! ```
+//!
+//! Dates are ISO 8601; only the date part counts. An interval is
+//! `[effective_from, effective_to)`, open at either end where the value is
+//! missing or not a date.
+//!
+//! Survivorship honors the intervals: for a `current` attribute, members
+//! whose interval is still open (no `effective_to`) survive before expired
+//! ones, as a type 1 slowly-changing dimension overwrites old values; for a
+//! `history` attribute every member counts alike. `most_recent` rules
+//! without a `timestamp` order by `effective_from`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How a slowly-changing attribute is resolved.
+pub const HISTORY_MODES: &[&str] = &["current", "history"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Temporal {
+    /// Attribute holding the start of each record's validity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_from: Option<String>,
+    /// Attribute holding the end of each record's validity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_to: Option<String>,
+    /// Largest gap, in days, between the intervals of records compared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_window_days: Option<u32>,
+    /// History mode by attribute; unlisted attributes are `current`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Temporal {
+    /// Whether every member's value of `attribute` counts alike, current or
+    /// not.
+    pub fn keeps_history(&self, attribute: &str) -> bool {
+        self.attributes.get(attribute).map(String::as_str) == Some("history")
+    }
+
+    /// Attributes kept with their history.
+    pub fn history_attributes(&self) -> Vec<&str> {
+        self.attributes
+            .iter()
+            .filter(|(_, mode)| *mode == "history")
+            .map(|(attribute, _)| attribute.as_str())
+            .collect()
+    }
+
+    /// Whether two records' intervals come close enough to compare them.
+    pub fn within_window(&self, a: Interval, b: Interval) -> bool {
+        self.match_window_days
+            .is_none_or(|window| a.gap_days(b) <= i64::from(window))
+    }
+}
+
+/// A validity interval in days since 1970-01-01; `None` ends are open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Interval {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl Interval {
+    pub fn parse(from: Option<&str>, to: Option<&str>) -> Self {
+        Interval {
+            from: from.and_then(day_number),
+            to: to.and_then(day_number),
+        }
+    }
+
+    /// Days between the end of one interval and the start of the other; 0
+    /// if they overlap.
+    pub fn gap_days(self, other: Interval) -> i64 {
+        let gap = |to: Option<i64>, from: Option<i64>| match (to, from) {
+            (Some(to), Some(from)) => from - to,
+            _ => 0,
+        };
+        gap(self.to, other.from)
+            .max(gap(other.to, self.from))
+            .max(0)
+    }
+}
+
+/// Days since 1970-01-01 of an ISO 8601 date or date-time, by its
+/// `YYYY-MM-DD` prefix.
+pub fn day_number(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let date = text.get(..10)?;
+    let mut parts = date.split('-');
+    let (Some(y), Some(m), Some(d), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    if y.len() != 4 || m.len() != 2 || d.len() != 2 {
+        return None;
+    }
+    let (y, m, d): (i64, i64, i64) = (y.parse().ok()?, m.parse().ok()?, d.parse().ok()?);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    // Days from civil (Howard Hinnant's algorithm).
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::AS_OF;
+
+    #[test]
+    fn test_temporal_intervals_bound_matching_and_survivorship() {
+        use crate::temporal::day_number;
+        use crate::{generate_plan, golden_records, learn_weights, Interval, Sample};
+
+        assert_eq!(day_number("1970-01-01"), Some(0));
+        assert_eq!(
+            day_number("2024-03-01T09:30:00Z")
+                .zip(day_number("2024-02-28"))
+                .map(|(a, b)| a - b),
+            Some(2)
+        );
+        assert_eq!(day_number("2024-13-01"), None);
+        let january = Interval::parse(Some("2024-01-01"), Some("2024-01-31"));
+        assert_eq!(
+            january.gap_days(Interval::parse(Some("2024-03-01"), None)),
+            30
+        );
+        assert_eq!(
+            january.gap_days(Interval::parse(None, Some("2024-01-15"))),
+            0
+        );
+
+        let spec = "api_version: kanoniv/v2\nidentity_version: retail_v1.0\nentity:\n  name: customer\n\
+        sources:\n  - name: crm\n    system: salesforce\n    table: contacts\n    id: contact_id\n    attributes:\n      \
+        email: email\n      address: address\n      valid_from: valid_from\n      valid_to: valid_to\n\
+        rules:\n  - name: email_exact\n    type: exact\n    field: email\n    weight: 1.0\n\
+        \x20 - name: address_exact\n    type: exact\n    field: address\n    weight: 0.5\n\
+        survivorship:\n  rules:\n    - field: address\n      strategy: most_recent\n\
+        temporal:\n  effective_from: valid_from\n  effective_to: valid_to\n  match_window_days: 30\n\
+        \x20 attributes:\n    address: history\n";
+        assert!(crate::validate_semantics(&crate::parse_yaml(spec).unwrap())
+            .unwrap()
+            .is_empty());
+
+        // crm:1 expired but started last: its longer email loses to the current
+        // one, while history keeps its newer address.
+        let members = Sample::from_csv(
+            "cluster_id,source_name,record_key,email,address,valid_from,valid_to\n\
+         e1,crm,crm:1,alexander@x.com,2 New St,2023-01-01,2024-01-01\n\
+         e1,crm,crm:2,al@x.com,1 Old St,2021-01-01,\n",
+        )
+        .unwrap();
+        let golden = golden_records(spec, &members, AS_OF).unwrap();
+        let fields: Vec<&str> = golden.fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(fields, ["address", "email", "valid_from", "valid_to"]);
+        assert_eq!(golden.records[0].values[0].as_deref(), Some("2 New St"));
+        assert_eq!(golden.records[0].values[1].as_deref(), Some("al@x.com"));
+
+        // Only records whose intervals come within 30 days are paired.
+        let data = Sample::from_csv(
+            "email,address,valid_from,valid_to\n\
+         a@x.com,1 Main St,2020-01-01,2020-06-01\n\
+         a@x.com,1 Main St,2020-06-15,2020-12-01\n\
+         b@x.com,9 High St,2024-01-01,2024-02-01\n\
+         c@x.com,9 High St,2024-02-10,\n",
+        )
+        .unwrap();
+        assert_eq!(learn_weights(spec, &data).unwrap().pairs, 2);
+
+        let plan = generate_plan(spec).unwrap();
+        let stages: Vec<&str> = plan
+            .execution_stages
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(
+            &stages[..4],
+            [
+                "Normalize sources",
+                "Resolve validity intervals",
+                "Generate blocking keys",
+                "Apply match window"
+            ]
+        );
+        assert!(plan
+            .execution_stages
+            .iter()
+            .enumerate()
+            .all(|(i, s)| s.stage == i + 1));
+        assert!(plan.risk_flags.iter().all(|f| f.code != "MISSING_TEMPORAL"));
+
+        let invalid = spec
+            .replace("address: history", "address: histry")
+            .replace("match_window_days: 30", "match_window_days: 1.5")
+            .replace("effective_to: valid_to", "effective_to: valid_until");
+        let found: Vec<(String, String)> =
+            crate::semantic_diagnostics(&crate::parse_yaml(&invalid).unwrap())
+                .into_iter()
+                .map(|d| (d.code, d.path.unwrap_or_default()))
+                .collect();
+        let expected = [
+            ("KNV0101", "temporal.effective_to"),
+            ("KNV0118", "temporal.match_window_days"),
+            ("KNV0118", "temporal.attributes.address"),
+        ];
+        assert_eq!(found, expected.map(|(c, p)| (c.to_string(), p.to_string())));
+
+        let missing = spec
+            .replace("  effective_from: valid_from\n", "")
+            .replace("match_window_days: 30", "match_window_days: -1");
+        let found: Vec<(String, String)> =
+            crate::schema_diagnostics(&crate::parse_yaml(&missing).unwrap())
+                .into_iter()
+                .map(|d| (d.code, d.path.unwrap_or_default()))
+                .collect();
+        let expected = [
+            ("KNV0001", "temporal.effective_from"),
+            ("KNV0004", "temporal.match_window_days"),
+        ];
+        assert_eq!(found, expected.map(|(c, p)| (c.to_string(), p.to_string())));
+    }
+}
@@ -0,0 +1,79 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{compile_to_ir, parse_yaml, Ir};
+
+pub(crate) const MINIMAL: &str = include_str!("../tests/fixtures/valid/minimal.yaml");
+pub(crate) const WORKSPACE: &str = include_str!("../tests/fixtures/valid/workspace.yaml");
+pub(crate) const RELATIONSHIPS: &str = include_str!("../tests/fixtures/valid/relationships.yaml");
+pub(crate) const TYPED: &str = include_str!("../tests/fixtures/valid/typed.yaml");
+pub(crate) const MAPPINGS: &str = include_str!("../tests/fixtures/valid/mappings.yaml");
+/// A spec and records per source for executing plans.
+pub(crate) const EXECUTION: &str = include_str!("../tests/fixtures/execution/customer.yaml");
+pub(crate) const EXECUTION_RECORDS: &str = include_str!("../tests/fixtures/execution/records.json");
+/// Protects MINIMAL's email, for tests that expect no PII advice.
+pub(crate) const PRIVACY: &str =
+    "privacy:\n  retention_days: 730\n  fields:\n    email:\n      masking: hash\n";
+/// The date runs expire retained values as of.
+pub(crate) const AS_OF: &str = "2026-06-01";
+
+/// `yaml` compiled to IR.
+pub(crate) fn compiled_ir(yaml: &str) -> Ir {
+    Ir::from_value(&compile_to_ir(&parse_yaml(yaml).unwrap()).unwrap()).unwrap()
+}
+
+/// Write text rows (the first one a header) as a Parquet file, with empty
+/// cells as nulls.
+#[cfg(feature = "parquet")]
+pub(crate) fn write_parquet(path: &std::path::Path, rows: &[[String; 4]]) {
+    use parquet::data_type::{ByteArray, ByteArrayType};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::sync::Arc;
+
+    let fields: Vec<String> = rows[0]
+        .iter()
+        .map(|c| format!("OPTIONAL BYTE_ARRAY {} (UTF8);", c))
+        .collect();
+    let schema = parse_message_type(&format!("message sample {{ {} }}", fields.join(" "))).unwrap();
+    let file = std::fs::File::create(path).unwrap();
+    let mut writer = SerializedFileWriter::new(
+        file,
+        Arc::new(schema),
+        Arc::new(WriterProperties::default()),
+    )
+    .unwrap();
+    let mut group = writer.next_row_group().unwrap();
+    for column in 0..4 {
+        let cells: Vec<&String> = rows[1..].iter().map(|r| &r[column]).collect();
+        let values: Vec<ByteArray> = cells
+            .iter()
+            .filter(|c| !c.is_empty())
+            .map(|c| ByteArray::from(c.as_str()))
+            .collect();
+        let levels: Vec<i16> = cells.iter().map(|c| !c.is_empty() as i16).collect();
+        let mut writer = group.next_column().unwrap().unwrap();
+        writer
+            .typed::<ByteArrayType>()
+            .write_batch(&values, Some(&levels), None)
+            .unwrap();
+        writer.close().unwrap();
+    }
+    group.close().unwrap();
+    writer.close().unwrap();
+}
+
+/// The execution fixture and its records with a quote in a source and a
+/// system name, and a space in a table and a column name.
+pub(crate) fn quoted_execution() -> (String, String) {
+    let spec = EXECUTION
+        .replace("- name: crm\n", "- name: crm'x\n")
+        .replace("system: salesforce", "system: sales'force")
+        .replace("table: contacts", "table: crm contacts")
+        .replace("email: email_address", "email: Email Address")
+        .replace("[billing, crm]", "[billing, \"crm'x\"]");
+    let records = EXECUTION_RECORDS
+        .replace("\"crm\"", "\"crm'x\"")
+        .replace("email_address", "Email Address");
+    (spec, records)
+}
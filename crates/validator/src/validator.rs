@@ -1,71 +1,164 @@
 use anyhow::Result;
 use serde_json::Value;
 
+use crate::attributes::{self, AttributeType};
+use crate::blocking;
+use crate::clustering::{self, ClusteringStrategy};
+use crate::deletion::Deletion;
+use crate::encoding::{self, Encoding, FieldEncoding};
+use crate::diagnostics::{codes, Diagnostic};
+use crate::embedding;
+use crate::execution::{self, ExecutionMode};
+use crate::expression::{Expression, Type};
+use crate::hashing::HashAlgorithm;
+use crate::lsh;
+use crate::mappings::{self, Mappings};
+use crate::normalize::{Locale, Normalizer, NORMALIZERS};
+use crate::phone::Region;
+use crate::policy::{self, Level};
+use crate::relationships::{self, Cardinality};
+use crate::rule_packs::RulePack;
+use crate::sample;
+use crate::privacy::{self, Privacy};
+use crate::similarity::{self, AlgorithmRegistry};
+use crate::stewardship::Stewardship;
+use crate::survivorship;
+use crate::temporal;
+use crate::workspace::Workspace;
+
+// Limits and required fields shared with the exported JSON Schema
+// (`crate::schema`), so the two cannot drift.
+pub(crate) const REQUIRED_TOP_LEVEL: [&str; 3] = ["api_version", "identity_version", "entity"];
+pub(crate) const REQUIRED_ENTITY: [&str; 1] = ["name"];
+pub(crate) const REQUIRED_RULE: [&str; 2] = ["name", "type"];
+pub(crate) const REQUIRED_SOURCE: [&str; 5] = ["name", "system", "table", "id", "attributes"];
+pub(crate) const UNIT_INTERVAL_RULE_FIELDS: [&str; 2] = ["weight", "threshold"];
+pub(crate) const REQUIRED_SORTED_NEIGHBORHOOD: [&str; 1] = ["sort_key"];
+pub(crate) const REQUIRED_CANOPY: [&str; 3] = ["field", "loose", "tight"];
+pub(crate) const UNIT_INTERVAL_CANOPY_FIELDS: [&str; 2] = ["loose", "tight"];
+pub(crate) const REQUIRED_HIERARCHICAL: [&str; 1] = ["threshold"];
+pub(crate) const REQUIRED_TEMPORAL: [&str; 1] = ["effective_from"];
+pub(crate) const REQUIRED_RELATIONSHIP: [&str; 3] = ["name", "to", "rules"];
+pub(crate) const REQUIRED_RELATIONSHIP_RULE: [&str; 3] = ["name", "type", "field"];
+pub(crate) const API_VERSION_PREFIX: &str = "kanoniv/v";
+pub(crate) const MAX_RULES: usize = 50;
+pub(crate) const MAX_SOURCES: usize = 10;
+pub(crate) const MAX_BLOCKING_KEYS: usize = 5;
+
 /// Validate against JSON Schema
 pub fn validate_schema(spec: &Value) -> Result<Vec<String>> {
-    let mut errors = Vec::new();
+    Ok(schema_diagnostics(spec)
+        .iter()
+        .map(ToString::to_string)
+        .collect())
+}
 
-    // Check required top-level fields
-    if spec.get("api_version").is_none() {
-        errors.push("Missing required field: api_version".to_string());
-    }
+/// Validate semantic/business rules. Only errors are returned; see
+/// `semantic_diagnostics` for warnings and info.
+pub fn validate_semantics(spec: &Value) -> Result<Vec<String>> {
+    Ok(semantic_diagnostics(spec)
+        .iter()
+        .filter(|d| d.is_error())
+        .map(ToString::to_string)
+        .collect())
+}
 
-    if spec.get("identity_version").is_none() {
-        errors.push("Missing required field: identity_version".to_string());
+/// `validate_semantics`, with the errors of a rule pack's checks.
+pub fn validate_semantics_with_rules(spec: &Value, rules: &RulePack) -> Result<Vec<String>> {
+    let mut diagnostics = semantic_diagnostics(spec);
+    diagnostics.extend(rules.diagnostics(spec));
+    Ok(diagnostics
+        .iter()
+        .filter(|d| d.is_error())
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// Schema checks as structured diagnostics (without spans; see
+/// `Diagnostic::locate`).
+pub fn schema_diagnostics(spec: &Value) -> Vec<Diagnostic> {
+    if let Some(workspace) = Workspace::from_value(spec) {
+        if workspace.entities.is_empty() {
+            return vec![Diagnostic::error(
+                codes::MISSING_FIELD,
+                "entities",
+                "The workspace has shared definitions but no entity spec",
+            )];
+        }
+        return per_entity(&workspace, schema_diagnostics);
     }
+    let mut errors = Vec::new();
 
-    if spec.get("entity").is_none() {
-        errors.push("Missing required field: entity".to_string());
+    // Check required top-level fields; a spec extending a base inherits them
+    let extends = spec.get("extends").is_some();
+    for field in REQUIRED_TOP_LEVEL {
+        if !extends && spec.get(field).is_none() {
+            errors.push(Diagnostic::error(
+                codes::MISSING_FIELD,
+                field,
+                format!("Missing required field: {}", field),
+            ));
+        }
     }
 
     // Validate api_version format
     if let Some(api_version) = spec.get("api_version").and_then(|v| v.as_str()) {
-        if !api_version.starts_with("kanoniv/v") {
-            errors.push(format!(
-                "Invalid api_version format: '{}'. Expected 'kanoniv/v<N>'",
-                api_version
+        if !api_version.starts_with(API_VERSION_PREFIX) {
+            errors.push(Diagnostic::error(
+                codes::INVALID_API_VERSION,
+                "api_version",
+                format!(
+                    "Invalid api_version format: '{}'. Expected 'kanoniv/v<N>'",
+                    api_version
+                ),
             ));
         }
     }
 
     // Validate entity structure
     if let Some(entity) = spec.get("entity") {
-        if entity.get("name").is_none() {
-            errors.push("entity.name is required".to_string());
+        for field in REQUIRED_ENTITY {
+            if entity.get(field).is_none() {
+                errors.push(Diagnostic::error(
+                    codes::MISSING_FIELD,
+                    format!("entity.{}", field),
+                    format!("entity.{} is required", field),
+                ));
+            }
         }
     }
 
     // Validate rules
     if let Some(rules) = spec.get("rules").and_then(|r| r.as_array()) {
-        if rules.len() > 50 {
-            errors.push(format!("Too many rules: {}. Maximum is 50.", rules.len()));
+        if rules.len() > MAX_RULES {
+            errors.push(Diagnostic::error(
+                codes::TOO_MANY_RULES,
+                "rules",
+                format!("Too many rules: {}. Maximum is {}.", rules.len(), MAX_RULES),
+            ));
         }
 
         for (i, rule) in rules.iter().enumerate() {
-            if rule.get("name").is_none() {
-                errors.push(format!("rules[{}]: missing required field 'name'", i));
-            }
-            if rule.get("type").is_none() {
-                errors.push(format!("rules[{}]: missing required field 'type'", i));
-            }
-
-            // Validate weight bounds
-            if let Some(weight) = rule.get("weight").and_then(|w| w.as_f64()) {
-                if !(0.0..=1.0).contains(&weight) {
-                    errors.push(format!(
-                        "rules[{}]: weight {} must be between 0 and 1",
-                        i, weight
+            for field in REQUIRED_RULE {
+                if rule.get(field).is_none() {
+                    errors.push(Diagnostic::error(
+                        codes::MISSING_FIELD,
+                        format!("rules[{}].{}", i, field),
+                        format!("rules[{}]: missing required field '{}'", i, field),
                     ));
                 }
             }
 
-            // Validate threshold bounds
-            if let Some(threshold) = rule.get("threshold").and_then(|t| t.as_f64()) {
-                if !(0.0..=1.0).contains(&threshold) {
-                    errors.push(format!(
-                        "rules[{}]: threshold {} must be between 0 and 1",
-                        i, threshold
-                    ));
+            // Validate weight and threshold bounds
+            for field in UNIT_INTERVAL_RULE_FIELDS {
+                if let Some(value) = rule.get(field).and_then(|w| w.as_f64()) {
+                    if !(0.0..=1.0).contains(&value) {
+                        errors.push(Diagnostic::error(
+                            codes::OUT_OF_RANGE,
+                            format!("rules[{}].{}", i, field),
+                            format!("rules[{}]: {} {} must be between 0 and 1", i, field, value),
+                        ));
+                    }
                 }
             }
         }
@@ -73,42 +166,483 @@ pub fn validate_schema(spec: &Value) -> Result<Vec<String>> {
 
     // Validate sources
     if let Some(sources) = spec.get("sources").and_then(|s| s.as_array()) {
-        if sources.len() > 10 {
-            errors.push(format!(
-                "Too many sources: {}. Maximum is 10.",
-                sources.len()
+        if sources.len() > MAX_SOURCES {
+            errors.push(Diagnostic::error(
+                codes::TOO_MANY_SOURCES,
+                "sources",
+                format!(
+                    "Too many sources: {}. Maximum is {}.",
+                    sources.len(),
+                    MAX_SOURCES
+                ),
             ));
         }
 
         for (i, source) in sources.iter().enumerate() {
-            for field in &["name", "system", "table", "id", "attributes"] {
+            for field in &REQUIRED_SOURCE {
                 if source.get(*field).is_none() {
-                    errors.push(format!(
-                        "sources[{}]: missing required field '{}'",
-                        i, field
+                    errors.push(Diagnostic::error(
+                        codes::MISSING_FIELD,
+                        format!("sources[{}].{}", i, field),
+                        format!("sources[{}]: missing required field '{}'", i, field),
+                    ));
+                }
+            }
+            if let Some(rows) = source.get("rows").filter(|r| !r.is_null()) {
+                if !rows.as_f64().is_some_and(|r| r >= 0.0) {
+                    errors.push(Diagnostic::error(
+                        codes::OUT_OF_RANGE,
+                        format!("sources[{}].rows", i),
+                        format!("sources[{}]: rows must be a non-negative number, got {}", i, rows),
                     ));
                 }
             }
+            match source.get("attributes") {
+                Some(Value::Object(attributes)) => {
+                    attribute_diagnostics(i, attributes, &mut errors)
+                }
+                Some(Value::Array(columns)) => {
+                    for (j, column) in columns.iter().enumerate() {
+                        if !column.is_string() {
+                            errors.push(Diagnostic::error(
+                                codes::INVALID_ATTRIBUTE,
+                                format!("sources[{}].attributes[{}]", i, j),
+                                format!(
+                                    "A listed attribute must be a column name, got {}.",
+                                    column
+                                ),
+                            ));
+                        }
+                    }
+                }
+                _ => {}
+            }
         }
     }
 
+    // Validate column mappings
+    mapping_diagnostics(spec, &mut errors);
+
+    // Validate the severity policy
+    policy_diagnostics(spec, &mut errors);
+
     // Validate blocking keys
     if let Some(blocking) = spec.get("blocking") {
         if let Some(keys) = blocking.get("keys").and_then(|k| k.as_array()) {
-            if keys.len() > 5 {
-                errors.push(format!(
-                    "Too many blocking keys: {}. Maximum is 5.",
-                    keys.len()
+            if keys.len() > MAX_BLOCKING_KEYS {
+                errors.push(Diagnostic::error(
+                    codes::TOO_MANY_BLOCKING_KEYS,
+                    "blocking.keys",
+                    format!(
+                        "Too many blocking keys: {}. Maximum is {}.",
+                        keys.len(),
+                        MAX_BLOCKING_KEYS
+                    ),
+                ));
+            }
+        }
+        strategy_schema_diagnostics(blocking, &mut errors);
+    }
+
+    // Validate normalization
+    if let Some(normalization) = spec.get("normalization").filter(|n| !n.is_null()) {
+        normalization_diagnostics(normalization, &mut errors);
+    }
+
+    // Validate the clustering cut
+    if let Some(clustering) = spec.get("clustering").filter(|c| !c.is_null()) {
+        if clustering.get("strategy").and_then(|s| s.as_str()) == Some("hierarchical") {
+            for field in REQUIRED_HIERARCHICAL {
+                if clustering.get(field).is_none() {
+                    errors.push(Diagnostic::error(
+                        codes::MISSING_FIELD,
+                        format!("clustering.{}", field),
+                        format!("clustering.{} is required by the hierarchical strategy", field),
+                    ));
+                }
+            }
+        }
+        if let Some(threshold) = clustering.get("threshold").and_then(|t| t.as_f64()) {
+            if !(0.0..=1.0).contains(&threshold) {
+                errors.push(Diagnostic::error(
+                    codes::OUT_OF_RANGE,
+                    "clustering.threshold",
+                    format!("clustering.threshold {} must be between 0 and 1", threshold),
                 ));
             }
         }
     }
 
-    Ok(errors)
+    // Validate the temporal section
+    if let Some(temporal) = spec.get("temporal").filter(|t| !t.is_null()) {
+        for field in REQUIRED_TEMPORAL {
+            if temporal.get(field).is_none() {
+                errors.push(Diagnostic::error(
+                    codes::MISSING_FIELD,
+                    format!("temporal.{}", field),
+                    format!("temporal.{} is required by the temporal section", field),
+                ));
+            }
+        }
+        if let Some(days) = temporal.get("match_window_days").and_then(|d| d.as_f64()) {
+            if days < 0.0 {
+                errors.push(Diagnostic::error(
+                    codes::OUT_OF_RANGE,
+                    "temporal.match_window_days",
+                    format!("temporal.match_window_days {} must not be negative", days),
+                ));
+            }
+        }
+    }
+
+    // Validate relationships
+    if let Some(relationships) = spec.get("relationships").and_then(|r| r.as_array()) {
+        for (i, relationship) in relationships.iter().enumerate() {
+            for field in REQUIRED_RELATIONSHIP {
+                if relationship.get(field).is_none() {
+                    errors.push(Diagnostic::error(
+                        codes::MISSING_FIELD,
+                        format!("relationships[{}].{}", i, field),
+                        format!("relationships[{}]: missing required field '{}'", i, field),
+                    ));
+                }
+            }
+            if let Some(threshold) = relationship.get("threshold").and_then(|t| t.as_f64()) {
+                if !(0.0..=1.0).contains(&threshold) {
+                    errors.push(Diagnostic::error(
+                        codes::OUT_OF_RANGE,
+                        format!("relationships[{}].threshold", i),
+                        format!("relationships[{}]: threshold {} must be between 0 and 1", i, threshold),
+                    ));
+                }
+            }
+            let rules = relationship.get("rules").and_then(|r| r.as_array());
+            for (j, rule) in rules.into_iter().flatten().enumerate() {
+                let path = format!("relationships[{}].rules[{}]", i, j);
+                for field in REQUIRED_RELATIONSHIP_RULE {
+                    if rule.get(field).is_none() {
+                        errors.push(Diagnostic::error(
+                            codes::MISSING_FIELD,
+                            format!("{}.{}", path, field),
+                            format!("{}: missing required field '{}'", path, field),
+                        ));
+                    }
+                }
+                for field in UNIT_INTERVAL_RULE_FIELDS {
+                    if let Some(value) = rule.get(field).and_then(|w| w.as_f64()) {
+                        if !(0.0..=1.0).contains(&value) {
+                            errors.push(Diagnostic::error(
+                                codes::OUT_OF_RANGE,
+                                format!("{}.{}", path, field),
+                                format!("{}: {} {} must be between 0 and 1", path, field, value),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    errors
 }
 
-/// Validate semantic/business rules
-pub fn validate_semantics(spec: &Value) -> Result<Vec<String>> {
+/// Required parameters and bounds of the blocking strategies that take
+/// them (see `crate::blocking`).
+fn strategy_schema_diagnostics(blocking: &Value, errors: &mut Vec<Diagnostic>) {
+    let strategy = blocking.get("strategy").and_then(|s| s.as_str());
+    let (name, required): (&str, &[&str]) = match strategy {
+        Some("sorted_neighborhood") => ("sorted_neighborhood", &REQUIRED_SORTED_NEIGHBORHOOD),
+        Some("canopy") => ("canopy", &REQUIRED_CANOPY),
+        _ => return,
+    };
+    let section = blocking.get(name).unwrap_or(&Value::Null);
+    for field in required {
+        if section.get(field).is_none() {
+            errors.push(Diagnostic::error(
+                codes::MISSING_FIELD,
+                format!("blocking.{}.{}", name, field),
+                format!("blocking.{}.{} is required by the {} strategy", name, field, name),
+            ));
+        }
+    }
+    if let Some(window) = section.get("window") {
+        let size = window.as_u64().unwrap_or(0) as usize;
+        if !(blocking::MIN_WINDOW..=blocking::MAX_WINDOW).contains(&size) {
+            errors.push(Diagnostic::error(
+                codes::OUT_OF_RANGE,
+                "blocking.sorted_neighborhood.window",
+                format!(
+                    "blocking.sorted_neighborhood.window {} must be a whole number from {} to {}",
+                    window,
+                    blocking::MIN_WINDOW,
+                    blocking::MAX_WINDOW
+                ),
+            ));
+        }
+    }
+    if name == "canopy" {
+        for field in UNIT_INTERVAL_CANOPY_FIELDS {
+            if let Some(value) = section.get(field).and_then(|v| v.as_f64()) {
+                if !(0.0..=1.0).contains(&value) {
+                    errors.push(Diagnostic::error(
+                        codes::OUT_OF_RANGE,
+                        format!("blocking.canopy.{}", field),
+                        format!("blocking.canopy.{} {} must be between 0 and 1", field, value),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn normalization_diagnostics(normalization: &Value, errors: &mut Vec<Diagnostic>) {
+    let invalid = |path: String, message: String| {
+        Diagnostic::error(codes::INVALID_NORMALIZATION, path, message)
+    };
+    if !normalization.is_object() {
+        errors.push(invalid(
+            "normalization".to_string(),
+            "normalization must be a mapping with `locale` and `fields`".to_string(),
+        ));
+        return;
+    }
+    if let Some(locale) = normalization.get("locale") {
+        if let Err(e) = locale.as_str().unwrap_or_default().parse::<Locale>() {
+            errors.push(invalid("normalization.locale".to_string(), e.to_string()));
+        }
+    }
+    if let Some(region) = normalization.get("default_region") {
+        if let Err(e) = region.as_str().unwrap_or_default().parse::<Region>() {
+            errors.push(invalid("normalization.default_region".to_string(), e.to_string()));
+        }
+    }
+    let Some(fields) = normalization.get("fields") else {
+        return;
+    };
+    let Some(fields) = fields.as_object() else {
+        errors.push(invalid(
+            "normalization.fields".to_string(),
+            "normalization.fields must map attributes to lists of normalizers".to_string(),
+        ));
+        return;
+    };
+    for (field, steps) in fields {
+        let Some(steps) = steps.as_array() else {
+            errors.push(invalid(
+                format!("normalization.fields.{}", field),
+                format!("normalization.fields.{} must be a list of normalizers", field),
+            ));
+            continue;
+        };
+        for (i, step) in steps.iter().enumerate() {
+            let Err(e) = Normalizer::from_value(step) else {
+                continue;
+            };
+            let path = format!("normalization.fields.{}[{}]", field, i);
+            let name = step.as_str().unwrap_or_default();
+            if step.is_object() || name == "regex_replace" {
+                errors.push(invalid(path, format!("Field '{}': {}.", field, e)));
+            } else {
+                errors.push(
+                    invalid(
+                        path,
+                        format!("Unknown normalizer '{}' for field '{}'.", name, field),
+                    )
+                    .with_suggestion(format!("Use one of: {}.", NORMALIZERS.join(", "))),
+                );
+            }
+        }
+    }
+}
+
+/// Problems with the `mappings` section, and sources holding one attribute
+/// in several columns once mappings are applied.
+fn mapping_diagnostics(spec: &Value, errors: &mut Vec<Diagnostic>) {
+    let invalid =
+        |path: String, message: String| Diagnostic::error(codes::INVALID_MAPPING, path, message);
+    let mut claimed: Vec<(String, &str)> = Vec::new();
+    match spec.get("mappings") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(entries)) => {
+            for (canonical, columns) in entries {
+                let Some(columns) = columns.as_array() else {
+                    errors.push(invalid(
+                        format!("mappings.{}", canonical),
+                        format!("mappings.{} must be a list of columns.", canonical),
+                    ));
+                    continue;
+                };
+                claimed.push((canonical.to_lowercase(), canonical));
+                for (j, column) in columns.iter().enumerate() {
+                    let path = format!("mappings.{}[{}]", canonical, j);
+                    let Some(column) = column.as_str() else {
+                        errors.push(invalid(
+                            path,
+                            format!(
+                                "mappings.{} must be a list of columns, got {}.",
+                                canonical, column
+                            ),
+                        ));
+                        continue;
+                    };
+                    match claimed.iter().find(|(c, _)| *c == column.to_lowercase()) {
+                        Some((_, other)) if other != canonical => errors.push(
+                            invalid(
+                                path,
+                                format!(
+                                    "Column '{}' is mapped to both '{}' and '{}'.",
+                                    column, other, canonical
+                                ),
+                            )
+                            .with_suggestion("Map each column to one attribute."),
+                        ),
+                        Some(_) => {}
+                        None => claimed.push((column.to_lowercase(), canonical)),
+                    }
+                }
+            }
+        }
+        Some(other) => errors.push(invalid(
+            "mappings".to_string(),
+            format!(
+                "mappings must map attributes to lists of columns, got {}.",
+                other
+            ),
+        )),
+    }
+
+    let mappings = Mappings::from_spec(spec).ok().flatten().unwrap_or_default();
+    let sources = spec.get("sources").and_then(|s| s.as_array());
+    for (i, source) in sources.into_iter().flatten().enumerate() {
+        let name = source.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+        let columns: Vec<(String, &str)> = match source.get("attributes") {
+            Some(Value::Array(columns)) => columns
+                .iter()
+                .enumerate()
+                .filter_map(|(j, c)| Some((format!("[{}]", j), c.as_str()?)))
+                .collect(),
+            Some(Value::Object(mapped)) => mapped
+                .keys()
+                .map(|key| (format!(".{}", key), key.as_str()))
+                .collect(),
+            _ => continue,
+        };
+        let mut seen: Vec<(&str, &str)> = Vec::new();
+        for (suffix, column) in columns {
+            let canonical = mappings.canonical(column);
+            match seen.iter().find(|(c, _)| *c == canonical) {
+                Some((_, first)) => errors.push(
+                    invalid(
+                        format!("sources[{}].attributes{}", i, suffix),
+                        format!(
+                            "Source '{}' holds attribute '{}' in both '{}' and '{}'.",
+                            name, canonical, first, column
+                        ),
+                    )
+                    .with_suggestion("Keep the column the source's values are best in."),
+                ),
+                None => seen.push((canonical, column)),
+            }
+        }
+    }
+}
+
+/// Problems with the `policy` section: each code maps to a level.
+fn policy_diagnostics(spec: &Value, errors: &mut Vec<Diagnostic>) {
+    match spec.get("policy") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(entries)) => {
+            for (code, level) in entries {
+                if level.as_str().is_some_and(|l| l.parse::<Level>().is_ok()) {
+                    continue;
+                }
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_POLICY,
+                        format!("policy.{}", code),
+                        format!("Unknown policy level {} for {}.", level, code),
+                    )
+                    .with_suggestion(format!("Use one of: {}.", policy::LEVELS.join(", "))),
+                );
+            }
+        }
+        Some(other) => errors.push(Diagnostic::error(
+            codes::INVALID_POLICY,
+            "policy",
+            format!("policy must map finding codes to levels, got {}.", other),
+        )),
+    }
+}
+
+/// Problems with a source's attribute mappings: each maps to a column name,
+/// or to a `column` and a known `type`.
+fn attribute_diagnostics(
+    i: usize,
+    attributes: &serde_json::Map<String, Value>,
+    errors: &mut Vec<Diagnostic>,
+) {
+    for (attribute, mapping) in attributes {
+        let path = format!("sources[{}].attributes.{}", i, attribute);
+        let Value::Object(mapping) = mapping else {
+            if !mapping.is_string() {
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_ATTRIBUTE,
+                        path,
+                        format!(
+                            "Attribute '{}' must map to a column name or a column and type, got {}.",
+                            attribute, mapping
+                        ),
+                    )
+                    .with_suggestion("Use `column: <name>` and an optional `type:`."),
+                );
+            }
+            continue;
+        };
+        if !mapping.get("column").is_some_and(Value::is_string) {
+            errors.push(Diagnostic::error(
+                codes::INVALID_ATTRIBUTE,
+                format!("{}.column", path),
+                format!("Attribute '{}' names no column to read.", attribute),
+            ));
+        }
+        if let Some(ty) = mapping.get("type") {
+            let name = ty.as_str().unwrap_or_default();
+            if name.parse::<AttributeType>().is_err() {
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_ATTRIBUTE,
+                        format!("{}.type", path),
+                        format!("Unknown type '{}' for attribute '{}'.", name, attribute),
+                    )
+                    .with_suggestion(format!("Use one of: {}.", attributes::TYPES.join(", "))),
+                );
+            }
+        }
+    }
+}
+
+/// Semantic checks as structured diagnostics, including advice (warnings
+/// and info).
+pub fn semantic_diagnostics(spec: &Value) -> Vec<Diagnostic> {
+    semantic_diagnostics_with(spec, &AlgorithmRegistry::builtin())
+}
+
+/// `semantic_diagnostics`, accepting the similarity algorithms in
+/// `algorithms` (for engines that register their own).
+pub fn semantic_diagnostics_with(spec: &Value, algorithms: &AlgorithmRegistry) -> Vec<Diagnostic> {
+    if let Some(workspace) = Workspace::from_value(spec) {
+        let mut errors = per_entity(&workspace, |entity| {
+            semantic_diagnostics_with(entity, algorithms)
+        });
+        errors.extend(workspace_diagnostics(&workspace));
+        return errors;
+    }
+    // Rules, keys and survivorship name canonical attributes; check them
+    // against the sources' attributes in canonical space.
+    let mappings = Mappings::from_spec(spec).ok().flatten().unwrap_or_default();
+    let resolved = mappings::resolve(spec);
+    let spec = &resolved;
     let mut errors = Vec::new();
 
     // Collect all field names from sources
@@ -127,7 +661,7 @@ pub fn validate_semantics(spec: &Value) -> Result<Vec<String>> {
 
     // Validate rule field references
     if let Some(rules) = spec.get("rules").and_then(|r| r.as_array()) {
-        for rule in rules {
+        for (i, rule) in rules.iter().enumerate() {
             if let Some(field) = rule.get("field").and_then(|f| f.as_str()) {
                 if !available_fields.is_empty() && !available_fields.contains(&field.to_string()) {
                     let rule_name = rule
@@ -135,45 +669,382 @@ pub fn validate_semantics(spec: &Value) -> Result<Vec<String>> {
                         .and_then(|n| n.as_str())
                         .unwrap_or("unknown");
 
+                    let mut diagnostic = Diagnostic::error(
+                        codes::UNKNOWN_FIELD,
+                        format!("rules[{}].field", i),
+                        format!("Rule '{}' references unknown field '{}'.", rule_name, field),
+                    );
+
                     // Suggest similar field names
-                    let suggestion = available_fields
-                        .iter()
-                        .find(|f| f.contains(field) || field.contains(f.as_str()))
-                        .map(|f| format!(" Did you mean '{}'?", f))
-                        .unwrap_or_default();
-
-                    errors.push(format!(
-                        "Rule '{}' references unknown field '{}'.{}",
-                        rule_name, field, suggestion
-                    ));
+                    if let Some(suggestion) = field_suggestion(field, &available_fields, &mappings)
+                    {
+                        diagnostic = diagnostic.with_suggestion(suggestion);
+                    }
+                    errors.push(diagnostic);
                 }
+            } else if rule.get("type").and_then(|t| t.as_str()).is_some() {
+                let rule_name = rule
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or("unknown");
+                errors.push(
+                    Diagnostic::error(
+                        codes::MISSING_FIELD,
+                        format!("rules[{}].field", i),
+                        format!("Rule '{}' names no field to compare.", rule_name),
+                    )
+                    .with_suggestion("Add `field:` with the attribute the rule compares."),
+                );
+            }
+        }
+    }
+
+    // Validate blocking key references
+    for (path, field) in blocking_key_fields(spec) {
+        if !available_fields.is_empty() && !available_fields.contains(&field.to_string()) {
+            let mut diagnostic = Diagnostic::error(
+                codes::UNKNOWN_FIELD,
+                path,
+                format!("Blocking key references unknown field '{}'.", field),
+            );
+            if let Some(suggestion) = field_suggestion(field, &available_fields, &mappings) {
+                diagnostic = diagnostic.with_suggestion(suggestion);
+            }
+            errors.push(diagnostic);
+        }
+    }
+
+    // Validate similarity algorithm names
+    if let Some(rules) = spec.get("rules").and_then(|r| r.as_array()) {
+        for (i, rule) in rules.iter().enumerate() {
+            let Some(algorithm) = rule.get("algorithm").and_then(|a| a.as_str()) else {
+                continue;
+            };
+            if algorithms.contains(algorithm) {
+                continue;
             }
+            let rule_name = rule
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or("unknown");
+            let mut diagnostic = Diagnostic::error(
+                codes::UNKNOWN_ALGORITHM,
+                format!("rules[{}].algorithm", i),
+                format!(
+                    "Rule '{}' uses unknown algorithm '{}'.",
+                    rule_name, algorithm
+                ),
+            );
+            if let Some(similar) = algorithms.closest(algorithm) {
+                diagnostic = diagnostic.with_suggestion(format!("Did you mean '{}'?", similar));
+            }
+            errors.push(diagnostic);
         }
     }
 
-    // Check for duplicate rule names
+    // Validate blocking transform names
+    for (path, transform) in blocking_transforms(spec) {
+        if sample::is_transform(transform) {
+            continue;
+        }
+        errors.push(
+            Diagnostic::error(
+                codes::UNKNOWN_TRANSFORM,
+                path,
+                format!("Unknown blocking transform '{}'.", transform),
+            )
+            .with_suggestion(format!(
+                "Use {}, or {}<n>.",
+                sample::TRANSFORMS.join(", "),
+                sample::TRANSFORM_PREFIXES.join("<n>, ")
+            )),
+        );
+    }
+
+    // Type-check match rule conditions
     if let Some(rules) = spec.get("rules").and_then(|r| r.as_array()) {
-        let mut seen_names: Vec<&str> = Vec::new();
-        for rule in rules {
-            if let Some(name) = rule.get("name").and_then(|n| n.as_str()) {
-                if seen_names.contains(&name) {
-                    errors.push(format!("Duplicate rule name: '{}'", name));
-                } else {
-                    seen_names.push(name);
+        let names = |name: &str| {
+            name.split_once('.').is_some_and(|(side, attribute)| {
+                matches!(side, "left" | "right")
+                    && (available_fields.is_empty()
+                        || available_fields.iter().any(|f| f == attribute))
+            })
+        };
+        let hint = "Conditions read left.<attribute> and right.<attribute>.";
+        for (i, rule) in rules.iter().enumerate() {
+            if let Some(condition) = rule.get("condition") {
+                let path = format!("rules[{}].condition", i);
+                errors.extend(expression_diagnostics(path, condition, &names, hint, true));
+            }
+        }
+    }
+
+    // Validate semantic rules' embedding configuration
+    if let Some(rules) = spec.get("rules").and_then(|r| r.as_array()) {
+        for (i, rule) in rules.iter().enumerate() {
+            if rule.get("type").and_then(|t| t.as_str()) == Some("semantic") {
+                errors.extend(semantic_rule_diagnostics(i, rule));
+            }
+        }
+    }
+
+    // Check rules and normalizers against attribute types
+    errors.extend(type_diagnostics(spec));
+
+    // Validate LSH blocking parameters
+    if let Some(blocking) = spec.get("blocking") {
+        let strategy = blocking.get("strategy").and_then(|s| s.as_str());
+        let section = blocking.get("lsh").filter(|l| !l.is_null());
+        if strategy == Some("lsh") {
+            for (key, message) in section.map(lsh::check).unwrap_or_default() {
+                errors.push(Diagnostic::error(
+                    codes::INVALID_LSH,
+                    format!("blocking.lsh.{}", key),
+                    format!("Invalid LSH {}: {}.", key, message),
+                ));
+            }
+            let keys = blocking.get("keys").and_then(|k| k.as_array());
+            if keys.is_none_or(|k| k.is_empty()) {
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_LSH,
+                        "blocking.keys",
+                        "LSH blocking has no keys to hash.",
+                    )
+                    .with_suggestion("Add the fields to hash under blocking.keys."),
+                );
+            }
+        } else if section.is_some() {
+            errors.push(
+                Diagnostic::error(
+                    codes::INVALID_LSH,
+                    "blocking.lsh",
+                    format!(
+                        "blocking.lsh is only used by the lsh strategy, not '{}'.",
+                        strategy.unwrap_or("none")
+                    ),
+                )
+                .with_suggestion("Set `strategy: lsh` or remove blocking.lsh."),
+            );
+        }
+    }
+
+    // Validate sorted-neighbourhood and canopy references
+    if let Some(blocking) = spec.get("blocking") {
+        let strategy = blocking.get("strategy").and_then(|s| s.as_str());
+        let field = match strategy {
+            Some("sorted_neighborhood") => Some(("sort_key", "sorted_neighborhood")),
+            Some("canopy") => Some(("field", "canopy")),
+            _ => None,
+        };
+        if let Some((key, name)) = field {
+            let section = blocking.get(name).unwrap_or(&Value::Null);
+            if let Some(field) = section.get(key).and_then(|f| f.as_str()) {
+                if !available_fields.is_empty() && !available_fields.contains(&field.to_string()) {
+                    errors.push(Diagnostic::error(
+                        codes::UNKNOWN_FIELD,
+                        format!("blocking.{}.{}", name, key),
+                        format!("Blocking strategy {} references unknown field '{}'.", name, field),
+                    ));
+                }
+            }
+        }
+        if strategy == Some("canopy") {
+            let canopy = blocking.get("canopy").unwrap_or(&Value::Null);
+            if let Some(algorithm) = canopy.get("algorithm").and_then(|a| a.as_str()) {
+                if !algorithms.contains(algorithm) {
+                    let mut diagnostic = Diagnostic::error(
+                        codes::UNKNOWN_ALGORITHM,
+                        "blocking.canopy.algorithm",
+                        format!("Canopy blocking uses unknown algorithm '{}'.", algorithm),
+                    );
+                    if let Some(similar) = algorithms.closest(algorithm) {
+                        diagnostic =
+                            diagnostic.with_suggestion(format!("Did you mean '{}'?", similar));
+                    }
+                    errors.push(diagnostic);
+                }
+            }
+            let threshold = |t: &str| canopy.get(t).and_then(|v| v.as_f64());
+            if let (Some(loose), Some(tight)) = (threshold("loose"), threshold("tight")) {
+                if loose > tight {
+                    errors.push(
+                        Diagnostic::error(
+                            codes::THRESHOLD_ORDER,
+                            "blocking.canopy",
+                            format!(
+                                "Canopy loose threshold ({}) is above its tight threshold ({}).",
+                                loose, tight
+                            ),
+                        )
+                        .with_suggestion("Canopies need loose <= tight."),
+                    );
                 }
             }
         }
     }
 
-    // Check for duplicate source names
-    if let Some(sources) = spec.get("sources").and_then(|s| s.as_array()) {
-        let mut seen_names: Vec<&str> = Vec::new();
-        for source in sources {
-            if let Some(name) = source.get("name").and_then(|n| n.as_str()) {
-                if seen_names.contains(&name) {
-                    errors.push(format!("Duplicate source name: '{}'", name));
-                } else {
-                    seen_names.push(name);
+    // Validate the clustering strategy
+    if let Some(clustering) = spec.get("clustering").filter(|c| !c.is_null()) {
+        let strategy = match clustering.get("strategy") {
+            None => Some(ClusteringStrategy::default()),
+            Some(value) => match value.as_str().map(str::parse::<ClusteringStrategy>) {
+                Some(Ok(strategy)) => Some(strategy),
+                _ => {
+                    errors.push(
+                        Diagnostic::error(
+                            codes::INVALID_CLUSTERING,
+                            "clustering.strategy",
+                            format!("Unknown clustering strategy {}.", value),
+                        )
+                        .with_suggestion(format!("Use one of: {}.", clustering::STRATEGIES.join(", "))),
+                    );
+                    None
+                }
+            },
+        };
+        if let Some(strategy) = strategy.filter(|s| *s != ClusteringStrategy::Hierarchical) {
+            if clustering.get("threshold").is_some() {
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_CLUSTERING,
+                        "clustering.threshold",
+                        format!(
+                            "clustering.threshold is only used by the hierarchical strategy, not '{}'.",
+                            strategy
+                        ),
+                    )
+                    .with_suggestion("Set `strategy: hierarchical` or remove clustering.threshold."),
+                );
+            }
+        }
+    }
+
+    // Validate normalized field references
+    if let Some(fields) = spec
+        .get("normalization")
+        .and_then(|n| n.get("fields"))
+        .and_then(|f| f.as_object())
+    {
+        for field in fields.keys() {
+            if !available_fields.is_empty() && !available_fields.contains(field) {
+                errors.push(Diagnostic::error(
+                    codes::UNKNOWN_FIELD,
+                    format!("normalization.fields.{}", field),
+                    format!("Normalization references unknown field '{}'.", field),
+                ));
+            }
+        }
+    }
+
+    // Validate survivorship strategies and their references
+    if let Some(rules) = spec
+        .get("survivorship")
+        .and_then(|s| s.get("rules"))
+        .and_then(|r| r.as_array())
+    {
+        let source_names: Vec<&str> = spec
+            .get("sources")
+            .and_then(|s| s.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|source| source.get("name")?.as_str())
+            .collect();
+        for (i, rule) in rules.iter().enumerate() {
+            errors.extend(survivorship_rule_diagnostics(
+                i,
+                rule,
+                &available_fields,
+                &source_names,
+            ));
+        }
+    }
+
+    // Validate the temporal section and its references
+    if let Some(temporal) = spec.get("temporal").filter(|t| !t.is_null()) {
+        errors.extend(temporal_diagnostics(temporal, &available_fields));
+    }
+
+    // Validate the execution mode and its settings
+    if let Some(execution) = spec.get("execution").filter(|e| !e.is_null()) {
+        errors.extend(execution_diagnostics(execution, &available_fields));
+    }
+
+    // Validate identifier hashing
+    if let Some(hashing) = spec.get("hashing").filter(|h| !h.is_null()) {
+        if let Some(algorithm) = hashing.get("algorithm").and_then(|a| a.as_str()) {
+            if algorithm.parse::<HashAlgorithm>().is_err() {
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_HASHING,
+                        "hashing.algorithm",
+                        format!("Unknown hash algorithm '{}'.", algorithm),
+                    )
+                    .with_suggestion("Use sha256, sha512 or blake3."),
+                );
+            }
+        }
+        if hashing.get("key_env").is_some() && hashing.get("key_file").is_some() {
+            errors.push(Diagnostic::error(
+                codes::INVALID_HASHING,
+                "hashing.key_file",
+                "Set either hashing.key_env or hashing.key_file, not both.",
+            ));
+        }
+    }
+
+    // Validate stewardship assertions and their references
+    if spec.get("stewardship").is_some_and(|s| !s.is_null()) {
+        errors.extend(stewardship_diagnostics(spec, &available_fields));
+    }
+
+    // Validate privacy settings and the attributes they name
+    if spec.get("privacy").is_some_and(|p| !p.is_null()) {
+        errors.extend(privacy_diagnostics(spec, &available_fields));
+    }
+
+    // Validate the encoding section and what compares encoded attributes
+    if spec.get("encoding").is_some_and(|e| !e.is_null()) {
+        errors.extend(encoding_diagnostics(spec, &available_fields));
+    }
+
+    // Validate the deletion section and its tombstone attribute
+    if spec.get("deletion").is_some_and(|d| !d.is_null()) {
+        errors.extend(deletion_diagnostics(spec, &available_fields));
+    }
+
+    // Validate relationships and their references
+    if let Some(relationships) = spec.get("relationships").filter(|r| !r.is_null()) {
+        let entity = spec
+            .get("entity")
+            .and_then(|e| e.get("name"))
+            .and_then(|n| n.as_str());
+        errors.extend(relationship_diagnostics(
+            relationships,
+            entity,
+            &available_fields,
+            algorithms,
+        ));
+    }
+
+    // Check for duplicate rule and source names
+    for (section, label, code) in [
+        ("rules", "rule", codes::DUPLICATE_RULE),
+        ("sources", "source", codes::DUPLICATE_SOURCE),
+    ] {
+        if let Some(items) = spec.get(section).and_then(|r| r.as_array()) {
+            let mut seen_names: Vec<&str> = Vec::new();
+            for (i, item) in items.iter().enumerate() {
+                if let Some(name) = item.get("name").and_then(|n| n.as_str()) {
+                    if seen_names.contains(&name) {
+                        errors.push(Diagnostic::error(
+                            code,
+                            format!("{}[{}].name", section, i),
+                            format!("Duplicate {} name: '{}'", label, name),
+                        ));
+                    } else {
+                        seen_names.push(name);
+                    }
                 }
             }
         }
@@ -196,13 +1067,1467 @@ pub fn validate_semantics(spec: &Value) -> Result<Vec<String>> {
                 .unwrap_or(0.0);
 
             if match_t < review_t {
-                errors.push("Threshold error: 'match' should be >= 'review'".to_string());
+                errors.push(Diagnostic::error(
+                    codes::THRESHOLD_ORDER,
+                    "decision.thresholds.match",
+                    "Threshold error: 'match' should be >= 'review'",
+                ));
             }
             if review_t < reject_t {
-                errors.push("Threshold error: 'review' should be >= 'reject'".to_string());
+                errors.push(Diagnostic::error(
+                    codes::THRESHOLD_ORDER,
+                    "decision.thresholds.review",
+                    "Threshold error: 'review' should be >= 'reject'",
+                ));
             }
         }
     }
 
-    Ok(errors)
+    // Advice on a spec that is already wrong is mostly noise (a misspelled
+    // rule field also leaves its attribute "unused"), so hold it back.
+    if errors.is_empty() {
+        errors.extend(advice(spec));
+    }
+    errors
+}
+
+/// Problems with the relationships section: unique names, known
+/// cardinalities and rule types, and rule fields this entity has. A
+/// relationship to the entity itself (a hierarchy) has its target fields
+/// checked here too; links to other entities are checked across a
+/// workspace (`workspace_diagnostics`).
+fn relationship_diagnostics(
+    relationships: &Value,
+    entity: Option<&str>,
+    available_fields: &[String],
+    algorithms: &AlgorithmRegistry,
+) -> Vec<Diagnostic> {
+    let Some(relationships) = relationships.as_array() else {
+        return vec![Diagnostic::error(
+            codes::INVALID_RELATIONSHIP,
+            "relationships",
+            format!("Expected a list of relationships, got {}.", relationships),
+        )];
+    };
+    let known =
+        |name: &str| available_fields.is_empty() || available_fields.iter().any(|f| f == name);
+    let mut errors = Vec::new();
+    let mut seen_names: Vec<&str> = Vec::new();
+    for (i, relationship) in relationships.iter().enumerate() {
+        let path = |key: &str| format!("relationships[{}].{}", i, key);
+        let name = relationship
+            .get("name")
+            .and_then(|n| n.as_str())
+            .unwrap_or("unknown");
+        if relationship.get("name").is_some() {
+            if seen_names.contains(&name) {
+                errors.push(Diagnostic::error(
+                    codes::INVALID_RELATIONSHIP,
+                    path("name"),
+                    format!("Duplicate relationship name: '{}'", name),
+                ));
+            } else {
+                seen_names.push(name);
+            }
+        }
+
+        if let Some(cardinality) = relationship.get("cardinality") {
+            if cardinality
+                .as_str()
+                .and_then(|c| c.parse::<Cardinality>().ok())
+                .is_none()
+            {
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_RELATIONSHIP,
+                        path("cardinality"),
+                        format!(
+                            "Unknown cardinality {} for relationship '{}'.",
+                            cardinality, name
+                        ),
+                    )
+                    .with_suggestion(format!("Use one of: {}.", relationships::CARDINALITIES.join(", "))),
+                );
+            }
+        }
+
+        let target = relationship.get("to").and_then(|t| t.as_str());
+        if let Some(to) = relationship.get("to").filter(|t| !t.is_string()) {
+            errors.push(Diagnostic::error(
+                codes::INVALID_RELATIONSHIP,
+                path("to"),
+                format!("Relationship '{}' must link to an entity name, got {}.", name, to),
+            ));
+        }
+        let reflexive = target.is_some() && target == entity;
+
+        let rules = match relationship.get("rules") {
+            None => continue,
+            Some(Value::Array(rules)) if !rules.is_empty() => rules,
+            Some(_) => {
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_RELATIONSHIP,
+                        path("rules"),
+                        format!("Relationship '{}' has no rules.", name),
+                    )
+                    .with_suggestion("Add a rule comparing an attribute with one of the target."),
+                );
+                continue;
+            }
+        };
+        for (j, rule) in rules.iter().enumerate() {
+            let rule_path = |key: &str| format!("relationships[{}].rules[{}].{}", i, j, key);
+            let rule_name = rule.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+            let field = rule.get("field").and_then(|f| f.as_str());
+            if let Some(field) = field.filter(|f| !known(f)) {
+                errors.push(Diagnostic::error(
+                    codes::UNKNOWN_FIELD,
+                    rule_path("field"),
+                    format!(
+                        "Relationship rule '{}' references unknown field '{}'.",
+                        rule_name, field
+                    ),
+                ));
+            }
+            let target_field = rule.get("target_field").and_then(|f| f.as_str()).or(field);
+            if let Some(target_field) = target_field.filter(|f| reflexive && !known(f)) {
+                errors.push(Diagnostic::error(
+                    codes::UNKNOWN_FIELD,
+                    rule_path("target_field"),
+                    format!(
+                        "Relationship rule '{}' references unknown target field '{}'.",
+                        rule_name, target_field
+                    ),
+                ));
+            }
+            match rule.get("type") {
+                None => {}
+                Some(Value::String(t)) if relationships::RULE_TYPES.contains(&t.as_str()) => {}
+                Some(other) => errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_RELATIONSHIP,
+                        rule_path("type"),
+                        format!(
+                            "Relationship rule '{}' has unsupported type {}.",
+                            rule_name, other
+                        ),
+                    )
+                    .with_suggestion(format!("Use one of: {}.", relationships::RULE_TYPES.join(", "))),
+                ),
+            }
+            if let Some(algorithm) = rule.get("algorithm").and_then(|a| a.as_str()) {
+                if !algorithms.contains(algorithm) {
+                    let mut diagnostic = Diagnostic::error(
+                        codes::UNKNOWN_ALGORITHM,
+                        rule_path("algorithm"),
+                        format!(
+                            "Relationship rule '{}' uses unknown algorithm '{}'.",
+                            rule_name, algorithm
+                        ),
+                    );
+                    if let Some(similar) = algorithms.closest(algorithm) {
+                        diagnostic =
+                            diagnostic.with_suggestion(format!("Did you mean '{}'?", similar));
+                    }
+                    errors.push(diagnostic);
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Problems with the survivorship rule at `survivorship.rules[i]`.
+fn survivorship_rule_diagnostics(
+    i: usize,
+    rule: &Value,
+    available_fields: &[String],
+    source_names: &[&str],
+) -> Vec<Diagnostic> {
+    let field = rule.get("field").and_then(|f| f.as_str()).unwrap_or("unknown");
+    let path = |key: &str| format!("survivorship.rules[{}].{}", i, key);
+    let error =
+        |key: &str, message: String| Diagnostic::error(codes::INVALID_SURVIVORSHIP, path(key), message);
+    let known =
+        |name: &str| available_fields.is_empty() || available_fields.iter().any(|f| f == name);
+    let mut errors = Vec::new();
+
+    let mut references: Vec<(String, &str)> = Vec::new();
+    if let Some(name) = rule.get("field").and_then(|f| f.as_str()) {
+        references.push((path("field"), name));
+    }
+    if let Some(name) = rule.get("timestamp").and_then(|t| t.as_str()) {
+        references.push((path("timestamp"), name));
+    }
+    if let Some(fields) = rule.get("fields").and_then(|f| f.as_array()) {
+        for (j, name) in fields.iter().enumerate() {
+            if let Some(name) = name.as_str() {
+                references.push((format!("{}[{}]", path("fields"), j), name));
+            }
+        }
+    }
+    for (location, name) in references {
+        if !known(name) {
+            errors.push(Diagnostic::error(
+                codes::UNKNOWN_FIELD,
+                location,
+                format!(
+                    "Survivorship rule for '{}' references unknown field '{}'.",
+                    field, name
+                ),
+            ));
+        }
+    }
+
+    if let Some(priority) = rule.get("source_priority").and_then(|p| p.as_array()) {
+        for (j, source) in priority.iter().enumerate() {
+            let Some(source) = source.as_str() else {
+                continue;
+            };
+            if !source_names.is_empty() && !source_names.contains(&source) {
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_SURVIVORSHIP,
+                        format!("{}[{}]", path("source_priority"), j),
+                        format!(
+                            "Survivorship rule for '{}' prioritizes unknown source '{}'.",
+                            field, source
+                        ),
+                    )
+                    .with_suggestion(format!("Sources are: {}.", source_names.join(", "))),
+                );
+            }
+        }
+    }
+
+    let strategy = rule.get("strategy").and_then(|s| s.as_str());
+    match strategy {
+        Some(strategy) if survivorship::STRATEGIES.contains(&strategy) => {}
+        _ => {
+            let value = rule.get("strategy").unwrap_or(&Value::Null);
+            errors.push(
+                error(
+                    "strategy",
+                    format!("Unknown survivorship strategy {} for '{}'.", value, field),
+                )
+                .with_suggestion(format!(
+                    "Use one of: {}.",
+                    survivorship::STRATEGIES.join(", ")
+                )),
+            );
+            return errors;
+        }
+    }
+    for (key, reader) in [
+        ("timestamp", "most_recent"),
+        ("fields", "non_null_priority"),
+        ("expression", "custom"),
+    ] {
+        if rule.get(key).is_some() && strategy != Some(reader) {
+            errors.push(error(
+                key,
+                format!(
+                    "`{}` is only used by the {} strategy, not '{}'.",
+                    key,
+                    reader,
+                    strategy.unwrap_or_default()
+                ),
+            ));
+        }
+    }
+    match strategy {
+        Some("non_null_priority")
+            if rule
+                .get("fields")
+                .and_then(|f| f.as_array())
+                .is_none_or(|f| f.is_empty()) =>
+        {
+            errors.push(
+                error(
+                    "fields",
+                    format!("non_null_priority rule for '{}' lists no fields.", field),
+                )
+                .with_suggestion("Add `fields:` with the attributes to try, in order."),
+            );
+        }
+        Some("custom") if rule.get("expression").is_none() => errors.push(
+            error(
+                "expression",
+                format!("Custom survivorship rule for '{}' has no expression.", field),
+            )
+            .with_suggestion("Add `expression:`; the member scoring highest survives."),
+        ),
+        _ => {}
+    }
+
+    let names = |name: &str| survivorship::EXPRESSION_NAMES.contains(&name) || known(name);
+    let hint = "Survivorship expressions read value, source, record_key and the attributes.";
+    for (key, condition) in [("expression", false), ("condition", true)] {
+        if let Some(source) = rule.get(key) {
+            errors.extend(expression_diagnostics(path(key), source, &names, hint, condition));
+        }
+    }
+    errors
+}
+
+/// Problems with the temporal section: its attributes must exist and be
+/// distinct, and history modes and the match window must be valid.
+fn temporal_diagnostics(temporal: &Value, available_fields: &[String]) -> Vec<Diagnostic> {
+    let invalid = |path: &str, message: String| {
+        Diagnostic::error(codes::INVALID_TEMPORAL, format!("temporal.{}", path), message)
+    };
+    let unknown = |path: String, name: &str| {
+        Diagnostic::error(
+            codes::UNKNOWN_FIELD,
+            path,
+            format!("Temporal section references unknown field '{}'.", name),
+        )
+    };
+    let known =
+        |name: &str| available_fields.is_empty() || available_fields.iter().any(|f| f == name);
+    let mut errors = Vec::new();
+    if !temporal.is_object() {
+        return vec![Diagnostic::error(
+            codes::INVALID_TEMPORAL,
+            "temporal",
+            format!("Expected a mapping, got {}.", temporal),
+        )];
+    }
+
+    let mut bounds = Vec::new();
+    for key in ["effective_from", "effective_to"] {
+        match temporal.get(key) {
+            None => {}
+            Some(Value::String(name)) if !known(name) => {
+                errors.push(unknown(format!("temporal.{}", key), name));
+            }
+            Some(Value::String(name)) => bounds.push(name.as_str()),
+            Some(other) => errors.push(invalid(
+                key,
+                format!("temporal.{} must name an attribute, got {}.", key, other),
+            )),
+        }
+    }
+    if let [from, to] = bounds[..] {
+        if from == to {
+            errors.push(invalid(
+                "effective_to",
+                format!("effective_from and effective_to are both '{}'.", from),
+            ));
+        }
+    }
+
+    if let Some(days) = temporal.get("match_window_days") {
+        // Negative windows are out of range, reported by the schema stage
+        let negative = days.as_f64().is_some_and(|d| d < 0.0);
+        if !negative && days.as_u64().and_then(|d| u32::try_from(d).ok()).is_none() {
+            errors.push(invalid(
+                "match_window_days",
+                format!("temporal.match_window_days must be a whole number of days, got {}.", days),
+            ));
+        }
+    }
+
+    match temporal.get("attributes") {
+        None => {}
+        Some(Value::Object(attributes)) => {
+            for (name, mode) in attributes {
+                let path = format!("temporal.attributes.{}", name);
+                if !known(name) {
+                    errors.push(unknown(path, name));
+                } else if !mode.as_str().is_some_and(|m| temporal::HISTORY_MODES.contains(&m)) {
+                    errors.push(
+                        Diagnostic::error(
+                            codes::INVALID_TEMPORAL,
+                            path,
+                            format!("Unknown history mode {} for '{}'.", mode, name),
+                        )
+                        .with_suggestion(format!(
+                            "Use one of: {}.",
+                            temporal::HISTORY_MODES.join(", ")
+                        )),
+                    );
+                }
+            }
+        }
+        Some(other) => errors.push(invalid(
+            "attributes",
+            format!("temporal.attributes must map attributes to history modes, got {}.", other),
+        )),
+    }
+    errors
+}
+
+/// Problems with the privacy section: well-formed settings for attributes
+/// that exist.
+fn privacy_diagnostics(spec: &Value, available_fields: &[String]) -> Vec<Diagnostic> {
+    let privacy = match Privacy::from_spec(spec) {
+        Ok(privacy) => privacy.unwrap_or_default(),
+        Err(e) => {
+            return vec![Diagnostic::error(
+                codes::INVALID_PRIVACY,
+                "privacy",
+                format!("{:#}.", e),
+            )]
+        }
+    };
+    let unknown =
+        |field: &String| !available_fields.is_empty() && !available_fields.contains(field);
+    let mut errors: Vec<Diagnostic> = privacy
+        .fields
+        .keys()
+        .filter(|field| unknown(field))
+        .map(|field| {
+            Diagnostic::error(
+                codes::UNKNOWN_FIELD,
+                format!("privacy.fields.{}", field),
+                format!("Privacy settings reference unknown field '{}'.", field),
+            )
+        })
+        .collect();
+    if let Some(field) = privacy.collected_at.as_ref().filter(|field| unknown(field)) {
+        errors.push(Diagnostic::error(
+            codes::UNKNOWN_FIELD,
+            "privacy.collected_at",
+            format!("Retention is counted from unknown field '{}'.", field),
+        ));
+    }
+    errors
+}
+
+/// Problems with the encoding section: well-formed, naming attributes that
+/// exist, with rules and blocking keys that can compare the encodings.
+fn encoding_diagnostics(spec: &Value, available_fields: &[String]) -> Vec<Diagnostic> {
+    let Some(section) = spec.get("encoding") else {
+        return Vec::new();
+    };
+    let mut errors: Vec<Diagnostic> = encoding::check(section)
+        .into_iter()
+        .map(|(key, message)| {
+            let path = match key.is_empty() {
+                true => "encoding".to_string(),
+                false => format!("encoding.{}", key),
+            };
+            Diagnostic::error(
+                codes::INVALID_ENCODING,
+                path,
+                format!("Invalid encoding: {}.", message),
+            )
+        })
+        .collect();
+    let Ok(Some(encoding)) = Encoding::from_spec(spec) else {
+        return errors;
+    };
+    for field in encoding.fields.keys() {
+        if !available_fields.is_empty() && !available_fields.contains(field) {
+            errors.push(Diagnostic::error(
+                codes::UNKNOWN_FIELD,
+                format!("encoding.fields.{}", field),
+                format!("Encoding references unknown field '{}'.", field),
+            ));
+        }
+    }
+
+    let mismatch = |path: String, message: String| {
+        Diagnostic::error(codes::ENCODED_RULE_MISMATCH, path, message)
+    };
+    let rules = spec.get("rules").and_then(|r| r.as_array());
+    for (i, rule) in rules.into_iter().flatten().enumerate() {
+        let Some(field) = rule.get("field").and_then(|f| f.as_str()) else {
+            continue;
+        };
+        let Some(method) = encoding.field(field) else {
+            continue;
+        };
+        let name = rule.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+        let algorithm = rule.get("algorithm").and_then(|a| a.as_str());
+        match (rule.get("type").and_then(|t| t.as_str()), method) {
+            (Some("semantic"), _) => errors.push(mismatch(
+                format!("rules[{}].type", i),
+                format!(
+                    "Semantic rule '{}' embeds {}-encoded attribute '{}'; encodings have no meaningful embeddings.",
+                    name, method.name(), field
+                ),
+            )),
+            (Some("fuzzy"), FieldEncoding::Hash) => errors.push(
+                mismatch(
+                    format!("rules[{}].type", i),
+                    format!(
+                        "Fuzzy rule '{}' compares hash-encoded attribute '{}'; digests of similar values share nothing.",
+                        name, field
+                    ),
+                )
+                .with_suggestion(format!(
+                    "Use an exact rule, or encode '{}' with bloom.",
+                    field
+                )),
+            ),
+            (Some("fuzzy"), FieldEncoding::Bloom(_)) if algorithm != Some("dice") => errors.push(
+                mismatch(
+                    format!("rules[{}].algorithm", i),
+                    format!(
+                        "Fuzzy rule '{}' compares Bloom-encoded attribute '{}' with '{}'; filters are compared by their bits.",
+                        name,
+                        field,
+                        algorithm.unwrap_or(similarity::DEFAULT_ALGORITHM)
+                    ),
+                )
+                .with_suggestion("Use algorithm: dice."),
+            ),
+            _ => {}
+        }
+    }
+    let keys = spec
+        .get("blocking")
+        .and_then(|b| b.get("keys"))
+        .and_then(|k| k.as_array());
+    for (i, key) in keys.into_iter().flatten().enumerate() {
+        let (Some(field), Some(transform)) = (
+            key.get("field").and_then(|f| f.as_str()),
+            key.get("transform").and_then(|t| t.as_str()),
+        ) else {
+            continue;
+        };
+        if transform != "identity" && encoding.field(field).is_some() {
+            errors.push(
+                mismatch(
+                    format!("blocking.keys[{}].transform", i),
+                    format!(
+                        "Blocking key transform '{}' applies to the encodings of '{}', not its values.",
+                        transform, field
+                    ),
+                )
+                .with_suggestion("Block on the encoding as is, or on another attribute."),
+            );
+        }
+    }
+    errors
+}
+
+/// Problems with the deletion section: well-formed, with a tombstone
+/// attribute that exists.
+fn deletion_diagnostics(spec: &Value, available_fields: &[String]) -> Vec<Diagnostic> {
+    match Deletion::from_spec(spec) {
+        Ok(Some(deletion))
+            if !available_fields.is_empty() && !available_fields.contains(&deletion.tombstone) =>
+        {
+            vec![Diagnostic::error(
+                codes::UNKNOWN_FIELD,
+                "deletion.tombstone",
+                format!("Tombstone references unknown field '{}'.", deletion.tombstone),
+            )]
+        }
+        Ok(_) => Vec::new(),
+        Err(e) => vec![Diagnostic::error(
+            codes::INVALID_DELETION,
+            "deletion",
+            format!("{:#}.", e),
+        )],
+    }
+}
+
+/// Problems with the stewardship section: well-formed entries, locked
+/// fields that exist, and no assertions that contradict each other.
+fn stewardship_diagnostics(spec: &Value, available_fields: &[String]) -> Vec<Diagnostic> {
+    let stewardship = match Stewardship::from_spec(spec) {
+        Ok(stewardship) => stewardship.unwrap_or_default(),
+        Err(e) => {
+            return vec![Diagnostic::error(
+                codes::INVALID_STEWARDSHIP,
+                "stewardship",
+                format!("{:#}.", e),
+            )]
+        }
+    };
+    let mut errors = Vec::new();
+    for (i, lock) in stewardship.locked.iter().enumerate() {
+        if !available_fields.is_empty() && !available_fields.contains(&lock.field) {
+            errors.push(Diagnostic::error(
+                codes::UNKNOWN_FIELD,
+                format!("stewardship.locked[{}].field", i),
+                format!("Stewardship lock references unknown field '{}'.", lock.field),
+            ));
+        }
+    }
+    for conflict in stewardship.conflicts() {
+        errors.push(Diagnostic::error(
+            codes::INVALID_STEWARDSHIP,
+            conflict.path,
+            conflict.message,
+        ));
+    }
+    errors
+}
+
+/// Problems with the execution section: a known mode, a delta column that
+/// exists and is read, and a boolean `stable_ids`.
+fn execution_diagnostics(execution: &Value, available_fields: &[String]) -> Vec<Diagnostic> {
+    let invalid = |path: &str, message: String| {
+        Diagnostic::error(codes::INVALID_EXECUTION, format!("execution.{}", path), message)
+    };
+    if !execution.is_object() {
+        return vec![Diagnostic::error(
+            codes::INVALID_EXECUTION,
+            "execution",
+            format!("Expected a mapping, got {}.", execution),
+        )];
+    }
+    let mut errors = Vec::new();
+
+    let mode = match execution.get("mode") {
+        None => Some(ExecutionMode::default()),
+        Some(value) => match value.as_str().map(str::parse::<ExecutionMode>) {
+            Some(Ok(mode)) => Some(mode),
+            _ => {
+                errors.push(
+                    invalid("mode", format!("Unknown execution mode {}.", value))
+                        .with_suggestion(format!("Use one of: {}.", execution::MODES.join(", "))),
+                );
+                None
+            }
+        },
+    };
+
+    match execution.get("delta_column") {
+        None => {}
+        Some(Value::String(column)) => {
+            if !available_fields.is_empty() && !available_fields.contains(column) {
+                errors.push(Diagnostic::error(
+                    codes::UNKNOWN_FIELD,
+                    "execution.delta_column",
+                    format!("Execution references unknown field '{}'.", column),
+                ));
+            }
+            if let Some(mode) = mode.filter(|m| *m != ExecutionMode::Incremental) {
+                errors.push(
+                    invalid(
+                        "delta_column",
+                        format!(
+                            "execution.delta_column is only used by incremental runs, not '{}'.",
+                            mode
+                        ),
+                    )
+                    .with_suggestion("Set `mode: incremental` or remove execution.delta_column."),
+                );
+            }
+        }
+        Some(other) => errors.push(invalid(
+            "delta_column",
+            format!("execution.delta_column must name an attribute, got {}.", other),
+        )),
+    }
+
+    if let Some(stable) = execution.get("stable_ids").filter(|s| !s.is_boolean()) {
+        errors.push(invalid(
+            "stable_ids",
+            format!("execution.stable_ids must be true or false, got {}.", stable),
+        ));
+    }
+    errors
+}
+
+/// Diagnostics for the expression at `path`: it must parse and type-check,
+/// with the names `names` accepts read as strings, and a condition must be
+/// a bool. Conditions are `INVALID_EXPRESSION`s; a custom survivorship
+/// expression keeps the `INVALID_SURVIVORSHIP` code it always had.
+fn expression_diagnostics(
+    path: String,
+    source: &Value,
+    names: &dyn Fn(&str) -> bool,
+    hint: &str,
+    condition: bool,
+) -> Vec<Diagnostic> {
+    let code = match condition {
+        true => codes::INVALID_EXPRESSION,
+        false => codes::INVALID_SURVIVORSHIP,
+    };
+    let error = |message: String| Diagnostic::error(code, path.clone(), message);
+    let Some(source) = source.as_str() else {
+        return vec![error(format!("Expected an expression string, got {}.", source))];
+    };
+    let expression = match Expression::parse(source) {
+        Ok(expression) => expression,
+        Err(e) => return vec![error(format!("Invalid expression: {}.", e))],
+    };
+    let unknown: Vec<Diagnostic> = expression
+        .names()
+        .into_iter()
+        .filter(|name| !names(name))
+        .map(|name| {
+            error(format!("Expression reads unknown name '{}'.", name)).with_suggestion(hint)
+        })
+        .collect();
+    if !unknown.is_empty() {
+        return unknown;
+    }
+    match expression.check(&|_| Some(Type::String)) {
+        Err(e) => vec![error(format!("Expression does not type-check: {}.", e))],
+        Ok(t) if condition && t.unify_with(Type::Bool).is_none() => vec![error(format!(
+            "A condition must be a bool, not a {}.",
+            t
+        ))
+        .with_suggestion("Compare values, e.g. `left.country == right.country`.")],
+        Ok(_) => Vec::new(),
+    }
+}
+
+/// Rules and normalizers that do not fit the types of the attributes they
+/// apply to, and attributes typed differently by different sources.
+fn type_diagnostics(spec: &Value) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    let mismatch =
+        |path: String, message: String| Diagnostic::error(codes::TYPE_MISMATCH, path, message);
+
+    let mut declared: Vec<(&str, AttributeType, &str)> = Vec::new();
+    let sources = spec.get("sources").and_then(|s| s.as_array());
+    for (i, source) in sources.into_iter().flatten().enumerate() {
+        let name = source.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+        let Some(attrs) = source.get("attributes").and_then(|a| a.as_object()) else {
+            continue;
+        };
+        for (attribute, mapping) in attrs {
+            let Some(ty) = attributes::declared_type(mapping) else {
+                continue;
+            };
+            match declared.iter().find(|(a, _, _)| a == attribute) {
+                Some((_, first, first_source)) if *first != ty => errors.push(
+                    mismatch(
+                        format!("sources[{}].attributes.{}.type", i, attribute),
+                        format!(
+                            "Attribute '{}' is typed {} in source '{}' but {} in '{}'.",
+                            attribute, ty, name, first, first_source
+                        ),
+                    )
+                    .with_suggestion(format!("Give '{}' one type in every source.", attribute)),
+                ),
+                Some(_) => {}
+                None => declared.push((attribute, ty, name)),
+            }
+        }
+    }
+    let types = attributes::declared_types(spec);
+    if types.is_empty() {
+        return errors;
+    }
+
+    let normalized = spec
+        .get("normalization")
+        .and_then(|n| n.get("fields"))
+        .and_then(|f| f.as_object());
+    let rules = spec.get("rules").and_then(|r| r.as_array());
+    for (i, rule) in rules.into_iter().flatten().enumerate() {
+        let Some(field) = rule.get("field").and_then(|f| f.as_str()) else {
+            continue;
+        };
+        let Some(ty) = types.get(field) else {
+            continue;
+        };
+        let name = rule.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+        let algorithm = rule.get("algorithm").and_then(|a| a.as_str());
+        match rule.get("type").and_then(|t| t.as_str()) {
+            Some("fuzzy") => match algorithm {
+                Some(algorithm) if !ty.accepts_algorithm(algorithm) => errors.push(
+                    mismatch(
+                        format!("rules[{}].algorithm", i),
+                        format!(
+                            "Rule '{}' compares {} attribute '{}' with string algorithm '{}'.",
+                            name, ty, field, algorithm
+                        ),
+                    )
+                    .with_suggestion(format!(
+                        "Match {} values exactly, or use an algorithm your engine registers for them.",
+                        ty
+                    )),
+                ),
+                None if !ty.is_textual() => errors.push(
+                    mismatch(
+                        format!("rules[{}]", i),
+                        format!(
+                            "Fuzzy rule '{}' on {} attribute '{}' would compare it as a string.",
+                            name, ty, field
+                        ),
+                    )
+                    .with_suggestion(format!("Match {} values exactly.", ty)),
+                ),
+                _ => {}
+            },
+            Some("semantic") if !ty.is_textual() => errors.push(mismatch(
+                format!("rules[{}].field", i),
+                format!(
+                    "Semantic rule '{}' embeds {} attribute '{}'; only text has meaningful embeddings.",
+                    name, ty, field
+                ),
+            )),
+            Some("exact")
+                if *ty == AttributeType::String
+                    && !normalized.is_some_and(|n| n.contains_key(field)) =>
+            {
+                errors.push(
+                    mismatch(
+                        format!("rules[{}]", i),
+                        format!(
+                            "Exact rule '{}' on free-text attribute '{}' has no normalization, so values differing in case or spacing never match.",
+                            name, field
+                        ),
+                    )
+                    .with_suggestion(format!(
+                        "Add normalization.fields.{}, e.g. [nfkc, casefold].",
+                        field
+                    )),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    for (field, steps) in normalized.into_iter().flatten() {
+        let (Some(ty), Some(steps)) = (types.get(field), steps.as_array()) else {
+            continue;
+        };
+        for (j, step) in steps.iter().enumerate() {
+            let Ok(normalizer) = Normalizer::from_value(step) else {
+                continue;
+            };
+            if !ty.accepts_normalizer(&normalizer) {
+                errors.push(mismatch(
+                    format!("normalization.fields.{}[{}]", field, j),
+                    format!(
+                        "Normalizer '{}' does not apply to {} attribute '{}'.",
+                        normalizer, ty, field
+                    ),
+                ));
+            }
+        }
+    }
+    errors
+}
+
+/// Problems with the embedding configuration of the semantic rule at
+/// `rules[i]`.
+fn semantic_rule_diagnostics(i: usize, rule: &Value) -> Vec<Diagnostic> {
+    let name = rule.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+    let error = |key: &str, message: String| {
+        Diagnostic::error(
+            codes::INVALID_SEMANTIC_RULE,
+            format!("rules[{}].{}", i, key),
+            message,
+        )
+    };
+    let mut errors = Vec::new();
+    if !rule.get("model").is_some_and(|m| m.is_string()) {
+        errors.push(
+            error("model", format!("Semantic rule '{}' names no embedding model.", name))
+                .with_suggestion("Add `model:` with the embedding model's name."),
+        );
+    }
+    match rule.get("endpoint").and_then(|e| e.as_str()) {
+        None => errors.push(
+            error(
+                "endpoint",
+                format!("Semantic rule '{}' has no embedding endpoint.", name),
+            )
+            .with_suggestion("Add `endpoint:` with the URL serving the model."),
+        ),
+        Some(endpoint) if !embedding::is_endpoint(endpoint) => errors.push(error(
+            "endpoint",
+            format!(
+                "Semantic rule '{}' has endpoint '{}', which is not an http(s) URL.",
+                name, endpoint
+            ),
+        )),
+        Some(_) => {}
+    }
+    if rule.get("threshold").and_then(|t| t.as_f64()).is_none() {
+        errors.push(
+            error(
+                "threshold",
+                format!("Semantic rule '{}' has no cosine similarity threshold.", name),
+            )
+            .with_suggestion("Add `threshold:` between 0 and 1, e.g. 0.85."),
+        );
+    }
+    if rule.get("algorithm").is_some() {
+        errors.push(error(
+            "algorithm",
+            format!(
+                "Semantic rule '{}' compares embeddings and takes no algorithm.",
+                name
+            ),
+        ));
+    }
+    errors
+}
+
+/// Warnings and info: valid specs that are probably not what was meant.
+fn advice(spec: &Value) -> Vec<Diagnostic> {
+    let mut findings = Vec::new();
+    let rules = spec
+        .get("rules")
+        .and_then(|r| r.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    for (i, rule) in rules.iter().enumerate() {
+        let name = rule.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+        if rule.get("weight").and_then(|w| w.as_f64()) == Some(0.0) {
+            findings.push(Diagnostic::warning(
+                codes::ZERO_WEIGHT_RULE,
+                format!("rules[{}].weight", i),
+                format!("Rule '{}' has weight 0 and never affects a score.", name),
+            ));
+        }
+        if rule.get("type").and_then(|t| t.as_str()) == Some("fuzzy")
+            && rule.get("algorithm").is_none()
+        {
+            findings.push(Diagnostic::warning(
+                codes::FUZZY_WITHOUT_ALGORITHM,
+                format!("rules[{}]", i),
+                format!(
+                    "Fuzzy rule '{}' has no algorithm; backends will use different defaults.",
+                    name
+                ),
+            ));
+        }
+    }
+
+    if rules.is_empty() {
+        return findings;
+    }
+
+    if spec
+        .get("decision")
+        .and_then(|d| d.get("thresholds"))
+        .is_none()
+    {
+        findings.push(Diagnostic::warning(
+            codes::MISSING_THRESHOLDS,
+            "decision",
+            "No decision thresholds; only perfect scores will match.",
+        ));
+    }
+
+    // Attributes no rule, blocking key or survivorship rule refers to
+    let survivorship_rules = spec
+        .get("survivorship")
+        .and_then(|s| s.get("rules"))
+        .and_then(|r| r.as_array());
+    let expressions: Vec<Expression> = rules
+        .iter()
+        .filter_map(|r| r.get("condition"))
+        .chain(
+            survivorship_rules
+                .into_iter()
+                .flatten()
+                .flat_map(|r| [r.get("expression"), r.get("condition")])
+                .flatten(),
+        )
+        .filter_map(|e| e.as_str()?.parse().ok())
+        .collect();
+    let mut used: Vec<&str> = rules
+        .iter()
+        .filter_map(|r| r.get("field").and_then(|f| f.as_str()))
+        .collect();
+    used.extend(blocking_key_fields(spec).into_iter().map(|(_, field)| field));
+    if let Some(blocking) = spec.get("blocking") {
+        used.extend(
+            [("sorted_neighborhood", "sort_key"), ("canopy", "field")]
+                .iter()
+                .filter_map(|(name, key)| blocking.get(name)?.get(key)?.as_str()),
+        );
+    }
+    if let Some(survivorship) = spec
+        .get("survivorship")
+        .and_then(|s| s.get("rules"))
+        .and_then(|r| r.as_array())
+    {
+        for rule in survivorship {
+            used.extend(
+                ["field", "timestamp"]
+                    .iter()
+                    .filter_map(|k| rule.get(k)?.as_str()),
+            );
+            if let Some(fields) = rule.get("fields").and_then(|f| f.as_array()) {
+                used.extend(fields.iter().filter_map(|f| f.as_str()));
+            }
+        }
+    }
+    // Conditions read `left.<attribute>` and `right.<attribute>`
+    used.extend(
+        expressions
+            .iter()
+            .flat_map(|e| e.names())
+            .map(|name| name.split_once('.').map_or(name, |(_, attribute)| attribute)),
+    );
+    if let Some(temporal) = spec.get("temporal") {
+        used.extend(
+            ["effective_from", "effective_to"]
+                .iter()
+                .filter_map(|k| temporal.get(k)?.as_str()),
+        );
+    }
+    if let Some(relationships) = spec.get("relationships").and_then(|r| r.as_array()) {
+        used.extend(
+            relationships
+                .iter()
+                .filter_map(|r| r.get("rules")?.as_array())
+                .flatten()
+                .filter_map(|rule| rule.get("field")?.as_str()),
+        );
+    }
+    if let Some(column) = spec
+        .get("execution")
+        .and_then(|e| e.get("delta_column"))
+        .and_then(|c| c.as_str())
+    {
+        used.push(column);
+    }
+    if let Some(tombstone) = spec
+        .get("deletion")
+        .and_then(|d| d.get("tombstone"))
+        .and_then(|t| t.as_str())
+    {
+        used.push(tombstone);
+    }
+    if let Some(collected_at) = spec
+        .get("privacy")
+        .and_then(|p| p.get("collected_at"))
+        .and_then(|c| c.as_str())
+    {
+        used.push(collected_at);
+    }
+
+    findings.extend(coverage(spec));
+    findings.extend(pii_protection(spec));
+    findings.extend(unmapped_tombstones(spec));
+
+    let mut reported: Vec<&str> = Vec::new();
+    if let Some(sources) = spec.get("sources").and_then(|s| s.as_array()) {
+        for (i, source) in sources.iter().enumerate() {
+            let Some(attrs) = source.get("attributes").and_then(|a| a.as_object()) else {
+                continue;
+            };
+            for attr in attrs.keys() {
+                if !used.contains(&attr.as_str()) && !reported.contains(&attr.as_str()) {
+                    reported.push(attr);
+                    findings.push(Diagnostic::info(
+                        codes::UNUSED_ATTRIBUTE,
+                        format!("sources[{}].attributes.{}", i, attr),
+                        format!(
+                            "Attribute '{}' is not used by any rule, blocking key or survivorship rule.",
+                            attr
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    findings
+}
+
+/// Attributes matched on (by rules and blocking keys) that only some
+/// sources provide: records from the others are never compared on them.
+fn coverage(spec: &Value) -> Vec<Diagnostic> {
+    let sources: Vec<(&str, Vec<&str>)> = spec
+        .get("sources")
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|source| {
+            let name = source.get("name")?.as_str()?;
+            let attrs = source.get("attributes")?.as_object()?;
+            Some((name, attrs.keys().map(String::as_str).collect()))
+        })
+        .collect();
+    if sources.len() < 2 {
+        return Vec::new();
+    }
+
+    let mut references: Vec<(String, &str, String)> = Vec::new();
+    if let Some(rules) = spec.get("rules").and_then(|r| r.as_array()) {
+        for (i, rule) in rules.iter().enumerate() {
+            if let Some(field) = rule.get("field").and_then(|f| f.as_str()) {
+                let name = rule.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+                references.push((format!("rules[{}].field", i), field, format!("Rule '{}'", name)));
+            }
+        }
+    }
+    for (path, field) in blocking_key_fields(spec) {
+        references.push((path, field, "Blocking key".to_string()));
+    }
+
+    let mut findings = Vec::new();
+    for (path, field, what) in references {
+        let (providers, missing): (Vec<_>, Vec<_>) = sources
+            .iter()
+            .partition(|(_, attrs)| attrs.contains(&field));
+        if providers.is_empty() || missing.is_empty() {
+            continue;
+        }
+        let names = |sources: &[&(&str, Vec<&str>)]| {
+            sources.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+        };
+        findings.push(Diagnostic::info(
+            codes::PARTIAL_COVERAGE,
+            path,
+            format!(
+                "{} uses '{}', which only {} of {} sources provide ({}); records from {} are never compared on it.",
+                what,
+                field,
+                providers.len(),
+                sources.len(),
+                names(&providers),
+                names(&missing)
+            ),
+        ));
+    }
+    findings
+}
+
+/// Attributes holding PII that nothing says how to mask, and PII kept for
+/// no set period.
+fn pii_protection(spec: &Value) -> Vec<Diagnostic> {
+    let pii = privacy::classify(spec);
+    let path = |attribute: &str| {
+        let sources = spec.get("sources").and_then(|s| s.as_array());
+        let i = sources
+            .into_iter()
+            .flatten()
+            .position(|s| s.get("attributes").and_then(|a| a.get(attribute)).is_some())
+            .unwrap_or_default();
+        format!("sources[{}].attributes.{}", i, attribute)
+    };
+
+    let mut findings = Vec::new();
+    for attribute in pii.iter().filter(|a| a.masking.is_none()) {
+        findings.push(
+            Diagnostic::warning(
+                codes::PII_WITHOUT_MASKING,
+                path(&attribute.attribute),
+                format!(
+                    "Attribute '{}' holds PII ({}) but has no masking.",
+                    attribute.attribute, attribute.kind
+                ),
+            )
+            .with_suggestion(format!(
+                "Set privacy.fields.{}.masking to one of: {}.",
+                attribute.attribute,
+                privacy::MASKINGS.join(", ")
+            )),
+        );
+    }
+    let unretained: Vec<&str> = pii
+        .iter()
+        .filter(|a| a.retention_days.is_none())
+        .map(|a| a.attribute.as_str())
+        .collect();
+    if let Some(first) = unretained.first() {
+        findings.push(
+            Diagnostic::warning(
+                codes::PII_WITHOUT_RETENTION,
+                path(first),
+                format!(
+                    "Attributes holding PII ({}) have no retention period.",
+                    unretained.join(", ")
+                ),
+            )
+            .with_suggestion("Set privacy.retention_days, or retention_days per attribute."),
+        );
+    }
+    findings
+}
+
+/// Sources that do not map the tombstone attribute, whose deletions are
+/// never seen.
+fn unmapped_tombstones(spec: &Value) -> Vec<Diagnostic> {
+    let Ok(Some(deletion)) = Deletion::from_spec(spec) else {
+        return Vec::new();
+    };
+    let sources = spec.get("sources").and_then(|s| s.as_array());
+    sources
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter(|(_, source)| {
+            source
+                .get("attributes")
+                .and_then(|a| a.get(&deletion.tombstone))
+                .is_none()
+        })
+        .map(|(i, source)| {
+            let name = source.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+            Diagnostic::warning(
+                codes::UNMAPPED_TOMBSTONE,
+                format!("sources[{}].attributes", i),
+                format!(
+                    "Source '{}' does not map tombstone '{}'; records deleted there are never seen as deleted.",
+                    name, deletion.tombstone
+                ),
+            )
+            .with_suggestion(format!(
+                "Map the source's soft-delete column to '{}'.",
+                deletion.tombstone
+            ))
+        })
+        .collect()
+}
+
+/// Each blocking key's path and the attribute it names.
+fn blocking_key_fields(spec: &Value) -> Vec<(String, &str)> {
+    let keys = spec
+        .get("blocking")
+        .and_then(|b| b.get("keys"))
+        .and_then(|k| k.as_array());
+    keys.into_iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(i, key)| {
+            if let Some(field) = key.as_str() {
+                return Some((format!("blocking.keys[{}]", i), field));
+            }
+            ["field", "name"].iter().find_map(|k| {
+                let field = key.get(k)?.as_str()?;
+                Some((format!("blocking.keys[{}].{}", i, k), field))
+            })
+        })
+        .collect()
+}
+
+/// Each blocking key's and the sorted-neighbourhood sort key's
+/// `transform`, with its path.
+fn blocking_transforms(spec: &Value) -> Vec<(String, &str)> {
+    let Some(blocking) = spec.get("blocking") else {
+        return Vec::new();
+    };
+    let keys = blocking.get("keys").and_then(|k| k.as_array());
+    let mut transforms: Vec<(String, &str)> = keys
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(i, key)| {
+            let transform = key.get("transform")?.as_str()?;
+            Some((format!("blocking.keys[{}].transform", i), transform))
+        })
+        .collect();
+    let window = blocking
+        .get("sorted_neighborhood")
+        .and_then(|w| w.get("transform"))
+        .and_then(|t| t.as_str());
+    if let Some(transform) = window {
+        transforms.push(("blocking.sorted_neighborhood.transform".to_string(), transform));
+    }
+    transforms
+}
+
+/// A declared attribute `field` may be a misspelling of.
+/// How to fix a reference to unknown `field`: name the canonical attribute
+/// a source column stands for, or a similar attribute.
+fn field_suggestion(
+    field: &str,
+    available_fields: &[String],
+    mappings: &Mappings,
+) -> Option<String> {
+    match mappings.alias_of(field) {
+        Some(canonical) => Some(format!(
+            "'{}' is a source column; refer to its attribute '{}'.",
+            field, canonical
+        )),
+        None => similar_field(field, available_fields).map(|f| format!("Did you mean '{}'?", f)),
+    }
+}
+
+fn similar_field<'a>(field: &str, available_fields: &'a [String]) -> Option<&'a str> {
+    available_fields
+        .iter()
+        .find(|f| f.contains(field) || field.contains(f.as_str()))
+        .map(String::as_str)
+}
+
+/// `check` run on each entity of a workspace, with paths under
+/// `entities[i]` and messages naming the entity.
+pub(crate) fn per_entity(
+    workspace: &Workspace,
+    check: impl Fn(&Value) -> Vec<Diagnostic>,
+) -> Vec<Diagnostic> {
+    let names = workspace.names();
+    let mut findings = Vec::new();
+    for (i, entity) in workspace.entities.iter().enumerate() {
+        let prefix = format!("entities[{}]", i);
+        let label = match names[i] {
+            "" => prefix.clone(),
+            name => name.to_string(),
+        };
+        findings.extend(check(entity).into_iter().map(|mut d| {
+            d.path = Some(match d.path.as_deref() {
+                Some("") | None => prefix.clone(),
+                Some(path) => format!("{}.{}", prefix, path),
+            });
+            d.message = format!("{}: {}", label, d.message);
+            d
+        }));
+    }
+    findings
+}
+
+/// Checks across the entities of a workspace.
+fn workspace_diagnostics(workspace: &Workspace) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for (i, name) in workspace.names().into_iter().enumerate() {
+        if name.is_empty() {
+            continue;
+        }
+        if seen.contains(&name) {
+            errors.push(Diagnostic::error(
+                codes::DUPLICATE_ENTITY,
+                format!("entities[{}].entity.name", i),
+                format!("Duplicate entity name: '{}'", name),
+            ));
+        } else {
+            seen.push(name);
+        }
+    }
+
+    // Relationships must link to an entity of the workspace, on its
+    // attributes; links within an entity are checked with the entity
+    let names = workspace.names();
+    for (i, entity) in workspace.entities.iter().enumerate() {
+        let Some(relationships) = entity.get("relationships").and_then(|r| r.as_array()) else {
+            continue;
+        };
+        for (j, relationship) in relationships.iter().enumerate() {
+            let Some(to) = relationship.get("to").and_then(|t| t.as_str()) else {
+                continue;
+            };
+            if to == names[i] {
+                continue;
+            }
+            let name = relationship
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or("unknown");
+            let path = format!("entities[{}].relationships[{}]", i, j);
+            let Ok(target) = workspace.select(Some(to)) else {
+                errors.push(
+                    Diagnostic::error(
+                        codes::INVALID_RELATIONSHIP,
+                        format!("{}.to", path),
+                        format!(
+                            "{}: Relationship '{}' links to '{}', which is not an entity of the workspace.",
+                            names[i], name, to
+                        ),
+                    )
+                    .with_suggestion(format!("Link to one of: {}.", names.join(", "))),
+                );
+                continue;
+            };
+            let target_fields = attributes(target);
+            let rules = relationship.get("rules").and_then(|r| r.as_array());
+            for (k, rule) in rules.into_iter().flatten().enumerate() {
+                let field = rule
+                    .get("target_field")
+                    .or_else(|| rule.get("field"))
+                    .and_then(|f| f.as_str());
+                let Some(field) = field else {
+                    continue;
+                };
+                if !target_fields.is_empty() && !target_fields.iter().any(|f| f == field) {
+                    let rule_name = rule.get("name").and_then(|n| n.as_str()).unwrap_or("unknown");
+                    errors.push(Diagnostic::error(
+                        codes::UNKNOWN_FIELD,
+                        format!("{}.rules[{}].target_field", path, k),
+                        format!(
+                            "{}: Relationship rule '{}' references field '{}', which {} does not have.",
+                            names[i], rule_name, field, to
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// The canonical attributes any source of `spec` declares.
+fn attributes(spec: &Value) -> Vec<String> {
+    let mut fields: Vec<String> = mappings::resolve(spec)
+        .get("sources")
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|source| source.get("attributes")?.as_object())
+        .flat_map(|attrs| attrs.keys().cloned())
+        .collect();
+    fields.sort_unstable();
+    fields.dedup();
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MINIMAL;
+    use crate::Severity;
+
+    #[test]
+    fn test_every_field_reference_resolves_against_sources() {
+        let findings = |yaml: &str| -> Vec<(String, Option<String>)> {
+            let spec = crate::parse_yaml(yaml).unwrap();
+            crate::semantic_diagnostics(&spec)
+                .into_iter()
+                .map(|d| (d.code, d.path))
+                .collect()
+        };
+        let finding = |code: &str, path: &str| (code.to_string(), Some(path.to_string()));
+
+        // Blocking keys, in either form, must name a declared attribute.
+        let blocked = format!(
+        "{}blocking:\n  keys:\n    - emial\n    - field: email_address\n      transform: lowercase\n",
+        MINIMAL
+    );
+        assert_eq!(
+            findings(&blocked),
+            [
+                finding("KNV0101", "blocking.keys[0]"),
+                finding("KNV0101", "blocking.keys[1].field"),
+            ]
+        );
+        let spec = crate::parse_yaml(&blocked).unwrap();
+        let suggestion = crate::semantic_diagnostics(&spec)[1].suggestion.clone();
+        assert_eq!(suggestion.as_deref(), Some("Did you mean 'email'?"));
+
+        // A rule comparing nothing would be left out of the plan.
+        let fieldless = MINIMAL.replace("    field: email\n", "");
+        assert_eq!(findings(&fieldless), [finding("KNV0001", "rules[0].field")]);
+
+        // Survivorship prioritizes declared sources.
+        let survivorship = format!(
+        "{}survivorship:\n  rules:\n    - field: email\n      strategy: source_priority\n      source_priority: [crm, web]\n",
+        MINIMAL
+    );
+        assert_eq!(
+            findings(&survivorship),
+            [finding(
+                "KNV0116",
+                "survivorship.rules[0].source_priority[1]"
+            )]
+        );
+
+        // Attributes matched on in only some sources are reported as info.
+        let two_sources = MINIMAL.replace(
+        "rules:",
+        "  - name: web\n    system: shopify\n    table: customers\n    id: customer_id\n    attributes:\n      phone: phone\nrules:",
+    );
+        let spec = crate::parse_yaml(&two_sources).unwrap();
+        let coverage: Vec<_> = crate::semantic_diagnostics(&spec)
+            .into_iter()
+            .filter(|d| d.code == "KNV0122")
+            .collect();
+        assert_eq!(coverage.len(), 1);
+        assert_eq!(coverage[0].severity, Severity::Info);
+        assert_eq!(coverage[0].path.as_deref(), Some("rules[0].field"));
+        assert!(coverage[0]
+            .message
+            .contains("only 1 of 2 sources provide (crm); records from web"));
+    }
 }
@@ -0,0 +1,317 @@
+//! Waivers: deliberate, documented suppression of findings.
+//!
+//! A finding can be waived with an annotation comment anywhere in the spec
+//!
+//! ```yaml
+//! # kanoniv-ignore: LOW_THRESHOLD reason="approved by risk team, ticket RISK-123" expires=2026-12-31
+//! ```
+//!
+//! or its short form, whose reason is the rest of the comment
+//!
+//! ```yaml
+//! # kanoniv:ignore SINGLE_SOURCE one CRM until the ERP migration
+//! ```
+//!
+//! or with an entry in a top-level `waivers:` section
+//!
+//! ```yaml
+//! waivers:
+//!   - code: LOW_THRESHOLD
+//!     reason: approved by risk team, ticket RISK-123
+//!     expires: 2026-12-31
+//! ```
+//!
+//! A waiver names a plan risk flag (`LOW_THRESHOLD`) or a diagnostic code
+//! (`KNV0106`, or its name `zero-weight-rule`) and applies to every finding
+//! with that code. A reason is required; an expired waiver stops applying.
+//! Errors cannot be waived. Waived findings are not dropped: they are
+//! reported separately, with the reason, in validation and plan output.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::clock::today;
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::parser::SourceMap;
+
+const ANNOTATION: &str = "kanoniv-ignore:";
+const SHORT_ANNOTATION: &str = "kanoniv:ignore ";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Waiver {
+    /// Risk flag or diagnostic code, normalized (`zero-weight-rule` -> `KNV0106`).
+    pub code: String,
+    pub reason: String,
+    /// Last day (`YYYY-MM-DD`) the waiver applies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    /// 1-based line of the annotation or `waivers` entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+/// A finding that a waiver suppressed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Waived {
+    pub code: String,
+    pub message: String,
+    pub waiver: Waiver,
+}
+
+/// The waivers declared by a spec.
+#[derive(Debug, Clone, Default)]
+pub struct Waivers {
+    /// Well-formed waivers that have not expired.
+    pub active: Vec<Waiver>,
+    /// Malformed and expired waivers.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Waivers {
+    /// Collect waivers from annotation comments in `source` and the
+    /// `waivers` section of the parsed `spec`, as of today (UTC).
+    pub fn collect(source: &str, spec: &Value) -> Self {
+        Self::collect_on(source, spec, &today())
+    }
+
+    /// As `collect`, with `today` given as `YYYY-MM-DD`.
+    pub fn collect_on(source: &str, spec: &Value, today: &str) -> Self {
+        let mut waivers = Waivers::default();
+
+        for (i, line) in source.lines().enumerate() {
+            let Some(comment) = line.find('#').map(|at| line[at + 1..].trim_start()) else {
+                continue;
+            };
+            let (code, fields) = if let Some(rest) = comment.strip_prefix(ANNOTATION) {
+                parse_annotation(rest)
+            } else if let Some(rest) = comment.strip_prefix(SHORT_ANNOTATION) {
+                parse_short_annotation(rest)
+            } else {
+                continue;
+            };
+            let field = |key: &str| {
+                fields
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.clone())
+            };
+            waivers.add(
+                code,
+                field("reason"),
+                field("expires"),
+                Some(i + 1),
+                "",
+                today,
+            );
+        }
+
+        if let Some(entries) = spec.get("waivers").and_then(|w| w.as_array()) {
+            let map = SourceMap::from_yaml(source);
+            for (i, entry) in entries.iter().enumerate() {
+                let path = format!("waivers[{}]", i);
+                let field = |key: &str| {
+                    entry
+                        .get(key)
+                        .and_then(|v| v.as_str())
+                        .map(|v| v.trim().to_string())
+                        .filter(|v| !v.is_empty())
+                };
+                let line = map.nearest(&format!("{}.code", path)).map(|s| s.line);
+                waivers.add(
+                    field("code").unwrap_or_default(),
+                    field("reason"),
+                    field("expires"),
+                    line,
+                    &path,
+                    today,
+                );
+            }
+        }
+
+        waivers
+    }
+
+    fn add(
+        &mut self,
+        code: String,
+        reason: Option<String>,
+        expires: Option<String>,
+        line: Option<usize>,
+        path: &str,
+        today: &str,
+    ) {
+        // Annotations have no YAML path; pin their findings to the line.
+        let finding = |diagnostic: Diagnostic| match line {
+            Some(line) if path.is_empty() => diagnostic.with_span(Span { line, column: 1 }),
+            _ => diagnostic,
+        };
+
+        if code.is_empty() {
+            self.diagnostics.push(finding(Diagnostic::error(
+                codes::INVALID_WAIVER,
+                path,
+                "Waiver does not name a finding code",
+            )));
+            return;
+        }
+        let code = codes::canonical(&code);
+        let Some(reason) = reason else {
+            self.diagnostics.push(finding(
+                Diagnostic::error(
+                    codes::INVALID_WAIVER,
+                    path,
+                    format!("Waiver for {} has no reason", code),
+                )
+                .with_suggestion(
+                    "Record why it was accepted, e.g. reason=\"approved, ticket RISK-123\".",
+                ),
+            ));
+            return;
+        };
+        if let Some(expires) = &expires {
+            if !is_date(expires) {
+                self.diagnostics.push(finding(Diagnostic::error(
+                    codes::INVALID_WAIVER,
+                    path,
+                    format!(
+                        "Waiver for {} has invalid expiry '{}' (expected YYYY-MM-DD)",
+                        code, expires
+                    ),
+                )));
+                return;
+            }
+            // ISO dates compare correctly as strings.
+            if expires.as_str() < today {
+                self.diagnostics.push(finding(Diagnostic::warning(
+                    codes::EXPIRED_WAIVER,
+                    path,
+                    format!("Waiver for {} expired on {}", code, expires),
+                )));
+                return;
+            }
+        }
+
+        self.active.push(Waiver {
+            code,
+            reason,
+            expires,
+            line,
+        });
+    }
+
+    /// The waiver covering findings with `code`, if any.
+    pub fn find(&self, code: &str) -> Option<&Waiver> {
+        self.active
+            .iter()
+            .find(|w| w.code.eq_ignore_ascii_case(code))
+    }
+
+    /// Add waiver problems to `diagnostics` and set aside the warnings and
+    /// info that a waiver covers.
+    pub fn apply(&self, diagnostics: Vec<Diagnostic>) -> (Vec<Diagnostic>, Vec<Waived>) {
+        let mut kept = Vec::new();
+        let mut waived = Vec::new();
+        for diagnostic in diagnostics
+            .into_iter()
+            .chain(self.diagnostics.iter().cloned())
+        {
+            match self.find(&diagnostic.code) {
+                Some(waiver) if !diagnostic.is_error() => waived.push(Waived {
+                    code: diagnostic.code.clone(),
+                    message: diagnostic.to_string(),
+                    waiver: waiver.clone(),
+                }),
+                _ => kept.push(diagnostic),
+            }
+        }
+        (kept, waived)
+    }
+}
+
+/// `CODE reason="..." expires=YYYY-MM-DD` -> code and `key=value` fields.
+fn parse_annotation(text: &str) -> (String, Vec<(String, String)>) {
+    let text = text.trim();
+    let (code, mut rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let mut fields = Vec::new();
+    while let Some((key, after)) = rest.trim_start().split_once('=') {
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+        };
+        fields.push((key.trim().to_string(), value.trim().to_string()));
+        rest = remaining;
+    }
+    fields.retain(|(_, v)| !v.is_empty());
+    (code.trim().to_string(), fields)
+}
+
+/// `CODE free-text reason`, or `CODE reason="..." ...` as in the long form.
+fn parse_short_annotation(text: &str) -> (String, Vec<(String, String)>) {
+    let text = text.trim();
+    let (code, reason) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let reason = reason.trim();
+    if reason.starts_with("reason=") {
+        return parse_annotation(text);
+    }
+    let mut fields = Vec::new();
+    if !reason.is_empty() {
+        fields.push(("reason".to_string(), reason.to_string()));
+    }
+    (code.to_string(), fields)
+}
+
+fn is_date(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('-').collect();
+    matches!(parts.as_slice(), [y, m, d]
+        if y.len() == 4 && m.len() == 2 && d.len() == 2
+            && [y, m, d].iter().all(|p| p.bytes().all(|b| b.is_ascii_digit()))
+            && (1..=12).contains(&m.parse::<u32>().unwrap_or(0))
+            && (1..=31).contains(&d.parse::<u32>().unwrap_or(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MINIMAL, PRIVACY};
+    use crate::{diagnose_yaml, Profile};
+
+    #[test]
+    fn test_waivers_suppress_findings_and_are_recorded() {
+        let yaml = MINIMAL.replace(
+        "    weight: 1.0\n",
+        "    weight: 0.0  # kanoniv-ignore: zero-weight-rule reason=\"kept for reports\"\n",
+    ) + "waivers:\n  - code: NO_BLOCKING\n    reason: tiny table\n    expires: 2999-12-31\n  - code: SINGLE_SOURCE\n    reason: one CRM\n    expires: 2000-01-01\n" + PRIVACY;
+
+        let spec = crate::parse_yaml(&yaml).unwrap();
+        let waivers = crate::Waivers::collect_on(&yaml, &spec, "2026-01-01");
+        let codes: Vec<&str> = waivers.active.iter().map(|w| w.code.as_str()).collect();
+        assert_eq!(codes, vec!["KNV0106", "NO_BLOCKING"]);
+        assert_eq!(waivers.active[0].line, Some(16));
+        assert_eq!(waivers.diagnostics[0].code, "KNV0110");
+
+        // The zero-weight warning is waived; the expired waiver is a warning
+        // itself, so strict validation fails on it.
+        let tiers = crate::validate_tiers(&yaml, Profile::Default);
+        assert!(tiers.is_valid(), "{:?}", tiers.errors);
+        assert_eq!(tiers.warnings.len(), 1);
+        assert_eq!(tiers.warnings[0].code, "KNV0110");
+        assert!(!crate::validate_tiers(&yaml, Profile::Strict).is_valid());
+        assert_eq!(tiers.waived.len(), 1);
+        assert_eq!(tiers.waived[0].waiver.reason, "kept for reports");
+
+        let plan = crate::generate_plan(&yaml).unwrap();
+        assert!(plan.risk_flags.iter().all(|f| f.code != "NO_BLOCKING"));
+        assert!(plan.risk_flags.iter().any(|f| f.code == "SINGLE_SOURCE"));
+        assert_eq!(plan.waived[0].code, "NO_BLOCKING");
+        assert!(plan.summary.contains("1 waived"));
+
+        // A waiver without a reason is an error and suppresses nothing.
+        let unexplained = MINIMAL.to_string() + PRIVACY + "waivers:\n  - code: NO_BLOCKING\n";
+        let diagnostics = diagnose_yaml(&unexplained);
+        assert_eq!(diagnostics[0].code, "KNV0109");
+        assert_eq!(diagnostics[0].path.as_deref(), Some("waivers[0]"));
+        assert!(crate::generate_plan(&unexplained)
+            .unwrap()
+            .waived
+            .is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+//! Watching spec files for `kanoniv watch`.
+//!
+//! `SpecWatcher` polls the spec files under a directory (see
+//! `compose::find_specs`), or a single file, for changes to their
+//! contents. Polling needs no platform notification API and sees edits on
+//! network and container mounts alike, and comparing contents rather than
+//! modification times catches edits within a coarse timestamp's
+//! resolution; specs are few and small, so it is cheap. Saving a file
+//! unchanged is not a change. Editors save in bursts (a temporary file, a
+//! rename, a second write), so `wait` returns only once the files have
+//! been quiet for a debounce interval.
+//!
+//! `DiagnosticDelta` compares two checks of a spec, so each re-check can
+//! report only what it found new and what the edit resolved.
+
+use anyhow::Result;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::compose;
+use crate::diagnostics::Diagnostic;
+
+/// A file's length and a hash of its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    len: u64,
+    hash: u64,
+}
+
+/// The spec files under a directory, as last seen.
+#[derive(Debug)]
+pub struct SpecWatcher {
+    root: PathBuf,
+    stamps: BTreeMap<PathBuf, Stamp>,
+}
+
+impl SpecWatcher {
+    /// Watch `root`: the spec files under it, or `root` itself if it is a
+    /// file. A file becoming a spec, or no longer one, is a change.
+    pub fn new(root: &Path) -> Result<Self> {
+        let mut watcher = SpecWatcher {
+            root: root.to_path_buf(),
+            stamps: BTreeMap::new(),
+        };
+        watcher.stamps = watcher.stamp()?;
+        Ok(watcher)
+    }
+
+    /// The spec files, in path order.
+    pub fn specs(&self) -> Vec<PathBuf> {
+        self.stamps.keys().cloned().collect()
+    }
+
+    /// Files added, changed or removed since the last scan, in path order.
+    pub fn scan(&mut self) -> Result<Vec<PathBuf>> {
+        let stamps = self.stamp()?;
+        let changed = self
+            .stamps
+            .keys()
+            .chain(stamps.keys())
+            .filter(|path| self.stamps.get(*path) != stamps.get(*path))
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        self.stamps = stamps;
+        Ok(changed.into_iter().collect())
+    }
+
+    /// Block until files change, polling every `interval`, and then until
+    /// none has changed for `debounce`. Returns every file changed since
+    /// the last scan.
+    pub fn wait(&mut self, interval: Duration, debounce: Duration) -> Result<Vec<PathBuf>> {
+        let mut changed = BTreeSet::new();
+        let mut quiet_since = Instant::now();
+        loop {
+            thread::sleep(interval);
+            let scanned = self.scan()?;
+            if !scanned.is_empty() {
+                changed.extend(scanned);
+                quiet_since = Instant::now();
+            } else if !changed.is_empty() && quiet_since.elapsed() >= debounce {
+                return Ok(changed.into_iter().collect());
+            }
+        }
+    }
+
+    fn stamp(&self) -> Result<BTreeMap<PathBuf, Stamp>> {
+        let specs = match self.root.is_file() {
+            true => vec![self.root.clone()],
+            false => compose::find_specs(&self.root)?,
+        };
+        Ok(specs
+            .into_iter()
+            .map(|path| {
+                let stamp = stamp_of(&path);
+                (path, stamp)
+            })
+            .collect())
+    }
+}
+
+/// A file removed between listing and reading stamps as empty; the next
+/// scan sees it gone.
+fn stamp_of(path: &Path) -> Stamp {
+    let contents = fs::read(path).unwrap_or_default();
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    Stamp {
+        len: contents.len() as u64,
+        hash: hasher.finish(),
+    }
+}
+
+/// How a spec's findings moved between two checks. Findings are the same
+/// if their code, path and message are; a finding that only moved lines
+/// because text above it changed is not new.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiagnosticDelta {
+    /// Findings of the second check the first did not have.
+    pub new: Vec<Diagnostic>,
+    /// Findings of the first check the second does not have.
+    pub resolved: Vec<Diagnostic>,
+    /// Findings both checks have.
+    pub unchanged: usize,
+}
+
+impl DiagnosticDelta {
+    pub fn between(before: &[Diagnostic], after: &[Diagnostic]) -> Self {
+        let new = missing(after, before);
+        DiagnosticDelta {
+            unchanged: after.len() - new.len(),
+            resolved: missing(before, after),
+            new,
+        }
+    }
+
+    /// Whether the findings are the same.
+    pub fn is_empty(&self) -> bool {
+        self.new.is_empty() && self.resolved.is_empty()
+    }
+}
+
+/// The findings of `from` that `other` has no match for, each match used
+/// once.
+fn missing(from: &[Diagnostic], other: &[Diagnostic]) -> Vec<Diagnostic> {
+    let key = |d: &Diagnostic| (d.code.clone(), d.path.clone(), d.message.clone());
+    let mut unmatched: Vec<_> = other.iter().map(key).collect();
+    let mut missing = Vec::new();
+    for diagnostic in from {
+        match unmatched.iter().position(|k| *k == key(diagnostic)) {
+            Some(i) => {
+                unmatched.swap_remove(i);
+            }
+            None => missing.push(diagnostic.clone()),
+        }
+    }
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::test_support::MINIMAL;
+    use std::time::Duration;
+
+    #[test]
+    fn test_spec_watcher_reports_changed_specs_and_new_findings() {
+        use crate::watch::{DiagnosticDelta, SpecWatcher};
+        use crate::Diagnostic;
+
+        let dir = tempfile::tempdir().unwrap();
+        let spec = dir.path().join("customer.yaml");
+        std::fs::write(&spec, MINIMAL).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join(".git/config.yaml"), "x: 1\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not a spec\n").unwrap();
+        std::fs::write(dir.path().join("ci.yml"), "on: push\njobs:\n  entity: {}\n").unwrap();
+        let mut watcher = SpecWatcher::new(dir.path()).unwrap();
+        assert_eq!(watcher.specs(), std::slice::from_ref(&spec));
+        assert!(watcher.scan().unwrap().is_empty());
+
+        std::fs::write(&spec, MINIMAL.replace("weight: 1.0", "weight: 7.0")).unwrap();
+        let nested = dir.path().join("teams/orders.yml");
+        std::fs::create_dir(dir.path().join("teams")).unwrap();
+        std::fs::write(&nested, MINIMAL).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "still not a spec\n").unwrap();
+        let changed = watcher
+            .wait(Duration::from_millis(10), Duration::from_millis(30))
+            .unwrap();
+        assert_eq!(changed, [spec.clone(), nested.clone()]);
+        std::fs::remove_file(&nested).unwrap();
+        assert_eq!(watcher.scan().unwrap(), [nested]);
+        assert_eq!(watcher.specs(), [spec]);
+
+        // A finding that only moved lines is neither new nor resolved.
+        let before = crate::diagnose_yaml(MINIMAL);
+        let after = crate::diagnose_yaml(&format!(
+            "# moved\n{}",
+            MINIMAL.replace("weight: 1.0", "weight: 7.0")
+        ));
+        let delta = DiagnosticDelta::between(&before, &after);
+        assert_eq!(delta.new.len(), 1, "{:?}", delta.new);
+        assert!(delta.new[0]
+            .message
+            .contains("weight 7 must be between 0 and 1"));
+        assert!(delta.resolved.is_empty() && delta.unchanged == before.len());
+        let fixed = DiagnosticDelta::between(&after, &before);
+        assert_eq!((fixed.new.len(), fixed.resolved.len()), (0, 1));
+        // Repeated findings are matched one for one.
+        let twice = [
+            before.clone(),
+            vec![Diagnostic::warning("KNV0001", "x", "again")],
+        ]
+        .concat();
+        let delta = DiagnosticDelta::between(&twice, &[twice.clone(), twice.clone()].concat());
+        assert_eq!(
+            (delta.new.len(), delta.unchanged),
+            (twice.len(), twice.len())
+        );
+        assert!(DiagnosticDelta::between(&twice, &twice).is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+//! Workspaces: several entity specs in one file or directory.
+//!
+//! A file of `---`-separated documents (or a directory of spec files) is a
+//! workspace when it holds more than one document. Each document is an
+//! entity spec, except `shared:` documents, whose definitions every entity
+//! spec is laid over as over a base (see `compose`):
+//!
+//! ```yaml
+//! shared:
+//!   api_version: kanoniv/v2
+//!   decision:
+//!     thresholds:
+//!       match: 0.9
+//! ---
+//! identity_version: customer_v1
+//! entity:
+//!   name: customer
+//! ...
+//! ---
+//! identity_version: household_v1
+//! entity:
+//!   name: household
+//! ...
+//! ```
+//!
+//! `parse_yaml` returns a workspace as `{"entities": [...], "shared": ...}`
+//! with every entity already composed, which validation checks entity by
+//! entity and across entities. Commands work on one entity, picked with
+//! `--entity` (or the only one).
+
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::compose::compose;
+use crate::format::format_merged;
+use crate::parser;
+
+/// A parsed workspace.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Workspace {
+    /// The `shared:` definitions, merged in document order.
+    pub shared: Option<Value>,
+    /// Entity specs, composed over `shared`, in document order.
+    pub entities: Vec<Value>,
+}
+
+/// Whether `spec` is a workspace rather than a single entity spec.
+pub fn is_workspace(spec: &Value) -> bool {
+    spec.get("entities").is_some_and(Value::is_array) && spec.get("entity").is_none()
+}
+
+impl Workspace {
+    /// Sort documents into shared definitions and entity specs.
+    pub fn from_documents(documents: Vec<Value>) -> Workspace {
+        let mut shared: Option<Value> = None;
+        let mut specs = Vec::new();
+        for document in documents {
+            match shared_definitions(&document) {
+                Some(definitions) => {
+                    shared = Some(match shared {
+                        Some(base) => compose(&base, definitions).0,
+                        None => definitions.clone(),
+                    });
+                }
+                None => specs.push(document),
+            }
+        }
+
+        let entities = specs
+            .into_iter()
+            .map(|spec| match &shared {
+                Some(base) => {
+                    let (mut composed, _) = compose(base, &spec);
+                    // `compose` drops the child's `extends`; the entity
+                    // still extends its base.
+                    if let (Some(extends), Value::Object(root)) =
+                        (spec.get("extends"), &mut composed)
+                    {
+                        root.insert("extends".to_string(), extends.clone());
+                    }
+                    composed
+                }
+                None => spec,
+            })
+            .collect();
+        Workspace { shared, entities }
+    }
+
+    /// The workspace `spec` holds, if it is one.
+    pub fn from_value(spec: &Value) -> Option<Workspace> {
+        if !is_workspace(spec) {
+            return None;
+        }
+        Some(Workspace {
+            shared: spec.get("shared").cloned(),
+            entities: spec["entities"].as_array().cloned().unwrap_or_default(),
+        })
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut value = json!({ "entities": self.entities });
+        if let Some(shared) = &self.shared {
+            value["shared"] = shared.clone();
+        }
+        value
+    }
+
+    /// Each entity's name, in order; empty where it has none.
+    pub fn names(&self) -> Vec<&str> {
+        self.entities.iter().map(entity_name).collect()
+    }
+
+    /// The entity called `name`, or the only one if `name` is `None`.
+    pub fn select(&self, name: Option<&str>) -> Result<&Value> {
+        let names = self.names();
+        match name {
+            Some(name) => match names.iter().position(|n| *n == name) {
+                Some(i) => Ok(&self.entities[i]),
+                None => bail!(
+                    "No entity '{}' in the workspace; it defines: {}",
+                    name,
+                    names.join(", ")
+                ),
+            },
+            None => match self.entities.as_slice() {
+                [entity] => Ok(entity),
+                [] => bail!("The workspace defines no entity, only shared definitions"),
+                _ => bail!(
+                    "The workspace defines {} entities ({}); select one with --entity",
+                    names.len(),
+                    names.join(", ")
+                ),
+            },
+        }
+    }
+}
+
+/// The spec for `entity` (or the only one) in `yaml`, as YAML: the entity's
+/// spec in canonical form if `yaml` is a workspace, else `yaml` itself.
+/// YAML that does not parse is returned as it is, for the caller to report.
+pub fn select_yaml(yaml: &str, entity: Option<&str>) -> Result<String> {
+    let Ok(spec) = parser::parse_yaml(yaml) else {
+        return Ok(yaml.to_string());
+    };
+    let Some(workspace) = Workspace::from_value(&spec) else {
+        let name = entity_name(&spec);
+        if let Some(entity) = entity.filter(|e| !name.is_empty() && *e != name) {
+            bail!("The spec defines entity '{}', not '{}'", name, entity);
+        }
+        return Ok(yaml.to_string());
+    };
+    let Value::Object(root) = workspace.select(entity)? else {
+        bail!("An entity spec must be a YAML mapping");
+    };
+    Ok(format_merged(root, HashMap::new()))
+}
+
+fn shared_definitions(document: &Value) -> Option<&Value> {
+    let root = document.as_object()?;
+    if root.len() == 1 {
+        root.get("shared")
+    } else {
+        None
+    }
+}
+
+fn entity_name(spec: &Value) -> &str {
+    spec.get("entity")
+        .and_then(|e| e.get("name"))
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MINIMAL, WORKSPACE};
+
+    #[test]
+    fn test_workspace_holds_several_entity_specs() {
+        use crate::workspace::{select_yaml, Workspace};
+
+        let spec = crate::parse_yaml(WORKSPACE).unwrap();
+        let workspace = Workspace::from_value(&spec).unwrap();
+        assert_eq!(workspace.names(), ["customer", "household"]);
+        // Shared definitions are laid under every entity.
+        for entity in &workspace.entities {
+            assert_eq!(entity["api_version"], "kanoniv/v2");
+            assert_eq!(entity["decision"]["thresholds"]["review"], 0.7);
+        }
+        assert!(crate::validate_yaml(WORKSPACE).unwrap().is_empty());
+        assert!(Workspace::from_value(&crate::parse_yaml(MINIMAL).unwrap()).is_none());
+
+        let household = select_yaml(WORKSPACE, Some("household")).unwrap();
+        let plan = crate::generate_plan(&household).unwrap();
+        assert_eq!(plan.entity, "household");
+        assert!(select_yaml(WORKSPACE, None)
+            .unwrap_err()
+            .to_string()
+            .contains("select one with --entity"));
+        assert!(select_yaml(MINIMAL, Some("household")).is_err());
+        assert_eq!(select_yaml(MINIMAL, Some("customer")).unwrap(), MINIMAL);
+        // Reading a workspace file takes the entity asked for.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("identity.yaml");
+        std::fs::write(&path, WORKSPACE).unwrap();
+        let variables = crate::interpolate::Variables::default();
+        assert_eq!(
+            crate::compose::read_spec(&path, Some("household"), &variables).unwrap(),
+            household
+        );
+        assert!(crate::compose::read_spec(&path, None, &variables).is_err());
+        assert_eq!(
+            crate::compose::read_workspace(&path, None, &variables).unwrap(),
+            WORKSPACE
+        );
+
+        // Entity names must be unique across the workspace.
+        let duplicated = WORKSPACE.replace("name: household", "name: customer");
+        let spec = crate::parse_yaml(&duplicated).unwrap();
+        let findings: Vec<(String, Option<String>)> = crate::semantic_diagnostics(&spec)
+            .into_iter()
+            .map(|d| (d.code, d.path))
+            .collect();
+        assert_eq!(
+            findings,
+            [(
+                "KNV0120".to_string(),
+                Some("entities[1].entity.name".to_string())
+            )]
+        );
+        // Findings within an entity are placed under it and name it.
+        let broken = WORKSPACE.replace("field: address", "field: street");
+        let errors = crate::validate_yaml(&broken).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("household: "), "{}", errors[0]);
+
+        // Each document is formatted on its own.
+        assert_eq!(crate::format_spec(WORKSPACE).unwrap(), WORKSPACE);
+    }
+}
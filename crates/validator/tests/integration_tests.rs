@@ -1,6 +1,20 @@
+// `Command::cargo_bin` is deprecated in newer assert_cmd releases.
+#![allow(deprecated)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 
+/// A kanoniv running in the background, killed when the test ends, even
+/// when it ends by failing.
+struct Spawned(std::process::Child);
+
+impl Drop for Spawned {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
 #[test]
 fn test_validate_minimal_success() {
     let mut cmd = Command::cargo_bin("kanoniv").unwrap();
@@ -34,11 +48,3057 @@ fn test_validate_unknown_field_failure() {
 }
 
 #[test]
-fn test_hash_success() {
+fn test_validate_rejects_unknown_blocking_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    let minimal = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    std::fs::write(&spec, format!("{}blocking:\n  keys: [emails]\n", minimal)).unwrap();
+
     let mut cmd = Command::cargo_bin("kanoniv").unwrap();
-    cmd.arg("hash").arg("tests/fixtures/valid/minimal.yaml");
+    cmd.arg("validate").arg(&spec);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Blocking key references unknown field 'emails'. Did you mean 'email'?",
+        ));
+}
+
+#[test]
+fn test_validate_rejects_string_similarity_on_a_date() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    let typed = std::fs::read_to_string("tests/fixtures/valid/typed.yaml").unwrap();
+    std::fs::write(
+        &spec,
+        typed.replace(
+            "    type: exact\n    field: dob\n",
+            "    type: fuzzy\n    field: dob\n    algorithm: jaro_winkler\n",
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&spec);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Rule 'dob_exact' compares date attribute 'dob' with string algorithm 'jaro_winkler'.",
+        ));
+}
+
+#[test]
+fn test_validate_points_column_references_at_their_attribute() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    let mappings = std::fs::read_to_string("tests/fixtures/valid/mappings.yaml").unwrap();
+    std::fs::write(&spec, mappings.replace("    field: phone\n", "    field: mobile\n")).unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&spec);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Rule 'phone_exact' references unknown field 'mobile'. 'mobile' is a source column; refer to its attribute 'phone'.",
+        ));
+}
+
+#[test]
+fn test_validate_rejects_unknown_normalizer() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    let minimal = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    std::fs::write(
+        &spec,
+        format!("{}normalization:\n  fields:\n    email: [nfkc, lowercase]\n", minimal),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&spec);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown normalizer 'lowercase' for field 'email'"));
+}
+
+#[test]
+fn test_validate_rejects_a_regex_replace_pattern_that_does_not_compile() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    let minimal = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    std::fs::write(
+        &spec,
+        format!(
+            "{}normalization:\n  fields:\n    email:\n      - trim\n      - regex_replace: {{ pattern: '[a-z', replacement: '' }}\n",
+            minimal
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&spec);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid regex_replace pattern '[a-z': unclosed character class",
+        ));
+}
+
+#[test]
+fn test_spec_values_interpolated_from_params_and_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    let yaml = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml")
+        .unwrap()
+        .replace("table: contacts", "table: \"{{ params.schema }}.contacts\"")
+        .replace("match: 0.9", "match: ${KNV_MATCH}");
+    std::fs::write(&spec, yaml).unwrap();
+    let env_file = dir.path().join("prod.env");
+    std::fs::write(&env_file, "KNV_MATCH=0.95\n").unwrap();
 
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&spec);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("undefined parameter params.schema"))
+        .stderr(predicate::str::contains("undefined variable ${KNV_MATCH}"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("compile")
+        .arg(&spec)
+        .args(["--param", "schema=crm_prod", "--env-file"])
+        .arg(&env_file);
     cmd.assert()
         .success()
-        .stdout(predicate::str::starts_with("sha256:"));
+        .stdout(predicate::str::contains("\"table\": \"crm_prod.contacts\""))
+        .stdout(predicate::str::contains("\"match\": 0.95"));
+    // The environment wins over the env file.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("compile")
+        .arg(&spec)
+        .args(["--param", "schema=crm_dev", "--env-file"])
+        .arg(&env_file)
+        .env("KNV_MATCH", "0.8");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"match\": 0.8"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("fmt").arg("--check").arg(&spec);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_commands_select_an_entity_from_a_workspace() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "tests/fixtures/valid/workspace.yaml"]);
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/workspace.yaml"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("2 entities (customer, household)"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "plan", "tests/fixtures/valid/workspace.yaml"])
+        .args(["--entity", "household"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Identity:     household (household_v1)"));
+
+    // A directory is a workspace of its spec files.
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::copy("tests/fixtures/valid/workspace.yaml", dir.path().join("a.yaml")).unwrap();
+    std::fs::copy("tests/fixtures/valid/minimal.yaml", dir.path().join("b.yml")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Duplicate entity name: 'customer'"));
+    std::fs::remove_file(dir.path().join("a.yaml")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("hash")
+        .arg(dir.path())
+        .args(["--entity", "customer"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["hash", "tests/fixtures/valid/minimal.yaml"]);
+    cmd.assert()
+        .success()
+        .stdout(String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn test_plan_resolves_relationships_between_entities() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "tests/fixtures/valid/relationships.yaml"]);
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "-v", "plan", "tests/fixtures/valid/relationships.yaml"])
+        .args(["--entity", "person"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Links:        member_of -> household (many_to_one)"))
+        .stdout(predicate::str::contains("8. Resolve relationship member_of: Link person to household"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("kanoniv.yml");
+    let yaml = std::fs::read_to_string("tests/fixtures/valid/relationships.yaml").unwrap();
+    std::fs::write(&spec, yaml.replace("to: household", "to: family")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&spec);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "person: Relationship 'member_of' links to 'family', which is not an entity of the workspace",
+        ));
+}
+
+#[test]
+fn test_plan_and_validate_from_ir() {
+    let dir = tempfile::tempdir().unwrap();
+    let ir = dir.path().join("ir.json");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["compile", "tests/fixtures/valid/minimal.yaml", "-o"])
+        .arg(&ir);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "plan", "--from-ir"]).arg(&ir);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Identity:     customer (retail_v1.0)"))
+        .stdout(predicate::str::contains("NO_BLOCKING"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "--from-ir"]).arg(&ir);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("is valid"));
+
+    let edited = std::fs::read_to_string(&ir).unwrap().replace("\"weight\": 1.0", "\"weight\": 0.5");
+    std::fs::write(&ir, edited).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "--from-ir"]).arg(&ir);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("KNV0902"));
+}
+
+#[test]
+fn test_ir_schema_and_compatibility_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let (old, new) = (dir.path().join("old.ir.json"), dir.path().join("new.ir.json"));
+    for (spec, ir) in [("tests/fixtures/valid/minimal.yaml", &old), ("tests/fixtures/valid/typed.yaml", &new)] {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["compile", spec, "-o"]).arg(ir);
+        cmd.assert().success();
+    }
+    assert!(std::fs::read_to_string(&old).unwrap().contains("\"ir_version\": \"1.0\""));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["ir", "schema"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"$id\": \"https://oss.kanoniv.com/schema/ir.json\""));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "ir", "check"]).arg(&old).arg(&new);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("IR 1.0 -> 1.0"))
+        .stdout(predicate::str::contains("sources[].types: new object"));
+
+    let retyped = std::fs::read_to_string(&new).unwrap().replace("\"ir_version\": \"1.0\"", "\"ir_version\": \"2.0\"");
+    std::fs::write(&new, retyped).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["ir", "check", "-f", "json"]).arg(&old).arg(&new);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("\"compatible\": false"))
+        .stderr(predicate::str::contains("1 breaking change(s)"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "--from-ir"]).arg(&new);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Cannot read IR version 2.0"));
+}
+
+#[test]
+fn test_decompile_reconstructs_a_spec() {
+    let dir = tempfile::tempdir().unwrap();
+    let (ir, spec) = (dir.path().join("plan.json"), dir.path().join("customer.yaml"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["compile", "conformance/multi_source/spec.yaml", "-o"])
+        .arg(&ir);
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("decompile").arg(&ir);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("# Decompiled from IR 1.0 (plan hash sha256:8a0fd33db57938a1"))
+        .stdout(predicate::str::contains("      source_priority: [crm, billing, support]\n"))
+        .stderr(predicate::str::is_empty());
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "decompile", "-o"]).arg(&spec).arg(&ir);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["compile", "-o"])
+        .arg(dir.path().join("again.json"))
+        .arg(&spec);
+    cmd.assert().success();
+    let plan_hash = |path: &std::path::Path| {
+        let ir: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        ir["plan_hash"].clone()
+    };
+    assert_eq!(plan_hash(&ir), plan_hash(&dir.path().join("again.json")));
+}
+
+#[test]
+fn test_compile_writes_ir_as_cbor() {
+    let dir = tempfile::tempdir().unwrap();
+    let (json, cbor) = (dir.path().join("plan.json"), dir.path().join("plan.cbor"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["compile", "tests/fixtures/execution/customer.yaml", "-o"])
+        .arg(&json);
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["compile", "tests/fixtures/execution/customer.yaml", "--format", "cbor", "-o"])
+        .arg(&cbor);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("digest sha256:"));
+    assert!(std::fs::metadata(&cbor).unwrap().len() * 3 < std::fs::metadata(&json).unwrap().len() * 2);
+
+    // Every encoding of the IR has the same digest, and reads as IR.
+    let digest = |path: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["ir", "digest"]).arg(path);
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert!(digest(&cbor).starts_with("sha256:"));
+    assert_eq!(digest(&cbor), digest(&json));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["compile", "tests/fixtures/execution/customer.yaml", "--format", "cbor-base64"]);
+    let base64 = cmd.output().unwrap();
+    let encoded = dir.path().join("plan.b64");
+    std::fs::write(&encoded, &base64.stdout).unwrap();
+    assert_eq!(digest(&encoded), digest(&json));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "plan", "--from-ir"]).arg(&cbor);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Sources:      2 (crm, billing)"))
+        .stderr(predicate::str::contains("edited after compiling").not());
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["compile", "tests/fixtures/execution/customer.yaml", "--target", "sql", "--format", "cbor"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--format cbor applies only to --target ir"));
+}
+
+#[test]
+fn test_watch_reports_new_and_resolved_findings() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("customer.yaml");
+    let valid = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    std::fs::write(&spec, &valid).unwrap();
+    let mut watch = Spawned(
+        std::process::Command::new(assert_cmd::cargo::cargo_bin("kanoniv"))
+            .args(["--plain", "watch", "--plan", "--interval", "0.05", "--debounce", "0.1"])
+            .arg(dir.path())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+    let (send, lines) = mpsc::channel();
+    let stdout = BufReader::new(watch.0.stdout.take().unwrap());
+    let stderr = BufReader::new(watch.0.stderr.take().unwrap());
+    for stream in [Box::new(stdout) as Box<dyn BufRead + Send>, Box::new(stderr)] {
+        let send = send.clone();
+        std::thread::spawn(move || stream.lines().map_while(Result::ok).for_each(|line| drop(send.send(line))));
+    }
+    // Lines up to those containing each of `until`; stdout and stderr are
+    // read apart, so their lines may interleave out of order.
+    let read_until = |until: &[&str]| {
+        let mut read = Vec::new();
+        while let Ok(line) = lines.recv_timeout(Duration::from_secs(20)) {
+            read.push(line);
+            if until.iter().all(|u| read.iter().any(|line| line.contains(u))) {
+                return read.join("\n");
+            }
+        }
+        panic!("no {:?} in:\n{}", until, read.join("\n"));
+    };
+
+    read_until(&["Watching 1 spec(s)", "customer.yaml is valid", "[NO_BLOCKING] critical risk"]);
+    std::fs::write(&spec, valid.replace("weight: 1.0", "weight: 7.0")).unwrap();
+    read_until(&["1 file(s) changed", "customer.yaml has 1 error(s)", "weight 7 must be between 0 and 1"]);
+    std::fs::write(&spec, &valid).unwrap();
+    let fixed = read_until(&["resolved [KNV0004]", "customer.yaml is valid"]);
+    // The plan's risk flags did not move, so they are not repeated.
+    assert!(fixed.contains("customer.yaml is valid") && !fixed.contains("NO_BLOCKING"), "{}", fixed);
+    drop(watch);
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "watch", "--interval", "inf"])
+        .arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid interval inf: expected a finite number of seconds"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "watch", "--debounce", "1e30"])
+        .arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid debounce 1000000000000000000000000000000: too long"));
+}
+
+#[test]
+fn test_check_reports_each_spec_and_fails_on_cross_spec_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let specs = dir.path().join("specs");
+    std::fs::create_dir(&specs).unwrap();
+    let minimal = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    std::fs::write(specs.join("customer.yaml"), &minimal).unwrap();
+    std::fs::write(specs.join("household.yaml"), minimal.replace("name: customer", "name: household").replace("retail_v1.0", "household_v1")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "check"]).arg(dir.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[ok] specs/customer.yaml  customer"))
+        .stdout(predicate::str::contains("Checked 2 spec file(s), 2 entities: 0 invalid, 0 error(s), 4 warning(s)"));
+
+    std::fs::write(specs.join("orders.yaml"), minimal.replace("weight: 1.0", "weight: 0.8")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "check"]).arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[fail] specs/orders.yaml  customer: 2 error(s)"))
+        .stderr(predicate::str::contains("[KNV0120] specs/orders.yaml:4:3: Entity 'customer' is also defined in specs/customer.yaml"))
+        .stderr(predicate::str::contains("2 error(s) across the specs under"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["check", "--format", "json"]).arg(dir.path());
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["errors"], 2);
+    assert_eq!(report["files"][2]["status"], "invalid");
+    assert_eq!(report["files"][2]["errors"][1]["code"], "KNV0134");
+}
+
+#[test]
+fn test_check_validates_specs_across_jobs() {
+    let dir = tempfile::tempdir().unwrap();
+    let minimal = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    for i in 0..12 {
+        let spec = minimal.replace("name: customer", &format!("name: entity_{}", i)).replace("retail_v1.0", &format!("v{}", i));
+        std::fs::write(dir.path().join(format!("spec_{:02}.yaml", i)), spec).unwrap();
+    }
+    let check = |jobs: &str| {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["check", "--format", "json", "--no-cache", "--jobs", jobs])
+            .arg(dir.path());
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let serial = check("1");
+    assert_eq!(check("4"), serial);
+    let report: serde_json::Value = serde_json::from_str(&serial).unwrap();
+    assert_eq!(report["entities"], 12);
+    assert_eq!(report["files"][11]["path"], "spec_11.yaml");
+}
+
+#[test]
+fn test_validate_check_and_plan_reuse_cached_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+    let minimal = std::fs::read_to_string("tests/fixtures/valid/minimal.yaml").unwrap();
+    let spec = dir.path().join("customer.yaml");
+    std::fs::write(&spec, &minimal).unwrap();
+    std::fs::write(dir.path().join("household.yaml"), minimal.replace("name: customer", "name: household").replace("retail_v1.0", "household_v1")).unwrap();
+    let kanoniv = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.env("KANONIV_CACHE_DIR", cache.path()).arg("--plain").args(args);
+        cmd
+    };
+    let dir_arg = dir.path().to_str().unwrap();
+    kanoniv(&["check", dir_arg])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("- cache: 0 unchanged, 2 validated"));
+    kanoniv(&["check", dir_arg])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("- cache: 2 unchanged, 0 validated"));
+    kanoniv(&["check", dir_arg, "--no-cache"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cache:").not());
+
+    let spec_arg = spec.to_str().unwrap();
+    for command in ["validate", "plan"] {
+        let cached = match command {
+            "validate" => "Unchanged since last validated",
+            _ => "Unchanged since last planned",
+        };
+        let first = kanoniv(&["-v", command, spec_arg]).output().unwrap();
+        assert!(first.status.success());
+        let second = kanoniv(&["-v", command, spec_arg]).output().unwrap();
+        let (first, second) = (String::from_utf8(first.stdout).unwrap(), String::from_utf8(second.stdout).unwrap());
+        assert!(!first.contains(cached) && second.contains(cached), "{}", second);
+        // Otherwise as validated or planned afresh.
+        assert_eq!(second.lines().filter(|l| !l.contains(cached)).collect::<Vec<_>>(), first.lines().collect::<Vec<_>>());
+        kanoniv(&["-v", command, spec_arg, "--no-cache"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(cached).not());
+    }
+    // A cached plan still answers to the deadline.
+    kanoniv(&["plan", spec_arg, "--timeout", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("timed out"));
+    for timeout in ["inf", "1e30"] {
+        kanoniv(&["plan", spec_arg, "--timeout", timeout])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid timeout"))
+            .stderr(predicate::str::contains("panicked").not());
+    }
+    kanoniv(&["execute", "tests/fixtures/execution/customer.yaml", "--records", "tests/fixtures/execution/records.json", "--timeout", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("timed out"));
+}
+
+#[test]
+fn test_execute_runs_a_plan_over_records() {
+    let records = "tests/fixtures/execution/records.json";
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", "tests/fixtures/execution/customer.yaml", "--records", records]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("entity_id,deleted,email,first_name,last_name,phone\n"))
+        .stdout(predicate::str::contains("billing:10,false,ann@example.com,Ann,Lee,555-0101\n"))
+        .stderr(predicate::str::contains("9 records -> 5 candidate pairs -> 6 entities"))
+        .stderr(predicate::str::contains("3 match, 1 review, 1 reject"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let ir = dir.path().join("customer.ir.json");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["compile", "tests/fixtures/execution/customer.yaml", "-o"])
+        .arg(&ir);
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["execute", "-f", "json", "--records", records, "--from-ir"])
+        .arg(&ir);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"candidate_pairs\": 5"))
+        .stdout(predicate::str::contains("\"cluster_id\": \"billing:10\""));
+
+    let unknown = dir.path().join("records.json");
+    std::fs::write(&unknown, r#"{"erp": [{"id": 1}]}"#).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["execute", "--from-ir"])
+        .arg(&ir)
+        .arg("--records")
+        .arg(&unknown);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Records given for unknown source 'erp'"));
+
+    // The same records stacked in one CSV with a source_name column.
+    let stacked = dir.path().join("records.csv");
+    std::fs::write(
+        &stacked,
+        "source_name,contact_id,email_address,mobile,given_name,family_name,customer_id,email,name_last,deleted\n\
+         crm,1,Ann@Example.com,555-0101,Ann,Lee,,,,\n\
+         crm,2,bob@example.com,555-0202,Bob,Garcia,,,,\n\
+         crm,3,,555-0303,Cy,Nguyen,,,,\n\
+         crm,4,ANN@example.com ,555-0101,Ann,Lee ,,,,\n\
+         crm,5,cy@example.com,555-0303,Cyrus,Nguyen,,,,\n\
+         billing,,,,,,10,ann@example.com,Lee,false\n\
+         billing,,,,,,11,bob@example.com,Okafor,\n\
+         billing,,,,,,12,cy@example.com,Nguyen,true\n\
+         billing,,,,,,13, dee@example.com ,Park,0\n",
+    )
+    .unwrap();
+    let entities = |records: &std::path::Path| {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["execute", "--from-ir"])
+            .arg(&ir)
+            .arg("--records")
+            .arg(records);
+        let output = cmd.output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        output.stdout
+    };
+    assert_eq!(entities(&stacked), entities(std::path::Path::new(records)));
+}
+
+#[test]
+fn test_execute_duckdb_backend_writes_the_same_entities() {
+    let spec = "tests/fixtures/execution/customer.yaml";
+    let records = "tests/fixtures/execution/records.json";
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", spec, "--records", records]);
+    let memory = cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "run", spec, "--records", records, "--backend", "duckdb"]);
+    if cfg!(feature = "duckdb") {
+        cmd.assert()
+            .success()
+            .stdout(memory.get_output().stdout.clone())
+            .stderr(predicate::str::contains("9 records -> 5 candidate pairs -> 6 entities"));
+    } else {
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("kanoniv was built without the `duckdb` feature"));
+    }
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", spec, "--records", records, "--backend", "sqlite"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown execution backend 'sqlite'. Use memory, duckdb"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", spec, "--records", records, "--backend", "duckdb", "--distributed", "127.0.0.1:0", "--tls-cert", "cert.pem", "--tls-key", "key.pem"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_execute_distributed_has_workers_cluster_the_partitions() {
+    use std::io::Read;
+    use std::process::Stdio;
+    use std::thread::JoinHandle;
+
+    fn drain(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+        std::thread::spawn(move || {
+            let mut read = Vec::new();
+            pipe.read_to_end(&mut read).unwrap();
+            read
+        })
+    }
+
+    let spec = "tests/fixtures/execution/customer.yaml";
+    let records = "tests/fixtures/execution/records.json";
+    let dir = tempfile::tempdir().unwrap();
+    let (cert, key, stranger) = (dir.path().join("cert.pem"), dir.path().join("key.pem"), dir.path().join("stranger.pem"));
+    let certified = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+    std::fs::write(&cert, certified.cert.pem()).unwrap();
+    std::fs::write(&key, certified.key_pair.serialize_pem()).unwrap();
+    let other = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+    std::fs::write(&stranger, other.cert.pem()).unwrap();
+    let (cert, key, stranger) = (cert.to_str().unwrap(), key.to_str().unwrap(), stranger.to_str().unwrap());
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", spec, "--records", records]);
+    let local = cmd.assert().success();
+    let address = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let mut coordinator = Spawned(
+        std::process::Command::new(assert_cmd::cargo::cargo_bin("kanoniv"))
+            .args(["--plain", "run", spec, "--records", records, "--distributed", &address, "--tls-cert", cert, "--tls-key", key, "--partitions", "3", "--timeout", "60"])
+            .env("KANONIV_WORKER_SECRET", "s3cret")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+    let stdout = drain(coordinator.0.stdout.take().unwrap());
+    let stderr = drain(coordinator.0.stderr.take().unwrap());
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "worker", &address, "--tls-ca", cert, "--secret", "guess"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("turned this worker away; check the shared secret"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "worker", &address, "--tls-ca", stranger, "--secret", "s3cret"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to reach a coordinator"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "worker", &address, "--tls-ca", cert])
+        .env("KANONIV_WORKER_SECRET", "s3cret");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Clustered: 8 records, 5 candidate pairs in 3 partition(s) for sha256:"));
+    assert!(coordinator.0.wait().unwrap().success());
+    assert_eq!(stdout.join().unwrap(), local.get_output().stdout);
+    let summary = String::from_utf8(stderr.join().unwrap()).unwrap();
+    assert!(summary.contains(&format!("Waiting for workers: kanoniv worker {}", address)));
+    assert!(summary.contains("9 records -> 5 candidate pairs -> 6 entities"));
+    assert!(summary.contains("clustered in 3 partition(s) by 1 worker(s)"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "worker", &address, "--tls-ca", cert, "--wait", "0", "--secret", "s3cret"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to reach a coordinator"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", spec, "--records", records, "--partitions", "3"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--distributed"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", spec, "--records", records, "--distributed", "127.0.0.1:0"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--tls-cert"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", spec, "--records", records, "--distributed", "127.0.0.1:0", "--tls-cert", cert, "--tls-key", cert]);
+    cmd.env("KANONIV_WORKER_SECRET", "s3cret");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid TLS certificate or key"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", spec, "--records", records, "--distributed", "127.0.0.1:0", "--tls-cert", cert, "--tls-key", key])
+        .env_remove("KANONIV_WORKER_SECRET");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("needs a shared secret"));
+}
+
+#[test]
+fn test_spec_tests_pass_and_report_unmet_expectations() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "test", "tests/fixtures/spec_tests"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("customer.yaml\n"))
+        .stdout(predicate::str::contains("[ok] ann - Ann's CRM and billing records are one customer"))
+        .stdout(predicate::str::contains("2 of 2 test(s) passed"));
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::copy("tests/fixtures/spec_tests/customer.yaml", dir.path().join("customer.yaml")).unwrap();
+    std::fs::create_dir(dir.path().join("tests")).unwrap();
+    let bob = std::fs::read_to_string("tests/fixtures/spec_tests/tests/bob.yaml").unwrap();
+    std::fs::write(dir.path().join("tests/bob.yaml"), bob.replace("expect: review", "expect: merge").replace("last_name: billing:11", "last_name: crm:2")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "test"])
+        .arg(dir.path().join("customer.yaml"));
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[fail] bob"))
+        .stderr(predicate::str::contains("-> crm:2 ~ billing:11: expected merge, got review (score 0.588)"))
+        .stderr(predicate::str::contains("-> billing:11 last_name: expected from 'crm:2', got from 'billing:11'"))
+        .stderr(predicate::str::contains("1 spec test(s) failed"));
+
+    // A second spec beside the first leaves a test naming neither ambiguous.
+    std::fs::copy("tests/fixtures/spec_tests/customer.yaml", dir.path().join("other.yaml")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["test"]).arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("holds 2 specs; name the one tested with `spec`"));
+    let named = std::fs::read_to_string(dir.path().join("tests/bob.yaml")).unwrap();
+    std::fs::write(dir.path().join("tests/bob.yaml"), format!("spec: other.yaml\n{}", named)).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["test", "-f", "json"]).arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("\"spec\": \"other.yaml\""))
+        .stdout(predicate::str::contains("\"subject\": \"crm:2 ~ billing:11\""));
+}
+
+#[test]
+fn test_generate_data_writes_records_and_ground_truth() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = "tests/fixtures/execution/customer.yaml";
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "generate-data", "--spec", spec, "--rows", "500", "--duplicate-rate", "0.2", "-o"])
+        .arg(dir.path());
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Generated: 500 records of 400 entities (100 duplicates, seed 0)"))
+        .stdout(predicate::str::contains("crm.csv"))
+        .stdout(predicate::str::contains("billing.csv"));
+    let labels = std::fs::read_to_string(dir.path().join("labels.csv")).unwrap();
+    assert_eq!(labels.lines().count(), 501);
+    let crm = std::fs::read_to_string(dir.path().join("crm.csv")).unwrap();
+    assert!(crm.starts_with("contact_id,email_address,given_name,family_name,mobile\n"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "execute", spec, "-o"])
+        .arg(dir.path().join("entities.csv"))
+        .arg("--records")
+        .arg(dir.path().join("records.json"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Executed: 500 records"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["generate-data", "--spec", spec, "--duplicate-rate", "1.5", "-o"])
+        .arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("duplicate rate must be at least 0 and below 1, not 1.5"));
+}
+
+#[test]
+fn test_evaluate_scores_generated_data_against_its_labels() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = "tests/fixtures/execution/customer.yaml";
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "generate-data", "--spec", spec, "--rows", "300", "--seed", "3", "-o"])
+        .arg(dir.path());
+    cmd.assert().success();
+
+    let evaluate = || {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["--plain", "evaluate", spec, "--truth"])
+            .arg(dir.path().join("labels.csv"))
+            .arg("--data")
+            .arg(dir.path().join("records.json"));
+        cmd
+    };
+    evaluate()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Evaluated: 300 records: 240 true entities ->"))
+        .stdout(predicate::str::contains("Pairwise: precision"))
+        .stdout(predicate::str::contains("Clusters: ARI"))
+        .stdout(predicate::str::contains("last_name_fuzzy (last_name): mean similarity"));
+
+    let output = evaluate().args(["-f", "json"]).output().unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["records"], 300);
+    let precision = report["pairwise"]["precision"].as_f64().unwrap();
+    assert!(precision > 0.0 && precision <= 1.0);
+    assert_eq!(report["rules"].as_array().unwrap().len(), 3);
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["evaluate", spec, "--truth"])
+        .arg(dir.path().join("crm.csv"))
+        .arg("--data")
+        .arg(dir.path().join("records.json"));
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("record_key"));
+}
+
+#[test]
+fn test_bench_suite_reports_and_compares_with_a_baseline() {
+    let dir = tempfile::tempdir().unwrap();
+    let bench = || {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["--plain", "bench-suite"]);
+        cmd
+    };
+    bench()
+        .args(["restaurants", "census", "--rows", "200"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "restaurants: 36 records: precision 1.000, recall 0.833, F1 0.909",
+        ))
+        .stdout(predicate::str::contains("census: 200 records: precision"));
+
+    let output = bench().args(["restaurants", "-f", "json"]).output().unwrap();
+    assert!(output.status.success());
+    let mut report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["passed"], true);
+    assert_eq!(report["results"][0]["dataset"], "restaurants");
+    let baseline = dir.path().join("baseline.json");
+    std::fs::write(&baseline, serde_json::to_string(&report).unwrap()).unwrap();
+    bench()
+        .arg("restaurants")
+        .arg("--baseline")
+        .arg(&baseline)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("F1 0.909 (+0.000 vs baseline)"));
+
+    // A better baseline F1 than the spec reaches fails the run.
+    report["results"][0]["f1"] = serde_json::json!(1.0);
+    std::fs::write(&baseline, serde_json::to_string(&report).unwrap()).unwrap();
+    bench()
+        .arg("restaurants")
+        .arg("--baseline")
+        .arg(&baseline)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("restaurants: f1 fell from 1.000 to 0.909"))
+        .stderr(predicate::str::contains("1 metric(s) fell more than 0.01 below the baseline"));
+
+    // A dataset directory without a spec of its own runs the one given.
+    let dataset = dir.path().join("customers");
+    std::fs::create_dir(&dataset).unwrap();
+    std::fs::copy("tests/fixtures/execution/records.json", dataset.join("records.json")).unwrap();
+    std::fs::write(
+        dataset.join("truth.csv"),
+        "record_key,cluster_id\n\
+         crm:1,a\ncrm:2,b\ncrm:3,c\ncrm:4,a\ncrm:5,c\n\
+         billing:10,a\nbilling:11,b\nbilling:13,d\n",
+    )
+    .unwrap();
+    bench()
+        .arg(&dataset)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Dataset 'customers' has no spec.yaml"));
+    bench()
+        .arg(&dataset)
+        .args(["--spec", "tests/fixtures/execution/customer.yaml"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("customers: 8 records: precision"));
+
+    bench()
+        .arg("nope")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown dataset 'nope'"));
+}
+
+#[test]
+fn test_snapshot_fails_when_compiled_output_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("customer.yaml");
+    std::fs::copy("tests/fixtures/execution/customer.yaml", &spec).unwrap();
+    std::fs::copy("tests/fixtures/valid/workspace.yaml", dir.path().join("workspace.yaml")).unwrap();
+    let snapshot = || {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["--plain", "snapshot"]).arg(dir.path());
+        cmd
+    };
+
+    snapshot()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("customer.yaml has no snapshot"))
+        .stderr(predicate::str::contains("3 snapshot(s) out of date"));
+    snapshot()
+        .arg("--update")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 of 3 snapshot(s) updated"));
+    let snapshots = dir.path().join("snapshots");
+    assert!(snapshots.join("customer.json").is_file());
+    assert!(snapshots.join("workspace.customer.json").is_file());
+    assert!(snapshots.join("workspace.household.json").is_file());
+    snapshot()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 of 3 snapshot(s) unchanged"));
+
+    // A changed threshold shows where the IR and plan moved.
+    let yaml = std::fs::read_to_string(&spec).unwrap();
+    std::fs::write(&spec, yaml.replace("match: 0.7", "match: 0.75")).unwrap();
+    std::fs::remove_file(dir.path().join("workspace.yaml")).unwrap();
+    snapshot()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("customer.yaml changed"))
+        .stderr(predicate::str::contains("ir differs at /thresholds/match: expected 0.7, got 0.75"))
+        .stderr(predicate::str::contains("workspace.customer.json has no spec"));
+    snapshot().arg("--update").assert().success();
+    assert!(!snapshots.join("workspace.customer.json").exists());
+    snapshot()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 of 1 snapshot(s) unchanged"));
+}
+
+#[test]
+fn test_hash_success() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("hash").arg("tests/fixtures/valid/minimal.yaml");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("sha256:"));
+}
+
+#[test]
+fn test_hash_algorithms_and_keyed_tokens() {
+    let spec = "tests/fixtures/valid/minimal.yaml";
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["hash", spec, "--algorithm", "blake3"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match("^blake3:[0-9a-f]{64}\n$").unwrap());
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["hash", spec, "--algorithm", "sha512", "--key-env", "KANONIV_TEST_KEY"])
+        .env("KANONIV_TEST_KEY", "secret");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match("^hmac-sha512:[0-9a-f]{128}\n$").unwrap());
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["tokenize", "--key-env", "KANONIV_TEST_KEY"])
+        .env("KANONIV_TEST_KEY", "secret")
+        .write_stdin("abc\n");
+    cmd.assert()
+        .success()
+        .stdout("hmac-sha256:9946dad4e00e913fc8be8e5d3f7e110a4a9e832f83fb09c345285d78638d8a0e\n");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["tokenize", "abc", "--key-env", "KANONIV_TEST_MISSING_KEY"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("KANONIV_TEST_MISSING_KEY is not set"));
+}
+
+#[test]
+fn test_compile_sql_target() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("compile")
+        .arg("tests/fixtures/valid/minimal.yaml")
+        .arg("--target")
+        .arg("sql")
+        .arg("--dialect")
+        .arg("postgres");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("CREATE OR REPLACE VIEW customer_candidate_pairs AS"))
+        .stdout(predicate::str::contains("customer_golden_records"));
+}
+
+#[test]
+fn test_validate_plain_and_quiet_modes() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("--plain")
+        .arg("validate")
+        .arg("tests/fixtures/valid/minimal.yaml");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[ok] Schema valid"))
+        .stdout(predicate::str::contains("✓").not());
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate")
+        .arg("--quiet")
+        .arg("tests/fixtures/valid/minimal.yaml");
+
+    cmd.assert().success().stdout(predicate::str::is_empty());
+
+    // Plain risk flags spell their dashes and powers in ASCII.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "plan", "tests/fixtures/valid/minimal.yaml"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("No blocking keys defined - all pairs will be compared (O(n^2))"))
+        .stdout(predicate::str::contains("—").not().and(predicate::str::contains("²").not()))
+        .stderr(predicate::str::contains("—").not());
+
+    // Piped output is colored only when CLICOLOR_FORCE asks for it.
+    let validate = |force: Option<&str>| {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.env_remove("NO_COLOR").env_remove("CLICOLOR_FORCE");
+        if let Some(force) = force {
+            cmd.env("CLICOLOR_FORCE", force);
+        }
+        let output = cmd.args(["validate", "tests/fixtures/valid/minimal.yaml"]).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert!(!validate(None).contains('\u{1b}'));
+    assert!(validate(Some("1")).contains('\u{1b}'));
+}
+
+#[test]
+fn test_compile_dbt_project() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("compile")
+        .arg("tests/fixtures/valid/minimal.yaml")
+        .arg("--target")
+        .arg("dbt")
+        .arg("-o")
+        .arg(dir.path());
+
+    cmd.assert().success();
+
+    let decisions = std::fs::read_to_string(
+        dir.path()
+            .join("models/customer/customer_match_decisions.sql"),
+    )
+    .unwrap();
+    assert!(decisions.contains("{{ ref('customer_exact_match_scores') }}"));
+    assert!(dir.path().join("models/sources.yml").exists());
+    assert!(dir.path().join("dbt_project.yml").exists());
+}
+
+#[test]
+fn test_compile_pyspark_target() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("compile")
+        .arg("tests/fixtures/valid/minimal.yaml")
+        .arg("--target")
+        .arg("pyspark");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("GraphFrame(vertices, edges).connectedComponents()"))
+        .stdout(predicate::str::contains("def golden_records("))
+        .stdout(predicate::str::contains("LINEAGE_JOB = \"customer.retail_v1.0\""))
+        .stdout(predicate::str::contains("emit_lineage(\"FAIL\", run_id, inputs, outputs, error)"));
+}
+
+#[test]
+fn test_compile_kafka_target() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("compile")
+        .arg("tests/fixtures/valid/minimal.yaml")
+        .arg("--target")
+        .arg("kafka");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("from confluent_kafka import Consumer, Producer"))
+        .stdout(predicate::str::contains("class StateStore:"))
+        .stdout(predicate::str::contains("OUTPUT_TOPIC = \"customer.merge_decisions\""))
+        .stdout(predicate::str::contains("emit_lineage(\"START\", run_id, inputs, outputs)"));
+}
+
+#[test]
+fn test_conformance_corpus() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("conformance")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/conformance"))
+        .arg("--plain");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[ok] multi_source"))
+        .stdout(predicate::str::contains("[fail]").not());
+}
+
+#[test]
+fn test_conformance_reports_mismatch() {
+    let corpus = tempfile::tempdir().unwrap();
+    let case = corpus.path().join("minimal");
+    std::fs::create_dir(&case).unwrap();
+    std::fs::copy("tests/fixtures/valid/minimal.yaml", case.join("spec.yaml")).unwrap();
+
+    let mut update = Command::cargo_bin("kanoniv").unwrap();
+    update.arg("conformance").arg(corpus.path()).arg("--update");
+    update.assert().success();
+
+    let expected = std::fs::read_to_string(case.join("expected.json")).unwrap();
+    std::fs::write(
+        case.join("expected.json"),
+        expected.replace("\"entity\": \"customer\"", "\"entity\": \"client\""),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("conformance").arg(corpus.path()).arg("--plain");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("ir differs at /entity"));
+
+    // A case with records fixes its decisions too.
+    std::fs::write(
+        case.join("records.json"),
+        r#"{"crm": [{"contact_id": "1", "email": "a@x.com"}, {"contact_id": "2", "email": "a@x.com"}]}"#,
+    )
+    .unwrap();
+    let mut update = Command::cargo_bin("kanoniv").unwrap();
+    update.arg("conformance").arg(corpus.path()).arg("--update");
+    update.assert().success();
+    let expected = std::fs::read_to_string(case.join("expected.json")).unwrap();
+    assert!(expected.contains("\"decision\": \"match\""), "{}", expected);
+    std::fs::write(
+        case.join("expected.json"),
+        expected.replace("\"decision\": \"match\"", "\"decision\": \"review\""),
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("conformance").arg(corpus.path()).arg("--plain");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("decisions differs at /decisions/0/decision"));
+}
+
+#[test]
+fn test_explain_diagnostic_code() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("explain").arg("unknown-field");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("KNV0101"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate")
+        .arg("tests/fixtures/invalid/missing_entity.yaml")
+        .arg("--plain");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[KNV0001] Missing required field: entity"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("explain").arg("KNV9999");
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_schema_export() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("schema").arg("export").arg("--format").arg("json-schema");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"$schema\": \"http://json-schema.org/draft-07/schema#\""))
+        .stdout(predicate::str::contains("\"maxItems\": 50"));
+}
+
+#[test]
+fn test_audit_schema_export() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["audit-schema", "export"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"$id\": \"https://oss.kanoniv.com/schema/audit.json\""))
+        .stdout(predicate::str::contains("\"const\": \"survivorship_choice\""));
+}
+
+#[test]
+fn test_validate_profiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("zero_weight.yaml");
+    std::fs::write(
+        &path,
+        include_str!("fixtures/valid/minimal.yaml").replace("weight: 1.0", "weight: 0"),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&path);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[KNV0106]"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "--profile", "strict"]).arg(&path);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[KNV0106]"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "--profile", "lenient", "-f", "json"])
+        .arg(&path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"warnings\": []"));
+}
+
+#[test]
+fn test_validate_sarif_output() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "tests/fixtures/invalid/unknown_field.yaml", "--format", "sarif"]);
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    let sarif: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    let run = &sarif["runs"][0];
+    assert_eq!(run["tool"]["driver"]["name"], "kanoniv");
+    assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "KNV0101");
+    assert_eq!(run["tool"]["driver"]["rules"][0]["name"], "unknown-field");
+
+    let result = &run["results"][0];
+    assert_eq!(result["ruleId"], "KNV0101");
+    assert_eq!((result["ruleIndex"].as_u64(), result["level"].as_str()), (Some(0), Some("error")));
+    let location = &result["locations"][0];
+    assert_eq!(location["physicalLocation"]["artifactLocation"]["uri"], "tests/fixtures/invalid/unknown_field.yaml");
+    assert_eq!(location["physicalLocation"]["region"]["startLine"], 15);
+    assert_eq!(location["logicalLocations"][0]["fullyQualifiedName"], "rules[0].field");
+}
+
+#[test]
+fn test_validate_junit_and_github_output() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "tests/fixtures/invalid/unknown_field.yaml", "--format", "junit"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("<testsuite name=\"kanoniv validate\" tests=\"1\" failures=\"1\">"))
+        .stdout(predicate::str::contains("<failure type=\"KNV0101\""));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "tests/fixtures/invalid/unknown_field.yaml", "--format", "github"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::starts_with(
+            "::error file=tests/fixtures/invalid/unknown_field.yaml,line=15,col=5,title=KNV0101::",
+        ));
+}
+
+#[test]
+fn test_unknown_output_format_is_rejected() {
+    for args in [
+        &["validate", "tests/fixtures/valid/minimal.yaml"][..],
+        &["check", "tests/fixtures/valid"],
+        &["compile", "tests/fixtures/valid/minimal.yaml"],
+    ] {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(args).args(["--format", "bogus"]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("invalid value 'bogus' for '--format <FORMAT>'"));
+    }
+}
+
+#[test]
+fn test_plan_ci_output_fails_on_severity() {
+    // The minimal spec raises NO_BLOCKING (critical), SINGLE_SIGNAL (high)
+    // and medium and low flags.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--format", "github"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("::warning file=tests/fixtures/valid/minimal.yaml,title=NO_BLOCKING::[critical]"))
+        .stdout(predicate::str::contains("::error").not());
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--format", "github", "--fail-on", "high"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("::error file=tests/fixtures/valid/minimal.yaml,line=12,col=1,title=SINGLE_SIGNAL::"))
+        .stdout(predicate::str::contains("::warning file=tests/fixtures/valid/minimal.yaml,line=17,col=1,title=NO_REVIEW_THRESHOLD::"))
+        .stderr(predicate::str::contains("2 risk flag(s) at or above high (--fail-on high)"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--format", "junit", "--fail-on", "critical"]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("failures=\"1\""))
+        .stdout(predicate::str::contains("name=\"NO_BLOCKING blocking\""));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--fail-on", "severe"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown severity 'severe'"));
+}
+
+#[test]
+fn test_policy_file_sets_levels_for_validate_and_plan() {
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("specs");
+    std::fs::create_dir(&nested).unwrap();
+    let path = nested.join("spec.yaml");
+    std::fs::write(&path, include_str!("fixtures/valid/minimal.yaml").replace("weight: 1.0", "weight: 0.0")).unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&path);
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("plan").arg(&path);
+    cmd.assert().success();
+
+    // The nearest .kanoniv.toml governs every spec below it.
+    std::fs::write(
+        dir.path().join(".kanoniv.toml"),
+        "[policy]\nzero-weight-rule = \"error\"\nNO_BLOCKING = \"ignore\"\nSINGLE_SOURCE = \"error\"\n",
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&path);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[KNV0106]"))
+        .stderr(predicate::str::contains("1 semantic error(s)"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "--format", "github"]).arg(&path);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("title=SINGLE_SOURCE::[low]"))
+        .stdout(predicate::str::contains("NO_BLOCKING").not())
+        .stderr(predicate::str::contains("1 risk flag(s) fail the severity policy"));
+
+    std::fs::write(dir.path().join(".kanoniv.toml"), "[policy]\nSINGLE_SOURCE = \"loud\"\n").unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&path);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown policy level 'loud'"));
+}
+
+#[test]
+fn test_fmt_check_and_rewrite() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("spec.yaml");
+    std::fs::write(
+        &path,
+        include_str!("fixtures/valid/minimal.yaml").replace("  name: customer", "    name: customer"),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["fmt", "--check"]).arg(&path);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("is not formatted"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("fmt").arg(&path);
+    cmd.assert().success();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        include_str!("fixtures/valid/minimal.yaml")
+    );
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["fmt", "--check"]).arg(&path);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_plan_renders_execution_dag() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--format", "mermaid"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("flowchart LR\n"))
+        .stdout(predicate::str::contains("  input_0[(\"crm\")]\n"))
+        .stdout(predicate::str::contains("  stage_3 --> rule_0\n  rule_0 -->|\"exact_match_scores\"| stage_5\n"))
+        .stdout(predicate::str::contains("Plan Summary").not());
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/typed.yaml", "--format", "dot"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("digraph plan {\n"))
+        .stdout(predicate::str::contains("  rule_1 [label=\"dob_exact\\ndob (exact, w=0.5)\", shape=ellipse];\n"))
+        .stdout(predicate::str::contains("  stage_8 -> output_2;\n}"));
+
+    // The plan has no JSON rendering; `json` is refused rather than
+    // silently printed as text.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--format", "json"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'json' for '--format <FORMAT>'"))
+        .stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn test_plan_custom_risks() {
+    let dir = tempfile::tempdir().unwrap();
+    let rules = dir.path().join("risks.yaml");
+    std::fs::write(
+        &rules,
+        "risks:\n  - code: NO_COUNTRY_BLOCKING\n    severity: high\n    message: Specs must block on country\n    when:\n      missing: \"blocking.keys[*]\"\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--custom-risks"])
+        .arg(&rules);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("NO_COUNTRY_BLOCKING"));
+
+    std::fs::write(&rules, "risks:\n  - code: X\n").unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--custom-risks"])
+        .arg(&rules);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid custom risks file").and(predicate::str::contains("missing field")));
+}
+
+#[test]
+fn test_validate_with_rule_pack_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("pii.yaml"),
+        "checks:\n  - code: CORP_PII_SURVIVORSHIP\n    severity: error\n    select: \"sources[*].attributes.*\"\n    when:\n      equals: { path: type, value: email }\n    require:\n      equals: { path: \"$.survivorship.rules[*].field\", value: \"{key}\" }\n    message: PII attribute '{key}' has no survivorship rule.\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("owners.yml"),
+        "checks:\n  - code: CORP_OWNERS\n    severity: warning\n    require: { exists: owners }\n    message: Specs must declare owners.\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("README.md"), "not a pack").unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "tests/fixtures/valid/typed.yaml", "--rules"])
+        .arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[CORP_PII_SURVIVORSHIP] tests/fixtures/valid/typed.yaml:17:7: PII attribute 'email' has no survivorship rule."))
+        .stderr(predicate::str::contains("1 semantic error(s)"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "tests/fixtures/valid/minimal.yaml", "--rules"])
+        .arg(dir.path());
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[CORP_OWNERS] Specs must declare owners."));
+
+    std::fs::write(dir.path().join("owners.yml"), "checks:\n  - code: KNV0106\n    severity: warning\n    require: { exists: owners }\n    message: m\n").unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "tests/fixtures/valid/minimal.yaml", "--rules"])
+        .arg(dir.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid rule pack").and(predicate::str::contains("built-in diagnostic code")));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_plugin_executables_extend_validate_plan_and_compile() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let plugin = dir.path().join("kanoniv-plugin-databricks");
+    std::fs::write(
+        &plugin,
+        r##"#!/bin/sh
+request=$(cat)
+case "$request" in
+  *'"method":"describe"'*)
+    echo '{"version":"0.3.0","description":"Databricks jobs","capabilities":["validate","risks","compile"],"targets":["databricks"]}' ;;
+  *'"method":"validate"'*)
+    echo '{"diagnostics":[{"severity":"warning","code":"DBX001","path":"sources[0]","message":"Source crm is not in Unity Catalog."}]}' ;;
+  *'"method":"risks"'*)
+    echo '{"risk_flags":[{"severity":"high","code":"DBX_NO_CLUSTER","message":"No cluster policy","recommendation":"Set a cluster policy"}]}' ;;
+  *'"method":"compile"'*)
+    echo '{"files":[{"path":"jobs/customer.py","contents":"# Databricks job"}]}' ;;
+  *) echo "unexpected request" >&2; exit 1 ;;
+esac
+"##,
+    )
+    .unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.env("KANONIV_PLUGIN_PATH", dir.path())
+        .args(["--plain", "--list-plugins"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("databricks 0.3.0  validate; risks; compile; targets: databricks"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.env("KANONIV_PLUGIN_PATH", dir.path())
+        .args(["--plain", "validate", "tests/fixtures/valid/minimal.yaml"]);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[DBX001] tests/fixtures/valid/minimal.yaml:6:3: Source crm is not in Unity Catalog."));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.env("KANONIV_PLUGIN_PATH", dir.path())
+        .args(["--plain", "plan", "tests/fixtures/valid/minimal.yaml"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("DBX_NO_CLUSTER"));
+
+    let out = dir.path().join("out");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.env("KANONIV_PLUGIN_PATH", dir.path())
+        .args(["--plain", "compile", "tests/fixtures/valid/minimal.yaml", "--target", "databricks", "-o"])
+        .arg(&out);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Generated databricks output"));
+    assert_eq!(std::fs::read_to_string(out.join("jobs/customer.py")).unwrap(), "# Databricks job");
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.env("KANONIV_PLUGIN_PATH", dir.path())
+        .args(["compile", "tests/fixtures/valid/minimal.yaml", "--target", "spark"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Expected one of: ir, sql, dbt, pyspark, kafka, databricks"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_validate_evaluates_rego_policies_with_opa() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let policies = dir.path().join("policies");
+    std::fs::create_dir(&policies).unwrap();
+    std::fs::write(policies.join("kanoniv.rego"), "package kanoniv\n").unwrap();
+    // Stands in for OPA: answers data.kanoniv once the input carries a plan.
+    let opa = dir.path().join("opa");
+    std::fs::write(
+        &opa,
+        r##"#!/bin/sh
+input=$(cat)
+case "$*" in
+  "eval --format json --stdin-input --data "*" data.kanoniv") ;;
+  *) echo "unexpected arguments: $*" >&2; exit 2 ;;
+esac
+case "$input" in
+  *'"plan":null'*)
+    echo '{"errors":[{"message":"input.plan is undefined","location":{"file":"policies/kanoniv.rego","row":4}}]}'; exit 1 ;;
+esac
+echo '{"result":[{"expressions":[{"value":{"deny":["specs must declare blocking keys"],"pii":{"warn":[{"code":"CORP_PII","msg":"email is not masked","path":"sources[0].attributes.email"}]}},"text":"data.kanoniv"}]}]}'
+"##,
+    )
+    .unwrap();
+    std::fs::set_permissions(&opa, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.env("KANONIV_OPA", &opa)
+        .args(["--plain", "validate", "tests/fixtures/valid/minimal.yaml", "--policy"])
+        .arg(&policies);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[KNV0132] specs must declare blocking keys"))
+        .stderr(predicate::str::contains("[CORP_PII] tests/fixtures/valid/minimal.yaml:11:7: email is not masked"));
+
+    // A spec with errors is evaluated without a plan.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.env("KANONIV_OPA", &opa)
+        .args(["--plain", "validate", "tests/fixtures/invalid/unknown_field.yaml", "--policy"])
+        .arg(&policies);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("policies/kanoniv.rego:4: input.plan is undefined"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.env("KANONIV_OPA", dir.path().join("missing-opa"))
+        .args(["validate", "tests/fixtures/valid/minimal.yaml", "--policy"])
+        .arg(&policies);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("install OPA, or name it with KANONIV_OPA"));
+}
+
+#[test]
+fn test_plan_with_sample() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    std::fs::write(
+        &spec,
+        include_str!("fixtures/valid/minimal.yaml")
+            .replace("decision:", "blocking:\n  keys:\n    - field: email\n      transform: lowercase\ndecision:"),
+    )
+    .unwrap();
+    let sample = dir.path().join("sample.csv");
+    std::fs::write(&sample, "contact_id,email\n1,A@x.com\n2,a@x.com\n3,b@x.com\n4,\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "plan"])
+        .arg(&spec)
+        .arg("--sample")
+        .arg(&sample);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("email (lowercase) - 2 blocks, largest 2, 1 missing, 1 pairs"))
+        .stdout(predicate::str::contains("Candidate pairs: 1 of 6 (83.3% reduction)"))
+        .stdout(predicate::str::contains("WEAK_BLOCKING"));
+}
+
+#[test]
+fn test_plan_estimates_costs_at_row_counts() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "plan", "tests/fixtures/valid/minimal.yaml", "--rows", "crm=2.5e6"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Estimates (2.5M records, heuristic):"))
+        .stdout(predicate::str::contains("Candidate pairs: ~3.1T of 3.1T"))
+        .stdout(predicate::str::contains("email_exact - ~3.1T comparisons"))
+        .stdout(predicate::str::contains("Total: ~"))
+        .stdout(predicate::str::contains("ESTIMATED_PAIRS_EXCEEDS_BUDGET"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "plan", "tests/fixtures/valid/minimal.yaml", "--rows", "crm=2_500_000", "--pair-budget", "1e13"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Estimate:     ~3.1T candidate pairs"))
+        .stdout(predicate::str::contains("ESTIMATED_PAIRS_EXCEEDS_BUDGET").not());
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--rows", "crm"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("expected SOURCE=ROWS"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["plan", "tests/fixtures/valid/minimal.yaml", "--rows", "erp=5"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("No source named 'erp' to set rows for"));
+}
+
+#[test]
+fn test_profile_flags_sparse_match_fields() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = dir.path().join("contacts.csv");
+    std::fs::write(
+        &data,
+        "contact_id,email_address,mobile,family_name\n1,a@x.com,555-123-4567,Smith\n2,bad-email,,Smyth\n3,c@x.com,n/a,\n4,d@x.com,,Jones\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "profile", "--spec", "conformance/multi_source/spec.yaml", "--data"])
+        .arg(&data);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Profile: crm (4 records)"))
+        .stdout(predicate::str::contains("'phone' is missing in 75% of records but is matched on by phone_exact"))
+        .stdout(predicate::str::contains("25% of 'email' values do not match the email format"))
+        .stdout(predicate::str::contains("[high] MISSING_COLUMN"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["profile", "--spec", "conformance/multi_source/spec.yaml", "--source", "pos", "--data"])
+        .arg(&data);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown source 'pos'"));
+}
+
+#[test]
+fn test_survivorship_impact_lists_changed_fields() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    std::fs::write(
+        &spec,
+        include_str!("../conformance/multi_source/spec.yaml")
+            .replace("[crm, billing, support]", "[billing, crm, support]"),
+    )
+    .unwrap();
+    let members = dir.path().join("members.csv");
+    std::fs::write(
+        &members,
+        "cluster_id,source_name,record_key,email,first_name,last_name,phone\n\
+         e1,crm,crm:1,a@x.com,Ann,Smith,555-0001\n\
+         e1,billing,billing:9,ann@y.com,,Smith,\n\
+         e2,crm,crm:2,b@x.com,Bob,Brown,555-0002\n",
+    )
+    .unwrap();
+    let canonical = dir.path().join("canonical.csv");
+    std::fs::write(
+        &canonical,
+        "entity_id,email,first_name,last_name,phone\ne1,a@x.com,Ann,Smith,555-0001\ne2,b@x.com,Bob,Brown,555-0002\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "survivorship-impact"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members)
+        .arg("--canonical")
+        .arg(&canonical);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("1 of 2 entities would change"))
+        .stdout(predicate::str::contains("email (source_priority) - 1 changed"))
+        .stdout(predicate::str::contains("e1  email: \"a@x.com\" -> \"ann@y.com\"  (billing:9)"));
+}
+
+#[test]
+fn test_calibrate_recommends_thresholds() {
+    let dir = tempfile::tempdir().unwrap();
+    let labels = dir.path().join("labels.csv");
+    std::fs::write(
+        &labels,
+        "label,left_email,right_email\n\
+         match,a@x.com,a@x.com\n\
+         match,b@x.com,B@x.com\n\
+         non_match,c@x.com,d@x.com\n\
+         non_match,e@x.com,e@y.com\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "calibrate", "tests/fixtures/valid/minimal.yaml", "--labels"])
+        .arg(&labels);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Calibration: 4 labeled pairs (2 matches)"))
+        .stdout(predicate::str::contains("email_exact           exact       4       1.00    1.00"))
+        .stdout(predicate::str::contains("match: 0.9 -> 1 (precision 1.00, recall 1.00)"));
+}
+
+#[test]
+fn test_learn_weights_writes_patched_spec() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = dir.path().join("records.csv");
+    std::fs::write(&data, "email\na@x.com\na@x.com\nb@x.com\nc@x.com\n").unwrap();
+    let output = dir.path().join("learned.yaml");
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "learn-weights", "tests/fixtures/valid/minimal.yaml", "--data"])
+        .arg(&data)
+        .arg("-o")
+        .arg(&output);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Learned weights: 4 records, 6 pairs"))
+        .stdout(predicate::str::contains("email_exact                 6"));
+    let learned = std::fs::read_to_string(&output).unwrap();
+    assert!(learned.contains("name: email_exact"));
+    assert!(learned.contains("weight: 1"));
+
+    // Without -o the spec goes to stdout and the summary to stderr.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "learn-weights", "tests/fixtures/valid/minimal.yaml", "--data"])
+        .arg(&data);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("api_version: kanoniv/v2"))
+        .stderr(predicate::str::contains("Learned weights:"));
+}
+
+#[test]
+fn test_analyze_thresholds_compares_proposed_with_current() {
+    let dir = tempfile::tempdir().unwrap();
+    let data = dir.path().join("scored.csv");
+    std::fs::write(
+        &data,
+        "left_key,right_key,score\na,b,0.95\nb,c,0.88\nc,d,0.86\nd,e,0.7\nf,g,0.91\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "analyze", "thresholds", "tests/fixtures/valid/minimal.yaml", "--data"])
+        .arg(&data)
+        .args(["--match", "0.85"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("0.90        2        0        3  (current)"))
+        .stdout(predicate::str::contains("0.85        4        0        1  (proposed)"))
+        .stdout(predicate::str::contains("-> merge +2, review +0, reject -2"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["analyze", "thresholds", "tests/fixtures/valid/minimal.yaml", "-f", "json", "--data"])
+        .arg(&data);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"pairs\": 5"));
+}
+
+#[test]
+fn test_explain_pair_prints_rule_breakdown() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.json");
+    let b = dir.path().join("b.json");
+    std::fs::write(&a, r#"{"email": "a@x.com"}"#).unwrap();
+    std::fs::write(&b, r#"{"email": "A@x.com"}"#).unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "explain-pair", "--spec", "tests/fixtures/valid/minimal.yaml", "--a"])
+        .arg(&a)
+        .arg("--b")
+        .arg(&b);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("email_exact           exact     1.000          -       1         1.000  a@x.com / a@x.com"))
+        .stdout(predicate::str::contains("Decision: match (score 1 is at least match 0.9)"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["explain-pair", "--spec", "tests/fixtures/valid/minimal.yaml", "-f", "json", "--a"])
+        .arg(&a)
+        .arg("--b")
+        .arg(dir.path().join("missing.json"));
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read file"));
+}
+
+#[test]
+fn test_cluster_writes_assignments() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    std::fs::write(
+        &spec,
+        format!(
+            "{}clustering:\n  strategy: star\n",
+            include_str!("fixtures/valid/minimal.yaml")
+        ),
+    )
+    .unwrap();
+    let decisions = dir.path().join("decisions.csv");
+    std::fs::write(
+        &decisions,
+        "left_key,right_key,score,decision\na,b,0.95,match\nb,c,0.92,match\nc,d,0.94,match\n",
+    )
+    .unwrap();
+    let output = dir.path().join("clusters.csv");
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "cluster"])
+        .arg(&spec)
+        .arg("--decisions")
+        .arg(&decisions)
+        .arg("-o")
+        .arg(&output);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("4 records, 3 pairs -> 2 clusters (star)"))
+        .stdout(predicate::str::contains("Transitive closure would form 1 clusters"));
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "record_key,cluster_id\na,a\nb,a\nc,a\nd,d\n"
+    );
+
+    // Without -o the assignments go to stdout and the summary to stderr.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "cluster"])
+        .arg(&spec)
+        .arg("--decisions")
+        .arg(&decisions);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("record_key,cluster_id\n"))
+        .stderr(predicate::str::contains("Clusters:"));
+}
+
+#[test]
+fn test_cluster_emits_openlineage_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = "tests/fixtures/valid/minimal.yaml";
+    let decisions = dir.path().join("decisions.csv");
+    std::fs::write(&decisions, "left_key,right_key,score,decision\na,b,0.95,match\n").unwrap();
+    let output = dir.path().join("clusters.csv");
+    let events = dir.path().join("events.jsonl");
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "cluster", spec])
+        .arg("--decisions")
+        .arg(&decisions)
+        .arg("-o")
+        .arg(&output)
+        .env("OPENLINEAGE_URL", &events)
+        .env("OPENLINEAGE_NAMESPACE", "identity");
+    cmd.assert().success();
+    let read = || -> Vec<serde_json::Value> {
+        std::fs::read_to_string(&events)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    };
+    let run = read();
+    assert_eq!(run.len(), 2);
+    assert_eq!((run[0]["eventType"].as_str(), run[1]["eventType"].as_str()), (Some("START"), Some("COMPLETE")));
+    assert_eq!(run[0]["run"]["runId"], run[1]["run"]["runId"]);
+    assert_eq!(run[0]["job"], serde_json::json!({ "namespace": "identity", "name": "customer.retail_v1.0" }));
+    assert!(run[0]["inputs"][0]["name"].as_str().unwrap().ends_with("decisions.csv"));
+    assert!(run[0]["outputs"][0]["name"].as_str().unwrap().ends_with("clusters.csv"));
+    let facet = &run[0]["run"]["facets"]["kanoniv"];
+    assert!(facet["planHash"].as_str().unwrap().starts_with("sha256:"));
+    assert_eq!(facet["risks"]["critical"], 1);
+
+    // A run that fails reports why; one whose events cannot be delivered
+    // still succeeds.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "cluster", spec])
+        .arg("--decisions")
+        .arg(dir.path().join("missing.csv"))
+        .env("OPENLINEAGE_URL", &events);
+    cmd.assert().failure();
+    let run = read();
+    assert_eq!(run[3]["eventType"], "FAIL");
+    assert!(run[3]["run"]["facets"]["errorMessage"]["message"].as_str().unwrap().contains("missing.csv"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "cluster", spec])
+        .arg("--decisions")
+        .arg(&decisions)
+        .arg("-o")
+        .arg(&output)
+        .env("OPENLINEAGE_URL", dir.path().join("no/such/dir/events.jsonl"));
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("OpenLineage: Failed to write file"));
+}
+
+#[test]
+fn test_cluster_review_queue_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = "tests/fixtures/valid/minimal.yaml";
+    let decisions = dir.path().join("decisions.csv");
+    std::fs::write(
+        &decisions,
+        "left_key,right_key,score,decision\na,b,0.85,review\nc,d,0.8,review\ne,f,0.95,match\n",
+    )
+    .unwrap();
+    let records = dir.path().join("records.csv");
+    std::fs::write(&records, "record_key,email\na,a@x.com\nb,A@x.com\nc,c@x.com\nd,d@y.com\n").unwrap();
+    let queue = dir.path().join("queue.jsonl");
+    let output = dir.path().join("clusters.csv");
+    let cluster = || {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["--plain", "cluster", spec])
+            .arg("--decisions")
+            .arg(&decisions)
+            .arg("--review-queue")
+            .arg(&queue)
+            .arg("--records")
+            .arg(&records)
+            .arg("-o")
+            .arg(&output);
+        cmd
+    };
+    let review = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["--plain", "review"]).args(args).arg("--queue").arg(&queue);
+        cmd
+    };
+
+    cluster()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 pair(s) queued, 0 verdict(s) applied"));
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "record_key,cluster_id\na,a\nb,b\nc,c\nd,d\ne,e\nf,e\n"
+    );
+
+    let listed = review(&["list", "-f", "json"]).assert().success();
+    let items: serde_json::Value = serde_json::from_slice(&listed.get_output().stdout).unwrap();
+    assert_eq!(items.as_array().unwrap().len(), 2);
+    assert_eq!(items[0]["status"], "pending");
+    assert_eq!(items[0]["left"]["email"], "a@x.com");
+    let (ab, cd) = (items[0]["id"].as_str().unwrap(), items[1]["id"].as_str().unwrap());
+
+    review(&["approve", &ab[..6], "--reviewer", "ana", "--note", "same inbox"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Approved {} (a / b)", ab)));
+    review(&["reject", cd]).assert().success();
+    review(&["list", "--status", "pending"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No pairs"));
+    review(&["inspect", cd, "--spec", spec])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Status: rejected"))
+        .stdout(predicate::str::contains("c@x.com / d@y.com"))
+        .stdout(predicate::str::contains("Decision: reject"));
+    review(&["log"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("merge     {}  ana: same inbox", ab)));
+
+    // Approved pairs merge on the next run; nothing is queued twice.
+    cluster()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 pair(s) queued, 2 verdict(s) applied"));
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "record_key,cluster_id\na,a\nb,a\nc,c\nd,d\ne,e\nf,e\n"
+    );
+
+    review(&["labels", "--spec", spec])
+        .assert()
+        .success()
+        .stdout("label,left_email,right_email\nmatch,a@x.com,A@x.com\nnon_match,c@x.com,d@y.com\n");
+}
+
+#[test]
+fn test_golden_records_writes_csv() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    std::fs::write(
+        &spec,
+        format!(
+            "{}survivorship:\n  rules:\n    - field: email\n      strategy: custom\n      expression: \"ends_with(value, '.org') ? 1 : 0\"\n",
+            include_str!("fixtures/valid/minimal.yaml")
+        ),
+    )
+    .unwrap();
+    let members = dir.path().join("members.csv");
+    std::fs::write(
+        &members,
+        "cluster_id,source_name,record_key,email\ne1,crm,a,ann@x.com\ne1,crm,b,ann@y.org\ne2,crm,c,\n",
+    )
+    .unwrap();
+    let output = dir.path().join("golden.csv");
+    let audit = dir.path().join("audit.jsonl");
+    let lineage = dir.path().join("lineage.json");
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "golden-records"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members)
+        .arg("-o")
+        .arg(&output)
+        .arg("--audit")
+        .arg(&audit)
+        .arg("--lineage")
+        .arg(&lineage);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("3 members -> 2 golden records"))
+        .stdout(predicate::str::contains("email (custom)"))
+        .stdout(predicate::str::contains("Wrote audit trail"))
+        .stdout(predicate::str::contains("Wrote lineage"));
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "entity_id,email\ne1,ann@y.org\ne2,\n"
+    );
+    let trail = std::fs::read_to_string(&audit).unwrap();
+    assert_eq!(trail.lines().count(), 2);
+    assert!(trail.contains(r#""event":"survivorship_choice","entity_id":"e1","field":"email","value":"ann@y.org","record_key":"b","strategy":"custom""#));
+    let traced: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&lineage).unwrap()).unwrap();
+    assert_eq!(traced["fields"][0]["record_key"], "b");
+    assert_eq!(traced["fields"][0]["rule"], "custom: ends_with(value, '.org') ? 1 : 0");
+    assert_eq!(traced["fields"][0]["candidates"][0]["value"], "ann@x.com");
+
+    // A .parquet name writes the lineage table instead.
+    if cfg!(feature = "parquet") {
+        let table = dir.path().join("lineage.parquet");
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["--plain", "golden-records"])
+            .arg(&spec)
+            .arg("--members")
+            .arg(&members)
+            .arg("-o")
+            .arg(&output)
+            .arg("--lineage")
+            .arg(&table);
+        cmd.assert().success();
+        assert!(std::fs::read(&table).unwrap().starts_with(b"PAR1"));
+    }
+
+    // Without -o the records go to stdout and the summary to stderr.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "golden-records"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("entity_id,email\n"))
+        .stderr(predicate::str::contains("Golden records:"));
+}
+
+#[test]
+fn test_maintain_expires_values_past_retention() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    let write_spec = |on_expiry: &str, collected_at: &str| {
+        std::fs::write(
+            &spec,
+            format!(
+                "{}privacy:\n  collected_at: {}\n  on_expiry: {}\n  fields:\n    email:\n      masking: none\n      retention_days: 365\n",
+                include_str!("fixtures/valid/minimal.yaml").replace("      email: email\n", "      email: email\n      updated_at: updated_at\n"),
+                collected_at,
+                on_expiry
+            ),
+        )
+        .unwrap()
+    };
+    let members = dir.path().join("members.csv");
+    std::fs::write(
+        &members,
+        "cluster_id,source_name,record_key,email,updated_at\ne1,crm,a,ann@x.com,2000-01-01\ne1,crm,b,ann@y.org,2999-01-01\ne2,crm,c,cy@z.com,\n",
+    )
+    .unwrap();
+    let output = dir.path().join("maintained");
+
+    write_spec("nullify", "updated_at");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "-v", "maintain"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members)
+        .args(["--as-of", "2026-01-01", "-o"])
+        .arg(&output);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("1 expired value(s) in 3 records as of 2026-01-01 (nullify)"))
+        .stdout(predicate::str::contains("a email: collected 2000-01-01, expired 2000-12-31 (365 days)"))
+        .stderr(predicate::str::contains("1 record(s) have no 'updated_at' date"));
+    let read = |name: &str| std::fs::read_to_string(output.join(name)).unwrap();
+    assert_eq!(
+        read("members.csv"),
+        "cluster_id,source_name,record_key,email,updated_at\ne1,crm,a,,2000-01-01\ne1,crm,b,ann@y.org,2999-01-01\ne2,crm,c,cy@z.com,\n"
+    );
+    assert_eq!(read("golden.csv"), "entity_id,email,updated_at\ne1,ann@y.org,2000-01-01\ne2,cy@z.com,\n");
+    assert!(!read("lineage.json").contains("ann@x.com"));
+    let audit: serde_json::Value = serde_json::from_str(&read("retention_audit.json")).unwrap();
+    assert_eq!(audit["expired"][0]["record_key"], "a");
+    assert_eq!(audit["undated"], 1);
+
+    // Before its period ends the value is kept.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "maintain"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members)
+        .args(["--as-of", "2000-12-30"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("0 expired value(s)"));
+
+    // A tombstone drops the whole record.
+    write_spec("tombstone", "updated_at");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "maintain"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members)
+        .args(["--as-of", "2026-01-01", "-f", "json"]);
+    let assert = cmd.assert().success();
+    let audit: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(audit["on_expiry"], "tombstone");
+    assert_eq!(audit["tombstoned"], 1);
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "golden-records"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("e1,ann@y.org,2999-01-01"))
+        .stderr(predicate::str::contains("1 record(s) tombstoned for 1 value(s) past retention"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "golden-records"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members)
+        .args(["--as-of", "2000-12-30"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("e1,ann@x.com,2000-01-01"))
+        .stderr(predicate::str::contains("tombstoned").not());
+
+    // Retention counted from an attribute the spec lacks is an error.
+    write_spec("nullify", "collected_on");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "validate"]).arg(&spec);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Retention is counted from unknown field 'collected_on'"));
+}
+
+#[test]
+fn test_cluster_and_golden_records_honor_stewardship() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("spec.yaml");
+    std::fs::write(&spec, include_str!("fixtures/valid/minimal.yaml")).unwrap();
+    let decisions = dir.path().join("decisions.csv");
+    std::fs::write(
+        &decisions,
+        "left_key,right_key,score,decision\na,b,0.95,match\nb,c,0.92,match\nd,e,0.2,no_match\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("spec.stewardship.yaml"),
+        "always_merge:\n  - records: [d, e]\nnever_merge:\n  - records: [a, c]\n    reason: Twins\n",
+    )
+    .unwrap();
+    let output = dir.path().join("clusters.csv");
+    let audit = dir.path().join("audit.jsonl");
+
+    // The sidecar next to the spec is picked up.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "cluster"])
+        .arg(&spec)
+        .arg("--decisions")
+        .arg(&decisions)
+        .arg("-o")
+        .arg(&output)
+        .arg("--audit")
+        .arg(&audit);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("2 stewardship assertion(s) honored"));
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "record_key,cluster_id\na,a\nb,a\nc,c\nd,d\ne,d\n"
+    );
+    let trail = std::fs::read_to_string(&audit).unwrap();
+    assert!(trail.contains(r#""event":"steward_override","assertion":"never_merge","record_keys":["a","c"],"reason":"Twins""#));
+
+    let members = dir.path().join("members.csv");
+    std::fs::write(&members, "cluster_id,source_name,record_key,email\ne1,crm,a,ann@x.com\ne1,crm,b,\n").unwrap();
+    let locks = dir.path().join("locks.yaml");
+    std::fs::write(&locks, "locked:\n  - record: b\n    field: email\n    value: ann@z.com\n").unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "golden-records"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members)
+        .arg("--stewardship")
+        .arg(&locks);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("entity_id,email\ne1,ann@z.com\n"))
+        .stderr(predicate::str::contains("1 field(s) locked by stewardship"));
+
+    // Contradicting assertions fail the run.
+    std::fs::write(&locks, "always_merge:\n  - records: [a, c]\nnever_merge:\n  - records: [c, a]\n").unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "cluster"])
+        .arg(&spec)
+        .arg("--decisions")
+        .arg(&decisions)
+        .arg("--stewardship")
+        .arg(&locks);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Conflicting stewardship assertions"));
+}
+
+#[test]
+fn test_registry_publish_pull_versions() {
+    let dir = tempfile::tempdir().unwrap();
+    let registry = dir.path().join("registry");
+    let spec = "tests/fixtures/valid/minimal.yaml";
+    let publish = |file: &std::path::Path, tag: &str| {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.arg("publish")
+            .arg(file)
+            .arg("--registry")
+            .arg(&registry)
+            .args(["--tag", tag]);
+        cmd.assert().success()
+    };
+
+    publish(std::path::Path::new(spec), "production").stdout(predicate::str::contains("sha256:"));
+    // A restyled copy is the same version; only the tag moves.
+    let restyled = dir.path().join("restyled.yaml");
+    std::fs::write(
+        &restyled,
+        include_str!("fixtures/valid/minimal.yaml").replace("weight: 1.0", "weight: 1"),
+    )
+    .unwrap();
+    publish(&restyled, "staging").stdout(predicate::str::contains("Already published"));
+    let changed = dir.path().join("changed.yaml");
+    std::fs::write(
+        &changed,
+        include_str!("fixtures/valid/minimal.yaml").replace("weight: 1.0", "weight: 0.9"),
+    )
+    .unwrap();
+    publish(&changed, "staging");
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["versions", "customer", "-f", "json"])
+        .env("KANONIV_REGISTRY", &registry);
+    let output = cmd.output().unwrap();
+    let versions: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(versions.as_array().unwrap().len(), 2);
+    assert_eq!(versions[0]["tags"], serde_json::json!(["staging"]));
+    assert_eq!(versions[1]["tags"], serde_json::json!(["production"]));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["pull", "customer", "production", "--registry"])
+        .arg(&registry);
+    cmd.assert()
+        .success()
+        .stdout(include_str!("fixtures/valid/minimal.yaml"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["pull", "customer", "canary", "--registry"])
+        .arg(&registry);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("No version 'canary' of 'customer'"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["publish", "tests/fixtures/invalid/missing_entity.yaml", "--registry"])
+        .arg(&registry);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Refusing to publish an invalid spec"));
+}
+
+#[test]
+fn test_compose_spec_extending_published_base() {
+    let dir = tempfile::tempdir().unwrap();
+    let registry = dir.path().join("registry");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["publish", "tests/fixtures/valid/minimal.yaml", "--registry"])
+        .arg(registry.join("org"));
+    cmd.assert().success();
+
+    let child = dir.path().join("child.yaml");
+    std::fs::write(
+        &child,
+        "extends: registry://org/customer@retail_v1.0\n\
+         identity_version: retail_emea_v1\n\
+         rules:\n  - name: phone_exact\n    type: exact\n    field: phone\n\
+         decision:\n  thresholds:\n    match: 0.85\n",
+    )
+    .unwrap();
+    // The composed spec is validated: no source provides `phone`.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate")
+        .arg(&child)
+        .env("KANONIV_REGISTRY", &registry);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("phone"));
+
+    let text = std::fs::read_to_string(&child).unwrap().replace("field: phone", "field: email");
+    std::fs::write(&child, text).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate")
+        .arg(&child)
+        .env("KANONIV_REGISTRY", &registry);
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "compose", "--diff"])
+        .arg(&child)
+        .arg("--registry")
+        .arg(&registry);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("~ decision.thresholds.match: 0.9 -> 0.85"))
+        .stdout(predicate::str::contains("+ rules.phone_exact"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("compose")
+        .arg(&child)
+        .arg("--registry")
+        .arg(&registry);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("entity:\n  name: customer"))
+        .stdout(predicate::str::contains("extends").not());
+
+    // Without a registry the base cannot be resolved.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("plan").arg(&child).env_remove("KANONIV_REGISTRY");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("set KANONIV_REGISTRY"));
+}
+
+#[test]
+fn test_resolve_spec_extending_base_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::copy("tests/fixtures/valid/minimal.yaml", dir.path().join("base.yaml")).unwrap();
+    std::fs::create_dir(dir.path().join("regions")).unwrap();
+    let emea = dir.path().join("regions/emea.yaml");
+    std::fs::write(
+        &emea,
+        "extends: ../base.yaml\n\
+         identity_version: emea_v1\n\
+         decision:\n  thresholds:\n    match: 0.85\n",
+    )
+    .unwrap();
+    let de = dir.path().join("regions/de.yaml");
+    std::fs::write(&de, "extends: emea.yaml\nidentity_version: de_v1\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("resolve").arg(&de);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("identity_version: de_v1"))
+        .stdout(predicate::str::contains("match: 0.85"))
+        .stdout(predicate::str::contains("extends").not());
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "resolve", "--diff"]).arg(&emea);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("~ decision.thresholds.match: 0.9 -> 0.85"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&de);
+    cmd.assert().success();
+
+    // Published specs are resolved by others, who do not have the file.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("publish")
+        .arg(&emea)
+        .arg("--registry")
+        .arg(dir.path().join("registry"));
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("extends a local file"));
+
+    std::fs::write(dir.path().join("base.yaml"), "extends: regions/de.yaml\n").unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&de);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("extends itself"));
+}
+
+#[test]
+fn test_diff_routing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let old = dir.path().join("old.yaml");
+    let new = dir.path().join("new.yaml");
+    let routing = dir.path().join("routing.json");
+    let owned = include_str!("fixtures/valid/minimal.yaml")
+        .replace("    id: contact_id\n", "    id: contact_id\n    owner: \"@crm\"\n")
+        + "owners:\n  rules: \"@matching\"\n  decision: \"@risk\"\n";
+    std::fs::write(&old, &owned).unwrap();
+    std::fs::write(&new, owned.replace("weight: 1.0", "weight: 0.5")).unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("diff")
+        .arg(&old)
+        .arg(&new)
+        .arg("--routing")
+        .arg(&routing);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Routing"));
+    let routed: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&routing).unwrap()).unwrap();
+    assert_eq!(routed["@matching"][0]["code"], "RULE_MODIFIED");
+    assert_eq!(routed["@crm"][0]["code"], "RULE_MODIFIED");
+    assert!(routed.get("@risk").is_none());
+}
+
+#[test]
+fn test_diff_fail_on_impact() {
+    let dir = tempfile::tempdir().unwrap();
+    let new = dir.path().join("new.yaml");
+    std::fs::write(
+        &new,
+        include_str!("fixtures/valid/minimal.yaml").replace("match: 0.9", "match: 0.8"),
+    )
+    .unwrap();
+    let diff = |fail_on: &str| {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["diff", "tests/fixtures/valid/minimal.yaml"])
+            .arg(&new)
+            .args(["--fail-on", fail_on]);
+        cmd.assert()
+    };
+
+    diff("breaking")
+        .success()
+        .stdout(predicate::str::contains("recommended bump: minor"));
+    diff("risky")
+        .failure()
+        .stderr(predicate::str::contains("Diff contains risky changes"));
+    diff("severe")
+        .failure()
+        .stderr(predicate::str::contains("Unknown impact 'severe'"));
+
+    // A change outside rules and thresholds is still a change, and -q
+    // keeps only what --fail-on reports.
+    std::fs::write(
+        &new,
+        include_str!("fixtures/valid/minimal.yaml").to_string() + "clustering:\n  strategy: star\n",
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["diff", "tests/fixtures/valid/minimal.yaml"])
+        .arg(&new);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Compatibility:"))
+        .stdout(predicate::str::contains("No significant changes detected.").not());
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["-q", "diff", "tests/fixtures/valid/minimal.yaml"])
+        .arg(&new);
+    cmd.assert().success().stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn test_migrate_plan_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let new = dir.path().join("new.yaml");
+    std::fs::write(
+        &new,
+        include_str!("fixtures/valid/minimal.yaml").replace("match: 0.9", "match: 0.8"),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["migrate-plan", "tests/fixtures/valid/minimal.yaml"])
+        .arg(&new)
+        .args(["-f", "json"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let plan: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(plan["full_rebuild"], false);
+    assert_eq!(plan["steps"][0]["name"], "Score & decide");
+    assert_eq!(plan["steps"][0]["scope"][0], "all");
+}
+
+#[test]
+fn test_merge_reports_conflicts() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = include_str!("fixtures/valid/minimal.yaml");
+    let write = |name: &str, content: String| {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    };
+    let ours = write("ours.yaml", base.replace("match: 0.9", "match: 0.95"));
+    let theirs = write("theirs.yaml", base.replace("weight: 1.0", "weight: 0.8"));
+    let merged = dir.path().join("merged.yaml");
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["merge", "tests/fixtures/valid/minimal.yaml"])
+        .args([&ours, &theirs])
+        .arg("-o")
+        .arg(&merged);
+    cmd.assert().success();
+    let content = std::fs::read_to_string(&merged).unwrap();
+    assert!(content.contains("match: 0.95") && content.contains("weight: 0.8"));
+
+    let other = write("other.yaml", base.replace("match: 0.9", "match: 0.85"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["merge", "tests/fixtures/valid/minimal.yaml"])
+        .args([&ours, &other]);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("<<<<<<< ours (decision.thresholds.match)"))
+        .stderr(predicate::str::contains("1 conflict(s)"));
+}
+
+#[test]
+fn test_risk_trend_over_git_history() {
+    let dir = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir.path())
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
+    };
+    let spec = dir.path().join("identity.yaml");
+    let minimal = include_str!("fixtures/valid/minimal.yaml");
+
+    git(&["init", "-q"]);
+    std::fs::write(&spec, minimal).unwrap();
+    git(&["add", "identity.yaml"]);
+    git(&["commit", "-qm", "Initial spec"]);
+    std::fs::write(&spec, format!("{}blocking:\n  keys: [email]\n", minimal)).unwrap();
+    git(&["commit", "-qam", "Block on email"]);
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["risk-trend", "-f", "json"]).arg(&spec);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let points: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let subjects: Vec<&str> = points
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["subject"].as_str().unwrap())
+        .collect();
+    assert_eq!(subjects, vec!["Initial spec", "Block on email"]);
+    let scores: Vec<u64> = points
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["risk_score"].as_u64().unwrap())
+        .collect();
+    // NO_BLOCKING (25) is resolved; PII_IN_BLOCKING_KEY (4) is raised.
+    assert_eq!(scores[0] - scores[1], 21);
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("risk-trend").arg(&spec);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("(safer)"));
+}
+
+#[test]
+fn test_report_writes_self_contained_html() {
+    let dir = tempfile::tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir.path())
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
+    };
+    let spec = dir.path().join("identity.yaml");
+    let report = dir.path().join("report.html");
+    let minimal = include_str!("fixtures/valid/minimal.yaml");
+
+    // Outside git there is nothing to compare with.
+    std::fs::write(&spec, minimal).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("report").arg(&spec).arg("-o").arg(&report);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote report"));
+    let html = std::fs::read_to_string(&report).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<title>Kanoniv report: customer (retail_v1.0)</title>"));
+    assert!(html.contains("<div class=\"label\">Risk score</div><div class=\"value\">45 / 100</div>"));
+    assert!(html.contains("<td>Exact matches</td>"));
+    assert!(html.contains("<td><span class=\"badge critical\">critical</span></td><td><code>NO_BLOCKING</code></td>"));
+    assert!(html.contains("<table class=\"sortable\">") && html.contains("<script>"));
+    assert!(!html.contains("src=") && !html.contains("href="), "report must not load anything");
+    assert!(html.contains("No previous version to compare with"));
+
+    // In git, the last committed change is the previous version.
+    git(&["init", "-q"]);
+    git(&["add", "identity.yaml"]);
+    git(&["commit", "-qm", "Initial spec"]);
+    std::fs::write(&spec, minimal.replace("match: 0.9", "match: 0.7")).unwrap();
+    git(&["commit", "-qam", "Loosen match threshold"]);
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("report").arg(&spec).arg("-o").arg(&report);
+    cmd.assert().success();
+    let html = std::fs::read_to_string(&report).unwrap();
+    assert!(html.contains("<p>Compared with commit "));
+    assert!(html.contains("<div class=\"label\">Changes</div><div class=\"value\">risky (minor)</div>"));
+    assert!(html.contains("<code>decision.thresholds.match</code>"));
+
+    // An explicit previous version wins; an invalid spec is still reported.
+    std::fs::write(&spec, minimal.replace("entity:\n  name: customer\n", "")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("report")
+        .arg(&spec)
+        .arg("-o")
+        .arg(&report)
+        .args(["--previous", "tests/fixtures/valid/minimal.yaml"]);
+    cmd.assert().success();
+    let html = std::fs::read_to_string(&report).unwrap();
+    assert!(html.contains("<p>Compared with tests/fixtures/valid/minimal.yaml.</p>"));
+    assert!(html.contains("<div class=\"card bad\"><div class=\"label\">Validation</div><div class=\"value\">1 error(s)</div>"));
+    assert!(html.contains("Missing required field: entity"));
+}
+
+#[test]
+fn test_docs_renders_markdown_for_a_spec() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--entity", "person", "docs", "tests/fixtures/valid/relationships.yaml"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("# person\n"))
+        .stdout(predicate::str::contains("## Match Rules"))
+        .stdout(predicate::str::contains("## Relationships"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let docs = dir.path().join("customer.md");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["docs", "tests/fixtures/valid/minimal.yaml", "-o"])
+        .arg(&docs);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote docs"));
+    let markdown = std::fs::read_to_string(&docs).unwrap();
+    assert!(markdown.contains("System: salesforce · Table: `contacts` · Key: `contact_id`"));
+    assert!(markdown.contains("| 1 | email_exact | exact | email |  |  | 1 |  |\n"));
+    assert!(markdown.contains("Strategy: `none`"));
+    assert!(markdown.contains("No survivorship rules."));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["docs", "tests/fixtures/valid/no_such_spec.yaml"]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_init_writes_a_valid_starter_spec() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("kanoniv.yml");
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("init")
+        .arg("-o")
+        .arg(&spec)
+        .write_stdin("person\ncrm:salesforce, billing:stripe\n\n");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote"));
+    let yaml = std::fs::read_to_string(&spec).unwrap();
+    assert!(yaml.contains("system: stripe"));
+    assert!(yaml.contains("name: full_name_fuzzy"));
+    assert!(yaml.contains("source_priority: [crm, billing]"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate")
+        .arg(&spec)
+        .arg("--profile")
+        .arg("strict");
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("fmt").arg("--check").arg(&spec);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("init")
+        .arg("-o")
+        .arg(&spec)
+        .args(["--entity", "org", "--source", "crm", "--field", "name"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("init")
+        .arg("-o")
+        .arg(&spec)
+        .args(["--force", "--entity", "my org", "--source", "crm"])
+        .write_stdin("name\n");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid entity name 'my org'"));
+}
+
+#[test]
+fn test_init_from_a_template() {
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["templates", "list"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("household"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["templates", "list", "--format", "json"]);
+    let output = cmd.output().unwrap();
+    let listed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 5);
+    assert_eq!(listed[0]["params"]["entity"], "person");
+
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("kanoniv.yml");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("init")
+        .arg("-o")
+        .arg(&spec)
+        .args(["--template", "person", "--entity", "customer"])
+        .args(["--source", "shop:shopify"]);
+    cmd.assert().success();
+    let yaml = std::fs::read_to_string(&spec).unwrap();
+    assert!(yaml.contains("name: customer"));
+    assert!(yaml.contains("system: shopify"));
+    assert!(yaml.contains("expand_nicknames"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&spec).args(["--profile", "strict"]);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("init")
+        .arg("-o")
+        .arg(&spec)
+        .args(["--force", "--template", "vehicle"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown template 'vehicle'"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("init")
+        .arg("-o")
+        .arg(&spec)
+        .args(["--force", "--template", "person", "--field", "email"]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_pii_warnings_and_blocking_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("customer.yaml");
+    let minimal = include_str!("fixtures/valid/minimal.yaml");
+    std::fs::write(&path, format!("{}blocking:\n  keys:\n    - email\n", minimal)).unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.arg("validate").arg(&path);
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("[KNV0126]"))
+        .stderr(predicate::str::contains("[KNV0127]"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "plan"]).arg(&path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("PII_IN_BLOCKING_KEY"))
+        .stdout(predicate::str::contains("PII:          email (email)"));
+
+    std::fs::write(
+        &path,
+        format!(
+            "{}blocking:\n  keys:\n    - email\nprivacy:\n  retention_days: 365\n  fields:\n    email:\n      masking: hash\n",
+            minimal
+        ),
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["validate", "--profile", "strict"]).arg(&path);
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "plan"]).arg(&path);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("PII_IN_BLOCKING_KEY").not());
+}
+
+#[test]
+fn test_deletion_drops_tombstoned_members() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("customer.yaml");
+    let members = dir.path().join("members.csv");
+    let output = dir.path().join("golden.csv");
+    let minimal = include_str!("fixtures/valid/minimal.yaml")
+        .replace("      email: email\n", "      email: email\n      deleted_at: deleted_at\n");
+    std::fs::write(&spec, format!("{}deletion:\n  tombstone: deleted_at\n", minimal)).unwrap();
+    std::fs::write(
+        &members,
+        "cluster_id,source_name,record_key,email,deleted_at\ne1,crm,a,ann@x.com,2026-03-01\ne1,crm,b,ann@y.com,\ne2,crm,c,cy@x.com,2026-03-02\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "golden-records"])
+        .arg(&spec)
+        .arg("--members")
+        .arg(&members)
+        .arg("-o")
+        .arg(&output);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("3 members -> 1 golden records"))
+        .stdout(predicate::str::contains("2 deleted member(s) dropped"));
+    let golden = std::fs::read_to_string(&output).unwrap();
+    assert!(golden.contains("ann@y.com") && !golden.contains("ann@x.com") && !golden.contains("cy@x.com"));
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["compile", "--target", "sql"]).arg(&spec);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("WHERE COALESCE(LOWER(TRIM(CAST(deleted_at AS VARCHAR))), '') IN ('', 'false', '0')"));
+}
+
+#[test]
+fn test_tokenize_encodes_attribute_values() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("customer.yaml");
+    let minimal = include_str!("fixtures/valid/minimal.yaml")
+        .replace("      email: email\n", "      email: email\n      ssn: ssn\n      full_name: name\n");
+    let encoding = "encoding:\n  salt_env: KANONIV_TEST_SALT\n  fields:\n    ssn: hash\n    full_name: bloom\n";
+    std::fs::write(&spec, format!("{}{}", minimal, encoding)).unwrap();
+
+    // Values are normalized before they are hashed, so " ABC" encodes as abc.
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["tokenize", "--field", "ssn", " ABC"])
+        .arg("--spec")
+        .arg(&spec)
+        .env("KANONIV_TEST_SALT", "secret");
+    cmd.assert()
+        .success()
+        .stdout("hmac-sha256:9946dad4e00e913fc8be8e5d3f7e110a4a9e832f83fb09c345285d78638d8a0e\n");
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["tokenize", "--field", "full_name"])
+        .arg("--spec")
+        .arg(&spec)
+        .env("KANONIV_TEST_SALT", "secret")
+        .write_stdin("Jon Smith\n");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match("^bloom:[0-9a-f]{256}\n$").unwrap());
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["tokenize", "--field", "email", "ann@x.com"])
+        .arg("--spec")
+        .arg(&spec)
+        .env("KANONIV_TEST_SALT", "secret");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("The spec does not encode 'email'"));
+
+    std::fs::write(&spec, format!("{}{}", minimal, encoding.replace("  salt_env: KANONIV_TEST_SALT\n", ""))).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "validate"]).arg(&spec);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("[KNV0130]"))
+        .stderr(predicate::str::contains("encoded attributes need a salt"));
+}
+
+#[test]
+fn test_sign_and_verify_specs() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = dir.path().join("customer.yaml");
+    let key = dir.path().join("release.key");
+    let public = dir.path().join("release.key.pub");
+    std::fs::write(&spec, include_str!("fixtures/valid/minimal.yaml")).unwrap();
+
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "verify"])
+        .arg(&spec)
+        .arg("--key")
+        .arg(&public);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read public key"));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "sign"])
+        .arg(&spec)
+        .arg("--generate-key")
+        .arg(&key);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with("customer.yaml.sig\n"));
+    assert!(public.exists() && dir.path().join("customer.yaml.sig").exists());
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "verify"])
+        .arg(&spec)
+        .arg("--key")
+        .arg(&public);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(": valid"));
+
+    // The signature does not carry over to a spec that differs only in its
+    // waivers.
+    let waived = dir.path().join("waived.yaml");
+    std::fs::write(&waived, format!("{}waivers:\n  - code: NO_BLOCKING\n    reason: small table\n", include_str!("fixtures/valid/minimal.yaml"))).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "verify"])
+        .arg(&waived)
+        .arg("--signatures")
+        .arg(dir.path().join("customer.yaml.sig"))
+        .arg("--key")
+        .arg(&public);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(": stale"));
+
+    // An embedded signature, from a key in the environment.
+    std::fs::remove_file(dir.path().join("customer.yaml.sig")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["sign", "--embed", "--key-env", "KANONIV_TEST_SIGNING_KEY"])
+        .arg(&spec)
+        .env("KANONIV_TEST_SIGNING_KEY", std::fs::read_to_string(&key).unwrap());
+    cmd.assert().success();
+    assert!(std::fs::read_to_string(&spec).unwrap().contains("\nsignatures:\n  - key_id: "));
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["verify", "-f", "json"])
+        .arg(&spec)
+        .arg("--key")
+        .arg(&public);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"status\": \"valid\""));
+
+    // Changing what the spec does leaves the signature stale.
+    let edited = std::fs::read_to_string(&spec).unwrap().replace("match: 0.9", "match: 0.8");
+    std::fs::write(&spec, edited).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "verify"])
+        .arg(&spec)
+        .arg("--key")
+        .arg(&public);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("stale"))
+        .stderr(predicate::str::contains("has no valid signature from a trusted key"));
+
+    // References are signed as written, so the signature holds whatever
+    // they resolve to.
+    let templated = dir.path().join("templated.yaml");
+    std::fs::write(&templated, include_str!("fixtures/valid/minimal.yaml").replace("match: 0.9", "match: ${KNV_TEST_SIGNED_MATCH}")).unwrap();
+    let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+    cmd.args(["--plain", "sign", "--key-file"])
+        .arg(&key)
+        .arg(&templated)
+        .env("KNV_TEST_SIGNED_MATCH", "0.9");
+    cmd.assert().success();
+    for value in [Some("0.8"), None] {
+        let mut cmd = Command::cargo_bin("kanoniv").unwrap();
+        cmd.args(["--plain", "verify"]).arg(&templated).arg("--key").arg(&public);
+        match value {
+            Some(value) => cmd.env("KNV_TEST_SIGNED_MATCH", value),
+            None => cmd.env_remove("KNV_TEST_SIGNED_MATCH"),
+        };
+        cmd.assert().success().stdout(predicate::str::contains(": valid"));
+    }
 }
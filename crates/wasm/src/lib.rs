@@ -0,0 +1,211 @@
+//! Kanoniv for JavaScript: the validator, compiler, differ, planner,
+//! canonical hash and reference interpreter of `kanoniv_core`, compiled to
+//! WebAssembly so a browser can check specs without a server round trip.
+//!
+//! ```sh
+//! wasm-pack build crates/wasm --target web
+//! ```
+//!
+//! `wasm-pack` writes the TypeScript definitions next to the module.
+//! Results are plain objects typed by the interfaces below; functions throw
+//! an `Error` when the spec cannot be parsed, compiled or planned.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TYPES: &'static str = r#"
+/** 1-based position in the spec source. */
+export interface Span {
+  line: number;
+  column: number;
+}
+
+/** A validation finding, with the stable code `kanoniv explain` describes. */
+export interface Diagnostic {
+  code: string;
+  severity: "error" | "warning" | "info";
+  message: string;
+  /** Dotted YAML path of the offending node, e.g. `rules[0].field`. */
+  path?: string;
+  span?: Span;
+  suggestion?: string;
+}
+
+/** A finding of the planner, with a recommendation. */
+export interface RiskFlag {
+  severity: "critical" | "high" | "medium" | "low";
+  code: string;
+  message: string;
+  recommendation: string;
+}
+
+export interface ExecutionStage {
+  stage: number;
+  name: string;
+  description: string;
+  inputs: string[];
+  outputs: string[];
+}
+
+export interface Plan {
+  entity: string;
+  identity_version: string;
+  plan_hash: string;
+  sources: { name: string; system: string; field_count: number }[];
+  execution_stages: ExecutionStage[];
+  match_strategies: Record<string, unknown>[];
+  survivorship_summary: Record<string, unknown>[];
+  blocking_analysis: Record<string, unknown>;
+  clustering?: Record<string, unknown>;
+  execution?: Record<string, unknown>;
+  risk_flags: RiskFlag[];
+  /** Severity-weighted sum of the risk flags, 0 (no flags) to 100. */
+  risk_score: number;
+  waived?: Record<string, unknown>[];
+  routing?: Record<string, unknown>;
+  summary: string;
+}
+
+export interface ClassifiedChange {
+  /** Spec path of the change (`rules.email_exact.threshold`). */
+  path: string;
+  impact: "safe" | "risky" | "breaking";
+  description: string;
+}
+
+export interface DiffResult {
+  rules_added: string[];
+  rules_removed: string[];
+  rules_modified: { name: string; field: string; old_value: string; new_value: string }[];
+  thresholds_changed: boolean;
+  compatibility: {
+    impact: "safe" | "risky" | "breaking" | null;
+    bump: "major" | "minor" | "patch" | "none";
+    suggested_version: string | null;
+    changes: ClassifiedChange[];
+  };
+  routing?: Record<string, unknown>;
+  summary: string;
+}
+
+/** A candidate pair's score and what the thresholds decided for it. */
+export interface MatchDecision {
+  left_key: string;
+  right_key: string;
+  score: number;
+  decision: string;
+}
+
+export interface Execution {
+  plan_hash: string;
+  records: number;
+  deleted: number;
+  candidate_pairs: number;
+  decisions: MatchDecision[];
+  clusters: Record<string, unknown>;
+  golden: Record<string, unknown>;
+  warnings?: string[];
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "Diagnostic[]")]
+    pub type Diagnostics;
+
+    #[wasm_bindgen(typescript_type = "Record<string, unknown>")]
+    pub type Document;
+
+    #[wasm_bindgen(typescript_type = "Plan")]
+    pub type Plan;
+
+    #[wasm_bindgen(typescript_type = "DiffResult")]
+    pub type DiffResult;
+
+    #[wasm_bindgen(typescript_type = "Record<string, Record<string, unknown>[]>")]
+    pub type Records;
+
+    #[wasm_bindgen(typescript_type = "Execution")]
+    pub type Execution;
+}
+
+/// Every finding in the spec (errors, warnings and info) with its line and
+/// column. Broken YAML is reported as findings too, so this never throws.
+#[wasm_bindgen]
+pub fn validate(yaml: &str) -> Result<Diagnostics, JsError> {
+    to_js(&kanoniv_core::diagnose_yaml(yaml))
+}
+
+/// The spec as a plain object.
+#[wasm_bindgen]
+pub fn parse(yaml: &str) -> Result<Document, JsError> {
+    let spec = kanoniv_core::parse_yaml(yaml).map_err(js_error)?;
+    to_js(&spec)
+}
+
+/// The spec compiled to the intermediate representation engines run.
+#[wasm_bindgen(js_name = compileIr)]
+pub fn compile_ir(yaml: &str) -> Result<Document, JsError> {
+    let spec = kanoniv_core::parse_yaml(yaml).map_err(js_error)?;
+    let ir = kanoniv_core::compile_to_ir(&spec).map_err(js_error)?;
+    to_js(&ir)
+}
+
+/// Changes from spec `a` to spec `b`, classified by impact.
+#[wasm_bindgen]
+pub fn diff(a: &str, b: &str) -> Result<DiffResult, JsError> {
+    let result = kanoniv_core::compute_diff(a, b).map_err(js_error)?;
+    to_js(&result)
+}
+
+/// The execution plan: stages, match strategies, risk flags and summary.
+#[wasm_bindgen]
+pub fn plan(yaml: &str) -> Result<Plan, JsError> {
+    let result = kanoniv_core::generate_plan(yaml).map_err(js_error)?;
+    to_js(&result)
+}
+
+/// The spec run over `records` (an object of source name to that source's
+/// records) as of the date `as_of` (`YYYY-MM-DD`): every candidate pair's
+/// decision, the clusters and the golden records. Semantic rules need an
+/// embedding endpoint, so they are left to the CLI.
+#[wasm_bindgen]
+pub fn execute(yaml: &str, records: Records, as_of: &str) -> Result<Execution, JsError> {
+    let spec = kanoniv_core::parse_yaml(yaml).map_err(js_error)?;
+    let ir = kanoniv_core::compile_to_ir(&spec)
+        .and_then(|ir| kanoniv_core::Ir::from_value(&ir))
+        .map_err(js_error)?;
+    let records: serde_json::Value = serde_wasm_bindgen::from_value(records.into())?;
+    let embedder = kanoniv_core::HttpEmbedder::new().map_err(js_error)?;
+    let result =
+        kanoniv_core::execute_plan_with(&ir, &records, &embedder, None, as_of).map_err(js_error)?;
+    to_js(&result)
+}
+
+/// Canonical hash of the spec, e.g. `sha256:<hex>`; `algorithm` is
+/// `sha256` (the default), `sha512` or `blake3`. Keyed hashes need a key
+/// file or environment variable, so they are left to the CLI.
+#[wasm_bindgen]
+pub fn hash(yaml: &str, algorithm: Option<String>) -> Result<String, JsError> {
+    let spec = kanoniv_core::parse_yaml(yaml).map_err(js_error)?;
+    let algorithm = match algorithm {
+        Some(name) => name.parse().map_err(js_error)?,
+        None => kanoniv_core::HashAlgorithm::default(),
+    };
+    Ok(kanoniv_core::canonical_hash_with(
+        &spec,
+        &kanoniv_core::Hasher::new(algorithm),
+    ))
+}
+
+/// `value` as a plain JavaScript object (maps as objects, not `Map`s).
+fn to_js<T: Serialize + ?Sized, R: JsCast>(value: &T) -> Result<R, JsError> {
+    let value = value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?;
+    Ok(value.unchecked_into())
+}
+
+fn js_error(e: anyhow::Error) -> JsError {
+    JsError::new(&format!("{:#}", e))
+}
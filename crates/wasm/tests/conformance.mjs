@@ -0,0 +1,64 @@
+// The conformance corpus (crates/validator/conformance) through the
+// WebAssembly module: every case's hash, IR and plan, and the decisions of
+// the cases with records, must equal expected.json.
+//
+//   wasm-pack build crates/wasm --target nodejs
+//   node crates/wasm/tests/conformance.mjs
+
+import { existsSync, readdirSync, readFileSync } from "node:fs";
+import { createRequire } from "node:module";
+import { dirname, join } from "node:path";
+import { fileURLToPath } from "node:url";
+
+const here = dirname(fileURLToPath(import.meta.url));
+const kanoniv = createRequire(import.meta.url)(join(here, "..", "pkg", "kanoniv_wasm.js"));
+const corpus = join(here, "..", "..", "validator", "conformance");
+
+// `AS_OF` in crates/validator/src/commands/conformance.rs.
+const AS_OF = "2026-01-01";
+
+// JSON with object keys sorted, so outputs compare regardless of the order
+// fields were serialized in.
+function canonical(value) {
+  if (Array.isArray(value)) {
+    return `[${value.map(canonical).join(",")}]`;
+  }
+  if (value !== null && typeof value === "object") {
+    const entries = Object.keys(value)
+      .sort()
+      .map((key) => `${JSON.stringify(key)}:${canonical(value[key])}`);
+    return `{${entries.join(",")}}`;
+  }
+  return JSON.stringify(value);
+}
+
+const read = (path) => readFileSync(path, "utf8");
+let failed = 0;
+const cases = readdirSync(corpus)
+  .filter((name) => existsSync(join(corpus, name, "spec.yaml")))
+  .sort();
+for (const name of cases) {
+  const dir = join(corpus, name);
+  const spec = read(join(dir, "spec.yaml"));
+  const expected = JSON.parse(read(join(dir, "expected.json")));
+  const actual = {
+    hash: kanoniv.hash(spec),
+    ir: kanoniv.compileIr(spec),
+    plan: kanoniv.plan(spec),
+  };
+  if (existsSync(join(dir, "records.json"))) {
+    const run = kanoniv.execute(spec, JSON.parse(read(join(dir, "records.json"))), AS_OF);
+    actual.decisions = { decisions: run.decisions, clusters: run.clusters };
+  }
+  const differing = Object.keys(expected).filter(
+    (surface) => canonical(expected[surface]) !== canonical(actual[surface]),
+  );
+  if (differing.length > 0) {
+    failed += 1;
+    console.error(`[fail] ${name}: ${differing.join(", ")} differ`);
+  } else {
+    console.log(`[ok] ${name}`);
+  }
+}
+console.log(`${cases.length - failed} of ${cases.length} case(s) conform`);
+process.exit(failed > 0 ? 1 : 0);
@@ -0,0 +1,149 @@
+//! Conversions between Python objects and the JSON values and samples
+//! `kanoniv_core` works with.
+
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyCapsule, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use serde::Serialize;
+
+/// A JSON value as the equivalent Python object.
+pub(crate) fn json_value_to_py(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
+    match value {
+        serde_json::Value::Null => Ok(py.None()),
+        serde_json::Value::Bool(b) => Ok(b.to_object(py)),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Ok(i.to_object(py))
+            } else if let Some(f) = n.as_f64() {
+                Ok(f.to_object(py))
+            } else {
+                Ok(py.None())
+            }
+        }
+        serde_json::Value::String(s) => Ok(s.to_object(py)),
+        serde_json::Value::Array(arr) => {
+            let list: Vec<PyObject> = arr
+                .iter()
+                .map(|v| json_value_to_py(py, v))
+                .collect::<PyResult<_>>()?;
+            Ok(list.to_object(py))
+        }
+        serde_json::Value::Object(map) => {
+            let dict = PyDict::new_bound(py);
+            for (k, v) in map {
+                dict.set_item(k, json_value_to_py(py, v)?)?;
+            }
+            Ok(dict.into())
+        }
+    }
+}
+
+/// A Python object built from dicts, lists, tuples, strings, numbers,
+/// booleans and `None` as the equivalent JSON value.
+pub(crate) fn py_to_json_value(obj: &Bound<'_, PyAny>) -> PyResult<serde_json::Value> {
+    if obj.is_none() {
+        return Ok(serde_json::Value::Null);
+    }
+    // `bool` subclasses `int`, so it goes first.
+    if obj.is_instance_of::<PyBool>() {
+        return Ok(serde_json::Value::Bool(obj.extract()?));
+    }
+    if obj.is_instance_of::<PyInt>() {
+        return Ok(serde_json::Value::from(obj.extract::<i64>()?));
+    }
+    if obj.is_instance_of::<PyFloat>() {
+        let f: f64 = obj.extract()?;
+        return serde_json::Number::from_f64(f)
+            .map(serde_json::Value::Number)
+            .ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{} is not valid JSON", f))
+            });
+    }
+    if let Ok(s) = obj.downcast::<PyString>() {
+        return Ok(serde_json::Value::String(s.to_str()?.to_string()));
+    }
+    if let Ok(dict) = obj.downcast::<PyDict>() {
+        let mut map = serde_json::Map::new();
+        for (k, v) in dict.iter() {
+            map.insert(k.extract::<String>()?, py_to_json_value(&v)?);
+        }
+        return Ok(serde_json::Value::Object(map));
+    }
+    if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        return obj
+            .iter()?
+            .map(|item| py_to_json_value(&item?))
+            .collect::<PyResult<_>>()
+            .map(serde_json::Value::Array);
+    }
+    Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+        "cannot convert {} to JSON",
+        obj.get_type().name()?
+    )))
+}
+
+/// The JSON form of a `kanoniv_core` result.
+pub(crate) fn to_json<T: Serialize>(value: &T) -> PyResult<serde_json::Value> {
+    serde_json::to_value(value)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+/// A `kanoniv_core` result as plain Python dicts and lists.
+pub(crate) fn to_py<T: Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    json_value_to_py(py, &to_json(value)?)
+}
+
+/// Records as CSV text (with a header row), or a DataFrame (pandas, polars,
+/// pyarrow) read through the Arrow PyCapsule interface, without a CSV
+/// round trip.
+pub(crate) fn sample_from_py(data: &Bound<'_, PyAny>) -> PyResult<kanoniv_core::Sample> {
+    if let Ok(csv) = data.extract::<String>() {
+        return kanoniv_core::Sample::from_csv(&csv)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{:#}", e)));
+    }
+    let frame = if data.hasattr("__arrow_c_stream__")? {
+        data.clone()
+    } else if data.hasattr("to_arrow")? {
+        // polars before 1.0
+        data.call_method0("to_arrow")?
+    } else {
+        // pandas before 2.2
+        let pyarrow = data.py().import_bound("pyarrow").map_err(|_| {
+            PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+                "expected CSV text or a DataFrame exporting __arrow_c_stream__",
+            )
+        })?;
+        pyarrow.call_method1("table", (data,))?
+    };
+    let capsule = frame
+        .call_method0("__arrow_c_stream__")?
+        .downcast_into::<PyCapsule>()?;
+    if capsule.name()?.and_then(|n| n.to_str().ok()) != Some("arrow_array_stream") {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
+            "__arrow_c_stream__ did not return an arrow_array_stream capsule",
+        ));
+    }
+    let stream = capsule.pointer() as *mut arrow_array::ffi_stream::FFI_ArrowArrayStream;
+    // SAFETY: the capsule holds a valid stream, which this takes over and
+    // leaves released for the capsule's destructor.
+    unsafe { kanoniv_core::Sample::from_arrow_c_stream(stream) }
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{:#}", e)))
+}
+
+/// `sample_from_py`, or a dict of them by source name, stacked into one
+/// sample of canonical attributes.
+pub(crate) fn records_from_py(
+    yaml_str: &str,
+    data: &Bound<'_, PyAny>,
+) -> PyResult<kanoniv_core::Sample> {
+    let Ok(by_source) = data.downcast::<PyDict>() else {
+        return sample_from_py(data);
+    };
+    let spec = kanoniv_core::parse_yaml(yaml_str)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let parts = by_source
+        .iter()
+        .map(|(source, records)| Ok((source.extract::<String>()?, sample_from_py(&records)?)))
+        .collect::<PyResult<Vec<_>>>()?;
+    kanoniv_core::Sample::from_sources(&spec, &parts)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{:#}", e)))
+}
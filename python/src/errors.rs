@@ -0,0 +1,82 @@
+//! Exceptions.
+//!
+//! Spec errors are raised as the class matching the code of the spec's first
+//! error, and carry that error's `code`, `path`, `line` and `column`, with
+//! every error under `diagnostics`. All are `ValueError`s, as before.
+
+use pyo3::create_exception;
+use pyo3::prelude::*;
+
+use crate::types::Diagnostic;
+
+create_exception!(
+    kanoniv._native,
+    KanonivError,
+    pyo3::exceptions::PyValueError,
+    "Base class of kanoniv spec errors."
+);
+create_exception!(
+    kanoniv._native,
+    ParseError,
+    KanonivError,
+    "The spec is not valid YAML (KNV09xx)."
+);
+create_exception!(
+    kanoniv._native,
+    SchemaError,
+    KanonivError,
+    "The spec breaks the schema (KNV00xx)."
+);
+create_exception!(
+    kanoniv._native,
+    SemanticError,
+    KanonivError,
+    "The spec is well-formed but inconsistent (KNV01xx)."
+);
+create_exception!(
+    kanoniv._native,
+    PlanError,
+    KanonivError,
+    "Planning or compiling a spec failed."
+);
+
+/// The error for `message` about `yaml_str`: by the code of the spec's
+/// first error, or `E` if the spec has none.
+pub(crate) fn spec_error<E: pyo3::PyTypeInfo>(yaml_str: &str, message: String) -> PyErr {
+    let errors: Vec<kanoniv_core::Diagnostic> = kanoniv_core::diagnose_yaml(yaml_str)
+        .into_iter()
+        .filter(|d| d.severity == kanoniv_core::Severity::Error)
+        .collect();
+    let err = match errors.first().and_then(|d| d.code.get(..5)) {
+        Some("KNV09") => ParseError::new_err(message),
+        Some("KNV00") => SchemaError::new_err(message),
+        Some("KNV01") => SemanticError::new_err(message),
+        _ => PyErr::new::<E, _>(message),
+    };
+    annotated(err, &errors)
+}
+
+/// A `KanonivError` not tied to a finding in the spec.
+pub(crate) fn kanoniv_error(message: String) -> PyErr {
+    annotated(KanonivError::new_err(message), &[])
+}
+
+fn annotated(err: PyErr, errors: &[kanoniv_core::Diagnostic]) -> PyErr {
+    let annotate = |py: Python<'_>| -> PyResult<()> {
+        let value = err.value_bound(py);
+        let first = errors.first();
+        value.setattr("code", first.map(|d| d.code.clone()))?;
+        value.setattr("path", first.and_then(|d| d.path.clone()))?;
+        value.setattr("line", first.and_then(|d| d.span).map(|s| s.line))?;
+        value.setattr("column", first.and_then(|d| d.span).map(|s| s.column))?;
+        let diagnostics = errors
+            .iter()
+            .map(Diagnostic::new)
+            .collect::<PyResult<Vec<_>>>()?;
+        value.setattr("diagnostics", diagnostics.into_py(py))
+    };
+    match Python::with_gil(annotate) {
+        Ok(()) => err,
+        Err(e) => e,
+    }
+}
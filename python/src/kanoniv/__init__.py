@@ -1,12 +1,19 @@
 """Kanoniv - Identity resolution as code."""
 
 from .spec import Spec
-from .validate import validate
+from .validate import validate, validate_many
 from .plan import plan
 from .diff import diff
+from ._native import Diagnostic, Plan, RiskFlag
+from .exceptions import SpecError, ParseError, SchemaError, SemanticError, PlanError
+from .profile import profile_source, SourceProfile
+from .calibrate import calibrate, Calibration
+from .lineage import lineage
+from .execute import execute
+from .hashing import tokenize
 from .source import Source
 from .reconcile import reconcile, ReconcileResult
-from .evaluate import EvaluateResult
+from .evaluate import EvaluateResult, EvaluationReport, evaluate_spec
 from .changelog import ChangeLog, EntityChange
 
 __version__ = "0.3.1"
@@ -44,11 +51,29 @@ __all__ = [
     "Spec",
     "Source",
     "validate",
+    "validate_many",
     "plan",
     "diff",
+    "Diagnostic",
+    "Plan",
+    "RiskFlag",
+    "SpecError",
+    "ParseError",
+    "SchemaError",
+    "SemanticError",
+    "PlanError",
+    "profile_source",
+    "SourceProfile",
+    "calibrate",
+    "Calibration",
+    "lineage",
+    "execute",
+    "tokenize",
     "reconcile",
     "ReconcileResult",
     "EvaluateResult",
+    "EvaluationReport",
+    "evaluate_spec",
     "ChangeLog",
     "EntityChange",
     "Client",
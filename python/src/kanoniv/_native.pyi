@@ -1,11 +1,133 @@
-"""Type stubs for the Rust native extension (kanoniv._native)."""
+"""Type stubs for the Rust native extension (kanoniv._native).
+
+Validation, planning, diffing, compilation and the sample functions release
+the GIL while they run, so they can be called from several threads at once.
+"""
+from typing import Any
+
+class KanonivError(ValueError):
+    """Base class of kanoniv spec errors.
+
+    ``code``, ``path``, ``line`` and ``column`` locate the spec's first error
+    (None when the error is not about a finding in the spec); ``diagnostics``
+    holds all of its errors.
+    """
+
+    code: str | None
+    path: str | None
+    line: int | None
+    column: int | None
+    diagnostics: list[Diagnostic]
+
+class ParseError(KanonivError):
+    """The spec is not valid YAML (KNV09xx)."""
+
+class SchemaError(KanonivError):
+    """The spec breaks the schema (KNV00xx)."""
+
+class SemanticError(KanonivError):
+    """The spec is well-formed but inconsistent (KNV01xx)."""
+
+class PlanError(KanonivError):
+    """Planning or compiling a spec failed."""
+
+# Results are typed classes over their JSON form: ``to_dict()`` returns it,
+# and subscripting (``flag["code"]``) reads a key of it.
+
+class Diagnostic:
+    """A validation finding, with the stable code ``kanoniv explain`` describes."""
+
+    code: str
+    severity: str
+    message: str
+    path: str | None
+    """Dotted YAML path of the offending node, e.g. ``rules[0].field``."""
+    line: int | None
+    column: int | None
+    suggestion: str | None
+    def to_dict(self) -> dict: ...
+    def __getitem__(self, key: str) -> Any: ...
+
+class RiskFlag:
+    """A finding of the planner, with a recommendation."""
+
+    severity: str
+    code: str
+    message: str
+    recommendation: str
+    def to_dict(self) -> dict: ...
+    def __getitem__(self, key: str) -> Any: ...
+
+class Plan:
+    """An execution plan: stages, match strategies, risk flags and summary."""
+
+    entity: str
+    identity_version: str
+    plan_hash: str
+    sources: list[dict]
+    execution_stages: list[dict]
+    match_strategies: list[dict]
+    survivorship_summary: list[dict]
+    blocking_analysis: dict
+    clustering: dict | None
+    execution: dict | None
+    risk_flags: list[RiskFlag]
+    risk_score: int
+    """Severity-weighted sum of the risk flags, 0 (no flags) to 100."""
+    waived: list[dict]
+    routing: dict
+    summary: str
+    def to_dict(self) -> dict: ...
+    def __getitem__(self, key: str) -> Any: ...
+
+class DiffResult:
+    """Changes between two spec versions."""
+
+    rules_added: list[str]
+    rules_removed: list[str]
+    rules_modified: list[dict]
+    thresholds_changed: bool
+    compatibility: dict
+    """Every change classified as ``safe``, ``risky`` or ``breaking``, with the
+    version bump they call for."""
+    routing: dict
+    summary: str
+    has_changes: bool
+    def to_dict(self) -> dict: ...
+    def __getitem__(self, key: str) -> Any: ...
 
 def validate(yaml_str: str) -> list[str]:
     """Validate a YAML spec - returns list of errors (empty = valid)."""
     ...
 
+def diagnose(yaml_str: str) -> list[Diagnostic]:
+    """Validate a YAML spec - returns structured diagnostics with code,
+    severity, message, path, line, column and suggestion.
+    Broken YAML yields one syntax diagnostic per broken top-level section."""
+    ...
+
+def validate_tiers(yaml_str: str, profile: str = "default") -> dict:
+    """Validate a YAML spec - returns {"errors", "warnings", "info"}, each a
+    list of ``Diagnostic``, plus "waived" (dicts) when waivers suppressed findings.
+    ``profile`` is "default", "strict" (warnings become errors) or "lenient"
+    (warnings become info)."""
+    ...
+
+def validate_many(
+    paths: list[str],
+    profile: str = "default",
+    jobs: int | None = None,
+    cache_dir: str | None = None,
+) -> list[dict]:
+    """Validate spec files across a thread pool of ``jobs`` workers (one per
+    core by default) - returns, for each path in order, a dict like
+    ``validate_tiers`` returns with its "path". A file that cannot be read or
+    composed over its base has a single KNV0903 error. With ``cache_dir``,
+    results are cached there and files unchanged since are not validated again."""
+    ...
+
 def validate_strict(yaml_str: str) -> list[str]:
-    """Strict validation including serde deserialization checks."""
+    """Strict validation: errors under the "strict" profile, so warnings fail too."""
     ...
 
 def validate_schema(yaml_str: str) -> list[str]:
@@ -20,20 +142,143 @@ def parse(yaml_str: str) -> dict:
     """Parse a YAML spec string into a dict."""
     ...
 
-def compile_ir(yaml_str: str) -> dict:
-    """Compile a YAML spec to intermediate representation with plan_hash."""
+def compile_ir(yaml_str: str, target: str = "ir", dialect: str = "ansi") -> dict | str:
+    """Compile a YAML spec to intermediate representation with plan_hash.
+
+    With ``target="sql"``, ``target="pyspark"`` or ``target="kafka"`` returns
+    generated code as a string.
+    """
     ...
 
-def diff(yaml_a: str, yaml_b: str) -> dict:
+def diff(yaml_a: str, yaml_b: str) -> DiffResult:
     """Diff two YAML specs - returns rules added/removed/modified, thresholds changed."""
     ...
 
-def hash(yaml_str: str) -> str:
-    """Compute SHA-256 hash of a spec (sha256:...)."""
+def hash(
+    yaml_str: str,
+    algorithm: str = "sha256",
+    key_env: str | None = None,
+    key_file: str | None = None,
+) -> str:
+    """Compute the hash of a spec's canonical form (sha256:... by default).
+    Key order, YAML style, comments and descriptions do not affect it.
+    algorithm is sha256, sha512 or blake3; a key read from the environment
+    variable key_env or the file key_file makes it keyed (hmac-sha256:...)."""
+    ...
+
+def tokenize(
+    ids: list[str],
+    yaml_str: str | None = None,
+    algorithm: str | None = None,
+    key_env: str | None = None,
+    key_file: str | None = None,
+) -> list[str]:
+    """Tokenize record identifiers as the spec's `hashing` section says;
+    algorithm, key_env and key_file override it."""
     ...
 
-def plan(yaml_str: str) -> dict:
-    """Generate a full execution plan with stages, strategies, risk flags, and summary."""
+def compose(yaml_str: str, registry: str | None = None) -> str:
+    """The spec composed over the base its `extends` names, resolved in
+    registry (default: $KANONIV_REGISTRY); unchanged if it extends none."""
+    ...
+
+def format_spec(yaml_str: str) -> str:
+    """Rewrite a spec in canonical form (same hash, comments kept)."""
+    ...
+
+def plan(
+    yaml_str: str,
+    timeout: float | None = None,
+    custom_risks: str | dict | None = None,
+    sample: Any | None = None,
+) -> Plan:
+    """Generate a full execution plan with stages, strategies, risk flags, and summary.
+
+    ``custom_risks`` is the YAML text of a custom risk rules file, or the dict
+    it parses to; its checks are flagged alongside the built-in ones. ``sample`` is records to measure
+    blocking on: CSV text, a DataFrame exporting ``__arrow_c_stream__``
+    (pandas 2.2+, polars, pyarrow; older pandas goes through pyarrow), or a
+    dict of those by source name, whose columns are mapped to attributes.
+
+    Raises TimeoutError if planning exceeds ``timeout`` seconds, ValueError
+    if ``timeout`` is not a finite number of seconds, 0 or more, and a
+    ``KanonivError`` subclass if the spec cannot be planned.
+    """
+    ...
+
+def profile_source(yaml_str: str, csv: Any, source: str | None = None) -> dict:
+    """Profile records (CSV text with a header row, or a DataFrame as for
+    ``plan``) against the attributes a source maps.
+
+    Reports null rates, distinct counts and email/phone/date format
+    conformance per attribute, with findings for attributes match rules
+    rely on. ``source`` defaults to the one the CSV columns identify.
+    """
+    ...
+
+def calibrate(yaml_str: str, labels: Any) -> dict:
+    """Score each match rule on labeled pairs and recommend thresholds and weights.
+
+    ``labels`` is CSV text or a DataFrame with a ``label`` column (match/non_match) and
+    ``left_<field>``/``right_<field>`` columns per rule field. Returns
+    per-rule precision/recall curves and suggested decision thresholds.
+    """
+    ...
+
+def learn_weights(yaml_str: str, csv: Any) -> dict:
+    """Learn match rule weights from unlabeled records (Fellegi-Sunter EM).
+
+    ``csv`` is records as for ``plan``'s ``sample``.
+    Returns per-rule m/u probabilities and weights, and the spec with the
+    learned weights under ``yaml``.
+    """
+    ...
+
+def evaluate(yaml_str: str, data: Any, truth: Any, as_of: str) -> dict:
+    """Score the spec's clusters on ``data`` against ground-truth labels.
+
+    ``data`` is a dict of records (CSV text or a DataFrame) by source name,
+    each in its source's columns, or one table of them with a
+    ``source_name`` column. ``truth`` is CSV text or a DataFrame with a
+    ``record_key`` (``source:id``) and a ``cluster_id`` column, as
+    ``kanoniv generate-data`` writes. Returns pairwise precision/recall/F1,
+    ARI and B-cubed metrics, and per-rule similarities and F1 without the
+    rule. Values past their retention period as of ``as_of``
+    (``YYYY-MM-DD``) are expired first.
+    """
+    ...
+
+def execute(yaml_str: str, data: Any, as_of: str, timeout: float | None = None) -> dict:
+    """Run the spec over ``data`` and return its decisions, clusters and golden records.
+
+    ``data`` is a dict of records by source name, each CSV text or a
+    DataFrame exporting ``__arrow_c_stream__`` (pandas 2.2+, polars,
+    pyarrow; older pandas goes through pyarrow) in its source's columns, or
+    one table of them with a ``source_name`` column. Values past their
+    retention period as of ``as_of`` (``YYYY-MM-DD``) are expired first.
+    The GIL is released while the records are matched.
+
+    Raises TimeoutError if the run exceeds ``timeout`` seconds, ValueError
+    if ``timeout`` is not a finite number of seconds, 0 or more, and a
+    ``KanonivError`` subclass if the spec cannot be run.
+    """
+    ...
+
+def lineage(
+    yaml_str: str,
+    members: Any,
+    as_of: str,
+    entity_id: str | None = None,
+    field: str | None = None,
+) -> list[dict]:
+    """Trace golden record fields back to the member records they survive from.
+
+    ``members`` is CSV text or a DataFrame of cluster members, as for
+    ``kanoniv golden-records``, with values expired as of ``as_of``
+    (``YYYY-MM-DD``). Returns one dict per golden field (the
+    survivor's source and record key, the losing candidates best first and
+    the rule that decided), narrowed to ``entity_id`` and/or ``field``.
+    """
     ...
 
 def reconcile_local(yaml_str: str, entities_json: str) -> dict:
@@ -0,0 +1,38 @@
+"""Match rule calibration - thin wrapper over the Rust calibrator."""
+from typing import Any
+
+from kanoniv._native import calibrate as _calibrate
+from kanoniv.spec import Spec
+
+
+class Calibration:
+    """Per-rule metrics and suggested thresholds from labeled pairs."""
+
+    def __init__(self, data: dict):
+        self._data = data
+
+    @property
+    def pairs(self) -> int:
+        return self._data.get("pairs", 0)
+
+    @property
+    def matches(self) -> int:
+        return self._data.get("matches", 0)
+
+    @property
+    def rules(self) -> list[dict]:
+        """Precision, recall and recommended threshold/weight per rule."""
+        return self._data.get("rules", [])
+
+    @property
+    def decision(self) -> dict:
+        """Current and recommended match/review thresholds."""
+        return self._data.get("decision", {})
+
+    def to_dict(self) -> dict:
+        return self._data
+
+
+def calibrate(spec: Spec, labels: Any) -> Calibration:
+    """Score ``spec``'s match rules on ``labels`` (labeled pairs, as CSV text or a DataFrame)."""
+    return Calibration(_calibrate(spec.raw, labels))
@@ -7,7 +7,7 @@ from dataclasses import dataclass, field
 from typing import Any
 
 from .source import Source
-from .spec import Spec
+from .spec import Spec, attribute_columns
 
 
 _BATCH_SIZE = 500
@@ -324,7 +324,7 @@ def reconcile(
     attr_maps: dict[str, dict[str, str]] = {}
     for spec_src in spec.sources:
         src_name = spec_src.get("name", "")
-        attrs = spec_src.get("attributes", {})
+        attrs = attribute_columns(spec_src)
         if attrs and src_name:
             attr_maps[src_name] = {v: k for k, v in attrs.items()}
 
@@ -134,6 +134,23 @@ class DiffResult:
         """Whether identity_version changed."""
         return self._data.get("version_changed", False)
 
+    # -- Compatibility --
+
+    @property
+    def compatibility(self) -> dict:
+        """Changes classified as ``safe``, ``risky`` or ``breaking``.
+
+        Keys: ``impact`` (most severe, or None), ``bump`` (``major``,
+        ``minor``, ``patch`` or ``none``), ``suggested_version`` and
+        ``changes`` (dicts with ``path``, ``impact``, ``description``).
+        """
+        return self._data.get("compatibility", {})
+
+    @property
+    def routing(self) -> dict:
+        """Changes by responsible owner, when the specs declare owners."""
+        return self._data.get("routing", {})
+
     # -- Aggregate --
 
     @property
@@ -177,5 +194,4 @@ def diff(spec_a: Spec, spec_b: Spec) -> DiffResult:
         A ``DiffResult`` with granular change information across rules,
         sources, entity, blocking, thresholds, survivorship, and scoring.
     """
-    data = _diff(spec_a.raw, spec_b.raw)
-    return DiffResult(data)
+    return DiffResult(_diff(spec_a.raw, spec_b.raw).to_dict())
@@ -2,10 +2,14 @@
 from __future__ import annotations
 
 from dataclasses import dataclass, field
+from datetime import date
 from itertools import combinations
-from typing import Any
+from typing import Any, Optional
 
+from ._native import evaluate as _evaluate_spec
+from .execute import _as_of
 from .reconcile import ReconcileResult
+from .spec import Spec
 
 
 @dataclass
@@ -302,3 +306,46 @@ def _evaluate(
         er.ground_truth_clusters = len(gt_clusters)
 
     return er
+
+
+class EvaluationReport:
+    """Pairwise, cluster and per-rule metrics of a spec against ground truth."""
+
+    def __init__(self, data: dict):
+        self._data = data
+
+    @property
+    def pairwise(self) -> dict:
+        """True/false positives, precision, recall, F1 and blocking's candidate recall."""
+        return self._data.get("pairwise", {})
+
+    @property
+    def clusters(self) -> dict:
+        """Adjusted Rand index, B-cubed precision/recall/F1 and exactly recovered clusters."""
+        return self._data.get("clusters", {})
+
+    @property
+    def rules(self) -> list[dict]:
+        """Mean similarity on true matches and non-matches, and F1 without each rule."""
+        return self._data.get("rules", [])
+
+    @property
+    def warnings(self) -> list[str]:
+        return self._data.get("warnings", [])
+
+    def to_dict(self) -> dict:
+        return self._data
+
+
+def evaluate_spec(
+    spec: Spec, data: Any, truth: Any, as_of: Optional[date] = None
+) -> EvaluationReport:
+    """Run ``spec`` on ``data`` and score its clusters against ``truth``.
+
+    ``data`` is a dict of records (CSV text or DataFrames) by source name, or
+    one table of them with a ``source_name`` column; ``truth`` has a
+    ``record_key`` and a ``cluster_id`` column, as ``kanoniv generate-data``
+    writes to ``labels.csv``. The run is as of ``as_of`` (today, UTC, by
+    default), as for ``execute``.
+    """
+    return EvaluationReport(_evaluate_spec(spec.raw, data, truth, _as_of(as_of)))
@@ -1,9 +1,16 @@
-"""Typed exceptions for Kanoniv API errors."""
+"""Typed exceptions for Kanoniv API errors and spec errors."""
 
 from __future__ import annotations
 
 from typing import Any
 
+# Errors of the native spec tooling (parse, validate, compile, plan, ...).
+# All are ValueErrors carrying ``code``, ``path``, ``line``, ``column`` and
+# ``diagnostics``; the base is re-exported as ``SpecError`` so it does not
+# shadow the API client's ``KanonivError`` below.
+from kanoniv._native import KanonivError as SpecError
+from kanoniv._native import ParseError, PlanError, SchemaError, SemanticError
+
 
 class KanonivError(Exception):
     """Base exception for all Kanoniv SDK errors."""
@@ -0,0 +1,30 @@
+"""Local execution - thin wrapper over the Rust interpreter."""
+from datetime import date, datetime, timezone
+from typing import Any, Optional
+
+from kanoniv._native import execute as _execute
+from kanoniv.spec import Spec
+
+
+def execute(
+    spec: Spec,
+    data: Any,
+    timeout: Optional[float] = None,
+    as_of: Optional[date] = None,
+) -> dict:
+    """Match ``data`` under ``spec`` and return the decisions, clusters and
+    golden records.
+
+    ``data`` is a dict of records by source name, each CSV text or a pandas or
+    polars DataFrame, or one table of them with a ``source_name`` column.
+    Values past their retention period as of ``as_of`` (today, UTC, by
+    default) are expired first.
+    """
+    return _execute(spec.raw, data, _as_of(as_of), timeout)
+
+
+def _as_of(as_of: Optional[date]) -> str:
+    """``as_of`` as ``YYYY-MM-DD``, or today's UTC date."""
+    if as_of is None:
+        as_of = datetime.now(timezone.utc).date()
+    return as_of.isoformat()
@@ -0,0 +1,27 @@
+"""Identifier tokenization - thin wrapper over the Rust hashers."""
+from typing import Optional
+
+from kanoniv._native import tokenize as _tokenize
+from kanoniv.spec import Spec
+
+
+def tokenize(
+    ids: list[str],
+    spec: Optional[Spec] = None,
+    algorithm: Optional[str] = None,
+    key_env: Optional[str] = None,
+    key_file: Optional[str] = None,
+) -> list[str]:
+    """Tokenize record identifiers as ``spec``'s ``hashing`` section says.
+
+    ``algorithm`` (sha256, sha512, blake3) and the key source (``key_env``
+    names an environment variable, ``key_file`` a file) override the spec.
+    With a key, tokens are keyed: ``hmac-sha256:...`` rather than ``sha256:...``.
+    """
+    return _tokenize(
+        ids,
+        spec.raw if spec is not None else None,
+        algorithm=algorithm,
+        key_env=key_env,
+        key_file=key_file,
+    )
@@ -0,0 +1,24 @@
+"""Identity lineage - thin wrapper over the Rust survivorship engine."""
+from datetime import date
+from typing import Any, Optional
+
+from kanoniv._native import lineage as _lineage
+from kanoniv.execute import _as_of
+from kanoniv.spec import Spec
+
+
+def lineage(
+    spec: Spec,
+    members: Any,
+    entity_id: Optional[str] = None,
+    field: Optional[str] = None,
+    as_of: Optional[date] = None,
+) -> list[dict]:
+    """Where each golden field of ``members`` (cluster members, as CSV text or a
+    DataFrame) comes from under ``spec``'s survivorship rules.
+
+    Pass ``entity_id`` for one entity's fields, ``field`` for one field across
+    entities, or both. Values past their retention period as of ``as_of``
+    (today, UTC, by default) have no lineage.
+    """
+    return _lineage(spec.raw, members, _as_of(as_of), entity_id, field)
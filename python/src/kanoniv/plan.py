@@ -1,12 +1,15 @@
 """Execution planning - thin wrapper over Rust planner."""
-from kanoniv._native import plan as _plan
+from typing import Any, Optional, Union
+
+from kanoniv._native import Plan, RiskFlag, plan as _plan
 from kanoniv.spec import Spec
 
 class PlanResult:
     """Structured execution plan for an identity spec."""
 
-    def __init__(self, data: dict):
-        self._data = data
+    def __init__(self, plan: Plan):
+        self._plan = plan
+        self._data = plan.to_dict()
 
     @property
     def entity(self) -> str:
@@ -33,8 +36,23 @@ class PlanResult:
         return self._data.get("blocking_analysis", {})
 
     @property
-    def risk_flags(self) -> list[dict]:
-        return self._data.get("risk_flags", [])
+    def risk_flags(self) -> list[RiskFlag]:
+        return self._plan.risk_flags
+
+    @property
+    def risk_score(self) -> int:
+        """Severity-weighted risk, 0 (no flags) to 100."""
+        return self._data.get("risk_score", 0)
+
+    @property
+    def waived(self) -> list[dict]:
+        """Risk flags suppressed by a waiver in the spec, with the waiver."""
+        return self._data.get("waived", [])
+
+    @property
+    def routing(self) -> dict:
+        """Risk flags by responsible owner, when the spec declares owners."""
+        return self._data.get("routing", {})
 
     def summary(self) -> str:
         """Human-readable plan summary."""
@@ -47,6 +65,17 @@ class PlanResult:
     def __repr__(self) -> str:
         return self.summary()
 
-def plan(spec: Spec) -> PlanResult:
-    data = _plan(spec.raw)
-    return PlanResult(data)
+def plan(
+    spec: Spec,
+    custom_risks: Optional[Union[str, dict]] = None,
+    sample: Optional[Any] = None,
+) -> PlanResult:
+    """Plan ``spec``; ``custom_risks`` is the YAML text of a custom risk rules
+    file, or the dict it parses to.
+
+    ``sample`` is records to measure blocking on: CSV text (with a header
+    row), a pandas or polars DataFrame (read through Arrow, no CSV round
+    trip), or a dict of those by source name. The measurements appear under
+    ``blocking["sample"]`` and per key.
+    """
+    return PlanResult(_plan(spec.raw, custom_risks=custom_risks, sample=sample))
@@ -0,0 +1,41 @@
+"""Source data profiling - thin wrapper over the Rust profiler."""
+from typing import Any, Optional
+
+from kanoniv._native import profile_source as _profile_source
+from kanoniv.spec import Spec
+
+
+class SourceProfile:
+    """Per-attribute profile of one source's records."""
+
+    def __init__(self, data: dict):
+        self._data = data
+
+    @property
+    def source(self) -> str:
+        return self._data.get("source", "")
+
+    @property
+    def records(self) -> int:
+        return self._data.get("records", 0)
+
+    @property
+    def attributes(self) -> list[dict]:
+        """Null rate, distinct count and format conformance per attribute."""
+        return self._data.get("attributes", [])
+
+    @property
+    def findings(self) -> list[dict]:
+        """Attributes too sparse or malformed for the rules matching on them."""
+        return self._data.get("findings", [])
+
+    def to_dict(self) -> dict:
+        return self._data
+
+
+def profile_source(spec: Spec, csv: Any, source: Optional[str] = None) -> SourceProfile:
+    """Profile ``csv`` as records of ``source`` in ``spec``.
+
+    ``csv`` is CSV text with a header row, or a pandas or polars DataFrame.
+    """
+    return SourceProfile(_profile_source(spec.raw, csv, source=source))
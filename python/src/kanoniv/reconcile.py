@@ -8,7 +8,7 @@ from pathlib import Path
 from typing import Any
 
 from .source import Source
-from .spec import Spec
+from .spec import Spec, attribute_columns
 from .validate import validate
 
 
@@ -167,7 +167,7 @@ def _validate_source_attributes(
             )
             continue
 
-        attrs = spec_by_name[source.name].get("attributes", {})
+        attrs = attribute_columns(spec_by_name[source.name])
         if not attrs:
             continue
 
@@ -255,7 +255,7 @@ def reconcile(
     attr_maps: dict[str, dict[str, str]] = {}
     for spec_src in spec.sources:
         src_name = spec_src.get("name", "")
-        attrs = spec_src.get("attributes", {})
+        attrs = attribute_columns(spec_src)
         if attrs and src_name:
             attr_maps[src_name] = {v: k for k, v in attrs.items()}
 
@@ -2,6 +2,47 @@
 from pathlib import Path
 from kanoniv._native import parse
 
+
+def attribute_columns(source: dict) -> dict[str, str]:
+    """Canonical attribute -> source column for one of a spec's sources.
+
+    An attribute maps to its column name, or to a ``{column, type}`` mapping
+    when the spec declares its type.
+    """
+    columns = {}
+    for canonical, mapping in (source.get("attributes") or {}).items():
+        if isinstance(mapping, dict):
+            mapping = mapping.get("column", canonical)
+        columns[canonical] = mapping
+    return columns
+
+
+def _aliases(mappings: dict) -> dict[str, str]:
+    """Lowercased column alias -> canonical attribute, from ``mappings``."""
+    aliases = {}
+    for canonical, columns in mappings.items():
+        for column in [canonical, *(columns or [])]:
+            aliases.setdefault(str(column).lower(), canonical)
+    return aliases
+
+
+def _resolve_attributes(source: dict, aliases: dict[str, str]) -> dict:
+    """``source`` with its attributes keyed by canonical attribute.
+
+    Sources may list columns, or key attributes by a column alias; both
+    resolve through the spec's ``mappings``.
+    """
+    attrs = source.get("attributes")
+    if isinstance(attrs, list):
+        attrs = {column: column for column in attrs}
+    elif not isinstance(attrs, dict) or not aliases:
+        return source
+    resolved = {}
+    for name, mapping in attrs.items():
+        resolved.setdefault(aliases.get(name.lower(), name), mapping)
+    return {**source, "attributes": resolved}
+
+
 class Spec:
     """A Kanoniv identity specification."""
 
@@ -35,8 +76,9 @@ class Spec:
         raw = self._parsed.get("sources", [])
         if isinstance(raw, dict):
             # Map-style: {name: {config...}} → [{name: name, ...config}]
-            return [{**v, "name": k} for k, v in raw.items()]
-        return raw
+            raw = [{**v, "name": k} for k, v in raw.items()]
+        aliases = _aliases(self._parsed.get("mappings") or {})
+        return [_resolve_attributes(source, aliases) for source in raw]
 
     @property
     def rules(self) -> list[dict]:
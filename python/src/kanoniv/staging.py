@@ -42,12 +42,13 @@ def stage_sources(
     import duckdb
 
     from .cloud_io import read_arrow
+    from .spec import attribute_columns
 
     # Build attribute mappings from spec: {source_name: {source_col: canonical_col}}
     attr_maps: dict[str, dict[str, str]] = {}
     for spec_src in spec.sources:
         src_name = spec_src.get("name", "")
-        attrs = spec_src.get("attributes", {})
+        attrs = attribute_columns(spec_src)
         if attrs and src_name:
             # spec has canonical -> source mapping; we need source -> canonical
             attr_maps[src_name] = {v: k for k, v in attrs.items()}
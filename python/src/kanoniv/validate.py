@@ -1,11 +1,32 @@
 """Spec validation - thin wrapper over Rust validator."""
-from kanoniv._native import validate as _validate
+from typing import Optional
+
+from kanoniv._native import Diagnostic, validate_many as _validate_many, validate_tiers as _validate_tiers
 from kanoniv.spec import Spec
 
 
+def _render(diagnostic: Diagnostic) -> str:
+    suggestion = diagnostic.suggestion
+    return f"{diagnostic.message} {suggestion}" if suggestion else diagnostic.message
+
+
 class ValidationResult:
-    def __init__(self, errors: list[str]):
+    def __init__(
+        self,
+        errors: list[str],
+        warnings: Optional[list[str]] = None,
+        info: Optional[list[str]] = None,
+        diagnostics: Optional[dict] = None,
+        path: Optional[str] = None,
+    ):
+        self.path = path
         self.errors = errors
+        self.warnings = warnings or []
+        self.info = info or []
+        # Structured findings by tier: {"errors": [Diagnostic, ...], "warnings": [...],
+        # "info": [...]}, plus "waived" (dicts) when waivers in the spec suppressed anything.
+        self.diagnostics = diagnostics or {"errors": [], "warnings": [], "info": []}
+        self.waived = self.diagnostics.get("waived", [])
         self.valid = len(errors) == 0
 
     def raise_on_error(self) -> None:
@@ -17,15 +38,53 @@ class ValidationResult:
 
     def __repr__(self) -> str:
         if self.valid:
+            if self.warnings:
+                return f"<ValidationResult: Valid, {len(self.warnings)} warnings>"
             return "<ValidationResult: Valid>"
         return f"<ValidationResult: {len(self.errors)} errors>"
 
 
-def validate(spec: Spec) -> ValidationResult:
+def validate(spec: Spec, profile: str = "default") -> ValidationResult:
     """Validate a spec for schema and semantic correctness.
 
     Args:
         spec: The identity spec to validate.
+        profile: ``"default"``, ``"strict"`` (warnings become errors) or
+            ``"lenient"`` (warnings become info).
+    """
+    return _result(_validate_tiers(spec.raw, profile))
+
+
+def validate_many(
+    paths: list[str],
+    profile: str = "default",
+    jobs: Optional[int] = None,
+    cache_dir: Optional[str] = None,
+) -> list[ValidationResult]:
+    """Validate many spec files at once, in parallel.
+
+    Each file is composed over any base it extends and validated under the
+    policy governing it, as ``kanoniv validate`` does.
+
+    Args:
+        paths: Spec files to validate.
+        profile: As for ``validate``.
+        jobs: Worker threads; one per core by default.
+        cache_dir: Directory to cache results in, so files unchanged since
+            an earlier call are not validated again.
+
+    Returns:
+        One result per path, in order, each with its ``path``.
     """
-    errors = _validate(spec.raw)
-    return ValidationResult(errors)
+    reports = _validate_many([str(p) for p in paths], profile, jobs, cache_dir)
+    return [_result(tiers, tiers["path"]) for tiers in reports]
+
+
+def _result(tiers: dict, path: Optional[str] = None) -> ValidationResult:
+    return ValidationResult(
+        errors=[_render(d) for d in tiers["errors"]],
+        warnings=[_render(d) for d in tiers["warnings"]],
+        info=[_render(d) for d in tiers["info"]],
+        diagnostics=tiers,
+        path=path,
+    )
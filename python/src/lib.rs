@@ -1,120 +1,396 @@
+// pyo3 0.22 macro expansion trips this lint on `PyResult` returns.
+#![allow(clippy::useless_conversion)]
+// ...and `create_exception!` checks pyo3's own `gil-refs` feature here.
+#![allow(unexpected_cfgs)]
+
+mod convert;
+mod errors;
+mod types;
+
 use pyo3::prelude::*;
+use std::path::PathBuf;
 use pyo3::types::PyDict;
 
-// ── Helper: serde_json::Value → Python object ──────────────────────
-
-fn json_value_to_py(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
-    match value {
-        serde_json::Value::Null => Ok(py.None()),
-        serde_json::Value::Bool(b) => Ok(b.to_object(py)),
-        serde_json::Value::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                Ok(i.to_object(py))
-            } else if let Some(f) = n.as_f64() {
-                Ok(f.to_object(py))
-            } else {
-                Ok(py.None())
-            }
-        }
-        serde_json::Value::String(s) => Ok(s.to_object(py)),
-        serde_json::Value::Array(arr) => {
-            let list: Vec<PyObject> = arr
-                .iter()
-                .map(|v| json_value_to_py(py, v))
-                .collect::<PyResult<_>>()?;
-            Ok(list.to_object(py))
-        }
-        serde_json::Value::Object(map) => {
-            let dict = PyDict::new_bound(py);
-            for (k, v) in map {
-                dict.set_item(k, json_value_to_py(py, v)?)?;
-            }
-            Ok(dict.into())
-        }
-    }
-}
+use convert::{json_value_to_py, py_to_json_value, records_from_py, sample_from_py, to_json, to_py};
+use errors::{kanoniv_error, spec_error, KanonivError, ParseError, PlanError, SchemaError, SemanticError};
+use types::{Diagnostic, DiffResult, Plan, RiskFlag};
 
 // ── PyO3 functions ─────────────────────────────────────────────────
 
 #[pyfunction]
-fn validate(yaml_str: &str) -> PyResult<Vec<String>> {
-    kanoniv_core::validate_yaml(yaml_str)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+fn validate(py: Python<'_>, yaml_str: &str) -> PyResult<Vec<String>> {
+    py.allow_threads(|| kanoniv_core::validate_yaml(yaml_str))
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))
+}
+
+#[pyfunction]
+fn diagnose(py: Python<'_>, yaml_str: &str) -> PyResult<Vec<Diagnostic>> {
+    py.allow_threads(|| kanoniv_core::diagnose_yaml(yaml_str))
+        .iter()
+        .map(Diagnostic::new)
+        .collect()
+}
+
+#[pyfunction]
+#[pyo3(signature = (yaml_str, profile="default"))]
+fn validate_tiers(py: Python<'_>, yaml_str: &str, profile: &str) -> PyResult<PyObject> {
+    let profile: kanoniv_core::Profile = profile
+        .parse()
+        .map_err(|e: anyhow::Error| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let tiers = py.allow_threads(|| kanoniv_core::validate_tiers(yaml_str, profile));
+    Ok(tiers_to_py(py, &tiers)?.into())
+}
+
+#[pyfunction]
+#[pyo3(signature = (paths, profile="default", jobs=None, cache_dir=None))]
+fn validate_many(
+    py: Python<'_>,
+    paths: Vec<PathBuf>,
+    profile: &str,
+    jobs: Option<usize>,
+    cache_dir: Option<PathBuf>,
+) -> PyResult<Vec<PyObject>> {
+    let profile: kanoniv_core::Profile = profile
+        .parse()
+        .map_err(|e: anyhow::Error| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let options = kanoniv_core::BatchOptions {
+        profile,
+        jobs,
+        cache: cache_dir.as_deref().map(kanoniv_core::Cache::new),
+        ..Default::default()
+    };
+    let reports = py.allow_threads(|| kanoniv_core::validate_many_with(&paths, &options));
+    reports
+        .iter()
+        .map(|report| {
+            let dict = tiers_to_py(py, &report.findings)?;
+            dict.set_item("path", report.path.to_string_lossy())?;
+            Ok(dict.into())
+        })
+        .collect()
+}
+
+/// Findings split by severity, as the dict `validate_tiers` returns.
+fn tiers_to_py<'py>(py: Python<'py>, tiers: &kanoniv_core::Tiers) -> PyResult<Bound<'py, PyDict>> {
+    let value = to_json(tiers)?;
+    let dict = PyDict::new_bound(py);
+    for tier in ["errors", "warnings", "info"] {
+        let diagnostics: Vec<Diagnostic> = value[tier]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|d| Diagnostic { data: d.clone() })
+            .collect();
+        dict.set_item(tier, diagnostics.into_py(py))?;
+    }
+    if let Some(waived) = value.get("waived") {
+        dict.set_item("waived", json_value_to_py(py, waived)?)?;
+    }
+    Ok(dict)
 }
 
 #[pyfunction]
-fn validate_strict(yaml_str: &str) -> PyResult<Vec<String>> {
-    kanoniv_core::validate_yaml(yaml_str)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+fn validate_strict(py: Python<'_>, yaml_str: &str) -> PyResult<Vec<String>> {
+    kanoniv_core::parse_yaml(yaml_str)
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
+    let tiers = py.allow_threads(|| kanoniv_core::validate_tiers(yaml_str, kanoniv_core::Profile::Strict));
+    Ok(tiers.errors.iter().map(ToString::to_string).collect())
 }
 
 #[pyfunction]
 fn validate_schema(yaml_str: &str) -> PyResult<Vec<String>> {
     let spec = kanoniv_core::parse_yaml(yaml_str)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
     kanoniv_core::validate_schema(&spec)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))
 }
 
 #[pyfunction]
 fn validate_semantics(yaml_str: &str) -> PyResult<Vec<String>> {
     let spec = kanoniv_core::parse_yaml(yaml_str)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
     kanoniv_core::validate_semantics(&spec)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))
 }
 
 #[pyfunction]
 fn parse(py: Python<'_>, yaml_str: &str) -> PyResult<PyObject> {
     let value = kanoniv_core::parse_yaml(yaml_str)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
     json_value_to_py(py, &value)
 }
 
 #[pyfunction]
-fn compile_ir(py: Python<'_>, yaml_str: &str) -> PyResult<PyObject> {
+#[pyo3(signature = (yaml_str, target="ir", dialect="ansi"))]
+fn compile_ir(py: Python<'_>, yaml_str: &str, target: &str, dialect: &str) -> PyResult<PyObject> {
     let spec = kanoniv_core::parse_yaml(yaml_str)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    let ir = kanoniv_core::compile_to_ir(&spec)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    json_value_to_py(py, &ir)
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
+    let ir = py.allow_threads(|| kanoniv_core::compile_to_ir(&spec))
+        .map_err(|e| spec_error::<PlanError>(yaml_str, e.to_string()))?;
+    if target == "ir" {
+        return json_value_to_py(py, &ir);
+    }
+    let typed = kanoniv_core::Ir::from_value(&ir)
+        .map_err(|e| PlanError::new_err(e.to_string()))?;
+    let code = match target {
+        "sql" => {
+            let dialect = dialect
+                .parse()
+                .map_err(|e: anyhow::Error| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+            py.allow_threads(|| kanoniv_core::generate_sql(&typed, dialect))
+        }
+        "pyspark" => py.allow_threads(|| kanoniv_core::generate_pyspark(&typed)),
+        "kafka" => py.allow_threads(|| kanoniv_core::generate_kafka(&typed)),
+        other => {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Unknown compile target: '{}'. Expected one of: ir, sql, pyspark, kafka",
+                other
+            )))
+        }
+    }
+    .map_err(|e| PlanError::new_err(e.to_string()))?;
+    Ok(code.to_object(py))
 }
 
 #[pyfunction]
-fn diff(py: Python<'_>, yaml_a: &str, yaml_b: &str) -> PyResult<PyObject> {
-    let result = kanoniv_core::compute_diff(yaml_a, yaml_b)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    let value = serde_json::to_value(&result)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    json_value_to_py(py, &value)
+fn diff(py: Python<'_>, yaml_a: &str, yaml_b: &str) -> PyResult<DiffResult> {
+    let result = py.allow_threads(|| kanoniv_core::compute_diff(yaml_a, yaml_b)).map_err(|e| {
+        let failed = if kanoniv_core::parse_yaml(yaml_a).is_err() { yaml_a } else { yaml_b };
+        spec_error::<KanonivError>(failed, e.to_string())
+    })?;
+    DiffResult::new(&result)
 }
 
 #[pyfunction]
-fn hash(yaml_str: &str) -> PyResult<String> {
-    use sha2::Digest;
-    let spec: serde_json::Value = serde_yaml::from_str(yaml_str)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    let canonical = serde_json::to_string(&spec)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    let mut hasher = sha2::Sha256::new();
-    hasher.update(canonical.as_bytes());
-    Ok(format!("sha256:{:x}", hasher.finalize()))
+#[pyo3(signature = (yaml_str, algorithm="sha256", key_env=None, key_file=None))]
+fn hash(
+    yaml_str: &str,
+    algorithm: &str,
+    key_env: Option<String>,
+    key_file: Option<String>,
+) -> PyResult<String> {
+    let spec = kanoniv_core::parse_yaml(yaml_str)
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
+    let key = kanoniv_core::KeySource {
+        key_env,
+        key_file: key_file.map(Into::into),
+    };
+    let hasher = algorithm
+        .parse()
+        .and_then(|algorithm| kanoniv_core::Hasher::from_source(algorithm, &key))
+        .map_err(|e| kanoniv_error(format!("{:#}", e)))?;
+    Ok(kanoniv_core::canonical_hash_with(&spec, &hasher))
 }
 
 #[pyfunction]
-fn plan(py: Python<'_>, yaml_str: &str) -> PyResult<PyObject> {
-    let result = kanoniv_core::generate_plan(yaml_str)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    let value = serde_json::to_value(&result)
+#[pyo3(signature = (ids, yaml_str=None, algorithm=None, key_env=None, key_file=None))]
+fn tokenize(
+    ids: Vec<String>,
+    yaml_str: Option<&str>,
+    algorithm: Option<&str>,
+    key_env: Option<String>,
+    key_file: Option<String>,
+) -> PyResult<Vec<String>> {
+    let key = kanoniv_core::KeySource {
+        key_env,
+        key_file: key_file.map(Into::into),
+    };
+    let config = match yaml_str {
+        Some(yaml_str) => kanoniv_core::parse_yaml(yaml_str)
+            .and_then(|spec| kanoniv_core::HashingConfig::from_spec(&spec))
+            .map_err(|e| spec_error::<KanonivError>(yaml_str, format!("{:#}", e)))?,
+        None => kanoniv_core::HashingConfig::default(),
+    };
+    let hasher = config
+        .overridden(algorithm, &key)
+        .and_then(|config| config.hasher())
+        .map_err(|e| kanoniv_error(format!("{:#}", e)))?;
+    Ok(ids.iter().map(|id| hasher.token(id)).collect())
+}
+
+#[pyfunction]
+#[pyo3(signature = (yaml_str, registry=None))]
+fn compose(yaml_str: &str, registry: Option<&str>) -> PyResult<String> {
+    kanoniv_core::compose_yaml(yaml_str, registry)
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, format!("{:#}", e)))
+}
+
+#[pyfunction]
+fn format_spec(yaml_str: &str) -> PyResult<String> {
+    kanoniv_core::format_spec(yaml_str)
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))
+}
+
+/// Custom risk rules as the YAML text of a rules file, or as the dict it
+/// would parse to.
+fn custom_risks_from_py(rules: &Bound<'_, PyAny>) -> PyResult<kanoniv_core::CustomRisks> {
+    let yaml = match rules.extract::<String>() {
+        Ok(yaml) => yaml,
+        Err(_) => serde_json::to_string(&py_to_json_value(rules)?)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+    };
+    kanoniv_core::CustomRisks::from_yaml(&yaml).map_err(|e| kanoniv_error(format!("{:#}", e)))
+}
+
+#[pyfunction]
+#[pyo3(signature = (yaml_str, timeout=None, custom_risks=None, sample=None, rows=None, pair_budget=None))]
+fn plan(
+    py: Python<'_>,
+    yaml_str: &str,
+    timeout: Option<f64>,
+    custom_risks: Option<&Bound<'_, PyAny>>,
+    sample: Option<&Bound<'_, PyAny>>,
+    rows: Option<std::collections::BTreeMap<String, u64>>,
+    pair_budget: Option<u64>,
+) -> PyResult<Plan> {
+    let custom_risks = custom_risks
+        .map(custom_risks_from_py)
+        .transpose()?
+        .unwrap_or_default();
+    let sample = sample
+        .map(|sample| records_from_py(yaml_str, sample))
+        .transpose()?;
+    let options = kanoniv_core::PlanOptions {
+        cancel: timeout
+            .map(kanoniv_core::CancellationToken::with_timeout_secs)
+            .transpose()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+        custom_risks,
+        sample,
+        policy: kanoniv_core::Policy::default(),
+        plugins: kanoniv_core::PluginRegistry::default(),
+        rows: rows.unwrap_or_default(),
+        pair_budget,
+    };
+    let result = py.allow_threads(|| kanoniv_core::generate_plan_with(yaml_str, &options)).map_err(|e| {
+        if e.downcast_ref::<kanoniv_core::Cancelled>().is_some() {
+            PyErr::new::<pyo3::exceptions::PyTimeoutError, _>(e.to_string())
+        } else {
+            spec_error::<PlanError>(yaml_str, e.to_string())
+        }
+    })?;
+    Plan::new(&result)
+}
+
+#[pyfunction]
+#[pyo3(signature = (yaml_str, csv, source=None))]
+fn profile_source(
+    py: Python<'_>,
+    yaml_str: &str,
+    csv: &Bound<'_, PyAny>,
+    source: Option<&str>,
+) -> PyResult<PyObject> {
+    let sample = sample_from_py(csv)?;
+    let result = py.allow_threads(|| kanoniv_core::profile_source(yaml_str, &sample, source))
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
+    to_py(py, &result)
+}
+
+#[pyfunction]
+fn calibrate(py: Python<'_>, yaml_str: &str, labels: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+    let labels = sample_from_py(labels)?;
+    let result = py.allow_threads(|| kanoniv_core::calibrate(yaml_str, &labels))
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
+    to_py(py, &result)
+}
+
+#[pyfunction]
+fn learn_weights(py: Python<'_>, yaml_str: &str, csv: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+    let data = records_from_py(yaml_str, csv)?;
+    let result = py.allow_threads(|| kanoniv_core::learn_weights(yaml_str, &data))
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
+    to_py(py, &result)
+}
+
+#[pyfunction]
+fn evaluate(py: Python<'_>, yaml_str: &str, data: &Bound<'_, PyAny>, truth: &Bound<'_, PyAny>, as_of: &str) -> PyResult<PyObject> {
+    let records = match data.downcast::<PyDict>() {
+        Ok(by_source) => {
+            let parts = by_source
+                .iter()
+                .map(|(source, records)| Ok((source.extract::<String>()?, sample_from_py(&records)?)))
+                .collect::<PyResult<Vec<_>>>()?;
+            kanoniv_core::records_json(&parts)
+        }
+        Err(_) => kanoniv_core::records_by_source(&sample_from_py(data)?)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{:#}", e)))?,
+    };
+    let truth = sample_from_py(truth)?;
+    let spec = kanoniv_core::parse_yaml(yaml_str)
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
+    let report = py.allow_threads(|| {
+        let ir = kanoniv_core::Ir::from_value(&kanoniv_core::compile_to_ir(&spec)?)?;
+        kanoniv_core::evaluate(&ir, &records, &truth, as_of)
+    })
+    .map_err(|e| spec_error::<KanonivError>(yaml_str, format!("{:#}", e)))?;
+    to_py(py, &report)
+}
+
+#[pyfunction]
+#[pyo3(signature = (yaml_str, data, as_of, timeout=None))]
+fn execute(py: Python<'_>, yaml_str: &str, data: &Bound<'_, PyAny>, as_of: &str, timeout: Option<f64>) -> PyResult<PyObject> {
+    let sources = match data.downcast::<PyDict>() {
+        Ok(by_source) => by_source
+            .iter()
+            .map(|(source, records)| Ok((source.extract::<String>()?, sample_from_py(&records)?)))
+            .collect::<PyResult<Vec<_>>>()?,
+        Err(_) => kanoniv_core::split_sources(&sample_from_py(data)?)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{:#}", e)))?,
+    };
+    let cancel = timeout
+        .map(kanoniv_core::CancellationToken::with_timeout_secs)
+        .transpose()
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    json_value_to_py(py, &value)
+    let spec = kanoniv_core::parse_yaml(yaml_str)
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
+    let result = py.allow_threads(|| {
+        let ir = kanoniv_core::Ir::from_value(&kanoniv_core::compile_to_ir(&spec)?)?;
+        let embedder = kanoniv_core::HttpEmbedder::new()?;
+        kanoniv_core::execute_sources_with(&ir, &sources, &embedder, cancel.as_ref(), as_of)
+    })
+    .map_err(|e| {
+        if e.downcast_ref::<kanoniv_core::Cancelled>().is_some() {
+            PyErr::new::<pyo3::exceptions::PyTimeoutError, _>(e.to_string())
+        } else {
+            spec_error::<KanonivError>(yaml_str, format!("{:#}", e))
+        }
+    })?;
+    to_py(py, &result)
+}
+
+#[pyfunction]
+#[pyo3(signature = (yaml_str, members, as_of, entity_id=None, field=None))]
+fn lineage(
+    py: Python<'_>,
+    yaml_str: &str,
+    members: &Bound<'_, PyAny>,
+    as_of: &str,
+    entity_id: Option<&str>,
+    field: Option<&str>,
+) -> PyResult<PyObject> {
+    let members = sample_from_py(members)?;
+    let golden = py.allow_threads(|| kanoniv_core::golden_records(yaml_str, &members, as_of))
+        .map_err(|e| spec_error::<KanonivError>(yaml_str, e.to_string()))?;
+    to_py(py, &golden.lineage.query(entity_id, field))
 }
 
 // ── Module definition ──────────────────────────────────────────────
 
 #[pymodule]
 fn _native(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("KanonivError", m.py().get_type_bound::<KanonivError>())?;
+    m.add("ParseError", m.py().get_type_bound::<ParseError>())?;
+    m.add("SchemaError", m.py().get_type_bound::<SchemaError>())?;
+    m.add("SemanticError", m.py().get_type_bound::<SemanticError>())?;
+    m.add("PlanError", m.py().get_type_bound::<PlanError>())?;
+    m.add_class::<Diagnostic>()?;
+    m.add_class::<RiskFlag>()?;
+    m.add_class::<Plan>()?;
+    m.add_class::<DiffResult>()?;
     m.add_function(wrap_pyfunction!(validate, m)?)?;
+    m.add_function(wrap_pyfunction!(diagnose, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_tiers, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_many, m)?)?;
     m.add_function(wrap_pyfunction!(validate_strict, m)?)?;
     m.add_function(wrap_pyfunction!(validate_schema, m)?)?;
     m.add_function(wrap_pyfunction!(validate_semantics, m)?)?;
@@ -122,6 +398,15 @@ fn _native(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(compile_ir, m)?)?;
     m.add_function(wrap_pyfunction!(diff, m)?)?;
     m.add_function(wrap_pyfunction!(hash, m)?)?;
+    m.add_function(wrap_pyfunction!(tokenize, m)?)?;
+    m.add_function(wrap_pyfunction!(format_spec, m)?)?;
+    m.add_function(wrap_pyfunction!(compose, m)?)?;
     m.add_function(wrap_pyfunction!(plan, m)?)?;
+    m.add_function(wrap_pyfunction!(profile_source, m)?)?;
+    m.add_function(wrap_pyfunction!(calibrate, m)?)?;
+    m.add_function(wrap_pyfunction!(learn_weights, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate, m)?)?;
+    m.add_function(wrap_pyfunction!(execute, m)?)?;
+    m.add_function(wrap_pyfunction!(lineage, m)?)?;
     Ok(())
 }
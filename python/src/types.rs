@@ -0,0 +1,391 @@
+//! Typed results.
+//!
+//! Each wraps the result's JSON form: attributes read from it, `to_dict()`
+//! returns it, and subscripting (`flag["code"]`) keeps code written against
+//! the plain dicts working.
+
+use pyo3::prelude::*;
+
+use crate::convert::{json_value_to_py, to_json};
+
+/// `data[key]` as a Python object; `KeyError` if absent.
+fn item(py: Python<'_>, data: &serde_json::Value, key: &str) -> PyResult<PyObject> {
+    match data.get(key) {
+        Some(value) => json_value_to_py(py, value),
+        None => Err(PyErr::new::<pyo3::exceptions::PyKeyError, _>(
+            key.to_string(),
+        )),
+    }
+}
+
+fn text<'a>(data: &'a serde_json::Value, key: &str) -> &'a str {
+    data.get(key).and_then(|v| v.as_str()).unwrap_or_default()
+}
+
+fn names(data: &serde_json::Value, key: &str) -> Vec<String> {
+    data.get(key)
+        .and_then(|v| v.as_array())
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|n| n.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `data[key]`, or `default` if absent.
+fn field(
+    py: Python<'_>,
+    data: &serde_json::Value,
+    key: &str,
+    default: serde_json::Value,
+) -> PyResult<PyObject> {
+    json_value_to_py(py, data.get(key).unwrap_or(&default))
+}
+
+/// A validation finding, with the stable code `kanoniv explain` describes.
+#[pyclass(module = "kanoniv._native", frozen)]
+pub(crate) struct Diagnostic {
+    pub(crate) data: serde_json::Value,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(result: &kanoniv_core::Diagnostic) -> PyResult<Self> {
+        Ok(Diagnostic {
+            data: to_json(result)?,
+        })
+    }
+}
+
+#[pymethods]
+impl Diagnostic {
+    #[getter]
+    fn code(&self) -> &str {
+        text(&self.data, "code")
+    }
+
+    #[getter]
+    fn severity(&self) -> &str {
+        text(&self.data, "severity")
+    }
+
+    #[getter]
+    fn message(&self) -> &str {
+        text(&self.data, "message")
+    }
+
+    /// Dotted YAML path of the offending node, e.g. `rules[0].field`.
+    #[getter]
+    fn path(&self) -> Option<&str> {
+        self.data.get("path").and_then(|v| v.as_str())
+    }
+
+    /// 1-based line of the node in the spec source.
+    #[getter]
+    fn line(&self) -> Option<u64> {
+        self.data.pointer("/span/line").and_then(|v| v.as_u64())
+    }
+
+    /// 1-based column of the node in the spec source.
+    #[getter]
+    fn column(&self) -> Option<u64> {
+        self.data.pointer("/span/column").and_then(|v| v.as_u64())
+    }
+
+    #[getter]
+    fn suggestion(&self) -> Option<&str> {
+        self.data.get("suggestion").and_then(|v| v.as_str())
+    }
+
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        json_value_to_py(py, &self.data)
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
+        item(py, &self.data, key)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Diagnostic {} {}: {}>",
+            self.code(),
+            self.severity(),
+            self.message()
+        )
+    }
+}
+
+/// A finding of the planner, with a recommendation.
+#[pyclass(module = "kanoniv._native", frozen)]
+pub(crate) struct RiskFlag {
+    pub(crate) data: serde_json::Value,
+}
+
+#[pymethods]
+impl RiskFlag {
+    #[getter]
+    fn severity(&self) -> &str {
+        text(&self.data, "severity")
+    }
+
+    #[getter]
+    fn code(&self) -> &str {
+        text(&self.data, "code")
+    }
+
+    #[getter]
+    fn message(&self) -> &str {
+        text(&self.data, "message")
+    }
+
+    #[getter]
+    fn recommendation(&self) -> &str {
+        text(&self.data, "recommendation")
+    }
+
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        json_value_to_py(py, &self.data)
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
+        item(py, &self.data, key)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<RiskFlag {} {}: {}>",
+            self.severity(),
+            self.code(),
+            self.message()
+        )
+    }
+}
+
+/// An execution plan: stages, match strategies, risk flags and summary.
+#[pyclass(module = "kanoniv._native", frozen)]
+pub(crate) struct Plan {
+    pub(crate) data: serde_json::Value,
+}
+
+impl Plan {
+    pub(crate) fn new(result: &kanoniv_core::PlanResult) -> PyResult<Self> {
+        Ok(Plan {
+            data: to_json(result)?,
+        })
+    }
+}
+
+#[pymethods]
+impl Plan {
+    #[getter]
+    fn entity(&self) -> &str {
+        text(&self.data, "entity")
+    }
+
+    #[getter]
+    fn identity_version(&self) -> &str {
+        text(&self.data, "identity_version")
+    }
+
+    #[getter]
+    fn plan_hash(&self) -> &str {
+        text(&self.data, "plan_hash")
+    }
+
+    #[getter]
+    fn sources(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "sources", serde_json::json!([]))
+    }
+
+    #[getter]
+    fn execution_stages(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "execution_stages", serde_json::json!([]))
+    }
+
+    #[getter]
+    fn match_strategies(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "match_strategies", serde_json::json!([]))
+    }
+
+    #[getter]
+    fn survivorship_summary(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(
+            py,
+            &self.data,
+            "survivorship_summary",
+            serde_json::json!([]),
+        )
+    }
+
+    #[getter]
+    fn blocking_analysis(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "blocking_analysis", serde_json::json!({}))
+    }
+
+    /// The spec's clustering strategy, when it sets one.
+    #[getter]
+    fn clustering(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "clustering", serde_json::Value::Null)
+    }
+
+    /// The spec's execution mode, when it sets one.
+    #[getter]
+    fn execution(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "execution", serde_json::Value::Null)
+    }
+
+    /// Pair counts, runtime and memory at the sources' row counts, when
+    /// any are known.
+    #[getter]
+    fn estimate(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "estimate", serde_json::Value::Null)
+    }
+
+    #[getter]
+    fn risk_flags(&self) -> Vec<RiskFlag> {
+        self.data
+            .get("risk_flags")
+            .and_then(|v| v.as_array())
+            .map(|flags| flags.iter().map(|f| RiskFlag { data: f.clone() }).collect())
+            .unwrap_or_default()
+    }
+
+    /// Severity-weighted sum of the risk flags, 0 (no flags) to 100.
+    #[getter]
+    fn risk_score(&self) -> u64 {
+        self.data
+            .get("risk_score")
+            .and_then(|v| v.as_u64())
+            .unwrap_or_default()
+    }
+
+    /// Risk flags suppressed by a waiver in the spec, with the waiver.
+    #[getter]
+    fn waived(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "waived", serde_json::json!([]))
+    }
+
+    /// Risk flags by responsible owner, when the spec declares owners.
+    #[getter]
+    fn routing(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "routing", serde_json::json!({}))
+    }
+
+    #[getter]
+    fn summary(&self) -> &str {
+        text(&self.data, "summary")
+    }
+
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        json_value_to_py(py, &self.data)
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
+        item(py, &self.data, key)
+    }
+
+    fn __repr__(&self) -> String {
+        let stages = self
+            .data
+            .get("execution_stages")
+            .and_then(|v| v.as_array())
+            .map_or(0, Vec::len);
+        let flags = self
+            .data
+            .get("risk_flags")
+            .and_then(|v| v.as_array())
+            .map_or(0, Vec::len);
+        format!(
+            "<Plan {}: {} stages, {} risk flags, risk score {}>",
+            self.entity(),
+            stages,
+            flags,
+            self.risk_score()
+        )
+    }
+}
+
+/// Changes between two spec versions.
+#[pyclass(module = "kanoniv._native", frozen)]
+pub(crate) struct DiffResult {
+    pub(crate) data: serde_json::Value,
+}
+
+impl DiffResult {
+    pub(crate) fn new(result: &kanoniv_core::DiffResult) -> PyResult<Self> {
+        Ok(DiffResult {
+            data: to_json(result)?,
+        })
+    }
+}
+
+#[pymethods]
+impl DiffResult {
+    #[getter]
+    fn rules_added(&self) -> Vec<String> {
+        names(&self.data, "rules_added")
+    }
+
+    #[getter]
+    fn rules_removed(&self) -> Vec<String> {
+        names(&self.data, "rules_removed")
+    }
+
+    /// Dicts with `name`, `field`, `old_value` and `new_value`.
+    #[getter]
+    fn rules_modified(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "rules_modified", serde_json::json!([]))
+    }
+
+    #[getter]
+    fn thresholds_changed(&self) -> bool {
+        self.data
+            .get("thresholds_changed")
+            .and_then(|v| v.as_bool())
+            .unwrap_or_default()
+    }
+
+    /// Every change classified as `safe`, `risky` or `breaking`, with the
+    /// version bump they call for.
+    #[getter]
+    fn compatibility(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "compatibility", serde_json::json!({}))
+    }
+
+    /// Changes by responsible owner, when either spec declares owners.
+    #[getter]
+    fn routing(&self, py: Python<'_>) -> PyResult<PyObject> {
+        field(py, &self.data, "routing", serde_json::json!({}))
+    }
+
+    #[getter]
+    fn summary(&self) -> &str {
+        text(&self.data, "summary")
+    }
+
+    /// Whether the specs differ in any classified way.
+    #[getter]
+    fn has_changes(&self) -> bool {
+        self.data
+            .pointer("/compatibility/changes")
+            .and_then(|v| v.as_array())
+            .is_some_and(|changes| !changes.is_empty())
+    }
+
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        json_value_to_py(py, &self.data)
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
+        item(py, &self.data, key)
+    }
+
+    fn __repr__(&self) -> String {
+        if self.has_changes() {
+            format!("<DiffResult: {}>", self.summary())
+        } else {
+            "<DiffResult: no changes>".to_string()
+        }
+    }
+}
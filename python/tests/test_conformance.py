@@ -0,0 +1,47 @@
+"""Conformance: the native bindings must match the shared fixture corpus."""
+
+from __future__ import annotations
+
+import csv
+import io
+import json
+from pathlib import Path
+
+import pytest
+
+_native = pytest.importorskip("kanoniv._native")
+
+CORPUS = Path(__file__).resolve().parents[2] / "crates" / "validator" / "conformance"
+CASES = sorted(p for p in CORPUS.iterdir() if (p / "spec.yaml").is_file())
+DECISION_CASES = [p for p in CASES if (p / "records.json").is_file()]
+# `AS_OF` in crates/validator/src/commands/conformance.rs.
+AS_OF = "2026-01-01"
+
+
+def _csv(records: list[dict]) -> str:
+    out = io.StringIO()
+    writer = csv.DictWriter(out, fieldnames=list(records[0]))
+    writer.writeheader()
+    writer.writerows(records)
+    return out.getvalue()
+
+
+@pytest.mark.parametrize("case", CASES, ids=[p.name for p in CASES])
+def test_native_outputs_match_corpus(case: Path):
+    spec = (case / "spec.yaml").read_text(encoding="utf-8")
+    expected = json.loads((case / "expected.json").read_text(encoding="utf-8"))
+
+    assert _native.hash(spec) == expected["hash"]
+    assert _native.compile_ir(spec) == expected["ir"]
+    assert _native.plan(spec).to_dict() == expected["plan"]
+
+
+@pytest.mark.parametrize("case", DECISION_CASES, ids=[p.name for p in DECISION_CASES])
+def test_native_decisions_match_corpus(case: Path):
+    spec = (case / "spec.yaml").read_text(encoding="utf-8")
+    records = json.loads((case / "records.json").read_text(encoding="utf-8"))
+    expected = json.loads((case / "expected.json").read_text(encoding="utf-8"))
+
+    result = _native.execute(spec, {source: _csv(rows) for source, rows in records.items()}, AS_OF)
+    decisions = {"decisions": result["decisions"], "clusters": result["clusters"]}
+    assert decisions == expected["decisions"]